@@ -0,0 +1,76 @@
+// SPDX-License-Identifier: Apache-2.0
+pragma solidity ^0.8.23;
+
+import "@openzeppelin/contracts/utils/Create2.sol";
+import "@openzeppelin/contracts/utils/cryptography/ECDSA.sol";
+import "account-abstraction/core/BaseAccount.sol";
+import "account-abstraction/core/Helpers.sol";
+
+/// Minimal ERC-4337 v0.7 account owned by an AirAccount KMS key.
+///
+/// "Hybrid" as in the KMS: the user's passkey authorises the signature inside
+/// the TEE (WebAuthn challenge bound to the userOpHash), and the chain checks
+/// the TEE's secp256k1 signature. The KMS signs the raw userOpHash when the
+/// caller declares a UserOp signing context, so no EIP-191 prefix here.
+contract HybridAccount is BaseAccount {
+    IEntryPoint private immutable _entryPoint;
+    address public immutable owner;
+
+    constructor(IEntryPoint anEntryPoint, address anOwner) {
+        _entryPoint = anEntryPoint;
+        owner = anOwner;
+    }
+
+    receive() external payable {}
+
+    function entryPoint() public view override returns (IEntryPoint) {
+        return _entryPoint;
+    }
+
+    function execute(address dest, uint256 value, bytes calldata func) external {
+        _requireFromEntryPoint();
+        (bool ok, bytes memory result) = dest.call{value: value}(func);
+        if (!ok) {
+            assembly {
+                revert(add(result, 32), mload(result))
+            }
+        }
+    }
+
+    function _validateSignature(PackedUserOperation calldata userOp, bytes32 userOpHash)
+        internal
+        view
+        override
+        returns (uint256 validationData)
+    {
+        (address signer, ECDSA.RecoverError err,) = ECDSA.tryRecover(userOpHash, userOp.signature);
+        if (err != ECDSA.RecoverError.NoError || signer != owner) {
+            return SIG_VALIDATION_FAILED;
+        }
+        return SIG_VALIDATION_SUCCESS;
+    }
+}
+
+/// CREATE2 factory, used through the UserOperation's initCode.
+contract HybridAccountFactory {
+    IEntryPoint public immutable entryPoint;
+
+    constructor(IEntryPoint anEntryPoint) {
+        entryPoint = anEntryPoint;
+    }
+
+    function createAccount(address owner, uint256 salt) external returns (HybridAccount) {
+        address account = getAddress(owner, salt);
+        if (account.code.length > 0) {
+            return HybridAccount(payable(account));
+        }
+        return new HybridAccount{salt: bytes32(salt)}(entryPoint, owner);
+    }
+
+    function getAddress(address owner, uint256 salt) public view returns (address) {
+        return Create2.computeAddress(
+            bytes32(salt),
+            keccak256(abi.encodePacked(type(HybridAccount).creationCode, abi.encode(entryPoint, owner)))
+        );
+    }
+}
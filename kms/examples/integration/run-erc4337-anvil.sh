@@ -0,0 +1,177 @@
+#!/bin/bash
+# ERC-4337 end to end against a local anvil node, signed by a running kms-api.
+#
+#   anvil → EntryPoint v0.7 + HybridAccountFactory → CreateKey (owner = KMS key)
+#   → UserOperation (initCode deploys the account, callData sends ETH)
+#   → userOpHash checked against EntryPoint.getUserOpHash
+#   → SignHash with a UserOp signing context (WebAuthn ceremony, TA recomputes the hash)
+#   → handleOps → assert account deployed, transfer done, nonce advanced
+#
+# Usage: ./run-erc4337-anvil.sh [kms host:port]      (default 127.0.0.1:3000)
+#   KMS_API_KEY=...   sent as x-api-key when set
+#   ANVIL_PORT=8545   local chain port
+#
+# Requires: foundry (anvil, forge, cast), curl, python3 + cryptography (for
+# kms/test/p256_helper.py). The first run fetches account-abstraction v0.7.0 and
+# openzeppelin-contracts v5.0.2 into lib/ (needs network once).
+
+set -uo pipefail
+HOST="${1:-127.0.0.1:3000}"
+BASE="http://$HOST"
+AK=()
+[ -n "${KMS_API_KEY:-}" ] && AK=(-H "x-api-key: $KMS_API_KEY")
+DIR="$(cd "$(dirname "$0")" && pwd)"
+HELPER="$DIR/../../test/p256_helper.py"
+FIXTURES="$DIR/../../test/test-fixtures"
+ANVIL_PORT="${ANVIL_PORT:-8545}"
+RPC="http://127.0.0.1:$ANVIL_PORT"
+CHAIN_ID=31337
+# anvil's well-known dev accounts 0 and 1: deployer and bundler.
+DEPLOYER_PK=0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80
+BUNDLER_PK=0x59c6995e998f97a5a0044966f0945389dc9e86dae88c7a8412f4603b6b78690d
+RECIPIENT=0x000000000000000000000000000000000000bEEF
+HD_PATH="m/44'/60'/0'/0/0"
+LASTF="$(mktemp)"; SCF="$(mktemp)"; echo 1 > "$SCF"
+ANVIL_PID=""
+trap 'rm -f "$LASTF" "$SCF"; [ -n "$ANVIL_PID" ] && kill "$ANVIL_PID" 2>/dev/null' EXIT
+RED='\033[0;31m'; GRN='\033[0;32m'; YEL='\033[1;33m'; NC='\033[0m'
+PASS=0; FAIL=0; FAILED_NAMES=()
+
+for tool in anvil forge cast curl python3; do
+  command -v "$tool" >/dev/null || { echo "missing: $tool"; exit 2; }
+done
+
+jbody() { python3 -c "import sys,json;d=json.load(open('$LASTF'));print(d$1)" 2>/dev/null; }
+post_code() { curl -s --max-time 30 -o "$LASTF" -w '%{http_code}' "${AK[@]}" -X POST "$BASE/$1" -H "Content-Type: application/json" -H "x-amz-target: TrentService.$1" -d "$2"; }
+get_code()  { curl -s --max-time 15 -o "$LASTF" -w '%{http_code}' "${AK[@]}" "$BASE$1"; }
+
+# chk <name> <got> <want>
+chk() {
+  if [ "$2" = "$3" ]; then PASS=$((PASS+1)); printf "${GRN} OK ${NC} %-46s %s\n" "$1" "$2"
+  else FAIL=$((FAIL+1)); FAILED_NAMES+=("$1"); printf "${RED}FAIL${NC} %-46s got=%s want=%s  %s\n" "$1" "$2" "$3" "$(head -c 90 "$LASTF")"; fi
+}
+
+# ceremony_payload <keyid> [payload_hex] — as in kms/test/run-full-e2e.sh:
+# challenge = SHA256(nonce ‖ payload), payload = the digest the TA signs;
+# without a payload the challenge is the nonce itself (non-signing ops).
+ceremony_payload() {
+  local kid="$1" payload_hex="$2" ba cid chal committed cred sc
+  sc=$(cat "$SCF"); sc=$((sc+1)); echo "$sc" > "$SCF"
+  ba=$(curl -s --max-time 15 "${AK[@]}" -X POST "$BASE/BeginAuthentication" -H "Content-Type: application/json" -H "x-amz-target: TrentService.BeginAuthentication" -d "{\"KeyId\":\"$kid\"}")
+  cid=$(echo "$ba" | python3 -c "import sys,json;print(json.load(sys.stdin)['ChallengeId'])" 2>/dev/null)
+  chal=$(echo "$ba" | python3 -c "import sys,json;print(json.load(sys.stdin)['Options']['challenge'])" 2>/dev/null)
+  [ -z "$cid" ] && { echo "{}"; return 1; }
+  committed="$chal"
+  [ -n "$payload_hex" ] && committed=$(python3 -c "
+import hashlib,base64
+chal='$chal'; payload=bytes.fromhex('$payload_hex')
+nonce=base64.urlsafe_b64decode(chal+'='*(-len(chal)%4))
+print(base64.urlsafe_b64encode(hashlib.sha256(nonce+payload).digest()).rstrip(b'=').decode())")
+  cred=$(python3 "$HELPER" ceremony "$PEM" "$committed" "dGVzdC1jcmVkZW50aWFs" "$sc")
+  echo "{\"ChallengeId\":\"$cid\",\"Credential\":$cred}"
+}
+
+lower() { echo "$1" | tr 'A-F' 'a-f'; }
+
+echo "════════ ERC-4337 on anvil, signed by $BASE ════════"
+
+echo -e "${YEL}[1] Chain${NC}"
+if [ ! -d "$DIR/lib/account-abstraction" ]; then
+  (cd "$DIR" && forge install --no-git eth-infinitism/account-abstraction@v0.7.0 OpenZeppelin/openzeppelin-contracts@v5.0.2) >/dev/null \
+    || { echo "forge install failed"; exit 2; }
+fi
+(cd "$DIR" && forge build -q) || { echo "forge build failed"; exit 2; }
+anvil --port "$ANVIL_PORT" --chain-id "$CHAIN_ID" >"$DIR/anvil.log" 2>&1 &
+ANVIL_PID=$!
+for _ in $(seq 1 50); do cast chain-id --rpc-url "$RPC" >/dev/null 2>&1 && break; sleep 0.2; done
+chk "anvil chain id" "$(cast chain-id --rpc-url "$RPC" 2>/dev/null)" "$CHAIN_ID"
+
+deploy() {
+  (cd "$DIR" && forge create "$1" --rpc-url "$RPC" --private-key "$DEPLOYER_PK" --broadcast ${2:+--constructor-args "$2"}) \
+    | awk '/Deployed to:/ {print $3}'
+}
+ENTRY_POINT=$(deploy lib/account-abstraction/contracts/core/EntryPoint.sol:EntryPoint)
+FACTORY=$(deploy contracts/HybridAccount.sol:HybridAccountFactory "$ENTRY_POINT")
+chk "EntryPoint deployed" "$([ -n "$(cast code "$ENTRY_POINT" --rpc-url "$RPC" 2>/dev/null | sed 's/^0x$//')" ] && echo yes)" yes
+chk "HybridAccountFactory deployed" "$([ -n "$(cast code "$FACTORY" --rpc-url "$RPC" 2>/dev/null | sed 's/^0x$//')" ] && echo yes)" yes
+echo "    EntryPoint=$ENTRY_POINT Factory=$FACTORY"
+
+echo -e "${YEL}[2] KMS owner key${NC}"
+[ -f "$FIXTURES/user1.json" ] || python3 "$HELPER" gen-all >/dev/null 2>&1
+PK=$(python3 -c "import json;print(json.load(open('$FIXTURES/user1.json'))['public_key_hex'])")
+PEM=$(python3 -c "import json;print(json.load(open('$FIXTURES/user1.json'))['private_key_pem'])")
+chk "POST /CreateKey" "$(post_code CreateKey "{\"Description\":\"erc4337-anvil\",\"KeyUsage\":\"SIGN_VERIFY\",\"KeySpec\":\"ECC_SECG_P256K1\",\"Origin\":\"AWS_KMS\",\"PasskeyPublicKey\":\"$PK\"}")" 200
+KEYID=$(jbody "['KeyMetadata']['KeyId']")
+OWNER=""
+for _ in $(seq 1 60); do
+  get_code "/KeyStatus?KeyId=$KEYID" >/dev/null
+  [ "$(jbody "['Status']")" = "ready" ] && { OWNER=$(jbody "['Address']"); break; }
+  sleep 1
+done
+chk "owner address derived" "$([ -n "$OWNER" ] && echo yes)" yes
+echo "    KeyId=$KEYID Owner=$OWNER"
+
+echo -e "${YEL}[3] UserOperation${NC}"
+SENDER=$(cast call "$FACTORY" "getAddress(address,uint256)(address)" "$OWNER" 0 --rpc-url "$RPC")
+cast send "$SENDER" --value 1ether --private-key "$DEPLOYER_PK" --rpc-url "$RPC" >/dev/null
+NONCE=$(cast call "$ENTRY_POINT" "getNonce(address,uint192)(uint256)" "$SENDER" 0 --rpc-url "$RPC")
+INIT_CODE="$FACTORY$(cast calldata "createAccount(address,uint256)" "$OWNER" 0 | sed 's/^0x//')"
+CALL_DATA=$(cast calldata "execute(address,uint256,bytes)" "$RECIPIENT" 10000000000000000 0x)
+ACCOUNT_GAS_LIMITS=0x$(printf "%032x%032x" 1000000 200000)   # verificationGasLimit ‖ callGasLimit
+PRE_VERIFICATION_GAS=100000
+GAS_FEES=0x$(printf "%032x%032x" 1000000000 20000000000)     # maxPriorityFeePerGas ‖ maxFeePerGas
+# v0.7: userOpHash = keccak256(abi.encode(keccak256(pack(userOp)), entryPoint, chainId))
+INNER_HASH=$(cast keccak "$(cast abi-encode "f(address,uint256,bytes32,bytes32,bytes32,uint256,bytes32,bytes32)" \
+  "$SENDER" "$NONCE" "$(cast keccak "$INIT_CODE")" "$(cast keccak "$CALL_DATA")" \
+  "$ACCOUNT_GAS_LIMITS" "$PRE_VERIFICATION_GAS" "$GAS_FEES" "$(cast keccak 0x)")")
+USER_OP_HASH=$(cast keccak "$(cast abi-encode "f(bytes32,address,uint256)" "$INNER_HASH" "$ENTRY_POINT" "$CHAIN_ID")")
+UNSIGNED="($SENDER,$NONCE,$INIT_CODE,$CALL_DATA,$ACCOUNT_GAS_LIMITS,$PRE_VERIFICATION_GAS,$GAS_FEES,0x,0x)"
+ONCHAIN_HASH=$(cast call "$ENTRY_POINT" "getUserOpHash((address,uint256,bytes,bytes,bytes32,uint256,bytes32,bytes,bytes))(bytes32)" "$UNSIGNED" --rpc-url "$RPC")
+chk "userOpHash matches EntryPoint" "$(lower "$ONCHAIN_HASH")" "$(lower "$USER_OP_HASH")"
+echo "    Sender=$SENDER userOpHash=$USER_OP_HASH"
+
+echo -e "${YEL}[4] Sign through the CA${NC}"
+HASH_HEX=$(echo "$USER_OP_HASH" | sed 's/^0x//')
+CONTEXT="{\"Type\":\"UserOp\",\"InnerHash\":\"$INNER_HASH\",\"EntryPoint\":\"$ENTRY_POINT\",\"ChainId\":$CHAIN_ID}"
+# A context that does not reproduce the hash must be refused by the TA.
+WA=$(ceremony_payload "$KEYID" "$HASH_HEX")
+BAD_CONTEXT="{\"Type\":\"UserOp\",\"InnerHash\":\"$INNER_HASH\",\"EntryPoint\":\"$ENTRY_POINT\",\"ChainId\":1}"
+chk "SignHash wrong chain → reject" "$(post_code SignHash "{\"KeyId\":\"$KEYID\",\"DerivationPath\":\"$HD_PATH\",\"Hash\":\"$USER_OP_HASH\",\"Context\":$BAD_CONTEXT,\"WebAuthn\":$WA}" | sed 's/^[45]..$/rejected/')" rejected
+WA=$(ceremony_payload "$KEYID" "$HASH_HEX")
+chk "POST /SignHash (UserOp context)" "$(post_code SignHash "{\"KeyId\":\"$KEYID\",\"DerivationPath\":\"$HD_PATH\",\"Hash\":\"$USER_OP_HASH\",\"Context\":$CONTEXT,\"WebAuthn\":$WA}")" 200
+SIGNATURE=$(python3 -c "
+import json
+s=bytes.fromhex(json.load(open('$LASTF'))['Signature'].removeprefix('0x'))
+v=s[64]+27 if s[64]<27 else s[64]
+print('0x'+(s[:64]+bytes([v])).hex())" 2>/dev/null)
+# ecrecover precompile: hash ‖ v ‖ r ‖ s
+ECRECOVER_IN=$(python3 -c "
+s=bytes.fromhex('$SIGNATURE'[2:])
+print('0x'+bytes.fromhex('$HASH_HEX').hex()+(s[64]).to_bytes(32,'big').hex()+s[:64].hex())" 2>/dev/null)
+RECOVERED=$(cast call 0x0000000000000000000000000000000000000001 "$ECRECOVER_IN" --rpc-url "$RPC" 2>/dev/null | sed 's/^0x000000000000000000000000/0x/')
+chk "signature recovers the owner" "$(lower "$RECOVERED")" "$(lower "$OWNER")"
+
+echo -e "${YEL}[5] handleOps${NC}"
+BEFORE=$(cast balance "$RECIPIENT" --rpc-url "$RPC")
+BUNDLER=$(cast wallet address --private-key "$BUNDLER_PK")
+SIGNED="($SENDER,$NONCE,$INIT_CODE,$CALL_DATA,$ACCOUNT_GAS_LIMITS,$PRE_VERIFICATION_GAS,$GAS_FEES,0x,$SIGNATURE)"
+STATUS=$(cast send "$ENTRY_POINT" "handleOps((address,uint256,bytes,bytes,bytes32,uint256,bytes32,bytes,bytes)[],address)" \
+  "[$SIGNED]" "$BUNDLER" --private-key "$BUNDLER_PK" --rpc-url "$RPC" --json 2>/dev/null \
+  | python3 -c "import sys,json;print(int(json.load(sys.stdin)['status'],16))" 2>/dev/null)
+chk "handleOps mined" "$STATUS" 1
+chk "account deployed by initCode" "$([ -n "$(cast code "$SENDER" --rpc-url "$RPC" | sed 's/^0x$//')" ] && echo yes)" yes
+chk "account owner = KMS address" "$(lower "$(cast call "$SENDER" "owner()(address)" --rpc-url "$RPC")")" "$(lower "$OWNER")"
+AFTER=$(cast balance "$RECIPIENT" --rpc-url "$RPC")
+chk "recipient received 0.01 ETH" "$(python3 -c "print($AFTER-$BEFORE)")" 10000000000000000
+chk "EntryPoint nonce advanced" "$(cast call "$ENTRY_POINT" "getNonce(address,uint192)(uint256)" "$SENDER" 0 --rpc-url "$RPC")" 1
+# Replaying the same signed op must fail (nonce consumed).
+chk "replayed UserOperation → revert" "$(cast send "$ENTRY_POINT" "handleOps((address,uint256,bytes,bytes,bytes32,uint256,bytes32,bytes,bytes)[],address)" \
+  "[$SIGNED]" "$BUNDLER" --private-key "$BUNDLER_PK" --rpc-url "$RPC" >/dev/null 2>&1 && echo mined || echo reverted)" reverted
+
+echo -e "${YEL}[6] Cleanup${NC}"
+WA=$(ceremony_payload "$KEYID" "")
+chk "POST /DeleteKey (ScheduleKeyDeletion)" "$(curl -s --max-time 30 -o "$LASTF" -w '%{http_code}' "${AK[@]}" -X POST "$BASE/DeleteKey" -H "Content-Type: application/json" -H "x-amz-target: TrentService.ScheduleKeyDeletion" -d "{\"KeyId\":\"$KEYID\",\"WebAuthn\":$WA}")" 200
+
+echo "════════════════════════════════════════════"
+echo -e "Total: ${GRN}$PASS passed${NC}, ${RED}$FAIL failed${NC}"
+if [ $FAIL -gt 0 ]; then printf "Failed: %s\n" "${FAILED_NAMES[*]}"; exit 1; else echo -e "${GRN}ALL PASSED${NC}"; fi
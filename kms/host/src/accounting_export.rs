@@ -0,0 +1,619 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Wallet activity for tax tools: Koinly and CoinTracker CSV, and OFX.
+//!
+//! `GET /kms/wallet/:id/export` (api_server.rs) reads three ledgers the CA
+//! already keeps and turns each row into an [`Entry`]:
+//!
+//! - `tx_history`: what the wallet signed. The value moved (native, or an
+//!   ERC-20 `transfer`) is the sent side; gas is the fee, in the native coin.
+//! - `chain_events`: deposits the chain watcher and stealth scanner saw.
+//! - settled `fee_payments`: gas paid in a token through a paymaster, a
+//!   fee-only entry.
+//!
+//! Quantities are exact decimals of base units. Decimals come from the
+//! bundled token list (`proto::calldata`); an unlisted token is exported in
+//! base units with its contract address as the currency, for the user to map
+//! in the tool rather than for the CA to guess.
+
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, Utc};
+use proto::calldata::{
+    decode_transaction, format_units, hex_addr, is_unlimited, native_symbol, summarize_transaction,
+    token_info, DecodedCall,
+};
+use proto::offline::decode_signed_legacy;
+
+use crate::db::{ChainEventRow, FeePaymentRow, TxHistoryRow};
+use crate::provisioning::csv_cell;
+
+/// Rows read per ledger for one export; past it the caller narrows the range.
+pub const MAX_EXPORT_ROWS: usize = 10_000;
+/// OFX caps NAME at 32 characters; the full description goes in MEMO.
+const OFX_NAME_LEN: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Koinly,
+    CoinTracker,
+    Ofx,
+}
+
+impl Format {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s {
+            "koinly" => Ok(Format::Koinly),
+            "cointracker" => Ok(Format::CoinTracker),
+            "ofx" => Ok(Format::Ofx),
+            _ => bail!("format must be koinly, cointracker or ofx"),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Format::Koinly => "koinly",
+            Format::CoinTracker => "cointracker",
+            Format::Ofx => "ofx",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Format::Koinly | Format::CoinTracker => "text/csv; charset=utf-8",
+            Format::Ofx => "application/x-ofx",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Koinly | Format::CoinTracker => "csv",
+            Format::Ofx => "ofx",
+        }
+    }
+}
+
+/// A quantity of one currency, as the tools take it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Amount {
+    /// Decimal, never negative.
+    pub quantity: String,
+    pub currency: String,
+}
+
+/// One line of the export.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// Unique within the wallet (`tx-<id>`, `event-<id>`, `fee-<quote>`); the OFX FITID.
+    pub id: String,
+    /// When the CA signed or saw it, unix seconds.
+    pub timestamp: i64,
+    pub chain_id: u64,
+    pub sent: Option<Amount>,
+    pub received: Option<Amount>,
+    pub fee: Option<Amount>,
+    pub tx_hash: String,
+    pub description: String,
+}
+
+fn native_currency(chain_id: u64) -> String {
+    match native_symbol(chain_id) {
+        "native" => format!("native:{}", chain_id),
+        symbol => symbol.to_string(),
+    }
+}
+
+fn native_amount(chain_id: u64, wei: u128) -> Amount {
+    Amount {
+        quantity: format_units(wei, 18),
+        currency: native_currency(chain_id),
+    }
+}
+
+fn token_amount(chain_id: u64, token: &[u8; 20], base_units: u128) -> Amount {
+    match token_info(chain_id, token) {
+        Some((symbol, decimals)) => Amount {
+            quantity: format_units(base_units, decimals),
+            currency: symbol.to_string(),
+        },
+        None => Amount {
+            quantity: base_units.to_string(),
+            currency: hex_addr(token),
+        },
+    }
+}
+
+/// A signed transaction. Replaced ones are skipped: their replacement is the
+/// one that can land. The fee is gas price × gas limit — the most it can
+/// cost; the CA keeps no receipts, and for a plain transfer the two agree.
+pub fn tx_entry(row: &TxHistoryRow) -> Result<Option<Entry>> {
+    if row.status == "replaced" {
+        return Ok(None);
+    }
+    let raw = hex::decode(row.entry.signed_tx.trim_start_matches("0x"))
+        .map_err(|e| anyhow!("tx {}: signed_tx is not hex: {}", row.id, e))?;
+    let tx = decode_signed_legacy(&raw)
+        .map_err(|e| anyhow!("tx {}: {}", row.id, e))?
+        .transaction;
+    let sent = match decode_transaction(&tx, &[]) {
+        DecodedCall::Transfer { token, amount, .. } if tx.value == 0 && !is_unlimited(&amount) => {
+            let mut lo = [0u8; 16];
+            lo.copy_from_slice(&amount[16..]);
+            Some(token_amount(tx.chain_id, &token, u128::from_be_bytes(lo)))
+        }
+        _ if tx.value > 0 => Some(native_amount(tx.chain_id, tx.value)),
+        _ => None,
+    };
+    let mut description = summarize_transaction(&tx, &[]);
+    if row.status == "pending" {
+        description.push_str(" (pending)");
+    }
+    Ok(Some(Entry {
+        id: format!("tx-{}", row.id),
+        timestamp: row.created_at,
+        chain_id: tx.chain_id,
+        sent,
+        received: None,
+        fee: Some(native_amount(
+            tx.chain_id,
+            tx.gas_price.saturating_mul(tx.gas),
+        )),
+        tx_hash: row.entry.tx_hash.clone(),
+        description,
+    }))
+}
+
+/// A deposit. Contract events and stealth announcements without an amount
+/// move nothing and are skipped, as are reorged-out rows.
+pub fn event_entry(row: &ChainEventRow) -> Option<Entry> {
+    use crate::chain_watch::{KIND_ERC20_IN, KIND_NATIVE_IN, KIND_STEALTH_IN};
+    let e = &row.event;
+    if row.removed || ![KIND_ERC20_IN, KIND_NATIVE_IN, KIND_STEALTH_IN].contains(&e.kind.as_str()) {
+        return None;
+    }
+    let value: u128 = e.value.as_deref()?.parse().ok()?;
+    let received = match e.token.as_deref() {
+        Some(token) => {
+            let token = proto::eip55::parse_address(token).ok()?;
+            token_amount(e.chain_id, &token, value)
+        }
+        None => native_amount(e.chain_id, value),
+    };
+    Some(Entry {
+        id: format!("event-{}", row.id),
+        timestamp: row.created_at,
+        chain_id: e.chain_id,
+        sent: None,
+        received: Some(received),
+        fee: None,
+        tx_hash: e.tx_hash.clone(),
+        description: format!(
+            "{} from {} to {}",
+            e.kind,
+            e.from_address.as_deref().unwrap_or("unknown"),
+            e.address
+        ),
+    })
+}
+
+/// Gas a paymaster charged in a token: what it actually took, at settlement.
+pub fn fee_payment_entry(row: &FeePaymentRow) -> Option<Entry> {
+    let cost = row.actual_token_cost?;
+    if row.status != "settled" {
+        return None;
+    }
+    let token = proto::eip55::parse_address(&row.entry.token).ok()?;
+    Some(Entry {
+        id: format!("fee-{}", row.entry.quote_hash),
+        timestamp: row.updated_at,
+        chain_id: row.entry.chain_id,
+        sent: None,
+        received: None,
+        fee: Some(token_amount(row.entry.chain_id, &token, cost)),
+        tx_hash: row
+            .tx_hash
+            .clone()
+            .or_else(|| row.user_op_hash.clone())
+            .unwrap_or_default(),
+        description: format!("paymaster fee ({})", row.entry.paymaster),
+    })
+}
+
+/// All three ledgers as one list, oldest first.
+pub fn collect(
+    txs: &[TxHistoryRow],
+    events: &[ChainEventRow],
+    fees: &[FeePaymentRow],
+) -> Result<Vec<Entry>> {
+    let mut entries = Vec::with_capacity(txs.len() + events.len() + fees.len());
+    for row in txs {
+        entries.extend(tx_entry(row)?);
+    }
+    entries.extend(events.iter().filter_map(event_entry));
+    entries.extend(fees.iter().filter_map(fee_payment_entry));
+    entries.sort_by(|a, b| (a.timestamp, &a.id).cmp(&(b.timestamp, &b.id)));
+    Ok(entries)
+}
+
+fn utc(ts: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp(ts, 0).unwrap_or_default()
+}
+
+fn split(amount: &Option<Amount>) -> (&str, &str) {
+    amount
+        .as_ref()
+        .map(|a| (a.quantity.as_str(), a.currency.as_str()))
+        .unwrap_or(("", ""))
+}
+
+fn csv_line(out: &mut String, cells: &[&str]) {
+    let row: Vec<String> = cells.iter().map(|c| csv_cell(c)).collect();
+    out.push_str(&row.join(","));
+    out.push('\n');
+}
+
+/// Koinly's universal template.
+pub fn koinly_csv(entries: &[Entry]) -> String {
+    let mut csv = String::from(
+        "Date,Sent Amount,Sent Currency,Received Amount,Received Currency,Fee Amount,\
+         Fee Currency,Net Worth Amount,Net Worth Currency,Label,Description,TxHash\n",
+    );
+    for e in entries {
+        let date = utc(e.timestamp).format("%Y-%m-%d %H:%M:%S UTC").to_string();
+        let (sent, sent_cur) = split(&e.sent);
+        let (recv, recv_cur) = split(&e.received);
+        let (fee, fee_cur) = split(&e.fee);
+        csv_line(
+            &mut csv,
+            &[
+                &date,
+                sent,
+                sent_cur,
+                recv,
+                recv_cur,
+                fee,
+                fee_cur,
+                "",
+                "",
+                "",
+                &e.description,
+                &e.tx_hash,
+            ],
+        );
+    }
+    csv
+}
+
+/// CoinTracker's CSV import template; dates are UTC.
+pub fn cointracker_csv(entries: &[Entry]) -> String {
+    let mut csv = String::from(
+        "Date,Received Quantity,Received Currency,Sent Quantity,Sent Currency,Fee Amount,\
+         Fee Currency,Tag\n",
+    );
+    for e in entries {
+        let date = utc(e.timestamp).format("%m/%d/%Y %H:%M:%S").to_string();
+        let (sent, sent_cur) = split(&e.sent);
+        let (recv, recv_cur) = split(&e.received);
+        let (fee, fee_cur) = split(&e.fee);
+        csv_line(
+            &mut csv,
+            &[&date, recv, recv_cur, sent, sent_cur, fee, fee_cur, ""],
+        );
+    }
+    csv
+}
+
+fn ofx_time(ts: i64) -> String {
+    format!("{}[0:GMT]", utc(ts).format("%Y%m%d%H%M%S"))
+}
+
+fn xml_text(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// OFX 2.2 with one bank statement per (chain, currency): a crypto asset is
+/// an account whose CURDEF is its symbol. Sent and received amounts are
+/// DEBIT / CREDIT, fees FEE. The CA does not know balances, so LEDGERBAL —
+/// which OFX requires — is 0.
+pub fn ofx(key_id: &str, entries: &[Entry], from: i64, to: i64, generated_at: i64) -> String {
+    let mut statements: Vec<((u64, String), Vec<String>)> = Vec::new();
+    for e in entries {
+        let legs = [
+            ("DEBIT", "-", "sent", &e.sent),
+            ("CREDIT", "", "received", &e.received),
+            ("FEE", "-", "fee", &e.fee),
+        ];
+        for (trntype, sign, leg, amount) in legs {
+            let Some(amount) = amount else { continue };
+            let name: String = e.description.chars().take(OFX_NAME_LEN).collect();
+            let trn = format!(
+                "<STMTTRN><TRNTYPE>{}</TRNTYPE><DTPOSTED>{}</DTPOSTED><TRNAMT>{}{}</TRNAMT>\
+                 <FITID>{}-{}</FITID><NAME>{}</NAME><MEMO>{}</MEMO></STMTTRN>",
+                trntype,
+                ofx_time(e.timestamp),
+                sign,
+                amount.quantity,
+                e.id,
+                leg,
+                xml_text(&name),
+                xml_text(&format!("{} {}", e.description, e.tx_hash)),
+            );
+            let account = (e.chain_id, amount.currency.clone());
+            match statements.iter_mut().find(|(a, _)| *a == account) {
+                Some((_, trns)) => trns.push(trn),
+                None => statements.push((account, vec![trn])),
+            }
+        }
+    }
+
+    let status = "<STATUS><CODE>0</CODE><SEVERITY>INFO</SEVERITY></STATUS>";
+    let mut out = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"no\"?>\n\
+         <?OFX OFXHEADER=\"200\" VERSION=\"220\" SECURITY=\"NONE\" OLDFILEUID=\"NONE\" NEWFILEUID=\"NONE\"?>\n\
+         <OFX>\n",
+    );
+    out.push_str(&format!(
+        "<SIGNONMSGSRSV1><SONRS>{}<DTSERVER>{}</DTSERVER><LANGUAGE>ENG</LANGUAGE></SONRS></SIGNONMSGSRSV1>\n",
+        status,
+        ofx_time(generated_at)
+    ));
+    out.push_str("<BANKMSGSRSV1>\n");
+    for (i, ((chain_id, currency), trns)) in statements.iter().enumerate() {
+        out.push_str(&format!(
+            "<STMTTRNRS><TRNUID>{}</TRNUID>{}<STMTRS><CURDEF>{}</CURDEF>\
+             <BANKACCTFROM><BANKID>{}</BANKID><ACCTID>{}:{}</ACCTID><ACCTTYPE>CHECKING</ACCTTYPE></BANKACCTFROM>\n\
+             <BANKTRANLIST><DTSTART>{}</DTSTART><DTEND>{}</DTEND>\n",
+            i + 1,
+            status,
+            xml_text(currency),
+            chain_id,
+            xml_text(key_id),
+            xml_text(currency),
+            ofx_time(from),
+            ofx_time(to),
+        ));
+        for trn in trns {
+            out.push_str(trn);
+            out.push('\n');
+        }
+        out.push_str(&format!(
+            "</BANKTRANLIST><LEDGERBAL><BALAMT>0</BALAMT><DTASOF>{}</DTASOF></LEDGERBAL></STMTRS></STMTTRNRS>\n",
+            ofx_time(to)
+        ));
+    }
+    out.push_str("</BANKMSGSRSV1>\n</OFX>\n");
+    out
+}
+
+/// The export body for `[from, to)`.
+pub fn render(
+    format: Format,
+    key_id: &str,
+    entries: &[Entry],
+    from: i64,
+    to: i64,
+    generated_at: i64,
+) -> String {
+    match format {
+        Format::Koinly => koinly_csv(entries),
+        Format::CoinTracker => cointracker_csv(entries),
+        Format::Ofx => ofx(key_id, entries, from, to, generated_at),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{ChainEvent, FeeQuoteEntry, SignedTxEntry};
+    use proto::offline::{signed_legacy_rlp, SignedLegacyTx};
+    use proto::EthTransaction;
+
+    const USDC: &str = "a0b86991c6218b36c1d19d4a2e9eb0ce3606eb48";
+
+    fn tx_row(id: i64, tx: EthTransaction, status: &str) -> TxHistoryRow {
+        let raw = signed_legacy_rlp(&SignedLegacyTx {
+            transaction: tx,
+            r: [1; 32],
+            s: [2; 32],
+            recovery_id: 0,
+        });
+        TxHistoryRow {
+            id,
+            entry: SignedTxEntry {
+                key_id: "w-1".into(),
+                address: "0xabc".into(),
+                derivation_path: "m/44'/60'/0'/0/0".into(),
+                chain_id: 1,
+                nonce: 0,
+                gas_price: 0,
+                signed_tx: format!("0x{}", hex::encode(raw)),
+                tx_hash: format!("0x{:064x}", id),
+            },
+            status: status.into(),
+            created_at: 1_767_225_600 + id,
+        }
+    }
+
+    fn transfer(to: Option<[u8; 20]>, value: u128, data: Vec<u8>) -> EthTransaction {
+        EthTransaction {
+            chain_id: 1,
+            nonce: 0,
+            to,
+            value,
+            gas_price: 10_000_000_000,
+            gas: 21_000,
+            data,
+        }
+    }
+
+    #[test]
+    fn ledgers_become_entries_with_decimals_and_fees() {
+        let usdc = proto::eip55::parse_address(USDC).unwrap();
+        let mut data = proto::calldata::selector("transfer(address,uint256)").to_vec();
+        data.extend_from_slice(&[0u8; 12]);
+        data.extend_from_slice(&[7; 20]);
+        data.extend_from_slice(&[0u8; 28]);
+        data.extend_from_slice(&12_500_000u32.to_be_bytes());
+        let txs = vec![
+            tx_row(
+                1,
+                transfer(Some([7; 20]), 1_500_000_000_000_000_000, vec![]),
+                "confirmed",
+            ),
+            tx_row(2, transfer(Some(usdc), 0, data), "pending"),
+            tx_row(3, transfer(Some([7; 20]), 1, vec![]), "replaced"),
+        ];
+        let event = |id: i64, kind: &str, token: Option<String>, removed: bool| ChainEventRow {
+            id,
+            event: ChainEvent {
+                key_id: "w-1".into(),
+                address: "0xabc".into(),
+                chain_id: 1,
+                kind: kind.into(),
+                token,
+                from_address: Some("0xdef".into()),
+                to_address: Some("0xabc".into()),
+                value: Some("2000000".into()),
+                topic0: None,
+                tx_hash: "0xin".into(),
+                block_number: 1,
+                log_index: 0,
+            },
+            removed,
+            created_at: 1_767_225_700,
+        };
+        let events = vec![
+            event(
+                1,
+                crate::chain_watch::KIND_ERC20_IN,
+                Some(format!("0x{}", USDC)),
+                false,
+            ),
+            event(
+                2,
+                crate::chain_watch::KIND_ERC20_IN,
+                Some(format!("0x{}", "42".repeat(20))),
+                false,
+            ),
+            event(3, crate::chain_watch::KIND_NATIVE_IN, None, true),
+            event(4, crate::chain_watch::KIND_CONTRACT_EVENT, None, false),
+        ];
+        let fees = vec![FeePaymentRow {
+            entry: FeeQuoteEntry {
+                quote_hash: "0xq".into(),
+                key_id: "w-1".into(),
+                chain_id: 1,
+                paymaster: "0xpm".into(),
+                token: format!("0x{}", USDC),
+                max_token_cost: 500_000,
+                quote: "{}".into(),
+            },
+            user_op_hash: Some("0xop".into()),
+            status: "settled".into(),
+            actual_token_cost: Some(310_000),
+            tx_hash: None,
+            created_at: 1_767_225_650,
+            updated_at: 1_767_225_800,
+        }];
+
+        let entries = collect(&txs, &events, &fees).unwrap();
+        let ids: Vec<_> = entries.iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(ids, ["tx-1", "tx-2", "event-1", "event-2", "fee-0xq"]);
+
+        let gas = Some(Amount {
+            quantity: "0.00021".into(),
+            currency: "ETH".into(),
+        });
+        assert_eq!(entries[0].sent.as_ref().unwrap().quantity, "1.5");
+        assert_eq!(entries[0].fee, gas);
+        let sent = entries[1].sent.as_ref().unwrap();
+        assert_eq!(
+            (sent.quantity.as_str(), sent.currency.as_str()),
+            ("12.5", "USDC")
+        );
+        assert!(entries[1].description.ends_with("(pending)"));
+        assert_eq!(entries[2].received.as_ref().unwrap().quantity, "2");
+        // unlisted token: base units under its address
+        let unlisted = entries[3].received.as_ref().unwrap();
+        assert_eq!(unlisted.quantity, "2000000");
+        assert_eq!(unlisted.currency, format!("0x{}", "42".repeat(20)));
+        let fee = entries[4].fee.as_ref().unwrap();
+        assert_eq!(
+            (fee.quantity.as_str(), entries[4].tx_hash.as_str()),
+            ("0.31", "0xop")
+        );
+        assert_eq!(native_currency(137), "native:137");
+    }
+
+    #[test]
+    fn renders_each_format() {
+        let entries = vec![Entry {
+            id: "tx-1".into(),
+            timestamp: 1_767_225_600,
+            chain_id: 1,
+            sent: Some(Amount {
+                quantity: "1.5".into(),
+                currency: "ETH".into(),
+            }),
+            received: None,
+            fee: Some(Amount {
+                quantity: "0.00021".into(),
+                currency: "ETH".into(),
+            }),
+            tx_hash: "0xabc".into(),
+            description: "send to 0x07, value 1.5 ETH".into(),
+        }];
+
+        let koinly = koinly_csv(&entries);
+        let mut lines = koinly.lines();
+        assert!(lines
+            .next()
+            .unwrap()
+            .starts_with("Date,Sent Amount,Sent Currency"));
+        assert_eq!(
+            lines.next().unwrap(),
+            "2026-01-01 00:00:00 UTC,1.5,ETH,,,0.00021,ETH,,,,\"send to 0x07, value 1.5 ETH\",0xabc"
+        );
+
+        let cointracker = cointracker_csv(&entries);
+        assert_eq!(
+            cointracker.lines().nth(1).unwrap(),
+            "01/01/2026 00:00:00,,,1.5,ETH,0.00021,ETH,"
+        );
+
+        let doc = ofx(
+            "w<1>",
+            &entries,
+            1_767_225_600,
+            1_767_312_000,
+            1_767_312_000,
+        );
+        assert!(doc.contains("<CURDEF>ETH</CURDEF>"));
+        assert!(doc.contains("<ACCTID>w&lt;1&gt;:ETH</ACCTID>"));
+        assert!(doc.contains("<TRNTYPE>DEBIT</TRNTYPE><DTPOSTED>20260101000000[0:GMT]</DTPOSTED><TRNAMT>-1.5</TRNAMT><FITID>tx-1-sent</FITID>"));
+        assert!(doc.contains("<TRNAMT>-0.00021</TRNAMT><FITID>tx-1-fee</FITID>"));
+        assert_eq!(doc.matches("<STMTTRNRS>").count(), 1);
+
+        assert_eq!(
+            Format::parse("ofx").unwrap().content_type(),
+            "application/x-ofx"
+        );
+        assert!(Format::parse("xlsx").is_err());
+    }
+}
@@ -2,13 +2,13 @@
 
 use anyhow::{anyhow, Result};
 use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
-#[cfg(feature = "tee")]
+#[cfg(any(feature = "tee", feature = "soft-tee"))]
 use chrono::Utc;
 use proto;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 
-#[cfg(feature = "tee")]
+#[cfg(any(feature = "tee", feature = "soft-tee"))]
 use crate::ta_client::TeeHandle;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -54,7 +54,7 @@ pub fn assemble_p256_session_jwt(
     Ok((jwt, payload.exp))
 }
 
-#[cfg(feature = "tee")]
+#[cfg(any(feature = "tee", feature = "soft-tee"))]
 pub async fn verify_credential(tee: &TeeHandle, jwt: &str) -> Result<JwtPayload> {
     let parts: Vec<&str> = jwt.split('.').collect();
     if parts.len() != 3 {
@@ -111,6 +111,37 @@ pub fn extract_signing_proof(jwt: &str) -> anyhow::Result<(String, Vec<u8>, Vec<
     Ok((header.kid, signing_input, hmac_bytes))
 }
 
+/// Refresh tokens are `rt1.<agent keyId>.<base64url secret>`: the key id says
+/// which family to redeem against, the secret is checked by the TA.
+pub fn format_refresh_token(key_id: &str, secret: &[u8; 32]) -> String {
+    format!("rt1.{}.{}", key_id, URL_SAFE_NO_PAD.encode(secret))
+}
+
+pub fn parse_refresh_token(token: &str) -> Result<(String, [u8; 32])> {
+    let (key_id, secret_b64) = token
+        .strip_prefix("rt1.")
+        .and_then(|rest| rest.rsplit_once('.'))
+        .ok_or_else(|| anyhow!("Invalid refresh token format"))?;
+    let bytes = URL_SAFE_NO_PAD
+        .decode(secret_b64)
+        .map_err(|e| anyhow!("refresh token base64url decode: {}", e))?;
+    if bytes.len() != 32 {
+        return Err(anyhow!("refresh token secret must be 32 bytes"));
+    }
+    let mut secret = [0u8; 32];
+    secret.copy_from_slice(&bytes);
+    Ok((key_id.to_string(), secret))
+}
+
+/// The TA and the DB only ever see SHA-256 of the client's device fingerprint.
+pub fn device_hash(fingerprint: &str) -> Result<[u8; 32]> {
+    let fingerprint = fingerprint.trim();
+    if fingerprint.is_empty() || fingerprint.len() > 512 {
+        return Err(anyhow!("deviceFingerprint must be 1..=512 characters"));
+    }
+    Ok(Sha256::digest(fingerprint.as_bytes()).into())
+}
+
 fn decode_json<T: serde::de::DeserializeOwned>(segment: &str) -> Result<T> {
     let bytes = URL_SAFE_NO_PAD
         .decode(segment)
@@ -0,0 +1,285 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Grouping and submission of BLS-signed userOps (`proto::aggregator`).
+//!
+//! `POST /kms/aggregator/sign` has the TA BLS-sign a userOpHash and queues
+//! the signed userOp here under its (EntryPoint, aggregator) pair.
+//! `POST /kms/aggregator/flush` sends each group to the bundler at
+//! `KMS_BUNDLER_URL` as one JSON-RPC batch of `eth_sendUserOperation`
+//! calls, every op carrying its `aggregator`, so the bundler can put the
+//! group into a single `handleAggregatedOps` entry and verify one aggregate
+//! signature for it. A group the bundler does not answer stays queued.
+//!
+//! Like the paymaster client, the bundler URL must be `http://`.
+
+use anyhow::{anyhow, bail, Context, Result};
+use proto::calldata::hex_addr;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+/// Signed userOps the queue holds before refusing more.
+pub const MAX_QUEUED: usize = 256;
+const BUNDLER_TIMEOUT_SECS: u64 = 10;
+
+#[derive(Debug, Clone)]
+pub struct BundlerConfig {
+    pub url: String,
+}
+
+impl BundlerConfig {
+    /// `KMS_BUNDLER_URL`; None when unset.
+    pub fn from_env() -> Option<Result<Self>> {
+        let url = std::env::var("KMS_BUNDLER_URL")
+            .ok()
+            .filter(|v| !v.is_empty())?;
+        Some(Self::new(url))
+    }
+
+    pub fn new(url: String) -> Result<Self> {
+        if !url.starts_with("http://") {
+            bail!("KMS_BUNDLER_URL must be http:// (no TLS in the CA)");
+        }
+        Ok(Self { url })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueuedUserOp {
+    pub key_id: String,
+    pub entry_point: [u8; 20],
+    pub aggregator: [u8; 20],
+    pub user_op_hash: [u8; 32],
+    /// The userOp in bundler RPC form, `signature` already set.
+    pub user_op: Value,
+}
+
+/// The userOps of one (EntryPoint, aggregator) pair.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AggregatedGroup {
+    pub entry_point: [u8; 20],
+    pub aggregator: [u8; 20],
+    pub ops: Vec<QueuedUserOp>,
+}
+
+impl AggregatedGroup {
+    /// The JSON-RPC batch that submits the group.
+    pub fn rpc_batch(&self) -> Value {
+        let entry_point = hex_addr(&self.entry_point);
+        let aggregator = hex_addr(&self.aggregator);
+        Value::Array(
+            self.ops
+                .iter()
+                .enumerate()
+                .map(|(i, op)| {
+                    let mut user_op = op.user_op.clone();
+                    user_op["aggregator"] = json!(aggregator);
+                    json!({
+                        "jsonrpc": "2.0",
+                        "id": i + 1,
+                        "method": "eth_sendUserOperation",
+                        "params": [user_op, entry_point],
+                    })
+                })
+                .collect(),
+        )
+    }
+
+    pub fn summary(&self) -> Value {
+        json!({
+            "entryPoint": hex_addr(&self.entry_point),
+            "aggregator": hex_addr(&self.aggregator),
+            "userOps": self.ops.len(),
+        })
+    }
+}
+
+#[derive(Default)]
+pub struct AggregationQueue {
+    ops: Mutex<Vec<QueuedUserOp>>,
+}
+
+impl AggregationQueue {
+    /// Queue a signed userOp; the same userOpHash again replaces the first.
+    /// Returns how many userOps now share its group.
+    pub fn push(&self, op: QueuedUserOp) -> Result<usize> {
+        let mut ops = self.ops.lock().map_err(|_| anyhow!("queue poisoned"))?;
+        ops.retain(|o| o.user_op_hash != op.user_op_hash);
+        if ops.len() >= MAX_QUEUED {
+            bail!(
+                "{} signed userOps are already waiting; flush the queue first",
+                MAX_QUEUED
+            );
+        }
+        let key = (op.entry_point, op.aggregator);
+        ops.push(op);
+        Ok(ops
+            .iter()
+            .filter(|o| (o.entry_point, o.aggregator) == key)
+            .count())
+    }
+
+    pub fn len(&self) -> usize {
+        self.ops.lock().map(|ops| ops.len()).unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Empty the queue into its groups, in (EntryPoint, aggregator) order
+    /// and each in queueing order.
+    pub fn take_groups(&self) -> Result<Vec<AggregatedGroup>> {
+        let mut ops = self.ops.lock().map_err(|_| anyhow!("queue poisoned"))?;
+        Ok(group(std::mem::take(&mut *ops)))
+    }
+
+    /// Put back a group that was not submitted.
+    pub fn requeue(&self, group: AggregatedGroup) -> Result<()> {
+        let mut ops = self.ops.lock().map_err(|_| anyhow!("queue poisoned"))?;
+        ops.extend(group.ops);
+        Ok(())
+    }
+}
+
+pub fn group(ops: Vec<QueuedUserOp>) -> Vec<AggregatedGroup> {
+    let mut groups: BTreeMap<([u8; 20], [u8; 20]), Vec<QueuedUserOp>> = BTreeMap::new();
+    for op in ops {
+        groups
+            .entry((op.entry_point, op.aggregator))
+            .or_default()
+            .push(op);
+    }
+    groups
+        .into_iter()
+        .map(|((entry_point, aggregator), ops)| AggregatedGroup {
+            entry_point,
+            aggregator,
+            ops,
+        })
+        .collect()
+}
+
+/// Send one group. Returns, per op in order, the bundler's userOpHash or
+/// its error; Err when the bundler could not be asked at all.
+pub async fn submit(
+    config: &BundlerConfig,
+    group: &AggregatedGroup,
+) -> Result<Vec<std::result::Result<String, String>>> {
+    use warp::hyper::{body, Body, Client, Request};
+    let req = Request::post(config.url.as_str())
+        .header("content-type", "application/json")
+        .body(Body::from(group.rpc_batch().to_string()))?;
+    let resp = tokio::time::timeout(
+        std::time::Duration::from_secs(BUNDLER_TIMEOUT_SECS),
+        Client::new().request(req),
+    )
+    .await
+    .map_err(|_| anyhow!("bundler did not answer within {}s", BUNDLER_TIMEOUT_SECS))?
+    .context("bundler unreachable")?;
+    let status = resp.status();
+    let bytes = body::to_bytes(resp.into_body()).await?;
+    if !status.is_success() {
+        bail!("bundler returned HTTP {}", status);
+    }
+    let reply: Value = serde_json::from_slice(&bytes).context("bundler reply is not JSON")?;
+    batch_results(&reply, group.ops.len())
+}
+
+/// Match a JSON-RPC batch reply (in any order) back to the request ids.
+fn batch_results(reply: &Value, n: usize) -> Result<Vec<std::result::Result<String, String>>> {
+    let replies = reply
+        .as_array()
+        .ok_or_else(|| anyhow!("bundler did not answer the batch with an array"))?;
+    let mut results = vec![Err("no reply from the bundler".to_string()); n];
+    for r in replies {
+        let slot = match r["id"].as_u64() {
+            Some(id) if id >= 1 && (id as usize) <= n => id as usize - 1,
+            _ => continue,
+        };
+        results[slot] = match (r.get("result"), r.get("error")) {
+            (_, Some(err)) => Err(err.to_string()),
+            (Some(Value::String(hash)), None) => Ok(hash.clone()),
+            _ => Err("malformed reply".to_string()),
+        };
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn op(hash: u8, aggregator: u8) -> QueuedUserOp {
+        QueuedUserOp {
+            key_id: "k".into(),
+            entry_point: [0xee; 20],
+            aggregator: [aggregator; 20],
+            user_op_hash: [hash; 32],
+            user_op: json!({ "sender": "0x01", "signature": format!("0x{:02x}", hash) }),
+        }
+    }
+
+    #[test]
+    fn queue_groups_per_aggregator_and_dedupes_hashes() {
+        let q = AggregationQueue::default();
+        assert_eq!(q.push(op(1, 0xa1)).unwrap(), 1);
+        assert_eq!(q.push(op(2, 0xa2)).unwrap(), 1);
+        assert_eq!(q.push(op(3, 0xa1)).unwrap(), 2);
+        // Re-signed op replaces its earlier copy.
+        assert_eq!(q.push(op(1, 0xa1)).unwrap(), 2);
+        assert_eq!(q.len(), 3);
+
+        let groups = q.take_groups().unwrap();
+        assert!(q.is_empty());
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].aggregator, [0xa1; 20]);
+        let hashes: Vec<u8> = groups[0].ops.iter().map(|o| o.user_op_hash[0]).collect();
+        assert_eq!(hashes, vec![3, 1]);
+        assert_eq!(groups[1].ops.len(), 1);
+
+        q.requeue(groups[1].clone()).unwrap();
+        assert_eq!(q.len(), 1);
+    }
+
+    #[test]
+    fn batch_carries_the_aggregator_and_matches_replies_by_id() {
+        let g = &group(vec![op(1, 0xa1), op(2, 0xa1)])[0];
+        let batch = g.rpc_batch();
+        assert_eq!(batch.as_array().unwrap().len(), 2);
+        assert_eq!(batch[1]["method"], "eth_sendUserOperation");
+        assert_eq!(batch[1]["params"][0]["aggregator"], hex_addr(&[0xa1; 20]));
+        assert_eq!(batch[1]["params"][0]["signature"], "0x02");
+        assert_eq!(batch[1]["params"][1], hex_addr(&[0xee; 20]));
+
+        let reply = json!([
+            { "jsonrpc": "2.0", "id": 2, "error": { "code": -32500, "message": "AA24" } },
+            { "jsonrpc": "2.0", "id": 1, "result": "0xabc" },
+        ]);
+        let results = batch_results(&reply, 2).unwrap();
+        assert_eq!(results[0], Ok("0xabc".to_string()));
+        assert!(results[1].as_ref().unwrap_err().contains("AA24"));
+        assert!(batch_results(&json!({ "error": "x" }), 2).is_err());
+    }
+
+    #[test]
+    fn bundler_url_must_be_plain_http() {
+        assert!(BundlerConfig::new("http://127.0.0.1:4337".into()).is_ok());
+        assert!(BundlerConfig::new("https://bundler.example".into()).is_err());
+    }
+}
@@ -0,0 +1,345 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Error taxonomy for the KMS HTTP API.
+//!
+//! Every failure a route returns is classified once into an [`ErrorKind`],
+//! which fixes both its status code and its `application/problem+json` body
+//! (RFC 9457). Typed errors in the chain decide first — an AWS
+//! [`KmsException`], a SQLite error, a command the TA does not have, a
+//! malformed derivation path or a full TA storage; the rest is classified by the messages the TA client,
+//! WebAuthn verifier and handlers already produce.
+//!
+//! Bodies keep the legacy `error` field next to the problem fields, so clients
+//! and test scripts that look for `"error"` keep working.
+
+use crate::verify::KmsException;
+use serde_json::{json, Value};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// Malformed or semantically invalid request.
+    Validation,
+    /// A WebAuthn ceremony is missing, stale or does not verify. Stays 400:
+    /// the request is refused, the caller's credentials are not in question.
+    WebAuthn,
+    /// Missing or bad API key / agent credential.
+    Unauthorized,
+    /// The wallet's spending policy refused the transaction.
+    PolicyViolation,
+    /// Nonce tracking refused a nonce the TA has already signed past.
+    NonceConflict,
+    /// The transaction is signed but held until its co-signers approve.
+    MultiSigPending,
+    NotFound,
+    PayloadTooLarge,
+    /// Per-key rate limit or a full TEE queue; retry shortly.
+    TooManyRequests,
+    /// Circuit breaker open, worker gone or a request shed from the queue.
+    Unavailable,
+    /// The TEE did not answer in time; the outcome is unknown.
+    Timeout,
+    /// The TA or TEE failed.
+    Tee,
+    /// The TA does not have the command: it is older than this CA.
+    Unsupported,
+    /// No room for another wallet in the TA's secure storage.
+    StorageExhausted,
+    Internal,
+}
+
+impl ErrorKind {
+    pub fn status(self) -> u16 {
+        match self {
+            ErrorKind::MultiSigPending => 202,
+            ErrorKind::Validation | ErrorKind::WebAuthn => 400,
+            ErrorKind::Unauthorized => 401,
+            ErrorKind::PolicyViolation => 403,
+            ErrorKind::NotFound => 404,
+            ErrorKind::NonceConflict => 409,
+            ErrorKind::PayloadTooLarge => 413,
+            ErrorKind::TooManyRequests => 429,
+            ErrorKind::Tee | ErrorKind::Internal => 500,
+            ErrorKind::Unsupported => 501,
+            ErrorKind::Unavailable => 503,
+            ErrorKind::Timeout => 504,
+            ErrorKind::StorageExhausted => 507,
+        }
+    }
+
+    fn slug(self) -> &'static str {
+        match self {
+            ErrorKind::Validation => "validation",
+            ErrorKind::WebAuthn => "webauthn",
+            ErrorKind::Unauthorized => "unauthorized",
+            ErrorKind::PolicyViolation => "policy-violation",
+            ErrorKind::NonceConflict => "nonce-conflict",
+            ErrorKind::MultiSigPending => "multisig-pending",
+            ErrorKind::NotFound => "not-found",
+            ErrorKind::PayloadTooLarge => "payload-too-large",
+            ErrorKind::TooManyRequests => "too-many-requests",
+            ErrorKind::Unavailable => "tee-unavailable",
+            ErrorKind::Timeout => "tee-timeout",
+            ErrorKind::Tee => "tee-error",
+            ErrorKind::Unsupported => "tee-unsupported",
+            ErrorKind::StorageExhausted => "storage-exhausted",
+            ErrorKind::Internal => "internal",
+        }
+    }
+
+    fn title(self) -> &'static str {
+        match self {
+            ErrorKind::Validation => "Invalid request",
+            ErrorKind::WebAuthn => "WebAuthn verification failed",
+            ErrorKind::Unauthorized => "Not authorized",
+            ErrorKind::PolicyViolation => "Refused by the spending policy",
+            ErrorKind::NonceConflict => "Nonce already signed",
+            ErrorKind::MultiSigPending => "Awaiting co-signer approval",
+            ErrorKind::NotFound => "Not found",
+            ErrorKind::PayloadTooLarge => "Payload too large",
+            ErrorKind::TooManyRequests => "Too many requests",
+            ErrorKind::Unavailable => "TEE unavailable",
+            ErrorKind::Timeout => "TEE timeout",
+            ErrorKind::Tee => "TEE error",
+            ErrorKind::Unsupported => "Not supported by this TA",
+            ErrorKind::StorageExhausted => "TEE storage exhausted",
+            ErrorKind::Internal => "Internal server error",
+        }
+    }
+
+    /// Classify an error by its typed causes, then by its message chain.
+    pub fn of(error: &anyhow::Error) -> Self {
+        for cause in error.chain() {
+            // AWS KMS answers every client exception with 400; so do we.
+            if cause.is::<KmsException>() {
+                return ErrorKind::Validation;
+            }
+            if cause.is::<rusqlite::Error>() || cause.is::<std::io::Error>() {
+                return ErrorKind::Internal;
+            }
+            if cause.is::<proto::wire::UnsupportedVersion>() {
+                return ErrorKind::Unsupported;
+            }
+            if cause.is::<proto::hd_path::InvalidPath>() {
+                return ErrorKind::Validation;
+            }
+            if cause.is::<proto::storage_quota::StorageExhausted>() {
+                return ErrorKind::StorageExhausted;
+            }
+            if cause.is::<proto::spending_policy::PolicyViolation>() {
+                return ErrorKind::PolicyViolation;
+            }
+            if cause.is::<proto::nonce_guard::NonceRefused>() {
+                return ErrorKind::NonceConflict;
+            }
+            if cause.is::<proto::multisig::MultiSigPending>() {
+                return ErrorKind::MultiSigPending;
+            }
+        }
+        Self::classify(&format!("{:#}", error))
+    }
+
+    /// Classify an error message. Order matters: a TA failure whose text says
+    /// "not found" is still a TEE fault.
+    pub fn classify(message: &str) -> Self {
+        let m = message.to_ascii_lowercase();
+        let any = |needles: &[&str]| needles.iter().any(|n| m.contains(n));
+        if any(&["storageexhausted:"]) {
+            ErrorKind::StorageExhausted
+        } else if any(&["policyviolation:"]) {
+            ErrorKind::PolicyViolation
+        } else if any(&["noncerefused:"]) {
+            ErrorKind::NonceConflict
+        } else if any(&["multisigpending:"]) {
+            ErrorKind::MultiSigPending
+        } else if any(&[
+            "tee queue full",
+            "provisioning rate exceeded",
+            "passkey locked",
+        ]) {
+            ErrorKind::TooManyRequests
+        } else if any(&[
+            "tee request dropped",
+            "circuit breaker",
+            "tee worker thread has exited",
+            "device quarantined",
+        ]) {
+            ErrorKind::Unavailable
+        } else if any(&["tee call timeout", "tee deadline exceeded"]) {
+            ErrorKind::Timeout
+        } else if any(&["0xffff", "panicked", "tee error"]) {
+            ErrorKind::Tee
+        } else if any(&["api key", "agent credential", "jwt", "bearer"]) {
+            ErrorKind::Unauthorized
+        } else if any(&[
+            "webauthn",
+            "challenge",
+            "passkey",
+            "assertion",
+            "clientdatajson",
+            "authdata",
+            "rpidhash",
+        ]) {
+            ErrorKind::WebAuthn
+        } else if any(&["not found"]) {
+            ErrorKind::NotFound
+        } else {
+            ErrorKind::Validation
+        }
+    }
+
+    /// The problem+json body. `correlation_id` ties it to the CA's logs.
+    pub fn problem(self, detail: &str, correlation_id: Option<&str>) -> Value {
+        let mut body = json!({
+            "type": format!("urn:airaccount:problem:{}", self.slug()),
+            "title": self.title(),
+            "status": self.status(),
+            "detail": detail,
+            "error": detail,
+        });
+        if let Some(id) = correlation_id {
+            body["correlationId"] = json!(id);
+        }
+        body
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::{anyhow, Context};
+
+    #[test]
+    fn messages_map_to_their_status() {
+        let cases = [
+            ("TEE queue full: 32 in-flight", 429),
+            (
+                "provisioning rate exceeded: 300 wallets in the last hour",
+                429,
+            ),
+            (
+                "passkey locked after repeated failures; retry in 60s",
+                429,
+            ),
+            (
+                "TEE circuit breaker OPEN: TA had 3 consecutive failures",
+                503,
+            ),
+            ("TEE deadline exceeded: SignHash not sent", 504),
+            (
+                "device quarantined: SignHash not sent (self-test failed)",
+                503,
+            ),
+            (
+                "TA command failed: Wallet not found (error: 0xffff0008)",
+                500,
+            ),
+            (
+                "TA create_wallet failed: StorageExhausted: secure storage is full (12/30000 wallets)",
+                507,
+            ),
+            (
+                "sign failed: PolicyViolation: chain-not-allowed chain_id=5",
+                403,
+            ),
+            (
+                "sign failed: NonceRefused: ledger-full tracked=64",
+                409,
+            ),
+            (
+                "MultiSigPending: operation 0x00 has 0/2 co-signer approvals",
+                202,
+            ),
+            ("Invalid API key", 401),
+            ("Invalid agent credential: expired", 401),
+            ("Challenge not found or expired: abc", 400),
+            ("Key not found: 1234", 404),
+            ("key is frozen", 400),
+        ];
+        for (message, status) in cases.iter() {
+            assert_eq!(
+                ErrorKind::classify(message).status(),
+                *status,
+                "{}",
+                message
+            );
+        }
+    }
+
+    #[test]
+    fn typed_causes_win_over_the_message() {
+        let db = anyhow::Error::from(rusqlite::Error::InvalidQuery).context("Key not found");
+        assert_eq!(ErrorKind::of(&db), ErrorKind::Internal);
+        let aws = crate::verify::exception("NotFoundException", "Key not found: x");
+        assert_eq!(ErrorKind::of(&aws), ErrorKind::Validation);
+        let wrapped = Err::<(), _>(anyhow!("TEE call timeout: SignHash"))
+            .context("signing failed")
+            .unwrap_err();
+        assert_eq!(ErrorKind::of(&wrapped), ErrorKind::Timeout);
+        let old_ta = anyhow::Error::new(proto::wire::UnsupportedVersion {
+            command: 68,
+            ca_version: 1,
+            ta_version: 0,
+        })
+        .context("SetLogLevel failed");
+        assert_eq!(ErrorKind::of(&old_ta).status(), 501);
+        let full = anyhow::Error::new(proto::storage_quota::StorageExhausted {
+            wallets: 30_000,
+            max_wallets: 30_000,
+            no_space: false,
+        })
+        .context("CreateKey failed");
+        assert_eq!(ErrorKind::of(&full), ErrorKind::StorageExhausted);
+        assert_eq!(ErrorKind::of(&full).status(), 507);
+        let refused = anyhow::Error::new(
+            proto::spending_policy::PolicyViolation::DailyLimitExceeded {
+                limit: 100,
+                spent: 90,
+                value: 20,
+            },
+        )
+        .context("Sign failed");
+        assert_eq!(ErrorKind::of(&refused), ErrorKind::PolicyViolation);
+        assert_eq!(ErrorKind::of(&refused).status(), 403);
+        let reused = anyhow::Error::new(proto::nonce_guard::NonceRefused::Reused {
+            chain_id: 1,
+            address: [0x35; 20],
+            nonce: 4,
+            highest: 7,
+        })
+        .context("Sign failed");
+        assert_eq!(ErrorKind::of(&reused).status(), 409);
+        let path = anyhow::Error::new(proto::hd_path::InvalidPath(
+            "Invalid index: 0x5".to_string(),
+        ))
+        .context("TEE error");
+        assert_eq!(ErrorKind::of(&path).status(), 400);
+    }
+
+    #[test]
+    fn problem_body_keeps_the_legacy_error_field() {
+        let body = ErrorKind::NotFound.problem("Key not found: x", Some("req-1"));
+        assert_eq!(body["type"], "urn:airaccount:problem:not-found");
+        assert_eq!(body["status"], 404);
+        assert_eq!(body["error"], body["detail"]);
+        assert_eq!(body["correlationId"], "req-1");
+        assert!(ErrorKind::Tee
+            .problem("x", None)
+            .get("correlationId")
+            .is_none());
+    }
+}
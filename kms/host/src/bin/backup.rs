@@ -0,0 +1,580 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! airaccount-backup — disaster-recovery archive of a KMS node.
+//!
+//!   airaccount-backup create --out node.tar.gz [--ta-config-file signed.json]
+//!   airaccount-backup restore node.tar.gz --verify-only
+//!   airaccount-backup restore node.tar.gz [--force]
+//!
+//! `create` snapshots the CA DB, the TA's capabilities and measurement, the
+//! signed TA config, the attestation manifest and the keystore files in
+//! `<config-dir>/wallet-backups` (layout: `kms::node_backup`). `restore`
+//! always verifies first — hashes, DB integrity and schema, and whether the
+//! running TA can take over — and with `--verify-only` stops there. A real
+//! restore replaces the DB (the old one is kept as `*.pre-restore-<time>`),
+//! puts the keystores back and reinstalls the TA config if it is newer.
+//! Stop kms-api before restoring. Both commands print a JSON report and exit
+//! non-zero if any step failed.
+
+use anyhow::{anyhow, bail, Context, Result};
+use kms::db::{self, KmsDb};
+use kms::node_backup::{
+    self, Archive, TaSnapshot, CAPABILITIES_ENTRY, DB_ENTRY, MEASUREMENTS_ENTRY, TA_CONFIG_ENTRY,
+    WALLETS_PREFIX,
+};
+use kms::ta_client::TaClient;
+use kms::ta_config::TaConfigRequest;
+use rand::RngCore;
+use serde::Serialize;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use structopt::StructOpt;
+
+/// The CA's published TA measurements (same file /.well-known serves).
+const MEASUREMENTS: &str = include_str!("../../attestation-measurements.json");
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "airaccount-backup",
+    about = "Disaster-recovery archive of an AirAccount KMS node"
+)]
+enum Opt {
+    /// Snapshot this node into one archive.
+    Create {
+        /// Archive to write (gzip'd tar, mode 0600).
+        #[structopt(long, parse(from_os_str))]
+        out: PathBuf,
+        #[structopt(flatten)]
+        node: NodePaths,
+        /// Signed TA config (the file given to airaccount-provision); the TA
+        /// keeps no signature, so this is the only way to restore its config.
+        #[structopt(long, parse(from_os_str))]
+        ta_config_file: Option<PathBuf>,
+    },
+    /// Verify an archive and, without --verify-only, restore it.
+    Restore {
+        #[structopt(parse(from_os_str))]
+        archive: PathBuf,
+        #[structopt(flatten)]
+        node: NodePaths,
+        /// Check integrity and TA compatibility only; change nothing.
+        #[structopt(long)]
+        verify_only: bool,
+        /// Replace an existing DB.
+        #[structopt(long)]
+        force: bool,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+struct NodePaths {
+    /// CA database [default: $KMS_DB_PATH, else /data/kms/kms.db, else ./kms.db].
+    #[structopt(long, parse(from_os_str))]
+    db_path: Option<PathBuf>,
+    /// airaccount-provision config dir (device.json, wallet-backups/).
+    #[structopt(long, default_value = "/etc/airaccount", parse(from_os_str))]
+    config_dir: PathBuf,
+}
+
+impl NodePaths {
+    fn db_path(&self) -> PathBuf {
+        self.db_path.clone().unwrap_or_else(|| {
+            PathBuf::from(std::env::var("KMS_DB_PATH").unwrap_or_else(|_| {
+                if Path::new("/data/kms").exists() {
+                    "/data/kms/kms.db".to_string()
+                } else {
+                    "kms.db".to_string()
+                }
+            }))
+        })
+    }
+
+    fn wallet_backups(&self) -> PathBuf {
+        self.config_dir.join("wallet-backups")
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum Status {
+    Ok,
+    Skipped,
+    Warn,
+    Failed,
+}
+
+#[derive(Serialize, Debug)]
+struct Step {
+    name: &'static str,
+    status: Status,
+    detail: String,
+}
+
+#[derive(Serialize, Debug, Default)]
+struct Report {
+    archive: String,
+    steps: Vec<Step>,
+}
+
+impl Report {
+    fn record(&mut self, name: &'static str, status: Status, detail: impl Into<String>) {
+        let detail = detail.into();
+        eprintln!("[backup] {:<10} {:?}: {}", name, status, detail);
+        self.steps.push(Step {
+            name,
+            status,
+            detail,
+        });
+    }
+
+    fn failed(&self) -> bool {
+        self.steps.iter().any(|s| s.status == Status::Failed)
+    }
+}
+
+/// Write via a same-directory temp file + rename, so a crash never leaves a
+/// half-written archive or DB behind.
+fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    let dir = match path.parent() {
+        Some(d) if !d.as_os_str().is_empty() => d,
+        _ => Path::new("."),
+    };
+    std::fs::create_dir_all(dir).with_context(|| format!("create {}", dir.display()))?;
+    let tmp = dir.join(format!(
+        ".{}.tmp",
+        path.file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("backup")
+    ));
+    {
+        let mut f = std::fs::File::create(&tmp)?;
+        f.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+        f.write_all(contents)?;
+        f.sync_all()?;
+    }
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+fn scratch_path(tag: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("airaccount-{}-{}.db", tag, uuid::Uuid::new_v4()))
+}
+
+/// The running TA, with its measurement when the board has the attestation PTA.
+fn running_ta(client: &mut TaClient, report: &mut Report) -> Result<TaSnapshot> {
+    let caps = client.get_capabilities().context("GetCapabilities")?;
+    let mut nonce = vec![0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut nonce);
+    let measurement = match client.get_attestation(nonce.clone()) {
+        Ok(ev) if ev.nonce == nonce => Some(ev.ta_measurement),
+        Ok(_) => bail!("attestation echoed a different nonce"),
+        Err(e) => {
+            report.record("ta", Status::Warn, format!("no attestation: {:#}", e));
+            None
+        }
+    };
+    Ok(TaSnapshot::new(&caps, measurement.as_deref()))
+}
+
+fn device_id(node: &NodePaths) -> Option<String> {
+    let raw = std::fs::read(node.config_dir.join("device.json")).ok()?;
+    let identity: serde_json::Value = serde_json::from_slice(&raw).ok()?;
+    identity["device_id"].as_str().map(str::to_string)
+}
+
+fn create(
+    out: &Path,
+    node: &NodePaths,
+    ta_config_file: Option<&Path>,
+    report: &mut Report,
+) -> Result<()> {
+    let db_path = node.db_path();
+    if !db_path.exists() {
+        bail!("no DB at {}", db_path.display());
+    }
+    let db = KmsDb::open(
+        db_path
+            .to_str()
+            .ok_or_else(|| anyhow!("{} is not UTF-8", db_path.display()))?,
+    )?;
+    let snapshot = scratch_path("backup");
+    db.snapshot_to(&snapshot)?;
+    let taken = db::inspect_file(&snapshot)
+        .and_then(|info| Ok((info, std::fs::read(&snapshot).context("read DB snapshot")?)));
+    let _ = std::fs::remove_file(&snapshot);
+    let (info, db_bytes) = taken?;
+    report.record(
+        "db",
+        Status::Ok,
+        format!(
+            "{}: schema {}, {} wallets",
+            db_path.display(),
+            info.schema_version,
+            info.wallets
+        ),
+    );
+
+    let mut client = TaClient::new()?;
+    let caps = client.get_capabilities().context("GetCapabilities")?;
+    let ta = running_ta(&mut client, report)?;
+    report.record(
+        "ta",
+        Status::Ok,
+        format!("TA {} [{}]", ta.ta_version, ta.features.join(",")),
+    );
+
+    let mut archive = Archive::new(
+        chrono::Utc::now().to_rfc3339(),
+        device_id(node),
+        db.schema_version()?,
+        ta,
+    );
+    archive.add(DB_ENTRY, db_bytes)?;
+    archive.add(CAPABILITIES_ENTRY, serde_json::to_vec_pretty(&caps)?)?;
+    archive.add(MEASUREMENTS_ENTRY, MEASUREMENTS.as_bytes().to_vec())?;
+
+    match ta_config_file {
+        Some(path) => {
+            let raw = std::fs::read(path).with_context(|| format!("read {}", path.display()))?;
+            let request: TaConfigRequest = serde_json::from_slice(&raw)
+                .with_context(|| format!("{} is not a TA config", path.display()))?;
+            let config = request.config()?;
+            request.signature(&config)?;
+            let installed = caps.config.as_ref().map(|c| c.sequence);
+            if installed != Some(config.sequence) {
+                report.record(
+                    "ta-config",
+                    Status::Warn,
+                    format!(
+                        "{} is sequence {}, the TA has {:?}",
+                        path.display(),
+                        config.sequence,
+                        installed
+                    ),
+                );
+            }
+            archive.add(TA_CONFIG_ENTRY, raw)?;
+            report.record(
+                "ta-config",
+                Status::Ok,
+                format!("sequence {} included", config.sequence),
+            );
+        }
+        None if caps.config.is_some() => report.record(
+            "ta-config",
+            Status::Warn,
+            "the TA has a config but no --ta-config-file was given; it cannot be restored",
+        ),
+        None => report.record("ta-config", Status::Skipped, "no TA config installed"),
+    }
+
+    let dir = node.wallet_backups();
+    let mut wallets = 0;
+    if let Ok(listing) = std::fs::read_dir(&dir) {
+        let mut paths: Vec<PathBuf> = listing
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.extension().is_some_and(|x| x == "json"))
+            .collect();
+        paths.sort();
+        for path in paths {
+            let raw = std::fs::read(&path).with_context(|| format!("read {}", path.display()))?;
+            let parsed = serde_json::from_slice(&raw)
+                .map_err(anyhow::Error::from)
+                .and_then(|v| kms::keystore::from_json(&v));
+            if let Err(e) = parsed {
+                report.record(
+                    "wallets",
+                    Status::Warn,
+                    format!("skipped {}: {:#}", path.display(), e),
+                );
+                continue;
+            }
+            let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+            archive.add(&format!("{}{}", WALLETS_PREFIX, name), raw)?;
+            wallets += 1;
+        }
+    }
+    report.record(
+        "wallets",
+        Status::Ok,
+        format!("{} keystore files from {}", wallets, dir.display()),
+    );
+
+    let mut buf = Vec::new();
+    archive.write_to(&mut buf)?;
+    write_atomic(out, &buf).with_context(|| format!("write {}", out.display()))?;
+    report.record(
+        "archive",
+        Status::Ok,
+        format!(
+            "{} ({} entries, {} bytes)",
+            out.display(),
+            archive.manifest.entries.len(),
+            buf.len()
+        ),
+    );
+    Ok(())
+}
+
+/// Everything `restore --verify-only` checks; the DB is inspected from a
+/// copy at `staged`.
+fn verify(
+    archive: &Archive,
+    staged: &Path,
+    client: Option<&mut TaClient>,
+    report: &mut Report,
+) -> Result<()> {
+    archive.verify()?;
+    let manifest = &archive.manifest;
+    report.record(
+        "integrity",
+        Status::Ok,
+        format!(
+            "{} entries match the manifest (created {} by {})",
+            manifest.entries.len(),
+            manifest.created_at,
+            manifest.device_id.as_deref().unwrap_or("an unknown device")
+        ),
+    );
+
+    write_atomic(staged, archive.get(DB_ENTRY).unwrap_or_default())?;
+    let info = db::inspect_file(staged)?;
+    if info.schema_version != manifest.db_schema_version {
+        bail!(
+            "DB snapshot is schema {}, manifest says {}",
+            info.schema_version,
+            manifest.db_schema_version
+        );
+    }
+    if info.schema_version > db::current_schema_version() {
+        bail!(
+            "DB snapshot is schema {}, this CA only knows up to {}",
+            info.schema_version,
+            db::current_schema_version()
+        );
+    }
+    report.record(
+        "db",
+        Status::Ok,
+        format!(
+            "schema {}, {} wallets, integrity ok",
+            info.schema_version, info.wallets
+        ),
+    );
+
+    let client = client.ok_or_else(|| anyhow!("no TA session; compatibility not checked"))?;
+    let running = running_ta(client, report)?;
+    let config = archive.ta_config()?.map(|r| r.config()).transpose()?;
+    for warning in node_backup::check_ta_compat(&manifest.ta, &running, config.as_ref())? {
+        report.record("ta", Status::Warn, warning);
+    }
+    let measurements: serde_json::Value = serde_json::from_str(MEASUREMENTS)?;
+    match running
+        .measurement
+        .as_deref()
+        .map(|m| (m, node_backup::measurement_status(&measurements, m)))
+    {
+        Some((m, Some("revoked"))) => bail!("running TA measurement {} is revoked", m),
+        Some((m, None)) => report.record(
+            "ta",
+            Status::Warn,
+            format!(
+                "running TA measurement {} is not in the attestation manifest",
+                m
+            ),
+        ),
+        _ => {}
+    }
+    report.record(
+        "ta",
+        Status::Ok,
+        format!(
+            "running TA {} can take over from {}",
+            running.ta_version, manifest.ta.ta_version
+        ),
+    );
+    Ok(())
+}
+
+fn restore_db(staged: &Path, db_path: &Path, force: bool, report: &mut Report) -> Result<()> {
+    if db_path.exists() {
+        if !force {
+            bail!(
+                "{} exists; stop kms-api and pass --force",
+                db_path.display()
+            );
+        }
+        let suffix = format!(
+            ".pre-restore-{}",
+            chrono::Utc::now().format("%Y%m%dT%H%M%S")
+        );
+        for sidecar in ["", "-wal", "-shm"] {
+            let from = PathBuf::from(format!("{}{}", db_path.display(), sidecar));
+            if from.exists() {
+                let to = PathBuf::from(format!("{}{}{}", db_path.display(), sidecar, suffix));
+                std::fs::rename(&from, &to)
+                    .with_context(|| format!("move {} aside", from.display()))?;
+            }
+        }
+        report.record(
+            "db",
+            Status::Ok,
+            format!("previous DB kept as {}{}", db_path.display(), suffix),
+        );
+    }
+    write_atomic(db_path, &std::fs::read(staged)?)?;
+    report.record("db", Status::Ok, format!("restored {}", db_path.display()));
+    Ok(())
+}
+
+fn restore_wallets(archive: &Archive, node: &NodePaths, report: &mut Report) -> Result<()> {
+    let dir = node.wallet_backups();
+    let (mut written, mut unchanged) = (0, 0);
+    for (name, raw) in archive.wallet_backups() {
+        let path = dir.join(name);
+        match std::fs::read(&path) {
+            Ok(existing) if existing == raw => unchanged += 1,
+            Ok(_) => bail!("{} exists with different contents", path.display()),
+            Err(_) => {
+                write_atomic(&path, raw)?;
+                written += 1;
+            }
+        }
+    }
+    report.record(
+        "wallets",
+        Status::Ok,
+        format!(
+            "{} keystore files written, {} already present in {}",
+            written,
+            unchanged,
+            dir.display()
+        ),
+    );
+    Ok(())
+}
+
+fn restore_ta_config(archive: &Archive, client: &mut TaClient, report: &mut Report) -> Result<()> {
+    let request = match archive.ta_config()? {
+        Some(request) => request,
+        None => {
+            report.record("ta-config", Status::Skipped, "archive holds no TA config");
+            return Ok(());
+        }
+    };
+    let config = request.config()?;
+    let installed = client.get_capabilities()?.config.map(|c| c.sequence);
+    if installed.is_some_and(|s| s >= config.sequence) {
+        report.record(
+            "ta-config",
+            Status::Skipped,
+            format!("TA already has sequence {:?}", installed),
+        );
+        return Ok(());
+    }
+    let signature = request.signature(&config)?;
+    client
+        .install_config(config.clone(), signature)
+        .context("InstallConfig")?;
+    report.record(
+        "ta-config",
+        Status::Ok,
+        format!("sequence {} installed", config.sequence),
+    );
+    Ok(())
+}
+
+fn restore(
+    path: &Path,
+    node: &NodePaths,
+    verify_only: bool,
+    force: bool,
+    report: &mut Report,
+) -> Result<()> {
+    let file = std::fs::File::open(path).with_context(|| format!("open {}", path.display()))?;
+    let archive = Archive::read_from(std::io::BufReader::new(file))?;
+    let mut client = match TaClient::new() {
+        Ok(c) => Some(c),
+        Err(e) => {
+            report.record("ta", Status::Failed, format!("{:#}", e));
+            None
+        }
+    };
+    let staged = scratch_path("restore");
+    let result = (|| {
+        verify(&archive, &staged, client.as_mut(), report)?;
+        if verify_only {
+            report.record("restore", Status::Skipped, "--verify-only");
+            return Ok(());
+        }
+        if report.failed() {
+            bail!("verification failed; nothing restored");
+        }
+        let client = client.as_mut().expect("verify needs a TA session");
+        restore_db(&staged, &node.db_path(), force, report)?;
+        restore_wallets(&archive, node, report)?;
+        restore_ta_config(&archive, client, report)?;
+        report.record(
+            "restore",
+            Status::Ok,
+            "start kms-api; wallets the TA no longer holds come back with CreateKey + Keystore",
+        );
+        Ok(())
+    })();
+    let _ = std::fs::remove_file(&staged);
+    result
+}
+
+fn main() -> Result<()> {
+    let opt = Opt::from_args();
+    let mut report = Report::default();
+    let (name, result) = match &opt {
+        Opt::Create {
+            out,
+            node,
+            ta_config_file,
+        } => {
+            report.archive = out.display().to_string();
+            (
+                "create",
+                create(out, node, ta_config_file.as_deref(), &mut report),
+            )
+        }
+        Opt::Restore {
+            archive,
+            node,
+            verify_only,
+            force,
+        } => {
+            report.archive = archive.display().to_string();
+            (
+                "restore",
+                restore(archive, node, *verify_only, *force, &mut report),
+            )
+        }
+    };
+    if let Err(e) = result {
+        report.record(name, Status::Failed, format!("{:#}", e));
+    }
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    if report.failed() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
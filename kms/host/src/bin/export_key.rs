@@ -1,21 +1,64 @@
 // Export private key CLI tool (admin only, no passkey required)
 // WARNING: This tool exports private keys in plain text.
+// With --keystore it writes a passphrase-encrypted wallet backup instead
+// (sealed in the TA; restore with CreateKey + Keystore).
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use kms::ta_client::TaClient;
+use proto::kdf::KdfConfig;
 use std::env;
 use uuid::Uuid;
 
+fn usage(program: &str) -> ! {
+    eprintln!("Usage: {} <wallet_id> [derivation_path]", program);
+    eprintln!(
+        "       {} <wallet_id> --keystore <passphrase_file> [--kdf <spec>]",
+        program
+    );
+    eprintln!("Default derivation path: m/44'/60'/0'/0/0");
+    eprintln!(
+        "KDF spec: argon2id[:m=512,t=16,p=1] | scrypt[:log_n=12,r=1,p=8] | pbkdf2[:c=262144]"
+    );
+    eprintln!("Default KDF: $KMS_KEYSTORE_KDF, else argon2id");
+    std::process::exit(1);
+}
+
 fn main() -> Result<()> {
     let args: Vec<String> = env::args().collect();
 
-    if args.len() < 2 || args.len() > 3 {
-        eprintln!("Usage: {} <wallet_id> [derivation_path]", args[0]);
-        eprintln!("Default derivation path: m/44'/60'/0'/0/0");
-        std::process::exit(1);
+    if args.len() < 2 || args.len() > 6 {
+        usage(&args[0]);
     }
 
     let wallet_id = Uuid::parse_str(&args[1])?;
+
+    if args.get(2).map(|s| s.as_str()) == Some("--keystore") {
+        let passphrase_file = args.get(3).unwrap_or_else(|| usage(&args[0]));
+        let kdf = match (args.get(4).map(|s| s.as_str()), args.get(5)) {
+            (Some("--kdf"), Some(spec)) => spec.clone(),
+            (None, None) => env::var("KMS_KEYSTORE_KDF").unwrap_or_else(|_| "argon2id".into()),
+            _ => usage(&args[0]),
+        };
+        let kdf: KdfConfig = kdf.parse().map_err(|e| anyhow!("--kdf: {}", e))?;
+        let passphrase = std::fs::read_to_string(passphrase_file)?;
+        let passphrase = passphrase.trim_end_matches(&['\r', '\n'][..]);
+        if passphrase.is_empty() {
+            return Err(anyhow!("passphrase file is empty"));
+        }
+
+        eprintln!("🔐 Exporting wallet keystore ({})...", kdf);
+        let mut ta_client = TaClient::new()?;
+        let keystore = ta_client.export_keystore(wallet_id, passphrase, kdf, None)?;
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&kms::keystore::to_json(&keystore))?
+        );
+        return Ok(());
+    }
+
+    if args.len() > 3 {
+        usage(&args[0]);
+    }
     let derivation_path = args
         .get(2)
         .map(|s| s.as_str())
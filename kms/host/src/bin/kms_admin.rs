@@ -22,9 +22,29 @@
 //!   kms-admin jwt-secret-status              # list kid versions, status, age
 //!   kms-admin list-agent-keys [--account <wallet_id>]
 //!   kms-admin revoke-agent-key <wallet_id>:<agent_index>
+//!   kms-admin crash-dumps [--clear]           # TA postmortem records of internal failures
+//!   kms-admin ta-storage-gc [--delete <digest>] # orphaned TA secure-storage objects
+//!   kms-admin provision-wallets --tenant <id> ... # bulk wallets through the running kms-api
+//!   kms-admin tenant-quota <tenant> [<quota>]
+//!   kms-admin tenant-wallets <tenant> [--tag <tag>]
+//!   kms-admin provisioning-report <batch_id> [--csv]
+//!   kms-admin events [<key_id>] [--after <seq>] [--limit <n>]  # CA event log as JSON lines
+//!   kms-admin verify-events                  # check the hash chain and the tables against it
+//!   kms-admin rebuild-projections            # rebuild wallets/address_index/tx_history from the log
+//!   kms-admin device-health [--limit <n>]    # quarantine state and recent TA health checks
+//!   kms-admin device-health-check            # run a check now through the running kms-api
+//!   kms-admin device-health-release [--note <text>]
+//!   kms-admin hsm-wallet --label <label> --passkey <hex> [--path <path>]  # wallet on a PKCS#11 key
+//!   kms-admin hsm-wallets
+//!   kms-admin standby-status                 # this node's role in an active-standby pair
+//!   kms-admin standby-promote [--force]      # fail over to this standby through the running kms-api
+//!   kms-admin import-wallet --passkey <hex>  # wallet from a recovery phrase read on stdin
 
-use anyhow::Result;
-use kms::db::KmsDb;
+use anyhow::{bail, Context, Result};
+use kms::db::{KmsDb, WalletRow, WalletSignerRow};
+use kms::pkcs11::{self, Pkcs11Config, Pkcs11Token};
+use kms::provisioning::ProvisioningReport;
+use kms::signer;
 
 fn db_path() -> String {
     std::env::var("KMS_DB_PATH").unwrap_or_else(|_| {
@@ -36,6 +56,13 @@ fn db_path() -> String {
     })
 }
 
+/// Value of `--name value`.
+fn flag<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.windows(2)
+        .find(|w| w[0] == name)
+        .map(|w| w[1].as_str())
+}
+
 /// Parse compound agent keyId "wallet_uuid:agent_index"
 fn parse_agent_key_id(key_id: &str) -> Result<(String, u32)> {
     let parts: Vec<&str> = key_id.splitn(2, ':').collect();
@@ -61,6 +88,23 @@ async fn main() -> Result<()> {
         "jwt-secret-status" => cmd_jwt_secret_status(),
         "list-agent-keys" => cmd_list_agent_keys(&args),
         "revoke-agent-key" => cmd_revoke_agent_key(&args),
+        "crash-dumps" => cmd_crash_dumps(&args).await,
+        "ta-storage-gc" => cmd_ta_storage_gc(&args).await,
+        "provision-wallets" => cmd_provision_wallets(&args),
+        "tenant-quota" => cmd_tenant_quota(&args),
+        "tenant-wallets" => cmd_tenant_wallets(&args),
+        "provisioning-report" => cmd_provisioning_report(&args),
+        "events" => cmd_events(&args),
+        "verify-events" => cmd_verify_events(),
+        "rebuild-projections" => cmd_rebuild_projections(),
+        "device-health" => cmd_device_health(&args),
+        "device-health-check" => cmd_device_health_check(),
+        "device-health-release" => cmd_device_health_release(&args),
+        "hsm-wallet" => cmd_hsm_wallet(&args),
+        "hsm-wallets" => cmd_hsm_wallets(),
+        "standby-status" => cmd_standby_status(),
+        "standby-promote" => cmd_standby_promote(&args),
+        "import-wallet" => cmd_import_wallet(&args),
         _ => {
             println!("KMS Admin CLI — host-access required");
             println!();
@@ -78,6 +122,87 @@ async fn main() -> Result<()> {
             println!();
             println!("  kms-admin revoke-agent-key <wallet_id>:<agent_index>");
             println!("    Force-revoke an agent key (e.g. abc123:0).");
+            println!();
+            println!("  kms-admin crash-dumps [--clear]");
+            println!("    Show the TA's records of internal failures. --clear: empty them after reading.");
+            println!();
+            println!("  kms-admin ta-storage-gc [--delete <digest>]");
+            println!("    List TA secure-storage objects whose wallet is gone. --delete: remove them, given");
+            println!("    the digest the listing printed; refused if the set changed in between.");
+            println!();
+            println!("  kms-admin provision-wallets --tenant <id> (--passkeys <file> | --passkey <hex> --count <n>)");
+            println!("                              [--tag <tag>]... [--description <text>] [--csv] [--out <file>]");
+            println!("    Create wallets for a tenant through kms-api (KMS_URL, default http://127.0.0.1:3000;");
+            println!("    KMS_PROVISIONING_TOKEN and KMS_API_KEY from the environment). <file>: one passkey");
+            println!("    public key per line. Writes the attested report (and with --csv the CSV beside it).");
+            println!();
+            println!("  kms-admin tenant-quota <tenant> [<quota>]");
+            println!("    Show a tenant's wallets against its quota, or set the quota.");
+            println!();
+            println!("  kms-admin tenant-wallets <tenant> [--tag <tag>]");
+            println!("    List a tenant's provisioned wallets, optionally only those with a tag.");
+            println!();
+            println!("  kms-admin provisioning-report <batch_id> [--csv]");
+            println!("    Print a provisioning batch's report as attested, or as CSV.");
+            println!();
+            println!("  kms-admin events [<key_id>] [--after <seq>] [--limit <n>]");
+            println!("    Print the CA event log as JSON lines: one key's history, or every event");
+            println!("    after <seq> (default 0, at most <n>, default 1000) for replication.");
+            println!();
+            println!("  kms-admin verify-events");
+            println!("    Check the event log's hash chain and the tables against a replay of it.");
+            println!();
+            println!("  kms-admin rebuild-projections");
+            println!("    Rebuild wallets, address_index and tx_history from the event log.");
+            println!("    Stop kms-api first; keep a backup of the DB.");
+            println!();
+            println!("  kms-admin device-health [--limit <n>]");
+            println!("    Show whether this device is quarantined and its last <n> (default 10)");
+            println!("    TA self-test / attestation checks.");
+            println!();
+            println!("  kms-admin device-health-check");
+            println!("  kms-admin device-health-release [--note <text>]");
+            println!(
+                "    Run a check now, or lift the quarantine once a fresh check passes, through"
+            );
+            println!(
+                "    kms-api (KMS_URL; KMS_ADMIN_TOKEN and KMS_API_KEY from the environment)."
+            );
+            println!();
+            println!("  kms-admin hsm-wallet --label <label> --passkey <hex> [--path <path>] [--description <text>]");
+            println!("    Register the secp256k1 key pair labelled <label> on the PKCS#11 token");
+            println!(
+                "    (KMS_PKCS11_MODULE, KMS_PKCS11_SLOT, KMS_PKCS11_PIN) as a wallet owned by the"
+            );
+            println!("    passkey; it signs for <path> only (default m/44'/60'/0'/0/0).");
+            println!();
+            println!("  kms-admin hsm-wallets");
+            println!("    List the wallets signed by the PKCS#11 token.");
+            println!();
+            println!("  kms-admin standby-status");
+            println!(
+                "    Show this node's role, fencing token and last sync in an active-standby pair."
+            );
+            println!();
+            println!("  kms-admin standby-promote [--force]");
+            println!(
+                "    Make this standby the active through kms-api (KMS_URL, KMS_ADMIN_TOKEN):"
+            );
+            println!(
+                "    checks every ready wallet is in this TA (--force: promote anyway) and fences"
+            );
+            println!("    the old active if it answers.");
+            println!();
+            println!("  kms-admin import-wallet --passkey <hex> [--description <text>]");
+            println!(
+                "    Create a wallet from a 24-word BIP39 recovery phrase read from stdin (never"
+            );
+            println!(
+                "    from the command line), through kms-api CreateKey (KMS_URL, KMS_API_KEY)."
+            );
+            println!(
+                "    Prints the wallet's first address to compare with the wallet it came from."
+            );
             Ok(())
         }
     }
@@ -92,7 +217,7 @@ async fn cmd_rotate_jwt_secret(args: &[String]) -> Result<()> {
         println!();
     }
 
-    #[cfg(feature = "tee")]
+    #[cfg(any(feature = "tee", feature = "soft-tee"))]
     {
         use kms::ta_client::TeeHandle;
         let tee = TeeHandle::new();
@@ -135,7 +260,7 @@ async fn cmd_rotate_jwt_secret(args: &[String]) -> Result<()> {
         }
     }
 
-    #[cfg(not(feature = "tee"))]
+    #[cfg(not(any(feature = "tee", feature = "soft-tee")))]
     {
         eprintln!("rotate-jwt-secret requires TEE feature (run on KMS host with OP-TEE)");
         std::process::exit(1);
@@ -239,3 +364,723 @@ fn cmd_revoke_agent_key(args: &[String]) -> Result<()> {
     }
     Ok(())
 }
+
+async fn cmd_crash_dumps(args: &[String]) -> Result<()> {
+    let clear = args.iter().any(|a| a == "--clear");
+
+    #[cfg(any(feature = "tee", feature = "soft-tee"))]
+    {
+        use kms::ta_client::TeeHandle;
+        let tee = TeeHandle::new();
+        let dumps = tee.crash_dumps(clear).await?;
+
+        if dumps.records.is_empty() {
+            println!("No crash records ({} dropped).", dumps.dropped);
+            return Ok(());
+        }
+        println!(
+            "{:<6} {:<8} {:<16} {:<12} {:<10} {:<14} CMD_CALLS/ERR",
+            "SEQ", "CMD", "CLASS", "CODE", "UPTIME_S", "TA_CALLS/ERR"
+        );
+        println!("{}", "-".repeat(90));
+        for r in &dumps.records {
+            // Debug ignores width, so the columns are rendered first.
+            let command = format!("{:?}", proto::Command::from(r.command));
+            let class = format!("{:?}", r.class);
+            let code = format!("0x{:08x}", r.code);
+            let ta_calls = format!("{}/{}", r.invocations, r.failures);
+            println!(
+                "{:<6} {:<8} {:<16} {:<12} {:<10} {:<14} {}/{}",
+                r.seq,
+                command,
+                class,
+                code,
+                r.uptime_secs,
+                ta_calls,
+                r.command_invocations,
+                r.command_failures
+            );
+        }
+        println!(
+            "\n{} record(s), {} older dropped.",
+            dumps.records.len(),
+            dumps.dropped
+        );
+        if clear {
+            println!("Crash log cleared.");
+        }
+    }
+
+    #[cfg(not(any(feature = "tee", feature = "soft-tee")))]
+    {
+        let _ = clear;
+        eprintln!("crash-dumps requires TEE feature (run on KMS host with OP-TEE)");
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+async fn cmd_ta_storage_gc(args: &[String]) -> Result<()> {
+    use std::convert::TryInto;
+    let confirm = match flag(args, "--delete") {
+        Some(d) => {
+            let bytes = hex::decode(d.trim_start_matches("0x")).context("--delete: hex digest")?;
+            let digest: [u8; 32] = bytes
+                .try_into()
+                .map_err(|_| anyhow::anyhow!("--delete: digest must be 32 bytes"))?;
+            Some(digest)
+        }
+        None => None,
+    };
+
+    #[cfg(any(feature = "tee", feature = "soft-tee"))]
+    {
+        use kms::ta_client::TeeHandle;
+        let tee = TeeHandle::new();
+        let out = tee.scavenge_storage(confirm).await?;
+
+        println!(
+            "{} per-wallet object(s) scanned, {} orphaned.",
+            out.scanned, out.orphans
+        );
+        for (kind, n) in &out.by_kind {
+            println!("  {:<20} {}", format!("{:?}", kind), n);
+        }
+        if !out.listed.is_empty() {
+            println!();
+            println!("{:<16} {:<20} STORE_ID", "REASON", "KIND");
+            for o in &out.listed {
+                println!(
+                    "{:<16} {:<20} {}",
+                    format!("{:?}", o.reason),
+                    format!("{:?}", o.kind),
+                    o.store_id
+                );
+            }
+            if out.listed.len() < out.orphans as usize {
+                println!("... and {} more", out.orphans as usize - out.listed.len());
+            }
+        }
+        if out.scavenged_at_load > 0 {
+            println!(
+                "\n{} removed automatically when this TA instance loaded.",
+                out.scavenged_at_load
+            );
+        }
+        // Older TAs report no storage usage.
+        if let Some(usage) = tee.ta_stats().await.ok().and_then(|s| s.storage) {
+            println!(
+                "\nWallets: {}/{} ({}%), {} creation(s) refused as StorageExhausted since load.",
+                usage.wallets,
+                usage.max_wallets,
+                usage.used_percent(),
+                usage.exhausted
+            );
+        }
+        if confirm.is_some() {
+            println!("\nDeleted {} object(s).", out.deleted);
+        } else if out.orphans > 0 {
+            println!("\nDigest: 0x{}", hex::encode(out.digest));
+            println!(
+                "Delete them with: kms-admin ta-storage-gc --delete 0x{}",
+                hex::encode(out.digest)
+            );
+        }
+    }
+
+    #[cfg(not(any(feature = "tee", feature = "soft-tee")))]
+    {
+        let _ = confirm;
+        eprintln!("ta-storage-gc requires TEE feature (run on KMS host with OP-TEE)");
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+fn cmd_provision_wallets(args: &[String]) -> Result<()> {
+    let tenant = flag(args, "--tenant").context("--tenant is required")?;
+    let mut body = serde_json::json!({
+        "tenant": tenant,
+        "tags": args
+            .windows(2)
+            .filter(|w| w[0] == "--tag")
+            .map(|w| w[1].as_str())
+            .collect::<Vec<_>>(),
+    });
+    match (flag(args, "--passkeys"), flag(args, "--passkey")) {
+        (Some(file), None) => {
+            let keys: Vec<String> = std::fs::read_to_string(file)
+                .with_context(|| format!("read {}", file))?
+                .lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty() && !l.starts_with('#'))
+                .map(str::to_string)
+                .collect();
+            body["passkeyPublicKeys"] = serde_json::json!(keys);
+        }
+        (None, Some(key)) => {
+            let count: usize = flag(args, "--count")
+                .context("--passkey needs --count")?
+                .parse()
+                .context("--count must be a number")?;
+            body["passkeyPublicKey"] = serde_json::json!(key);
+            body["count"] = serde_json::json!(count);
+        }
+        _ => bail!("give either --passkeys <file> or --passkey <hex> --count <n>"),
+    }
+    if let Some(description) = flag(args, "--description") {
+        body["description"] = serde_json::json!(description);
+    }
+    let csv = args.iter().any(|a| a == "--csv");
+    if csv {
+        body["format"] = serde_json::json!("csv");
+    }
+
+    let url = format!(
+        "{}/admin/provision-wallets",
+        std::env::var("KMS_URL")
+            .unwrap_or_else(|_| "http://127.0.0.1:3000".to_string())
+            .trim_end_matches('/')
+    );
+    let token = std::env::var("KMS_PROVISIONING_TOKEN")
+        .ok()
+        .filter(|t| !t.is_empty())
+        .context("KMS_PROVISIONING_TOKEN is not set")?;
+    let api_key = std::env::var("KMS_API_KEY").ok().filter(|k| !k.is_empty());
+    let response: serde_json::Value =
+        serde_json::from_str(&post_json(&url, &token, api_key.as_deref(), &body)?)
+            .context("kms-api answered with something other than JSON")?;
+
+    let batch_id = response["batchId"].as_str().unwrap_or("batch").to_string();
+    let report: ProvisioningReport = serde_json::from_str(
+        response["report"]
+            .as_str()
+            .context("response carries no report")?,
+    )?;
+    let out = flag(args, "--out").map(str::to_string).unwrap_or_else(|| {
+        format!(
+            "provisioning-{}.{}",
+            batch_id,
+            if csv { "csv" } else { "json" }
+        )
+    });
+    if csv {
+        let csv_body = response["csv"]
+            .as_str()
+            .context("response carries no csv")?;
+        std::fs::write(&out, csv_body).with_context(|| format!("write {}", out))?;
+        let proof = format!("{}.attestation.json", out.trim_end_matches(".csv"));
+        let mut bundle = response.clone();
+        if let Some(fields) = bundle.as_object_mut() {
+            fields.remove("csv");
+        }
+        std::fs::write(&proof, serde_json::to_vec_pretty(&bundle)?)
+            .with_context(|| format!("write {}", proof))?;
+        println!("Report: {} (attested report and evidence: {})", out, proof);
+    } else {
+        std::fs::write(&out, serde_json::to_vec_pretty(&response)?)
+            .with_context(|| format!("write {}", out))?;
+        println!("Report: {}", out);
+    }
+    println!(
+        "Batch {}: {} of {} wallet(s) for tenant {}",
+        batch_id,
+        report.wallets.len(),
+        report.requested,
+        report.tenant
+    );
+    if let Some(reason) = &report.incomplete {
+        println!("   Stopped early: {}", reason);
+    }
+    if response["attestation"].is_null() {
+        println!("   WARNING: no attestation evidence — the TA did not answer GetAttestation.");
+    }
+    Ok(())
+}
+
+/// POST `body` to an admin or provisioning endpoint with its bearer token.
+fn post_json(
+    url: &str,
+    token: &str,
+    api_key: Option<&str>,
+    body: &serde_json::Value,
+) -> Result<String> {
+    let mut headers = vec![format!("Authorization: Bearer {}", token)];
+    if let Some(k) = api_key {
+        headers.push(format!("x-api-key: {}", k));
+    }
+    curl_post(url, &headers, body)
+}
+
+/// POST `body` with curl, as airaccount-provision does: no HTTP client
+/// dependency, the headers (bearer token, API key) go in through a config on
+/// stdin so they never show up in the process list, and the body (passkey
+/// keys, a recovery phrase) through a 0600 temp file.
+fn curl_post(url: &str, headers: &[String], body: &serde_json::Value) -> Result<String> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::process::{Command, Stdio};
+    let unsafe_header = |v: &str| v.contains(|c: char| c == '"' || c == '\\' || c.is_control());
+    if headers.iter().any(|h| unsafe_header(h)) {
+        bail!("token or API key contains characters that cannot be passed to curl");
+    }
+    let body_path = std::env::temp_dir().join(format!(".kms-admin-{}.json", std::process::id()));
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&body_path)
+        .and_then(|mut f| f.write_all(&serde_json::to_vec(body)?))
+        .with_context(|| format!("write {}", body_path.display()))?;
+    let mut child = Command::new("curl")
+        .args(["-sS", "-m", "900", "-K", "-"])
+        .args(["-H", "content-type: application/json"])
+        .arg("--data-binary")
+        .arg(format!("@{}", body_path.display()))
+        .args(["-w", "\n%{http_code}"])
+        .arg(url)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .inspect_err(|_| {
+            let _ = std::fs::remove_file(&body_path);
+        })
+        .context("spawn curl")?;
+    if let Some(mut stdin) = child.stdin.take() {
+        for header in headers {
+            writeln!(stdin, "header = \"{}\"", header)?;
+        }
+    }
+    let out = child.wait_with_output();
+    let _ = std::fs::remove_file(&body_path);
+    let out = out.context("wait for curl")?;
+    if !out.status.success() {
+        bail!(
+            "request to {} failed: {}",
+            url,
+            String::from_utf8_lossy(&out.stderr).trim()
+        );
+    }
+    let text = String::from_utf8_lossy(&out.stdout).into_owned();
+    let (body, status) = text.rsplit_once('\n').unwrap_or((text.as_str(), ""));
+    if status != "200" {
+        bail!("kms-api answered {}: {}", status, body.trim());
+    }
+    Ok(body.to_string())
+}
+
+fn cmd_tenant_quota(args: &[String]) -> Result<()> {
+    let tenant = args
+        .get(2)
+        .context("usage: kms-admin tenant-quota <tenant> [<quota>]")?;
+    kms::provisioning::validate_tenant(tenant)?;
+    let db = KmsDb::open(&db_path())?;
+    if let Some(quota) = args.get(3) {
+        let quota: u32 = quota
+            .parse()
+            .ok()
+            .filter(|q| *q > 0)
+            .context("quota must be a positive integer")?;
+        db.set_tenant_quota(tenant, quota)?;
+        println!("Tenant {} quota set to {} wallets.", tenant, quota);
+    }
+    let held = db.count_tenant_wallets(tenant, 0)?;
+    match db.tenant_quota(tenant)? {
+        Some(quota) => println!("Tenant {}: {} of {} wallets.", tenant, held, quota),
+        None => println!(
+            "Tenant {}: {} wallets (default quota, KMS_TENANT_WALLET_QUOTA).",
+            tenant, held
+        ),
+    }
+    Ok(())
+}
+
+fn cmd_tenant_wallets(args: &[String]) -> Result<()> {
+    let tenant = args
+        .get(2)
+        .context("usage: kms-admin tenant-wallets <tenant> [--tag <tag>]")?;
+    let db = KmsDb::open(&db_path())?;
+    let rows = db.list_tenant_wallets(tenant, flag(args, "--tag"))?;
+    if rows.is_empty() {
+        println!("No wallets provisioned for tenant {}.", tenant);
+        return Ok(());
+    }
+    println!("{:<38} {:<44} {:<38} TAGS", "KEY_ID", "ADDRESS", "BATCH_ID");
+    println!("{}", "-".repeat(140));
+    for row in &rows {
+        let address = db
+            .get_wallet(&row.key_id)?
+            .and_then(|w| w.address)
+            .unwrap_or_else(|| "-".to_string());
+        println!(
+            "{:<38} {:<44} {:<38} {}",
+            row.key_id,
+            address,
+            row.batch_id,
+            row.tags.join(",")
+        );
+    }
+    println!("\n{} wallet(s).", rows.len());
+    Ok(())
+}
+
+fn cmd_provisioning_report(args: &[String]) -> Result<()> {
+    let batch_id = args
+        .get(2)
+        .context("usage: kms-admin provisioning-report <batch_id> [--csv]")?;
+    let db = KmsDb::open(&db_path())?;
+    let batch = db
+        .get_provisioning_batch(batch_id)?
+        .with_context(|| format!("no provisioning batch {}", batch_id))?;
+    if args.iter().any(|a| a == "--csv") {
+        let report: ProvisioningReport = serde_json::from_str(&batch.report)?;
+        print!("{}", report.to_csv());
+    } else {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&serde_json::json!({
+                "batchId": batch.batch_id,
+                "report": batch.report,
+                "digest": hex::encode(kms::provisioning::report_digest(&batch.report)),
+                "attestation": serde_json::from_str::<serde_json::Value>(&batch.attestation)?,
+            }))?
+        );
+    }
+    Ok(())
+}
+
+fn cmd_events(args: &[String]) -> Result<()> {
+    let db = KmsDb::open(&db_path())?;
+    let events = match args.get(2).filter(|a| !a.starts_with("--")) {
+        Some(key_id) => db.events_for(key_id)?,
+        None => {
+            let after = match flag(args, "--after") {
+                Some(v) => v.parse().context("--after: expected a seq number")?,
+                None => 0,
+            };
+            let limit = match flag(args, "--limit") {
+                Some(v) => v.parse().context("--limit: expected a count")?,
+                None => 1000,
+            };
+            db.events_after(after, limit)?
+        }
+    };
+    for e in &events {
+        println!("{}", serde_json::to_string(e)?);
+    }
+    Ok(())
+}
+
+fn cmd_verify_events() -> Result<()> {
+    let db = KmsDb::open(&db_path())?;
+    let head = db.verify_event_chain()?;
+    println!(
+        "Event chain intact: {} event(s), head {}",
+        head.events, head.hash
+    );
+    let diffs = db.verify_projections()?;
+    if diffs.is_empty() {
+        println!("Tables match the log.");
+        return Ok(());
+    }
+    for d in &diffs {
+        println!("{} {}", d.table, d.key);
+        println!("  stored:   {}", d.stored.as_deref().unwrap_or("(missing)"));
+        println!(
+            "  replayed: {}",
+            d.replayed.as_deref().unwrap_or("(missing)")
+        );
+    }
+    bail!(
+        "{} row(s) differ from the log — `kms-admin rebuild-projections` restores them",
+        diffs.len()
+    )
+}
+
+fn cmd_rebuild_projections() -> Result<()> {
+    let db = KmsDb::open(&db_path())?;
+    let report = db.rebuild_projections()?;
+    println!(
+        "Replayed {} event(s) (head {}): {} wallet(s), {} address(es), {} transaction(s).",
+        report.events, report.head, report.wallets, report.addresses, report.txs
+    );
+    if report.orphans > 0 {
+        println!(
+            "WARNING: {} row(s) in other tables refer to wallets the log does not have \
+             (see PRAGMA foreign_key_check).",
+            report.orphans
+        );
+    }
+    Ok(())
+}
+
+fn cmd_device_health(args: &[String]) -> Result<()> {
+    let limit = match flag(args, "--limit") {
+        Some(v) => v.parse().context("--limit: expected a count")?,
+        None => 10,
+    };
+    let db = KmsDb::open(&db_path())?;
+    match db.device_quarantine()? {
+        Some(q) => println!(
+            "QUARANTINED since {} (check #{}): {}",
+            chrono::DateTime::from_timestamp(q.since, 0)
+                .map(|t| t.to_rfc3339())
+                .unwrap_or_else(|| q.since.to_string()),
+            q.check_id
+                .map(|id| id.to_string())
+                .unwrap_or_else(|| "-".to_string()),
+            q.reason
+        ),
+        None => println!("Not quarantined."),
+    }
+    let checks = db.list_health_checks(limit)?;
+    if checks.is_empty() {
+        println!("No health checks recorded.");
+        return Ok(());
+    }
+    println!(
+        "{:<8} {:<26} {:<10} {:<8} FAILURES",
+        "ID", "CHECKED_AT", "SOURCE", "RESULT"
+    );
+    for c in &checks {
+        let failures = serde_json::from_str::<kms::device_health::HealthReport>(&c.report)
+            .map(|r| r.reason())
+            .unwrap_or_else(|_| "(unreadable report)".to_string());
+        println!(
+            "{:<8} {:<26} {:<10} {:<8} {}",
+            c.id,
+            chrono::DateTime::from_timestamp(c.checked_at, 0)
+                .map(|t| t.to_rfc3339())
+                .unwrap_or_default(),
+            c.source,
+            if c.passed { "pass" } else { "FAIL" },
+            failures
+        );
+    }
+    Ok(())
+}
+
+fn cmd_device_health_check() -> Result<()> {
+    let report: kms::device_health::HealthReport = serde_json::from_str(&post_admin(
+        "/admin/device-health/check",
+        &serde_json::json!({}),
+    )?)
+    .context("kms-api answered with something other than a health report")?;
+    for check in &report.self_test {
+        println!(
+            "  {:<18} {}{}",
+            check.name,
+            if check.passed { "pass" } else { "FAIL" },
+            if check.detail.is_empty() {
+                String::new()
+            } else {
+                format!(" — {}", check.detail)
+            }
+        );
+    }
+    println!(
+        "  {:<18} {}",
+        "measurement",
+        report.measurement_status.as_deref().unwrap_or("-")
+    );
+    if report.passed() {
+        println!("Device health check passed.");
+        Ok(())
+    } else {
+        bail!(
+            "device health check failed, device quarantined: {}",
+            report.reason()
+        )
+    }
+}
+
+fn cmd_device_health_release(args: &[String]) -> Result<()> {
+    let mut body = serde_json::json!({});
+    if let Some(note) = flag(args, "--note") {
+        body["note"] = serde_json::json!(note);
+    }
+    post_admin("/admin/device-health/release", &body)?;
+    println!("Quarantine lifted; signing resumes on this device.");
+    Ok(())
+}
+
+/// POST to kms-api's operator endpoints with `KMS_ADMIN_TOKEN`.
+fn post_admin(path: &str, body: &serde_json::Value) -> Result<String> {
+    let url = format!(
+        "{}{}",
+        std::env::var("KMS_URL")
+            .unwrap_or_else(|_| "http://127.0.0.1:3000".to_string())
+            .trim_end_matches('/'),
+        path
+    );
+    let token = std::env::var("KMS_ADMIN_TOKEN")
+        .ok()
+        .filter(|t| !t.is_empty())
+        .context("KMS_ADMIN_TOKEN is not set")?;
+    let api_key = std::env::var("KMS_API_KEY").ok().filter(|k| !k.is_empty());
+    post_json(&url, &token, api_key.as_deref(), body)
+}
+
+fn cmd_import_wallet(args: &[String]) -> Result<()> {
+    use std::io::BufRead;
+    let passkey = flag(args, "--passkey").context("--passkey is required")?;
+    let mut phrase = String::new();
+    std::io::stdin()
+        .lock()
+        .read_line(&mut phrase)
+        .context("read the recovery phrase from stdin")?;
+    let words = phrase.split_whitespace().count();
+    if words != 24 {
+        bail!(
+            "expected a 24-word recovery phrase on stdin, got {} words",
+            words
+        );
+    }
+    let body = serde_json::json!({
+        "Description": flag(args, "--description").unwrap_or("Imported wallet"),
+        "KeyUsage": "SIGN_VERIFY",
+        "KeySpec": "ECC_SECG_P256K1",
+        "Origin": "EXTERNAL",
+        "PasskeyPublicKey": passkey,
+        "Mnemonic": phrase.trim(),
+    });
+    let url = format!(
+        "{}/CreateKey",
+        std::env::var("KMS_URL")
+            .unwrap_or_else(|_| "http://127.0.0.1:3000".to_string())
+            .trim_end_matches('/')
+    );
+    let mut headers = vec!["x-amz-target: TrentService.CreateKey".to_string()];
+    if let Some(k) = std::env::var("KMS_API_KEY").ok().filter(|k| !k.is_empty()) {
+        headers.push(format!("x-api-key: {}", k));
+    }
+    let reply: serde_json::Value = serde_json::from_str(&curl_post(&url, &headers, &body)?)
+        .context("kms-api answered with something other than a CreateKey result")?;
+    println!(
+        "Imported wallet {}: m/44'/60'/0'/0/0 = {}",
+        reply["KeyMetadata"]["KeyId"].as_str().unwrap_or("?"),
+        reply["KeyMetadata"]["Address"].as_str().unwrap_or("?")
+    );
+    println!("Check this address against the wallet the phrase came from before funding it.");
+    Ok(())
+}
+
+fn cmd_hsm_wallet(args: &[String]) -> Result<()> {
+    let label = flag(args, "--label").context("--label is required")?;
+    let passkey = flag(args, "--passkey").context("--passkey is required")?;
+    let path = flag(args, "--path").unwrap_or(kms::replication::CHECK_PATH);
+    let passkey_bytes =
+        hex::decode(passkey.trim_start_matches("0x")).context("--passkey: expected hex")?;
+    p256::PublicKey::from_sec1_bytes(&passkey_bytes)
+        .map_err(|_| anyhow::anyhow!("--passkey is not a P-256 public key"))?;
+    let config = Pkcs11Config::from_env().context("KMS_PKCS11_MODULE is not set")??;
+    let public_key = Pkcs11Token::open(&config)?.public_key(label)?;
+    let address = format!("0x{}", hex::encode(pkcs11::address(&public_key)));
+
+    let db = KmsDb::open(&db_path())?;
+    let key_id = uuid::Uuid::new_v4().to_string();
+    let now = chrono::Utc::now();
+    db.register_signer_wallet(
+        &WalletRow {
+            key_id: key_id.clone(),
+            address: Some(address.clone()),
+            public_key: Some(format!("0x{}", hex::encode(public_key))),
+            derivation_path: Some(path.to_string()),
+            description: flag(args, "--description")
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("HSM key {}", label)),
+            key_usage: "SIGN_VERIFY".to_string(),
+            key_spec: "ECC_SECG_P256K1".to_string(),
+            origin: "EXTERNAL_KEY_STORE".to_string(),
+            passkey_pubkey: Some(format!("0x{}", hex::encode(&passkey_bytes))),
+            credential_id: None,
+            sign_count: 0,
+            status: "ready".to_string(),
+            error_msg: None,
+            created_at: now.to_rfc3339(),
+        },
+        &WalletSignerRow {
+            key_id: key_id.clone(),
+            backend: signer::PKCS11.to_string(),
+            key_label: label.to_string(),
+            derivation_path: path.to_string(),
+            created_at: now.timestamp(),
+        },
+    )?;
+    println!("HSM wallet {}: {} ({} on {})", key_id, address, label, path);
+    Ok(())
+}
+
+fn cmd_hsm_wallets() -> Result<()> {
+    let db = KmsDb::open(&db_path())?;
+    let rows = db.list_wallet_signers()?;
+    if rows.is_empty() {
+        println!("No HSM wallets.");
+        return Ok(());
+    }
+    println!(
+        "{:<38} {:<8} {:<24} {:<20} ADDRESS",
+        "KEY_ID", "BACKEND", "KEY_LABEL", "PATH"
+    );
+    for r in &rows {
+        let address = db
+            .get_wallet(&r.key_id)?
+            .and_then(|w| w.address)
+            .unwrap_or_default();
+        println!(
+            "{:<38} {:<8} {:<24} {:<20} {}",
+            r.key_id, r.backend, r.key_label, r.derivation_path, address
+        );
+    }
+    Ok(())
+}
+
+fn cmd_standby_status() -> Result<()> {
+    let db = KmsDb::open(&db_path())?;
+    let time = |t: Option<i64>| {
+        t.and_then(|t| chrono::DateTime::from_timestamp(t, 0))
+            .map(|t| t.to_rfc3339())
+            .unwrap_or_else(|| "-".to_string())
+    };
+    match db.standby_state()? {
+        Some(s) => {
+            println!("Role:          {}", s.role.as_str());
+            println!("Fencing token: {}", s.fencing_token);
+            println!("Last sync:     {}", time(s.synced_at));
+            println!("Promoted:      {}", time(s.promoted_at));
+        }
+        None => println!("Not part of an active-standby pair (KMS_STANDBY_ROLE unset)."),
+    }
+    let (seq, hash) = db.event_head()?;
+    println!("Event log:     {} event(s), head {}", seq, hash);
+    Ok(())
+}
+
+fn cmd_standby_promote(args: &[String]) -> Result<()> {
+    let force = args.iter().any(|a| a == "--force");
+    let reply: serde_json::Value = serde_json::from_str(&post_admin(
+        "/admin/standby/promote",
+        &serde_json::json!({ "force": force }),
+    )?)
+    .context("kms-api answered with something other than a promotion result")?;
+    println!(
+        "Promoted: this node is active with fencing token {} ({} wallet(s) checked in the TA).",
+        reply["state"]["fencingToken"], reply["walletsChecked"]
+    );
+    if let Some(missing) = reply["missingWallets"].as_array().filter(|m| !m.is_empty()) {
+        println!("{} wallet(s) missing from this TA:", missing.len());
+        for m in missing {
+            println!("  {}  {}", m["keyId"].as_str().unwrap_or("?"), m["error"]);
+        }
+    }
+    if let Some(e) = reply["peer"]["error"].as_str() {
+        println!(
+            "Old active not reachable ({}); it fences itself when it sees the new token.",
+            e
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,896 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! airaccount-provision — one-command first-boot setup for a fleet device.
+//!
+//! Runs, in order, and records each step in a JSON report:
+//!
+//! 1. `ta`: install (`--ta-file`) and/or verify the signed TA in the TA dir.
+//!    With `--ta-manifest`, the image must match its signed release manifest
+//!    first; a refused image is not installed, and no session is opened to it.
+//! 2. `selftest`: open a session, TaStats, rollback counter read, attestation
+//!    (optional: older TAs / boards without the PTA).
+//! 3. `policy`: install the signed deployment policy from `--policy-file`
+//!    (skipped without one; already-installed sequences are left alone), then
+//!    record the TA's capabilities.
+//! 4. `limits`: install the TA config (limits, hardening flags) from
+//!    `--ta-config-file`, signed by the provisioning key built into the TA
+//!    (skipped without one; already-installed sequences are left alone).
+//! 5. `tamper`: apply the signed tamper order from `--tamper-order-file` —
+//!    the failed-auth wipe limit, or the unlock a wiped device needs before
+//!    it can be provisioned again — then record the wipe state.
+//! 6. `identity`: device id, generated once and kept in `<config-dir>/device.json`,
+//!    plus the attestation evidence bound to it.
+//! 7. `register`: POST the identity to `--fleet-url` (skipped without one).
+//! 8. `config`: upsert `<config-dir>/kms.env` with KMS_DEVICE_ID, KMS_API_KEY and
+//!    KMS_BLS_SIGNER_TOKEN, each generated only when absent, and — after a
+//!    verified release — KMS_TA_MANIFEST / KMS_TA_RELEASE_SIGNERS so kms-api
+//!    re-checks the TA at every start.
+//!
+//! Re-running is safe: existing identity and secrets are reused, never rotated.
+//! It does not provision the BLS / keeper keys — that stays with
+//! node-setup/aastar-kms-selfinit.sh, which needs kms-api running with the
+//! provisioning gate open. Exit code is non-zero if any step failed.
+
+use anyhow::{anyhow, bail, Context, Result};
+use kms::key_pin::address_hex;
+use kms::ta_client::{TaClient, TA_UUID};
+use kms::ta_config::TaConfigRequest;
+use kms::ta_release::{parse_signers, ReleaseManifest, DEFAULT_TA_DIR};
+use kms::tamper::TamperOrderRequest;
+use proto::deployment_policy::{DeploymentPolicy, POLICY_FORMAT_VERSION};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "airaccount-provision",
+    about = "First-boot provisioning for an AirAccount KMS device"
+)]
+struct Opt {
+    /// Directory for device.json and kms.env.
+    #[structopt(long, default_value = "/etc/airaccount", parse(from_os_str))]
+    config_dir: PathBuf,
+    /// OP-TEE TA load directory.
+    #[structopt(long, default_value = "/lib/optee_armtz", parse(from_os_str))]
+    ta_dir: PathBuf,
+    /// Signed TA to install before verifying (copied to <ta-dir>/<uuid>.ta).
+    #[structopt(long, parse(from_os_str))]
+    ta_file: Option<PathBuf>,
+    /// Signed TA release manifest (JSON: taUuid, taVersion, sha256,
+    /// minCaVersion, signature). The TA is refused unless it matches; without
+    /// `signature` the step fails and prints the text to sign.
+    #[structopt(long, parse(from_os_str))]
+    ta_manifest: Option<PathBuf>,
+    /// Release-key addresses trusted to sign TA manifests (comma-separated).
+    /// Set without --ta-manifest, the TA counts as unsigned and is refused.
+    #[structopt(long, env = "AIRACCOUNT_TA_RELEASE_SIGNERS")]
+    ta_release_signers: Option<String>,
+    /// Signed deployment policy (JSON: deployment, sequence, disabledCommands,
+    /// signature). Without `signature` the step fails and prints the text to sign.
+    #[structopt(long, parse(from_os_str))]
+    policy_file: Option<PathBuf>,
+    /// Signed TA config (JSON: deployment, sequence, limits, requireTaChallenge,
+    /// requireSigningContext, signature). Limits left out keep their defaults;
+    /// without `signature` the step fails and prints the text to sign.
+    #[structopt(long, parse(from_os_str))]
+    ta_config_file: Option<PathBuf>,
+    /// Signed tamper order (JSON: deployment, sequence, device, action,
+    /// signature), e.g. `"action": "failed-auth-limit 5"` or `"unlock"`.
+    #[structopt(long, parse(from_os_str))]
+    tamper_order_file: Option<PathBuf>,
+    /// Fleet registration endpoint (POST, JSON). Registration is skipped without it.
+    #[structopt(long)]
+    fleet_url: Option<String>,
+    /// Bearer token for the fleet endpoint.
+    #[structopt(long, env = "AIRACCOUNT_FLEET_TOKEN", hide_env_values = true)]
+    fleet_token: Option<String>,
+    /// Free-form label reported to the fleet (site, rack, owner …).
+    #[structopt(long, default_value = "")]
+    label: String,
+    /// Write the report here as well as to stdout.
+    #[structopt(long, parse(from_os_str))]
+    report: Option<PathBuf>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum Status {
+    Ok,
+    Skipped,
+    Warn,
+    Failed,
+}
+
+#[derive(Serialize, Debug)]
+struct Step {
+    name: &'static str,
+    status: Status,
+    detail: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct DeviceIdentity {
+    device_id: uuid::Uuid,
+    created_at: String,
+    ta_uuid: String,
+}
+
+#[derive(Serialize, Debug)]
+struct Attestation {
+    nonce: String,
+    ta_measurement: String,
+    signature: String,
+    attest_pubkey_exp: String,
+    attest_pubkey_mod: String,
+    sig_alg: u32,
+}
+
+/// A command in the policy file, by id or by name (`"ExportPrivateKey"`).
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum CommandRef {
+    Id(u32),
+    Name(String),
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct PolicyFile {
+    deployment: String,
+    sequence: u64,
+    #[serde(default)]
+    disabled_commands: Vec<CommandRef>,
+    /// 0x-hex personal_sign signature over the policy text.
+    #[serde(default)]
+    signature: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+struct Capabilities {
+    ta_version: String,
+    features: Vec<String>,
+    deployment: Option<String>,
+    policy_sequence: Option<u64>,
+    disabled_commands: Vec<String>,
+    ta_config_sequence: Option<u64>,
+}
+
+#[derive(Serialize, Debug)]
+struct Report {
+    tool_version: &'static str,
+    finished_at: String,
+    device: Option<DeviceIdentity>,
+    ta_sha256: Option<String>,
+    /// Version from the verified release manifest.
+    ta_release: Option<String>,
+    rollback_counter: Option<u64>,
+    attestation: Option<Attestation>,
+    capabilities: Option<Capabilities>,
+    steps: Vec<Step>,
+}
+
+impl Report {
+    fn record(&mut self, name: &'static str, status: Status, detail: impl Into<String>) {
+        let detail = detail.into();
+        eprintln!("[provision] {:<8} {:?}: {}", name, status, detail);
+        self.steps.push(Step {
+            name,
+            status,
+            detail,
+        });
+    }
+
+    fn failed(&self) -> bool {
+        self.steps.iter().any(|s| s.status == Status::Failed)
+    }
+}
+
+fn sha256_file(path: &Path) -> Result<String> {
+    let bytes = std::fs::read(path).with_context(|| format!("read {}", path.display()))?;
+    Ok(hex::encode(Sha256::digest(&bytes)))
+}
+
+fn random_hex(n: usize) -> String {
+    let mut buf = vec![0u8; n];
+    rand::rngs::OsRng.fill_bytes(&mut buf);
+    hex::encode(buf)
+}
+
+/// Write via a same-directory temp file + rename so a power cut never leaves a
+/// half-written config behind.
+fn write_atomic(path: &Path, contents: &[u8], mode: u32) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    let dir = path
+        .parent()
+        .ok_or_else(|| anyhow!("{} has no parent directory", path.display()))?;
+    let tmp = dir.join(format!(
+        ".{}.tmp",
+        path.file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("provision")
+    ));
+    {
+        let mut f = std::fs::File::create(&tmp)?;
+        f.set_permissions(std::fs::Permissions::from_mode(mode))?;
+        f.write_all(contents)?;
+        f.sync_all()?;
+    }
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+fn valid_env_key(key: &str) -> bool {
+    let mut chars = key.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_uppercase() || c == '_')
+        && chars.all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+}
+
+fn env_get<'a>(contents: &'a str, key: &str) -> Option<&'a str> {
+    contents
+        .lines()
+        .find_map(|l| l.strip_prefix(key).and_then(|r| r.strip_prefix('=')))
+}
+
+/// Set `key` only if it is absent. Same injection guard as the selfinit
+/// script: keys are `[A-Z_][A-Z0-9_]*` and values are a single line.
+fn env_set_if_absent(contents: &mut String, key: &str, value: &str) -> Result<bool> {
+    if !valid_env_key(key) {
+        bail!("invalid env key: {}", key);
+    }
+    if value.contains('\n') || value.contains('\r') {
+        bail!("refusing multi-line value for {}", key);
+    }
+    if env_get(contents, key).is_some() {
+        return Ok(false);
+    }
+    if !contents.is_empty() && !contents.ends_with('\n') {
+        contents.push('\n');
+    }
+    contents.push_str(&format!("{}={}\n", key, value));
+    Ok(true)
+}
+
+fn step_ta(opt: &Opt, report: &mut Report) -> Result<()> {
+    let target = opt.ta_dir.join(format!("{}.ta", TA_UUID.trim()));
+    let release = match (&opt.ta_manifest, &opt.ta_release_signers) {
+        (None, None) => None,
+        (Some(manifest), Some(signers)) => Some((
+            ReleaseManifest::from_file(manifest)?,
+            parse_signers(signers)?,
+        )),
+        (Some(_), None) => bail!("--ta-manifest needs --ta-release-signers"),
+        (None, Some(_)) => bail!("release signers are set but there is no --ta-manifest"),
+    };
+    // Checked before anything is written, so a refused image never lands in
+    // the TA dir.
+    let check = |image: &[u8]| match &release {
+        Some((manifest, signers)) => manifest
+            .verify(image, TA_UUID.trim(), env!("CARGO_PKG_VERSION"), signers)
+            .map(|r| {
+                format!(
+                    ", release {} signed by {}",
+                    r.ta_version,
+                    address_hex(&r.signer)
+                )
+            }),
+        None => Ok(String::new()),
+    };
+    if let Some(src) = &opt.ta_file {
+        let bytes = std::fs::read(src).with_context(|| format!("read {}", src.display()))?;
+        let verified = check(&bytes)?;
+        let want = hex::encode(Sha256::digest(&bytes));
+        let have = sha256_file(&target).ok();
+        if have.as_deref() == Some(want.as_str()) {
+            report.record(
+                "ta",
+                Status::Ok,
+                format!("TA already installed (same sha256){}", verified),
+            );
+        } else {
+            write_atomic(&target, &bytes, 0o444)
+                .with_context(|| format!("install {}", target.display()))?;
+            report.record(
+                "ta",
+                Status::Ok,
+                format!("installed {}{}", target.display(), verified),
+            );
+        }
+    } else if target.exists() {
+        let bytes = std::fs::read(&target)?;
+        let verified = check(&bytes)?;
+        report.record(
+            "ta",
+            Status::Ok,
+            format!("found {}{}", target.display(), verified),
+        );
+    } else {
+        bail!(
+            "{} not found (pass --ta-file to install it)",
+            target.display()
+        );
+    }
+    report.ta_sha256 = Some(sha256_file(&target)?);
+    if let Some((manifest, _)) = &release {
+        let path = opt.config_dir.join("ta-release.json");
+        write_atomic(
+            &path,
+            serde_json::to_string_pretty(manifest)?.as_bytes(),
+            0o644,
+        )?;
+        report.ta_release = Some(manifest.ta_version.clone());
+    }
+    Ok(())
+}
+
+fn step_selftest(client: &mut TaClient, report: &mut Report) -> Result<()> {
+    client.ta_stats().context("TA session / TaStats")?;
+    let counter = client
+        .read_rollback_counter()
+        .context("rollback counter read")?;
+    report.rollback_counter = Some(counter);
+    report.record(
+        "selftest",
+        Status::Ok,
+        format!("TA responds; rollback counter {}", counter),
+    );
+    Ok(())
+}
+
+fn command_id(c: &CommandRef) -> Result<u32> {
+    let id = match c {
+        CommandRef::Id(id) => *id,
+        CommandRef::Name(name) => (0u32..64)
+            .find(|&id| format!("{:?}", proto::Command::from(id)) == *name)
+            .ok_or_else(|| anyhow!("unknown command name {:?}", name))?,
+    };
+    if proto::Command::from(id) == proto::Command::Unknown {
+        bail!("unknown command id {}", id);
+    }
+    Ok(id)
+}
+
+fn policy_from_file(file: &PolicyFile) -> Result<DeploymentPolicy> {
+    let mut disabled = file
+        .disabled_commands
+        .iter()
+        .map(command_id)
+        .collect::<Result<Vec<_>>>()?;
+    disabled.sort_unstable();
+    disabled.dedup();
+    let policy = DeploymentPolicy {
+        format: POLICY_FORMAT_VERSION,
+        deployment: file.deployment.clone(),
+        sequence: file.sequence,
+        disabled_commands: disabled,
+    };
+    policy.validate().map_err(|e| anyhow!("{}", e))?;
+    Ok(policy)
+}
+
+fn step_policy(opt: &Opt, client: &mut TaClient, report: &mut Report) -> Result<()> {
+    let installed = client.get_capabilities().context("GetCapabilities")?.policy;
+    if let Some(path) = &opt.policy_file {
+        let raw = std::fs::read(path).with_context(|| format!("read {}", path.display()))?;
+        let file: PolicyFile = serde_json::from_slice(&raw)
+            .with_context(|| format!("{} is not a deployment policy", path.display()))?;
+        let policy = policy_from_file(&file)?;
+        if installed
+            .as_ref()
+            .is_some_and(|p| p.sequence >= policy.sequence)
+        {
+            report.record(
+                "policy",
+                Status::Skipped,
+                format!("sequence {} already installed", policy.sequence),
+            );
+        } else {
+            let signature = match &file.signature {
+                Some(sig) => hex::decode(sig.trim_start_matches("0x"))
+                    .context("policy signature is not hex")?,
+                None => bail!(
+                    "{} is unsigned; personal_sign exactly this text:\n{}",
+                    path.display(),
+                    policy.message()
+                ),
+            };
+            let out = client
+                .install_deployment_policy(policy.clone(), signature)
+                .context("InstallDeploymentPolicy")?;
+            report.record(
+                "policy",
+                Status::Ok,
+                format!(
+                    "\"{}\" sequence {} installed (signer 0x{}, previous {:?})",
+                    policy.deployment,
+                    policy.sequence,
+                    hex::encode(out.signer),
+                    out.previous_sequence
+                ),
+            );
+        }
+    } else {
+        report.record("policy", Status::Skipped, "no --policy-file");
+    }
+    let caps = client.get_capabilities().context("GetCapabilities")?;
+    report.capabilities = Some(Capabilities {
+        ta_version: caps.ta_version,
+        features: caps.features,
+        deployment: caps.policy.as_ref().map(|p| p.deployment.clone()),
+        policy_sequence: caps.policy.as_ref().map(|p| p.sequence),
+        disabled_commands: caps
+            .policy
+            .map(|p| {
+                p.disabled_commands
+                    .iter()
+                    .map(|&id| format!("{:?}", proto::Command::from(id)))
+                    .collect()
+            })
+            .unwrap_or_default(),
+        ta_config_sequence: caps.config.map(|c| c.sequence),
+    });
+    Ok(())
+}
+
+fn step_ta_config(opt: &Opt, client: &mut TaClient, report: &mut Report) -> Result<()> {
+    let path = match &opt.ta_config_file {
+        Some(path) => path,
+        None => {
+            report.record("limits", Status::Skipped, "no --ta-config-file");
+            return Ok(());
+        }
+    };
+    let raw = std::fs::read(path).with_context(|| format!("read {}", path.display()))?;
+    let request: TaConfigRequest = serde_json::from_slice(&raw)
+        .with_context(|| format!("{} is not a TA config", path.display()))?;
+    let config = request.config()?;
+    let caps = client.get_capabilities().context("GetCapabilities")?;
+    if caps
+        .config
+        .as_ref()
+        .is_some_and(|c| c.sequence >= config.sequence)
+    {
+        report.record(
+            "limits",
+            Status::Skipped,
+            format!("sequence {} already installed", config.sequence),
+        );
+        return Ok(());
+    }
+    let key = caps.provisioning_key.ok_or_else(|| {
+        anyhow!(
+            "this TA image has no provisioning key; rebuild it with AIRACCOUNT_PROVISIONING_KEY"
+        )
+    })?;
+    let signature = request.signature(&config).with_context(|| {
+        format!(
+            "{} (provisioning key 0x{})",
+            path.display(),
+            hex::encode(key)
+        )
+    })?;
+    let out = client
+        .install_config(config.clone(), signature)
+        .context("InstallConfig")?;
+    report.record(
+        "limits",
+        Status::Ok,
+        format!(
+            "\"{}\" sequence {} installed (previous {:?})",
+            config.deployment, config.sequence, out.previous_sequence
+        ),
+    );
+    Ok(())
+}
+
+fn step_tamper(opt: &Opt, client: &mut TaClient, report: &mut Report) -> Result<()> {
+    if let Some(path) = &opt.tamper_order_file {
+        let raw = std::fs::read(path).with_context(|| format!("read {}", path.display()))?;
+        let request: TamperOrderRequest = serde_json::from_slice(&raw)
+            .with_context(|| format!("{} is not a tamper order", path.display()))?;
+        let order = request.order()?;
+        let signature = request
+            .signature(&order)
+            .with_context(|| path.display().to_string())?;
+        client
+            .tamper_order(order.clone(), signature)
+            .context("TamperOrder")?;
+        report.record(
+            "tamper",
+            Status::Ok,
+            format!(
+                "order {} applied: {}",
+                order.sequence,
+                request.action.trim()
+            ),
+        );
+    }
+    let status = match client.tamper_status() {
+        Ok(status) => status,
+        // TAs older than erase-on-tamper have no status to report.
+        Err(e) => {
+            report.record("tamper", Status::Warn, format!("no tamper status: {:#}", e));
+            return Ok(());
+        }
+    };
+    let limit = match status.failed_auth_limit {
+        Some(n) => n.to_string(),
+        None => "off".to_string(),
+    };
+    match status.locked {
+        Some(lock) => report.record(
+            "tamper",
+            Status::Failed,
+            format!(
+                "device was wiped ({:?}) and is locked; apply an unlock order",
+                lock.reason
+            ),
+        ),
+        None => report.record(
+            "tamper",
+            Status::Ok,
+            format!(
+                "failed-auth limit {}, {} consecutive failures",
+                limit, status.consecutive_failures
+            ),
+        ),
+    }
+    Ok(())
+}
+
+fn load_or_create_identity(opt: &Opt) -> Result<(DeviceIdentity, bool)> {
+    let path = opt.config_dir.join("device.json");
+    if let Ok(raw) = std::fs::read(&path) {
+        let id: DeviceIdentity = serde_json::from_slice(&raw)
+            .with_context(|| format!("{} is not a device identity", path.display()))?;
+        return Ok((id, false));
+    }
+    let id = DeviceIdentity {
+        device_id: uuid::Uuid::new_v4(),
+        created_at: chrono::Utc::now().to_rfc3339(),
+        ta_uuid: TA_UUID.trim().to_string(),
+    };
+    write_atomic(&path, &serde_json::to_vec_pretty(&id)?, 0o644)?;
+    Ok((id, true))
+}
+
+/// Attestation nonce bound to the device id, so the evidence a fleet receives
+/// cannot be lifted from another device's report.
+fn identity_nonce(device_id: &uuid::Uuid) -> Vec<u8> {
+    let mut h = Sha256::new();
+    h.update(b"airaccount-provision-v1");
+    h.update(device_id.as_bytes());
+    h.finalize().to_vec()
+}
+
+fn step_identity(opt: &Opt, client: Option<&mut TaClient>, report: &mut Report) -> Result<()> {
+    let (id, created) = load_or_create_identity(opt)?;
+    report.record(
+        "identity",
+        Status::Ok,
+        format!(
+            "device {} ({})",
+            id.device_id,
+            if created { "new" } else { "existing" }
+        ),
+    );
+    if let Some(client) = client {
+        let nonce = identity_nonce(&id.device_id);
+        match client.get_attestation(nonce.clone()) {
+            Ok(ev) if ev.nonce == nonce => {
+                report.attestation = Some(Attestation {
+                    nonce: hex::encode(&ev.nonce),
+                    ta_measurement: hex::encode(&ev.ta_measurement),
+                    signature: hex::encode(&ev.signature),
+                    attest_pubkey_exp: hex::encode(&ev.attest_pubkey_exp),
+                    attest_pubkey_mod: hex::encode(&ev.attest_pubkey_mod),
+                    sig_alg: ev.sig_alg,
+                })
+            }
+            Ok(_) => report.record(
+                "identity",
+                Status::Failed,
+                "attestation echoed a different nonce",
+            ),
+            Err(e) => report.record(
+                "identity",
+                Status::Warn,
+                format!("no attestation evidence: {:#}", e),
+            ),
+        }
+    }
+    report.device = Some(id);
+    Ok(())
+}
+
+/// POST `body` with curl. curl keeps this binary free of an HTTP client
+/// dependency; it is already required on every board by the selfinit script.
+/// The bearer token goes in through a config on stdin so it never shows up
+/// in the process list; the body goes through a 0600 temp file.
+fn post_json(
+    url: &str,
+    token: Option<&str>,
+    body: &serde_json::Value,
+    tmp_dir: &Path,
+) -> Result<String> {
+    use std::process::{Command, Stdio};
+    if let Some(t) = token {
+        if t.contains(|c: char| c == '"' || c == '\\' || c.is_control()) {
+            bail!("fleet token contains characters that cannot be passed to curl");
+        }
+    }
+    let body_path = tmp_dir.join(".provision-body.json");
+    write_atomic(&body_path, &serde_json::to_vec(body)?, 0o600)?;
+    let mut child = Command::new("curl")
+        .args(["-sS", "--fail", "-m", "20", "-K", "-"])
+        .args(["-H", "content-type: application/json"])
+        .arg("--data-binary")
+        .arg(format!("@{}", body_path.display()))
+        .arg(url)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("spawn curl")?;
+    if let Some(mut stdin) = child.stdin.take() {
+        if let Some(t) = token {
+            writeln!(stdin, "header = \"Authorization: Bearer {}\"", t)?;
+        }
+    }
+    let out = child.wait_with_output();
+    let _ = std::fs::remove_file(&body_path);
+    let out = out.context("wait for curl")?;
+    if !out.status.success() {
+        bail!(
+            "fleet registration failed ({}): {}",
+            out.status,
+            String::from_utf8_lossy(&out.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).into_owned())
+}
+
+fn step_register(opt: &Opt, report: &mut Report) -> Result<()> {
+    let url = match &opt.fleet_url {
+        Some(u) => u,
+        None => {
+            report.record("register", Status::Skipped, "no --fleet-url");
+            return Ok(());
+        }
+    };
+    if !url.starts_with("https://") {
+        bail!("fleet url must be https: {}", url);
+    }
+    if report.device.is_none() {
+        bail!("no device identity to register");
+    }
+    let body = serde_json::json!({
+        "device": report.device,
+        "label": opt.label,
+        "ta_sha256": report.ta_sha256,
+        "attestation": report.attestation,
+        "kms_version": env!("CARGO_PKG_VERSION"),
+    });
+    let resp = post_json(url, opt.fleet_token.as_deref(), &body, &opt.config_dir)?;
+    let mut summary: String = resp.trim().chars().take(200).collect();
+    if summary.is_empty() {
+        summary = "accepted".to_string();
+    }
+    report.record("register", Status::Ok, summary);
+    Ok(())
+}
+
+fn step_config(opt: &Opt, report: &mut Report) -> Result<()> {
+    let device_id = match &report.device {
+        Some(d) => d.device_id.to_string(),
+        None => bail!("no device identity; not writing kms.env"),
+    };
+    let path = opt.config_dir.join("kms.env");
+    let mut contents = std::fs::read_to_string(&path).unwrap_or_default();
+    let mut added = Vec::new();
+    if env_set_if_absent(&mut contents, "KMS_DEVICE_ID", &device_id)? {
+        added.push("KMS_DEVICE_ID");
+    }
+    // #145: the API is fail-closed without a key; same format as selfinit.
+    if env_get(&contents, "KMS_ALLOW_OPEN_MODE") != Some("1")
+        && env_set_if_absent(
+            &mut contents,
+            "KMS_API_KEY",
+            &format!("kms_{}", random_hex(24)),
+        )?
+    {
+        added.push("KMS_API_KEY");
+    }
+    if env_set_if_absent(&mut contents, "KMS_BLS_SIGNER_TOKEN", &random_hex(32))? {
+        added.push("KMS_BLS_SIGNER_TOKEN");
+    }
+    if let (Some(_), Some(signers)) = (&report.ta_release, &opt.ta_release_signers) {
+        let manifest = opt.config_dir.join("ta-release.json");
+        let mut release_env = vec![
+            ("KMS_TA_MANIFEST", manifest.display().to_string()),
+            ("KMS_TA_RELEASE_SIGNERS", signers.clone()),
+        ];
+        if opt.ta_dir != Path::new(DEFAULT_TA_DIR) {
+            release_env.push(("KMS_TA_DIR", opt.ta_dir.display().to_string()));
+        }
+        for (key, value) in release_env {
+            if env_set_if_absent(&mut contents, key, &value)? {
+                added.push(key);
+            }
+        }
+    }
+    if added.is_empty() {
+        report.record(
+            "config",
+            Status::Ok,
+            format!("{} unchanged", path.display()),
+        );
+        return Ok(());
+    }
+    write_atomic(&path, contents.as_bytes(), 0o600)?;
+    report.record(
+        "config",
+        Status::Ok,
+        format!("{}: added {}", path.display(), added.join(", ")),
+    );
+    Ok(())
+}
+
+fn run(opt: &Opt) -> Report {
+    let mut report = Report {
+        tool_version: env!("CARGO_PKG_VERSION"),
+        finished_at: String::new(),
+        device: None,
+        ta_sha256: None,
+        ta_release: None,
+        rollback_counter: None,
+        attestation: None,
+        capabilities: None,
+        steps: Vec::new(),
+    };
+    if let Err(e) = std::fs::create_dir_all(&opt.config_dir) {
+        report.record(
+            "setup",
+            Status::Failed,
+            format!("{}: {}", opt.config_dir.display(), e),
+        );
+        return report;
+    }
+
+    let ta_refused = match step_ta(opt, &mut report) {
+        Ok(()) => false,
+        Err(e) => {
+            report.record("ta", Status::Failed, format!("{:#}", e));
+            opt.ta_manifest.is_some() || opt.ta_release_signers.is_some()
+        }
+    };
+    // Without a working TA there is nothing to self-test or attest, but the
+    // identity and config are still written so a re-run only has to fix the TA.
+    let mut client = if ta_refused {
+        report.record(
+            "selftest",
+            Status::Skipped,
+            "TA failed its release check; not opening a session to it",
+        );
+        None
+    } else {
+        match TaClient::new() {
+            Ok(c) => Some(c),
+            Err(e) => {
+                report.record("selftest", Status::Failed, format!("{:#}", e));
+                None
+            }
+        }
+    };
+    if let Some(c) = client.as_mut() {
+        if let Err(e) = step_selftest(c, &mut report) {
+            report.record("selftest", Status::Failed, format!("{:#}", e));
+            client = None;
+        }
+    }
+    if let Some(c) = client.as_mut() {
+        if let Err(e) = step_policy(opt, c, &mut report) {
+            report.record("policy", Status::Failed, format!("{:#}", e));
+        }
+        if let Err(e) = step_ta_config(opt, c, &mut report) {
+            report.record("limits", Status::Failed, format!("{:#}", e));
+        }
+        if let Err(e) = step_tamper(opt, c, &mut report) {
+            report.record("tamper", Status::Failed, format!("{:#}", e));
+        }
+    }
+    if let Err(e) = step_identity(opt, client.as_mut(), &mut report) {
+        report.record("identity", Status::Failed, format!("{:#}", e));
+    }
+    if report.failed() && opt.fleet_url.is_some() {
+        // Never enrol a device that failed its own checks.
+        report.record("register", Status::Skipped, "earlier step failed");
+    } else if let Err(e) = step_register(opt, &mut report) {
+        report.record("register", Status::Failed, format!("{:#}", e));
+    }
+    if let Err(e) = step_config(opt, &mut report) {
+        report.record("config", Status::Failed, format!("{:#}", e));
+    }
+    report.finished_at = chrono::Utc::now().to_rfc3339();
+    report
+}
+
+fn main() -> Result<()> {
+    let opt = Opt::from_args();
+    let report = run(&opt);
+    let json = serde_json::to_string_pretty(&report)?;
+    println!("{}", json);
+    if let Some(path) = &opt.report {
+        write_atomic(path, json.as_bytes(), 0o600)
+            .with_context(|| format!("write report {}", path.display()))?;
+    }
+    if report.failed() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn env_upsert_keeps_existing_and_rejects_injection() {
+        let mut env = "KMS_API_KEY=kms_old\nFOO=1".to_string();
+        assert!(!env_set_if_absent(&mut env, "KMS_API_KEY", "kms_new").unwrap());
+        assert!(env_set_if_absent(&mut env, "KMS_DEVICE_ID", "abc").unwrap());
+        assert_eq!(env, "KMS_API_KEY=kms_old\nFOO=1\nKMS_DEVICE_ID=abc\n");
+        assert_eq!(env_get(&env, "KMS_API_KEY"), Some("kms_old"));
+        // a key that is a prefix of another must not match it
+        assert_eq!(env_get(&env, "KMS_API"), None);
+        assert!(env_set_if_absent(&mut env, "X", "a\nKMS_BLS_PROVISIONING=1").is_err());
+        assert!(env_set_if_absent(&mut env, "lower", "v").is_err());
+    }
+
+    #[test]
+    fn policy_file_accepts_names_and_ids() {
+        let file: PolicyFile = serde_json::from_str(
+            r#"{"deployment":"rack-3","sequence":2,"disabledCommands":["SignHash",7,5]}"#,
+        )
+        .unwrap();
+        let policy = policy_from_file(&file).unwrap();
+        assert_eq!(policy.disabled_commands, vec![5, 7]);
+        assert!(file.signature.is_none());
+        let bad: PolicyFile = serde_json::from_str(
+            r#"{"deployment":"rack-3","sequence":2,"disabledCommands":["NoSuchCommand"]}"#,
+        )
+        .unwrap();
+        assert!(policy_from_file(&bad).is_err());
+        let locked: PolicyFile = serde_json::from_str(
+            r#"{"deployment":"rack-3","sequence":2,"disabledCommands":["InstallDeploymentPolicy"]}"#,
+        )
+        .unwrap();
+        assert!(policy_from_file(&locked).is_err());
+    }
+
+    #[test]
+    fn identity_nonce_is_per_device() {
+        let a = identity_nonce(&uuid::Uuid::from_bytes([1; 16]));
+        let b = identity_nonce(&uuid::Uuid::from_bytes([2; 16]));
+        assert_eq!(a.len(), 32);
+        assert_ne!(a, b);
+    }
+}
@@ -0,0 +1,698 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Chain watcher — deposits and contract events for this node's wallets.
+//!
+//! Three `eth_subscribe` streams over one WebSocket JSON-RPC connection:
+//! ERC-20 `Transfer` logs whose recipient is watched, every log a watched
+//! address emits (account-contract events), and new heads, whose blocks are
+//! fetched and scanned for plain ETH sent to a watched address. The watch set
+//! is `address_index`, re-read every [`REFRESH_SECS`]; when it changes the
+//! connection is rebuilt with the new filters. While any stealth meta-address
+//! is registered a fourth stream stores EIP-5564 announcements for the
+//! stealth scanner (stealth.rs); they are not events until a scan matches one.
+//!
+//! Events go to `chain_events` (db.rs); new ones are POSTed to an optional
+//! webhook as a `proto::event` envelope (`chain.event`, version 1) carrying
+//! `x-airaccount-signature: sha256=<HMAC of the body>`, unless the wallet's
+//! notification preferences (notify.rs) hold them back. A log the node later
+//! reports as `removed` (reorg) is flagged, not deleted.
+//!
+//! The CA has no TLS stack, so `KMS_CHAIN_WS_URL` must be `ws://` — a local
+//! node or a TLS-terminating proxy — and the webhook `http://`.
+
+use anyhow::{anyhow, bail, Context, Result};
+use futures_util::{SinkExt, StreamExt};
+use hmac::{Hmac, Mac};
+use proto::event::{ChainEventPayload, EventEnvelope};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::db::{ChainEvent, ChainEventRow, KmsDb};
+use crate::notify::{Delivery, NotificationPrefs};
+use crate::standby;
+use crate::stealth::{self, Announcement};
+
+/// keccak256("Transfer(address,address,uint256)")
+pub const TRANSFER_TOPIC: &str =
+    "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef";
+
+/// How often the watch set is re-read from the DB.
+pub const REFRESH_SECS: u64 = 60;
+const MAX_BACKOFF_SECS: u64 = 60;
+const WEBHOOK_ATTEMPTS: u32 = 3;
+
+pub const KIND_ERC20_IN: &str = "erc20-transfer-in";
+pub const KIND_NATIVE_IN: &str = "native-transfer-in";
+pub const KIND_CONTRACT_EVENT: &str = "contract-event";
+/// Found by the stealth scanner, not the watch set (stealth.rs).
+pub const KIND_STEALTH_IN: &str = "stealth-payment-in";
+
+/// Lowercase address → key id.
+pub type WatchSet = HashMap<String, String>;
+
+#[derive(Debug, Clone)]
+pub struct WatchConfig {
+    pub ws_url: String,
+    pub webhook_url: Option<String>,
+    pub webhook_secret: Option<Vec<u8>>,
+}
+
+impl WatchConfig {
+    /// `KMS_CHAIN_WS_URL` (required to enable the watcher),
+    /// `KMS_EVENT_WEBHOOK_URL` and `KMS_EVENT_WEBHOOK_SECRET` (optional).
+    pub fn from_env() -> Option<Result<Self>> {
+        let ws_url = std::env::var("KMS_CHAIN_WS_URL")
+            .ok()
+            .filter(|v| !v.is_empty())?;
+        let webhook_url = std::env::var("KMS_EVENT_WEBHOOK_URL")
+            .ok()
+            .filter(|v| !v.is_empty());
+        let webhook_secret = std::env::var("KMS_EVENT_WEBHOOK_SECRET")
+            .ok()
+            .filter(|v| !v.is_empty())
+            .map(String::into_bytes);
+        Some(Self::new(ws_url, webhook_url, webhook_secret))
+    }
+
+    pub fn new(
+        ws_url: String,
+        webhook_url: Option<String>,
+        webhook_secret: Option<Vec<u8>>,
+    ) -> Result<Self> {
+        if !ws_url.starts_with("ws://") {
+            bail!("KMS_CHAIN_WS_URL must be ws:// (no TLS in the CA; use a local proxy)");
+        }
+        if let Some(url) = &webhook_url {
+            if !url.starts_with("http://") {
+                bail!("KMS_EVENT_WEBHOOK_URL must be http:// (no TLS in the CA)");
+            }
+            if webhook_secret.is_none() {
+                bail!("KMS_EVENT_WEBHOOK_URL needs KMS_EVENT_WEBHOOK_SECRET");
+            }
+        }
+        Ok(Self {
+            ws_url,
+            webhook_url,
+            webhook_secret,
+        })
+    }
+}
+
+/// A 20-byte address as a 32-byte log topic.
+fn address_topic(address: &str) -> String {
+    format!("0x{:0>64}", address.trim_start_matches("0x"))
+}
+
+fn topic_address(topic: &str) -> Option<String> {
+    let t = topic.trim_start_matches("0x");
+    if t.len() != 64 || !t[..24].bytes().all(|b| b == b'0') {
+        return None;
+    }
+    Some(format!("0x{}", t[24..].to_lowercase()))
+}
+
+fn quantity(v: &Value) -> Option<u64> {
+    u64::from_str_radix(v.as_str()?.trim_start_matches("0x"), 16).ok()
+}
+
+/// Big-endian hex (up to 256 bits) as a decimal string.
+pub(crate) fn decimal(hex_value: &str) -> Option<String> {
+    let h = hex_value.trim_start_matches("0x");
+    if h.is_empty() || h.len() > 64 {
+        return None;
+    }
+    let bytes = hex::decode(format!("{:0>64}", h)).ok()?;
+    let mut digits = Vec::new();
+    let mut n = bytes;
+    while n.iter().any(|b| *b != 0) {
+        let mut rem = 0u32;
+        for b in n.iter_mut() {
+            let cur = (rem << 8) | u32::from(*b);
+            *b = (cur / 10) as u8;
+            rem = cur % 10;
+        }
+        digits.push(b'0' + rem as u8);
+    }
+    if digits.is_empty() {
+        return Some("0".into());
+    }
+    digits.reverse();
+    String::from_utf8(digits).ok()
+}
+
+/// `eth_subscribe` params for the two log streams and the head stream.
+pub fn subscriptions(watch: &WatchSet) -> Vec<Value> {
+    let mut addresses: Vec<&String> = watch.keys().collect();
+    addresses.sort();
+    let topics: Vec<String> = addresses.iter().map(|a| address_topic(a)).collect();
+    vec![
+        json!(["logs", { "topics": [TRANSFER_TOPIC, null, topics] }]),
+        json!(["logs", { "address": addresses }]),
+        json!(["newHeads"]),
+    ]
+}
+
+/// Events a log notification produces for the watch set. `removed` is the
+/// node's reorg flag.
+pub fn events_from_log(chain_id: u64, log: &Value, watch: &WatchSet) -> Vec<(ChainEvent, bool)> {
+    let mut out = Vec::new();
+    let topics: Vec<&str> = log["topics"]
+        .as_array()
+        .map(|t| t.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default();
+    let (tx_hash, block, index) = match (
+        log["transactionHash"].as_str(),
+        quantity(&log["blockNumber"]),
+        quantity(&log["logIndex"]),
+    ) {
+        (Some(h), Some(b), Some(i)) => (h.to_lowercase(), b, i as i64),
+        _ => return out,
+    };
+    let emitter = log["address"].as_str().unwrap_or_default().to_lowercase();
+    let removed = log["removed"].as_bool().unwrap_or(false);
+    let base = |key_id: &str, address: &str, kind: &str| ChainEvent {
+        key_id: key_id.to_string(),
+        address: address.to_string(),
+        chain_id,
+        kind: kind.to_string(),
+        token: None,
+        from_address: None,
+        to_address: None,
+        value: None,
+        topic0: topics.first().map(|t| t.to_lowercase()),
+        tx_hash: tx_hash.clone(),
+        block_number: block,
+        log_index: index,
+    };
+
+    // ERC-20 Transfer (three topics; ERC-721 has four and is not a deposit).
+    if topics.len() == 3 && topics[0].eq_ignore_ascii_case(TRANSFER_TOPIC) {
+        if let (Some(from), Some(to)) = (topic_address(topics[1]), topic_address(topics[2])) {
+            if let Some(key_id) = watch.get(&to) {
+                let mut e = base(key_id, &to, KIND_ERC20_IN);
+                e.token = Some(emitter.clone());
+                e.from_address = Some(from);
+                e.to_address = Some(to.clone());
+                e.value = log["data"].as_str().and_then(decimal);
+                out.push((e, removed));
+            }
+        }
+    }
+    if let Some(key_id) = watch.get(&emitter) {
+        out.push((base(key_id, &emitter, KIND_CONTRACT_EVENT), removed));
+    }
+    out
+}
+
+/// Plain ETH sent to a watched address in a full block (`eth_getBlockByNumber`
+/// with transaction objects). `log_index` is -1 - the transaction's index.
+pub fn events_from_block(chain_id: u64, block: &Value, watch: &WatchSet) -> Vec<ChainEvent> {
+    let number = match quantity(&block["number"]) {
+        Some(n) => n,
+        None => return Vec::new(),
+    };
+    let txs = match block["transactions"].as_array() {
+        Some(t) => t,
+        None => return Vec::new(),
+    };
+    let mut out = Vec::new();
+    for tx in txs {
+        let to = match tx["to"].as_str() {
+            Some(to) => to.to_lowercase(),
+            None => continue,
+        };
+        let key_id = match watch.get(&to) {
+            Some(k) => k,
+            None => continue,
+        };
+        let value = match tx["value"].as_str().and_then(decimal) {
+            Some(v) if v != "0" => v,
+            _ => continue,
+        };
+        let (hash, index) = match (tx["hash"].as_str(), quantity(&tx["transactionIndex"])) {
+            (Some(h), Some(i)) => (h.to_lowercase(), i as i64),
+            _ => continue,
+        };
+        out.push(ChainEvent {
+            key_id: key_id.clone(),
+            address: to.clone(),
+            chain_id,
+            kind: KIND_NATIVE_IN.to_string(),
+            token: None,
+            from_address: tx["from"].as_str().map(str::to_lowercase),
+            to_address: Some(to),
+            value: Some(value),
+            topic0: None,
+            tx_hash: hash,
+            block_number: number,
+            log_index: -1 - index,
+        });
+    }
+    out
+}
+
+/// The `chain.event` payload: the webhook body's `payload`, and each item of
+/// `/kms/wallet/{keyId}/events` (which adds `observedAt`).
+pub fn event_payload(row: &ChainEventRow) -> ChainEventPayload {
+    let e = &row.event;
+    ChainEventPayload {
+        id: row.id,
+        key_id: e.key_id.clone(),
+        address: e.address.clone(),
+        chain_id: e.chain_id,
+        kind: e.kind.clone(),
+        token: e.token.clone(),
+        from: e.from_address.clone(),
+        to: e.to_address.clone(),
+        value: e.value.clone(),
+        topic0: e.topic0.clone(),
+        tx_hash: e.tx_hash.clone(),
+        block_number: e.block_number,
+        log_index: if e.log_index >= 0 {
+            Some(e.log_index)
+        } else {
+            None
+        },
+        removed: row.removed,
+        email: false,
+    }
+}
+
+/// The event as served by `/kms/wallet/{keyId}/events`.
+pub fn event_json(row: &ChainEventRow) -> Value {
+    let mut v = serde_json::to_value(event_payload(row)).unwrap_or(Value::Null);
+    v["observedAt"] = json!(row.created_at);
+    v
+}
+
+/// The webhook body. No request caused a chain event, so the correlation id
+/// is derived from the event row and stays the same across retries.
+pub fn event_envelope(row: &ChainEventRow, email: bool) -> EventEnvelope<ChainEventPayload> {
+    EventEnvelope::new(
+        ChainEventPayload {
+            email,
+            ..event_payload(row)
+        },
+        row.created_at,
+        format!("chain-event-{}", row.id),
+    )
+}
+
+/// `sha256=<hex HMAC-SHA256(secret, body)>`.
+pub fn webhook_signature(secret: &[u8], body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC takes any key length");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Run forever: connect, subscribe, record, reconnect on error or when the
+/// watch set changes.
+pub async fn run(config: WatchConfig, db: KmsDb) {
+    let mut backoff = 1;
+    loop {
+        // A standby records what the active's watcher saw.
+        if !standby::accepts_writes() {
+            tokio::time::sleep(Duration::from_secs(REFRESH_SECS)).await;
+            continue;
+        }
+        let started = Instant::now();
+        match watch_once(&config, &db).await {
+            Ok(()) => println!("🔭 Chain watcher: watch set changed, resubscribing"),
+            Err(e) => eprintln!("⚠️  Chain watcher: {:#}", e),
+        }
+        if started.elapsed() > Duration::from_secs(MAX_BACKOFF_SECS) {
+            backoff = 1;
+        }
+        tokio::time::sleep(Duration::from_secs(backoff)).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF_SECS);
+    }
+}
+
+/// Whether any wallet wants stealth announcements.
+async fn stealth_wanted(db: &KmsDb) -> Result<bool> {
+    Ok(!db.run(|db| db.list_stealth_meta()).await?.is_empty())
+}
+
+/// One connection. Returns Ok when the watch set changed.
+async fn watch_once(config: &WatchConfig, db: &KmsDb) -> Result<()> {
+    let watch = db.run(|db| db.watched_addresses()).await?;
+    let stealth = stealth_wanted(db).await?;
+    let (mut ws, _) = tokio_tungstenite::connect_async(config.ws_url.as_str())
+        .await
+        .context("connect")?;
+
+    let mut next_id = 1u64;
+    let mut send = |method: &str, params: Value| {
+        let id = next_id;
+        next_id += 1;
+        (
+            id,
+            json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }).to_string(),
+        )
+    };
+    let (chain_id_req, msg) = send("eth_chainId", json!([]));
+    ws.send(Message::Text(msg)).await?;
+    let mut chain_id = None;
+    let mut head_sub = None;
+    let mut sub_reqs = HashMap::new();
+    let mut block_reqs = HashMap::new();
+    if !watch.is_empty() {
+        for (i, params) in subscriptions(&watch).into_iter().enumerate() {
+            let (id, msg) = send("eth_subscribe", params);
+            sub_reqs.insert(id, i);
+            ws.send(Message::Text(msg)).await?;
+        }
+    }
+    if stealth {
+        let (id, msg) = send("eth_subscribe", stealth::announcement_subscription());
+        sub_reqs.insert(id, 3);
+        ws.send(Message::Text(msg)).await?;
+    }
+    println!(
+        "🔭 Chain watcher: {} watching {} address(es)",
+        config.ws_url,
+        watch.len()
+    );
+
+    let mut refresh = tokio::time::interval(Duration::from_secs(REFRESH_SECS));
+    refresh.tick().await;
+    loop {
+        tokio::select! {
+            _ = refresh.tick() => {
+                if !standby::accepts_writes() {
+                    bail!("this node is no longer the active CA");
+                }
+                if db.run(|db| db.watched_addresses()).await? != watch
+                    || stealth_wanted(db).await? != stealth
+                {
+                    return Ok(());
+                }
+            }
+            frame = ws.next() => {
+                let text = match frame {
+                    Some(Ok(Message::Text(t))) => t,
+                    Some(Ok(Message::Ping(p))) => {
+                        ws.send(Message::Pong(p)).await?;
+                        continue;
+                    }
+                    Some(Ok(Message::Close(_))) | None => bail!("connection closed"),
+                    Some(Ok(_)) => continue,
+                    Some(Err(e)) => return Err(e.into()),
+                };
+                let msg: Value = serde_json::from_str(&text).context("non-JSON frame")?;
+                if let Some(err) = msg.get("error") {
+                    bail!("RPC error: {}", err);
+                }
+                if let Some(id) = msg["id"].as_u64() {
+                    if id == chain_id_req {
+                        chain_id = Some(quantity(&msg["result"]).ok_or_else(|| anyhow!("bad eth_chainId"))?);
+                    } else if sub_reqs.get(&id) == Some(&2) {
+                        head_sub = msg["result"].as_str().map(str::to_string);
+                    } else if block_reqs.remove(&id).is_some() {
+                        let chain = chain_id.ok_or_else(|| anyhow!("block before eth_chainId"))?;
+                        for e in events_from_block(chain, &msg["result"], &watch) {
+                            record(config, db, e, false).await?;
+                        }
+                    }
+                    continue;
+                }
+                let params = &msg["params"];
+                let result = &params["result"];
+                let chain = match chain_id {
+                    Some(c) => c,
+                    None => continue,
+                };
+                if params["subscription"].as_str().is_some() && params["subscription"].as_str() == head_sub.as_deref() {
+                    if let Some(number) = result["number"].as_str() {
+                        let (id, msg) = send("eth_getBlockByNumber", json!([number, true]));
+                        block_reqs.insert(id, ());
+                        ws.send(Message::Text(msg)).await?;
+                    }
+                } else {
+                    if let Some((announcement, removed)) = stealth::announcement_from_log(chain, result) {
+                        record_announcement(config, db, announcement, removed).await?;
+                    }
+                    for (e, removed) in events_from_log(chain, result, &watch) {
+                        record(config, db, e, removed).await?;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Store an announcement for the scanner. One reorged out is deleted, and
+/// the payment it became, if a scan matched it, is flagged removed.
+async fn record_announcement(
+    config: &WatchConfig,
+    db: &KmsDb,
+    announcement: Announcement,
+    removed: bool,
+) -> Result<()> {
+    if !removed {
+        db.run(move |db| db.insert_stealth_announcement(&announcement))
+            .await?;
+        return Ok(());
+    }
+    let event = announcement.payment_event("");
+    db.run(move |db| db.delete_stealth_announcement(&announcement))
+        .await?;
+    record(config, db, event, true).await
+}
+
+/// Store one event; deliver it to the webhook if it is new or newly removed
+/// and the wallet's preferences let it through.
+pub async fn record(
+    config: &WatchConfig,
+    db: &KmsDb,
+    event: ChainEvent,
+    removed: bool,
+) -> Result<()> {
+    let row = db
+        .run(move |db| {
+            if removed {
+                db.mark_chain_event_removed(&event)
+            } else {
+                db.insert_chain_event(&event)
+            }
+        })
+        .await?;
+    let row = match row {
+        Some(r) => r,
+        None => return Ok(()),
+    };
+    println!(
+        "🔭 {} {} {} tx={}",
+        row.event.kind,
+        row.event.address,
+        if row.removed { "removed" } else { "seen" },
+        row.event.tx_hash
+    );
+    if let (Some(url), Some(secret)) = (&config.webhook_url, &config.webhook_secret) {
+        let key_id = row.event.key_id.clone();
+        let prefs = db.run(move |db| db.notification_prefs(&key_id)).await?;
+        let email = match delivery(prefs, &row) {
+            Delivery::Push { email } => email,
+            Delivery::Hold(reason) => {
+                println!(
+                    "🔕 event {} for {} not pushed: {}",
+                    row.id, row.event.key_id, reason
+                );
+                return Ok(());
+            }
+        };
+        let (url, secret) = (url.clone(), secret.clone());
+        tokio::spawn(async move { deliver(&url, &secret, &row, email).await });
+    }
+    Ok(())
+}
+
+/// Preferences as stored. A row that no longer parses (written by a later
+/// build, say) falls back to the defaults rather than silencing the wallet.
+fn delivery(prefs: Option<(String, i64)>, row: &ChainEventRow) -> Delivery {
+    let prefs = match prefs {
+        Some((json, _)) => serde_json::from_str::<NotificationPrefs>(&json).unwrap_or_else(|e| {
+            eprintln!(
+                "⚠️  Notification preferences of {}: {}",
+                row.event.key_id, e
+            );
+            NotificationPrefs::default()
+        }),
+        None => NotificationPrefs::default(),
+    };
+    prefs.delivery(&row.event, chrono::Utc::now().timestamp())
+}
+
+async fn deliver(url: &str, secret: &[u8], row: &ChainEventRow, email: bool) {
+    match serde_json::to_string(&event_envelope(row, email)) {
+        Ok(body) => post_signed(url, secret, body, &format!("event {}", row.id)).await,
+        Err(e) => eprintln!("⚠️  Event webhook: {} for event {}", e, row.id),
+    }
+}
+
+/// POST an event body to the webhook with its HMAC signature, retrying with
+/// backoff. Shared by every CA event that goes to `KMS_EVENT_WEBHOOK_URL`.
+pub async fn post_signed(url: &str, secret: &[u8], body: String, what: &str) {
+    use warp::hyper::{Body, Client, Request};
+    let signature = webhook_signature(secret, body.as_bytes());
+    let client = Client::new();
+    for attempt in 0..WEBHOOK_ATTEMPTS {
+        let req = Request::post(url)
+            .header("content-type", "application/json")
+            .header("x-airaccount-signature", signature.as_str())
+            .body(Body::from(body.clone()));
+        let outcome = match req {
+            Ok(req) => client.request(req).await.map_err(anyhow::Error::from),
+            Err(e) => Err(e.into()),
+        };
+        match outcome {
+            Ok(resp) if resp.status().is_success() => return,
+            Ok(resp) => eprintln!("⚠️  Event webhook: {} for {}", resp.status(), what),
+            Err(e) => eprintln!("⚠️  Event webhook: {} for {}", e, what),
+        }
+        tokio::time::sleep(Duration::from_secs(1 << attempt)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALICE: &str = "0x1111111111111111111111111111111111111111";
+    const BOB: &str = "0x2222222222222222222222222222222222222222";
+    const TOKEN: &str = "0x3333333333333333333333333333333333333333";
+
+    fn watch() -> WatchSet {
+        vec![(ALICE.to_string(), "key-a".to_string())]
+            .into_iter()
+            .collect()
+    }
+
+    #[test]
+    fn uint256_renders_as_decimal() {
+        assert_eq!(decimal("0x0").as_deref(), Some("0"));
+        assert_eq!(
+            decimal("0xde0b6b3a7640000").as_deref(),
+            Some("1000000000000000000")
+        );
+        assert_eq!(
+            decimal(&format!("0x{}", "f".repeat(64))).as_deref(),
+            Some("115792089237316195423570985008687907853269984665640564039457584007913129639935")
+        );
+        assert!(decimal(&format!("0x1{}", "0".repeat(64))).is_none());
+    }
+
+    #[test]
+    fn incoming_erc20_transfer_is_recorded_for_the_recipient() {
+        let log = json!({
+            "address": TOKEN,
+            "topics": [TRANSFER_TOPIC, address_topic(BOB), address_topic(ALICE)],
+            "data": "0x00000000000000000000000000000000000000000000000000000000000f4240",
+            "transactionHash": "0xAB",
+            "blockNumber": "0x10",
+            "logIndex": "0x2",
+        });
+        let events = events_from_log(11155111, &log, &watch());
+        assert_eq!(events.len(), 1);
+        let (e, removed) = &events[0];
+        assert!(!removed);
+        assert_eq!(e.kind, KIND_ERC20_IN);
+        assert_eq!(e.key_id, "key-a");
+        assert_eq!(e.token.as_deref(), Some(TOKEN));
+        assert_eq!(e.from_address.as_deref(), Some(BOB));
+        assert_eq!(e.value.as_deref(), Some("1000000"));
+        assert_eq!(
+            (e.tx_hash.as_str(), e.block_number, e.log_index),
+            ("0xab", 16, 2)
+        );
+
+        // Outgoing transfers and other recipients are not deposits.
+        let mut out = log.clone();
+        out["topics"] = json!([TRANSFER_TOPIC, address_topic(ALICE), address_topic(BOB)]);
+        assert!(events_from_log(1, &out, &watch()).is_empty());
+    }
+
+    #[test]
+    fn logs_emitted_by_a_watched_account_are_contract_events() {
+        let log = json!({
+            "address": ALICE.to_uppercase().replace("0X", "0x"),
+            "topics": ["0xbeef"],
+            "data": "0x",
+            "transactionHash": "0xcd",
+            "blockNumber": "0x11",
+            "logIndex": "0x0",
+            "removed": true,
+        });
+        let events = events_from_log(1, &log, &watch());
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].0.kind, KIND_CONTRACT_EVENT);
+        assert_eq!(events[0].0.topic0.as_deref(), Some("0xbeef"));
+        assert!(events[0].1);
+    }
+
+    #[test]
+    fn native_deposits_come_from_block_transactions() {
+        let block = json!({
+            "number": "0x20",
+            "transactions": [
+                { "hash": "0x01", "from": BOB, "to": ALICE, "value": "0x5", "transactionIndex": "0x0" },
+                { "hash": "0x02", "from": BOB, "to": ALICE, "value": "0x0", "transactionIndex": "0x1" },
+                { "hash": "0x03", "from": BOB, "to": BOB, "value": "0x5", "transactionIndex": "0x2" },
+                { "hash": "0x04", "from": BOB, "to": null, "value": "0x5", "transactionIndex": "0x3" },
+            ],
+        });
+        let events = events_from_block(1, &block, &watch());
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind, KIND_NATIVE_IN);
+        assert_eq!(events[0].value.as_deref(), Some("5"));
+        assert_eq!(events[0].log_index, -1);
+
+        let row = ChainEventRow {
+            id: 7,
+            event: events.into_iter().next().unwrap(),
+            removed: false,
+            created_at: 1_790_000_000,
+        };
+        let body = serde_json::to_value(event_envelope(&row, false)).unwrap();
+        assert_eq!(body["type"], "chain.event");
+        assert_eq!(body["version"], 1);
+        assert_eq!(body["correlationId"], "chain-event-7");
+        assert_eq!(body["payload"]["logIndex"], Value::Null);
+        assert!(body["payload"].get("email").is_none());
+        let body = serde_json::to_value(event_envelope(&row, true)).unwrap();
+        assert_eq!(body["payload"]["email"], true);
+        assert_eq!(event_json(&row)["observedAt"], 1_790_000_000);
+    }
+
+    #[test]
+    fn subscriptions_filter_on_the_watch_set() {
+        let subs = subscriptions(&watch());
+        assert_eq!(subs[0][1]["topics"][2][0], address_topic(ALICE));
+        assert_eq!(subs[1][1]["address"][0], ALICE);
+        assert_eq!(subs[2][0], "newHeads");
+        assert!(WatchConfig::new("wss://x".into(), None, None).is_err());
+        assert!(WatchConfig::new("ws://x".into(), Some("http://h".into()), None).is_err());
+        assert_eq!(
+            webhook_signature(b"key", b"The quick brown fox jumps over the lazy dog"),
+            "sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+    }
+}
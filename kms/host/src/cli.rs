@@ -47,6 +47,15 @@ pub struct DeriveAddressOpt {
     pub hd_path: String,
 }
 
+#[derive(Debug, StructOpt)]
+pub struct DeriveSolanaAddressOpt {
+    #[structopt(short, long, required = true, parse(try_from_str = decode_str_to_uuid))]
+    pub wallet_id: uuid::Uuid,
+    /// SLIP-0010 path, every level hardened.
+    #[structopt(short, long, default_value = "m/44'/501'/0'/0'")]
+    pub hd_path: String,
+}
+
 #[derive(Debug, StructOpt)]
 pub struct SignTransactionOpt {
     #[structopt(short, long, required = true, parse(try_from_str = decode_str_to_uuid))]
@@ -67,6 +76,31 @@ pub struct SignTransactionOpt {
     pub gas: u128,
 }
 
+#[derive(Debug, StructOpt)]
+pub struct BackupOpt {
+    #[structopt(short, long, required = true, parse(try_from_str = decode_str_to_uuid))]
+    pub wallet_id: uuid::Uuid,
+    /// File holding the backup password (first line).
+    #[structopt(short, long, parse(from_os_str))]
+    pub password_file: std::path::PathBuf,
+    /// Argon2id parameters, e.g. `argon2id:m=512,t=16,p=1`.
+    #[structopt(short, long, default_value = "argon2id")]
+    pub kdf: proto::kdf::KdfConfig,
+    /// Where to write the backup (JSON).
+    #[structopt(short, long, parse(from_os_str))]
+    pub out: std::path::PathBuf,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct RestoreOpt {
+    /// Backup written by `backup`.
+    #[structopt(parse(from_os_str))]
+    pub backup: std::path::PathBuf,
+    /// File holding the backup password (first line).
+    #[structopt(short, long, parse(from_os_str))]
+    pub password_file: std::path::PathBuf,
+}
+
 #[derive(Debug, StructOpt)]
 pub enum Command {
     /// Create a new wallet.
@@ -75,9 +109,18 @@ pub enum Command {
     /// Derive an address from a wallet.
     #[structopt(name = "derive-address")]
     DeriveAddress(DeriveAddressOpt),
+    /// Derive a Solana (ed25519) address from a wallet.
+    #[structopt(name = "derive-solana-address")]
+    DeriveSolanaAddress(DeriveSolanaAddressOpt),
     /// Sign a transaction.
     #[structopt(name = "sign-transaction")]
     SignTransaction(SignTransactionOpt),
+    /// Write an encrypted backup of a wallet (password + device sealing key).
+    #[structopt(name = "backup")]
+    Backup(BackupOpt),
+    /// Restore a wallet from an encrypted backup sealed on this device.
+    #[structopt(name = "restore")]
+    Restore(RestoreOpt),
     /// Run tests
     #[structopt(name = "test")]
     Test,
@@ -0,0 +1,373 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Sanctions / compliance screening of the transactions the CA broadcasts.
+//!
+//! Screening sits between the TA's signature and `eth_sendRawTransaction`,
+//! not in front of the TA: the signing decision stays with the owner and the
+//! TEE, and a slow vendor never holds a TA session. Today the only
+//! transaction the CA sends itself is a gas-tank refill; a signature returned
+//! to the caller is broadcast (and screened) by the caller.
+//!
+//! [`ComplianceProvider`] is the point an operator plugs a vendor into.
+//! [`NoScreening`] allows everything and is what runs without configuration;
+//! [`WebhookScreening`] asks an `http://` adapter in front of the vendor's
+//! API. [`Screening`] adds the failure policy: when the provider errors or
+//! times out, `closed` (the default) holds the transaction and `open` sends it.
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+
+pub type ScreenFuture<'a> = Pin<Box<dyn Future<Output = Result<Verdict>> + Send + 'a>>;
+
+/// How long the webhook gets before the failure policy applies.
+pub const WEBHOOK_TIMEOUT_SECS: u64 = 5;
+
+/// One transaction about to be broadcast.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScreeningRequest {
+    pub chain_id: u64,
+    /// 0x-prefixed sender and destination.
+    pub from: String,
+    pub to: String,
+    pub value_wei: u128,
+    /// Which CA flow is sending, e.g. `gas-tank-topup`.
+    pub purpose: &'static str,
+}
+
+/// A provider's answer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Allow,
+    Deny { reason: String },
+}
+
+pub trait ComplianceProvider: Send + Sync {
+    /// Shown in startup logs, audit entries and responses.
+    fn name(&self) -> &'static str;
+
+    /// An error is not a denial; [`Screening`] decides what it means.
+    fn screen<'a>(&'a self, request: &'a ScreeningRequest) -> ScreenFuture<'a>;
+}
+
+/// The default: every destination is allowed.
+pub struct NoScreening;
+
+impl ComplianceProvider for NoScreening {
+    fn name(&self) -> &'static str {
+        "none"
+    }
+
+    fn screen<'a>(&'a self, _request: &'a ScreeningRequest) -> ScreenFuture<'a> {
+        Box::pin(async { Ok(Verdict::Allow) })
+    }
+}
+
+/// POSTs `{chainId, from, to, valueWei, purpose}` and expects
+/// `{"allow": bool, "reason"?: string}`.
+pub struct WebhookScreening {
+    pub url: String,
+}
+
+impl WebhookScreening {
+    pub fn new(url: &str) -> Result<Self> {
+        if !url.starts_with("http://") {
+            bail!("KMS_COMPLIANCE_URL must be an http:// URL (no TLS in the CA)");
+        }
+        Ok(Self {
+            url: url.to_string(),
+        })
+    }
+}
+
+impl ComplianceProvider for WebhookScreening {
+    fn name(&self) -> &'static str {
+        "webhook"
+    }
+
+    fn screen<'a>(&'a self, request: &'a ScreeningRequest) -> ScreenFuture<'a> {
+        Box::pin(async move {
+            use warp::hyper::{body, Body, Client, Request};
+            let payload = json!({
+                "chainId": request.chain_id,
+                "from": request.from,
+                "to": request.to,
+                "valueWei": request.value_wei.to_string(),
+                "purpose": request.purpose,
+            });
+            let req = Request::post(self.url.as_str())
+                .header("content-type", "application/json")
+                .body(Body::from(payload.to_string()))?;
+            let resp = tokio::time::timeout(
+                std::time::Duration::from_secs(WEBHOOK_TIMEOUT_SECS),
+                Client::new().request(req),
+            )
+            .await
+            .map_err(|_| {
+                anyhow!(
+                    "screening service did not answer within {}s",
+                    WEBHOOK_TIMEOUT_SECS
+                )
+            })?
+            .context("screening service unreachable")?;
+            let status = resp.status();
+            let bytes = body::to_bytes(resp.into_body()).await?;
+            if !status.is_success() {
+                bail!("screening service returned HTTP {}", status);
+            }
+            parse_webhook_reply(&bytes)
+        })
+    }
+}
+
+fn parse_webhook_reply(bytes: &[u8]) -> Result<Verdict> {
+    let reply: Value =
+        serde_json::from_slice(bytes).context("screening service reply is not JSON")?;
+    let allow = reply["allow"]
+        .as_bool()
+        .ok_or_else(|| anyhow!("screening service reply needs a boolean allow"))?;
+    if allow {
+        return Ok(Verdict::Allow);
+    }
+    let reason = reply["reason"]
+        .as_str()
+        .filter(|r| !r.is_empty())
+        .unwrap_or("denied by screening service");
+    Ok(Verdict::Deny {
+        reason: reason.to_string(),
+    })
+}
+
+/// What a provider error means for the transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailurePolicy {
+    /// Hold it: nothing unscreened goes out.
+    Closed,
+    /// Send it: screening outages do not stop refills.
+    Open,
+}
+
+impl fmt::Display for FailurePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FailurePolicy::Closed => "closed",
+            FailurePolicy::Open => "open",
+        })
+    }
+}
+
+impl FailurePolicy {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s {
+            "closed" => Ok(FailurePolicy::Closed),
+            "open" => Ok(FailurePolicy::Open),
+            _ => bail!("KMS_COMPLIANCE_ON_ERROR must be closed or open"),
+        }
+    }
+}
+
+/// The outcome of one screening, as audited and returned.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Decision {
+    pub provider: String,
+    /// allowed | denied | error
+    pub outcome: &'static str,
+    /// Whether the CA may send the transaction.
+    pub broadcast: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl Decision {
+    /// For the account audit log.
+    pub fn audit_detail(&self, request: &ScreeningRequest) -> String {
+        let mut detail = format!(
+            "{} {} via {}: {} wei to {} on chain {}",
+            request.purpose,
+            self.outcome,
+            self.provider,
+            request.value_wei,
+            request.to,
+            request.chain_id
+        );
+        if let Some(reason) = &self.reason {
+            detail.push_str(" — ");
+            detail.push_str(reason);
+        }
+        if self.outcome == "error" {
+            detail.push_str(if self.broadcast {
+                " (fail-open, sent)"
+            } else {
+                " (fail-closed, held)"
+            });
+        }
+        detail
+    }
+}
+
+/// A provider plus its failure policy.
+pub struct Screening {
+    provider: Box<dyn ComplianceProvider>,
+    on_error: FailurePolicy,
+}
+
+impl Screening {
+    pub fn new(provider: Box<dyn ComplianceProvider>, on_error: FailurePolicy) -> Self {
+        Self { provider, on_error }
+    }
+
+    pub fn none() -> Self {
+        Self::new(Box::new(NoScreening), FailurePolicy::Closed)
+    }
+
+    /// `KMS_COMPLIANCE_URL` (http://…) turns screening on;
+    /// `KMS_COMPLIANCE_ON_ERROR` is closed (default) or open.
+    pub fn from_env() -> Option<Result<Self>> {
+        let var = |k: &str| std::env::var(k).ok().filter(|v| !v.is_empty());
+        let url = var("KMS_COMPLIANCE_URL")?;
+        Some((|| {
+            let on_error = match var("KMS_COMPLIANCE_ON_ERROR") {
+                Some(s) => FailurePolicy::parse(&s)?,
+                None => FailurePolicy::Closed,
+            };
+            Ok(Self::new(Box::new(WebhookScreening::new(&url)?), on_error))
+        })())
+    }
+
+    pub fn provider_name(&self) -> &'static str {
+        self.provider.name()
+    }
+
+    pub fn on_error(&self) -> FailurePolicy {
+        self.on_error
+    }
+
+    pub async fn check(&self, request: &ScreeningRequest) -> Decision {
+        let provider = self.provider.name().to_string();
+        match self.provider.screen(request).await {
+            Ok(Verdict::Allow) => Decision {
+                provider,
+                outcome: "allowed",
+                broadcast: true,
+                reason: None,
+            },
+            Ok(Verdict::Deny { reason }) => Decision {
+                provider,
+                outcome: "denied",
+                broadcast: false,
+                reason: Some(reason),
+            },
+            Err(e) => Decision {
+                provider,
+                outcome: "error",
+                broadcast: self.on_error == FailurePolicy::Open,
+                reason: Some(format!("{:#}", e)),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Fixed(std::result::Result<Verdict, &'static str>);
+
+    impl ComplianceProvider for Fixed {
+        fn name(&self) -> &'static str {
+            "fixed"
+        }
+
+        fn screen<'a>(&'a self, _request: &'a ScreeningRequest) -> ScreenFuture<'a> {
+            let answer = self.0.clone().map_err(|e| anyhow!(e));
+            Box::pin(async move { answer })
+        }
+    }
+
+    fn request() -> ScreeningRequest {
+        ScreeningRequest {
+            chain_id: 1,
+            from: format!("0x{}", "11".repeat(20)),
+            to: format!("0x{}", "22".repeat(20)),
+            value_wei: 10u128.pow(17),
+            purpose: "gas-tank-topup",
+        }
+    }
+
+    fn check(provider: Fixed, on_error: FailurePolicy) -> Decision {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(Screening::new(Box::new(provider), on_error).check(&request()))
+    }
+
+    #[test]
+    fn failure_policy_applies_only_to_errors() {
+        let deny = || {
+            Fixed(Ok(Verdict::Deny {
+                reason: "SDN match".into(),
+            }))
+        };
+        for on_error in [FailurePolicy::Closed, FailurePolicy::Open] {
+            assert!(check(Fixed(Ok(Verdict::Allow)), on_error).broadcast);
+            let denied = check(deny(), on_error);
+            assert_eq!((denied.outcome, denied.broadcast), ("denied", false));
+        }
+        let closed = check(Fixed(Err("timeout")), FailurePolicy::Closed);
+        assert_eq!((closed.outcome, closed.broadcast), ("error", false));
+        assert!(closed
+            .audit_detail(&request())
+            .ends_with("timeout (fail-closed, held)"));
+        assert!(check(Fixed(Err("timeout")), FailurePolicy::Open).broadcast);
+
+        let none = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(Screening::none().check(&request()));
+        assert_eq!((none.provider.as_str(), none.broadcast), ("none", true));
+    }
+
+    #[test]
+    fn webhook_reply_and_config() {
+        assert_eq!(
+            parse_webhook_reply(br#"{"allow":true}"#).unwrap(),
+            Verdict::Allow
+        );
+        assert_eq!(
+            parse_webhook_reply(br#"{"allow":false,"reason":"OFAC SDN"}"#).unwrap(),
+            Verdict::Deny {
+                reason: "OFAC SDN".into()
+            }
+        );
+        assert!(matches!(
+            parse_webhook_reply(br#"{"allow":false}"#).unwrap(),
+            Verdict::Deny { .. }
+        ));
+        assert!(parse_webhook_reply(br#"{"allow":"yes"}"#).is_err());
+        assert!(parse_webhook_reply(b"<html>").is_err());
+
+        assert!(WebhookScreening::new("https://vendor.example/screen").is_err());
+        assert!(WebhookScreening::new("http://127.0.0.1:9300/screen").is_ok());
+        assert_eq!(FailurePolicy::parse("open").unwrap(), FailurePolicy::Open);
+        assert!(FailurePolicy::parse("fail-open").is_err());
+    }
+}
@@ -0,0 +1,399 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! The part of the CA's configuration that can change without a restart.
+//!
+//! Everything else still comes from the environment once, at start-up. A
+//! section is hot-reloadable when its consumers read it per request:
+//!
+//! - `rateLimits`: per-minute limits of the API-key, agent and OTP limiters
+//!   (`KMS_RATE_LIMIT`, `KMS_AGENT_RATE_LIMIT`, `KMS_OTP_RATE_LIMIT`);
+//! - `risk`: the step-up score and freshness of risk scoring
+//!   (`KMS_RISK_STEP_UP_SCORE`, `KMS_RISK_FRESH_SECS`). Turning scoring on
+//!   or off, or changing its backend, is a restart.
+//!
+//! `GET /admin/config` returns [`ConfigManager::snapshot`]; `PATCH` merges
+//! the fields it names into their sections through
+//! [`ConfigManager::update_section`], which validates the merged section as
+//! a whole and says which field is wrong and why. The caller audits every
+//! [`SectionChange`] with the hash of the value it replaced.
+
+use anyhow::{anyhow, bail, Result};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use std::sync::{Mutex, RwLock};
+
+use crate::rate_limit::RateLimiter;
+use crate::risk::RiskPolicy;
+
+pub const SECTIONS: [&str; 2] = ["rateLimits", "risk"];
+
+/// Highest per-minute limit a PATCH may set.
+pub const MAX_RATE_LIMIT: usize = 100_000;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct RateLimitsSection {
+    pub api_per_minute: usize,
+    pub agent_per_minute: usize,
+    pub otp_per_minute: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct RiskSection {
+    /// Read-only here: scoring is switched on by `KMS_RISK_BACKEND`.
+    pub backend: Option<String>,
+    pub step_up_score: u8,
+    pub fresh_secs: i64,
+}
+
+/// One applied section: what it was, what it is now.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SectionChange {
+    pub section: &'static str,
+    pub previous: Value,
+    pub previous_hash: String,
+    pub current: Value,
+    pub current_hash: String,
+}
+
+pub struct ConfigManager {
+    api_limiter: RateLimiter,
+    agent_limiter: RateLimiter,
+    otp_limiter: RateLimiter,
+    /// Err = `KMS_RISK_*` misconfigured at start-up, which stays a restart.
+    risk: RwLock<std::result::Result<Option<RiskPolicy>, String>>,
+    /// One PATCH at a time, so a merge never starts from a stale section.
+    update: Mutex<()>,
+}
+
+impl ConfigManager {
+    /// Takes clones of the live limiters; they share their limit.
+    pub fn new(
+        api_limiter: RateLimiter,
+        agent_limiter: RateLimiter,
+        otp_limiter: RateLimiter,
+        risk: std::result::Result<Option<RiskPolicy>, String>,
+    ) -> Self {
+        Self {
+            api_limiter,
+            agent_limiter,
+            otp_limiter,
+            risk: RwLock::new(risk),
+            update: Mutex::new(()),
+        }
+    }
+
+    /// The risk policy in force now.
+    pub fn risk(&self) -> std::result::Result<Option<RiskPolicy>, String> {
+        self.risk
+            .read()
+            .map(|r| r.clone())
+            .unwrap_or_else(|_| Err("risk policy lock poisoned".to_string()))
+    }
+
+    pub fn snapshot(&self) -> Value {
+        let mut out = Map::new();
+        for name in SECTIONS {
+            out.insert(
+                name.to_string(),
+                self.section(name).unwrap_or_else(|e| json!({ "error": e.to_string() })),
+            );
+        }
+        Value::Object(out)
+    }
+
+    pub fn section(&self, name: &str) -> Result<Value> {
+        match name {
+            "rateLimits" => Ok(serde_json::to_value(self.rate_limits())?),
+            "risk" => Ok(serde_json::to_value(self.risk_section()?)?),
+            _ => bail!(
+                "unknown config section `{}`; hot-reloadable sections are: {}",
+                name,
+                SECTIONS.join(", ")
+            ),
+        }
+    }
+
+    /// Merge `patch` (an object of the fields to change) into section
+    /// `name`, validate the result and apply it.
+    pub fn update_section(&self, name: &str, patch: &Value) -> Result<SectionChange> {
+        let _guard = self
+            .update
+            .lock()
+            .map_err(|_| anyhow!("config update lock poisoned"))?;
+        let prepared = self.prepare(name, patch)?;
+        Ok(self.apply(prepared))
+    }
+
+    /// Several sections at once: every one is validated before any is
+    /// applied, so a bad field leaves the whole configuration as it was.
+    pub fn update(&self, patch: &Value) -> Result<Vec<SectionChange>> {
+        let sections = patch
+            .as_object()
+            .ok_or_else(|| anyhow!("config patch must be a JSON object of sections"))?;
+        if sections.is_empty() {
+            bail!(
+                "config patch names no section; hot-reloadable sections are: {}",
+                SECTIONS.join(", ")
+            );
+        }
+        let _guard = self
+            .update
+            .lock()
+            .map_err(|_| anyhow!("config update lock poisoned"))?;
+        let prepared = sections
+            .iter()
+            .map(|(name, fields)| self.prepare(name, fields))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(prepared.into_iter().map(|p| self.apply(p)).collect())
+    }
+
+    fn rate_limits(&self) -> RateLimitsSection {
+        RateLimitsSection {
+            api_per_minute: self.api_limiter.limit(),
+            agent_per_minute: self.agent_limiter.limit(),
+            otp_per_minute: self.otp_limiter.limit(),
+        }
+    }
+
+    fn risk_section(&self) -> Result<RiskSection> {
+        match self.risk() {
+            Ok(Some(policy)) => Ok(RiskSection {
+                backend: Some(policy.backend.name().to_string()),
+                step_up_score: policy.step_up_score,
+                fresh_secs: policy.fresh_secs,
+            }),
+            Ok(None) => Ok(RiskSection {
+                backend: None,
+                step_up_score: 0,
+                fresh_secs: 0,
+            }),
+            Err(e) => Err(anyhow!("risk policy misconfigured at start-up: {}", e)),
+        }
+    }
+
+    fn prepare(&self, name: &str, patch: &Value) -> Result<Prepared> {
+        let previous = self.section(name)?;
+        let merged = merge(name, &previous, patch)?;
+        match name {
+            "rateLimits" => {
+                let s: RateLimitsSection = typed(name, merged.clone())?;
+                for (field, v) in [
+                    ("apiPerMinute", s.api_per_minute),
+                    ("agentPerMinute", s.agent_per_minute),
+                    ("otpPerMinute", s.otp_per_minute),
+                ] {
+                    if !(1..=MAX_RATE_LIMIT).contains(&v) {
+                        bail!(
+                            "rateLimits.{} must be 1-{} requests per minute (got {})",
+                            field,
+                            MAX_RATE_LIMIT,
+                            v
+                        );
+                    }
+                }
+                Ok(Prepared {
+                    previous,
+                    current: merged,
+                    update: Update::RateLimits(s),
+                })
+            }
+            "risk" => {
+                let s: RiskSection = typed(name, merged.clone())?;
+                if s.backend != previous["backend"].as_str().map(str::to_string) {
+                    bail!("risk.backend cannot change at runtime; set KMS_RISK_BACKEND and restart");
+                }
+                if s.backend.is_none() {
+                    bail!("risk scoring is off (KMS_RISK_BACKEND unset); enabling it needs a restart");
+                }
+                if !(1..=100).contains(&s.step_up_score) {
+                    bail!(
+                        "risk.stepUpScore must be 1-100 (got {})",
+                        s.step_up_score
+                    );
+                }
+                if !(10..=300).contains(&s.fresh_secs) {
+                    bail!(
+                        "risk.freshSecs must be 10-300, at most the 300 s a challenge lives (got {})",
+                        s.fresh_secs
+                    );
+                }
+                Ok(Prepared {
+                    previous,
+                    current: merged,
+                    update: Update::Risk(s),
+                })
+            }
+            _ => unreachable!("section() rejected unknown names"),
+        }
+    }
+
+    fn apply(&self, p: Prepared) -> SectionChange {
+        let section = match p.update {
+            Update::RateLimits(s) => {
+                self.api_limiter.set_limit(s.api_per_minute);
+                self.agent_limiter.set_limit(s.agent_per_minute);
+                self.otp_limiter.set_limit(s.otp_per_minute);
+                "rateLimits"
+            }
+            Update::Risk(s) => {
+                if let Ok(mut risk) = self.risk.write() {
+                    if let Ok(Some(policy)) = risk.as_mut() {
+                        policy.step_up_score = s.step_up_score;
+                        policy.fresh_secs = s.fresh_secs;
+                    }
+                }
+                "risk"
+            }
+        };
+        SectionChange {
+            section,
+            previous_hash: value_hash(&p.previous),
+            previous: p.previous,
+            current_hash: value_hash(&p.current),
+            current: p.current,
+        }
+    }
+}
+
+struct Prepared {
+    previous: Value,
+    current: Value,
+    update: Update,
+}
+
+enum Update {
+    RateLimits(RateLimitsSection),
+    Risk(RiskSection),
+}
+
+fn merge(name: &str, current: &Value, patch: &Value) -> Result<Value> {
+    let fields = patch
+        .as_object()
+        .ok_or_else(|| anyhow!("{}: expected an object of fields, got {}", name, patch))?;
+    let mut merged = current.clone();
+    for (field, value) in fields {
+        if value.is_null() {
+            bail!("{}.{}: null is not a value; omit the field to keep it", name, field);
+        }
+        merged[field.as_str()] = value.clone();
+    }
+    Ok(merged)
+}
+
+fn typed<T: DeserializeOwned>(name: &str, value: Value) -> Result<T> {
+    serde_json::from_value(value).map_err(|e| anyhow!("{}: {}", name, e))
+}
+
+/// sha256 of the value's JSON text (object keys sorted), 0x-hex.
+pub fn value_hash(value: &Value) -> String {
+    format!(
+        "0x{}",
+        hex::encode(Sha256::digest(value.to_string().as_bytes()))
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::risk::RiskBackend;
+
+    fn manager(risk: Option<RiskPolicy>) -> ConfigManager {
+        ConfigManager::new(
+            RateLimiter::new(100, 10),
+            RateLimiter::new(20, 10),
+            RateLimiter::new(10, 10),
+            Ok(risk),
+        )
+    }
+
+    fn builtin() -> RiskPolicy {
+        RiskPolicy {
+            backend: RiskBackend::parse("builtin").unwrap(),
+            step_up_score: 70,
+            fresh_secs: 60,
+        }
+    }
+
+    #[test]
+    fn patch_merges_into_the_live_limiters() {
+        let api = RateLimiter::new(100, 10);
+        let m = ConfigManager::new(
+            api.clone(),
+            RateLimiter::new(20, 10),
+            RateLimiter::new(10, 10),
+            Ok(None),
+        );
+        let before = m.section("rateLimits").unwrap();
+        let change = m
+            .update_section("rateLimits", &json!({ "apiPerMinute": 250 }))
+            .unwrap();
+        assert_eq!(api.limit(), 250);
+        assert_eq!(change.previous, before);
+        assert_eq!(change.previous_hash, value_hash(&before));
+        assert_eq!(change.current["agentPerMinute"], 20);
+        assert_ne!(change.previous_hash, change.current_hash);
+    }
+
+    #[test]
+    fn errors_name_the_field_and_nothing_is_applied() {
+        let m = manager(Some(builtin()));
+        let e = m
+            .update_section("rateLimits", &json!({ "apiPerMinute": 0 }))
+            .unwrap_err()
+            .to_string();
+        assert!(e.contains("rateLimits.apiPerMinute") && e.contains("got 0"), "{}", e);
+        let e = m
+            .update_section("rateLimits", &json!({ "burst": 5 }))
+            .unwrap_err()
+            .to_string();
+        assert!(e.contains("unknown field `burst`"), "{}", e);
+        let e = m.update_section("tls", &json!({})).unwrap_err().to_string();
+        assert!(e.contains("rateLimits, risk"), "{}", e);
+
+        // The valid rateLimits half is not applied when risk is refused.
+        let e = m
+            .update(&json!({
+                "rateLimits": { "otpPerMinute": 3 },
+                "risk": { "freshSecs": 5 },
+            }))
+            .unwrap_err()
+            .to_string();
+        assert!(e.contains("risk.freshSecs"), "{}", e);
+        assert_eq!(m.section("rateLimits").unwrap()["otpPerMinute"], 10);
+    }
+
+    #[test]
+    fn risk_tuning_is_hot_but_the_backend_is_not() {
+        let m = manager(Some(builtin()));
+        m.update(&json!({ "risk": { "stepUpScore": 50 } })).unwrap();
+        assert_eq!(m.risk().unwrap().unwrap().step_up_score, 50);
+        assert!(m
+            .update_section("risk", &json!({ "backend": "http://127.0.0.1:1" }))
+            .is_err());
+
+        let off = manager(None);
+        assert!(off
+            .update_section("risk", &json!({ "stepUpScore": 50 }))
+            .unwrap_err()
+            .to_string()
+            .contains("restart"));
+    }
+}
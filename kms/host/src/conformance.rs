@@ -0,0 +1,456 @@
+//! SDK conformance suite — deterministic flows behind `/api/conformance/*`.
+//!
+//! External SDKs (JS, mobile) need to check their request encoding, WebAuthn
+//! assertion building and signature parsing against a real CA without a real
+//! wallet. Every value here is public: the test wallet is the Hardhat
+//! `test … junk` mnemonic (accounts from `ta/conformance/vectors`), the
+//! passkey is a P-256 key derived from a published label, and the challenge
+//! never changes. Nothing in this module reads the database or calls the TA,
+//! so a conformance run cannot reach a stored wallet.
+//!
+//! The passkey's rpId and origin sit under `.invalid` (RFC 6761): no CA or
+//! TA lists them, so an assertion made with the published key — replayable
+//! by design, the challenge being fixed — is refused everywhere else.
+//!
+//! Signing flows commit like the strict TA does: the assertion's challenge
+//! is `SHA-256(challenge ‖ digest)` over the digest being signed; the
+//! non-signing DeriveAddress takes the bare challenge.
+
+use crate::key_pin;
+use crate::webauthn::{
+    self, AuthenticationOptions, AuthenticationOptionsResponse, AuthenticationResponseJSON,
+    CredentialDescriptor,
+};
+use anyhow::{anyhow, Result};
+use k256::elliptic_curve::ops::Reduce;
+use p256::ecdsa::SigningKey as PasskeyKey;
+use proto::eip55::parse_address;
+use proto::SigningContext;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+/// Bumped whenever a value below changes; SDK suites pin it.
+pub const SUITE_VERSION: &str = "airaccount-conformance-v1";
+
+/// KeyId of the test wallet, the only key these endpoints answer for.
+pub const KEY_ID: &str = "00000000-c0f0-4000-8000-000000000001";
+
+pub const MNEMONIC: &str = "test test test test test test test test test test test junk";
+
+pub const RP_ID: &str = "conformance.airaccount.invalid";
+pub const ORIGIN: &str = "https://conformance.airaccount.invalid";
+
+/// ChallengeId returned by the conformance BeginAuthentication.
+pub const CHALLENGE_ID: &str = "conformance-v1";
+
+/// Raw credential id of the test passkey (base64url in requests).
+pub const CREDENTIAL_ID: &[u8] = b"airaccount-conformance-passkey-v1";
+
+const CHALLENGE_LABEL: &[u8] = b"AirAccount.Conformance.Challenge.v1";
+const PASSKEY_LABEL: &[u8] = b"AirAccount.Conformance.Passkey.v1";
+
+/// One derived account of the test wallet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Account {
+    pub path: &'static str,
+    pub private_key: &'static str,
+    /// EIP-55, as published.
+    pub address: &'static str,
+}
+
+/// `m/44'/60'/0'/0/{0,1,2}` of [`MNEMONIC`].
+pub const ACCOUNTS: [Account; 3] = [
+    Account {
+        path: "m/44'/60'/0'/0/0",
+        private_key: "ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80",
+        address: "0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266",
+    },
+    Account {
+        path: "m/44'/60'/0'/0/1",
+        private_key: "59c6995e998f97a5a0044966f0945389dc9e86dae88c7a8412f4603b6b78690d",
+        address: "0x70997970C51812dc3A010C7d01b50e0d17dc79C8",
+    },
+    Account {
+        path: "m/44'/60'/0'/0/2",
+        private_key: "5de4111afa1a4b94908f83103eb1f1706367c2e68ca870fc3fb9a804cdab365a",
+        address: "0x3C44CdDdB6a900fa2b585dd299e03d12FA4293BC",
+    },
+];
+
+/// A canned SignHash: `signature` is r ‖ s ‖ v (RFC 6979, low-S, v = 27/28).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct SignVector {
+    #[serde(rename = "DerivationPath")]
+    pub path: &'static str,
+    #[serde(rename = "Hash")]
+    pub hash: &'static str,
+    #[serde(rename = "Signature")]
+    pub signature: &'static str,
+}
+
+/// The `sign` lines of `ta/conformance/vectors/derivation.txt` made with
+/// the test wallet's keys — external, never regenerated from this code.
+pub const SIGN_VECTORS: [SignVector; 4] = [
+    SignVector {
+        path: "m/44'/60'/0'/0/0",
+        hash: "50b2c43fd39106bafbba0da34fc430e1f91e3c96ea2acee2bc34119f92b37750",
+        signature: "f16ea9a3478698f695fd1401bfe27e9e4a7e8e3da94aa72b021125e31fa899cc573c48ea3fe1d4ab61a9db10c19032026e3ed2dbccba5a178235ac27f94504311c",
+    },
+    SignVector {
+        path: "m/44'/60'/0'/0/0",
+        hash: "9d9f61b52610bb55cd920f43a6aec52ededd1b26f48411495bdfb9a5fb06a692",
+        signature: "6d7402ed44f9ae26f9a13ce69f9b8eb28561a0de3b554a2989c999181f7e93f513b64c1adba136a418d5a589a4f35134977747a41ecc8605503f96e5fcf4f3b71b",
+    },
+    SignVector {
+        path: "m/44'/60'/0'/0/0",
+        hash: "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470",
+        signature: "f9a45c11d904141226963b371ccbc2128a29d146ce3c0d43326414711e3e153d536804917f10c70c5d0cd7b730b559d95eb812d1d783f6dd534e5854f01b3e551b",
+    },
+    SignVector {
+        path: "m/44'/60'/0'/0/1",
+        hash: "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+        signature: "c468c4e582120a0e5bbf01841d6b2bb7ca52074c34eb1401632b1dfbd22ed4d72d6c0cf17d1bd5b1bce864dfb5b102a3e33d391440da45625bebe81e433f43471b",
+    },
+];
+
+/// The known challenge: SHA-256 of a fixed label.
+pub fn challenge() -> [u8; 32] {
+    Sha256::digest(CHALLENGE_LABEL).into()
+}
+
+/// What a signing assertion's challenge must be for `digest`.
+pub fn signing_challenge(digest: &[u8; 32]) -> [u8; 32] {
+    let mut h = Sha256::new();
+    h.update(challenge());
+    h.update(digest);
+    h.finalize().into()
+}
+
+/// Private scalar of the test passkey: SHA-256 of a fixed label.
+pub fn passkey_private_key() -> [u8; 32] {
+    Sha256::digest(PASSKEY_LABEL).into()
+}
+
+/// Uncompressed SEC1 public key of the test passkey.
+pub fn passkey_public_key() -> Vec<u8> {
+    let key = PasskeyKey::from_slice(&passkey_private_key()).expect("label hash is a valid scalar");
+    key.verifying_key()
+        .to_encoded_point(false)
+        .as_bytes()
+        .to_vec()
+}
+
+/// The test wallet's account named by Address, or by KeyId and an optional
+/// DerivationPath (absent = the first account, as a real key's primary
+/// path). Anything else is refused before a handler could look further.
+pub fn account(
+    key_id: Option<&str>,
+    derivation_path: Option<&str>,
+    address: Option<&str>,
+) -> Result<&'static Account> {
+    if let Some(address) = address {
+        let wanted =
+            parse_address(address).map_err(|e| anyhow!("Invalid Address {}: {}", address, e))?;
+        return ACCOUNTS
+            .iter()
+            .find(|a| parse_address(a.address).ok() == Some(wanted))
+            .ok_or_else(|| {
+                anyhow!(
+                    "{} is not an account of the conformance wallet (see GET /api/conformance)",
+                    address
+                )
+            });
+    }
+    let key_id = key_id.ok_or_else(|| anyhow!("Either KeyId or Address must be provided"))?;
+    if !key_id.eq_ignore_ascii_case(KEY_ID) {
+        return Err(anyhow!(
+            "Key {} is not the conformance wallet {}; these endpoints never touch stored wallets",
+            key_id,
+            KEY_ID
+        ));
+    }
+    let path = match derivation_path {
+        Some(path) => path,
+        None => return Ok(&ACCOUNTS[0]),
+    };
+    ACCOUNTS.iter().find(|a| a.path == path).ok_or_else(|| {
+        let paths: Vec<&str> = ACCOUNTS.iter().map(|a| a.path).collect();
+        anyhow!(
+            "The conformance wallet has no vectors for {}; use one of {}",
+            path,
+            paths.join(", ")
+        )
+    })
+}
+
+fn signing_key(account: &Account) -> k256::ecdsa::SigningKey {
+    let secret = hex::decode(account.private_key).expect("hex constant");
+    k256::ecdsa::SigningKey::from_slice(&secret).expect("published test key")
+}
+
+/// Uncompressed secp256k1 public key of `account`.
+pub fn public_key(account: &Account) -> Vec<u8> {
+    signing_key(account)
+        .verifying_key()
+        .to_encoded_point(false)
+        .as_bytes()
+        .to_vec()
+}
+
+/// r ‖ s ‖ v over `digest`, the way the TA signs (RFC 6979, low-S, v = 27/28).
+pub fn sign_hash(account: &Account, digest: &[u8; 32]) -> Result<Vec<u8>> {
+    // RFC 6979 seeds the nonce with the digest reduced mod n; k256 seeds it
+    // with the bytes as given. Same signature except for a digest >= n.
+    let reduced = <k256::Scalar as Reduce<k256::U256>>::reduce_bytes(&(*digest).into());
+    let (signature, recovery_id) = signing_key(account)
+        .sign_prehash_recoverable(&reduced.to_bytes())
+        .map_err(|e| anyhow!("sign: {}", e))?;
+    let mut out = signature.to_bytes().to_vec();
+    out.push(recovery_id.to_byte() + 27);
+    Ok(out)
+}
+
+/// The digest a Sign of `message` signs under `context`. Login needs the
+/// TA's EIP-4361 checks and is not part of the suite.
+pub fn message_digest(context: &SigningContext, message: &[u8]) -> Result<[u8; 32]> {
+    match context {
+        SigningContext::Raw | SigningContext::PersonalMsg => {
+            key_pin::message_digest(context, message)
+                .ok_or_else(|| anyhow!("no digest for this context"))
+        }
+        _ => Err(anyhow!(
+            "the conformance Sign covers Raw and PersonalMsg messages only"
+        )),
+    }
+}
+
+/// Check an assertion made with the test passkey. `digest` = Some for a
+/// signing flow, whose challenge commits to it.
+pub fn verify_assertion(
+    challenge_id: &str,
+    credential: &AuthenticationResponseJSON,
+    digest: Option<&[u8; 32]>,
+) -> Result<()> {
+    if challenge_id != CHALLENGE_ID {
+        return Err(anyhow!(
+            "ChallengeId must be {} (from /api/conformance/BeginAuthentication)",
+            CHALLENGE_ID
+        ));
+    }
+    let expected = match digest {
+        Some(d) => signing_challenge(d),
+        None => challenge(),
+    };
+    let verified = webauthn::verify_authentication_response(
+        credential,
+        &expected,
+        &[ORIGIN.to_string()],
+        RP_ID,
+        &passkey_public_key(),
+        0,
+        false,
+    )?;
+    if verified.credential_id != CREDENTIAL_ID {
+        return Err(anyhow!("credential id is not the conformance passkey's"));
+    }
+    Ok(())
+}
+
+/// The conformance BeginAuthentication: the same shape as the real one,
+/// with the fixed challenge and the test passkey.
+pub fn authentication_options() -> AuthenticationOptionsResponse {
+    AuthenticationOptionsResponse {
+        challenge_id: CHALLENGE_ID.to_string(),
+        options: AuthenticationOptions {
+            challenge: webauthn::b64url_encode(&challenge()),
+            timeout: 300_000,
+            rp_id: RP_ID.to_string(),
+            allow_credentials: vec![CredentialDescriptor {
+                id: webauthn::b64url_encode(CREDENTIAL_ID),
+                type_: "public-key".to_string(),
+                transports: None,
+            }],
+            user_verification: "required".to_string(),
+        },
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ManifestAccount {
+    #[serde(rename = "DerivationPath")]
+    pub path: &'static str,
+    #[serde(rename = "Address")]
+    pub address: &'static str,
+    #[serde(rename = "PublicKey")]
+    pub public_key: String,
+    #[serde(rename = "PrivateKey")]
+    pub private_key: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ManifestPasskey {
+    #[serde(rename = "CredentialId")]
+    pub credential_id: String,
+    /// The raw P-256 scalar, hex, for a software authenticator.
+    #[serde(rename = "PrivateKey")]
+    pub private_key: String,
+    #[serde(rename = "PublicKey")]
+    pub public_key: String,
+    #[serde(rename = "RpId")]
+    pub rp_id: &'static str,
+    #[serde(rename = "Origin")]
+    pub origin: &'static str,
+}
+
+/// `GET /api/conformance`: everything an SDK suite needs to build requests
+/// and check answers without asking anything else of the CA.
+#[derive(Debug, Serialize)]
+pub struct Manifest {
+    #[serde(rename = "SuiteVersion")]
+    pub suite_version: &'static str,
+    #[serde(rename = "KeyId")]
+    pub key_id: &'static str,
+    #[serde(rename = "Mnemonic")]
+    pub mnemonic: &'static str,
+    #[serde(rename = "Accounts")]
+    pub accounts: Vec<ManifestAccount>,
+    #[serde(rename = "Passkey")]
+    pub passkey: ManifestPasskey,
+    #[serde(rename = "ChallengeId")]
+    pub challenge_id: &'static str,
+    /// base64url, as in BeginAuthentication.
+    #[serde(rename = "Challenge")]
+    pub challenge: String,
+    /// How a signing flow's challenge is formed.
+    #[serde(rename = "SigningChallenge")]
+    pub signing_challenge: &'static str,
+    #[serde(rename = "SignVectors")]
+    pub sign_vectors: &'static [SignVector],
+    #[serde(rename = "Endpoints")]
+    pub endpoints: &'static [&'static str],
+}
+
+pub const ENDPOINTS: &[&str] = &[
+    "GET  /api/conformance",
+    "POST /api/conformance/BeginAuthentication",
+    "POST /api/conformance/DeriveAddress",
+    "POST /api/conformance/GetPublicKey",
+    "POST /api/conformance/SignHash",
+    "POST /api/conformance/Sign",
+];
+
+pub fn manifest() -> Manifest {
+    Manifest {
+        suite_version: SUITE_VERSION,
+        key_id: KEY_ID,
+        mnemonic: MNEMONIC,
+        accounts: ACCOUNTS
+            .iter()
+            .map(|a| ManifestAccount {
+                path: a.path,
+                address: a.address,
+                public_key: hex::encode(public_key(a)),
+                private_key: a.private_key,
+            })
+            .collect(),
+        passkey: ManifestPasskey {
+            credential_id: webauthn::b64url_encode(CREDENTIAL_ID),
+            private_key: hex::encode(passkey_private_key()),
+            public_key: hex::encode(passkey_public_key()),
+            rp_id: RP_ID,
+            origin: ORIGIN,
+        },
+        challenge_id: CHALLENGE_ID,
+        challenge: webauthn::b64url_encode(&challenge()),
+        signing_challenge: "SHA-256(Challenge || digest signed)",
+        sign_vectors: &SIGN_VECTORS,
+        endpoints: ENDPOINTS,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::webauthn::AssertionResponseJSON;
+    use p256::ecdsa::signature::Signer;
+
+    /// What an SDK's software authenticator does with the manifest.
+    fn assert_with_test_passkey(challenge: &[u8], origin: &str) -> AuthenticationResponseJSON {
+        let client_data = serde_json::json!({
+            "type": "webauthn.get",
+            "challenge": webauthn::b64url_encode(challenge),
+            "origin": origin,
+        })
+        .to_string();
+        let mut auth_data = Sha256::digest(RP_ID.as_bytes()).to_vec();
+        auth_data.push(0x05); // UP | UV
+        auth_data.extend_from_slice(&1u32.to_be_bytes());
+        let mut signed = auth_data.clone();
+        signed.extend_from_slice(&Sha256::digest(client_data.as_bytes()));
+        let key = PasskeyKey::from_slice(&passkey_private_key()).unwrap();
+        let signature: p256::ecdsa::Signature = key.sign(&signed);
+        let id = webauthn::b64url_encode(CREDENTIAL_ID);
+        AuthenticationResponseJSON {
+            id: id.clone(),
+            raw_id: id,
+            response: AssertionResponseJSON {
+                client_data_json: webauthn::b64url_encode(client_data.as_bytes()),
+                authenticator_data: webauthn::b64url_encode(&auth_data),
+                signature: webauthn::b64url_encode(signature.to_der().as_bytes()),
+                user_handle: None,
+            },
+            type_: "public-key".to_string(),
+            client_extension_results: serde_json::Value::Null,
+        }
+    }
+
+    #[test]
+    fn canned_vectors_are_reproduced_byte_for_byte() {
+        for v in SIGN_VECTORS.iter() {
+            let account = account(Some(KEY_ID), Some(v.path), None).unwrap();
+            let mut hash = [0u8; 32];
+            hash.copy_from_slice(&hex::decode(v.hash).unwrap());
+            let signature = sign_hash(account, &hash).unwrap();
+            assert_eq!(hex::encode(&signature), v.signature, "{}", v.hash);
+            let signer = key_pin::recover_signer(&hash, &signature).unwrap();
+            assert_eq!(Some(signer), parse_address(account.address).ok());
+        }
+        for a in ACCOUNTS.iter() {
+            let by_address = account(None, None, Some(&a.address.to_lowercase())).unwrap();
+            assert_eq!(by_address, a);
+        }
+    }
+
+    #[test]
+    fn assertions_must_use_the_test_passkey_and_commit_to_the_digest() {
+        let digest = [7u8; 32];
+        let signing = assert_with_test_passkey(&signing_challenge(&digest), ORIGIN);
+        verify_assertion(CHALLENGE_ID, &signing, Some(&digest)).unwrap();
+        // A commitment binds one digest, and a signing assertion is not a
+        // plain one.
+        assert!(verify_assertion(CHALLENGE_ID, &signing, Some(&[8u8; 32])).is_err());
+        assert!(verify_assertion(CHALLENGE_ID, &signing, None).is_err());
+        assert!(verify_assertion("other", &signing, Some(&digest)).is_err());
+
+        let plain = assert_with_test_passkey(&challenge(), ORIGIN);
+        verify_assertion(CHALLENGE_ID, &plain, None).unwrap();
+        let elsewhere = assert_with_test_passkey(&challenge(), "https://aastar.io");
+        assert!(verify_assertion(CHALLENGE_ID, &elsewhere, None).is_err());
+    }
+
+    #[test]
+    fn only_the_test_wallet_is_answered_for() {
+        let real = "4319f351-0b24-4097-b659-80ee4f824cdd";
+        assert!(account(Some(real), Some(ACCOUNTS[0].path), None).is_err());
+        assert!(account(Some(KEY_ID), Some("m/44'/60'/0'/0/3"), None).is_err());
+        assert!(account(
+            None,
+            None,
+            Some("0x8626f6940E2eb28930eFb4CeF49B2d1F2C9C1199")
+        )
+        .is_err());
+        assert!(account(None, Some(ACCOUNTS[0].path), None).is_err());
+        assert_eq!(account(Some(KEY_ID), None, None).unwrap(), &ACCOUNTS[0]);
+        assert!(message_digest(&SigningContext::Login, b"x").is_err());
+    }
+}
@@ -5,10 +5,18 @@
 
 use anyhow::{Context, Result};
 use chrono::Utc;
-use rusqlite::{params, Connection, TransactionBehavior};
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension, TransactionBehavior};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
 use std::sync::{Arc, Mutex};
 use uuid::Uuid;
 
+use crate::event_store::{self, CaEvent, ChainHead, EventRecord, ProjectionDiff, RebuildReport};
+use crate::key_pin::PinCheck;
+use crate::standby::{self, PullRequest, Role, StandbyState, SyncBatch};
+use crate::stealth::Announcement;
+
 const DEFAULT_DB_PATH: &str = "/root/shared/kms.db";
 
 /// How long a 'pending' P256 session key placeholder is considered in-flight before
@@ -36,7 +44,11 @@ CREATE TABLE IF NOT EXISTS wallets (
     status          TEXT NOT NULL DEFAULT 'creating',
     error_msg       TEXT,
     created_at      TEXT NOT NULL,
-    lifecycle_status TEXT NOT NULL DEFAULT 'active'
+    lifecycle_status TEXT NOT NULL DEFAULT 'active',
+    attestation_type TEXT,                              -- none | self | basic (webauthn::AttestationType)
+    aaguid          TEXT,
+    certification   TEXT,                               -- FIDO MDS level the attestation chains to
+    passkey_role    TEXT                                -- KMS_FIDO_ATTESTATION_POLICY role
 );
 
 CREATE TABLE IF NOT EXISTS address_index (
@@ -117,6 +129,23 @@ CREATE TABLE IF NOT EXISTS p256_session_keys (
     FOREIGN KEY (wallet_id) REFERENCES wallets(key_id) ON DELETE CASCADE
 );
 
+-- Scoped AA session keys (secp256k1, TEE-generated, owner-signed permission).
+-- Host-side index allocation + listing only: the scope and the authoritative
+-- revocation list live in the TA.
+CREATE TABLE IF NOT EXISTS scoped_session_keys (
+    wallet_id        TEXT NOT NULL,
+    session_index    INTEGER NOT NULL,
+    session_address  TEXT NOT NULL,
+    permission_hash  TEXT NOT NULL,
+    chain_id         INTEGER NOT NULL,
+    expires_at       INTEGER NOT NULL,
+    status           TEXT NOT NULL DEFAULT 'active',   -- active|revoked
+    created_at       TEXT NOT NULL,
+    revoked_at       TEXT,
+    PRIMARY KEY (wallet_id, session_index),
+    FOREIGN KEY (wallet_id) REFERENCES wallets(key_id) ON DELETE CASCADE
+);
+
 -- #129 / aastar-sdk#193: verified notification contact bindings (Telegram/email).
 -- PII, NOT keys: stored host-side (never in TEE), isolated from wallet/key tables.
 -- contact_ref is the verified channel id (telegram chat id / email); stored only
@@ -139,6 +168,347 @@ CREATE TABLE IF NOT EXISTS contact_bindings (
     FOREIGN KEY (account) REFERENCES wallets(key_id) ON DELETE CASCADE
 );
 
+-- Account lifecycle audit trail: contact binding transitions and credential
+-- changes, append-only. `detail` never holds a secret (no codes, tokens or
+-- full contact refs — display hints only).
+CREATE TABLE IF NOT EXISTS account_audit (
+    id              INTEGER PRIMARY KEY AUTOINCREMENT,
+    account         TEXT NOT NULL,
+    event           TEXT NOT NULL,
+    detail          TEXT,
+    created_at      INTEGER NOT NULL,
+    correlation_id  TEXT                                 -- x-correlation-id of the causing request
+);
+
+-- Air-gapped sign requests exported by this CA. `request` is the exported QR
+-- text; exactly one signed response is accepted per row (pending → completed).
+CREATE TABLE IF NOT EXISTS offline_requests (
+    request_id      TEXT PRIMARY KEY,                    -- hex, 16 bytes
+    key_id          TEXT NOT NULL,
+    address         TEXT NOT NULL,                       -- expected signer, lowercase
+    request         TEXT NOT NULL,
+    status          TEXT NOT NULL DEFAULT 'pending',     -- pending|completed
+    tx_hash         TEXT,
+    created_at      INTEGER NOT NULL,
+    expires_at      INTEGER NOT NULL,
+    completed_at    INTEGER
+);
+
+-- Ledger of signed transactions, for stuck-transaction rescue. A rescue
+-- replacement points back via `replaced_by`; `confirmed` is set once the
+-- caller reports an on-chain nonce past this one.
+CREATE TABLE IF NOT EXISTS tx_history (
+    id              INTEGER PRIMARY KEY AUTOINCREMENT,
+    key_id          TEXT NOT NULL,
+    address         TEXT NOT NULL,                       -- signer, lowercase
+    derivation_path TEXT NOT NULL,
+    chain_id        INTEGER NOT NULL,
+    nonce           INTEGER NOT NULL,
+    gas_price       TEXT NOT NULL,                       -- wei, decimal
+    signed_tx       TEXT NOT NULL,                       -- 0x raw RLP
+    tx_hash         TEXT NOT NULL,
+    status          TEXT NOT NULL DEFAULT 'pending',     -- pending|replaced|confirmed
+    replaced_by     INTEGER,
+    created_at      INTEGER NOT NULL
+);
+
+-- Incoming side of the ledger: deposits and contract events seen by the
+-- chain watcher (chain_watch.rs). A log the node reports as reorged out is
+-- flagged `removed`, never deleted; native transfers use log_index -1 - txIndex.
+CREATE TABLE IF NOT EXISTS chain_events (
+    id              INTEGER PRIMARY KEY AUTOINCREMENT,
+    key_id          TEXT NOT NULL,
+    address         TEXT NOT NULL,                       -- watched address, lowercase
+    chain_id        INTEGER NOT NULL,
+    kind            TEXT NOT NULL,                       -- erc20-transfer-in|native-transfer-in|contract-event
+    token           TEXT,
+    from_address    TEXT,
+    to_address      TEXT,
+    value           TEXT,                                -- base units, decimal
+    topic0          TEXT,
+    tx_hash         TEXT NOT NULL,
+    block_number    INTEGER NOT NULL,
+    log_index       INTEGER NOT NULL,
+    removed         INTEGER NOT NULL DEFAULT 0,
+    created_at      INTEGER NOT NULL,
+    UNIQUE (chain_id, tx_hash, log_index, address, kind)
+);
+
+-- Which chain events the webhook pushes for a wallet (notify.rs). No row =
+-- the defaults: everything pushed, nothing emailed.
+CREATE TABLE IF NOT EXISTS notification_prefs (
+    key_id          TEXT PRIMARY KEY,
+    prefs           TEXT NOT NULL,                       -- NotificationPrefs JSON
+    updated_at      INTEGER NOT NULL,
+    FOREIGN KEY (key_id) REFERENCES wallets(key_id) ON DELETE CASCADE
+);
+
+-- Where to push a wallet's signing requests (remote_approval.rs). The key
+-- that counts is the one paired in the TA; this row only routes the push.
+CREATE TABLE IF NOT EXISTS remote_approvers (
+    key_id          TEXT PRIMARY KEY,
+    approver_key    TEXT NOT NULL,                       -- compressed secp256k1, hex
+    platform        TEXT NOT NULL,                       -- fcm | apns
+    push_token      TEXT NOT NULL,
+    paired_at       INTEGER NOT NULL,
+    FOREIGN KEY (key_id) REFERENCES wallets(key_id) ON DELETE CASCADE
+);
+
+-- EIP-5564 meta-addresses the stealth scanner works for (stealth.rs).
+-- scanned_through is the last stealth_announcements id handed to the TA for
+-- this account; a new registration starts from 0 and catches up.
+CREATE TABLE IF NOT EXISTS stealth_meta (
+    key_id          TEXT NOT NULL,
+    account         INTEGER NOT NULL,                    -- m/5564'/60'/account'/{0,1}'
+    meta_address    TEXT NOT NULL,                       -- st:eth:0x…
+    scanned_through INTEGER NOT NULL DEFAULT 0,
+    created_at      INTEGER NOT NULL,
+    PRIMARY KEY (key_id, account),
+    FOREIGN KEY (key_id) REFERENCES wallets(key_id) ON DELETE CASCADE
+);
+
+-- Scheme-1 announcements the watcher saw while any meta-address was
+-- registered. Whom they pay is only known after a TA scan. A reorged-out
+-- announcement is deleted: if it is mined again it gets a new id and is
+-- scanned again.
+CREATE TABLE IF NOT EXISTS stealth_announcements (
+    id               INTEGER PRIMARY KEY AUTOINCREMENT,
+    chain_id         INTEGER NOT NULL,
+    stealth_address  TEXT NOT NULL,                      -- lowercase 0x
+    caller           TEXT NOT NULL,
+    ephemeral_pubkey TEXT NOT NULL,                      -- 0x, compressed
+    metadata         TEXT NOT NULL,                      -- 0x; byte 0 is the view tag
+    tx_hash          TEXT NOT NULL,
+    block_number     INTEGER NOT NULL,
+    log_index        INTEGER NOT NULL,
+    created_at       INTEGER NOT NULL,
+    UNIQUE (chain_id, tx_hash, log_index)
+);
+
+-- Enterprise tenants of bulk provisioning (provisioning.rs). A tenant
+-- without a row here gets the default quota on its first batch.
+CREATE TABLE IF NOT EXISTS tenants (
+    tenant_id    TEXT PRIMARY KEY,
+    wallet_quota INTEGER NOT NULL,                       -- wallets the tenant may hold
+    created_at   INTEGER NOT NULL
+);
+
+-- Which tenant a provisioned wallet belongs to. Deleting the wallet frees
+-- its quota slot.
+CREATE TABLE IF NOT EXISTS tenant_wallets (
+    key_id     TEXT PRIMARY KEY,
+    tenant_id  TEXT NOT NULL,
+    tags       TEXT NOT NULL,                            -- comma-separated
+    batch_id   TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+    FOREIGN KEY (key_id) REFERENCES wallets(key_id) ON DELETE CASCADE
+);
+CREATE INDEX IF NOT EXISTS idx_tenant_wallets_tenant ON tenant_wallets(tenant_id, created_at);
+
+-- One row per provisioning batch: the report exactly as attested, so it can
+-- be exported again and checked against the same evidence later.
+CREATE TABLE IF NOT EXISTS provisioning_batches (
+    batch_id    TEXT PRIMARY KEY,
+    tenant_id   TEXT NOT NULL,
+    report      TEXT NOT NULL,                           -- JSON; nonce = SHA-256(report)
+    attestation TEXT NOT NULL,                           -- JSON evidence, as returned
+    created_at  INTEGER NOT NULL
+);
+
+-- Fees paid in ERC-20 through a paymaster: one row per quote, from the
+-- quote through the TA signature to on-chain settlement (paymaster.rs).
+CREATE TABLE IF NOT EXISTS fee_payments (
+    quote_hash        TEXT PRIMARY KEY,                  -- 0x, proto::paymaster::quote_hash
+    key_id            TEXT NOT NULL,
+    chain_id          INTEGER NOT NULL,
+    paymaster         TEXT NOT NULL,
+    token             TEXT NOT NULL,
+    max_token_cost    TEXT NOT NULL,                     -- base units, decimal
+    quote             TEXT NOT NULL,                     -- JSON, as returned by /kms/fee/quote
+    user_op_hash      TEXT,
+    status            TEXT NOT NULL DEFAULT 'quoted',    -- quoted|signed|settled|failed
+    actual_token_cost TEXT,
+    tx_hash           TEXT,
+    created_at        INTEGER NOT NULL,
+    updated_at        INTEGER NOT NULL
+);
+
+-- Refresh-token families of agent credentials. The TA keeps the secret
+-- hashes and decides; this row lets the CA refuse a revoked family without a
+-- TEE call and say why (agent_jwt.rs).
+CREATE TABLE IF NOT EXISTS refresh_families (
+    key_id         TEXT PRIMARY KEY,                     -- agent key id, <wallet>:<index>
+    wallet_id      TEXT NOT NULL,
+    credential_id  TEXT,                                 -- wallets.credential_id at issue
+    device_hash    TEXT NOT NULL,                        -- hex SHA-256 of the device fingerprint
+    generation     INTEGER NOT NULL DEFAULT 0,
+    status         TEXT NOT NULL DEFAULT 'active',       -- active | revoked
+    revoked_reason TEXT,
+    created_at     INTEGER NOT NULL,
+    updated_at     INTEGER NOT NULL
+);
+
+-- Deletion certificates (proof-of-erasure) from RemoveWallet, kept after the
+-- wallet row is gone so compliance can fetch them later.
+CREATE TABLE IF NOT EXISTS deletion_certificates (
+    key_id      TEXT PRIMARY KEY,
+    certificate TEXT NOT NULL,                           -- JSON, as returned by DeleteKey
+    signed      INTEGER NOT NULL,                        -- 1 = device attestation attached
+    created_at  INTEGER NOT NULL
+);
+
+-- Scheduled health checks of this node (device_health.rs): TA self-test and
+-- attestation against the measurements manifest, one row per run.
+CREATE TABLE IF NOT EXISTS device_health_checks (
+    id          INTEGER PRIMARY KEY AUTOINCREMENT,
+    checked_at  INTEGER NOT NULL,
+    passed      INTEGER NOT NULL,
+    source      TEXT NOT NULL,                           -- schedule | startup | operator
+    report      TEXT NOT NULL                            -- HealthReport JSON
+);
+
+-- This node's quarantine; at most one row. While it exists the CA sends its
+-- TA nothing that uses a key, so signing fails over to replicas.
+CREATE TABLE IF NOT EXISTS device_quarantine (
+    id          INTEGER PRIMARY KEY CHECK (id = 1),
+    reason      TEXT NOT NULL,
+    check_id    INTEGER,                                 -- device_health_checks.id that failed
+    since       INTEGER NOT NULL
+);
+
+-- Wallets whose key is not in the TA (signer.rs). No row = the TA signs.
+CREATE TABLE IF NOT EXISTS wallet_signers (
+    key_id          TEXT PRIMARY KEY,
+    backend         TEXT NOT NULL CHECK (backend IN ('pkcs11')),
+    key_label       TEXT NOT NULL UNIQUE,                -- CKA_LABEL of the HSM key pair
+    derivation_path TEXT NOT NULL,                       -- the one path it answers for
+    created_at      INTEGER NOT NULL,
+    FOREIGN KEY (key_id) REFERENCES wallets(key_id) ON DELETE CASCADE
+);
+
+-- Multi-device sync: every device holding a replica of a wallet. Signing is
+-- routed to an active device with a recent heartbeat (replication.rs).
+CREATE TABLE IF NOT EXISTS wallet_devices (
+    key_id             TEXT NOT NULL,
+    device_id          TEXT NOT NULL,                    -- KMS_DEVICE_ID of that node
+    endpoint           TEXT,                             -- its kms-api base URL; NULL = this node
+    attestation_key_fp TEXT NOT NULL,                    -- pinned at first sight
+    status             TEXT NOT NULL DEFAULT 'active',   -- active | revoked
+    added_at           INTEGER NOT NULL,
+    revoked_at         INTEGER,
+    last_seen          INTEGER NOT NULL,
+    quarantined        INTEGER NOT NULL DEFAULT 0,       -- 1 = its health check failed
+    PRIMARY KEY (key_id, device_id)
+);
+
+-- AWS multi-region keys: which device serves each region of a key, and which
+-- region is primary. Rows exist only for keys created with MultiRegion.
+CREATE TABLE IF NOT EXISTS key_regions (
+    key_id     TEXT NOT NULL,
+    region     TEXT NOT NULL,                            -- KMS_REGION of the serving device
+    device_id  TEXT NOT NULL,                            -- wallet_devices.device_id
+    role       TEXT NOT NULL,                            -- PRIMARY | REPLICA
+    created_at INTEGER NOT NULL,
+    PRIMARY KEY (key_id, region)
+);
+
+-- Erase-on-tamper: wipe certificates reported by the TA (panic wipe or the
+-- failed-auth limit). One row per certificate, keyed by its digest.
+CREATE TABLE IF NOT EXISTS device_wipes (
+    digest      TEXT PRIMARY KEY,                        -- keccak256 of the certificate text
+    reason      TEXT NOT NULL,                           -- PanicWipe | FailedAuth(n)
+    certificate TEXT NOT NULL,                           -- JSON, as returned by /kms/tamper/*
+    signed      INTEGER NOT NULL,                        -- 1 = device attestation attached
+    created_at  INTEGER NOT NULL
+);
+
+-- Account linkage: a user identity owning wallet addresses across chains.
+-- The owner wallet created it and approves every other wallet that joins.
+CREATE TABLE IF NOT EXISTS identities (
+    identity_id  TEXT PRIMARY KEY,
+    label        TEXT NOT NULL DEFAULT '',
+    owner_key_id TEXT NOT NULL,
+    created_at   INTEGER NOT NULL,
+    FOREIGN KEY (owner_key_id) REFERENCES wallets(key_id) ON DELETE CASCADE
+);
+
+-- One row per linked address, with the statement its key signed in the TA.
+CREATE TABLE IF NOT EXISTS identity_links (
+    address         TEXT PRIMARY KEY,                    -- lowercase 0x; one identity per address
+    identity_id     TEXT NOT NULL,
+    key_id          TEXT NOT NULL,
+    derivation_path TEXT NOT NULL,
+    chain_ids       TEXT NOT NULL,                       -- comma-separated, ascending
+    statement       TEXT NOT NULL,                       -- proto::identity::link_statement
+    signature       TEXT NOT NULL,                       -- 0x r‖s‖v personal_sign by `address`
+    linked_at       INTEGER NOT NULL,
+    FOREIGN KEY (identity_id) REFERENCES identities(identity_id) ON DELETE CASCADE,
+    FOREIGN KEY (key_id) REFERENCES wallets(key_id) ON DELETE CASCADE
+);
+
+-- Gas tanks: relayer addresses whose native balance the CA keeps above a
+-- floor. With top_up_wei the monitor proposes refills from a treasury wallet.
+CREATE TABLE IF NOT EXISTS gas_tanks (
+    address         TEXT NOT NULL,                       -- lowercase 0x
+    chain_id        INTEGER NOT NULL,
+    label           TEXT NOT NULL DEFAULT '',
+    min_balance_wei TEXT NOT NULL,                       -- decimal
+    top_up_wei      TEXT,                                -- decimal; NULL = alert only
+    treasury_key_id TEXT,
+    treasury_path   TEXT,
+    balance_wei     TEXT,                                -- last observed, decimal
+    checked_at      INTEGER,
+    low_since       INTEGER,                             -- set while below the floor
+    created_at      INTEGER NOT NULL,
+    PRIMARY KEY (address, chain_id),
+    FOREIGN KEY (treasury_key_id) REFERENCES wallets(key_id) ON DELETE SET NULL
+);
+
+-- Treasury → relayer refills. proposed → approved (second person) → sent, or
+-- signed when the broadcast failed; expired when nobody acted in time.
+CREATE TABLE IF NOT EXISTS gas_tank_topups (
+    id              INTEGER PRIMARY KEY AUTOINCREMENT,
+    address         TEXT NOT NULL,
+    chain_id        INTEGER NOT NULL,
+    treasury_key_id TEXT NOT NULL,
+    treasury_path   TEXT NOT NULL,
+    amount_wei      TEXT NOT NULL,
+    status          TEXT NOT NULL,
+    approved_by     TEXT,                                -- approver key id
+    tx_json         TEXT,                                -- EthereumTransaction shown to the treasury
+    tx_hash         TEXT,
+    created_at      INTEGER NOT NULL,
+    updated_at      INTEGER NOT NULL,
+    FOREIGN KEY (treasury_key_id) REFERENCES wallets(key_id) ON DELETE CASCADE
+);
+
+-- This node's half of an active-standby pair (standby.rs); at most one row,
+-- never replicated. fencing_token is the highest one this node has seen.
+CREATE TABLE IF NOT EXISTS standby_state (
+    id            INTEGER PRIMARY KEY CHECK (id = 1),
+    role          TEXT NOT NULL CHECK (role IN ('active', 'standby', 'fenced')),
+    fencing_token INTEGER NOT NULL,
+    synced_at     INTEGER,                               -- last batch applied (standby)
+    promoted_at   INTEGER,
+    updated_at    INTEGER NOT NULL
+);
+
+-- Append-only log that wallets, address_index and tx_history are projected
+-- from (event_store.rs). hash = SHA-256(prev_hash || seq || payload).
+CREATE TABLE IF NOT EXISTS ca_events (
+    seq             INTEGER PRIMARY KEY,                 -- 1, 2, ... without gaps
+    kind            TEXT NOT NULL,                       -- CaEvent variant, snake_case
+    aggregate       TEXT NOT NULL,                       -- key_id, else address, else tx id
+    payload         TEXT NOT NULL,                       -- CaEvent JSON, as hashed
+    created_at      INTEGER NOT NULL,
+    prev_hash       TEXT NOT NULL,
+    hash            TEXT NOT NULL
+);
+CREATE TRIGGER IF NOT EXISTS ca_events_no_update BEFORE UPDATE ON ca_events
+BEGIN SELECT RAISE(ABORT, 'ca_events is append-only'); END;
+CREATE TRIGGER IF NOT EXISTS ca_events_no_delete BEFORE DELETE ON ca_events
+BEGIN SELECT RAISE(ABORT, 'ca_events is append-only'); END;
+
 CREATE INDEX IF NOT EXISTS idx_address_key ON address_index(key_id);
 CREATE INDEX IF NOT EXISTS idx_challenge_expire ON challenges(expires_at);
 CREATE INDEX IF NOT EXISTS idx_wallet_credential ON wallets(credential_id);
@@ -146,9 +516,17 @@ CREATE INDEX IF NOT EXISTS idx_tx_log_created ON tx_log(created_at);
 CREATE INDEX IF NOT EXISTS idx_tx_log_op ON tx_log(op);
 CREATE INDEX IF NOT EXISTS idx_agent_keys_human ON agent_keys(human_id);
 CREATE INDEX IF NOT EXISTS idx_agent_keys_address ON agent_keys(agent_address);
+CREATE INDEX IF NOT EXISTS idx_refresh_families_wallet ON refresh_families(wallet_id, status);
 CREATE INDEX IF NOT EXISTS idx_jwt_secret_meta_status ON jwt_secret_meta(status);
 CREATE INDEX IF NOT EXISTS idx_p256_session_gc ON p256_session_keys(wallet_id, status, credential_expires_at);
 CREATE INDEX IF NOT EXISTS idx_contact_binding_code ON contact_bindings(binding_code);
+CREATE INDEX IF NOT EXISTS idx_account_audit_account ON account_audit(account, id);
+CREATE INDEX IF NOT EXISTS idx_offline_requests_expire ON offline_requests(expires_at);
+CREATE INDEX IF NOT EXISTS idx_chain_events_key ON chain_events(key_id, id);
+CREATE INDEX IF NOT EXISTS idx_tx_history_account ON tx_history(address, chain_id, status, nonce);
+CREATE INDEX IF NOT EXISTS idx_identity_links_identity ON identity_links(identity_id);
+CREATE INDEX IF NOT EXISTS idx_gas_tank_topups_tank ON gas_tank_topups(address, chain_id, status);
+CREATE INDEX IF NOT EXISTS idx_ca_events_aggregate ON ca_events(aggregate, seq);
 "#;
 
 // ── TX stats ──
@@ -168,7 +546,7 @@ pub struct TxStats {
 
 // ── Row types ──
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct WalletRow {
     pub key_id: String,
     pub address: Option<String>,
@@ -186,6 +564,15 @@ pub struct WalletRow {
     pub created_at: String,
 }
 
+/// Registration attestation of a wallet's passkey.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PasskeyAttestation {
+    pub attestation_type: Option<String>,
+    pub aaguid: Option<String>,
+    pub certification: Option<String>,
+    pub role: Option<String>,
+}
+
 #[derive(Debug, Clone)]
 pub struct AddressRow {
     pub address: String,
@@ -217,6 +604,357 @@ pub struct ContactBinding {
     pub verified_at: Option<i64>,
 }
 
+/// Per-op tx_log totals for one key over a period (activity statements).
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct OpCount {
+    pub op: String,
+    pub succeeded: i64,
+    pub failed: i64,
+}
+
+/// Who asked for a signing op, and the native value it moved (usage.rs).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxUsage {
+    pub origin: String,
+    pub chain_id: Option<u64>,
+    pub value_wei: Option<u128>,
+}
+
+/// tx_log totals of one op from one origin ('' = untagged).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OriginOpCount {
+    pub origin: String,
+    pub op: String,
+    pub succeeded: i64,
+    pub failed: i64,
+}
+
+/// Native value one successful, tagged signing op moved.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OriginValue {
+    pub origin: String,
+    pub chain_id: u64,
+    pub value_wei: u128,
+}
+
+#[derive(Debug, Clone)]
+pub struct AccountEvent {
+    pub id: i64,
+    pub event: String,
+    pub detail: Option<String>,
+    pub created_at: i64,
+    pub correlation_id: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct OfflineRequestRow {
+    pub request_id: String,
+    pub key_id: String,
+    pub address: String,
+    pub request: String,
+    pub status: String,
+    pub tx_hash: Option<String>,
+    pub expires_at: i64,
+}
+
+/// A signed transaction about to enter the ledger.
+#[derive(Debug, Clone)]
+pub struct SignedTxEntry {
+    pub key_id: String,
+    pub address: String,
+    pub derivation_path: String,
+    pub chain_id: u64,
+    pub nonce: u64,
+    pub gas_price: u128,
+    pub signed_tx: String,
+    pub tx_hash: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct TxHistoryRow {
+    pub id: i64,
+    pub entry: SignedTxEntry,
+    pub status: String,
+    pub created_at: i64,
+}
+
+fn tx_history_row(row: &rusqlite::Row) -> rusqlite::Result<TxHistoryRow> {
+    let gas_price: String = row.get(6)?;
+    Ok(TxHistoryRow {
+        id: row.get(0)?,
+        entry: SignedTxEntry {
+            key_id: row.get(1)?,
+            address: row.get(2)?,
+            derivation_path: row.get(3)?,
+            chain_id: row.get::<_, i64>(4)? as u64,
+            nonce: row.get::<_, i64>(5)? as u64,
+            gas_price: gas_price.parse().unwrap_or(0),
+            signed_tx: row.get(7)?,
+            tx_hash: row.get(8)?,
+        },
+        status: row.get(9)?,
+        created_at: row.get(10)?,
+    })
+}
+
+/// An on-chain event touching a watched address (chain_watch.rs).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainEvent {
+    pub key_id: String,
+    pub address: String,
+    pub chain_id: u64,
+    pub kind: String,
+    pub token: Option<String>,
+    pub from_address: Option<String>,
+    pub to_address: Option<String>,
+    pub value: Option<String>,
+    pub topic0: Option<String>,
+    pub tx_hash: String,
+    pub block_number: u64,
+    pub log_index: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct ChainEventRow {
+    pub id: i64,
+    pub event: ChainEvent,
+    pub removed: bool,
+    pub created_at: i64,
+}
+
+/// A registered stealth meta-address and how far it has been scanned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StealthMetaRow {
+    pub key_id: String,
+    pub account: u32,
+    pub meta_address: String,
+    pub scanned_through: i64,
+    pub created_at: i64,
+}
+
+/// A wallet created by bulk provisioning and the tenant it belongs to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TenantWalletRow {
+    pub key_id: String,
+    pub tenant_id: String,
+    pub tags: Vec<String>,
+    pub batch_id: String,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProvisioningBatchRow {
+    pub batch_id: String,
+    pub tenant_id: String,
+    pub report: String,
+    pub attestation: String,
+    pub created_at: i64,
+}
+
+/// A paymaster quote the CA fetched for a key (paymaster.rs).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeeQuoteEntry {
+    pub quote_hash: String,
+    pub key_id: String,
+    pub chain_id: u64,
+    pub paymaster: String,
+    pub token: String,
+    pub max_token_cost: u128,
+    pub quote: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct FeePaymentRow {
+    pub entry: FeeQuoteEntry,
+    pub user_op_hash: Option<String>,
+    pub status: String,
+    pub actual_token_cost: Option<u128>,
+    pub tx_hash: Option<String>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+/// A refresh-token family as the CA tracks it; the secrets are only in the TA.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RefreshFamilyRow {
+    pub key_id: String,
+    pub wallet_id: String,
+    pub credential_id: Option<String>,
+    pub device_hash: String,
+    pub generation: u32,
+    pub status: String,
+    pub revoked_reason: Option<String>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+const FEE_PAYMENT_COLUMNS: &str = "quote_hash, key_id, chain_id, paymaster, token, \
+     max_token_cost, quote, user_op_hash, status, actual_token_cost, tx_hash, created_at, \
+     updated_at";
+
+fn fee_payment_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<FeePaymentRow> {
+    let max: String = row.get(5)?;
+    let actual: Option<String> = row.get(9)?;
+    Ok(FeePaymentRow {
+        entry: FeeQuoteEntry {
+            quote_hash: row.get(0)?,
+            key_id: row.get(1)?,
+            chain_id: row.get::<_, i64>(2)? as u64,
+            paymaster: row.get(3)?,
+            token: row.get(4)?,
+            max_token_cost: max.parse().unwrap_or(0),
+            quote: row.get(6)?,
+        },
+        user_op_hash: row.get(7)?,
+        status: row.get(8)?,
+        actual_token_cost: actual.and_then(|a| a.parse().ok()),
+        tx_hash: row.get(10)?,
+        created_at: row.get(11)?,
+        updated_at: row.get(12)?,
+    })
+}
+
+const CHAIN_EVENT_COLUMNS: &str = "id, key_id, address, chain_id, kind, token, from_address, \
+     to_address, value, topic0, tx_hash, block_number, log_index, removed, created_at";
+
+fn chain_event_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<ChainEventRow> {
+    Ok(ChainEventRow {
+        id: row.get(0)?,
+        event: ChainEvent {
+            key_id: row.get(1)?,
+            address: row.get(2)?,
+            chain_id: row.get::<_, i64>(3)? as u64,
+            kind: row.get(4)?,
+            token: row.get(5)?,
+            from_address: row.get(6)?,
+            to_address: row.get(7)?,
+            value: row.get(8)?,
+            topic0: row.get(9)?,
+            tx_hash: row.get(10)?,
+            block_number: row.get::<_, i64>(11)? as u64,
+            log_index: row.get(12)?,
+        },
+        removed: row.get::<_, i64>(13)? != 0,
+        created_at: row.get(14)?,
+    })
+}
+
+/// One device in a wallet's replica set.
+#[derive(Debug, Clone)]
+pub struct WalletDeviceRow {
+    pub key_id: String,
+    pub device_id: String,
+    pub endpoint: Option<String>,
+    pub attestation_key_fp: String,
+    pub status: String,
+    pub added_at: i64,
+    pub revoked_at: Option<i64>,
+    pub last_seen: i64,
+    pub quarantined: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceHealthCheckRow {
+    pub id: i64,
+    pub checked_at: i64,
+    pub passed: bool,
+    pub source: String,
+    pub report: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceQuarantineRow {
+    pub reason: String,
+    pub check_id: Option<i64>,
+    pub since: i64,
+}
+
+/// A wallet signed for outside the TA.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WalletSignerRow {
+    pub key_id: String,
+    pub backend: String,
+    pub key_label: String,
+    pub derivation_path: String,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdentityRow {
+    pub identity_id: String,
+    pub label: String,
+    pub owner_key_id: String,
+    pub created_at: i64,
+}
+
+/// A wallet address linked to an identity.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdentityLinkRow {
+    pub address: String,
+    pub identity_id: String,
+    pub key_id: String,
+    pub derivation_path: String,
+    pub chain_ids: Vec<u64>,
+    pub statement: String,
+    pub signature: String,
+    pub linked_at: i64,
+}
+
+/// Where a gas tank's refills come from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GasTankSource {
+    pub amount_wei: u128,
+    pub treasury_key_id: String,
+    pub treasury_path: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GasTankRow {
+    pub address: String,
+    pub chain_id: u64,
+    pub label: String,
+    pub min_balance_wei: u128,
+    /// None = alert only.
+    pub top_up: Option<GasTankSource>,
+    pub balance_wei: Option<u128>,
+    pub checked_at: Option<i64>,
+    pub low_since: Option<i64>,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GasTankTopUpRow {
+    pub id: i64,
+    pub address: String,
+    pub chain_id: u64,
+    pub source: GasTankSource,
+    pub status: String,
+    pub approved_by: Option<String>,
+    pub transaction: Option<String>,
+    pub tx_hash: Option<String>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteApproverRow {
+    pub key_id: String,
+    /// Hex, no 0x.
+    pub approver_key: String,
+    pub platform: String,
+    pub push_token: String,
+    pub paired_at: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct KeyRegionRow {
+    pub key_id: String,
+    pub region: String,
+    pub device_id: String,
+    pub role: String,
+    pub created_at: i64,
+}
+
 #[derive(Debug, Clone)]
 pub struct AgentKeyRow {
     pub wallet_id: String,
@@ -257,6 +995,168 @@ pub struct P256SessionKeyRow {
     pub revoked_at: Option<String>,
 }
 
+#[derive(Debug, Clone)]
+pub struct ScopedSessionKeyRow {
+    pub wallet_id: String,
+    pub session_index: u32,
+    pub session_address: String,
+    pub permission_hash: String,
+    pub chain_id: u64,
+    pub expires_at: i64,
+    pub status: String,
+    pub created_at: String,
+    pub revoked_at: Option<String>,
+}
+
+// ── Migrations ──
+
+/// A column added after the table first shipped. `SCHEMA` creates fresh DBs
+/// with every column already present; these bring older DBs up to date.
+struct Migration {
+    table: &'static str,
+    column: &'static str,
+    decl: &'static str,
+}
+
+/// Append-only. `PRAGMA user_version` records how many have been applied, so
+/// a step runs at most once per DB — but each is still idempotent, because DBs
+/// upgraded before versioning existed have the columns at user_version 0.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        table: "p256_session_keys",
+        column: "tee_deleted",
+        decl: "INTEGER NOT NULL DEFAULT 0",
+    },
+    // Issue #42 (dormant-key freeze). Existing rows default to 'active'.
+    Migration {
+        table: "wallets",
+        column: "lifecycle_status",
+        decl: "TEXT NOT NULL DEFAULT 'active'",
+    },
+    // Audit export envelopes (proto::event) carry the causing request's id.
+    Migration {
+        table: "account_audit",
+        column: "correlation_id",
+        decl: "TEXT",
+    },
+    // Passkey attestation checked against FIDO MDS (fido_mds).
+    Migration {
+        table: "wallets",
+        column: "attestation_type",
+        decl: "TEXT",
+    },
+    Migration {
+        table: "wallets",
+        column: "aaguid",
+        decl: "TEXT",
+    },
+    Migration {
+        table: "wallets",
+        column: "certification",
+        decl: "TEXT",
+    },
+    Migration {
+        table: "wallets",
+        column: "passkey_role",
+        decl: "TEXT",
+    },
+    // Device health quarantine (device_health), reported in heartbeats.
+    Migration {
+        table: "wallet_devices",
+        column: "quarantined",
+        decl: "INTEGER NOT NULL DEFAULT 0",
+    },
+    // Usage reports per dapp origin (usage.rs). NULL on rows logged before.
+    Migration {
+        table: "tx_log",
+        column: "origin",
+        decl: "TEXT",
+    },
+    Migration {
+        table: "tx_log",
+        column: "chain_id",
+        decl: "INTEGER",
+    },
+    Migration {
+        table: "tx_log",
+        column: "value_wei",
+        decl: "TEXT",
+    },
+];
+
+fn column_exists(conn: &Connection, table: &str, column: &str) -> Result<bool> {
+    let mut stmt = conn
+        .prepare(&format!("PRAGMA table_info({})", table))
+        .with_context(|| format!("Failed to query {} schema", table))?;
+    let names: Vec<String> = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<rusqlite::Result<_>>()
+        .with_context(|| format!("Failed to read {} schema", table))?;
+    Ok(names.iter().any(|n| n == column))
+}
+
+fn migrate(conn: &Connection) -> Result<()> {
+    let applied: u32 = conn.query_row("PRAGMA user_version", [], |r| r.get(0))?;
+    for (i, m) in MIGRATIONS.iter().enumerate().skip(applied as usize) {
+        // PRAGMA table_info separates "already exists" from real errors (disk
+        // full, corruption). TOCTOU against a concurrent open is handled by
+        // re-checking on ALTER failure: only propagate if the column is still absent.
+        if !column_exists(conn, m.table, m.column)? {
+            if let Err(alter_err) = conn.execute_batch(&format!(
+                "ALTER TABLE {} ADD COLUMN {} {};",
+                m.table, m.column, m.decl
+            )) {
+                if !column_exists(conn, m.table, m.column)
+                    .context("Re-check after ALTER TABLE failure")?
+                {
+                    return Err(alter_err).with_context(|| {
+                        format!("Failed to add {} column to {}", m.column, m.table)
+                    });
+                }
+            }
+        }
+        conn.execute_batch(&format!("PRAGMA user_version = {};", i + 1))
+            .context("Failed to record schema version")?;
+    }
+    Ok(())
+}
+
+/// Schema version this build migrates every DB to.
+pub fn current_schema_version() -> u32 {
+    MIGRATIONS.len() as u32
+}
+
+/// What a DB file holds, read without migrating it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DbFileInfo {
+    pub schema_version: u32,
+    pub wallets: u64,
+}
+
+/// Open `path` read-only, run SQLite's quick_check and report its schema
+/// version and wallet count. Used to vet a backup before it replaces the
+/// live DB.
+pub fn inspect_file(path: &Path) -> Result<DbFileInfo> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .with_context(|| format!("Failed to open {} read-only", path.display()))?;
+    let check: String = conn.query_row("PRAGMA quick_check", [], |r| r.get(0))?;
+    if check != "ok" {
+        return Err(anyhow::anyhow!(
+            "{} failed integrity check: {}",
+            path.display(),
+            check
+        ));
+    }
+    let schema_version = conn.query_row("PRAGMA user_version", [], |r| r.get(0))?;
+    let wallets: i64 = conn
+        .query_row("SELECT COUNT(*) FROM wallets", [], |r| r.get(0))
+        .context("no wallets table")?;
+    Ok(DbFileInfo {
+        schema_version,
+        wallets: wallets as u64,
+    })
+}
+
 // ── KmsDb ──
 
 #[derive(Clone)]
@@ -266,7 +1166,7 @@ pub struct KmsDb {
 
 impl KmsDb {
     pub fn open(path: &str) -> Result<Self> {
-        let conn = Connection::open(path)
+        let mut conn = Connection::open(path)
             .with_context(|| format!("Failed to open SQLite DB at {}", path))?;
         // Prevent SQLITE_BUSY on schema init and migration: retry automatically for up to 5s
         // before returning an error. DDL operations on a shared WAL-mode DB can be transiently
@@ -275,68 +1175,11 @@ impl KmsDb {
             .context("Failed to set SQLite busy timeout")?;
         conn.execute_batch(SCHEMA)
             .context("Failed to initialize DB schema")?;
-        // Migration: add tee_deleted column to DBs created before this column existed.
-        // Uses PRAGMA table_info to distinguish "already exists" (safe to skip) from real
-        // errors (disk full, corruption) that must propagate. TOCTOU is handled by re-verifying
-        // on ALTER failure: if concurrent open already added the column, we treat it as success.
-        {
-            let check_col_exists = |c: &Connection| -> Result<bool> {
-                let mut stmt = c
-                    .prepare("PRAGMA table_info(p256_session_keys)")
-                    .context("Failed to query p256_session_keys schema")?;
-                let names: Vec<String> = stmt
-                    .query_map([], |row| row.get::<_, String>(1))?
-                    .collect::<rusqlite::Result<_>>()
-                    .context("Failed to read p256_session_keys schema")?;
-                Ok(names.iter().any(|n| n == "tee_deleted"))
-            };
-            if !check_col_exists(&conn)? {
-                match conn.execute_batch(
-                    "ALTER TABLE p256_session_keys \
-                     ADD COLUMN tee_deleted INTEGER NOT NULL DEFAULT 0;",
-                ) {
-                    Ok(()) => {}
-                    Err(alter_err) => {
-                        // Re-verify: concurrent process may have added the column between
-                        // our check and the ALTER. Only propagate if column still absent.
-                        if !check_col_exists(&conn).context("Re-check after ALTER TABLE failure")? {
-                            return Err(alter_err)
-                                .context("Failed to add tee_deleted column to p256_session_keys");
-                        }
-                    }
-                }
-            }
-        }
-        // Migration: add lifecycle_status column to wallets for DBs created before
-        // issue #42 (dormant-key freeze). Same idempotent PRAGMA-check + ALTER pattern
-        // as tee_deleted above. Existing rows default to 'active'.
-        {
-            let check_col_exists = |c: &Connection| -> Result<bool> {
-                let mut stmt = c
-                    .prepare("PRAGMA table_info(wallets)")
-                    .context("Failed to query wallets schema")?;
-                let names: Vec<String> = stmt
-                    .query_map([], |row| row.get::<_, String>(1))?
-                    .collect::<rusqlite::Result<_>>()
-                    .context("Failed to read wallets schema")?;
-                Ok(names.iter().any(|n| n == "lifecycle_status"))
-            };
-            if !check_col_exists(&conn)? {
-                match conn.execute_batch(
-                    "ALTER TABLE wallets \
-                     ADD COLUMN lifecycle_status TEXT NOT NULL DEFAULT 'active';",
-                ) {
-                    Ok(()) => {}
-                    Err(alter_err) => {
-                        // Re-verify: concurrent process may have added the column between
-                        // our check and the ALTER. Only propagate if column still absent.
-                        if !check_col_exists(&conn).context("Re-check after ALTER TABLE failure")? {
-                            return Err(alter_err)
-                                .context("Failed to add lifecycle_status column to wallets");
-                        }
-                    }
-                }
-            }
+        migrate(&conn).context("Failed to migrate DB schema")?;
+        let snapshot =
+            event_store::bootstrap(&mut conn).context("Failed to start the event log")?;
+        if snapshot > 0 {
+            eprintln!("📦 Event log started from {} existing rows", snapshot);
         }
         // stderr, not stdout: the `api-key generate` CLI prints the new key to
         // stdout, so keep this diagnostic off stdout to allow clean capture,
@@ -361,36 +1204,143 @@ impl KmsDb {
         self.conn.lock().expect("DB mutex poisoned")
     }
 
-    // ── Wallet CRUD ──
+    /// Run DB work on tokio's blocking pool. Every call takes the connection
+    /// mutex and may sit in SQLite's 5s busy retry, which must not park an
+    /// async worker that is also serving other requests.
+    pub async fn run<F, T>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&KmsDb) -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let db = self.clone();
+        tokio::task::spawn_blocking(move || f(&db))
+            .await
+            .context("DB task panicked")?
+    }
 
-    pub fn insert_wallet(&self, w: &WalletRow) -> Result<()> {
+    /// Number of entries in [`MIGRATIONS`] applied to this DB.
+    pub fn schema_version(&self) -> Result<u32> {
         let conn = self.lock();
-        conn.execute(
-            "INSERT INTO wallets (key_id, address, public_key, derivation_path, description, \
-             key_usage, key_spec, origin, passkey_pubkey, credential_id, sign_count, status, \
-             error_msg, created_at) VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10,?11,?12,?13,?14)",
-            params![
-                w.key_id,
-                w.address,
-                w.public_key,
-                w.derivation_path,
-                w.description,
-                w.key_usage,
-                w.key_spec,
-                w.origin,
-                w.passkey_pubkey,
-                w.credential_id,
-                w.sign_count,
-                w.status,
-                w.error_msg,
-                w.created_at,
-            ],
-        )
-        .context("insert_wallet")?;
+        Ok(conn.query_row("PRAGMA user_version", [], |r| r.get(0))?)
+    }
+
+    /// Consistent copy of the whole DB into a new file at `path` (WAL folded
+    /// in), safe while the API server keeps writing.
+    pub fn snapshot_to(&self, path: &Path) -> Result<()> {
+        let target = path
+            .to_str()
+            .ok_or_else(|| anyhow::anyhow!("{} is not UTF-8", path.display()))?;
+        let conn = self.lock();
+        conn.execute("VACUUM INTO ?1", params![target])
+            .with_context(|| format!("Failed to snapshot DB into {}", path.display()))?;
         Ok(())
     }
 
-    pub fn get_wallet(&self, key_id: &str) -> Result<Option<WalletRow>> {
+    // ── Wallet CRUD ──
+
+    // ── Event log (event_store.rs) ──
+
+    /// Apply `event` and append it to `ca_events` in one transaction.
+    /// Returns the rows it changed; a no-op is not logged.
+    fn record(&self, event: CaEvent) -> Result<usize> {
+        let mut conn = self.lock();
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let n = event_store::record(&tx, &event)?;
+        tx.commit()?;
+        Ok(n)
+    }
+
+    /// Events after `seq`, oldest first: what a replica that has applied
+    /// up to `seq` needs next.
+    pub fn events_after(&self, seq: u64, limit: usize) -> Result<Vec<EventRecord>> {
+        let conn = self.lock();
+        event_store::events_after(&conn, seq, limit)
+    }
+
+    /// Every event recorded against a key id (or, for confirmations, an
+    /// address).
+    pub fn events_for(&self, aggregate: &str) -> Result<Vec<EventRecord>> {
+        let conn = self.lock();
+        event_store::events_for(&conn, aggregate)
+    }
+
+    pub fn verify_event_chain(&self) -> Result<ChainHead> {
+        let conn = self.lock();
+        event_store::verify_chain(&conn)
+    }
+
+    /// Replay the log into a scratch DB and list the rows where the live
+    /// projections differ. Empty means they are exactly what the log says.
+    pub fn verify_projections(&self) -> Result<Vec<ProjectionDiff>> {
+        let scratch = Connection::open_in_memory()?;
+        scratch.execute_batch(SCHEMA)?;
+        // As in a rebuild: the log alone decides what the projections hold.
+        scratch.execute_batch("PRAGMA foreign_keys=OFF")?;
+        let conn = self.lock();
+        event_store::replay(&conn, &scratch)?;
+        event_store::diff_projections(&conn, &scratch)
+    }
+
+    /// Rebuild wallets, address_index and tx_history from the log. Holds
+    /// the write lock for the whole replay.
+    pub fn rebuild_projections(&self) -> Result<RebuildReport> {
+        let mut conn = self.lock();
+        event_store::rebuild(&mut conn)
+    }
+
+    // ── Active-standby replication (standby.rs) ──
+
+    pub fn standby_state(&self) -> Result<Option<StandbyState>> {
+        let conn = self.lock();
+        standby::state(&conn)
+    }
+
+    pub fn init_standby(&self, configured: Role, now: i64) -> Result<StandbyState> {
+        let conn = self.lock();
+        standby::init(&conn, configured, now)
+    }
+
+    pub fn observe_fencing_token(&self, token: u64, now: i64) -> Result<StandbyState> {
+        let conn = self.lock();
+        standby::observe_token(&conn, token, now)
+    }
+
+    pub fn promote_standby(&self, token: u64, now: i64) -> Result<StandbyState> {
+        let mut conn = self.lock();
+        standby::promote(&mut conn, token, now)
+    }
+
+    /// Seq and hash of the last event.
+    pub fn event_head(&self) -> Result<(u64, String)> {
+        let conn = self.lock();
+        event_store::head(&conn)
+    }
+
+    pub fn standby_pull_request(&self) -> Result<PullRequest> {
+        let conn = self.lock();
+        standby::pull_request(&conn)
+    }
+
+    pub fn serve_standby_sync(&self, req: &PullRequest, now: i64) -> Result<SyncBatch> {
+        let conn = self.lock();
+        standby::serve_sync(&conn, req, now)
+    }
+
+    /// Apply a batch from the active; holds the write lock throughout.
+    pub fn apply_standby_batch(&self, batch: &SyncBatch, now: i64) -> Result<usize> {
+        let mut conn = self.lock();
+        standby::apply_batch(&mut conn, batch, now)
+    }
+
+    // ── Wallet CRUD ──
+
+    pub fn insert_wallet(&self, w: &WalletRow) -> Result<()> {
+        self.record(CaEvent::WalletCreated(w.clone()))
+            .context("insert_wallet")?;
+        Ok(())
+    }
+
+    pub fn get_wallet(&self, key_id: &str) -> Result<Option<WalletRow>> {
         let conn = self.lock();
         let mut stmt = conn.prepare(
             "SELECT key_id, address, public_key, derivation_path, description, key_usage, \
@@ -439,12 +1389,13 @@ impl KmsDb {
         derivation_path: &str,
         status: &str,
     ) -> Result<()> {
-        let conn = self.lock();
-        conn.execute(
-            "UPDATE wallets SET address=?2, public_key=?3, derivation_path=?4, status=?5 \
-             WHERE key_id=?1",
-            params![key_id, address, public_key, derivation_path, status],
-        )?;
+        self.record(CaEvent::WalletDerived {
+            key_id: key_id.to_string(),
+            address: address.to_string(),
+            public_key: public_key.to_string(),
+            derivation_path: derivation_path.to_string(),
+            status: status.to_string(),
+        })?;
         Ok(())
     }
 
@@ -454,11 +1405,11 @@ impl KmsDb {
         status: &str,
         error_msg: Option<&str>,
     ) -> Result<()> {
-        let conn = self.lock();
-        conn.execute(
-            "UPDATE wallets SET status=?2, error_msg=?3 WHERE key_id=?1",
-            params![key_id, status, error_msg],
-        )?;
+        self.record(CaEvent::WalletStatusChanged {
+            key_id: key_id.to_string(),
+            status: status.to_string(),
+            error_msg: error_msg.map(str::to_string),
+        })?;
         Ok(())
     }
 
@@ -468,26 +1419,38 @@ impl KmsDb {
         passkey_pubkey: &str,
         credential_id: Option<&str>,
     ) -> Result<()> {
-        let conn = self.lock();
-        conn.execute(
-            "UPDATE wallets SET passkey_pubkey=?2, credential_id=?3 WHERE key_id=?1",
-            params![key_id, passkey_pubkey, credential_id],
+        let mut conn = self.lock();
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        event_store::record(
+            &tx,
+            &CaEvent::PasskeyChanged {
+                key_id: key_id.to_string(),
+                passkey_pubkey: passkey_pubkey.to_string(),
+                credential_id: credential_id.map(str::to_string),
+            },
+        )?;
+        // Refresh tokens were issued against the old credential.
+        tx.execute(
+            "UPDATE refresh_families SET status='revoked', revoked_reason='passkey changed', \
+             updated_at=?2 WHERE wallet_id=?1 AND status='active'",
+            params![key_id, current_unix()],
         )?;
+        tx.commit()?;
         Ok(())
     }
 
     pub fn update_wallet_sign_count(&self, key_id: &str, sign_count: u32) -> Result<()> {
-        let conn = self.lock();
-        conn.execute(
-            "UPDATE wallets SET sign_count=?2 WHERE key_id=?1",
-            params![key_id, sign_count],
-        )?;
+        self.record(CaEvent::SignCountAdvanced {
+            key_id: key_id.to_string(),
+            sign_count,
+        })?;
         Ok(())
     }
 
     pub fn delete_wallet(&self, key_id: &str) -> Result<()> {
-        let conn = self.lock();
-        conn.execute("DELETE FROM wallets WHERE key_id=?1", params![key_id])?;
+        self.record(CaEvent::WalletDeleted {
+            key_id: key_id.to_string(),
+        })?;
         Ok(())
     }
 
@@ -548,6 +1511,28 @@ impl KmsDb {
         Ok(v)
     }
 
+    /// Per-op success/failure counts for a key in a calendar month (`YYYY-MM`),
+    /// matched by key_id or signing address exactly like last_used_at.
+    pub fn activity_for_month(&self, key_id: &str, month: &str) -> Result<Vec<OpCount>> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare(
+            "SELECT op, SUM(success=1), SUM(success=0) FROM tx_log \
+             WHERE created_at LIKE ?2 AND ( \
+               key_id=?1 \
+               OR addr=(SELECT address FROM wallets WHERE key_id=?1) \
+               OR addr IN (SELECT address FROM address_index WHERE key_id=?1) \
+             ) GROUP BY op ORDER BY op",
+        )?;
+        let rows = stmt.query_map(params![key_id, format!("{}-%", month)], |row| {
+            Ok(OpCount {
+                op: row.get(0)?,
+                succeeded: row.get(1)?,
+                failed: row.get(2)?,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
     /// Current lifecycle_status for a key ('active' | 'frozen'), or None if the
     /// key_id does not exist.
     pub fn get_lifecycle_status(&self, key_id: &str) -> Result<Option<String>> {
@@ -562,12 +1547,42 @@ impl KmsDb {
 
     /// Set lifecycle_status for a key. Returns true if a row was updated.
     pub fn set_lifecycle_status(&self, key_id: &str, status: &str) -> Result<bool> {
+        let n = self.record(CaEvent::LifecycleChanged {
+            key_id: key_id.to_string(),
+            status: status.to_string(),
+        })?;
+        Ok(n > 0)
+    }
+
+    /// Record how the wallet's passkey was attested at registration.
+    pub fn set_passkey_attestation(&self, key_id: &str, a: &PasskeyAttestation) -> Result<bool> {
+        let n = self.record(CaEvent::PasskeyAttested {
+            key_id: key_id.to_string(),
+            attestation: a.clone(),
+        })?;
+        Ok(n > 0)
+    }
+
+    /// None for an unknown key; a wallet registered before attestation was
+    /// recorded has every field None.
+    pub fn get_passkey_attestation(&self, key_id: &str) -> Result<Option<PasskeyAttestation>> {
         let conn = self.lock();
-        let n = conn.execute(
-            "UPDATE wallets SET lifecycle_status=?2 WHERE key_id=?1",
-            params![key_id, status],
+        let mut stmt = conn.prepare(
+            "SELECT attestation_type, aaguid, certification, passkey_role FROM wallets \
+             WHERE key_id=?1",
         )?;
-        Ok(n > 0)
+        let mut rows = stmt.query_map(params![key_id], |row| {
+            Ok(PasskeyAttestation {
+                attestation_type: row.get(0)?,
+                aaguid: row.get(1)?,
+                certification: row.get(2)?,
+                role: row.get(3)?,
+            })
+        })?;
+        match rows.next() {
+            Some(r) => Ok(Some(r?)),
+            None => Ok(None),
+        }
     }
 
     /// Auto-freeze dormant keys: set lifecycle_status='frozen' for every currently
@@ -578,7 +1593,7 @@ impl KmsDb {
     /// Returns the list of key_ids that were frozen.
     pub fn freeze_dormant_keys(&self, now_unix: i64, threshold_secs: i64) -> Result<Vec<String>> {
         let cutoff = now_unix - threshold_secs;
-        let conn = self.lock();
+        let mut conn = self.lock();
         // created_at (wallets + tx_log) is RFC3339 text; strftime('%s', ...) parses it
         // to a unix epoch for comparison. COALESCE picks the latest signing activity,
         // falling back to wallet creation time.
@@ -602,13 +1617,17 @@ impl KmsDb {
             .query_map(params![cutoff], |row| row.get::<_, String>(0))?
             .collect::<rusqlite::Result<_>>()?;
         drop(stmt);
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
         for id in &ids {
-            conn.execute(
-                "UPDATE wallets SET lifecycle_status='frozen' \
-                 WHERE key_id=?1 AND lifecycle_status='active'",
-                params![id],
+            event_store::record(
+                &tx,
+                &CaEvent::LifecycleChanged {
+                    key_id: id.clone(),
+                    status: "frozen".to_string(),
+                },
             )?;
         }
+        tx.commit()?;
         Ok(ids)
     }
 
@@ -626,14 +1645,78 @@ impl KmsDb {
         // case-insensitive consumer (SDK passes checksummed, DVT passes userOp.sender) would
         // miss it → contact/verify fail-closed (silent, hard to debug). hex::encode already
         // emits lowercase today; this nails it regardless of caller case.
+        self.record(CaEvent::AddressIndexed {
+            address: address.to_lowercase(),
+            key_id: key_id.to_string(),
+            derivation_path: derivation_path.to_string(),
+            public_key: public_key.map(str::to_string),
+        })?;
+        Ok(())
+    }
+
+    /// Pin the first address derived for (key_id, path). Never replaces a pinned
+    /// row: a different answer later is reported, not stored.
+    pub fn pin_address(
+        &self,
+        key_id: &str,
+        derivation_path: &str,
+        address: &str,
+        public_key: Option<&str>,
+    ) -> Result<PinCheck> {
         let address = address.to_lowercase();
-        let conn = self.lock();
-        conn.execute(
-            "INSERT OR REPLACE INTO address_index (address, key_id, derivation_path, public_key) \
-             VALUES (?1,?2,?3,?4)",
-            params![address, key_id, derivation_path, public_key],
+        let mut conn = self.lock();
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let mut stmt = tx.prepare(
+            "SELECT address, key_id, derivation_path FROM address_index \
+             WHERE address=?1 OR (key_id=?2 AND derivation_path=?3)",
         )?;
-        Ok(())
+        let rows = stmt
+            .query_map(params![address, key_id, derivation_path], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        drop(stmt);
+        for (pinned, k, p) in &rows {
+            if k == key_id && p == derivation_path && *pinned != address {
+                return Ok(PinCheck::Mismatch {
+                    pinned: pinned.clone(),
+                });
+            }
+            if *pinned == address && (k != key_id || p != derivation_path) {
+                return Ok(PinCheck::Conflict {
+                    key_id: k.clone(),
+                    derivation_path: p.clone(),
+                });
+            }
+        }
+        if !rows.is_empty() {
+            if let Some(public_key) = public_key {
+                event_store::record(
+                    &tx,
+                    &CaEvent::AddressPublicKeyFilled {
+                        address,
+                        public_key: public_key.to_string(),
+                    },
+                )?;
+                tx.commit()?;
+            }
+            return Ok(PinCheck::Matches);
+        }
+        event_store::record(
+            &tx,
+            &CaEvent::AddressIndexed {
+                address,
+                key_id: key_id.to_string(),
+                derivation_path: derivation_path.to_string(),
+                public_key: public_key.map(str::to_string),
+            },
+        )?;
+        tx.commit()?;
+        Ok(PinCheck::Pinned)
     }
 
     /// #52: look up the cached Ethereum address for a (key_id, derivation_path)
@@ -1199,6 +2282,84 @@ impl KmsDb {
         Ok(updated)
     }
 
+    // ── Scoped session keys ──
+
+    /// Next unused index for a wallet. Revoked rows are kept, so an index is
+    /// never handed out twice (the TA refuses revoked/issued indices anyway).
+    pub fn next_scoped_session_index(&self, wallet_id: &str) -> Result<u32> {
+        let conn = self.lock();
+        let next: i64 = conn.query_row(
+            "SELECT COALESCE(MAX(session_index)+1, 0) FROM scoped_session_keys WHERE wallet_id=?1",
+            params![wallet_id],
+            |row| row.get(0),
+        )?;
+        Ok(next as u32)
+    }
+
+    pub fn insert_scoped_session_key(&self, row: &ScopedSessionKeyRow) -> Result<()> {
+        let conn = self.lock();
+        conn.execute(
+            "INSERT INTO scoped_session_keys \
+             (wallet_id, session_index, session_address, permission_hash, chain_id, \
+              expires_at, status, created_at, revoked_at) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                row.wallet_id,
+                row.session_index,
+                row.session_address,
+                row.permission_hash,
+                row.chain_id as i64,
+                row.expires_at,
+                row.status,
+                row.created_at,
+                row.revoked_at
+            ],
+        )
+        .context("insert_scoped_session_key")?;
+        Ok(())
+    }
+
+    pub fn get_scoped_session_key(
+        &self,
+        wallet_id: &str,
+        session_index: u32,
+    ) -> Result<Option<ScopedSessionKeyRow>> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare(
+            "SELECT wallet_id, session_index, session_address, permission_hash, chain_id, \
+             expires_at, status, created_at, revoked_at \
+             FROM scoped_session_keys WHERE wallet_id=?1 AND session_index=?2",
+        )?;
+        let mut rows = stmt.query_map(params![wallet_id, session_index], |row| {
+            Ok(ScopedSessionKeyRow {
+                wallet_id: row.get(0)?,
+                session_index: row.get::<_, u32>(1)?,
+                session_address: row.get(2)?,
+                permission_hash: row.get(3)?,
+                chain_id: row.get::<_, i64>(4)? as u64,
+                expires_at: row.get(5)?,
+                status: row.get(6)?,
+                created_at: row.get(7)?,
+                revoked_at: row.get(8)?,
+            })
+        })?;
+        match rows.next() {
+            Some(row) => Ok(Some(row?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn revoke_scoped_session_key(&self, wallet_id: &str, session_index: u32) -> Result<bool> {
+        let now = Utc::now().to_rfc3339();
+        let conn = self.lock();
+        let updated = conn.execute(
+            "UPDATE scoped_session_keys SET status='revoked', revoked_at=?3 \
+             WHERE wallet_id=?1 AND session_index=?2 AND status != 'revoked'",
+            params![wallet_id, session_index, now],
+        )?;
+        Ok(updated > 0)
+    }
+
     // ── Challenge management ──
 
     pub fn store_challenge(
@@ -1220,15 +2381,41 @@ impl KmsDb {
         Ok(())
     }
 
+    /// When an unexpired challenge was issued, without consuming it.
+    pub fn challenge_created_at(&self, id: &str) -> Result<Option<i64>> {
+        let conn = self.lock();
+        Ok(conn
+            .query_row(
+                "SELECT created_at FROM challenges WHERE id=?1 AND expires_at > ?2",
+                params![id, current_unix()],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
     /// Consume a challenge: returns it and deletes atomically. Returns None if expired or not found.
     pub fn consume_challenge(&self, id: &str) -> Result<Option<ChallengeRow>> {
+        self.consume_challenge_matching(id, None)
+    }
+
+    /// Like `consume_challenge`, but a challenge issued for another purpose is
+    /// treated as absent and left in place for its own consumer.
+    pub fn consume_challenge_for(&self, id: &str, purpose: &str) -> Result<Option<ChallengeRow>> {
+        self.consume_challenge_matching(id, Some(purpose))
+    }
+
+    fn consume_challenge_matching(
+        &self,
+        id: &str,
+        purpose: Option<&str>,
+    ) -> Result<Option<ChallengeRow>> {
         let conn = self.lock();
         let now = current_unix();
         let mut stmt = conn.prepare(
             "SELECT id, challenge, key_id, purpose, rp_id, created_at, expires_at \
-             FROM challenges WHERE id=?1 AND expires_at > ?2",
+             FROM challenges WHERE id=?1 AND expires_at > ?2 AND (?3 IS NULL OR purpose=?3)",
         )?;
-        let mut rows = stmt.query_map(params![id, now], |row| {
+        let mut rows = stmt.query_map(params![id, now, purpose], |row| {
             Ok(ChallengeRow {
                 id: row.get(0)?,
                 challenge: row.get(1)?,
@@ -1322,6 +2509,61 @@ impl KmsDb {
         Ok(n > 0)
     }
 
+    /// Begin an email binding. Unlike telegram the address is known up front, so
+    /// contact_ref is recorded now — but stays unverified until the owner returns
+    /// the verify_token that only the mailbox receives (see claim_email_binding).
+    pub fn begin_email_binding(
+        &self,
+        account: &str,
+        binding_code: &str,
+        email: &str,
+        display_hint: &str,
+        ttl_secs: i64,
+    ) -> Result<()> {
+        let now = current_unix();
+        let conn = self.lock();
+        conn.execute(
+            "INSERT INTO contact_bindings \
+               (account, channel, contact_ref, status, binding_code, display_hint, created_at, expires_at) \
+             VALUES (?1,'email',?2,'pending',?3,?4,?5,?6) \
+             ON CONFLICT(account, channel) DO UPDATE SET \
+               status='pending', contact_ref=?2, binding_code=?3, display_hint=?4, verify_token=NULL, \
+               bot_id=NULL, claimed_at=NULL, verified_at=NULL, created_at=?5, expires_at=?6",
+            params![account, email, binding_code, display_hint, now, now + ttl_secs],
+        )?;
+        Ok(())
+    }
+
+    /// Email claim: the mail relay (api-key) picks up a pending binding, attaches
+    /// the one-time verify_token it is about to mail, and gets the destination
+    /// address back. First-claim-wins like the telegram claim, so a second relay
+    /// call can't swap the token after the mail went out. None if the code is
+    /// unknown, expired or already claimed.
+    pub fn claim_email_binding(
+        &self,
+        binding_code: &str,
+        verify_token: &str,
+        relay_id: Option<&str>,
+        ttl_secs: i64,
+    ) -> Result<Option<String>> {
+        let now = current_unix();
+        let conn = self.lock();
+        let mut stmt = conn.prepare(
+            "UPDATE contact_bindings SET status='claimed', verify_token=?2, bot_id=?3, \
+               claimed_at=?4, expires_at=?5 \
+             WHERE binding_code=?1 AND channel='email' AND status='pending' AND expires_at > ?4 \
+             RETURNING contact_ref",
+        )?;
+        let mut rows = stmt.query_map(
+            params![binding_code, verify_token, relay_id, now, now + ttl_secs],
+            |row| row.get::<_, Option<String>>(0),
+        )?;
+        Ok(match rows.next() {
+            Some(r) => r?,
+            None => None,
+        })
+    }
+
     /// Confirm: app submits {binding_code, verify_token} (the token round-tripped via the
     /// app's passkey session). Marks verified, persists contact_ref, clears the one-time
     /// secrets. Returns false if code+token don't match a claimed, non-expired row.
@@ -1405,622 +2647,3489 @@ impl KmsDb {
         Ok(n > 0)
     }
 
-    // ── API keys ──
+    // ── Account audit ──
 
-    /// Generate a new API key, store it, and return the plaintext key.
-    pub fn generate_api_key(&self, label: &str) -> Result<String> {
-        let key = format!("kms_{}", Uuid::new_v4().to_string().replace("-", ""));
-        let now = Utc::now().to_rfc3339();
+    pub fn record_account_event(
+        &self,
+        account: &str,
+        event: &str,
+        detail: Option<&str>,
+        correlation_id: Option<&str>,
+    ) -> Result<()> {
+        // A software-TEE build marks every entry it writes.
+        #[cfg(feature = "soft-tee")]
+        let detail = Some(crate::soft_tee::audit_detail(detail));
         let conn = self.lock();
         conn.execute(
-            "INSERT INTO api_keys (api_key, label, created_at) VALUES (?1, ?2, ?3)",
-            params![key, label, now],
-        )
-        .context("generate_api_key")?;
-        Ok(key)
+            "INSERT INTO account_audit (account, event, detail, created_at, correlation_id) \
+             VALUES (?1,?2,?3,?4,?5)",
+            params![account, event, detail, current_unix(), correlation_id],
+        )?;
+        Ok(())
     }
 
-    /// Check if an API key is valid.
-    pub fn validate_api_key(&self, key: &str) -> Result<bool> {
+    /// Most recent events first; with `before_id`, only older ones (paging).
+    pub fn list_account_events(
+        &self,
+        account: &str,
+        limit: u32,
+        before_id: Option<i64>,
+    ) -> Result<Vec<AccountEvent>> {
         let conn = self.lock();
-        let count: i64 = conn.query_row(
-            "SELECT COUNT(*) FROM api_keys WHERE api_key = ?1",
-            params![key],
-            |row| row.get(0),
+        let mut stmt = conn.prepare(
+            "SELECT id, event, detail, created_at, correlation_id FROM account_audit \
+             WHERE account=?1 AND id < ?3 ORDER BY id DESC LIMIT ?2",
         )?;
-        Ok(count > 0)
+        let rows = stmt.query_map(
+            params![account, limit, before_id.unwrap_or(i64::MAX)],
+            |row| {
+                Ok(AccountEvent {
+                    id: row.get(0)?,
+                    event: row.get(1)?,
+                    detail: row.get(2)?,
+                    created_at: row.get(3)?,
+                    correlation_id: row.get(4)?,
+                })
+            },
+        )?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
     }
 
-    /// List all API keys (returns key, label, created_at).
-    pub fn list_api_keys(&self) -> Result<Vec<(String, String, String)>> {
+    // ── Offline sign requests ──
+
+    pub fn insert_offline_request(
+        &self,
+        request_id: &str,
+        key_id: &str,
+        address: &str,
+        request: &str,
+        created_at: i64,
+        expires_at: i64,
+    ) -> Result<()> {
         let conn = self.lock();
-        let mut stmt =
-            conn.prepare("SELECT api_key, label, created_at FROM api_keys ORDER BY created_at")?;
-        let rows = stmt.query_map([], |row| {
-            Ok((
-                row.get::<_, String>(0)?,
-                row.get::<_, String>(1)?,
-                row.get::<_, String>(2)?,
-            ))
-        })?;
-        rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+        conn.execute(
+            "INSERT INTO offline_requests (request_id, key_id, address, request, created_at, expires_at) \
+             VALUES (?1,?2,?3,?4,?5,?6)",
+            params![request_id, key_id, address.to_lowercase(), request, created_at, expires_at],
+        )?;
+        Ok(())
     }
 
-    /// Revoke (delete) an API key.
-    pub fn revoke_api_key(&self, key: &str) -> Result<bool> {
+    pub fn get_offline_request(&self, request_id: &str) -> Result<Option<OfflineRequestRow>> {
         let conn = self.lock();
-        let deleted = conn.execute("DELETE FROM api_keys WHERE api_key = ?1", params![key])?;
-        Ok(deleted > 0)
+        let mut stmt = conn.prepare(
+            "SELECT request_id, key_id, address, request, status, tx_hash, expires_at \
+             FROM offline_requests WHERE request_id=?1",
+        )?;
+        let mut rows = stmt.query_map(params![request_id], |row| {
+            Ok(OfflineRequestRow {
+                request_id: row.get(0)?,
+                key_id: row.get(1)?,
+                address: row.get(2)?,
+                request: row.get(3)?,
+                status: row.get(4)?,
+                tx_hash: row.get(5)?,
+                expires_at: row.get(6)?,
+            })
+        })?;
+        match rows.next() {
+            Some(row) => Ok(Some(row?)),
+            None => Ok(None),
+        }
     }
 
-    /// Check if any API keys exist in DB.
-    pub fn has_api_keys(&self) -> Result<bool> {
+    // ── Transaction ledger ──
+
+    /// Record a signed transaction as pending. With `replaces`, the old row
+    /// must still be pending — two rescues racing for one nonce get one win.
+    pub fn insert_tx_history(&self, e: &SignedTxEntry, replaces: Option<i64>) -> Result<i64> {
+        let mut conn = self.lock();
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        // The id AUTOINCREMENT would pick, fixed in the event for replay.
+        let id: i64 = tx.query_row(
+            "SELECT MAX(COALESCE((SELECT seq FROM sqlite_sequence WHERE name='tx_history'), 0), \
+             COALESCE((SELECT MAX(id) FROM tx_history), 0)) + 1",
+            [],
+            |row| row.get(0),
+        )?;
+        event_store::record(
+            &tx,
+            &CaEvent::TxSigned {
+                id,
+                key_id: e.key_id.clone(),
+                address: e.address.to_lowercase(),
+                derivation_path: e.derivation_path.clone(),
+                chain_id: e.chain_id,
+                nonce: e.nonce,
+                gas_price: e.gas_price.to_string(),
+                signed_tx: e.signed_tx.clone(),
+                tx_hash: e.tx_hash.clone(),
+                created_at: current_unix(),
+            },
+        )?;
+        if let Some(old) = replaces {
+            let n = event_store::record(
+                &tx,
+                &CaEvent::TxReplaced {
+                    id: old,
+                    replaced_by: id,
+                },
+            )?;
+            if n == 0 {
+                return Err(anyhow::anyhow!(
+                    "transaction {} is no longer pending (already replaced or confirmed)",
+                    old
+                ));
+            }
+        }
+        tx.commit()?;
+        Ok(id)
+    }
+
+    /// Pending ledger entries for one account on one chain, lowest nonce first.
+    pub fn list_pending_txs(&self, address: &str, chain_id: u64) -> Result<Vec<TxHistoryRow>> {
         let conn = self.lock();
-        let count: i64 = conn.query_row("SELECT COUNT(*) FROM api_keys", [], |row| row.get(0))?;
-        Ok(count > 0)
+        let mut stmt = conn.prepare(
+            "SELECT id, key_id, address, derivation_path, chain_id, nonce, gas_price, \
+             signed_tx, tx_hash, status, created_at FROM tx_history \
+             WHERE address=?1 AND chain_id=?2 AND status='pending' ORDER BY nonce, id",
+        )?;
+        let rows = stmt.query_map(
+            params![address.to_lowercase(), chain_id as i64],
+            tx_history_row,
+        )?;
+        let mut out = Vec::new();
+        for r in rows {
+            out.push(r?);
+        }
+        Ok(out)
     }
 
-    pub fn cleanup_expired_challenges(&self) -> Result<usize> {
+    /// A key's latest ledger entries on every chain, newest first; replaced
+    /// transactions are left out (their replacement is in the list).
+    pub fn recent_txs(&self, key_id: &str, limit: usize) -> Result<Vec<TxHistoryRow>> {
         let conn = self.lock();
-        let deleted = conn.execute(
-            "DELETE FROM challenges WHERE expires_at <= ?1",
-            params![current_unix()],
+        let mut stmt = conn.prepare(
+            "SELECT id, key_id, address, derivation_path, chain_id, nonce, gas_price, \
+             signed_tx, tx_hash, status, created_at FROM tx_history \
+             WHERE key_id=?1 AND status!='replaced' ORDER BY id DESC LIMIT ?2",
         )?;
-        Ok(deleted)
+        let rows = stmt.query_map(params![key_id, limit as i64], tx_history_row)?;
+        let mut out = Vec::new();
+        for r in rows {
+            out.push(r?);
+        }
+        Ok(out)
     }
 
-    // ── TX log ──
+    /// A key's ledger entries signed in `[from, to)`, oldest first, without
+    /// replaced transactions; at most `limit`.
+    pub fn txs_between(
+        &self,
+        key_id: &str,
+        from: i64,
+        to: i64,
+        limit: usize,
+    ) -> Result<Vec<TxHistoryRow>> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare(
+            "SELECT id, key_id, address, derivation_path, chain_id, nonce, gas_price, \
+             signed_tx, tx_hash, status, created_at FROM tx_history \
+             WHERE key_id=?1 AND status!='replaced' AND created_at>=?2 AND created_at<?3 \
+             ORDER BY created_at, id LIMIT ?4",
+        )?;
+        let rows = stmt.query_map(params![key_id, from, to, limit as i64], tx_history_row)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
 
-    pub fn record_tx(
+    /// The account's on-chain nonce is `nonce`: everything below it was mined.
+    pub fn mark_txs_confirmed_below(
         &self,
-        op: &str,
-        key_id: Option<&str>,
-        addr: Option<&str>,
-        webauthn: bool,
-        latency_ms: u64,
-        success: bool,
-        is_panic: bool,
-    ) -> Result<()> {
-        let now = Utc::now().to_rfc3339();
-        // Normalize the signing address to lowercase to match wallets.address /
-        // address_index.address (both lowercase). The dormant-key check (#42,
-        // freeze_dormant_keys) matches tx_log rows by key_id OR by `addr` with a
-        // case-sensitive equality; a checksummed addr here (now that lookup_address is
-        // case-insensitive, a checksummed Sign succeeds and reaches record_tx) would miss
-        // that comparison → the wallet looks dormant → wrongly auto-frozen. (codex review)
-        let addr = addr.map(|a| a.to_lowercase());
+        address: &str,
+        chain_id: u64,
+        nonce: u64,
+    ) -> Result<usize> {
+        self.record(CaEvent::TxsConfirmed {
+            address: address.to_lowercase(),
+            chain_id,
+            below_nonce: nonce,
+        })
+    }
+
+    /// Every derived address and the key it belongs to — the chain watcher's
+    /// watch set.
+    pub fn watched_addresses(&self) -> Result<HashMap<String, String>> {
         let conn = self.lock();
-        conn.execute(
-            "INSERT INTO tx_log (op, key_id, addr, webauthn, latency_ms, success, is_panic, created_at) \
-             VALUES (?1,?2,?3,?4,?5,?6,?7,?8)",
-            params![op, key_id, addr.as_deref(), webauthn as i32, latency_ms as i64,
-                    success as i32, is_panic as i32, now],
+        let mut stmt = conn.prepare("SELECT address, key_id FROM address_index")?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Record an event. Returns the row if it is new (or reappeared after a
+    /// reorg), None if it was already recorded.
+    pub fn insert_chain_event(&self, e: &ChainEvent) -> Result<Option<ChainEventRow>> {
+        let conn = self.lock();
+        let n = conn.execute(
+            "INSERT INTO chain_events (key_id, address, chain_id, kind, token, from_address, \
+             to_address, value, topic0, tx_hash, block_number, log_index, created_at) \
+             VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10,?11,?12,?13) \
+             ON CONFLICT (chain_id, tx_hash, log_index, address, kind) \
+             DO UPDATE SET removed=0 WHERE removed=1",
+            params![
+                e.key_id,
+                e.address.to_lowercase(),
+                e.chain_id as i64,
+                e.kind,
+                e.token,
+                e.from_address,
+                e.to_address,
+                e.value,
+                e.topic0,
+                e.tx_hash,
+                e.block_number as i64,
+                e.log_index,
+                current_unix()
+            ],
         )?;
-        Ok(())
+        if n == 0 {
+            return Ok(None);
+        }
+        Self::chain_event_by_key(&conn, e)
     }
 
-    pub fn get_tx_stats(&self) -> Result<TxStats> {
+    /// Flag a recorded event as reorged out. Returns the row if it changed.
+    pub fn mark_chain_event_removed(&self, e: &ChainEvent) -> Result<Option<ChainEventRow>> {
         let conn = self.lock();
-        let today = chrono::Utc::now().format("%Y-%m-%d").to_string();
-        let today_prefix = format!("{}%", today);
+        let n = conn.execute(
+            "UPDATE chain_events SET removed=1 WHERE chain_id=?1 AND tx_hash=?2 \
+             AND log_index=?3 AND address=?4 AND kind=?5 AND removed=0",
+            params![
+                e.chain_id as i64,
+                e.tx_hash,
+                e.log_index,
+                e.address.to_lowercase(),
+                e.kind
+            ],
+        )?;
+        if n == 0 {
+            return Ok(None);
+        }
+        Self::chain_event_by_key(&conn, e)
+    }
 
-        let total_sign: i64 = conn
+    fn chain_event_by_key(conn: &Connection, e: &ChainEvent) -> Result<Option<ChainEventRow>> {
+        Ok(conn
             .query_row(
-                "SELECT COUNT(*) FROM tx_log WHERE op IN ('Sign','SignHash') AND success=1",
-                [],
-                |r| r.get(0),
+                &format!(
+                    "SELECT {} FROM chain_events WHERE chain_id=?1 AND tx_hash=?2 \
+                     AND log_index=?3 AND address=?4 AND kind=?5",
+                    CHAIN_EVENT_COLUMNS
+                ),
+                params![
+                    e.chain_id as i64,
+                    e.tx_hash,
+                    e.log_index,
+                    e.address.to_lowercase(),
+                    e.kind
+                ],
+                chain_event_row,
             )
-            .unwrap_or(0);
+            .optional()?)
+    }
 
-        let daily_sign: i64 = conn.query_row(
-            "SELECT COUNT(*) FROM tx_log WHERE op IN ('Sign','SignHash') AND success=1 AND created_at LIKE ?1",
-            params![&today_prefix], |r| r.get(0),
-        ).unwrap_or(0);
+    /// A wallet's events, newest first, below `before` (an event id) if given.
+    pub fn list_chain_events(
+        &self,
+        key_id: &str,
+        before: Option<i64>,
+        limit: u32,
+    ) -> Result<Vec<ChainEventRow>> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM chain_events WHERE key_id=?1 AND id<?2 ORDER BY id DESC LIMIT ?3",
+            CHAIN_EVENT_COLUMNS
+        ))?;
+        let rows = stmt.query_map(
+            params![key_id, before.unwrap_or(i64::MAX), limit],
+            chain_event_row,
+        )?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
 
-        let total_ops: i64 = conn
-            .query_row("SELECT COUNT(*) FROM tx_log WHERE success=1", [], |r| {
-                r.get(0)
-            })
-            .unwrap_or(0);
+    /// A wallet's events recorded in `[from, to)`, oldest first, without the
+    /// reorged-out ones; at most `limit`.
+    pub fn chain_events_between(
+        &self,
+        key_id: &str,
+        from: i64,
+        to: i64,
+        limit: u32,
+    ) -> Result<Vec<ChainEventRow>> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM chain_events WHERE key_id=?1 AND removed=0 \
+             AND created_at>=?2 AND created_at<?3 ORDER BY created_at, id LIMIT ?4",
+            CHAIN_EVENT_COLUMNS
+        ))?;
+        let rows = stmt.query_map(params![key_id, from, to, limit], chain_event_row)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
 
-        let daily_ops: i64 = conn
+    // ── Notification preferences (notify.rs) ──
+
+    /// A wallet's preferences JSON and when it was set; None = the defaults.
+    pub fn notification_prefs(&self, key_id: &str) -> Result<Option<(String, i64)>> {
+        let conn = self.lock();
+        Ok(conn
             .query_row(
-                "SELECT COUNT(*) FROM tx_log WHERE success=1 AND created_at LIKE ?1",
-                params![&today_prefix],
-                |r| r.get(0),
+                "SELECT prefs, updated_at FROM notification_prefs WHERE key_id=?1",
+                params![key_id],
+                |r| Ok((r.get(0)?, r.get(1)?)),
             )
-            .unwrap_or(0);
-
-        let avg_sign_ms: f64 = conn.query_row(
-            "SELECT COALESCE(AVG(latency_ms),0) FROM tx_log WHERE op IN ('Sign','SignHash') AND success=1",
-            [], |r| r.get(0),
-        ).unwrap_or(0.0);
+            .optional()?)
+    }
 
-        let avg_derive_ms: f64 = conn.query_row(
-            "SELECT COALESCE(AVG(latency_ms),0) FROM tx_log WHERE op='DeriveAddress' AND success=1",
-            [], |r| r.get(0),
-        ).unwrap_or(0.0);
+    /// Replace a wallet's preferences.
+    pub fn set_notification_prefs(&self, key_id: &str, prefs: &str) -> Result<i64> {
+        let now = current_unix();
+        self.lock().execute(
+            "INSERT INTO notification_prefs (key_id, prefs, updated_at) VALUES (?1,?2,?3) \
+             ON CONFLICT (key_id) DO UPDATE SET prefs=excluded.prefs, updated_at=excluded.updated_at",
+            params![key_id, prefs, now],
+        )?;
+        Ok(now)
+    }
 
-        let panic_count: i64 = conn
-            .query_row("SELECT COUNT(*) FROM tx_log WHERE is_panic=1", [], |r| {
-                r.get(0)
-            })
-            .unwrap_or(0);
+    /// Back to the defaults. True if the wallet had preferences.
+    pub fn clear_notification_prefs(&self, key_id: &str) -> Result<bool> {
+        let n = self.lock().execute(
+            "DELETE FROM notification_prefs WHERE key_id=?1",
+            params![key_id],
+        )?;
+        Ok(n > 0)
+    }
 
-        let error_count: i64 = conn
-            .query_row("SELECT COUNT(*) FROM tx_log WHERE success=0", [], |r| {
-                r.get(0)
-            })
-            .unwrap_or(0);
+    // ── Remote approver phones (remote_approval.rs) ──
 
-        let webauthn_count: i64 = conn
+    pub fn remote_approver(&self, key_id: &str) -> Result<Option<RemoteApproverRow>> {
+        let conn = self.lock();
+        Ok(conn
             .query_row(
-                "SELECT COUNT(*) FROM tx_log WHERE webauthn=1 AND success=1",
-                [],
-                |r| r.get(0),
+                "SELECT key_id, approver_key, platform, push_token, paired_at \
+                 FROM remote_approvers WHERE key_id=?1",
+                params![key_id],
+                |r| {
+                    Ok(RemoteApproverRow {
+                        key_id: r.get(0)?,
+                        approver_key: r.get(1)?,
+                        platform: r.get(2)?,
+                        push_token: r.get(3)?,
+                        paired_at: r.get(4)?,
+                    })
+                },
             )
-            .unwrap_or(0);
+            .optional()?)
+    }
 
-        Ok(TxStats {
-            total_sign,
-            daily_sign,
-            total_ops,
-            daily_ops,
-            avg_sign_ms,
-            avg_derive_ms,
-            panic_count,
-            error_count,
-            webauthn_count,
-        })
+    /// Pair a phone, replacing the wallet's previous one.
+    pub fn set_remote_approver(
+        &self,
+        key_id: &str,
+        approver_key: &str,
+        platform: &str,
+        push_token: &str,
+    ) -> Result<i64> {
+        let now = current_unix();
+        self.lock().execute(
+            "INSERT INTO remote_approvers (key_id, approver_key, platform, push_token, paired_at) \
+             VALUES (?1,?2,?3,?4,?5) ON CONFLICT (key_id) DO UPDATE SET \
+             approver_key=excluded.approver_key, platform=excluded.platform, \
+             push_token=excluded.push_token, paired_at=excluded.paired_at",
+            params![key_id, approver_key, platform, push_token, now],
+        )?;
+        Ok(now)
     }
-}
 
-fn current_unix() -> i64 {
-    std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap()
-        .as_secs() as i64
-}
+    /// True if the wallet had a phone paired.
+    pub fn clear_remote_approver(&self, key_id: &str) -> Result<bool> {
+        let n = self.lock().execute(
+            "DELETE FROM remote_approvers WHERE key_id=?1",
+            params![key_id],
+        )?;
+        Ok(n > 0)
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    // ── Stealth addresses ──
 
-    fn test_db() -> KmsDb {
-        KmsDb::open_memory().unwrap()
+    /// Register (or re-confirm) an account's meta-address. The scan cursor of
+    /// an existing registration is kept.
+    pub fn upsert_stealth_meta(
+        &self,
+        key_id: &str,
+        account: u32,
+        meta_address: &str,
+    ) -> Result<()> {
+        let conn = self.lock();
+        conn.execute(
+            "INSERT INTO stealth_meta (key_id, account, meta_address, created_at) \
+             VALUES (?1,?2,?3,?4) \
+             ON CONFLICT (key_id, account) DO UPDATE SET meta_address=excluded.meta_address",
+            params![key_id, account, meta_address, current_unix()],
+        )?;
+        Ok(())
     }
 
-    fn sample_wallet(key_id: &str) -> WalletRow {
-        WalletRow {
-            key_id: key_id.to_string(),
-            address: None,
-            public_key: None,
-            derivation_path: None,
-            description: "test".to_string(),
-            key_usage: "SIGN_VERIFY".to_string(),
-            key_spec: "ECC_SECG_P256K1".to_string(),
-            origin: "EXTERNAL_KMS".to_string(),
-            passkey_pubkey: Some("0x04abcd".to_string()),
-            credential_id: None,
-            sign_count: 0,
-            status: "creating".to_string(),
-            error_msg: None,
-            created_at: "2026-03-02T00:00:00Z".to_string(),
-        }
+    pub fn list_stealth_meta(&self) -> Result<Vec<StealthMetaRow>> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare(
+            "SELECT key_id, account, meta_address, scanned_through, created_at \
+             FROM stealth_meta ORDER BY key_id, account",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(StealthMetaRow {
+                key_id: row.get(0)?,
+                account: row.get(1)?,
+                meta_address: row.get(2)?,
+                scanned_through: row.get(3)?,
+                created_at: row.get(4)?,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
     }
 
-    #[test]
-    fn insert_and_get_wallet() {
+    pub fn advance_stealth_cursor(&self, key_id: &str, account: u32, through: i64) -> Result<()> {
+        let conn = self.lock();
+        conn.execute(
+            "UPDATE stealth_meta SET scanned_through=?3 \
+             WHERE key_id=?1 AND account=?2 AND scanned_through<?3",
+            params![key_id, account, through],
+        )?;
+        Ok(())
+    }
+
+    /// Store an announcement. Returns false if it was already stored.
+    pub fn insert_stealth_announcement(&self, a: &Announcement) -> Result<bool> {
+        let conn = self.lock();
+        let n = conn.execute(
+            "INSERT OR IGNORE INTO stealth_announcements (chain_id, stealth_address, caller, \
+             ephemeral_pubkey, metadata, tx_hash, block_number, log_index, created_at) \
+             VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9)",
+            params![
+                a.chain_id as i64,
+                a.stealth_address.to_lowercase(),
+                a.caller,
+                format!("0x{}", hex::encode(&a.ephemeral_pubkey)),
+                format!("0x{}", hex::encode(&a.metadata)),
+                a.tx_hash,
+                a.block_number as i64,
+                a.log_index,
+                current_unix()
+            ],
+        )?;
+        Ok(n > 0)
+    }
+
+    /// Drop a reorged-out announcement. Returns whether it was stored.
+    pub fn delete_stealth_announcement(&self, a: &Announcement) -> Result<bool> {
+        let conn = self.lock();
+        let n = conn.execute(
+            "DELETE FROM stealth_announcements WHERE chain_id=?1 AND tx_hash=?2 AND log_index=?3",
+            params![a.chain_id as i64, a.tx_hash, a.log_index],
+        )?;
+        Ok(n > 0)
+    }
+
+    /// Announcements stored after id `after`, oldest first.
+    pub fn stealth_announcements_after(
+        &self,
+        after: i64,
+        limit: u32,
+    ) -> Result<Vec<(i64, Announcement)>> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare(
+            "SELECT id, chain_id, stealth_address, caller, ephemeral_pubkey, metadata, tx_hash, \
+             block_number, log_index FROM stealth_announcements WHERE id>?1 ORDER BY id LIMIT ?2",
+        )?;
+        let unhex = |s: String| hex::decode(s.trim_start_matches("0x")).unwrap_or_default();
+        let rows = stmt.query_map(params![after, limit], |row| {
+            Ok((
+                row.get(0)?,
+                Announcement {
+                    chain_id: row.get::<_, i64>(1)? as u64,
+                    stealth_address: row.get(2)?,
+                    caller: row.get(3)?,
+                    ephemeral_pubkey: unhex(row.get(4)?),
+                    metadata: unhex(row.get(5)?),
+                    tx_hash: row.get(6)?,
+                    block_number: row.get::<_, i64>(7)? as u64,
+                    log_index: row.get(8)?,
+                },
+            ))
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    // ── Tenants (bulk provisioning) ──
+
+    pub fn tenant_quota(&self, tenant_id: &str) -> Result<Option<u32>> {
+        let conn = self.lock();
+        Ok(conn
+            .query_row(
+                "SELECT wallet_quota FROM tenants WHERE tenant_id=?1",
+                params![tenant_id],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
+    pub fn set_tenant_quota(&self, tenant_id: &str, wallet_quota: u32) -> Result<()> {
+        let conn = self.lock();
+        conn.execute(
+            "INSERT INTO tenants (tenant_id, wallet_quota, created_at) VALUES (?1,?2,?3) \
+             ON CONFLICT (tenant_id) DO UPDATE SET wallet_quota=excluded.wallet_quota",
+            params![tenant_id, wallet_quota, current_unix()],
+        )?;
+        Ok(())
+    }
+
+    /// Wallets of the tenant provisioned at or after `since` (unix secs).
+    pub fn count_tenant_wallets(&self, tenant_id: &str, since: i64) -> Result<u64> {
+        let conn = self.lock();
+        let n: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM tenant_wallets WHERE tenant_id=?1 AND created_at>=?2",
+            params![tenant_id, since],
+            |row| row.get(0),
+        )?;
+        Ok(n as u64)
+    }
+
+    pub fn insert_tenant_wallet(&self, w: &TenantWalletRow) -> Result<()> {
+        let conn = self.lock();
+        conn.execute(
+            "INSERT INTO tenant_wallets (key_id, tenant_id, tags, batch_id, created_at) \
+             VALUES (?1,?2,?3,?4,?5)",
+            params![
+                w.key_id,
+                w.tenant_id,
+                w.tags.join(","),
+                w.batch_id,
+                w.created_at
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// The tenant's wallets, oldest first, optionally only those with `tag`.
+    pub fn list_tenant_wallets(
+        &self,
+        tenant_id: &str,
+        tag: Option<&str>,
+    ) -> Result<Vec<TenantWalletRow>> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare(
+            "SELECT key_id, tenant_id, tags, batch_id, created_at FROM tenant_wallets \
+             WHERE tenant_id=?1 ORDER BY created_at, key_id",
+        )?;
+        let rows = stmt.query_map(params![tenant_id], |row| {
+            let tags: String = row.get(2)?;
+            Ok(TenantWalletRow {
+                key_id: row.get(0)?,
+                tenant_id: row.get(1)?,
+                tags: tags
+                    .split(',')
+                    .filter(|t| !t.is_empty())
+                    .map(str::to_string)
+                    .collect(),
+                batch_id: row.get(3)?,
+                created_at: row.get(4)?,
+            })
+        })?;
+        let rows = rows.collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(match tag {
+            Some(tag) => rows
+                .into_iter()
+                .filter(|r| r.tags.iter().any(|t| t == tag))
+                .collect(),
+            None => rows,
+        })
+    }
+
+    pub fn insert_provisioning_batch(&self, b: &ProvisioningBatchRow) -> Result<()> {
+        let conn = self.lock();
+        conn.execute(
+            "INSERT INTO provisioning_batches (batch_id, tenant_id, report, attestation, \
+             created_at) VALUES (?1,?2,?3,?4,?5)",
+            params![
+                b.batch_id,
+                b.tenant_id,
+                b.report,
+                b.attestation,
+                b.created_at
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_provisioning_batch(&self, batch_id: &str) -> Result<Option<ProvisioningBatchRow>> {
+        let conn = self.lock();
+        Ok(conn
+            .query_row(
+                "SELECT batch_id, tenant_id, report, attestation, created_at \
+                 FROM provisioning_batches WHERE batch_id=?1",
+                params![batch_id],
+                |row| {
+                    Ok(ProvisioningBatchRow {
+                        batch_id: row.get(0)?,
+                        tenant_id: row.get(1)?,
+                        report: row.get(2)?,
+                        attestation: row.get(3)?,
+                        created_at: row.get(4)?,
+                    })
+                },
+            )
+            .optional()?)
+    }
+
+    // ── Fee payments ──
+
+    pub fn insert_fee_quote(&self, e: &FeeQuoteEntry) -> Result<()> {
+        let now = current_unix();
+        let conn = self.lock();
+        conn.execute(
+            "INSERT INTO fee_payments (quote_hash, key_id, chain_id, paymaster, token, \
+             max_token_cost, quote, created_at, updated_at) \
+             VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?8)",
+            params![
+                e.quote_hash,
+                e.key_id,
+                e.chain_id as i64,
+                e.paymaster.to_lowercase(),
+                e.token.to_lowercase(),
+                e.max_token_cost.to_string(),
+                e.quote,
+                now
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn fee_payment(&self, quote_hash: &str) -> Result<Option<FeePaymentRow>> {
+        let conn = self.lock();
+        Ok(conn
+            .query_row(
+                &format!(
+                    "SELECT {} FROM fee_payments WHERE quote_hash=?1",
+                    FEE_PAYMENT_COLUMNS
+                ),
+                params![quote_hash],
+                fee_payment_row,
+            )
+            .optional()?)
+    }
+
+    /// quoted → signed for `user_op_hash`, once. Claimed before the TA call
+    /// so one quote pays for one UserOperation; false if already claimed.
+    pub fn claim_fee_quote(&self, quote_hash: &str, user_op_hash: &str) -> Result<bool> {
+        let conn = self.lock();
+        let n = conn.execute(
+            "UPDATE fee_payments SET status='signed', user_op_hash=?2, updated_at=?3 \
+             WHERE quote_hash=?1 AND status='quoted'",
+            params![quote_hash, user_op_hash, current_unix()],
+        )?;
+        Ok(n == 1)
+    }
+
+    /// Undo [`Self::claim_fee_quote`] after the TA refused to sign.
+    pub fn release_fee_quote(&self, quote_hash: &str, user_op_hash: &str) -> Result<()> {
+        let conn = self.lock();
+        conn.execute(
+            "UPDATE fee_payments SET status='quoted', user_op_hash=NULL, updated_at=?3 \
+             WHERE quote_hash=?1 AND status='signed' AND user_op_hash=?2",
+            params![quote_hash, user_op_hash, current_unix()],
+        )?;
+        Ok(())
+    }
+
+    /// signed → settled | failed, once. None if the quote is unknown or not
+    /// in the signed state.
+    pub fn settle_fee_payment(
+        &self,
+        quote_hash: &str,
+        settled: bool,
+        tx_hash: Option<&str>,
+        actual_token_cost: Option<u128>,
+    ) -> Result<Option<FeePaymentRow>> {
+        let conn = self.lock();
+        let n = conn.execute(
+            "UPDATE fee_payments SET status=?2, tx_hash=?3, actual_token_cost=?4, updated_at=?5 \
+             WHERE quote_hash=?1 AND status='signed'",
+            params![
+                quote_hash,
+                if settled { "settled" } else { "failed" },
+                tx_hash,
+                actual_token_cost.map(|c| c.to_string()),
+                current_unix()
+            ],
+        )?;
+        if n == 0 {
+            return Ok(None);
+        }
+        Ok(conn
+            .query_row(
+                &format!(
+                    "SELECT {} FROM fee_payments WHERE quote_hash=?1",
+                    FEE_PAYMENT_COLUMNS
+                ),
+                params![quote_hash],
+                fee_payment_row,
+            )
+            .optional()?)
+    }
+
+    /// A key's fee payments, newest first.
+    pub fn list_fee_payments(&self, key_id: &str, limit: u32) -> Result<Vec<FeePaymentRow>> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM fee_payments WHERE key_id=?1 \
+             ORDER BY created_at DESC, rowid DESC LIMIT ?2",
+            FEE_PAYMENT_COLUMNS
+        ))?;
+        let rows = stmt.query_map(params![key_id, limit], fee_payment_row)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// A key's fee payments settled in `[from, to)`, oldest first; at most `limit`.
+    pub fn settled_fee_payments_between(
+        &self,
+        key_id: &str,
+        from: i64,
+        to: i64,
+        limit: u32,
+    ) -> Result<Vec<FeePaymentRow>> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM fee_payments WHERE key_id=?1 AND status='settled' \
+             AND updated_at>=?2 AND updated_at<?3 ORDER BY updated_at, rowid LIMIT ?4",
+            FEE_PAYMENT_COLUMNS
+        ))?;
+        let rows = stmt.query_map(params![key_id, from, to, limit], fee_payment_row)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    // ── Refresh-token families ──
+
+    /// A passkey mint opened a family; it replaces any earlier one for the key.
+    pub fn open_refresh_family(
+        &self,
+        key_id: &str,
+        wallet_id: &str,
+        credential_id: Option<&str>,
+        device_hash: &str,
+    ) -> Result<()> {
+        let now = current_unix();
+        let conn = self.lock();
+        conn.execute(
+            "INSERT OR REPLACE INTO refresh_families (key_id, wallet_id, credential_id, \
+             device_hash, generation, status, revoked_reason, created_at, updated_at) \
+             VALUES (?1, ?2, ?3, ?4, 0, 'active', NULL, ?5, ?5)",
+            params![key_id, wallet_id, credential_id, device_hash, now],
+        )?;
+        Ok(())
+    }
+
+    pub fn refresh_family(&self, key_id: &str) -> Result<Option<RefreshFamilyRow>> {
+        let conn = self.lock();
+        Ok(conn
+            .query_row(
+                "SELECT key_id, wallet_id, credential_id, device_hash, generation, status, \
+                 revoked_reason, created_at, updated_at FROM refresh_families WHERE key_id=?1",
+                params![key_id],
+                |row| {
+                    Ok(RefreshFamilyRow {
+                        key_id: row.get(0)?,
+                        wallet_id: row.get(1)?,
+                        credential_id: row.get(2)?,
+                        device_hash: row.get(3)?,
+                        generation: row.get::<_, i64>(4)? as u32,
+                        status: row.get(5)?,
+                        revoked_reason: row.get(6)?,
+                        created_at: row.get(7)?,
+                        updated_at: row.get(8)?,
+                    })
+                },
+            )
+            .optional()?)
+    }
+
+    /// The TA rotated the secret. False if the family is no longer active.
+    pub fn advance_refresh_family(&self, key_id: &str) -> Result<bool> {
+        let conn = self.lock();
+        let n = conn.execute(
+            "UPDATE refresh_families SET generation=generation+1, updated_at=?2 \
+             WHERE key_id=?1 AND status='active'",
+            params![key_id, current_unix()],
+        )?;
+        Ok(n == 1)
+    }
+
+    /// active → revoked; false if there was no active family.
+    pub fn revoke_refresh_family(&self, key_id: &str, reason: &str) -> Result<bool> {
+        let conn = self.lock();
+        let n = conn.execute(
+            "UPDATE refresh_families SET status='revoked', revoked_reason=?2, updated_at=?3 \
+             WHERE key_id=?1 AND status='active'",
+            params![key_id, reason, current_unix()],
+        )?;
+        Ok(n == 1)
+    }
+
+    /// pending → completed, once, and only before expiry. False means the
+    /// response was already imported or arrived too late.
+    pub fn complete_offline_request(&self, request_id: &str, tx_hash: &str) -> Result<bool> {
+        let now = current_unix();
+        let conn = self.lock();
+        let n = conn.execute(
+            "UPDATE offline_requests SET status='completed', tx_hash=?2, completed_at=?3 \
+             WHERE request_id=?1 AND status='pending' AND expires_at > ?3",
+            params![request_id, tx_hash, now],
+        )?;
+        Ok(n == 1)
+    }
+
+    // ── Deletion certificates ──
+
+    pub fn record_deletion_certificate(
+        &self,
+        key_id: &str,
+        certificate_json: &str,
+        signed: bool,
+    ) -> Result<()> {
+        let conn = self.lock();
+        conn.execute(
+            "INSERT OR REPLACE INTO deletion_certificates (key_id, certificate, signed, created_at) \
+             VALUES (?1, ?2, ?3, ?4)",
+            params![key_id, certificate_json, signed, current_unix()],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_deletion_certificate(&self, key_id: &str) -> Result<Option<String>> {
+        let conn = self.lock();
+        let mut stmt =
+            conn.prepare("SELECT certificate FROM deletion_certificates WHERE key_id=?1")?;
+        let mut rows = stmt.query_map(params![key_id], |row| row.get::<_, String>(0))?;
+        match rows.next() {
+            Some(r) => Ok(Some(r?)),
+            None => Ok(None),
+        }
+    }
+
+    // ── Replica device sets ──
+
+    /// Add a device to a wallet's set, or reactivate it after a new replication.
+    pub fn add_wallet_device(
+        &self,
+        key_id: &str,
+        device_id: &str,
+        endpoint: Option<&str>,
+        attestation_key_fp: &str,
+    ) -> Result<()> {
+        let now = current_unix();
+        let conn = self.lock();
+        conn.execute(
+            "INSERT INTO wallet_devices (key_id, device_id, endpoint, attestation_key_fp, \
+             status, added_at, last_seen) VALUES (?1, ?2, ?3, ?4, 'active', ?5, ?5) \
+             ON CONFLICT(key_id, device_id) DO UPDATE SET endpoint=excluded.endpoint, \
+             attestation_key_fp=excluded.attestation_key_fp, status='active', \
+             revoked_at=NULL, last_seen=excluded.last_seen",
+            params![key_id, device_id, endpoint, attestation_key_fp, now],
+        )?;
+        Ok(())
+    }
+
+    /// Attestation key fingerprint a device was first seen with, if any.
+    pub fn device_attestation_fp(&self, device_id: &str) -> Result<Option<String>> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare(
+            "SELECT attestation_key_fp FROM wallet_devices WHERE device_id=?1 \
+             ORDER BY added_at LIMIT 1",
+        )?;
+        let mut rows = stmt.query_map(params![device_id], |row| row.get::<_, String>(0))?;
+        match rows.next() {
+            Some(r) => Ok(Some(r?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn list_wallet_devices(&self, key_id: &str) -> Result<Vec<WalletDeviceRow>> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare(
+            "SELECT key_id, device_id, endpoint, attestation_key_fp, status, added_at, \
+             revoked_at, last_seen, quarantined FROM wallet_devices WHERE key_id=?1 \
+             ORDER BY added_at",
+        )?;
+        let rows = stmt.query_map(params![key_id], |row| {
+            Ok(WalletDeviceRow {
+                key_id: row.get(0)?,
+                device_id: row.get(1)?,
+                endpoint: row.get(2)?,
+                attestation_key_fp: row.get(3)?,
+                status: row.get(4)?,
+                added_at: row.get(5)?,
+                revoked_at: row.get(6)?,
+                last_seen: row.get(7)?,
+                quarantined: row.get(8)?,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    /// False if the device is not in the wallet's set or already revoked.
+    pub fn revoke_wallet_device(&self, key_id: &str, device_id: &str) -> Result<bool> {
+        let conn = self.lock();
+        let n = conn.execute(
+            "UPDATE wallet_devices SET status='revoked', revoked_at=?3 \
+             WHERE key_id=?1 AND device_id=?2 AND status='active'",
+            params![key_id, device_id, current_unix()],
+        )?;
+        Ok(n > 0)
+    }
+
+    /// Mark a device alive in every set it belongs to; returns the row count.
+    /// `quarantined` is what the device said about itself; None keeps the
+    /// stored flag.
+    pub fn record_device_heartbeat(
+        &self,
+        device_id: &str,
+        endpoint: Option<&str>,
+        quarantined: Option<bool>,
+    ) -> Result<usize> {
+        let conn = self.lock();
+        let n = conn.execute(
+            "UPDATE wallet_devices SET last_seen=?2, endpoint=COALESCE(?3, endpoint), \
+             quarantined=COALESCE(?4, quarantined) WHERE device_id=?1",
+            params![device_id, current_unix(), endpoint, quarantined],
+        )?;
+        Ok(n)
+    }
+
+    // ── Multi-region keys ──
+
+    /// Record the device serving a region of a key; replaces an earlier entry.
+    pub fn set_key_region(
+        &self,
+        key_id: &str,
+        region: &str,
+        device_id: &str,
+        role: &str,
+    ) -> Result<()> {
+        let conn = self.lock();
+        conn.execute(
+            "INSERT INTO key_regions (key_id, region, device_id, role, created_at) \
+             VALUES (?1, ?2, ?3, ?4, ?5) ON CONFLICT(key_id, region) DO UPDATE SET \
+             device_id=excluded.device_id, role=excluded.role",
+            params![key_id, region, device_id, role, current_unix()],
+        )?;
+        Ok(())
+    }
+
+    /// Regions of a key, primary first.
+    pub fn list_key_regions(&self, key_id: &str) -> Result<Vec<KeyRegionRow>> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare(
+            "SELECT key_id, region, device_id, role, created_at FROM key_regions \
+             WHERE key_id=?1 ORDER BY role = 'PRIMARY' DESC, created_at, region",
+        )?;
+        let rows = stmt.query_map(params![key_id], |row| {
+            Ok(KeyRegionRow {
+                key_id: row.get(0)?,
+                region: row.get(1)?,
+                device_id: row.get(2)?,
+                role: row.get(3)?,
+                created_at: row.get(4)?,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    /// Make `region` the primary and every other region a replica. False if
+    /// the key has no such region.
+    pub fn set_primary_region(&self, key_id: &str, region: &str) -> Result<bool> {
+        let mut conn = self.lock();
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let known: i64 = tx.query_row(
+            "SELECT COUNT(*) FROM key_regions WHERE key_id=?1 AND region=?2",
+            params![key_id, region],
+            |row| row.get(0),
+        )?;
+        if known == 0 {
+            return Ok(false);
+        }
+        tx.execute(
+            "UPDATE key_regions SET role = CASE WHEN region=?2 THEN 'PRIMARY' \
+             ELSE 'REPLICA' END WHERE key_id=?1",
+            params![key_id, region],
+        )?;
+        tx.commit()?;
+        Ok(true)
+    }
+
+    // ── Identity links ──
+
+    /// Link an address, creating `new_identity` first when given. Re-linking
+    /// an address to its own identity replaces its chains and proof; an
+    /// address linked elsewhere is refused.
+    pub fn link_identity_address(
+        &self,
+        link: &IdentityLinkRow,
+        new_identity: Option<&IdentityRow>,
+    ) -> Result<()> {
+        let address = link.address.to_lowercase();
+        let mut conn = self.lock();
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        if let Some(identity) = new_identity {
+            tx.execute(
+                "INSERT INTO identities (identity_id, label, owner_key_id, created_at) \
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    identity.identity_id,
+                    identity.label,
+                    identity.owner_key_id,
+                    identity.created_at
+                ],
+            )?;
+        }
+        let current: Option<String> = tx
+            .query_row(
+                "SELECT identity_id FROM identity_links WHERE address=?1",
+                params![address],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if let Some(other) = current.filter(|other| *other != link.identity_id) {
+            return Err(anyhow::anyhow!(
+                "{} is already linked to identity {}",
+                address,
+                other
+            ));
+        }
+        let chain_ids: Vec<String> = link.chain_ids.iter().map(u64::to_string).collect();
+        tx.execute(
+            "INSERT INTO identity_links (address, identity_id, key_id, derivation_path, \
+             chain_ids, statement, signature, linked_at) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8) ON CONFLICT(address) DO UPDATE SET \
+             chain_ids=excluded.chain_ids, statement=excluded.statement, \
+             signature=excluded.signature, linked_at=excluded.linked_at",
+            params![
+                address,
+                link.identity_id,
+                link.key_id,
+                link.derivation_path,
+                chain_ids.join(","),
+                link.statement,
+                link.signature,
+                link.linked_at
+            ],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Drop a link; the identity goes with its last one. False if the
+    /// address was not linked to it.
+    pub fn unlink_identity_address(&self, identity_id: &str, address: &str) -> Result<bool> {
+        let mut conn = self.lock();
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let n = tx.execute(
+            "DELETE FROM identity_links WHERE identity_id=?1 AND address=?2",
+            params![identity_id, address.to_lowercase()],
+        )?;
+        tx.execute(
+            "DELETE FROM identities WHERE identity_id=?1 AND NOT EXISTS \
+             (SELECT 1 FROM identity_links WHERE identity_id=?1)",
+            params![identity_id],
+        )?;
+        tx.commit()?;
+        Ok(n == 1)
+    }
+
+    pub fn get_identity(&self, identity_id: &str) -> Result<Option<IdentityRow>> {
+        let conn = self.lock();
+        Ok(conn
+            .query_row(
+                "SELECT identity_id, label, owner_key_id, created_at FROM identities \
+                 WHERE identity_id=?1",
+                params![identity_id],
+                |row| {
+                    Ok(IdentityRow {
+                        identity_id: row.get(0)?,
+                        label: row.get(1)?,
+                        owner_key_id: row.get(2)?,
+                        created_at: row.get(3)?,
+                    })
+                },
+            )
+            .optional()?)
+    }
+
+    /// The identity an address is linked to.
+    pub fn identity_for_address(&self, address: &str) -> Result<Option<String>> {
+        let conn = self.lock();
+        Ok(conn
+            .query_row(
+                "SELECT identity_id FROM identity_links WHERE address=?1",
+                params![address.to_lowercase()],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
+    /// Links of an identity, oldest first.
+    pub fn list_identity_links(&self, identity_id: &str) -> Result<Vec<IdentityLinkRow>> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare(
+            "SELECT address, identity_id, key_id, derivation_path, chain_ids, statement, \
+             signature, linked_at FROM identity_links WHERE identity_id=?1 \
+             ORDER BY linked_at, address",
+        )?;
+        let rows = stmt.query_map(params![identity_id], |row| {
+            let chain_ids: String = row.get(4)?;
+            Ok(IdentityLinkRow {
+                address: row.get(0)?,
+                identity_id: row.get(1)?,
+                key_id: row.get(2)?,
+                derivation_path: row.get(3)?,
+                chain_ids: chain_ids
+                    .split(',')
+                    .filter_map(|id| id.parse().ok())
+                    .collect(),
+                statement: row.get(5)?,
+                signature: row.get(6)?,
+                linked_at: row.get(7)?,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    // ── Gas tanks ──
+
+    /// Designate a relayer address, or change its floor and refill source.
+    /// The observed balance and low state are kept.
+    pub fn upsert_gas_tank(&self, tank: &GasTankRow) -> Result<()> {
+        let top_up = tank.top_up.as_ref();
+        self.lock().execute(
+            "INSERT INTO gas_tanks (address, chain_id, label, min_balance_wei, top_up_wei, \
+             treasury_key_id, treasury_path, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8) \
+             ON CONFLICT(address, chain_id) DO UPDATE SET label=excluded.label, \
+             min_balance_wei=excluded.min_balance_wei, top_up_wei=excluded.top_up_wei, \
+             treasury_key_id=excluded.treasury_key_id, treasury_path=excluded.treasury_path",
+            params![
+                tank.address.to_lowercase(),
+                tank.chain_id as i64,
+                tank.label,
+                tank.min_balance_wei.to_string(),
+                top_up.map(|t| t.amount_wei.to_string()),
+                top_up.map(|t| t.treasury_key_id.as_str()),
+                top_up.map(|t| t.treasury_path.as_str()),
+                tank.created_at
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_gas_tank(&self, address: &str, chain_id: u64) -> Result<bool> {
+        let n = self.lock().execute(
+            "DELETE FROM gas_tanks WHERE address=?1 AND chain_id=?2",
+            params![address.to_lowercase(), chain_id as i64],
+        )?;
+        Ok(n == 1)
+    }
+
+    pub fn list_gas_tanks(&self) -> Result<Vec<GasTankRow>> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare(
+            "SELECT address, chain_id, label, min_balance_wei, top_up_wei, treasury_key_id, \
+             treasury_path, balance_wei, checked_at, low_since, created_at FROM gas_tanks \
+             ORDER BY chain_id, address",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            let wei = |s: Option<String>| s.and_then(|s| s.parse::<u128>().ok());
+            let top_up = match (
+                wei(row.get(4)?),
+                row.get::<_, Option<String>>(5)?,
+                row.get::<_, Option<String>>(6)?,
+            ) {
+                (Some(amount_wei), Some(treasury_key_id), Some(treasury_path)) => {
+                    Some(GasTankSource {
+                        amount_wei,
+                        treasury_key_id,
+                        treasury_path,
+                    })
+                }
+                _ => None,
+            };
+            Ok(GasTankRow {
+                address: row.get(0)?,
+                chain_id: row.get::<_, i64>(1)? as u64,
+                label: row.get(2)?,
+                min_balance_wei: wei(row.get(3)?).unwrap_or(0),
+                top_up,
+                balance_wei: wei(row.get(7)?),
+                checked_at: row.get(8)?,
+                low_since: row.get(9)?,
+                created_at: row.get(10)?,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    pub fn record_gas_tank_balance(
+        &self,
+        address: &str,
+        chain_id: u64,
+        balance_wei: u128,
+        checked_at: i64,
+        low_since: Option<i64>,
+    ) -> Result<()> {
+        self.lock().execute(
+            "UPDATE gas_tanks SET balance_wei=?3, checked_at=?4, low_since=?5 \
+             WHERE address=?1 AND chain_id=?2",
+            params![
+                address.to_lowercase(),
+                chain_id as i64,
+                balance_wei.to_string(),
+                checked_at,
+                low_since
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Propose a refill unless the tank already has one open; None then.
+    pub fn propose_gas_tank_topup(
+        &self,
+        address: &str,
+        chain_id: u64,
+        source: &GasTankSource,
+        now: i64,
+    ) -> Result<Option<i64>> {
+        let address = address.to_lowercase();
+        let mut conn = self.lock();
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let open: Option<i64> = tx
+            .query_row(
+                "SELECT id FROM gas_tank_topups WHERE address=?1 AND chain_id=?2 \
+                 AND status IN ('proposed', 'approved')",
+                params![address, chain_id as i64],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if open.is_some() {
+            return Ok(None);
+        }
+        tx.execute(
+            "INSERT INTO gas_tank_topups (address, chain_id, treasury_key_id, treasury_path, \
+             amount_wei, status, created_at, updated_at) \
+             VALUES (?1, ?2, ?3, ?4, ?5, 'proposed', ?6, ?6)",
+            params![
+                address,
+                chain_id as i64,
+                source.treasury_key_id,
+                source.treasury_path,
+                source.amount_wei.to_string(),
+                now
+            ],
+        )?;
+        let id = tx.last_insert_rowid();
+        tx.commit()?;
+        Ok(Some(id))
+    }
+
+    fn gas_tank_topups_where(
+        &self,
+        clause: &str,
+        args: &[&dyn rusqlite::ToSql],
+    ) -> Result<Vec<GasTankTopUpRow>> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT id, address, chain_id, treasury_key_id, treasury_path, amount_wei, status, \
+             approved_by, tx_json, tx_hash, created_at, updated_at FROM gas_tank_topups \
+             WHERE {} ORDER BY id",
+            clause
+        ))?;
+        let rows = stmt.query_map(args, |row| {
+            Ok(GasTankTopUpRow {
+                id: row.get(0)?,
+                address: row.get(1)?,
+                chain_id: row.get::<_, i64>(2)? as u64,
+                source: GasTankSource {
+                    treasury_key_id: row.get(3)?,
+                    treasury_path: row.get(4)?,
+                    amount_wei: row.get::<_, String>(5)?.parse().unwrap_or(0),
+                },
+                status: row.get(6)?,
+                approved_by: row.get(7)?,
+                transaction: row.get(8)?,
+                tx_hash: row.get(9)?,
+                created_at: row.get(10)?,
+                updated_at: row.get(11)?,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    pub fn get_gas_tank_topup(&self, id: i64) -> Result<Option<GasTankTopUpRow>> {
+        Ok(self
+            .gas_tank_topups_where("id=?1", &[&id])?
+            .into_iter()
+            .next())
+    }
+
+    /// Proposed and approved refills, oldest first.
+    pub fn open_gas_tank_topups(&self) -> Result<Vec<GasTankTopUpRow>> {
+        self.gas_tank_topups_where("status IN ('proposed', 'approved')", &[])
+    }
+
+    /// Move a refill from one status to the next, optionally setting the
+    /// approver, the prepared transaction or the tx hash. False if it was no
+    /// longer in `from` (someone else acted first, or it expired).
+    pub fn advance_gas_tank_topup(
+        &self,
+        id: i64,
+        from: &str,
+        to: &str,
+        approved_by: Option<&str>,
+        transaction: Option<&str>,
+        tx_hash: Option<&str>,
+    ) -> Result<bool> {
+        let n = self.lock().execute(
+            "UPDATE gas_tank_topups SET status=?3, approved_by=COALESCE(?4, approved_by), \
+             tx_json=COALESCE(?5, tx_json), tx_hash=COALESCE(?6, tx_hash), \
+             updated_at=?7 WHERE id=?1 AND status=?2",
+            params![
+                id,
+                from,
+                to,
+                approved_by,
+                transaction,
+                tx_hash,
+                current_unix()
+            ],
+        )?;
+        Ok(n == 1)
+    }
+
+    /// Expire refills nobody finished since `before`; returns how many.
+    pub fn expire_gas_tank_topups(&self, before: i64) -> Result<usize> {
+        Ok(self.lock().execute(
+            "UPDATE gas_tank_topups SET status='expired', updated_at=?2 \
+             WHERE status IN ('proposed', 'approved') AND created_at < ?1",
+            params![before, current_unix()],
+        )?)
+    }
+
+    // ── Device wipes ──
+
+    /// Keep a wipe certificate; false if it was already on file.
+    pub fn record_device_wipe(
+        &self,
+        digest: &str,
+        reason: &str,
+        certificate_json: &str,
+        signed: bool,
+    ) -> Result<bool> {
+        let conn = self.lock();
+        let n = conn.execute(
+            "INSERT OR IGNORE INTO device_wipes (digest, reason, certificate, signed, created_at) \
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![digest, reason, certificate_json, signed, current_unix()],
+        )?;
+        Ok(n == 1)
+    }
+
+    /// Wipe certificates, newest first.
+    pub fn list_device_wipes(&self) -> Result<Vec<String>> {
+        let conn = self.lock();
+        let mut stmt =
+            conn.prepare("SELECT certificate FROM device_wipes ORDER BY created_at DESC, digest")?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    // ── Device health ──
+
+    pub fn record_health_check(
+        &self,
+        checked_at: i64,
+        passed: bool,
+        source: &str,
+        report_json: &str,
+    ) -> Result<i64> {
+        let conn = self.lock();
+        conn.execute(
+            "INSERT INTO device_health_checks (checked_at, passed, source, report) \
+             VALUES (?1, ?2, ?3, ?4)",
+            params![checked_at, passed, source, report_json],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// Newest first.
+    pub fn list_health_checks(&self, limit: u32) -> Result<Vec<DeviceHealthCheckRow>> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare(
+            "SELECT id, checked_at, passed, source, report FROM device_health_checks \
+             ORDER BY id DESC LIMIT ?1",
+        )?;
+        let rows = stmt.query_map(params![limit], |row| {
+            Ok(DeviceHealthCheckRow {
+                id: row.get(0)?,
+                checked_at: row.get(1)?,
+                passed: row.get(2)?,
+                source: row.get(3)?,
+                report: row.get(4)?,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    /// Quarantine this node. False if it already was: the first reason and
+    /// time are kept. This node's replica-set entries are flagged with it.
+    pub fn quarantine_device(&self, reason: &str, check_id: Option<i64>, now: i64) -> Result<bool> {
+        let mut conn = self.lock();
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let n = tx.execute(
+            "INSERT OR IGNORE INTO device_quarantine (id, reason, check_id, since) \
+             VALUES (1, ?1, ?2, ?3)",
+            params![reason, check_id, now],
+        )?;
+        tx.execute(
+            "UPDATE wallet_devices SET quarantined=1 WHERE endpoint IS NULL",
+            [],
+        )?;
+        tx.commit()?;
+        Ok(n == 1)
+    }
+
+    pub fn device_quarantine(&self) -> Result<Option<DeviceQuarantineRow>> {
+        let conn = self.lock();
+        read_device_quarantine(&conn)
+    }
+
+    /// Lift the quarantine; returns what was lifted, None if there was none.
+    pub fn release_device_quarantine(&self) -> Result<Option<DeviceQuarantineRow>> {
+        let mut conn = self.lock();
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let released = read_device_quarantine(&tx)?;
+        tx.execute("DELETE FROM device_quarantine", [])?;
+        tx.execute(
+            "UPDATE wallet_devices SET quarantined=0 WHERE endpoint IS NULL",
+            [],
+        )?;
+        tx.commit()?;
+        Ok(released)
+    }
+
+    // ── Signing backends (signer.rs) ──
+
+    /// Add a wallet whose key already exists outside the TA: its row, the
+    /// backend that holds the key, and the address pinned to it. Refused if
+    /// the address or the key label belongs to another wallet.
+    pub fn register_signer_wallet(&self, w: &WalletRow, signer: &WalletSignerRow) -> Result<()> {
+        let address = w
+            .address
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("register_signer_wallet: no address"))?;
+        if let Some(row) = self.lookup_address(address)? {
+            return Err(anyhow::anyhow!(
+                "address {} already belongs to key {}",
+                address,
+                row.key_id
+            ));
+        }
+        if let Some(existing) = self.wallet_signer_by_label(&signer.key_label)? {
+            return Err(anyhow::anyhow!(
+                "key label {:?} already holds wallet {}",
+                signer.key_label,
+                existing.key_id
+            ));
+        }
+        self.insert_wallet(w)?;
+        self.lock()
+            .execute(
+                "INSERT INTO wallet_signers (key_id, backend, key_label, derivation_path, \
+                 created_at) VALUES (?1,?2,?3,?4,?5)",
+                params![
+                    signer.key_id,
+                    signer.backend,
+                    signer.key_label,
+                    signer.derivation_path,
+                    signer.created_at
+                ],
+            )
+            .context("register_signer_wallet")?;
+        match self.pin_address(
+            &w.key_id,
+            &signer.derivation_path,
+            address,
+            w.public_key.as_deref(),
+        )? {
+            PinCheck::Pinned | PinCheck::Matches => Ok(()),
+            other => Err(anyhow::anyhow!(
+                "address {} not pinned: {:?}",
+                address,
+                other
+            )),
+        }
+    }
+
+    /// The backend of a wallet not signed by the TA; None for TA wallets.
+    pub fn wallet_signer(&self, key_id: &str) -> Result<Option<WalletSignerRow>> {
+        self.query_wallet_signer("key_id", key_id)
+    }
+
+    pub fn wallet_signer_by_label(&self, key_label: &str) -> Result<Option<WalletSignerRow>> {
+        self.query_wallet_signer("key_label", key_label)
+    }
+
+    fn query_wallet_signer(&self, column: &str, value: &str) -> Result<Option<WalletSignerRow>> {
+        let conn = self.lock();
+        conn.query_row(
+            &format!(
+                "SELECT key_id, backend, key_label, derivation_path, created_at \
+                 FROM wallet_signers WHERE {}=?1",
+                column
+            ),
+            params![value],
+            |row| {
+                Ok(WalletSignerRow {
+                    key_id: row.get(0)?,
+                    backend: row.get(1)?,
+                    key_label: row.get(2)?,
+                    derivation_path: row.get(3)?,
+                    created_at: row.get(4)?,
+                })
+            },
+        )
+        .optional()
+        .context("wallet_signer")
+    }
+
+    pub fn list_wallet_signers(&self) -> Result<Vec<WalletSignerRow>> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare(
+            "SELECT key_id, backend, key_label, derivation_path, created_at \
+             FROM wallet_signers ORDER BY created_at, key_id",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(WalletSignerRow {
+                key_id: row.get(0)?,
+                backend: row.get(1)?,
+                key_label: row.get(2)?,
+                derivation_path: row.get(3)?,
+                created_at: row.get(4)?,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    // ── API keys ──
+
+    /// Generate a new API key, store it, and return the plaintext key.
+    pub fn generate_api_key(&self, label: &str) -> Result<String> {
+        let key = format!("kms_{}", Uuid::new_v4().to_string().replace("-", ""));
+        let now = Utc::now().to_rfc3339();
+        let conn = self.lock();
+        conn.execute(
+            "INSERT INTO api_keys (api_key, label, created_at) VALUES (?1, ?2, ?3)",
+            params![key, label, now],
+        )
+        .context("generate_api_key")?;
+        Ok(key)
+    }
+
+    /// Check if an API key is valid.
+    pub fn validate_api_key(&self, key: &str) -> Result<bool> {
+        let conn = self.lock();
+        let count: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM api_keys WHERE api_key = ?1",
+            params![key],
+            |row| row.get(0),
+        )?;
+        Ok(count > 0)
+    }
+
+    /// Label of a stored API key; None for an unknown (or legacy env) key.
+    pub fn api_key_label(&self, key: &str) -> Result<Option<String>> {
+        let conn = self.lock();
+        Ok(conn
+            .query_row(
+                "SELECT label FROM api_keys WHERE api_key = ?1",
+                params![key],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
+    /// List all API keys (returns key, label, created_at).
+    pub fn list_api_keys(&self) -> Result<Vec<(String, String, String)>> {
+        let conn = self.lock();
+        let mut stmt =
+            conn.prepare("SELECT api_key, label, created_at FROM api_keys ORDER BY created_at")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+    }
+
+    /// Revoke (delete) an API key.
+    pub fn revoke_api_key(&self, key: &str) -> Result<bool> {
+        let conn = self.lock();
+        let deleted = conn.execute("DELETE FROM api_keys WHERE api_key = ?1", params![key])?;
+        Ok(deleted > 0)
+    }
+
+    /// Check if any API keys exist in DB.
+    pub fn has_api_keys(&self) -> Result<bool> {
+        let conn = self.lock();
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM api_keys", [], |row| row.get(0))?;
+        Ok(count > 0)
+    }
+
+    pub fn cleanup_expired_challenges(&self) -> Result<usize> {
+        let conn = self.lock();
+        let deleted = conn.execute(
+            "DELETE FROM challenges WHERE expires_at <= ?1",
+            params![current_unix()],
+        )?;
+        Ok(deleted)
+    }
+
+    // ── TX log ──
+
+    pub fn record_tx(
+        &self,
+        op: &str,
+        key_id: Option<&str>,
+        addr: Option<&str>,
+        webauthn: bool,
+        latency_ms: u64,
+        success: bool,
+        is_panic: bool,
+    ) -> Result<()> {
+        self.record_tx_usage(
+            op, key_id, addr, webauthn, latency_ms, success, is_panic, None,
+        )
+    }
+
+    /// `record_tx` for a signing op, tagged with the origin that asked for it.
+    #[allow(clippy::too_many_arguments)]
+    pub fn record_tx_usage(
+        &self,
+        op: &str,
+        key_id: Option<&str>,
+        addr: Option<&str>,
+        webauthn: bool,
+        latency_ms: u64,
+        success: bool,
+        is_panic: bool,
+        usage: Option<&TxUsage>,
+    ) -> Result<()> {
+        let now = Utc::now().to_rfc3339();
+        // Normalize the signing address to lowercase to match wallets.address /
+        // address_index.address (both lowercase). The dormant-key check (#42,
+        // freeze_dormant_keys) matches tx_log rows by key_id OR by `addr` with a
+        // case-sensitive equality; a checksummed addr here (now that lookup_address is
+        // case-insensitive, a checksummed Sign succeeds and reaches record_tx) would miss
+        // that comparison → the wallet looks dormant → wrongly auto-frozen. (codex review)
+        let addr = addr.map(|a| a.to_lowercase());
+        let conn = self.lock();
+        conn.execute(
+            "INSERT INTO tx_log (op, key_id, addr, webauthn, latency_ms, success, is_panic, created_at, \
+             origin, chain_id, value_wei) VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10,?11)",
+            params![op, key_id, addr.as_deref(), webauthn as i32, latency_ms as i64,
+                    success as i32, is_panic as i32, now,
+                    usage.map(|u| u.origin.as_str()),
+                    usage.and_then(|u| u.chain_id).map(|c| c as i64),
+                    usage.and_then(|u| u.value_wei).map(|v| v.to_string())],
+        )?;
+        Ok(())
+    }
+
+    /// Per-origin, per-op counts of a key's signing ops with created_at in
+    /// unix `[from, to)`, matched by key_id or signing address like
+    /// `activity_for_month`.
+    pub fn usage_counts_between(
+        &self,
+        key_id: &str,
+        from: i64,
+        to: i64,
+    ) -> Result<Vec<OriginOpCount>> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT COALESCE(origin,''), op, SUM(success=1), SUM(success=0) FROM tx_log \
+             WHERE {} GROUP BY 1, 2 ORDER BY 1, 2",
+            USAGE_ROWS
+        ))?;
+        let rows = stmt.query_map(params![key_id, from, to], |row| {
+            Ok(OriginOpCount {
+                origin: row.get(0)?,
+                op: row.get(1)?,
+                succeeded: row.get(2)?,
+                failed: row.get(3)?,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// The nonzero native values behind `usage_counts_between`'s successes.
+    pub fn usage_values_between(
+        &self,
+        key_id: &str,
+        from: i64,
+        to: i64,
+    ) -> Result<Vec<OriginValue>> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT COALESCE(origin,''), chain_id, value_wei FROM tx_log \
+             WHERE success=1 AND chain_id IS NOT NULL AND value_wei IS NOT NULL \
+               AND value_wei != '0' AND {} ORDER BY id",
+            USAGE_ROWS
+        ))?;
+        let rows = stmt.query_map(params![key_id, from, to], |row| {
+            let value: String = row.get(2)?;
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?, value))
+        })?;
+        rows.map(|r| {
+            let (origin, chain_id, value) = r?;
+            Ok(OriginValue {
+                origin,
+                chain_id: chain_id as u64,
+                value_wei: value
+                    .parse()
+                    .with_context(|| format!("tx_log value_wei {:?}", value))?,
+            })
+        })
+        .collect()
+    }
+
+    pub fn get_tx_stats(&self) -> Result<TxStats> {
+        let conn = self.lock();
+        let today = chrono::Utc::now().format("%Y-%m-%d").to_string();
+        let today_prefix = format!("{}%", today);
+
+        let total_sign: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM tx_log WHERE op IN ('Sign','SignHash') AND success=1",
+                [],
+                |r| r.get(0),
+            )
+            .unwrap_or(0);
+
+        let daily_sign: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM tx_log WHERE op IN ('Sign','SignHash') AND success=1 AND created_at LIKE ?1",
+            params![&today_prefix], |r| r.get(0),
+        ).unwrap_or(0);
+
+        let total_ops: i64 = conn
+            .query_row("SELECT COUNT(*) FROM tx_log WHERE success=1", [], |r| {
+                r.get(0)
+            })
+            .unwrap_or(0);
+
+        let daily_ops: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM tx_log WHERE success=1 AND created_at LIKE ?1",
+                params![&today_prefix],
+                |r| r.get(0),
+            )
+            .unwrap_or(0);
+
+        let avg_sign_ms: f64 = conn.query_row(
+            "SELECT COALESCE(AVG(latency_ms),0) FROM tx_log WHERE op IN ('Sign','SignHash') AND success=1",
+            [], |r| r.get(0),
+        ).unwrap_or(0.0);
+
+        let avg_derive_ms: f64 = conn.query_row(
+            "SELECT COALESCE(AVG(latency_ms),0) FROM tx_log WHERE op='DeriveAddress' AND success=1",
+            [], |r| r.get(0),
+        ).unwrap_or(0.0);
+
+        let panic_count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM tx_log WHERE is_panic=1", [], |r| {
+                r.get(0)
+            })
+            .unwrap_or(0);
+
+        let error_count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM tx_log WHERE success=0", [], |r| {
+                r.get(0)
+            })
+            .unwrap_or(0);
+
+        let webauthn_count: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM tx_log WHERE webauthn=1 AND success=1",
+                [],
+                |r| r.get(0),
+            )
+            .unwrap_or(0);
+
+        Ok(TxStats {
+            total_sign,
+            daily_sign,
+            total_ops,
+            daily_ops,
+            avg_sign_ms,
+            avg_derive_ms,
+            panic_count,
+            error_count,
+            webauthn_count,
+        })
+    }
+}
+
+/// The tx_log rows a usage report covers: signing ops of key ?1 in unix
+/// [?2, ?3).
+const USAGE_ROWS: &str = "op IN ('Sign','SignHash','SiweSign') \
+     AND CAST(strftime('%s', created_at) AS INTEGER) >= ?2 \
+     AND CAST(strftime('%s', created_at) AS INTEGER) < ?3 \
+     AND ( \
+       key_id=?1 \
+       OR addr=(SELECT address FROM wallets WHERE key_id=?1) \
+       OR addr IN (SELECT address FROM address_index WHERE key_id=?1) \
+     )";
+
+fn current_unix() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64
+}
+
+fn read_device_quarantine(conn: &Connection) -> Result<Option<DeviceQuarantineRow>> {
+    Ok(conn
+        .query_row(
+            "SELECT reason, check_id, since FROM device_quarantine WHERE id=1",
+            [],
+            |row| {
+                Ok(DeviceQuarantineRow {
+                    reason: row.get(0)?,
+                    check_id: row.get(1)?,
+                    since: row.get(2)?,
+                })
+            },
+        )
+        .optional()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_db() -> KmsDb {
+        KmsDb::open_memory().unwrap()
+    }
+
+    fn sample_wallet(key_id: &str) -> WalletRow {
+        WalletRow {
+            key_id: key_id.to_string(),
+            address: None,
+            public_key: None,
+            derivation_path: None,
+            description: "test".to_string(),
+            key_usage: "SIGN_VERIFY".to_string(),
+            key_spec: "ECC_SECG_P256K1".to_string(),
+            origin: "EXTERNAL_KMS".to_string(),
+            passkey_pubkey: Some("0x04abcd".to_string()),
+            credential_id: None,
+            sign_count: 0,
+            status: "creating".to_string(),
+            error_msg: None,
+            created_at: "2026-03-02T00:00:00Z".to_string(),
+        }
+    }
+
+    #[test]
+    fn insert_and_get_wallet() {
+        let db = test_db();
+        let w = sample_wallet("w1");
+        db.insert_wallet(&w).unwrap();
+        let got = db.get_wallet("w1").unwrap().unwrap();
+        assert_eq!(got.key_id, "w1");
+        assert_eq!(got.description, "test");
+        assert_eq!(got.passkey_pubkey, Some("0x04abcd".to_string()));
+
+        assert_eq!(
+            db.get_passkey_attestation("w1").unwrap(),
+            Some(PasskeyAttestation::default())
+        );
+        let att = PasskeyAttestation {
+            attestation_type: Some("basic".to_string()),
+            aaguid: Some("2fc0579f-8113-47ea-b116-bb5a8db9202a".to_string()),
+            certification: Some("FIDO_CERTIFIED_L2".to_string()),
+            role: Some("treasury".to_string()),
+        };
+        assert!(db.set_passkey_attestation("w1", &att).unwrap());
+        assert_eq!(db.get_passkey_attestation("w1").unwrap(), Some(att));
+        assert_eq!(db.get_passkey_attestation("nope").unwrap(), None);
+    }
+
+    #[test]
+    fn migrations_upgrade_legacy_db_once() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE wallets (key_id TEXT PRIMARY KEY);
+             CREATE TABLE p256_session_keys (wallet_id TEXT, tee_deleted INTEGER);
+             CREATE TABLE account_audit (id INTEGER PRIMARY KEY, account TEXT);
+             CREATE TABLE wallet_devices (key_id TEXT, device_id TEXT);
+             CREATE TABLE tx_log (id INTEGER PRIMARY KEY, op TEXT);",
+        )
+        .unwrap();
+        migrate(&conn).unwrap();
+        assert!(column_exists(&conn, "wallets", "lifecycle_status").unwrap());
+        assert!(column_exists(&conn, "account_audit", "correlation_id").unwrap());
+        assert!(column_exists(&conn, "wallets", "passkey_role").unwrap());
+        assert!(column_exists(&conn, "wallet_devices", "quarantined").unwrap());
+        assert!(column_exists(&conn, "tx_log", "value_wei").unwrap());
+        let v: u32 = conn
+            .query_row("PRAGMA user_version", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(v as usize, MIGRATIONS.len());
+        migrate(&conn).unwrap();
+        assert_eq!(
+            test_db().schema_version().unwrap() as usize,
+            MIGRATIONS.len()
+        );
+    }
+
+    #[test]
+    fn snapshot_is_a_complete_current_db() {
+        let db = test_db();
+        db.insert_wallet(&sample_wallet("w1")).unwrap();
+        let path = std::env::temp_dir().join(format!("kms-snapshot-{}.db", Uuid::new_v4()));
+        db.snapshot_to(&path).unwrap();
+        assert!(db.snapshot_to(&path).is_err(), "never overwrites");
+        let info = inspect_file(&path).unwrap();
+        assert_eq!(info.schema_version, current_schema_version());
+        assert_eq!(info.wallets, 1);
+        let copy = KmsDb::open(path.to_str().unwrap()).unwrap();
+        assert!(copy.get_wallet("w1").unwrap().is_some());
+        drop(copy);
+        std::fs::write(&path, b"not a database").unwrap();
+        assert!(inspect_file(&path).is_err());
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn wallet_not_found() {
+        let db = test_db();
+        assert!(db.get_wallet("nope").unwrap().is_none());
+    }
+
+    #[test]
+    fn scoped_session_key_index_never_reused() {
+        let db = test_db();
+        db.insert_wallet(&sample_wallet("w1")).unwrap();
+        assert_eq!(db.next_scoped_session_index("w1").unwrap(), 0);
+        db.insert_scoped_session_key(&ScopedSessionKeyRow {
+            wallet_id: "w1".into(),
+            session_index: 0,
+            session_address: "0x01".into(),
+            permission_hash: "0x02".into(),
+            chain_id: 10,
+            expires_at: 1_700_000_000,
+            status: "active".into(),
+            created_at: "2026-03-02T00:00:00Z".into(),
+            revoked_at: None,
+        })
+        .unwrap();
+        assert!(db.revoke_scoped_session_key("w1", 0).unwrap());
+        assert!(!db.revoke_scoped_session_key("w1", 0).unwrap());
+        let row = db.get_scoped_session_key("w1", 0).unwrap().unwrap();
+        assert_eq!(row.status, "revoked");
+        assert_eq!(row.chain_id, 10);
+        assert_eq!(db.next_scoped_session_index("w1").unwrap(), 1);
+    }
+
+    #[test]
+    fn contact_binding_telegram_roundtrip() {
+        let db = test_db();
+        db.insert_wallet(&sample_wallet("acct1")).unwrap();
+        // begin → pending, not verified
+        db.begin_contact_binding("acct1", "telegram", "code123", Some("@alice"), 300)
+            .unwrap();
+        assert!(db.get_verified_contacts("acct1").unwrap().is_empty());
+        // claim → bot records tentative chat + verify_token (status=claimed)
+        assert!(db
+            .claim_contact_binding(
+                "code123",
+                "chat-789",
+                Some("@alice"),
+                "vtok456",
+                Some("aastarbot"),
+                300
+            )
+            .unwrap());
+        assert!(db.get_verified_contacts("acct1").unwrap().is_empty());
+        // confirm with WRONG token → rejected, still not verified
+        assert!(!db
+            .confirm_contact_binding("acct1", "code123", "WRONG")
+            .unwrap());
+        assert!(db.get_verified_contacts("acct1").unwrap().is_empty());
+        // confirm under the WRONG account → rejected (account match)
+        assert!(!db
+            .confirm_contact_binding("acct2", "code123", "vtok456")
+            .unwrap());
+        // confirm with right account+token → verified
+        assert!(db
+            .confirm_contact_binding("acct1", "code123", "vtok456")
+            .unwrap());
+        let c = db.get_verified_contacts("acct1").unwrap();
+        assert_eq!(c.len(), 1);
+        assert_eq!(c[0].channel, "telegram");
+        assert_eq!(c[0].contact_ref, Some("chat-789".to_string()));
+        assert_eq!(c[0].status, "verified");
+        // double-confirm → false (code cleared after verify; no re-verify)
+        assert!(!db
+            .confirm_contact_binding("acct1", "code123", "vtok456")
+            .unwrap());
+        // unbind → deleted
+        assert!(db.unbind_contact("acct1", "telegram").unwrap());
+        assert!(db.get_verified_contacts("acct1").unwrap().is_empty());
+    }
+
+    #[test]
+    fn contact_binding_claim_unknown_code_rejected() {
+        let db = test_db();
+        db.insert_wallet(&sample_wallet("acct2")).unwrap();
+        // no begin → claiming an unknown code must fail (bot can't conjure a binding)
+        assert!(!db
+            .claim_contact_binding("ghost", "chat", None, "tok", None, 300)
+            .unwrap());
+        assert!(db.get_verified_contacts("acct2").unwrap().is_empty());
+    }
+
+    #[test]
+    fn contact_binding_email_roundtrip() {
+        let db = test_db();
+        db.insert_wallet(&sample_wallet("acct3")).unwrap();
+        db.begin_email_binding(
+            "acct3",
+            "ecode",
+            "alice@example.com",
+            "a***@example.com",
+            300,
+        )
+        .unwrap();
+        // the telegram bot can't claim an email binding
+        assert!(!db
+            .claim_contact_binding("ecode", "chat", None, "tok", None, 300)
+            .unwrap());
+        assert_eq!(
+            db.claim_email_binding("ecode", "etok", Some("relay"), 300)
+                .unwrap()
+                .as_deref(),
+            Some("alice@example.com")
+        );
+        // first claim wins
+        assert!(db
+            .claim_email_binding("ecode", "other", None, 300)
+            .unwrap()
+            .is_none());
+        assert!(db.get_verified_contacts("acct3").unwrap().is_empty());
+        assert!(db
+            .confirm_contact_binding("acct3", "ecode", "etok")
+            .unwrap());
+        let c = db.get_verified_contacts("acct3").unwrap();
+        assert_eq!(c[0].channel, "email");
+        assert_eq!(c[0].contact_ref.as_deref(), Some("alice@example.com"));
+
+        db.record_account_event("acct3", "contact_verified", Some("email"), None)
+            .unwrap();
+        db.record_account_event("acct3", "passkey_changed", None, Some("req-1"))
+            .unwrap();
+        let ev = db.list_account_events("acct3", 10, None).unwrap();
+        assert_eq!(ev.len(), 2);
+        assert_eq!(ev[0].event, "passkey_changed");
+        assert_eq!(ev[0].correlation_id.as_deref(), Some("req-1"));
+        let older = db.list_account_events("acct3", 10, Some(ev[0].id)).unwrap();
+        assert_eq!(older.len(), 1);
+        assert_eq!(older[0].event, "contact_verified");
+        assert!(db
+            .list_account_events("acct1", 10, None)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn lookup_address_is_case_insensitive() {
+        // Locks the address-case fix: SDK (#203) sends EIP-55 checksummed addresses, DVT
+        // sends userOp.sender — both must resolve regardless of case, or /contact +
+        // /verify-confirm-assertion fail-closed for legit users.
+        let db = test_db();
+        db.insert_wallet(&sample_wallet("w-addr")).unwrap();
+        let checksummed = "0xAbCdEf0000000000000000000000000000000001";
+        let lowercased = "0xabcdef0000000000000000000000000000000001";
+        // Store via a checksummed address → normalized lowercase on write.
+        db.upsert_address(checksummed, "w-addr", "m/44'/60'/0'/0/0", None)
+            .unwrap();
+        // Look up either case → both resolve to the same wallet.
+        assert_eq!(
+            db.lookup_address(lowercased).unwrap().map(|r| r.key_id),
+            Some("w-addr".to_string())
+        );
+        assert_eq!(
+            db.lookup_address(checksummed).unwrap().map(|r| r.key_id),
+            Some("w-addr".to_string())
+        );
+        // Stored value itself is lowercase.
+        assert_eq!(
+            db.lookup_address(lowercased).unwrap().unwrap().address,
+            lowercased
+        );
+    }
+
+    #[test]
+    fn last_used_at_resolves_checksummed_tx_addr() {
+        // Locks the dormant-freeze fix: an address-mode Sign logs tx_log.addr by address
+        // only. The SDK sends a checksummed address; record_tx must lowercase it so the
+        // dormant check (last_used_at matches addr vs the lowercase address columns) finds
+        // the activity — else the wallet looks dormant and gets wrongly auto-frozen (#42).
+        let db = test_db();
+        db.insert_wallet(&sample_wallet("w-tx")).unwrap();
+        let checksummed = "0xAbCdEf0000000000000000000000000000000002";
+        db.upsert_address(checksummed, "w-tx", "m/44'/60'/0'/0/0", None)
+            .unwrap();
+        db.record_tx("Sign", None, Some(checksummed), true, 5, true, false)
+            .unwrap();
+        // Activity is found via the (now lowercase) addr → wallet is NOT dormant.
+        assert!(db.last_used_at("w-tx").unwrap().is_some());
+    }
+
+    #[test]
+    fn freeze_dormant_resolves_checksummed_addr_activity() {
+        // Covers freeze_dormant_keys' INDEPENDENT SQL copy (Opus flagged last_used_at's test
+        // doesn't reach it). Write-time normalization (record_tx) makes tx_log.addr lowercase,
+        // so this second case-sensitive addr comparison also matches → an address-mode Sign
+        // logged with a checksummed addr keeps the wallet active. Without the fix it would
+        // fall back to created_at (old) and wrongly freeze a live key (#42).
+        use chrono::Utc;
+        let db = test_db();
+        db.insert_wallet(&sample_wallet("w-fz")).unwrap(); // lifecycle_status defaults 'active'
+        let checksummed = "0xAbCdEf0000000000000000000000000000000004";
+        db.upsert_address(checksummed, "w-fz", "m/44'/60'/0'/0/0", None)
+            .unwrap();
+        db.record_tx("Sign", None, Some(checksummed), true, 5, true, false)
+            .unwrap();
+        let now = Utc::now().timestamp() + 10;
+        let frozen = db.freeze_dormant_keys(now, 86_400).unwrap();
+        assert!(!frozen.contains(&"w-fz".to_string()));
+    }
+
+    #[test]
+    fn wallet_exists_check() {
+        let db = test_db();
+        db.insert_wallet(&sample_wallet("w1")).unwrap();
+        assert!(db.wallet_exists("w1").unwrap());
+        assert!(!db.wallet_exists("w2").unwrap());
+    }
+
+    #[test]
+    fn update_wallet_derived() {
+        let db = test_db();
+        db.insert_wallet(&sample_wallet("w1")).unwrap();
+        db.update_wallet_derived("w1", "0xaddr", "0xpub", "m/44'/60'/0'/0/0", "ready")
+            .unwrap();
+        let got = db.get_wallet("w1").unwrap().unwrap();
+        assert_eq!(got.address.as_deref(), Some("0xaddr"));
+        assert_eq!(got.status, "ready");
+    }
+
+    #[test]
+    fn delete_wallet_cascades_address() {
+        let db = test_db();
+        db.insert_wallet(&sample_wallet("w1")).unwrap();
+        db.upsert_address("0xaddr", "w1", "m/0", None).unwrap();
+        db.delete_wallet("w1").unwrap();
+        assert!(db.lookup_address("0xaddr").unwrap().is_none());
+    }
+
+    #[test]
+    fn list_wallets_returns_all() {
+        let db = test_db();
+        db.insert_wallet(&sample_wallet("w1")).unwrap();
+        db.insert_wallet(&sample_wallet("w2")).unwrap();
+        assert_eq!(db.list_wallets().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn address_upsert_and_lookup() {
+        let db = test_db();
+        db.insert_wallet(&sample_wallet("w1")).unwrap();
+        db.upsert_address("0xaaaa", "w1", "m/44'/60'/0'/0/0", Some("0xpub"))
+            .unwrap();
+        let row = db.lookup_address("0xaaaa").unwrap().unwrap();
+        assert_eq!(row.key_id, "w1");
+        assert_eq!(row.public_key.as_deref(), Some("0xpub"));
+    }
+
+    #[test]
+    fn challenge_store_and_consume() {
+        let db = test_db();
+        let challenge = vec![1u8, 2, 3, 4];
+        db.store_challenge("c1", &challenge, None, "registration", "example.com", 300)
+            .unwrap();
+        let got = db.consume_challenge("c1").unwrap().unwrap();
+        assert_eq!(got.challenge, challenge);
+        assert_eq!(got.purpose, "registration");
+        // Consumed — should be gone
+        assert!(db.consume_challenge("c1").unwrap().is_none());
+    }
+
+    #[test]
+    fn challenge_of_another_purpose_is_left_in_place() {
+        let db = test_db();
+        db.store_challenge("c1", &[1], None, "authentication", "example.com", 300)
+            .unwrap();
+        assert!(db
+            .consume_challenge_for("c1", "registration")
+            .unwrap()
+            .is_none());
+        let got = db.consume_challenge_for("c1", "authentication").unwrap();
+        assert_eq!(got.unwrap().purpose, "authentication");
+        assert!(db.consume_challenge("c1").unwrap().is_none());
+    }
+
+    #[test]
+    fn challenge_not_found() {
+        let db = test_db();
+        assert!(db.consume_challenge("nope").unwrap().is_none());
+    }
+
+    #[test]
+    fn api_key_generate_and_validate() {
+        let db = test_db();
+        assert!(!db.has_api_keys().unwrap());
+        let key = db.generate_api_key("test-service").unwrap();
+        assert!(key.starts_with("kms_"));
+        assert!(db.has_api_keys().unwrap());
+        assert!(db.validate_api_key(&key).unwrap());
+        assert!(!db.validate_api_key("kms_invalid").unwrap());
+    }
+
+    #[test]
+    fn api_key_list_and_revoke() {
+        let db = test_db();
+        let k1 = db.generate_api_key("svc-a").unwrap();
+        let _k2 = db.generate_api_key("svc-b").unwrap();
+        assert_eq!(db.list_api_keys().unwrap().len(), 2);
+        assert!(db.revoke_api_key(&k1).unwrap());
+        assert!(!db.validate_api_key(&k1).unwrap());
+        assert_eq!(db.list_api_keys().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn update_passkey() {
+        let db = test_db();
+        db.insert_wallet(&sample_wallet("w1")).unwrap();
+        db.update_wallet_sign_count("w1", 42).unwrap();
+        db.update_wallet_passkey("w1", "0x04new", Some("cred-123"))
+            .unwrap();
+        let got = db.get_wallet("w1").unwrap().unwrap();
+        assert_eq!(got.passkey_pubkey.as_deref(), Some("0x04new"));
+        assert_eq!(got.credential_id.as_deref(), Some("cred-123"));
+        assert_eq!(got.sign_count, 0);
+    }
+
+    #[test]
+    fn concurrent_access() {
+        use std::thread;
+        let db = test_db();
+        db.insert_wallet(&sample_wallet("w-concurrent")).unwrap();
+
+        let handles: Vec<_> = (0..10)
+            .map(|i| {
+                let db = db.clone();
+                thread::spawn(move || {
+                    let addr = format!("0xaddr{}", i);
+                    db.upsert_address(&addr, "w-concurrent", &format!("m/0/{}", i), None)
+                        .unwrap();
+                    db.get_wallet("w-concurrent").unwrap().unwrap();
+                })
+            })
+            .collect();
+
+        for h in handles {
+            h.join().unwrap();
+        }
+        // All 10 addresses should exist
+        for i in 0..10 {
+            let addr = format!("0xaddr{}", i);
+            assert!(db.lookup_address(&addr).unwrap().is_some());
+        }
+    }
+
+    #[test]
+    fn update_wallet_status_with_error() {
+        let db = test_db();
+        db.insert_wallet(&sample_wallet("w-err")).unwrap();
+        db.update_wallet_status("w-err", "error", Some("PBKDF2 timeout"))
+            .unwrap();
+        let got = db.get_wallet("w-err").unwrap().unwrap();
+        assert_eq!(got.status, "error");
+        assert_eq!(got.error_msg.as_deref(), Some("PBKDF2 timeout"));
+    }
+
+    #[test]
+    fn update_sign_count() {
+        let db = test_db();
+        db.insert_wallet(&sample_wallet("w-sc")).unwrap();
+        db.update_wallet_sign_count("w-sc", 42).unwrap();
+        let got = db.get_wallet("w-sc").unwrap().unwrap();
+        assert_eq!(got.sign_count, 42);
+    }
+
+    #[test]
+    fn cleanup_expired_challenges() {
+        let db = test_db();
+        // Store a challenge with -1 second TTL (already expired)
+        db.store_challenge("c-expired", &[1, 2, 3], None, "auth", "localhost", -1)
+            .unwrap();
+        db.store_challenge("c-valid", &[4, 5, 6], None, "auth", "localhost", 300)
+            .unwrap();
+
+        let cleaned = db.cleanup_expired_challenges().unwrap();
+        assert_eq!(cleaned, 1);
+
+        // Valid challenge should still be consumable
+        assert!(db.consume_challenge("c-valid").unwrap().is_some());
+        // Expired one was cleaned up
+        assert!(db.consume_challenge("c-expired").unwrap().is_none());
+    }
+
+    // ── Issue #42: dormant-key freeze + last_used_at ──
+
+    #[test]
+    fn lifecycle_status_defaults_active_and_round_trips() {
+        let db = test_db();
+        db.insert_wallet(&sample_wallet("w-lc")).unwrap();
+        assert_eq!(
+            db.get_lifecycle_status("w-lc").unwrap().as_deref(),
+            Some("active")
+        );
+        assert!(db.set_lifecycle_status("w-lc", "frozen").unwrap());
+        assert_eq!(
+            db.get_lifecycle_status("w-lc").unwrap().as_deref(),
+            Some("frozen")
+        );
+        // Unknown key -> None
+        assert!(db.get_lifecycle_status("nope").unwrap().is_none());
+        assert!(!db.set_lifecycle_status("nope", "active").unwrap());
+    }
+
+    #[test]
+    fn last_used_at_none_then_some() {
+        let db = test_db();
+        db.insert_wallet(&sample_wallet("w-lu")).unwrap();
+        // No tx_log rows yet.
+        assert!(db.last_used_at("w-lu").unwrap().is_none());
+        // A failed op does not count.
+        db.record_tx("Sign", Some("w-lu"), None, false, 5, false, false)
+            .unwrap();
+        assert!(db.last_used_at("w-lu").unwrap().is_none());
+        // A successful op sets it.
+        db.record_tx("Sign", Some("w-lu"), None, false, 5, true, false)
+            .unwrap();
+        assert!(db.last_used_at("w-lu").unwrap().is_some());
+    }
+
+    #[test]
+    fn activity_for_month_groups_by_op() {
+        let db = test_db();
+        let mut w = sample_wallet("w-act");
+        w.address = Some("0x00000000000000000000000000000000000000a1".into());
+        db.insert_wallet(&w).unwrap();
+        db.record_tx("Sign", Some("w-act"), None, false, 5, true, false)
+            .unwrap();
+        db.record_tx("Sign", None, w.address.as_deref(), false, 5, false, false)
+            .unwrap();
+        db.record_tx("SignHash", Some("w-act"), None, false, 5, true, false)
+            .unwrap();
+        db.record_tx("Sign", Some("other"), None, false, 5, true, false)
+            .unwrap();
+        let month = chrono::Utc::now().format("%Y-%m").to_string();
+        let got = db.activity_for_month("w-act", &month).unwrap();
+        assert_eq!(
+            got,
+            vec![
+                OpCount {
+                    op: "Sign".into(),
+                    succeeded: 1,
+                    failed: 1
+                },
+                OpCount {
+                    op: "SignHash".into(),
+                    succeeded: 1,
+                    failed: 0
+                },
+            ]
+        );
+        assert!(db
+            .activity_for_month("w-act", "2001-01")
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn usage_is_counted_per_origin_and_range() {
         let db = test_db();
-        let w = sample_wallet("w1");
+        let mut w = sample_wallet("w-use");
+        w.address = Some("0x00000000000000000000000000000000000000b2".into());
         db.insert_wallet(&w).unwrap();
-        let got = db.get_wallet("w1").unwrap().unwrap();
-        assert_eq!(got.key_id, "w1");
-        assert_eq!(got.description, "test");
-        assert_eq!(got.passkey_pubkey, Some("0x04abcd".to_string()));
+        let tag = |origin: &str, value: Option<u128>| TxUsage {
+            origin: origin.into(),
+            chain_id: value.map(|_| 1),
+            value_wei: value,
+        };
+        let log = |op: &str, key_id: Option<&str>, success: bool, usage: Option<&TxUsage>| {
+            let addr = key_id.map_or(w.address.as_deref(), |_| None);
+            db.record_tx_usage(op, key_id, addr, true, 5, success, false, usage)
+                .unwrap()
+        };
+        let app = tag("app.example", Some(5));
+        log("Sign", None, true, Some(&app));
+        log("Sign", None, false, Some(&app));
+        log(
+            "Sign",
+            Some("w-use"),
+            true,
+            Some(&tag("app.example", Some(0))),
+        );
+        log(
+            "SiweSign",
+            Some("w-use"),
+            true,
+            Some(&tag("login.example", None)),
+        );
+        log("SignHash", Some("w-use"), true, None);
+        log("DeriveAddress", Some("w-use"), true, None);
+        log("Sign", Some("other"), true, Some(&app));
+
+        let now = chrono::Utc::now().timestamp();
+        let counts = db
+            .usage_counts_between("w-use", now - 60, now + 60)
+            .unwrap();
+        let row = |origin: &str, op: &str, succeeded, failed| OriginOpCount {
+            origin: origin.into(),
+            op: op.into(),
+            succeeded,
+            failed,
+        };
+        assert_eq!(
+            counts,
+            vec![
+                row("", "SignHash", 1, 0),
+                row("app.example", "Sign", 2, 1),
+                row("login.example", "SiweSign", 1, 0),
+            ]
+        );
+        assert_eq!(
+            db.usage_values_between("w-use", now - 60, now + 60)
+                .unwrap(),
+            vec![OriginValue {
+                origin: "app.example".into(),
+                chain_id: 1,
+                value_wei: 5,
+            }]
+        );
+        assert!(db
+            .usage_counts_between("w-use", now + 60, now + 120)
+            .unwrap()
+            .is_empty());
     }
 
     #[test]
-    fn wallet_not_found() {
+    fn freeze_dormant_by_created_at_fallback() {
         let db = test_db();
-        assert!(db.get_wallet("nope").unwrap().is_none());
+        // sample_wallet created_at is 2026-03-02 (well in the past relative to now).
+        db.insert_wallet(&sample_wallet("w-old")).unwrap();
+        let now = chrono::Utc::now().timestamp();
+        // Threshold of 1 day: the never-used, old key is dormant -> frozen.
+        let frozen = db.freeze_dormant_keys(now, 24 * 60 * 60).unwrap();
+        assert_eq!(frozen, vec!["w-old".to_string()]);
+        assert_eq!(
+            db.get_lifecycle_status("w-old").unwrap().as_deref(),
+            Some("frozen")
+        );
     }
 
     #[test]
-    fn contact_binding_telegram_roundtrip() {
+    fn freeze_skips_recently_used_keys() {
         let db = test_db();
-        db.insert_wallet(&sample_wallet("acct1")).unwrap();
-        // begin → pending, not verified
-        db.begin_contact_binding("acct1", "telegram", "code123", Some("@alice"), 300)
+        db.insert_wallet(&sample_wallet("w-active")).unwrap();
+        // Recent successful op (record_tx stamps now).
+        db.record_tx("Sign", Some("w-active"), None, false, 5, true, false)
             .unwrap();
-        assert!(db.get_verified_contacts("acct1").unwrap().is_empty());
-        // claim → bot records tentative chat + verify_token (status=claimed)
-        assert!(db
-            .claim_contact_binding(
-                "code123",
-                "chat-789",
-                Some("@alice"),
-                "vtok456",
-                Some("aastarbot"),
-                300
-            )
-            .unwrap());
-        assert!(db.get_verified_contacts("acct1").unwrap().is_empty());
-        // confirm with WRONG token → rejected, still not verified
-        assert!(!db
-            .confirm_contact_binding("acct1", "code123", "WRONG")
-            .unwrap());
-        assert!(db.get_verified_contacts("acct1").unwrap().is_empty());
-        // confirm under the WRONG account → rejected (account match)
-        assert!(!db
-            .confirm_contact_binding("acct2", "code123", "vtok456")
-            .unwrap());
-        // confirm with right account+token → verified
-        assert!(db
-            .confirm_contact_binding("acct1", "code123", "vtok456")
-            .unwrap());
-        let c = db.get_verified_contacts("acct1").unwrap();
-        assert_eq!(c.len(), 1);
-        assert_eq!(c[0].channel, "telegram");
-        assert_eq!(c[0].contact_ref, Some("chat-789".to_string()));
-        assert_eq!(c[0].status, "verified");
-        // double-confirm → false (code cleared after verify; no re-verify)
-        assert!(!db
-            .confirm_contact_binding("acct1", "code123", "vtok456")
-            .unwrap());
-        // unbind → deleted
-        assert!(db.unbind_contact("acct1", "telegram").unwrap());
-        assert!(db.get_verified_contacts("acct1").unwrap().is_empty());
+        let now = chrono::Utc::now().timestamp();
+        // Even with a 1-second threshold, the just-now activity keeps it active.
+        let frozen = db.freeze_dormant_keys(now, 1).unwrap();
+        assert!(frozen.is_empty());
+        assert_eq!(
+            db.get_lifecycle_status("w-active").unwrap().as_deref(),
+            Some("active")
+        );
     }
 
     #[test]
-    fn contact_binding_claim_unknown_code_rejected() {
+    fn freeze_is_idempotent_and_skips_already_frozen() {
         let db = test_db();
-        db.insert_wallet(&sample_wallet("acct2")).unwrap();
-        // no begin → claiming an unknown code must fail (bot can't conjure a binding)
-        assert!(!db
-            .claim_contact_binding("ghost", "chat", None, "tok", None, 300)
-            .unwrap());
-        assert!(db.get_verified_contacts("acct2").unwrap().is_empty());
+        db.insert_wallet(&sample_wallet("w-f")).unwrap();
+        db.set_lifecycle_status("w-f", "frozen").unwrap();
+        let now = chrono::Utc::now().timestamp();
+        // Already frozen -> not returned again.
+        let frozen = db.freeze_dormant_keys(now, 1).unwrap();
+        assert!(frozen.is_empty());
     }
 
     #[test]
-    fn lookup_address_is_case_insensitive() {
-        // Locks the address-case fix: SDK (#203) sends EIP-55 checksummed addresses, DVT
-        // sends userOp.sender — both must resolve regardless of case, or /contact +
-        // /verify-confirm-assertion fail-closed for legit users.
+    fn freeze_skips_address_mode_recently_used() {
+        // Regression (codex review): address-based Sign/SignHash log activity by
+        // `addr` with key_id=None. last_used_at / freeze_dormant_keys must still
+        // credit that activity to the wallet via its address (primary or derived),
+        // otherwise an actively-used address-mode key looks dormant and is wrongly
+        // frozen.
         let db = test_db();
-        db.insert_wallet(&sample_wallet("w-addr")).unwrap();
-        let checksummed = "0xAbCdEf0000000000000000000000000000000001";
-        let lowercased = "0xabcdef0000000000000000000000000000000001";
-        // Store via a checksummed address → normalized lowercase on write.
-        db.upsert_address(checksummed, "w-addr", "m/44'/60'/0'/0/0", None)
+        let mut w = sample_wallet("w-addr");
+        w.address = Some("0xdeadbeef00000000000000000000000000000000".to_string());
+        db.insert_wallet(&w).unwrap();
+        // Recent op recorded by ADDRESS only (no key_id) — the address-mode path.
+        db.record_tx("Sign", None, w.address.as_deref(), false, 5, true, false)
             .unwrap();
-        // Look up either case → both resolve to the same wallet.
-        assert_eq!(
-            db.lookup_address(lowercased).unwrap().map(|r| r.key_id),
-            Some("w-addr".to_string())
+        let now = chrono::Utc::now().timestamp();
+        // Even a 1-second threshold must not freeze it: the address activity counts.
+        let frozen = db.freeze_dormant_keys(now, 1).unwrap();
+        assert!(
+            frozen.is_empty(),
+            "address-mode activity must keep the key active"
         );
         assert_eq!(
-            db.lookup_address(checksummed).unwrap().map(|r| r.key_id),
-            Some("w-addr".to_string())
+            db.get_lifecycle_status("w-addr").unwrap().as_deref(),
+            Some("active")
         );
-        // Stored value itself is lowercase.
-        assert_eq!(
-            db.lookup_address(lowercased).unwrap().unwrap().address,
-            lowercased
+        assert!(
+            db.last_used_at("w-addr").unwrap().is_some(),
+            "last_used_at must see the address-mode op"
         );
     }
 
     #[test]
-    fn last_used_at_resolves_checksummed_tx_addr() {
-        // Locks the dormant-freeze fix: an address-mode Sign logs tx_log.addr by address
-        // only. The SDK sends a checksummed address; record_tx must lowercase it so the
-        // dormant check (last_used_at matches addr vs the lowercase address columns) finds
-        // the activity — else the wallet looks dormant and gets wrongly auto-frozen (#42).
+    fn offline_request_completes_once() {
         let db = test_db();
-        db.insert_wallet(&sample_wallet("w-tx")).unwrap();
-        let checksummed = "0xAbCdEf0000000000000000000000000000000002";
-        db.upsert_address(checksummed, "w-tx", "m/44'/60'/0'/0/0", None)
-            .unwrap();
-        db.record_tx("Sign", None, Some(checksummed), true, 5, true, false)
+        let now = current_unix();
+        db.insert_offline_request("aa01", "w-1", "0xABC", "aa-offline-req:x", now, now + 600)
             .unwrap();
-        // Activity is found via the (now lowercase) addr → wallet is NOT dormant.
-        assert!(db.last_used_at("w-tx").unwrap().is_some());
+        db.insert_offline_request(
+            "aa02",
+            "w-1",
+            "0xabc",
+            "aa-offline-req:y",
+            now - 700,
+            now - 100,
+        )
+        .unwrap();
+        let row = db.get_offline_request("aa01").unwrap().unwrap();
+        assert_eq!(
+            (row.status.as_str(), row.address.as_str()),
+            ("pending", "0xabc")
+        );
+        assert!(db.complete_offline_request("aa01", "0xhash").unwrap());
+        // a second import of the same response is a replay
+        assert!(!db.complete_offline_request("aa01", "0xhash").unwrap());
+        // expired before the response came back
+        assert!(!db.complete_offline_request("aa02", "0xhash").unwrap());
+        assert!(db.get_offline_request("missing").unwrap().is_none());
+        let row = db.get_offline_request("aa01").unwrap().unwrap();
+        assert_eq!(row.tx_hash.as_deref(), Some("0xhash"));
     }
 
     #[test]
-    fn freeze_dormant_resolves_checksummed_addr_activity() {
-        // Covers freeze_dormant_keys' INDEPENDENT SQL copy (Opus flagged last_used_at's test
-        // doesn't reach it). Write-time normalization (record_tx) makes tx_log.addr lowercase,
-        // so this second case-sensitive addr comparison also matches → an address-mode Sign
-        // logged with a checksummed addr keeps the wallet active. Without the fix it would
-        // fall back to created_at (old) and wrongly freeze a live key (#42).
-        use chrono::Utc;
+    fn tx_history_replacement_and_confirmation() {
         let db = test_db();
-        db.insert_wallet(&sample_wallet("w-fz")).unwrap(); // lifecycle_status defaults 'active'
-        let checksummed = "0xAbCdEf0000000000000000000000000000000004";
-        db.upsert_address(checksummed, "w-fz", "m/44'/60'/0'/0/0", None)
-            .unwrap();
-        db.record_tx("Sign", None, Some(checksummed), true, 5, true, false)
+        let entry = |nonce: u64, gas_price: u128| SignedTxEntry {
+            key_id: "w-1".into(),
+            address: "0xABC".into(),
+            derivation_path: "m/44'/60'/0'/0/0".into(),
+            chain_id: 1,
+            nonce,
+            gas_price,
+            signed_tx: "0x00".into(),
+            tx_hash: format!("0x{}", nonce),
+        };
+        let a = db.insert_tx_history(&entry(4, 100), None).unwrap();
+        let c = db.insert_tx_history(&entry(5, 100), None).unwrap();
+        let b = db.insert_tx_history(&entry(4, 115), Some(a)).unwrap();
+        // a racing second rescue of the same original loses
+        assert!(db.insert_tx_history(&entry(4, 130), Some(a)).is_err());
+
+        let pending = db.list_pending_txs("0xabc", 1).unwrap();
+        assert_eq!(
+            pending
+                .iter()
+                .map(|r| (r.id, r.entry.nonce))
+                .collect::<Vec<_>>(),
+            vec![(b, 4), (c, 5)]
+        );
+        assert_eq!(pending[0].entry.gas_price, 115);
+        assert!(db.list_pending_txs("0xabc", 5).unwrap().is_empty());
+
+        assert_eq!(db.mark_txs_confirmed_below("0xabc", 1, 5).unwrap(), 1);
+        let pending = db.list_pending_txs("0xabc", 1).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].entry.nonce, 5);
+
+        // the spending profile sees the replacement, not the original
+        let recent = db.recent_txs("w-1", 10).unwrap();
+        assert_eq!(recent.iter().map(|r| r.id).collect::<Vec<_>>(), vec![b, c]);
+        // and so does the accounting export, oldest first
+        let range = db.txs_between("w-1", 0, i64::MAX, 10).unwrap();
+        assert_eq!(range.iter().map(|r| r.id).collect::<Vec<_>>(), vec![c, b]);
+        assert!(db.txs_between("w-1", 0, 1, 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn chain_events_dedup_and_survive_reorgs() {
+        let db = test_db();
+        db.insert_wallet(&sample_wallet("w-1")).unwrap();
+        db.upsert_address("0xAAA", "w-1", "m/44'/60'/0'/0/0", None)
             .unwrap();
-        let now = Utc::now().timestamp() + 10;
-        let frozen = db.freeze_dormant_keys(now, 86_400).unwrap();
-        assert!(!frozen.contains(&"w-fz".to_string()));
+        assert_eq!(
+            db.watched_addresses()
+                .unwrap()
+                .get("0xaaa")
+                .map(String::as_str),
+            Some("w-1")
+        );
+        let event = |log_index: i64| ChainEvent {
+            key_id: "w-1".into(),
+            address: "0xaaa".into(),
+            chain_id: 1,
+            kind: "erc20-transfer-in".into(),
+            token: Some("0xt".into()),
+            from_address: Some("0xb".into()),
+            to_address: Some("0xaaa".into()),
+            value: Some("1000".into()),
+            topic0: None,
+            tx_hash: "0x01".into(),
+            block_number: 7,
+            log_index,
+        };
+        let first = db.insert_chain_event(&event(0)).unwrap().unwrap();
+        assert_eq!(first.event, event(0));
+        assert!(db.insert_chain_event(&event(0)).unwrap().is_none());
+        let second = db.insert_chain_event(&event(1)).unwrap().unwrap();
+
+        let removed = db.mark_chain_event_removed(&event(0)).unwrap().unwrap();
+        assert!(removed.removed);
+        assert!(db.mark_chain_event_removed(&event(0)).unwrap().is_none());
+        // Mined again after the reorg: reported as new once more.
+        let back = db.insert_chain_event(&event(0)).unwrap().unwrap();
+        assert_eq!((back.id, back.removed), (first.id, false));
+
+        let ids = |before| {
+            db.list_chain_events("w-1", before, 10)
+                .unwrap()
+                .iter()
+                .map(|r| r.id)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids(None), vec![second.id, first.id]);
+        assert_eq!(ids(Some(second.id)), vec![first.id]);
+        assert!(db.list_chain_events("w-2", None, 10).unwrap().is_empty());
     }
 
     #[test]
-    fn wallet_exists_check() {
+    fn remote_approver_replace_clear_and_cascade() {
         let db = test_db();
-        db.insert_wallet(&sample_wallet("w1")).unwrap();
-        assert!(db.wallet_exists("w1").unwrap());
-        assert!(!db.wallet_exists("w2").unwrap());
+        db.insert_wallet(&sample_wallet("w-1")).unwrap();
+        assert!(db.remote_approver("w-1").unwrap().is_none());
+        db.set_remote_approver("w-1", "02aa", "fcm", "tok-1")
+            .unwrap();
+        db.set_remote_approver("w-1", "03bb", "apns", "tok-2")
+            .unwrap();
+        let row = db.remote_approver("w-1").unwrap().unwrap();
+        assert_eq!(
+            (
+                row.approver_key.as_str(),
+                row.platform.as_str(),
+                row.push_token.as_str()
+            ),
+            ("03bb", "apns", "tok-2")
+        );
+        assert!(db.clear_remote_approver("w-1").unwrap());
+        assert!(!db.clear_remote_approver("w-1").unwrap());
+        assert!(db.set_remote_approver("w-2", "02aa", "fcm", "t").is_err());
+        db.set_remote_approver("w-1", "02aa", "fcm", "t").unwrap();
+        db.delete_wallet("w-1").unwrap();
+        assert!(db.remote_approver("w-1").unwrap().is_none());
     }
 
     #[test]
-    fn update_wallet_derived() {
+    fn notification_prefs_replace_clear_and_cascade() {
         let db = test_db();
-        db.insert_wallet(&sample_wallet("w1")).unwrap();
-        db.update_wallet_derived("w1", "0xaddr", "0xpub", "m/44'/60'/0'/0/0", "ready")
+        db.insert_wallet(&sample_wallet("w-1")).unwrap();
+        assert!(db.notification_prefs("w-1").unwrap().is_none());
+        db.set_notification_prefs("w-1", r#"{"events":[]}"#)
             .unwrap();
-        let got = db.get_wallet("w1").unwrap().unwrap();
-        assert_eq!(got.address.as_deref(), Some("0xaddr"));
-        assert_eq!(got.status, "ready");
+        db.set_notification_prefs("w-1", r#"{"emailEvents":[]}"#)
+            .unwrap();
+        assert_eq!(
+            db.notification_prefs("w-1").unwrap().unwrap().0,
+            r#"{"emailEvents":[]}"#
+        );
+        assert!(db.clear_notification_prefs("w-1").unwrap());
+        assert!(!db.clear_notification_prefs("w-1").unwrap());
+        // Only a wallet the node holds has preferences, and they go with it.
+        assert!(db.set_notification_prefs("w-2", "{}").is_err());
+        db.set_notification_prefs("w-1", "{}").unwrap();
+        db.delete_wallet("w-1").unwrap();
+        assert!(db.notification_prefs("w-1").unwrap().is_none());
     }
 
     #[test]
-    fn delete_wallet_cascades_address() {
+    fn stealth_scan_cursor_and_announcements() {
         let db = test_db();
-        db.insert_wallet(&sample_wallet("w1")).unwrap();
-        db.upsert_address("0xaddr", "w1", "m/0", None).unwrap();
-        db.delete_wallet("w1").unwrap();
-        assert!(db.lookup_address("0xaddr").unwrap().is_none());
+        db.insert_wallet(&sample_wallet("w-1")).unwrap();
+        db.upsert_stealth_meta("w-1", 0, "st:eth:0xaa").unwrap();
+        db.advance_stealth_cursor("w-1", 0, 5).unwrap();
+        // Re-registering keeps the cursor; it never moves back.
+        db.upsert_stealth_meta("w-1", 0, "st:eth:0xbb").unwrap();
+        db.advance_stealth_cursor("w-1", 0, 3).unwrap();
+        let rows = db.list_stealth_meta().unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(
+            (rows[0].meta_address.as_str(), rows[0].scanned_through),
+            ("st:eth:0xbb", 5)
+        );
+
+        let ann = |log_index: i64| Announcement {
+            chain_id: 1,
+            stealth_address: "0x3b9a".into(),
+            caller: "0x1111".into(),
+            ephemeral_pubkey: vec![0x02; 33],
+            metadata: vec![0x33],
+            tx_hash: "0x01".into(),
+            block_number: 9,
+            log_index,
+        };
+        assert!(db.insert_stealth_announcement(&ann(0)).unwrap());
+        assert!(!db.insert_stealth_announcement(&ann(0)).unwrap());
+        assert!(db.insert_stealth_announcement(&ann(1)).unwrap());
+        let all = db.stealth_announcements_after(0, 10).unwrap();
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[0].1, ann(0));
+        assert_eq!(
+            db.stealth_announcements_after(all[0].0, 10).unwrap()[0].1,
+            ann(1)
+        );
+
+        // Reorged out, then mined again: a new id, so it is scanned again.
+        assert!(db.delete_stealth_announcement(&ann(0)).unwrap());
+        assert!(db.insert_stealth_announcement(&ann(0)).unwrap());
+        let again = db.stealth_announcements_after(all[1].0, 10).unwrap();
+        assert_eq!(again.len(), 1);
+        assert_eq!(again[0].1, ann(0));
+
+        db.delete_wallet("w-1").unwrap();
+        assert!(db.list_stealth_meta().unwrap().is_empty());
     }
 
     #[test]
-    fn list_wallets_returns_all() {
+    fn tenant_wallets_quota_and_batches() {
         let db = test_db();
-        db.insert_wallet(&sample_wallet("w1")).unwrap();
-        db.insert_wallet(&sample_wallet("w2")).unwrap();
-        assert_eq!(db.list_wallets().unwrap().len(), 2);
+        assert_eq!(db.tenant_quota("acme").unwrap(), None);
+        db.set_tenant_quota("acme", 10).unwrap();
+        db.set_tenant_quota("acme", 20).unwrap();
+        assert_eq!(db.tenant_quota("acme").unwrap(), Some(20));
+
+        let assign = |key_id: &str, tags: &[&str], created_at: i64| {
+            db.insert_wallet(&sample_wallet(key_id)).unwrap();
+            db.insert_tenant_wallet(&TenantWalletRow {
+                key_id: key_id.into(),
+                tenant_id: "acme".into(),
+                tags: tags.iter().map(|t| t.to_string()).collect(),
+                batch_id: "b-1".into(),
+                created_at,
+            })
+            .unwrap();
+        };
+        assign("w-1", &["payroll", "eu"], 100);
+        assign("w-2", &["eu"], 200);
+        assign("w-3", &[], 300);
+        assert_eq!(db.count_tenant_wallets("acme", 0).unwrap(), 3);
+        assert_eq!(db.count_tenant_wallets("acme", 200).unwrap(), 2);
+        assert_eq!(db.count_tenant_wallets("other", 0).unwrap(), 0);
+        let eu: Vec<_> = db
+            .list_tenant_wallets("acme", Some("eu"))
+            .unwrap()
+            .into_iter()
+            .map(|w| w.key_id)
+            .collect();
+        assert_eq!(eu, vec!["w-1", "w-2"]);
+        assert!(db.list_tenant_wallets("acme", None).unwrap()[2]
+            .tags
+            .is_empty());
+
+        // A deleted wallet no longer counts against the quota.
+        db.delete_wallet("w-2").unwrap();
+        assert_eq!(db.count_tenant_wallets("acme", 0).unwrap(), 2);
+
+        let batch = ProvisioningBatchRow {
+            batch_id: "b-1".into(),
+            tenant_id: "acme".into(),
+            report: "{}".into(),
+            attestation: "{}".into(),
+            created_at: 300,
+        };
+        db.insert_provisioning_batch(&batch).unwrap();
+        assert!(db.insert_provisioning_batch(&batch).is_err());
+        assert_eq!(db.get_provisioning_batch("b-1").unwrap(), Some(batch));
+        assert_eq!(db.get_provisioning_batch("b-2").unwrap(), None);
     }
 
     #[test]
-    fn address_upsert_and_lookup() {
+    fn projections_rebuild_from_the_event_log() {
         let db = test_db();
-        db.insert_wallet(&sample_wallet("w1")).unwrap();
-        db.upsert_address("0xaaaa", "w1", "m/44'/60'/0'/0/0", Some("0xpub"))
+        let path = "m/44'/60'/0'/0/0";
+        db.insert_wallet(&sample_wallet("w-1")).unwrap();
+        db.insert_wallet(&sample_wallet("w-2")).unwrap();
+        db.update_wallet_derived("w-1", "0xAAA", "0x04aa", path, "ready")
             .unwrap();
-        let row = db.lookup_address("0xaaaa").unwrap().unwrap();
-        assert_eq!(row.key_id, "w1");
-        assert_eq!(row.public_key.as_deref(), Some("0xpub"));
+        assert_eq!(
+            db.pin_address("w-1", path, "0xAAA", None).unwrap(),
+            PinCheck::Pinned
+        );
+        db.pin_address("w-1", path, "0xaaa", Some("0x04aa"))
+            .unwrap();
+        db.update_wallet_passkey("w-1", "0x04new", Some("cred"))
+            .unwrap();
+        db.update_wallet_sign_count("w-1", 7).unwrap();
+        db.set_lifecycle_status("w-1", "frozen").unwrap();
+        // unknown key: nothing changes, nothing is logged
+        db.update_wallet_status("nope", "error", Some("x")).unwrap();
+        let entry = |nonce: u64| SignedTxEntry {
+            key_id: "w-1".into(),
+            address: "0xAAA".into(),
+            derivation_path: path.into(),
+            chain_id: 1,
+            nonce,
+            gas_price: 100,
+            signed_tx: "0x00".into(),
+            tx_hash: format!("0x{}", nonce),
+        };
+        let a = db.insert_tx_history(&entry(1), None).unwrap();
+        db.insert_tx_history(&entry(1), Some(a)).unwrap();
+        db.insert_tx_history(&entry(2), None).unwrap();
+        db.mark_txs_confirmed_below("0xaaa", 1, 2).unwrap();
+        db.upsert_address("0xBBB", "w-2", path, None).unwrap();
+        db.delete_wallet("w-2").unwrap();
+
+        let head = db.verify_event_chain().unwrap();
+        assert_eq!(head.events, 15);
+        let kinds: Vec<String> = db
+            .events_for("w-1")
+            .unwrap()
+            .into_iter()
+            .map(|e| e.kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                "wallet_created",
+                "wallet_derived",
+                "address_indexed",
+                "passkey_changed",
+                "sign_count_advanced",
+                "lifecycle_changed",
+                "tx_signed",
+                "tx_signed",
+                "tx_signed"
+            ]
+        );
+        let feed = db.events_after(13, 10).unwrap();
+        assert_eq!(feed.len(), 2);
+        assert_eq!(feed[0].prev_hash, db.events_after(12, 1).unwrap()[0].hash);
+        assert_eq!(db.events_after(15, 10).unwrap(), vec![]);
+        assert_eq!(db.verify_projections().unwrap(), vec![]);
+
+        // Damage the tables behind the log's back, and add a wallet it
+        // never saw with a tenant row hanging off it.
+        {
+            let conn = db.lock();
+            conn.execute_batch(
+                "UPDATE wallets SET status='error' WHERE key_id='w-1';
+                 DELETE FROM tx_history WHERE id=1;
+                 INSERT INTO wallets (key_id, created_at) VALUES ('ghost', 'x');
+                 INSERT INTO tenant_wallets (key_id, tenant_id, tags, batch_id, created_at)
+                     VALUES ('ghost', 'acme', '', 'b', 0);",
+            )
+            .unwrap();
+        }
+        let diffs = db.verify_projections().unwrap();
+        assert_eq!(
+            diffs
+                .iter()
+                .map(|d| (
+                    d.table,
+                    d.key.as_str(),
+                    d.stored.is_some(),
+                    d.replayed.is_some()
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                ("wallets", "ghost", true, false),
+                ("wallets", "w-1", true, true),
+                ("tx_history", "1", false, true),
+            ]
+        );
+
+        let report = db.rebuild_projections().unwrap();
+        assert_eq!(report.events, 15);
+        assert_eq!(report.head, head.hash);
+        assert_eq!(
+            (report.wallets, report.addresses, report.txs, report.orphans),
+            (1, 1, 3, 1)
+        );
+        assert_eq!(db.verify_projections().unwrap(), vec![]);
+        let w = db.get_wallet("w-1").unwrap().unwrap();
+        assert_eq!(
+            (w.status.as_str(), w.sign_count, w.passkey_pubkey.as_deref()),
+            ("ready", 7, Some("0x04new"))
+        );
+        assert_eq!(
+            db.get_lifecycle_status("w-1").unwrap().as_deref(),
+            Some("frozen")
+        );
+        let pending = db.list_pending_txs("0xaaa", 1).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].entry.nonce, 2);
+        // AUTOINCREMENT carries on past the replayed ids
+        assert_eq!(db.insert_tx_history(&entry(3), None).unwrap(), 4);
     }
 
     #[test]
-    fn challenge_store_and_consume() {
+    fn event_log_is_append_only_and_tamper_evident() {
         let db = test_db();
-        let challenge = vec![1u8, 2, 3, 4];
-        db.store_challenge("c1", &challenge, None, "registration", "example.com", 300)
+        db.insert_wallet(&sample_wallet("w-1")).unwrap();
+        db.update_wallet_status("w-1", "ready", None).unwrap();
+        {
+            let conn = db.lock();
+            assert!(conn
+                .execute("UPDATE ca_events SET aggregate='x' WHERE seq=1", [])
+                .is_err());
+            assert!(conn.execute("DELETE FROM ca_events", []).is_err());
+            // Someone with the file can still drop the guard and rewrite
+            // history; the chain shows it.
+            conn.execute_batch(
+                "DROP TRIGGER ca_events_no_update;
+                 UPDATE ca_events SET payload=replace(payload, 'ready', 'error') WHERE seq=2;",
+            )
             .unwrap();
-        let got = db.consume_challenge("c1").unwrap().unwrap();
-        assert_eq!(got.challenge, challenge);
-        assert_eq!(got.purpose, "registration");
-        // Consumed — should be gone
-        assert!(db.consume_challenge("c1").unwrap().is_none());
+        }
+        let err = db.verify_event_chain().unwrap_err().to_string();
+        assert!(err.contains("event 2 does not match its hash"), "{}", err);
+        // A rebuild refuses the log and leaves the tables as they were.
+        assert!(db.rebuild_projections().is_err());
+        assert_eq!(db.get_wallet("w-1").unwrap().unwrap().status, "ready");
+        let fk: bool = db
+            .lock()
+            .query_row("PRAGMA foreign_keys", [], |r| r.get(0))
+            .unwrap();
+        assert!(fk);
     }
 
     #[test]
-    fn challenge_not_found() {
-        let db = test_db();
-        assert!(db.consume_challenge("nope").unwrap().is_none());
+    fn event_log_starts_from_an_existing_db() {
+        let path = std::env::temp_dir().join(format!("kms-events-{}.db", Uuid::new_v4()));
+        {
+            let conn = Connection::open(&path).unwrap();
+            conn.execute_batch(SCHEMA).unwrap();
+            conn.execute_batch(
+                "INSERT INTO wallets (key_id, address, created_at, lifecycle_status, aaguid)
+                     VALUES ('w-1', '0xaaa', '2026-01-01T00:00:00Z', 'frozen', 'g-1');
+                 INSERT INTO address_index (address, key_id, derivation_path)
+                     VALUES ('0xaaa', 'w-1', 'm/0');
+                 INSERT INTO tx_history (key_id, address, derivation_path, chain_id, nonce,
+                     gas_price, signed_tx, tx_hash, status, replaced_by, created_at)
+                     VALUES ('w-1', '0xaaa', 'm/0', 1, 0, '1', '0x', '0x1', 'replaced', 2, 5),
+                            ('w-1', '0xaaa', 'm/0', 1, 0, '2', '0x', '0x2', 'pending', NULL, 6);",
+            )
+            .unwrap();
+        }
+        let db = KmsDb::open(path.to_str().unwrap()).unwrap();
+        let kinds: Vec<String> = db
+            .events_after(0, 100)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                "wallet_created",
+                "lifecycle_changed",
+                "passkey_attested",
+                "address_indexed",
+                "tx_imported",
+                "tx_imported"
+            ]
+        );
+        assert_eq!(db.verify_projections().unwrap(), vec![]);
+        drop(db);
+        // a second open does not snapshot again
+        let db = KmsDb::open(path.to_str().unwrap()).unwrap();
+        assert_eq!(db.verify_event_chain().unwrap().events, 6);
+        assert_eq!(
+            db.insert_tx_history(
+                &SignedTxEntry {
+                    key_id: "w-1".into(),
+                    address: "0xaaa".into(),
+                    derivation_path: "m/0".into(),
+                    chain_id: 1,
+                    nonce: 1,
+                    gas_price: 1,
+                    signed_tx: "0x".into(),
+                    tx_hash: "0x3".into(),
+                },
+                None
+            )
+            .unwrap(),
+            3
+        );
+        assert_eq!(db.verify_projections().unwrap(), vec![]);
+        drop(db);
+        let _ = std::fs::remove_file(&path);
     }
 
     #[test]
-    fn api_key_generate_and_validate() {
+    fn fee_quote_pays_for_one_user_op_and_settles_once() {
         let db = test_db();
-        assert!(!db.has_api_keys().unwrap());
-        let key = db.generate_api_key("test-service").unwrap();
-        assert!(key.starts_with("kms_"));
-        assert!(db.has_api_keys().unwrap());
-        assert!(db.validate_api_key(&key).unwrap());
-        assert!(!db.validate_api_key("kms_invalid").unwrap());
-    }
+        let entry = FeeQuoteEntry {
+            quote_hash: "0xq1".into(),
+            key_id: "w-1".into(),
+            chain_id: 10,
+            paymaster: "0xPM".into(),
+            token: "0xUSDC".into(),
+            max_token_cost: 5_000_000,
+            quote: "{}".into(),
+        };
+        db.insert_fee_quote(&entry).unwrap();
+        assert!(db.insert_fee_quote(&entry).is_err(), "quote hash is unique");
+        // Nothing to settle before the TA signed.
+        assert!(db
+            .settle_fee_payment("0xq1", true, Some("0xtx"), None)
+            .unwrap()
+            .is_none());
 
-    #[test]
-    fn api_key_list_and_revoke() {
-        let db = test_db();
-        let k1 = db.generate_api_key("svc-a").unwrap();
-        let _k2 = db.generate_api_key("svc-b").unwrap();
-        assert_eq!(db.list_api_keys().unwrap().len(), 2);
-        assert!(db.revoke_api_key(&k1).unwrap());
-        assert!(!db.validate_api_key(&k1).unwrap());
-        assert_eq!(db.list_api_keys().unwrap().len(), 1);
-    }
+        assert!(db.claim_fee_quote("0xq1", "0xop1").unwrap());
+        assert!(!db.claim_fee_quote("0xq1", "0xop2").unwrap());
+        // A stale release for another op does not free the quote.
+        db.release_fee_quote("0xq1", "0xop2").unwrap();
+        assert_eq!(db.fee_payment("0xq1").unwrap().unwrap().status, "signed");
+        db.release_fee_quote("0xq1", "0xop1").unwrap();
+        assert!(db.claim_fee_quote("0xq1", "0xop2").unwrap());
 
-    #[test]
-    fn update_passkey() {
-        let db = test_db();
-        db.insert_wallet(&sample_wallet("w1")).unwrap();
-        db.update_wallet_passkey("w1", "0x04new", Some("cred-123"))
+        let row = db
+            .settle_fee_payment("0xq1", true, Some("0xtx"), Some(4_200_000))
+            .unwrap()
             .unwrap();
-        let got = db.get_wallet("w1").unwrap().unwrap();
-        assert_eq!(got.passkey_pubkey.as_deref(), Some("0x04new"));
-        assert_eq!(got.credential_id.as_deref(), Some("cred-123"));
+        assert_eq!(row.status, "settled");
+        assert_eq!(row.user_op_hash.as_deref(), Some("0xop2"));
+        assert_eq!(row.actual_token_cost, Some(4_200_000));
+        assert_eq!(row.entry.paymaster, "0xpm");
+        assert!(db
+            .settle_fee_payment("0xq1", false, None, None)
+            .unwrap()
+            .is_none());
+        assert_eq!(db.list_fee_payments("w-1", 10).unwrap().len(), 1);
+        assert!(db.list_fee_payments("w-2", 10).unwrap().is_empty());
     }
 
     #[test]
-    fn concurrent_access() {
-        use std::thread;
+    fn refresh_family_rotates_until_revoked() {
         let db = test_db();
-        db.insert_wallet(&sample_wallet("w-concurrent")).unwrap();
-
-        let handles: Vec<_> = (0..10)
-            .map(|i| {
-                let db = db.clone();
-                thread::spawn(move || {
-                    let addr = format!("0xaddr{}", i);
-                    db.upsert_address(&addr, "w-concurrent", &format!("m/0/{}", i), None)
-                        .unwrap();
-                    db.get_wallet("w-concurrent").unwrap().unwrap();
-                })
-            })
-            .collect();
+        db.insert_wallet(&sample_wallet("w-1")).unwrap();
+        db.open_refresh_family("w-1:0", "w-1", Some("cred-1"), "ab")
+            .unwrap();
+        assert!(db.advance_refresh_family("w-1:0").unwrap());
+        let row = db.refresh_family("w-1:0").unwrap().unwrap();
+        assert_eq!((row.generation, row.status.as_str()), (1, "active"));
+
+        assert!(db.revoke_refresh_family("w-1:0", "reuse").unwrap());
+        assert!(!db.revoke_refresh_family("w-1:0", "reuse").unwrap());
+        assert!(!db.advance_refresh_family("w-1:0").unwrap());
+        // A new passkey mint starts a fresh family.
+        db.open_refresh_family("w-1:0", "w-1", Some("cred-1"), "ab")
+            .unwrap();
+        assert_eq!(db.refresh_family("w-1:0").unwrap().unwrap().generation, 0);
 
-        for h in handles {
-            h.join().unwrap();
-        }
-        // All 10 addresses should exist
-        for i in 0..10 {
-            let addr = format!("0xaddr{}", i);
-            assert!(db.lookup_address(&addr).unwrap().is_some());
-        }
+        // Changing the passkey revokes every family of the wallet.
+        db.update_wallet_passkey("w-1", "0x04new", Some("cred-2"))
+            .unwrap();
+        let row = db.refresh_family("w-1:0").unwrap().unwrap();
+        assert_eq!(row.status, "revoked");
+        assert_eq!(row.revoked_reason.as_deref(), Some("passkey changed"));
+        assert!(db.refresh_family("w-1:9").unwrap().is_none());
     }
 
     #[test]
-    fn update_wallet_status_with_error() {
+    fn deletion_certificate_outlives_the_wallet() {
         let db = test_db();
-        db.insert_wallet(&sample_wallet("w-err")).unwrap();
-        db.update_wallet_status("w-err", "error", Some("PBKDF2 timeout"))
+        db.insert_wallet(&sample_wallet("w-1")).unwrap();
+        db.delete_wallet("w-1").unwrap();
+        db.record_deletion_certificate("w-1", r#"{"Digest":"ab"}"#, true)
             .unwrap();
-        let got = db.get_wallet("w-err").unwrap().unwrap();
-        assert_eq!(got.status, "error");
-        assert_eq!(got.error_msg.as_deref(), Some("PBKDF2 timeout"));
+        assert_eq!(
+            db.get_deletion_certificate("w-1").unwrap().as_deref(),
+            Some(r#"{"Digest":"ab"}"#)
+        );
+        assert!(db.get_deletion_certificate("w-2").unwrap().is_none());
     }
 
     #[test]
-    fn update_sign_count() {
+    fn device_wipe_recorded_once() {
         let db = test_db();
-        db.insert_wallet(&sample_wallet("w-sc")).unwrap();
-        db.update_wallet_sign_count("w-sc", 42).unwrap();
-        let got = db.get_wallet("w-sc").unwrap().unwrap();
-        assert_eq!(got.sign_count, 42);
+        assert!(db
+            .record_device_wipe("ab", "PanicWipe", r#"{"Digest":"ab"}"#, true)
+            .unwrap());
+        assert!(!db
+            .record_device_wipe("ab", "PanicWipe", r#"{"Digest":"ab"}"#, true)
+            .unwrap());
+        assert_eq!(
+            db.list_device_wipes().unwrap(),
+            vec![r#"{"Digest":"ab"}"#.to_string()]
+        );
     }
 
     #[test]
-    fn cleanup_expired_challenges() {
+    fn wallet_device_set_revoke_and_reactivate() {
         let db = test_db();
-        // Store a challenge with -1 second TTL (already expired)
-        db.store_challenge("c-expired", &[1, 2, 3], None, "auth", "localhost", -1)
-            .unwrap();
-        db.store_challenge("c-valid", &[4, 5, 6], None, "auth", "localhost", 300)
+        db.add_wallet_device("w-1", "dev-a", None, "0xfa").unwrap();
+        db.add_wallet_device("w-1", "dev-b", Some("https://b"), "0xfb")
             .unwrap();
+        assert_eq!(
+            db.device_attestation_fp("dev-b").unwrap().as_deref(),
+            Some("0xfb")
+        );
+        assert!(db.device_attestation_fp("dev-c").unwrap().is_none());
 
-        let cleaned = db.cleanup_expired_challenges().unwrap();
-        assert_eq!(cleaned, 1);
+        assert!(db.revoke_wallet_device("w-1", "dev-b").unwrap());
+        assert!(!db.revoke_wallet_device("w-1", "dev-b").unwrap());
+        let devices = db.list_wallet_devices("w-1").unwrap();
+        assert_eq!(devices.len(), 2);
+        assert_eq!(devices[1].status, "revoked");
+        assert!(devices[1].revoked_at.is_some());
 
-        // Valid challenge should still be consumable
-        assert!(db.consume_challenge("c-valid").unwrap().is_some());
-        // Expired one was cleaned up
-        assert!(db.consume_challenge("c-expired").unwrap().is_none());
+        // heartbeats keep the endpoint unless a new one is reported
+        assert_eq!(db.record_device_heartbeat("dev-b", None, None).unwrap(), 1);
+        assert_eq!(
+            db.list_wallet_devices("w-1").unwrap()[1]
+                .endpoint
+                .as_deref(),
+            Some("https://b")
+        );
+        // a fresh replication to the same device brings it back
+        db.add_wallet_device("w-1", "dev-b", Some("https://b2"), "0xfb")
+            .unwrap();
+        let back = &db.list_wallet_devices("w-1").unwrap()[1];
+        assert_eq!((back.status.as_str(), back.revoked_at), ("active", None));
     }
 
-    // ── Issue #42: dormant-key freeze + last_used_at ──
-
     #[test]
-    fn lifecycle_status_defaults_active_and_round_trips() {
+    fn device_quarantine_flags_this_node_and_reported_peers() {
         let db = test_db();
-        db.insert_wallet(&sample_wallet("w-lc")).unwrap();
-        assert_eq!(
-            db.get_lifecycle_status("w-lc").unwrap().as_deref(),
-            Some("active")
-        );
-        assert!(db.set_lifecycle_status("w-lc", "frozen").unwrap());
+        db.add_wallet_device("w-1", "dev-a", None, "0xfa").unwrap();
+        db.add_wallet_device("w-1", "dev-b", Some("https://b"), "0xfb")
+            .unwrap();
+        let report = r#"{"passed":false}"#;
+        let check = db
+            .record_health_check(100, false, "schedule", report)
+            .unwrap();
+        assert!(db
+            .quarantine_device("self-test failed: rng", Some(check), 100)
+            .unwrap());
+        // a second failure keeps the first reason
+        assert!(!db
+            .quarantine_device("measurement revoked", None, 200)
+            .unwrap());
+        let q = db.device_quarantine().unwrap().unwrap();
         assert_eq!(
-            db.get_lifecycle_status("w-lc").unwrap().as_deref(),
-            Some("frozen")
+            (q.reason.as_str(), q.check_id, q.since),
+            ("self-test failed: rng", Some(check), 100)
         );
-        // Unknown key -> None
-        assert!(db.get_lifecycle_status("nope").unwrap().is_none());
-        assert!(!db.set_lifecycle_status("nope", "active").unwrap());
+        let devices = db.list_wallet_devices("w-1").unwrap();
+        assert!(devices[0].quarantined && !devices[1].quarantined);
+
+        // a peer reports its own quarantine; a heartbeat without the flag keeps it
+        db.record_device_heartbeat("dev-b", None, Some(true))
+            .unwrap();
+        db.record_device_heartbeat("dev-b", None, None).unwrap();
+        assert!(db.list_wallet_devices("w-1").unwrap()[1].quarantined);
+
+        assert_eq!(db.release_device_quarantine().unwrap(), Some(q));
+        assert!(db.device_quarantine().unwrap().is_none());
+        assert!(db.release_device_quarantine().unwrap().is_none());
+        assert!(!db.list_wallet_devices("w-1").unwrap()[0].quarantined);
+
+        db.record_health_check(300, true, "operator", "{}").unwrap();
+        let checks = db.list_health_checks(10).unwrap();
+        assert_eq!(checks.len(), 2);
+        assert!(checks[0].passed && checks[0].source == "operator");
+        assert_eq!(checks[1].report, report);
     }
 
     #[test]
-    fn last_used_at_none_then_some() {
+    fn signer_wallets_are_registered_with_their_address_pinned() {
         let db = test_db();
-        db.insert_wallet(&sample_wallet("w-lu")).unwrap();
-        // No tx_log rows yet.
-        assert!(db.last_used_at("w-lu").unwrap().is_none());
-        // A failed op does not count.
-        db.record_tx("Sign", Some("w-lu"), None, false, 5, false, false)
-            .unwrap();
-        assert!(db.last_used_at("w-lu").unwrap().is_none());
-        // A successful op sets it.
-        db.record_tx("Sign", Some("w-lu"), None, false, 5, true, false)
+        let hsm = |key_id: &str, label: &str, address: &str| {
+            let mut w = sample_wallet(key_id);
+            w.address = Some(address.to_string());
+            w.derivation_path = Some("m/44'/60'/0'/0/0".to_string());
+            w.status = "ready".to_string();
+            let signer = WalletSignerRow {
+                key_id: key_id.to_string(),
+                backend: "pkcs11".to_string(),
+                key_label: label.to_string(),
+                derivation_path: "m/44'/60'/0'/0/0".to_string(),
+                created_at: 100,
+            };
+            (w, signer)
+        };
+        let (w, signer) = hsm(
+            "h1",
+            "treasury",
+            "0x9D8A62f656a8d1615C1294fd71e9CFb3E4855A4F",
+        );
+        db.register_signer_wallet(&w, &signer).unwrap();
+        assert_eq!(db.wallet_signer("h1").unwrap(), Some(signer.clone()));
+        assert_eq!(
+            db.wallet_signer_by_label("treasury")
+                .unwrap()
+                .unwrap()
+                .key_id,
+            "h1"
+        );
+        assert!(db.wallet_signer("w-tee").unwrap().is_none());
+        let pinned = db
+            .lookup_address("0x9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f")
+            .unwrap()
             .unwrap();
-        assert!(db.last_used_at("w-lu").unwrap().is_some());
+        assert_eq!(pinned.key_id, "h1");
+
+        // the same key under a second wallet, or a second key under the label
+        let (w2, s2) = hsm("h2", "ops", "0x9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f");
+        assert!(db.register_signer_wallet(&w2, &s2).is_err());
+        let (w3, s3) = hsm(
+            "h3",
+            "treasury",
+            "0x00000000000000000000000000000000000000aa",
+        );
+        assert!(db.register_signer_wallet(&w3, &s3).is_err());
+        assert!(db.get_wallet("h2").unwrap().is_none());
+        assert!(db.get_wallet("h3").unwrap().is_none());
+        assert_eq!(db.list_wallet_signers().unwrap(), vec![signer]);
     }
 
     #[test]
-    fn freeze_dormant_by_created_at_fallback() {
+    fn identity_links_follow_their_wallets() {
         let db = test_db();
-        // sample_wallet created_at is 2026-03-02 (well in the past relative to now).
-        db.insert_wallet(&sample_wallet("w-old")).unwrap();
-        let now = chrono::Utc::now().timestamp();
-        // Threshold of 1 day: the never-used, old key is dormant -> frozen.
-        let frozen = db.freeze_dormant_keys(now, 24 * 60 * 60).unwrap();
-        assert_eq!(frozen, vec!["w-old".to_string()]);
+        db.insert_wallet(&sample_wallet("w-1")).unwrap();
+        db.insert_wallet(&sample_wallet("w-2")).unwrap();
+        let identity = IdentityRow {
+            identity_id: "id-1".to_string(),
+            label: "alice".to_string(),
+            owner_key_id: "w-1".to_string(),
+            created_at: 1,
+        };
+        let link = |address: &str, key_id: &str, identity_id: &str| IdentityLinkRow {
+            address: address.to_string(),
+            identity_id: identity_id.to_string(),
+            key_id: key_id.to_string(),
+            derivation_path: "m/44'/60'/0'/0/0".to_string(),
+            chain_ids: vec![1, 8453],
+            statement: "stmt".to_string(),
+            signature: "0x00".to_string(),
+            linked_at: 2,
+        };
+        db.link_identity_address(&link("0xAA", "w-1", "id-1"), Some(&identity))
+            .unwrap();
+        db.link_identity_address(&link("0xbb", "w-2", "id-1"), None)
+            .unwrap();
         assert_eq!(
-            db.get_lifecycle_status("w-old").unwrap().as_deref(),
-            Some("frozen")
+            db.identity_for_address("0xaa").unwrap().as_deref(),
+            Some("id-1")
         );
+        let links = db.list_identity_links("id-1").unwrap();
+        assert_eq!(links.len(), 2);
+        assert_eq!(links[0].chain_ids, vec![1, 8453]);
+
+        // an address belongs to one identity
+        let other = IdentityRow {
+            identity_id: "id-2".to_string(),
+            ..identity.clone()
+        };
+        assert!(db
+            .link_identity_address(&link("0xbb", "w-2", "id-2"), Some(&other))
+            .is_err());
+        assert!(db.get_identity("id-2").unwrap().is_none());
+
+        // deleting a member wallet drops its link; the last unlink drops the identity
+        db.delete_wallet("w-2").unwrap();
+        assert_eq!(db.list_identity_links("id-1").unwrap().len(), 1);
+        assert!(!db.unlink_identity_address("id-1", "0xbb").unwrap());
+        assert!(db.unlink_identity_address("id-1", "0xAA").unwrap());
+        assert!(db.get_identity("id-1").unwrap().is_none());
     }
 
     #[test]
-    fn freeze_skips_recently_used_keys() {
+    fn gas_tank_topups_stay_single_and_advance_once() {
         let db = test_db();
-        db.insert_wallet(&sample_wallet("w-active")).unwrap();
-        // Recent successful op (record_tx stamps now).
-        db.record_tx("Sign", Some("w-active"), None, false, 5, true, false)
+        db.insert_wallet(&sample_wallet("treasury")).unwrap();
+        let source = GasTankSource {
+            amount_wei: 5 * 10u128.pow(17),
+            treasury_key_id: "treasury".to_string(),
+            treasury_path: "m/44'/60'/0'/0/0".to_string(),
+        };
+        let tank = GasTankRow {
+            address: "0xAB".to_string(),
+            chain_id: 8453,
+            label: "bundler".to_string(),
+            min_balance_wei: 10u128.pow(17),
+            top_up: Some(source.clone()),
+            balance_wei: None,
+            checked_at: None,
+            low_since: None,
+            created_at: 1,
+        };
+        db.upsert_gas_tank(&tank).unwrap();
+        db.record_gas_tank_balance("0xab", 8453, 42, 10, Some(10))
             .unwrap();
-        let now = chrono::Utc::now().timestamp();
-        // Even with a 1-second threshold, the just-now activity keeps it active.
-        let frozen = db.freeze_dormant_keys(now, 1).unwrap();
-        assert!(frozen.is_empty());
+        // a re-designation keeps what the monitor observed
+        db.upsert_gas_tank(&GasTankRow {
+            label: "bundler-1".to_string(),
+            ..tank.clone()
+        })
+        .unwrap();
+        let tanks = db.list_gas_tanks().unwrap();
+        assert_eq!(tanks.len(), 1);
+        assert_eq!(tanks[0].address, "0xab");
+        assert_eq!(tanks[0].label, "bundler-1");
         assert_eq!(
-            db.get_lifecycle_status("w-active").unwrap().as_deref(),
-            Some("active")
+            (tanks[0].balance_wei, tanks[0].low_since),
+            (Some(42), Some(10))
         );
+        assert_eq!(tanks[0].top_up.as_ref(), Some(&source));
+
+        let id = db
+            .propose_gas_tank_topup("0xab", 8453, &source, 100)
+            .unwrap()
+            .unwrap();
+        assert!(db
+            .propose_gas_tank_topup("0xab", 8453, &source, 101)
+            .unwrap()
+            .is_none());
+        assert!(db
+            .advance_gas_tank_topup(id, "proposed", "approved", Some("ops"), None, None)
+            .unwrap());
+        assert!(!db
+            .advance_gas_tank_topup(id, "proposed", "approved", Some("ops-2"), None, None)
+            .unwrap());
+        db.advance_gas_tank_topup(id, "approved", "approved", None, Some("{}"), None)
+            .unwrap();
+        let row = db.get_gas_tank_topup(id).unwrap().unwrap();
+        assert_eq!(row.approved_by.as_deref(), Some("ops"));
+        assert_eq!(row.transaction.as_deref(), Some("{}"));
+        assert_eq!(db.open_gas_tank_topups().unwrap().len(), 1);
+
+        assert_eq!(db.expire_gas_tank_topups(101).unwrap(), 1);
+        assert!(db.open_gas_tank_topups().unwrap().is_empty());
+        assert!(db
+            .propose_gas_tank_topup("0xab", 8453, &source, 200)
+            .unwrap()
+            .is_some());
+
+        // without its treasury the tank drops back to alert-only
+        db.delete_wallet("treasury").unwrap();
+        assert!(db.list_gas_tanks().unwrap()[0].top_up.is_none());
+        assert!(db.delete_gas_tank("0xAB", 8453).unwrap());
     }
 
     #[test]
-    fn freeze_is_idempotent_and_skips_already_frozen() {
+    fn key_regions_move_the_primary() {
         let db = test_db();
-        db.insert_wallet(&sample_wallet("w-f")).unwrap();
-        db.set_lifecycle_status("w-f", "frozen").unwrap();
-        let now = chrono::Utc::now().timestamp();
-        // Already frozen -> not returned again.
-        let frozen = db.freeze_dormant_keys(now, 1).unwrap();
-        assert!(frozen.is_empty());
+        db.set_key_region("w-1", "us-east-1", "dev-a", "PRIMARY")
+            .unwrap();
+        db.set_key_region("w-1", "eu-west-1", "dev-b", "REPLICA")
+            .unwrap();
+        assert!(db.list_key_regions("w-2").unwrap().is_empty());
+
+        assert!(!db.set_primary_region("w-1", "ap-east-1").unwrap());
+        assert!(db.set_primary_region("w-1", "eu-west-1").unwrap());
+        let regions = db.list_key_regions("w-1").unwrap();
+        let roles: Vec<_> = regions
+            .iter()
+            .map(|r| (r.region.as_str(), r.role.as_str()))
+            .collect();
+        assert_eq!(
+            roles,
+            vec![("eu-west-1", "PRIMARY"), ("us-east-1", "REPLICA")]
+        );
     }
 
     #[test]
-    fn freeze_skips_address_mode_recently_used() {
-        // Regression (codex review): address-based Sign/SignHash log activity by
-        // `addr` with key_id=None. last_used_at / freeze_dormant_keys must still
-        // credit that activity to the wallet via its address (primary or derived),
-        // otherwise an actively-used address-mode key looks dormant and is wrongly
-        // frozen.
+    fn pinned_address_is_never_replaced() {
         let db = test_db();
-        let mut w = sample_wallet("w-addr");
-        w.address = Some("0xdeadbeef00000000000000000000000000000000".to_string());
-        db.insert_wallet(&w).unwrap();
-        // Recent op recorded by ADDRESS only (no key_id) — the address-mode path.
-        db.record_tx("Sign", None, w.address.as_deref(), false, 5, true, false)
-            .unwrap();
-        let now = chrono::Utc::now().timestamp();
-        // Even a 1-second threshold must not freeze it: the address activity counts.
-        let frozen = db.freeze_dormant_keys(now, 1).unwrap();
-        assert!(
-            frozen.is_empty(),
-            "address-mode activity must keep the key active"
+        db.insert_wallet(&sample_wallet("w-1")).unwrap();
+        db.insert_wallet(&sample_wallet("w-2")).unwrap();
+        let path = "m/44'/60'/0'/0/0";
+        assert_eq!(
+            db.pin_address("w-1", path, "0xAAA", None).unwrap(),
+            PinCheck::Pinned
         );
         assert_eq!(
-            db.get_lifecycle_status("w-addr").unwrap().as_deref(),
-            Some("active")
+            db.pin_address("w-1", path, "0xaaa", Some("0x04ff"))
+                .unwrap(),
+            PinCheck::Matches
         );
-        assert!(
-            db.last_used_at("w-addr").unwrap().is_some(),
-            "last_used_at must see the address-mode op"
+        assert_eq!(
+            db.pin_address("w-1", path, "0xbbb", None).unwrap(),
+            PinCheck::Mismatch {
+                pinned: "0xaaa".into()
+            }
+        );
+        assert_eq!(
+            db.pin_address("w-2", path, "0xaaa", None).unwrap(),
+            PinCheck::Conflict {
+                key_id: "w-1".into(),
+                derivation_path: path.into()
+            }
+        );
+        // the mismatching answers were not stored
+        assert_eq!(
+            db.address_for_key_path("w-1", path).unwrap().as_deref(),
+            Some("0xaaa")
+        );
+        assert_eq!(
+            db.lookup_address("0xaaa")
+                .unwrap()
+                .unwrap()
+                .public_key
+                .as_deref(),
+            Some("0x04ff")
         );
     }
 
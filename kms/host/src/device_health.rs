@@ -0,0 +1,414 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Device health — a scheduled check that this node's TA still computes
+//! correctly and is the image the fleet expects, with a quarantine when not.
+//!
+//! Every `KMS_HEALTH_CHECK_INTERVAL_SECS` (weekly by default, counted from
+//! the last check in `device_health_checks`, so restarts do not postpone it)
+//! the CA runs the TA's `SelfTest` and asks for attestation evidence over a
+//! fresh nonce. [`assess`] fails the check when a known-answer test fails,
+//! no evidence comes back, the evidence is for another TA or nonce, the
+//! measurement is revoked or missing from the compiled measurements
+//! manifest, or the attestation key is not the one this device was pinned
+//! with in its replica sets.
+//!
+//! A failed check quarantines the node (`device_quarantine` in db.rs):
+//! `TeeHandle` stops sending anything that uses a key, so a Sign fails over
+//! to a replica, /health answers 503 and heartbeats tell peers not to route
+//! here. Operators see the `kms.device.quarantined` gauge and a
+//! `device.health` webhook event. Only an operator lifts the quarantine, and
+//! only once a fresh check passes (service.rs).
+
+use anyhow::{anyhow, bail, Result};
+use proto::event::{DeviceHealthPayload, EventEnvelope};
+use proto::{GetAttestationOutput, SelfTestOutput};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::replication;
+
+/// The measurements manifest served at
+/// /.well-known/attestation-measurements.json.
+pub const MEASUREMENTS_MANIFEST: &str = include_str!("../attestation-measurements.json");
+
+const DEFAULT_INTERVAL_SECS: u64 = 7 * 24 * 3600;
+const MIN_INTERVAL_SECS: u64 = 3600;
+const MAX_INTERVAL_SECS: u64 = 30 * 24 * 3600;
+
+#[derive(Debug, Clone)]
+pub struct HealthConfig {
+    /// None: no scheduled checks, only the ones an operator asks for.
+    pub interval_secs: Option<u64>,
+    pub webhook_url: Option<String>,
+    pub webhook_secret: Option<Vec<u8>>,
+}
+
+impl HealthConfig {
+    /// `KMS_HEALTH_CHECK_INTERVAL_SECS` (3600-2592000, default one week;
+    /// `0` turns the schedule off, e.g. on a dev board whose TA is not in the
+    /// manifest); alerts go to `KMS_EVENT_WEBHOOK_URL` /
+    /// `KMS_EVENT_WEBHOOK_SECRET` when set.
+    pub fn from_env() -> Result<Self> {
+        let var = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+        Self::parse(
+            var("KMS_HEALTH_CHECK_INTERVAL_SECS").as_deref(),
+            var("KMS_EVENT_WEBHOOK_URL"),
+            var("KMS_EVENT_WEBHOOK_SECRET").map(String::into_bytes),
+        )
+    }
+
+    pub fn parse(
+        interval: Option<&str>,
+        webhook_url: Option<String>,
+        webhook_secret: Option<Vec<u8>>,
+    ) -> Result<Self> {
+        let interval_secs = match interval {
+            None => Some(DEFAULT_INTERVAL_SECS),
+            Some("0") => None,
+            Some(v) => Some(
+                v.parse()
+                    .ok()
+                    .filter(|s| (MIN_INTERVAL_SECS..=MAX_INTERVAL_SECS).contains(s))
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "KMS_HEALTH_CHECK_INTERVAL_SECS must be 0 or {}-{}",
+                            MIN_INTERVAL_SECS,
+                            MAX_INTERVAL_SECS
+                        )
+                    })?,
+            ),
+        };
+        if let Some(url) = &webhook_url {
+            if !url.starts_with("http://") {
+                bail!("KMS_EVENT_WEBHOOK_URL must be http:// (no TLS in the CA)");
+            }
+            if webhook_secret.is_none() {
+                bail!("KMS_EVENT_WEBHOOK_URL needs KMS_EVENT_WEBHOOK_SECRET");
+            }
+        }
+        Ok(Self {
+            interval_secs,
+            webhook_url,
+            webhook_secret,
+        })
+    }
+
+    /// Seconds until the next scheduled check: `interval_secs` after the
+    /// last one, at once if there was none or the clock went back past it.
+    pub fn next_check_in(&self, last_checked_at: Option<i64>, now: i64) -> Option<u64> {
+        let interval = self.interval_secs? as i64;
+        Some(match last_checked_at {
+            Some(last) if last <= now => (last + interval - now).max(0) as u64,
+            _ => 0,
+        })
+    }
+}
+
+/// One check as stored in `device_health_checks.report`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthReport {
+    pub checked_at: i64,
+    /// The TA's checks; empty when SelfTest itself failed.
+    pub self_test: Vec<proto::SelfTestCheck>,
+    /// Hex, from the attestation evidence.
+    pub ta_measurement: Option<String>,
+    /// current | previous | revoked, as listed in the manifest.
+    pub measurement_status: Option<String>,
+    /// Why the check failed; empty when it passed.
+    pub failures: Vec<String>,
+}
+
+impl HealthReport {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    pub fn reason(&self) -> String {
+        self.failures.join("; ")
+    }
+
+    pub fn failed_checks(&self) -> Vec<String> {
+        self.self_test
+            .iter()
+            .filter(|c| !c.passed)
+            .map(|c| c.name.clone())
+            .collect()
+    }
+}
+
+/// Judge one round of SelfTest and GetAttestation. `pinned_attestation_key`
+/// is the fingerprint this device's replica sets hold for it, if any.
+pub fn assess(
+    checked_at: i64,
+    self_test: std::result::Result<SelfTestOutput, String>,
+    evidence: std::result::Result<GetAttestationOutput, String>,
+    nonce: &[u8],
+    measurements: &Value,
+    pinned_attestation_key: Option<&str>,
+) -> HealthReport {
+    let mut failures = Vec::new();
+    let checks = match self_test {
+        Ok(out) => {
+            if out.checks.is_empty() {
+                failures.push("self-test ran no checks".to_string());
+            }
+            for c in out.checks.iter().filter(|c| !c.passed) {
+                failures.push(format!("self-test failed: {} ({})", c.name, c.detail));
+            }
+            out.checks
+        }
+        Err(e) => {
+            failures.push(format!("self-test unavailable: {}", e));
+            Vec::new()
+        }
+    };
+    let (mut ta_measurement, mut measurement_status) = (None, None);
+    match evidence {
+        Err(e) => failures.push(format!("no attestation evidence: {}", e)),
+        Ok(ev) => {
+            if ev.nonce != nonce {
+                failures.push("attestation echoed a different nonce".to_string());
+            }
+            let uuid = hex::encode(&ev.ta_uuid);
+            match measurements["body"]["ta_uuid"].as_str() {
+                Some(expected) if expected != uuid => failures.push(format!(
+                    "attestation is for TA {}, manifest lists {}",
+                    uuid, expected
+                )),
+                _ => {}
+            }
+            let measurement = hex::encode(&ev.ta_measurement);
+            let status = crate::node_backup::measurement_status(measurements, &measurement);
+            match status {
+                Some("current") | Some("previous") => {}
+                Some("revoked") => {
+                    failures.push(format!("TA measurement {} is revoked", measurement))
+                }
+                Some(other) => failures.push(format!(
+                    "TA measurement {} has unknown status {:?}",
+                    measurement, other
+                )),
+                None => failures.push(format!(
+                    "TA measurement {} is not in the measurements manifest",
+                    measurement
+                )),
+            }
+            let fingerprint = replication::attestation_key_fingerprint(&ev);
+            if let Some(pinned) = pinned_attestation_key {
+                if pinned != fingerprint {
+                    failures.push(format!(
+                        "attestation key {} differs from the pinned {}",
+                        fingerprint, pinned
+                    ));
+                }
+            }
+            measurement_status = status.map(str::to_string);
+            ta_measurement = Some(measurement);
+        }
+    }
+    HealthReport {
+        checked_at,
+        self_test: checks,
+        ta_measurement,
+        measurement_status,
+        failures,
+    }
+}
+
+/// Gauges for the latest state; `passed` is None before any check ran.
+pub fn export_gauges(passed: Option<bool>, quarantined: bool) {
+    crate::otel::set_gauge("kms.device.quarantined", &[], quarantined as u8 as f64);
+    if let Some(passed) = passed {
+        crate::otel::set_gauge("kms.device.health_check.passed", &[], passed as u8 as f64);
+    }
+}
+
+/// The alert envelope. Its correlation id names the moment the quarantine
+/// began, so the quarantine and its release line up.
+pub fn alert_envelope(
+    quarantined: bool,
+    reason: &str,
+    report: Option<&HealthReport>,
+    since: i64,
+    now: i64,
+) -> EventEnvelope<DeviceHealthPayload> {
+    let device_id = std::env::var("KMS_DEVICE_ID")
+        .ok()
+        .filter(|v| !v.is_empty());
+    EventEnvelope::new(
+        DeviceHealthPayload {
+            device_id,
+            state: if quarantined {
+                "quarantined"
+            } else {
+                "released"
+            }
+            .to_string(),
+            reason: reason.to_string(),
+            failed_checks: report.map(HealthReport::failed_checks).unwrap_or_default(),
+            ta_measurement: report.and_then(|r| r.ta_measurement.clone()),
+        },
+        now,
+        format!("device-health-{}", since),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proto::SelfTestCheck;
+
+    fn manifest() -> Value {
+        serde_json::from_str(MEASUREMENTS_MANIFEST).unwrap()
+    }
+
+    fn listed(status: &str) -> Vec<u8> {
+        let doc = manifest();
+        let m = doc["body"]["measurements"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|m| m["status"] == status)
+            .unwrap();
+        hex::decode(m["ta_measurement"].as_str().unwrap()).unwrap()
+    }
+
+    fn passing_self_test() -> SelfTestOutput {
+        SelfTestOutput {
+            checks: ["sha256", "rng"]
+                .iter()
+                .map(|n| SelfTestCheck {
+                    name: n.to_string(),
+                    passed: true,
+                    detail: String::new(),
+                })
+                .collect(),
+        }
+    }
+
+    fn evidence(nonce: &[u8], measurement: Vec<u8>) -> GetAttestationOutput {
+        GetAttestationOutput {
+            nonce: nonce.to_vec(),
+            ta_uuid: hex::decode(manifest()["body"]["ta_uuid"].as_str().unwrap()).unwrap(),
+            ta_measurement: measurement,
+            signature: vec![1; 256],
+            attest_pubkey_exp: vec![1, 0, 1],
+            attest_pubkey_mod: vec![0xc5; 256],
+            sig_alg: 0x7041_4930,
+            ree_time_secs: 1_700_000_000,
+        }
+    }
+
+    #[test]
+    fn config_defaults_to_weekly_and_can_be_turned_off() {
+        let c = HealthConfig::parse(None, None, None).unwrap();
+        assert_eq!(c.interval_secs, Some(604_800));
+        let off = HealthConfig::parse(Some("0"), None, None).unwrap();
+        assert_eq!(off.next_check_in(None, 1_000), None);
+        assert!(HealthConfig::parse(Some("60"), None, None).is_err());
+        assert!(HealthConfig::parse(None, Some("https://h".into()), Some(vec![1])).is_err());
+        assert!(HealthConfig::parse(None, Some("http://h".into()), None).is_err());
+
+        assert_eq!(c.next_check_in(None, 1_000), Some(0));
+        assert_eq!(c.next_check_in(Some(1_000), 1_000 + 4_800), Some(600_000));
+        assert_eq!(c.next_check_in(Some(1_000), 1_000 + 700_000), Some(0));
+        // a last check in the future: the clock moved back, check now
+        assert_eq!(c.next_check_in(Some(5_000), 1_000), Some(0));
+    }
+
+    #[test]
+    fn listed_measurements_pass_and_the_rest_fail() {
+        let nonce = [7u8; 32];
+        for status in ["current", "previous"].iter() {
+            let r = assess(
+                1,
+                Ok(passing_self_test()),
+                Ok(evidence(&nonce, listed(status))),
+                &nonce,
+                &manifest(),
+                None,
+            );
+            assert!(r.passed(), "{}: {:?}", status, r.failures);
+            assert_eq!(r.measurement_status.as_deref(), Some(*status));
+        }
+        let revoked = assess(
+            1,
+            Ok(passing_self_test()),
+            Ok(evidence(&nonce, listed("revoked"))),
+            &nonce,
+            &manifest(),
+            None,
+        );
+        assert!(revoked.reason().contains("is revoked"));
+        let unlisted = assess(
+            1,
+            Ok(passing_self_test()),
+            Ok(evidence(&nonce, vec![0x42; 32])),
+            &nonce,
+            &manifest(),
+            None,
+        );
+        assert!(unlisted
+            .reason()
+            .contains("not in the measurements manifest"));
+    }
+
+    #[test]
+    fn self_test_nonce_and_key_failures_are_reported() {
+        let nonce = [7u8; 32];
+        let mut self_test = passing_self_test();
+        self_test.checks[1].passed = false;
+        self_test.checks[1].detail = "two RNG draws were equal".into();
+        let ev = evidence(&[8u8; 32], listed("current"));
+        let fp = replication::attestation_key_fingerprint(&ev);
+        let r = assess(
+            9,
+            Ok(self_test),
+            Ok(ev),
+            &nonce,
+            &manifest(),
+            Some("0xpinned"),
+        );
+        assert!(!r.passed());
+        assert_eq!(r.failed_checks(), vec!["rng".to_string()]);
+        assert_eq!(r.failures.len(), 3, "{:?}", r.failures);
+        assert!(r.failures[0].starts_with("self-test failed: rng"));
+        assert!(r.failures[1].contains("different nonce"));
+        assert!(r.failures[2].contains(&fp));
+
+        let down = assess(
+            9,
+            Err("TEE call timeout".into()),
+            Err("GetAttestation not supported".into()),
+            &nonce,
+            &manifest(),
+            None,
+        );
+        assert_eq!(down.failures.len(), 2);
+        assert!(down.self_test.is_empty() && down.ta_measurement.is_none());
+
+        let envelope = alert_envelope(true, &r.reason(), Some(&r), 9, 10);
+        assert_eq!(envelope.event_type, "device.health");
+        assert_eq!(envelope.payload.state, "quarantined");
+        assert_eq!(envelope.payload.failed_checks, vec!["rng".to_string()]);
+        assert_eq!(envelope.correlation_id, "device-health-9");
+        let back: HealthReport = serde_json::from_str(&serde_json::to_string(&r).unwrap()).unwrap();
+        assert_eq!(back, r);
+    }
+}
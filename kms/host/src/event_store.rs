@@ -0,0 +1,802 @@
+//! Event log behind the CA's wallet, address and transaction tables.
+//!
+//! `wallets`, `address_index` and `tx_history` are projections of
+//! `ca_events` (db.rs). Every KmsDb method that changes them builds a
+//! [`CaEvent`], applies it with [`apply`] and appends it in the same SQLite
+//! transaction, so the log and the tables cannot drift apart on a crash.
+//! Replaying the log from seq 1 into empty tables gives the same rows again:
+//! [`rebuild`] does that in place after corruption or a bad manual edit, and
+//! `KmsDb::verify_projections` does it into a scratch DB and compares.
+//!
+//! Each event carries the hash of the one before it, so a log copied off
+//! the box (or replicated row by row in seq order) can be checked without
+//! trusting the copy. Events only hold what the tables already hold —
+//! public keys, addresses, signed raw transactions — never key material.
+//!
+//! Other tables (sessions, audit, tenants, ...) keep their direct writes.
+//! The ones with a foreign key to `wallets` are left alone by a rebuild;
+//! [`RebuildReport::orphans`] counts rows whose wallet the log no longer has.
+
+use anyhow::{bail, Context, Result};
+use rusqlite::{params, Connection, OptionalExtension, TransactionBehavior};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::db::{PasskeyAttestation, WalletRow};
+
+/// `prev_hash` of seq 1.
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// One change to a projection. Serialized as the event's payload; the
+/// variant name (snake_case) is its `kind`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CaEvent {
+    WalletCreated(WalletRow),
+    WalletDerived {
+        key_id: String,
+        address: String,
+        public_key: String,
+        derivation_path: String,
+        status: String,
+    },
+    WalletStatusChanged {
+        key_id: String,
+        status: String,
+        error_msg: Option<String>,
+    },
+    PasskeyChanged {
+        key_id: String,
+        passkey_pubkey: String,
+        credential_id: Option<String>,
+    },
+    SignCountAdvanced {
+        key_id: String,
+        sign_count: u32,
+    },
+    /// Also drops the wallet's `address_index` rows, which the live DB does
+    /// by cascade but a rebuild (foreign keys off) would not.
+    WalletDeleted {
+        key_id: String,
+    },
+    LifecycleChanged {
+        key_id: String,
+        status: String,
+    },
+    PasskeyAttested {
+        key_id: String,
+        attestation: PasskeyAttestation,
+    },
+    /// Address stored lowercase; replaces an existing row.
+    AddressIndexed {
+        address: String,
+        key_id: String,
+        derivation_path: String,
+        public_key: Option<String>,
+    },
+    /// Public key learned for an address pinned without one.
+    AddressPublicKeyFilled {
+        address: String,
+        public_key: String,
+    },
+    /// A new pending ledger entry. The id is fixed here so a replay
+    /// reproduces the `replaced_by` links.
+    TxSigned {
+        id: i64,
+        key_id: String,
+        address: String,
+        derivation_path: String,
+        chain_id: u64,
+        nonce: u64,
+        /// Wei, decimal.
+        gas_price: String,
+        signed_tx: String,
+        tx_hash: String,
+        created_at: i64,
+    },
+    /// `id` was pending and is now superseded by `replaced_by`.
+    TxReplaced {
+        id: i64,
+        replaced_by: i64,
+    },
+    TxsConfirmed {
+        address: String,
+        chain_id: u64,
+        below_nonce: u64,
+    },
+    /// A ledger row as found when the log was started on an existing DB.
+    TxImported {
+        id: i64,
+        key_id: String,
+        address: String,
+        derivation_path: String,
+        chain_id: u64,
+        nonce: u64,
+        gas_price: String,
+        signed_tx: String,
+        tx_hash: String,
+        status: String,
+        replaced_by: Option<i64>,
+        created_at: i64,
+    },
+}
+
+impl CaEvent {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            CaEvent::WalletCreated(_) => "wallet_created",
+            CaEvent::WalletDerived { .. } => "wallet_derived",
+            CaEvent::WalletStatusChanged { .. } => "wallet_status_changed",
+            CaEvent::PasskeyChanged { .. } => "passkey_changed",
+            CaEvent::SignCountAdvanced { .. } => "sign_count_advanced",
+            CaEvent::WalletDeleted { .. } => "wallet_deleted",
+            CaEvent::LifecycleChanged { .. } => "lifecycle_changed",
+            CaEvent::PasskeyAttested { .. } => "passkey_attested",
+            CaEvent::AddressIndexed { .. } => "address_indexed",
+            CaEvent::AddressPublicKeyFilled { .. } => "address_public_key_filled",
+            CaEvent::TxSigned { .. } => "tx_signed",
+            CaEvent::TxReplaced { .. } => "tx_replaced",
+            CaEvent::TxsConfirmed { .. } => "txs_confirmed",
+            CaEvent::TxImported { .. } => "tx_imported",
+        }
+    }
+
+    /// What `events_for` looks the event up by: the wallet's key id, else
+    /// the account address, else the ledger row id.
+    pub fn aggregate(&self) -> String {
+        match self {
+            CaEvent::WalletCreated(w) => w.key_id.clone(),
+            CaEvent::WalletDerived { key_id, .. }
+            | CaEvent::WalletStatusChanged { key_id, .. }
+            | CaEvent::PasskeyChanged { key_id, .. }
+            | CaEvent::SignCountAdvanced { key_id, .. }
+            | CaEvent::WalletDeleted { key_id }
+            | CaEvent::LifecycleChanged { key_id, .. }
+            | CaEvent::PasskeyAttested { key_id, .. }
+            | CaEvent::AddressIndexed { key_id, .. }
+            | CaEvent::TxSigned { key_id, .. }
+            | CaEvent::TxImported { key_id, .. } => key_id.clone(),
+            CaEvent::AddressPublicKeyFilled { address, .. }
+            | CaEvent::TxsConfirmed { address, .. } => address.clone(),
+            CaEvent::TxReplaced { id, .. } => id.to_string(),
+        }
+    }
+}
+
+/// A stored event, as exported for audit or replication.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventRecord {
+    pub seq: u64,
+    pub kind: String,
+    pub aggregate: String,
+    /// The serialized [`CaEvent`], exactly as hashed.
+    pub payload: String,
+    pub created_at: i64,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+impl EventRecord {
+    pub fn event(&self) -> Result<CaEvent> {
+        serde_json::from_str(&self.payload)
+            .with_context(|| format!("event {} has an unreadable payload", self.seq))
+    }
+}
+
+/// SHA-256(prev_hash ‖ seq as u64 BE ‖ payload), hex.
+pub fn chain_hash(prev_hash: &str, seq: u64, payload: &str) -> String {
+    let mut h = Sha256::new();
+    h.update(prev_hash.as_bytes());
+    h.update(seq.to_be_bytes());
+    h.update(payload.as_bytes());
+    hex::encode(h.finalize())
+}
+
+/// Apply `event` to the projections. Returns the rows it changed.
+pub fn apply(conn: &Connection, event: &CaEvent) -> Result<usize> {
+    let n = match event {
+        CaEvent::WalletCreated(w) => conn.execute(
+            "INSERT INTO wallets (key_id, address, public_key, derivation_path, description, \
+             key_usage, key_spec, origin, passkey_pubkey, credential_id, sign_count, status, \
+             error_msg, created_at) VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10,?11,?12,?13,?14)",
+            params![
+                w.key_id,
+                w.address,
+                w.public_key,
+                w.derivation_path,
+                w.description,
+                w.key_usage,
+                w.key_spec,
+                w.origin,
+                w.passkey_pubkey,
+                w.credential_id,
+                w.sign_count,
+                w.status,
+                w.error_msg,
+                w.created_at,
+            ],
+        )?,
+        CaEvent::WalletDerived {
+            key_id,
+            address,
+            public_key,
+            derivation_path,
+            status,
+        } => conn.execute(
+            "UPDATE wallets SET address=?2, public_key=?3, derivation_path=?4, status=?5 \
+             WHERE key_id=?1",
+            params![key_id, address, public_key, derivation_path, status],
+        )?,
+        CaEvent::WalletStatusChanged {
+            key_id,
+            status,
+            error_msg,
+        } => conn.execute(
+            "UPDATE wallets SET status=?2, error_msg=?3 WHERE key_id=?1",
+            params![key_id, status, error_msg],
+        )?,
+        CaEvent::PasskeyChanged {
+            key_id,
+            passkey_pubkey,
+            credential_id,
+        } => conn.execute(
+            // The counter belonged to the old authenticator: a new one
+            // starting below it would read as a clone.
+            "UPDATE wallets SET passkey_pubkey=?2, credential_id=?3, sign_count=0 WHERE key_id=?1",
+            params![key_id, passkey_pubkey, credential_id],
+        )?,
+        CaEvent::SignCountAdvanced { key_id, sign_count } => conn.execute(
+            "UPDATE wallets SET sign_count=?2 WHERE key_id=?1",
+            params![key_id, sign_count],
+        )?,
+        CaEvent::WalletDeleted { key_id } => {
+            conn.execute("DELETE FROM address_index WHERE key_id=?1", params![key_id])?;
+            conn.execute("DELETE FROM wallets WHERE key_id=?1", params![key_id])?
+        }
+        CaEvent::LifecycleChanged { key_id, status } => conn.execute(
+            "UPDATE wallets SET lifecycle_status=?2 WHERE key_id=?1",
+            params![key_id, status],
+        )?,
+        CaEvent::PasskeyAttested {
+            key_id,
+            attestation: a,
+        } => conn.execute(
+            "UPDATE wallets SET attestation_type=?2, aaguid=?3, certification=?4, \
+             passkey_role=?5 WHERE key_id=?1",
+            params![
+                key_id,
+                a.attestation_type,
+                a.aaguid,
+                a.certification,
+                a.role
+            ],
+        )?,
+        CaEvent::AddressIndexed {
+            address,
+            key_id,
+            derivation_path,
+            public_key,
+        } => conn.execute(
+            "INSERT OR REPLACE INTO address_index (address, key_id, derivation_path, public_key) \
+             VALUES (?1,?2,?3,?4)",
+            params![address, key_id, derivation_path, public_key],
+        )?,
+        CaEvent::AddressPublicKeyFilled {
+            address,
+            public_key,
+        } => conn.execute(
+            "UPDATE address_index SET public_key=?2 WHERE address=?1 AND public_key IS NULL",
+            params![address, public_key],
+        )?,
+        CaEvent::TxSigned {
+            id,
+            key_id,
+            address,
+            derivation_path,
+            chain_id,
+            nonce,
+            gas_price,
+            signed_tx,
+            tx_hash,
+            created_at,
+        } => conn.execute(
+            "INSERT INTO tx_history (id, key_id, address, derivation_path, chain_id, nonce, \
+             gas_price, signed_tx, tx_hash, status, created_at) \
+             VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,'pending',?10)",
+            params![
+                id,
+                key_id,
+                address,
+                derivation_path,
+                *chain_id as i64,
+                *nonce as i64,
+                gas_price,
+                signed_tx,
+                tx_hash,
+                created_at
+            ],
+        )?,
+        CaEvent::TxReplaced { id, replaced_by } => conn.execute(
+            "UPDATE tx_history SET status='replaced', replaced_by=?2 \
+             WHERE id=?1 AND status='pending'",
+            params![id, replaced_by],
+        )?,
+        CaEvent::TxsConfirmed {
+            address,
+            chain_id,
+            below_nonce,
+        } => conn.execute(
+            "UPDATE tx_history SET status='confirmed' \
+             WHERE address=?1 AND chain_id=?2 AND status='pending' AND nonce<?3",
+            params![address, *chain_id as i64, *below_nonce as i64],
+        )?,
+        CaEvent::TxImported {
+            id,
+            key_id,
+            address,
+            derivation_path,
+            chain_id,
+            nonce,
+            gas_price,
+            signed_tx,
+            tx_hash,
+            status,
+            replaced_by,
+            created_at,
+        } => conn.execute(
+            "INSERT INTO tx_history (id, key_id, address, derivation_path, chain_id, nonce, \
+             gas_price, signed_tx, tx_hash, status, replaced_by, created_at) \
+             VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10,?11,?12)",
+            params![
+                id,
+                key_id,
+                address,
+                derivation_path,
+                *chain_id as i64,
+                *nonce as i64,
+                gas_price,
+                signed_tx,
+                tx_hash,
+                status,
+                replaced_by,
+                created_at
+            ],
+        )?,
+    };
+    Ok(n)
+}
+
+/// Seq and hash of the last event; (0, [`GENESIS_HASH`]) for an empty log.
+pub fn head(conn: &Connection) -> Result<(u64, String)> {
+    Ok(conn
+        .query_row(
+            "SELECT seq, hash FROM ca_events ORDER BY seq DESC LIMIT 1",
+            [],
+            |row| Ok((row.get::<_, i64>(0)? as u64, row.get::<_, String>(1)?)),
+        )
+        .optional()?
+        .unwrap_or((0, GENESIS_HASH.to_string())))
+}
+
+/// Append `event` after the current head. The caller holds a write
+/// transaction, which is what keeps seq gap-free across processes.
+fn append(conn: &Connection, event: &CaEvent) -> Result<u64> {
+    let (head, prev_hash) = head(conn)?;
+    let seq = head + 1;
+    let payload = serde_json::to_string(event)?;
+    let hash = chain_hash(&prev_hash, seq, &payload);
+    conn.execute(
+        "INSERT INTO ca_events (seq, kind, aggregate, payload, created_at, prev_hash, hash) \
+         VALUES (?1,?2,?3,?4,?5,?6,?7)",
+        params![
+            seq as i64,
+            event.kind(),
+            event.aggregate(),
+            payload,
+            chrono::Utc::now().timestamp(),
+            prev_hash,
+            hash
+        ],
+    )?;
+    Ok(seq)
+}
+
+/// Apply and append. A no-op (no row changed) is not logged. Call inside
+/// a transaction.
+pub(crate) fn record(conn: &Connection, event: &CaEvent) -> Result<usize> {
+    let n = apply(conn, event)?;
+    if n > 0 {
+        append(conn, event)?;
+    }
+    Ok(n)
+}
+
+/// Apply an event another node recorded and store it verbatim, after
+/// checking that it extends this log: the standby side of active-standby
+/// replication (standby.rs). Call inside a transaction.
+pub fn ingest(conn: &Connection, e: &EventRecord) -> Result<()> {
+    let (seq, hash) = head(conn)?;
+    if e.seq != seq + 1 || e.prev_hash != hash {
+        bail!(
+            "event {} does not extend the local log (head {} {})",
+            e.seq,
+            seq,
+            hash
+        );
+    }
+    if chain_hash(&e.prev_hash, e.seq, &e.payload) != e.hash {
+        bail!("event {} does not match its hash", e.seq);
+    }
+    let event = e.event()?;
+    if event.kind() != e.kind || event.aggregate() != e.aggregate {
+        bail!("event {} is labelled differently from its payload", e.seq);
+    }
+    apply(conn, &event).with_context(|| format!("applying event {}", e.seq))?;
+    conn.execute(
+        &format!(
+            "INSERT INTO ca_events ({}) VALUES (?1,?2,?3,?4,?5,?6,?7)",
+            EVENT_COLUMNS
+        ),
+        params![
+            e.seq as i64,
+            e.kind,
+            e.aggregate,
+            e.payload,
+            e.created_at,
+            e.prev_hash,
+            e.hash
+        ],
+    )?;
+    Ok(())
+}
+
+fn event_record(row: &rusqlite::Row) -> rusqlite::Result<EventRecord> {
+    Ok(EventRecord {
+        seq: row.get::<_, i64>(0)? as u64,
+        kind: row.get(1)?,
+        aggregate: row.get(2)?,
+        payload: row.get(3)?,
+        created_at: row.get(4)?,
+        prev_hash: row.get(5)?,
+        hash: row.get(6)?,
+    })
+}
+
+const EVENT_COLUMNS: &str = "seq, kind, aggregate, payload, created_at, prev_hash, hash";
+
+/// Events with seq > `after`, oldest first — the replication feed.
+pub fn events_after(conn: &Connection, after: u64, limit: usize) -> Result<Vec<EventRecord>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM ca_events WHERE seq > ?1 ORDER BY seq LIMIT ?2",
+        EVENT_COLUMNS
+    ))?;
+    let rows = stmt.query_map(params![after as i64, limit as i64], event_record)?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+/// One wallet's (or address's) history, oldest first.
+pub fn events_for(conn: &Connection, aggregate: &str) -> Result<Vec<EventRecord>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM ca_events WHERE aggregate=?1 ORDER BY seq",
+        EVENT_COLUMNS
+    ))?;
+    let rows = stmt.query_map(params![aggregate], event_record)?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+/// Head of an intact log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainHead {
+    pub events: u64,
+    pub hash: String,
+}
+
+/// Walk the log in seq order, checking each event's link and hash, and
+/// call `each` on the events that pass. Stops at the first broken link.
+fn walk(conn: &Connection, mut each: impl FnMut(&EventRecord) -> Result<()>) -> Result<ChainHead> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM ca_events ORDER BY seq",
+        EVENT_COLUMNS
+    ))?;
+    let mut rows = stmt.query([])?;
+    let mut head = ChainHead {
+        events: 0,
+        hash: GENESIS_HASH.to_string(),
+    };
+    while let Some(row) = rows.next()? {
+        let e = event_record(row)?;
+        if e.seq != head.events + 1 {
+            bail!("event log gap: seq {} follows {}", e.seq, head.events);
+        }
+        if e.prev_hash != head.hash {
+            bail!("event {} does not link to event {}", e.seq, head.events);
+        }
+        if chain_hash(&e.prev_hash, e.seq, &e.payload) != e.hash {
+            bail!("event {} does not match its hash", e.seq);
+        }
+        each(&e)?;
+        head = ChainHead {
+            events: e.seq,
+            hash: e.hash,
+        };
+    }
+    Ok(head)
+}
+
+pub fn verify_chain(conn: &Connection) -> Result<ChainHead> {
+    walk(conn, |_| Ok(()))
+}
+
+/// Replay the log of `from` into the (empty) projections of `into`.
+pub fn replay(from: &Connection, into: &Connection) -> Result<ChainHead> {
+    walk(from, |e| {
+        apply(into, &e.event()?).with_context(|| format!("replaying event {}", e.seq))?;
+        Ok(())
+    })
+}
+
+/// Projection tables with their columns and key, in the order a replay
+/// fills them.
+pub const PROJECTIONS: &[(&str, &str, &str)] = &[
+    (
+        "wallets",
+        "key_id, address, public_key, derivation_path, description, key_usage, key_spec, \
+         origin, passkey_pubkey, credential_id, sign_count, status, error_msg, created_at, \
+         lifecycle_status, attestation_type, aaguid, certification, passkey_role",
+        "key_id",
+    ),
+    (
+        "address_index",
+        "address, key_id, derivation_path, public_key",
+        "address",
+    ),
+    (
+        "tx_history",
+        "id, key_id, address, derivation_path, chain_id, nonce, gas_price, signed_tx, \
+         tx_hash, status, replaced_by, created_at",
+        "id",
+    ),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RebuildReport {
+    pub events: u64,
+    pub head: String,
+    pub wallets: u64,
+    pub addresses: u64,
+    pub txs: u64,
+    /// Rows in other tables still pointing at a wallet the log deleted or
+    /// never created (`PRAGMA foreign_key_check`). Reported, not removed.
+    pub orphans: u64,
+}
+
+fn count(conn: &Connection, table: &str) -> Result<u64> {
+    let n: i64 = conn.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |r| r.get(0))?;
+    Ok(n as u64)
+}
+
+/// Empty the projections and replay the whole log into them, in one
+/// transaction: a broken chain or a failing event leaves the tables as
+/// they were. Foreign keys are off meanwhile so clearing `wallets` does not
+/// cascade into the tables that are not event-sourced.
+pub fn rebuild(conn: &mut Connection) -> Result<RebuildReport> {
+    conn.execute_batch("PRAGMA foreign_keys=OFF")?;
+    let result = rebuild_projections(conn);
+    conn.execute_batch("PRAGMA foreign_keys=ON")?;
+    result
+}
+
+fn rebuild_projections(conn: &mut Connection) -> Result<RebuildReport> {
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    for (table, _, _) in PROJECTIONS.iter().rev() {
+        tx.execute(&format!("DELETE FROM {}", table), [])?;
+    }
+    let head = replay(&tx, &tx)?;
+    let orphans = {
+        let mut stmt = tx.prepare("PRAGMA foreign_key_check")?;
+        let mut rows = stmt.query([])?;
+        let mut n = 0u64;
+        while rows.next()?.is_some() {
+            n += 1;
+        }
+        n
+    };
+    let report = RebuildReport {
+        events: head.events,
+        head: head.hash,
+        wallets: count(&tx, "wallets")?,
+        addresses: count(&tx, "address_index")?,
+        txs: count(&tx, "tx_history")?,
+        orphans,
+    };
+    tx.commit()?;
+    Ok(report)
+}
+
+/// Start the log of a DB that has projection rows but no events yet (one
+/// written before the log existed): snapshot every row as an event, without
+/// re-applying it. Does nothing once the log has an event.
+pub fn bootstrap(conn: &mut Connection) -> Result<u64> {
+    let nothing_to_snapshot = |conn: &Connection| -> Result<bool> {
+        let (logged, projected): (bool, bool) = conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM ca_events), \
+             EXISTS(SELECT 1 FROM wallets) OR EXISTS(SELECT 1 FROM address_index) \
+             OR EXISTS(SELECT 1 FROM tx_history)",
+            [],
+            |r| Ok((r.get(0)?, r.get(1)?)),
+        )?;
+        Ok(logged || !projected)
+    };
+    if nothing_to_snapshot(conn)? {
+        return Ok(0);
+    }
+    // Checked again under the write lock: another process may be opening
+    // the same DB.
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    if nothing_to_snapshot(&tx)? {
+        return Ok(0);
+    }
+    let mut events = Vec::new();
+    {
+        let mut stmt = tx.prepare(&format!(
+            "SELECT {} FROM wallets ORDER BY created_at, key_id",
+            PROJECTIONS[0].1
+        ))?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let wallet = WalletRow {
+                key_id: row.get(0)?,
+                address: row.get(1)?,
+                public_key: row.get(2)?,
+                derivation_path: row.get(3)?,
+                description: row.get(4)?,
+                key_usage: row.get(5)?,
+                key_spec: row.get(6)?,
+                origin: row.get(7)?,
+                passkey_pubkey: row.get(8)?,
+                credential_id: row.get(9)?,
+                sign_count: row.get(10)?,
+                status: row.get(11)?,
+                error_msg: row.get(12)?,
+                created_at: row.get(13)?,
+            };
+            let key_id = wallet.key_id.clone();
+            events.push(CaEvent::WalletCreated(wallet));
+            let lifecycle: String = row.get(14)?;
+            if lifecycle != "active" {
+                events.push(CaEvent::LifecycleChanged {
+                    key_id: key_id.clone(),
+                    status: lifecycle,
+                });
+            }
+            let attestation = PasskeyAttestation {
+                attestation_type: row.get(15)?,
+                aaguid: row.get(16)?,
+                certification: row.get(17)?,
+                role: row.get(18)?,
+            };
+            if attestation != PasskeyAttestation::default() {
+                events.push(CaEvent::PasskeyAttested {
+                    key_id,
+                    attestation,
+                });
+            }
+        }
+    }
+    {
+        let mut stmt = tx.prepare(&format!(
+            "SELECT {} FROM address_index ORDER BY address",
+            PROJECTIONS[1].1
+        ))?;
+        let rows = stmt.query_map([], |row| {
+            Ok(CaEvent::AddressIndexed {
+                address: row.get(0)?,
+                key_id: row.get(1)?,
+                derivation_path: row.get(2)?,
+                public_key: row.get(3)?,
+            })
+        })?;
+        for r in rows {
+            events.push(r?);
+        }
+    }
+    {
+        let mut stmt = tx.prepare(&format!(
+            "SELECT {} FROM tx_history ORDER BY id",
+            PROJECTIONS[2].1
+        ))?;
+        let rows = stmt.query_map([], |row| {
+            Ok(CaEvent::TxImported {
+                id: row.get(0)?,
+                key_id: row.get(1)?,
+                address: row.get(2)?,
+                derivation_path: row.get(3)?,
+                chain_id: row.get::<_, i64>(4)? as u64,
+                nonce: row.get::<_, i64>(5)? as u64,
+                gas_price: row.get(6)?,
+                signed_tx: row.get(7)?,
+                tx_hash: row.get(8)?,
+                status: row.get(9)?,
+                replaced_by: row.get(10)?,
+                created_at: row.get(11)?,
+            })
+        })?;
+        for r in rows {
+            events.push(r?);
+        }
+    }
+    for e in &events {
+        append(&tx, e)?;
+    }
+    tx.commit()?;
+    Ok(events.len() as u64)
+}
+
+/// A row that differs between the tables and a replay of the log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProjectionDiff {
+    pub table: &'static str,
+    pub key: String,
+    /// The row as stored; None if only the log has it.
+    pub stored: Option<String>,
+    /// The row the log produces; None if only the table has it.
+    pub replayed: Option<String>,
+}
+
+fn table_rows(
+    conn: &Connection,
+    table: &str,
+    columns: &str,
+    key: &str,
+) -> Result<std::collections::BTreeMap<String, String>> {
+    let mut stmt = conn.prepare(&format!("SELECT {}, {} FROM {}", key, columns, table))?;
+    let width = stmt.column_count();
+    let mut rows = stmt.query([])?;
+    let mut out = std::collections::BTreeMap::new();
+    while let Some(row) = rows.next()? {
+        let key: rusqlite::types::Value = row.get(0)?;
+        let values = (1..width)
+            .map(|i| row.get::<_, rusqlite::types::Value>(i))
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        out.insert(value_text(&key), format!("{:?}", values));
+    }
+    Ok(out)
+}
+
+fn value_text(v: &rusqlite::types::Value) -> String {
+    match v {
+        rusqlite::types::Value::Text(s) => s.clone(),
+        rusqlite::types::Value::Integer(i) => i.to_string(),
+        other => format!("{:?}", other),
+    }
+}
+
+/// Compare `live`'s projections with `replayed`, a DB the log was replayed
+/// into.
+pub fn diff_projections(live: &Connection, replayed: &Connection) -> Result<Vec<ProjectionDiff>> {
+    let mut diffs = Vec::new();
+    for (table, columns, key) in PROJECTIONS {
+        let stored = table_rows(live, table, columns, key)?;
+        let mut expected = table_rows(replayed, table, columns, key)?;
+        for (k, row) in stored {
+            match expected.remove(&k) {
+                Some(r) if r == row => {}
+                other => diffs.push(ProjectionDiff {
+                    table,
+                    key: k,
+                    stored: Some(row),
+                    replayed: other,
+                }),
+            }
+        }
+        for (k, row) in expected {
+            diffs.push(ProjectionDiff {
+                table,
+                key: k,
+                stored: None,
+                replayed: Some(row),
+            });
+        }
+    }
+    Ok(diffs)
+}
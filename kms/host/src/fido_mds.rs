@@ -0,0 +1,605 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! FIDO Metadata Service (MDS3) checks for WebAuthn registration.
+//!
+//! The operator downloads the MDS3 BLOB, checks its JWT signature against
+//! the FIDO root and saves the decoded payload at `KMS_FIDO_MDS_FILE`. With
+//! it loaded, BeginRegistration asks for `direct` attestation and
+//! CompleteRegistration chains the authenticator's `x5c` to the roots MDS
+//! lists for its AAGUID, refusing models MDS reports as compromised.
+//!
+//! `KMS_FIDO_ATTESTATION_POLICY` names roles (a JSON file, see
+//! [`AttestationPolicy`]); a registration that asks for a role must come
+//! from an attested authenticator with an allowed AAGUID and at least the
+//! role's certification level.
+//!
+//! Only ECDSA P-256 / SHA-256 certificates are checked: the CA links no RSA
+//! code, so a chain it cannot verify leaves the credential uncertified
+//! rather than failing the registration.
+
+use anyhow::{anyhow, bail, Context, Result};
+use p256::ecdsa::{signature::Verifier, Signature, VerifyingKey};
+use p256::pkcs8::DecodePublicKey;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use uuid::Uuid;
+
+/// FIDO certification levels, lowest first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(try_from = "String")]
+pub enum CertificationLevel {
+    L1,
+    L1Plus,
+    L2,
+    L2Plus,
+    L3,
+    L3Plus,
+}
+
+impl CertificationLevel {
+    /// MDS status string, e.g. `FIDO_CERTIFIED_L2`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CertificationLevel::L1 => "FIDO_CERTIFIED_L1",
+            CertificationLevel::L1Plus => "FIDO_CERTIFIED_L1plus",
+            CertificationLevel::L2 => "FIDO_CERTIFIED_L2",
+            CertificationLevel::L2Plus => "FIDO_CERTIFIED_L2plus",
+            CertificationLevel::L3 => "FIDO_CERTIFIED_L3",
+            CertificationLevel::L3Plus => "FIDO_CERTIFIED_L3plus",
+        }
+    }
+
+    /// The level an MDS status report grants, if it is a certification.
+    /// Plain `FIDO_CERTIFIED` predates the levels and counts as L1.
+    pub fn from_status(status: &str) -> Option<Self> {
+        Some(match status {
+            "FIDO_CERTIFIED" | "FIDO_CERTIFIED_L1" => CertificationLevel::L1,
+            "FIDO_CERTIFIED_L1plus" => CertificationLevel::L1Plus,
+            "FIDO_CERTIFIED_L2" => CertificationLevel::L2,
+            "FIDO_CERTIFIED_L2plus" => CertificationLevel::L2Plus,
+            "FIDO_CERTIFIED_L3" => CertificationLevel::L3,
+            "FIDO_CERTIFIED_L3plus" => CertificationLevel::L3Plus,
+            _ => return None,
+        })
+    }
+}
+
+impl TryFrom<String> for CertificationLevel {
+    type Error = String;
+    fn try_from(s: String) -> std::result::Result<Self, String> {
+        Self::from_status(&s).ok_or_else(|| format!("unknown certification level {:?}", s))
+    }
+}
+
+/// Status reports after which an authenticator model must not register.
+const COMPROMISED_STATUSES: &[&str] = &[
+    "REVOKED",
+    "ATTESTATION_KEY_COMPROMISE",
+    "USER_VERIFICATION_BYPASS",
+    "USER_KEY_REMOTE_COMPROMISE",
+    "USER_KEY_PHYSICAL_COMPROMISE",
+];
+
+/// What MDS says about one AAGUID.
+#[derive(Debug, Clone)]
+pub struct MdsEntry {
+    pub description: String,
+    /// DER attestation root certificates.
+    pub roots: Vec<Vec<u8>>,
+    /// Highest level any status report grants.
+    pub certification: Option<CertificationLevel>,
+    /// The latest status report, when it is one of [`COMPROMISED_STATUSES`].
+    pub compromised: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct BlobJson {
+    entries: Vec<EntryJson>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EntryJson {
+    // FIDO U2F entries have attestationCertificateKeyIdentifiers instead.
+    #[serde(default)]
+    aaguid: Option<String>,
+    #[serde(default)]
+    metadata_statement: Option<StatementJson>,
+    #[serde(default)]
+    status_reports: Vec<StatusJson>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StatementJson {
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    attestation_root_certificates: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct StatusJson {
+    status: String,
+}
+
+/// Requirements a role puts on the registering authenticator.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct RoleRequirement {
+    /// Allowed AAGUIDs; empty allows any model MDS vouches for.
+    #[serde(default)]
+    pub aaguids: Vec<Uuid>,
+    #[serde(default)]
+    pub min_certification: Option<CertificationLevel>,
+}
+
+/// `KMS_FIDO_ATTESTATION_POLICY`, e.g.
+/// `{"roles": {"treasury": {"minCertification": "FIDO_CERTIFIED_L2"}}}`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AttestationPolicy {
+    pub roles: BTreeMap<String, RoleRequirement>,
+}
+
+/// Loaded MDS payload plus the role policy.
+#[derive(Debug, Clone, Default)]
+pub struct FidoMds {
+    entries: BTreeMap<Uuid, MdsEntry>,
+    pub policy: AttestationPolicy,
+}
+
+impl FidoMds {
+    /// `KMS_FIDO_MDS_FILE` and `KMS_FIDO_ATTESTATION_POLICY`; None when neither
+    /// is set. A policy without metadata is an error: roles cannot be checked.
+    pub fn from_env() -> Option<Result<Self>> {
+        let var = |k: &str| std::env::var(k).ok().filter(|v| !v.is_empty());
+        let mds_file = var("KMS_FIDO_MDS_FILE");
+        let policy_file = var("KMS_FIDO_ATTESTATION_POLICY");
+        if mds_file.is_none() && policy_file.is_none() {
+            return None;
+        }
+        Some((|| {
+            let mds_file = mds_file
+                .ok_or_else(|| anyhow!("KMS_FIDO_ATTESTATION_POLICY needs KMS_FIDO_MDS_FILE"))?;
+            let blob =
+                std::fs::read_to_string(&mds_file).with_context(|| format!("read {}", mds_file))?;
+            let mut mds = Self::from_blob_json(&blob)?;
+            if let Some(path) = policy_file {
+                let policy =
+                    std::fs::read_to_string(&path).with_context(|| format!("read {}", path))?;
+                mds.policy = serde_json::from_str(&policy)
+                    .with_context(|| format!("parse attestation policy {}", path))?;
+            }
+            Ok(mds)
+        })())
+    }
+
+    /// Parse the MDS3 BLOB payload (the JSON inside the JWT).
+    pub fn from_blob_json(json: &str) -> Result<Self> {
+        let blob: BlobJson = serde_json::from_str(json).context("parse MDS3 payload")?;
+        let mut entries = BTreeMap::new();
+        for e in blob.entries {
+            let aaguid = match e.aaguid.as_deref().map(Uuid::parse_str) {
+                Some(Ok(a)) => a,
+                Some(Err(err)) => bail!("MDS entry has a bad aaguid: {}", err),
+                None => continue,
+            };
+            let statement = e.metadata_statement.unwrap_or(StatementJson {
+                description: String::new(),
+                attestation_root_certificates: Vec::new(),
+            });
+            let roots = statement
+                .attestation_root_certificates
+                .iter()
+                .map(|c| {
+                    base64::Engine::decode(&base64::engine::general_purpose::STANDARD, c)
+                        .map_err(|err| anyhow!("MDS root for {} is not base64: {}", aaguid, err))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let certification = e
+                .status_reports
+                .iter()
+                .filter_map(|r| CertificationLevel::from_status(&r.status))
+                .max();
+            let compromised = e
+                .status_reports
+                .last()
+                .filter(|r| COMPROMISED_STATUSES.contains(&r.status.as_str()))
+                .map(|r| r.status.clone());
+            entries.insert(
+                aaguid,
+                MdsEntry {
+                    description: statement.description,
+                    roots,
+                    certification,
+                    compromised,
+                },
+            );
+        }
+        Ok(Self {
+            entries,
+            policy: AttestationPolicy::default(),
+        })
+    }
+
+    pub fn entry(&self, aaguid: &Uuid) -> Option<&MdsEntry> {
+        self.entries.get(aaguid)
+    }
+
+    /// Refuse an unknown role before the browser ceremony starts.
+    pub fn check_role_known(&self, role: &str) -> Result<()> {
+        if !self.policy.roles.contains_key(role) {
+            bail!("unknown registration role '{}'", role);
+        }
+        Ok(())
+    }
+
+    /// Judge a verified registration. Returns the certification level MDS
+    /// vouches for, or None when the attestation does not chain to MDS.
+    pub fn check(
+        &self,
+        role: Option<&str>,
+        aaguid: &Uuid,
+        x5c: &[Vec<u8>],
+    ) -> Result<Option<CertificationLevel>> {
+        let entry = self.entry(aaguid);
+        if let Some(status) = entry.and_then(|e| e.compromised.as_deref()) {
+            bail!(
+                "authenticator {} is reported {} by FIDO MDS",
+                aaguid,
+                status
+            );
+        }
+        let certification = match entry {
+            Some(e) if !x5c.is_empty() => match verify_chain(x5c, &e.roots)? {
+                // Attested but uncertified models are still MDS-listed: treat
+                // them as below L1 by leaving the level empty.
+                ChainStatus::Verified => e.certification,
+                ChainStatus::Unsupported => None,
+            },
+            _ => None,
+        };
+        if let Some(role) = role {
+            let req = self
+                .policy
+                .roles
+                .get(role)
+                .ok_or_else(|| anyhow!("unknown registration role '{}'", role))?;
+            let level = certification.ok_or_else(|| {
+                anyhow!(
+                    "role '{}' needs an authenticator whose attestation FIDO MDS certifies",
+                    role
+                )
+            })?;
+            if !req.aaguids.is_empty() && !req.aaguids.contains(aaguid) {
+                bail!(
+                    "authenticator {} is not allowed for role '{}'",
+                    aaguid,
+                    role
+                );
+            }
+            if let Some(min) = req.min_certification {
+                if level < min {
+                    bail!(
+                        "role '{}' needs {} or higher, authenticator is {}",
+                        role,
+                        min.as_str(),
+                        level.as_str()
+                    );
+                }
+            }
+        }
+        Ok(certification)
+    }
+}
+
+// ========================================
+// Minimal X.509 (DER) for attestation chains
+// ========================================
+
+/// ecdsa-with-SHA256, 1.2.840.10045.4.3.2.
+const OID_ECDSA_SHA256: &[u8] = &[0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02];
+
+/// (tag, contents, whole TLV, rest of the input).
+type Tlv<'a> = (u8, &'a [u8], &'a [u8], &'a [u8]);
+
+/// Split one DER TLV off `input`.
+fn der_next(input: &[u8]) -> Result<Tlv<'_>> {
+    if input.len() < 2 {
+        bail!("truncated DER");
+    }
+    let tag = input[0];
+    let (len, hdr) = match input[1] {
+        n if n < 0x80 => (n as usize, 2),
+        0x81 if input.len() >= 3 => (input[2] as usize, 3),
+        0x82 if input.len() >= 4 => (((input[2] as usize) << 8) | input[3] as usize, 4),
+        0x83 if input.len() >= 5 => (
+            ((input[2] as usize) << 16) | ((input[3] as usize) << 8) | input[4] as usize,
+            5,
+        ),
+        _ => bail!("unsupported DER length"),
+    };
+    if input.len() < hdr + len {
+        bail!("truncated DER");
+    }
+    Ok((
+        tag,
+        &input[hdr..hdr + len],
+        &input[..hdr + len],
+        &input[hdr + len..],
+    ))
+}
+
+/// The parts of a certificate a chain check needs.
+pub struct Certificate<'a> {
+    der: &'a [u8],
+    tbs: &'a [u8],
+    sig_alg: &'a [u8],
+    signature: &'a [u8],
+    issuer: &'a [u8],
+    subject: &'a [u8],
+    spki: &'a [u8],
+}
+
+impl<'a> Certificate<'a> {
+    pub fn parse(der: &'a [u8]) -> Result<Self> {
+        let (tag, body, _, _) = der_next(der)?;
+        if tag != 0x30 {
+            bail!("certificate is not a SEQUENCE");
+        }
+        let (_, _, tbs, rest) = der_next(body)?;
+        let (_, sig_alg, _, rest) = der_next(rest)?;
+        let (tag, sig_bits, _, _) = der_next(rest)?;
+        if tag != 0x03 || sig_bits.first() != Some(&0) {
+            bail!("bad certificate signature");
+        }
+
+        let (_, mut fields, _, _) = der_next(tbs)?;
+        let mut next = || -> Result<(u8, &'a [u8])> {
+            let (tag, _, whole, rest) = der_next(fields)?;
+            fields = rest;
+            Ok((tag, whole))
+        };
+        let mut tag = next()?.0;
+        if tag == 0xa0 {
+            // [0] version
+            tag = next()?.0;
+        }
+        if tag != 0x02 {
+            bail!("certificate serial missing");
+        }
+        let _ = next()?; // signature algorithm
+        let (_, issuer) = next()?;
+        let _ = next()?; // validity
+        let (_, subject) = next()?;
+        let (_, spki) = next()?;
+        Ok(Self {
+            der,
+            tbs,
+            sig_alg,
+            signature: &sig_bits[1..],
+            issuer,
+            subject,
+            spki,
+        })
+    }
+
+    /// The subject key, if it is P-256.
+    pub fn p256_key(&self) -> Option<VerifyingKey> {
+        VerifyingKey::from_public_key_der(self.spki).ok()
+    }
+
+    fn signed_by(&self, issuer: &Certificate) -> ChainLink {
+        if self.issuer != issuer.subject {
+            return ChainLink::Invalid;
+        }
+        let key = match issuer.p256_key() {
+            Some(k) if self.sig_alg.starts_with(OID_ECDSA_SHA256) => k,
+            _ => return ChainLink::Unsupported,
+        };
+        match Signature::from_der(self.signature) {
+            Ok(sig) if key.verify(self.tbs, &sig).is_ok() => ChainLink::Verified,
+            _ => ChainLink::Invalid,
+        }
+    }
+}
+
+enum ChainLink {
+    Verified,
+    Unsupported,
+    Invalid,
+}
+
+/// Outcome of a chain that did not fail outright.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChainStatus {
+    Verified,
+    /// Uses an algorithm other than ECDSA P-256 / SHA-256 somewhere.
+    Unsupported,
+}
+
+/// Check `x5c` (leaf first) up to one of `roots`. A forged link is an error.
+pub fn verify_chain(x5c: &[Vec<u8>], roots: &[Vec<u8>]) -> Result<ChainStatus> {
+    let certs = x5c
+        .iter()
+        .map(|c| Certificate::parse(c))
+        .collect::<Result<Vec<_>>>()?;
+    let last = certs.last().ok_or_else(|| anyhow!("empty x5c"))?;
+    let mut unsupported = false;
+    for pair in certs.windows(2) {
+        match pair[0].signed_by(&pair[1]) {
+            ChainLink::Verified => {}
+            ChainLink::Unsupported => unsupported = true,
+            ChainLink::Invalid => bail!("attestation certificate chain is broken"),
+        }
+    }
+    for root in roots {
+        let root = match Certificate::parse(root) {
+            Ok(r) => r,
+            Err(_) => continue,
+        };
+        if root.der == last.der {
+            return Ok(if unsupported {
+                ChainStatus::Unsupported
+            } else {
+                ChainStatus::Verified
+            });
+        }
+        if last.issuer != root.subject {
+            continue;
+        }
+        match last.signed_by(&root) {
+            ChainLink::Verified if !unsupported => return Ok(ChainStatus::Verified),
+            ChainLink::Verified | ChainLink::Unsupported => return Ok(ChainStatus::Unsupported),
+            ChainLink::Invalid => bail!("attestation certificate is not signed by its MDS root"),
+        }
+    }
+    bail!("attestation does not chain to an MDS root for this authenticator")
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use p256::ecdsa::{signature::Signer, SigningKey};
+    use p256::pkcs8::EncodePublicKey;
+
+    fn tlv(tag: u8, body: &[u8]) -> Vec<u8> {
+        let mut out = vec![tag];
+        if body.len() < 0x80 {
+            out.push(body.len() as u8);
+        } else if body.len() < 0x100 {
+            out.extend_from_slice(&[0x81, body.len() as u8]);
+        } else {
+            out.extend_from_slice(&[0x82, (body.len() >> 8) as u8, body.len() as u8]);
+        }
+        out.extend_from_slice(body);
+        out
+    }
+
+    fn name(cn: &str) -> Vec<u8> {
+        // SEQUENCE { SET { SEQUENCE { OID commonName, UTF8String } } }
+        let atv = [tlv(0x06, &[0x55, 0x04, 0x03]), tlv(0x0c, cn.as_bytes())].concat();
+        tlv(0x30, &tlv(0x31, &tlv(0x30, &atv)))
+    }
+
+    /// A DER certificate for `subject_key`, signed by `issuer_key`.
+    pub(crate) fn make_cert(
+        subject: &str,
+        subject_key: &SigningKey,
+        issuer: &str,
+        issuer_key: &SigningKey,
+    ) -> Vec<u8> {
+        let alg = tlv(0x30, OID_ECDSA_SHA256);
+        let validity = tlv(
+            0x30,
+            &[tlv(0x17, b"260101000000Z"), tlv(0x17, b"360101000000Z")].concat(),
+        );
+        let spki = VerifyingKey::from(subject_key)
+            .to_public_key_der()
+            .unwrap()
+            .as_bytes()
+            .to_vec();
+        let tbs = tlv(
+            0x30,
+            &[
+                tlv(0xa0, &tlv(0x02, &[2])),
+                tlv(0x02, &[1]),
+                alg.clone(),
+                name(issuer),
+                validity,
+                name(subject),
+                spki,
+            ]
+            .concat(),
+        );
+        let sig: Signature = issuer_key.sign(&tbs);
+        let mut bits = vec![0u8];
+        bits.extend_from_slice(sig.to_der().as_bytes());
+        tlv(0x30, &[tbs, alg, tlv(0x03, &bits)].concat())
+    }
+
+    const AAGUID: &str = "2fc0579f-8113-47ea-b116-bb5a8db9202a";
+
+    fn mds_with(root: &[u8], status: &str) -> FidoMds {
+        let json = serde_json::json!({
+            "no": 1,
+            "nextUpdate": "2026-11-01",
+            "entries": [{
+                "aaguid": AAGUID,
+                "metadataStatement": {
+                    "description": "Test Key",
+                    "attestationRootCertificates": [
+                        base64::Engine::encode(&base64::engine::general_purpose::STANDARD, root)
+                    ]
+                },
+                "statusReports": [{ "status": "FIDO_CERTIFIED_L1" }, { "status": status }]
+            }, {
+                "attestationCertificateKeyIdentifiers": ["00"],
+                "statusReports": []
+            }]
+        });
+        FidoMds::from_blob_json(&json.to_string()).unwrap()
+    }
+
+    #[test]
+    fn chain_to_mds_root_and_role_policy() {
+        let root_key = SigningKey::from_slice(&[1; 32]).unwrap();
+        let leaf_key = SigningKey::from_slice(&[2; 32]).unwrap();
+        let root = make_cert("Test Root", &root_key, "Test Root", &root_key);
+        let leaf = make_cert("Test Attestation", &leaf_key, "Test Root", &root_key);
+        let aaguid = Uuid::parse_str(AAGUID).unwrap();
+
+        let mut mds = mds_with(&root, "FIDO_CERTIFIED_L2");
+        mds.policy = serde_json::from_str(
+            r#"{"roles": {"treasury": {"minCertification": "FIDO_CERTIFIED_L3"},
+                          "ops": {"aaguids": ["2fc0579f-8113-47ea-b116-bb5a8db9202a"]}}}"#,
+        )
+        .unwrap();
+
+        let x5c = vec![leaf.clone()];
+        assert_eq!(
+            mds.check(None, &aaguid, &x5c).unwrap(),
+            Some(CertificationLevel::L2)
+        );
+        assert!(mds.check(Some("ops"), &aaguid, &x5c).is_ok());
+        let err = mds.check(Some("treasury"), &aaguid, &x5c).unwrap_err();
+        assert!(err.to_string().contains("FIDO_CERTIFIED_L3"), "{}", err);
+        assert!(mds.check(Some("auditor"), &aaguid, &x5c).is_err());
+        // Self-attested: no chain, so no role.
+        assert_eq!(mds.check(None, &aaguid, &[]).unwrap(), None);
+        assert!(mds.check(Some("ops"), &aaguid, &[]).is_err());
+
+        // A leaf signed by another key does not pass as the root's.
+        let rogue_key = SigningKey::from_slice(&[3; 32]).unwrap();
+        let forged = make_cert("Test Attestation", &leaf_key, "Test Root", &rogue_key);
+        assert!(mds.check(None, &aaguid, &[forged]).is_err());
+    }
+
+    #[test]
+    fn compromised_authenticators_are_refused() {
+        let root_key = SigningKey::from_slice(&[1; 32]).unwrap();
+        let root = make_cert("Test Root", &root_key, "Test Root", &root_key);
+        let mds = mds_with(&root, "ATTESTATION_KEY_COMPROMISE");
+        let aaguid = Uuid::parse_str(AAGUID).unwrap();
+        assert!(mds.check(None, &aaguid, &[]).is_err());
+        // Unlisted models register uncertified.
+        assert_eq!(mds.check(None, &Uuid::nil(), &[]).unwrap(), None);
+    }
+}
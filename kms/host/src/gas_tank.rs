@@ -0,0 +1,474 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Gas tanks — native balances of operator relayer wallets.
+//!
+//! Bundlers and paymaster signers pay gas from plain EOAs; when one runs dry
+//! the sponsored pipeline stalls without an error anyone sees. The monitor
+//! polls `eth_getBalance` for every tank in `gas_tanks` (db.rs) each
+//! `KMS_GAS_TANK_INTERVAL_SECS`, exports `kms.gas_tank.balance` and
+//! `kms.gas_tank.low` gauges, and on crossing the floor POSTs a
+//! `gas_tank.alert` event to the event webhook.
+//!
+//! A tank with a treasury source also gets a refill proposal. Nothing is sent
+//! until one of `KMS_GAS_TANK_APPROVERS` approves it with their passkey and the
+//! treasury wallet's owner signs the transaction through the normal `/Sign`
+//! flow (service.rs) — two people, two ceremonies.
+//!
+//! JSON-RPC goes to `KMS_GAS_TANK_RPC`, one `http://` endpoint per chain.
+
+use anyhow::{anyhow, bail, Context, Result};
+use proto::event::{EventEnvelope, GasTankAlertPayload};
+use serde_json::{json, Value};
+use sha3::{Digest, Keccak256};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use crate::db::{GasTankRow, GasTankTopUpRow, KmsDb};
+use crate::standby;
+
+/// How long a refill proposal stays open before it is expired.
+pub const TOPUP_TTL_SECS: i64 = 3600;
+const DEFAULT_INTERVAL_SECS: u64 = 60;
+const RPC_TIMEOUT_SECS: u64 = 10;
+
+#[derive(Debug, Clone)]
+pub struct GasTankConfig {
+    /// Chain id → JSON-RPC URL.
+    pub rpc_urls: BTreeMap<u64, String>,
+    pub interval_secs: u64,
+    /// Key ids whose passkeys may approve a refill.
+    pub approvers: Vec<String>,
+    pub webhook_url: Option<String>,
+    pub webhook_secret: Option<Vec<u8>>,
+}
+
+impl GasTankConfig {
+    /// `KMS_GAS_TANK_RPC` (`<chainId>=<url>,…`, required to enable),
+    /// `KMS_GAS_TANK_INTERVAL_SECS` (15-3600, default 60),
+    /// `KMS_GAS_TANK_APPROVERS` (comma-separated key ids); alerts go to
+    /// `KMS_EVENT_WEBHOOK_URL` / `KMS_EVENT_WEBHOOK_SECRET` when set.
+    pub fn from_env() -> Option<Result<Self>> {
+        let var = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+        let rpc = var("KMS_GAS_TANK_RPC")?;
+        Some(Self::parse(
+            &rpc,
+            var("KMS_GAS_TANK_INTERVAL_SECS").as_deref(),
+            var("KMS_GAS_TANK_APPROVERS").as_deref(),
+            var("KMS_EVENT_WEBHOOK_URL"),
+            var("KMS_EVENT_WEBHOOK_SECRET").map(String::into_bytes),
+        ))
+    }
+
+    pub fn parse(
+        rpc: &str,
+        interval: Option<&str>,
+        approvers: Option<&str>,
+        webhook_url: Option<String>,
+        webhook_secret: Option<Vec<u8>>,
+    ) -> Result<Self> {
+        let mut rpc_urls = BTreeMap::new();
+        for entry in rpc.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (chain, url) = entry.split_once('=').ok_or_else(|| {
+                anyhow!("KMS_GAS_TANK_RPC entry {:?} is not <chainId>=<url>", entry)
+            })?;
+            let chain: u64 = chain
+                .trim()
+                .parse()
+                .ok()
+                .filter(|c| *c != 0)
+                .ok_or_else(|| anyhow!("KMS_GAS_TANK_RPC: bad chain id {:?}", chain))?;
+            let url = url.trim();
+            if !url.starts_with("http://") {
+                bail!("KMS_GAS_TANK_RPC must use http:// (no TLS in the CA; use a local proxy)");
+            }
+            if rpc_urls.insert(chain, url.to_string()).is_some() {
+                bail!("KMS_GAS_TANK_RPC lists chain {} twice", chain);
+            }
+        }
+        if rpc_urls.is_empty() {
+            bail!("KMS_GAS_TANK_RPC names no chains");
+        }
+        let interval_secs = match interval {
+            None => DEFAULT_INTERVAL_SECS,
+            Some(v) => v
+                .parse()
+                .ok()
+                .filter(|s| (15..=3600).contains(s))
+                .ok_or_else(|| anyhow!("KMS_GAS_TANK_INTERVAL_SECS must be 15-3600"))?,
+        };
+        let approvers = approvers
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|a| !a.is_empty())
+            .map(str::to_string)
+            .collect();
+        if let Some(url) = &webhook_url {
+            if !url.starts_with("http://") {
+                bail!("KMS_EVENT_WEBHOOK_URL must be http:// (no TLS in the CA)");
+            }
+            if webhook_secret.is_none() {
+                bail!("KMS_EVENT_WEBHOOK_URL needs KMS_EVENT_WEBHOOK_SECRET");
+            }
+        }
+        Ok(Self {
+            rpc_urls,
+            interval_secs,
+            approvers,
+            webhook_url,
+            webhook_secret,
+        })
+    }
+
+    pub fn rpc_url(&self, chain_id: u64) -> Result<&str> {
+        self.rpc_urls
+            .get(&chain_id)
+            .map(String::as_str)
+            .ok_or_else(|| anyhow!("no KMS_GAS_TANK_RPC endpoint for chain {}", chain_id))
+    }
+}
+
+/// What one balance reading means for a tank.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transition {
+    Healthy,
+    BecameLow,
+    StillLow,
+    Recovered,
+}
+
+pub fn transition(low_since: Option<i64>, balance_wei: u128, min_balance_wei: u128) -> Transition {
+    match (low_since.is_some(), balance_wei < min_balance_wei) {
+        (false, false) => Transition::Healthy,
+        (false, true) => Transition::BecameLow,
+        (true, true) => Transition::StillLow,
+        (true, false) => Transition::Recovered,
+    }
+}
+
+/// A JSON-RPC quantity. Balances beyond u128 (3.4e20 ETH) saturate.
+pub fn quantity(v: &Value) -> Option<u128> {
+    let h = v.as_str()?.strip_prefix("0x")?;
+    if h.is_empty() || !h.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let h = h.trim_start_matches('0');
+    if h.len() > 32 {
+        return Some(u128::MAX);
+    }
+    Some(u128::from_str_radix(h, 16).unwrap_or(0))
+}
+
+/// One JSON-RPC call over HTTP.
+pub async fn rpc(url: &str, method: &str, params: Value) -> Result<Value> {
+    use warp::hyper::{body, Body, Client, Request};
+    let request = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+    let req = Request::post(url)
+        .header("content-type", "application/json")
+        .body(Body::from(request.to_string()))?;
+    let resp = tokio::time::timeout(
+        Duration::from_secs(RPC_TIMEOUT_SECS),
+        Client::new().request(req),
+    )
+    .await
+    .map_err(|_| anyhow!("{} did not answer within {}s", method, RPC_TIMEOUT_SECS))?
+    .with_context(|| format!("{} unreachable", url))?;
+    let status = resp.status();
+    let bytes = body::to_bytes(resp.into_body()).await?;
+    if !status.is_success() {
+        bail!("{} returned HTTP {}", method, status);
+    }
+    let mut reply: Value = serde_json::from_slice(&bytes).context("RPC reply is not JSON")?;
+    if let Some(err) = reply.get("error") {
+        bail!("{} error: {}", method, err);
+    }
+    Ok(reply["result"].take())
+}
+
+async fn rpc_quantity(url: &str, method: &str, params: Value) -> Result<u128> {
+    let result = rpc(url, method, params).await?;
+    quantity(&result).ok_or_else(|| anyhow!("{} returned {}", method, result))
+}
+
+pub async fn balance(url: &str, address: &str) -> Result<u128> {
+    rpc_quantity(url, "eth_getBalance", json!([address, "latest"])).await
+}
+
+/// Next nonce, counting the sender's pending transactions.
+pub async fn pending_nonce(url: &str, address: &str) -> Result<u64> {
+    use std::convert::TryFrom;
+    let n = rpc_quantity(url, "eth_getTransactionCount", json!([address, "pending"])).await?;
+    u64::try_from(n).map_err(|_| anyhow!("nonce out of range"))
+}
+
+pub async fn gas_price(url: &str) -> Result<u128> {
+    rpc_quantity(url, "eth_gasPrice", json!([])).await
+}
+
+/// Broadcast a signed raw transaction; returns its hash.
+pub async fn send_raw(url: &str, raw_tx: &str) -> Result<String> {
+    let result = rpc(url, "eth_sendRawTransaction", json!([raw_tx])).await?;
+    result
+        .as_str()
+        .map(str::to_lowercase)
+        .ok_or_else(|| anyhow!("eth_sendRawTransaction returned {}", result))
+}
+
+/// A `gas_tanks` row as the API returns it, with its open refills.
+pub fn tank_json(tank: &GasTankRow, open: &[&GasTankTopUpRow]) -> Value {
+    json!({
+        "address": tank.address,
+        "chainId": tank.chain_id,
+        "label": tank.label,
+        "minBalanceWei": tank.min_balance_wei.to_string(),
+        "topUpWei": tank.top_up.as_ref().map(|t| t.amount_wei.to_string()),
+        "treasuryKeyId": tank.top_up.as_ref().map(|t| t.treasury_key_id.clone()),
+        "treasuryDerivationPath": tank.top_up.as_ref().map(|t| t.treasury_path.clone()),
+        "balanceWei": tank.balance_wei.map(|b| b.to_string()),
+        "checkedAt": tank.checked_at,
+        "low": tank.low_since.is_some(),
+        "lowSince": tank.low_since,
+        "openTopUps": open.iter().map(|t| topup_json(t)).collect::<Vec<_>>(),
+    })
+}
+
+/// A `gas_tank_topups` row as the API returns it.
+pub fn topup_json(row: &GasTankTopUpRow) -> Value {
+    json!({
+        "topUpId": row.id,
+        "address": row.address,
+        "chainId": row.chain_id,
+        "amountWei": row.source.amount_wei.to_string(),
+        "treasuryKeyId": row.source.treasury_key_id,
+        "treasuryDerivationPath": row.source.treasury_path,
+        "status": row.status,
+        "approvedBy": row.approved_by,
+        "transaction": row
+            .transaction
+            .as_deref()
+            .and_then(|t| serde_json::from_str::<Value>(t).ok()),
+        "transactionHash": row.tx_hash,
+        "createdAt": row.created_at,
+        "expiresAt": row.created_at + TOPUP_TTL_SECS,
+    })
+}
+
+/// keccak256 of a signed raw transaction — its hash on chain.
+pub fn raw_tx_hash(raw_tx: &str) -> Result<String> {
+    let raw = hex::decode(raw_tx.trim_start_matches("0x")).context("raw transaction")?;
+    Ok(format!("0x{}", hex::encode(Keccak256::digest(&raw))))
+}
+
+/// The alert envelope. Its correlation id names the tank and the moment it
+/// crossed, so webhook retries and the two edges of one episode line up.
+pub fn alert_envelope(
+    tank: &GasTankRow,
+    balance_wei: u128,
+    low: bool,
+    since: i64,
+    top_up_id: Option<i64>,
+    now: i64,
+) -> EventEnvelope<GasTankAlertPayload> {
+    EventEnvelope::new(
+        GasTankAlertPayload {
+            address: tank.address.clone(),
+            chain_id: tank.chain_id,
+            label: tank.label.clone(),
+            state: if low { "low" } else { "recovered" }.to_string(),
+            balance_wei: balance_wei.to_string(),
+            min_balance_wei: tank.min_balance_wei.to_string(),
+            top_up_id,
+        },
+        now,
+        format!("gas-tank-{}-{}-{}", tank.chain_id, tank.address, since),
+    )
+}
+
+/// Run forever: check every tank, then sleep `interval_secs`.
+pub async fn run(config: GasTankConfig, db: KmsDb) {
+    println!(
+        "⛽ Gas tanks: checking every {}s on chain(s) {:?}",
+        config.interval_secs,
+        config.rpc_urls.keys().collect::<Vec<_>>()
+    );
+    let mut tick = tokio::time::interval(Duration::from_secs(config.interval_secs));
+    loop {
+        tick.tick().await;
+        // Balances are the active's to record and act on.
+        if !standby::accepts_writes() {
+            continue;
+        }
+        if let Err(e) = check_all(&config, &db).await {
+            eprintln!("⚠️  Gas tanks: {:#}", e);
+        }
+    }
+}
+
+async fn check_all(config: &GasTankConfig, db: &KmsDb) -> Result<()> {
+    let now = now_unix();
+    let expired = db
+        .run(move |db| db.expire_gas_tank_topups(now - TOPUP_TTL_SECS))
+        .await?;
+    if expired > 0 {
+        println!(
+            "⛽ Gas tanks: {} refill proposal(s) expired unapproved",
+            expired
+        );
+    }
+    for tank in db.run(|db| db.list_gas_tanks()).await? {
+        if let Err(e) = check(config, db, &tank).await {
+            eprintln!(
+                "⚠️  Gas tank {} on chain {}: {:#}",
+                tank.address, tank.chain_id, e
+            );
+        }
+    }
+    Ok(())
+}
+
+async fn check(config: &GasTankConfig, db: &KmsDb, tank: &GasTankRow) -> Result<()> {
+    let balance_wei = balance(config.rpc_url(tank.chain_id)?, &tank.address).await?;
+    let now = now_unix();
+    let state = transition(tank.low_since, balance_wei, tank.min_balance_wei);
+    let low_since = match state {
+        Transition::BecameLow => Some(now),
+        Transition::StillLow => tank.low_since,
+        Transition::Healthy | Transition::Recovered => None,
+    };
+    let (address, chain_id) = (tank.address.clone(), tank.chain_id);
+    db.run(move |db| db.record_gas_tank_balance(&address, chain_id, balance_wei, now, low_since))
+        .await?;
+
+    let chain = tank.chain_id.to_string();
+    let attrs = [
+        ("chain.id", chain.as_str()),
+        ("address", tank.address.as_str()),
+    ];
+    crate::otel::set_gauge("kms.gas_tank.balance", &attrs, balance_wei as f64);
+    crate::otel::set_gauge("kms.gas_tank.low", &attrs, low_since.is_some() as u8 as f64);
+
+    let (since, top_up_id) = match state {
+        Transition::Healthy | Transition::StillLow => return Ok(()),
+        Transition::BecameLow => (now, propose(config, db, tank, now).await?),
+        Transition::Recovered => (tank.low_since.unwrap_or(now), None),
+    };
+    let low = state == Transition::BecameLow;
+    println!(
+        "⛽ Gas tank {} ({}) on chain {}: {} — balance {} wei, floor {} wei{}",
+        tank.address,
+        tank.label,
+        tank.chain_id,
+        if low { "LOW" } else { "recovered" },
+        balance_wei,
+        tank.min_balance_wei,
+        top_up_id
+            .map(|id| format!(", refill #{} awaits approval", id))
+            .unwrap_or_default()
+    );
+    if let (Some(url), Some(secret)) = (&config.webhook_url, &config.webhook_secret) {
+        let envelope = alert_envelope(tank, balance_wei, low, since, top_up_id, now);
+        let body = serde_json::to_string(&envelope)?;
+        let (url, secret) = (url.clone(), secret.clone());
+        let what = format!("gas tank {}", tank.address);
+        tokio::spawn(
+            async move { crate::chain_watch::post_signed(&url, &secret, body, &what).await },
+        );
+    }
+    Ok(())
+}
+
+/// Open a refill proposal for a tank that just went low, if it has a source
+/// and someone can approve it.
+async fn propose(
+    config: &GasTankConfig,
+    db: &KmsDb,
+    tank: &GasTankRow,
+    now: i64,
+) -> Result<Option<i64>> {
+    let source = match &tank.top_up {
+        Some(s) if !config.approvers.is_empty() => s.clone(),
+        _ => return Ok(None),
+    };
+    let (address, chain_id) = (tank.address.clone(), tank.chain_id);
+    db.run(move |db| db.propose_gas_tank_topup(&address, chain_id, &source, now))
+        .await
+}
+
+fn now_unix() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_needs_http_endpoints_per_chain() {
+        let c = GasTankConfig::parse(
+            "1=http://eth:8545, 8453=http://base:8545",
+            None,
+            Some("ops-a, ops-b,"),
+            None,
+            None,
+        )
+        .unwrap();
+        assert_eq!(c.rpc_url(8453).unwrap(), "http://base:8545");
+        assert!(c.rpc_url(10).is_err());
+        assert_eq!(c.interval_secs, 60);
+        assert_eq!(c.approvers, vec!["ops-a", "ops-b"]);
+
+        for (rpc, interval) in [
+            ("1=https://eth", None),
+            ("eth=http://eth", None),
+            ("0=http://eth", None),
+            ("1=http://a,1=http://b", None),
+            ("", None),
+            ("1=http://eth", Some("5")),
+        ] {
+            assert!(GasTankConfig::parse(rpc, interval, None, None, None).is_err());
+        }
+        assert!(
+            GasTankConfig::parse("1=http://eth", None, None, Some("http://hook".into()), None)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn crossing_the_floor_is_reported_once_each_way() {
+        assert_eq!(transition(None, 10, 5), Transition::Healthy);
+        assert_eq!(transition(None, 4, 5), Transition::BecameLow);
+        assert_eq!(transition(Some(1), 4, 5), Transition::StillLow);
+        assert_eq!(transition(Some(1), 5, 5), Transition::Recovered);
+    }
+
+    #[test]
+    fn quantities_parse_and_saturate() {
+        assert_eq!(quantity(&json!("0x0")), Some(0));
+        assert_eq!(quantity(&json!("0xde0b6b3a7640000")), Some(10u128.pow(18)));
+        assert_eq!(
+            quantity(&json!(format!("0x{}", "f".repeat(40)))),
+            Some(u128::MAX)
+        );
+        assert_eq!(quantity(&json!("0x")), None);
+        assert_eq!(quantity(&json!("12")), None);
+        assert_eq!(quantity(&json!(12)), None);
+    }
+}
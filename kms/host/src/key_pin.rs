@@ -0,0 +1,183 @@
+//! Key pinning — detect a TA that starts answering with a different key.
+//!
+//! The first address the TA derives for a (key_id, path) is pinned in
+//! `address_index`. Every later derivation must return the same address, and
+//! every signature the TA hands back must recover to it. A mismatch means the
+//! TA's secure storage was replaced or rolled back, or the CA is talking to
+//! the wrong device; the CA fails the request and writes a `key_substitution`
+//! audit event instead of passing the new key on. Pure functions here; the
+//! DB side is `KmsDb::pin_address` and the wiring is in service.rs.
+//!
+//! Checked: DeriveAddress, background derivation, Sign (transaction and
+//! message) and SignHash. SignTypedData is not re-checked — the CA has no
+//! EIP-712 encoder to rebuild the digest the TA signed.
+
+use anyhow::{anyhow, Result};
+use k256::ecdsa::{RecoveryId, Signature, VerifyingKey};
+use proto::eip1559::{self, EIP1559_TX_TYPE};
+use proto::offline::{decode_signed_legacy, legacy_signing_hash};
+use proto::SigningContext;
+use sha3::{Digest, Keccak256};
+
+/// Audit event name recorded against the wallet on a mismatch.
+pub const KEY_SUBSTITUTION_EVENT: &str = "key_substitution";
+
+/// Outcome of pinning a freshly derived address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PinCheck {
+    /// First derivation for this (key_id, path); now pinned.
+    Pinned,
+    /// Same address as the pinned one.
+    Matches,
+    /// The TA returned something other than the pinned address.
+    Mismatch { pinned: String },
+    /// The address is already pinned to a different (key_id, path).
+    Conflict {
+        key_id: String,
+        derivation_path: String,
+    },
+}
+
+pub fn address_hex(address: &[u8; 20]) -> String {
+    format!("0x{}", hex::encode(address))
+}
+
+/// Signer of `hash` from a 65-byte r ‖ s ‖ v signature (v = 0/1 or 27/28).
+pub fn recover_signer(hash: &[u8; 32], signature: &[u8]) -> Result<[u8; 20]> {
+    if signature.len() != 65 {
+        return Err(anyhow!(
+            "expected 65-byte signature, got {}",
+            signature.len()
+        ));
+    }
+    let v = match signature[64] {
+        v @ 0..=1 => v,
+        v @ 27..=28 => v - 27,
+        v => return Err(anyhow!("bad signature v: {}", v)),
+    };
+    recover(hash, &signature[..64], v)
+}
+
+/// Signer of an EIP-155 signed legacy tx, as returned by SignTransaction,
+/// or of an EIP-1559 one, as returned by SignEip1559Transaction.
+pub fn signed_tx_signer(raw: &[u8]) -> Result<[u8; 20]> {
+    if raw.first() == Some(&EIP1559_TX_TYPE) {
+        let signed =
+            eip1559::decode_signed(raw).map_err(|e| anyhow!("signed transaction: {}", e))?;
+        let mut rs = [0u8; 64];
+        rs[..32].copy_from_slice(&signed.r);
+        rs[32..].copy_from_slice(&signed.s);
+        return recover(&signed.transaction.signing_hash(), &rs, signed.y_parity);
+    }
+    let signed = decode_signed_legacy(raw).map_err(|e| anyhow!("signed transaction: {}", e))?;
+    let mut rs = [0u8; 64];
+    rs[..32].copy_from_slice(&signed.r);
+    rs[32..].copy_from_slice(&signed.s);
+    recover(
+        &legacy_signing_hash(&signed.transaction),
+        &rs,
+        signed.recovery_id,
+    )
+}
+
+fn recover(hash: &[u8; 32], rs: &[u8], recovery_id: u8) -> Result<[u8; 20]> {
+    let sig = Signature::from_slice(rs).map_err(|e| anyhow!("signature: {}", e))?;
+    let recid =
+        RecoveryId::from_byte(recovery_id).ok_or_else(|| anyhow!("signature: bad recovery id"))?;
+    let key = VerifyingKey::recover_from_prehash(hash, &sig, recid)
+        .map_err(|e| anyhow!("signature does not recover: {}", e))?;
+    let point = key.to_encoded_point(false);
+    let mut address = [0u8; 20];
+    address.copy_from_slice(&Keccak256::digest(&point.as_bytes()[1..])[12..]);
+    Ok(address)
+}
+
+/// The digest SignMessage signs for `context` — mirrors the TA's
+/// `signing_context::message_digest`. None for contexts SignMessage refuses.
+pub fn message_digest(context: &SigningContext, message: &[u8]) -> Option<[u8; 32]> {
+    match context {
+        SigningContext::Raw => Some(Keccak256::digest(message).into()),
+        SigningContext::PersonalMsg | SigningContext::Login => {
+            let mut h = Keccak256::new();
+            h.update(format!("\x19Ethereum Signed Message:\n{}", message.len()).as_bytes());
+            h.update(message);
+            Some(h.finalize().into())
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // EIP-155 worked example, key 0x4646…46.
+    const SIGNED: &str = "f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83";
+    const SIGNER: &str = "0x9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f";
+
+    #[test]
+    fn recovers_tx_and_hash_signers() {
+        let raw = hex::decode(SIGNED).unwrap();
+        assert_eq!(address_hex(&signed_tx_signer(&raw).unwrap()), SIGNER);
+
+        // same r ‖ s over the signing hash, as a SignHash-style 65-byte signature
+        let signed = decode_signed_legacy(&raw).unwrap();
+        let hash = legacy_signing_hash(&signed.transaction);
+        let mut sig = Vec::with_capacity(65);
+        sig.extend_from_slice(&signed.r);
+        sig.extend_from_slice(&signed.s);
+        sig.push(27 + signed.recovery_id);
+        assert_eq!(address_hex(&recover_signer(&hash, &sig).unwrap()), SIGNER);
+        // flipping v recovers some other key, never the pinned one
+        sig[64] ^= 1;
+        assert_ne!(
+            recover_signer(&hash, &sig).ok().map(|a| address_hex(&a)),
+            Some(SIGNER.to_string())
+        );
+        assert!(recover_signer(&hash, &sig[..64]).is_err());
+    }
+
+    #[test]
+    fn recovers_eip1559_tx_signer() {
+        let tx = eip1559::Eip1559Transaction {
+            chain_id: 1,
+            nonce: 9,
+            max_priority_fee_per_gas: 1_500_000_000,
+            max_fee_per_gas: 40_000_000_000,
+            gas: 21_000,
+            to: Some([0x35; 20]),
+            value: 1_000_000_000_000_000_000,
+            data: vec![],
+            access_list: vec![],
+        };
+        let key = k256::ecdsa::SigningKey::from_slice(&[0x46; 32]).unwrap();
+        let (signature, recid) = key.sign_prehash_recoverable(&tx.signing_hash()).unwrap();
+        let bytes = signature.to_bytes();
+        let mut signed = eip1559::SignedEip1559Tx {
+            transaction: tx,
+            y_parity: recid.to_byte(),
+            r: [0; 32],
+            s: [0; 32],
+        };
+        signed.r.copy_from_slice(&bytes[..32]);
+        signed.s.copy_from_slice(&bytes[32..]);
+        let raw = eip1559::signed_rlp(&signed);
+        assert_eq!(address_hex(&signed_tx_signer(&raw).unwrap()), SIGNER);
+        signed.transaction.nonce += 1;
+        let tampered = eip1559::signed_rlp(&signed);
+        assert_ne!(
+            signed_tx_signer(&tampered).ok().map(|a| address_hex(&a)),
+            Some(SIGNER.to_string())
+        );
+    }
+
+    #[test]
+    fn personal_message_digest_matches_eip191() {
+        // keccak256("\x19Ethereum Signed Message:\n5hello")
+        assert_eq!(
+            hex::encode(message_digest(&SigningContext::PersonalMsg, b"hello").unwrap()),
+            "50b2c43fd39106bafbba0da34fc430e1f91e3c96ea2acee2bc34119f92b37750"
+        );
+        assert!(message_digest(&SigningContext::EthTx, b"hello").is_none());
+    }
+}
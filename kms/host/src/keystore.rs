@@ -0,0 +1,307 @@
+//! Passphrase wallet backups as Web3 Secret Storage v3 files.
+//!
+//! The TA seals and opens the `crypto` section (`proto::kdf::Keystore`,
+//! `ExportKeystore` / `ImportKeystore`); this module only maps it to and
+//! from the JSON file. The secret is the wallet's BIP39 entropy, not a
+//! private key, so files carry `"airaccount": "wallet-entropy-v1"` and a
+//! plain Ethereum keystore is refused here: importing one would silently
+//! create an unrelated wallet. `"kdf": "argon2id"` is our extension; pbkdf2
+//! and scrypt files use the spec's field names.
+//!
+//! Encrypted backups (`proto::backup`, `ExportEncryptedBackup`) reuse the
+//! `crypto` section next to their header fields, tagged [`BACKUP_KIND`]. Their
+//! keystore passphrase is sealed to the device, so they are not v3 files.
+
+use anyhow::{anyhow, Result};
+use proto::backup::{BackupHeader, EncryptedBackup};
+use proto::kdf::{KdfConfig, Keystore, DERIVED_KEY_LEN};
+use serde_json::{json, Value};
+use std::convert::TryInto;
+
+pub const KIND: &str = "wallet-entropy-v1";
+pub const BACKUP_KIND: &str = "encrypted-backup-v1";
+
+pub fn to_json(keystore: &Keystore) -> Value {
+    json!({
+        "version": 3,
+        "id": uuid::Uuid::new_v4().to_string(),
+        "airaccount": KIND,
+        "crypto": crypto_to_json(keystore),
+    })
+}
+
+fn crypto_to_json(keystore: &Keystore) -> Value {
+    let salt = hex::encode(&keystore.salt);
+    let kdfparams = match keystore.kdf {
+        KdfConfig::Pbkdf2Sha256 { iterations } => json!({
+            "c": iterations,
+            "dklen": DERIVED_KEY_LEN,
+            "prf": "hmac-sha256",
+            "salt": salt,
+        }),
+        KdfConfig::Scrypt { log_n, r, p } => json!({
+            "n": 1u64 << log_n,
+            "r": r,
+            "p": p,
+            "dklen": DERIVED_KEY_LEN,
+            "salt": salt,
+        }),
+        KdfConfig::Argon2id {
+            memory_kib,
+            iterations,
+            parallelism,
+        } => json!({
+            "m": memory_kib,
+            "t": iterations,
+            "p": parallelism,
+            "dklen": DERIVED_KEY_LEN,
+            "salt": salt,
+        }),
+    };
+    json!({
+        "cipher": "aes-128-ctr",
+        "cipherparams": { "iv": hex::encode(keystore.iv) },
+        "ciphertext": hex::encode(&keystore.ciphertext),
+        "kdf": keystore.kdf.name(),
+        "kdfparams": kdfparams,
+        "mac": hex::encode(keystore.mac),
+    })
+}
+
+pub fn from_json(file: &Value) -> Result<Keystore> {
+    if file["version"].as_u64() != Some(3) {
+        return Err(anyhow!("keystore must be version 3"));
+    }
+    if file["airaccount"].as_str() != Some(KIND) {
+        return Err(anyhow!(
+            "not an AirAccount wallet backup (a plain Ethereum keystore holds a private key, \
+             not wallet entropy)"
+        ));
+    }
+    // geth wrote "Crypto" before the spec settled on "crypto".
+    let crypto = match (&file["crypto"], &file["Crypto"]) {
+        (Value::Object(_), _) => &file["crypto"],
+        (_, Value::Object(_)) => &file["Crypto"],
+        _ => return Err(anyhow!("keystore has no crypto section")),
+    };
+    crypto_from_json(crypto)
+}
+
+fn crypto_from_json(crypto: &Value) -> Result<Keystore> {
+    if crypto["cipher"].as_str() != Some("aes-128-ctr") {
+        return Err(anyhow!("keystore cipher must be aes-128-ctr"));
+    }
+    let params = &crypto["kdfparams"];
+    if params["dklen"].as_u64() != Some(DERIVED_KEY_LEN as u64) {
+        return Err(anyhow!("keystore dklen must be {}", DERIVED_KEY_LEN));
+    }
+    let param = |name: &str| -> Result<u32> {
+        params[name]
+            .as_u64()
+            .and_then(|v| v.try_into().ok())
+            .ok_or_else(|| anyhow!("kdfparams.{} missing or out of range", name))
+    };
+    let kdf = match crypto["kdf"].as_str() {
+        Some("pbkdf2") => {
+            if params["prf"].as_str() != Some("hmac-sha256") {
+                return Err(anyhow!("pbkdf2 prf must be hmac-sha256"));
+            }
+            KdfConfig::Pbkdf2Sha256 {
+                iterations: param("c")?,
+            }
+        }
+        Some("scrypt") => {
+            let n = param("n")?;
+            if !n.is_power_of_two() {
+                return Err(anyhow!("scrypt n must be a power of two"));
+            }
+            KdfConfig::Scrypt {
+                log_n: n.trailing_zeros() as u8,
+                r: param("r")?,
+                p: param("p")?,
+            }
+        }
+        Some("argon2id") => KdfConfig::Argon2id {
+            memory_kib: param("m")?,
+            iterations: param("t")?,
+            parallelism: param("p")?,
+        },
+        other => return Err(anyhow!("unsupported keystore kdf {:?}", other)),
+    };
+    let bytes = |v: &Value, what: &str| -> Result<Vec<u8>> {
+        v.as_str()
+            .and_then(|s| hex::decode(s.trim_start_matches("0x")).ok())
+            .ok_or_else(|| anyhow!("keystore {} must be hex", what))
+    };
+    let keystore = Keystore {
+        kdf,
+        salt: bytes(&params["salt"], "salt")?,
+        iv: bytes(&crypto["cipherparams"]["iv"], "iv")?
+            .try_into()
+            .map_err(|_| anyhow!("keystore iv must be 16 bytes"))?,
+        ciphertext: bytes(&crypto["ciphertext"], "ciphertext")?,
+        mac: bytes(&crypto["mac"], "mac")?
+            .try_into()
+            .map_err(|_| anyhow!("keystore mac must be 32 bytes"))?,
+    };
+    keystore.validate().map_err(|e| anyhow!("{}", e))?;
+    Ok(keystore)
+}
+
+pub fn backup_to_json(backup: &EncryptedBackup) -> Value {
+    let h = &backup.header;
+    json!({
+        "version": h.version,
+        "airaccount": BACKUP_KIND,
+        "walletId": h.wallet_id.to_string(),
+        "passkeyPublicKey": hex::encode(&h.passkey_pubkey),
+        "nextAddressIndex": h.next_address_index,
+        "nextAccountIndex": h.next_account_index,
+        "createdAt": h.created_at,
+        "device": hex::encode(h.device),
+        "crypto": crypto_to_json(&backup.keystore),
+    })
+}
+
+pub fn backup_from_json(file: &Value) -> Result<EncryptedBackup> {
+    if file["airaccount"].as_str() != Some(BACKUP_KIND) {
+        return Err(anyhow!("not an AirAccount encrypted backup"));
+    }
+    let bytes = |name: &str| -> Result<Vec<u8>> {
+        file[name]
+            .as_str()
+            .and_then(|s| hex::decode(s.trim_start_matches("0x")).ok())
+            .ok_or_else(|| anyhow!("backup {} must be hex", name))
+    };
+    let number = |name: &str| -> Result<u64> {
+        file[name]
+            .as_u64()
+            .ok_or_else(|| anyhow!("backup {} missing", name))
+    };
+    let index = |name: &str| -> Result<u32> {
+        number(name)?
+            .try_into()
+            .map_err(|_| anyhow!("backup {} out of range", name))
+    };
+    let backup = EncryptedBackup {
+        header: BackupHeader {
+            version: number("version")?
+                .try_into()
+                .map_err(|_| anyhow!("unsupported backup version"))?,
+            wallet_id: file["walletId"]
+                .as_str()
+                .and_then(|s| uuid::Uuid::parse_str(s).ok())
+                .ok_or_else(|| anyhow!("backup walletId must be a UUID"))?,
+            passkey_pubkey: bytes("passkeyPublicKey")?,
+            next_address_index: index("nextAddressIndex")?,
+            next_account_index: index("nextAccountIndex")?,
+            created_at: number("createdAt")?,
+            device: bytes("device")?
+                .try_into()
+                .map_err(|_| anyhow!("backup device must be 32 bytes"))?,
+        },
+        keystore: crypto_from_json(&file["crypto"])?,
+    };
+    backup.validate().map_err(|e| anyhow!("{}", e))?;
+    Ok(backup)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(kdf: KdfConfig) -> Keystore {
+        Keystore {
+            kdf,
+            salt: vec![0x11; 32],
+            iv: [0x22; 16],
+            ciphertext: vec![0x33; 32],
+            mac: [0x44; 32],
+        }
+    }
+
+    #[test]
+    fn json_roundtrip_per_kdf() {
+        for kdf in [
+            KdfConfig::default(),
+            KdfConfig::Scrypt {
+                log_n: 12,
+                r: 1,
+                p: 8,
+            },
+            KdfConfig::Pbkdf2Sha256 {
+                iterations: 262_144,
+            },
+        ] {
+            let ks = sample(kdf);
+            let file = to_json(&ks);
+            assert_eq!(file["crypto"]["kdf"], kdf.name());
+            assert_eq!(from_json(&file).unwrap(), ks);
+        }
+        let mut weak = to_json(&sample(KdfConfig::default()));
+        weak["crypto"]["kdfparams"]["m"] = json!(65536);
+        assert!(from_json(&weak).is_err(), "over the TEE memory budget");
+    }
+
+    #[test]
+    fn backup_json_roundtrip() {
+        let backup = EncryptedBackup {
+            header: BackupHeader {
+                version: proto::backup::BACKUP_VERSION,
+                wallet_id: uuid::Uuid::from_bytes([7; 16]),
+                passkey_pubkey: [vec![0x04], vec![0x55; 64]].concat(),
+                next_address_index: 3,
+                next_account_index: 1,
+                created_at: 1_700_000_000,
+                device: [0x66; 32],
+            },
+            keystore: sample(KdfConfig::default()),
+        };
+        let file = backup_to_json(&backup);
+        assert_eq!(file["walletId"], "07070707-0707-0707-0707-070707070707");
+        assert_eq!(backup_from_json(&file).unwrap(), backup);
+        // Neither file passes for the other.
+        assert!(from_json(&file).is_err());
+        assert!(backup_from_json(&to_json(&backup.keystore)).is_err());
+        let mut scrypt = file.clone();
+        scrypt["crypto"] = crypto_to_json(&sample(KdfConfig::Scrypt {
+            log_n: 12,
+            r: 1,
+            p: 8,
+        }));
+        assert!(backup_from_json(&scrypt).is_err(), "argon2id only");
+        let mut short = file;
+        short["device"] = json!("66");
+        assert!(backup_from_json(&short).is_err());
+    }
+
+    // Web3 Secret Storage v3 test vector: the field mapping is the spec's,
+    // but a file without our tag is refused.
+    #[test]
+    fn spec_pbkdf2_file_maps_but_needs_the_tag() {
+        let mut file = json!({
+            "version": 3,
+            "id": "3198bc9c-6672-5ab3-d995-4942343ae5b6",
+            "crypto": {
+                "cipher": "aes-128-ctr",
+                "cipherparams": { "iv": "6087dab2f9fdbbfaddc31a909735c1e6" },
+                "ciphertext": "5318b4d5bcd28de64ee5559e671353e16f075ecae9f99c7a79a38af5f869aa46",
+                "kdf": "pbkdf2",
+                "kdfparams": {
+                    "c": 262144,
+                    "dklen": 32,
+                    "prf": "hmac-sha256",
+                    "salt": "ae3cd4e7013836a3df6bd7241b12db061dbe2c6785853cce422d148a624ce0bd"
+                },
+                "mac": "517ead924a9d0dc3124507e3393d175ce3ff7c1e96529c6c555ce9e51205e9b2"
+            }
+        });
+        assert!(from_json(&file).is_err());
+        file["airaccount"] = json!(KIND);
+        let keystore = from_json(&file).unwrap();
+        assert_eq!(
+            hex::encode(keystore.open(b"testpassword").unwrap()),
+            "7a28b5ba57c53603b0b07b56bba752f7784bf506fa95edc395f5cf6c7514fe9d"
+        );
+    }
+}
@@ -20,15 +20,60 @@
 //! KMS Host Library
 //! Shared modules for CLI and API server
 
+pub mod accounting_export;
+pub mod aggregator;
 pub mod address_cache;
 pub mod agent_jwt;
+pub mod api_error;
+pub mod chain_watch;
 pub mod cli;
+pub mod compliance;
+pub mod config_manager;
+pub mod conformance;
 pub mod db;
+pub mod device_health;
+pub mod event_store;
+pub mod fido_mds;
+pub mod gas_tank;
+pub mod key_pin;
+pub mod keystore;
+pub mod log_level;
+pub mod migration;
+pub mod node_backup;
+pub mod notify;
+pub mod offline;
+pub mod otel;
+pub mod passkey_lockout;
+pub mod paymaster;
+pub mod permit;
+pub mod pkcs11;
+pub mod provisioning;
 pub mod rate_limit;
-#[cfg(feature = "tee")]
+pub mod remote_approval;
+pub mod replication;
+pub mod response_cache;
+pub mod risk;
+#[cfg(any(feature = "tee", feature = "soft-tee"))]
+pub mod service;
+pub mod signer;
+pub mod siwe;
+pub mod standby;
+pub mod stealth;
+#[cfg(feature = "soft-tee")]
+pub mod soft_tee;
+#[cfg(any(feature = "tee", feature = "soft-tee"))]
 pub mod ta_client;
-#[cfg(feature = "tee")]
+pub mod ta_config;
+pub mod ta_release;
+pub mod tamper;
+#[cfg(any(feature = "tee", feature = "soft-tee"))]
 pub mod tests;
+#[cfg(feature = "threshold-ecdsa")]
+pub mod threshold;
+pub mod token_transfer;
+pub mod tx_rescue;
+pub mod usage;
+pub mod verify;
 pub mod webauthn;
 
 // Re-export commonly used items
@@ -36,5 +81,5 @@ pub use address_cache::{
     load_address_map, lookup_address, save_address_map, update_address_entry, AddressMap,
     AddressMetadata,
 };
-#[cfg(feature = "tee")]
+#[cfg(any(feature = "tee", feature = "soft-tee"))]
 pub use ta_client::{create_wallet, derive_address, sign_transaction, TaClient, TeeHandle};
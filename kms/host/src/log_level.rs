@@ -0,0 +1,123 @@
+//! TA log levels — the JSON form of a signed log-level order.
+//!
+//! Accepted by `POST /kms/ta/log-level`. `levels` maps a category (`crypto`,
+//! `storage`, `session`, `policy`) to `off`, `warn`, `info` or `debug`; a
+//! category left out stays at `info`. Turning on `debug` needs `until`,
+//! TA-clock seconds at most seven days ahead. As with tamper orders, the TA
+//! checks the signature; this side builds the order and, without a
+//! signature, returns the text to sign.
+
+use anyhow::{anyhow, Context, Result};
+use proto::log_level::{LogCategory, LogLevelOrder, LogLevels, LOG_ORDER_FORMAT};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogLevelOrderRequest {
+    pub deployment: String,
+    pub sequence: u64,
+    /// 0x-hex attestation-key fingerprint of one device; absent = every
+    /// device of the deployment.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device: Option<String>,
+    #[serde(default)]
+    pub levels: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<u64>,
+    /// 0x-hex personal_sign signature over the order text.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+impl LogLevelOrderRequest {
+    pub fn order(&self) -> Result<LogLevelOrder> {
+        let device = match &self.device {
+            Some(fp) => {
+                let bytes = hex::decode(fp.trim_start_matches("0x"))
+                    .context("device fingerprint is not hex")?;
+                Some(
+                    <[u8; 32]>::try_from(bytes.as_slice())
+                        .map_err(|_| anyhow!("device fingerprint must be 32 bytes"))?,
+                )
+            }
+            None => None,
+        };
+        let mut levels = LogLevels::DEFAULT;
+        for (category, level) in &self.levels {
+            let category: LogCategory = category.parse().map_err(|e| anyhow!("{}", e))?;
+            levels.set(category, level.parse().map_err(|e| anyhow!("{}", e))?);
+        }
+        let order = LogLevelOrder {
+            format: LOG_ORDER_FORMAT,
+            deployment: self.deployment.clone(),
+            device,
+            sequence: self.sequence,
+            levels,
+            until: self.until,
+        };
+        order.validate().map_err(|e| anyhow!("{}", e))?;
+        Ok(order)
+    }
+
+    /// The signature bytes, or an error carrying the text to sign.
+    pub fn signature(&self, order: &LogLevelOrder) -> Result<Vec<u8>> {
+        match &self.signature {
+            Some(sig) => hex::decode(sig.trim_start_matches("0x")).context("signature is not hex"),
+            None => Err(anyhow!(
+                "log-level order is unsigned; personal_sign exactly this text:\n{}",
+                order.message()
+            )),
+        }
+    }
+}
+
+/// `{"crypto": "info", …}`, as the API reports levels.
+pub fn levels_map(levels: &LogLevels) -> BTreeMap<String, String> {
+    LogCategory::ALL
+        .iter()
+        .map(|c| (c.name().to_string(), levels.get(*c).name().to_string()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proto::log_level::LogLevel;
+
+    fn request(json: &str) -> LogLevelOrderRequest {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn order_from_json() {
+        let req = request(
+            r#"{"deployment":"rack-3","sequence":4,"levels":{"storage":"debug","policy":"off"},"until":1700003600}"#,
+        );
+        let order = req.order().unwrap();
+        assert_eq!(order.levels.storage, LogLevel::Debug);
+        assert_eq!(order.levels.policy, LogLevel::Off);
+        assert_eq!(order.levels.crypto, LogLevel::Info);
+        let unsigned = req.signature(&order).unwrap_err().to_string();
+        assert!(unsigned.ends_with(&order.message()));
+        assert_eq!(levels_map(&order.levels)["storage"], "debug");
+
+        // debug without an expiry, unknown names
+        assert!(
+            request(r#"{"deployment":"rack-3","sequence":5,"levels":{"crypto":"debug"}}"#)
+                .order()
+                .is_err()
+        );
+        assert!(
+            request(r#"{"deployment":"rack-3","sequence":5,"levels":{"network":"info"}}"#)
+                .order()
+                .is_err()
+        );
+        assert!(
+            request(r#"{"deployment":"rack-3","sequence":5,"levels":{"crypto":"trace"}}"#)
+                .order()
+                .is_err()
+        );
+    }
+}
@@ -15,11 +15,34 @@
 // specific language governing permissions and limitations
 // under the License.
 
-use kms::{cli, create_wallet, derive_address, sign_transaction, tests};
+use kms::{cli, create_wallet, derive_address, keystore, sign_transaction, tests, TaClient};
 
 use anyhow::{bail, Result};
+use std::path::Path;
 use structopt::StructOpt;
 
+fn read_password(path: &Path) -> Result<String> {
+    let text = std::fs::read_to_string(path)?;
+    let password = text.lines().next().unwrap_or("").to_string();
+    if password.is_empty() {
+        bail!("password file is empty");
+    }
+    Ok(password)
+}
+
+/// The backup is only as strong as its password, so keep it 0600.
+fn write_private(path: &Path, data: &[u8]) -> Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)?;
+    file.write_all(data)?;
+    Ok(())
+}
+
 fn main() -> Result<()> {
     let args = cli::Opt::from_args();
     match args.command {
@@ -35,6 +58,12 @@ fn main() -> Result<()> {
             let address = derive_address(opt.wallet_id, &opt.hd_path, None)?;
             println!("Address: 0x{}", hex::encode(&address));
         }
+        cli::Command::DeriveSolanaAddress(opt) => {
+            let mut ta_client = TaClient::new()?;
+            let derived = ta_client.derive_solana_address(opt.wallet_id, &opt.hd_path, None)?;
+            println!("Address: {}", derived.address);
+            println!("Public key: {}", hex::encode(derived.public_key));
+        }
         cli::Command::SignTransaction(opt) => {
             let signature = sign_transaction(
                 opt.wallet_id,
@@ -48,6 +77,28 @@ fn main() -> Result<()> {
             )?;
             println!("Signature: {}", hex::encode(&signature));
         }
+        cli::Command::Backup(opt) => {
+            let password = read_password(&opt.password_file)?;
+            let mut ta_client = TaClient::new()?;
+            let backup =
+                ta_client.export_encrypted_backup(opt.wallet_id, &password, opt.kdf, None)?;
+            let file = serde_json::to_string_pretty(&keystore::backup_to_json(&backup))?;
+            write_private(&opt.out, file.as_bytes())?;
+            println!(
+                "Backup of {} written to {}",
+                opt.wallet_id,
+                opt.out.display()
+            );
+        }
+        cli::Command::Restore(opt) => {
+            let password = read_password(&opt.password_file)?;
+            let file: serde_json::Value = serde_json::from_slice(&std::fs::read(&opt.backup)?)?;
+            let backup = keystore::backup_from_json(&file)?;
+            let mut ta_client = TaClient::new()?;
+            let restored = ta_client.import_encrypted_backup(backup, &password)?;
+            println!("Wallet ID: {}", restored.wallet_id);
+            println!("Address: 0x{}", hex::encode(restored.address));
+        }
         cli::Command::Test => {
             tests::tests::test_workflow();
             println!("Tests passed");
@@ -0,0 +1,232 @@
+//! Hardware-wallet migration — the CA half.
+//!
+//! The user exports the account xpub (`m/44'/60'/0'`) from a Ledger or
+//! Trezor. The CA decodes it and derives the first receive addresses
+//! (`m/44'/60'/0'/0/i`) with public derivation, so the user can compare them
+//! with the device before the recovery phrase moves at all. The phrase itself
+//! is encrypted by the client to the TA's ephemeral key
+//! (`proto::migration`); the CA never sees it. Pure functions here; the
+//! endpoints are in api_server.rs.
+
+use anyhow::{anyhow, Result};
+use hmac::{Hmac, Mac};
+use k256::elliptic_curve::sec1::ToEncodedPoint;
+use k256::{ProjectivePoint, PublicKey, SecretKey};
+use proto::migration::AccountXpub;
+use sha2::{Sha256, Sha512};
+use sha3::{Digest, Keccak256};
+
+/// Mainnet `xpub` version bytes; Ethereum apps export the account node with it.
+const XPUB_VERSION: [u8; 4] = [0x04, 0x88, 0xb2, 0x1e];
+
+/// Receive addresses shown for comparison when the request does not say.
+pub const DEFAULT_ADDRESS_COUNT: u32 = 5;
+
+const BASE58: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// A BIP32 extended public key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtendedPublicKey {
+    pub depth: u8,
+    pub child_number: u32,
+    /// Compressed SEC1 point.
+    pub public_key: [u8; 33],
+    pub chain_code: [u8; 32],
+}
+
+fn base58check_decode(text: &str) -> Result<Vec<u8>> {
+    let mut bytes: Vec<u8> = Vec::new();
+    for c in text.bytes() {
+        let mut carry = BASE58
+            .iter()
+            .position(|&b| b == c)
+            .ok_or_else(|| anyhow!("xpub is not base58"))? as u32;
+        for b in bytes.iter_mut().rev() {
+            carry += (*b as u32) * 58;
+            *b = carry as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.insert(0, carry as u8);
+            carry >>= 8;
+        }
+    }
+    let zeros = text.bytes().take_while(|&c| c == b'1').count();
+    let mut out = vec![0u8; zeros];
+    out.extend_from_slice(&bytes);
+    if out.len() < 4 {
+        return Err(anyhow!("xpub is too short"));
+    }
+    let (payload, checksum) = out.split_at(out.len() - 4);
+    if Sha256::digest(Sha256::digest(payload))[..4] != *checksum {
+        return Err(anyhow!("xpub checksum mismatch (mistyped or truncated?)"));
+    }
+    Ok(payload.to_vec())
+}
+
+impl ExtendedPublicKey {
+    /// Decode a base58check `xpub…` string.
+    pub fn decode(text: &str) -> Result<Self> {
+        let payload = base58check_decode(text.trim())?;
+        if payload.len() != 78 {
+            return Err(anyhow!("xpub must be 78 bytes, got {}", payload.len()));
+        }
+        if payload[..4] != XPUB_VERSION {
+            return Err(anyhow!(
+                "only mainnet xpub keys are accepted (version 0x{})",
+                hex::encode(&payload[..4])
+            ));
+        }
+        let mut chain_code = [0u8; 32];
+        chain_code.copy_from_slice(&payload[13..45]);
+        let mut public_key = [0u8; 33];
+        public_key.copy_from_slice(&payload[45..78]);
+        PublicKey::from_sec1_bytes(&public_key)
+            .map_err(|_| anyhow!("xpub key is not a compressed secp256k1 point"))?;
+        if public_key[0] != 0x02 && public_key[0] != 0x03 {
+            return Err(anyhow!("xpub key is not a compressed secp256k1 point"));
+        }
+        Ok(Self {
+            depth: payload[4],
+            child_number: u32::from_be_bytes([payload[9], payload[10], payload[11], payload[12]]),
+            public_key,
+            chain_code,
+        })
+    }
+
+    /// Non-hardened child (CKDpub).
+    pub fn child(&self, index: u32) -> Result<Self> {
+        if index >= 0x8000_0000 {
+            return Err(anyhow!("hardened children cannot be derived from an xpub"));
+        }
+        let mut mac =
+            Hmac::<Sha512>::new_from_slice(&self.chain_code).expect("HMAC accepts any key length");
+        mac.update(&self.public_key);
+        mac.update(&index.to_be_bytes());
+        let i = mac.finalize().into_bytes();
+        let tweak = SecretKey::from_slice(&i[..32])
+            .map_err(|_| anyhow!("BIP32: invalid child {}", index))?;
+        let parent = PublicKey::from_sec1_bytes(&self.public_key)
+            .map_err(|_| anyhow!("xpub key is not a secp256k1 point"))?;
+        let point =
+            ProjectivePoint::GENERATOR * *tweak.to_nonzero_scalar() + parent.to_projective();
+        let child = PublicKey::from_affine(point.to_affine())
+            .map_err(|_| anyhow!("BIP32: child {} is the point at infinity", index))?;
+        let mut public_key = [0u8; 33];
+        public_key.copy_from_slice(child.to_encoded_point(true).as_bytes());
+        let mut chain_code = [0u8; 32];
+        chain_code.copy_from_slice(&i[32..]);
+        Ok(Self {
+            depth: self.depth.wrapping_add(1),
+            child_number: index,
+            public_key,
+            chain_code,
+        })
+    }
+
+    pub fn address(&self) -> Result<[u8; 20]> {
+        let key = PublicKey::from_sec1_bytes(&self.public_key)
+            .map_err(|_| anyhow!("xpub key is not a secp256k1 point"))?;
+        let hash = Keccak256::digest(&key.to_encoded_point(false).as_bytes()[1..]);
+        let mut address = [0u8; 20];
+        address.copy_from_slice(&hash[12..]);
+        Ok(address)
+    }
+}
+
+/// Decode an xpub and check it is the `m/44'/60'/0'` node: depth 3, child
+/// 0'. The parent fingerprint is not checked — devices differ in what they
+/// put there, and the TA's comparison with the phrase is the real test.
+pub fn parse_account_xpub(text: &str) -> Result<ExtendedPublicKey> {
+    let xpub = ExtendedPublicKey::decode(text)?;
+    if xpub.depth != 3 || xpub.child_number != 0x8000_0000 {
+        return Err(anyhow!(
+            "expected the account xpub at {} (depth 3, child 0'), got depth {} child {:#x}",
+            proto::migration::ACCOUNT_PATH,
+            xpub.depth,
+            xpub.child_number
+        ));
+    }
+    Ok(xpub)
+}
+
+/// Receive addresses `m/44'/60'/0'/0/0 .. count-1`.
+pub fn receive_addresses(account: &ExtendedPublicKey, count: u32) -> Result<Vec<[u8; 20]>> {
+    let external = account.child(0)?;
+    (0..count).map(|i| external.child(i)?.address()).collect()
+}
+
+impl From<&ExtendedPublicKey> for AccountXpub {
+    fn from(xpub: &ExtendedPublicKey) -> Self {
+        AccountXpub {
+            public_key: xpub.public_key.to_vec(),
+            chain_code: xpub.chain_code,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // BIP32 test vector 1: m/0H and m/0H/1.
+    const M_0H: &str = "xpub68Gmy5EdvgibQVfPdqkBBCHxA5htiqg55crXYuXoQRKfDBFA1WEjWgP6LHhwBZeNK1VTsfTFUHCdrfp1bgwQ9xv5ski8PX9rL2dZXvgGDnw";
+    const M_0H_1: &str = "xpub6ASuArnXKPbfEwhqN6e3mwBcDTgzisQN1wXN9BJcM47sSikHjJf3UFHKkNAWbWMiGj7Wf5uMash7SyYq527Hqck2AxYysAA7xmALppuCkwQ";
+
+    #[test]
+    fn decode_and_public_derivation_match_bip32_vectors() {
+        let parent = ExtendedPublicKey::decode(M_0H).unwrap();
+        assert_eq!((parent.depth, parent.child_number), (1, 0x8000_0000));
+        let expected = ExtendedPublicKey::decode(M_0H_1).unwrap();
+        assert_eq!(parent.child(1).unwrap(), expected);
+
+        // Not the account node, and a one-character typo breaks the checksum.
+        assert!(parse_account_xpub(M_0H)
+            .unwrap_err()
+            .to_string()
+            .contains("depth 3"));
+        let typo = M_0H.replacen("Gmy", "Gmz", 1);
+        assert!(ExtendedPublicKey::decode(&typo).is_err());
+        assert!(parent.child(0x8000_0000).is_err());
+    }
+
+    /// CKDpriv from the m/0H private key, for comparison with CKDpub.
+    fn private_child(key: &[u8; 32], chain: &[u8; 32], index: u32) -> ([u8; 32], [u8; 32]) {
+        let parent = SecretKey::from_slice(key).unwrap();
+        let mut mac = Hmac::<Sha512>::new_from_slice(chain).unwrap();
+        mac.update(parent.public_key().to_encoded_point(true).as_bytes());
+        mac.update(&index.to_be_bytes());
+        let i = mac.finalize().into_bytes();
+        let tweak = SecretKey::from_slice(&i[..32]).unwrap();
+        let sum = *tweak.to_nonzero_scalar() + *parent.to_nonzero_scalar();
+        let mut out = ([0u8; 32], [0u8; 32]);
+        out.0.copy_from_slice(&sum.to_bytes());
+        out.1.copy_from_slice(&i[32..]);
+        out
+    }
+
+    #[test]
+    fn receive_addresses_match_private_derivation() {
+        let account = ExtendedPublicKey::decode(M_0H).unwrap();
+        let mut key = [0u8; 32];
+        hex::decode_to_slice(
+            "edb2e14f9ee77d26dd93b4ecede8d16ed408ce149b6cd80b0715a2d911a0afea",
+            &mut key,
+        )
+        .unwrap();
+        let parent = SecretKey::from_slice(&key).unwrap();
+        assert_eq!(
+            parent.public_key().to_encoded_point(true).as_bytes(),
+            &account.public_key[..]
+        );
+
+        let addresses = receive_addresses(&account, 3).unwrap();
+        let (ext_key, ext_chain) = private_child(&key, &account.chain_code, 0);
+        for (i, address) in addresses.iter().enumerate() {
+            let (child, _) = private_child(&ext_key, &ext_chain, i as u32);
+            let point = SecretKey::from_slice(&child).unwrap().public_key();
+            let hash = Keccak256::digest(&point.to_encoded_point(false).as_bytes()[1..]);
+            assert_eq!(address[..], hash[12..]);
+        }
+    }
+}
@@ -0,0 +1,497 @@
+//! Node backup archive (`airaccount-backup`): everything the CA side needs
+//! to rebuild a node, in one gzip'd tar with a SHA-256 manifest.
+//!
+//! Entries: a consistent `kms.db` snapshot, the TA's capabilities (version,
+//! features, installed policy and config), the signed TA config file, the
+//! CA's attestation manifest, and passphrase-encrypted wallet backups
+//! (`export_key --keystore`). TEE secure storage is never included: it is
+//! bound to the board (see docs/BACKUP.md).
+//!
+//! The manifest catches corruption and truncation, not tampering — whoever
+//! can rewrite the archive can rewrite the manifest. The parts that matter
+//! carry their own protection: the TA config is signed by the provisioning
+//! key and the keystores are passphrase-sealed, and the TA re-checks both.
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+
+use crate::ta_config::TaConfigRequest;
+
+pub const SCHEMA: &str = "airaccount.node-backup.v1";
+
+pub const MANIFEST_ENTRY: &str = "manifest.json";
+pub const DB_ENTRY: &str = "kms.db";
+pub const CAPABILITIES_ENTRY: &str = "ta/capabilities.json";
+/// The operator's signed `--ta-config-file`; the TA keeps no signature, so
+/// the installed config can only be restored from this.
+pub const TA_CONFIG_ENTRY: &str = "ta/ta-config.json";
+pub const MEASUREMENTS_ENTRY: &str = "attestation/measurements.json";
+pub const WALLETS_PREFIX: &str = "wallets/";
+
+/// Features whose absence on the restore target would loosen the node.
+const HARDENING_FEATURES: [&str; 3] = [
+    "strict-challenge",
+    "strict-signing-context",
+    "secure-display",
+];
+/// Features a production node should not gain on restore.
+const DEV_FEATURES: [&str; 3] = ["export-secrets", "dev-rpid", "deterministic"];
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct TaSnapshot {
+    pub ta_version: String,
+    pub features: Vec<String>,
+    /// Hex TA measurement from attestation; absent on boards without the PTA.
+    #[serde(default)]
+    pub measurement: Option<String>,
+    #[serde(default)]
+    pub provisioning_key: Option<String>,
+    #[serde(default)]
+    pub config_sequence: Option<u64>,
+    #[serde(default)]
+    pub policy_sequence: Option<u64>,
+}
+
+impl TaSnapshot {
+    pub fn new(caps: &proto::GetCapabilitiesOutput, measurement: Option<&[u8]>) -> Self {
+        TaSnapshot {
+            ta_version: caps.ta_version.clone(),
+            features: caps.features.clone(),
+            measurement: measurement.map(hex::encode),
+            provisioning_key: caps
+                .provisioning_key
+                .map(|a| format!("0x{}", hex::encode(a))),
+            config_sequence: caps.config.as_ref().map(|c| c.sequence),
+            policy_sequence: caps.policy.as_ref().map(|p| p.sequence),
+        }
+    }
+
+    fn has(&self, feature: &str) -> bool {
+        self.features.iter().any(|f| f == feature)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ManifestEntry {
+    pub path: String,
+    pub sha256: String,
+    pub size: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Manifest {
+    pub schema: String,
+    pub created_at: String,
+    pub tool_version: String,
+    #[serde(default)]
+    pub device_id: Option<String>,
+    pub db_schema_version: u32,
+    pub ta: TaSnapshot,
+    pub entries: Vec<ManifestEntry>,
+}
+
+pub struct Archive {
+    pub manifest: Manifest,
+    files: BTreeMap<String, Vec<u8>>,
+}
+
+/// Relative, slash-separated, no `.`/`..`, under a known top-level name.
+fn check_path(path: &str) -> Result<()> {
+    let known = [
+        DB_ENTRY,
+        CAPABILITIES_ENTRY,
+        TA_CONFIG_ENTRY,
+        MEASUREMENTS_ENTRY,
+    ];
+    let safe = !path.is_empty()
+        && path
+            .split('/')
+            .all(|part| !part.is_empty() && part != "." && part != "..")
+        && !path.contains('\\');
+    let wallet = path
+        .strip_prefix(WALLETS_PREFIX)
+        .is_some_and(|name| !name.contains('/') && name.ends_with(".json"));
+    if !safe || !(known.contains(&path) || wallet) {
+        bail!("unexpected archive entry {:?}", path);
+    }
+    Ok(())
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+impl Archive {
+    pub fn new(
+        created_at: String,
+        device_id: Option<String>,
+        db_schema_version: u32,
+        ta: TaSnapshot,
+    ) -> Self {
+        Archive {
+            manifest: Manifest {
+                schema: SCHEMA.to_string(),
+                created_at,
+                tool_version: env!("CARGO_PKG_VERSION").to_string(),
+                device_id,
+                db_schema_version,
+                ta,
+                entries: Vec::new(),
+            },
+            files: BTreeMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, path: &str, data: Vec<u8>) -> Result<()> {
+        check_path(path)?;
+        if self.files.contains_key(path) {
+            bail!("duplicate archive entry {}", path);
+        }
+        self.manifest.entries.push(ManifestEntry {
+            path: path.to_string(),
+            sha256: sha256_hex(&data),
+            size: data.len() as u64,
+        });
+        self.files.insert(path.to_string(), data);
+        Ok(())
+    }
+
+    pub fn get(&self, path: &str) -> Option<&[u8]> {
+        self.files.get(path).map(|d| d.as_slice())
+    }
+
+    /// `(file name, contents)` of every wallet backup.
+    pub fn wallet_backups(&self) -> impl Iterator<Item = (&str, &[u8])> {
+        self.files.iter().filter_map(|(path, data)| {
+            path.strip_prefix(WALLETS_PREFIX)
+                .map(|name| (name, data.as_slice()))
+        })
+    }
+
+    /// The signed TA config, if the archive carries one.
+    pub fn ta_config(&self) -> Result<Option<TaConfigRequest>> {
+        self.get(TA_CONFIG_ENTRY)
+            .map(|raw| serde_json::from_slice(raw).context(TA_CONFIG_ENTRY))
+            .transpose()
+    }
+
+    pub fn write_to<W: Write>(&self, out: W) -> Result<()> {
+        let gz = flate2::write::GzEncoder::new(out, flate2::Compression::default());
+        let mut tar = tar::Builder::new(gz);
+        let mut append = |path: &str, data: &[u8]| -> Result<()> {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o600);
+            header.set_cksum();
+            tar.append_data(&mut header, path, data)
+                .with_context(|| format!("write {}", path))
+        };
+        append(MANIFEST_ENTRY, &serde_json::to_vec_pretty(&self.manifest)?)?;
+        for entry in &self.manifest.entries {
+            append(&entry.path, &self.files[&entry.path])?;
+        }
+        tar.into_inner()?.finish()?.flush()?;
+        Ok(())
+    }
+
+    /// Parse an archive. Only the layout is checked here; [`Self::verify`]
+    /// checks the contents.
+    pub fn read_from<R: Read>(input: R) -> Result<Self> {
+        let mut tar = tar::Archive::new(flate2::read::GzDecoder::new(input));
+        let mut manifest = None;
+        let mut files = BTreeMap::new();
+        for entry in tar.entries().context("not a gzip'd tar archive")? {
+            let mut entry = entry.context("corrupt archive")?;
+            if entry.header().entry_type() != tar::EntryType::Regular {
+                bail!("archive holds a non-file entry");
+            }
+            let path = entry
+                .path()?
+                .to_str()
+                .ok_or_else(|| anyhow!("archive entry name is not UTF-8"))?
+                .to_string();
+            let mut data = Vec::new();
+            entry
+                .read_to_end(&mut data)
+                .with_context(|| format!("read {}", path))?;
+            if path == MANIFEST_ENTRY {
+                if manifest.is_some() {
+                    bail!("archive holds two manifests");
+                }
+                manifest = Some(serde_json::from_slice::<Manifest>(&data).context(MANIFEST_ENTRY)?);
+                continue;
+            }
+            check_path(&path)?;
+            if files.insert(path.clone(), data).is_some() {
+                bail!("duplicate archive entry {}", path);
+            }
+        }
+        let manifest = manifest.ok_or_else(|| anyhow!("archive has no {}", MANIFEST_ENTRY))?;
+        Ok(Archive { manifest, files })
+    }
+
+    /// Every listed entry is present with the recorded size and hash, nothing
+    /// unlisted is present, and each entry parses as what it claims to be.
+    pub fn verify(&self) -> Result<()> {
+        if self.manifest.schema != SCHEMA {
+            bail!("unsupported backup schema {:?}", self.manifest.schema);
+        }
+        for entry in &self.manifest.entries {
+            let data = self
+                .files
+                .get(&entry.path)
+                .ok_or_else(|| anyhow!("{} is listed but missing", entry.path))?;
+            if data.len() as u64 != entry.size || sha256_hex(data) != entry.sha256 {
+                bail!("{} does not match its manifest hash", entry.path);
+            }
+        }
+        if let Some(extra) = self
+            .files
+            .keys()
+            .find(|path| !self.manifest.entries.iter().any(|e| &e.path == *path))
+        {
+            bail!("{} is not in the manifest", extra);
+        }
+        for required in [DB_ENTRY, CAPABILITIES_ENTRY] {
+            if !self.files.contains_key(required) {
+                bail!("archive has no {}", required);
+            }
+        }
+        let caps: proto::GetCapabilitiesOutput =
+            serde_json::from_slice(&self.files[CAPABILITIES_ENTRY]).context(CAPABILITIES_ENTRY)?;
+        if caps.ta_version != self.manifest.ta.ta_version {
+            bail!("{} disagrees with the manifest", CAPABILITIES_ENTRY);
+        }
+        if let Some(request) = self.ta_config()? {
+            let config = request.config().context(TA_CONFIG_ENTRY)?;
+            request.signature(&config).context(TA_CONFIG_ENTRY)?;
+        }
+        if let Some(raw) = self.get(MEASUREMENTS_ENTRY) {
+            serde_json::from_slice::<serde_json::Value>(raw).context(MEASUREMENTS_ENTRY)?;
+        }
+        for (name, raw) in self.wallet_backups() {
+            let file: serde_json::Value = serde_json::from_slice(raw)
+                .with_context(|| format!("{}{}", WALLETS_PREFIX, name))?;
+            crate::keystore::from_json(&file)
+                .with_context(|| format!("{}{}", WALLETS_PREFIX, name))?;
+        }
+        Ok(())
+    }
+}
+
+/// `major.minor.patch` of a version like `0.29.0` or `v0.28.0-strict`.
+pub(crate) fn version_triple(version: &str) -> Option<(u64, u64, u64)> {
+    let core = version.trim_start_matches('v').split('-').next()?;
+    let mut parts = core.split('.').map(|p| p.parse::<u64>().ok());
+    Some((
+        parts.next()??,
+        parts.next()??,
+        parts.next().unwrap_or(Some(0))?,
+    ))
+}
+
+/// Whether the running TA can take over from the one the backup was made
+/// on. Err = restoring would break or loosen the node; Ok carries warnings.
+pub fn check_ta_compat(
+    backup: &TaSnapshot,
+    running: &TaSnapshot,
+    signed_config: Option<&proto::ta_config::TaConfig>,
+) -> Result<Vec<String>> {
+    let mut warnings = Vec::new();
+    match (
+        version_triple(&backup.ta_version),
+        version_triple(&running.ta_version),
+    ) {
+        (Some(b), Some(r)) if r < b => bail!(
+            "running TA {} is older than the backed-up TA {}",
+            running.ta_version,
+            backup.ta_version
+        ),
+        (Some(_), Some(_)) => {}
+        _ => warnings.push(format!(
+            "cannot compare TA versions {:?} and {:?}",
+            backup.ta_version, running.ta_version
+        )),
+    }
+    for feature in HARDENING_FEATURES.iter() {
+        if backup.has(feature) && !running.has(feature) {
+            bail!(
+                "running TA is built without {}, the backed-up TA had it",
+                feature
+            );
+        }
+    }
+    for feature in DEV_FEATURES.iter() {
+        if running.has(feature) && !backup.has(feature) {
+            warnings.push(format!("running TA is a {} build", feature));
+        }
+    }
+    if let Some(config) = signed_config {
+        if running.provisioning_key != backup.provisioning_key {
+            bail!(
+                "TA config is signed for provisioning key {:?}, running TA accepts {:?}",
+                backup.provisioning_key,
+                running.provisioning_key
+            );
+        }
+        if running
+            .config_sequence
+            .is_some_and(|s| s >= config.sequence)
+        {
+            warnings.push(format!(
+                "running TA already has config sequence {:?}; the archived one ({}) will not be installed",
+                running.config_sequence, config.sequence
+            ));
+        }
+    } else if backup.config_sequence.is_some() {
+        warnings.push(
+            "backed-up TA had a config but the archive holds no signed copy to reinstall"
+                .to_string(),
+        );
+    }
+    if backup.policy_sequence.is_some() && running.policy_sequence.is_none() {
+        warnings.push(
+            "backed-up TA had a deployment policy; install it again with airaccount-provision"
+                .to_string(),
+        );
+    }
+    Ok(warnings)
+}
+
+/// Status of `measurement` in an attestation-measurements document
+/// (`current`, `previous`, `revoked`), or None when it is not listed.
+pub fn measurement_status<'a>(
+    measurements: &'a serde_json::Value,
+    measurement: &str,
+) -> Option<&'a str> {
+    measurements["body"]["measurements"]
+        .as_array()?
+        .iter()
+        .find(|m| m["ta_measurement"].as_str() == Some(measurement))
+        .and_then(|m| m["status"].as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(version: &str, features: &[&str]) -> TaSnapshot {
+        TaSnapshot {
+            ta_version: version.into(),
+            features: features.iter().map(|f| f.to_string()).collect(),
+            measurement: None,
+            provisioning_key: Some("0x11".into()),
+            config_sequence: None,
+            policy_sequence: None,
+        }
+    }
+
+    fn archive() -> Archive {
+        let ta = snapshot("0.29.0", &["strict-challenge"]);
+        let caps = proto::GetCapabilitiesOutput {
+            ta_version: "0.29.0".into(),
+            features: ta.features.clone(),
+            policy: None,
+            policy_signer: None,
+            config: None,
+            provisioning_key: None,
+        };
+        let mut a = Archive::new("2026-10-16T00:00:00Z".into(), None, 3, ta);
+        a.add(DB_ENTRY, b"sqlite".to_vec()).unwrap();
+        a.add(CAPABILITIES_ENTRY, serde_json::to_vec(&caps).unwrap())
+            .unwrap();
+        a
+    }
+
+    fn reread(a: &Archive) -> Archive {
+        let mut buf = Vec::new();
+        a.write_to(&mut buf).unwrap();
+        Archive::read_from(buf.as_slice()).unwrap()
+    }
+
+    #[test]
+    fn archive_roundtrip_and_tamper_detection() {
+        let a = archive();
+        let b = reread(&a);
+        assert_eq!(b.manifest, a.manifest);
+        b.verify().unwrap();
+        assert_eq!(b.get(DB_ENTRY), Some(&b"sqlite"[..]));
+
+        let mut corrupt = reread(&a);
+        corrupt.files.insert(DB_ENTRY.into(), b"sqlitf".to_vec());
+        assert!(corrupt.verify().is_err());
+        let mut missing = reread(&a);
+        missing.files.remove(CAPABILITIES_ENTRY);
+        assert!(missing.verify().is_err());
+        let mut unlisted = reread(&a);
+        unlisted
+            .files
+            .insert("wallets/extra.json".into(), b"{}".to_vec());
+        assert!(unlisted.verify().is_err());
+
+        let mut bad = archive();
+        assert!(bad.add("../etc/passwd", vec![]).is_err());
+        assert!(bad.add("wallets/a/b.json", vec![]).is_err());
+        assert!(bad.add(DB_ENTRY, vec![]).is_err(), "duplicate");
+        bad.add("wallets/w.json", b"{}".to_vec()).unwrap();
+        assert!(bad.verify().is_err(), "not a keystore");
+    }
+
+    #[test]
+    fn ta_compat_refuses_older_or_looser_targets() {
+        let backup = snapshot("0.29.0", &["strict-challenge"]);
+        assert!(
+            check_ta_compat(&backup, &snapshot("0.29.0", &["strict-challenge"]), None)
+                .unwrap()
+                .is_empty()
+        );
+        assert!(check_ta_compat(&backup, &snapshot("0.30.1", &["strict-challenge"]), None).is_ok());
+        assert!(
+            check_ta_compat(&backup, &snapshot("0.28.9", &["strict-challenge"]), None).is_err()
+        );
+        assert!(check_ta_compat(&backup, &snapshot("0.29.0", &[]), None).is_err());
+        let dev = check_ta_compat(
+            &backup,
+            &snapshot("0.29.0", &["strict-challenge", "export-secrets"]),
+            None,
+        )
+        .unwrap();
+        assert_eq!(dev.len(), 1);
+
+        let config = TaConfigRequest {
+            deployment: "rack-3".into(),
+            sequence: 2,
+            limits: Default::default(),
+            require_ta_challenge: false,
+            require_signing_context: false,
+            signature: None,
+        }
+        .config()
+        .unwrap();
+        let mut other_key = snapshot("0.29.0", &["strict-challenge"]);
+        other_key.provisioning_key = Some("0x22".into());
+        assert!(check_ta_compat(&backup, &other_key, Some(&config)).is_err());
+        assert!(check_ta_compat(
+            &backup,
+            &snapshot("0.29.0", &["strict-challenge"]),
+            Some(&config)
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn measurement_status_reads_the_published_manifest() {
+        let doc: serde_json::Value =
+            serde_json::from_str(include_str!("../attestation-measurements.json")).unwrap();
+        let current = doc["body"]["measurements"][0]["ta_measurement"]
+            .as_str()
+            .unwrap();
+        assert_eq!(measurement_status(&doc, current), Some("current"));
+        assert_eq!(measurement_status(&doc, &"00".repeat(32)), None);
+    }
+}
@@ -0,0 +1,322 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Wallet notification preferences.
+//!
+//! Which of a wallet's chain events are pushed to the event webhook, which of
+//! those the mail relay also emails to the wallet's bound address, value
+//! floors per asset, and quiet hours. Stored as JSON in
+//! `notification_prefs` (db.rs) and applied by `chain_watch::record` to
+//! every delivery. A wallet without a row gets [`NotificationPrefs::default`]:
+//! every event to the webhook, none by email — what a consumer wallet wants,
+//! and what every wallet got before preferences existed. A relayer that sees
+//! hundreds of deposits a day narrows `events` or sets floors instead.
+//!
+//! Preferences only decide pushes: every event is still recorded and listed
+//! by `/kms/wallet/{keyId}/events`. Operator alerts (gas tanks, device health)
+//! are not a wallet's to silence and ignore them.
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::chain_watch::{KIND_CONTRACT_EVENT, KIND_ERC20_IN, KIND_NATIVE_IN, KIND_STEALTH_IN};
+use crate::db::ChainEvent;
+
+/// Event kinds a preference can name.
+pub const EVENT_KINDS: [&str; 4] = [
+    KIND_ERC20_IN,
+    KIND_NATIVE_IN,
+    KIND_CONTRACT_EVENT,
+    KIND_STEALTH_IN,
+];
+
+/// `thresholds` key of native ETH; tokens are keyed by contract address.
+pub const NATIVE: &str = "native";
+
+const MAX_THRESHOLDS: usize = 64;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct NotificationPrefs {
+    /// Kinds pushed to the webhook; None = all of them.
+    #[serde(default)]
+    pub events: Option<Vec<String>>,
+    /// Kinds the mail relay also emails; each must be pushed at all.
+    #[serde(default)]
+    pub email_events: Vec<String>,
+    /// `native` or a token address → decimal base units. A transfer of less
+    /// is not pushed; assets without an entry always are.
+    #[serde(default)]
+    pub thresholds: BTreeMap<String, String>,
+    #[serde(default)]
+    pub quiet_hours: Option<QuietHours>,
+}
+
+/// A daily window, in the owner's local time, with no pushes. `start` after
+/// `end` spans midnight.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct QuietHours {
+    /// "HH:MM"
+    pub start: String,
+    /// "HH:MM", exclusive.
+    pub end: String,
+    /// Local time minus UTC, -720..=840.
+    #[serde(default)]
+    pub utc_offset_minutes: i32,
+}
+
+/// What to do with one event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delivery {
+    /// Push it; `email` asks the mail relay to send it on as well.
+    Push { email: bool },
+    /// Not pushed, for this reason (logged).
+    Hold(&'static str),
+}
+
+impl NotificationPrefs {
+    /// Parse and check a body from `POST /kms/wallet/{keyId}/notifications`.
+    /// Token addresses are stored lowercase.
+    pub fn parse(value: serde_json::Value) -> Result<Self> {
+        let mut prefs: Self =
+            serde_json::from_value(value).map_err(|e| anyhow::anyhow!("preferences: {}", e))?;
+        let known = |kind: &String| -> Result<()> {
+            if !EVENT_KINDS.contains(&kind.as_str()) {
+                bail!(
+                    "unknown event kind '{}' (expected one of {})",
+                    kind,
+                    EVENT_KINDS.join(", ")
+                );
+            }
+            Ok(())
+        };
+        if let Some(events) = &prefs.events {
+            events.iter().try_for_each(known)?;
+        }
+        for kind in &prefs.email_events {
+            known(kind)?;
+            if !prefs.pushes(kind) {
+                bail!("'{}' is emailed but not in events", kind);
+            }
+        }
+        if prefs.thresholds.len() > MAX_THRESHOLDS {
+            bail!("at most {} thresholds", MAX_THRESHOLDS);
+        }
+        let mut thresholds = BTreeMap::new();
+        for (asset, floor) in std::mem::take(&mut prefs.thresholds) {
+            let asset = if asset == NATIVE {
+                asset
+            } else {
+                let address = proto::eip55::parse_address(&asset)
+                    .map_err(|e| anyhow::anyhow!("threshold asset {}: {}", asset, e))?;
+                format!("0x{}", hex::encode(address))
+            };
+            if floor.is_empty() || floor.len() > 78 || !floor.bytes().all(|b| b.is_ascii_digit()) {
+                bail!("threshold for {} must be decimal base units", asset);
+            }
+            thresholds.insert(asset, floor);
+        }
+        prefs.thresholds = thresholds;
+        if let Some(q) = &prefs.quiet_hours {
+            if minute_of_day(&q.start)? == minute_of_day(&q.end)? {
+                bail!("quiet hours start and end at the same time");
+            }
+            if !(-720..=840).contains(&q.utc_offset_minutes) {
+                bail!("utcOffsetMinutes must be within -720..=840");
+            }
+        }
+        Ok(prefs)
+    }
+
+    fn pushes(&self, kind: &str) -> bool {
+        match &self.events {
+            Some(events) => events.iter().any(|k| k == kind),
+            None => true,
+        }
+    }
+
+    /// Decide one delivery at unix time `now`.
+    pub fn delivery(&self, event: &ChainEvent, now: i64) -> Delivery {
+        if !self.pushes(&event.kind) {
+            return Delivery::Hold("event kind muted");
+        }
+        let asset = match (event.kind.as_str(), &event.token) {
+            (KIND_ERC20_IN, Some(token)) => Some(token.to_lowercase()),
+            (KIND_NATIVE_IN, _) => Some(NATIVE.to_string()),
+            _ => None,
+        };
+        let floor = asset.and_then(|a| self.thresholds.get(&a));
+        if let (Some(floor), Some(value)) = (floor, &event.value) {
+            if decimal_lt(value, floor) {
+                return Delivery::Hold("below threshold");
+            }
+        }
+        if let Some(q) = &self.quiet_hours {
+            if q.contains(now) {
+                return Delivery::Hold("quiet hours");
+            }
+        }
+        Delivery::Push {
+            email: self.email_events.contains(&event.kind),
+        }
+    }
+}
+
+impl QuietHours {
+    fn contains(&self, now: i64) -> bool {
+        let (start, end) = match (minute_of_day(&self.start), minute_of_day(&self.end)) {
+            (Ok(s), Ok(e)) => (s, e),
+            _ => return false,
+        };
+        let local = (now + i64::from(self.utc_offset_minutes) * 60).rem_euclid(86_400) / 60;
+        if start < end {
+            start <= local && local < end
+        } else {
+            local >= start || local < end
+        }
+    }
+}
+
+fn minute_of_day(hhmm: &str) -> Result<i64> {
+    let parsed = hhmm.split_once(':').and_then(|(h, m)| {
+        if h.len() != 2 || m.len() != 2 || !(h.bytes().chain(m.bytes())).all(|b| b.is_ascii_digit())
+        {
+            return None;
+        }
+        Some((h.parse::<i64>().ok()?, m.parse::<i64>().ok()?))
+    });
+    match parsed {
+        Some((h, m)) if (0..24).contains(&h) && (0..60).contains(&m) => Ok(h * 60 + m),
+        _ => bail!("quiet hours time '{}' is not HH:MM", hhmm),
+    }
+}
+
+/// `a < b` for unsigned decimal strings of any length.
+fn decimal_lt(a: &str, b: &str) -> bool {
+    let a = a.trim_start_matches('0');
+    let b = b.trim_start_matches('0');
+    (a.len(), a) < (b.len(), b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const USDC: &str = "0x0b2c639c533813f4aa9d7837caf62653d097ff85";
+
+    fn event(kind: &str, token: Option<&str>, value: &str) -> ChainEvent {
+        ChainEvent {
+            key_id: "k".into(),
+            address: "0x1111111111111111111111111111111111111111".into(),
+            chain_id: 10,
+            kind: kind.into(),
+            token: token.map(str::to_string),
+            from_address: None,
+            to_address: None,
+            value: Some(value.into()),
+            topic0: None,
+            tx_hash: "0xab".into(),
+            block_number: 1,
+            log_index: 0,
+        }
+    }
+
+    #[test]
+    fn defaults_push_everything_and_email_nothing() {
+        let prefs = NotificationPrefs::parse(json!({})).unwrap();
+        assert_eq!(prefs, NotificationPrefs::default());
+        for kind in EVENT_KINDS {
+            assert_eq!(
+                prefs.delivery(&event(kind, None, "1"), 0),
+                Delivery::Push { email: false }
+            );
+        }
+    }
+
+    #[test]
+    fn relayer_prefs_mute_kinds_and_small_transfers() {
+        let prefs = NotificationPrefs::parse(json!({
+            "events": ["erc20-transfer-in", "native-transfer-in"],
+            "emailEvents": ["native-transfer-in"],
+            "thresholds": { "native": "1000000000000000000", "0x0B2C639C533813F4AA9D7837CAF62653D097FF85": "1000000" },
+        }))
+        .unwrap();
+        assert!(prefs.thresholds.contains_key(USDC));
+
+        let hold = |kind, token, value| prefs.delivery(&event(kind, token, value), 0);
+        assert_eq!(
+            hold(KIND_CONTRACT_EVENT, None, "0"),
+            Delivery::Hold("event kind muted")
+        );
+        assert_eq!(
+            hold(KIND_ERC20_IN, Some(USDC), "999999"),
+            Delivery::Hold("below threshold")
+        );
+        assert_eq!(
+            hold(KIND_ERC20_IN, Some(USDC), "0001000000"),
+            Delivery::Push { email: false }
+        );
+        // A token without a floor is always pushed.
+        assert_eq!(
+            hold(
+                KIND_ERC20_IN,
+                Some("0x3333333333333333333333333333333333333333"),
+                "1"
+            ),
+            Delivery::Push { email: false }
+        );
+        assert_eq!(
+            hold(KIND_NATIVE_IN, None, "20000000000000000000"),
+            Delivery::Push { email: true }
+        );
+        assert!(decimal_lt("0", "1") && !decimal_lt("10", "9") && !decimal_lt("00", "0"));
+    }
+
+    #[test]
+    fn quiet_hours_span_midnight_in_local_time() {
+        let prefs = NotificationPrefs::parse(json!({
+            "quietHours": { "start": "22:00", "end": "07:00", "utcOffsetMinutes": 480 },
+        }))
+        .unwrap();
+        let e = event(KIND_NATIVE_IN, None, "1");
+        // 2026-10-16 15:00 UTC = 23:00 UTC+8; 23:00 UTC = 07:00 local.
+        let at = |hour: i64| prefs.delivery(&e, 1_792_108_800 + hour * 3600);
+        assert_eq!(at(15), Delivery::Hold("quiet hours"));
+        assert_eq!(at(22), Delivery::Hold("quiet hours"));
+        assert_eq!(at(23), Delivery::Push { email: false });
+        assert_eq!(at(13), Delivery::Push { email: false });
+    }
+
+    #[test]
+    fn bad_preferences_are_refused() {
+        for bad in [
+            json!({ "events": ["transfer"] }),
+            json!({ "events": ["contract-event"], "emailEvents": ["native-transfer-in"] }),
+            json!({ "thresholds": { "usdc": "1" } }),
+            json!({ "thresholds": { "native": "1e18" } }),
+            json!({ "quietHours": { "start": "7:00", "end": "22:00" } }),
+            json!({ "quietHours": { "start": "22:00", "end": "22:00" } }),
+            json!({ "quietHours": { "start": "22:00", "end": "07:00", "utcOffsetMinutes": 900 } }),
+            json!({ "webhook": false }),
+        ] {
+            assert!(NotificationPrefs::parse(bad.clone()).is_err(), "{}", bad);
+        }
+    }
+}
@@ -0,0 +1,265 @@
+//! Air-gapped signing transport — CBOR codec and response verification.
+//!
+//! Requests and responses travel as compact CBOR arrays (positional, no map
+//! keys) wrapped in a prefixed base64url string that fits a single QR code
+//! for ordinary transfers. Pure functions: the export/import bookkeeping is in
+//! db.rs and the endpoints in api_server.rs.
+
+use anyhow::{anyhow, Result};
+use ciborium::Value;
+use proto::offline::{decode_signed_legacy, OfflineSignRequest, OfflineSignResponse};
+use proto::EthTransaction;
+use sha3::{Digest, Keccak256};
+use std::convert::{TryFrom, TryInto};
+use uuid::Uuid;
+
+use crate::key_pin::signed_tx_signer;
+use crate::webauthn::{b64url_decode, b64url_encode};
+
+/// Text prefixes: a response pasted where a request is expected (or a QR
+/// from something else entirely) fails with a clear error.
+pub const REQUEST_PREFIX: &str = "aa-offline-req:";
+pub const RESPONSE_PREFIX: &str = "aa-offline-res:";
+
+fn uint_bytes(v: u128) -> Value {
+    let be = v.to_be_bytes();
+    let skip = be.iter().take_while(|b| **b == 0).count();
+    Value::Bytes(be[skip..].to_vec())
+}
+
+fn tx_value(tx: &EthTransaction) -> Value {
+    Value::Array(vec![
+        Value::Integer(tx.chain_id.into()),
+        uint_bytes(tx.nonce),
+        Value::Bytes(tx.to.map(|t| t.to_vec()).unwrap_or_default()),
+        uint_bytes(tx.value),
+        uint_bytes(tx.gas_price),
+        uint_bytes(tx.gas),
+        Value::Bytes(tx.data.clone()),
+    ])
+}
+
+fn to_cbor(v: &Value) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    ciborium::into_writer(v, &mut out).map_err(|e| anyhow!("CBOR encode: {}", e))?;
+    Ok(out)
+}
+
+fn from_cbor(bytes: &[u8], len: usize, what: &str) -> Result<Vec<Value>> {
+    match ciborium::from_reader::<Value, _>(bytes) {
+        Ok(Value::Array(items)) if items.len() == len => Ok(items),
+        Ok(_) => Err(anyhow!("{}: unexpected CBOR shape", what)),
+        Err(e) => Err(anyhow!("{}: CBOR decode: {}", what, e)),
+    }
+}
+
+fn as_u64(v: &Value, field: &str) -> Result<u64> {
+    v.as_integer()
+        .and_then(|i| u64::try_from(i).ok())
+        .ok_or_else(|| anyhow!("{}: expected unsigned integer", field))
+}
+
+fn as_bytes<'a>(v: &'a Value, field: &str) -> Result<&'a [u8]> {
+    v.as_bytes()
+        .map(|b| b.as_slice())
+        .ok_or_else(|| anyhow!("{}: expected bytes", field))
+}
+
+fn as_array<const N: usize>(v: &Value, field: &str) -> Result<[u8; N]> {
+    let b = as_bytes(v, field)?;
+    b.try_into()
+        .map_err(|_| anyhow!("{}: expected {} bytes, got {}", field, N, b.len()))
+}
+
+fn as_uint(v: &Value, field: &str) -> Result<u128> {
+    let b = as_bytes(v, field)?;
+    if b.len() > 16 || b.first() == Some(&0) {
+        return Err(anyhow!("{}: not a minimal uint128", field));
+    }
+    Ok(b.iter().fold(0u128, |a, x| (a << 8) | *x as u128))
+}
+
+fn tx_from_value(v: &Value) -> Result<EthTransaction> {
+    let f = match v {
+        Value::Array(f) if f.len() == 7 => f,
+        _ => return Err(anyhow!("transaction: unexpected CBOR shape")),
+    };
+    let to = match as_bytes(&f[2], "to")? {
+        [] => None,
+        _ => Some(as_array::<20>(&f[2], "to")?),
+    };
+    Ok(EthTransaction {
+        chain_id: as_u64(&f[0], "chainId")?,
+        nonce: as_uint(&f[1], "nonce")?,
+        to,
+        value: as_uint(&f[3], "value")?,
+        gas_price: as_uint(&f[4], "gasPrice")?,
+        gas: as_uint(&f[5], "gas")?,
+        data: as_bytes(&f[6], "data")?.to_vec(),
+    })
+}
+
+pub fn encode_request(req: &OfflineSignRequest) -> Result<String> {
+    let cbor = to_cbor(&Value::Array(vec![
+        Value::Integer(req.version.into()),
+        Value::Bytes(req.request_id.to_vec()),
+        Value::Bytes(req.wallet_id.as_bytes().to_vec()),
+        Value::Text(req.hd_path.clone()),
+        tx_value(&req.transaction),
+        Value::Integer(req.created_at.into()),
+        Value::Integer(req.expires_at.into()),
+    ]))?;
+    Ok(format!("{}{}", REQUEST_PREFIX, b64url_encode(&cbor)))
+}
+
+pub fn decode_request(text: &str) -> Result<OfflineSignRequest> {
+    let b64 = text
+        .trim()
+        .strip_prefix(REQUEST_PREFIX)
+        .ok_or_else(|| anyhow!("not an offline sign request (expected {})", REQUEST_PREFIX))?;
+    let f = from_cbor(&b64url_decode(b64)?, 7, "offline request")?;
+    let version = as_u64(&f[0], "version")?;
+    Ok(OfflineSignRequest {
+        version: u8::try_from(version).map_err(|_| anyhow!("version out of range"))?,
+        request_id: as_array::<16>(&f[1], "requestId")?,
+        wallet_id: Uuid::from_bytes(as_array::<16>(&f[2], "walletId")?),
+        hd_path: f[3]
+            .as_text()
+            .ok_or_else(|| anyhow!("hdPath: expected text"))?
+            .to_string(),
+        transaction: tx_from_value(&f[4])?,
+        created_at: as_u64(&f[5], "createdAt")?,
+        expires_at: as_u64(&f[6], "expiresAt")?,
+    })
+}
+
+pub fn encode_response(resp: &OfflineSignResponse) -> Result<String> {
+    let cbor = to_cbor(&Value::Array(vec![
+        Value::Integer(resp.version.into()),
+        Value::Bytes(resp.request_id.to_vec()),
+        Value::Bytes(resp.signed_transaction.clone()),
+    ]))?;
+    Ok(format!("{}{}", RESPONSE_PREFIX, b64url_encode(&cbor)))
+}
+
+pub fn decode_response(text: &str) -> Result<OfflineSignResponse> {
+    let b64 = text.trim().strip_prefix(RESPONSE_PREFIX).ok_or_else(|| {
+        anyhow!(
+            "not an offline sign response (expected {})",
+            RESPONSE_PREFIX
+        )
+    })?;
+    let f = from_cbor(&b64url_decode(b64)?, 3, "offline response")?;
+    let version = as_u64(&f[0], "version")?;
+    Ok(OfflineSignResponse {
+        version: u8::try_from(version).map_err(|_| anyhow!("version out of range"))?,
+        request_id: as_array::<16>(&f[1], "requestId")?,
+        signed_transaction: as_bytes(&f[2], "signedTransaction")?.to_vec(),
+    })
+}
+
+/// Result of checking a response against the request it answers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifiedResponse {
+    /// keccak256 of the signed tx — the on-chain transaction hash.
+    pub transaction_hash: [u8; 32],
+    pub signer: [u8; 20],
+}
+
+/// Check that `resp` signs exactly `req.transaction` and recover the signer.
+/// The caller compares `signer` with the address it has on record.
+pub fn verify_response(
+    req: &OfflineSignRequest,
+    resp: &OfflineSignResponse,
+) -> Result<VerifiedResponse> {
+    if resp.request_id != req.request_id {
+        return Err(anyhow!("response is for a different request"));
+    }
+    let signed = decode_signed_legacy(&resp.signed_transaction)
+        .map_err(|e| anyhow!("signed transaction: {}", e))?;
+    if signed.transaction != req.transaction {
+        return Err(anyhow!(
+            "signed transaction differs from the exported request"
+        ));
+    }
+    let signer = signed_tx_signer(&resp.signed_transaction)?;
+    Ok(VerifiedResponse {
+        transaction_hash: Keccak256::digest(&resp.signed_transaction).into(),
+        signer,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proto::offline::OFFLINE_FORMAT_VERSION;
+
+    /// The worked example from EIP-155 (key 0x4646…46).
+    fn eip155_request() -> OfflineSignRequest {
+        OfflineSignRequest {
+            version: OFFLINE_FORMAT_VERSION,
+            request_id: [0x11; 16],
+            wallet_id: Uuid::from_bytes([0x22; 16]),
+            hd_path: "m/44'/60'/0'/0/0".into(),
+            transaction: EthTransaction {
+                chain_id: 1,
+                nonce: 9,
+                to: Some([0x35; 20]),
+                value: 1_000_000_000_000_000_000,
+                gas_price: 20_000_000_000,
+                gas: 21_000,
+                data: vec![],
+            },
+            created_at: 1_700_000_000,
+            expires_at: 1_700_000_600,
+        }
+    }
+
+    fn eip155_response() -> OfflineSignResponse {
+        OfflineSignResponse {
+            version: OFFLINE_FORMAT_VERSION,
+            request_id: [0x11; 16],
+            signed_transaction: hex::decode(
+                "f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a7\
+                 6400008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a0\
+                 67cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83",
+            )
+            .unwrap(),
+        }
+    }
+
+    #[test]
+    fn qr_text_roundtrip() {
+        let req = eip155_request();
+        let text = encode_request(&req).unwrap();
+        assert!(
+            text.len() < 300,
+            "request should fit one QR: {}",
+            text.len()
+        );
+        assert_eq!(decode_request(&text).unwrap(), req);
+        let resp = eip155_response();
+        let text = encode_response(&resp).unwrap();
+        assert_eq!(decode_response(&text).unwrap(), resp);
+        // direction is checked by prefix
+        assert!(decode_request(&text).is_err());
+    }
+
+    #[test]
+    fn response_verification() {
+        let req = eip155_request();
+        let v = verify_response(&req, &eip155_response()).unwrap();
+        assert_eq!(
+            hex::encode(v.signer),
+            "9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f"
+        );
+        // same signed tx answering a different request
+        let mut other = req.clone();
+        other.request_id = [0x12; 16];
+        assert!(verify_response(&other, &eip155_response()).is_err());
+        // a tx other than the one exported
+        let mut changed = req;
+        changed.transaction.value += 1;
+        assert!(verify_response(&changed, &eip155_response()).is_err());
+    }
+}
@@ -0,0 +1,743 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! OpenTelemetry export over OTLP/HTTP (JSON encoding).
+//!
+//! `KMS_OTLP_ENDPOINT` (e.g. `http://collector:4318`) turns it on. Spans go to
+//! `/v1/traces` in batches; every span's duration also lands in a histogram
+//! (`kms.span.duration`, by span name and TA command) pushed to `/v1/metrics`
+//! every 30s, so metrics stay complete when traces are sampled.
+//!
+//! Written against the OTLP wire format rather than the opentelemetry crates:
+//! those pull in a gRPC/protobuf stack the CA's pinned toolchain does not
+//! build, and the CA only needs to emit, not to instrument libraries.
+//!
+//! W3C `traceparent` on an incoming request continues the caller's trace and
+//! its sampling decision; without one, `KMS_OTLP_SAMPLE_RATIO` decides.
+
+use anyhow::{anyhow, bail, Result};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Spans buffered between flushes; beyond this they are dropped and counted.
+const QUEUE_CAPACITY: usize = 2048;
+const BATCH_SIZE: usize = 256;
+const TRACE_FLUSH: Duration = Duration::from_secs(5);
+const METRICS_INTERVAL: Duration = Duration::from_secs(30);
+/// Upper bounds (ms) of the duration histogram buckets.
+const BUCKETS_MS: [f64; 13] = [
+    1.0, 2.0, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0, 2500.0, 5000.0, 10000.0,
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OtelConfig {
+    /// Base URL; `/v1/traces` and `/v1/metrics` are appended.
+    pub endpoint: String,
+    /// Fraction of new traces recorded, 0.0-1.0.
+    pub sample_ratio: f64,
+    pub service_name: String,
+    /// Extra request headers (`KMS_OTLP_HEADERS=authorization=Bearer x,...`).
+    pub headers: Vec<(String, String)>,
+}
+
+impl OtelConfig {
+    pub fn from_env() -> Option<Result<Self>> {
+        let endpoint = std::env::var("KMS_OTLP_ENDPOINT")
+            .ok()
+            .filter(|v| !v.is_empty())?;
+        let var = |name| std::env::var(name).ok().filter(|v: &String| !v.is_empty());
+        Some(Self::new(
+            endpoint,
+            var("KMS_OTLP_SAMPLE_RATIO").as_deref(),
+            var("KMS_OTLP_SERVICE_NAME"),
+            var("KMS_OTLP_HEADERS").as_deref(),
+        ))
+    }
+
+    pub fn new(
+        endpoint: String,
+        sample_ratio: Option<&str>,
+        service_name: Option<String>,
+        headers: Option<&str>,
+    ) -> Result<Self> {
+        if !endpoint.starts_with("http://") {
+            bail!("KMS_OTLP_ENDPOINT must be http:// (no TLS in the CA)");
+        }
+        let sample_ratio = match sample_ratio {
+            Some(v) => v
+                .parse::<f64>()
+                .ok()
+                .filter(|r| (0.0..=1.0).contains(r))
+                .ok_or_else(|| anyhow!("KMS_OTLP_SAMPLE_RATIO must be between 0 and 1"))?,
+            None => 1.0,
+        };
+        let mut parsed = Vec::new();
+        for pair in headers.unwrap_or("").split(',').filter(|p| !p.is_empty()) {
+            let (k, v) = pair
+                .split_once('=')
+                .ok_or_else(|| anyhow!("KMS_OTLP_HEADERS entry without '=': {}", pair))?;
+            parsed.push((k.trim().to_string(), v.trim().to_string()));
+        }
+        Ok(Self {
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            sample_ratio,
+            service_name: service_name.unwrap_or_else(|| "airaccount-kms".to_string()),
+            headers: parsed,
+        })
+    }
+}
+
+/// Identity of a span as propagated in `traceparent`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceContext {
+    pub trace_id: [u8; 16],
+    pub span_id: [u8; 8],
+    pub sampled: bool,
+}
+
+impl TraceContext {
+    /// W3C trace context, version 00. Anything malformed is ignored (a new
+    /// trace starts) rather than rejected.
+    pub fn from_traceparent(header: &str) -> Option<Self> {
+        let parts: Vec<&str> = header.trim().split('-').collect();
+        if parts.len() != 4 || parts[0] != "00" {
+            return None;
+        }
+        let mut trace_id = [0u8; 16];
+        let mut span_id = [0u8; 8];
+        hex::decode_to_slice(parts[1], &mut trace_id).ok()?;
+        hex::decode_to_slice(parts[2], &mut span_id).ok()?;
+        let flags = u8::from_str_radix(parts[3], 16).ok()?;
+        if parts[3].len() != 2 || trace_id == [0; 16] || span_id == [0; 8] {
+            return None;
+        }
+        Some(Self {
+            trace_id,
+            span_id,
+            sampled: flags & 1 == 1,
+        })
+    }
+
+    pub fn traceparent(&self) -> String {
+        format!(
+            "00-{}-{}-{:02x}",
+            hex::encode(self.trace_id),
+            hex::encode(self.span_id),
+            self.sampled as u8
+        )
+    }
+}
+
+/// Trace-id-ratio sampling: the same trace id gives the same answer on every
+/// service that uses the ratio.
+pub fn sample(trace_id: &[u8; 16], ratio: f64) -> bool {
+    let mut low = [0u8; 8];
+    low.copy_from_slice(&trace_id[8..]);
+    let x = u64::from_be_bytes(low) >> 11;
+    (x as f64) < ratio * (1u64 << 53) as f64
+}
+
+fn random_bytes<const N: usize>() -> [u8; N] {
+    use rand::RngCore;
+    let mut b = [0u8; N];
+    while b == [0u8; N] {
+        rand::thread_rng().fill_bytes(&mut b);
+    }
+    b
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpanKind {
+    Internal,
+    Server,
+    Client,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttrValue {
+    Str(String),
+    Int(i64),
+    Bool(bool),
+}
+
+impl From<&str> for AttrValue {
+    fn from(v: &str) -> Self {
+        AttrValue::Str(v.to_string())
+    }
+}
+
+impl From<String> for AttrValue {
+    fn from(v: String) -> Self {
+        AttrValue::Str(v)
+    }
+}
+
+impl From<i64> for AttrValue {
+    fn from(v: i64) -> Self {
+        AttrValue::Int(v)
+    }
+}
+
+impl From<u32> for AttrValue {
+    fn from(v: u32) -> Self {
+        AttrValue::Int(v as i64)
+    }
+}
+
+impl From<usize> for AttrValue {
+    fn from(v: usize) -> Self {
+        AttrValue::Int(v as i64)
+    }
+}
+
+impl From<bool> for AttrValue {
+    fn from(v: bool) -> Self {
+        AttrValue::Bool(v)
+    }
+}
+
+/// One operation. Ends (and is exported) when dropped or on [`Span::end_at`].
+/// A span is cheap and inert while export is off.
+#[derive(Debug)]
+pub struct Span {
+    context: TraceContext,
+    parent_span_id: Option<[u8; 8]>,
+    name: String,
+    kind: SpanKind,
+    start: SystemTime,
+    attributes: Vec<(&'static str, AttrValue)>,
+    error: Option<String>,
+    ended: bool,
+}
+
+impl Span {
+    /// A child of `parent`, or the root of a new trace.
+    pub fn start(name: impl Into<String>, kind: SpanKind, parent: Option<TraceContext>) -> Self {
+        Self::start_at(name, kind, parent, SystemTime::now())
+    }
+
+    pub fn start_at(
+        name: impl Into<String>,
+        kind: SpanKind,
+        parent: Option<TraceContext>,
+        start: SystemTime,
+    ) -> Self {
+        let context = match parent {
+            Some(p) => TraceContext {
+                span_id: random_bytes(),
+                ..p
+            },
+            None => {
+                let trace_id = random_bytes();
+                let ratio = EXPORTER.get().map_or(0.0, |e| e.config.sample_ratio);
+                TraceContext {
+                    trace_id,
+                    span_id: random_bytes(),
+                    sampled: sample(&trace_id, ratio),
+                }
+            }
+        };
+        Self {
+            context,
+            parent_span_id: parent.map(|p| p.span_id),
+            name: name.into(),
+            kind,
+            start,
+            attributes: Vec::new(),
+            error: None,
+            ended: false,
+        }
+    }
+
+    /// What children of this span and outgoing `traceparent` headers use.
+    pub fn context(&self) -> TraceContext {
+        self.context
+    }
+
+    pub fn attr(&mut self, key: &'static str, value: impl Into<AttrValue>) {
+        if EXPORTER.get().is_some() {
+            self.attributes.push((key, value.into()));
+        }
+    }
+
+    /// Mark the span failed. Messages go to the collector, so callers pass
+    /// error text that is already safe to log.
+    pub fn set_error(&mut self, message: impl Into<String>) {
+        self.error = Some(message.into());
+    }
+
+    pub fn record_result<T>(&mut self, result: &Result<T>) {
+        if let Err(e) = result {
+            self.set_error(e.to_string());
+        }
+    }
+
+    pub fn end_at(mut self, end: SystemTime) {
+        self.finish(end);
+    }
+
+    fn finish(&mut self, end: SystemTime) {
+        if self.ended {
+            return;
+        }
+        self.ended = true;
+        if let Some(exporter) = EXPORTER.get() {
+            exporter.record(self, end);
+        }
+    }
+
+    fn command_attr(&self) -> Option<String> {
+        self.attributes.iter().find_map(|(k, v)| match v {
+            AttrValue::Str(s) if *k == "kms.ta.command" => Some(s.clone()),
+            _ => None,
+        })
+    }
+
+    /// OTLP/JSON `Span`.
+    pub fn to_otlp(&self, end: SystemTime) -> Value {
+        let mut span = json!({
+            "traceId": hex::encode(self.context.trace_id),
+            "spanId": hex::encode(self.context.span_id),
+            "name": self.name,
+            "kind": match self.kind {
+                SpanKind::Internal => 1,
+                SpanKind::Server => 2,
+                SpanKind::Client => 3,
+            },
+            "startTimeUnixNano": unix_nanos(self.start).to_string(),
+            "endTimeUnixNano": unix_nanos(end).to_string(),
+            "attributes": self.attributes.iter().map(|(k, v)| attribute(k, v)).collect::<Vec<_>>(),
+            "status": match &self.error {
+                Some(message) => json!({ "code": 2, "message": message }),
+                None => json!({ "code": 0 }),
+            },
+        });
+        if let Some(parent) = self.parent_span_id {
+            span["parentSpanId"] = hex::encode(parent).into();
+        }
+        span
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        self.finish(SystemTime::now());
+    }
+}
+
+fn unix_nanos(t: SystemTime) -> u128 {
+    t.duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos()
+}
+
+fn attribute(key: &str, value: &AttrValue) -> Value {
+    let value = match value {
+        AttrValue::Str(s) => json!({ "stringValue": s }),
+        // OTLP/JSON carries 64-bit integers as strings.
+        AttrValue::Int(i) => json!({ "intValue": i.to_string() }),
+        AttrValue::Bool(b) => json!({ "boolValue": b }),
+    };
+    json!({ "key": key, "value": value })
+}
+
+/// Span name for an HTTP request. Path segments that look like ids (UUIDs,
+/// addresses, hashes) become `{id}` so names stay low-cardinality.
+pub fn route_name(method: &str, path: &str) -> String {
+    let route: Vec<&str> = path
+        .split('/')
+        .map(|seg| {
+            let hexish = seg.trim_start_matches("0x");
+            if seg.len() >= 16 && hexish.chars().all(|c| c.is_ascii_hexdigit() || c == '-') {
+                "{id}"
+            } else {
+                seg
+            }
+        })
+        .collect();
+    format!("{} {}", method, route.join("/"))
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Histogram {
+    counts: [u64; BUCKETS_MS.len() + 1],
+    sum_ms: f64,
+    errors: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, ms: f64, error: bool) {
+        let i = BUCKETS_MS
+            .iter()
+            .position(|&b| ms <= b)
+            .unwrap_or(BUCKETS_MS.len());
+        self.counts[i] += 1;
+        self.sum_ms += ms;
+        if error {
+            self.errors += 1;
+        }
+    }
+
+    fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+}
+
+type SeriesKey = (String, Option<String>);
+/// Gauge name and its attributes.
+type GaugeKey = (&'static str, Vec<(&'static str, String)>);
+
+struct Exporter {
+    config: OtelConfig,
+    spans: tokio::sync::mpsc::Sender<Value>,
+    histograms: Mutex<BTreeMap<SeriesKey, Histogram>>,
+    gauges: Mutex<BTreeMap<GaugeKey, f64>>,
+    dropped: std::sync::atomic::AtomicU64,
+    started: SystemTime,
+}
+
+static EXPORTER: OnceLock<Exporter> = OnceLock::new();
+
+impl Exporter {
+    fn record(&self, span: &Span, end: SystemTime) {
+        let ms = end
+            .duration_since(span.start)
+            .unwrap_or_default()
+            .as_secs_f64()
+            * 1000.0;
+        self.histograms
+            .lock()
+            .unwrap()
+            .entry((span.name.clone(), span.command_attr()))
+            .or_default()
+            .observe(ms, span.error.is_some());
+        if span.context.sampled && self.spans.try_send(span.to_otlp(end)).is_err() {
+            self.dropped
+                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        }
+    }
+
+    fn resource(&self) -> Value {
+        json!({
+            "attributes": [
+                attribute("service.name", &self.config.service_name.as_str().into()),
+                attribute("service.version", &env!("CARGO_PKG_VERSION").into()),
+            ]
+        })
+    }
+
+    fn scope() -> Value {
+        json!({ "name": "kms", "version": env!("CARGO_PKG_VERSION") })
+    }
+
+    fn traces_body(&self, spans: Vec<Value>) -> Value {
+        json!({
+            "resourceSpans": [{
+                "resource": self.resource(),
+                "scopeSpans": [{ "scope": Self::scope(), "spans": spans }],
+            }]
+        })
+    }
+
+    fn metrics_body(&self, now: SystemTime) -> Value {
+        let histograms = self.histograms.lock().unwrap().clone();
+        let start = unix_nanos(self.started).to_string();
+        let now = unix_nanos(now).to_string();
+        let mut points = Vec::new();
+        for ((name, command), h) in &histograms {
+            let mut attributes = vec![attribute("span.name", &name.as_str().into())];
+            if let Some(command) = command {
+                attributes.push(attribute("kms.ta.command", &command.as_str().into()));
+            }
+            points.push(json!({
+                "attributes": attributes,
+                "startTimeUnixNano": start,
+                "timeUnixNano": now,
+                "count": h.count().to_string(),
+                "sum": h.sum_ms,
+                "bucketCounts": h.counts.iter().map(|c| c.to_string()).collect::<Vec<_>>(),
+                "explicitBounds": BUCKETS_MS.to_vec(),
+            }));
+        }
+        let errors: Vec<Value> = histograms
+            .iter()
+            .map(|((name, command), h)| {
+                let mut attributes = vec![attribute("span.name", &name.as_str().into())];
+                if let Some(command) = command {
+                    attributes.push(attribute("kms.ta.command", &command.as_str().into()));
+                }
+                json!({
+                    "attributes": attributes,
+                    "startTimeUnixNano": start,
+                    "timeUnixNano": now,
+                    "asInt": h.errors.to_string(),
+                })
+            })
+            .collect();
+        // Cumulative temporality (2): each export repeats the totals since start.
+        let mut metrics = vec![
+            json!({
+                "name": "kms.span.duration",
+                "unit": "ms",
+                "histogram": { "aggregationTemporality": 2, "dataPoints": points },
+            }),
+            json!({
+                "name": "kms.span.errors",
+                "unit": "1",
+                "sum": {
+                    "aggregationTemporality": 2,
+                    "isMonotonic": true,
+                    "dataPoints": errors,
+                },
+            }),
+        ];
+        metrics.extend(gauge_metrics(&self.gauges.lock().unwrap(), &now));
+        json!({
+            "resourceMetrics": [{
+                "resource": self.resource(),
+                "scopeMetrics": [{ "scope": Self::scope(), "metrics": metrics }],
+            }]
+        })
+    }
+
+    async fn post(&self, path: &str, body: &Value) -> Result<()> {
+        use warp::hyper::{Body, Client, Request};
+        let mut req = Request::post(format!("{}{}", self.config.endpoint, path))
+            .header("content-type", "application/json");
+        for (k, v) in &self.config.headers {
+            req = req.header(k.as_str(), v.as_str());
+        }
+        let req = req.body(Body::from(body.to_string()))?;
+        let resp = tokio::time::timeout(Duration::from_secs(10), Client::new().request(req))
+            .await
+            .map_err(|_| anyhow!("collector did not answer within 10s"))??;
+        if !resp.status().is_success() {
+            bail!("collector returned HTTP {}", resp.status());
+        }
+        Ok(())
+    }
+}
+
+/// One OTLP gauge per name, carrying the last value of each series.
+fn gauge_metrics(gauges: &BTreeMap<GaugeKey, f64>, now: &str) -> Vec<Value> {
+    let mut by_name: BTreeMap<&str, Vec<Value>> = BTreeMap::new();
+    for ((name, attributes), value) in gauges {
+        let attributes: Vec<Value> = attributes
+            .iter()
+            .map(|(k, v)| attribute(k, &v.as_str().into()))
+            .collect();
+        by_name.entry(name).or_default().push(json!({
+            "attributes": attributes,
+            "timeUnixNano": now,
+            "asDouble": value,
+        }));
+    }
+    by_name
+        .into_iter()
+        .map(|(name, points)| json!({ "name": name, "gauge": { "dataPoints": points } }))
+        .collect()
+}
+
+/// Record the current value of a gauge series, exported with the next
+/// metrics push. Inert while export is off.
+pub fn set_gauge(name: &'static str, attributes: &[(&'static str, &str)], value: f64) {
+    if let Some(exporter) = EXPORTER.get() {
+        let attributes = attributes
+            .iter()
+            .map(|(k, v)| (*k, v.to_string()))
+            .collect();
+        exporter
+            .gauges
+            .lock()
+            .unwrap()
+            .insert((name, attributes), value);
+    }
+}
+
+/// Install the exporter and start its background task. Call once, from
+/// inside the tokio runtime; later calls are ignored.
+pub fn init(config: OtelConfig) {
+    let (tx, mut rx) = tokio::sync::mpsc::channel(QUEUE_CAPACITY);
+    let exporter = Exporter {
+        config,
+        spans: tx,
+        histograms: Mutex::new(BTreeMap::new()),
+        gauges: Mutex::new(BTreeMap::new()),
+        dropped: std::sync::atomic::AtomicU64::new(0),
+        started: SystemTime::now(),
+    };
+    if EXPORTER.set(exporter).is_err() {
+        return;
+    }
+    tokio::spawn(async move {
+        let exporter = EXPORTER.get().expect("set above");
+        let mut flush = tokio::time::interval(TRACE_FLUSH);
+        let mut metrics = tokio::time::interval(METRICS_INTERVAL);
+        let mut batch = Vec::with_capacity(BATCH_SIZE);
+        // Log the first failure of a streak only; a collector outage must not
+        // flood the CA log.
+        let mut failing = false;
+        let mut report = |what: &str, result: Result<()>| match result {
+            Ok(()) => failing = false,
+            Err(e) if !failing => {
+                failing = true;
+                eprintln!("⚠️  OTLP {} export failed: {}", what, e);
+            }
+            Err(_) => {}
+        };
+        loop {
+            tokio::select! {
+                span = rx.recv() => match span {
+                    Some(span) => {
+                        batch.push(span);
+                        if batch.len() < BATCH_SIZE {
+                            continue;
+                        }
+                    }
+                    None => return,
+                },
+                _ = flush.tick() => {}
+                _ = metrics.tick() => {
+                    let body = exporter.metrics_body(SystemTime::now());
+                    report("metrics", exporter.post("/v1/metrics", &body).await);
+                    continue;
+                }
+            }
+            if !batch.is_empty() {
+                let body = exporter.traces_body(std::mem::take(&mut batch));
+                report("trace", exporter.post("/v1/traces", &body).await);
+            }
+        }
+    });
+}
+
+/// Spans dropped because the export queue was full.
+pub fn dropped_spans() -> u64 {
+    EXPORTER
+        .get()
+        .map_or(0, |e| e.dropped.load(std::sync::atomic::Ordering::Relaxed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn traceparent_roundtrip_and_sampling() {
+        let h = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        let ctx = TraceContext::from_traceparent(h).unwrap();
+        assert!(ctx.sampled);
+        assert_eq!(ctx.traceparent(), h);
+        for bad in [
+            "01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+            "00-4bf92f3577b34da6a3ce929d0e0e47-00f067aa0ba902b7-01",
+        ] {
+            assert!(TraceContext::from_traceparent(bad).is_none(), "{}", bad);
+        }
+
+        // A child keeps the trace and the sampling decision.
+        let child = Span::start("ta.invoke", SpanKind::Client, Some(ctx));
+        assert_eq!(child.context().trace_id, ctx.trace_id);
+        assert_ne!(child.context().span_id, ctx.span_id);
+        assert!(child.context().sampled);
+
+        let id = ctx.trace_id;
+        assert!(sample(&id, 1.0));
+        assert!(!sample(&id, 0.0));
+        let kept = (0..2000).filter(|_| sample(&random_bytes(), 0.25)).count();
+        assert!((350..650).contains(&kept), "{}", kept);
+    }
+
+    #[test]
+    fn otlp_json_shapes() {
+        let parent = TraceContext::from_traceparent(
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+        );
+        let start = UNIX_EPOCH + Duration::from_millis(1_700_000_000_000);
+        let mut span = Span::start_at("ta.invoke", SpanKind::Client, parent, start);
+        span.attributes
+            .push(("kms.ta.command_id", AttrValue::Int(5)));
+        span.set_error("TA command failed");
+        let v = span.to_otlp(start + Duration::from_millis(12));
+        assert_eq!(v["traceId"], "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(v["parentSpanId"], "00f067aa0ba902b7");
+        assert_eq!(v["kind"], 3);
+        assert_eq!(v["startTimeUnixNano"], "1700000000000000000");
+        assert_eq!(v["endTimeUnixNano"], "1700000000012000000");
+        assert_eq!(v["attributes"][0]["value"]["intValue"], "5");
+        assert_eq!(v["status"]["code"], 2);
+
+        let mut h = Histogram::default();
+        h.observe(0.5, false);
+        h.observe(12.0, true);
+        h.observe(60_000.0, false);
+        assert_eq!(h.counts[0], 1);
+        assert_eq!(h.counts[4], 1);
+        assert_eq!(h.counts[BUCKETS_MS.len()], 1);
+        assert_eq!((h.count(), h.errors), (3, 1));
+
+        let mut gauges = BTreeMap::new();
+        gauges.insert(
+            ("kms.gas_tank.balance", vec![("chain.id", "1".to_string())]),
+            2.5,
+        );
+        gauges.insert(
+            ("kms.gas_tank.balance", vec![("chain.id", "10".to_string())]),
+            0.0,
+        );
+        gauges.insert(
+            ("kms.gas_tank.low", vec![("chain.id", "10".to_string())]),
+            1.0,
+        );
+        let g = gauge_metrics(&gauges, "1700000000000000000");
+        assert_eq!(g.len(), 2);
+        assert_eq!(g[0]["name"], "kms.gas_tank.balance");
+        assert_eq!(g[0]["gauge"]["dataPoints"][0]["asDouble"], 2.5);
+        assert_eq!(
+            g[0]["gauge"]["dataPoints"][1]["attributes"][0]["value"]["stringValue"],
+            "10"
+        );
+    }
+
+    #[test]
+    fn config_and_route_names() {
+        let c = OtelConfig::new(
+            "http://collector:4318/".into(),
+            Some("0.1"),
+            None,
+            Some("authorization=Bearer t, x-team=kms"),
+        )
+        .unwrap();
+        assert_eq!(c.endpoint, "http://collector:4318");
+        assert_eq!(c.service_name, "airaccount-kms");
+        assert_eq!(c.headers[1], ("x-team".into(), "kms".into()));
+        assert!(OtelConfig::new("https://c".into(), None, None, None).is_err());
+        assert!(OtelConfig::new("http://c".into(), Some("2"), None, None).is_err());
+
+        assert_eq!(route_name("POST", "/kms/otp/code"), "POST /kms/otp/code");
+        assert_eq!(
+            route_name("GET", "/contact/0x742d35Cc6634C0532925a3b844Bc9e7595f2bD18"),
+            "GET /contact/{id}"
+        );
+        assert_eq!(
+            route_name("GET", "/wallet/4319f351-0b24-4097-b659-80ee4f824cdd/events"),
+            "GET /wallet/{id}/events"
+        );
+    }
+}
@@ -0,0 +1,205 @@
+//! Per-wallet lockout after repeated passkey assertion failures (process-in-memory).
+//!
+//! The API-key limiter (rate_limit.rs) bounds how often a caller may ask; this
+//! bounds how often one wallet's passkey may be guessed at. After
+//! `KMS_PASSKEY_LOCKOUT_THRESHOLD` consecutive failed assertions (default 5,
+//! 0 disables) the wallet is locked for `KMS_PASSKEY_LOCKOUT_SECS` (default
+//! 60); every further failure doubles the lock, up to an hour. A verified
+//! assertion clears the count, and a count idle for an hour starts over.
+//!
+//! **Limitations**: same as the rate limiter — state is process-local and
+//! resets on restart. The TA still verifies every assertion; this only stops
+//! the CA from forwarding a burst of bad ones.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const DEFAULT_THRESHOLD: u32 = 5;
+const DEFAULT_LOCKOUT_SECS: u64 = 60;
+const MAX_LOCKOUT: Duration = Duration::from_secs(3600);
+/// A failure count with no new failure for this long is forgotten.
+const IDLE_RESET: Duration = Duration::from_secs(3600);
+/// Same default cap as the rate limiter (KMS_RATE_LIMIT_MAX_KEYS).
+const DEFAULT_MAX_TRACKED_KEYS: usize = 10_000;
+
+struct Entry {
+    failures: u32,
+    last_failure: Instant,
+    locked_until: Option<Instant>,
+}
+
+#[derive(Clone)]
+pub struct PasskeyLockout {
+    entries: Arc<Mutex<HashMap<String, Entry>>>,
+    threshold: u32,
+    base: Duration,
+    max_keys: usize,
+}
+
+impl PasskeyLockout {
+    pub fn new(threshold: u32, base: Duration, max_keys: usize) -> Self {
+        Self {
+            entries: Arc::new(Mutex::new(HashMap::new())),
+            threshold,
+            base,
+            max_keys,
+        }
+    }
+
+    pub fn from_env() -> Self {
+        let threshold = std::env::var("KMS_PASSKEY_LOCKOUT_THRESHOLD")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(DEFAULT_THRESHOLD);
+        let secs = std::env::var("KMS_PASSKEY_LOCKOUT_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(DEFAULT_LOCKOUT_SECS);
+        let max_keys = std::env::var("KMS_RATE_LIMIT_MAX_KEYS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(DEFAULT_MAX_TRACKED_KEYS);
+        let base = Duration::from_secs(secs).min(MAX_LOCKOUT);
+        Self::new(threshold, base, max_keys)
+    }
+
+    pub fn threshold(&self) -> u32 {
+        self.threshold
+    }
+
+    /// Ok if `key` may try an assertion, Err(time left) while it is locked.
+    pub fn check(&self, key: &str) -> Result<(), Duration> {
+        self.check_at(key, Instant::now())
+    }
+
+    /// Count a failed assertion. Returns the lock it started, if any.
+    pub fn record_failure(&self, key: &str) -> Option<Duration> {
+        self.record_failure_at(key, Instant::now())
+    }
+
+    pub fn record_success(&self, key: &str) {
+        self.entries.lock().unwrap().remove(key);
+    }
+
+    fn check_at(&self, key: &str, now: Instant) -> Result<(), Duration> {
+        match self.entries.lock().unwrap().get(key) {
+            Some(Entry {
+                locked_until: Some(until),
+                ..
+            }) if *until > now => Err(*until - now),
+            _ => Ok(()),
+        }
+    }
+
+    fn record_failure_at(&self, key: &str, now: Instant) -> Option<Duration> {
+        if self.threshold == 0 {
+            return None;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        if !entries.contains_key(key) && entries.len() >= self.max_keys {
+            // Only wallets that exist get this far, so the map is bounded by
+            // the wallet count; the sweep keeps it below the cap regardless.
+            entries.retain(|_, e| !Self::forgotten(e, now));
+            if entries.len() >= self.max_keys {
+                eprintln!(
+                    "⚠️  passkey-lockout: key cap ({}) reached, not tracking {}",
+                    self.max_keys, key
+                );
+                return None;
+            }
+        }
+        let entry = entries.entry(key.to_string()).or_insert(Entry {
+            failures: 0,
+            last_failure: now,
+            locked_until: None,
+        });
+        if Self::forgotten(entry, now) {
+            entry.failures = 0;
+        }
+        entry.failures += 1;
+        entry.last_failure = now;
+        if entry.failures < self.threshold {
+            return None;
+        }
+        let doublings = (entry.failures - self.threshold).min(16);
+        // Checked: a panic here would poison the lock for every later call.
+        let lock = self
+            .base
+            .checked_mul(1 << doublings)
+            .unwrap_or(MAX_LOCKOUT)
+            .min(MAX_LOCKOUT);
+        entry.locked_until = Some(now + lock);
+        Some(lock)
+    }
+
+    fn forgotten(entry: &Entry, now: Instant) -> bool {
+        let unlocked = !matches!(entry.locked_until, Some(until) if until > now);
+        unlocked && now.duration_since(entry.last_failure) >= IDLE_RESET
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lockout() -> PasskeyLockout {
+        PasskeyLockout::new(3, Duration::from_secs(60), 100)
+    }
+
+    #[test]
+    fn locks_at_the_threshold_and_doubles() {
+        let l = lockout();
+        let t = Instant::now();
+        assert_eq!(l.record_failure_at("w", t), None);
+        assert_eq!(l.record_failure_at("w", t), None);
+        assert!(l.check_at("w", t).is_ok());
+        assert_eq!(l.record_failure_at("w", t), Some(Duration::from_secs(60)));
+        assert_eq!(l.check_at("w", t), Err(Duration::from_secs(60)));
+        assert!(l.check_at("other", t).is_ok());
+
+        let later = t + Duration::from_secs(61);
+        assert!(l.check_at("w", later).is_ok());
+        assert_eq!(
+            l.record_failure_at("w", later),
+            Some(Duration::from_secs(120))
+        );
+        for _ in 0..10 {
+            l.record_failure_at("w", later);
+        }
+        assert_eq!(l.check_at("w", later), Err(MAX_LOCKOUT));
+    }
+
+    #[test]
+    fn success_and_idle_time_clear_the_count() {
+        let l = lockout();
+        let t = Instant::now();
+        l.record_failure_at("w", t);
+        l.record_failure_at("w", t);
+        l.record_success("w");
+        assert_eq!(l.record_failure_at("w", t), None);
+        l.record_failure_at("w", t);
+        assert_eq!(l.record_failure_at("w", t + IDLE_RESET), None);
+    }
+
+    #[test]
+    fn a_huge_base_saturates_instead_of_overflowing() {
+        let l = PasskeyLockout::new(1, Duration::from_secs(u64::MAX), 100);
+        let t = Instant::now();
+        assert_eq!(l.record_failure_at("w", t), Some(MAX_LOCKOUT));
+        for _ in 0..20 {
+            l.record_failure_at("w", t);
+        }
+        assert_eq!(l.check_at("w", t), Err(MAX_LOCKOUT));
+    }
+
+    #[test]
+    fn zero_threshold_disables() {
+        let l = PasskeyLockout::new(0, Duration::from_secs(60), 100);
+        let t = Instant::now();
+        for _ in 0..10 {
+            assert_eq!(l.record_failure_at("w", t), None);
+        }
+        assert!(l.check_at("w", t).is_ok());
+    }
+}
@@ -0,0 +1,269 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! ERC-20 gas payment — fetching paymaster quotes and their JSON form.
+//!
+//! `POST /kms/fee/quote` asks the paymaster at `KMS_PAYMASTER_URL` for a
+//! quote (JSON-RPC `pm_getTokenQuote`) and records it in `fee_payments`;
+//! SignHash with `FeeQuoteHash` hands it to the TA, which refuses it if it
+//! expired or is for another chain and binds the passkey to
+//! `proto::paymaster::fee_review_digest`. Settlement is reported back with
+//! `POST /kms/fee/settlement`.
+//!
+//! Like the chain watcher, the CA has no TLS stack: the paymaster URL must be
+//! `http://` (a local bundler/paymaster or a TLS-terminating proxy).
+
+use anyhow::{anyhow, bail, Context, Result};
+use proto::calldata::hex_addr;
+use proto::eip55::parse_address;
+use proto::paymaster::FeeQuote;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::db::FeePaymentRow;
+use crate::permit::parse_word;
+
+/// How long a fresh quote must still be valid to be worth confirming.
+pub const MIN_QUOTE_LIFETIME_SECS: u64 = 30;
+
+#[derive(Debug, Clone)]
+pub struct PaymasterConfig {
+    pub url: String,
+}
+
+impl PaymasterConfig {
+    /// `KMS_PAYMASTER_URL`; None when unset.
+    pub fn from_env() -> Option<Result<Self>> {
+        let url = std::env::var("KMS_PAYMASTER_URL")
+            .ok()
+            .filter(|v| !v.is_empty())?;
+        Some(Self::new(url))
+    }
+
+    pub fn new(url: String) -> Result<Self> {
+        if !url.starts_with("http://") {
+            bail!("KMS_PAYMASTER_URL must be http:// (no TLS in the CA)");
+        }
+        Ok(Self { url })
+    }
+}
+
+/// A [`FeeQuote`] as JSON: addresses 0x-hex, amounts decimal strings.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct FeeQuoteJson {
+    pub chain_id: u64,
+    pub paymaster: String,
+    pub token: String,
+    /// Token base units per 10^18 wei of gas (decimal or 0x-hex).
+    pub exchange_rate: String,
+    pub max_token_cost: String,
+    pub valid_until: u64,
+    /// 0x + 32 bytes.
+    pub quote_id: String,
+}
+
+/// Decimal or 0x-hex that must fit in 128 bits.
+pub fn parse_u128(field: &str, v: &str) -> Result<u128> {
+    let w = parse_word(field, v)?;
+    if w[..16].iter().any(|&b| b != 0) {
+        bail!("{} must fit in uint128", field);
+    }
+    let mut lo = [0u8; 16];
+    lo.copy_from_slice(&w[16..]);
+    Ok(u128::from_be_bytes(lo))
+}
+
+impl FeeQuoteJson {
+    pub fn to_proto(&self) -> Result<FeeQuote> {
+        let address =
+            |field: &str, v: &str| parse_address(v).map_err(|e| anyhow!("quote {}: {}", field, e));
+        let id = hex::decode(self.quote_id.trim_start_matches("0x"))
+            .ok()
+            .filter(|b| b.len() == 32)
+            .ok_or_else(|| anyhow!("quote quoteId must be 32 bytes of hex"))?;
+        let mut quote_id = [0u8; 32];
+        quote_id.copy_from_slice(&id);
+        Ok(FeeQuote {
+            chain_id: self.chain_id,
+            paymaster: address("paymaster", &self.paymaster)?,
+            token: address("token", &self.token)?,
+            exchange_rate: parse_u128("exchangeRate", &self.exchange_rate)?,
+            max_token_cost: parse_u128("maxTokenCost", &self.max_token_cost)?,
+            valid_until: self.valid_until,
+            quote_id,
+        })
+    }
+
+    pub fn from_proto(q: &FeeQuote) -> Self {
+        Self {
+            chain_id: q.chain_id,
+            paymaster: hex_addr(&q.paymaster),
+            token: hex_addr(&q.token),
+            exchange_rate: q.exchange_rate.to_string(),
+            max_token_cost: q.max_token_cost.to_string(),
+            valid_until: q.valid_until,
+            quote_id: format!("0x{}", hex::encode(q.quote_id)),
+        }
+    }
+}
+
+/// Check a paymaster's answer against what was asked: same chain and token,
+/// valid long enough to confirm, and a cap that covers `max_gas_cost_wei` at
+/// the quoted rate (otherwise the UserOperation could fail at execution).
+pub fn check_quote(
+    quote: &FeeQuote,
+    chain_id: u64,
+    token: &[u8; 20],
+    max_gas_cost_wei: u128,
+    now: u64,
+) -> Result<()> {
+    quote
+        .validate(chain_id, now + MIN_QUOTE_LIFETIME_SECS)
+        .map_err(|e| anyhow!("paymaster quote rejected: {}", e))?;
+    if &quote.token != token {
+        bail!("paymaster quote rejected: quoted a different token");
+    }
+    let cost = quote
+        .token_cost(max_gas_cost_wei)
+        .ok_or_else(|| anyhow!("paymaster quote rejected: token cost overflows"))?;
+    if cost > quote.max_token_cost {
+        bail!(
+            "paymaster quote rejected: cap {} is below the {} the gas limit costs",
+            quote.max_token_cost,
+            cost
+        );
+    }
+    Ok(())
+}
+
+/// Ask the paymaster for a quote. The caller runs [`check_quote`].
+pub async fn fetch_quote(
+    config: &PaymasterConfig,
+    chain_id: u64,
+    token: &[u8; 20],
+    max_gas_cost_wei: u128,
+) -> Result<FeeQuote> {
+    use warp::hyper::{body, Body, Client, Request};
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "pm_getTokenQuote",
+        "params": [{
+            "chainId": chain_id,
+            "token": hex_addr(token),
+            "maxGasCostWei": max_gas_cost_wei.to_string(),
+        }],
+    });
+    let req = Request::post(config.url.as_str())
+        .header("content-type", "application/json")
+        .body(Body::from(request.to_string()))?;
+    let resp = tokio::time::timeout(
+        std::time::Duration::from_secs(10),
+        Client::new().request(req),
+    )
+    .await
+    .map_err(|_| anyhow!("paymaster did not answer within 10s"))?
+    .context("paymaster unreachable")?;
+    let status = resp.status();
+    let bytes = body::to_bytes(resp.into_body()).await?;
+    if !status.is_success() {
+        bail!("paymaster returned HTTP {}", status);
+    }
+    let reply: Value = serde_json::from_slice(&bytes).context("paymaster reply is not JSON")?;
+    if let Some(err) = reply.get("error") {
+        bail!("paymaster error: {}", err);
+    }
+    let quote: FeeQuoteJson = serde_json::from_value(reply["result"].clone())
+        .context("paymaster reply has no valid quote")?;
+    quote.to_proto()
+}
+
+/// The paymaster took more than the cap the user confirmed.
+pub fn overcharged(row: &FeePaymentRow) -> bool {
+    matches!(row.actual_token_cost, Some(c) if c > row.entry.max_token_cost)
+}
+
+/// A `fee_payments` row as the API returns it.
+pub fn fee_payment_json(row: &FeePaymentRow) -> Value {
+    let e = &row.entry;
+    json!({
+        "quoteHash": e.quote_hash,
+        "keyId": e.key_id,
+        "chainId": e.chain_id,
+        "paymaster": e.paymaster,
+        "token": e.token,
+        "maxTokenCost": e.max_token_cost.to_string(),
+        "quote": serde_json::from_str::<Value>(&e.quote).unwrap_or(Value::Null),
+        "userOpHash": row.user_op_hash,
+        "status": row.status,
+        "actualTokenCost": row.actual_token_cost.map(|c| c.to_string()),
+        "overcharged": overcharged(row),
+        "txHash": row.tx_hash,
+        "createdAt": row.created_at,
+        "updatedAt": row.updated_at,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quote_json() -> FeeQuoteJson {
+        FeeQuoteJson {
+            chain_id: 10,
+            paymaster: "0x1111111111111111111111111111111111111111".into(),
+            token: "0x2222222222222222222222222222222222222222".into(),
+            exchange_rate: "0xb2d05e00".into(),
+            max_token_cost: "5000000".into(),
+            valid_until: 1_800_000_000,
+            quote_id: format!("0x{}", "07".repeat(32)),
+        }
+    }
+
+    #[test]
+    fn quote_json_roundtrips_through_proto() {
+        let q = quote_json().to_proto().unwrap();
+        assert_eq!(q.exchange_rate, 3_000_000_000);
+        let back = FeeQuoteJson::from_proto(&q);
+        assert_eq!(back.exchange_rate, "3000000000");
+        assert_eq!(back.to_proto().unwrap(), q);
+
+        let mut bad = quote_json();
+        bad.quote_id = "0x07".into();
+        assert!(bad.to_proto().is_err());
+        bad = quote_json();
+        bad.max_token_cost = format!("0x{}", "ff".repeat(17));
+        assert!(bad.to_proto().is_err());
+    }
+
+    #[test]
+    fn check_quote_refuses_what_was_not_asked_for() {
+        let q = quote_json().to_proto().unwrap();
+        let token = q.token;
+        let now = 1_800_000_000 - 60;
+        // 0.001 ETH at 3000 USDC/ETH = 3 USDC, under the 5 USDC cap.
+        assert!(check_quote(&q, 10, &token, 1_000_000_000_000_000, now).is_ok());
+        assert!(check_quote(&q, 1, &token, 1_000_000_000_000_000, now).is_err());
+        assert!(check_quote(&q, 10, &[0x33; 20], 1_000_000_000_000_000, now).is_err());
+        // 0.002 ETH would cost 6 USDC: over the cap.
+        assert!(check_quote(&q, 10, &token, 2_000_000_000_000_000, now).is_err());
+        // Expires within the confirmation window.
+        assert!(check_quote(&q, 10, &token, 1, 1_800_000_000 - 10).is_err());
+        assert!(PaymasterConfig::new("https://pm.example".into()).is_err());
+    }
+}
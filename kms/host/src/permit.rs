@@ -0,0 +1,188 @@
+//! EIP-2612 / Permit2 — the JSON form of a permit.
+//!
+//! `POST /kms/DescribePermit` and `POST /kms/SignPermit` both take a
+//! [`PermitJson`]; the review string and digests come from
+//! `proto::permit`, the same code the TA runs at sign time, so a preview
+//! is exactly what the passkey will be asked to confirm.
+
+use anyhow::{anyhow, Result};
+use proto::calldata::Word;
+use proto::eip55::parse_address;
+use proto::permit::{Erc2612Permit, Permit2Single, Permit2Transfer, PermitRequest};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PermitJson {
+    /// "erc2612" | "permit2-single" | "permit2-transfer"
+    pub kind: String,
+    pub chain_id: u64,
+    pub token: String,
+    pub spender: String,
+    /// Decimal or 0x-hex, up to uint256 ("max" = all ones).
+    pub amount: String,
+    pub nonce: String,
+    /// Signature deadline, unix seconds (decimal, 0x-hex or "max").
+    pub deadline: String,
+    /// erc2612: the signing address.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    /// erc2612: the token's EIP-712 domain name and version.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_version: Option<String>,
+    /// permit2-single: allowance expiry, unix seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expiration: Option<u64>,
+}
+
+fn address(field: &str, v: &str) -> Result<[u8; 20]> {
+    parse_address(v).map_err(|e| anyhow!("{}: {}", field, e))
+}
+
+/// Decimal (up to u128), 0x-hex (up to 32 bytes) or "max".
+pub fn parse_word(field: &str, v: &str) -> Result<Word> {
+    let v = v.trim();
+    let mut w = [0u8; 32];
+    if v == "max" {
+        return Ok([0xff; 32]);
+    }
+    if let Some(h) = v.strip_prefix("0x") {
+        let h = if h.len() % 2 == 1 {
+            format!("0{}", h)
+        } else {
+            h.to_string()
+        };
+        let bytes = hex::decode(&h).map_err(|_| anyhow!("{} is not hex: {}", field, v))?;
+        if bytes.len() > 32 {
+            return Err(anyhow!("{} exceeds uint256", field));
+        }
+        w[32 - bytes.len()..].copy_from_slice(&bytes);
+    } else {
+        let n: u128 = v
+            .parse()
+            .map_err(|_| anyhow!("{} must be decimal, 0x-hex or \"max\": {}", field, v))?;
+        w[16..].copy_from_slice(&n.to_be_bytes());
+    }
+    Ok(w)
+}
+
+fn narrow(field: &str, w: &Word, bits: u32) -> Result<u64> {
+    let mut lo = [0u8; 8];
+    lo.copy_from_slice(&w[24..]);
+    let n = u64::from_be_bytes(lo);
+    if w[..24].iter().any(|&b| b != 0) || n >= 1u64 << bits {
+        return Err(anyhow!("{} must fit in uint{}", field, bits));
+    }
+    Ok(n)
+}
+
+impl PermitJson {
+    pub fn to_proto(&self) -> Result<PermitRequest> {
+        let token = address("token", &self.token)?;
+        let spender = address("spender", &self.spender)?;
+        let amount = parse_word("amount", &self.amount)?;
+        let nonce = parse_word("nonce", &self.nonce)?;
+        let deadline = parse_word("deadline", &self.deadline)?;
+        let permit = match self.kind.as_str() {
+            "erc2612" => PermitRequest::Erc2612(Erc2612Permit {
+                chain_id: self.chain_id,
+                token,
+                token_name: self
+                    .token_name
+                    .clone()
+                    .ok_or_else(|| anyhow!("erc2612 permit requires tokenName"))?,
+                token_version: self.token_version.clone().unwrap_or_else(|| "1".into()),
+                owner: address(
+                    "owner",
+                    self.owner
+                        .as_deref()
+                        .ok_or_else(|| anyhow!("erc2612 permit requires owner"))?,
+                )?,
+                spender,
+                value: amount,
+                nonce,
+                deadline,
+            }),
+            "permit2-single" => PermitRequest::Permit2Single(Permit2Single {
+                chain_id: self.chain_id,
+                token,
+                // "max" means type(uint160).max here.
+                amount: if amount == [0xff; 32] {
+                    let mut w = [0u8; 32];
+                    w[12..].copy_from_slice(&[0xff; 20]);
+                    w
+                } else {
+                    amount
+                },
+                expiration: self
+                    .expiration
+                    .ok_or_else(|| anyhow!("permit2-single requires expiration"))?,
+                nonce: narrow("nonce", &nonce, 48)?,
+                spender,
+                sig_deadline: deadline,
+            }),
+            "permit2-transfer" => PermitRequest::Permit2Transfer(Permit2Transfer {
+                chain_id: self.chain_id,
+                token,
+                amount,
+                spender,
+                nonce,
+                deadline,
+            }),
+            other => {
+                return Err(anyhow!(
+                    "unknown permit kind {:?} (erc2612 | permit2-single | permit2-transfer)",
+                    other
+                ))
+            }
+        };
+        permit.validate().map_err(|e| anyhow!("{}", e))?;
+        Ok(permit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn permit(json: &str) -> Result<PermitRequest> {
+        serde_json::from_str::<PermitJson>(json).unwrap().to_proto()
+    }
+
+    #[test]
+    fn permit_from_json() {
+        let p = permit(
+            r#"{"kind":"permit2-single","chainId":1,
+                "token":"0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48",
+                "spender":"0x1111111111111111111111111111111111111111",
+                "amount":"max","nonce":"7","deadline":"1700000000","expiration":1700086400}"#,
+        )
+        .unwrap();
+        match &p {
+            PermitRequest::Permit2Single(s) => {
+                assert_eq!(s.nonce, 7);
+                assert_eq!(s.amount[..12], [0u8; 12]);
+            }
+            other => panic!("{:?}", other),
+        }
+        assert!(p.summary().contains("USDC unlimited"), "{}", p.summary());
+
+        // erc2612 needs the owner and the token's domain name.
+        assert!(permit(
+            r#"{"kind":"erc2612","chainId":1,"token":"0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48",
+                "spender":"0x1111111111111111111111111111111111111111",
+                "amount":"0x01","nonce":"0","deadline":"max"}"#
+        )
+        .is_err());
+        assert!(permit(
+            r#"{"kind":"permit2-batch","chainId":1,"token":"0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48",
+                "spender":"0x1111111111111111111111111111111111111111",
+                "amount":"1","nonce":"0","deadline":"1"}"#
+        )
+        .is_err());
+        assert_eq!(parse_word("n", "0x1").unwrap()[31], 1);
+        assert!(parse_word("n", &format!("0x{}", "00".repeat(33))).is_err());
+    }
+}
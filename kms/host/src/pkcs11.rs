@@ -0,0 +1,706 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! PKCS#11 signer — wallets whose secp256k1 key lives in an HSM.
+//!
+//! The vendor module (`KMS_PKCS11_MODULE`, e.g. SoftHSM's
+//! `libsofthsm2.so`) is loaded at startup; one session on the token in
+//! `KMS_PKCS11_SLOT` (default: the first slot with a token) is logged in
+//! with `KMS_PKCS11_PIN` and shared, one call at a time. A wallet is one key
+//! pair found by `CKA_LABEL` and answers for one derivation path only: the
+//! HSM holds a key, not a seed. The token signs the digest with `CKM_ECDSA`
+//! and the Ethereum glue (low-S, recovery id, EIP-155 encoding) is done
+//! here.
+//!
+//! Passkeys: the HSM does not check assertions, so the CA is the only gate.
+//! It verifies the assertion with the challenge held to the one-time nonce
+//! (`binds_passkey_to_payload` is false); clients sign the bare nonce for
+//! these wallets, not the payload commitment the TA accepts.
+//!
+//! Only the C_* functions listed in [`FunctionList`] are used.
+
+use anyhow::{anyhow, Context, Result};
+use k256::ecdsa::{RecoveryId, Signature, VerifyingKey};
+use libc::{c_ulong, c_void};
+use proto::eip1559::{signed_rlp, Eip1559Transaction, SignedEip1559Tx};
+use proto::offline::{legacy_signing_hash, signed_legacy_rlp, SignedLegacyTx};
+use proto::paymaster::FeeQuote;
+use proto::{EthTransaction, PasskeyAssertion, SigningContext};
+use sha3::{Digest, Keccak256};
+use std::ffi::{CStr, CString};
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+use crate::db::{KmsDb, WalletSignerRow};
+use crate::signer::{SignFuture, Signer, PKCS11};
+
+#[derive(Clone)]
+pub struct Pkcs11Config {
+    pub module: String,
+    pub slot: Option<u64>,
+    pin: String,
+}
+
+impl std::fmt::Debug for Pkcs11Config {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Pkcs11Config")
+            .field("module", &self.module)
+            .field("slot", &self.slot)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Pkcs11Config {
+    /// None unless `KMS_PKCS11_MODULE` is set; then the PIN is required.
+    pub fn from_env() -> Option<Result<Self>> {
+        let var = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+        let module = var("KMS_PKCS11_MODULE")?;
+        Some(Self::parse(
+            module,
+            var("KMS_PKCS11_SLOT").as_deref(),
+            var("KMS_PKCS11_PIN"),
+        ))
+    }
+
+    pub fn parse(module: String, slot: Option<&str>, pin: Option<String>) -> Result<Self> {
+        if !module.starts_with('/') {
+            return Err(anyhow!(
+                "KMS_PKCS11_MODULE must be an absolute path, got {:?}",
+                module
+            ));
+        }
+        let slot = slot
+            .map(|s| {
+                s.parse()
+                    .map_err(|_| anyhow!("KMS_PKCS11_SLOT: not a slot id: {:?}", s))
+            })
+            .transpose()?;
+        let pin =
+            pin.ok_or_else(|| anyhow!("KMS_PKCS11_MODULE is set but KMS_PKCS11_PIN is not"))?;
+        Ok(Self { module, slot, pin })
+    }
+}
+
+// ── Cryptoki ABI (PKCS#11 v2.40, LP64: CK_ULONG = unsigned long) ──
+
+type CkRv = c_ulong;
+
+const CKR_OK: CkRv = 0;
+const CKR_USER_ALREADY_LOGGED_IN: CkRv = 0x100;
+const CKR_CRYPTOKI_ALREADY_INITIALIZED: CkRv = 0x191;
+const CKF_SERIAL_SESSION: c_ulong = 0x4;
+const CKU_USER: c_ulong = 1;
+const CKA_CLASS: c_ulong = 0x0;
+const CKA_LABEL: c_ulong = 0x3;
+const CKA_EC_POINT: c_ulong = 0x181;
+const CKO_PUBLIC_KEY: c_ulong = 2;
+const CKO_PRIVATE_KEY: c_ulong = 3;
+const CKM_ECDSA: c_ulong = 0x1041;
+
+#[repr(C)]
+struct Attribute {
+    kind: c_ulong,
+    value: *mut c_void,
+    len: c_ulong,
+}
+
+#[repr(C)]
+struct Mechanism {
+    mechanism: c_ulong,
+    parameter: *mut c_void,
+    len: c_ulong,
+}
+
+/// `CK_FUNCTION_LIST` up to C_Sign; the slots this module never calls are
+/// kept as opaque pointers so the offsets stay right.
+#[repr(C)]
+struct FunctionList {
+    version: [u8; 2],
+    initialize: unsafe extern "C" fn(*mut c_void) -> CkRv,
+    finalize: unsafe extern "C" fn(*mut c_void) -> CkRv,
+    _get_info_get_function_list: [usize; 2],
+    get_slot_list: unsafe extern "C" fn(u8, *mut c_ulong, *mut c_ulong) -> CkRv,
+    _slot_token_mechanism_pin: [usize; 7],
+    open_session:
+        unsafe extern "C" fn(c_ulong, c_ulong, *mut c_void, *mut c_void, *mut c_ulong) -> CkRv,
+    close_session: unsafe extern "C" fn(c_ulong) -> CkRv,
+    _session_state: [usize; 4],
+    login: unsafe extern "C" fn(c_ulong, c_ulong, *const u8, c_ulong) -> CkRv,
+    _logout_objects: [usize; 5],
+    get_attribute_value: unsafe extern "C" fn(c_ulong, c_ulong, *mut Attribute, c_ulong) -> CkRv,
+    _set_attribute_value: [usize; 1],
+    find_objects_init: unsafe extern "C" fn(c_ulong, *mut Attribute, c_ulong) -> CkRv,
+    find_objects: unsafe extern "C" fn(c_ulong, *mut c_ulong, c_ulong, *mut c_ulong) -> CkRv,
+    find_objects_final: unsafe extern "C" fn(c_ulong) -> CkRv,
+    _encrypt_decrypt_digest: [usize; 13],
+    sign_init: unsafe extern "C" fn(c_ulong, *mut Mechanism, c_ulong) -> CkRv,
+    sign: unsafe extern "C" fn(c_ulong, *const u8, c_ulong, *mut u8, *mut c_ulong) -> CkRv,
+}
+
+fn check(rv: CkRv, what: &str) -> Result<()> {
+    let name = match rv {
+        CKR_OK => return Ok(()),
+        0x5 => "CKR_GENERAL_ERROR",
+        0x6 => "CKR_FUNCTION_FAILED",
+        0x30 => "CKR_DEVICE_ERROR",
+        0x32 => "CKR_DEVICE_REMOVED",
+        0x60 => "CKR_KEY_HANDLE_INVALID",
+        0x70 => "CKR_MECHANISM_INVALID",
+        0xa0 => "CKR_PIN_INCORRECT",
+        0xa4 => "CKR_PIN_LOCKED",
+        0xb3 => "CKR_SESSION_HANDLE_INVALID",
+        0xe0 => "CKR_TOKEN_NOT_PRESENT",
+        0x101 => "CKR_USER_NOT_LOGGED_IN",
+        0x150 => "CKR_BUFFER_TOO_SMALL",
+        _ => "",
+    };
+    Err(anyhow!("PKCS#11 {} failed: {:#x} {}", what, rv, name))
+}
+
+/// A logged-in session on the token. Not thread-safe by itself: the signer
+/// keeps it behind a mutex.
+pub struct Pkcs11Token {
+    library: *mut c_void,
+    functions: *const FunctionList,
+    session: c_ulong,
+}
+
+// The raw pointers are only used under the signer's mutex (or by the one
+// thread of kms-admin).
+unsafe impl Send for Pkcs11Token {}
+
+impl Pkcs11Token {
+    pub fn open(config: &Pkcs11Config) -> Result<Self> {
+        let path = CString::new(config.module.as_str())?;
+        // SAFETY: dlopen/dlsym with NUL-terminated strings; the function
+        // list is the module's static table and outlives the handle we keep.
+        unsafe {
+            let library = libc::dlopen(path.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL);
+            if library.is_null() {
+                return Err(anyhow!("dlopen {}: {}", config.module, dl_error()));
+            }
+            let symbol = libc::dlsym(
+                library,
+                b"C_GetFunctionList\0".as_ptr() as *const libc::c_char,
+            );
+            if symbol.is_null() {
+                libc::dlclose(library);
+                return Err(anyhow!("{} is not a PKCS#11 module", config.module));
+            }
+            let get_function_list: unsafe extern "C" fn(*mut *const FunctionList) -> CkRv =
+                std::mem::transmute(symbol);
+            let mut functions = std::ptr::null();
+            let rv = get_function_list(&mut functions);
+            if rv != CKR_OK || functions.is_null() {
+                libc::dlclose(library);
+                check(rv, "C_GetFunctionList")?;
+                return Err(anyhow!("C_GetFunctionList returned no table"));
+            }
+            let mut token = Self {
+                library,
+                functions,
+                session: 0,
+            };
+            let f = &*functions;
+            match (f.initialize)(std::ptr::null_mut()) {
+                CKR_OK | CKR_CRYPTOKI_ALREADY_INITIALIZED => {}
+                rv => {
+                    token.functions = std::ptr::null();
+                    check(rv, "C_Initialize")?;
+                }
+            }
+            let slot = match config.slot {
+                Some(slot) => slot as c_ulong,
+                None => token.first_slot()?,
+            };
+            check(
+                (f.open_session)(
+                    slot,
+                    CKF_SERIAL_SESSION,
+                    std::ptr::null_mut(),
+                    std::ptr::null_mut(),
+                    &mut token.session,
+                ),
+                "C_OpenSession",
+            )?;
+            match (f.login)(
+                token.session,
+                CKU_USER,
+                config.pin.as_ptr(),
+                config.pin.len() as c_ulong,
+            ) {
+                CKR_OK | CKR_USER_ALREADY_LOGGED_IN => {}
+                rv => check(rv, "C_Login")?,
+            }
+            Ok(token)
+        }
+    }
+
+    fn functions(&self) -> &FunctionList {
+        // SAFETY: set once by open() from the module's table.
+        unsafe { &*self.functions }
+    }
+
+    fn first_slot(&self) -> Result<c_ulong> {
+        let f = self.functions();
+        let mut count: c_ulong = 0;
+        // SAFETY: size query, then a buffer of that size.
+        unsafe {
+            check(
+                (f.get_slot_list)(1, std::ptr::null_mut(), &mut count),
+                "C_GetSlotList",
+            )?;
+            let mut slots = vec![0 as c_ulong; count as usize];
+            check(
+                (f.get_slot_list)(1, slots.as_mut_ptr(), &mut count),
+                "C_GetSlotList",
+            )?;
+            slots
+                .first()
+                .copied()
+                .ok_or_else(|| anyhow!("PKCS#11: no slot has a token"))
+        }
+    }
+
+    /// The one object of `class` labelled `label`.
+    fn find(&self, class: c_ulong, label: &str) -> Result<c_ulong> {
+        let f = self.functions();
+        let mut class = class;
+        let mut template = [
+            Attribute {
+                kind: CKA_CLASS,
+                value: &mut class as *mut c_ulong as *mut c_void,
+                len: std::mem::size_of::<c_ulong>() as c_ulong,
+            },
+            Attribute {
+                kind: CKA_LABEL,
+                value: label.as_ptr() as *mut c_void,
+                len: label.len() as c_ulong,
+            },
+        ];
+        let mut found = [0 as c_ulong; 2];
+        let mut count: c_ulong = 0;
+        // SAFETY: the template points at locals alive for the whole search.
+        unsafe {
+            check(
+                (f.find_objects_init)(self.session, template.as_mut_ptr(), 2),
+                "C_FindObjectsInit",
+            )?;
+            let rv = (f.find_objects)(self.session, found.as_mut_ptr(), 2, &mut count);
+            check((f.find_objects_final)(self.session), "C_FindObjectsFinal")?;
+            check(rv, "C_FindObjects")?;
+        }
+        match count {
+            1 => Ok(found[0]),
+            0 => Err(anyhow!("PKCS#11: no key labelled {:?}", label)),
+            _ => Err(anyhow!("PKCS#11: more than one key labelled {:?}", label)),
+        }
+    }
+
+    /// Uncompressed SEC1 public key of the pair labelled `label`.
+    pub fn public_key(&self, label: &str) -> Result<[u8; 65]> {
+        let object = self.find(CKO_PUBLIC_KEY, label)?;
+        let f = self.functions();
+        let mut attribute = Attribute {
+            kind: CKA_EC_POINT,
+            value: std::ptr::null_mut(),
+            len: 0,
+        };
+        // SAFETY: length query, then a buffer of that length.
+        let der = unsafe {
+            check(
+                (f.get_attribute_value)(self.session, object, &mut attribute, 1),
+                "C_GetAttributeValue",
+            )?;
+            let mut der = vec![0u8; attribute.len as usize];
+            attribute.value = der.as_mut_ptr() as *mut c_void;
+            check(
+                (f.get_attribute_value)(self.session, object, &mut attribute, 1),
+                "C_GetAttributeValue",
+            )?;
+            der.truncate(attribute.len as usize);
+            der
+        };
+        ec_point(&der).with_context(|| format!("public key {:?}", label))
+    }
+
+    /// Raw r ‖ s over `digest` with the private key labelled `label`.
+    pub fn sign_digest(&self, label: &str, digest: &[u8; 32]) -> Result<[u8; 64]> {
+        let key = self.find(CKO_PRIVATE_KEY, label)?;
+        let f = self.functions();
+        let mut mechanism = Mechanism {
+            mechanism: CKM_ECDSA,
+            parameter: std::ptr::null_mut(),
+            len: 0,
+        };
+        let mut rs = [0u8; 64];
+        let mut len = rs.len() as c_ulong;
+        // SAFETY: mechanism, digest and output buffer outlive both calls.
+        unsafe {
+            check(
+                (f.sign_init)(self.session, &mut mechanism, key),
+                "C_SignInit",
+            )?;
+            check(
+                (f.sign)(
+                    self.session,
+                    digest.as_ptr(),
+                    digest.len() as c_ulong,
+                    rs.as_mut_ptr(),
+                    &mut len,
+                ),
+                "C_Sign",
+            )?;
+        }
+        if len != 64 {
+            return Err(anyhow!(
+                "PKCS#11: {:?} gave a {}-byte signature; not a secp256k1 key?",
+                label,
+                len
+            ));
+        }
+        Ok(rs)
+    }
+}
+
+impl Drop for Pkcs11Token {
+    fn drop(&mut self) {
+        // SAFETY: closes what open() opened; errors are of no use here.
+        unsafe {
+            if !self.functions.is_null() {
+                let f = &*self.functions;
+                if self.session != 0 {
+                    (f.close_session)(self.session);
+                }
+                (f.finalize)(std::ptr::null_mut());
+            }
+            libc::dlclose(self.library);
+        }
+    }
+}
+
+fn dl_error() -> String {
+    // SAFETY: dlerror returns NULL or a NUL-terminated message.
+    unsafe {
+        let message = libc::dlerror();
+        if message.is_null() {
+            "unknown error".to_string()
+        } else {
+            CStr::from_ptr(message).to_string_lossy().into_owned()
+        }
+    }
+}
+
+// ── Ethereum glue ──
+
+/// `CKA_EC_POINT`: a DER OCTET STRING around the SEC1 point (the standard),
+/// or the bare point (some modules).
+pub fn ec_point(der: &[u8]) -> Result<[u8; 65]> {
+    let point = match der {
+        [0x04, 0x41, rest @ ..] if rest.len() == 65 => rest,
+        bare if bare.len() == 65 => bare,
+        _ => return Err(anyhow!("CKA_EC_POINT is not an uncompressed point")),
+    };
+    VerifyingKey::from_sec1_bytes(point).map_err(|_| anyhow!("not a secp256k1 point"))?;
+    let mut out = [0u8; 65];
+    out.copy_from_slice(point);
+    Ok(out)
+}
+
+pub fn address(public_key: &[u8; 65]) -> [u8; 20] {
+    let mut address = [0u8; 20];
+    address.copy_from_slice(&Keccak256::digest(&public_key[1..])[12..]);
+    address
+}
+
+/// r ‖ s ‖ v (v = 27/28) from a raw HSM signature: low-S as Ethereum
+/// requires, and the recovery id that gives back `public_key`.
+pub fn eth_signature(digest: &[u8; 32], rs: &[u8], public_key: &[u8; 65]) -> Result<[u8; 65]> {
+    let signature = Signature::from_slice(rs).map_err(|e| anyhow!("HSM signature: {}", e))?;
+    let signature = signature.normalize_s().unwrap_or(signature);
+    let expected =
+        VerifyingKey::from_sec1_bytes(public_key).map_err(|_| anyhow!("not a secp256k1 key"))?;
+    for id in 0..2u8 {
+        let recid = RecoveryId::from_byte(id).expect("0 and 1 are recovery ids");
+        if VerifyingKey::recover_from_prehash(digest, &signature, recid).ok() == Some(expected) {
+            let mut out = [0u8; 65];
+            out[..64].copy_from_slice(&signature.to_bytes());
+            out[64] = 27 + id;
+            return Ok(out);
+        }
+    }
+    Err(anyhow!(
+        "HSM signature does not verify under the wallet's key"
+    ))
+}
+
+// ── Signer ──
+
+/// Signs for the wallets in `wallet_signers` with backend `pkcs11`.
+pub struct Pkcs11Signer {
+    token: Arc<Mutex<Pkcs11Token>>,
+    db: KmsDb,
+}
+
+impl Pkcs11Signer {
+    pub fn new(token: Pkcs11Token, db: KmsDb) -> Self {
+        Self {
+            token: Arc::new(Mutex::new(token)),
+            db,
+        }
+    }
+
+    fn key(&self, wallet_id: Uuid, hd_path: &str) -> Result<WalletSignerRow> {
+        let key_id = wallet_id.to_string();
+        let row = self
+            .db
+            .wallet_signer(&key_id)?
+            .filter(|r| r.backend == PKCS11)
+            .ok_or_else(|| anyhow!("wallet {} is not held by the PKCS#11 signer", key_id))?;
+        if row.derivation_path != hd_path {
+            return Err(anyhow!(
+                "HSM wallet {} only has {}, not {}",
+                key_id,
+                row.derivation_path,
+                hd_path
+            ));
+        }
+        Ok(row)
+    }
+
+    /// The HSM call runs on the blocking pool; the token is one session.
+    async fn sign_digest(&self, label: String, digest: [u8; 32]) -> Result<[u8; 65]> {
+        let token = self.token.clone();
+        tokio::task::spawn_blocking(move || {
+            let token = token.lock().unwrap_or_else(|e| e.into_inner());
+            let rs = token.sign_digest(&label, &digest)?;
+            eth_signature(&digest, &rs, &token.public_key(&label)?)
+        })
+        .await
+        .context("PKCS#11 task panicked")?
+    }
+
+    async fn signed(
+        &self,
+        wallet_id: Uuid,
+        hd_path: &str,
+        passkey_assertion: Option<PasskeyAssertion>,
+        digest: [u8; 32],
+    ) -> Result<[u8; 65]> {
+        if passkey_assertion.is_none() {
+            return Err(anyhow!("HSM wallets need a verified passkey assertion"));
+        }
+        let key = self.key(wallet_id, hd_path)?;
+        self.sign_digest(key.key_label, digest).await
+    }
+}
+
+impl Signer for Pkcs11Signer {
+    fn backend(&self) -> &'static str {
+        PKCS11
+    }
+
+    fn binds_passkey_to_payload(&self) -> bool {
+        false
+    }
+
+    fn derive_address<'a>(
+        &'a self,
+        wallet_id: Uuid,
+        hd_path: &'a str,
+        _passkey_assertion: Option<PasskeyAssertion>,
+    ) -> SignFuture<'a, [u8; 20]> {
+        Box::pin(async move {
+            let label = self.key(wallet_id, hd_path)?.key_label;
+            let token = self.token.clone();
+            let public_key = tokio::task::spawn_blocking(move || {
+                token
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .public_key(&label)
+            })
+            .await
+            .context("PKCS#11 task panicked")??;
+            Ok(address(&public_key))
+        })
+    }
+
+    fn sign_transaction<'a>(
+        &'a self,
+        wallet_id: Uuid,
+        hd_path: &'a str,
+        transaction: EthTransaction,
+        passkey_assertion: Option<PasskeyAssertion>,
+        _summary_abis: Vec<String>,
+        _summary_committed: bool,
+    ) -> SignFuture<'a, Vec<u8>> {
+        Box::pin(async move {
+            let digest = legacy_signing_hash(&transaction);
+            let signature = self
+                .signed(wallet_id, hd_path, passkey_assertion, digest)
+                .await?;
+            let mut r = [0u8; 32];
+            let mut s = [0u8; 32];
+            r.copy_from_slice(&signature[..32]);
+            s.copy_from_slice(&signature[32..64]);
+            Ok(signed_legacy_rlp(&SignedLegacyTx {
+                transaction,
+                r,
+                s,
+                recovery_id: signature[64] - 27,
+            }))
+        })
+    }
+
+    fn sign_eip1559_transaction<'a>(
+        &'a self,
+        wallet_id: Uuid,
+        hd_path: &'a str,
+        transaction: Eip1559Transaction,
+        passkey_assertion: Option<PasskeyAssertion>,
+        _summary_abis: Vec<String>,
+        _summary_committed: bool,
+    ) -> SignFuture<'a, Vec<u8>> {
+        Box::pin(async move {
+            transaction.check().map_err(|e| anyhow!(e))?;
+            let digest = transaction.signing_hash();
+            let signature = self
+                .signed(wallet_id, hd_path, passkey_assertion, digest)
+                .await?;
+            let mut r = [0u8; 32];
+            let mut s = [0u8; 32];
+            r.copy_from_slice(&signature[..32]);
+            s.copy_from_slice(&signature[32..64]);
+            Ok(signed_rlp(&SignedEip1559Tx {
+                transaction,
+                y_parity: signature[64] - 27,
+                r,
+                s,
+            }))
+        })
+    }
+
+    fn sign_message<'a>(
+        &'a self,
+        wallet_id: Uuid,
+        hd_path: &'a str,
+        message: &'a [u8],
+        passkey_assertion: Option<PasskeyAssertion>,
+        context: SigningContext,
+    ) -> SignFuture<'a, Vec<u8>> {
+        Box::pin(async move {
+            let digest = crate::key_pin::message_digest(&context, message).ok_or_else(|| {
+                anyhow!("HSM wallets sign Raw, PersonalMsg and Login messages only")
+            })?;
+            Ok(self
+                .signed(wallet_id, hd_path, passkey_assertion, digest)
+                .await?
+                .to_vec())
+        })
+    }
+
+    fn sign_hash<'a>(
+        &'a self,
+        wallet_id: Uuid,
+        hd_path: &'a str,
+        hash: &'a [u8; 32],
+        passkey_assertion: Option<PasskeyAssertion>,
+        _context: SigningContext,
+        fee_quote: Option<FeeQuote>,
+    ) -> SignFuture<'a, Vec<u8>> {
+        Box::pin(async move {
+            if fee_quote.is_some() {
+                return Err(anyhow!(
+                    "fee quotes are enforced by the TA; HSM wallets cannot use them"
+                ));
+            }
+            Ok(self
+                .signed(wallet_id, hd_path, passkey_assertion, *hash)
+                .await?
+                .to_vec())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use k256::ecdsa::SigningKey;
+
+    fn key() -> (SigningKey, [u8; 65]) {
+        let key = SigningKey::from_slice(&[0x46; 32]).unwrap();
+        let mut public_key = [0u8; 65];
+        public_key.copy_from_slice(key.verifying_key().to_encoded_point(false).as_bytes());
+        (key, public_key)
+    }
+
+    #[test]
+    fn ec_point_accepts_der_and_bare_points() {
+        let (_, public_key) = key();
+        let mut der = vec![0x04, 0x41];
+        der.extend_from_slice(&public_key);
+        assert_eq!(ec_point(&der).unwrap(), public_key);
+        assert_eq!(ec_point(&public_key).unwrap(), public_key);
+        assert!(ec_point(&der[..60]).is_err());
+        let mut off_curve = public_key;
+        off_curve[64] ^= 1;
+        assert!(ec_point(&off_curve).is_err());
+        // the EIP-155 example key
+        assert_eq!(
+            crate::key_pin::address_hex(&address(&public_key)),
+            "0x9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f"
+        );
+    }
+
+    #[test]
+    fn hsm_signatures_become_low_s_recoverable() {
+        let (key, public_key) = key();
+        let digest = [0x5a; 32];
+        let (signature, _) = key.sign_prehash_recoverable(&digest).unwrap();
+        // an HSM is free to return the high-S twin
+        let high_s = Signature::from_scalars(signature.r(), -*signature.s()).unwrap();
+        for raw in [signature, high_s] {
+            let eth = eth_signature(&digest, &raw.to_bytes(), &public_key).unwrap();
+            assert!(Signature::from_slice(&eth[..64])
+                .unwrap()
+                .normalize_s()
+                .is_none());
+            assert_eq!(
+                crate::key_pin::recover_signer(&digest, &eth).unwrap(),
+                address(&public_key)
+            );
+        }
+        let (_, other) = {
+            let k = SigningKey::from_slice(&[0x47; 32]).unwrap();
+            let mut p = [0u8; 65];
+            p.copy_from_slice(k.verifying_key().to_encoded_point(false).as_bytes());
+            (k, p)
+        };
+        assert!(eth_signature(&digest, &signature.to_bytes(), &other).is_err());
+    }
+
+    #[test]
+    fn config_needs_an_absolute_module_and_a_pin() {
+        let module = "/usr/lib/softhsm/libsofthsm2.so".to_string();
+        let config = Pkcs11Config::parse(module.clone(), Some("7"), Some("1234".into())).unwrap();
+        assert_eq!(config.slot, Some(7));
+        assert!(!format!("{:?}", config).contains("1234"));
+        assert!(Pkcs11Config::parse(module.clone(), None, None).is_err());
+        assert!(Pkcs11Config::parse(module, Some("x"), Some("1".into())).is_err());
+        assert!(Pkcs11Config::parse("libsofthsm2.so".into(), None, Some("1".into())).is_err());
+    }
+}
@@ -0,0 +1,298 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Bulk wallet provisioning for enterprise onboarding.
+//!
+//! `POST /admin/provision-wallets` (api_server.rs) creates up to
+//! [`MAX_BATCH`] wallets for a tenant in one call. Each wallet is still one
+//! CreateWallet and one DeriveAddressAuto on the TEE's batch lane: the TA
+//! cannot create several wallets in one invoke (its wallet cache must not be
+//! touched after a storage write), and the batch lane keeps interactive
+//! signing ahead of an onboarding run.
+//!
+//! The wallets land in `tenant_wallets` (db.rs) with the batch's tags. The
+//! [`ProvisioningReport`] lists their addresses; one attestation per batch,
+//! with nonce = SHA-256 of the report JSON, proves they were made by this TA.
+//! The CSV export is a view of the same report.
+//!
+//! Two limits apply per tenant: a quota on wallets held (`tenants` row, or
+//! `KMS_TENANT_WALLET_QUOTA`) and a ceiling on wallets provisioned in the
+//! last hour (`KMS_PROVISION_RATE_PER_HOUR`).
+
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Wallets one call may create.
+pub const MAX_BATCH: usize = 100;
+pub const MAX_TAGS: usize = 16;
+const MAX_ID_LEN: usize = 64;
+const MAX_TAG_LEN: usize = 32;
+const DEFAULT_TENANT_QUOTA: u32 = 1000;
+const DEFAULT_RATE_PER_HOUR: u64 = 300;
+
+pub const REPORT_SCHEMA: &str = "airaccount.provisioning.v1";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProvisioningLimits {
+    /// Quota of a tenant without a `tenants` row.
+    pub default_quota: u32,
+    pub per_hour: u64,
+}
+
+impl ProvisioningLimits {
+    /// `KMS_TENANT_WALLET_QUOTA` (default 1000) and
+    /// `KMS_PROVISION_RATE_PER_HOUR` (default 300), both positive integers.
+    pub fn from_env() -> Result<Self> {
+        let var = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+        Self::parse(
+            var("KMS_TENANT_WALLET_QUOTA").as_deref(),
+            var("KMS_PROVISION_RATE_PER_HOUR").as_deref(),
+        )
+    }
+
+    pub fn parse(quota: Option<&str>, per_hour: Option<&str>) -> Result<Self> {
+        let default_quota = match quota {
+            None => DEFAULT_TENANT_QUOTA,
+            Some(v) => v
+                .parse()
+                .ok()
+                .filter(|q| *q > 0)
+                .ok_or_else(|| anyhow!("KMS_TENANT_WALLET_QUOTA must be a positive integer"))?,
+        };
+        let per_hour =
+            match per_hour {
+                None => DEFAULT_RATE_PER_HOUR,
+                Some(v) => v.parse().ok().filter(|r| *r > 0).ok_or_else(|| {
+                    anyhow!("KMS_PROVISION_RATE_PER_HOUR must be a positive integer")
+                })?,
+            };
+        Ok(ProvisioningLimits {
+            default_quota,
+            per_hour,
+        })
+    }
+
+    /// Whether `requested` more wallets fit: `held` against `quota`, and
+    /// `last_hour` against the hourly ceiling.
+    pub fn admit(&self, quota: u32, held: u64, last_hour: u64, requested: usize) -> Result<()> {
+        let requested = requested as u64;
+        if held + requested > quota as u64 {
+            bail!(
+                "tenant quota exceeded: holds {} of {} wallets, {} requested",
+                held,
+                quota,
+                requested
+            );
+        }
+        if last_hour + requested > self.per_hour {
+            bail!(
+                "provisioning rate exceeded: {} wallets in the last hour (max {}/h), \
+                 {} requested — retry later",
+                last_hour,
+                self.per_hour,
+                requested
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Tenant ids and tags are stored comma-joined and go into CSV cells, so
+/// both are kept to `[A-Za-z0-9._-]`.
+fn check_label(kind: &str, s: &str, max: usize) -> Result<()> {
+    if s.is_empty() || s.len() > max {
+        bail!("{} must be 1-{} characters", kind, max);
+    }
+    if !s
+        .bytes()
+        .all(|b| b.is_ascii_alphanumeric() || b == b'.' || b == b'_' || b == b'-')
+    {
+        bail!(
+            "{} {:?} may only contain letters, digits, '.', '_' and '-'",
+            kind,
+            s
+        );
+    }
+    Ok(())
+}
+
+pub fn validate_tenant(tenant: &str) -> Result<()> {
+    check_label("tenant", tenant, MAX_ID_LEN)
+}
+
+pub fn validate_tags(tags: &[String]) -> Result<()> {
+    if tags.len() > MAX_TAGS {
+        bail!("at most {} tags per batch", MAX_TAGS);
+    }
+    for (i, tag) in tags.iter().enumerate() {
+        check_label("tag", tag, MAX_TAG_LEN)?;
+        if tags[..i].contains(tag) {
+            bail!("tag {:?} given twice", tag);
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProvisionedWallet {
+    pub key_id: String,
+    /// 0x, EIP-55.
+    pub address: String,
+    pub public_key: String,
+    pub derivation_path: String,
+    pub passkey_public_key: String,
+}
+
+/// What a batch produced. Serialized once; that string is what the
+/// attestation binds and what `provisioning_batches` keeps.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProvisioningReport {
+    pub schema: String,
+    pub batch_id: String,
+    pub tenant: String,
+    pub tags: Vec<String>,
+    pub requested: usize,
+    pub wallets: Vec<ProvisionedWallet>,
+    /// Why the batch stopped early. The wallets listed were still created
+    /// and assigned.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub incomplete: Option<String>,
+    pub generated_at: String,
+}
+
+impl ProvisioningReport {
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// One row per wallet. Tags are joined with `;` to stay in one cell.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "tenant,batch_id,key_id,address,public_key,derivation_path,passkey_public_key,tags\n",
+        );
+        let tags = self.tags.join(";");
+        for w in &self.wallets {
+            let cells = [
+                self.tenant.as_str(),
+                self.batch_id.as_str(),
+                w.key_id.as_str(),
+                w.address.as_str(),
+                w.public_key.as_str(),
+                w.derivation_path.as_str(),
+                w.passkey_public_key.as_str(),
+                tags.as_str(),
+            ];
+            let row: Vec<String> = cells.iter().map(|c| csv_cell(c)).collect();
+            csv.push_str(&row.join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+/// RFC 4180 quoting, and a leading `'` on anything a spreadsheet would
+/// evaluate as a formula.
+pub(crate) fn csv_cell(s: &str) -> String {
+    let s = if s.starts_with(['=', '+', '-', '@']) {
+        format!("'{}", s)
+    } else {
+        s.to_string()
+    };
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s
+    }
+}
+
+/// The attestation nonce of a report.
+pub fn report_digest(report_json: &str) -> [u8; 32] {
+    Sha256::digest(report_json.as_bytes()).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report() -> ProvisioningReport {
+        ProvisioningReport {
+            schema: REPORT_SCHEMA.into(),
+            batch_id: "b-1".into(),
+            tenant: "acme".into(),
+            tags: vec!["payroll".into(), "eu".into()],
+            requested: 2,
+            wallets: vec![ProvisionedWallet {
+                key_id: "k-1".into(),
+                address: "0xAbC".into(),
+                public_key: "0x04aa".into(),
+                derivation_path: "m/44'/60'/0'/0/0".into(),
+                passkey_public_key: "0x04bb".into(),
+            }],
+            incomplete: Some("TEE queue full".into()),
+            generated_at: "2026-10-16T00:00:00Z".into(),
+        }
+    }
+
+    #[test]
+    fn csv_is_a_view_of_the_attested_report() {
+        let r = report();
+        assert_eq!(
+            r.to_csv(),
+            "tenant,batch_id,key_id,address,public_key,derivation_path,passkey_public_key,tags\n\
+             acme,b-1,k-1,0xAbC,0x04aa,m/44'/60'/0'/0/0,0x04bb,payroll;eu\n"
+        );
+        let json = r.to_json().unwrap();
+        assert!(json.contains("\"batchId\":\"b-1\""));
+        assert_eq!(
+            serde_json::from_str::<ProvisioningReport>(&json).unwrap(),
+            r
+        );
+        assert_eq!(report_digest(&json), report_digest(&r.to_json().unwrap()));
+
+        assert_eq!(csv_cell("a,b"), "\"a,b\"");
+        assert_eq!(csv_cell("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_cell("=cmd()"), "'=cmd()");
+    }
+
+    #[test]
+    fn quota_and_rate_admit_whole_batches_only() {
+        let limits = ProvisioningLimits::parse(None, Some("50")).unwrap();
+        assert_eq!(limits.default_quota, 1000);
+        assert!(limits.admit(100, 90, 0, 10).is_ok());
+        assert!(limits.admit(100, 91, 0, 10).is_err());
+        assert!(limits.admit(1000, 0, 45, 5).is_ok());
+        assert!(limits.admit(1000, 0, 45, 6).is_err());
+        assert!(ProvisioningLimits::parse(Some("0"), None).is_err());
+        assert!(ProvisioningLimits::parse(None, Some("fast")).is_err());
+    }
+
+    #[test]
+    fn tenant_and_tags_are_plain_labels() {
+        assert!(validate_tenant("acme-corp.eu_1").is_ok());
+        assert!(validate_tenant("").is_err());
+        assert!(validate_tenant("acme,corp").is_err());
+        assert!(validate_tenant(&"a".repeat(65)).is_err());
+        assert!(validate_tags(&["eu".into(), "payroll".into()]).is_ok());
+        assert!(validate_tags(&["eu".into(), "eu".into()]).is_err());
+        assert!(validate_tags(&["a b".into()]).is_err());
+        let many: Vec<String> = (0..=MAX_TAGS).map(|i| format!("t{}", i)).collect();
+        assert!(validate_tags(&many).is_err());
+    }
+}
@@ -10,6 +10,7 @@
 //! - For stronger guarantees, move state to TEE secure storage or a shared DB table.
 
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
@@ -28,7 +29,8 @@ struct Inner {
 #[derive(Clone)]
 pub struct RateLimiter {
     inner: Arc<Mutex<Inner>>,
-    limit: usize,
+    /// Shared by every clone, so `/admin/config` can retune a live limiter.
+    limit: Arc<AtomicUsize>,
     max_keys: usize,
 }
 
@@ -41,7 +43,7 @@ impl RateLimiter {
                 // Initialize far enough in the past so the first cap-reject warning is visible immediately.
                 last_cap_log: Instant::now() - Duration::from_secs(WINDOW_SECS),
             })),
-            limit,
+            limit: Arc::new(AtomicUsize::new(limit)),
             max_keys,
         }
     }
@@ -60,6 +62,7 @@ impl RateLimiter {
 
     /// Check if request is allowed. Returns Ok(remaining) or Err(limit).
     pub fn check(&self, key: &str) -> Result<usize, usize> {
+        let limit = self.limit();
         let mut inner = self.inner.lock().unwrap();
         let now = Instant::now();
         let cutoff = now - Duration::from_secs(WINDOW_SECS);
@@ -99,7 +102,7 @@ impl RateLimiter {
                     );
                     inner.last_cap_log = now;
                 }
-                return Err(self.limit);
+                return Err(limit);
             }
         }
 
@@ -110,16 +113,21 @@ impl RateLimiter {
             .or_insert_with(Vec::new);
         timestamps.retain(|t| *t > cutoff);
 
-        if timestamps.len() >= self.limit {
-            Err(self.limit)
+        if timestamps.len() >= limit {
+            Err(limit)
         } else {
             timestamps.push(now);
-            Ok(self.limit - timestamps.len())
+            Ok(limit - timestamps.len())
         }
     }
 
     pub fn limit(&self) -> usize {
-        self.limit
+        self.limit.load(Ordering::Relaxed)
+    }
+
+    /// Takes effect on the next check; windows already counted are kept.
+    pub fn set_limit(&self, limit: usize) {
+        self.limit.store(limit, Ordering::Relaxed);
     }
 }
 
@@ -151,4 +159,16 @@ mod tests {
         assert!(rl.check("key1").is_err());
         assert!(rl.check("key2").is_err());
     }
+
+    #[test]
+    fn set_limit_reaches_every_clone() {
+        let rl = RateLimiter::new(1, 100);
+        let clone = rl.clone();
+        assert!(rl.check("key1").is_ok());
+        assert!(rl.check("key1").is_err());
+        clone.set_limit(2);
+        assert_eq!(rl.limit(), 2);
+        assert!(rl.check("key1").is_ok());
+        assert!(rl.check("key1").is_err());
+    }
 }
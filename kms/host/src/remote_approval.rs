@@ -0,0 +1,258 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Push delivery of signing requests to a wallet's paired approver phone.
+//!
+//! The request is sealed to the phone's secp256k1 key in the ECIES layout of
+//! `proto::ecies` (what eciesjs and the `ecies` crate open), so the relay and
+//! FCM / APNs behind it carry ciphertext only. The relay is the operator's
+//! (`KMS_PUSH_RELAY_URL`): the CA posts `{platform, pushToken, ciphertext}`
+//! and the relay holds the FCM / APNs credentials. Whether the phone agrees
+//! is decided in the TA, from its signature; nothing here is trusted for it.
+
+use aes_gcm::aead::{consts::U16, AeadInPlace};
+use aes_gcm::{aes::Aes256, AesGcm, KeyInit};
+use anyhow::{anyhow, bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use k256::elliptic_curve::sec1::ToEncodedPoint;
+use rand::RngCore;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use proto::ecies::{EPHEMERAL_KEY_LEN, NONCE_LEN};
+
+/// Sealed request bytes the push can carry: FCM and APNs both stop at 4 KiB
+/// of payload, and the envelope goes base64 inside the relay's JSON.
+pub const MAX_PUSH_PLAINTEXT: usize = 2048;
+const RELAY_TIMEOUT_SECS: u64 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Platform {
+    Fcm,
+    Apns,
+}
+
+impl Platform {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s {
+            "fcm" => Ok(Platform::Fcm),
+            "apns" => Ok(Platform::Apns),
+            _ => bail!("platform must be fcm or apns"),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Platform::Fcm => "fcm",
+            Platform::Apns => "apns",
+        }
+    }
+}
+
+/// What the phone decrypts. It shows `summary`, rebuilds `payload` from
+/// `transaction` (the tx signing hash, or `calldata::confirmation_digest`
+/// of it and `summary` when `summaryCommitted`) rather than trusting it,
+/// and signs `remote_approval::approval_digest` of it.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApprovalRequest {
+    pub version: u8,
+    pub key_id: String,
+    /// None = the Sign's default account.
+    pub derivation_path: Option<String>,
+    /// As the CA's Sign takes it.
+    pub transaction: Value,
+    pub summary: String,
+    pub summary_committed: bool,
+    /// 0x-hex.
+    pub payload: String,
+    pub requested_at: i64,
+}
+
+impl ApprovalRequest {
+    pub fn to_plaintext(&self) -> Result<Vec<u8>> {
+        let bytes = serde_json::to_vec(self)?;
+        if bytes.len() > MAX_PUSH_PLAINTEXT {
+            bail!(
+                "signing request is {} bytes, more than a push carries ({}); approve it on a device instead",
+                bytes.len(),
+                MAX_PUSH_PLAINTEXT
+            );
+        }
+        Ok(bytes)
+    }
+}
+
+/// ECIES-seal `plaintext` to a compressed (or uncompressed) secp256k1 key.
+pub fn seal(recipient: &[u8], plaintext: &[u8]) -> Result<Vec<u8>> {
+    let recipient = k256::PublicKey::from_sec1_bytes(recipient)
+        .map_err(|_| anyhow!("approver key is not a secp256k1 point"))?;
+    let ephemeral = k256::SecretKey::random(&mut rand::rngs::OsRng);
+    let mut nonce = [0u8; NONCE_LEN];
+    rand::rngs::OsRng.fill_bytes(&mut nonce);
+    seal_with(&recipient, &ephemeral, &nonce, plaintext)
+}
+
+fn seal_with(
+    recipient: &k256::PublicKey,
+    ephemeral: &k256::SecretKey,
+    nonce: &[u8; NONCE_LEN],
+    plaintext: &[u8],
+) -> Result<Vec<u8>> {
+    let ephemeral_pub = ephemeral.public_key().to_encoded_point(false);
+    let shared = (recipient.to_projective() * *ephemeral.to_nonzero_scalar())
+        .to_affine()
+        .to_encoded_point(false);
+
+    let mut ikm = [0u8; 2 * EPHEMERAL_KEY_LEN];
+    ikm[..EPHEMERAL_KEY_LEN].copy_from_slice(ephemeral_pub.as_bytes());
+    ikm[EPHEMERAL_KEY_LEN..].copy_from_slice(shared.as_bytes());
+    let mut key = [0u8; 32];
+    hkdf::Hkdf::<sha2::Sha256>::new(None, &ikm)
+        .expand(&[], &mut key)
+        .map_err(|_| anyhow!("HKDF expand failed"))?;
+    ikm.fill(0);
+    let cipher = AesGcm::<Aes256, U16>::new(&key.into());
+    key.fill(0);
+
+    let mut ciphertext = plaintext.to_vec();
+    let tag = cipher
+        .encrypt_in_place_detached(nonce.into(), &[], &mut ciphertext)
+        .map_err(|_| anyhow!("ECIES encryption failed"))?;
+    let mut envelope = Vec::with_capacity(proto::ecies::OVERHEAD + ciphertext.len());
+    envelope.extend_from_slice(ephemeral_pub.as_bytes());
+    envelope.extend_from_slice(nonce);
+    envelope.extend_from_slice(&tag);
+    envelope.extend_from_slice(&ciphertext);
+    Ok(envelope)
+}
+
+/// The operator's push relay.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PushRelay {
+    pub url: String,
+}
+
+impl PushRelay {
+    /// `KMS_PUSH_RELAY_URL` (http://…); without it a wallet can still pair,
+    /// but no signing request reaches the phone.
+    pub fn from_env() -> Option<Result<Self>> {
+        let url = std::env::var("KMS_PUSH_RELAY_URL")
+            .ok()
+            .filter(|v| !v.is_empty())?;
+        Some(Self::new(&url))
+    }
+
+    pub fn new(url: &str) -> Result<Self> {
+        if !url.starts_with("http://") {
+            bail!("KMS_PUSH_RELAY_URL must be an http:// URL (no TLS in the CA)");
+        }
+        Ok(Self {
+            url: url.to_string(),
+        })
+    }
+
+    pub async fn deliver(&self, platform: Platform, push_token: &str, sealed: &[u8]) -> Result<()> {
+        use warp::hyper::{Body, Client, Request};
+        let body = relay_body(platform, push_token, sealed);
+        let req = Request::post(self.url.as_str())
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))?;
+        let resp = tokio::time::timeout(
+            std::time::Duration::from_secs(RELAY_TIMEOUT_SECS),
+            Client::new().request(req),
+        )
+        .await
+        .map_err(|_| anyhow!("push relay did not answer within {}s", RELAY_TIMEOUT_SECS))?
+        .context("push relay unreachable")?;
+        if !resp.status().is_success() {
+            bail!("push relay returned HTTP {}", resp.status());
+        }
+        Ok(())
+    }
+}
+
+fn relay_body(platform: Platform, push_token: &str, sealed: &[u8]) -> Value {
+    json!({
+        "platform": platform,
+        "pushToken": push_token,
+        "ciphertext": STANDARD.encode(sealed),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open(recipient: &k256::SecretKey, envelope: &[u8]) -> Vec<u8> {
+        let parsed = proto::ecies::Envelope::parse(envelope).unwrap();
+        let ephemeral = k256::PublicKey::from_sec1_bytes(parsed.ephemeral_pubkey).unwrap();
+        let shared = (ephemeral.to_projective() * *recipient.to_nonzero_scalar())
+            .to_affine()
+            .to_encoded_point(false);
+        let mut point = [0u8; 65];
+        point.copy_from_slice(shared.as_bytes());
+        parsed.open(&point).unwrap()
+    }
+
+    #[test]
+    fn sealed_request_opens_with_the_phone_key_only() {
+        let phone = k256::SecretKey::from_slice(&[0x42; 32]).unwrap();
+        let compressed = phone.public_key().to_encoded_point(true);
+        let envelope = seal(compressed.as_bytes(), b"approve me").unwrap();
+        assert_eq!(envelope.len(), proto::ecies::OVERHEAD + 10);
+        assert_eq!(open(&phone, &envelope), b"approve me");
+
+        let other = k256::SecretKey::from_slice(&[0x43; 32]).unwrap();
+        let parsed = proto::ecies::Envelope::parse(&envelope).unwrap();
+        let ephemeral = k256::PublicKey::from_sec1_bytes(parsed.ephemeral_pubkey).unwrap();
+        let wrong = (ephemeral.to_projective() * *other.to_nonzero_scalar())
+            .to_affine()
+            .to_encoded_point(false);
+        let mut point = [0u8; 65];
+        point.copy_from_slice(wrong.as_bytes());
+        assert!(parsed.open(&point).is_err());
+        assert!(seal(&[0x05; 33], b"x").is_err());
+    }
+
+    #[test]
+    fn request_size_relay_and_platform() {
+        let mut request = ApprovalRequest {
+            version: 1,
+            key_id: "w-1".into(),
+            derivation_path: Some("m/44'/60'/0'/0/0".into()),
+            transaction: json!({ "chainId": 1, "data": "0x" }),
+            summary: "Send 1 ETH".into(),
+            summary_committed: true,
+            payload: format!("0x{}", "ab".repeat(32)),
+            requested_at: 1_800_000_000,
+        };
+        let plaintext = request.to_plaintext().unwrap();
+        let value: Value = serde_json::from_slice(&plaintext).unwrap();
+        assert_eq!(value["summaryCommitted"], true);
+        request.transaction = json!({ "data": format!("0x{}", "00".repeat(MAX_PUSH_PLAINTEXT)) });
+        assert!(request.to_plaintext().is_err());
+
+        let body = relay_body(Platform::Apns, "tok", &[1, 2, 3]);
+        assert_eq!(body["platform"], "apns");
+        assert_eq!(body["ciphertext"], "AQID");
+        assert_eq!(Platform::parse("fcm").unwrap(), Platform::Fcm);
+        assert!(Platform::parse("sms").is_err());
+        assert!(PushRelay::new("https://relay.example").is_err());
+    }
+}
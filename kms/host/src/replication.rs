@@ -0,0 +1,275 @@
+//! Multi-device wallet sync — transport and routing for seed replication.
+//!
+//! The TAs do the ceremony (`proto::replication`); the CAs carry the target's
+//! hello and the encrypted package between devices as prefixed base64url
+//! text, and keep the device set of each wallet in `wallet_devices` (db.rs).
+//! Each device is identified by its provisioning id (`KMS_DEVICE_ID`) and
+//! pinned to the fingerprint of its attestation key the first time it is
+//! seen; a later hello under another key for the same id is refused. Pure
+//! functions here; the endpoints are in api_server.rs.
+//!
+//! AWS multi-region keys sit on top of this: a region is the `KMS_REGION` of
+//! the device serving it (`key_regions` in db.rs), `ReplicateKey` runs the
+//! export above, and a Sign that this node cannot serve is redirected to a
+//! healthy replica ([`Failover`]).
+
+use anyhow::{anyhow, Result};
+use proto::replication::{ReplicationHello, ReplicationPackage};
+use proto::GetAttestationOutput;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use sha3::{Digest, Keccak256};
+
+use crate::db::WalletDeviceRow;
+use crate::webauthn::{b64url_decode, b64url_encode};
+
+pub const HELLO_PREFIX: &str = "aa-repl-hello:";
+pub const PACKAGE_PREFIX: &str = "aa-repl-pkg:";
+
+/// The path whose address the target reports back after an import.
+pub const CHECK_PATH: &str = "m/44'/60'/0'/0/0";
+
+/// A device whose heartbeat is older than this is not routed to.
+pub const HEALTHY_WITHIN_SECS: i64 = 120;
+
+/// This node's id in device sets; written to kms.env by `kms-provision`.
+pub fn local_device_id() -> String {
+    std::env::var("KMS_DEVICE_ID").unwrap_or_else(|_| "local".to_string())
+}
+
+/// AWS region name this node answers for; defaults to the device id.
+pub fn local_region() -> String {
+    std::env::var("KMS_REGION").unwrap_or_else(|_| local_device_id())
+}
+
+/// `MultiRegionKeyType` values, also stored as `key_regions.role`.
+pub const PRIMARY: &str = "PRIMARY";
+pub const REPLICA: &str = "REPLICA";
+
+pub fn region_arn(region: &str, key_id: &str) -> String {
+    format!("arn:aws:kms:{}:account:key/{}", region, key_id)
+}
+
+fn encode<T: Serialize>(prefix: &str, value: &T) -> Result<String> {
+    let bytes = bincode::serialize(value).map_err(|e| anyhow!("encode: {}", e))?;
+    Ok(format!("{}{}", prefix, b64url_encode(&bytes)))
+}
+
+fn decode<T: DeserializeOwned>(prefix: &str, what: &str, text: &str) -> Result<T> {
+    let b64 = text
+        .trim()
+        .strip_prefix(prefix)
+        .ok_or_else(|| anyhow!("not a replication {} (expected {})", what, prefix))?;
+    bincode::deserialize(&b64url_decode(b64)?).map_err(|e| anyhow!("{}: {}", what, e))
+}
+
+pub fn encode_hello(hello: &ReplicationHello) -> Result<String> {
+    encode(HELLO_PREFIX, hello)
+}
+
+pub fn decode_hello(text: &str) -> Result<ReplicationHello> {
+    decode(HELLO_PREFIX, "hello", text)
+}
+
+pub fn encode_package(package: &ReplicationPackage) -> Result<String> {
+    encode(PACKAGE_PREFIX, package)
+}
+
+pub fn decode_package(text: &str) -> Result<ReplicationPackage> {
+    decode(PACKAGE_PREFIX, "package", text)
+}
+
+/// keccak256(modulus ‖ exponent) of the device's attestation key, 0x-hex.
+pub fn attestation_key_fingerprint(evidence: &GetAttestationOutput) -> String {
+    let mut h = Keccak256::new();
+    h.update(&evidence.attest_pubkey_mod);
+    h.update(&evidence.attest_pubkey_exp);
+    format!("0x{}", hex::encode(h.finalize()))
+}
+
+/// Active, heard from recently, and not quarantined by its health check.
+pub fn is_healthy(device: &WalletDeviceRow, now: i64) -> bool {
+    device.status == "active"
+        && !device.quarantined
+        && now - device.last_seen <= HEALTHY_WITHIN_SECS
+}
+
+/// Where to send a signing request for a wallet: a healthy device, this one
+/// first, otherwise the most recently seen.
+pub fn route(devices: &[WalletDeviceRow], now: i64) -> Option<&WalletDeviceRow> {
+    devices
+        .iter()
+        .filter(|d| is_healthy(d, now))
+        .max_by_key(|d| (d.endpoint.is_none(), d.last_seen))
+}
+
+/// Whether this node may sign for a wallet: it was never replicated, or this
+/// device is still active in its set.
+pub fn local_can_sign(devices: &[WalletDeviceRow]) -> bool {
+    devices.is_empty()
+        || devices
+            .iter()
+            .any(|d| d.endpoint.is_none() && d.status == "active")
+}
+
+/// The freshest healthy device other than this one.
+pub fn failover(devices: &[WalletDeviceRow], now: i64) -> Option<&WalletDeviceRow> {
+    devices
+        .iter()
+        .filter(|d| d.endpoint.is_some() && is_healthy(d, now))
+        .max_by_key(|d| d.last_seen)
+}
+
+/// TEE errors that say nothing about the request — worth retrying elsewhere.
+pub fn tee_unavailable(error: &str) -> bool {
+    [
+        "circuit breaker",
+        "TEE call timeout",
+        "TEE request dropped",
+        "TEE queue full",
+        "device quarantined",
+    ]
+    .iter()
+    .any(|m| error.contains(m))
+}
+
+/// A Sign this node will not serve, and the replica that can.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Failover {
+    pub key_id: String,
+    pub device_id: String,
+    pub region: Option<String>,
+    pub endpoint: String,
+    pub reason: String,
+}
+
+impl Failover {
+    /// Where the client should repeat the request.
+    pub fn location(&self, path: &str) -> String {
+        format!("{}/{}", self.endpoint.trim_end_matches('/'), path)
+    }
+}
+
+impl std::fmt::Display for Failover {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} for {}; use replica {} at {}",
+            self.reason, self.key_id, self.device_id, self.endpoint
+        )
+    }
+}
+
+impl std::error::Error for Failover {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proto::replication::{ReplicationRole, REPLICATION_VERSION};
+
+    fn hello() -> ReplicationHello {
+        ReplicationHello {
+            version: REPLICATION_VERSION,
+            role: ReplicationRole::Target,
+            session_id: [0x5e; 16],
+            ephemeral_pubkey: vec![0x02; 33],
+            created_at: 1_700_000_000,
+            attestation: GetAttestationOutput {
+                nonce: vec![0xab; 32],
+                ta_uuid: vec![0; 16],
+                ta_measurement: vec![0x4d; 32],
+                signature: vec![1; 256],
+                attest_pubkey_exp: vec![1, 0, 1],
+                attest_pubkey_mod: vec![0xc5; 256],
+                sig_alg: 0x7041_4930,
+                ree_time_secs: 1_700_000_000,
+            },
+        }
+    }
+
+    fn device(id: &str, endpoint: Option<&str>, status: &str, last_seen: i64) -> WalletDeviceRow {
+        WalletDeviceRow {
+            key_id: "w1".into(),
+            device_id: id.into(),
+            endpoint: endpoint.map(|e| e.to_string()),
+            attestation_key_fp: "0xfp".into(),
+            status: status.into(),
+            added_at: 0,
+            revoked_at: None,
+            last_seen,
+            quarantined: false,
+        }
+    }
+
+    #[test]
+    fn hello_text_roundtrip() {
+        let text = encode_hello(&hello()).unwrap();
+        assert!(text.starts_with(HELLO_PREFIX));
+        assert_eq!(decode_hello(&text).unwrap(), hello());
+        // a hello pasted where a package is expected
+        assert!(decode_package(&text).is_err());
+        // another device's attestation key
+        let mut other = hello().attestation;
+        other.attest_pubkey_mod[0] ^= 1;
+        assert_ne!(
+            attestation_key_fingerprint(&other),
+            attestation_key_fingerprint(&hello().attestation)
+        );
+    }
+
+    #[test]
+    fn route_prefers_local_then_freshest_healthy() {
+        let now = 10_000;
+        let devices = vec![
+            device("a", Some("https://a"), "active", now - 10),
+            device("b", Some("https://b"), "active", now - 5),
+            device("c", Some("https://c"), "revoked", now),
+            device(
+                "d",
+                Some("https://d"),
+                "active",
+                now - HEALTHY_WITHIN_SECS - 1,
+            ),
+        ];
+        assert_eq!(route(&devices, now).unwrap().device_id, "b");
+        let mut with_local = devices.clone();
+        with_local.push(device("self", None, "active", now - 60));
+        assert_eq!(route(&with_local, now).unwrap().device_id, "self");
+        assert!(route(&devices[2..], now).is_none());
+    }
+
+    #[test]
+    fn failover_skips_this_node_and_revoked_replicas() {
+        let now = 10_000;
+        let mut devices = vec![
+            device("self", None, "active", now),
+            device("a", Some("https://a/"), "active", now - 30),
+            device("b", Some("https://b"), "revoked", now),
+        ];
+        assert!(local_can_sign(&devices));
+        assert!(local_can_sign(&[]));
+        let f = failover(&devices, now).unwrap();
+        assert_eq!(f.device_id, "a");
+        devices[0].status = "revoked".into();
+        assert!(!local_can_sign(&devices));
+        // a quarantined replica is not failed over to
+        devices[1].quarantined = true;
+        assert!(failover(&devices, now).is_none());
+        devices[1].quarantined = false;
+        devices[1].last_seen = now - HEALTHY_WITHIN_SECS - 1;
+        assert!(failover(&devices, now).is_none());
+
+        let redirect = Failover {
+            key_id: "w1".into(),
+            device_id: "a".into(),
+            region: Some("eu-west-1".into()),
+            endpoint: "https://a/".into(),
+            reason: "TEE circuit breaker OPEN".into(),
+        };
+        assert_eq!(redirect.location("Sign"), "https://a/Sign");
+        assert!(tee_unavailable(
+            "TEE circuit breaker OPEN: TA had 3 consecutive failures"
+        ));
+        assert!(!tee_unavailable("PassKey verification failed"));
+    }
+}
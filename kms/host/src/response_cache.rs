@@ -0,0 +1,371 @@
+//! Read-through cache for the TA reads the CA repeats: capabilities, stealth
+//! meta-addresses and derivation-path policies.
+//!
+//! Entries are keyed by (wallet, command, serialized input) and hold the raw
+//! TA output, so a hit decodes exactly like a fresh call. Errors are never
+//! cached. The `TeeHandle` methods that change one of these answers drop the
+//! wallet's entries when the command returns; the short TTL bounds how long a
+//! change this process does not see stays hidden — one made through another
+//! TA session (kms-admin) or on another node.
+//!
+//! Wallet rows, addresses and public keys are served from SQLite and never
+//! reach the TA, so they are not cached here.
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+pub const DEFAULT_TTL_SECS: u64 = 10;
+/// Longer than this and a kms-admin change would look lost.
+pub const MAX_TTL_SECS: u64 = 300;
+pub const DEFAULT_MAX_ENTRIES: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheConfig {
+    pub ttl: Duration,
+    pub max_entries: usize,
+}
+
+impl CacheConfig {
+    /// None = `KMS_RESPONSE_CACHE_TTL_SECS=0`, every read goes to the TA.
+    pub fn from_env() -> Result<Option<Self>> {
+        let var = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+        Self::parse(
+            var("KMS_RESPONSE_CACHE_TTL_SECS").as_deref(),
+            var("KMS_RESPONSE_CACHE_MAX_ENTRIES").as_deref(),
+        )
+    }
+
+    pub fn parse(ttl: Option<&str>, max_entries: Option<&str>) -> Result<Option<Self>> {
+        let ttl_secs = match ttl {
+            None => DEFAULT_TTL_SECS,
+            Some(v) => v
+                .parse()
+                .ok()
+                .filter(|s| *s <= MAX_TTL_SECS)
+                .ok_or_else(|| anyhow!("KMS_RESPONSE_CACHE_TTL_SECS must be 0-{}", MAX_TTL_SECS))?,
+        };
+        let max_entries = match max_entries {
+            None => DEFAULT_MAX_ENTRIES,
+            Some(v) => v.parse().ok().filter(|n| *n > 0).ok_or_else(|| {
+                anyhow!("KMS_RESPONSE_CACHE_MAX_ENTRIES must be a positive integer")
+            })?,
+        };
+        if ttl_secs == 0 {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            ttl: Duration::from_secs(ttl_secs),
+            max_entries,
+        }))
+    }
+}
+
+/// Counters since start, for `/stats`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct CacheStats {
+    pub enabled: bool,
+    pub ttl_secs: u64,
+    pub entries: usize,
+    pub hits: u64,
+    pub misses: u64,
+    pub invalidations: u64,
+    /// hits / (hits + misses); None before the first lookup.
+    pub hit_rate: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Key {
+    /// None for answers that belong to no wallet (capabilities).
+    wallet: Option<Uuid>,
+    command: u32,
+    input: Vec<u8>,
+}
+
+struct Inner {
+    entries: HashMap<Key, (Instant, Vec<u8>)>,
+    /// Bumped by every invalidation. A fill whose TA call started under an
+    /// older epoch may hold the answer from before the change, so it is dropped.
+    epoch: u64,
+}
+
+pub struct ResponseCache {
+    config: Option<CacheConfig>,
+    inner: Mutex<Inner>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    invalidations: AtomicU64,
+}
+
+impl ResponseCache {
+    pub fn new(config: Option<CacheConfig>) -> Self {
+        Self {
+            config,
+            inner: Mutex::new(Inner {
+                entries: HashMap::new(),
+                epoch: 0,
+            }),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            invalidations: AtomicU64::new(0),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.is_some()
+    }
+
+    /// Take before the TA call; hand back to [`Self::put`].
+    pub fn epoch(&self) -> u64 {
+        self.lock().epoch
+    }
+
+    pub fn get(
+        &self,
+        wallet: Option<Uuid>,
+        command: proto::Command,
+        input: &[u8],
+    ) -> Option<Vec<u8>> {
+        self.get_at(wallet, command, input, Instant::now())
+    }
+
+    fn get_at(
+        &self,
+        wallet: Option<Uuid>,
+        command: proto::Command,
+        input: &[u8],
+        now: Instant,
+    ) -> Option<Vec<u8>> {
+        let config = self.config?;
+        let key = Key {
+            wallet,
+            command: command.into(),
+            input: input.to_vec(),
+        };
+        let mut inner = self.lock();
+        let hit = match inner.entries.get(&key) {
+            Some((at, output)) if now.saturating_duration_since(*at) < config.ttl => {
+                Some(output.clone())
+            }
+            Some(_) => {
+                inner.entries.remove(&key);
+                None
+            }
+            None => None,
+        };
+        let counter = if hit.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        hit
+    }
+
+    pub fn put(
+        &self,
+        wallet: Option<Uuid>,
+        command: proto::Command,
+        input: Vec<u8>,
+        output: Vec<u8>,
+        epoch: u64,
+    ) {
+        self.put_at(wallet, command, input, output, epoch, Instant::now())
+    }
+
+    fn put_at(
+        &self,
+        wallet: Option<Uuid>,
+        command: proto::Command,
+        input: Vec<u8>,
+        output: Vec<u8>,
+        epoch: u64,
+        now: Instant,
+    ) {
+        let config = match self.config {
+            Some(config) => config,
+            None => return,
+        };
+        let mut inner = self.lock();
+        if inner.epoch != epoch {
+            return;
+        }
+        if inner.entries.len() >= config.max_entries {
+            inner
+                .entries
+                .retain(|_, (at, _)| now.saturating_duration_since(*at) < config.ttl);
+        }
+        if inner.entries.len() >= config.max_entries {
+            let oldest = inner
+                .entries
+                .iter()
+                .min_by_key(|(_, (at, _))| *at)
+                .map(|(k, _)| k.clone());
+            if let Some(oldest) = oldest {
+                inner.entries.remove(&oldest);
+            }
+        }
+        let key = Key {
+            wallet,
+            command: command.into(),
+            input,
+        };
+        inner.entries.insert(key, (now, output));
+    }
+
+    /// Drop every answer about `wallet` (None: the wallet-less ones).
+    pub fn invalidate(&self, wallet: Option<Uuid>) {
+        if !self.enabled() {
+            return;
+        }
+        let mut inner = self.lock();
+        inner.epoch += 1;
+        inner.entries.retain(|k, _| k.wallet != wallet);
+        self.invalidations.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let hits = self.hits.load(Ordering::Relaxed);
+        let misses = self.misses.load(Ordering::Relaxed);
+        CacheStats {
+            enabled: self.enabled(),
+            ttl_secs: self.config.map_or(0, |c| c.ttl.as_secs()),
+            entries: self.lock().entries.len(),
+            hits,
+            misses,
+            invalidations: self.invalidations.load(Ordering::Relaxed),
+            hit_rate: if hits + misses == 0 {
+                None
+            } else {
+                Some(hits as f64 / (hits + misses) as f64)
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proto::Command;
+
+    const A: Uuid = Uuid::from_bytes([0xa; 16]);
+    const B: Uuid = Uuid::from_bytes([0xb; 16]);
+
+    fn cache(ttl_secs: u64, max_entries: usize) -> ResponseCache {
+        ResponseCache::new(Some(CacheConfig {
+            ttl: Duration::from_secs(ttl_secs),
+            max_entries,
+        }))
+    }
+
+    #[test]
+    fn config_defaults_and_off_switch() {
+        let config = CacheConfig::parse(None, None).unwrap().unwrap();
+        assert_eq!(config.ttl, Duration::from_secs(DEFAULT_TTL_SECS));
+        assert_eq!(config.max_entries, DEFAULT_MAX_ENTRIES);
+        assert_eq!(CacheConfig::parse(Some("0"), None).unwrap(), None);
+        assert!(CacheConfig::parse(Some("301"), None).is_err());
+        assert!(CacheConfig::parse(Some("ten"), None).is_err());
+        assert!(CacheConfig::parse(None, Some("0")).is_err());
+    }
+
+    #[test]
+    fn hits_until_the_ttl_runs_out() {
+        let c = cache(10, 16);
+        let t0 = Instant::now();
+        assert_eq!(c.get_at(Some(A), Command::OtpList, b"in", t0), None);
+        c.put_at(
+            Some(A),
+            Command::OtpList,
+            b"in".to_vec(),
+            b"out".to_vec(),
+            c.epoch(),
+            t0,
+        );
+        let later = t0 + Duration::from_secs(9);
+        assert_eq!(
+            c.get_at(Some(A), Command::OtpList, b"in", later),
+            Some(b"out".to_vec())
+        );
+        assert_eq!(c.get_at(Some(A), Command::OtpList, b"other", later), None);
+        assert_eq!(c.get_at(Some(A), Command::PathPolicy, b"in", later), None);
+        let expired = t0 + Duration::from_secs(10);
+        assert_eq!(c.get_at(Some(A), Command::OtpList, b"in", expired), None);
+        let stats = c.stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (1, 4, 0));
+        assert_eq!(stats.hit_rate, Some(0.2));
+    }
+
+    #[test]
+    fn invalidation_is_per_wallet_and_drops_fills_that_raced_it() {
+        let c = cache(10, 16);
+        let t0 = Instant::now();
+        let epoch = c.epoch();
+        c.put_at(Some(A), Command::OtpList, vec![], b"a".to_vec(), epoch, t0);
+        c.put_at(Some(B), Command::OtpList, vec![], b"b".to_vec(), epoch, t0);
+        c.put_at(
+            None,
+            Command::GetCapabilities,
+            vec![],
+            b"caps".to_vec(),
+            epoch,
+            t0,
+        );
+        c.invalidate(Some(A));
+        assert_eq!(c.get_at(Some(A), Command::OtpList, &[], t0), None);
+        assert!(c.get_at(Some(B), Command::OtpList, &[], t0).is_some());
+        assert!(c.get_at(None, Command::GetCapabilities, &[], t0).is_some());
+
+        // A read that went to the TA before the change must not refill.
+        c.put_at(
+            Some(A),
+            Command::OtpList,
+            vec![],
+            b"stale".to_vec(),
+            epoch,
+            t0,
+        );
+        assert_eq!(c.get_at(Some(A), Command::OtpList, &[], t0), None);
+        assert_eq!(c.stats().invalidations, 1);
+    }
+
+    #[test]
+    fn full_cache_evicts_the_oldest_entry() {
+        let c = cache(10, 2);
+        let t0 = Instant::now();
+        for (i, wallet) in [A, B, Uuid::nil()].iter().enumerate() {
+            let at = t0 + Duration::from_secs(i as u64);
+            c.put_at(
+                Some(*wallet),
+                Command::OtpList,
+                vec![],
+                vec![i as u8],
+                0,
+                at,
+            );
+        }
+        let now = t0 + Duration::from_secs(3);
+        assert_eq!(c.get_at(Some(A), Command::OtpList, &[], now), None);
+        assert_eq!(c.get_at(Some(B), Command::OtpList, &[], now), Some(vec![1]));
+        assert_eq!(c.stats().entries, 2);
+    }
+
+    #[test]
+    fn disabled_cache_stores_and_counts_nothing() {
+        let c = ResponseCache::new(None);
+        c.put(Some(A), Command::OtpList, vec![], vec![1], c.epoch());
+        assert_eq!(c.get(Some(A), Command::OtpList, &[]), None);
+        c.invalidate(Some(A));
+        let stats = c.stats();
+        assert!(!stats.enabled);
+        assert_eq!((stats.hits, stats.misses, stats.invalidations), (0, 0, 0));
+    }
+}
@@ -0,0 +1,503 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Spending analytics and anomaly scoring for transaction signing.
+//!
+//! Before a transaction Sign reaches the passkey check, the CA compares it
+//! with the wallet's own ledger (`tx_history`): the hour it is signed at, the
+//! destination, and the amount against earlier amounts of the same asset. A
+//! [`RiskBackend`] turns those features into a 0-100 score; at or above the
+//! policy's step-up score the Sign needs a WebAuthn ceremony whose challenge
+//! was issued within the last `fresh_secs`.
+//!
+//! Scoring only decides how much confirmation a request needs. It never
+//! signs anything, so a wrong score costs the user a ceremony, not funds.
+
+use anyhow::{anyhow, bail, Context, Result};
+use proto::calldata::{decode_transaction, hex_addr, is_unlimited, DecodedCall};
+use proto::EthTransaction;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashSet};
+
+/// Ledger entries a profile is built from, newest first.
+pub const HISTORY_LIMIT: usize = 200;
+/// Below this many transactions the hour-of-day feature stays quiet.
+pub const MIN_HOUR_HISTORY: usize = 10;
+/// Below this many amounts of one asset there is no z-score.
+pub const MIN_AMOUNT_HISTORY: usize = 5;
+
+/// One outgoing transaction as the scorer sees it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpendSample {
+    pub chain_id: u64,
+    /// Recipient, spender or operator for a decoded token call, else `to`.
+    pub destination: [u8; 20],
+    /// None = native coin.
+    pub asset: Option<[u8; 20]>,
+    /// None = unlimited, or no amount (NFT operator approvals, unknown calls).
+    pub amount: Option<u128>,
+    pub unlimited: bool,
+    pub at: i64,
+}
+
+impl SpendSample {
+    /// None for contract deployments, which have no destination.
+    pub fn of(tx: &EthTransaction, at: i64) -> Option<Self> {
+        let word = |w: &[u8; 32]| {
+            if is_unlimited(w) {
+                None
+            } else {
+                let mut lo = [0u8; 16];
+                lo.copy_from_slice(&w[16..]);
+                Some(u128::from_be_bytes(lo))
+            }
+        };
+        let (destination, asset, amount, unlimited) = match decode_transaction(tx, &[]) {
+            DecodedCall::Deploy { .. } => return None,
+            DecodedCall::NativeTransfer { to } => (to, None, Some(tx.value), false),
+            DecodedCall::Transfer { token, to, amount }
+            | DecodedCall::TransferFrom {
+                token, to, amount, ..
+            } => (to, Some(token), word(&amount), is_unlimited(&amount)),
+            DecodedCall::Approve {
+                token,
+                spender,
+                amount,
+            }
+            | DecodedCall::IncreaseAllowance {
+                token,
+                spender,
+                amount,
+            }
+            | DecodedCall::Permit {
+                token,
+                spender,
+                amount,
+                ..
+            } => (spender, Some(token), word(&amount), is_unlimited(&amount)),
+            DecodedCall::SetApprovalForAll { operator, .. } => (operator, None, None, false),
+            DecodedCall::Named { target, .. } | DecodedCall::Unknown { target, .. } => {
+                (target, None, Some(tx.value), false)
+            }
+        };
+        Some(Self {
+            chain_id: tx.chain_id,
+            destination,
+            asset,
+            amount,
+            unlimited,
+            at,
+        })
+    }
+
+    fn hour(&self) -> usize {
+        (self.at.rem_euclid(86_400) / 3_600) as usize
+    }
+}
+
+/// Mean and standard deviation of one asset's amounts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AmountStats {
+    pub count: usize,
+    pub mean: f64,
+    pub std_dev: f64,
+}
+
+/// What a wallet's ledger says is normal for it.
+#[derive(Debug, Clone, Default)]
+pub struct SpendingProfile {
+    pub transactions: usize,
+    /// Transactions per UTC hour.
+    pub hours: [usize; 24],
+    pub destinations: HashSet<[u8; 20]>,
+    pub amounts: BTreeMap<(u64, Option<[u8; 20]>), AmountStats>,
+}
+
+impl SpendingProfile {
+    pub fn from_samples(samples: &[SpendSample]) -> Self {
+        let mut profile = Self {
+            transactions: samples.len(),
+            ..Self::default()
+        };
+        let mut by_asset: BTreeMap<(u64, Option<[u8; 20]>), Vec<f64>> = BTreeMap::new();
+        for s in samples {
+            profile.hours[s.hour()] += 1;
+            profile.destinations.insert(s.destination);
+            if let Some(amount) = s.amount.filter(|a| *a > 0) {
+                by_asset
+                    .entry((s.chain_id, s.asset))
+                    .or_default()
+                    .push(amount as f64);
+            }
+        }
+        for (asset, values) in by_asset {
+            let n = values.len() as f64;
+            let mean = values.iter().sum::<f64>() / n;
+            let var = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n;
+            profile.amounts.insert(
+                asset,
+                AmountStats {
+                    count: values.len(),
+                    mean,
+                    std_dev: var.sqrt(),
+                },
+            );
+        }
+        profile
+    }
+
+    /// `/kms/spending-analytics` body.
+    pub fn to_json(&self) -> Value {
+        let assets: Vec<Value> = self
+            .amounts
+            .iter()
+            .map(|((chain_id, asset), s)| {
+                json!({
+                    "chainId": chain_id,
+                    "asset": asset.as_ref().map(hex_addr),
+                    "count": s.count,
+                    "mean": s.mean,
+                    "stdDev": s.std_dev,
+                })
+            })
+            .collect();
+        json!({
+            "transactions": self.transactions,
+            "hoursUtc": self.hours.to_vec(),
+            "distinctDestinations": self.destinations.len(),
+            "assets": assets,
+        })
+    }
+}
+
+/// Features of one request against its wallet's profile.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Features {
+    pub hour_utc: u8,
+    pub unusual_hour: bool,
+    pub new_destination: bool,
+    /// None below [`MIN_AMOUNT_HISTORY`] samples of the asset, or no amount.
+    pub amount_z_score: Option<f64>,
+    pub unlimited_amount: bool,
+    pub history: usize,
+}
+
+impl Features {
+    pub fn of(profile: &SpendingProfile, sample: &SpendSample) -> Self {
+        let hour = sample.hour();
+        // The hour and its neighbours; fewer than 1 in 20 past transactions
+        // there counts as unusual.
+        let near: usize = [23, 0, 1]
+            .iter()
+            .map(|d| profile.hours[(hour + d) % 24])
+            .sum();
+        let unusual_hour =
+            profile.transactions >= MIN_HOUR_HISTORY && near * 20 < profile.transactions;
+        let amount_z_score = match (
+            sample.amount,
+            profile.amounts.get(&(sample.chain_id, sample.asset)),
+        ) {
+            (Some(amount), Some(stats)) if stats.count >= MIN_AMOUNT_HISTORY => {
+                // A wallet that always sends the same amount has no spread;
+                // a tenth of the mean keeps one odd amount from scoring infinity.
+                let spread = stats.std_dev.max(stats.mean / 10.0).max(1.0);
+                Some((amount as f64 - stats.mean) / spread)
+            }
+            _ => None,
+        };
+        Self {
+            hour_utc: hour as u8,
+            unusual_hour,
+            new_destination: !profile.destinations.contains(&sample.destination),
+            amount_z_score,
+            unlimited_amount: sample.unlimited,
+            history: profile.transactions,
+        }
+    }
+
+    /// The built-in score: additive, capped at 100.
+    pub fn builtin_score(&self) -> (u8, Vec<String>) {
+        let mut score = 0u32;
+        let mut reasons = Vec::new();
+        if self.new_destination {
+            score += 40;
+            reasons.push("new destination".to_string());
+        }
+        if self.unusual_hour {
+            score += 20;
+            reasons.push(format!("unusual hour ({:02}:00 UTC)", self.hour_utc));
+        }
+        if self.unlimited_amount {
+            score += 40;
+            reasons.push("unlimited amount".to_string());
+        } else if let Some(z) = self.amount_z_score.filter(|z| *z >= 2.0) {
+            score += if z >= 3.0 { 40 } else { 20 };
+            reasons.push(format!(
+                "amount is {:.1} standard deviations above usual",
+                z
+            ));
+        }
+        (score.min(100) as u8, reasons)
+    }
+}
+
+/// Where scores come from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RiskBackend {
+    /// [`Features::builtin_score`].
+    Builtin,
+    /// POST the features to an `http://` scorer; see [`RiskBackend::score`].
+    Webhook { url: String },
+}
+
+impl RiskBackend {
+    pub fn parse(spec: &str) -> Result<Self> {
+        match spec {
+            "builtin" => Ok(RiskBackend::Builtin),
+            url if url.starts_with("http://") => Ok(RiskBackend::Webhook {
+                url: url.to_string(),
+            }),
+            _ => bail!("KMS_RISK_BACKEND must be builtin or an http:// URL (no TLS in the CA)"),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RiskBackend::Builtin => "builtin",
+            RiskBackend::Webhook { .. } => "webhook",
+        }
+    }
+
+    /// Score a request. A webhook gets `{keyId, chainId, destination, asset,
+    /// amount, features, builtin: {score, reasons}}` and answers
+    /// `{score, reasons}`.
+    pub async fn score(
+        &self,
+        key_id: &str,
+        sample: &SpendSample,
+        features: &Features,
+    ) -> Result<(u8, Vec<String>)> {
+        let builtin = features.builtin_score();
+        let url = match self {
+            RiskBackend::Builtin => return Ok(builtin),
+            RiskBackend::Webhook { url } => url,
+        };
+        use warp::hyper::{body, Body, Client, Request};
+        let request = json!({
+            "keyId": key_id,
+            "chainId": sample.chain_id,
+            "destination": hex_addr(&sample.destination),
+            "asset": sample.asset.as_ref().map(hex_addr),
+            "amount": sample.amount.map(|a| a.to_string()),
+            "features": features,
+            "builtin": { "score": builtin.0, "reasons": builtin.1 },
+        });
+        let req = Request::post(url.as_str())
+            .header("content-type", "application/json")
+            .body(Body::from(request.to_string()))?;
+        let resp = tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            Client::new().request(req),
+        )
+        .await
+        .map_err(|_| anyhow!("risk scorer did not answer within 5s"))?
+        .context("risk scorer unreachable")?;
+        let status = resp.status();
+        let bytes = body::to_bytes(resp.into_body()).await?;
+        if !status.is_success() {
+            bail!("risk scorer returned HTTP {}", status);
+        }
+        parse_webhook_reply(&bytes)
+    }
+}
+
+fn parse_webhook_reply(bytes: &[u8]) -> Result<(u8, Vec<String>)> {
+    let reply: Value = serde_json::from_slice(bytes).context("risk scorer reply is not JSON")?;
+    let score = reply["score"]
+        .as_u64()
+        .filter(|s| *s <= 100)
+        .ok_or_else(|| anyhow!("risk scorer reply needs a score of 0-100"))?;
+    let reasons = match reply.get("reasons") {
+        Some(r) => serde_json::from_value(r.clone()).context("risk scorer reasons")?,
+        None => Vec::new(),
+    };
+    Ok((score as u8, reasons))
+}
+
+/// When a score calls for step-up authentication.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RiskPolicy {
+    pub backend: RiskBackend,
+    /// Scores at or above this need a fresh ceremony.
+    pub step_up_score: u8,
+    /// How recently the step-up challenge must have been issued.
+    pub fresh_secs: i64,
+}
+
+impl RiskPolicy {
+    /// `KMS_RISK_BACKEND` (builtin | http://…) turns scoring on;
+    /// `KMS_RISK_STEP_UP_SCORE` (default 70) and `KMS_RISK_FRESH_SECS`
+    /// (default 60, at most the 300 s a challenge lives) tune it.
+    pub fn from_env() -> Option<Result<Self>> {
+        let var = |k: &str| std::env::var(k).ok().filter(|v| !v.is_empty());
+        let backend = var("KMS_RISK_BACKEND")?;
+        Some((|| {
+            let step_up_score = match var("KMS_RISK_STEP_UP_SCORE") {
+                Some(s) => s
+                    .parse::<u8>()
+                    .ok()
+                    .filter(|s| (1..=100).contains(s))
+                    .ok_or_else(|| anyhow!("KMS_RISK_STEP_UP_SCORE must be 1-100"))?,
+                None => 70,
+            };
+            let fresh_secs = match var("KMS_RISK_FRESH_SECS") {
+                Some(s) => s
+                    .parse::<i64>()
+                    .ok()
+                    .filter(|s| (10..=300).contains(s))
+                    .ok_or_else(|| anyhow!("KMS_RISK_FRESH_SECS must be 10-300"))?,
+                None => 60,
+            };
+            Ok(Self {
+                backend: RiskBackend::parse(&backend)?,
+                step_up_score,
+                fresh_secs,
+            })
+        })())
+    }
+}
+
+/// The verdict a Sign or DescribeTransaction reports.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct RiskAssessment {
+    pub score: u8,
+    pub reasons: Vec<String>,
+    pub backend: String,
+    pub step_up_required: bool,
+    /// Age limit of the step-up challenge, when one is required.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fresh_within_secs: Option<i64>,
+}
+
+impl RiskAssessment {
+    pub fn new(policy: &RiskPolicy, score: u8, reasons: Vec<String>) -> Self {
+        let step_up_required = score >= policy.step_up_score;
+        Self {
+            score,
+            reasons,
+            backend: policy.backend.name().to_string(),
+            step_up_required,
+            fresh_within_secs: Some(policy.fresh_secs).filter(|_| step_up_required),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: i64 = 3_600;
+
+    fn native(to: u8, value: u128, at: i64) -> SpendSample {
+        SpendSample::of(
+            &EthTransaction {
+                chain_id: 1,
+                nonce: 0,
+                to: Some([to; 20]),
+                value,
+                gas_price: 1,
+                gas: 21_000,
+                data: vec![],
+            },
+            at,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn scores_against_the_wallets_own_history() {
+        // Ten daytime payments of about 1 ETH to two addresses.
+        let history: Vec<_> = (0..10)
+            .map(|i| {
+                native(
+                    1 + (i % 2) as u8,
+                    1_000 + i as u128 * 10,
+                    14 * HOUR + i * 86_400,
+                )
+            })
+            .collect();
+        let profile = SpendingProfile::from_samples(&history);
+
+        let usual = Features::of(&profile, &native(1, 1_040, 15 * HOUR));
+        assert!(!usual.new_destination && !usual.unusual_hour);
+        assert!(usual.amount_z_score.unwrap().abs() < 1.0);
+        assert_eq!(usual.builtin_score().0, 0);
+
+        let odd = Features::of(&profile, &native(9, 50_000, 3 * HOUR));
+        assert!(odd.new_destination && odd.unusual_hour);
+        assert!(odd.amount_z_score.unwrap() > 3.0);
+        let (score, reasons) = odd.builtin_score();
+        assert_eq!(score, 100);
+        assert_eq!(reasons.len(), 3);
+
+        // No history: only the destination is news.
+        let first = Features::of(&SpendingProfile::default(), &native(1, 10, 3 * HOUR));
+        assert_eq!(first.builtin_score().0, 40);
+    }
+
+    #[test]
+    fn decodes_token_calls_and_webhook_replies() {
+        let mut data = proto::calldata::selector("approve(address,uint256)").to_vec();
+        data.extend_from_slice(&[0u8; 12]);
+        data.extend_from_slice(&[0x22; 20]);
+        data.extend_from_slice(&[0xff; 32]);
+        let tx = EthTransaction {
+            chain_id: 1,
+            nonce: 0,
+            to: Some([0x11; 20]),
+            value: 0,
+            gas_price: 1,
+            gas: 60_000,
+            data,
+        };
+        let sample = SpendSample::of(&tx, 0).unwrap();
+        assert_eq!(sample.destination, [0x22; 20]);
+        assert_eq!(sample.asset, Some([0x11; 20]));
+        assert!(sample.unlimited && sample.amount.is_none());
+
+        assert_eq!(
+            parse_webhook_reply(br#"{"score":85,"reasons":["sanctioned"]}"#).unwrap(),
+            (85, vec!["sanctioned".to_string()])
+        );
+        assert!(parse_webhook_reply(br#"{"score":101}"#).is_err());
+        assert!(RiskBackend::parse("https://scorer").is_err());
+
+        let policy = RiskPolicy {
+            backend: RiskBackend::Builtin,
+            step_up_score: 70,
+            fresh_secs: 60,
+        };
+        assert!(!RiskAssessment::new(&policy, 69, vec![]).step_up_required);
+        assert_eq!(
+            RiskAssessment::new(&policy, 70, vec![]).fresh_within_secs,
+            Some(60)
+        );
+    }
+}
@@ -0,0 +1,12919 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! The CA's operations, independent of HTTP.
+//!
+//! [`KmsApiServer`] owns the database, the TEE handle and every backend the
+//! CA talks to, and has one method per API operation. A method takes the
+//! request struct, checks passkeys, policy and risk, calls the TEE and
+//! returns the response struct or an `anyhow::Error` that `api_error`
+//! classifies. Nothing here knows about warp: the routes, handlers,
+//! rejection mapping and background tasks are in the `kms-api-server`
+//! binary (api_server.rs), which only decodes a request, calls the method
+//! and encodes the result. The tests below drive the methods with an
+//! in-memory database and no server.
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use hex;
+use p256::ecdsa::{signature::Verifier, Signature, VerifyingKey};
+use p256::EncodedPoint;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::accounting_export;
+use crate::aggregator::{self, AggregationQueue, BundlerConfig};
+use crate::agent_jwt;
+use crate::chain_watch;
+use crate::compliance::{self, Screening, ScreeningRequest};
+use crate::config_manager::ConfigManager;
+use crate::conformance;
+use crate::db::{
+    AgentKeyRow, GasTankRow, GasTankTopUpRow, IdentityLinkRow, IdentityRow, KeyRegionRow, KmsDb,
+    PasskeyAttestation, ProvisioningBatchRow, StealthMetaRow, TenantWalletRow, WalletDeviceRow,
+    WalletRow,
+};
+use crate::device_health::{self, HealthConfig, HealthReport};
+use crate::fido_mds::FidoMds;
+use crate::gas_tank::{self, GasTankConfig};
+use crate::key_pin::{self, PinCheck};
+use crate::keystore;
+use crate::log_level::{self, LogLevelOrderRequest};
+use crate::migration;
+use crate::notify::NotificationPrefs;
+use crate::otel;
+use crate::passkey_lockout::PasskeyLockout;
+use crate::paymaster::{self, FeeQuoteJson, PaymasterConfig};
+use crate::permit;
+use crate::pkcs11::Pkcs11Signer;
+use crate::provisioning::{self, ProvisionedWallet, ProvisioningLimits, ProvisioningReport};
+use crate::rate_limit::RateLimiter;
+use crate::remote_approval::{self, PushRelay};
+use crate::replication::{self, Failover};
+use crate::response_cache::CacheConfig;
+use crate::risk::{self, RiskAssessment, RiskPolicy, SpendSample, SpendingProfile};
+use crate::signer::{self, Signer};
+use crate::siwe;
+use crate::standby::{self, FenceRequest, PeerStatus, Role, Sealed, StandbyConfig};
+use crate::stealth;
+use crate::ta_client::{RequestContext, TeeHandle, TA_UUID};
+use crate::ta_release::{ReleaseCheckConfig, VerifiedRelease};
+use crate::tamper::TamperOrderRequest;
+#[cfg(feature = "threshold-ecdsa")]
+use crate::threshold::{self, CoSigner, HttpCoSigner};
+use crate::token_transfer;
+use crate::tx_rescue::{self, RescueMode};
+use crate::usage::{self, RequestOrigin};
+use crate::verify;
+use crate::webauthn;
+use proto;
+use proto::chain::Chain;
+use proto::event::{AuditEventPayload, EventEnvelope};
+
+/// Estimated seconds per TEE operation with persistent session
+const TEE_OP_ESTIMATE_SECS: u64 = 1;
+
+// ========================================
+// AWS KMS 兼容的数据结构
+// ========================================
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateKeyRequest {
+    #[serde(rename = "KeyId", skip_serializing_if = "Option::is_none", default)]
+    pub key_id: Option<String>,
+    #[serde(rename = "Description")]
+    pub description: String,
+    #[serde(rename = "KeyUsage")]
+    pub key_usage: String,
+    #[serde(rename = "KeySpec")]
+    pub key_spec: String,
+    #[serde(rename = "Origin")]
+    pub origin: String,
+    /// P-256 PassKey public key in hex (0x04..., 65 bytes uncompressed) — mandatory
+    #[serde(rename = "PasskeyPublicKey")]
+    pub passkey_public_key: String,
+    /// Create a multi-region primary in this node's region (`KMS_REGION`).
+    #[serde(
+        rename = "MultiRegion",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub multi_region: Option<bool>,
+    /// Restore from a passphrase backup (`crate::keystore` JSON, as written by
+    /// `export_key --keystore`) instead of fresh entropy. The TA decrypts it.
+    #[serde(rename = "Keystore", skip_serializing_if = "Option::is_none", default)]
+    pub keystore: Option<serde_json::Value>,
+    #[serde(
+        rename = "KeystorePassphrase",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub keystore_passphrase: Option<String>,
+    /// Finish a hardware-wallet migration opened by /kms/migration/begin. The
+    /// TA creates the wallet only if the phrase reproduces the xpub and every
+    /// confirmed address.
+    #[serde(rename = "Migration", skip_serializing_if = "Option::is_none", default)]
+    pub migration: Option<MigrationPackage>,
+    /// Import an existing 24-word BIP39 phrase; the TA checks its checksum.
+    /// Never logged or stored by the CA.
+    #[serde(rename = "Mnemonic", skip_serializing_if = "Option::is_none", default)]
+    pub mnemonic: Option<String>,
+}
+
+/// The recovery phrase sealed to the TA key from /kms/migration/begin
+/// (`proto::migration`), with the receive addresses the user compared on the
+/// hardware wallet. Binary fields are hex.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MigrationPackage {
+    #[serde(rename = "SessionId")]
+    pub session_id: String,
+    /// The client's ephemeral secp256k1 key, compressed.
+    #[serde(rename = "ClientPublicKey")]
+    pub client_public_key: String,
+    #[serde(rename = "Ciphertext")]
+    pub ciphertext: String,
+    #[serde(rename = "Mac")]
+    pub mac: String,
+    #[serde(rename = "ConfirmedAddresses")]
+    pub confirmed_addresses: Vec<MigrationConfirmedAddress>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MigrationConfirmedAddress {
+    /// `m/44'/60'/0'/0/{Index}`.
+    #[serde(rename = "Index")]
+    pub index: u32,
+    #[serde(rename = "Address")]
+    pub address: String,
+}
+
+impl MigrationPackage {
+    fn to_input(&self, passkey_pubkey: &[u8]) -> Result<proto::MigrationImportInput> {
+        fn fixed<const N: usize>(name: &str, text: &str) -> Result<[u8; N]> {
+            use std::convert::TryInto;
+            let bytes = hex::decode(text.trim_start_matches("0x"))
+                .map_err(|e| anyhow!("Invalid Migration.{} hex: {}", name, e))?;
+            bytes
+                .try_into()
+                .map_err(|_| anyhow!("Migration.{} must be {} bytes", name, N))
+        }
+        let confirmed = self
+            .confirmed_addresses
+            .iter()
+            .map(|c| {
+                Ok(proto::migration::ConfirmedAddress {
+                    index: c.index,
+                    address: KmsApiServer::parse_address_hex(&c.address)?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        proto::migration::validate_confirmed(&confirmed).map_err(|e| anyhow!("{}", e))?;
+        Ok(proto::MigrationImportInput {
+            session_id: fixed::<16>("SessionId", &self.session_id)?,
+            client_pubkey: fixed::<33>("ClientPublicKey", &self.client_public_key)?.to_vec(),
+            ciphertext: hex::decode(self.ciphertext.trim_start_matches("0x"))
+                .map_err(|e| anyhow!("Invalid Migration.Ciphertext hex: {}", e))?,
+            mac: fixed::<32>("Mac", &self.mac)?,
+            passkey_pubkey: passkey_pubkey.to_vec(),
+            confirmed,
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateKeyResponse {
+    #[serde(rename = "KeyMetadata")]
+    pub key_metadata: KeyMetadata,
+    #[serde(rename = "Mnemonic")]
+    pub mnemonic: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DescribeKeyRequest {
+    #[serde(rename = "KeyId")]
+    pub key_id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DescribeKeyResponse {
+    #[serde(rename = "KeyMetadata")]
+    pub key_metadata: KeyMetadata,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ListKeysRequest {
+    #[serde(rename = "Limit", skip_serializing_if = "Option::is_none")]
+    pub limit: Option<i32>,
+    #[serde(rename = "Marker", skip_serializing_if = "Option::is_none")]
+    pub marker: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ListKeysResponse {
+    #[serde(rename = "Keys")]
+    pub keys: Vec<KeyListEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct KeyListEntry {
+    #[serde(rename = "KeyId")]
+    pub key_id: String,
+    #[serde(rename = "KeyArn")]
+    pub key_arn: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyMetadata {
+    #[serde(rename = "KeyId")]
+    pub key_id: String,
+    #[serde(rename = "Address", skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    #[serde(rename = "PublicKey", skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<String>,
+    #[serde(rename = "DerivationPath", skip_serializing_if = "Option::is_none")]
+    pub derivation_path: Option<String>,
+    #[serde(rename = "Arn")]
+    pub arn: String,
+    #[serde(rename = "CreationDate")]
+    pub creation_date: DateTime<Utc>,
+    #[serde(rename = "Enabled")]
+    pub enabled: bool,
+    #[serde(rename = "Description")]
+    pub description: String,
+    #[serde(rename = "KeyUsage")]
+    pub key_usage: String,
+    #[serde(rename = "KeySpec")]
+    pub key_spec: String,
+    #[serde(rename = "Origin")]
+    pub origin: String,
+    #[serde(rename = "PasskeyPublicKey", skip_serializing_if = "Option::is_none")]
+    pub passkey_public_key: Option<String>,
+    /// Issue #42: RFC3339 timestamp of the last successful operation for this key,
+    /// derived from tx_log. None if the key has never had a successful operation.
+    #[serde(rename = "LastUsedAt", skip_serializing_if = "Option::is_none")]
+    pub last_used_at: Option<String>,
+    /// Issue #42: key lifecycle gate — "active" or "frozen". Frozen keys reject
+    /// signing until unfrozen via passkey (POST /UnfreezeKey).
+    #[serde(rename = "LifecycleStatus")]
+    pub lifecycle_status: String,
+    #[serde(rename = "MultiRegion", default)]
+    pub multi_region: bool,
+    #[serde(
+        rename = "MultiRegionConfiguration",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub multi_region_configuration: Option<MultiRegionConfiguration>,
+}
+
+/// AWS multi-region view of a key; each region is a device holding a replica
+/// of the wallet (`key_regions`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MultiRegionConfiguration {
+    /// PRIMARY or REPLICA — the role of the region answering.
+    #[serde(rename = "MultiRegionKeyType")]
+    pub multi_region_key_type: String,
+    #[serde(rename = "PrimaryKey", skip_serializing_if = "Option::is_none")]
+    pub primary_key: Option<MultiRegionKey>,
+    #[serde(rename = "ReplicaKeys")]
+    pub replica_keys: Vec<MultiRegionKey>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MultiRegionKey {
+    #[serde(rename = "Arn")]
+    pub arn: String,
+    #[serde(rename = "Region")]
+    pub region: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeriveAddressRequest {
+    #[serde(rename = "KeyId")]
+    pub key_id: String,
+    #[serde(rename = "DerivationPath")]
+    pub derivation_path: String,
+    /// Legacy: raw PassKey assertion (hex)
+    #[serde(rename = "Passkey", skip_serializing_if = "Option::is_none", default)]
+    pub passkey: Option<PasskeyAssertion>,
+    /// WebAuthn ceremony assertion (from BeginAuthentication)
+    #[serde(rename = "WebAuthn", skip_serializing_if = "Option::is_none", default)]
+    pub webauthn: Option<WebAuthnAssertion>,
+    /// `ethereum` (default), `bsc`, `polygon` or `tron`; the path's coin type
+    /// must be the chain's (60 for the EVM chains, 195 for Tron).
+    #[serde(rename = "Chain", skip_serializing_if = "Option::is_none", default)]
+    pub chain: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeriveAddressResponse {
+    #[serde(rename = "Address")]
+    pub address: String,
+    #[serde(rename = "PublicKey")]
+    pub public_key: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignRequest {
+    // New: Address-based lookup (priority)
+    #[serde(rename = "Address", skip_serializing_if = "Option::is_none", default)]
+    pub address: Option<String>,
+    // Old: KeyId + DerivationPath (backward compatibility)
+    #[serde(rename = "KeyId", skip_serializing_if = "Option::is_none", default)]
+    pub key_id: Option<String>,
+    #[serde(
+        rename = "DerivationPath",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub derivation_path: Option<String>,
+    // Transaction signing mode (original)
+    #[serde(
+        rename = "Transaction",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub transaction: Option<EthereumTransaction>,
+    // Message signing mode (new)
+    #[serde(rename = "Message", skip_serializing_if = "Option::is_none", default)]
+    pub message: Option<String>,
+    #[serde(
+        rename = "SigningAlgorithm",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub signing_algorithm: Option<String>,
+    /// Legacy: raw PassKey assertion (hex)
+    #[serde(rename = "Passkey", skip_serializing_if = "Option::is_none", default)]
+    pub passkey: Option<PasskeyAssertion>,
+    /// WebAuthn ceremony assertion (from BeginAuthentication)
+    #[serde(rename = "WebAuthn", skip_serializing_if = "Option::is_none", default)]
+    pub webauthn: Option<WebAuthnAssertion>,
+    /// Signing context for Message mode (Raw / PersonalMsg / Login). Absent = Raw.
+    #[serde(rename = "Context", skip_serializing_if = "Option::is_none", default)]
+    pub context: Option<SigningContextRequest>,
+    /// Transaction mode: extra function signatures for the calldata summary.
+    #[serde(
+        rename = "SummaryAbis",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub summary_abis: Option<Vec<String>>,
+    /// Transaction mode: the WebAuthn challenge commits to the summary
+    /// (see /DescribeTransaction) rather than the bare tx hash.
+    #[serde(
+        rename = "SummaryCommitted",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub summary_committed: Option<bool>,
+}
+
+/// Domain-separation context declared by a Sign/SignHash caller. The TA
+/// recomputes the digest for UserOp/Eip712 and applies EIP-191 for
+/// PersonalMsg/Login; Raw is the legacy opaque-digest mode.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "Type")]
+pub enum SigningContextRequest {
+    Raw,
+    UserOp {
+        #[serde(rename = "InnerHash")]
+        inner_hash: String,
+        #[serde(rename = "EntryPoint")]
+        entry_point: String,
+        #[serde(rename = "ChainId")]
+        chain_id: u64,
+    },
+    Eip712 {
+        #[serde(rename = "DomainSeparator")]
+        domain_separator: String,
+        #[serde(rename = "StructHash")]
+        struct_hash: String,
+    },
+    PersonalMsg,
+    Login,
+}
+
+impl SigningContextRequest {
+    fn to_proto(&self) -> Result<proto::SigningContext> {
+        fn fixed<const N: usize>(field: &str, v: &str) -> Result<[u8; N]> {
+            let bytes = hex::decode(v.trim_start_matches("0x"))
+                .map_err(|e| anyhow!("Invalid {} hex: {}", field, e))?;
+            if bytes.len() != N {
+                return Err(anyhow!("{} must be exactly {} bytes", field, N));
+            }
+            let mut arr = [0u8; N];
+            arr.copy_from_slice(&bytes);
+            Ok(arr)
+        }
+        Ok(match self {
+            Self::Raw => proto::SigningContext::Raw,
+            Self::UserOp {
+                inner_hash,
+                entry_point,
+                chain_id,
+            } => proto::SigningContext::UserOp {
+                inner_hash: fixed("InnerHash", inner_hash)?,
+                entry_point: fixed("EntryPoint", entry_point)?,
+                chain_id: *chain_id,
+            },
+            Self::Eip712 {
+                domain_separator,
+                struct_hash,
+            } => proto::SigningContext::Eip712 {
+                domain_separator: fixed("DomainSeparator", domain_separator)?,
+                struct_hash: fixed("StructHash", struct_hash)?,
+            },
+            Self::PersonalMsg => proto::SigningContext::PersonalMsg,
+            Self::Login => proto::SigningContext::Login,
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignResponse {
+    #[serde(rename = "Signature")]
+    pub signature: String,
+    #[serde(rename = "TransactionHash")]
+    pub transaction_hash: String,
+    #[serde(rename = "Summary", skip_serializing_if = "Option::is_none", default)]
+    pub summary: Option<String>,
+    /// Transaction mode with KMS_RISK_BACKEND set.
+    #[serde(rename = "Risk", skip_serializing_if = "Option::is_none")]
+    pub risk: Option<RiskAssessment>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DescribeTransactionRequest {
+    #[serde(rename = "Transaction")]
+    pub transaction: EthereumTransaction,
+    #[serde(
+        rename = "SummaryAbis",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub summary_abis: Option<Vec<String>>,
+    /// The signing key, to score the transaction against its history.
+    #[serde(rename = "KeyId", skip_serializing_if = "Option::is_none", default)]
+    pub key_id: Option<String>,
+    #[serde(rename = "Address", skip_serializing_if = "Option::is_none", default)]
+    pub address: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DescribeTransactionResponse {
+    /// Exactly the string the TA rebuilds; a summary-committed Sign binds
+    /// challenge = SHA-256(nonce || keccak256("AirAccount-tx-summary-v1" || tx_hash || Summary)).
+    #[serde(rename = "Summary")]
+    pub summary: String,
+    /// What Sign will ask for: with `StepUpRequired`, a WebAuthn challenge
+    /// begun at most `FreshWithinSecs` before the Sign.
+    #[serde(rename = "Risk", skip_serializing_if = "Option::is_none")]
+    pub risk: Option<RiskAssessment>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignHashRequest {
+    #[serde(rename = "KeyId", skip_serializing_if = "Option::is_none", default)]
+    pub key_id: Option<String>,
+    #[serde(rename = "Address", skip_serializing_if = "Option::is_none", default)]
+    pub address: Option<String>,
+    #[serde(
+        rename = "DerivationPath",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub derivation_path: Option<String>,
+    #[serde(rename = "Hash")]
+    pub hash: String,
+    #[serde(
+        rename = "SigningAlgorithm",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub signing_algorithm: Option<String>,
+    /// Legacy: raw PassKey assertion (hex)
+    #[serde(rename = "Passkey", skip_serializing_if = "Option::is_none", default)]
+    pub passkey: Option<PasskeyAssertion>,
+    /// WebAuthn ceremony assertion (from BeginAuthentication)
+    #[serde(rename = "WebAuthn", skip_serializing_if = "Option::is_none", default)]
+    pub webauthn: Option<WebAuthnAssertion>,
+    /// Signing context (Raw / UserOp / Eip712). Absent = Raw.
+    #[serde(rename = "Context", skip_serializing_if = "Option::is_none", default)]
+    pub context: Option<SigningContextRequest>,
+    /// UserOp paying its fee in ERC-20: the `quoteHash` from /kms/fee/quote.
+    /// The WebAuthn challenge then commits to that quote's `reviewDigest`.
+    #[serde(
+        rename = "FeeQuoteHash",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub fee_quote_hash: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignHashResponse {
+    #[serde(rename = "Signature")]
+    pub signature: String,
+}
+
+/// TrentService.Verify. The key is named like Sign: Address, or KeyId with
+/// an optional DerivationPath (absent = the key's primary address).
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VerifyRequest {
+    #[serde(rename = "KeyId", skip_serializing_if = "Option::is_none", default)]
+    pub key_id: Option<String>,
+    #[serde(rename = "Address", skip_serializing_if = "Option::is_none", default)]
+    pub address: Option<String>,
+    #[serde(
+        rename = "DerivationPath",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub derivation_path: Option<String>,
+    /// base64 (AWS) or 0x-hex.
+    #[serde(rename = "Message")]
+    pub message: String,
+    /// RAW (default) | DIGEST
+    #[serde(
+        rename = "MessageType",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub message_type: Option<String>,
+    /// DER (AWS) or the 65-byte R || S || V from Sign; base64 or 0x-hex.
+    #[serde(rename = "Signature")]
+    pub signature: String,
+    #[serde(rename = "SigningAlgorithm")]
+    pub signing_algorithm: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VerifyResponse {
+    #[serde(rename = "KeyId")]
+    pub key_id: String,
+    /// Always true: an invalid signature is a KMSInvalidSignatureException.
+    #[serde(rename = "SignatureValid")]
+    pub signature_valid: bool,
+    #[serde(rename = "SigningAlgorithm")]
+    pub signing_algorithm: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeleteKeyRequest {
+    #[serde(rename = "KeyId")]
+    pub key_id: String,
+    #[serde(
+        rename = "PendingWindowInDays",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub pending_window_in_days: Option<i32>,
+    /// Legacy: raw PassKey assertion (hex)
+    #[serde(rename = "Passkey", skip_serializing_if = "Option::is_none", default)]
+    pub passkey: Option<PasskeyAssertion>,
+    /// WebAuthn ceremony assertion (from BeginAuthentication)
+    #[serde(rename = "WebAuthn", skip_serializing_if = "Option::is_none", default)]
+    pub webauthn: Option<WebAuthnAssertion>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeleteKeyResponse {
+    #[serde(rename = "KeyId")]
+    pub key_id: String,
+    #[serde(rename = "DeletionDate")]
+    pub deletion_date: DateTime<Utc>,
+    /// Proof-of-erasure from the TA; absent for gap keys and older TAs.
+    #[serde(
+        rename = "DeletionCertificate",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub deletion_certificate: Option<DeletionCertificateView>,
+}
+
+/// A TA deletion certificate. `Attestation` is the device's attestation
+/// evidence with `nonce` = `Digest` = keccak256(`Certificate`); verify it like
+/// any `GET /attestation` evidence.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeletionCertificateView {
+    #[serde(rename = "Certificate")]
+    pub certificate: String,
+    #[serde(rename = "Digest")]
+    pub digest: String,
+    #[serde(rename = "WalletId")]
+    pub wallet_id: String,
+    #[serde(rename = "DeletedAt")]
+    pub deleted_at: u64,
+    #[serde(rename = "RollbackCounter")]
+    pub rollback_counter: Option<u64>,
+    #[serde(rename = "Attestation")]
+    pub attestation: Option<serde_json::Value>,
+}
+
+impl DeletionCertificateView {
+    fn from_proof(proof: proto::erasure::DeletionProof) -> Result<Self> {
+        proof.check_binding().map_err(|e| anyhow!(e))?;
+        let cert = &proof.certificate;
+        Ok(DeletionCertificateView {
+            certificate: cert.message(),
+            digest: hex::encode(cert.digest()),
+            wallet_id: cert.wallet_id.to_string(),
+            deleted_at: cert.deleted_at,
+            rollback_counter: cert.rollback_counter,
+            attestation: proof
+                .attestation
+                .map(|ev| serde_json::to_value(AttestationResponse::from_evidence(ev)))
+                .transpose()?,
+        })
+    }
+}
+
+/// Issue #42: POST /UnfreezeKey — owner WebAuthn-gated unfreeze of a dormant key.
+/// Same passkey authentication shape as DeleteKey (strict assertion required).
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UnfreezeKeyRequest {
+    #[serde(rename = "KeyId")]
+    pub key_id: String,
+    /// Legacy: raw PassKey assertion (hex)
+    #[serde(rename = "Passkey", skip_serializing_if = "Option::is_none", default)]
+    pub passkey: Option<PasskeyAssertion>,
+    /// WebAuthn ceremony assertion (from BeginAuthentication)
+    #[serde(rename = "WebAuthn", skip_serializing_if = "Option::is_none", default)]
+    pub webauthn: Option<WebAuthnAssertion>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UnfreezeKeyResponse {
+    #[serde(rename = "KeyId")]
+    pub key_id: String,
+    #[serde(rename = "LifecycleStatus")]
+    pub lifecycle_status: String,
+}
+
+/// Admin force-purge request — bypasses passkey, deletes from TEE + SQLite.
+/// Requires Authorization: Bearer $KMS_ADMIN_TOKEN header.
+///
+/// DEV/TEST ONLY — compiled in only under the `admin-purge` feature. Release
+/// builds (no feature) contain no admin surface at all.
+#[cfg(feature = "admin-purge")]
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AdminPurgeKeyRequest {
+    pub key_id: String,
+    /// Human-readable reason for audit log (e.g. "orphan cleanup", "test key")
+    #[serde(default)]
+    pub reason: String,
+}
+
+#[cfg(feature = "admin-purge")]
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AdminPurgeKeyResponse {
+    pub key_id: String,
+    pub tee_purged: bool,
+    pub sqlite_deleted: bool,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GetPublicKeyRequest {
+    #[serde(rename = "KeyId")]
+    pub key_id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GetPublicKeyResponse {
+    #[serde(rename = "KeyId")]
+    pub key_id: String,
+    #[serde(rename = "PublicKey")]
+    pub public_key: String,
+    #[serde(rename = "KeyUsage")]
+    pub key_usage: String,
+    #[serde(rename = "KeySpec")]
+    pub key_spec: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EthereumTransaction {
+    #[serde(rename = "chainId")]
+    pub chain_id: u64,
+    pub nonce: u64,
+    pub to: String,
+    pub value: String,
+    /// Empty for an EIP-1559 transaction.
+    #[serde(rename = "gasPrice", default)]
+    pub gas_price: String,
+    pub gas: u64,
+    pub data: String,
+    /// EIP-1559: set with `maxPriorityFeePerGas` instead of `gasPrice`, and
+    /// Sign signs a type-2 transaction.
+    #[serde(
+        rename = "maxFeePerGas",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub max_fee_per_gas: Option<String>,
+    #[serde(
+        rename = "maxPriorityFeePerGas",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub max_priority_fee_per_gas: Option<String>,
+    #[serde(rename = "accessList", skip_serializing_if = "Vec::is_empty", default)]
+    pub access_list: Vec<AccessListEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AccessListEntry {
+    pub address: String,
+    #[serde(rename = "storageKeys", default)]
+    pub storage_keys: Vec<String>,
+}
+
+impl EthereumTransaction {
+    fn is_eip1559(&self) -> bool {
+        self.max_fee_per_gas.is_some()
+            || self.max_priority_fee_per_gas.is_some()
+            || !self.access_list.is_empty()
+    }
+
+    /// The legacy transaction. Only Sign takes EIP-1559 fields.
+    fn to_proto(&self) -> Result<proto::EthTransaction> {
+        if self.is_eip1559() {
+            return Err(anyhow!(
+                "maxFeePerGas, maxPriorityFeePerGas and accessList are only accepted by Sign"
+            ));
+        }
+        proto::tx_builder::build_legacy(
+            self.chain_id,
+            self.nonce,
+            &self.to,
+            &self.value,
+            &self.gas_price,
+            self.gas,
+            &self.data,
+        )
+        .map_err(|e| anyhow!(e))
+    }
+
+    fn to_eip1559(&self) -> Result<proto::eip1559::Eip1559Transaction> {
+        use proto::tx_builder::{parse_data, parse_quantity};
+        use std::convert::TryInto;
+        if !self.gas_price.is_empty() {
+            return Err(anyhow!("gasPrice cannot be combined with maxFeePerGas"));
+        }
+        let fee = |value: &Option<String>, field: &str| -> Result<u128> {
+            let value = value
+                .as_deref()
+                .ok_or_else(|| anyhow!("Transaction.{} is required for EIP-1559", field))?;
+            parse_quantity(value, field).map_err(|e| anyhow!(e))
+        };
+        let mut access_list = Vec::with_capacity(self.access_list.len());
+        for entry in &self.access_list {
+            let mut storage_keys = Vec::with_capacity(entry.storage_keys.len());
+            for key in &entry.storage_keys {
+                let key = parse_data(key).map_err(|e| anyhow!(e))?;
+                storage_keys.push(
+                    key.try_into().map_err(|_| {
+                        anyhow!("Transaction.accessList: storage keys are 32 bytes")
+                    })?,
+                );
+            }
+            access_list.push(proto::eip1559::AccessListItem {
+                address: proto::eip55::parse_address(&entry.address)
+                    .map_err(|e| anyhow!("Transaction.accessList: {}", e))?,
+                storage_keys,
+            });
+        }
+        let transaction = proto::eip1559::Eip1559Transaction {
+            chain_id: self.chain_id,
+            nonce: self.nonce as u128,
+            max_priority_fee_per_gas: fee(&self.max_priority_fee_per_gas, "maxPriorityFeePerGas")?,
+            max_fee_per_gas: fee(&self.max_fee_per_gas, "maxFeePerGas")?,
+            gas: self.gas as u128,
+            to: Some(
+                proto::eip55::parse_address(&self.to)
+                    .map_err(|e| anyhow!("Transaction.to: {}", e))?,
+            ),
+            value: parse_quantity(&self.value, "value").map_err(|e| anyhow!(e))?,
+            data: parse_data(&self.data).map_err(|e| anyhow!(e))?,
+            access_list,
+        };
+        transaction.check().map_err(|e| anyhow!(e))?;
+        Ok(transaction)
+    }
+
+    /// What the risk check and calldata summary see: the legacy transaction,
+    /// or an EIP-1559 one with its fee cap as the gas price.
+    pub fn to_call(&self) -> Result<proto::EthTransaction> {
+        if self.is_eip1559() {
+            Ok(self.to_eip1559()?.as_call())
+        } else {
+            self.to_proto()
+        }
+    }
+
+    fn from_proto(tx: &proto::EthTransaction) -> Self {
+        EthereumTransaction {
+            chain_id: tx.chain_id,
+            nonce: tx.nonce as u64,
+            to: tx
+                .to
+                .map(|t| format!("0x{}", hex::encode(t)))
+                .unwrap_or_default(),
+            value: format!("0x{:x}", tx.value),
+            gas_price: format!("0x{:x}", tx.gas_price),
+            gas: tx.gas as u64,
+            data: format!("0x{}", hex::encode(&tx.data)),
+            max_fee_per_gas: None,
+            max_priority_fee_per_gas: None,
+            access_list: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct KeyStatusResponse {
+    #[serde(rename = "KeyId")]
+    pub key_id: String,
+    #[serde(rename = "Status")]
+    pub status: String, // "creating" | "deriving" | "ready" | "error"
+    #[serde(rename = "Address", skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    #[serde(rename = "PublicKey", skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<String>,
+    #[serde(rename = "DerivationPath", skip_serializing_if = "Option::is_none")]
+    pub derivation_path: Option<String>,
+    #[serde(rename = "Error", skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Issue #42: RFC3339 of last successful op (from tx_log), None if never used.
+    #[serde(rename = "LastUsedAt", skip_serializing_if = "Option::is_none")]
+    pub last_used_at: Option<String>,
+    /// Issue #42: "active" or "frozen".
+    #[serde(rename = "LifecycleStatus")]
+    pub lifecycle_status: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QueueStatusResponse {
+    pub queue_depth: usize,
+    /// Batch-lane share of `queue_depth` (background / maintenance commands).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub batch_depth: Option<usize>,
+    pub estimated_wait_seconds: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub circuit_breaker_open: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub consecutive_failures: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChangePasskeyRequest {
+    #[serde(rename = "KeyId")]
+    pub key_id: String,
+    /// New P-256 public key in uncompressed hex (0x04...)
+    #[serde(rename = "PasskeyPublicKey")]
+    pub passkey_public_key: String,
+    /// Legacy: current passkey assertion (hex)
+    #[serde(rename = "Passkey", skip_serializing_if = "Option::is_none", default)]
+    pub passkey: Option<PasskeyAssertion>,
+    /// WebAuthn ceremony assertion (from BeginAuthentication)
+    #[serde(rename = "WebAuthn", skip_serializing_if = "Option::is_none", default)]
+    pub webauthn: Option<WebAuthnAssertion>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChangePasskeyResponse {
+    #[serde(rename = "KeyId")]
+    pub key_id: String,
+    #[serde(rename = "Changed")]
+    pub changed: bool,
+}
+
+/// WebAuthn assertion data attached to Sign/SignHash requests
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PasskeyAssertion {
+    /// authenticatorData in hex
+    #[serde(rename = "AuthenticatorData")]
+    pub authenticator_data: String,
+    /// SHA-256(clientDataJSON) in hex
+    #[serde(rename = "ClientDataHash")]
+    pub client_data_hash: String,
+    /// ECDSA signature in hex (DER or r||s 64 bytes)
+    #[serde(rename = "Signature")]
+    pub signature: String,
+}
+
+/// WebAuthn ceremony-based assertion (from BeginAuthentication flow)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WebAuthnAssertion {
+    #[serde(rename = "ChallengeId")]
+    pub challenge_id: String,
+    #[serde(rename = "Credential")]
+    pub credential: webauthn::AuthenticationResponseJSON,
+}
+
+// ========================================
+// Agent Key Request/Response Structs
+// ========================================
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateAgentKeyRequest {
+    #[serde(rename = "humanKeyId")]
+    pub human_key_id: String,
+    #[serde(rename = "label", default)]
+    pub label: String,
+    #[serde(rename = "passkeyAssertion", skip_serializing_if = "Option::is_none")]
+    pub passkey_assertion: Option<PasskeyAssertion>,
+    #[serde(rename = "webAuthnAssertion", skip_serializing_if = "Option::is_none")]
+    pub webauthn_assertion: Option<WebAuthnAssertion>,
+    /// Set to also get a refresh token bound to this device.
+    #[serde(rename = "deviceFingerprint", default)]
+    pub device_fingerprint: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateAgentKeyResponse {
+    #[serde(rename = "keyId")]
+    pub key_id: String,
+    #[serde(rename = "agentAddress")]
+    pub agent_address: String,
+    #[serde(rename = "derivationPath")]
+    pub derivation_path: String,
+    #[serde(rename = "agentCredential")]
+    pub agent_credential: String,
+    #[serde(rename = "expiresAt")]
+    pub expires_at: i64,
+    /// Single use: each /kms/auth/refresh returns the next one.
+    #[serde(
+        rename = "refreshToken",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub refresh_token: Option<String>,
+}
+
+/// POST /kms/auth/refresh
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuthRefreshRequest {
+    #[serde(rename = "refreshToken")]
+    pub refresh_token: String,
+    #[serde(rename = "deviceFingerprint")]
+    pub device_fingerprint: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignAgentRequest {
+    #[serde(rename = "keyId")]
+    pub key_id: String,
+    #[serde(rename = "payload")]
+    pub payload: String,
+    #[serde(rename = "algorithm", default = "default_secp256k1")]
+    pub algorithm: String,
+    /// Smart Account contract address bound to this session key (v0.17.2+).
+    /// Embedded in the 106-byte signature: [0x08][account(20)][key(20)][ECDSA(65)].
+    /// Must be the ERC-4337 account that will call SessionKeyValidator.validateUserOp.
+    #[serde(rename = "accountAddress")]
+    pub account_address: String,
+}
+
+fn default_secp256k1() -> String {
+    "secp256k1".to_string()
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignAgentResponse {
+    #[serde(rename = "keyId")]
+    pub key_id: String,
+    #[serde(rename = "agentAddress")]
+    pub agent_address: String,
+    #[serde(rename = "signature")]
+    pub signature: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RefreshAgentCredentialRequest {
+    #[serde(rename = "keyId")]
+    pub key_id: String,
+    #[serde(rename = "passkeyAssertion", skip_serializing_if = "Option::is_none")]
+    pub passkey_assertion: Option<PasskeyAssertion>,
+    #[serde(rename = "webAuthnAssertion", skip_serializing_if = "Option::is_none")]
+    pub webauthn_assertion: Option<WebAuthnAssertion>,
+    /// Set to (re)open a refresh-token family for this device.
+    #[serde(rename = "deviceFingerprint", default)]
+    pub device_fingerprint: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RevokeAgentCredentialRequest {
+    #[serde(rename = "keyId")]
+    pub key_id: String,
+    #[serde(rename = "passkeyAssertion", skip_serializing_if = "Option::is_none")]
+    pub passkey_assertion: Option<PasskeyAssertion>,
+    #[serde(rename = "webAuthnAssertion", skip_serializing_if = "Option::is_none")]
+    pub webauthn_assertion: Option<WebAuthnAssertion>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RevokeAgentCredentialResponse {
+    pub success: bool,
+    #[serde(rename = "revokedAt")]
+    pub revoked_at: i64,
+}
+
+// ── EIP-712 SignTypedData ──
+
+/// JSON representation of an EIP-712 domain separator.
+/// All fields are optional per EIP-712 spec; only present fields contribute to the domain hash.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JsonEip712Domain {
+    pub name: Option<String>,
+    pub version: Option<String>,
+    #[serde(rename = "chainId")]
+    pub chain_id: Option<u64>,
+    #[serde(rename = "verifyingContract")]
+    pub verifying_contract: Option<String>, // "0x..." 20-byte hex
+}
+
+/// JSON representation of a single field type definition.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JsonEip712TypeField {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub field_type: String,
+}
+
+/// JSON representation of a type definition (name + ordered field list).
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JsonEip712TypeDef {
+    pub name: String,
+    pub fields: Vec<JsonEip712TypeField>,
+}
+
+/// JSON representation of a typed field value.
+/// The `value` carries a JSON value; its interpretation is driven by the declared field type.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JsonEip712FieldValue {
+    pub name: String,
+    pub value: serde_json::Value,
+}
+
+/// Request body for `POST /kms/SignTypedData`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignTypedDataRequest {
+    /// KMS key ID (wallet UUID)
+    #[serde(rename = "keyId")]
+    pub key_id: String,
+    /// BIP-44 derivation path
+    #[serde(rename = "hdPath", default = "default_hd_path")]
+    pub hd_path: String,
+    /// EIP-712 domain separator
+    pub domain: JsonEip712Domain,
+    /// Name of the primary struct type being signed
+    #[serde(rename = "primaryType")]
+    pub primary_type: String,
+    /// All referenced type definitions
+    pub types: Vec<JsonEip712TypeDef>,
+    /// Field values for the primary type
+    pub message: Vec<JsonEip712FieldValue>,
+    /// WebAuthn ceremony assertion (challenge-based, replay-protected). Required if no Bearer JWT.
+    #[serde(rename = "webAuthnAssertion", default)]
+    pub webauthn_assertion: Option<WebAuthnAssertion>,
+    /// Deprecated: legacy raw passkey assertion. NOT accepted by sign-typed-data (no replay protection).
+    /// Field kept for JSON parse compatibility; the server rejects requests that rely on it.
+    #[serde(rename = "passkeyAssertion", default)]
+    pub passkey_assertion: Option<PasskeyAssertion>,
+}
+
+fn default_hd_path() -> String {
+    "m/44'/60'/0'/0/0".to_string()
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignTypedDataResponse {
+    #[serde(rename = "keyId")]
+    pub key_id: String,
+    /// Hex-encoded 65-byte ECDSA signature: R(32) || S(32) || V(1), V=27/28
+    pub signature: String,
+}
+
+// ── P2 SuperPaymaster convenience signers (v0.18.1) ──
+// Each builds a fixed EIP-712 domain+type in the host and signs via the existing
+// SignTypedData TA command — no new TA command/Command-ID is introduced.
+
+/// `POST /kms/SignMicropaymentVoucher`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignMicropaymentVoucherRequest {
+    #[serde(rename = "keyId")]
+    pub key_id: String,
+    #[serde(rename = "hdPath", default = "default_hd_path")]
+    pub hd_path: String,
+    #[serde(rename = "chainId")]
+    pub chain_id: u64,
+    /// MicroPaymentChannel contract address (0x…, 20 bytes)
+    #[serde(rename = "verifyingContract")]
+    pub verifying_contract: String,
+    /// Payment channel ID (0x…, 32 bytes)
+    #[serde(rename = "channelId")]
+    pub channel_id: String,
+    /// Cumulative amount (decimal or 0x… hex, up to uint256)
+    #[serde(rename = "cumulativeAmount")]
+    pub cumulative_amount: String,
+    /// WebAuthn ceremony assertion (challenge-bound, replay-protected). Required if no Bearer JWT.
+    #[serde(rename = "webAuthnAssertion", default)]
+    pub webauthn_assertion: Option<WebAuthnAssertion>,
+}
+
+/// `POST /kms/SignGTokenAuthorization`
+/// Builds GToken EIP-3009 TransferWithAuthorization domain + type and signs.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignGTokenAuthorizationRequest {
+    #[serde(rename = "keyId")]
+    pub key_id: String,
+    #[serde(rename = "hdPath", default = "default_hd_path")]
+    pub hd_path: String,
+    #[serde(rename = "chainId")]
+    pub chain_id: u64,
+    /// GToken ERC-20 contract address (0x…, 20 bytes)
+    #[serde(rename = "gTokenAddress")]
+    pub gtoken_address: String,
+    /// Transfer sender — MUST equal the Ethereum address derived from keyId+hdPath.
+    /// On-chain EIP-3009 verifies ecrecover(hash,sig) == from; a mismatch causes revert.
+    pub from: String,
+    pub to: String,
+    /// Token amount (decimal or 0x… hex)
+    pub value: String,
+    #[serde(rename = "validAfter")]
+    pub valid_after: String,
+    #[serde(rename = "validBefore")]
+    pub valid_before: String,
+    /// 32-byte random nonce (0x…)
+    pub nonce: String,
+    /// WebAuthn ceremony assertion (challenge-bound, replay-protected). Required if no Bearer JWT.
+    #[serde(rename = "webAuthnAssertion", default)]
+    pub webauthn_assertion: Option<WebAuthnAssertion>,
+}
+
+/// `POST /kms/SignX402Payment`
+/// Builds SuperPaymaster x402 PaymentPayload EIP-712 struct and signs.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignX402PaymentRequest {
+    #[serde(rename = "keyId")]
+    pub key_id: String,
+    #[serde(rename = "hdPath", default = "default_hd_path")]
+    pub hd_path: String,
+    #[serde(rename = "chainId")]
+    pub chain_id: u64,
+    /// SuperPaymaster contract address (0x…, 20 bytes)
+    #[serde(rename = "verifyingContract")]
+    pub verifying_contract: String,
+    /// Unique payment ID (0x…, 32 bytes)
+    #[serde(rename = "paymentId")]
+    pub payment_id: String,
+    /// Amount to settle (decimal or 0x… hex)
+    pub amount: String,
+    /// Recipient address (0x…)
+    pub recipient: String,
+    /// Deadline Unix timestamp (decimal or 0x… hex)
+    pub deadline: String,
+    /// WebAuthn ceremony assertion (challenge-bound, replay-protected). Required if no Bearer JWT.
+    #[serde(rename = "webAuthnAssertion", default)]
+    pub webauthn_assertion: Option<WebAuthnAssertion>,
+}
+
+// P2 responses share the same shape as SignTypedDataResponse
+pub type SignMicropaymentVoucherResponse = SignTypedDataResponse;
+pub type SignGTokenAuthorizationResponse = SignTypedDataResponse;
+pub type SignX402PaymentResponse = SignTypedDataResponse;
+
+// ── Grant Session Signing ──
+
+/// Parse bytes4 hex string ("0x..." or bare 8 hex chars) into [u8; 4].
+fn parse_bytes4_hex(s: &str) -> Result<[u8; 4]> {
+    let bytes = hex::decode(s.trim_start_matches("0x"))
+        .map_err(|e| anyhow!("Invalid bytes4 hex '{}': {}", s, e))?;
+    if bytes.len() != 4 {
+        return Err(anyhow!(
+            "bytes4 must be exactly 4 bytes, got {}",
+            bytes.len()
+        ));
+    }
+    let mut arr = [0u8; 4];
+    arr.copy_from_slice(&bytes);
+    Ok(arr)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignGrantSessionRequest {
+    #[serde(rename = "keyId")]
+    pub key_id: String,
+    #[serde(rename = "hdPath", default = "default_hd_path")]
+    pub hd_path: String,
+    #[serde(rename = "chainId")]
+    pub chain_id: u64,
+    /// SessionKeyValidator contract address ("0x..." 20 bytes)
+    #[serde(rename = "verifyingContract")]
+    pub verifying_contract: String,
+    /// Smart Account address ("0x..." 20 bytes)
+    pub account: String,
+    /// secp256k1 session key address ("0x..." 20 bytes)
+    #[serde(rename = "sessionKey")]
+    pub session_key: String,
+    /// Session expiry as Unix timestamp (uint48)
+    pub expiry: u64,
+    /// Allowed contract address or zero for any
+    #[serde(rename = "contractScope")]
+    pub contract_scope: String,
+    /// Allowed 4-byte selector or zero for any
+    #[serde(rename = "selectorScope")]
+    pub selector_scope: String,
+    #[serde(rename = "velocityLimit")]
+    pub velocity_limit: u16,
+    #[serde(rename = "velocityWindow")]
+    pub velocity_window: u32,
+    /// Allowed call target addresses (empty = any)
+    #[serde(rename = "callTargets", default)]
+    pub call_targets: Vec<String>,
+    /// Allowed 4-byte function selectors (empty = any)
+    #[serde(rename = "selectorAllowlist", default)]
+    pub selector_allowlist: Vec<String>,
+    /// grantNonces[account][sessionKey] read from chain (decimal u64)
+    pub nonce: u64,
+    #[serde(rename = "passkeyAssertion", default)]
+    pub passkey_assertion: Option<PasskeyAssertion>,
+    #[serde(rename = "webAuthnAssertion", default)]
+    pub webauthn_assertion: Option<WebAuthnAssertion>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignGrantSessionResponse {
+    #[serde(rename = "keyId")]
+    pub key_id: String,
+    /// Hex-encoded 65-byte ECDSA signature: R(32) || S(32) || V(1), V=27/28
+    pub signature: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignP256GrantSessionRequest {
+    #[serde(rename = "keyId")]
+    pub key_id: String,
+    #[serde(rename = "hdPath", default = "default_hd_path")]
+    pub hd_path: String,
+    #[serde(rename = "chainId")]
+    pub chain_id: u64,
+    #[serde(rename = "verifyingContract")]
+    pub verifying_contract: String,
+    pub account: String,
+    /// P256 session key X coordinate (hex, 32 bytes)
+    #[serde(rename = "keyX")]
+    pub key_x: String,
+    /// P256 session key Y coordinate (hex, 32 bytes)
+    #[serde(rename = "keyY")]
+    pub key_y: String,
+    pub expiry: u64,
+    #[serde(rename = "contractScope")]
+    pub contract_scope: String,
+    #[serde(rename = "selectorScope")]
+    pub selector_scope: String,
+    #[serde(rename = "velocityLimit")]
+    pub velocity_limit: u16,
+    #[serde(rename = "velocityWindow")]
+    pub velocity_window: u32,
+    #[serde(rename = "callTargets", default)]
+    pub call_targets: Vec<String>,
+    #[serde(rename = "selectorAllowlist", default)]
+    pub selector_allowlist: Vec<String>,
+    /// grantNonces_p256[account][keyHash] read from chain (decimal u64)
+    pub nonce: u64,
+    #[serde(rename = "passkeyAssertion", default)]
+    pub passkey_assertion: Option<PasskeyAssertion>,
+    #[serde(rename = "webAuthnAssertion", default)]
+    pub webauthn_assertion: Option<WebAuthnAssertion>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignP256GrantSessionResponse {
+    #[serde(rename = "keyId")]
+    pub key_id: String,
+    pub signature: String,
+}
+
+// ── P256 Session Key (v0.18.1) ──
+
+/// POST /kms/create-p256-session-key
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateP256SessionKeyRequest {
+    #[serde(rename = "humanKeyId")]
+    pub human_key_id: String,
+    #[serde(rename = "label", default)]
+    pub label: String,
+    #[serde(rename = "webAuthnAssertion", skip_serializing_if = "Option::is_none")]
+    pub webauthn_assertion: Option<WebAuthnAssertion>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateP256SessionKeyResponse {
+    #[serde(rename = "keyId")]
+    pub key_id: String,
+    #[serde(rename = "pubKeyX")]
+    pub pub_key_x: String,
+    #[serde(rename = "pubKeyY")]
+    pub pub_key_y: String,
+    pub algorithm: String,
+    #[serde(rename = "agentCredential")]
+    pub agent_credential: String,
+    #[serde(rename = "expiresAt")]
+    pub expires_at: i64,
+}
+
+/// POST /kms/revoke-p256-session-key
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RevokeP256SessionKeyRequest {
+    #[serde(rename = "keyId")]
+    pub key_id: String,
+    #[serde(rename = "webAuthnAssertion", skip_serializing_if = "Option::is_none")]
+    pub webauthn_assertion: Option<WebAuthnAssertion>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RevokeP256SessionKeyResponse {
+    pub success: bool,
+    #[serde(rename = "revokedAt")]
+    pub revoked_at: i64,
+}
+
+// ── Scoped AA session keys ──
+
+/// POST /kms/create-session-key
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateSessionKeyRequest {
+    #[serde(rename = "keyId")]
+    pub key_id: String,
+    /// Root-key path that signs the permission object.
+    #[serde(rename = "hdPath", default = "default_hd_path")]
+    pub hd_path: String,
+    #[serde(rename = "chainId")]
+    pub chain_id: u64,
+    /// Smart Account address the session acts for ("0x..." 20 bytes)
+    pub account: String,
+    /// Allowed call targets (non-empty)
+    pub targets: Vec<String>,
+    /// Allowed 4-byte selectors (empty = any selector on an allowed target)
+    #[serde(default)]
+    pub selectors: Vec<String>,
+    /// Max native value per call, decimal wei string
+    #[serde(rename = "valueCap", default = "default_zero_wei")]
+    pub value_cap: String,
+    /// Unix seconds; the TA caps the lifetime at 7 days
+    pub expiry: u64,
+    #[serde(rename = "webAuthnAssertion", skip_serializing_if = "Option::is_none")]
+    pub webauthn_assertion: Option<WebAuthnAssertion>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateSessionKeyResponse {
+    /// "<wallet_id>:<session_index>"
+    #[serde(rename = "sessionKeyId")]
+    pub session_key_id: String,
+    #[serde(rename = "sessionAddress")]
+    pub session_address: String,
+    #[serde(rename = "permissionHash")]
+    pub permission_hash: String,
+    /// Root key over EIP-191(permissionHash): R(32) || S(32) || V(1)
+    #[serde(rename = "ownerSignature")]
+    pub owner_signature: String,
+    #[serde(rename = "expiresAt")]
+    pub expires_at: u64,
+}
+
+/// POST /kms/sign-session-key
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignSessionKeyRequest {
+    #[serde(rename = "sessionKeyId")]
+    pub session_key_id: String,
+    #[serde(rename = "chainId")]
+    pub chain_id: u64,
+    /// Declared call — checked by the TA against the signed scope
+    pub target: String,
+    pub selector: String,
+    #[serde(default = "default_zero_wei")]
+    pub value: String,
+    #[serde(rename = "userOpHash")]
+    pub user_op_hash: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignSessionKeyResponse {
+    #[serde(rename = "sessionKeyId")]
+    pub session_key_id: String,
+    /// Session key over EIP-191(userOpHash): R(32) || S(32) || V(1)
+    pub signature: String,
+}
+
+/// POST /kms/revoke-session-key
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RevokeSessionKeyRequest {
+    #[serde(rename = "sessionKeyId")]
+    pub session_key_id: String,
+    #[serde(rename = "webAuthnAssertion", skip_serializing_if = "Option::is_none")]
+    pub webauthn_assertion: Option<WebAuthnAssertion>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RevokeSessionKeyResponse {
+    pub success: bool,
+    /// false if the key was already revoked
+    pub revoked: bool,
+}
+
+/// POST /kms/allowance-policy
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetAllowancePolicyRequest {
+    #[serde(rename = "keyId")]
+    pub key_id: String,
+    /// "off" | "reject-unlimited" | "cap"
+    pub rule: String,
+    /// cap: largest allowed approval in raw token units (decimal)
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub max: Option<String>,
+    #[serde(rename = "webAuthnAssertion", skip_serializing_if = "Option::is_none")]
+    pub webauthn_assertion: Option<WebAuthnAssertion>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetAllowancePolicyResponse {
+    pub success: bool,
+    pub previous: String,
+}
+
+/// POST /kms/confirm-allowance-override
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConfirmAllowanceOverrideRequest {
+    #[serde(rename = "keyId")]
+    pub key_id: String,
+    pub transaction: EthereumTransaction,
+    #[serde(rename = "summaryAbis", default)]
+    pub summary_abis: Vec<String>,
+    #[serde(rename = "webAuthnAssertion", skip_serializing_if = "Option::is_none")]
+    pub webauthn_assertion: Option<WebAuthnAssertion>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConfirmAllowanceOverrideResponse {
+    /// Sign this exact transaction next with SummaryCommitted = true.
+    pub summary: String,
+}
+
+/// POST /kms/spender-allow-list
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetSpenderAllowListRequest {
+    #[serde(rename = "keyId")]
+    pub key_id: String,
+    /// Addresses that may be granted an allowance; empty = any spender.
+    pub spenders: Vec<String>,
+    #[serde(rename = "webAuthnAssertion", skip_serializing_if = "Option::is_none")]
+    pub webauthn_assertion: Option<WebAuthnAssertion>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetSpenderAllowListResponse {
+    pub success: bool,
+    pub previous: Vec<String>,
+}
+
+/// POST /kms/path-policy
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PathPolicyRequest {
+    #[serde(rename = "keyId")]
+    pub key_id: String,
+    /// Replacement list, e.g. `m/44'/60'/0'/0/*`; empty = any path. Omit to read.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub patterns: Option<Vec<String>>,
+    /// Needed unless the new list only narrows the current one.
+    #[serde(rename = "webAuthnAssertion", skip_serializing_if = "Option::is_none")]
+    pub webauthn_assertion: Option<WebAuthnAssertion>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PathPolicyResponse {
+    /// In force after the call; empty = any path.
+    pub patterns: Vec<String>,
+    pub previous: Vec<String>,
+}
+
+/// POST /kms/spending-policy
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SpendingPolicyRequest {
+    #[serde(rename = "keyId")]
+    pub key_id: String,
+    /// Replacement policy. Omit to read.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub policy: Option<SpendingPolicyJson>,
+    #[serde(rename = "webAuthnAssertion", skip_serializing_if = "Option::is_none")]
+    pub webauthn_assertion: Option<WebAuthnAssertion>,
+}
+
+/// `proto::spending_policy::SpendingPolicy`; a field left out is no rule.
+#[derive(Debug, Serialize, Deserialize, Default, PartialEq)]
+pub struct SpendingPolicyJson {
+    /// Native value per UTC day, decimal wei.
+    #[serde(
+        rename = "dailyLimitWei",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub daily_limit_wei: Option<String>,
+    #[serde(rename = "allowedDestinations", default)]
+    pub allowed_destinations: Vec<String>,
+    #[serde(rename = "deniedDestinations", default)]
+    pub denied_destinations: Vec<String>,
+    /// Decimal wei; `maxFeePerGas` for EIP-1559 transactions.
+    #[serde(
+        rename = "maxGasPriceWei",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub max_gas_price_wei: Option<String>,
+    #[serde(rename = "allowedChainIds", default)]
+    pub allowed_chain_ids: Vec<u64>,
+}
+
+impl SpendingPolicyJson {
+    fn to_proto(&self) -> Result<proto::spending_policy::SpendingPolicy> {
+        let addresses = |field: &str, list: &[String]| {
+            list.iter()
+                .map(|a| {
+                    proto::eip55::parse_address(a).map_err(|e| anyhow!("{} {}: {}", field, a, e))
+                })
+                .collect::<Result<Vec<_>>>()
+        };
+        Ok(proto::spending_policy::SpendingPolicy {
+            daily_limit_wei: self
+                .daily_limit_wei
+                .as_deref()
+                .map(|v| parse_wei("dailyLimitWei", v))
+                .transpose()?,
+            allowed_destinations: addresses("allowedDestinations", &self.allowed_destinations)?,
+            denied_destinations: addresses("deniedDestinations", &self.denied_destinations)?,
+            max_gas_price_wei: self
+                .max_gas_price_wei
+                .as_deref()
+                .map(|v| parse_wei("maxGasPriceWei", v))
+                .transpose()?,
+            allowed_chain_ids: self.allowed_chain_ids.clone(),
+        })
+    }
+
+    fn from_proto(policy: &proto::spending_policy::SpendingPolicy) -> Self {
+        let addresses =
+            |list: &[[u8; 20]]| list.iter().map(proto::eip55::to_checksum_address).collect();
+        SpendingPolicyJson {
+            daily_limit_wei: policy.daily_limit_wei.map(|v| v.to_string()),
+            allowed_destinations: addresses(&policy.allowed_destinations),
+            denied_destinations: addresses(&policy.denied_destinations),
+            max_gas_price_wei: policy.max_gas_price_wei.map(|v| v.to_string()),
+            allowed_chain_ids: policy.allowed_chain_ids.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SpendingPolicyResponse {
+    /// In force after the call.
+    pub policy: SpendingPolicyJson,
+    /// Replaced by this call; absent on a read.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub previous: Option<SpendingPolicyJson>,
+    /// Native value counted against today's limit, decimal wei.
+    #[serde(rename = "spentTodayWei")]
+    pub spent_today_wei: String,
+}
+
+/// POST /kms/nonce-tracking
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NonceTrackingRequest {
+    #[serde(rename = "keyId")]
+    pub key_id: String,
+    /// Turn tracking on or off. Omit to read.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub enabled: Option<bool>,
+    #[serde(rename = "webAuthnAssertion", skip_serializing_if = "Option::is_none")]
+    pub webauthn_assertion: Option<WebAuthnAssertion>,
+}
+
+/// `proto::nonce_guard::NonceEntry`.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct SignedNonceJson {
+    #[serde(rename = "chainId")]
+    pub chain_id: u64,
+    pub address: String,
+    #[serde(rename = "highestNonce")]
+    pub highest_nonce: u128,
+}
+
+/// `proto::nonce_guard::NonceOverride`.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct NonceOverrideJson {
+    #[serde(rename = "chainId")]
+    pub chain_id: u64,
+    pub address: String,
+    pub nonce: u128,
+    /// TA time, unix seconds.
+    #[serde(rename = "expiresAt")]
+    pub expires_at: u64,
+}
+
+impl From<&proto::nonce_guard::NonceOverride> for NonceOverrideJson {
+    fn from(armed: &proto::nonce_guard::NonceOverride) -> Self {
+        NonceOverrideJson {
+            chain_id: armed.chain_id,
+            address: proto::eip55::to_checksum_address(&armed.address),
+            nonce: armed.nonce,
+            expires_at: armed.expires_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NonceTrackingResponse {
+    /// In force after the call.
+    pub enabled: bool,
+    /// Replaced by this call; absent on a read.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub previous: Option<bool>,
+    /// Highest nonce signed per (chain, signer) since tracking was turned on.
+    pub nonces: Vec<SignedNonceJson>,
+    #[serde(
+        rename = "pendingOverride",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub pending_override: Option<NonceOverrideJson>,
+}
+
+impl From<proto::NonceTrackingOutput> for NonceTrackingResponse {
+    fn from(output: proto::NonceTrackingOutput) -> Self {
+        NonceTrackingResponse {
+            enabled: output.enabled,
+            previous: output.previous,
+            nonces: output
+                .entries
+                .iter()
+                .map(|e| SignedNonceJson {
+                    chain_id: e.chain_id,
+                    address: proto::eip55::to_checksum_address(&e.address),
+                    highest_nonce: e.highest,
+                })
+                .collect(),
+            pending_override: output
+                .pending_override
+                .as_ref()
+                .map(NonceOverrideJson::from),
+        }
+    }
+}
+
+/// POST /kms/nonce-override
+///
+/// Lets the TA sign `nonce` once more for (chainId, address) — a fee bump or
+/// cancel — while tracking is on. The challenge binds
+/// `nonce_guard::override_commitment`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OverrideNonceRequest {
+    #[serde(rename = "keyId")]
+    pub key_id: String,
+    #[serde(rename = "chainId")]
+    pub chain_id: u64,
+    /// The signing address (0x-hex), as for the transaction's `from`.
+    pub address: String,
+    pub nonce: u128,
+    #[serde(rename = "webAuthnAssertion", skip_serializing_if = "Option::is_none")]
+    pub webauthn_assertion: Option<WebAuthnAssertion>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OverrideNonceResponse {
+    pub armed: NonceOverrideJson,
+    /// Highest nonce signed for the pair; absent if none yet.
+    #[serde(
+        rename = "highestNonce",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub highest_nonce: Option<u128>,
+}
+
+/// POST /kms/approver/pair
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PairApproverRequest {
+    #[serde(rename = "keyId")]
+    pub key_id: String,
+    /// The phone's compressed secp256k1 key, 0x-hex. Omit to unpair.
+    #[serde(
+        rename = "approverKey",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub approver_key: Option<String>,
+    /// fcm | apns; required to pair.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub platform: Option<String>,
+    #[serde(rename = "pushToken", skip_serializing_if = "Option::is_none", default)]
+    pub push_token: Option<String>,
+    #[serde(rename = "webAuthnAssertion", skip_serializing_if = "Option::is_none")]
+    pub webauthn_assertion: Option<WebAuthnAssertion>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PairApproverResponse {
+    #[serde(rename = "approverKey")]
+    pub approver_key: Option<String>,
+    pub previous: Option<String>,
+}
+
+/// POST /kms/approver/request
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RemoteApprovalRequest {
+    #[serde(rename = "keyId")]
+    pub key_id: String,
+    #[serde(
+        rename = "derivationPath",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub derivation_path: Option<String>,
+    /// The transaction the Sign that follows will carry, unchanged.
+    pub transaction: EthereumTransaction,
+    #[serde(rename = "summaryAbis", default)]
+    pub summary_abis: Vec<String>,
+    /// Must match the Sign's SummaryCommitted.
+    #[serde(rename = "summaryCommitted", default)]
+    pub summary_committed: bool,
+}
+
+/// POST /kms/approver/respond, from the phone app.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RemoteApproveRequest {
+    #[serde(rename = "keyId")]
+    pub key_id: String,
+    /// 0x-hex, as pushed.
+    pub payload: String,
+    /// r ‖ s over `remote_approval::approval_digest`, 0x-hex.
+    pub signature: String,
+}
+
+/// POST /kms/multisig/policy
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetMultiSigPolicyRequest {
+    #[serde(rename = "keyId")]
+    pub key_id: String,
+    /// Omit to remove the policy.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub policy: Option<MultiSigPolicyJson>,
+    #[serde(rename = "webAuthnAssertion", skip_serializing_if = "Option::is_none")]
+    pub webauthn_assertion: Option<WebAuthnAssertion>,
+}
+
+/// `proto::multisig::MultiSigPolicy`.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct MultiSigPolicyJson {
+    /// Transactions sending more native value than this (decimal wei) wait
+    /// for the co-signers.
+    #[serde(rename = "thresholdWei")]
+    pub threshold_wei: String,
+    pub required: u8,
+    /// Compressed secp256k1 keys, 0x-hex.
+    pub cosigners: Vec<String>,
+}
+
+impl MultiSigPolicyJson {
+    fn to_proto(&self) -> Result<proto::multisig::MultiSigPolicy> {
+        let policy = proto::multisig::MultiSigPolicy {
+            threshold_wei: parse_wei("thresholdWei", &self.threshold_wei)?,
+            required: self.required,
+            cosigners: self
+                .cosigners
+                .iter()
+                .map(|k| {
+                    hex::decode(k.trim_start_matches("0x"))
+                        .map_err(|_| anyhow!("co-signer key is not hex: {}", k))
+                })
+                .collect::<Result<Vec<_>>>()?,
+        };
+        policy.validate().map_err(|e| anyhow!(e))?;
+        Ok(policy)
+    }
+
+    fn from_proto(policy: &proto::multisig::MultiSigPolicy) -> Self {
+        MultiSigPolicyJson {
+            threshold_wei: policy.threshold_wei.to_string(),
+            required: policy.required,
+            cosigners: policy
+                .cosigners
+                .iter()
+                .map(|k| format!("0x{}", hex::encode(k)))
+                .collect(),
+        }
+    }
+}
+
+/// POST /kms/multisig/approve, from a co-signer.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ApproveMultiSigRequest {
+    #[serde(rename = "keyId")]
+    pub key_id: String,
+    /// 0x-hex, from the held Sign's `MultiSigPending` answer.
+    #[serde(rename = "operationId")]
+    pub operation_id: String,
+    #[serde(rename = "cosignerKey")]
+    pub cosigner_key: String,
+    /// r ‖ s over `multisig::approval_digest`, 0x-hex.
+    pub signature: String,
+}
+
+/// POST /kms/recovery/guardians
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetGuardiansRequest {
+    #[serde(rename = "keyId")]
+    pub key_id: String,
+    /// Omit to remove the guardians.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub guardians: Option<GuardianSetJson>,
+    #[serde(rename = "webAuthnAssertion", skip_serializing_if = "Option::is_none")]
+    pub webauthn_assertion: Option<WebAuthnAssertion>,
+}
+
+/// `proto::social_recovery::GuardianSet`.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct GuardianSetJson {
+    pub threshold: u8,
+    /// Compressed secp256k1 keys, 0x-hex.
+    pub guardians: Vec<String>,
+    /// Timelock between the threshold attestation and completion.
+    #[serde(rename = "delaySecs")]
+    pub delay_secs: u64,
+}
+
+impl GuardianSetJson {
+    fn to_proto(&self) -> Result<proto::social_recovery::GuardianSet> {
+        let set = proto::social_recovery::GuardianSet {
+            threshold: self.threshold,
+            guardians: self
+                .guardians
+                .iter()
+                .map(|k| {
+                    hex::decode(k.trim_start_matches("0x"))
+                        .map_err(|_| anyhow!("guardian key is not hex: {}", k))
+                })
+                .collect::<Result<Vec<_>>>()?,
+            delay_secs: self.delay_secs,
+        };
+        set.validate().map_err(|e| anyhow!(e))?;
+        Ok(set)
+    }
+
+    fn from_proto(set: &proto::social_recovery::GuardianSet) -> Self {
+        GuardianSetJson {
+            threshold: set.threshold,
+            guardians: set
+                .guardians
+                .iter()
+                .map(|k| format!("0x{}", hex::encode(k)))
+                .collect(),
+            delay_secs: set.delay_secs,
+        }
+    }
+}
+
+/// `proto::social_recovery::RecoveryStatus` as the recovery endpoints
+/// answer it.
+fn recovery_status_json(
+    key_id: &str,
+    status: &proto::social_recovery::RecoveryStatus,
+) -> serde_json::Value {
+    serde_json::json!({
+        "keyId": key_id,
+        "guardians": status.guardians.as_ref().map(GuardianSetJson::from_proto),
+        "round": status.round,
+        "candidates": status
+            .candidates
+            .iter()
+            .map(|c| serde_json::json!({
+                "passkeyPublicKey": format!("0x{}", hex::encode(&c.passkey)),
+                "attestations": c.attestations,
+            }))
+            .collect::<Vec<_>>(),
+        "armed": status.armed.as_ref().map(|a| serde_json::json!({
+            "passkeyPublicKey": format!("0x{}", hex::encode(&a.passkey)),
+            "readyAt": a.ready_at,
+        })),
+    })
+}
+
+/// POST /kms/recovery/status, /kms/recovery/complete
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecoveryKeyRequest {
+    #[serde(rename = "keyId")]
+    pub key_id: String,
+}
+
+/// POST /kms/recovery/attest, from a guardian.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecoveryAttestRequest {
+    #[serde(rename = "keyId")]
+    pub key_id: String,
+    /// From /kms/recovery/status.
+    pub round: u64,
+    #[serde(rename = "guardianKey")]
+    pub guardian_key: String,
+    /// Uncompressed P-256 (0x04...), as for ChangePasskey.
+    #[serde(rename = "newPasskeyPublicKey")]
+    pub new_passkey_public_key: String,
+    /// r ‖ s over `social_recovery::attestation_digest`, 0x-hex.
+    pub signature: String,
+}
+
+/// POST /kms/recovery/cancel
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CancelRecoveryRequest {
+    #[serde(rename = "keyId")]
+    pub key_id: String,
+    #[serde(rename = "webAuthnAssertion", skip_serializing_if = "Option::is_none")]
+    pub webauthn_assertion: Option<WebAuthnAssertion>,
+}
+
+/// POST /kms/backup/export
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportBackupRequest {
+    #[serde(rename = "keyId")]
+    pub key_id: String,
+    pub password: String,
+    /// Argon2id spec (`argon2id:m=512,t=16,p=1`); default `argon2id`.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub kdf: Option<String>,
+    #[serde(rename = "webAuthnAssertion", skip_serializing_if = "Option::is_none")]
+    pub webauthn_assertion: Option<WebAuthnAssertion>,
+}
+
+/// POST /kms/backup/restore
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RestoreBackupRequest {
+    /// The file /kms/backup/export returned (`keystore::backup_to_json`).
+    pub backup: serde_json::Value,
+    pub password: String,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub description: Option<String>,
+}
+
+/// POST /kms/aggregator/set
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetAggregatorRequest {
+    #[serde(rename = "keyId")]
+    pub key_id: String,
+    /// The IAggregator contract, 0x-hex. Omit to go back to ECDSA.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub aggregator: Option<String>,
+    #[serde(rename = "webAuthnAssertion", skip_serializing_if = "Option::is_none")]
+    pub webauthn_assertion: Option<WebAuthnAssertion>,
+}
+
+/// POST /kms/aggregator/sign
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignAggregatedUserOpRequest {
+    #[serde(rename = "keyId")]
+    pub key_id: String,
+    #[serde(rename = "entryPoint")]
+    pub entry_point: String,
+    #[serde(rename = "userOpHash")]
+    pub user_op_hash: String,
+    /// The userOp in bundler RPC form; `signature` is filled in.
+    #[serde(rename = "userOp")]
+    pub user_op: serde_json::Value,
+    #[serde(rename = "webAuthnAssertion", skip_serializing_if = "Option::is_none")]
+    pub webauthn_assertion: Option<WebAuthnAssertion>,
+}
+
+/// POST /kms/DescribePermit
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DescribePermitRequest {
+    pub permit: permit::PermitJson,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DescribePermitResponse {
+    /// Exactly the review the TA rebuilds at sign time.
+    pub summary: String,
+    /// EIP-712 digest the signature will be over.
+    pub digest: String,
+    /// SignPermit challenge = SHA-256(nonce || reviewDigest).
+    pub review_digest: String,
+}
+
+/// POST /kms/SignPermit
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignPermitRequest {
+    #[serde(rename = "keyId")]
+    pub key_id: String,
+    #[serde(rename = "hdPath", default = "default_hd_path")]
+    pub hd_path: String,
+    pub permit: permit::PermitJson,
+    #[serde(rename = "webAuthnAssertion", default)]
+    pub webauthn_assertion: Option<WebAuthnAssertion>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignPermitResponse {
+    #[serde(rename = "keyId")]
+    pub key_id: String,
+    /// Hex-encoded 65-byte ECDSA signature: R(32) || S(32) || V(1), V=27/28
+    pub signature: String,
+    /// The review the passkey confirmed.
+    pub summary: String,
+}
+
+/// POST /kms/siwe/sign
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SiweSignRequest {
+    #[serde(rename = "keyId")]
+    pub key_id: String,
+    #[serde(rename = "hdPath", default = "default_hd_path")]
+    pub hd_path: String,
+    /// Canonical EIP-4361 text, exactly as it will be signed.
+    pub message: String,
+    /// Origin of the page asking for the sign-in; when given, the message's
+    /// domain must be its authority.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub origin: Option<String>,
+    #[serde(rename = "webAuthnAssertion", default)]
+    pub webauthn_assertion: Option<WebAuthnAssertion>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SiweSignResponse {
+    #[serde(rename = "keyId")]
+    pub key_id: String,
+    /// EIP-55 sign-in address.
+    pub address: String,
+    /// Hex-encoded 65-byte ECDSA signature: R(32) || S(32) || V(1), V=27/28
+    pub signature: String,
+}
+
+/// POST /kms/siwe/verify
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SiweVerifyRequest {
+    pub message: String,
+    /// 0x-hex 65-byte signature.
+    pub signature: String,
+    /// The relying party's own domain; checked when given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub domain: Option<String>,
+    /// The nonce the relying party issued; checked when given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SiweVerifyResponse {
+    pub valid: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chain_id: Option<u64>,
+    /// Why `valid` is false.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// POST /kms/offline/export (online CA)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportOfflineRequest {
+    #[serde(rename = "keyId")]
+    pub key_id: String,
+    #[serde(rename = "derivationPath")]
+    pub derivation_path: String,
+    pub transaction: EthereumTransaction,
+    /// Validity window; default 15 min, max 24 h.
+    #[serde(
+        rename = "ttlSeconds",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub ttl_seconds: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportOfflineResponse {
+    #[serde(rename = "requestId")]
+    pub request_id: String,
+    /// QR payload for the offline device.
+    pub request: String,
+    #[serde(rename = "expiresAt")]
+    pub expires_at: u64,
+    pub summary: String,
+}
+
+/// POST /kms/offline/sign (air-gapped device)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignOfflineRequest {
+    pub request: String,
+    #[serde(rename = "webAuthnAssertion", skip_serializing_if = "Option::is_none")]
+    pub webauthn_assertion: Option<WebAuthnAssertion>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignOfflineResponse {
+    #[serde(rename = "requestId")]
+    pub request_id: String,
+    /// QR payload to carry back to the online CA.
+    pub response: String,
+    pub summary: String,
+}
+
+/// POST /kms/offline/import (online CA)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImportOfflineRequest {
+    pub response: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImportOfflineResponse {
+    #[serde(rename = "requestId")]
+    pub request_id: String,
+    #[serde(rename = "keyId")]
+    pub key_id: String,
+    pub address: String,
+    #[serde(rename = "transactionHash")]
+    pub transaction_hash: String,
+    /// Raw signed tx, ready for eth_sendRawTransaction.
+    #[serde(rename = "signedTransaction")]
+    pub signed_transaction: String,
+}
+
+const DEFAULT_OFFLINE_TTL_SECS: u64 = 15 * 60;
+
+/// POST /kms/transaction/rescue
+///
+/// Without `nonce` this only reports. With `nonce` and no assertion it returns
+/// the replacement to confirm; the WebAuthn challenge binds its signing hash
+/// exactly as for a transaction Sign. With the assertion it signs.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RescueTransactionRequest {
+    #[serde(rename = "keyId")]
+    pub key_id: String,
+    /// Default: the wallet's derivation path.
+    #[serde(
+        rename = "derivationPath",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub derivation_path: Option<String>,
+    #[serde(rename = "chainId")]
+    pub chain_id: u64,
+    /// eth_getTransactionCount(address, "latest"); ledger entries below it
+    /// are marked confirmed.
+    #[serde(
+        rename = "confirmedNonce",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub confirmed_nonce: Option<u64>,
+    #[serde(
+        rename = "stuckAfterSeconds",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub stuck_after_seconds: Option<u64>,
+    /// Nonce to rescue: a stuck transaction or a gap.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub nonce: Option<u64>,
+    /// "speedup" (default) | "cancel". Gaps are always filled with a cancel.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub mode: Option<String>,
+    #[serde(
+        rename = "feeBumpPercent",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub fee_bump_percent: Option<u32>,
+    /// Minimum gas price for the replacement (hex wei), e.g. current network price.
+    #[serde(rename = "gasPrice", skip_serializing_if = "Option::is_none", default)]
+    pub gas_price: Option<String>,
+    #[serde(rename = "webAuthnAssertion", skip_serializing_if = "Option::is_none")]
+    pub webauthn_assertion: Option<WebAuthnAssertion>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StuckTransaction {
+    pub nonce: u64,
+    #[serde(rename = "transactionHash")]
+    pub transaction_hash: String,
+    #[serde(rename = "gasPrice")]
+    pub gas_price: String,
+    #[serde(rename = "ageSeconds")]
+    pub age_seconds: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RescueReplacement {
+    pub nonce: u64,
+    pub mode: String,
+    pub transaction: EthereumTransaction,
+    /// Legacy signing hash — the payload digest the WebAuthn challenge binds.
+    #[serde(rename = "signingHash")]
+    pub signing_hash: String,
+    pub summary: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RescueTransactionResponse {
+    pub address: String,
+    pub stuck: Vec<StuckTransaction>,
+    #[serde(rename = "nonceGaps")]
+    pub nonce_gaps: Vec<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replacement: Option<RescueReplacement>,
+    #[serde(rename = "signedTransaction", skip_serializing_if = "Option::is_none")]
+    pub signed_transaction: Option<String>,
+    #[serde(rename = "transactionHash", skip_serializing_if = "Option::is_none")]
+    pub transaction_hash: Option<String>,
+}
+
+const DEFAULT_STUCK_AFTER_SECS: u64 = 10 * 60;
+
+/// GET /kms/capabilities — the TA's side of the capabilities handshake.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CapabilitiesResponse {
+    #[serde(rename = "taVersion")]
+    pub ta_version: String,
+    pub features: Vec<String>,
+    /// Deployment policy label; absent when no policy is installed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deployment: Option<String>,
+    #[serde(rename = "policySequence", skip_serializing_if = "Option::is_none")]
+    pub policy_sequence: Option<u64>,
+    #[serde(rename = "policySigner", skip_serializing_if = "Option::is_none")]
+    pub policy_signer: Option<String>,
+    #[serde(rename = "enabledCommands")]
+    pub enabled_commands: Vec<String>,
+    #[serde(rename = "disabledCommands")]
+    pub disabled_commands: Vec<String>,
+    /// Installed TA config; absent = built-in default limits.
+    #[serde(rename = "taConfig", skip_serializing_if = "Option::is_none")]
+    pub ta_config: Option<proto::ta_config::TaConfig>,
+    /// Key the image accepts InstallConfig from; absent = none compiled in.
+    #[serde(rename = "provisioningKey", skip_serializing_if = "Option::is_none")]
+    pub provisioning_key: Option<String>,
+}
+
+/// Whether the TA refuses assertions over a challenge it did not issue.
+fn ta_requires_own_challenge(caps: &proto::GetCapabilitiesOutput) -> bool {
+    caps.features.iter().any(|f| f == "strict-challenge")
+        || matches!(&caps.config, Some(config) if config.require_ta_challenge)
+}
+
+fn capabilities_response(caps: proto::GetCapabilitiesOutput) -> CapabilitiesResponse {
+    let name = |id: u32| format!("{:?}", proto::Command::from(id));
+    let disabled: Vec<u32> = caps
+        .policy
+        .as_ref()
+        .map(|p| p.disabled_commands.clone())
+        .unwrap_or_default();
+    CapabilitiesResponse {
+        ta_version: caps.ta_version,
+        features: caps.features,
+        deployment: caps.policy.as_ref().map(|p| p.deployment.clone()),
+        policy_sequence: caps.policy.as_ref().map(|p| p.sequence),
+        policy_signer: caps.policy_signer.map(|a| format!("0x{}", hex::encode(a))),
+        enabled_commands: (0..=proto::buffers::MAX_COMMAND_ID)
+            .filter(|id| proto::Command::from(*id) != proto::Command::Unknown)
+            .filter(|id| !disabled.contains(id))
+            .map(name)
+            .collect(),
+        disabled_commands: disabled.into_iter().map(name).collect(),
+        ta_config: caps.config,
+        provisioning_key: caps
+            .provisioning_key
+            .map(|a| format!("0x{}", hex::encode(a))),
+    }
+}
+
+/// POST /kms/migration/begin — step 1 of a hardware-wallet migration; the
+/// package goes to CreateKey's `Migration`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct MigrationBeginRequest {
+    /// Account xpub (`m/44'/60'/0'`) exported from the hardware wallet.
+    pub xpub: String,
+    /// Receive addresses to derive for comparison, 1-20 (default 5).
+    #[serde(default)]
+    pub address_count: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MigrationExpectedAddress {
+    pub index: u32,
+    pub path: String,
+    pub address: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MigrationBeginResponse {
+    pub session_id: String,
+    /// The TA's ephemeral key (compressed) the phrase is sealed to.
+    pub ephemeral_public_key: String,
+    pub account_path: &'static str,
+    /// Compare each with the hardware wallet before sending the phrase.
+    pub expected_addresses: Vec<MigrationExpectedAddress>,
+    /// `proto::migration::offer_digest` — the attestation nonce.
+    pub offer_digest: String,
+    pub expires_at: u64,
+    /// Absent when this device has no attestation PTA.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attestation: Option<AttestationResponse>,
+}
+
+/// POST /kms/gas-tank/set — designate a relayer address to keep funded.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct GasTankSetRequest {
+    pub address: String,
+    pub chain_id: u64,
+    /// Alert (and propose a refill) below this balance. Decimal wei.
+    pub min_balance_wei: String,
+    #[serde(default)]
+    pub label: Option<String>,
+    /// Refill amount, decimal wei; with `treasuryKeyId`. Absent = alert only.
+    #[serde(default)]
+    pub top_up_wei: Option<String>,
+    #[serde(default)]
+    pub treasury_key_id: Option<String>,
+    /// Default m/44'/60'/0'/0/0.
+    #[serde(default)]
+    pub treasury_derivation_path: Option<String>,
+}
+
+/// POST /kms/gas-tank/remove
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct GasTankRemoveRequest {
+    pub address: String,
+    pub chain_id: u64,
+}
+
+/// POST /kms/gas-tank/topup/approve — first of the two controls.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct GasTankTopUpApproveRequest {
+    pub top_up_id: i64,
+    /// A key id listed in KMS_GAS_TANK_APPROVERS, not the treasury.
+    pub approver_key_id: String,
+    #[serde(rename = "webAuthnAssertion", default)]
+    pub webauthn_assertion: Option<WebAuthnAssertion>,
+}
+
+/// POST /kms/gas-tank/topup/execute — without an assertion, prepare the
+/// refill transaction; with the treasury's, sign and broadcast it.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct GasTankTopUpExecuteRequest {
+    pub top_up_id: i64,
+    #[serde(default)]
+    pub summary_committed: Option<bool>,
+    #[serde(rename = "webAuthnAssertion", default)]
+    pub webauthn_assertion: Option<WebAuthnAssertion>,
+}
+
+/// POST /kms/replication/offer — run on the device that will receive a wallet.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReplicationOfferResponse {
+    #[serde(rename = "deviceId")]
+    pub device_id: String,
+    /// `KMS_REGION` of this node — the ReplicaRegion of a ReplicateKey call.
+    pub region: String,
+    /// `aa-repl-hello:` text, passed to the source device's export call.
+    pub hello: String,
+    #[serde(rename = "attestationKeyFingerprint")]
+    pub attestation_key_fingerprint: String,
+    #[serde(rename = "taMeasurement")]
+    pub ta_measurement: String,
+    #[serde(rename = "expiresAt")]
+    pub expires_at: u64,
+}
+
+/// POST /kms/replication/export — on the device holding the wallet. Without
+/// an assertion it returns the payload the owner approves (the target hello
+/// digest, committed in the WebAuthn challenge); with one it exports.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReplicationExportRequest {
+    #[serde(rename = "keyId")]
+    pub key_id: String,
+    pub hello: String,
+    #[serde(rename = "targetDeviceId")]
+    pub target_device_id: String,
+    #[serde(
+        rename = "targetEndpoint",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub target_endpoint: Option<String>,
+    #[serde(rename = "webAuthnAssertion", skip_serializing_if = "Option::is_none")]
+    pub webauthn_assertion: Option<WebAuthnAssertion>,
+}
+
+/// CA-side wallet record the target needs next to the TA package.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplicatedWallet {
+    pub description: String,
+    #[serde(rename = "keyUsage")]
+    pub key_usage: String,
+    #[serde(rename = "keySpec")]
+    pub key_spec: String,
+    pub origin: String,
+    #[serde(rename = "passkeyPubkey")]
+    pub passkey_pubkey: Option<String>,
+    #[serde(rename = "credentialId")]
+    pub credential_id: Option<String>,
+    /// Pinned address at m/44'/60'/0'/0/0, if the source has derived it.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub address: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReplicationExportResponse {
+    #[serde(rename = "keyId")]
+    pub key_id: String,
+    #[serde(rename = "targetDeviceId")]
+    pub target_device_id: String,
+    #[serde(rename = "targetAttestationKeyFingerprint")]
+    pub target_attestation_key_fingerprint: String,
+    /// 0x-hex digest of the target hello — the payload the challenge binds.
+    #[serde(rename = "approvalPayload")]
+    pub approval_payload: String,
+    #[serde(rename = "sourceDeviceId", skip_serializing_if = "Option::is_none")]
+    pub source_device_id: Option<String>,
+    /// `aa-repl-pkg:` text for the target's import call.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub package: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wallet: Option<ReplicatedWallet>,
+}
+
+/// POST /kms/replication/import — back on the target device.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReplicationImportRequest {
+    pub package: String,
+    pub wallet: ReplicatedWallet,
+    #[serde(rename = "sourceDeviceId")]
+    pub source_device_id: String,
+    #[serde(
+        rename = "sourceEndpoint",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub source_endpoint: Option<String>,
+    /// Set by ReplicateKey: the source's region, recorded as the primary.
+    #[serde(
+        rename = "primaryRegion",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub primary_region: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WalletDeviceView {
+    #[serde(rename = "deviceId")]
+    pub device_id: String,
+    /// Absent for this node.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+    #[serde(rename = "attestationKeyFingerprint")]
+    pub attestation_key_fingerprint: String,
+    pub status: String,
+    #[serde(rename = "lastSeen")]
+    pub last_seen: i64,
+    pub quarantined: bool,
+    pub healthy: bool,
+}
+
+/// GET /kms/replication/devices/{keyId}; also returned by import and revoke.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WalletDevicesResponse {
+    #[serde(rename = "keyId")]
+    pub key_id: String,
+    pub devices: Vec<WalletDeviceView>,
+    /// Device to send signing requests to; absent when none is healthy.
+    #[serde(rename = "routeDeviceId", skip_serializing_if = "Option::is_none")]
+    pub route_device_id: Option<String>,
+    #[serde(rename = "routeEndpoint", skip_serializing_if = "Option::is_none")]
+    pub route_endpoint: Option<String>,
+}
+
+/// POST /kms/replication/revoke — owner-approved removal from the device set.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReplicationRevokeRequest {
+    #[serde(rename = "keyId")]
+    pub key_id: String,
+    #[serde(rename = "deviceId")]
+    pub device_id: String,
+    #[serde(rename = "webAuthnAssertion", skip_serializing_if = "Option::is_none")]
+    pub webauthn_assertion: Option<WebAuthnAssertion>,
+}
+
+/// POST /kms/replication/heartbeat — a peer device reporting in.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeviceHeartbeatRequest {
+    #[serde(rename = "deviceId")]
+    pub device_id: String,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub endpoint: Option<String>,
+    /// The peer's own health check quarantined it; absent from peers that
+    /// predate the check, which keeps the stored flag.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub quarantined: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeviceHeartbeatResponse {
+    #[serde(rename = "deviceId")]
+    pub device_id: String,
+    /// Replica-set entries refreshed.
+    pub wallets: usize,
+}
+
+/// TrentService.ReplicateKey, run in the primary region. The replica is made
+/// by the seed-replication ceremony, so besides the AWS fields the caller
+/// passes the target's offer and the owner's approval: without `WebAuthn`
+/// the response only carries the `ApprovalPayload` to sign.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReplicateKeyRequest {
+    #[serde(rename = "KeyId")]
+    pub key_id: String,
+    #[serde(rename = "ReplicaRegion")]
+    pub replica_region: String,
+    #[serde(
+        rename = "Description",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub description: Option<String>,
+    /// `hello` from /kms/replication/offer on the replica's device.
+    #[serde(rename = "ReplicationHello")]
+    pub replication_hello: String,
+    /// Defaults to ReplicaRegion.
+    #[serde(
+        rename = "ReplicaDeviceId",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub replica_device_id: Option<String>,
+    #[serde(
+        rename = "ReplicaEndpoint",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub replica_endpoint: Option<String>,
+    #[serde(rename = "WebAuthn", skip_serializing_if = "Option::is_none", default)]
+    pub webauthn: Option<WebAuthnAssertion>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReplicateKeyResponse {
+    #[serde(rename = "ReplicaKeyMetadata")]
+    pub replica_key_metadata: KeyMetadata,
+    /// 0x-hex digest the owner's passkey approves (the target hello).
+    #[serde(rename = "ApprovalPayload")]
+    pub approval_payload: String,
+    /// Body for /kms/replication/import on the replica's device; the replica
+    /// is `Creating` until that call succeeds there.
+    #[serde(rename = "ReplicaImport", skip_serializing_if = "Option::is_none")]
+    pub replica_import: Option<ReplicationImportRequest>,
+}
+
+/// TrentService.UpdatePrimaryRegion. Metadata only — every region holds the
+/// full seed — so it is applied per node; call it in each region.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdatePrimaryRegionRequest {
+    #[serde(rename = "KeyId")]
+    pub key_id: String,
+    #[serde(rename = "PrimaryRegion")]
+    pub primary_region: String,
+}
+
+fn wallet_devices_response(
+    key_id: &str,
+    rows: &[WalletDeviceRow],
+    now: i64,
+) -> WalletDevicesResponse {
+    let route = replication::route(rows, now);
+    WalletDevicesResponse {
+        key_id: key_id.to_string(),
+        devices: rows
+            .iter()
+            .map(|d| WalletDeviceView {
+                device_id: d.device_id.clone(),
+                endpoint: d.endpoint.clone(),
+                attestation_key_fingerprint: d.attestation_key_fp.clone(),
+                status: d.status.clone(),
+                last_seen: d.last_seen,
+                quarantined: d.quarantined,
+                healthy: replication::is_healthy(d, now),
+            })
+            .collect(),
+        route_device_id: route.map(|d| d.device_id.clone()),
+        route_endpoint: route.and_then(|d| d.endpoint.clone()),
+    }
+}
+
+/// A device id stays bound to the attestation key it was first seen with.
+fn check_device_pin(db: &KmsDb, device_id: &str, fingerprint: &str) -> Result<()> {
+    match db.device_attestation_fp(device_id)? {
+        Some(pinned) if pinned != fingerprint => Err(anyhow!(
+            "device {} presented attestation key {}, pinned {}",
+            device_id,
+            fingerprint,
+            pinned
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// A TA wipe certificate. `attestation` is evidence with `nonce` = `digest`
+/// = keccak256(`certificate`), checked like a deletion certificate's.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WipeCertificateView {
+    pub certificate: String,
+    pub digest: String,
+    pub reason: String,
+    pub wallets: u32,
+    #[serde(rename = "walletSet")]
+    pub wallet_set: String,
+    #[serde(rename = "wipedAt")]
+    pub wiped_at: u64,
+    #[serde(rename = "rollbackCounter")]
+    pub rollback_counter: Option<u64>,
+    pub attestation: Option<serde_json::Value>,
+}
+
+impl WipeCertificateView {
+    fn from_proof(proof: proto::tamper::WipeProof) -> Result<Self> {
+        proof.check_binding().map_err(|e| anyhow!(e))?;
+        let cert = &proof.certificate;
+        Ok(WipeCertificateView {
+            certificate: cert.message(),
+            digest: hex::encode(cert.digest()),
+            reason: format!("{:?}", cert.reason),
+            wallets: cert.wallets,
+            wallet_set: format!("0x{}", hex::encode(cert.wallet_set)),
+            wiped_at: cert.wiped_at,
+            rollback_counter: cert.rollback_counter,
+            attestation: proof
+                .attestation
+                .map(|ev| serde_json::to_value(AttestationResponse::from_evidence(ev)))
+                .transpose()?,
+        })
+    }
+}
+
+/// GET /kms/tamper/status — erase-on-tamper policy and lock state.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TamperStatusResponse {
+    /// Absent = automatic wipe off.
+    #[serde(rename = "failedAuthLimit", skip_serializing_if = "Option::is_none")]
+    pub failed_auth_limit: Option<u32>,
+    #[serde(rename = "consecutiveFailures")]
+    pub consecutive_failures: u32,
+    #[serde(rename = "lastOrderSequence")]
+    pub last_order_sequence: u64,
+    pub locked: bool,
+    #[serde(rename = "lockReason", skip_serializing_if = "Option::is_none")]
+    pub lock_reason: Option<String>,
+    #[serde(rename = "lockedSince", skip_serializing_if = "Option::is_none")]
+    pub locked_since: Option<u64>,
+    #[serde(rename = "lastWipe", skip_serializing_if = "Option::is_none")]
+    pub last_wipe: Option<WipeCertificateView>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TamperOrderResponse {
+    pub wiped: bool,
+    #[serde(flatten)]
+    pub status: TamperStatusResponse,
+}
+
+/// POST /kms/ta/log-level — the TA's trace verbosity after the order.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetLogLevelResponse {
+    pub levels: std::collections::BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub until: Option<u64>,
+    /// 0 = the first order this TA accepted.
+    #[serde(rename = "previousSequence")]
+    pub previous_sequence: u64,
+}
+
+fn parse_allowance_rule(rule: &str, max: Option<&str>) -> Result<proto::AllowanceRule> {
+    match (rule, max) {
+        ("off", None) => Ok(proto::AllowanceRule::Off),
+        ("reject-unlimited", None) => Ok(proto::AllowanceRule::RejectUnlimited),
+        ("cap", Some(max)) => Ok(proto::AllowanceRule::Cap {
+            max: parse_wei("max", max)?,
+        }),
+        ("cap", None) => Err(anyhow!("rule \"cap\" requires max")),
+        ("off", Some(_)) | ("reject-unlimited", Some(_)) => {
+            Err(anyhow!("max is only valid with rule \"cap\""))
+        }
+        (other, _) => Err(anyhow!(
+            "unknown allowance rule {:?} (off | reject-unlimited | cap)",
+            other
+        )),
+    }
+}
+
+fn allowance_rule_name(rule: &proto::AllowanceRule) -> String {
+    match rule {
+        proto::AllowanceRule::Off => "off".to_string(),
+        proto::AllowanceRule::RejectUnlimited => "reject-unlimited".to_string(),
+        proto::AllowanceRule::Cap { max } => format!("cap:{}", max),
+    }
+}
+
+fn default_zero_wei() -> String {
+    "0".to_string()
+}
+
+fn parse_wei(field: &str, v: &str) -> Result<u128> {
+    v.trim()
+        .parse::<u128>()
+        .map_err(|_| anyhow!("{} must be a decimal wei amount: {}", field, v))
+}
+
+/// POST /kms/sign-p256-user-op
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignP256UserOpRequest {
+    #[serde(rename = "keyId")]
+    pub key_id: String,
+    #[serde(rename = "payload")]
+    pub payload: String,
+    /// ERC-4337 Smart Account contract address (embedded in the 149-byte signature)
+    #[serde(rename = "accountAddress")]
+    pub account_address: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignP256UserOpResponse {
+    #[serde(rename = "keyId")]
+    pub key_id: String,
+    #[serde(rename = "pubKeyX")]
+    pub pub_key_x: String,
+    #[serde(rename = "pubKeyY")]
+    pub pub_key_y: String,
+    /// Hex-encoded 149-byte wire format: [0x08][account(20)][keyX(32)][keyY(32)][r(32)][s(32)]
+    pub signature: String,
+}
+
+/// Parse compound agent keyId "wallet_uuid:agent_index"
+fn parse_agent_key_id(key_id: &str) -> Result<(Uuid, u32)> {
+    let parts: Vec<&str> = key_id.splitn(2, ':').collect();
+    if parts.len() != 2 {
+        return Err(anyhow!(
+            "Invalid agent keyId format (expected wallet_id:index): {}",
+            key_id
+        ));
+    }
+    let wallet_id = Uuid::parse_str(parts[0])
+        .map_err(|_| anyhow!("Invalid wallet_id in agent keyId: {}", parts[0]))?;
+    let agent_index: u32 = parts[1]
+        .parse()
+        .map_err(|_| anyhow!("Invalid agent_index in keyId: {}", parts[1]))?;
+    Ok((wallet_id, agent_index))
+}
+
+/// Convert a JSON value to a proto::Eip712Value using the declared ABI type for guidance.
+///
+/// Supported types: address, uint*, int*, bytes32, bytes*, bool, string
+fn json_to_eip712_value(
+    json_val: &serde_json::Value,
+    declared_type: &str,
+) -> Result<proto::Eip712Value> {
+    let t = declared_type.trim();
+    if t == "address" {
+        let s = json_val
+            .as_str()
+            .ok_or_else(|| anyhow!("address field must be a JSON string"))?;
+        let bytes = hex::decode(s.trim_start_matches("0x"))
+            .map_err(|e| anyhow!("Invalid address hex '{}': {}", s, e))?;
+        if bytes.len() != 20 {
+            return Err(anyhow!("address must be 20 bytes, got {}", bytes.len()));
+        }
+        let mut arr = [0u8; 20];
+        arr.copy_from_slice(&bytes);
+        return Ok(proto::Eip712Value::Address(arr));
+    }
+    if t == "bool" {
+        let b = json_val
+            .as_bool()
+            .ok_or_else(|| anyhow!("bool field must be a JSON boolean"))?;
+        return Ok(proto::Eip712Value::Bool(b));
+    }
+    if t == "string" {
+        let s = json_val
+            .as_str()
+            .ok_or_else(|| anyhow!("string field must be a JSON string"))?;
+        return Ok(proto::Eip712Value::Str(s.to_string()));
+    }
+    if t == "bytes32" {
+        let s = json_val
+            .as_str()
+            .ok_or_else(|| anyhow!("bytes32 field must be a JSON hex string"))?;
+        let bytes = hex::decode(s.trim_start_matches("0x"))
+            .map_err(|e| anyhow!("Invalid bytes32 hex '{}': {}", s, e))?;
+        if bytes.len() != 32 {
+            return Err(anyhow!(
+                "bytes32 must be exactly 32 bytes, got {}",
+                bytes.len()
+            ));
+        }
+        let mut arr = [0u8; 32];
+        arr.copy_from_slice(&bytes);
+        return Ok(proto::Eip712Value::Bytes32(arr));
+    }
+    if t.starts_with("bytes") {
+        let s = json_val
+            .as_str()
+            .ok_or_else(|| anyhow!("bytes field must be a JSON hex string"))?;
+        let bytes = hex::decode(s.trim_start_matches("0x"))
+            .map_err(|e| anyhow!("Invalid bytes hex '{}': {}", s, e))?;
+        return Ok(proto::Eip712Value::Bytes(bytes));
+    }
+    if t.starts_with("uint") || t.starts_with("int") {
+        // Accept JSON number or decimal/hex string
+        let be_bytes = if let Some(n) = json_val.as_u64() {
+            n.to_be_bytes().to_vec()
+        } else if let Some(s) = json_val.as_str() {
+            let hex_str = s.trim_start_matches("0x");
+            if s.starts_with("0x") {
+                hex::decode(hex_str).map_err(|e| anyhow!("Invalid uint hex '{}': {}", s, e))?
+            } else {
+                // Decimal string — parse as u128 max (covers uint8–uint128)
+                let n: u128 = s
+                    .parse()
+                    .map_err(|_| anyhow!("Invalid uint decimal '{}'", s))?;
+                n.to_be_bytes().to_vec()
+            }
+        } else {
+            return Err(anyhow!(
+                "uint/int field must be number or string, got {:?}",
+                json_val
+            ));
+        };
+        return Ok(proto::Eip712Value::Uint(be_bytes));
+    }
+    Err(anyhow!("Unsupported EIP-712 field type '{}' (v0.18.0 supports: address, bool, string, bytes, bytes32, uint*, int*)", t))
+}
+
+/// Parse DER-encoded ECDSA signature into (r, s) 32-byte arrays
+fn parse_der_signature(der: &[u8]) -> Result<([u8; 32], [u8; 32])> {
+    if der.len() < 8 || der[0] != 0x30 {
+        return Err(anyhow!("Invalid DER signature: bad header"));
+    }
+    let total_len = der[1] as usize;
+    if total_len > 0x7F {
+        return Err(anyhow!(
+            "Invalid DER signature: long-form length not supported"
+        ));
+    }
+    if 2 + total_len > der.len() {
+        return Err(anyhow!(
+            "Invalid DER signature: declared length {} exceeds data {}",
+            total_len,
+            der.len()
+        ));
+    }
+    let mut pos = 2;
+    if pos >= der.len() || der[pos] != 0x02 {
+        return Err(anyhow!("Invalid DER signature: expected INTEGER tag for r"));
+    }
+    pos += 1;
+    if pos >= der.len() {
+        return Err(anyhow!("Invalid DER signature: missing r_len byte"));
+    }
+    let r_len = der[pos] as usize;
+    pos += 1;
+    if pos + r_len > der.len() {
+        return Err(anyhow!(
+            "Invalid DER signature: r overflows buffer (r_len={} pos={} len={})",
+            r_len,
+            pos,
+            der.len()
+        ));
+    }
+    let r_raw = &der[pos..pos + r_len];
+    pos += r_len;
+    if pos >= der.len() || der[pos] != 0x02 {
+        return Err(anyhow!("Invalid DER signature: expected INTEGER tag for s"));
+    }
+    pos += 1;
+    if pos >= der.len() {
+        return Err(anyhow!("Invalid DER signature: missing s_len byte"));
+    }
+    let s_len = der[pos] as usize;
+    pos += 1;
+    if pos + s_len > der.len() {
+        return Err(anyhow!(
+            "Invalid DER signature: s overflows buffer (s_len={} pos={} len={})",
+            s_len,
+            pos,
+            der.len()
+        ));
+    }
+    let s_raw = &der[pos..pos + s_len];
+
+    // Pad/trim to 32 bytes (DER integers may have leading zero for sign)
+    let mut r = [0u8; 32];
+    let mut s = [0u8; 32];
+    if r_raw.len() > 32 {
+        r.copy_from_slice(&r_raw[r_raw.len() - 32..]);
+    } else {
+        r[32 - r_raw.len()..].copy_from_slice(r_raw);
+    }
+    if s_raw.len() > 32 {
+        s.copy_from_slice(&s_raw[s_raw.len() - 32..]);
+    } else {
+        s[32 - s_raw.len()..].copy_from_slice(s_raw);
+    }
+    Ok((r, s))
+}
+
+// ========================================
+// KMS API Server
+// ========================================
+
+/// A passkey public key as CreateKey takes it: hex of a 65-byte uncompressed
+/// P-256 point.
+fn parse_passkey_pubkey(pk: &str) -> Result<Vec<u8>> {
+    let passkey_pubkey = hex::decode(pk.trim_start_matches("0x"))
+        .map_err(|e| anyhow!("Invalid PasskeyPublicKey hex: {}", e))?;
+    if passkey_pubkey.len() != 65 || passkey_pubkey[0] != 0x04 {
+        return Err(anyhow!(
+            "PasskeyPublicKey must be 65 bytes uncompressed (0x04||x||y), got {} bytes",
+            passkey_pubkey.len()
+        ));
+    }
+    // Validate the point is actually on the P-256 curve (prevents gap keys)
+    if p256::PublicKey::from_sec1_bytes(&passkey_pubkey).is_err() {
+        return Err(anyhow!(
+            "PasskeyPublicKey is not a valid point on the P-256 curve"
+        ));
+    }
+    Ok(passkey_pubkey)
+}
+
+/// Key pinning: compare what the TA just answered for (key_id, path) with
+/// the pinned address, pinning it on first sight. A substitution is audited
+/// against the wallet and fails the request — the caller must not hand the
+/// new address or signature out.
+fn enforce_key_pin(
+    db: &KmsDb,
+    key_id: &str,
+    derivation_path: &str,
+    address: &str,
+    public_key: Option<&str>,
+) -> Result<()> {
+    let detail = match db.pin_address(key_id, derivation_path, address, public_key)? {
+        PinCheck::Pinned | PinCheck::Matches => return Ok(()),
+        PinCheck::Mismatch { pinned } => format!(
+            "path {}: TA answered {}, pinned {}",
+            derivation_path, address, pinned
+        ),
+        PinCheck::Conflict {
+            key_id: other,
+            derivation_path: other_path,
+        } => format!(
+            "path {}: TA answered {}, already pinned to {} {}",
+            derivation_path, address, other, other_path
+        ),
+    };
+    eprintln!("🔴 KEY SUBSTITUTION for {}: {}", key_id, detail);
+    let correlation_id = RequestContext::current().map(|c| c.correlation_id);
+    if let Err(e) = db.record_account_event(
+        key_id,
+        key_pin::KEY_SUBSTITUTION_EVENT,
+        Some(&detail),
+        correlation_id.as_deref(),
+    ) {
+        eprintln!(
+            "⚠️ key_substitution audit write failed for {}: {}",
+            key_id, e
+        );
+    }
+    Err(anyhow!(
+        "SECURITY: TA key does not match the pinned address for {} ({})",
+        key_id,
+        detail
+    ))
+}
+
+fn wallet_to_metadata(w: &WalletRow) -> KeyMetadata {
+    let creation_date = w
+        .created_at
+        .parse::<DateTime<Utc>>()
+        .unwrap_or_else(|_| Utc::now());
+    KeyMetadata {
+        key_id: w.key_id.clone(),
+        address: w.address.clone(),
+        public_key: w.public_key.clone(),
+        derivation_path: w.derivation_path.clone(),
+        arn: format!("arn:aws:kms:region:account:key/{}", w.key_id),
+        creation_date,
+        enabled: true,
+        description: w.description.clone(),
+        key_usage: w.key_usage.clone(),
+        key_spec: w.key_spec.clone(),
+        origin: w.origin.clone(),
+        passkey_public_key: w.passkey_pubkey.clone(),
+        // Issue #42: populated by the caller (describe_key) via DB lookups, since
+        // WalletRow intentionally does not carry tx_log-derived / lifecycle data.
+        last_used_at: None,
+        lifecycle_status: "active".to_string(),
+        multi_region: false,
+        multi_region_configuration: None,
+    }
+}
+
+/// `None` for a single-region key. The key type is the role of this node's
+/// region; a node outside the set reports the key as a replica.
+fn multi_region_configuration(
+    key_id: &str,
+    regions: &[KeyRegionRow],
+    local_region: &str,
+) -> Option<MultiRegionConfiguration> {
+    if regions.is_empty() {
+        return None;
+    }
+    let key = |r: &KeyRegionRow| MultiRegionKey {
+        arn: replication::region_arn(&r.region, key_id),
+        region: r.region.clone(),
+    };
+    let is_local_primary = regions
+        .iter()
+        .any(|r| r.region == local_region && r.role == replication::PRIMARY);
+    Some(MultiRegionConfiguration {
+        multi_region_key_type: if is_local_primary {
+            replication::PRIMARY
+        } else {
+            replication::REPLICA
+        }
+        .to_string(),
+        primary_key: regions
+            .iter()
+            .find(|r| r.role == replication::PRIMARY)
+            .map(key),
+        replica_keys: regions
+            .iter()
+            .filter(|r| r.role != replication::PRIMARY)
+            .map(key)
+            .collect(),
+    })
+}
+
+pub const KMS_VERSION: &str = "0.29.0";
+
+/// #124 (DVT path-2 out-of-band confirm): a WebAuthn assertion the account owner
+/// produced over `challenge = userOpHash`. `passkey` is the standard browser
+/// AuthenticationResponseJSON (base64url; {authenticatorData, clientDataJSON,
+/// signature} live under `.response`).
+#[derive(Debug, serde::Deserialize)]
+pub struct VerifyConfirmAssertionRequest {
+    pub account: String,
+    #[serde(rename = "userOpHash")]
+    pub user_op_hash: String,
+    pub passkey: webauthn::AuthenticationResponseJSON,
+}
+
+/// Attestation evidence with binary fields hex-encoded for transport.
+#[derive(serde::Serialize)]
+pub struct AttestationResponse {
+    /// Evidence schema version (bump on layout changes).
+    schema: &'static str,
+    nonce: String,
+    ta_uuid: String,
+    ta_measurement: String,
+    signature: String,
+    attest_pubkey_exp: String,
+    attest_pubkey_mod: String,
+    /// Signature algorithm id (TEE_ALG_*). 0x70414930 = RSASSA_PKCS1_PSS_MGF1_SHA256.
+    sig_alg: u32,
+    ree_time_secs: u64,
+    /// Honest trust-root disclosure (see design doc §9 / R-1).
+    trust_root: &'static str,
+}
+
+impl AttestationResponse {
+    pub fn from_evidence(ev: proto::GetAttestationOutput) -> Self {
+        AttestationResponse {
+            schema: "airaccount.attestation.v1",
+            nonce: hex::encode(&ev.nonce),
+            ta_uuid: hex::encode(&ev.ta_uuid),
+            ta_measurement: hex::encode(&ev.ta_measurement),
+            signature: hex::encode(&ev.signature),
+            attest_pubkey_exp: hex::encode(&ev.attest_pubkey_exp),
+            attest_pubkey_mod: hex::encode(&ev.attest_pubkey_mod),
+            sig_alg: ev.sig_alg,
+            ree_time_secs: ev.ree_time_secs,
+            trust_root: "tofu-self-signed-optee-key (no NXP chain; see issue #37 R-1)",
+        }
+    }
+}
+
+/// POST /kms/transfer/token
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct TransferTokenRequest {
+    key_id: String,
+    #[serde(default = "default_hd_path")]
+    hd_path: String,
+    chain_id: u64,
+    /// Bundled symbol ("USDC") or 0x address.
+    token: String,
+    /// Required for an unlisted token; checked for a bundled one.
+    #[serde(default)]
+    decimals: Option<u32>,
+    /// "12.50" or "12.50 USDC".
+    amount: String,
+    /// Transfer mode: recipient, nonce, gasPrice (hex wei) and gas.
+    #[serde(default)]
+    to: Option<String>,
+    #[serde(default)]
+    nonce: Option<u64>,
+    #[serde(default)]
+    gas_price: Option<String>,
+    #[serde(default)]
+    gas: Option<u64>,
+    /// Transfer mode: the WebAuthn challenge commits to the summary.
+    #[serde(default)]
+    summary_committed: Option<bool>,
+    /// Permit mode: sign an EIP-2612 permit for the amount instead.
+    #[serde(default)]
+    permit: Option<TransferPermit>,
+    #[serde(rename = "webAuthnAssertion", default)]
+    webauthn_assertion: Option<WebAuthnAssertion>,
+}
+
+/// Gas limit for a plain ERC-20 transfer when the caller gives none.
+const DEFAULT_TOKEN_TRANSFER_GAS: u64 = 100_000;
+
+/// POST /kms/fee/quote
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct FeeQuoteRequest {
+    key_id: String,
+    chain_id: u64,
+    /// ERC-20 the fee is paid in.
+    token: String,
+    /// The UserOperation's maximum gas cost in wei (decimal).
+    max_gas_cost_wei: String,
+    #[serde(default)]
+    user_op_hash: Option<String>,
+}
+
+/// POST /kms/fee/settlement
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct FeeSettlementRequest {
+    quote_hash: String,
+    /// "settled" | "failed"
+    status: String,
+    #[serde(default)]
+    tx_hash: Option<String>,
+    /// Tokens the paymaster actually took, base units.
+    #[serde(default)]
+    actual_token_cost: Option<String>,
+}
+
+/// Query string for GET /kms/wallet/:id/events and /kms/wallet/:id/audit.
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct WalletEventsQuery {
+    /// 1-200, default 50.
+    limit: Option<u32>,
+    /// Only events with a smaller id.
+    before: Option<i64>,
+}
+
+/// Query string for GET /kms/wallet/:id/export.
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct WalletExportQuery {
+    /// koinly | cointracker | ofx.
+    format: String,
+    /// Unix seconds, inclusive; default: from the start.
+    from: Option<i64>,
+    /// Unix seconds, exclusive; default: now.
+    to: Option<i64>,
+}
+
+/// Query string for GET /kms/wallet/:id/usage.
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct WalletUsageQuery {
+    /// Unix seconds, inclusive; default: from the start.
+    from: Option<i64>,
+    /// Unix seconds, exclusive; default: now.
+    to: Option<i64>,
+}
+
+/// POST /kms/wallet/:id/notifications
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct NotificationPrefsRequest {
+    /// `NotificationPrefs` (notify.rs); null = the defaults.
+    preferences: Option<serde_json::Value>,
+    #[serde(rename = "webAuthnAssertion", default)]
+    webauthn_assertion: Option<WebAuthnAssertion>,
+}
+
+/// POST /kms/storage/{put,get,delete}: one key of a dapp's namespace.
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct DappStorageRequest {
+    key_id: String,
+    app_id: String,
+    key: String,
+    /// Put only: standard base64, at most 4096 bytes decoded.
+    #[serde(default)]
+    value: Option<String>,
+    #[serde(rename = "webAuthnAssertion", default)]
+    webauthn_assertion: Option<WebAuthnAssertion>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OtpAction {
+    Enroll,
+    Code,
+    Remove,
+    List,
+}
+
+/// POST /kms/otp/{enroll,code,remove,list}
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct OtpRequest {
+    key_id: String,
+    /// Every action except list.
+    #[serde(default)]
+    label: Option<String>,
+    /// Enroll only: the issuer's base32 secret.
+    #[serde(default)]
+    secret: Option<String>,
+    /// Enroll only: "totp" (default) or "hotp".
+    #[serde(default)]
+    r#type: Option<String>,
+    /// Enroll only: SHA1 (default), SHA256 or SHA512.
+    #[serde(default)]
+    algorithm: Option<String>,
+    /// Enroll only: 6 (default) to 8.
+    #[serde(default)]
+    digits: Option<u8>,
+    /// Enroll, totp only: seconds per code, 30 by default.
+    #[serde(default)]
+    period: Option<u32>,
+    #[serde(rename = "webAuthnAssertion", default)]
+    webauthn_assertion: Option<WebAuthnAssertion>,
+}
+
+/// POST /kms/identity/{link,unlink}
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct IdentityLinkRequest {
+    /// A link to an unknown id creates that identity, owned by this wallet.
+    identity_id: String,
+    /// New identities only.
+    #[serde(default)]
+    label: Option<String>,
+    key_id: String,
+    #[serde(default = "default_hd_path")]
+    derivation_path: String,
+    /// Link only: chains the address is used on.
+    #[serde(default)]
+    chain_ids: Vec<u64>,
+    #[serde(rename = "webAuthnAssertion", default)]
+    webauthn_assertion: Option<WebAuthnAssertion>,
+    /// The identity owner's ceremony, when `key_id` is not the owner.
+    #[serde(rename = "ownerWebAuthnAssertion", default)]
+    owner_webauthn_assertion: Option<WebAuthnAssertion>,
+}
+
+/// POST /kms/spending-analytics
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct SpendingAnalyticsRequest {
+    key_id: String,
+}
+
+/// POST /kms/identity/get
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct IdentityGetRequest {
+    #[serde(default)]
+    identity_id: Option<String>,
+    #[serde(default)]
+    address: Option<String>,
+}
+
+fn otp_entry_json(label: &str, params: &proto::otp::OtpParams) -> serde_json::Value {
+    use proto::otp::{OtpAlgorithm, OtpKind};
+    let (kind, period) = match params.kind {
+        OtpKind::Totp { period_secs } => ("totp", Some(period_secs)),
+        OtpKind::Hotp => ("hotp", None),
+    };
+    serde_json::json!({
+        "label": label,
+        "type": kind,
+        "algorithm": match params.algorithm {
+            OtpAlgorithm::Sha1 => "SHA1",
+            OtpAlgorithm::Sha256 => "SHA256",
+            OtpAlgorithm::Sha512 => "SHA512",
+        },
+        "digits": params.digits,
+        "period": period,
+    })
+}
+
+/// POST /kms/bip85/export
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct Bip85ExportRequest {
+    key_id: String,
+    /// "bip39" (needs `words`) or "hex" (needs `numBytes`).
+    application: String,
+    #[serde(default)]
+    words: Option<u32>,
+    #[serde(default)]
+    num_bytes: Option<u32>,
+    index: u32,
+    /// 0x-hex compressed secp256k1 key of the app being provisioned.
+    recipient_public_key: String,
+    #[serde(rename = "webAuthnAssertion", default)]
+    webauthn_assertion: Option<WebAuthnAssertion>,
+}
+
+/// POST /kms/decrypt/session
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct OpenDecryptSessionRequest {
+    key_id: String,
+    #[serde(default = "default_hd_path")]
+    derivation_path: String,
+    max_decryptions: u32,
+    ttl_secs: u32,
+    #[serde(rename = "webAuthnAssertion", default)]
+    webauthn_assertion: Option<WebAuthnAssertion>,
+}
+
+/// POST /kms/decrypt
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct EciesDecryptRequest {
+    key_id: String,
+    /// 0x-hex, from /kms/decrypt/session.
+    session_id: String,
+    /// 0x-hex ECIES envelope (eciesjs layout, uncompressed ephemeral key).
+    payload: String,
+}
+
+/// POST /kms/stealth/meta-address
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct StealthMetaAddressRequest {
+    key_id: String,
+    /// `m/5564'/60'/account'`.
+    #[serde(default)]
+    account: u32,
+}
+
+/// POST /kms/stealth/address
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct StealthAddressRequest {
+    /// `st:eth:0x…`, any wallet's.
+    meta_address: String,
+}
+
+/// POST /kms/stealth/scan
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct StealthScanRequest {
+    key_id: String,
+    #[serde(default)]
+    account: u32,
+}
+
+/// POST /admin/provision-wallets
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ProvisionWalletsRequest {
+    tenant: String,
+    #[serde(default)]
+    tags: Vec<String>,
+    /// One wallet per passkey.
+    #[serde(default)]
+    passkey_public_keys: Option<Vec<String>>,
+    /// Or `count` wallets under one passkey, e.g. an onboarding officer's
+    /// until each holder moves theirs over with ChangePasskey.
+    #[serde(default)]
+    passkey_public_key: Option<String>,
+    #[serde(default)]
+    count: Option<usize>,
+    #[serde(default)]
+    description: Option<String>,
+    /// "json" (default) or "csv": csv adds the report as CSV too.
+    #[serde(default)]
+    format: Option<String>,
+}
+
+/// POST /admin/standby/promote
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct StandbyPromoteRequest {
+    /// Promote even though some ready wallets are missing from this TA.
+    #[serde(default)]
+    force: bool,
+}
+
+/// POST /admin/device-health/release
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct DeviceHealthReleaseRequest {
+    /// Why the operator lifts it (ticket, board swapped …); logged and alerted.
+    #[serde(default)]
+    note: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProvisionWalletsResponse {
+    pub batch_id: String,
+    /// Report JSON, verbatim — hash these bytes, don't re-serialize.
+    report: String,
+    /// SHA-256(report), hex. This is the attestation nonce.
+    digest: String,
+    /// None if the TA gave no evidence; the wallets exist regardless.
+    attestation: Option<AttestationResponse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    csv: Option<String>,
+}
+
+/// The attested body. Field order is the serialization order, and the digest
+/// is over the exact bytes returned in `ActivityStatementResponse::statement`.
+#[derive(Debug, serde::Serialize)]
+struct ActivityStatement<'a> {
+    schema: &'static str,
+    key_id: &'a str,
+    address: Option<String>,
+    month: &'a str,
+    period_start: String,
+    period_end: String,
+    operations: Vec<crate::db::OpCount>,
+    generated_at: String,
+}
+
+#[derive(serde::Serialize)]
+pub struct ActivityStatementResponse {
+    /// Statement JSON, verbatim — hash these bytes, don't re-serialize.
+    statement: String,
+    /// SHA-256(statement), hex. This is the attestation nonce.
+    digest: String,
+    attestation: AttestationResponse,
+}
+
+/// [start, end) of a finished UTC month. The current or a future month is
+/// rejected so an archived statement is final, never a partial snapshot.
+fn statement_period(month: &str, now: DateTime<Utc>) -> Result<(DateTime<Utc>, DateTime<Utc>)> {
+    use chrono::{Datelike, NaiveDate, TimeZone};
+    let bad = || anyhow!("Month must be YYYY-MM");
+    if month.len() != 7 {
+        return Err(bad());
+    }
+    let first =
+        NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d").map_err(|_| bad())?;
+    let next = if first.month() == 12 {
+        NaiveDate::from_ymd_opt(first.year() + 1, 1, 1)
+    } else {
+        NaiveDate::from_ymd_opt(first.year(), first.month() + 1, 1)
+    }
+    .ok_or_else(bad)?;
+    let start = Utc.from_utc_datetime(&first.and_hms_opt(0, 0, 0).ok_or_else(bad)?);
+    let end = Utc.from_utc_datetime(&next.and_hms_opt(0, 0, 0).ok_or_else(bad)?);
+    if end > now {
+        return Err(anyhow!("Month {} has not ended yet", month));
+    }
+    Ok((start, end))
+}
+
+/// POST /contact/begin-binding — owner ceremony + the channel to bind (#129).
+#[derive(Debug, serde::Deserialize)]
+pub struct BeginBindingRequest {
+    pub account: String,
+    pub channel: String, // 'telegram' | 'email'
+    /// Required for channel 'email'; ignored for telegram.
+    #[serde(default)]
+    pub email: Option<String>,
+    // Match the existing KMS API field name `WebAuthn` (what the SDK sends) so a real
+    // ceremony isn't silently dropped to None; keep lowercase aliases for flexibility.
+    #[serde(
+        rename = "WebAuthn",
+        alias = "webauthn",
+        alias = "webauthn_assertion",
+        default
+    )]
+    pub webauthn_assertion: Option<WebAuthnAssertion>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct BeginBindingResponse {
+    #[serde(rename = "bindingCode")]
+    binding_code: String,
+    #[serde(rename = "expiresAt")]
+    expires_at: i64,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct ClaimBindingRequest {
+    #[serde(rename = "bindingCode", alias = "binding_code")]
+    pub binding_code: String,
+    #[serde(rename = "telegramChatId", alias = "telegram_chat_id")]
+    pub telegram_chat_id: String,
+    #[serde(rename = "telegramUsername", alias = "telegram_username")]
+    pub telegram_username: Option<String>,
+    #[serde(rename = "botId", alias = "bot_id")]
+    pub bot_id: Option<String>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct ClaimBindingResponse {
+    #[serde(rename = "verifyToken")]
+    verify_token: String,
+    #[serde(rename = "expiresAt")]
+    expires_at: i64,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct ClaimEmailBindingRequest {
+    #[serde(rename = "bindingCode", alias = "binding_code")]
+    pub binding_code: String,
+    #[serde(rename = "relayId", alias = "relay_id", default)]
+    pub relay_id: Option<String>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct ClaimEmailBindingResponse {
+    email: String,
+    #[serde(rename = "verifyToken")]
+    verify_token: String,
+    #[serde(rename = "expiresAt")]
+    expires_at: i64,
+}
+
+/// Lowercased, trimmed address. Deliberately loose (one '@', non-empty parts,
+/// no whitespace): the mailed token is the real ownership proof.
+fn normalize_email(raw: &str) -> Result<String> {
+    let e = raw.trim().to_lowercase();
+    let valid = e.len() <= 254
+        && !e.chars().any(char::is_whitespace)
+        && matches!(e.split_once('@'), Some((l, d)) if !l.is_empty() && d.contains('.') && !d.contains('@'));
+    if !valid {
+        return Err(anyhow!("invalid email address"));
+    }
+    Ok(e)
+}
+
+/// "alice@example.com" → "a***@example.com", for the owner to recognise the binding.
+fn mask_email(email: &str) -> String {
+    match email.split_once('@') {
+        Some((local, domain)) => {
+            let first: String = local.chars().take(1).collect();
+            format!("{}***@{}", first, domain)
+        }
+        None => "***".to_string(),
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct ConfirmBindingRequest {
+    pub account: String,
+    #[serde(rename = "bindingCode", alias = "binding_code")]
+    pub binding_code: String,
+    #[serde(rename = "verifyToken", alias = "verify_token")]
+    pub verify_token: String,
+    // Match the existing KMS API field name `WebAuthn` (what the SDK sends) so a real
+    // ceremony isn't silently dropped to None; keep lowercase aliases for flexibility.
+    #[serde(
+        rename = "WebAuthn",
+        alias = "webauthn",
+        alias = "webauthn_assertion",
+        default
+    )]
+    pub webauthn_assertion: Option<WebAuthnAssertion>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct ConfirmBindingResponse {
+    status: String,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct UnbindRequest {
+    pub account: String,
+    pub channel: String,
+    // Match the existing KMS API field name `WebAuthn` (what the SDK sends) so a real
+    // ceremony isn't silently dropped to None; keep lowercase aliases for flexibility.
+    #[serde(
+        rename = "WebAuthn",
+        alias = "webauthn",
+        alias = "webauthn_assertion",
+        default
+    )]
+    pub webauthn_assertion: Option<WebAuthnAssertion>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct UnbindResponse {
+    status: String,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct ContactView {
+    channel: String,
+    #[serde(rename = "contactRef")]
+    contact_ref: Option<String>,
+    #[serde(rename = "displayHint")]
+    display_hint: Option<String>,
+    status: String,
+    #[serde(rename = "verifiedAt")]
+    verified_at: Option<i64>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct TransferPermit {
+    spender: String,
+    nonce: String,
+    deadline: String,
+    /// The token's EIP-712 domain name and version.
+    token_name: String,
+    #[serde(default)]
+    token_version: Option<String>,
+}
+
+#[cfg(feature = "threshold-ecdsa")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThresholdAction {
+    Keygen,
+    Sign,
+    Share,
+    Recombine,
+    Status,
+}
+
+/// POST /kms/threshold/{keygen,sign,share,recombine,status}
+#[cfg(feature = "threshold-ecdsa")]
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ThresholdRequest {
+    key_id: String,
+    /// Sign only: the 32-byte hash, hex.
+    #[serde(default)]
+    digest: Option<String>,
+    /// Recombine only: the co-signer's share sealed to the TA, as share
+    /// returned it.
+    #[serde(default)]
+    sealed_share: Option<String>,
+    /// Recombine only: a secp256k1 public key to seal the whole key to.
+    #[serde(default)]
+    export_to: Option<String>,
+    #[serde(rename = "webAuthnAssertion", default)]
+    webauthn_assertion: Option<WebAuthnAssertion>,
+}
+
+/// Issue #73: minimum seconds between `/health` attestation probes while
+/// capability is not yet confirmed. Bounds TEE load from frequent /health
+/// polling against an older/incapable TA (or during the startup window).
+const ATTESTATION_PROBE_MIN_INTERVAL_SECS: i64 = 30;
+
+/// Minimum seconds between TaStats calls behind `/stats`. The endpoint is
+/// unauthenticated, so dashboards polling it must not queue a TEE call each.
+const TA_STATS_MIN_INTERVAL_SECS: i64 = 10;
+
+pub struct KmsApiServer {
+    pub db: KmsDb,
+    pub tee: TeeHandle,
+    pub rate_limiter: RateLimiter,
+    agent_rate_limiter: RateLimiter,
+    /// OTP codes and enrollments per wallet per minute (KMS_OTP_RATE_LIMIT).
+    otp_rate_limiter: RateLimiter,
+    /// Locks a wallet after repeated failed passkey assertions.
+    passkey_lockout: PasskeyLockout,
+    rp_name: String,
+    rp_ids: Vec<String>,
+    expected_origins: Vec<String>,
+    /// Issue #73 — attestation capability for `/health`, replacing a hardcoded
+    /// `true`. `attestation_capable` is a **monotonic latch**: the first probe
+    /// that proves the deployed TA supports GetAttestation (=26) latches it
+    /// `true` for the process lifetime (a TA that gains/loses the command needs a
+    /// redeploy, which restarts the process and resets this). While unconfirmed,
+    /// `attestation_probe_at` (unix secs of the last probe) rate-limits re-probes
+    /// so a transient startup window OR an older TA cannot trigger a TEE call on
+    /// every `/health`.
+    attestation_capable: std::sync::atomic::AtomicBool,
+    attestation_probe_at: std::sync::atomic::AtomicI64,
+    /// (unix secs of the last TaStats attempt, last good snapshot).
+    ta_stats_cache: std::sync::Mutex<(i64, Option<proto::TaStatsOutput>)>,
+    /// ERC-20 fee quotes; None = /kms/fee/quote is off (KMS_PAYMASTER_URL).
+    paymaster: Option<PaymasterConfig>,
+    /// FIDO MDS attestation checks; Err = misconfigured, so registration is
+    /// refused rather than let a role through unchecked.
+    fido_mds: std::result::Result<Option<FidoMds>, String>,
+    /// Release-manifest check of the installed TA (KMS_TA_MANIFEST), for
+    /// `/health`. Err = the TA was refused and `tee` fails every command.
+    pub ta_release: std::result::Result<Option<VerifiedRelease>, String>,
+    /// Hot-reloadable sections: rate limits and the risk policy
+    /// (KMS_RISK_BACKEND; Err = misconfigured, so every scored Sign needs
+    /// step-up).
+    pub config: ConfigManager,
+    /// Screening of what the CA broadcasts (KMS_COMPLIANCE_URL); Err =
+    /// misconfigured, so nothing is broadcast.
+    compliance: std::result::Result<Screening, String>,
+    /// Push relay to approver phones (KMS_PUSH_RELAY_URL); Err =
+    /// misconfigured, so no signing request is pushed.
+    push_relay: std::result::Result<Option<PushRelay>, String>,
+    /// The threshold key's co-signer (KMS_THRESHOLD_COSIGNER_URL); Err =
+    /// misconfigured, so only a recombined threshold key signs.
+    #[cfg(feature = "threshold-ecdsa")]
+    threshold_cosigner: std::result::Result<Option<HttpCoSigner>, String>,
+    /// Bundler the aggregated userOps go to (KMS_BUNDLER_URL); Err =
+    /// misconfigured, so the queue is never flushed.
+    bundler: std::result::Result<Option<BundlerConfig>, String>,
+    /// BLS-signed userOps waiting for a flush, grouped by aggregator.
+    aggregation_queue: AggregationQueue,
+    /// Relayer balance monitor (KMS_GAS_TANK_RPC), set by `start_kms_server`,
+    /// which refuses to start on a bad config.
+    pub gas_tanks: Option<GasTankConfig>,
+    /// Chain watcher (KMS_CHAIN_WS_URL); stealth payments found by a scan go
+    /// through its webhook like the deposits it sees itself.
+    pub chain_watch: Option<chain_watch::WatchConfig>,
+    /// HSM signer of the wallets in `wallet_signers` (KMS_PKCS11_MODULE).
+    pub pkcs11: Option<Pkcs11Signer>,
+    /// Active-standby pair (KMS_STANDBY_ROLE); this node's role is in
+    /// `standby_state`.
+    pub standby: Option<StandbyConfig>,
+    /// Tenant quota and hourly ceiling of bulk provisioning; Err =
+    /// misconfigured, so /admin/provision-wallets is refused.
+    provisioning: std::result::Result<ProvisioningLimits, String>,
+    /// Held by the provisioning batch in progress: batches run one at a time,
+    /// which also makes each batch's quota check final.
+    provisioning_batch: tokio::sync::Mutex<()>,
+    /// Schedule and alert webhook of the device health check.
+    pub device_health: HealthConfig,
+    /// Held by the health check in progress, so an operator's check and the
+    /// scheduled one never quarantine from interleaved answers.
+    health_check_running: tokio::sync::Mutex<()>,
+}
+
+impl KmsApiServer {
+    pub fn new(db: KmsDb) -> Self {
+        // DEV/TEST builds (feature dev-rpid) bake localhost into the defaults so
+        // a test image is self-contained; production builds default to aastar.io
+        // only. KMS_RP_ID / KMS_ORIGIN env always override either default.
+        // NOTE: the host default/env only governs what the CA advertises and
+        // pre-checks; the TA's compiled-in rpId allow-list is the binding gate
+        // (the TA must also be a dev-rpid build to accept localhost assertions).
+        let rp_ids: Vec<String> = std::env::var("KMS_RP_ID")
+            .map(|v| v.split(',').map(|s| s.trim().to_string()).collect())
+            .unwrap_or_else(|_| {
+                if cfg!(feature = "dev-rpid") {
+                    vec!["aastar.io".to_string(), "localhost".to_string()]
+                } else {
+                    vec!["aastar.io".to_string()]
+                }
+            });
+        let rp_name = std::env::var("KMS_RP_NAME").unwrap_or_else(|_| "AirAccount KMS".to_string());
+        let expected_origins: Vec<String> = std::env::var("KMS_ORIGIN")
+            .map(|v| v.split(',').map(|s| s.trim().to_string()).collect())
+            .unwrap_or_else(|_| {
+                if cfg!(feature = "dev-rpid") {
+                    vec![
+                        "https://aastar.io".to_string(),
+                        "https://*.aastar.io".to_string(),
+                        "http://localhost:*".to_string(),
+                    ]
+                } else {
+                    vec![format!("https://{}", rp_ids[0])]
+                }
+            });
+        #[cfg(feature = "dev-rpid")]
+        println!("⚠️  DEV-RPID build: localhost rpId/origin accepted — NOT a production image");
+        println!("🌐 Allowed origins: {:?}", expected_origins);
+        println!("🔑 Allowed rpIds: {:?}", rp_ids);
+        let rate_limiter = RateLimiter::from_env();
+        println!("⏱️  Rate limiter: {}/min per API key", rate_limiter.limit());
+        let agent_rl_limit = std::env::var("KMS_AGENT_RATE_LIMIT")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(20);
+        let agent_rl_max_keys = std::env::var("KMS_RATE_LIMIT_MAX_KEYS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(10_000);
+        let agent_rate_limiter = RateLimiter::new(agent_rl_limit, agent_rl_max_keys);
+        println!(
+            "⏱️  Agent rate limiter: {}/min per credential (max {} tracked keys)",
+            agent_rl_limit, agent_rl_max_keys
+        );
+        let otp_rl_limit = std::env::var("KMS_OTP_RATE_LIMIT")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(10);
+        let otp_rate_limiter = RateLimiter::new(otp_rl_limit, agent_rl_max_keys);
+        let passkey_lockout = PasskeyLockout::from_env();
+        println!(
+            "🔒 Passkey lockout after {} failed assertions (0 = off)",
+            passkey_lockout.threshold()
+        );
+        let paymaster = match PaymasterConfig::from_env() {
+            Some(Ok(config)) => {
+                println!("⛽ Paymaster quotes: {}", config.url);
+                Some(config)
+            }
+            Some(Err(e)) => {
+                eprintln!("⚠️  {} — ERC-20 fee quotes disabled", e);
+                None
+            }
+            None => None,
+        };
+        match otel::OtelConfig::from_env() {
+            Some(Ok(config)) => {
+                println!(
+                    "📡 OTLP export: {} (sample ratio {})",
+                    config.endpoint, config.sample_ratio
+                );
+                otel::init(config);
+            }
+            Some(Err(e)) => eprintln!("⚠️  {} — OTLP export disabled", e),
+            None => {}
+        }
+        let fido_mds = match FidoMds::from_env() {
+            Some(Ok(mds)) => {
+                println!(
+                    "🛡️  FIDO MDS attestation checks on ({} roles)",
+                    mds.policy.roles.len()
+                );
+                Ok(Some(mds))
+            }
+            Some(Err(e)) => {
+                eprintln!("⚠️  {:#} — WebAuthn registration disabled", e);
+                Err(format!("{:#}", e))
+            }
+            None => Ok(None),
+        };
+        let risk = match RiskPolicy::from_env() {
+            Some(Ok(policy)) => {
+                println!(
+                    "🚨 Risk scoring: {} backend, step-up at score {} (challenge < {}s old)",
+                    policy.backend.name(),
+                    policy.step_up_score,
+                    policy.fresh_secs
+                );
+                Ok(Some(policy))
+            }
+            Some(Err(e)) => {
+                eprintln!("⚠️  {:#} — every transaction Sign needs step-up", e);
+                Err(format!("{:#}", e))
+            }
+            None => Ok(None),
+        };
+        let config = ConfigManager::new(
+            rate_limiter.clone(),
+            agent_rate_limiter.clone(),
+            otp_rate_limiter.clone(),
+            risk,
+        );
+        let compliance = match Screening::from_env() {
+            Some(Ok(screening)) => {
+                println!(
+                    "🧾 Compliance screening: {} provider before broadcast, fail-{}",
+                    screening.provider_name(),
+                    screening.on_error()
+                );
+                Ok(screening)
+            }
+            Some(Err(e)) => {
+                eprintln!("⚠️  {:#} — the CA broadcasts nothing", e);
+                Err(format!("{:#}", e))
+            }
+            None => Ok(Screening::none()),
+        };
+        let push_relay = match PushRelay::from_env() {
+            Some(Ok(relay)) => {
+                println!("📲 Approver push relay: {}", relay.url);
+                Ok(Some(relay))
+            }
+            Some(Err(e)) => {
+                eprintln!("⚠️  {:#} — signing requests are not pushed", e);
+                Err(format!("{:#}", e))
+            }
+            None => Ok(None),
+        };
+        #[cfg(feature = "threshold-ecdsa")]
+        let threshold_cosigner = match HttpCoSigner::from_env() {
+            Some(Ok(cosigner)) => {
+                println!("🔑 Threshold ECDSA co-signer: {}", cosigner.url);
+                Ok(Some(cosigner))
+            }
+            Some(Err(e)) => {
+                eprintln!("⚠️  {:#} — threshold keys sign only once recombined", e);
+                Err(format!("{:#}", e))
+            }
+            None => Ok(None),
+        };
+        let bundler = match BundlerConfig::from_env() {
+            Some(Ok(bundler)) => {
+                println!("📦 Bundler for aggregated userOps: {}", bundler.url);
+                Ok(Some(bundler))
+            }
+            Some(Err(e)) => {
+                eprintln!("⚠️  {:#} — aggregated userOps are not submitted", e);
+                Err(format!("{:#}", e))
+            }
+            None => Ok(None),
+        };
+        let provisioning = ProvisioningLimits::from_env().map_err(|e| {
+            eprintln!("⚠️  {:#} — bulk provisioning disabled", e);
+            format!("{:#}", e)
+        });
+        let mut tee = TeeHandle::new();
+        match CacheConfig::from_env() {
+            Ok(config) => {
+                if let Some(c) = &config {
+                    println!(
+                        "🗃️  Response cache: ttl {}s, at most {} entries",
+                        c.ttl.as_secs(),
+                        c.max_entries
+                    );
+                }
+                tee.enable_response_cache(config);
+            }
+            Err(e) => eprintln!("⚠️  {:#} — response cache off", e),
+        }
+        let ta_release = match ReleaseCheckConfig::from_env()
+            .map(|c| c.and_then(|c| c.check(TA_UUID, KMS_VERSION)))
+        {
+            Some(Ok(release)) => {
+                println!(
+                    "📦 TA release {} verified (sha256 {}, signed by {})",
+                    release.ta_version,
+                    release.sha256,
+                    key_pin::address_hex(&release.signer)
+                );
+                Ok(Some(release))
+            }
+            Some(Err(e)) => {
+                eprintln!("❌ {:#} — TA refused, every TEE command will fail", e);
+                tee.refuse(format!("{:#}", e));
+                Err(format!("{:#}", e))
+            }
+            None => Ok(None),
+        };
+        // A quarantine outlives restarts until an operator lifts it; if its
+        // state cannot be read, signing stays off rather than on.
+        match db.device_quarantine() {
+            Ok(Some(q)) => {
+                eprintln!(
+                    "🚧 Device quarantined since {}: {} — signing drained to replicas",
+                    q.since, q.reason
+                );
+                tee.quarantine(&q.reason);
+            }
+            Ok(None) => {}
+            Err(e) => {
+                eprintln!("❌ device quarantine state unreadable: {:#}", e);
+                tee.quarantine("quarantine state unreadable");
+            }
+        }
+        device_health::export_gauges(None, tee.quarantine_reason().is_some());
+        Self {
+            db,
+            tee,
+            rate_limiter,
+            agent_rate_limiter,
+            otp_rate_limiter,
+            passkey_lockout,
+            rp_name,
+            rp_ids,
+            expected_origins,
+            attestation_capable: std::sync::atomic::AtomicBool::new(false),
+            attestation_probe_at: std::sync::atomic::AtomicI64::new(0),
+            ta_stats_cache: std::sync::Mutex::new((0, None)),
+            paymaster,
+            fido_mds,
+            ta_release,
+            config,
+            compliance,
+            push_relay,
+            #[cfg(feature = "threshold-ecdsa")]
+            threshold_cosigner,
+            bundler,
+            aggregation_queue: AggregationQueue::default(),
+            gas_tanks: None,
+            chain_watch: None,
+            pkcs11: None,
+            standby: None,
+            provisioning,
+            provisioning_batch: tokio::sync::Mutex::new(()),
+            device_health: HealthConfig {
+                interval_secs: None,
+                webhook_url: None,
+                webhook_secret: None,
+            },
+            health_check_running: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    fn fido_mds(&self) -> Result<Option<&FidoMds>> {
+        self.fido_mds
+            .as_ref()
+            .map(Option::as_ref)
+            .map_err(|e| anyhow!("FIDO attestation config invalid: {}", e))
+    }
+
+    /// Issue #73 — real attestation capability for `/health`, replacing a
+    /// hardcoded `true`. Capability is a **monotonic latch**: the first probe
+    /// that succeeds (GetAttestation with a fixed, non-secret dummy nonce; the
+    /// evidence is discarded) latches `true` for the process lifetime. While
+    /// unconfirmed, probes are rate-limited (>= `ATTESTATION_PROBE_MIN_INTERVAL_SECS`
+    /// apart) so a transient startup window OR an older TA without cmd 26 cannot
+    /// trigger a TEE call on every `/health`.
+    ///
+    /// There is deliberately **no coupling to any error wording**: any error
+    /// just means "not capable right now", and the next probe (after the
+    /// interval) flips it to `true` the moment a capable TA is ready. This is
+    /// both robust (a reworded TA error can't mislead it) and fail-safe (worst
+    /// case is an extra probe per interval, never a wrong permanent verdict).
+    pub async fn attestation_capable(&self) -> bool {
+        use std::sync::atomic::Ordering;
+        // Monotonic: a TA cannot lose cmd 26 under a running host (that needs a
+        // redeploy, which restarts the process and resets this latch).
+        if self.attestation_capable.load(Ordering::Relaxed) {
+            return true;
+        }
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+        let last = self.attestation_probe_at.load(Ordering::Relaxed);
+        // Skip only when the clock has genuinely advanced but less than the
+        // interval. `now < last` means wall time moved backward (NTP / tz jump);
+        // treat that as probe-due rather than freezing re-probes until wall time
+        // catches up to a stale future timestamp.
+        if now >= last && now.saturating_sub(last) < ATTESTATION_PROBE_MIN_INTERVAL_SECS {
+            // Probed recently and still not capable — don't hammer the TEE.
+            return false;
+        }
+        self.attestation_probe_at.store(now, Ordering::Relaxed);
+        match self.get_attestation(b"health-probe".to_vec()).await {
+            Ok(_) => {
+                self.attestation_capable.store(true, Ordering::Relaxed);
+                true
+            }
+            // Transient (worker not ready) OR unsupported (older TA): both mean
+            // "not capable now". Re-probed after the interval — no error-string match.
+            Err(_) => false,
+        }
+    }
+
+    /// TA per-command latency histograms, fetched at most once per
+    /// `TA_STATS_MIN_INTERVAL_SECS`. A failed fetch keeps serving the previous
+    /// snapshot (or None) until the next interval; an older TA without cmd 40
+    /// just never reports.
+    pub async fn ta_latency(&self) -> Option<proto::TaStatsOutput> {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+        let (at, cached) = self.ta_stats_cache.lock().ok()?.clone();
+        if now >= at && now - at < TA_STATS_MIN_INTERVAL_SECS {
+            return cached;
+        }
+        let snapshot = self.tee.ta_stats().await.ok().or(cached);
+        if let Ok(mut guard) = self.ta_stats_cache.lock() {
+            *guard = (now, snapshot.clone());
+        }
+        snapshot
+    }
+
+    // ========================================
+    // CA-side input validation (defense-in-depth)
+    // Validates BEFORE sending to TA to prevent TA crashes from bad input.
+    // ========================================
+
+    /// Validate BIP-44 derivation path format with the TA's own rules
+    /// (`proto::hd_path`): m/44'/60'/0'/{account}/{address}, at most 64 chars.
+    fn validate_derivation_path(path: &str) -> Result<()> {
+        proto::hd_path::parse_eth_path(path)
+            .map(|_| ())
+            .map_err(|e| proto::hd_path::InvalidPath(e).into())
+    }
+
+    fn parse_chain(chain: Option<&str>) -> Result<Chain> {
+        match chain {
+            Some(name) => name.parse().map_err(|e: String| anyhow!(e)),
+            None => Ok(Chain::Ethereum),
+        }
+    }
+
+    /// `validate_derivation_path` for the coin type of `chain`.
+    fn validate_chain_path(path: &str, chain: Chain) -> Result<()> {
+        proto::hd_path::parse_coin_path(path, chain.coin_type())
+            .map(|_| ())
+            .map_err(|e| proto::hd_path::InvalidPath(e).into())
+    }
+
+    /// Validate wallet UUID format at CA layer.
+    fn validate_key_id(key_id: &str) -> Result<Uuid> {
+        Uuid::parse_str(key_id)
+            .map_err(|_| anyhow!("Invalid KeyId format (expected UUID): {}", key_id))
+    }
+
+    /// Validate hex-encoded hash (must be exactly 32 bytes = 64 hex chars).
+    fn parse_address_hex(addr: &str) -> Result<[u8; 20]> {
+        let hex_str = addr.trim_start_matches("0x");
+        let bytes = hex::decode(hex_str).map_err(|e| anyhow!("Invalid address hex: {}", e))?;
+        if bytes.len() != 20 {
+            return Err(anyhow!(
+                "Address must be exactly 20 bytes, got {}",
+                bytes.len()
+            ));
+        }
+        let mut arr = [0u8; 20];
+        arr.copy_from_slice(&bytes);
+        Ok(arr)
+    }
+
+    fn validate_hash_hex(hash: &str) -> Result<[u8; 32]> {
+        let hex_str = hash.trim_start_matches("0x");
+        let bytes = hex::decode(hex_str).map_err(|e| anyhow!("Invalid hash hex: {}", e))?;
+        if bytes.len() != 32 {
+            return Err(anyhow!(
+                "Hash must be exactly 32 bytes, got {} bytes",
+                bytes.len()
+            ));
+        }
+        let mut arr = [0u8; 32];
+        arr.copy_from_slice(&bytes);
+        Ok(arr)
+    }
+
+    /// Validate hex-encoded message (reasonable size limit for TA).
+    fn validate_message(message: &str) -> Result<()> {
+        let max_len = 64 * 1024; // 64KB
+        if message.len() > max_len {
+            return Err(anyhow!(
+                "Message too large: {} bytes (max {})",
+                message.len(),
+                max_len
+            ));
+        }
+        Ok(())
+    }
+
+    /// Sign's `Message`: 0x-hex, else base64, else the UTF-8 text itself.
+    fn decode_message(message: &str) -> Result<Vec<u8>> {
+        use base64::{engine::general_purpose::STANDARD, Engine};
+        Ok(if let Some(hex_str) = message.strip_prefix("0x") {
+            hex::decode(hex_str)?
+        } else {
+            STANDARD
+                .decode(message)
+                .unwrap_or_else(|_| message.as_bytes().to_vec())
+        })
+    }
+
+    pub async fn create_key(&self, req: CreateKeyRequest) -> Result<CreateKeyResponse> {
+        println!("📝 KMS CreateKey API called");
+
+        // Decode and validate passkey public key (mandatory)
+        let passkey_pubkey = parse_passkey_pubkey(&req.passkey_public_key)?;
+
+        // The first address of an imported phrase, for the user to compare
+        // with the wallet it came from.
+        let mut imported_address = None;
+        let (wallet_id, origin) = match (&req.keystore, &req.migration, &req.mnemonic) {
+            (None, None, Some(phrase)) => {
+                let out = self
+                    .tee
+                    .import_wallet(&passkey_pubkey, phrase.clone())
+                    .await?;
+                let address = proto::eip55::to_checksum_address(&out.address);
+                println!(
+                    "🔑 CreateKey: recovery phrase imported, m/44'/60'/0'/0/0 = {}",
+                    address
+                );
+                imported_address = Some(address);
+                (out.wallet_id, "EXTERNAL".to_string())
+            }
+            (None, Some(package), None) => {
+                let out = self
+                    .tee
+                    .migration_import(package.to_input(&passkey_pubkey)?)
+                    .await?;
+                println!(
+                    "🔑 CreateKey: hardware wallet migrated, m/44'/60'/0'/0/0 = {}",
+                    proto::eip55::to_checksum_address(&out.address)
+                );
+                (out.wallet_id, "EXTERNAL".to_string())
+            }
+            (Some(file), None, None) => {
+                let keystore = keystore::from_json(file)?;
+                let passphrase = req
+                    .keystore_passphrase
+                    .clone()
+                    .ok_or_else(|| anyhow!("Keystore requires KeystorePassphrase"))?;
+                let wallet_id = self
+                    .tee
+                    .import_keystore(&passkey_pubkey, keystore, passphrase)
+                    .await?;
+                (wallet_id, "EXTERNAL".to_string())
+            }
+            (None, None, None) => (
+                self.tee.create_wallet(&passkey_pubkey, None).await?,
+                req.origin.clone(),
+            ),
+            _ => {
+                return Err(anyhow!(
+                    "Keystore, Migration and Mnemonic cannot be combined"
+                ));
+            }
+        };
+        let now = Utc::now();
+
+        let mut key_metadata = KeyMetadata {
+            key_id: wallet_id.to_string(),
+            address: imported_address,
+            public_key: None,
+            derivation_path: None,
+            arn: format!("arn:aws:kms:region:account:key/{}", wallet_id),
+            creation_date: now,
+            enabled: true,
+            description: req.description.clone(),
+            key_usage: req.key_usage.clone(),
+            key_spec: req.key_spec.clone(),
+            origin: origin.clone(),
+            passkey_public_key: Some(req.passkey_public_key.clone()),
+            // Issue #42: a just-created key is active and has no usage history yet.
+            last_used_at: None,
+            lifecycle_status: "active".to_string(),
+            multi_region: false,
+            multi_region_configuration: None,
+        };
+
+        let row = WalletRow {
+            key_id: wallet_id.to_string(),
+            address: None,
+            public_key: None,
+            derivation_path: None,
+            description: req.description,
+            key_usage: req.key_usage,
+            key_spec: req.key_spec,
+            origin,
+            passkey_pubkey: Some(req.passkey_public_key),
+            credential_id: None,
+            sign_count: 0,
+            status: "deriving".to_string(),
+            error_msg: None,
+            created_at: now.to_rfc3339(),
+        };
+        self.insert_new_wallet("CreateKey", &row).await?;
+        if req.multi_region.unwrap_or(false) {
+            let region = replication::local_region();
+            let key_id = wallet_id.to_string();
+            self.db.set_key_region(
+                &key_id,
+                &region,
+                &replication::local_device_id(),
+                replication::PRIMARY,
+            )?;
+            key_metadata.arn = replication::region_arn(&region, &key_id);
+            key_metadata.multi_region = true;
+            key_metadata.multi_region_configuration =
+                multi_region_configuration(&key_id, &self.db.list_key_regions(&key_id)?, &region);
+        }
+
+        // Spawn background address derivation
+        let db = self.db.clone();
+        let tee = self.tee.clone();
+        tokio::spawn(async move {
+            match tee.derive_address_auto(wallet_id).await {
+                Ok((_wid, address_bytes, public_key, derivation_path)) => {
+                    let address_hex = format!("0x{}", hex::encode(&address_bytes));
+                    let pubkey_hex = format!("0x{}", hex::encode(&public_key));
+                    println!(
+                        "✅ Background derivation done for {}: {}",
+                        wallet_id, address_hex
+                    );
+
+                    if let Err(e) = enforce_key_pin(
+                        &db,
+                        &wallet_id.to_string(),
+                        &derivation_path,
+                        &address_hex,
+                        Some(&pubkey_hex),
+                    ) {
+                        let _ = db.update_wallet_status(
+                            &wallet_id.to_string(),
+                            "error",
+                            Some(&e.to_string()),
+                        );
+                        return;
+                    }
+                    let _ = db.update_wallet_derived(
+                        &wallet_id.to_string(),
+                        &address_hex,
+                        &pubkey_hex,
+                        &derivation_path,
+                        "ready",
+                    );
+                }
+                Err(e) => {
+                    let err_msg = format!("{}", e);
+                    eprintln!(
+                        "❌ Background derivation failed for {}: {}",
+                        wallet_id, err_msg
+                    );
+                    let _ =
+                        db.update_wallet_status(&wallet_id.to_string(), "error", Some(&err_msg));
+                }
+            }
+        });
+
+        Ok(CreateKeyResponse {
+            key_metadata,
+            mnemonic: "[MNEMONIC_IN_SECURE_WORLD]".to_string(),
+        })
+    }
+
+    /// Persist the row of a wallet the TA just created.
+    /// H-C: if this insert fails the TA wallet becomes an invisible orphan
+    /// (occupies an RPMB slot, unreachable via API). A host-side
+    /// compensating delete is impossible — the TA mandates passkey
+    /// verification for removal. So: retry the (usually transient) SQLite
+    /// failure, and if it still fails, log CRITICAL with the orphan id for
+    /// ForceRemoveWallet cleanup (admin command, PR #35).
+    async fn insert_new_wallet(&self, op: &str, row: &WalletRow) -> Result<()> {
+        let wallet_id = &row.key_id;
+        let mut insert_result = self.db.insert_wallet(row);
+        for attempt in 1..=3u64 {
+            if insert_result.is_ok() {
+                break;
+            }
+            eprintln!(
+                "⚠️  {}: DB insert attempt {}/4 failed for {}: {:?}",
+                op, attempt, wallet_id, insert_result
+            );
+            tokio::time::sleep(std::time::Duration::from_millis(100 * attempt)).await;
+            insert_result = self.db.insert_wallet(row);
+        }
+        if let Err(e) = insert_result {
+            eprintln!(
+                "🔴 CRITICAL: TA wallet {} created but DB insert failed after retries — \
+                 ORPHAN in TEE storage (no DB row). Clean up via ForceRemoveWallet. \
+                 Error: {:?}",
+                wallet_id, e
+            );
+            return Err(anyhow!(
+                "{}: metadata persistence failed (TEE wallet {} orphaned, \
+                 operator notified): {}",
+                op,
+                wallet_id,
+                e
+            ));
+        }
+        Ok(())
+    }
+
+    /// Preview of the calldata summary a transaction Sign would confirm. No TEE
+    /// call: the decoder is shared with the TA, which recomputes it at sign time.
+    pub async fn describe_transaction(
+        &self,
+        req: DescribeTransactionRequest,
+    ) -> Result<DescribeTransactionResponse> {
+        let tx = req.transaction.to_call()?;
+        let summary =
+            proto::calldata::summarize_transaction(&tx, req.summary_abis.as_deref().unwrap_or(&[]));
+        let key_id = match (&req.key_id, &req.address) {
+            (_, Some(address)) => Some(
+                self.db
+                    .lookup_address(address)?
+                    .ok_or_else(|| anyhow!("Address not found: {}", address))?
+                    .key_id,
+            ),
+            (Some(key_id), None) => Some(key_id.clone()),
+            (None, None) => None,
+        };
+        let risk = match key_id {
+            Some(key_id) => self.assess_risk(&key_id, &tx).await?,
+            None => None,
+        };
+        Ok(DescribeTransactionResponse { summary, risk })
+    }
+
+    /// The key's spending profile, from its last [`risk::HISTORY_LIMIT`]
+    /// signed transactions.
+    fn spending_profile(&self, key_id: &str) -> Result<SpendingProfile> {
+        let samples: Vec<SpendSample> = self
+            .db
+            .recent_txs(key_id, risk::HISTORY_LIMIT)?
+            .iter()
+            .filter_map(|row| {
+                let raw = hex::decode(row.entry.signed_tx.trim_start_matches("0x")).ok()?;
+                let signed = proto::offline::decode_signed_legacy(&raw).ok()?;
+                SpendSample::of(&signed.transaction, row.created_at)
+            })
+            .collect();
+        Ok(SpendingProfile::from_samples(&samples))
+    }
+
+    /// Score a transaction before its confirmation. None when scoring is off
+    /// or the transaction is a deployment. A scorer that fails, or a broken
+    /// policy, asks for step-up rather than waving the request through.
+    async fn assess_risk(
+        &self,
+        key_id: &str,
+        tx: &proto::EthTransaction,
+    ) -> Result<Option<RiskAssessment>> {
+        let policy = match self.config.risk() {
+            Ok(Some(policy)) => policy,
+            Ok(None) => return Ok(None),
+            Err(e) => {
+                return Ok(Some(RiskAssessment {
+                    score: 100,
+                    reasons: vec![format!("risk policy misconfigured: {}", e)],
+                    backend: "none".to_string(),
+                    step_up_required: true,
+                    fresh_within_secs: Some(60),
+                }))
+            }
+        };
+        let sample = match SpendSample::of(tx, Utc::now().timestamp()) {
+            Some(sample) => sample,
+            None => return Ok(None),
+        };
+        let features = risk::Features::of(&self.spending_profile(key_id)?, &sample);
+        let (score, reasons) = match policy.backend.score(key_id, &sample, &features).await {
+            Ok(scored) => scored,
+            Err(e) => {
+                eprintln!("⚠️  risk scorer failed for {}: {:#}", key_id, e);
+                (100, vec![format!("risk scorer unavailable: {:#}", e)])
+            }
+        };
+        Ok(Some(RiskAssessment::new(&policy, score, reasons)))
+    }
+
+    /// A high-risk Sign needs a WebAuthn ceremony begun within the policy's
+    /// window; a stored or slow-walked assertion does not count.
+    fn require_step_up(&self, wa: Option<&WebAuthnAssertion>, risk: &RiskAssessment) -> Result<()> {
+        let fresh_secs = risk.fresh_within_secs.unwrap_or(60);
+        let wa = wa.ok_or_else(|| {
+            anyhow!(
+                "high-risk transaction (score {}: {}) needs a fresh WebAuthn ceremony",
+                risk.score,
+                risk.reasons.join("; ")
+            )
+        })?;
+        let created_at = self
+            .db
+            .challenge_created_at(&wa.challenge_id)?
+            .ok_or_else(|| anyhow!("Challenge not found or expired: {}", wa.challenge_id))?;
+        if Utc::now().timestamp() - created_at > fresh_secs {
+            return Err(anyhow!(
+                "high-risk transaction needs a WebAuthn challenge begun within the last {}s",
+                fresh_secs
+            ));
+        }
+        Ok(())
+    }
+
+    /// POST /kms/spending-analytics — what the scorer compares against.
+    pub fn spending_analytics(&self, req: SpendingAnalyticsRequest) -> Result<serde_json::Value> {
+        if !self.db.wallet_exists(&req.key_id)? {
+            return Err(anyhow!("Key not found: {}", req.key_id));
+        }
+        let mut body = self.spending_profile(&req.key_id)?.to_json();
+        body["keyId"] = serde_json::json!(req.key_id);
+        Ok(body)
+    }
+
+    /// AWS-compatible Verify against the pinned address. No TEE call.
+    pub fn verify(&self, req: VerifyRequest) -> Result<VerifyResponse> {
+        let message_type = verify::MessageType::parse(req.message_type.as_deref())?;
+        let message = verify::decode_blob("Message", &req.message)?;
+        let digest = verify::digest(&req.signing_algorithm, message_type, &message)?;
+        let signature = verify::decode_blob("Signature", &req.signature)?;
+        let (key_id, address) = self.verification_address(&req)?;
+        let address = proto::eip55::parse_address(&address)
+            .map_err(|e| anyhow!("stored address for {}: {}", key_id, e))?;
+        verify::verify_for_address(&digest, &signature, &address)?;
+        Ok(VerifyResponse {
+            key_id,
+            signature_valid: true,
+            signing_algorithm: req.signing_algorithm,
+        })
+    }
+
+    fn verification_address(&self, req: &VerifyRequest) -> Result<(String, String)> {
+        let not_found = |what: String| verify::exception("NotFoundException", what);
+        if let Some(address) = &req.address {
+            let row = self
+                .db
+                .lookup_address(address)?
+                .ok_or_else(|| not_found(format!("Address not found: {}", address)))?;
+            return Ok((row.key_id, row.address));
+        }
+        let key_id = req.key_id.as_ref().ok_or_else(|| {
+            verify::exception("ValidationException", "KeyId or Address is required")
+        })?;
+        let wallet = self
+            .db
+            .get_wallet(key_id)?
+            .ok_or_else(|| not_found(format!("Key not found: {}", key_id)))?;
+        let address = match &req.derivation_path {
+            Some(path) => self.db.address_for_key_path(key_id, path)?,
+            None => wallet.address,
+        };
+        let address = address.ok_or_else(|| {
+            verify::exception(
+                "KMSInvalidStateException",
+                "no address derived for this key/path yet; call DeriveAddress first",
+            )
+        })?;
+        Ok((key_id.clone(), address))
+    }
+
+    // ── SDK conformance suite (crate::conformance) ──
+    //
+    // Same request and response shapes as the real APIs, answered from the
+    // published test wallet. No DB, no TEE: a KeyId or Address outside the
+    // test wallet is refused by `conformance::account`.
+
+    fn conformance_assertion(
+        webauthn: Option<&WebAuthnAssertion>,
+        digest: Option<&[u8; 32]>,
+    ) -> Result<()> {
+        let assertion = webauthn.ok_or_else(|| {
+            anyhow!("WebAuthn assertion required (see /api/conformance/BeginAuthentication)")
+        })?;
+        conformance::verify_assertion(&assertion.challenge_id, &assertion.credential, digest)
+    }
+
+    pub fn conformance_begin_authentication(
+        req: webauthn::BeginAuthenticationRequest,
+    ) -> Result<webauthn::AuthenticationOptionsResponse> {
+        if req.key_id.is_some() || req.address.is_some() {
+            conformance::account(req.key_id.as_deref(), None, req.address.as_deref())?;
+        }
+        Ok(conformance::authentication_options())
+    }
+
+    pub fn conformance_derive_address(req: DeriveAddressRequest) -> Result<DeriveAddressResponse> {
+        if !Self::parse_chain(req.chain.as_deref())?.is_evm() {
+            return Err(anyhow!("Conformance accounts are EVM accounts"));
+        }
+        let account = conformance::account(Some(&req.key_id), Some(&req.derivation_path), None)?;
+        Self::conformance_assertion(req.webauthn.as_ref(), None)?;
+        Ok(DeriveAddressResponse {
+            address: account.address.to_lowercase(),
+            public_key: hex::encode(conformance::public_key(account)),
+        })
+    }
+
+    pub fn conformance_get_public_key(req: GetPublicKeyRequest) -> Result<GetPublicKeyResponse> {
+        let account = conformance::account(Some(&req.key_id), None, None)?;
+        Ok(GetPublicKeyResponse {
+            key_id: req.key_id,
+            public_key: hex::encode(conformance::public_key(account)),
+            key_usage: "SIGN_VERIFY".to_string(),
+            key_spec: "ECC_SECG_P256K1".to_string(),
+        })
+    }
+
+    pub fn conformance_sign_hash(req: SignHashRequest) -> Result<SignHashResponse> {
+        let hash = Self::validate_hash_hex(&req.hash)?;
+        let context = match req.context {
+            Some(ref c) => c.to_proto()?,
+            None => proto::SigningContext::Raw,
+        };
+        if context != proto::SigningContext::Raw || req.fee_quote_hash.is_some() {
+            return Err(anyhow!(
+                "the conformance SignHash covers raw digests only (no Context, no FeeQuoteHash)"
+            ));
+        }
+        let account = conformance::account(
+            req.key_id.as_deref(),
+            req.derivation_path.as_deref(),
+            req.address.as_deref(),
+        )?;
+        Self::conformance_assertion(req.webauthn.as_ref(), Some(&hash))?;
+        Ok(SignHashResponse {
+            signature: hex::encode(conformance::sign_hash(account, &hash)?),
+        })
+    }
+
+    pub fn conformance_sign(req: SignRequest) -> Result<SignResponse> {
+        let message = match (&req.message, &req.transaction) {
+            (Some(message), None) => message,
+            _ => {
+                return Err(anyhow!(
+                    "the conformance Sign covers Message mode only (no Transaction)"
+                ))
+            }
+        };
+        Self::validate_message(message)?;
+        let context = match req.context {
+            Some(ref c) => c.to_proto()?,
+            None => proto::SigningContext::Raw,
+        };
+        let digest = conformance::message_digest(&context, &Self::decode_message(message)?)?;
+        let account = conformance::account(
+            req.key_id.as_deref(),
+            req.derivation_path.as_deref(),
+            req.address.as_deref(),
+        )?;
+        Self::conformance_assertion(req.webauthn.as_ref(), Some(&digest))?;
+        Ok(SignResponse {
+            signature: hex::encode(conformance::sign_hash(account, &digest)?),
+            transaction_hash: "[TX_HASH_OR_MESSAGE_HASH]".to_string(),
+            summary: None,
+            risk: None,
+        })
+    }
+
+    pub async fn describe_key(&self, req: DescribeKeyRequest) -> Result<DescribeKeyResponse> {
+        println!("📝 KMS DescribeKey API called for key: {}", req.key_id);
+
+        let w = self
+            .db
+            .get_wallet(&req.key_id)?
+            .ok_or_else(|| anyhow!("Key not found: {}", req.key_id))?;
+
+        let mut key_metadata = wallet_to_metadata(&w);
+        // Issue #42: enrich with tx_log-derived last-used and lifecycle gate.
+        key_metadata.last_used_at = self.db.last_used_at(&req.key_id)?;
+        if let Some(ls) = self.db.get_lifecycle_status(&req.key_id)? {
+            key_metadata.lifecycle_status = ls;
+        }
+        let region = replication::local_region();
+        key_metadata.multi_region_configuration =
+            multi_region_configuration(&w.key_id, &self.db.list_key_regions(&w.key_id)?, &region);
+        if key_metadata.multi_region_configuration.is_some() {
+            key_metadata.multi_region = true;
+            key_metadata.arn = replication::region_arn(&region, &w.key_id);
+        }
+
+        Ok(DescribeKeyResponse { key_metadata })
+    }
+
+    pub async fn list_keys(&self, _req: ListKeysRequest) -> Result<ListKeysResponse> {
+        println!("📝 KMS ListKeys API called");
+
+        let wallets = self.db.list_wallets()?;
+        let keys = wallets
+            .iter()
+            .map(|w| KeyListEntry {
+                key_id: w.key_id.clone(),
+                key_arn: format!("arn:aws:kms:region:account:key/{}", w.key_id),
+            })
+            .collect();
+
+        Ok(ListKeysResponse { keys })
+    }
+
+    pub async fn key_status(&self, key_id: &str) -> Result<KeyStatusResponse> {
+        let w = self
+            .db
+            .get_wallet(key_id)?
+            .ok_or_else(|| anyhow!("Key not found: {}", key_id))?;
+
+        let (status, error) = if w.status.starts_with("error") {
+            ("error", w.error_msg.clone())
+        } else {
+            (w.status.as_str(), None)
+        };
+
+        // Issue #42: surface dormancy/lifecycle alongside derivation status.
+        let last_used_at = self.db.last_used_at(key_id)?;
+        let lifecycle_status = self
+            .db
+            .get_lifecycle_status(key_id)?
+            .unwrap_or_else(|| "active".to_string());
+
+        Ok(KeyStatusResponse {
+            key_id: key_id.to_string(),
+            status: status.to_string(),
+            address: w.address,
+            public_key: w.public_key,
+            derivation_path: w.derivation_path,
+            error,
+            last_used_at,
+            lifecycle_status,
+        })
+    }
+
+    pub fn queue_status(&self) -> QueueStatusResponse {
+        let depth = self.tee.pending_count();
+        let (cb_open, cb_failures) = self.tee.circuit_breaker_status();
+        QueueStatusResponse {
+            queue_depth: depth,
+            batch_depth: Some(self.tee.pending_batch_count()),
+            estimated_wait_seconds: depth as u64 * TEE_OP_ESTIMATE_SECS,
+            circuit_breaker_open: Some(cb_open),
+            consecutive_failures: Some(cb_failures),
+        }
+    }
+
+    pub async fn read_rollback_counter(&self) -> Result<u64> {
+        self.tee.read_rollback_counter().await
+    }
+
+    /// Issue #37 — produce a remote-attestation evidence blob bound to `nonce`.
+    pub async fn get_attestation(&self, nonce: Vec<u8>) -> Result<proto::GetAttestationOutput> {
+        self.tee.get_attestation(nonce).await
+    }
+
+    /// Monthly proof-of-activity: per-op counts from tx_log, bound into a TEE
+    /// attestation with nonce = SHA-256(statement). Anyone holding the device
+    /// attestation key can verify an archived statement without trusting the CA
+    /// operator's DB after the fact.
+    pub async fn activity_statement(
+        &self,
+        key_id: &str,
+        month: &str,
+    ) -> Result<ActivityStatementResponse> {
+        use sha2::{Digest, Sha256};
+        let wallet = self
+            .db
+            .get_wallet(key_id)?
+            .ok_or_else(|| anyhow!("Key not found: {}", key_id))?;
+        let now = Utc::now();
+        let (start, end) = statement_period(month, now)?;
+        let statement = ActivityStatement {
+            schema: "airaccount.statement.v1",
+            key_id,
+            address: wallet.address,
+            month,
+            period_start: start.to_rfc3339(),
+            period_end: end.to_rfc3339(),
+            operations: self.db.activity_for_month(key_id, month)?,
+            generated_at: now.to_rfc3339(),
+        };
+        let statement = serde_json::to_string(&statement)?;
+        let digest = Sha256::digest(statement.as_bytes());
+        let evidence = self.get_attestation(digest.to_vec()).await?;
+        Ok(ActivityStatementResponse {
+            statement,
+            digest: hex::encode(digest),
+            attestation: AttestationResponse::from_evidence(evidence),
+        })
+    }
+
+    /// Bulk onboarding (provisioning.rs): up to MAX_BATCH wallets for a
+    /// tenant, one after another in the TEE's batch lane, and a report of
+    /// their addresses bound into one attestation. A batch that fails midway
+    /// reports, and keeps, the wallets it made.
+    pub async fn provision_wallets(
+        &self,
+        req: ProvisionWalletsRequest,
+    ) -> Result<ProvisionWalletsResponse> {
+        use provisioning::MAX_BATCH;
+
+        let limits = self
+            .provisioning
+            .as_ref()
+            .map_err(|e| anyhow!("bulk provisioning config invalid: {}", e))?;
+        provisioning::validate_tenant(&req.tenant)?;
+        provisioning::validate_tags(&req.tags)?;
+        let csv = match req.format.as_deref() {
+            None | Some("json") => false,
+            Some("csv") => true,
+            Some(other) => return Err(anyhow!("format must be json or csv, not {:?}", other)),
+        };
+        let passkeys = match (&req.passkey_public_keys, &req.passkey_public_key, req.count) {
+            (Some(keys), None, None) => keys.clone(),
+            (None, Some(key), Some(count)) if count <= MAX_BATCH => vec![key.clone(); count],
+            (None, Some(_), Some(count)) => {
+                return Err(anyhow!(
+                    "a batch is at most {} wallets, got {}",
+                    MAX_BATCH,
+                    count
+                ))
+            }
+            _ => {
+                return Err(anyhow!(
+                    "give either passkeyPublicKeys, or passkeyPublicKey with count"
+                ))
+            }
+        };
+        if passkeys.is_empty() || passkeys.len() > MAX_BATCH {
+            return Err(anyhow!(
+                "a batch is 1-{} wallets, got {}",
+                MAX_BATCH,
+                passkeys.len()
+            ));
+        }
+        let passkeys = passkeys
+            .iter()
+            .map(|pk| parse_passkey_pubkey(pk))
+            .collect::<Result<Vec<_>>>()?;
+
+        let _batch = self.provisioning_batch.lock().await;
+        let quota = self
+            .db
+            .tenant_quota(&req.tenant)?
+            .unwrap_or(limits.default_quota);
+        let held = self.db.count_tenant_wallets(&req.tenant, 0)?;
+        let last_hour = self
+            .db
+            .count_tenant_wallets(&req.tenant, Utc::now().timestamp() - 3600)?;
+        limits.admit(quota, held, last_hour, passkeys.len())?;
+
+        let batch_id = Uuid::new_v4().to_string();
+        println!(
+            "📝 ProvisionWallets: {} wallet(s) for tenant {} (batch {})",
+            passkeys.len(),
+            req.tenant,
+            batch_id
+        );
+        let mut wallets = Vec::new();
+        let mut incomplete = None;
+        for passkey in &passkeys {
+            match self.provision_wallet(&req, &batch_id, passkey).await {
+                Ok(wallet) => wallets.push(wallet),
+                Err(e) => {
+                    eprintln!(
+                        "❌ ProvisionWallets: batch {} stopped after {} of {}: {:#}",
+                        batch_id,
+                        wallets.len(),
+                        passkeys.len(),
+                        e
+                    );
+                    incomplete = Some(format!("{:#}", e));
+                    break;
+                }
+            }
+        }
+        if wallets.is_empty() {
+            return Err(anyhow!(
+                "provisioning batch {} created no wallet: {}",
+                batch_id,
+                incomplete.unwrap_or_default()
+            ));
+        }
+
+        let now = Utc::now();
+        let report = ProvisioningReport {
+            schema: provisioning::REPORT_SCHEMA.to_string(),
+            batch_id: batch_id.clone(),
+            tenant: req.tenant.clone(),
+            tags: req.tags.clone(),
+            requested: passkeys.len(),
+            wallets,
+            incomplete,
+            generated_at: now.to_rfc3339(),
+        };
+        let report_json = report.to_json()?;
+        let digest = provisioning::report_digest(&report_json);
+        let attestation = match self.get_attestation(digest.to_vec()).await {
+            Ok(evidence) => Some(AttestationResponse::from_evidence(evidence)),
+            Err(e) => {
+                eprintln!(
+                    "⚠️  ProvisionWallets: no attestation for batch {}: {:#}",
+                    batch_id, e
+                );
+                None
+            }
+        };
+        self.db.insert_provisioning_batch(&ProvisioningBatchRow {
+            batch_id: batch_id.clone(),
+            tenant_id: req.tenant.clone(),
+            report: report_json.clone(),
+            attestation: serde_json::to_string(&attestation)?,
+            created_at: now.timestamp(),
+        })?;
+        Ok(ProvisionWalletsResponse {
+            batch_id,
+            csv: if csv { Some(report.to_csv()) } else { None },
+            report: report_json,
+            digest: hex::encode(digest),
+            attestation,
+        })
+    }
+
+    /// One wallet of a provisioning batch: CreateWallet, its row and tenant,
+    /// then the address — derived here rather than in the background so the
+    /// report can carry it. A failed derivation leaves the wallet in status
+    /// `error`, as it would after CreateKey.
+    async fn provision_wallet(
+        &self,
+        req: &ProvisionWalletsRequest,
+        batch_id: &str,
+        passkey: &[u8],
+    ) -> Result<ProvisionedWallet> {
+        let wallet_id = self.tee.create_wallet_batch(passkey).await?;
+        let key_id = wallet_id.to_string();
+        let passkey_hex = format!("0x{}", hex::encode(passkey));
+        let now = Utc::now();
+        let row = WalletRow {
+            key_id: key_id.clone(),
+            address: None,
+            public_key: None,
+            derivation_path: None,
+            description: req
+                .description
+                .clone()
+                .unwrap_or_else(|| format!("provisioned for {}", req.tenant)),
+            key_usage: "SIGN_VERIFY".to_string(),
+            key_spec: "ECC_SECG_P256K1".to_string(),
+            origin: "AWS_KMS".to_string(),
+            passkey_pubkey: Some(passkey_hex.clone()),
+            credential_id: None,
+            sign_count: 0,
+            status: "deriving".to_string(),
+            error_msg: None,
+            created_at: now.to_rfc3339(),
+        };
+        self.insert_new_wallet("ProvisionWallets", &row).await?;
+        self.db.insert_tenant_wallet(&TenantWalletRow {
+            key_id: key_id.clone(),
+            tenant_id: req.tenant.clone(),
+            tags: req.tags.clone(),
+            batch_id: batch_id.to_string(),
+            created_at: now.timestamp(),
+        })?;
+
+        let derived = self.tee.derive_address_auto(wallet_id).await.and_then(
+            |(_, address, public_key, derivation_path)| {
+                let address_hex = format!("0x{}", hex::encode(address));
+                let pubkey_hex = format!("0x{}", hex::encode(&public_key));
+                enforce_key_pin(
+                    &self.db,
+                    &key_id,
+                    &derivation_path,
+                    &address_hex,
+                    Some(&pubkey_hex),
+                )?;
+                Ok((address, address_hex, pubkey_hex, derivation_path))
+            },
+        );
+        let (address, address_hex, pubkey_hex, derivation_path) = match derived {
+            Ok(d) => d,
+            Err(e) => {
+                let _ = self
+                    .db
+                    .update_wallet_status(&key_id, "error", Some(&e.to_string()));
+                return Err(e);
+            }
+        };
+        self.db.update_wallet_derived(
+            &key_id,
+            &address_hex,
+            &pubkey_hex,
+            &derivation_path,
+            "ready",
+        )?;
+        Ok(ProvisionedWallet {
+            key_id,
+            address: proto::eip55::to_checksum_address(&address),
+            public_key: pubkey_hex,
+            derivation_path,
+            passkey_public_key: passkey_hex,
+        })
+    }
+
+    pub async fn change_passkey(&self, req: ChangePasskeyRequest) -> Result<ChangePasskeyResponse> {
+        println!("📝 KMS ChangePasskey API called for key: {}", req.key_id);
+
+        if !self.db.wallet_exists(&req.key_id)? {
+            return Err(anyhow!("Key not found: {}", req.key_id));
+        }
+
+        // Decode public key from hex
+        let pubkey_hex = req.passkey_public_key.trim_start_matches("0x");
+        let pubkey_bytes = hex::decode(pubkey_hex)
+            .map_err(|e| anyhow!("Invalid passkey public key hex: {}", e))?;
+
+        if pubkey_bytes.len() != 65 || pubkey_bytes[0] != 0x04 {
+            return Err(anyhow!(
+                "PassKey public key must be 65 bytes uncompressed (0x04 || x || y), got {} bytes",
+                pubkey_bytes.len()
+            ));
+        }
+
+        // Resolve current passkey assertion (WebAuthn or legacy hex)
+        let passkey_assertion = self
+            .resolve_passkey_assertion_strict(
+                &req.key_id,
+                req.passkey.as_ref(),
+                req.webauthn.as_ref(),
+                false, // #110: nonce-only op — TA enforces challenge==nonce; host stays strict
+            )
+            .await?;
+
+        // Change passkey in TEE secure storage (TA verifies current passkey first)
+        let wallet_uuid = uuid::Uuid::parse_str(&req.key_id)?;
+        self.tee
+            .register_passkey_ta(wallet_uuid, &pubkey_bytes, passkey_assertion)
+            .await?;
+
+        self.record_passkey_change(&req.key_id, &format!("0x{}", pubkey_hex), "ChangePasskey")
+            .await?;
+        self.audit(&req.key_id, "passkey_changed", None);
+
+        Ok(ChangePasskeyResponse {
+            key_id: req.key_id,
+            changed: true,
+        })
+    }
+
+    /// H-B: the TA has now committed the NEW passkey. If the DB update
+    /// below is lost, the DB keeps the OLD pubkey and every subsequent
+    /// WebAuthn verification for this wallet fails against the wrong key —
+    /// the wallet is effectively locked out. Retry with backoff and log
+    /// CRITICAL with the exact recovery SQL if all retries fail.
+    async fn record_passkey_change(&self, key_id: &str, new_pk: &str, op: &str) -> Result<()> {
+        let mut db_result = Ok(());
+        for attempt in 1..=3 {
+            db_result = self
+                .db
+                .update_wallet_passkey(key_id, new_pk, None)
+                .map(|_| ());
+            if db_result.is_ok() {
+                break;
+            }
+            eprintln!(
+                "⚠️  {}: DB update attempt {}/3 failed for key {}: {:?}",
+                op, attempt, key_id, db_result
+            );
+            tokio::time::sleep(std::time::Duration::from_millis(100 * attempt)).await;
+        }
+        if let Err(e) = db_result {
+            eprintln!(
+                "🔴 CRITICAL: TA passkey changed but DB update FAILED for key {} — \
+                 WebAuthn for this wallet will verify against a STALE pubkey. \
+                 Manual recovery: UPDATE wallets SET passkey_pubkey='{}' WHERE key_id='{}'; \
+                 error: {:?}",
+                key_id, new_pk, key_id, e
+            );
+            return Err(anyhow!(
+                "Passkey changed in TEE but metadata update failed — contact operator \
+                 (wallet may not authenticate until DB is repaired): {}",
+                e
+            ));
+        }
+        Ok(())
+    }
+
+    /// Parse API-layer PasskeyAssertion (hex strings) into proto::PasskeyAssertion (bytes).
+    /// Returns None if no assertion provided — TA will decide whether to allow or reject.
+    fn parse_passkey_assertion(
+        passkey: Option<&PasskeyAssertion>,
+    ) -> Result<Option<proto::PasskeyAssertion>> {
+        let assertion = match passkey {
+            Some(a) => a,
+            None => return Ok(None),
+        };
+
+        let auth_data = hex::decode(assertion.authenticator_data.trim_start_matches("0x"))
+            .map_err(|e| anyhow!("Invalid authenticator_data hex: {}", e))?;
+        let cdh_bytes = hex::decode(assertion.client_data_hash.trim_start_matches("0x"))
+            .map_err(|e| anyhow!("Invalid client_data_hash hex: {}", e))?;
+        if cdh_bytes.len() != 32 {
+            return Err(anyhow!("client_data_hash must be 32 bytes"));
+        }
+        let mut client_data_hash = [0u8; 32];
+        client_data_hash.copy_from_slice(&cdh_bytes);
+
+        let sig_bytes = hex::decode(assertion.signature.trim_start_matches("0x"))
+            .map_err(|e| anyhow!("Invalid signature hex: {}", e))?;
+
+        let (signature_r, signature_s) = if sig_bytes.len() == 64 {
+            let mut r = [0u8; 32];
+            let mut s = [0u8; 32];
+            r.copy_from_slice(&sig_bytes[..32]);
+            s.copy_from_slice(&sig_bytes[32..]);
+            (r, s)
+        } else {
+            parse_der_signature(&sig_bytes)?
+        };
+
+        Ok(Some(proto::PasskeyAssertion {
+            authenticator_data: auth_data,
+            client_data_hash,
+            signature_r,
+            signature_s,
+            // Legacy hex path carries no clientDataJSON. The TA treats this as the
+            // transition/legacy case (issue #49): no challenge binding. This path is
+            // already DEPRECATED + gated elsewhere; the WebAuthn ceremony path
+            // (verify_authentication_response) is the one that gets challenge binding.
+            client_data_json: None,
+        }))
+    }
+
+    /// CA-side passkey pre-verification (mirrors TA logic).
+    /// If pubkey_hex and assertion are both present, verify ECDSA P-256 signature
+    /// before forwarding to TA queue. Returns Ok(()) on success or missing data.
+    ///
+    /// Verification: ECDSA_verify(pubkey, SHA256(auth_data || cdh), signature)
+    /// Uses Verifier::verify(msg, sig) which internally hashes msg with SHA-256.
+    fn verify_passkey_ca(pubkey_hex: &str, assertion: &proto::PasskeyAssertion) -> Result<()> {
+        let pk_hex = pubkey_hex.trim_start_matches("0x");
+        let pk_bytes =
+            hex::decode(pk_hex).map_err(|e| anyhow!("Invalid stored passkey pubkey hex: {}", e))?;
+
+        let encoded_point = EncodedPoint::from_bytes(&pk_bytes)
+            .map_err(|e| anyhow!("Invalid passkey public key point: {:?}", e))?;
+        let verifying_key = VerifyingKey::from_encoded_point(&encoded_point)
+            .map_err(|e| anyhow!("Failed to parse passkey verifying key: {:?}", e))?;
+
+        // Concatenate raw message: auth_data || client_data_hash
+        // Verifier::verify() internally computes SHA-256(msg) before ECDSA math,
+        // matching TA's verify_digest(SHA256(auth_data || cdh), sig).
+        let mut msg = Vec::with_capacity(assertion.authenticator_data.len() + 32);
+        msg.extend_from_slice(&assertion.authenticator_data);
+        msg.extend_from_slice(&assertion.client_data_hash);
+
+        let signature = Signature::from_scalars(assertion.signature_r, assertion.signature_s)
+            .map_err(|e| anyhow!("Invalid passkey signature: {:?}", e))?;
+
+        verifying_key
+            .verify(&msg, &signature)
+            .map_err(|_| anyhow!("PassKey verification failed (CA pre-check)"))?;
+
+        Ok(())
+    }
+
+    /// Pre-verify passkey at CA level if metadata has pubkey and assertion is present.
+    /// Rejects bad signatures before they reach TA queue.
+    async fn pre_verify_passkey(
+        &self,
+        key_id: &str,
+        assertion: &Option<proto::PasskeyAssertion>,
+    ) -> Result<()> {
+        let pubkey_hex = self.db.get_wallet(key_id)?.and_then(|w| w.passkey_pubkey);
+
+        if let (Some(ref pk), Some(ref a)) = (pubkey_hex, assertion) {
+            Self::verify_passkey_ca(pk, a)?;
+        }
+        Ok(())
+    }
+
+    /// Resolve passkey assertion from either legacy hex format or WebAuthn ceremony.
+    /// WebAuthn path: consume challenge, verify assertion, update sign_count, return proto assertion.
+    /// Legacy path: parse hex assertion + CA pre-verify.
+    /// Returns None if neither is provided.
+    async fn resolve_passkey_assertion(
+        &self,
+        key_id: &str,
+        raw: Option<&PasskeyAssertion>,
+        wa: Option<&WebAuthnAssertion>,
+        // Issue #110: true when this op is re-verified inside the TA (signing/
+        // mutating paths) → host delegates the challenge-value binding to the TA
+        // (accepts a payload-commitment challenge, not just the bare nonce). false
+        // for host-authoritative paths → keep strict `challenge == nonce`.
+        delegate_challenge_to_ta: bool,
+    ) -> Result<Option<proto::PasskeyAssertion>> {
+        if let Some(wa) = wa {
+            // WebAuthn ceremony path
+            let mut span = otel::Span::start(
+                "webauthn.assertion.verify",
+                otel::SpanKind::Internal,
+                RequestContext::current().and_then(|c| c.trace),
+            );
+            let verified = self.verify_webauthn_assertion(key_id, wa, delegate_challenge_to_ta);
+            span.record_result(&verified);
+            verified.map(Some)
+        } else if raw.is_some() {
+            // Legacy hex path: DEPRECATED — raw ECDSA bytes with no challenge or origin binding.
+            // Vulnerable to replay if an attacker captures a valid assertion.
+            // Retained for backward compatibility only; new clients should use WebAuthn ceremony.
+            eprintln!("⚠️  DEPRECATED: legacy passkey assertion (no challenge binding) for key_id={}. Migrate to WebAuthn ceremony.", key_id);
+            let assertion = Self::parse_passkey_assertion(raw)?;
+            self.pre_verify_passkey(key_id, &assertion).await?;
+            Ok(assertion)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// The WebAuthn ceremony half of [`Self::resolve_passkey_assertion`]:
+    /// consume the challenge, verify the assertion, update sign_count.
+    fn verify_webauthn_assertion(
+        &self,
+        key_id: &str,
+        wa: &WebAuthnAssertion,
+        delegate_challenge_to_ta: bool,
+    ) -> Result<proto::PasskeyAssertion> {
+        let challenge_row = self
+            .db
+            .consume_challenge(&wa.challenge_id)?
+            .ok_or_else(|| anyhow!("Challenge not found or expired: {}", wa.challenge_id))?;
+
+        // Reject operation-specific challenges (e.g. "grant-session") to prevent
+        // cross-purpose replay. This resolver is for generic authentication only.
+        if challenge_row.purpose != "authentication" {
+            return Err(anyhow!(
+                "Challenge purpose '{}' cannot be used for this operation",
+                challenge_row.purpose
+            ));
+        }
+
+        // challenge must be bound to this key
+        if let Some(ref bound_key) = challenge_row.key_id {
+            if bound_key != key_id {
+                return Err(anyhow!("Challenge bound to different key"));
+            }
+        }
+
+        let w = self
+            .db
+            .get_wallet(key_id)?
+            .ok_or_else(|| anyhow!("Key not found: {}", key_id))?;
+
+        let pubkey_hex = w
+            .passkey_pubkey
+            .ok_or_else(|| anyhow!("Wallet has no passkey public key"))?;
+        let pk_bytes = hex::decode(pubkey_hex.trim_start_matches("0x"))
+            .map_err(|e| anyhow!("Invalid stored passkey hex: {}", e))?;
+
+        let sign_count = w.sign_count;
+        let verified = self.with_passkey_lockout(key_id, || {
+            webauthn::verify_authentication_response(
+                &wa.credential,
+                &challenge_row.challenge,
+                &self.expected_origins,
+                &challenge_row.rp_id,
+                &pk_bytes,
+                sign_count,
+                delegate_challenge_to_ta,
+            )
+        })?;
+
+        // Update sign_count in DB
+        let _ = self
+            .db
+            .update_wallet_sign_count(key_id, verified.new_counter);
+
+        Ok(verified.proto_assertion)
+    }
+
+    /// P0-2: strict resolver for the signing / mutating endpoints
+    /// (Sign, SignHash, DeriveAddress, DeleteKey, ChangePasskey).
+    ///
+    /// Two hardenings over `resolve_passkey_assertion`:
+    /// 1. The legacy raw-hex path carries NO challenge binding — a captured
+    ///    assertion is replayable forever. It is rejected here unless
+    ///    `KMS_ALLOW_LEGACY_PASSKEY=1` is set (test environments only).
+    /// 2. If the wallet has a passkey bound, an assertion is REQUIRED at the
+    ///    CA layer (defence in depth). Previously we passed `None` through
+    ///    and relied on the TA alone; every agent/p256/grant endpoint already
+    ///    enforces presence host-side — the core KMS paths now match.
+    ///
+    /// Issue #42: host-side gate that rejects signing on a frozen (dormant) key
+    /// before any TEE call. Returns Err("key is frozen") when the wallet's
+    /// lifecycle_status is 'frozen'. Unknown/missing keys and 'active' keys pass
+    /// through (callers do their own existence checks). This is a soft CA-layer
+    /// gate, not a TEE-enforced lock — the private key material is untouched.
+    ///
+    /// TOCTOU note: this check and the subsequent TEE call are not atomic with the
+    /// background dormant-sweep. The success tx_log row (which advances
+    /// last_used_at) is written only AFTER sign() returns, so during an in-flight
+    /// sign the sweep can still read a stale last_used_at, pass its own dormancy
+    /// test, and freeze the key after this precheck but before the signature
+    /// completes. This race is accepted by design and deliberately not locked
+    /// against: (a) freeze is a dormancy re-verification gate, not key-security
+    /// enforcement, and this sign is already authorized by a live WebAuthn
+    /// ceremony; (b) the TEE has already produced the signature by the time any
+    /// post-check could run, so it cannot be rolled back anyway. The only
+    /// observable effect of losing the race is that the key ends up frozen right
+    /// after this one signature, and the next operation needs an UnfreezeKey.
+    fn ensure_not_frozen(&self, key_id: &str) -> Result<()> {
+        if let Some(status) = self.db.get_lifecycle_status(key_id)? {
+            if status == "frozen" {
+                return Err(anyhow!("key is frozen"));
+            }
+        }
+        Ok(())
+    }
+
+    /// The backend holding a wallet's key (signer.rs): the TA unless the
+    /// wallet was registered on an HSM.
+    fn signer_for(&self, key_id: &str) -> Result<&dyn Signer> {
+        match self.db.wallet_signer(key_id)? {
+            None => Ok(&self.tee),
+            Some(row) if row.backend == signer::PKCS11 => match &self.pkcs11 {
+                Some(hsm) => Ok(hsm),
+                None => Err(anyhow!(
+                    "key {} is held by a PKCS#11 token, but KMS_PKCS11_MODULE is not configured",
+                    key_id
+                )),
+            },
+            Some(row) => Err(anyhow!(
+                "key {} has unknown signer backend {:?}",
+                key_id,
+                row.backend
+            )),
+        }
+    }
+
+    /// Best-effort account audit entry: the state change it describes has already
+    /// committed, so a failed audit write is logged rather than failing the request.
+    fn audit(&self, account: &str, event: &str, detail: Option<&str>) {
+        let correlation_id = RequestContext::current().map(|c| c.correlation_id);
+        if let Err(e) =
+            self.db
+                .record_account_event(account, event, detail, correlation_id.as_deref())
+        {
+            eprintln!("⚠️  audit write failed ({} {}): {}", account, event, e);
+        }
+    }
+
+    /// Run a passkey assertion check for `key_id` under the failure lockout
+    /// (passkey_lockout.rs): refused while locked, counted when it fails.
+    fn with_passkey_lockout<T>(
+        &self,
+        key_id: &str,
+        verify: impl FnOnce() -> Result<T>,
+    ) -> Result<T> {
+        if let Err(left) = self.passkey_lockout.check(key_id) {
+            return Err(anyhow!(
+                "passkey locked after repeated failures; retry in {}s",
+                left.as_secs().max(1)
+            ));
+        }
+        match verify() {
+            Ok(verified) => {
+                self.passkey_lockout.record_success(key_id);
+                Ok(verified)
+            }
+            Err(e) => {
+                if let Some(lock) = self.passkey_lockout.record_failure(key_id) {
+                    eprintln!("🔒 Passkey lockout: {} for {}s", key_id, lock.as_secs());
+                    let detail = format!("{}s after: {}", lock.as_secs(), e);
+                    self.audit(key_id, "passkey_locked", Some(&detail));
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Resolve a caller-supplied `account` to a wallet key_id. Accepts either the key_id
+    /// (UUID) directly or a wallet **address** — the latter resolved via address_index, the
+    /// same way the Sign/SignHash endpoints accept an address. This lets DVT (which has the
+    /// userOp sender address) and the SDK (which uses the account Address) call the contact /
+    /// confirm-verify endpoints without tracking the KMS UUID. Falls back to the original
+    /// string when neither resolves, so downstream not-found handling is unchanged.
+    fn resolve_account_key_id(&self, account: &str) -> Result<String> {
+        if self.db.wallet_exists(account)? {
+            return Ok(account.to_string());
+        }
+        if let Some(row) = self.db.lookup_address(account)? {
+            return Ok(row.key_id);
+        }
+        Ok(account.to_string())
+    }
+
+    /// Wallets with no passkey bound (legacy/pre-passkey wallets) still pass
+    /// `None` through; the TA applies its own policy for those.
+    async fn resolve_passkey_assertion_strict(
+        &self,
+        key_id: &str,
+        raw: Option<&PasskeyAssertion>,
+        wa: Option<&WebAuthnAssertion>,
+        // #110: true ONLY for ops the TA re-binds with a payload digest (Sign/
+        // SignHash). The host then accepts a payload-commitment challenge and lets
+        // the TA bind it. For nonce-only ops (DeriveAddress/DeleteKey/ChangePasskey
+        // — TA enforces challenge==nonce) and HOST-ONLY ops that never reach the TA
+        // (UnfreezeKey — flips lifecycle in the DB, no TA call), pass false so the
+        // host keeps the authoritative `challenge == nonce` check. ⚠️ Passing true
+        // for a host-only op (UnfreezeKey) would drop challenge binding entirely
+        // (host skips it, TA never sees it) → replay of a captured assertion.
+        delegate_challenge_to_ta: bool,
+    ) -> Result<Option<proto::PasskeyAssertion>> {
+        if raw.is_some() && wa.is_none() {
+            let legacy_allowed =
+                std::env::var("KMS_ALLOW_LEGACY_PASSKEY").ok().as_deref() == Some("1");
+            if !legacy_allowed {
+                return Err(anyhow!(
+                    "Legacy raw passkey assertions are not accepted for signing/mutating \
+                     operations (no challenge binding — replayable). Use the WebAuthn \
+                     ceremony via /webauthn/begin-authentication. \
+                     (KMS_ALLOW_LEGACY_PASSKEY=1 re-enables it for test environments only.)"
+                ));
+            }
+            eprintln!(
+                "⚠️  KMS_ALLOW_LEGACY_PASSKEY=1: accepting replayable legacy assertion \
+                 for key_id={} — NEVER enable this in production",
+                key_id
+            );
+        }
+
+        // #110: delegate decision is per-op (see param doc) — true only for the
+        // payload-commitment signers; false keeps the host as the authoritative
+        // challenge==nonce check for nonce-only / host-only ops.
+        let resolved = self
+            .resolve_passkey_assertion(key_id, raw, wa, delegate_challenge_to_ta)
+            .await?;
+
+        if resolved.is_none() {
+            // No assertion supplied at all. Only acceptable when the wallet
+            // genuinely has no passkey bound.
+            if let Some(w) = self.db.get_wallet(key_id)? {
+                let has_passkey = w
+                    .passkey_pubkey
+                    .as_deref()
+                    .map(|s| !s.is_empty())
+                    .unwrap_or(false);
+                if has_passkey {
+                    return Err(anyhow!(
+                        "Passkey authorization required: key {} has a passkey bound. \
+                         Provide a WebAuthn assertion (begin-authentication ceremony).",
+                        key_id
+                    ));
+                }
+            }
+        }
+
+        Ok(resolved)
+    }
+
+    /// WebAuthn assertion resolver with operation-specific purpose check.
+    /// Ensures the challenge was created specifically for the given purpose
+    /// (e.g., "grant-session"), preventing cross-operation challenge replay.
+    async fn resolve_grant_passkey_assertion(
+        &self,
+        key_id: &str,
+        wa: &WebAuthnAssertion,
+        required_purpose: &str,
+    ) -> Result<proto::PasskeyAssertion> {
+        let challenge_row = self
+            .db
+            .consume_challenge(&wa.challenge_id)?
+            .ok_or_else(|| anyhow!("Challenge not found or expired: {}", wa.challenge_id))?;
+
+        if challenge_row.purpose != required_purpose {
+            return Err(anyhow!(
+                "Challenge purpose '{}' is not valid for this operation (expected '{}')",
+                challenge_row.purpose,
+                required_purpose
+            ));
+        }
+
+        if let Some(ref bound_key) = challenge_row.key_id {
+            if bound_key != key_id {
+                return Err(anyhow!("Challenge bound to different key"));
+            }
+        }
+
+        let w = self
+            .db
+            .get_wallet(key_id)?
+            .ok_or_else(|| anyhow!("Key not found: {}", key_id))?;
+        let pubkey_hex = w
+            .passkey_pubkey
+            .ok_or_else(|| anyhow!("Wallet has no passkey public key"))?;
+        let pk_bytes = hex::decode(pubkey_hex.trim_start_matches("0x"))
+            .map_err(|e| anyhow!("Invalid stored passkey hex: {}", e))?;
+
+        // #112: grant-session now uses a TA-issued nonce (begin_grant_session_auth →
+        // GetChallenge), and the TA re-binds it at sign time (sign_grant_session /
+        // sign_p256_grant_session call verify_passkey_for_wallet with Some(final_hash)
+        // → payload commitment). So the host delegates the challenge-value check to
+        // the TA (true) — exactly like the regular signing path — accepting a
+        // payload-commitment challenge in strict, and the bare nonce in transition.
+        // (Host still verifies signature + origin + rpId + one-time challenge_id.)
+        let sign_count = w.sign_count;
+        let verified = self.with_passkey_lockout(key_id, || {
+            webauthn::verify_authentication_response(
+                &wa.credential,
+                &challenge_row.challenge,
+                &self.expected_origins,
+                &challenge_row.rp_id,
+                &pk_bytes,
+                sign_count,
+                true,
+            )
+        })?;
+
+        let _ = self
+            .db
+            .update_wallet_sign_count(key_id, verified.new_counter);
+
+        // #112: DO NOT strip client_data_json anymore. The TA now holds the nonce
+        // (GetChallenge) and is the authoritative binder — forward the assertion so
+        // the TA verifies challenge↔nonce↔payload. (Pre-#112 the grant challenge was
+        // host-random, absent from the TA pending table, so we had to strip; that is
+        // no longer the case.)
+        Ok(verified.proto_assertion)
+    }
+
+    pub async fn derive_address(&self, req: DeriveAddressRequest) -> Result<DeriveAddressResponse> {
+        println!("📝 KMS DeriveAddress API called for key: {}", req.key_id);
+
+        // CA-side validation before TA call
+        let wallet_uuid = Self::validate_key_id(&req.key_id)?;
+        let chain = Self::parse_chain(req.chain.as_deref())?;
+        Self::validate_chain_path(&req.derivation_path, chain)?;
+
+        if !self.db.wallet_exists(&req.key_id)? {
+            return Err(anyhow!("Key not found: {}", req.key_id));
+        }
+        // Issue #42: reject dormant/frozen keys before any TEE call.
+        self.ensure_not_frozen(&req.key_id)?;
+        let signer = self.signer_for(&req.key_id)?;
+        if !chain.is_evm() && signer.backend() != "tee" {
+            return Err(anyhow!(
+                "key {} is held by a {} token, which derives EVM addresses only",
+                req.key_id,
+                signer.backend()
+            ));
+        }
+        let passkey_assertion = self
+            .resolve_passkey_assertion_strict(
+                &req.key_id,
+                req.passkey.as_ref(),
+                req.webauthn.as_ref(),
+                false, // #110: nonce-only op — TA enforces challenge==nonce; host stays strict
+            )
+            .await?;
+        let address = if chain.is_evm() {
+            let address_bytes = signer
+                .derive_address(wallet_uuid, &req.derivation_path, passkey_assertion)
+                .await?;
+            format!("0x{}", hex::encode(address_bytes))
+        } else {
+            self.tee
+                .derive_chain_address(wallet_uuid, &req.derivation_path, chain, passkey_assertion)
+                .await?
+        };
+        enforce_key_pin(&self.db, &req.key_id, &req.derivation_path, &address, None)?;
+
+        Ok(DeriveAddressResponse {
+            address,
+            public_key: "[PUBKEY_FROM_TA]".to_string(),
+        })
+    }
+
+    pub async fn sign(&self, req: SignRequest) -> Result<SignResponse> {
+        // CA-side validation: message size
+        if let Some(ref msg) = req.message {
+            Self::validate_message(msg)?;
+        }
+        let context = match req.context {
+            Some(ref c) => c.to_proto()?,
+            None => proto::SigningContext::Raw,
+        };
+
+        // Resolve wallet_id and derivation_path (support both Address and KeyId modes)
+        let (wallet_uuid, derivation_path) = if let Some(ref address) = req.address {
+            println!("📝 KMS Sign API called with Address: {}", address);
+
+            let row = self
+                .db
+                .lookup_address(address)?
+                .ok_or_else(|| anyhow!("Address not found: {}", address))?;
+
+            (Uuid::parse_str(&row.key_id)?, row.derivation_path)
+        } else if let (Some(ref key_id), Some(ref path)) =
+            (req.key_id.as_ref(), req.derivation_path.as_ref())
+        {
+            println!(
+                "📝 KMS Sign API called with KeyId: {}, Path: {}",
+                key_id, path
+            );
+
+            if !self.db.wallet_exists(key_id)? {
+                return Err(anyhow!("Key not found: {}", key_id));
+            }
+
+            (Uuid::parse_str(key_id)?, path.to_string())
+        } else {
+            return Err(anyhow!(
+                "Must provide either Address or (KeyId + DerivationPath)"
+            ));
+        };
+
+        // Resolve passkey assertion (WebAuthn ceremony or legacy hex)
+        let key_id_str = wallet_uuid.to_string();
+        // Issue #42: reject dormant/frozen keys before any TEE call.
+        self.ensure_not_frozen(&key_id_str)?;
+        // The owner revoked this device: send the client to a replica.
+        if !replication::local_can_sign(&self.db.list_wallet_devices(&key_id_str)?) {
+            const REVOKED: &str = "this device was removed from the key's replica set";
+            return Err(match self.sign_failover(&key_id_str, REVOKED)? {
+                Some(failover) => failover.into(),
+                None => anyhow!("{} for {} and no replica is healthy", REVOKED, key_id_str),
+            });
+        }
+        // Scored before the passkey is checked, so a high-risk request cannot
+        // ride on a challenge begun long before the user saw it.
+        let risk = match &req.transaction {
+            Some(transaction) => {
+                self.assess_risk(&key_id_str, &transaction.to_call()?)
+                    .await?
+            }
+            None => None,
+        };
+        if let Some(risk) = risk.as_ref().filter(|r| r.step_up_required) {
+            self.require_step_up(req.webauthn.as_ref(), risk)?;
+        }
+        let signer = self.signer_for(&key_id_str)?;
+        let passkey_assertion = self
+            .resolve_passkey_assertion_strict(
+                &key_id_str,
+                req.passkey.as_ref(),
+                req.webauthn.as_ref(),
+                // #110: TA binds Some(tx/msg digest) — accept payload-commitment challenge
+                signer.binds_passkey_to_payload(),
+            )
+            .await?;
+
+        // Prepare sign payload
+        let mut summary = None;
+        let tx_mode = req.transaction.is_some();
+        let fee_market = req.transaction.as_ref().is_some_and(|t| t.is_eip1559());
+        let (signature, signer) = if let Some(transaction) = req.transaction {
+            println!("  📝 Transaction signing mode");
+            let summary_abis = req.summary_abis.unwrap_or_default();
+            let summary_committed = req.summary_committed.unwrap_or(false);
+            let signed = if fee_market {
+                let eip1559 = transaction.to_eip1559()?;
+                summary = Some(proto::calldata::summarize_transaction(
+                    &eip1559.as_call(),
+                    &summary_abis,
+                ));
+                signer
+                    .sign_eip1559_transaction(
+                        wallet_uuid,
+                        &derivation_path,
+                        eip1559,
+                        passkey_assertion.clone(),
+                        summary_abis,
+                        summary_committed,
+                    )
+                    .await?
+            } else {
+                let eth_transaction = transaction.to_proto()?;
+                summary = Some(proto::calldata::summarize_transaction(
+                    &eth_transaction,
+                    &summary_abis,
+                ));
+                signer
+                    .sign_transaction(
+                        wallet_uuid,
+                        &derivation_path,
+                        eth_transaction,
+                        passkey_assertion.clone(),
+                        summary_abis,
+                        summary_committed,
+                    )
+                    .await?
+            };
+            let signer = key_pin::signed_tx_signer(&signed)?;
+            (signed, Some(signer))
+        } else if let Some(message) = req.message {
+            println!("  📝 Message signing mode");
+            let message_bytes = Self::decode_message(&message)?;
+            let digest = key_pin::message_digest(&context, &message_bytes);
+            let signature = signer
+                .sign_message(
+                    wallet_uuid,
+                    &derivation_path,
+                    &message_bytes,
+                    passkey_assertion,
+                    context,
+                )
+                .await?;
+            let signer = match digest {
+                Some(d) => Some(key_pin::recover_signer(&d, &signature)?),
+                None => None,
+            };
+            (signature, signer)
+        } else {
+            return Err(anyhow!("Either Transaction or Message must be provided"));
+        };
+        if let Some(signer) = signer {
+            enforce_key_pin(
+                &self.db,
+                &key_id_str,
+                &derivation_path,
+                &key_pin::address_hex(&signer),
+                None,
+            )?;
+            // Ledger for /kms/transaction/rescue. Best effort: the signature
+            // is already good, a bookkeeping failure must not withhold it.
+            // Rescue replaces legacy transactions only.
+            if tx_mode && !fee_market {
+                let recorded = tx_rescue::ledger_entry(
+                    &key_id_str,
+                    &derivation_path,
+                    &key_pin::address_hex(&signer),
+                    &signature,
+                )
+                .and_then(|e| self.db.insert_tx_history(&e, None));
+                if let Err(e) = recorded {
+                    eprintln!("⚠️ tx_history write failed for {}: {}", key_id_str, e);
+                }
+            }
+        }
+
+        if let Some(risk) = risk.as_ref().filter(|r| r.step_up_required) {
+            self.audit(
+                &key_id_str,
+                "risk_step_up",
+                Some(&format!(
+                    "score {}: {}",
+                    risk.score,
+                    risk.reasons.join("; ")
+                )),
+            );
+        }
+
+        Ok(SignResponse {
+            signature: hex::encode(&signature),
+            transaction_hash: "[TX_HASH_OR_MESSAGE_HASH]".to_string(),
+            summary,
+            risk,
+        })
+    }
+
+    /// #124 (DVT path-2): RP-verify a WebAuthn confirm-assertion. The account owner's
+    /// passkey signs `challenge = userOpHash` (WYSIWYS) in YAA; a DVT node forwards the
+    /// assertion here. Stateless + idempotent: no KMS nonce, sign_count=0 (counter check
+    /// skipped + never updated) so the SAME assertion can be verified by every quorum
+    /// node. Replay is bounded by userOpHash uniqueness + the node's single-use pending.
+    /// A leaked secp256k1 owner key cannot produce a P256 WebAuthn assertion → this is
+    /// what genuinely defends against owner-key theft (per Validator#124).
+    pub async fn verify_confirm_assertion(
+        &self,
+        req: VerifyConfirmAssertionRequest,
+    ) -> Result<bool> {
+        // (opus review) Validate the request STRUCTURE first — independent of account
+        // existence — so the only Err (→ 400) is genuinely-malformed caller input and does
+        // NOT leak whether `account` exists. Every account-dependent outcome below
+        // (not-found / no-passkey / dormant-frozen / bad stored key / bad signature)
+        // returns Ok(false) → uniform 200 {verified:false}, no enumeration oracle.
+        let uoh = hex::decode(req.user_op_hash.trim_start_matches("0x"))
+            .map_err(|e| anyhow!("invalid userOpHash hex: {}", e))?;
+        if uoh.len() != 32 {
+            return Err(anyhow!("userOpHash must be 32 bytes"));
+        }
+        // account may be the wallet key_id OR an address (DVT has the userOp sender address).
+        let key_id = self.resolve_account_key_id(&req.account)?;
+        let wallet = match self.db.get_wallet(&key_id)? {
+            Some(w) => w,
+            None => return Ok(false),
+        };
+        // Defense-in-depth (opus review): a dormant/frozen wallet (#42) must not produce a
+        // co-signable confirmation. The op may not re-route through this KMS's sign_hash
+        // (path-2: final sig is owner/YAA-produced), so this is NOT redundant. Frozen →
+        // Ok(false) (uniform, not an error → no oracle).
+        if self.ensure_not_frozen(&key_id).is_err() {
+            return Ok(false);
+        }
+        let pk_hex = match wallet.passkey_pubkey {
+            Some(h) => h,
+            None => return Ok(false),
+        };
+        let pk = match hex::decode(pk_hex.trim_start_matches("0x")) {
+            Ok(b) => b,
+            Err(_) => return Ok(false), // corrupt stored key = not verifiable, not a caller error
+        };
+        // TODO(multi-passkey): with >1 passkey per wallet, resolve the assertion's
+        // credential_id to the SPECIFIC bound pubkey before verifying — do not accept any
+        // of the account's keys. Inert today (single passkey_pubkey), load-bearing then.
+        //
+        // Try each configured rpId (prod = aastar.io only → strict; dev board also
+        // localhost). delegate=false → host enforces challenge == userOpHash (WYSIWYS).
+        // sign_count=0 → counter monotonicity check skipped and never updated, so this is
+        // idempotent across the quorum (each node verifies the same assertion).
+        let mut last_err = None;
+        for rp_id in &self.rp_ids {
+            match webauthn::verify_authentication_response(
+                &req.passkey,
+                &uoh,
+                &self.expected_origins,
+                rp_id,
+                &pk,
+                0,
+                false,
+            ) {
+                Ok(_) => return Ok(true),
+                Err(e) => last_err = Some(e),
+            }
+        }
+        if let Some(e) = last_err {
+            println!(
+                "⚠️ verify_confirm_assertion: not verified for account={}: {}",
+                req.account, e
+            );
+        }
+        Ok(false)
+    }
+
+    pub async fn sign_hash(&self, req: SignHashRequest) -> Result<SignHashResponse> {
+        // CA-side validation: hash format
+        let hash_array = Self::validate_hash_hex(&req.hash)?;
+        let context = match req.context {
+            Some(ref c) => c.to_proto()?,
+            None => proto::SigningContext::Raw,
+        };
+
+        // 支持三种方式:
+        // 1. Address (优先级最高,从 DB 查找)
+        // 2. KeyId + DerivationPath (手动指定路径)
+        // 3. KeyId only (自动使用默认路径)
+        let (wallet_uuid, derivation_path) = if let Some(address) = &req.address {
+            println!("📝 KMS SignHash API called with Address: {}", address);
+
+            let row = self
+                .db
+                .lookup_address(address)?
+                .ok_or_else(|| anyhow!("Address not found: {}", address))?;
+
+            (Self::validate_key_id(&row.key_id)?, row.derivation_path)
+        } else if let Some(key_id) = &req.key_id {
+            println!("📝 KMS SignHash API called with KeyId: {}", key_id);
+
+            let w = self
+                .db
+                .get_wallet(key_id)?
+                .ok_or_else(|| anyhow!("Key not found: {}", key_id))?;
+
+            let derivation_path = req
+                .derivation_path
+                .or(w.derivation_path)
+                .ok_or_else(|| anyhow!("No derivation path available for this key"))?;
+
+            (Self::validate_key_id(key_id)?, derivation_path)
+        } else {
+            return Err(anyhow!("Either KeyId or Address must be provided"));
+        };
+
+        // CA-side validation: derivation path
+        Self::validate_derivation_path(&derivation_path)?;
+
+        // Resolve passkey assertion (WebAuthn ceremony or legacy hex)
+        let key_id_str = wallet_uuid.to_string();
+        // Issue #42: reject dormant/frozen keys before any TEE call.
+        self.ensure_not_frozen(&key_id_str)?;
+        let user_op_hash = format!("0x{}", hex::encode(hash_array));
+        let fee_quote = match &req.fee_quote_hash {
+            Some(quote_hash) => {
+                Some(self.claim_fee_quote(&key_id_str, quote_hash, &user_op_hash)?)
+            }
+            None => None,
+        };
+        let signer = self.signer_for(&key_id_str);
+        let passkey_assertion = match &signer {
+            Ok(signer) => {
+                self.resolve_passkey_assertion_strict(
+                    &key_id_str,
+                    req.passkey.as_ref(),
+                    req.webauthn.as_ref(),
+                    // #110: TA binds Some(hash) — accept payload-commitment challenge
+                    signer.binds_passkey_to_payload(),
+                )
+                .await
+            }
+            Err(e) => Err(anyhow!("{}", e)),
+        };
+        let signed = match (signer, passkey_assertion) {
+            (Ok(signer), Ok(passkey_assertion)) => {
+                signer
+                    .sign_hash(
+                        wallet_uuid,
+                        &derivation_path,
+                        &hash_array,
+                        passkey_assertion,
+                        context,
+                        fee_quote,
+                    )
+                    .await
+            }
+            (_, Err(e)) | (Err(e), _) => Err(e),
+        };
+        let signature = match signed {
+            Ok(signature) => signature,
+            Err(e) => {
+                // The quote stays usable for a retry of the same confirmation.
+                if let Some(quote_hash) = &req.fee_quote_hash {
+                    if let Err(release) = self.db.release_fee_quote(quote_hash, &user_op_hash) {
+                        eprintln!(
+                            "⚠️  Failed to release fee quote {}: {}",
+                            quote_hash, release
+                        );
+                    }
+                }
+                return Err(e);
+            }
+        };
+        let signer = key_pin::recover_signer(&hash_array, &signature)?;
+        enforce_key_pin(
+            &self.db,
+            &key_id_str,
+            &derivation_path,
+            &key_pin::address_hex(&signer),
+            None,
+        )?;
+
+        Ok(SignHashResponse {
+            signature: hex::encode(&signature),
+        })
+    }
+
+    pub async fn get_public_key(&self, req: GetPublicKeyRequest) -> Result<GetPublicKeyResponse> {
+        println!("📝 KMS GetPublicKey API called for key: {}", req.key_id);
+
+        let w = self
+            .db
+            .get_wallet(&req.key_id)?
+            .ok_or_else(|| anyhow!("Key not found: {}", req.key_id))?;
+
+        Ok(GetPublicKeyResponse {
+            key_id: req.key_id,
+            public_key: w
+                .public_key
+                .unwrap_or_else(|| "[PUBLIC_KEY_PENDING]".to_string()),
+            key_usage: w.key_usage,
+            key_spec: w.key_spec,
+        })
+    }
+
+    pub async fn delete_key(&self, req: DeleteKeyRequest) -> Result<DeleteKeyResponse> {
+        println!("📝 KMS DeleteKey API called for key: {}", req.key_id);
+
+        let wallet_uuid = Uuid::parse_str(&req.key_id)?;
+        // Check whether the stored passkey is a valid P-256 curve point.
+        // If it isn't (a "gap key" created before the CreateKey validation was
+        // tightened), skip passkey verification and TEE removal — the TEE has
+        // no valid key material to protect, so the DB record is all that remains.
+        let is_gap_key = self
+            .db
+            .get_wallet(&req.key_id)?
+            .and_then(|w| w.passkey_pubkey)
+            .and_then(|hex| hex::decode(hex.trim_start_matches("0x")).ok())
+            .map(|bytes| p256::PublicKey::from_sec1_bytes(&bytes).is_err())
+            .unwrap_or(false);
+
+        let mut proof = None;
+        if is_gap_key {
+            // Gap key: passkey_pubkey is not a valid P-256 curve point.
+            // Attempt TEE force-removal (ForceRemoveWallet = cmd 23, added in TA v0.20.0).
+            // On older TA binaries this call returns "Unsupported command" — we log and
+            // continue so the SQLite row is still cleaned up regardless.
+            match self.tee.force_remove_wallet(wallet_uuid).await {
+                Ok(()) => {
+                    println!("✅ Gap key TEE entry purged (ForceRemoveWallet succeeded)");
+                }
+                Err(e) => {
+                    // TA older than v0.20.0 — TEE orphan remains (~1-2 KB, inaccessible).
+                    // SQLite cleanup still proceeds. Rebuild TA to fully resolve.
+                    eprintln!(
+                        "⚠️  Gap key TEE purge failed (TA may need rebuild): {}. \
+                        SQLite row will still be deleted. TEE orphan is inaccessible.",
+                        e
+                    );
+                }
+            }
+        } else {
+            // Normal key: strict passkey/WebAuthn verification (audit-hardened) before removal.
+            let passkey_assertion = self
+                .resolve_passkey_assertion_strict(
+                    &req.key_id,
+                    req.passkey.as_ref(),
+                    req.webauthn.as_ref(),
+                    false, // #110: nonce-only op — TA enforces challenge==nonce; host stays strict
+                )
+                .await?;
+            proof = self
+                .tee
+                .remove_wallet(wallet_uuid, passkey_assertion)
+                .await?;
+        }
+
+        // Remove from DB (CASCADE deletes address_index entries).
+        // H-C: the TA wallet is already gone. If this DB delete is lost the
+        // row becomes a "ghost" — later operations on it fail with confusing
+        // TEE errors. Retry, and on persistent failure mark the row instead
+        // of leaving it looking alive.
+        let mut del_result = self.db.delete_wallet(&req.key_id).map(|_| ());
+        for attempt in 1..=3u64 {
+            if del_result.is_ok() {
+                break;
+            }
+            eprintln!(
+                "⚠️  DeleteKey: DB delete attempt {}/4 failed for {}: {:?}",
+                attempt, req.key_id, del_result
+            );
+            tokio::time::sleep(std::time::Duration::from_millis(100 * attempt)).await;
+            del_result = self.db.delete_wallet(&req.key_id).map(|_| ());
+        }
+        if let Err(e) = del_result {
+            // Best effort: flag the ghost row so it doesn't pose as a live key.
+            let _ = self.db.update_wallet_status(
+                &req.key_id,
+                "error",
+                Some("TEE wallet deleted but DB row could not be removed — ghost row"),
+            );
+            eprintln!(
+                "🔴 CRITICAL: TEE wallet {} deleted but DB row removal failed — \
+                 ghost row flagged as 'error'. Manual cleanup: \
+                 DELETE FROM wallets WHERE key_id='{}'; Error: {:?}",
+                req.key_id, req.key_id, e
+            );
+        }
+
+        let deletion_certificate = match proof.map(DeletionCertificateView::from_proof) {
+            Some(Ok(view)) => {
+                let signed = view.attestation.is_some();
+                if let Err(e) = serde_json::to_string(&view)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|json| {
+                        self.db
+                            .record_deletion_certificate(&req.key_id, &json, signed)
+                    })
+                {
+                    eprintln!(
+                        "⚠️  DeleteKey: could not store deletion certificate for {}: {}",
+                        req.key_id, e
+                    );
+                }
+                Some(view)
+            }
+            Some(Err(e)) => {
+                eprintln!(
+                    "🔴 DeleteKey: TA deletion certificate for {} rejected: {}",
+                    req.key_id, e
+                );
+                None
+            }
+            None => None,
+        };
+
+        let days = req.pending_window_in_days.unwrap_or(7);
+        let deletion_date = Utc::now() + chrono::Duration::days(days as i64);
+
+        Ok(DeleteKeyResponse {
+            key_id: req.key_id,
+            deletion_date,
+            deletion_certificate,
+        })
+    }
+
+    /// Stored proof-of-erasure for a deleted key.
+    pub async fn deletion_certificate(&self, key_id: &str) -> Result<serde_json::Value> {
+        let json = self
+            .db
+            .get_deletion_certificate(key_id)?
+            .ok_or_else(|| anyhow!("No deletion certificate for key: {}", key_id))?;
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    /// An ERC-20 transfer (or permit) from a human-denominated amount, signed
+    /// through `sign` / `sign_permit` so every check of those paths applies.
+    pub async fn transfer_token(&self, req: TransferTokenRequest) -> Result<serde_json::Value> {
+        let token = token_transfer::resolve_token(req.chain_id, &req.token, req.decimals)?;
+        let amount = token_transfer::parse_amount(&req.amount, &token)?;
+        let token_hex = proto::calldata::hex_addr(&token.address);
+        let mut out = serde_json::json!({
+            "keyId": req.key_id.clone(),
+            "chainId": req.chain_id,
+            "token": token_hex,
+            "symbol": token.symbol,
+            "decimals": token.decimals,
+            "amount": amount.to_string(),
+            "baseUnits": amount.base_units.to_string(),
+        });
+
+        if let Some(permit) = req.permit {
+            if req.to.is_some() || req.nonce.is_some() || req.gas_price.is_some() {
+                return Err(anyhow!(
+                    "permit mode signs no transaction: drop to, nonce and gasPrice"
+                ));
+            }
+            let (key_id, hd_path) = (&req.key_id, &req.hd_path);
+            let owner = self
+                .db
+                .address_for_key_path(key_id, hd_path)?
+                .ok_or_else(|| {
+                    anyhow!(
+                        "address for {} at {} not derived yet; call DeriveAddress first",
+                        key_id,
+                        hd_path
+                    )
+                })?;
+            let signed = self
+                .sign_permit(SignPermitRequest {
+                    key_id: req.key_id,
+                    hd_path: req.hd_path,
+                    permit: permit::PermitJson {
+                        kind: "erc2612".to_string(),
+                        chain_id: req.chain_id,
+                        token: token_hex,
+                        spender: permit.spender.clone(),
+                        amount: amount.base_units.to_string(),
+                        nonce: permit.nonce,
+                        deadline: permit.deadline,
+                        owner: Some(owner),
+                        token_name: Some(permit.token_name),
+                        token_version: permit.token_version,
+                        expiration: None,
+                    },
+                    webauthn_assertion: req.webauthn_assertion,
+                })
+                .await?;
+            out["mode"] = serde_json::json!("permit");
+            out["spender"] = serde_json::json!(permit.spender);
+            out["signature"] = serde_json::json!(signed.signature);
+            out["summary"] = serde_json::json!(signed.summary);
+            return Ok(out);
+        }
+
+        let to = req
+            .to
+            .ok_or_else(|| anyhow!("transfer mode needs to (or pass permit)"))?;
+        let to_address = proto::eip55::parse_address(&to).map_err(|e| anyhow!("to: {}", e))?;
+        let transaction = EthereumTransaction {
+            chain_id: req.chain_id,
+            nonce: req
+                .nonce
+                .ok_or_else(|| anyhow!("transfer mode needs nonce"))?,
+            to: token_hex,
+            value: "0x0".to_string(),
+            gas_price: req
+                .gas_price
+                .ok_or_else(|| anyhow!("transfer mode needs gasPrice"))?,
+            gas: req.gas.unwrap_or(DEFAULT_TOKEN_TRANSFER_GAS),
+            data: format!(
+                "0x{}",
+                hex::encode(token_transfer::transfer_calldata(&to_address, &amount))
+            ),
+            max_fee_per_gas: None,
+            max_priority_fee_per_gas: None,
+            access_list: Vec::new(),
+        };
+        let signed = self
+            .sign(SignRequest {
+                address: None,
+                key_id: Some(req.key_id),
+                derivation_path: Some(req.hd_path),
+                transaction: Some(transaction),
+                message: None,
+                signing_algorithm: None,
+                passkey: None,
+                webauthn: req.webauthn_assertion,
+                context: None,
+                summary_abis: None,
+                summary_committed: req.summary_committed,
+            })
+            .await?;
+        out["mode"] = serde_json::json!("transfer");
+        out["to"] = serde_json::json!(proto::calldata::hex_addr(&to_address));
+        out["signature"] = serde_json::json!(signed.signature);
+        out["transactionHash"] = serde_json::json!(signed.transaction_hash);
+        out["summary"] = serde_json::json!(signed.summary);
+        Ok(out)
+    }
+
+    /// Fetch a paymaster quote for paying a UserOperation's gas in `token`
+    /// and record it for the key. With `userOpHash`, also return the digest
+    /// the WebAuthn challenge must commit to when signing with it.
+    pub async fn fee_quote(&self, req: FeeQuoteRequest) -> Result<serde_json::Value> {
+        let config = self
+            .paymaster
+            .as_ref()
+            .ok_or_else(|| anyhow!("No paymaster configured (KMS_PAYMASTER_URL)"))?;
+        if !self.db.wallet_exists(&req.key_id)? {
+            return Err(anyhow!("Key not found: {}", req.key_id));
+        }
+        let token = proto::eip55::parse_address(&req.token).map_err(|e| anyhow!("token: {}", e))?;
+        let max_gas_cost_wei = parse_wei("maxGasCostWei", &req.max_gas_cost_wei)?;
+        let user_op_hash = match &req.user_op_hash {
+            Some(h) => Some(Self::validate_hash_hex(h)?),
+            None => None,
+        };
+        let quote = paymaster::fetch_quote(config, req.chain_id, &token, max_gas_cost_wei).await?;
+        paymaster::check_quote(
+            &quote,
+            req.chain_id,
+            &token,
+            max_gas_cost_wei,
+            chrono::Utc::now().timestamp().max(0) as u64,
+        )?;
+        let quote_hash = format!("0x{}", hex::encode(proto::paymaster::quote_hash(&quote)));
+        let quote_json = FeeQuoteJson::from_proto(&quote);
+        self.db.insert_fee_quote(&crate::db::FeeQuoteEntry {
+            quote_hash: quote_hash.clone(),
+            key_id: req.key_id.clone(),
+            chain_id: quote.chain_id,
+            paymaster: quote_json.paymaster.clone(),
+            token: quote_json.token.clone(),
+            max_token_cost: quote.max_token_cost,
+            quote: serde_json::to_string(&quote_json)?,
+        })?;
+        Ok(serde_json::json!({
+            "keyId": req.key_id,
+            "quoteHash": quote_hash,
+            "quote": quote_json,
+            "summary": quote.summary(),
+            "reviewDigest": user_op_hash.map(|h| format!(
+                "0x{}",
+                hex::encode(proto::paymaster::fee_review_digest(&h, &quote))
+            )),
+        }))
+    }
+
+    /// Reserve a recorded quote for one userOpHash and return it for the TA.
+    fn claim_fee_quote(
+        &self,
+        key_id: &str,
+        quote_hash: &str,
+        user_op_hash: &str,
+    ) -> Result<proto::paymaster::FeeQuote> {
+        let row = self
+            .db
+            .fee_payment(quote_hash)?
+            .filter(|r| r.entry.key_id == key_id)
+            .ok_or_else(|| anyhow!("Fee quote not found: {}", quote_hash))?;
+        let quote: FeeQuoteJson = serde_json::from_str(&row.entry.quote)?;
+        let quote = quote.to_proto()?;
+        if !self.db.claim_fee_quote(quote_hash, user_op_hash)? {
+            return Err(anyhow!(
+                "fee quote {} was already used for another UserOperation",
+                quote_hash
+            ));
+        }
+        Ok(quote)
+    }
+
+    /// Record how a token-paid UserOperation settled on chain.
+    pub async fn fee_settlement(&self, req: FeeSettlementRequest) -> Result<serde_json::Value> {
+        let settled = match req.status.as_str() {
+            "settled" => true,
+            "failed" => false,
+            other => return Err(anyhow!("status must be settled or failed: {}", other)),
+        };
+        if settled && req.tx_hash.is_none() {
+            return Err(anyhow!("a settled fee payment needs txHash"));
+        }
+        if let Some(tx_hash) = &req.tx_hash {
+            Self::validate_hash_hex(tx_hash)?;
+        }
+        let actual = match &req.actual_token_cost {
+            Some(c) => Some(paymaster::parse_u128("actualTokenCost", c)?),
+            None => None,
+        };
+        let row = self
+            .db
+            .settle_fee_payment(&req.quote_hash, settled, req.tx_hash.as_deref(), actual)?
+            .ok_or_else(|| {
+                anyhow!(
+                    "Fee quote {} is not awaiting settlement (unknown, unsigned or already settled)",
+                    req.quote_hash
+                )
+            })?;
+        if paymaster::overcharged(&row) {
+            eprintln!(
+                "⚠️  Fee payment {} charged {}, above the quoted cap {}",
+                req.quote_hash,
+                row.actual_token_cost.unwrap_or(0),
+                row.entry.max_token_cost
+            );
+        }
+        Ok(paymaster::fee_payment_json(&row))
+    }
+
+    /// A key's fee payments, newest first.
+    pub async fn fee_payments(&self, key_id: &str) -> Result<serde_json::Value> {
+        if !self.db.wallet_exists(key_id)? {
+            return Err(anyhow!("Key not found: {}", key_id));
+        }
+        let rows = self.db.list_fee_payments(key_id, 200)?;
+        Ok(serde_json::json!({
+            "keyId": key_id,
+            "payments": rows.iter().map(paymaster::fee_payment_json).collect::<Vec<_>>(),
+        }))
+    }
+
+    /// Deposits and contract events the chain watcher recorded for a wallet,
+    /// and stealth payments the scanner matched, newest first. Page with
+    /// `before` = the previous page's `nextBefore`.
+    pub async fn wallet_events(
+        &self,
+        key_id: &str,
+        query: WalletEventsQuery,
+    ) -> Result<serde_json::Value> {
+        if !self.db.wallet_exists(key_id)? {
+            return Err(anyhow!("Key not found: {}", key_id));
+        }
+        let limit = query.limit.unwrap_or(50).clamp(1, 200);
+        let rows = self.db.list_chain_events(key_id, query.before, limit)?;
+        let next_before = if rows.len() == limit as usize {
+            rows.last().map(|r| r.id)
+        } else {
+            None
+        };
+        Ok(serde_json::json!({
+            "keyId": key_id,
+            "events": rows.iter().map(chain_watch::event_json).collect::<Vec<_>>(),
+            "nextBefore": next_before,
+        }))
+    }
+
+    /// A wallet's activity in `[from, to)` in an accounting tool's format
+    /// (accounting_export.rs): the body and the format it is in.
+    pub async fn wallet_export(
+        &self,
+        key_id: &str,
+        query: WalletExportQuery,
+    ) -> Result<(accounting_export::Format, String)> {
+        let format = accounting_export::Format::parse(&query.format)?;
+        if !self.db.wallet_exists(key_id)? {
+            return Err(anyhow!("Key not found: {}", key_id));
+        }
+        let now = Utc::now().timestamp();
+        let from = query.from.unwrap_or(0);
+        let to = query.to.unwrap_or(now + 1);
+        if from >= to {
+            return Err(anyhow!("from must be before to"));
+        }
+        let max = accounting_export::MAX_EXPORT_ROWS;
+        let txs = self.db.txs_between(key_id, from, to, max + 1)?;
+        let events = self
+            .db
+            .chain_events_between(key_id, from, to, max as u32 + 1)?;
+        let fees = self
+            .db
+            .settled_fee_payments_between(key_id, from, to, max as u32 + 1)?;
+        if txs.len() > max || events.len() > max || fees.len() > max {
+            return Err(anyhow!(
+                "more than {} ledger rows in range; export a shorter date range",
+                max
+            ));
+        }
+        let entries = accounting_export::collect(&txs, &events, &fees)?;
+        self.audit(
+            key_id,
+            "wallet_export",
+            Some(&format!(
+                "format={} from={} to={} entries={}",
+                format.as_str(),
+                from,
+                to,
+                entries.len()
+            )),
+        );
+        Ok((
+            format,
+            accounting_export::render(format, key_id, &entries, from, to, now),
+        ))
+    }
+
+    /// Signatures and native value moved per requesting origin (usage.rs),
+    /// over `[from, to)`.
+    pub async fn wallet_usage(
+        &self,
+        key_id: &str,
+        query: WalletUsageQuery,
+    ) -> Result<serde_json::Value> {
+        if !self.db.wallet_exists(key_id)? {
+            return Err(anyhow!("Key not found: {}", key_id));
+        }
+        let now = Utc::now().timestamp();
+        let from = query.from.unwrap_or(0);
+        let to = query.to.unwrap_or(now + 1);
+        if from >= to {
+            return Err(anyhow!("from must be before to"));
+        }
+        let counts = self.db.usage_counts_between(key_id, from, to)?;
+        let values = self.db.usage_values_between(key_id, from, to)?;
+        let lines = usage::report(&counts, &values);
+        Ok(serde_json::json!({
+            "keyId": key_id,
+            "from": from,
+            "to": to,
+            "origins": lines.iter().map(usage::OriginUsage::to_json).collect::<Vec<_>>(),
+        }))
+    }
+
+    /// The usage tag of a signing request, with its API key's label.
+    pub fn usage_origin(&self, mut origin: RequestOrigin) -> String {
+        if let Some(key) = &origin.api_key {
+            origin.api_key_label = self.db.api_key_label(key).ok().flatten();
+        }
+        origin.tag()
+    }
+
+    /// The account audit trail as `account.audit` event envelopes, newest
+    /// first, for export to a SIEM. Pages like `wallet_events`.
+    pub async fn audit_export(
+        &self,
+        key_id: &str,
+        query: WalletEventsQuery,
+    ) -> Result<serde_json::Value> {
+        if !self.db.wallet_exists(key_id)? {
+            return Err(anyhow!("Key not found: {}", key_id));
+        }
+        let limit = query.limit.unwrap_or(50).clamp(1, 200);
+        let rows = self.db.list_account_events(key_id, limit, query.before)?;
+        let next_before = if rows.len() == limit as usize {
+            rows.last().map(|r| r.id)
+        } else {
+            None
+        };
+        let events: Vec<_> = rows
+            .into_iter()
+            .map(|r| {
+                // Rows written before correlation ids were stored get a stable stand-in.
+                let id = r.id;
+                let correlation_id = r.correlation_id.unwrap_or_else(|| format!("audit-{}", id));
+                EventEnvelope::new(
+                    AuditEventPayload {
+                        account: key_id.to_string(),
+                        event: r.event,
+                        detail: r.detail,
+                    },
+                    r.created_at,
+                    correlation_id,
+                )
+            })
+            .collect();
+        Ok(serde_json::json!({
+            "keyId": key_id,
+            "events": events,
+            "nextBefore": next_before,
+        }))
+    }
+
+    /// The preferences `chain_watch::record` applies to a wallet's webhook
+    /// pushes; `custom: false` means the defaults.
+    pub async fn notification_prefs(&self, key_id: &str) -> Result<serde_json::Value> {
+        if !self.db.wallet_exists(key_id)? {
+            return Err(anyhow!("Key not found: {}", key_id));
+        }
+        let stored = self.db.notification_prefs(key_id)?;
+        let prefs = match &stored {
+            Some((json, _)) => serde_json::from_str(json)?,
+            None => NotificationPrefs::default(),
+        };
+        Ok(serde_json::json!({
+            "keyId": key_id,
+            "custom": stored.is_some(),
+            "preferences": prefs,
+            "updatedAt": stored.map(|(_, at)| at),
+        }))
+    }
+
+    /// Replace a wallet's notification preferences, or with `preferences:
+    /// null` go back to the defaults. Host-only, so the owner's ceremony is
+    /// held to challenge == nonce.
+    pub async fn set_notification_prefs(
+        &self,
+        key_id: &str,
+        req: NotificationPrefsRequest,
+    ) -> Result<serde_json::Value> {
+        if !self.db.wallet_exists(key_id)? {
+            return Err(anyhow!("Key not found: {}", key_id));
+        }
+        let prefs = req.preferences.map(NotificationPrefs::parse).transpose()?;
+        if req.webauthn_assertion.is_none() {
+            return Err(anyhow!(
+                "notification preferences require WebAuthn ceremony"
+            ));
+        }
+        self.resolve_passkey_assertion_strict(key_id, None, req.webauthn_assertion.as_ref(), false)
+            .await?;
+        match &prefs {
+            Some(prefs) => {
+                self.db
+                    .set_notification_prefs(key_id, &serde_json::to_string(prefs)?)?;
+                self.audit(key_id, "notification_prefs_set", None);
+            }
+            None => {
+                self.db.clear_notification_prefs(key_id)?;
+                self.audit(key_id, "notification_prefs_reset", None);
+            }
+        }
+        println!(
+            "🔔 Notification preferences of {} {}",
+            key_id,
+            if prefs.is_some() { "set" } else { "reset" }
+        );
+        self.notification_prefs(key_id).await
+    }
+
+    /// Issue #42: owner-authorized unfreeze. Verifies owner via WebAuthn (same
+    /// strict passkey resolution as DeleteKey), then flips lifecycle_status
+    /// frozen→active. No TEE call — this only touches host SQLite metadata.
+    /// Idempotent: unfreezing an already-active key succeeds and returns 'active'.
+    pub async fn unfreeze_key(&self, req: UnfreezeKeyRequest) -> Result<UnfreezeKeyResponse> {
+        println!("📝 KMS UnfreezeKey API called for key: {}", req.key_id);
+
+        // Existence + UUID validation (mirrors delete_key's parse).
+        let _wallet_uuid = Uuid::parse_str(&req.key_id)?;
+        let current = self
+            .db
+            .get_lifecycle_status(&req.key_id)?
+            .ok_or_else(|| anyhow!("Key not found: {}", req.key_id))?;
+
+        // Owner authentication — identical strict assertion path as DeleteKey.
+        // Verifying ownership is required even when the key is already active so
+        // the endpoint cannot be used as an unauthenticated key-state probe.
+        // #110 (codex Q2): UnfreezeKey is HOST-ONLY — it flips lifecycle_status in
+        // the DB and never calls the TA, so the host's challenge==nonce check is the
+        // ONLY binding. MUST pass false; true would let a captured assertion be
+        // replayed with a fresh challenge_id (host skips value, TA never sees it).
+        self.resolve_passkey_assertion_strict(
+            &req.key_id,
+            req.passkey.as_ref(),
+            req.webauthn.as_ref(),
+            false,
+        )
+        .await?;
+
+        if current != "frozen" {
+            // Already active (or some future state): nothing to flip, report as-is.
+            return Ok(UnfreezeKeyResponse {
+                key_id: req.key_id,
+                lifecycle_status: current,
+            });
+        }
+
+        self.db.set_lifecycle_status(&req.key_id, "active")?;
+        println!("✅ Key unfrozen: {}", req.key_id);
+
+        Ok(UnfreezeKeyResponse {
+            key_id: req.key_id,
+            lifecycle_status: "active".to_string(),
+        })
+    }
+
+    /// Admin force-purge: removes a key from TEE + SQLite without passkey verification.
+    /// Used for: TEE orphans (SQLite row gone), test keys, gap keys.
+    /// Requires KMS_ADMIN_TOKEN to be set in the environment.
+    /// Returns (tee_purged, sqlite_deleted).
+    ///
+    /// DEV/TEST ONLY — compiled in only under the `admin-purge` feature.
+    #[cfg(feature = "admin-purge")]
+    pub async fn admin_purge_key(&self, key_id: &str, reason: &str) -> Result<(bool, bool)> {
+        let wallet_uuid = Uuid::parse_str(key_id)?;
+
+        println!("🔑 AdminPurgeKey: {} reason={}", key_id, reason);
+
+        // Try TEE removal (ForceRemoveWallet = cmd 23).
+        // Succeeds only if the entry exists in TEE and TA supports cmd 23.
+        let tee_ok = match self.tee.force_remove_wallet(wallet_uuid).await {
+            Ok(()) => {
+                println!("  ✅ TEE entry purged");
+                true
+            }
+            Err(e) => {
+                eprintln!("  ⚠️  TEE purge failed (orphan or old TA): {}", e);
+                false
+            }
+        };
+
+        // Delete from SQLite (ignore if already gone).
+        let sqlite_ok = match self.db.delete_wallet(key_id) {
+            Ok(()) => {
+                println!("  ✅ SQLite row deleted");
+                true
+            }
+            Err(e) => {
+                eprintln!("  ⚠️  SQLite delete failed (row may not exist): {}", e);
+                false
+            }
+        };
+
+        Ok((tee_ok, sqlite_ok))
+    }
+
+    // ── WebAuthn ceremonies ──
+
+    /// Pick rpId from configured KMS_RP_ID list based on caller's HTTP Origin header.
+    /// e.g. origin "http://localhost:5173" → matches "localhost" if in list.
+    /// Falls back to first configured rpId.
+    fn resolve_rp_id(&self, caller_origin: Option<&str>) -> String {
+        if let Some(origin) = caller_origin {
+            let host = origin
+                .trim_start_matches("http://")
+                .trim_start_matches("https://")
+                .split(':')
+                .next()
+                .unwrap_or("");
+            // Check if any configured rpId matches (exact or suffix)
+            for rp in &self.rp_ids {
+                if host == rp.as_str() || host.ends_with(&format!(".{}", rp)) {
+                    return rp.clone();
+                }
+            }
+        }
+        self.rp_ids[0].clone()
+    }
+
+    pub async fn begin_registration(
+        &self,
+        req: webauthn::BeginRegistrationRequest,
+        origin_header: Option<&str>,
+    ) -> Result<webauthn::RegistrationOptionsResponse> {
+        let user_name = req.user_name.as_deref().unwrap_or("wallet-user");
+        let user_display = req
+            .user_display_name
+            .as_deref()
+            .unwrap_or("AirAccount Wallet");
+        let rp_id = self.resolve_rp_id(origin_header);
+        println!(
+            "🔑 WebAuthn rpId resolved: {} (from origin: {:?})",
+            rp_id, req.origin
+        );
+        let mds = self.fido_mds()?;
+        if let Some(role) = req.role.as_deref() {
+            mds.ok_or_else(|| anyhow!("registration roles need KMS_FIDO_ATTESTATION_POLICY"))?
+                .check_role_known(role)?;
+        }
+
+        let (challenge_id, challenge_bytes, mut resp) = webauthn::generate_registration_options(
+            &self.rp_name,
+            &rp_id,
+            user_name,
+            user_display,
+            vec![],
+        );
+        if mds.is_some() {
+            // The certificate chain is only sent when asked for.
+            resp.options.attestation = "direct".to_string();
+        }
+
+        self.db.store_challenge(
+            &challenge_id,
+            &challenge_bytes,
+            None,
+            "registration",
+            &rp_id,
+            300,
+        )?;
+
+        // Stash description/key_usage/etc in challenge metadata (store as JSON in key_id field)
+        let meta_json = serde_json::to_string(&serde_json::json!({
+            "description": req.description.unwrap_or_default(),
+            "key_usage": req.key_usage.unwrap_or_else(|| "SIGN_VERIFY".to_string()),
+            "key_spec": req.key_spec.unwrap_or_else(|| "ECC_SECG_P256K1".to_string()),
+            "origin": req.origin.unwrap_or_else(|| "EXTERNAL_KMS".to_string()),
+            "role": req.role,
+        }))?;
+        // Re-store with metadata in key_id field
+        self.db.store_challenge(
+            &format!("{}_meta", challenge_id),
+            meta_json.as_bytes(),
+            None,
+            "registration_meta",
+            &rp_id,
+            300,
+        )?;
+
+        println!(
+            "📝 WebAuthn BeginRegistration: challenge_id={}",
+            challenge_id
+        );
+        Ok(resp)
+    }
+
+    pub async fn complete_registration(
+        &self,
+        req: webauthn::CompleteRegistrationRequest,
+    ) -> Result<webauthn::CompleteRegistrationResponse> {
+        // 1. Consume challenge. Only a registration challenge qualifies: an
+        // authentication challenge (or the `_meta` row itself) is left unconsumed.
+        let challenge_row = self
+            .db
+            .consume_challenge_for(&req.challenge_id, "registration")?
+            .ok_or_else(|| {
+                anyhow!(
+                    "Challenge not found, expired or not issued for registration: {}",
+                    req.challenge_id
+                )
+            })?;
+
+        // 2. Load stashed metadata
+        let meta_row = self
+            .db
+            .consume_challenge(&format!("{}_meta", req.challenge_id))?;
+        let mut role = None;
+        let (description, key_usage, key_spec, origin) = if let Some(mr) = meta_row {
+            let v: serde_json::Value = serde_json::from_slice(&mr.challenge).unwrap_or_default();
+            role = v["role"].as_str().map(str::to_string);
+            (
+                v["description"].as_str().unwrap_or("").to_string(),
+                v["key_usage"].as_str().unwrap_or("SIGN_VERIFY").to_string(),
+                v["key_spec"]
+                    .as_str()
+                    .unwrap_or("ECC_SECG_P256K1")
+                    .to_string(),
+                v["origin"].as_str().unwrap_or("EXTERNAL_KMS").to_string(),
+            )
+        } else {
+            (
+                req.description.unwrap_or_default(),
+                req.key_usage.unwrap_or_else(|| "SIGN_VERIFY".to_string()),
+                req.key_spec
+                    .unwrap_or_else(|| "ECC_SECG_P256K1".to_string()),
+                req.origin.unwrap_or_else(|| "EXTERNAL_KMS".to_string()),
+            )
+        };
+
+        // 3. Verify attestation (use rpId from stored challenge, not hardcoded)
+        let rp_id = &challenge_row.rp_id;
+        let verified = webauthn::verify_registration_response(
+            &req.credential,
+            &challenge_row.challenge,
+            &self.expected_origins,
+            rp_id,
+        )?;
+
+        println!(
+            "✅ WebAuthn registration verified, pubkey {} bytes, credential_id {} bytes",
+            verified.public_key.len(),
+            verified.credential_id.len()
+        );
+        let certification = match self.fido_mds()? {
+            Some(mds) => mds.check(role.as_deref(), &verified.aaguid, &verified.x5c)?,
+            None => None,
+        };
+        let attestation = PasskeyAttestation {
+            attestation_type: Some(verified.attestation_type.as_str().to_string()),
+            aaguid: Some(verified.aaguid)
+                .filter(|a| !a.is_nil())
+                .map(|a| a.to_string()),
+            certification: certification.map(|c| c.as_str().to_string()),
+            role,
+        };
+
+        // 4. Create wallet in TA with extracted P-256 pubkey (+ PRF entropy factor
+        //    when the authenticator evaluated it at create()).
+        if verified.prf_output.is_some() {
+            println!("🔐 WebAuthn PRF output present — mixing into wallet entropy");
+        }
+        let wallet_id = self
+            .tee
+            .create_wallet(&verified.public_key, verified.prf_output)
+            .await?;
+        let now = Utc::now();
+        let credential_id_b64 = webauthn::b64url_encode(&verified.credential_id);
+        let passkey_pubkey_hex = format!("0x{}", hex::encode(&verified.public_key));
+
+        // 5. Persist to DB
+        self.db.insert_wallet(&WalletRow {
+            key_id: wallet_id.to_string(),
+            address: None,
+            public_key: None,
+            derivation_path: None,
+            description,
+            key_usage,
+            key_spec,
+            origin,
+            passkey_pubkey: Some(passkey_pubkey_hex),
+            credential_id: Some(credential_id_b64.clone()),
+            sign_count: verified.sign_count,
+            status: "deriving".to_string(),
+            error_msg: None,
+            created_at: now.to_rfc3339(),
+        })?;
+        self.db
+            .set_passkey_attestation(&wallet_id.to_string(), &attestation)?;
+
+        // 6. Spawn background address derivation
+        let db = self.db.clone();
+        let tee = self.tee.clone();
+        tokio::spawn(async move {
+            match tee.derive_address_auto(wallet_id).await {
+                Ok((_wid, address_bytes, public_key, derivation_path)) => {
+                    let address_hex = format!("0x{}", hex::encode(&address_bytes));
+                    let pubkey_hex = format!("0x{}", hex::encode(&public_key));
+                    println!(
+                        "✅ Background derivation done for {}: {}",
+                        wallet_id, address_hex
+                    );
+                    if let Err(e) = enforce_key_pin(
+                        &db,
+                        &wallet_id.to_string(),
+                        &derivation_path,
+                        &address_hex,
+                        Some(&pubkey_hex),
+                    ) {
+                        let _ = db.update_wallet_status(
+                            &wallet_id.to_string(),
+                            "error",
+                            Some(&e.to_string()),
+                        );
+                        return;
+                    }
+                    let _ = db.update_wallet_derived(
+                        &wallet_id.to_string(),
+                        &address_hex,
+                        &pubkey_hex,
+                        &derivation_path,
+                        "ready",
+                    );
+                }
+                Err(e) => {
+                    eprintln!("❌ Background derivation failed for {}: {}", wallet_id, e);
+                    let _ = db.update_wallet_status(
+                        &wallet_id.to_string(),
+                        "error",
+                        Some(&e.to_string()),
+                    );
+                }
+            }
+        });
+
+        Ok(webauthn::CompleteRegistrationResponse {
+            key_id: wallet_id.to_string(),
+            credential_id: credential_id_b64,
+            status: "deriving".to_string(),
+        })
+    }
+
+    pub async fn begin_authentication(
+        &self,
+        req: webauthn::BeginAuthenticationRequest,
+        origin_header: Option<&str>,
+    ) -> Result<webauthn::AuthenticationOptionsResponse> {
+        // Resolve key_id from KeyId or Address
+        let key_id = if let Some(ref kid) = req.key_id {
+            kid.clone()
+        } else if let Some(ref addr) = req.address {
+            let row = self
+                .db
+                .lookup_address(addr)?
+                .ok_or_else(|| anyhow!("Address not found: {}", addr))?;
+            row.key_id
+        } else {
+            return Err(anyhow!("Must provide either KeyId or Address"));
+        };
+
+        let w = self
+            .db
+            .get_wallet(&key_id)?
+            .ok_or_else(|| anyhow!("Key not found: {}", key_id))?;
+
+        let allow_credentials = if let Some(ref cid) = w.credential_id {
+            vec![webauthn::CredentialDescriptor {
+                id: cid.clone(),
+                type_: "public-key".to_string(),
+                transports: Some(vec!["internal".to_string(), "hybrid".to_string()]),
+            }]
+        } else {
+            vec![]
+        };
+
+        let rp_id = self.resolve_rp_id(origin_header);
+
+        // Issue #49: source the challenge from the TA so the authenticator signs
+        // the exact nonce the TA will later verify + consume (anti-replay).
+        // key_id is the TA wallet UUID string (see Self::validate_key_id / sign path).
+        // Fallback to a host-random challenge only for a transition-mode TA
+        // (see ta_challenge_options); a strict TA gets no ceremony instead.
+        //
+        // Issue #68: the TA returns a plain random nonce. For a signing op the
+        // client must use challenge = SHA-256(nonce || payload_digest) in the
+        // WebAuthn ceremony; the TA recomputes + verifies that commitment at
+        // signing time. The challenge issuance itself is payload-free.
+        let (challenge_id, challenge_bytes, resp) = self
+            .ta_challenge_options(&key_id, &rp_id, allow_credentials, "authentication")
+            .await?;
+
+        self.db.store_challenge(
+            &challenge_id,
+            &challenge_bytes,
+            Some(&key_id),
+            "authentication",
+            &rp_id,
+            300,
+        )?;
+
+        println!(
+            "📝 WebAuthn BeginAuthentication: challenge_id={}, key_id={}",
+            challenge_id, key_id
+        );
+        Ok(resp)
+    }
+
+    /// Authentication options over a nonce the TA issued for the wallet
+    /// (GetChallenge), so the TA verifies and consumes exactly what the
+    /// authenticator signed and the CA only relays it. A host-random challenge
+    /// is the fallback for a transition-mode TA that cannot issue one (older
+    /// TA, transient error); a TA that requires its own nonces would refuse
+    /// that assertion, so then no ceremony is started.
+    async fn ta_challenge_options(
+        &self,
+        key_id: &str,
+        rp_id: &str,
+        allow_credentials: Vec<webauthn::CredentialDescriptor>,
+        purpose: &str,
+    ) -> Result<(String, Vec<u8>, webauthn::AuthenticationOptionsResponse)> {
+        let issued = match uuid::Uuid::parse_str(key_id) {
+            Ok(wallet_uuid) => self.tee.get_challenge(wallet_uuid).await,
+            Err(_) => Err(anyhow!("{} is not a TA wallet id", key_id)),
+        };
+        match issued {
+            Ok(nonce) => {
+                println!(
+                    "🔐 Issue #49: using TA-issued {} challenge for key_id={}",
+                    purpose, key_id
+                );
+                Ok(webauthn::generate_authentication_options_with_challenge(
+                    rp_id,
+                    allow_credentials,
+                    nonce,
+                ))
+            }
+            Err(e) if self.ta_challenge_required().await => Err(anyhow!(
+                "TA challenge unavailable for {} ({}); this TA only accepts challenges it issued",
+                purpose,
+                e
+            )),
+            Err(e) => {
+                eprintln!(
+                    "⚠️  Issue #49: TA GetChallenge unavailable ({}); {} falls back to \
+                     host-random challenge (TA transition path)",
+                    e, purpose
+                );
+                Ok(webauthn::generate_authentication_options(
+                    rp_id,
+                    allow_credentials,
+                ))
+            }
+        }
+    }
+
+    /// A strict-challenge build on either side, or a TA config requiring TA
+    /// challenges. Capabilities the TA cannot report count as not required:
+    /// the TA enforces its own mode whatever the CA decides here.
+    async fn ta_challenge_required(&self) -> bool {
+        cfg!(feature = "strict-challenge")
+            || self
+                .tee
+                .get_capabilities()
+                .await
+                .map(|caps| ta_requires_own_challenge(&caps))
+                .unwrap_or(false)
+    }
+
+    /// Start a purpose-bound WebAuthn challenge for grant-session signing.
+    /// The stored challenge has purpose="grant-session", which sign_grant_session
+    /// and sign_p256_grant_session verify before accepting the assertion.
+    pub async fn begin_grant_session_auth(
+        &self,
+        key_id: &str,
+        origin_header: Option<&str>,
+    ) -> Result<webauthn::AuthenticationOptionsResponse> {
+        let w = self
+            .db
+            .get_wallet(key_id)?
+            .ok_or_else(|| anyhow!("Key not found: {}", key_id))?;
+
+        let allow_credentials = if let Some(ref cid) = w.credential_id {
+            vec![webauthn::CredentialDescriptor {
+                id: cid.clone(),
+                type_: "public-key".to_string(),
+                transports: Some(vec!["internal".to_string(), "hybrid".to_string()]),
+            }]
+        } else {
+            vec![]
+        };
+
+        let rp_id = self.resolve_rp_id(origin_header);
+
+        // #112: source the grant-session challenge from the TA (GetChallenge) so it
+        // lands in the TA's pending-nonce table and the TA can bind it at sign time
+        // (sign_grant_session / sign_p256_grant_session pass Some(final_hash) → payload
+        // commitment). This mirrors the regular BeginAuthentication path and lets the
+        // resolver stop stripping client_data_json (so the TA — not just the host —
+        // verifies the challenge). Same fallback rule as BeginAuthentication.
+        let (challenge_id, challenge_bytes, resp) = self
+            .ta_challenge_options(key_id, &rp_id, allow_credentials, "grant-session")
+            .await?;
+
+        self.db.store_challenge(
+            &challenge_id,
+            &challenge_bytes,
+            Some(key_id),
+            "grant-session",
+            &rp_id,
+            300,
+        )?;
+
+        println!(
+            "📝 WebAuthn BeginGrantSessionAuth: challenge_id={}, key_id={}",
+            challenge_id, key_id
+        );
+        Ok(resp)
+    }
+
+    // ========================================
+    // Agent Key methods
+    // ========================================
+
+    pub async fn create_agent_key(
+        &self,
+        req: CreateAgentKeyRequest,
+    ) -> Result<CreateAgentKeyResponse> {
+        let wallet_id = Self::validate_key_id(&req.human_key_id)?;
+
+        // Verify human wallet exists
+        let _wallet = self
+            .db
+            .get_wallet(&req.human_key_id)?
+            .ok_or_else(|| anyhow!("Human wallet not found: {}", req.human_key_id))?;
+
+        // Agent operations MUST use WebAuthn ceremony (challenge-based) to prevent replay attacks.
+        // Legacy raw passkey assertions lack challenge/origin binding and can be replayed.
+        if req.webauthn_assertion.is_none() {
+            return Err(anyhow!("create-agent-key requires WebAuthn ceremony (BeginAuthentication flow). Legacy passkey assertions are not accepted for agent operations."));
+        }
+        let assertion = self
+            .resolve_passkey_assertion(
+                &req.human_key_id,
+                None, // reject legacy path
+                req.webauthn_assertion.as_ref(),
+                // #115: the TA now re-binds the mint challenge to the label
+                // (Some(mint_label_digest)), so the host must DELEGATE the
+                // challenge-value check (true) — otherwise it rejects the
+                // SHA-256(nonce‖digest) commitment as "not the bare nonce" before
+                // it reaches the TA. (host still verifies sig+origin+rpId+one-time.)
+                true,
+            )
+            .await?;
+        if assertion.is_none() {
+            return Err(anyhow!("Passkey assertion required to create agent key"));
+        }
+
+        let refresh = req
+            .device_fingerprint
+            .as_deref()
+            .map(agent_jwt::device_hash)
+            .transpose()?
+            .map(|device_hash| proto::AgentRefresh {
+                device_hash,
+                secret: None,
+            });
+
+        // Atomically allocate the next agent_index (MAX+1 in a single lock acquire).
+        // Avoids the race between count() and insert() that could yield duplicate indices.
+        let agent_index = self.db.next_agent_index_for_wallet(&req.human_key_id)?;
+
+        // Derive agent key in TEE; TA constructs JWT payload internally (no oracle exposure).
+        // TA computes iat from its own clock — host no longer supplies iat.
+        let tee_result = self
+            .tee
+            .create_agent_key(
+                wallet_id,
+                agent_index,
+                &req.human_key_id,
+                24 * 3600, // #115: 24h cap (was 3d)
+                assertion,
+                &req.label, // #115: bound into mint commitment
+                false,      // #115: CREATE (binds label)
+                refresh.clone(),
+            )
+            .await?;
+        let agent_address = format!("0x{}", hex::encode(&tee_result.agent_address));
+        let pubkey_hex = hex::encode(&tee_result.public_key_compressed);
+        let derivation_path = format!("m/44'/60'/0'/1/{}", agent_index);
+
+        // Assemble JWT from TEE-produced material (no host-side signing)
+        let (jwt, expires_at) = agent_jwt::assemble_jwt(&tee_result)?;
+
+        // Store in DB — only credential_hash, never the full JWT
+        let now = Utc::now().to_rfc3339();
+        let cred_hash = agent_jwt::credential_hash(&jwt);
+        self.db.insert_agent_key(&AgentKeyRow {
+            wallet_id: req.human_key_id.clone(),
+            agent_index,
+            human_id: req.human_key_id.clone(),
+            agent_address: agent_address.clone(),
+            public_key_compressed: pubkey_hex,
+            credential_hash: Some(cred_hash),
+            credential_jwt: None,
+            credential_expires_at: Some(expires_at),
+            status: "active".to_string(),
+            created_at: now.clone(),
+            updated_at: now,
+            revoked_at: None,
+        })?;
+
+        let key_id = format!("{}:{}", req.human_key_id, agent_index);
+        let refresh_token =
+            self.open_refresh_family(&key_id, &req.human_key_id, refresh.as_ref(), &tee_result)?;
+        println!(
+            "✅ CreateAgentKey: wallet={} idx={} addr={}",
+            req.human_key_id, agent_index, agent_address
+        );
+
+        Ok(CreateAgentKeyResponse {
+            key_id,
+            agent_address,
+            derivation_path,
+            agent_credential: jwt,
+            expires_at,
+            refresh_token,
+        })
+    }
+
+    pub async fn sign_agent(
+        &self,
+        bearer_jwt: String,
+        req: SignAgentRequest,
+    ) -> Result<SignAgentResponse> {
+        // Verify JWT via TEE HMAC
+        let payload = agent_jwt::verify_credential(&self.tee, &bearer_jwt)
+            .await
+            .map_err(|e| anyhow!("Invalid agent credential: {}", e))?;
+
+        // Validate keyId matches JWT payload
+        let (wallet_uuid, agent_index) = parse_agent_key_id(&req.key_id)?;
+        let wallet_id_str = wallet_uuid.to_string();
+        if payload.wallet_id != wallet_id_str || payload.agent_index != agent_index {
+            return Err(anyhow!("keyId does not match agent credential"));
+        }
+        // Issue #42: reject dormant/frozen parent wallet before any TEE signing.
+        self.ensure_not_frozen(&wallet_id_str)?;
+
+        // Per-credential rate limit (design §2.2): prevents single compromised key from
+        // flooding TEE signing. Keyed by wallet_id/agent_index — independent of global API key limit.
+        let cred_rl_key = format!("{}/{}", wallet_id_str, agent_index);
+        self.agent_rate_limiter
+            .check(&cred_rl_key)
+            .map_err(|limit| {
+                anyhow!(
+                    "Per-credential rate limit exceeded ({}/min). Retry after 60s.",
+                    limit
+                )
+            })?;
+
+        // Check agent key is active in DB + credential_hash matches
+        let agent_key = self
+            .db
+            .get_agent_key(&wallet_id_str, agent_index)?
+            .ok_or_else(|| anyhow!("Agent key not found: {}", req.key_id))?;
+        if agent_key.status != "active" {
+            return Err(anyhow!("Agent key is revoked"));
+        }
+        let current_hash = agent_jwt::credential_hash(&bearer_jwt);
+        if agent_key.credential_hash.as_deref() != Some(current_hash.as_str()) {
+            return Err(anyhow!("Agent credential has been superseded or revoked"));
+        }
+
+        // Validate userOpHash (exactly 32 bytes)
+        let user_op_hash = Self::validate_hash_hex(&req.payload)?;
+
+        // Parse accountAddress — must be a 20-byte hex string (with or without 0x prefix)
+        let account_address = Self::parse_address_hex(&req.account_address)
+            .map_err(|e| anyhow!("Invalid accountAddress: {}", e))?;
+
+        // Extract JWT proof for TA-side authorization (defense-in-depth)
+        let (jwt_kid, jwt_signing_input, jwt_hmac) = agent_jwt::extract_signing_proof(&bearer_jwt)
+            .map_err(|e| anyhow!("Failed to extract JWT proof: {}", e))?;
+
+        // Sign in TEE — TA re-verifies JWT HMAC before signing; EIP-191 inside TEE; V=27/28
+        // Returns 106-byte v0.17.2 format: [0x08][account(20)][key(20)][ECDSA(65)]
+        let sig_bytes = self
+            .tee
+            .sign_agent_user_op(
+                wallet_uuid,
+                agent_index,
+                &user_op_hash,
+                jwt_kid,
+                jwt_signing_input,
+                jwt_hmac,
+                account_address,
+            )
+            .await?;
+
+        println!(
+            "✅ SignAgent: wallet={} idx={} addr={}",
+            wallet_id_str, agent_index, agent_key.agent_address
+        );
+
+        Ok(SignAgentResponse {
+            key_id: req.key_id,
+            agent_address: agent_key.agent_address,
+            signature: format!("0x{}", hex::encode(&sig_bytes)),
+        })
+    }
+
+    pub async fn sign_typed_data(
+        &self,
+        bearer: Option<String>,
+        req: SignTypedDataRequest,
+    ) -> Result<SignTypedDataResponse> {
+        let wallet_id = Self::validate_key_id(&req.key_id)?;
+        let wallet_id_str = wallet_id.to_string();
+        // Issue #42: reject dormant/frozen keys before any TEE call. Covers the
+        // EIP-712 family (voucher / gtoken / x402 all route through this method).
+        self.ensure_not_frozen(&wallet_id_str)?;
+
+        // Auth gate: require one of two paths.
+        // Path A — Bearer JWT (agent key): user previously authorized via WebAuthn; checked against
+        //           DB active-status, credential_hash, and per-credential rate limit. JWT path is
+        //           locked to the agent's own derivation path — it cannot escalate to the owner root key.
+        // Path B — WebAuthn ceremony assertion: live, challenge-bound proof of ownership; replay-resistant.
+        // Legacy raw passkeyAssertion is NOT accepted for sign-typed-data (no challenge binding → replay risk).
+        // No auth at all → reject.
+        let passkey_assertion = match (&bearer, &req.webauthn_assertion) {
+            (Some(jwt), _) => {
+                // Path A: agent key JWT
+                let payload = agent_jwt::verify_credential(&self.tee, jwt)
+                    .await
+                    .map_err(|e| anyhow!("Invalid agent credential for sign-typed-data: {}", e))?;
+                if payload.wallet_id != wallet_id_str {
+                    return Err(anyhow!("Agent credential wallet does not match keyId"));
+                }
+
+                // Enforce: JWT can only sign on its own agent derivation path, not the owner root key.
+                let expected_path = format!("m/44'/60'/0'/1/{}", payload.agent_index);
+                if req.hd_path != expected_path {
+                    return Err(anyhow!(
+                        "Agent credential may only sign typed-data on path '{}' (requested '{}'). \
+                         Use WebAuthn to sign on other paths.",
+                        expected_path,
+                        req.hd_path
+                    ));
+                }
+
+                // DB checks: active status + credential_hash match (same pattern as sign_agent)
+                let agent_key = self
+                    .db
+                    .get_agent_key(&wallet_id_str, payload.agent_index)?
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "Agent key not found: {}:{}",
+                            wallet_id_str,
+                            payload.agent_index
+                        )
+                    })?;
+                if agent_key.status != "active" {
+                    return Err(anyhow!("Agent key is revoked"));
+                }
+                let current_hash = agent_jwt::credential_hash(jwt);
+                if agent_key.credential_hash.as_deref() != Some(current_hash.as_str()) {
+                    return Err(anyhow!("Agent credential has been superseded or revoked"));
+                }
+
+                // Per-credential rate limit (shared key with sign_agent — same credential budget)
+                let cred_rl_key = format!("{}/{}", wallet_id_str, payload.agent_index);
+                self.agent_rate_limiter
+                    .check(&cred_rl_key)
+                    .map_err(|limit| {
+                        anyhow!(
+                            "Per-credential rate limit exceeded ({}/min). Retry after 60s.",
+                            limit
+                        )
+                    })?;
+
+                None // no passkey forwarded to TA; JWT auth is host-enforced
+            }
+            (None, Some(_)) => {
+                // Path B: WebAuthn ceremony (preferred, replay-protected, no hdPath restriction)
+                // #110: the TA's sign_typed_data binds the challenge to the EIP-712
+                // digest — verify_passkey_for_wallet(..., Some(&digest)) — so the signed
+                // challenge is the payload COMMITMENT, not the bare nonce. Delegate the
+                // challenge-value check to the TA (true), exactly like SignHash/Sign;
+                // otherwise the host's bare-nonce check blocks the commitment (liveness)
+                // and, in transition, the typed-data payload stays unbound (V4 CA-swap
+                // hole). Covers the convenience signers (micropayment / GToken / x402)
+                // that route through sign_typed_data.
+                let assertion = self
+                    .resolve_passkey_assertion(
+                        &req.key_id,
+                        None,
+                        req.webauthn_assertion.as_ref(),
+                        true,
+                    )
+                    .await?;
+                if assertion.is_none() {
+                    return Err(anyhow!(
+                        "WebAuthn assertion verification failed for sign-typed-data"
+                    ));
+                }
+                assertion
+            }
+            (None, None) => {
+                return Err(anyhow!(
+                    "sign-typed-data requires authentication: \
+                     provide Authorization: Bearer <agent-jwt> OR webAuthnAssertion. \
+                     Legacy passkeyAssertion is not accepted for this endpoint."
+                ));
+            }
+        };
+
+        // Convert domain verifyingContract from hex string to [u8; 20]
+        let verifying_contract = match &req.domain.verifying_contract {
+            Some(hex_str) => {
+                let bytes = hex::decode(hex_str.trim_start_matches("0x"))
+                    .map_err(|e| anyhow!("Invalid verifyingContract hex: {}", e))?;
+                if bytes.len() != 20 {
+                    return Err(anyhow!(
+                        "verifyingContract must be 20 bytes, got {}",
+                        bytes.len()
+                    ));
+                }
+                let mut arr = [0u8; 20];
+                arr.copy_from_slice(&bytes);
+                Some(arr)
+            }
+            None => None,
+        };
+
+        // Convert JSON types to proto types
+        let domain = proto::Eip712Domain {
+            name: req.domain.name.clone(),
+            version: req.domain.version.clone(),
+            chain_id: req.domain.chain_id,
+            verifying_contract,
+        };
+
+        let types: Vec<proto::Eip712TypeDef> = req
+            .types
+            .iter()
+            .map(|td| proto::Eip712TypeDef {
+                name: td.name.clone(),
+                fields: td
+                    .fields
+                    .iter()
+                    .map(|f| proto::Eip712TypeField {
+                        name: f.name.clone(),
+                        field_type: f.field_type.clone(),
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        // Find the primary type definition to help with value conversion
+        let primary_type_def = req
+            .types
+            .iter()
+            .find(|td| td.name == req.primary_type)
+            .ok_or_else(|| anyhow!("Primary type '{}' not in types list", req.primary_type))?;
+
+        // Convert JSON field values to proto Eip712Value using declared field types for guidance
+        let message = req
+            .message
+            .iter()
+            .map(|fv| {
+                let declared_type = primary_type_def
+                    .fields
+                    .iter()
+                    .find(|f| f.name == fv.name)
+                    .map(|f| f.field_type.as_str())
+                    .unwrap_or("");
+                let value = json_to_eip712_value(&fv.value, declared_type)?;
+                Ok(proto::Eip712FieldValue {
+                    name: fv.name.clone(),
+                    value,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        // Extract JWT proof for TA-side verification on JWT path (defense-in-depth).
+        let (jwt_kid, jwt_signing_input, jwt_hmac) = if let Some(ref jwt) = bearer {
+            let (kid, si, hmac) = agent_jwt::extract_signing_proof(jwt)
+                .map_err(|e| anyhow!("Failed to extract JWT proof: {}", e))?;
+            (Some(kid), Some(si), Some(hmac))
+        } else {
+            (None, None, None)
+        };
+
+        let ta_input = proto::SignTypedDataInput {
+            wallet_id,
+            hd_path: req.hd_path.clone(),
+            domain,
+            primary_type: req.primary_type.clone(),
+            types,
+            message,
+            passkey_assertion,
+            jwt_kid,
+            jwt_signing_input,
+            jwt_hmac,
+        };
+
+        let output = self.tee.sign_typed_data(ta_input).await?;
+
+        println!(
+            "✅ SignTypedData: keyId={} primaryType={}",
+            req.key_id, req.primary_type
+        );
+        Ok(SignTypedDataResponse {
+            key_id: req.key_id,
+            signature: format!("0x{}", hex::encode(&output.signature)),
+        })
+    }
+
+    // P2 convenience signers build a fixed EIP-712 struct in the host and delegate to
+    // sign_typed_data(), inheriting its auth gate (Bearer agent-JWT OR replay-protected
+    // WebAuthn ceremony; legacy raw passkey is rejected). They add no new TA command.
+
+    pub async fn sign_micropayment_voucher(
+        &self,
+        bearer: Option<String>,
+        req: SignMicropaymentVoucherRequest,
+    ) -> Result<SignTypedDataResponse> {
+        let std_req = SignTypedDataRequest {
+            key_id: req.key_id,
+            hd_path: req.hd_path,
+            domain: JsonEip712Domain {
+                name: Some("MicroPaymentChannel".to_string()),
+                // EIP-712 version must match the contract's _domainNameAndVersion()
+                // (MicroPaymentChannel.sol returns "1.0.0"); "1" would make the
+                // domainSeparator differ and voucher signatures fail on-chain (#21).
+                version: Some("1.0.0".to_string()),
+                chain_id: Some(req.chain_id),
+                verifying_contract: Some(req.verifying_contract),
+            },
+            primary_type: "Voucher".to_string(),
+            types: vec![JsonEip712TypeDef {
+                name: "Voucher".to_string(),
+                fields: vec![
+                    JsonEip712TypeField {
+                        name: "channelId".to_string(),
+                        field_type: "bytes32".to_string(),
+                    },
+                    JsonEip712TypeField {
+                        name: "cumulativeAmount".to_string(),
+                        field_type: "uint256".to_string(),
+                    },
+                ],
+            }],
+            message: vec![
+                JsonEip712FieldValue {
+                    name: "channelId".to_string(),
+                    value: serde_json::Value::String(req.channel_id),
+                },
+                JsonEip712FieldValue {
+                    name: "cumulativeAmount".to_string(),
+                    value: serde_json::Value::String(req.cumulative_amount),
+                },
+            ],
+            webauthn_assertion: req.webauthn_assertion,
+            passkey_assertion: None,
+        };
+        self.sign_typed_data(bearer, std_req).await
+    }
+
+    pub async fn sign_gtoken_authorization(
+        &self,
+        bearer: Option<String>,
+        req: SignGTokenAuthorizationRequest,
+    ) -> Result<SignTypedDataResponse> {
+        // #52: verify `from` equals the address actually derived from keyId+hdPath.
+        // EIP-3009 TransferWithAuthorization is checked on-chain by
+        // ecrecover(hash, sig) == from; signing with a key whose address != from
+        // makes the transfer revert and wastes the user's gas. Catch it here with a
+        // clear error. The address must already be cached (DeriveAddress called at
+        // least once for this key+path); if not, refuse rather than risk a revert.
+        match self.db.address_for_key_path(&req.key_id, &req.hd_path)? {
+            Some(addr) => {
+                // Normalize both sides — strip an optional 0x/0X prefix and
+                // lower-case — so a mere format difference ("0xABCD" vs "abcd")
+                // is not treated as a from-mismatch. Ethereum addresses are
+                // case-insensitive at the byte level (EIP-55 checksum is
+                // display-only), and callers may or may not include the prefix.
+                let norm = |s: &str| {
+                    s.strip_prefix("0x")
+                        .or_else(|| s.strip_prefix("0X"))
+                        .unwrap_or(s)
+                        .to_ascii_lowercase()
+                };
+                if norm(&addr) != norm(&req.from) {
+                    return Err(anyhow!(
+                        "from {} does not match the address derived from keyId+hdPath \
+                         ({}); EIP-3009 would revert on-chain",
+                        req.from,
+                        addr
+                    ));
+                }
+            }
+            None => {
+                return Err(anyhow!(
+                    "no cached address for this keyId+hdPath — call DeriveAddress first \
+                     so the signer can verify `from` (prevents an on-chain EIP-3009 revert)"
+                ));
+            }
+        }
+
+        let std_req = SignTypedDataRequest {
+            key_id: req.key_id,
+            hd_path: req.hd_path,
+            domain: JsonEip712Domain {
+                name: Some("GToken".to_string()),
+                version: Some("1".to_string()),
+                chain_id: Some(req.chain_id),
+                verifying_contract: Some(req.gtoken_address),
+            },
+            primary_type: "TransferWithAuthorization".to_string(),
+            types: vec![JsonEip712TypeDef {
+                name: "TransferWithAuthorization".to_string(),
+                fields: vec![
+                    JsonEip712TypeField {
+                        name: "from".to_string(),
+                        field_type: "address".to_string(),
+                    },
+                    JsonEip712TypeField {
+                        name: "to".to_string(),
+                        field_type: "address".to_string(),
+                    },
+                    JsonEip712TypeField {
+                        name: "value".to_string(),
+                        field_type: "uint256".to_string(),
+                    },
+                    JsonEip712TypeField {
+                        name: "validAfter".to_string(),
+                        field_type: "uint256".to_string(),
+                    },
+                    JsonEip712TypeField {
+                        name: "validBefore".to_string(),
+                        field_type: "uint256".to_string(),
+                    },
+                    JsonEip712TypeField {
+                        name: "nonce".to_string(),
+                        field_type: "bytes32".to_string(),
+                    },
+                ],
+            }],
+            message: vec![
+                JsonEip712FieldValue {
+                    name: "from".to_string(),
+                    value: serde_json::Value::String(req.from),
+                },
+                JsonEip712FieldValue {
+                    name: "to".to_string(),
+                    value: serde_json::Value::String(req.to),
+                },
+                JsonEip712FieldValue {
+                    name: "value".to_string(),
+                    value: serde_json::Value::String(req.value),
+                },
+                JsonEip712FieldValue {
+                    name: "validAfter".to_string(),
+                    value: serde_json::Value::String(req.valid_after),
+                },
+                JsonEip712FieldValue {
+                    name: "validBefore".to_string(),
+                    value: serde_json::Value::String(req.valid_before),
+                },
+                JsonEip712FieldValue {
+                    name: "nonce".to_string(),
+                    value: serde_json::Value::String(req.nonce),
+                },
+            ],
+            webauthn_assertion: req.webauthn_assertion,
+            passkey_assertion: None,
+        };
+        self.sign_typed_data(bearer, std_req).await
+    }
+
+    pub async fn sign_x402_payment(
+        &self,
+        bearer: Option<String>,
+        req: SignX402PaymentRequest,
+    ) -> Result<SignTypedDataResponse> {
+        let std_req = SignTypedDataRequest {
+            key_id: req.key_id,
+            hd_path: req.hd_path,
+            domain: JsonEip712Domain {
+                name: Some("SuperPaymaster".to_string()),
+                version: Some("1".to_string()),
+                chain_id: Some(req.chain_id),
+                verifying_contract: Some(req.verifying_contract),
+            },
+            primary_type: "PaymentPayload".to_string(),
+            types: vec![JsonEip712TypeDef {
+                name: "PaymentPayload".to_string(),
+                fields: vec![
+                    JsonEip712TypeField {
+                        name: "paymentId".to_string(),
+                        field_type: "bytes32".to_string(),
+                    },
+                    JsonEip712TypeField {
+                        name: "amount".to_string(),
+                        field_type: "uint256".to_string(),
+                    },
+                    JsonEip712TypeField {
+                        name: "recipient".to_string(),
+                        field_type: "address".to_string(),
+                    },
+                    JsonEip712TypeField {
+                        name: "deadline".to_string(),
+                        field_type: "uint256".to_string(),
+                    },
+                ],
+            }],
+            message: vec![
+                JsonEip712FieldValue {
+                    name: "paymentId".to_string(),
+                    value: serde_json::Value::String(req.payment_id),
+                },
+                JsonEip712FieldValue {
+                    name: "amount".to_string(),
+                    value: serde_json::Value::String(req.amount),
+                },
+                JsonEip712FieldValue {
+                    name: "recipient".to_string(),
+                    value: serde_json::Value::String(req.recipient),
+                },
+                JsonEip712FieldValue {
+                    name: "deadline".to_string(),
+                    value: serde_json::Value::String(req.deadline),
+                },
+            ],
+            webauthn_assertion: req.webauthn_assertion,
+            passkey_assertion: None,
+        };
+        self.sign_typed_data(bearer, std_req).await
+    }
+
+    pub async fn sign_grant_session(
+        &self,
+        req: SignGrantSessionRequest,
+    ) -> Result<SignGrantSessionResponse> {
+        let wallet_id = Self::validate_key_id(&req.key_id)?;
+        let key_id_str = wallet_id.to_string();
+        // Issue #42: reject dormant/frozen keys before any TEE call.
+        self.ensure_not_frozen(&key_id_str)?;
+
+        // Grant signing requires purpose-bound WebAuthn challenge to prevent
+        // cross-operation replay. Challenge must have been created by begin-grant-session-auth.
+        let wa = req.webauthn_assertion.as_ref().ok_or_else(|| {
+            anyhow!("sign-grant-session requires WebAuthn ceremony started via /kms/begin-grant-session-auth")
+        })?;
+        let passkey_assertion = Some(
+            self.resolve_grant_passkey_assertion(&key_id_str, wa, "grant-session")
+                .await?,
+        );
+
+        // expiry is uint48 in the contract — reject out-of-range values to keep hash match
+        const UINT48_MAX: u64 = (1u64 << 48) - 1;
+        if req.expiry > UINT48_MAX {
+            return Err(anyhow!(
+                "expiry {} exceeds uint48 max ({})",
+                req.expiry,
+                UINT48_MAX
+            ));
+        }
+
+        let verifying_contract = Self::parse_address_hex(&req.verifying_contract)?;
+        let account = Self::parse_address_hex(&req.account)?;
+        let session_key = Self::parse_address_hex(&req.session_key)?;
+        let contract_scope = Self::parse_address_hex(&req.contract_scope)?;
+        let selector_scope = parse_bytes4_hex(&req.selector_scope)?;
+
+        let mut call_targets = Vec::with_capacity(req.call_targets.len());
+        for s in &req.call_targets {
+            call_targets.push(Self::parse_address_hex(s)?);
+        }
+        let mut selector_allowlist = Vec::with_capacity(req.selector_allowlist.len());
+        for s in &req.selector_allowlist {
+            selector_allowlist.push(parse_bytes4_hex(s)?);
+        }
+
+        let mut nonce = [0u8; 32];
+        nonce[24..32].copy_from_slice(&req.nonce.to_be_bytes());
+
+        let ta_input = proto::SignGrantSessionInput {
+            wallet_id,
+            hd_path: req.hd_path,
+            chain_id: req.chain_id,
+            verifying_contract,
+            account,
+            session_key,
+            expiry: req.expiry,
+            contract_scope,
+            selector_scope,
+            velocity_limit: req.velocity_limit,
+            velocity_window: req.velocity_window,
+            call_targets,
+            selector_allowlist,
+            nonce,
+            passkey_assertion,
+        };
+
+        let output = self.tee.sign_grant_session(ta_input).await?;
+        println!("✅ SignGrantSession: keyId={}", req.key_id);
+        Ok(SignGrantSessionResponse {
+            key_id: req.key_id,
+            signature: format!("0x{}", hex::encode(&output.signature)),
+        })
+    }
+
+    pub async fn sign_p256_grant_session(
+        &self,
+        req: SignP256GrantSessionRequest,
+    ) -> Result<SignP256GrantSessionResponse> {
+        let wallet_id = Self::validate_key_id(&req.key_id)?;
+        let key_id_str = wallet_id.to_string();
+        // Issue #42: reject dormant/frozen keys before any TEE call.
+        self.ensure_not_frozen(&key_id_str)?;
+
+        let wa = req.webauthn_assertion.as_ref().ok_or_else(|| {
+            anyhow!("sign-p256-grant-session requires WebAuthn ceremony started via /kms/begin-grant-session-auth")
+        })?;
+        let passkey_assertion = Some(
+            self.resolve_grant_passkey_assertion(&key_id_str, wa, "grant-session")
+                .await?,
+        );
+
+        const UINT48_MAX: u64 = (1u64 << 48) - 1;
+        if req.expiry > UINT48_MAX {
+            return Err(anyhow!(
+                "expiry {} exceeds uint48 max ({})",
+                req.expiry,
+                UINT48_MAX
+            ));
+        }
+
+        let verifying_contract = Self::parse_address_hex(&req.verifying_contract)?;
+        let account = Self::parse_address_hex(&req.account)?;
+        let key_x = Self::validate_hash_hex(&req.key_x)?;
+        let key_y = Self::validate_hash_hex(&req.key_y)?;
+        let contract_scope = Self::parse_address_hex(&req.contract_scope)?;
+        let selector_scope = parse_bytes4_hex(&req.selector_scope)?;
+
+        let mut call_targets = Vec::with_capacity(req.call_targets.len());
+        for s in &req.call_targets {
+            call_targets.push(Self::parse_address_hex(s)?);
+        }
+        let mut selector_allowlist = Vec::with_capacity(req.selector_allowlist.len());
+        for s in &req.selector_allowlist {
+            selector_allowlist.push(parse_bytes4_hex(s)?);
+        }
+
+        let mut nonce = [0u8; 32];
+        nonce[24..32].copy_from_slice(&req.nonce.to_be_bytes());
+
+        let ta_input = proto::SignP256GrantSessionInput {
+            wallet_id,
+            hd_path: req.hd_path,
+            chain_id: req.chain_id,
+            verifying_contract,
+            account,
+            key_x,
+            key_y,
+            expiry: req.expiry,
+            contract_scope,
+            selector_scope,
+            velocity_limit: req.velocity_limit,
+            velocity_window: req.velocity_window,
+            call_targets,
+            selector_allowlist,
+            nonce,
+            passkey_assertion,
+        };
+
+        let output = self.tee.sign_p256_grant_session(ta_input).await?;
+        println!("✅ SignP256GrantSession: keyId={}", req.key_id);
+        Ok(SignP256GrantSessionResponse {
+            key_id: req.key_id,
+            signature: format!("0x{}", hex::encode(&output.signature)),
+        })
+    }
+
+    pub async fn refresh_agent_credential(
+        &self,
+        bearer_jwt: String,
+        req: RefreshAgentCredentialRequest,
+    ) -> Result<CreateAgentKeyResponse> {
+        // Verify current JWT is still valid
+        let payload = agent_jwt::verify_credential(&self.tee, &bearer_jwt)
+            .await
+            .map_err(|e| anyhow!("Invalid agent credential: {}", e))?;
+
+        let (wallet_uuid, agent_index) = parse_agent_key_id(&req.key_id)?;
+        let wallet_id_str = wallet_uuid.to_string();
+        if payload.wallet_id != wallet_id_str || payload.agent_index != agent_index {
+            return Err(anyhow!("keyId does not match agent credential"));
+        }
+
+        // Require WebAuthn ceremony for replay protection (no legacy passkey path)
+        if req.webauthn_assertion.is_none() {
+            return Err(anyhow!("refresh-agent-credential requires WebAuthn ceremony. Legacy passkey assertions are not accepted for agent operations."));
+        }
+        let assertion = self
+            .resolve_passkey_assertion(
+                &wallet_id_str,
+                None, // reject legacy path
+                req.webauthn_assertion.as_ref(),
+                // #115: refresh re-mints via CreateAgentKey, which the TA now binds to
+                // the (empty) label digest → delegate the challenge-value check to the TA.
+                true,
+            )
+            .await?;
+        if assertion.is_none() {
+            return Err(anyhow!(
+                "Passkey assertion required to refresh agent credential"
+            ));
+        }
+
+        // Check agent key is active
+        let agent_key = self
+            .db
+            .get_agent_key(&wallet_id_str, agent_index)?
+            .ok_or_else(|| anyhow!("Agent key not found: {}", req.key_id))?;
+        if agent_key.status != "active" {
+            return Err(anyhow!("Agent key is revoked"));
+        }
+        let refresh = req
+            .device_fingerprint
+            .as_deref()
+            .map(agent_jwt::device_hash)
+            .transpose()?
+            .map(|device_hash| proto::AgentRefresh {
+                device_hash,
+                secret: None,
+            });
+
+        // Re-derive agent key in TEE with fresh JWT (same key, new TTL — idempotent derivation).
+        // TA computes iat from its own clock — host no longer supplies iat.
+        // #115: refresh does not (re)set a label — the key identity is fixed by key_id —
+        // so bind the empty label (digest still binds wallet_id). The refreshing client
+        // commits to the empty label likewise.
+        let tee_result = self
+            .tee
+            .create_agent_key(
+                wallet_uuid,
+                agent_index,
+                &wallet_id_str,
+                24 * 3600, // #115: 24h cap (was 3d)
+                assertion,
+                "",   // #115: refresh binds the index, not a label
+                true, // #115: REFRESH (binds agent_index under a distinct tag)
+                refresh.clone(),
+            )
+            .await?;
+        let (new_jwt, expires_at) = agent_jwt::assemble_jwt(&tee_result)?;
+
+        let cred_hash = agent_jwt::credential_hash(&new_jwt);
+        self.db
+            .update_agent_credential(&wallet_id_str, agent_index, &cred_hash, expires_at)?;
+        let refresh_token =
+            self.open_refresh_family(&req.key_id, &wallet_id_str, refresh.as_ref(), &tee_result)?;
+
+        let derivation_path = format!("m/44'/60'/0'/1/{}", agent_index);
+        println!(
+            "✅ RefreshAgentCredential: wallet={} idx={}",
+            wallet_id_str, agent_index
+        );
+
+        Ok(CreateAgentKeyResponse {
+            key_id: req.key_id,
+            agent_address: agent_key.agent_address,
+            derivation_path,
+            agent_credential: new_jwt,
+            expires_at,
+            refresh_token,
+        })
+    }
+
+    /// Record the family a passkey mint opened and hand out its first token.
+    fn open_refresh_family(
+        &self,
+        key_id: &str,
+        wallet_id: &str,
+        refresh: Option<&proto::AgentRefresh>,
+        tee_result: &proto::CreateAgentKeyOutput,
+    ) -> Result<Option<String>> {
+        let (refresh, secret) = match (refresh, tee_result.refresh_secret) {
+            (Some(r), Some(s)) => (r, s),
+            (Some(_), None) => return Err(anyhow!("TA did not issue a refresh token")),
+            (None, _) => return Ok(None),
+        };
+        let credential_id = self.db.get_wallet(wallet_id)?.and_then(|w| w.credential_id);
+        self.db.open_refresh_family(
+            key_id,
+            wallet_id,
+            credential_id.as_deref(),
+            &hex::encode(refresh.device_hash),
+        )?;
+        Ok(Some(agent_jwt::format_refresh_token(key_id, &secret)))
+    }
+
+    /// Exchange a refresh token for a new agent JWT and the next refresh token,
+    /// without a passkey ceremony. The TA checks the secret, device and passkey
+    /// and closes the family on reuse; the CA refuses a family it already knows
+    /// is revoked (passkey changed, agent revoked, earlier reuse).
+    pub async fn auth_refresh(&self, req: AuthRefreshRequest) -> Result<CreateAgentKeyResponse> {
+        let (key_id, secret) = agent_jwt::parse_refresh_token(&req.refresh_token)?;
+        let device_hash = agent_jwt::device_hash(&req.device_fingerprint)?;
+        let (wallet_uuid, agent_index) = parse_agent_key_id(&key_id)?;
+        let wallet_id_str = wallet_uuid.to_string();
+
+        let family = self
+            .db
+            .refresh_family(&key_id)?
+            .ok_or_else(|| anyhow!("Unknown refresh token"))?;
+        if family.status != "active" {
+            return Err(anyhow!(
+                "Refresh token revoked ({}); authenticate with the passkey",
+                family.revoked_reason.as_deref().unwrap_or("revoked")
+            ));
+        }
+        let wallet = self
+            .db
+            .get_wallet(&wallet_id_str)?
+            .ok_or_else(|| anyhow!("Human wallet not found: {}", wallet_id_str))?;
+        if wallet.credential_id != family.credential_id {
+            self.db
+                .revoke_refresh_family(&key_id, "credential removed")?;
+            return Err(anyhow!(
+                "Refresh token revoked (credential removed); authenticate with the passkey"
+            ));
+        }
+        let agent_key = self
+            .db
+            .get_agent_key(&wallet_id_str, agent_index)?
+            .ok_or_else(|| anyhow!("Agent key not found: {}", key_id))?;
+        if agent_key.status != "active" {
+            self.db.revoke_refresh_family(&key_id, "agent revoked")?;
+            return Err(anyhow!("Agent key is revoked"));
+        }
+
+        let tee_result = match self
+            .tee
+            .create_agent_key(
+                wallet_uuid,
+                agent_index,
+                &wallet_id_str,
+                24 * 3600,
+                None,
+                "",
+                true,
+                Some(proto::AgentRefresh {
+                    device_hash,
+                    secret: Some(secret),
+                }),
+            )
+            .await
+        {
+            Ok(r) => r,
+            Err(e) => {
+                // The TA deleted the family; mirror it so later attempts stop here.
+                if e.to_string().contains("refresh token family revoked") {
+                    let _ = self.db.revoke_refresh_family(&key_id, "reuse or mismatch");
+                    eprintln!("🔴 AuthRefresh: family {} closed by the TA: {}", key_id, e);
+                }
+                return Err(e);
+            }
+        };
+        let next = tee_result
+            .refresh_secret
+            .ok_or_else(|| anyhow!("TA did not issue the next refresh token"))?;
+        let (new_jwt, expires_at) = agent_jwt::assemble_jwt(&tee_result)?;
+        self.db.update_agent_credential(
+            &wallet_id_str,
+            agent_index,
+            &agent_jwt::credential_hash(&new_jwt),
+            expires_at,
+        )?;
+        self.db.advance_refresh_family(&key_id)?;
+        println!(
+            "✅ AuthRefresh: wallet={} idx={} generation={}",
+            wallet_id_str,
+            agent_index,
+            family.generation + 1
+        );
+
+        Ok(CreateAgentKeyResponse {
+            agent_address: agent_key.agent_address,
+            derivation_path: format!("m/44'/60'/0'/1/{}", agent_index),
+            agent_credential: new_jwt,
+            expires_at,
+            refresh_token: Some(agent_jwt::format_refresh_token(&key_id, &next)),
+            key_id,
+        })
+    }
+
+    pub async fn revoke_agent_credential(
+        &self,
+        req: RevokeAgentCredentialRequest,
+    ) -> Result<RevokeAgentCredentialResponse> {
+        let (wallet_uuid, agent_index) = parse_agent_key_id(&req.key_id)?;
+        let wallet_id_str = wallet_uuid.to_string();
+
+        // Require WebAuthn ceremony for replay protection (no legacy passkey path)
+        if req.webauthn_assertion.is_none() {
+            return Err(anyhow!("revoke-agent-credential requires WebAuthn ceremony. Legacy passkey assertions are not accepted for agent operations."));
+        }
+        let assertion = self
+            .resolve_passkey_assertion(
+                &wallet_id_str,
+                None, // reject legacy path
+                req.webauthn_assertion.as_ref(),
+                false, // #110: agent path is host-authoritative → keep challenge==nonce
+            )
+            .await?;
+        if assertion.is_none() {
+            return Err(anyhow!("Passkey assertion required to revoke agent key"));
+        }
+
+        // Revoke in DB
+        let revoked = self.db.revoke_agent_key(&wallet_id_str, agent_index)?;
+        self.db
+            .revoke_refresh_family(&req.key_id, "agent revoked")?;
+        if !revoked {
+            return Err(anyhow!(
+                "Agent key not found or already revoked: {}",
+                req.key_id
+            ));
+        }
+
+        let revoked_at = Utc::now().timestamp();
+        println!(
+            "✅ RevokeAgentCredential: wallet={} idx={}",
+            wallet_id_str, agent_index
+        );
+
+        Ok(RevokeAgentCredentialResponse {
+            success: true,
+            revoked_at,
+        })
+    }
+
+    /// Lazy GC: delete expired P256 session keys for a wallet from DB and TEE.
+    /// Called silently on create/sign/revoke — errors are logged, never propagated.
+    ///
+    /// Ordering: DB-first (atomic claim) → then TEE delete → then tee_deleted=1.
+    /// Pass 0 (retry): rows with status='revoked' AND tee_deleted=0 from a prior failed
+    /// TEE delete are retried immediately. This closes the ghost-TEE gap where a key
+    /// was claimed in DB but TEE delete failed — such rows are excluded from list_expired
+    /// (status='revoked'), but list_unconfirmed_tee_deletes catches them for retry.
+    ///
+    /// `exclude_session_index`: skip this index during sign (prevents GC-ing the key in use).
+    /// Grace window: gc_cutoff = now - 60s so keys are GC-eligible 60s after credential_expires_at.
+    async fn gc_expired_p256_session_keys(
+        &self,
+        wallet_id_str: &str,
+        wallet_uuid: uuid::Uuid,
+        exclude_session_index: Option<u32>,
+    ) {
+        // Pass 0: Retry TEE deletes that previously failed (DB=revoked, tee_deleted=0).
+        let unconfirmed = match self.db.list_unconfirmed_tee_deletes(wallet_id_str) {
+            Ok(v) => v,
+            Err(e) => {
+                eprintln!(
+                    "⚠️  P256 GC: list_unconfirmed_tee_deletes failed for {}: {}",
+                    wallet_id_str, e
+                );
+                vec![]
+            }
+        };
+        for session_index in unconfirmed {
+            match self
+                .tee
+                .delete_p256_session_key(wallet_uuid, session_index)
+                .await
+            {
+                Ok(_) => {
+                    let _ = self.db.mark_p256_tee_deleted(wallet_id_str, session_index);
+                    println!(
+                        "🗑️  P256 GC retry: confirmed TEE delete for {}:{}",
+                        wallet_id_str, session_index
+                    );
+                }
+                Err(e) => {
+                    eprintln!(
+                        "⚠️  P256 GC retry: TEE delete still failing for {}:{}: {}",
+                        wallet_id_str, session_index, e
+                    );
+                }
+            }
+        }
+
+        // Pass 1: GC newly expired/stuck-pending keys.
+        // 60-second grace window guards against host-clock drift causing premature deletion.
+        let gc_cutoff = Utc::now().timestamp() - 60;
+        let expired = match self.db.list_expired_p256_session_keys(
+            wallet_id_str,
+            gc_cutoff,
+            exclude_session_index,
+        ) {
+            Ok(v) => v,
+            Err(e) => {
+                eprintln!("⚠️  P256 GC: DB query failed for {}: {}", wallet_id_str, e);
+                return;
+            }
+        };
+        for session_index in expired {
+            // Step 1: Atomically claim the key in DB (status → 'revoked', tee_deleted=0).
+            // If claim fails (0 rows: already revoked), skip — another path handled it.
+            let claimed = match self
+                .db
+                .mark_p256_session_key_gc(wallet_id_str, session_index)
+            {
+                Ok(c) => c,
+                Err(e) => {
+                    eprintln!(
+                        "⚠️  P256 GC: DB claim failed for {}:{}: {}",
+                        wallet_id_str, session_index, e
+                    );
+                    continue;
+                }
+            };
+            if !claimed {
+                continue; // already revoked by concurrent path
+            }
+
+            // Step 2: Delete TEE key (idempotent — stuck-pending rows may have no TEE key).
+            match self
+                .tee
+                .delete_p256_session_key(wallet_uuid, session_index)
+                .await
+            {
+                Ok(tee_deleted) => {
+                    let _ = self.db.mark_p256_tee_deleted(wallet_id_str, session_index);
+                    println!(
+                        "🗑️  P256 GC: cleaned {}:{} (tee_deleted={})",
+                        wallet_id_str, session_index, tee_deleted
+                    );
+                }
+                Err(e) => {
+                    // TEE delete failed; DB row is already 'revoked' (key inaccessible via API).
+                    // tee_deleted remains 0 so Pass 0 retries it on the next GC trigger.
+                    eprintln!(
+                        "⚠️  P256 GC: DB claimed but TEE delete failed for {}:{}: {} \
+                         (will retry via unconfirmed-tee-delete pass)",
+                        wallet_id_str, session_index, e
+                    );
+                }
+            }
+        }
+
+        // Pass 2: Physically delete rows that are fully cleaned up (revoked + tee_deleted=1)
+        // and were revoked more than 24 hours ago. Prevents unbounded DB row accumulation.
+        let phys_cutoff = Utc::now().timestamp() - 86400;
+        match self
+            .db
+            .delete_confirmed_revoked_p256_session_keys(wallet_id_str, phys_cutoff)
+        {
+            Ok(0) => {}
+            Ok(n) => println!(
+                "🗑️  P256 GC Pass 2: physically deleted {} rows for {}",
+                n, wallet_id_str
+            ),
+            Err(e) => eprintln!(
+                "⚠️  P256 GC Pass 2: physical delete failed for {}: {}",
+                wallet_id_str, e
+            ),
+        }
+    }
+
+    pub async fn sign_p256_user_op(
+        &self,
+        bearer_jwt: String,
+        req: SignP256UserOpRequest,
+    ) -> Result<SignP256UserOpResponse> {
+        // Verify JWT via TEE HMAC
+        let payload = agent_jwt::verify_credential(&self.tee, &bearer_jwt)
+            .await
+            .map_err(|e| anyhow!("Invalid P256 session credential: {}", e))?;
+
+        // Validate keyId matches JWT payload
+        let (wallet_uuid, session_index) = parse_agent_key_id(&req.key_id)?;
+        let wallet_id_str = wallet_uuid.to_string();
+        if payload.wallet_id != wallet_id_str || payload.agent_index != session_index {
+            return Err(anyhow!("keyId does not match P256 session credential"));
+        }
+        // Issue #42: reject dormant/frozen parent wallet before any TEE signing.
+        self.ensure_not_frozen(&wallet_id_str)?;
+
+        // Lazy GC: clean up other expired P256 session keys for this wallet.
+        // Exclude the current session_index to avoid GC-ing the key being signed.
+        self.gc_expired_p256_session_keys(&wallet_id_str, wallet_uuid, Some(session_index))
+            .await;
+
+        // Per-credential rate limit
+        let cred_rl_key = format!("p256/{}/{}", wallet_id_str, session_index);
+        self.agent_rate_limiter
+            .check(&cred_rl_key)
+            .map_err(|limit| {
+                anyhow!(
+                    "Per-credential rate limit exceeded ({}/min). Retry after 60s.",
+                    limit
+                )
+            })?;
+
+        // Check session key is active and credential_hash matches
+        let session_key = self
+            .db
+            .get_p256_session_key(&wallet_id_str, session_index)?
+            .ok_or_else(|| anyhow!("P256 session key not found: {}", req.key_id))?;
+        if session_key.status != "active" {
+            return Err(anyhow!("P256 session key is revoked"));
+        }
+        let current_hash = agent_jwt::credential_hash(&bearer_jwt);
+        if session_key.credential_hash.as_deref() != Some(current_hash.as_str()) {
+            return Err(anyhow!(
+                "P256 session credential has been superseded or revoked"
+            ));
+        }
+
+        // Validate userOpHash (exactly 32 bytes)
+        let user_op_hash = Self::validate_hash_hex(&req.payload)?;
+
+        // Parse accountAddress
+        let account_address = Self::parse_address_hex(&req.account_address)
+            .map_err(|e| anyhow!("Invalid accountAddress: {}", e))?;
+
+        // Extract JWT proof for TA-side authorization (defense-in-depth)
+        let (jwt_kid, jwt_signing_input, jwt_hmac) = agent_jwt::extract_signing_proof(&bearer_jwt)
+            .map_err(|e| anyhow!("Failed to extract JWT proof: {}", e))?;
+
+        // Sign in TEE — 149-byte P256 format
+        let sig_bytes = self
+            .tee
+            .sign_p256_user_op(
+                wallet_uuid,
+                session_index,
+                &user_op_hash,
+                jwt_kid,
+                jwt_signing_input,
+                jwt_hmac,
+                account_address,
+            )
+            .await?;
+
+        // Post-check: guard against concurrent revocation between the DB active-check above
+        // and the TEE sign. The TEE client uses a single global worker loop, so all TEE
+        // commands are globally serialized — a queued delete cannot physically interleave
+        // with this sign call. However, DB revocation (status → 'revoked') is a plain
+        // SQL UPDATE that can commit any time before the TEE delete is dispatched.
+        // Discard the produced signature if the key was revoked during the TEE call window.
+        // Guarantee: at the time of this post-check query the key was not yet revoked.
+        // Note: revocation could still commit between this SELECT and the HTTP response,
+        // so this is a best-effort defense, not a strict serialization barrier.
+        if self
+            .db
+            .p256_session_key_is_revoked(&wallet_id_str, session_index)?
+        {
+            return Err(anyhow!(
+                "P256 session key was revoked concurrently during signing"
+            ));
+        }
+
+        if sig_bytes.len() != 149 {
+            return Err(anyhow!(
+                "Unexpected P256 signature length: {} (expected 149)",
+                sig_bytes.len()
+            ));
+        }
+
+        println!(
+            "✅ SignP256UserOp: wallet={} idx={}",
+            wallet_id_str, session_index
+        );
+
+        Ok(SignP256UserOpResponse {
+            key_id: req.key_id,
+            pub_key_x: session_key.pub_key_x,
+            pub_key_y: session_key.pub_key_y,
+            signature: format!("0x{}", hex::encode(&sig_bytes)),
+        })
+    }
+
+    pub async fn create_session_key(
+        &self,
+        req: CreateSessionKeyRequest,
+    ) -> Result<CreateSessionKeyResponse> {
+        let wallet_id = Self::validate_key_id(&req.key_id)?;
+        let key_id_str = wallet_id.to_string();
+        self.ensure_not_frozen(&key_id_str)?;
+        Self::validate_derivation_path(&req.hd_path)?;
+
+        if req.webauthn_assertion.is_none() {
+            return Err(anyhow!(
+                "create-session-key requires WebAuthn ceremony. \
+                 Legacy passkey assertions are not accepted."
+            ));
+        }
+        // TA binds the challenge to the permission commitment → delegate (true).
+        let passkey_assertion = self
+            .resolve_passkey_assertion(&key_id_str, None, req.webauthn_assertion.as_ref(), true)
+            .await?
+            .ok_or_else(|| anyhow!("Passkey assertion required to create session key"))?;
+
+        let account =
+            Self::parse_address_hex(&req.account).map_err(|e| anyhow!("Invalid account: {}", e))?;
+        let mut targets = Vec::with_capacity(req.targets.len());
+        for t in &req.targets {
+            targets.push(Self::parse_address_hex(t)?);
+        }
+        let mut selectors = Vec::with_capacity(req.selectors.len());
+        for sel in &req.selectors {
+            selectors.push(parse_bytes4_hex(sel)?);
+        }
+        let permission = proto::SessionPermission {
+            chain_id: req.chain_id,
+            account,
+            targets,
+            selectors,
+            value_cap: parse_wei("valueCap", &req.value_cap)?,
+            expiry: req.expiry,
+        };
+
+        let session_index = self.db.next_scoped_session_index(&key_id_str)?;
+        let output = self
+            .tee
+            .create_scoped_session_key(proto::CreateScopedSessionKeyInput {
+                wallet_id,
+                hd_path: req.hd_path,
+                session_index,
+                permission,
+                passkey_assertion: Some(passkey_assertion),
+            })
+            .await?;
+
+        let session_address = format!("0x{}", hex::encode(output.session_address));
+        let permission_hash = format!("0x{}", hex::encode(output.permission_hash));
+        self.db
+            .insert_scoped_session_key(&crate::db::ScopedSessionKeyRow {
+                wallet_id: key_id_str.clone(),
+                session_index,
+                session_address: session_address.clone(),
+                permission_hash: permission_hash.clone(),
+                chain_id: req.chain_id,
+                expires_at: req.expiry as i64,
+                status: "active".to_string(),
+                created_at: Utc::now().to_rfc3339(),
+                revoked_at: None,
+            })?;
+
+        println!(
+            "✅ CreateSessionKey: wallet={} idx={} addr={}",
+            key_id_str, session_index, session_address
+        );
+        Ok(CreateSessionKeyResponse {
+            session_key_id: format!("{}:{}", key_id_str, session_index),
+            session_address,
+            permission_hash,
+            owner_signature: format!("0x{}", hex::encode(&output.owner_signature)),
+            expires_at: req.expiry,
+        })
+    }
+
+    /// No passkey: the TA enforces the owner-signed scope and revocation list.
+    /// The DB status check only fails fast before a TEE round-trip.
+    pub async fn sign_session_key(
+        &self,
+        req: SignSessionKeyRequest,
+    ) -> Result<SignSessionKeyResponse> {
+        let (wallet_uuid, session_index) = parse_agent_key_id(&req.session_key_id)?;
+        let wallet_id_str = wallet_uuid.to_string();
+        self.ensure_not_frozen(&wallet_id_str)?;
+        match self
+            .db
+            .get_scoped_session_key(&wallet_id_str, session_index)?
+        {
+            Some(row) if row.status == "active" => {}
+            Some(_) => return Err(anyhow!("Session key revoked: {}", req.session_key_id)),
+            None => return Err(anyhow!("Session key not found: {}", req.session_key_id)),
+        }
+
+        let call = proto::SessionCall {
+            target: Self::parse_address_hex(&req.target)
+                .map_err(|e| anyhow!("Invalid target: {}", e))?,
+            selector: parse_bytes4_hex(&req.selector)?,
+            value: parse_wei("value", &req.value)?,
+        };
+        let user_op_hash = Self::validate_hash_hex(&req.user_op_hash)?;
+
+        let signature = self
+            .tee
+            .sign_with_session_key(proto::SignWithSessionKeyInput {
+                wallet_id: wallet_uuid,
+                session_index,
+                chain_id: req.chain_id,
+                call,
+                user_op_hash,
+            })
+            .await?;
+
+        Ok(SignSessionKeyResponse {
+            session_key_id: req.session_key_id,
+            signature: format!("0x{}", hex::encode(&signature)),
+        })
+    }
+
+    pub async fn revoke_session_key(
+        &self,
+        req: RevokeSessionKeyRequest,
+    ) -> Result<RevokeSessionKeyResponse> {
+        let (wallet_uuid, session_index) = parse_agent_key_id(&req.session_key_id)?;
+        let wallet_id_str = wallet_uuid.to_string();
+
+        if req.webauthn_assertion.is_none() {
+            return Err(anyhow!(
+                "revoke-session-key requires WebAuthn ceremony. \
+                 Legacy passkey assertions are not accepted."
+            ));
+        }
+        // TA binds the challenge to (wallet, index) → delegate (true).
+        let assertion = self
+            .resolve_passkey_assertion(&wallet_id_str, None, req.webauthn_assertion.as_ref(), true)
+            .await?
+            .ok_or_else(|| anyhow!("Passkey assertion required to revoke session key"))?;
+
+        // TA first: its revocation list is authoritative and takes effect at once.
+        let revoked = self
+            .tee
+            .revoke_scoped_session_key(wallet_uuid, session_index, Some(assertion))
+            .await?;
+        let _ = self
+            .db
+            .revoke_scoped_session_key(&wallet_id_str, session_index)?;
+
+        println!(
+            "✅ RevokeSessionKey: wallet={} idx={} revoked={}",
+            wallet_id_str, session_index, revoked
+        );
+        Ok(RevokeSessionKeyResponse {
+            success: true,
+            revoked,
+        })
+    }
+
+    pub async fn set_allowance_policy(
+        &self,
+        req: SetAllowancePolicyRequest,
+    ) -> Result<SetAllowancePolicyResponse> {
+        let wallet_uuid = Uuid::parse_str(&req.key_id)?;
+        let wallet_id_str = wallet_uuid.to_string();
+        self.ensure_not_frozen(&wallet_id_str)?;
+        let rule = parse_allowance_rule(&req.rule, req.max.as_deref())?;
+
+        if req.webauthn_assertion.is_none() {
+            return Err(anyhow!("allowance-policy requires WebAuthn ceremony"));
+        }
+        // TA binds the challenge to (wallet, rule) → delegate (true).
+        let assertion = self
+            .resolve_passkey_assertion(&wallet_id_str, None, req.webauthn_assertion.as_ref(), true)
+            .await?
+            .ok_or_else(|| anyhow!("Passkey assertion required to set allowance policy"))?;
+
+        let previous = self
+            .tee
+            .set_allowance_policy(wallet_uuid, rule, Some(assertion))
+            .await?;
+        println!(
+            "✅ SetAllowancePolicy: wallet={} {} -> {}",
+            wallet_id_str,
+            allowance_rule_name(&previous),
+            allowance_rule_name(&rule)
+        );
+        Ok(SetAllowancePolicyResponse {
+            success: true,
+            previous: allowance_rule_name(&previous),
+        })
+    }
+
+    pub async fn confirm_allowance_override(
+        &self,
+        req: ConfirmAllowanceOverrideRequest,
+    ) -> Result<ConfirmAllowanceOverrideResponse> {
+        let wallet_uuid = Uuid::parse_str(&req.key_id)?;
+        let wallet_id_str = wallet_uuid.to_string();
+        self.ensure_not_frozen(&wallet_id_str)?;
+        let transaction = req.transaction.to_proto()?;
+
+        if req.webauthn_assertion.is_none() {
+            return Err(anyhow!(
+                "confirm-allowance-override requires WebAuthn ceremony"
+            ));
+        }
+        let assertion = self
+            .resolve_passkey_assertion(&wallet_id_str, None, req.webauthn_assertion.as_ref(), true)
+            .await?
+            .ok_or_else(|| anyhow!("Passkey assertion required to override allowance policy"))?;
+
+        let summary = self
+            .tee
+            .confirm_allowance_override(wallet_uuid, transaction, req.summary_abis, Some(assertion))
+            .await?;
+        println!(
+            "⚠️  AllowanceOverride armed: wallet={} \"{}\"",
+            wallet_id_str, summary
+        );
+        Ok(ConfirmAllowanceOverrideResponse { summary })
+    }
+
+    pub async fn set_spender_allow_list(
+        &self,
+        req: SetSpenderAllowListRequest,
+    ) -> Result<SetSpenderAllowListResponse> {
+        let wallet_uuid = Uuid::parse_str(&req.key_id)?;
+        let wallet_id_str = wallet_uuid.to_string();
+        self.ensure_not_frozen(&wallet_id_str)?;
+        let spenders = req
+            .spenders
+            .iter()
+            .map(|s| proto::eip55::parse_address(s).map_err(|e| anyhow!("spender {}: {}", s, e)))
+            .collect::<Result<Vec<_>>>()?;
+
+        if req.webauthn_assertion.is_none() {
+            return Err(anyhow!("spender-allow-list requires WebAuthn ceremony"));
+        }
+        // TA binds the challenge to (wallet, list as sent) → delegate (true).
+        let assertion = self
+            .resolve_passkey_assertion(&wallet_id_str, None, req.webauthn_assertion.as_ref(), true)
+            .await?
+            .ok_or_else(|| anyhow!("Passkey assertion required to set spender allow-list"))?;
+
+        let previous = self
+            .tee
+            .set_spender_allow_list(wallet_uuid, spenders, Some(assertion))
+            .await?;
+        println!(
+            "✅ SetSpenderAllowList: wallet={} {} -> {} spenders",
+            wallet_id_str,
+            previous.len(),
+            req.spenders.len()
+        );
+        Ok(SetSpenderAllowListResponse {
+            success: true,
+            previous: previous
+                .iter()
+                .map(proto::eip55::to_checksum_address)
+                .collect(),
+        })
+    }
+
+    pub async fn path_policy(&self, req: PathPolicyRequest) -> Result<PathPolicyResponse> {
+        let wallet_uuid = Uuid::parse_str(&req.key_id)?;
+        let wallet_id_str = wallet_uuid.to_string();
+        let patterns = match &req.patterns {
+            Some(patterns) => {
+                self.ensure_not_frozen(&wallet_id_str)?;
+                Some(
+                    patterns
+                        .iter()
+                        .map(|p| proto::hd_path::parse_path_pattern(p).map_err(|e| anyhow!(e)))
+                        .collect::<Result<Vec<_>>>()?,
+                )
+            }
+            None => None,
+        };
+        // Narrowing needs no passkey; the TA decides. When one is sent it is
+        // bound to (wallet, list as sent) → delegate (true).
+        let assertion = match patterns {
+            Some(_) => {
+                self.resolve_passkey_assertion(
+                    &wallet_id_str,
+                    None,
+                    req.webauthn_assertion.as_ref(),
+                    true,
+                )
+                .await?
+            }
+            None => None,
+        };
+
+        let setting = patterns.is_some();
+        let output = self
+            .tee
+            .path_policy(wallet_uuid, patterns, assertion)
+            .await?;
+        let patterns: Vec<String> = output.patterns.iter().map(|p| p.to_string()).collect();
+        if setting {
+            println!(
+                "✅ PathPolicy: wallet={} {} -> {} patterns",
+                wallet_id_str,
+                output.previous.len(),
+                patterns.len()
+            );
+        }
+        Ok(PathPolicyResponse {
+            patterns,
+            previous: output.previous.iter().map(|p| p.to_string()).collect(),
+        })
+    }
+
+    /// Read the wallet's nonce ledger, or turn tracking on or off (`enabled`
+    /// set) with a passkey bound to (wallet, enabled) → delegate (true).
+    pub async fn nonce_tracking(&self, req: NonceTrackingRequest) -> Result<NonceTrackingResponse> {
+        let wallet_uuid = Uuid::parse_str(&req.key_id)?;
+        let wallet_id_str = wallet_uuid.to_string();
+        let assertion = match req.enabled {
+            Some(_) => {
+                self.ensure_not_frozen(&wallet_id_str)?;
+                if req.webauthn_assertion.is_none() {
+                    return Err(anyhow!("nonce-tracking requires WebAuthn ceremony"));
+                }
+                self.resolve_passkey_assertion(
+                    &wallet_id_str,
+                    None,
+                    req.webauthn_assertion.as_ref(),
+                    true,
+                )
+                .await?
+            }
+            None => None,
+        };
+
+        let output = self
+            .tee
+            .nonce_tracking(wallet_uuid, req.enabled, assertion)
+            .await?;
+        if let Some(previous) = output.previous {
+            let detail = format!("{} -> {}", previous, output.enabled);
+            self.audit(&wallet_id_str, "nonce_tracking_set", Some(&detail));
+            println!("✅ NonceTracking: wallet={} {}", wallet_id_str, detail);
+        }
+        Ok(NonceTrackingResponse::from(output))
+    }
+
+    /// Arm a one-time override so the TA signs (chain, address, nonce) again,
+    /// with a passkey bound to exactly that → delegate (true).
+    pub async fn override_nonce(&self, req: OverrideNonceRequest) -> Result<OverrideNonceResponse> {
+        let wallet_uuid = Uuid::parse_str(&req.key_id)?;
+        let wallet_id_str = wallet_uuid.to_string();
+        self.ensure_not_frozen(&wallet_id_str)?;
+        let address = proto::eip55::parse_address(&req.address)
+            .map_err(|e| anyhow!("address {}: {}", req.address, e))?;
+        if req.webauthn_assertion.is_none() {
+            return Err(anyhow!("nonce-override requires WebAuthn ceremony"));
+        }
+        let assertion = self
+            .resolve_passkey_assertion(&wallet_id_str, None, req.webauthn_assertion.as_ref(), true)
+            .await?;
+
+        let output = self
+            .tee
+            .override_nonce(proto::OverrideNonceInput {
+                wallet_id: wallet_uuid,
+                chain_id: req.chain_id,
+                address,
+                nonce: req.nonce,
+                passkey_assertion: assertion,
+            })
+            .await?;
+        let armed = NonceOverrideJson::from(&output.armed);
+        let detail = format!(
+            "chain={} address={} nonce={}",
+            armed.chain_id, armed.address, armed.nonce
+        );
+        self.audit(&wallet_id_str, "nonce_override_armed", Some(&detail));
+        println!("✅ NonceOverride: wallet={} {}", wallet_id_str, detail);
+        Ok(OverrideNonceResponse {
+            armed,
+            highest_nonce: output.highest,
+        })
+    }
+
+    /// Read the wallet's spending policy, or replace it (`policy` set) with
+    /// a passkey bound to (wallet, policy as sent) → delegate (true).
+    pub async fn spending_policy(
+        &self,
+        req: SpendingPolicyRequest,
+    ) -> Result<SpendingPolicyResponse> {
+        let wallet_uuid = Uuid::parse_str(&req.key_id)?;
+        let wallet_id_str = wallet_uuid.to_string();
+        let policy = match &req.policy {
+            Some(policy) => {
+                self.ensure_not_frozen(&wallet_id_str)?;
+                let policy = policy.to_proto()?;
+                policy.normalized().map_err(|e| anyhow!(e))?;
+                Some(policy)
+            }
+            None => None,
+        };
+        let assertion = match policy {
+            Some(_) => {
+                if req.webauthn_assertion.is_none() {
+                    return Err(anyhow!("spending-policy requires WebAuthn ceremony"));
+                }
+                self.resolve_passkey_assertion(
+                    &wallet_id_str,
+                    None,
+                    req.webauthn_assertion.as_ref(),
+                    true,
+                )
+                .await?
+            }
+            None => None,
+        };
+
+        let setting = policy.is_some();
+        let output = self
+            .tee
+            .spending_policy(wallet_uuid, policy, assertion)
+            .await?;
+        if setting {
+            let detail = serde_json::to_string(&SpendingPolicyJson::from_proto(&output.policy))?;
+            self.audit(&wallet_id_str, "spending_policy_set", Some(&detail));
+            println!("✅ SpendingPolicy: wallet={} {}", wallet_id_str, detail);
+        }
+        Ok(SpendingPolicyResponse {
+            policy: SpendingPolicyJson::from_proto(&output.policy),
+            previous: output.previous.as_ref().map(SpendingPolicyJson::from_proto),
+            spent_today_wei: output.spent_today_wei.to_string(),
+        })
+    }
+
+    /// Pair the phone whose approval the TA then needs for every transaction
+    /// Sign of the wallet, or unpair it (no `approverKey`). Either way the
+    /// passkey confirms it, bound to (wallet, key) → delegate (true).
+    pub async fn pair_approver(&self, req: PairApproverRequest) -> Result<PairApproverResponse> {
+        let wallet_uuid = Uuid::parse_str(&req.key_id)?;
+        let wallet_id_str = wallet_uuid.to_string();
+        self.ensure_not_frozen(&wallet_id_str)?;
+        let pairing = match &req.approver_key {
+            Some(key) => {
+                let key = hex::decode(key.trim_start_matches("0x"))
+                    .map_err(|_| anyhow!("approverKey is not hex"))?;
+                proto::remote_approval::validate_approver_key(&key).map_err(|e| anyhow!(e))?;
+                let platform = remote_approval::Platform::parse(
+                    req.platform
+                        .as_deref()
+                        .ok_or_else(|| anyhow!("platform is required to pair"))?,
+                )?;
+                let push_token = req
+                    .push_token
+                    .clone()
+                    .filter(|t| !t.is_empty())
+                    .ok_or_else(|| anyhow!("pushToken is required to pair"))?;
+                Some((key, platform, push_token))
+            }
+            None => None,
+        };
+
+        if req.webauthn_assertion.is_none() {
+            return Err(anyhow!("approver pairing requires WebAuthn ceremony"));
+        }
+        let assertion = self
+            .resolve_passkey_assertion(&wallet_id_str, None, req.webauthn_assertion.as_ref(), true)
+            .await?
+            .ok_or_else(|| anyhow!("Passkey assertion required to pair an approver"))?;
+
+        let previous = self
+            .tee
+            .pair_approver(
+                wallet_uuid,
+                pairing.as_ref().map(|(key, _, _)| key.clone()),
+                Some(assertion),
+            )
+            .await?;
+        let approver_key = match &pairing {
+            Some((key, platform, push_token)) => {
+                self.db.set_remote_approver(
+                    &wallet_id_str,
+                    &hex::encode(key),
+                    platform.as_str(),
+                    push_token,
+                )?;
+                self.audit(
+                    &wallet_id_str,
+                    "remote_approver_paired",
+                    Some(platform.as_str()),
+                );
+                Some(format!("0x{}", hex::encode(key)))
+            }
+            None => {
+                self.db.clear_remote_approver(&wallet_id_str)?;
+                self.audit(&wallet_id_str, "remote_approver_unpaired", None);
+                None
+            }
+        };
+        println!(
+            "📲 PairApprover: wallet={} {}",
+            wallet_id_str,
+            if approver_key.is_some() {
+                "paired"
+            } else {
+                "unpaired"
+            }
+        );
+        Ok(PairApproverResponse {
+            approver_key,
+            previous: previous.map(|k| format!("0x{}", hex::encode(k))),
+        })
+    }
+
+    /// Seal a transaction Sign to the wallet's paired phone and push it. The
+    /// payload is the digest the Sign's passkey will confirm.
+    pub async fn request_remote_approval(
+        &self,
+        req: RemoteApprovalRequest,
+    ) -> Result<serde_json::Value> {
+        let wallet_uuid = Uuid::parse_str(&req.key_id)?;
+        let key_id = wallet_uuid.to_string();
+        self.ensure_not_frozen(&key_id)?;
+        let relay = match &self.push_relay {
+            Ok(Some(relay)) => relay,
+            Ok(None) => return Err(anyhow!("no push relay configured (KMS_PUSH_RELAY_URL)")),
+            Err(e) => return Err(anyhow!("push relay misconfigured: {}", e)),
+        };
+        let row = self
+            .db
+            .remote_approver(&key_id)?
+            .ok_or_else(|| anyhow!("no approver phone is paired with {}", key_id))?;
+
+        let transaction = req.transaction.to_call()?;
+        let summary = proto::calldata::summarize_transaction(&transaction, &req.summary_abis);
+        // The hash the TA's Sign will check the approval against.
+        let tx_hash = if req.transaction.is_eip1559() {
+            req.transaction.to_eip1559()?.signing_hash()
+        } else {
+            proto::offline::legacy_signing_hash(&transaction)
+        };
+        let payload = if req.summary_committed {
+            proto::calldata::confirmation_digest(&tx_hash, &summary)
+        } else {
+            tx_hash
+        };
+        let request = remote_approval::ApprovalRequest {
+            version: 1,
+            key_id: key_id.clone(),
+            derivation_path: req.derivation_path.clone(),
+            transaction: serde_json::to_value(&req.transaction)?,
+            summary: summary.clone(),
+            summary_committed: req.summary_committed,
+            payload: format!("0x{}", hex::encode(payload)),
+            requested_at: Utc::now().timestamp(),
+        };
+        let sealed =
+            remote_approval::seal(&hex::decode(&row.approver_key)?, &request.to_plaintext()?)?;
+        relay
+            .deliver(
+                remote_approval::Platform::parse(&row.platform)?,
+                &row.push_token,
+                &sealed,
+            )
+            .await?;
+        self.audit(&key_id, "remote_approval_requested", Some(&summary));
+        println!("📲 Approval pushed: wallet={} \"{}\"", key_id, summary);
+        Ok(serde_json::json!({
+            "keyId": key_id,
+            "payload": request.payload,
+            "summary": summary,
+            "platform": row.platform,
+        }))
+    }
+
+    /// The phone's answer. The TA checks the signature against the key it
+    /// paired and arms a one-shot approval for the payload.
+    pub async fn remote_approve(&self, req: RemoteApproveRequest) -> Result<serde_json::Value> {
+        use std::convert::TryInto;
+
+        let wallet_uuid = Uuid::parse_str(&req.key_id)?;
+        let key_id = wallet_uuid.to_string();
+        let payload: [u8; 32] = hex::decode(req.payload.trim_start_matches("0x"))
+            .ok()
+            .and_then(|b| b.try_into().ok())
+            .ok_or_else(|| anyhow!("payload must be 32 bytes of hex"))?;
+        let signature = hex::decode(req.signature.trim_start_matches("0x"))
+            .map_err(|_| anyhow!("signature is not hex"))?;
+        let expires_at = self
+            .tee
+            .remote_approve(wallet_uuid, payload, signature)
+            .await?;
+        self.audit(&key_id, "remote_approval_granted", Some(&req.payload));
+        println!(
+            "📲 Approval granted: wallet={} payload={}",
+            key_id, req.payload
+        );
+        Ok(serde_json::json!({
+            "keyId": key_id,
+            "payload": req.payload,
+            "expiresAt": expires_at,
+        }))
+    }
+
+    /// Require co-signer approval above a value threshold, or stop (no
+    /// `policy`). The passkey is bound to (wallet, policy) → delegate (true).
+    pub async fn set_multisig_policy(
+        &self,
+        req: SetMultiSigPolicyRequest,
+    ) -> Result<serde_json::Value> {
+        let wallet_uuid = Uuid::parse_str(&req.key_id)?;
+        let wallet_id_str = wallet_uuid.to_string();
+        self.ensure_not_frozen(&wallet_id_str)?;
+        let policy = req.policy.as_ref().map(|p| p.to_proto()).transpose()?;
+        if req.webauthn_assertion.is_none() {
+            return Err(anyhow!("multisig-policy requires WebAuthn ceremony"));
+        }
+        let assertion = self
+            .resolve_passkey_assertion(&wallet_id_str, None, req.webauthn_assertion.as_ref(), true)
+            .await?
+            .ok_or_else(|| anyhow!("Passkey assertion required to set a multi-sig policy"))?;
+        let output = self
+            .tee
+            .set_multisig_policy(wallet_uuid, policy.clone(), Some(assertion))
+            .await?;
+        let summary = match &policy {
+            Some(p) => format!(
+                "{}-of-{} above {} wei",
+                p.required,
+                p.cosigners.len(),
+                p.threshold_wei
+            ),
+            None => "none".to_string(),
+        };
+        self.audit(&wallet_id_str, "multisig_policy_set", Some(&summary));
+        println!(
+            "👥 SetMultiSigPolicy: wallet={} {} (dropped {} held)",
+            wallet_id_str, summary, output.dropped
+        );
+        Ok(serde_json::json!({
+            "keyId": wallet_id_str,
+            "policy": policy.as_ref().map(MultiSigPolicyJson::from_proto),
+            "previous": output.previous.as_ref().map(MultiSigPolicyJson::from_proto),
+            "dropped": output.dropped,
+        }))
+    }
+
+    /// One co-signer's approval. The one that reaches the threshold gets
+    /// the held Sign's signature, checked against the key pin like any
+    /// other.
+    pub async fn approve_multisig(&self, req: ApproveMultiSigRequest) -> Result<serde_json::Value> {
+        use std::convert::TryInto;
+
+        let wallet_uuid = Uuid::parse_str(&req.key_id)?;
+        let key_id = wallet_uuid.to_string();
+        let operation: [u8; 32] = hex::decode(req.operation_id.trim_start_matches("0x"))
+            .ok()
+            .and_then(|b| b.try_into().ok())
+            .ok_or_else(|| anyhow!("operationId must be 32 bytes of hex"))?;
+        let cosigner_key = hex::decode(req.cosigner_key.trim_start_matches("0x"))
+            .map_err(|_| anyhow!("cosignerKey is not hex"))?;
+        let signature = hex::decode(req.signature.trim_start_matches("0x"))
+            .map_err(|_| anyhow!("signature is not hex"))?;
+        let output = self
+            .tee
+            .approve_multisig(wallet_uuid, operation, cosigner_key, signature)
+            .await?;
+        self.audit(
+            &key_id,
+            "multisig_approval",
+            Some(&format!(
+                "{} by {} ({}/{})",
+                req.operation_id, req.cosigner_key, output.approvals, output.required
+            )),
+        );
+        let released = match output.released {
+            Some(release) => {
+                let signer = key_pin::signed_tx_signer(&release.signed)?;
+                enforce_key_pin(
+                    &self.db,
+                    &key_id,
+                    &release.hd_path,
+                    &key_pin::address_hex(&signer),
+                    None,
+                )?;
+                self.audit(&key_id, "multisig_released", Some(&req.operation_id));
+                Some(hex::encode(&release.signed))
+            }
+            None => None,
+        };
+        println!(
+            "👥 MultiSig approval: wallet={} operation={} {}/{}{}",
+            key_id,
+            req.operation_id,
+            output.approvals,
+            output.required,
+            if released.is_some() { " released" } else { "" }
+        );
+        Ok(serde_json::json!({
+            "keyId": key_id,
+            "operationId": req.operation_id,
+            "approvals": output.approvals,
+            "required": output.required,
+            "signature": released,
+        }))
+    }
+
+    /// Register recovery guardians, or remove them (no `guardians`). The
+    /// passkey is bound to (wallet, guardian set) → delegate (true).
+    pub async fn set_guardians(&self, req: SetGuardiansRequest) -> Result<serde_json::Value> {
+        let wallet_uuid = Uuid::parse_str(&req.key_id)?;
+        let wallet_id_str = wallet_uuid.to_string();
+        self.ensure_not_frozen(&wallet_id_str)?;
+        let guardians = req.guardians.as_ref().map(|g| g.to_proto()).transpose()?;
+        if req.webauthn_assertion.is_none() {
+            return Err(anyhow!("recovery-guardians requires WebAuthn ceremony"));
+        }
+        let assertion = self
+            .resolve_passkey_assertion(&wallet_id_str, None, req.webauthn_assertion.as_ref(), true)
+            .await?
+            .ok_or_else(|| anyhow!("Passkey assertion required to set recovery guardians"))?;
+        let output = self
+            .tee
+            .set_guardians(wallet_uuid, guardians.clone(), Some(assertion))
+            .await?;
+        let summary = match &guardians {
+            Some(g) => format!(
+                "{}-of-{}, {} s timelock",
+                g.threshold,
+                g.guardians.len(),
+                g.delay_secs
+            ),
+            None => "none".to_string(),
+        };
+        self.audit(&wallet_id_str, "recovery_guardians_set", Some(&summary));
+        if output.cancelled {
+            self.audit(
+                &wallet_id_str,
+                "recovery_cancelled",
+                Some("guardians changed"),
+            );
+        }
+        println!("🛟 SetGuardians: wallet={} {}", wallet_id_str, summary);
+        Ok(serde_json::json!({
+            "keyId": wallet_id_str,
+            "guardians": guardians.as_ref().map(GuardianSetJson::from_proto),
+            "previous": output.previous.as_ref().map(GuardianSetJson::from_proto),
+            "cancelled": output.cancelled,
+        }))
+    }
+
+    /// The guardians and the round they sign; no credential, guardians
+    /// read it before attesting.
+    pub async fn recovery_status(&self, req: RecoveryKeyRequest) -> Result<serde_json::Value> {
+        let wallet_uuid = Uuid::parse_str(&req.key_id)?;
+        let status = self.tee.recovery_status(wallet_uuid).await?;
+        Ok(recovery_status_json(&wallet_uuid.to_string(), &status))
+    }
+
+    /// One guardian's attestation of the owner's new passkey.
+    pub async fn recovery_attest(&self, req: RecoveryAttestRequest) -> Result<serde_json::Value> {
+        let wallet_uuid = Uuid::parse_str(&req.key_id)?;
+        let key_id = wallet_uuid.to_string();
+        let guardian_key = hex::decode(req.guardian_key.trim_start_matches("0x"))
+            .map_err(|_| anyhow!("guardianKey is not hex"))?;
+        let new_passkey = hex::decode(req.new_passkey_public_key.trim_start_matches("0x"))
+            .map_err(|_| anyhow!("newPasskeyPublicKey is not hex"))?;
+        let signature = hex::decode(req.signature.trim_start_matches("0x"))
+            .map_err(|_| anyhow!("signature is not hex"))?;
+        let output = self
+            .tee
+            .recovery_attest(wallet_uuid, req.round, guardian_key, new_passkey, signature)
+            .await?;
+        self.audit(
+            &key_id,
+            "recovery_attestation",
+            Some(&format!(
+                "round {} by {} for {}",
+                req.round, req.guardian_key, req.new_passkey_public_key
+            )),
+        );
+        if let Some(armed) = output.status.armed.as_ref().filter(|_| output.armed_now) {
+            self.audit(
+                &key_id,
+                "recovery_armed",
+                Some(&format!("ready at {}", armed.ready_at)),
+            );
+        }
+        println!(
+            "🛟 Recovery attestation: wallet={} round={}{}",
+            key_id,
+            req.round,
+            if output.armed_now { " armed" } else { "" }
+        );
+        Ok(recovery_status_json(&key_id, &output.status))
+    }
+
+    /// The owner stops the round in progress. The passkey is bound to
+    /// (wallet, round) → delegate (true).
+    pub async fn cancel_recovery(&self, req: CancelRecoveryRequest) -> Result<serde_json::Value> {
+        let wallet_uuid = Uuid::parse_str(&req.key_id)?;
+        let key_id = wallet_uuid.to_string();
+        if req.webauthn_assertion.is_none() {
+            return Err(anyhow!("recovery-cancel requires WebAuthn ceremony"));
+        }
+        let assertion = self
+            .resolve_passkey_assertion(&key_id, None, req.webauthn_assertion.as_ref(), true)
+            .await?
+            .ok_or_else(|| anyhow!("Passkey assertion required to cancel a recovery"))?;
+        let status = self
+            .tee
+            .cancel_recovery(wallet_uuid, Some(assertion))
+            .await?;
+        self.audit(&key_id, "recovery_cancelled", Some("by the owner"));
+        println!("🛟 Recovery cancelled: wallet={}", key_id);
+        Ok(recovery_status_json(&key_id, &status))
+    }
+
+    /// After the timelock, rebind the wallet to the attested passkey and
+    /// record it like ChangePasskey does.
+    pub async fn complete_recovery(&self, req: RecoveryKeyRequest) -> Result<serde_json::Value> {
+        let wallet_uuid = Uuid::parse_str(&req.key_id)?;
+        let key_id = wallet_uuid.to_string();
+        if !self.db.wallet_exists(&key_id)? {
+            return Err(anyhow!("Key not found: {}", key_id));
+        }
+        self.ensure_not_frozen(&key_id)?;
+        let output = self.tee.complete_recovery(wallet_uuid).await?;
+        let new_pk = format!("0x{}", hex::encode(&output.passkey_pubkey));
+        self.record_passkey_change(&key_id, &new_pk, "CompleteRecovery")
+            .await?;
+        self.audit(&key_id, "recovery_completed", Some(&new_pk));
+        println!(
+            "🛟 Recovery completed: wallet={} passkey={}",
+            key_id, new_pk
+        );
+        Ok(serde_json::json!({
+            "keyId": key_id,
+            "passkeyPublicKey": new_pk,
+            "round": output.round,
+        }))
+    }
+
+    /// Seal the wallet entropy under a password and this device's sealing
+    /// key. The passkey is bound to (wallet, kdf) → delegate (true).
+    pub async fn export_backup(&self, req: ExportBackupRequest) -> Result<serde_json::Value> {
+        let wallet_uuid = Uuid::parse_str(&req.key_id)?;
+        let key_id = wallet_uuid.to_string();
+        self.ensure_not_frozen(&key_id)?;
+        let kdf: proto::kdf::KdfConfig = match &req.kdf {
+            Some(spec) => spec.parse().map_err(|e| anyhow!("kdf: {}", e))?,
+            None => proto::kdf::KdfConfig::default(),
+        };
+        if req.webauthn_assertion.is_none() {
+            return Err(anyhow!("backup export requires WebAuthn ceremony"));
+        }
+        let assertion = self
+            .resolve_passkey_assertion(&key_id, None, req.webauthn_assertion.as_ref(), true)
+            .await?
+            .ok_or_else(|| anyhow!("Passkey assertion required to export a backup"))?;
+        let backup = self
+            .tee
+            .export_encrypted_backup(wallet_uuid, req.password, kdf, Some(assertion))
+            .await?;
+        self.audit(&key_id, "backup_exported", Some(&kdf.to_string()));
+        println!("💾 Encrypted backup exported: wallet={} ({})", key_id, kdf);
+        Ok(keystore::backup_to_json(&backup))
+    }
+
+    /// Put a wallet back from a backup sealed on this device, under its own
+    /// id and passkey. No passkey: the password and the device are the
+    /// authorization, as in the TA. A surviving DB row is kept and checked
+    /// against the address pin.
+    pub async fn restore_backup(&self, req: RestoreBackupRequest) -> Result<serde_json::Value> {
+        let backup = keystore::backup_from_json(&req.backup)?;
+        let key_id = backup.header.wallet_id.to_string();
+        let existing = self.db.get_wallet(&key_id)?;
+        if existing.is_some() {
+            self.ensure_not_frozen(&key_id)?;
+        }
+        let restored = self
+            .tee
+            .import_encrypted_backup(backup, req.password)
+            .await?;
+        let address = key_pin::address_hex(&restored.address);
+        let passkey = format!("0x{}", hex::encode(&restored.passkey_pubkey));
+        match existing {
+            None => {
+                let row = WalletRow {
+                    key_id: key_id.clone(),
+                    address: Some(address.clone()),
+                    public_key: None,
+                    derivation_path: Some(replication::CHECK_PATH.to_string()),
+                    description: req.description.unwrap_or_default(),
+                    key_usage: "SIGN_VERIFY".to_string(),
+                    key_spec: "ECC_SECG_P256K1".to_string(),
+                    origin: "EXTERNAL".to_string(),
+                    passkey_pubkey: Some(passkey.clone()),
+                    credential_id: None,
+                    sign_count: 0,
+                    status: "ready".to_string(),
+                    error_msg: None,
+                    created_at: Utc::now().to_rfc3339(),
+                };
+                self.insert_new_wallet("RestoreBackup", &row).await?;
+            }
+            Some(row) => {
+                let same = row.passkey_pubkey.as_deref().map(|pk| {
+                    pk.trim_start_matches("0x")
+                        .eq_ignore_ascii_case(&passkey[2..])
+                });
+                if same != Some(true) {
+                    self.record_passkey_change(&key_id, &passkey, "RestoreBackup")
+                        .await?;
+                }
+            }
+        }
+        enforce_key_pin(&self.db, &key_id, replication::CHECK_PATH, &address, None)?;
+        self.audit(&key_id, "backup_restored", Some(&address));
+        println!("💾 Backup restored: wallet={} address={}", key_id, address);
+        Ok(serde_json::json!({
+            "keyId": key_id,
+            "address": address,
+            "passkeyPublicKey": passkey,
+        }))
+    }
+
+    /// Switch the wallet to BLS signatures under an ERC-4337 aggregator, or
+    /// back to ECDSA (no `aggregator`). The passkey is bound to
+    /// (wallet, aggregator) → delegate (true).
+    pub async fn set_aggregator(&self, req: SetAggregatorRequest) -> Result<serde_json::Value> {
+        let wallet_uuid = Uuid::parse_str(&req.key_id)?;
+        let wallet_id_str = wallet_uuid.to_string();
+        self.ensure_not_frozen(&wallet_id_str)?;
+        let aggregator = match &req.aggregator {
+            Some(a) => Some(
+                proto::eip55::parse_address(a).map_err(|e| anyhow!("aggregator: {}", e))?,
+            ),
+            None => None,
+        };
+        if req.webauthn_assertion.is_none() {
+            return Err(anyhow!("changing the signature scheme requires WebAuthn ceremony"));
+        }
+        let assertion = self
+            .resolve_passkey_assertion(&wallet_id_str, None, req.webauthn_assertion.as_ref(), true)
+            .await?
+            .ok_or_else(|| anyhow!("Passkey assertion required to set an aggregator"))?;
+        let output = self
+            .tee
+            .set_aggregator(wallet_uuid, aggregator, Some(assertion))
+            .await?;
+        let aggregator = aggregator.map(|a| proto::calldata::hex_addr(&a));
+        self.audit(
+            &wallet_id_str,
+            "aggregator_scheme_set",
+            Some(aggregator.as_deref().unwrap_or("ecdsa")),
+        );
+        println!(
+            "📦 SetAggregator: wallet={} aggregator={}",
+            wallet_id_str,
+            aggregator.as_deref().unwrap_or("none")
+        );
+        Ok(serde_json::json!({
+            "keyId": wallet_id_str,
+            "aggregator": aggregator,
+            "blsPublicKey": output.public_key.map(|k| format!("0x{}", hex::encode(k))),
+            "previous": output.previous.map(|a| proto::calldata::hex_addr(&a)),
+        }))
+    }
+
+    /// BLS-sign a userOp in the TA and queue it for the next flush under
+    /// its (EntryPoint, aggregator) group. The passkey challenge is the
+    /// userOpHash, as for SignHash.
+    pub async fn sign_aggregated_user_op(
+        &self,
+        req: SignAggregatedUserOpRequest,
+    ) -> Result<serde_json::Value> {
+        use std::convert::TryInto;
+
+        let wallet_uuid = Uuid::parse_str(&req.key_id)?;
+        let key_id = wallet_uuid.to_string();
+        self.ensure_not_frozen(&key_id)?;
+        let entry_point = proto::eip55::parse_address(&req.entry_point)
+            .map_err(|e| anyhow!("entryPoint: {}", e))?;
+        let user_op_hash: [u8; 32] = hex::decode(req.user_op_hash.trim_start_matches("0x"))
+            .ok()
+            .and_then(|b| b.try_into().ok())
+            .ok_or_else(|| anyhow!("userOpHash must be 32 bytes of hex"))?;
+        if !req.user_op.is_object() {
+            return Err(anyhow!("userOp must be a JSON object"));
+        }
+        if req.webauthn_assertion.is_none() {
+            return Err(anyhow!("aggregated signing requires WebAuthn ceremony"));
+        }
+        let assertion = self
+            .resolve_passkey_assertion(&key_id, None, req.webauthn_assertion.as_ref(), true)
+            .await?
+            .ok_or_else(|| anyhow!("Passkey assertion required to sign"))?;
+        let output = self
+            .tee
+            .sign_aggregated_user_op(wallet_uuid, user_op_hash, Some(assertion))
+            .await?;
+        let signature = format!("0x{}", hex::encode(&output.signature));
+        let mut user_op = req.user_op;
+        user_op["signature"] = serde_json::json!(signature);
+        let grouped = self.aggregation_queue.push(aggregator::QueuedUserOp {
+            key_id: key_id.clone(),
+            entry_point,
+            aggregator: output.aggregator,
+            user_op_hash,
+            user_op,
+        })?;
+        let aggregator = proto::calldata::hex_addr(&output.aggregator);
+        self.audit(&key_id, "aggregated_user_op_signed", Some(&req.user_op_hash));
+        println!(
+            "📦 SignAggregatedUserOp: wallet={} aggregator={} group={}",
+            key_id, aggregator, grouped
+        );
+        Ok(serde_json::json!({
+            "keyId": key_id,
+            "userOpHash": req.user_op_hash,
+            "aggregator": aggregator,
+            "signature": signature,
+            "signatureCompact": format!("0x{}", hex::encode(&output.signature_compact)),
+            "queuedInGroup": grouped,
+        }))
+    }
+
+    /// Submit every queued group to the bundler. A group the bundler did
+    /// not answer goes back on the queue; per-op rejections are reported.
+    pub async fn flush_aggregated_user_ops(&self) -> Result<serde_json::Value> {
+        let bundler = match &self.bundler {
+            Ok(Some(bundler)) => bundler,
+            Ok(None) => return Err(anyhow!("no bundler configured (KMS_BUNDLER_URL)")),
+            Err(e) => return Err(anyhow!("bundler misconfigured: {}", e)),
+        };
+        let mut report = Vec::new();
+        for group in self.aggregation_queue.take_groups()? {
+            let mut summary = group.summary();
+            match aggregator::submit(bundler, &group).await {
+                Ok(results) => {
+                    summary["results"] = results
+                        .iter()
+                        .zip(&group.ops)
+                        .map(|(r, op)| match r {
+                            Ok(hash) => serde_json::json!({
+                                "userOpHash": format!("0x{}", hex::encode(op.user_op_hash)),
+                                "accepted": hash,
+                            }),
+                            Err(e) => serde_json::json!({
+                                "userOpHash": format!("0x{}", hex::encode(op.user_op_hash)),
+                                "error": e,
+                            }),
+                        })
+                        .collect();
+                }
+                Err(e) => {
+                    eprintln!("⚠️  aggregated group not submitted: {:#}", e);
+                    summary["error"] = serde_json::json!(format!("{:#}", e));
+                    summary["requeued"] = serde_json::json!(true);
+                    self.aggregation_queue.requeue(group)?;
+                }
+            }
+            report.push(summary);
+        }
+        Ok(serde_json::json!({
+            "groups": report,
+            "stillQueued": self.aggregation_queue.len(),
+        }))
+    }
+
+    /// Put, Get or Delete one dapp storage key. The TA re-checks names and
+    /// quotas and binds the passkey to `dapp_storage::op_commitment`.
+    pub async fn dapp_storage(
+        &self,
+        op: proto::dapp_storage::StorageOp,
+        req: DappStorageRequest,
+    ) -> Result<serde_json::Value> {
+        use base64::{engine::general_purpose::STANDARD, Engine};
+        use proto::dapp_storage::{self as ds, StorageOp};
+
+        let wallet_id = Uuid::parse_str(&req.key_id)?;
+        ds::validate_app_id(&req.app_id).map_err(|e| anyhow!("{}", e))?;
+        ds::validate_key(&req.key).map_err(|e| anyhow!("{}", e))?;
+        let value = match (op, &req.value) {
+            (StorageOp::Put, Some(v)) => {
+                let bytes = STANDARD
+                    .decode(v)
+                    .map_err(|e| anyhow!("value is not valid base64: {}", e))?;
+                if bytes.len() > ds::MAX_VALUE_BYTES {
+                    return Err(anyhow!("value exceeds {} bytes", ds::MAX_VALUE_BYTES));
+                }
+                bytes
+            }
+            (StorageOp::Put, None) => return Err(anyhow!("value is required")),
+            (_, Some(_)) => return Err(anyhow!("value is only accepted by put")),
+            (_, None) => Vec::new(),
+        };
+        if op != StorageOp::Get {
+            self.ensure_not_frozen(&req.key_id)?;
+        }
+        if req.webauthn_assertion.is_none() {
+            return Err(anyhow!("dapp storage requires WebAuthn ceremony"));
+        }
+        // TA binds the challenge to (op, wallet, app, key, value) → delegate (true).
+        let passkey_assertion = self
+            .resolve_passkey_assertion(&req.key_id, None, req.webauthn_assertion.as_ref(), true)
+            .await?;
+
+        let mut out = serde_json::json!({
+            "keyId": req.key_id,
+            "appId": req.app_id,
+            "key": req.key,
+        });
+        match op {
+            StorageOp::Put => {
+                let usage = self
+                    .tee
+                    .dapp_storage_put(proto::DappStoragePutInput {
+                        wallet_id,
+                        app_id: req.app_id.clone(),
+                        key: req.key.clone(),
+                        value,
+                        passkey_assertion,
+                    })
+                    .await?;
+                out["usedKeys"] = usage.used_keys.into();
+                out["usedBytes"] = usage.used_bytes.into();
+            }
+            StorageOp::Get => {
+                let value = self
+                    .tee
+                    .dapp_storage_get(proto::DappStorageGetInput {
+                        wallet_id,
+                        app_id: req.app_id.clone(),
+                        key: req.key.clone(),
+                        passkey_assertion,
+                    })
+                    .await?;
+                out["value"] = STANDARD.encode(value).into();
+            }
+            StorageOp::Delete => {
+                let existed = self
+                    .tee
+                    .dapp_storage_delete(proto::DappStorageDeleteInput {
+                        wallet_id,
+                        app_id: req.app_id.clone(),
+                        key: req.key.clone(),
+                        passkey_assertion,
+                    })
+                    .await?;
+                out["existed"] = existed.into();
+            }
+        }
+        Ok(out)
+    }
+
+    /// OTP vault: enroll a secret, get a code, remove a secret, or list labels.
+    /// Secrets go into the TA once and only codes come back.
+    pub async fn otp(&self, action: OtpAction, req: OtpRequest) -> Result<serde_json::Value> {
+        use proto::otp::{self, OtpAlgorithm, OtpKind, OtpParams};
+
+        let wallet_id = Uuid::parse_str(&req.key_id)?;
+        if action == OtpAction::List {
+            if req.webauthn_assertion.is_none() {
+                return Err(anyhow!("OTP vault requires WebAuthn ceremony"));
+            }
+            // TA binds the challenge to the wallet → delegate (true).
+            let passkey_assertion = self
+                .resolve_passkey_assertion(&req.key_id, None, req.webauthn_assertion.as_ref(), true)
+                .await?;
+            let entries = self
+                .tee
+                .otp_list(proto::OtpListInput {
+                    wallet_id,
+                    passkey_assertion,
+                })
+                .await?;
+            let entries: Vec<serde_json::Value> = entries
+                .iter()
+                .map(|e| otp_entry_json(&e.label, &e.params))
+                .collect();
+            return Ok(serde_json::json!({ "keyId": req.key_id, "entries": entries }));
+        }
+        let label = req
+            .label
+            .as_deref()
+            .ok_or_else(|| anyhow!("label is required"))?;
+        otp::validate_label(label).map_err(|e| anyhow!("{}", e))?;
+        let enrollment = match (action, &req.secret) {
+            (OtpAction::Enroll, Some(secret)) => {
+                let secret = otp::base32_decode(secret).map_err(|e| anyhow!("{}", e))?;
+                otp::validate_secret(&secret).map_err(|e| anyhow!("{}", e))?;
+                let algorithm = match req.algorithm.as_deref().unwrap_or("SHA1") {
+                    "SHA1" => OtpAlgorithm::Sha1,
+                    "SHA256" => OtpAlgorithm::Sha256,
+                    "SHA512" => OtpAlgorithm::Sha512,
+                    other => return Err(anyhow!("unsupported OTP algorithm: {}", other)),
+                };
+                let kind = match (req.r#type.as_deref().unwrap_or("totp"), req.period) {
+                    ("totp", period) => OtpKind::Totp {
+                        period_secs: period.unwrap_or(30),
+                    },
+                    ("hotp", None) => OtpKind::Hotp,
+                    ("hotp", Some(_)) => return Err(anyhow!("period is only accepted for totp")),
+                    (other, _) => return Err(anyhow!("unsupported OTP type: {}", other)),
+                };
+                let params = OtpParams {
+                    algorithm,
+                    digits: req.digits.unwrap_or(6),
+                    kind,
+                };
+                params.validate().map_err(|e| anyhow!("{}", e))?;
+                Some((secret, params))
+            }
+            (OtpAction::Enroll, None) => return Err(anyhow!("secret is required")),
+            (_, Some(_)) => return Err(anyhow!("secret is only accepted by enroll")),
+            (_, None) => None,
+        };
+        if action != OtpAction::Remove {
+            // A leaked API key plus a phished passkey prompt should not turn
+            // the vault into a code oracle.
+            self.otp_rate_limiter.check(&req.key_id).map_err(|limit| {
+                anyhow!("OTP rate limit exceeded ({}/min). Retry after 60s.", limit)
+            })?;
+        }
+        self.ensure_not_frozen(&req.key_id)?;
+        if req.webauthn_assertion.is_none() {
+            return Err(anyhow!("OTP vault requires WebAuthn ceremony"));
+        }
+        // TA binds the challenge to the wallet, label (and secret) → delegate (true).
+        let passkey_assertion = self
+            .resolve_passkey_assertion(&req.key_id, None, req.webauthn_assertion.as_ref(), true)
+            .await?;
+
+        let mut out = serde_json::json!({ "keyId": req.key_id, "label": label });
+        match (action, enrollment) {
+            (OtpAction::Enroll, Some((secret, params))) => {
+                let entries = self
+                    .tee
+                    .otp_enroll(proto::OtpEnrollInput {
+                        wallet_id,
+                        label: label.to_string(),
+                        secret,
+                        params,
+                        passkey_assertion,
+                    })
+                    .await?;
+                self.audit(&req.key_id, "otp_enroll", Some(label));
+                out = otp_entry_json(label, &params);
+                out["keyId"] = req.key_id.clone().into();
+                out["entries"] = entries.into();
+            }
+            (OtpAction::Code, _) => {
+                let code = self
+                    .tee
+                    .otp_code(proto::OtpCodeInput {
+                        wallet_id,
+                        label: label.to_string(),
+                        passkey_assertion,
+                    })
+                    .await?;
+                out["code"] = code.code.into();
+                out["counter"] = code.counter.into();
+                out["validForSecs"] = code.valid_for_secs.into();
+            }
+            (OtpAction::Remove, _) => {
+                let existed = self
+                    .tee
+                    .otp_remove(proto::OtpRemoveInput {
+                        wallet_id,
+                        label: label.to_string(),
+                        passkey_assertion,
+                    })
+                    .await?;
+                if existed {
+                    self.audit(&req.key_id, "otp_remove", Some(label));
+                }
+                out["existed"] = existed.into();
+            }
+            _ => unreachable!("enroll always carries its secret"),
+        }
+        Ok(out)
+    }
+
+    #[cfg(feature = "threshold-ecdsa")]
+    fn threshold_cosigner(&self) -> Result<&HttpCoSigner> {
+        match &self.threshold_cosigner {
+            Ok(Some(cosigner)) => Ok(cosigner),
+            Ok(None) => Err(anyhow!(
+                "no threshold co-signer configured (KMS_THRESHOLD_COSIGNER_URL)"
+            )),
+            Err(e) => Err(anyhow!("threshold co-signer misconfigured: {}", e)),
+        }
+    }
+
+    /// A wallet's 2-of-2 threshold key (see `crate::threshold`). Keygen, sign
+    /// and recombine are passkey-gated in the TA against the
+    /// `proto::threshold` commitments; share only fetches the co-signer's
+    /// share sealed to the TA, which the recombine assertion then commits to.
+    #[cfg(feature = "threshold-ecdsa")]
+    pub async fn threshold(
+        &self,
+        action: ThresholdAction,
+        req: ThresholdRequest,
+    ) -> Result<serde_json::Value> {
+        use std::convert::TryInto;
+
+        let wallet_id = Uuid::parse_str(&req.key_id)?;
+        let key_id = wallet_id.to_string();
+        let status = self
+            .tee
+            .threshold_status(proto::ThresholdStatusInput { wallet_id })
+            .await?;
+        let hex0x = |b: &[u8]| format!("0x{}", hex::encode(b));
+        let parse_hex = |field: &str, value: Option<&String>| -> Result<Vec<u8>> {
+            let value = value.ok_or_else(|| anyhow!("{} is required", field))?;
+            hex::decode(value.trim_start_matches("0x"))
+                .map_err(|e| anyhow!("{} is not hex: {}", field, e))
+        };
+        match action {
+            ThresholdAction::Status => Ok(serde_json::json!({
+                "keyId": key_id,
+                "publicKey": status.public_key.as_deref().map(hex0x),
+                "address": status.address.as_ref().map(|a| hex0x(a)),
+                "presignatures": status.available,
+                "recombined": status.recombined,
+            })),
+            ThresholdAction::Share => {
+                let sealed = self.threshold_cosigner()?.seal_share(wallet_id).await?;
+                Ok(serde_json::json!({ "keyId": key_id, "sealedShare": hex0x(&sealed) }))
+            }
+            ThresholdAction::Keygen => {
+                let cosigner = self.threshold_cosigner()?;
+                let passkey_assertion = self.threshold_passkey(&key_id, &req).await?;
+                let out =
+                    threshold::keygen(&self.tee, cosigner, wallet_id, passkey_assertion).await?;
+                self.audit(&key_id, "threshold_keygen", None);
+                Ok(serde_json::json!({
+                    "keyId": key_id,
+                    "publicKey": hex0x(&out.public_key),
+                    "address": hex0x(&out.address),
+                }))
+            }
+            ThresholdAction::Sign => {
+                let digest: [u8; 32] = parse_hex("digest", req.digest.as_ref())?
+                    .try_into()
+                    .map_err(|_| anyhow!("digest must be 32 bytes"))?;
+                // After the fallback ceremony the TA signs alone; until then
+                // no co-signer means no signature.
+                let cosigner = match self.threshold_cosigner() {
+                    Ok(cosigner) => Some(cosigner),
+                    Err(_) if status.recombined => None,
+                    Err(e) => return Err(e),
+                };
+                let passkey_assertion = self.threshold_passkey(&key_id, &req).await?;
+                let out = match cosigner {
+                    Some(cosigner) => {
+                        threshold::sign(&self.tee, cosigner, wallet_id, digest, passkey_assertion)
+                            .await?
+                    }
+                    None => {
+                        self.tee
+                            .threshold_sign(proto::ThresholdSignInput {
+                                wallet_id,
+                                digest,
+                                reply: None,
+                                passkey_assertion,
+                            })
+                            .await?
+                    }
+                };
+                Ok(serde_json::json!({
+                    "keyId": key_id,
+                    "signature": hex0x(&out.signature),
+                    "presignatures": out.available,
+                }))
+            }
+            ThresholdAction::Recombine => {
+                let sealed_share = parse_hex("sealedShare", req.sealed_share.as_ref())?;
+                let export_to = match &req.export_to {
+                    Some(_) => Some(parse_hex("exportTo", req.export_to.as_ref())?),
+                    None => None,
+                };
+                let passkey_assertion = self.threshold_passkey(&key_id, &req).await?;
+                let out = self
+                    .tee
+                    .threshold_recombine(proto::ThresholdRecombineInput {
+                        wallet_id,
+                        sealed_share,
+                        export_to,
+                        passkey_assertion,
+                    })
+                    .await?;
+                self.audit(
+                    &key_id,
+                    "threshold_recombine",
+                    out.exported.as_ref().map(|_| "exported"),
+                );
+                Ok(serde_json::json!({
+                    "keyId": key_id,
+                    "address": hex0x(&out.address),
+                    "exported": out.exported.as_deref().map(hex0x),
+                }))
+            }
+        }
+    }
+
+    #[cfg(feature = "threshold-ecdsa")]
+    async fn threshold_passkey(
+        &self,
+        key_id: &str,
+        req: &ThresholdRequest,
+    ) -> Result<Option<proto::PasskeyAssertion>> {
+        self.ensure_not_frozen(key_id)?;
+        if req.webauthn_assertion.is_none() {
+            return Err(anyhow!("Threshold ECDSA requires WebAuthn ceremony"));
+        }
+        // TA binds the challenge to the proto::threshold commitment → delegate (true).
+        self.resolve_passkey_assertion(key_id, None, req.webauthn_assertion.as_ref(), true)
+            .await
+    }
+
+    /// Account linkage: the TA proves control of the address (and the owner's
+    /// approval when another wallet is involved) and signs the statement; the
+    /// CA keeps the identity records.
+    pub async fn identity_link(
+        &self,
+        action: proto::identity::LinkAction,
+        req: IdentityLinkRequest,
+    ) -> Result<serde_json::Value> {
+        use proto::identity::{self, LinkAction};
+
+        let wallet_id = Uuid::parse_str(&req.key_id)?;
+        if self.db.get_wallet(&req.key_id)?.is_none() {
+            return Err(anyhow!("Key not found: {}", req.key_id));
+        }
+        let mut chain_ids = req.chain_ids.clone();
+        chain_ids.sort_unstable();
+        identity::validate_chain_ids(action, &chain_ids).map_err(|e| anyhow!("{}", e))?;
+        // The client picks a new identity's id, since its passkey challenge
+        // commits to it.
+        let identity_id = Uuid::parse_str(&req.identity_id)
+            .map_err(|_| anyhow!("identityId must be a UUID"))?
+            .to_string();
+        let existing = self.db.get_identity(&identity_id)?;
+        if existing.is_none() && action == LinkAction::Unlink {
+            return Err(anyhow!("identity not found: {}", identity_id));
+        }
+        if existing.is_some() && req.label.is_some() {
+            return Err(anyhow!("label is only accepted when creating an identity"));
+        }
+        // The owner approves any other wallet joining or leaving; a new
+        // identity is owned by the wallet that creates it.
+        let owner_key_id = existing
+            .as_ref()
+            .map(|i| i.owner_key_id.clone())
+            .filter(|owner| *owner != req.key_id);
+        if owner_key_id.is_some() && req.owner_webauthn_assertion.is_none() {
+            return Err(anyhow!(
+                "only the identity owner can approve another wallet; ownerWebAuthnAssertion is required"
+            ));
+        }
+        if owner_key_id.is_none() && req.owner_webauthn_assertion.is_some() {
+            return Err(anyhow!(
+                "ownerWebAuthnAssertion is only accepted for non-owner wallets"
+            ));
+        }
+        let linked = self.db.list_identity_links(&identity_id)?;
+        if action == LinkAction::Unlink && owner_key_id.is_none() {
+            // The owner's wallet leaves last, or nobody could approve again.
+            let others = linked.iter().filter(|l| l.key_id != req.key_id).count();
+            let own = linked.iter().filter(|l| l.key_id == req.key_id).count();
+            if others > 0 && own <= 1 {
+                return Err(anyhow!(
+                    "the owner wallet's last address can only be unlinked after every other wallet"
+                ));
+            }
+        }
+        self.ensure_not_frozen(&req.key_id)?;
+        if req.webauthn_assertion.is_none() {
+            return Err(anyhow!("identity links require WebAuthn ceremony"));
+        }
+        // TA binds both challenges to the same link commitment → delegate (true).
+        let passkey_assertion = self
+            .resolve_passkey_assertion(&req.key_id, None, req.webauthn_assertion.as_ref(), true)
+            .await?;
+        let owner = match &owner_key_id {
+            Some(owner_key_id) => Some(proto::OwnerApproval {
+                wallet_id: Uuid::parse_str(owner_key_id)?,
+                passkey_assertion: self
+                    .resolve_passkey_assertion(
+                        owner_key_id,
+                        None,
+                        req.owner_webauthn_assertion.as_ref(),
+                        true,
+                    )
+                    .await?,
+            }),
+            None => None,
+        };
+        let out = self
+            .tee
+            .link_wallet(proto::LinkWalletInput {
+                action,
+                identity_id: Uuid::parse_str(&identity_id)?,
+                wallet_id,
+                hd_path: req.derivation_path.clone(),
+                chain_ids: chain_ids.clone(),
+                passkey_assertion,
+                owner,
+            })
+            .await?;
+        let address = key_pin::address_hex(&out.address);
+        enforce_key_pin(&self.db, &req.key_id, &req.derivation_path, &address, None)?;
+        let signer =
+            key_pin::recover_signer(&identity::statement_digest(&out.statement), &out.signature)?;
+        if signer != out.address {
+            return Err(anyhow!(
+                "SECURITY: link statement is not signed by {}",
+                address
+            ));
+        }
+        let signature = format!("0x{}", hex::encode(&out.signature));
+
+        match action {
+            LinkAction::Link => {
+                let new_identity = match existing {
+                    Some(_) => None,
+                    None => Some(IdentityRow {
+                        identity_id: identity_id.clone(),
+                        label: req.label.clone().unwrap_or_default(),
+                        owner_key_id: req.key_id.clone(),
+                        created_at: Utc::now().timestamp(),
+                    }),
+                };
+                self.db.link_identity_address(
+                    &IdentityLinkRow {
+                        address: address.clone(),
+                        identity_id: identity_id.clone(),
+                        key_id: req.key_id.clone(),
+                        derivation_path: req.derivation_path.clone(),
+                        chain_ids,
+                        statement: out.statement.clone(),
+                        signature: signature.clone(),
+                        linked_at: Utc::now().timestamp(),
+                    },
+                    new_identity.as_ref(),
+                )?;
+                self.audit(&req.key_id, "identity_link", Some(&identity_id));
+            }
+            LinkAction::Unlink => {
+                if !self.db.unlink_identity_address(&identity_id, &address)? {
+                    return Err(anyhow!(
+                        "{} is not linked to identity {}",
+                        address,
+                        identity_id
+                    ));
+                }
+                self.audit(&req.key_id, "identity_unlink", Some(&identity_id));
+            }
+        }
+        let mut view = self.identity_view(&identity_id)?;
+        view["proof"] = serde_json::json!({
+            "address": address,
+            "statement": out.statement,
+            "signature": signature,
+        });
+        Ok(view)
+    }
+
+    /// The unified identity view, by identity id or by any linked address.
+    pub fn get_identity(&self, req: IdentityGetRequest) -> Result<serde_json::Value> {
+        let identity_id = match (req.identity_id, req.address) {
+            (Some(id), None) => id,
+            (None, Some(address)) => self
+                .db
+                .identity_for_address(&address)?
+                .ok_or_else(|| anyhow!("{} is not linked to an identity", address))?,
+            _ => return Err(anyhow!("give exactly one of identityId and address")),
+        };
+        self.identity_view(&identity_id)
+    }
+
+    /// An unlink of the last address returns the identity as deleted.
+    fn identity_view(&self, identity_id: &str) -> Result<serde_json::Value> {
+        let identity = match self.db.get_identity(identity_id)? {
+            Some(identity) => identity,
+            None => return Ok(serde_json::json!({ "identityId": identity_id, "deleted": true })),
+        };
+        let links = self.db.list_identity_links(identity_id)?;
+        let chains: std::collections::BTreeSet<u64> = links
+            .iter()
+            .flat_map(|l| l.chain_ids.iter().copied())
+            .collect();
+        let wallets: Vec<serde_json::Value> = links
+            .iter()
+            .map(|l| {
+                serde_json::json!({
+                    "keyId": l.key_id,
+                    "address": l.address,
+                    "derivationPath": l.derivation_path,
+                    "chainIds": l.chain_ids,
+                    "linkedAt": l.linked_at,
+                    "statement": l.statement,
+                    "signature": l.signature,
+                })
+            })
+            .collect();
+        Ok(serde_json::json!({
+            "identityId": identity.identity_id,
+            "label": identity.label,
+            "ownerKeyId": identity.owner_key_id,
+            "createdAt": identity.created_at,
+            "chainIds": chains,
+            "wallets": wallets,
+        }))
+    }
+
+    /// BIP85 child secret sealed to the recipient key in the TA; the CA only
+    /// relays the ciphertext.
+    pub async fn bip85_export(&self, req: Bip85ExportRequest) -> Result<serde_json::Value> {
+        use proto::bip85::Bip85App;
+
+        let wallet_id = Uuid::parse_str(&req.key_id)?;
+        let app = match (req.application.as_str(), req.words, req.num_bytes) {
+            ("bip39", Some(words), None) => Bip85App::Bip39 {
+                words,
+                index: req.index,
+            },
+            ("hex", None, Some(num_bytes)) => Bip85App::Hex {
+                num_bytes,
+                index: req.index,
+            },
+            ("bip39", _, _) => return Err(anyhow!("bip39 takes words and no numBytes")),
+            ("hex", _, _) => return Err(anyhow!("hex takes numBytes and no words")),
+            (other, _, _) => return Err(anyhow!("unsupported BIP85 application: {}", other)),
+        };
+        app.validate().map_err(|e| anyhow!("{}", e))?;
+        let recipient_pubkey = hex::decode(req.recipient_public_key.trim_start_matches("0x"))
+            .map_err(|e| anyhow!("recipientPublicKey is not hex: {}", e))?;
+        if recipient_pubkey.len() != 33 {
+            return Err(anyhow!(
+                "recipientPublicKey must be a compressed secp256k1 key (33 bytes)"
+            ));
+        }
+        self.ensure_not_frozen(&req.key_id)?;
+        if req.webauthn_assertion.is_none() {
+            return Err(anyhow!("BIP85 export requires WebAuthn ceremony"));
+        }
+        // TA binds the challenge to (wallet, child path, recipient) → delegate (true).
+        let passkey_assertion = self
+            .resolve_passkey_assertion(&req.key_id, None, req.webauthn_assertion.as_ref(), true)
+            .await?;
+        let package = self
+            .tee
+            .bip85_export(proto::Bip85ExportInput {
+                wallet_id,
+                app,
+                recipient_pubkey,
+                passkey_assertion,
+            })
+            .await?;
+        self.audit(&req.key_id, "bip85_export", Some(&app.path_string()));
+        Ok(serde_json::json!({
+            "keyId": req.key_id,
+            "path": app.path_string(),
+            "entropyBytes": app.entropy_len(),
+            "recipientPublicKey": format!("0x{}", hex::encode(&package.recipient_pubkey)),
+            "ephemeralPublicKey": format!("0x{}", hex::encode(&package.ephemeral_pubkey)),
+            "nonce": format!("0x{}", hex::encode(package.nonce)),
+            "ciphertext": format!("0x{}", hex::encode(&package.ciphertext)),
+            "mac": format!("0x{}", hex::encode(package.mac)),
+        }))
+    }
+
+    /// Passkey-approved ECIES decrypt session on one key of the wallet.
+    pub async fn open_decrypt_session(
+        &self,
+        req: OpenDecryptSessionRequest,
+    ) -> Result<serde_json::Value> {
+        let wallet_id = Uuid::parse_str(&req.key_id)?;
+        let scope = proto::ecies::DecryptScope {
+            hd_path: req.derivation_path,
+            max_decryptions: req.max_decryptions,
+            ttl_secs: req.ttl_secs,
+        };
+        scope.validate().map_err(|e| anyhow!("{}", e))?;
+        self.ensure_not_frozen(&req.key_id)?;
+        if req.webauthn_assertion.is_none() {
+            return Err(anyhow!(
+                "opening a decrypt session requires WebAuthn ceremony"
+            ));
+        }
+        // TA binds the challenge to (wallet, path, uses, lifetime) → delegate (true).
+        let passkey_assertion = self
+            .resolve_passkey_assertion(&req.key_id, None, req.webauthn_assertion.as_ref(), true)
+            .await?;
+        let session = self
+            .tee
+            .open_decrypt_session(proto::OpenDecryptSessionInput {
+                wallet_id,
+                scope: scope.clone(),
+                passkey_assertion,
+            })
+            .await?;
+        self.audit(&req.key_id, "decrypt_session_open", Some(&scope.hd_path));
+        Ok(serde_json::json!({
+            "keyId": req.key_id,
+            "derivationPath": scope.hd_path,
+            "sessionId": format!("0x{}", hex::encode(session.session_id)),
+            "maxDecryptions": scope.max_decryptions,
+            "expiresAt": session.expires_at,
+        }))
+    }
+
+    /// One ECIES payload opened in the TA under an open session.
+    pub async fn ecies_decrypt(&self, req: EciesDecryptRequest) -> Result<serde_json::Value> {
+        use std::convert::TryInto;
+
+        let wallet_id = Uuid::parse_str(&req.key_id)?;
+        let session_id: [u8; 16] = hex::decode(req.session_id.trim_start_matches("0x"))
+            .ok()
+            .and_then(|b| b.try_into().ok())
+            .ok_or_else(|| anyhow!("sessionId must be 16 bytes of hex"))?;
+        let payload = hex::decode(req.payload.trim_start_matches("0x"))
+            .map_err(|e| anyhow!("payload is not hex: {}", e))?;
+        proto::ecies::Envelope::parse(&payload).map_err(|e| anyhow!("{}", e))?;
+        self.ensure_not_frozen(&req.key_id)?;
+        let out = self
+            .tee
+            .ecies_decrypt(proto::EciesDecryptInput {
+                wallet_id,
+                session_id,
+                payload,
+            })
+            .await?;
+        self.audit(&req.key_id, "ecies_decrypt", None);
+        Ok(serde_json::json!({
+            "keyId": req.key_id,
+            "plaintext": format!("0x{}", hex::encode(&out.plaintext)),
+            "remaining": out.remaining,
+        }))
+    }
+
+    /// An account's EIP-5564 meta-address, registered with the stealth
+    /// scanner. Public keys only, so no passkey.
+    pub async fn stealth_meta_address(
+        &self,
+        req: StealthMetaAddressRequest,
+    ) -> Result<serde_json::Value> {
+        let wallet_id = Uuid::parse_str(&req.key_id)?;
+        if !self.db.wallet_exists(&req.key_id)? {
+            return Err(anyhow!("Key not found: {}", req.key_id));
+        }
+        let meta = self
+            .tee
+            .get_stealth_meta_address(proto::GetStealthMetaAddressInput {
+                wallet_id,
+                account: req.account,
+            })
+            .await?
+            .to_string();
+        self.db
+            .upsert_stealth_meta(&req.key_id, req.account, &meta)?;
+        self.audit(
+            &req.key_id,
+            "stealth_meta_address",
+            Some(&req.account.to_string()),
+        );
+        Ok(serde_json::json!({
+            "keyId": req.key_id,
+            "account": req.account,
+            "schemeId": proto::stealth::SCHEME_ID,
+            "metaAddress": meta,
+        }))
+    }
+
+    /// A one-time address paying `metaAddress`, and what the sender announces
+    /// with it. Works for any meta-address; no TEE call.
+    pub fn stealth_address(&self, req: StealthAddressRequest) -> Result<serde_json::Value> {
+        let meta = proto::stealth::StealthMetaAddress::parse(&req.meta_address)
+            .map_err(|e| anyhow!("{}", e))?;
+        let ephemeral = k256::SecretKey::random(&mut rand::rngs::OsRng);
+        let out = stealth::generate(&meta, &ephemeral)?;
+        Ok(serde_json::json!({
+            "stealthAddress": proto::eip55::to_checksum_address(&out.stealth_address),
+            "ephemeralPublicKey": format!("0x{}", hex::encode(&out.ephemeral_pubkey)),
+            "viewTag": format!("0x{:02x}", out.view_tag),
+            "schemeId": proto::stealth::SCHEME_ID,
+            "announcer": proto::stealth::ANNOUNCER,
+        }))
+    }
+
+    /// Scan one registered account now rather than at the next sweep.
+    pub async fn stealth_scan(&self, req: StealthScanRequest) -> Result<serde_json::Value> {
+        let row = self
+            .db
+            .list_stealth_meta()?
+            .into_iter()
+            .find(|r| r.key_id == req.key_id && r.account == req.account)
+            .ok_or_else(|| {
+                anyhow!(
+                    "no stealth meta-address registered for {} account {}",
+                    req.key_id,
+                    req.account
+                )
+            })?;
+        let (found, scanned_through) = self.scan_stealth_account(&row).await?;
+        Ok(serde_json::json!({
+            "keyId": req.key_id,
+            "account": req.account,
+            "found": found,
+            "scannedThrough": scanned_through,
+        }))
+    }
+
+    /// Hand the announcements past the account's cursor to the TA, a batch at
+    /// a time, and record each match in the ledger (`chain_events`). The
+    /// cursor moves after each batch, so an interrupted scan resumes.
+    pub async fn scan_stealth_account(&self, row: &StealthMetaRow) -> Result<(usize, i64)> {
+        use proto::stealth::MAX_SCAN_BATCH;
+
+        let wallet_id = Uuid::parse_str(&row.key_id)?;
+        let mut cursor = row.scanned_through;
+        let mut found = 0;
+        loop {
+            let batch = self
+                .db
+                .stealth_announcements_after(cursor, MAX_SCAN_BATCH as u32)?;
+            let last = match batch.last() {
+                Some((id, _)) => *id,
+                None => break,
+            };
+            let (positions, candidates): (Vec<usize>, Vec<_>) = batch
+                .iter()
+                .enumerate()
+                .filter_map(|(i, (_, a))| a.candidate().map(|c| (i, c)))
+                .unzip();
+            let matches = self
+                .tee
+                .stealth_scan(proto::StealthScanInput {
+                    wallet_id,
+                    account: row.account,
+                    candidates,
+                })
+                .await?;
+            for m in matches {
+                let (_, announcement) = positions
+                    .get(m as usize)
+                    .and_then(|&i| batch.get(i))
+                    .ok_or_else(|| anyhow!("TA matched announcement {} of a smaller batch", m))?;
+                let event = announcement.payment_event(&row.key_id);
+                match &self.chain_watch {
+                    Some(config) => chain_watch::record(config, &self.db, event, false).await?,
+                    None => {
+                        self.db.insert_chain_event(&event)?;
+                    }
+                }
+                found += 1;
+            }
+            self.db
+                .advance_stealth_cursor(&row.key_id, row.account, last)?;
+            cursor = last;
+            if batch.len() < MAX_SCAN_BATCH {
+                break;
+            }
+        }
+        Ok((found, cursor))
+    }
+
+    /// Preview of the permit review a SignPermit passkey confirms. No TEE call.
+    pub fn describe_permit(&self, req: DescribePermitRequest) -> Result<DescribePermitResponse> {
+        let permit = req.permit.to_proto()?;
+        let digest = permit.digest();
+        let summary = permit.summary();
+        Ok(DescribePermitResponse {
+            review_digest: format!(
+                "0x{}",
+                hex::encode(proto::permit::review_digest(&digest, &summary))
+            ),
+            digest: format!("0x{}", hex::encode(digest)),
+            summary,
+        })
+    }
+
+    pub async fn sign_permit(&self, req: SignPermitRequest) -> Result<SignPermitResponse> {
+        let wallet_id = Self::validate_key_id(&req.key_id)?;
+        let wallet_id_str = wallet_id.to_string();
+        self.ensure_not_frozen(&wallet_id_str)?;
+        let permit = req.permit.to_proto()?;
+
+        if req.webauthn_assertion.is_none() {
+            return Err(anyhow!("SignPermit requires WebAuthn ceremony"));
+        }
+        // TA binds the challenge to the permit review digest → delegate (true).
+        let passkey_assertion = self
+            .resolve_passkey_assertion(&wallet_id_str, None, req.webauthn_assertion.as_ref(), true)
+            .await?
+            .ok_or_else(|| anyhow!("Passkey assertion required to sign a permit"))?;
+
+        let output = self
+            .tee
+            .sign_permit(proto::SignPermitInput {
+                wallet_id,
+                hd_path: req.hd_path,
+                permit,
+                passkey_assertion: Some(passkey_assertion),
+            })
+            .await?;
+        println!("✅ SignPermit: keyId={} \"{}\"", req.key_id, output.summary);
+        Ok(SignPermitResponse {
+            key_id: req.key_id,
+            signature: format!("0x{}", hex::encode(&output.signature)),
+            summary: output.summary,
+        })
+    }
+
+    /// EIP-4361 sign-in as `keyId`. The TA re-parses the message and enforces
+    /// address, nonce, domain binding and freshness; the checks here only
+    /// fail early with a clearer error and bind the page origin.
+    pub async fn siwe_sign(&self, req: SiweSignRequest) -> Result<SiweSignResponse> {
+        let wallet_id = Self::validate_key_id(&req.key_id)?;
+        let wallet_id_str = wallet_id.to_string();
+        self.ensure_not_frozen(&wallet_id_str)?;
+        let message = proto::siwe::SiweMessage::parse(&req.message)
+            .map_err(|e| anyhow!("not a valid sign-in message: {}", e))?;
+        if let Some(origin) = &req.origin {
+            if siwe::origin_authority(origin) != message.domain {
+                return Err(anyhow!(
+                    "sign-in domain {} does not match origin {}",
+                    message.domain,
+                    origin
+                ));
+            }
+        }
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+        message.check_validity(now).map_err(|e| anyhow!("{}", e))?;
+
+        if req.webauthn_assertion.is_none() {
+            return Err(anyhow!("SIWE sign-in requires WebAuthn ceremony"));
+        }
+        // TA binds the challenge to the EIP-191 digest → delegate (true).
+        let passkey_assertion = self
+            .resolve_passkey_assertion(&wallet_id_str, None, req.webauthn_assertion.as_ref(), true)
+            .await?;
+        let signature = self
+            .tee
+            .sign_message(
+                wallet_id,
+                &req.hd_path,
+                req.message.as_bytes(),
+                passkey_assertion,
+                proto::SigningContext::Login,
+            )
+            .await?;
+        let signer = key_pin::recover_signer(&message.digest(), &signature)?;
+        enforce_key_pin(
+            &self.db,
+            &wallet_id_str,
+            &req.hd_path,
+            &key_pin::address_hex(&signer),
+            None,
+        )?;
+        println!(
+            "✅ SIWE sign-in: keyId={} domain={}",
+            req.key_id, message.domain
+        );
+        Ok(SiweSignResponse {
+            key_id: req.key_id,
+            address: proto::eip55::to_checksum_address(&signer),
+            signature: format!("0x{}", hex::encode(&signature)),
+        })
+    }
+
+    /// Verify a third party's sign-in. A failed check is `valid: false` with
+    /// a reason, not an error. No TEE call.
+    pub fn siwe_verify(&self, req: SiweVerifyRequest) -> Result<SiweVerifyResponse> {
+        let signature = hex::decode(req.signature.trim_start_matches("0x"))
+            .map_err(|_| anyhow!("signature is not hex"))?;
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+        Ok(
+            match siwe::verify(
+                &req.message,
+                &signature,
+                req.domain.as_deref(),
+                req.nonce.as_deref(),
+                now,
+            ) {
+                Ok(v) => SiweVerifyResponse {
+                    valid: true,
+                    address: Some(proto::eip55::to_checksum_address(&v.address)),
+                    chain_id: Some(v.message.chain_id),
+                    reason: None,
+                },
+                Err(e) => SiweVerifyResponse {
+                    valid: false,
+                    address: None,
+                    chain_id: None,
+                    reason: Some(e.to_string()),
+                },
+            },
+        )
+    }
+
+    /// Online side: build and record an offline request. No TEE call — the
+    /// key lives on the air-gapped device; this CA only needs the address to
+    /// check the returned signature against.
+    pub async fn export_offline_request(
+        &self,
+        req: ExportOfflineRequest,
+    ) -> Result<ExportOfflineResponse> {
+        let wallet_uuid = Uuid::parse_str(&req.key_id)?;
+        let key_id = wallet_uuid.to_string();
+        let address = self
+            .db
+            .address_for_key_path(&key_id, &req.derivation_path)?
+            .ok_or_else(|| {
+                anyhow!(
+                    "No derived address for {} at {}; derive it before exporting",
+                    key_id,
+                    req.derivation_path
+                )
+            })?;
+        let ttl = req.ttl_seconds.unwrap_or(DEFAULT_OFFLINE_TTL_SECS);
+        if ttl == 0 || ttl > proto::offline::MAX_OFFLINE_TTL_SECS {
+            return Err(anyhow!(
+                "ttlSeconds must be 1..={}",
+                proto::offline::MAX_OFFLINE_TTL_SECS
+            ));
+        }
+        let mut request_id = [0u8; 16];
+        rand::RngCore::fill_bytes(&mut rand::rngs::OsRng, &mut request_id);
+        let created_at = Utc::now().timestamp() as u64;
+        let request = proto::offline::OfflineSignRequest {
+            version: proto::offline::OFFLINE_FORMAT_VERSION,
+            request_id,
+            wallet_id: wallet_uuid,
+            hd_path: req.derivation_path,
+            transaction: req.transaction.to_proto()?,
+            created_at,
+            expires_at: created_at + ttl,
+        };
+        let text = crate::offline::encode_request(&request)?;
+        self.db.insert_offline_request(
+            &hex::encode(request_id),
+            &key_id,
+            &address,
+            &text,
+            created_at as i64,
+            request.expires_at as i64,
+        )?;
+        println!(
+            "📤 OfflineExport: wallet={} request={} expires_at={}",
+            key_id,
+            hex::encode(request_id),
+            request.expires_at
+        );
+        Ok(ExportOfflineResponse {
+            request_id: hex::encode(request_id),
+            summary: proto::calldata::summarize_transaction(&request.transaction, &[]),
+            request: text,
+            expires_at: request.expires_at,
+        })
+    }
+
+    /// Air-gapped side: the TA checks expiry and the request id, so a
+    /// request signed once here can never be signed again.
+    pub async fn sign_offline_request(
+        &self,
+        req: SignOfflineRequest,
+    ) -> Result<SignOfflineResponse> {
+        let request = crate::offline::decode_request(&req.request)?;
+        request
+            .check(Utc::now().timestamp() as u64)
+            .map_err(|e| anyhow!("{}", e))?;
+        let wallet_id_str = request.wallet_id.to_string();
+        self.ensure_not_frozen(&wallet_id_str)?;
+
+        if req.webauthn_assertion.is_none() {
+            return Err(anyhow!("offline signing requires WebAuthn ceremony"));
+        }
+        // TA binds the challenge to offline_commitment(request, tx hash).
+        let assertion = self
+            .resolve_passkey_assertion(&wallet_id_str, None, req.webauthn_assertion.as_ref(), true)
+            .await?
+            .ok_or_else(|| anyhow!("Passkey assertion required for offline signing"))?;
+
+        let summary = proto::calldata::summarize_transaction(&request.transaction, &[]);
+        let response = self.tee.sign_offline(request, Some(assertion)).await?;
+        println!(
+            "✅ OfflineSign: wallet={} request={} \"{}\"",
+            wallet_id_str,
+            hex::encode(response.request_id),
+            summary
+        );
+        Ok(SignOfflineResponse {
+            request_id: hex::encode(response.request_id),
+            response: crate::offline::encode_response(&response)?,
+            summary,
+        })
+    }
+
+    /// Online side: accept one verified response per exported request.
+    pub async fn import_offline_response(
+        &self,
+        req: ImportOfflineRequest,
+    ) -> Result<ImportOfflineResponse> {
+        let response = crate::offline::decode_response(&req.response)?;
+        let request_id = hex::encode(response.request_id);
+        let row = self
+            .db
+            .get_offline_request(&request_id)?
+            .ok_or_else(|| anyhow!("Unknown offline request {}", request_id))?;
+        if row.status != "pending" {
+            return Err(anyhow!("Offline request {} already imported", request_id));
+        }
+        let request = crate::offline::decode_request(&row.request)?;
+        let verified = crate::offline::verify_response(&request, &response)?;
+        let signer = format!("0x{}", hex::encode(verified.signer));
+        if signer != row.address {
+            return Err(anyhow!(
+                "Offline response signed by {}, expected {}",
+                signer,
+                row.address
+            ));
+        }
+        let tx_hash = format!("0x{}", hex::encode(verified.transaction_hash));
+        if !self.db.complete_offline_request(&request_id, &tx_hash)? {
+            return Err(anyhow!(
+                "Offline request {} expired or already imported",
+                request_id
+            ));
+        }
+        println!(
+            "📥 OfflineImport: wallet={} request={} tx={}",
+            row.key_id, request_id, tx_hash
+        );
+        Ok(ImportOfflineResponse {
+            request_id,
+            key_id: row.key_id,
+            address: row.address,
+            transaction_hash: tx_hash,
+            signed_transaction: format!("0x{}", hex::encode(&response.signed_transaction)),
+        })
+    }
+
+    pub async fn capabilities(&self) -> Result<CapabilitiesResponse> {
+        Ok(capabilities_response(self.tee.get_capabilities().await?))
+    }
+
+    /// Target side: open a replication session on this TA.
+    /// Decode the hardware wallet's account xpub, derive the addresses the
+    /// user should see on the device, and have the TA open a session bound
+    /// to that xpub.
+    pub async fn migration_begin(
+        &self,
+        req: MigrationBeginRequest,
+    ) -> Result<MigrationBeginResponse> {
+        let count = req
+            .address_count
+            .unwrap_or(migration::DEFAULT_ADDRESS_COUNT);
+        if count == 0 || count as usize > proto::migration::MAX_CONFIRMED_ADDRESSES {
+            return Err(anyhow!("addressCount must be 1-20"));
+        }
+        let xpub = migration::parse_account_xpub(&req.xpub)?;
+        let addresses = migration::receive_addresses(&xpub, count)?;
+        let account = proto::migration::AccountXpub::from(&xpub);
+        let offer = self.tee.migration_offer(account.clone()).await?;
+        let digest = proto::migration::offer_digest(
+            &offer.session_id,
+            &offer.ephemeral_pubkey,
+            &account,
+            offer.created_at,
+        );
+        println!(
+            "🔑 MigrationBegin: session {} for account key 0x{}",
+            hex::encode(offer.session_id),
+            hex::encode(xpub.public_key)
+        );
+        Ok(MigrationBeginResponse {
+            session_id: format!("0x{}", hex::encode(offer.session_id)),
+            ephemeral_public_key: format!("0x{}", hex::encode(&offer.ephemeral_pubkey)),
+            account_path: proto::migration::ACCOUNT_PATH,
+            expected_addresses: addresses
+                .iter()
+                .enumerate()
+                .map(|(i, address)| MigrationExpectedAddress {
+                    index: i as u32,
+                    path: format!("{}/0/{}", proto::migration::ACCOUNT_PATH, i),
+                    address: proto::eip55::to_checksum_address(address),
+                })
+                .collect(),
+            offer_digest: format!("0x{}", hex::encode(digest)),
+            expires_at: offer.created_at + proto::migration::MIGRATION_TTL_SECS,
+            attestation: offer.attestation.map(AttestationResponse::from_evidence),
+        })
+    }
+
+    fn gas_tank_config(&self) -> Result<&GasTankConfig> {
+        self.gas_tanks
+            .as_ref()
+            .ok_or_else(|| anyhow!("Gas tanks are off (KMS_GAS_TANK_RPC)"))
+    }
+
+    /// Designate a relayer address as a gas tank, or change its floor and
+    /// refill source.
+    pub async fn gas_tank_set(&self, req: GasTankSetRequest) -> Result<serde_json::Value> {
+        let config = self.gas_tank_config()?;
+        config.rpc_url(req.chain_id)?;
+        let address =
+            proto::eip55::parse_address(&req.address).map_err(|e| anyhow!("address: {}", e))?;
+        let address = proto::calldata::hex_addr(&address);
+        let min_balance_wei = parse_wei("minBalanceWei", &req.min_balance_wei)?;
+        let top_up = match (req.top_up_wei, req.treasury_key_id) {
+            (None, None) => None,
+            (Some(amount), Some(key_id)) => {
+                let amount_wei = parse_wei("topUpWei", &amount)?;
+                if amount_wei == 0 {
+                    return Err(anyhow!("topUpWei must be positive"));
+                }
+                let path = req.treasury_derivation_path.unwrap_or_else(default_hd_path);
+                let treasury = self
+                    .db
+                    .address_for_key_path(&key_id, &path)?
+                    .ok_or_else(|| {
+                        anyhow!(
+                        "treasury address for {} at {} not derived yet; call DeriveAddress first",
+                        key_id,
+                        path
+                    )
+                    })?;
+                if treasury.eq_ignore_ascii_case(&address) {
+                    return Err(anyhow!("a gas tank cannot refill itself"));
+                }
+                Some(crate::db::GasTankSource {
+                    amount_wei,
+                    treasury_key_id: key_id,
+                    treasury_path: path,
+                })
+            }
+            _ => return Err(anyhow!("topUpWei and treasuryKeyId go together")),
+        };
+        let tank = GasTankRow {
+            address,
+            chain_id: req.chain_id,
+            label: req.label.unwrap_or_default(),
+            min_balance_wei,
+            top_up,
+            balance_wei: None,
+            checked_at: None,
+            low_since: None,
+            created_at: chrono::Utc::now().timestamp(),
+        };
+        self.db.upsert_gas_tank(&tank)?;
+        println!(
+            "⛽ Gas tank {} on chain {}: floor {} wei{}",
+            tank.address,
+            tank.chain_id,
+            tank.min_balance_wei,
+            if tank.top_up.is_some() {
+                ", refills from treasury"
+            } else {
+                ""
+            }
+        );
+        let tank = self
+            .db
+            .list_gas_tanks()?
+            .into_iter()
+            .find(|t| t.address == tank.address && t.chain_id == tank.chain_id)
+            .ok_or_else(|| anyhow!("gas tank vanished"))?;
+        Ok(gas_tank::tank_json(&tank, &[]))
+    }
+
+    pub async fn gas_tank_remove(&self, req: GasTankRemoveRequest) -> Result<serde_json::Value> {
+        if !self.db.delete_gas_tank(&req.address, req.chain_id)? {
+            return Err(anyhow!(
+                "Gas tank not found: {} on chain {}",
+                req.address,
+                req.chain_id
+            ));
+        }
+        Ok(
+            serde_json::json!({ "address": req.address.to_lowercase(), "chainId": req.chain_id, "removed": true }),
+        )
+    }
+
+    /// Every tank with its last reading and open refills.
+    pub async fn gas_tanks(&self) -> Result<serde_json::Value> {
+        let db = self.db.clone();
+        let (tanks, open) = db
+            .run(|db| Ok((db.list_gas_tanks()?, db.open_gas_tank_topups()?)))
+            .await?;
+        Ok(serde_json::json!({
+            "enabled": self.gas_tanks.is_some(),
+            "tanks": tanks
+                .iter()
+                .map(|t| {
+                    let mine: Vec<&GasTankTopUpRow> = open
+                        .iter()
+                        .filter(|o| o.address == t.address && o.chain_id == t.chain_id)
+                        .collect();
+                    gas_tank::tank_json(t, &mine)
+                })
+                .collect::<Vec<_>>(),
+        }))
+    }
+
+    /// First control: an operator named in KMS_GAS_TANK_APPROVERS approves a
+    /// proposed refill with their own passkey. The treasury's owner cannot
+    /// approve their own outflow.
+    pub async fn gas_tank_topup_approve(
+        &self,
+        req: GasTankTopUpApproveRequest,
+    ) -> Result<serde_json::Value> {
+        let config = self.gas_tank_config()?;
+        if !config.approvers.contains(&req.approver_key_id) {
+            return Err(anyhow!(
+                "{} is not a gas tank approver (KMS_GAS_TANK_APPROVERS)",
+                req.approver_key_id
+            ));
+        }
+        let row = self.gas_tank_topup(req.top_up_id)?;
+        if row.source.treasury_key_id == req.approver_key_id {
+            return Err(anyhow!("the treasury wallet cannot approve its own top-up"));
+        }
+        self.ensure_not_frozen(&req.approver_key_id)?;
+        self.resolve_passkey_assertion(
+            &req.approver_key_id,
+            None,
+            req.webauthn_assertion.as_ref(),
+            false,
+        )
+        .await?
+        .ok_or_else(|| anyhow!("approver WebAuthn ceremony required"))?;
+        if !self.db.advance_gas_tank_topup(
+            row.id,
+            "proposed",
+            "approved",
+            Some(&req.approver_key_id),
+            None,
+            None,
+        )? {
+            return Err(anyhow!("top-up {} is no longer awaiting approval", row.id));
+        }
+        let detail = format!(
+            "#{} {} wei to {} on chain {}",
+            row.id, row.source.amount_wei, row.address, row.chain_id
+        );
+        self.audit(
+            &req.approver_key_id,
+            "gas_tank_topup_approved",
+            Some(&detail),
+        );
+        println!("⛽ Top-up {} approved by {}", detail, req.approver_key_id);
+        Ok(gas_tank::topup_json(&self.gas_tank_topup(row.id)?))
+    }
+
+    /// Second control: the treasury wallet signs the approved refill through
+    /// the normal Sign flow. Without an assertion, build the transaction (fresh
+    /// nonce and gas price) and return it for review; with one, sign exactly
+    /// that transaction and broadcast it.
+    pub async fn gas_tank_topup_execute(
+        &self,
+        req: GasTankTopUpExecuteRequest,
+    ) -> Result<serde_json::Value> {
+        let config = self.gas_tank_config()?;
+        let row = self.gas_tank_topup(req.top_up_id)?;
+        if row.status != "approved" {
+            return Err(anyhow!("top-up {} is {}, not approved", row.id, row.status));
+        }
+        let rpc_url = config.rpc_url(row.chain_id)?;
+        let source = &row.source;
+
+        let webauthn = match req.webauthn_assertion {
+            Some(wa) => wa,
+            None => {
+                let treasury = self
+                    .db
+                    .address_for_key_path(&source.treasury_key_id, &source.treasury_path)?
+                    .ok_or_else(|| anyhow!("treasury address not derived"))?;
+                let transaction = EthereumTransaction {
+                    chain_id: row.chain_id,
+                    nonce: gas_tank::pending_nonce(rpc_url, &treasury).await?,
+                    to: row.address.clone(),
+                    value: format!("0x{:x}", source.amount_wei),
+                    gas_price: format!("0x{:x}", gas_tank::gas_price(rpc_url).await?),
+                    gas: tx_rescue::TRANSFER_GAS as u64,
+                    data: "0x".to_string(),
+                    max_fee_per_gas: None,
+                    max_priority_fee_per_gas: None,
+                    access_list: Vec::new(),
+                };
+                let summary = proto::calldata::summarize_transaction(&transaction.to_proto()?, &[]);
+                let stored = serde_json::to_string(&transaction)?;
+                if !self.db.advance_gas_tank_topup(
+                    row.id,
+                    "approved",
+                    "approved",
+                    None,
+                    Some(&stored),
+                    None,
+                )? {
+                    return Err(anyhow!("top-up {} is no longer approved", row.id));
+                }
+                let mut out = gas_tank::topup_json(&self.gas_tank_topup(row.id)?);
+                out["summary"] = serde_json::json!(summary);
+                return Ok(out);
+            }
+        };
+        let transaction: EthereumTransaction =
+            serde_json::from_str(row.transaction.as_deref().ok_or_else(|| {
+                anyhow!(
+                    "top-up {} has no transaction yet; call without webAuthnAssertion first",
+                    row.id
+                )
+            })?)?;
+        let signed = self
+            .sign(SignRequest {
+                address: None,
+                key_id: Some(source.treasury_key_id.clone()),
+                derivation_path: Some(source.treasury_path.clone()),
+                transaction: Some(transaction),
+                message: None,
+                signing_algorithm: None,
+                passkey: None,
+                webauthn: Some(webauthn),
+                context: None,
+                summary_abis: None,
+                summary_committed: req.summary_committed,
+            })
+            .await?;
+        let raw_tx = format!("0x{}", signed.signature.trim_start_matches("0x"));
+        let tx_hash = gas_tank::raw_tx_hash(&raw_tx)?;
+        if !self.db.advance_gas_tank_topup(
+            row.id,
+            "approved",
+            "signed",
+            None,
+            None,
+            Some(&tx_hash),
+        )? {
+            return Err(anyhow!("top-up {} is no longer approved", row.id));
+        }
+        let detail = format!(
+            "#{} {} wei to {} on chain {}",
+            row.id, source.amount_wei, row.address, row.chain_id
+        );
+        self.audit(
+            &source.treasury_key_id,
+            "gas_tank_topup_signed",
+            Some(&detail),
+        );
+        let treasury = self
+            .db
+            .address_for_key_path(&source.treasury_key_id, &source.treasury_path)?
+            .ok_or_else(|| anyhow!("treasury address not derived"))?;
+        let screening = self
+            .screen_broadcast(
+                &source.treasury_key_id,
+                &ScreeningRequest {
+                    chain_id: row.chain_id,
+                    from: treasury,
+                    to: row.address.clone(),
+                    value_wei: source.amount_wei,
+                    purpose: "gas-tank-topup",
+                },
+            )
+            .await;
+        let mut out = if screening.broadcast {
+            let broadcast = gas_tank::send_raw(rpc_url, &raw_tx).await;
+            if broadcast.is_ok() {
+                self.db
+                    .advance_gas_tank_topup(row.id, "signed", "sent", None, None, None)?;
+            }
+            let mut out = gas_tank::topup_json(&self.gas_tank_topup(row.id)?);
+            match broadcast {
+                Ok(_) => println!("⛽ Top-up {} sent: {}", detail, tx_hash),
+                Err(e) => {
+                    eprintln!("⚠️  Top-up {} signed but not sent: {:#}", detail, e);
+                    out["rawTransaction"] = serde_json::json!(raw_tx);
+                    out["broadcastError"] = serde_json::json!(format!("{:#}", e));
+                }
+            }
+            out
+        } else {
+            // Held: the raw transaction is not handed out either, or the
+            // caller could broadcast what screening stopped.
+            eprintln!(
+                "🧾 Top-up {} signed but held by compliance screening",
+                detail
+            );
+            gas_tank::topup_json(&self.gas_tank_topup(row.id)?)
+        };
+        out["screening"] = serde_json::json!(screening);
+        out["summary"] = serde_json::json!(signed.summary);
+        Ok(out)
+    }
+
+    /// Screen a transaction the CA is about to send and audit the decision
+    /// on `key_id`, the sending wallet.
+    async fn screen_broadcast(
+        &self,
+        key_id: &str,
+        request: &ScreeningRequest,
+    ) -> compliance::Decision {
+        let decision = match &self.compliance {
+            Ok(screening) => screening.check(request).await,
+            Err(e) => compliance::Decision {
+                provider: "misconfigured".to_string(),
+                outcome: "error",
+                broadcast: false,
+                reason: Some(e.clone()),
+            },
+        };
+        self.audit(
+            key_id,
+            "compliance_screening",
+            Some(&decision.audit_detail(request)),
+        );
+        decision
+    }
+
+    fn gas_tank_topup(&self, id: i64) -> Result<GasTankTopUpRow> {
+        self.db
+            .get_gas_tank_topup(id)?
+            .ok_or_else(|| anyhow!("Gas tank top-up not found: {}", id))
+    }
+
+    pub async fn replication_offer(&self) -> Result<ReplicationOfferResponse> {
+        let hello = self.tee.replication_offer().await?;
+        let device_id = replication::local_device_id();
+        println!("🔁 ReplicationOffer: device={}", device_id);
+        Ok(ReplicationOfferResponse {
+            region: replication::local_region(),
+            attestation_key_fingerprint: replication::attestation_key_fingerprint(
+                &hello.attestation,
+            ),
+            ta_measurement: format!("0x{}", hex::encode(&hello.attestation.ta_measurement)),
+            expires_at: hello.created_at + proto::replication::REPLICATION_TTL_SECS,
+            hello: replication::encode_hello(&hello)?,
+            device_id,
+        })
+    }
+
+    /// Source side: the TA checks the target's attestation and the owner's
+    /// approval of its hello, then encrypts the wallet to it.
+    pub async fn replication_export(
+        &self,
+        req: ReplicationExportRequest,
+    ) -> Result<ReplicationExportResponse> {
+        let wallet_uuid = Self::validate_key_id(&req.key_id)?;
+        let key_id = wallet_uuid.to_string();
+        let wallet = self
+            .db
+            .get_wallet(&key_id)?
+            .ok_or_else(|| anyhow!("Key not found: {}", key_id))?;
+        self.ensure_not_frozen(&key_id)?;
+        let hello = replication::decode_hello(&req.hello)?;
+        let target_fp = replication::attestation_key_fingerprint(&hello.attestation);
+        check_device_pin(&self.db, &req.target_device_id, &target_fp)?;
+        let mut resp = ReplicationExportResponse {
+            key_id: key_id.clone(),
+            target_device_id: req.target_device_id.clone(),
+            target_attestation_key_fingerprint: target_fp.clone(),
+            approval_payload: format!("0x{}", hex::encode(hello.digest())),
+            source_device_id: None,
+            package: None,
+            wallet: None,
+        };
+        if req.webauthn_assertion.is_none() {
+            // Approval step: the owner confirms the target device and signs.
+            return Ok(resp);
+        }
+        let assertion = self
+            .resolve_passkey_assertion_strict(&key_id, None, req.webauthn_assertion.as_ref(), true)
+            .await?
+            .ok_or_else(|| anyhow!("Passkey assertion required for replication"))?;
+        let package = self
+            .tee
+            .replication_export(wallet_uuid, hello, Some(assertion))
+            .await?;
+
+        let source_id = replication::local_device_id();
+        let source_fp = replication::attestation_key_fingerprint(&package.source.attestation);
+        self.db
+            .add_wallet_device(&key_id, &source_id, None, &source_fp)?;
+        self.db.add_wallet_device(
+            &key_id,
+            &req.target_device_id,
+            req.target_endpoint.as_deref(),
+            &target_fp,
+        )?;
+        println!(
+            "🔁 ReplicationExport: wallet={} → device={} ({})",
+            key_id, req.target_device_id, target_fp
+        );
+        resp.source_device_id = Some(source_id);
+        resp.package = Some(replication::encode_package(&package)?);
+        resp.wallet = Some(ReplicatedWallet {
+            description: wallet.description,
+            key_usage: wallet.key_usage,
+            key_spec: wallet.key_spec,
+            origin: wallet.origin,
+            passkey_pubkey: wallet.passkey_pubkey,
+            credential_id: wallet.credential_id,
+            address: self
+                .db
+                .address_for_key_path(&key_id, replication::CHECK_PATH)?,
+        });
+        Ok(resp)
+    }
+
+    /// Target side: store the replica, check it derives the source's address
+    /// and record both devices in the wallet's set.
+    pub async fn replication_import(
+        &self,
+        req: ReplicationImportRequest,
+    ) -> Result<WalletDevicesResponse> {
+        let package = replication::decode_package(&req.package)?;
+        let key_id = package.wallet_id.to_string();
+        if self.db.get_wallet(&key_id)?.is_some() {
+            return Err(anyhow!("Key {} already exists on this device", key_id));
+        }
+        let source_fp = replication::attestation_key_fingerprint(&package.source.attestation);
+        check_device_pin(&self.db, &req.source_device_id, &source_fp)?;
+        let nonce = package.session_id.to_vec();
+        let imported = self.tee.replication_import(package).await?;
+        let address = key_pin::address_hex(&imported.address);
+        if let Some(expected) = &req.wallet.address {
+            if !expected.eq_ignore_ascii_case(&address) {
+                eprintln!(
+                    "🔴 Replication of {} derives {}, source reported {}",
+                    key_id, address, expected
+                );
+                return Err(anyhow!(
+                    "SECURITY: replica derives {}, source reported {}",
+                    address,
+                    expected
+                ));
+            }
+        }
+        let w = req.wallet;
+        self.db.insert_wallet(&WalletRow {
+            key_id: key_id.clone(),
+            address: Some(address.clone()),
+            public_key: None,
+            derivation_path: Some(replication::CHECK_PATH.to_string()),
+            description: w.description,
+            key_usage: w.key_usage,
+            key_spec: w.key_spec,
+            origin: w.origin,
+            passkey_pubkey: w.passkey_pubkey,
+            credential_id: w.credential_id,
+            sign_count: 0,
+            status: "ready".to_string(),
+            error_msg: None,
+            created_at: Utc::now().to_rfc3339(),
+        })?;
+        enforce_key_pin(&self.db, &key_id, replication::CHECK_PATH, &address, None)?;
+
+        let own = self.tee.get_attestation(nonce).await?;
+        self.db.add_wallet_device(
+            &key_id,
+            &replication::local_device_id(),
+            None,
+            &replication::attestation_key_fingerprint(&own),
+        )?;
+        self.db.add_wallet_device(
+            &key_id,
+            &req.source_device_id,
+            req.source_endpoint.as_deref(),
+            &source_fp,
+        )?;
+        if let Some(primary) = &req.primary_region {
+            self.db.set_key_region(
+                &key_id,
+                primary,
+                &req.source_device_id,
+                replication::PRIMARY,
+            )?;
+            self.db.set_key_region(
+                &key_id,
+                &replication::local_region(),
+                &replication::local_device_id(),
+                replication::REPLICA,
+            )?;
+        }
+        println!(
+            "🔁 ReplicationImport: wallet={} address={} from device={}",
+            key_id, address, req.source_device_id
+        );
+        self.replication_devices(&key_id).await
+    }
+
+    /// AWS ReplicateKey over the export ceremony: the replica region becomes
+    /// part of the key's region set once the owner has approved the export.
+    pub async fn replicate_key(&self, req: ReplicateKeyRequest) -> Result<ReplicateKeyResponse> {
+        let key_id = Self::validate_key_id(&req.key_id)?.to_string();
+        let region = replication::local_region();
+        let regions = self.db.list_key_regions(&key_id)?;
+        if !regions
+            .iter()
+            .any(|r| r.region == region && r.role == replication::PRIMARY)
+        {
+            return Err(verify::exception(
+                "UnsupportedOperationException",
+                format!("{} is not a multi-region primary key in {}", key_id, region),
+            ));
+        }
+        if regions.iter().any(|r| r.region == req.replica_region) {
+            return Err(verify::exception(
+                "AlreadyExistsException",
+                format!("{} already has a key in {}", key_id, req.replica_region),
+            ));
+        }
+        let device_id = req
+            .replica_device_id
+            .clone()
+            .unwrap_or_else(|| req.replica_region.clone());
+        let approve = req.webauthn.is_some();
+        let export = self
+            .replication_export(ReplicationExportRequest {
+                key_id: key_id.clone(),
+                hello: req.replication_hello,
+                target_device_id: device_id.clone(),
+                target_endpoint: req.replica_endpoint.clone(),
+                webauthn_assertion: req.webauthn,
+            })
+            .await?;
+        let mut replica_import = None;
+        if approve {
+            let (package, mut wallet) = export
+                .package
+                .zip(export.wallet)
+                .ok_or_else(|| anyhow!("export returned no package for {}", key_id))?;
+            if let Some(description) = req.description {
+                wallet.description = description;
+            }
+            self.db.set_key_region(
+                &key_id,
+                &req.replica_region,
+                &device_id,
+                replication::REPLICA,
+            )?;
+            println!(
+                "🔁 ReplicateKey: {} {} → {} (device {})",
+                key_id, region, req.replica_region, device_id
+            );
+            replica_import = Some(ReplicationImportRequest {
+                package,
+                wallet,
+                source_device_id: replication::local_device_id(),
+                source_endpoint: None,
+                primary_region: Some(region),
+            });
+        }
+
+        let w = self
+            .db
+            .get_wallet(&key_id)?
+            .ok_or_else(|| anyhow!("Key not found: {}", key_id))?;
+        let mut metadata = wallet_to_metadata(&w);
+        metadata.arn = replication::region_arn(&req.replica_region, &key_id);
+        metadata.multi_region = true;
+        let mut regions = self.db.list_key_regions(&key_id)?;
+        if !approve {
+            // Show the set as it will be once approved.
+            regions.push(KeyRegionRow {
+                key_id: key_id.clone(),
+                region: req.replica_region.clone(),
+                device_id,
+                role: replication::REPLICA.to_string(),
+                created_at: 0,
+            });
+        }
+        metadata.multi_region_configuration =
+            multi_region_configuration(&key_id, &regions, &req.replica_region);
+        Ok(ReplicateKeyResponse {
+            replica_key_metadata: metadata,
+            approval_payload: export.approval_payload,
+            replica_import,
+        })
+    }
+
+    pub fn update_primary_region(&self, req: UpdatePrimaryRegionRequest) -> Result<()> {
+        let key_id = Self::validate_key_id(&req.key_id)?.to_string();
+        if self.db.list_key_regions(&key_id)?.is_empty() {
+            return Err(verify::exception(
+                "UnsupportedOperationException",
+                format!("{} is not a multi-region key", key_id),
+            ));
+        }
+        if !self.db.set_primary_region(&key_id, &req.primary_region)? {
+            return Err(verify::exception(
+                "NotFoundException",
+                format!("{} has no replica in {}", key_id, req.primary_region),
+            ));
+        }
+        println!(
+            "🔁 UpdatePrimaryRegion: {} primary={} (this node: {})",
+            key_id,
+            req.primary_region,
+            replication::local_region()
+        );
+        Ok(())
+    }
+
+    /// A healthy replica to send a Sign for `key_id` to instead of this node.
+    pub fn sign_failover(&self, key_id: &str, reason: &str) -> Result<Option<Failover>> {
+        let devices = self.db.list_wallet_devices(key_id)?;
+        let device = match replication::failover(&devices, Utc::now().timestamp()) {
+            Some(d) => d,
+            None => return Ok(None),
+        };
+        let region = self
+            .db
+            .list_key_regions(key_id)?
+            .into_iter()
+            .find(|r| r.device_id == device.device_id)
+            .map(|r| r.region);
+        Ok(Some(Failover {
+            key_id: key_id.to_string(),
+            device_id: device.device_id.clone(),
+            region,
+            // failover() only returns remote devices
+            endpoint: device.endpoint.clone().unwrap_or_default(),
+            reason: reason.to_string(),
+        }))
+    }
+
+    pub async fn replication_devices(&self, key_id: &str) -> Result<WalletDevicesResponse> {
+        let key_id = Self::validate_key_id(key_id)?.to_string();
+        // This node is answering, so it is alive.
+        self.db
+            .record_device_heartbeat(&replication::local_device_id(), None, None)?;
+        let rows = self.db.list_wallet_devices(&key_id)?;
+        if rows.is_empty() {
+            return Err(anyhow!("Key {} has no replica devices", key_id));
+        }
+        Ok(wallet_devices_response(
+            &key_id,
+            &rows,
+            Utc::now().timestamp(),
+        ))
+    }
+
+    /// Owner-approved: stop routing to a device. The device's TA still holds
+    /// its copy until the key is deleted there.
+    pub async fn replication_revoke(
+        &self,
+        req: ReplicationRevokeRequest,
+    ) -> Result<WalletDevicesResponse> {
+        let key_id = Self::validate_key_id(&req.key_id)?.to_string();
+        if req.webauthn_assertion.is_none() {
+            return Err(anyhow!("revoking a device requires WebAuthn ceremony"));
+        }
+        // Host-only change: keep the strict challenge == nonce check.
+        self.resolve_passkey_assertion_strict(
+            &key_id,
+            None,
+            req.webauthn_assertion.as_ref(),
+            false,
+        )
+        .await?
+        .ok_or_else(|| anyhow!("Passkey assertion required to revoke a device"))?;
+        if !self.db.revoke_wallet_device(&key_id, &req.device_id)? {
+            return Err(anyhow!(
+                "device {} is not an active replica of {}",
+                req.device_id,
+                key_id
+            ));
+        }
+        self.audit(
+            &key_id,
+            "replica_revoked",
+            Some(&format!("device {}", req.device_id)),
+        );
+        println!(
+            "🔁 ReplicationRevoke: wallet={} device={}",
+            key_id, req.device_id
+        );
+        self.replication_devices(&key_id).await
+    }
+
+    pub async fn device_heartbeat(
+        &self,
+        req: DeviceHeartbeatRequest,
+    ) -> Result<DeviceHeartbeatResponse> {
+        let wallets = self.db.record_device_heartbeat(
+            &req.device_id,
+            req.endpoint.as_deref(),
+            req.quarantined,
+        )?;
+        if req.quarantined == Some(true) {
+            println!(
+                "🚧 Heartbeat: device {} reports itself quarantined — not routed to",
+                req.device_id
+            );
+        }
+        Ok(DeviceHeartbeatResponse {
+            device_id: req.device_id,
+            wallets,
+        })
+    }
+
+    // ── Device health (device_health.rs) ──
+
+    /// SelfTest and attestation over a fresh nonce, judged against the
+    /// compiled measurements manifest. Both commands still run while
+    /// quarantined. A failure quarantines this node; a pass lifts nothing.
+    pub async fn run_device_health_check(&self, source: &str) -> Result<HealthReport> {
+        let _running = self.health_check_running.lock().await;
+        let mut nonce = vec![0u8; 32];
+        rand::RngCore::fill_bytes(&mut rand::rngs::OsRng, &mut nonce);
+        let self_test = self.tee.self_test().await.map_err(|e| format!("{:#}", e));
+        let evidence = self
+            .tee
+            .get_attestation(nonce.clone())
+            .await
+            .map_err(|e| format!("{:#}", e));
+        let device_id = replication::local_device_id();
+        let pinned = self
+            .db
+            .run(move |db| db.device_attestation_fp(&device_id))
+            .await?;
+        let manifest: serde_json::Value =
+            serde_json::from_str(device_health::MEASUREMENTS_MANIFEST)
+                .map_err(|e| anyhow!("compiled attestation-measurements.json: {}", e))?;
+        let now = Utc::now().timestamp();
+        let report = device_health::assess(
+            now,
+            self_test,
+            evidence,
+            &nonce,
+            &manifest,
+            pinned.as_deref(),
+        );
+        let (passed, json, src) = (
+            report.passed(),
+            serde_json::to_string(&report)?,
+            source.to_string(),
+        );
+        let check_id = self
+            .db
+            .run(move |db| db.record_health_check(now, passed, &src, &json))
+            .await?;
+        if passed {
+            println!("🩺 Device health check #{} ({}): passed", check_id, source);
+        } else {
+            let reason = report.reason();
+            eprintln!(
+                "🩺 Device health check #{} ({}) FAILED: {}",
+                check_id, source, reason
+            );
+            let r = reason.clone();
+            let newly = self
+                .db
+                .run(move |db| db.quarantine_device(&r, Some(check_id), now))
+                .await?;
+            if self.tee.quarantine_reason().is_none() {
+                self.tee.quarantine(&reason);
+            }
+            if newly {
+                eprintln!("🚧 Device QUARANTINED: signing drained to replicas until released");
+                self.device_health_alert(true, &reason, Some(&report), now);
+            }
+        }
+        device_health::export_gauges(Some(passed), self.tee.quarantine_reason().is_some());
+        Ok(report)
+    }
+
+    /// Merge a PATCH into the hot-reloadable config sections. Every section
+    /// is validated before any is applied; each applied one is audited with
+    /// the hash of the value it replaced.
+    pub fn patch_config(&self, patch: serde_json::Value) -> Result<serde_json::Value> {
+        // Field-level detail is the point of the answer; classify it as a
+        // bad request whatever words the validator used.
+        let changes = self.config.update(&patch).map_err(|e| {
+            crate::verify::exception("ValidationException", format!("config rejected: {:#}", e))
+        })?;
+        for change in &changes {
+            self.audit(
+                "config",
+                "config_section_updated",
+                Some(&format!(
+                    "{} previous={} current={}",
+                    change.section, change.previous_hash, change.current_hash
+                )),
+            );
+            println!(
+                "⚙️  Config {} updated: {} → {}",
+                change.section, change.previous, change.current
+            );
+        }
+        Ok(serde_json::json!({
+            "updated": changes
+                .iter()
+                .map(|c| serde_json::json!({
+                    "section": c.section,
+                    "previousHash": c.previous_hash,
+                    "currentHash": c.current_hash,
+                }))
+                .collect::<Vec<_>>(),
+            "config": self.config.snapshot(),
+        }))
+    }
+
+    /// Operator release: only after a fresh check passes.
+    pub async fn release_device_quarantine(
+        &self,
+        req: DeviceHealthReleaseRequest,
+    ) -> Result<serde_json::Value> {
+        let quarantine = self
+            .db
+            .device_quarantine()?
+            .ok_or_else(|| anyhow!("device is not quarantined"))?;
+        let report = self.run_device_health_check("operator").await?;
+        if !report.passed() {
+            return Err(anyhow!(
+                "device health check still failing, quarantine kept: {}",
+                report.reason()
+            ));
+        }
+        self.db.release_device_quarantine()?;
+        self.tee.release_quarantine();
+        device_health::export_gauges(Some(true), false);
+        let note = req.note.unwrap_or_default();
+        println!(
+            "🩺 Device quarantine lifted (was: {}; since {}){}",
+            quarantine.reason,
+            quarantine.since,
+            if note.is_empty() {
+                String::new()
+            } else {
+                format!(" — {}", note)
+            }
+        );
+        let reason = if note.is_empty() {
+            format!(
+                "released after a passing check (was: {})",
+                quarantine.reason
+            )
+        } else {
+            format!("released: {} (was: {})", note, quarantine.reason)
+        };
+        self.device_health_alert(false, &reason, Some(&report), quarantine.since);
+        self.device_health_status().await
+    }
+
+    /// Quarantine state, schedule and the latest checks.
+    pub async fn device_health_status(&self) -> Result<serde_json::Value> {
+        let (quarantine, checks) = self
+            .db
+            .run(|db| Ok((db.device_quarantine()?, db.list_health_checks(10)?)))
+            .await?;
+        let now = Utc::now().timestamp();
+        let next_check_at = self
+            .device_health
+            .next_check_in(checks.first().map(|c| c.checked_at), now)
+            .map(|secs| now + secs as i64);
+        Ok(serde_json::json!({
+            "deviceId": replication::local_device_id(),
+            "quarantined": quarantine.is_some(),
+            "quarantine": quarantine.map(|q| serde_json::json!({
+                "reason": q.reason,
+                "checkId": q.check_id,
+                "since": q.since,
+            })),
+            "intervalSecs": self.device_health.interval_secs,
+            "nextCheckAt": next_check_at,
+            "checks": checks
+                .iter()
+                .map(|c| serde_json::json!({
+                    "id": c.id,
+                    "checkedAt": c.checked_at,
+                    "passed": c.passed,
+                    "source": c.source,
+                    "report": serde_json::from_str::<serde_json::Value>(&c.report)
+                        .unwrap_or(serde_json::Value::Null),
+                }))
+                .collect::<Vec<_>>(),
+        }))
+    }
+
+    fn standby_config(&self) -> Result<&StandbyConfig> {
+        self.standby
+            .as_ref()
+            .ok_or_else(|| anyhow!("standby replication not configured (KMS_STANDBY_ROLE)"))
+    }
+
+    /// A sealed request from the peer to `/standby/{path}`.
+    pub async fn serve_standby(&self, path: String, sealed: Sealed) -> Result<Sealed> {
+        standby::serve(self.standby_config()?, &self.db, &path, sealed).await
+    }
+
+    /// This node's role and log head, and what the peer says about itself.
+    pub async fn standby_status(&self) -> Result<serde_json::Value> {
+        let config = self.standby_config()?;
+        let (state, head) = self
+            .db
+            .run(|db| Ok((db.standby_state()?, db.event_head()?)))
+            .await?;
+        let peer = match config
+            .call::<_, PeerStatus>(standby::STATUS_PATH, &())
+            .await
+        {
+            Ok(peer) => serde_json::to_value(peer)?,
+            Err(e) => serde_json::json!({ "error": format!("{:#}", e) }),
+        };
+        Ok(serde_json::json!({
+            "nodeId": replication::local_device_id(),
+            "peerUrl": config.peer,
+            "state": state,
+            "headSeq": head.0,
+            "headHash": head.1,
+            "peer": peer,
+        }))
+    }
+
+    /// Fail over to this standby. Refused while quarantined, when the event
+    /// log does not verify, when a ready wallet is missing from this TA
+    /// (unless `force`), or when the old active answers and will not step
+    /// down. An unreachable old active is what a failover is for; it fences
+    /// itself once its watchdog sees the new token.
+    pub async fn promote_standby(&self, req: StandbyPromoteRequest) -> Result<serde_json::Value> {
+        let config = self.standby_config()?;
+        let state = self
+            .db
+            .run(|db| db.standby_state())
+            .await?
+            .ok_or_else(|| anyhow!("standby state not initialised"))?;
+        if state.role != Role::Standby {
+            return Err(anyhow!(
+                "this node is {}; only a standby can be promoted",
+                state.role.as_str()
+            ));
+        }
+        if let Some(reason) = self.tee.quarantine_reason() {
+            return Err(anyhow!(
+                "device quarantined ({}): promote the other node or release the quarantine first",
+                reason
+            ));
+        }
+        let head = self
+            .db
+            .run(|db| db.verify_event_chain())
+            .await
+            .map_err(|e| anyhow!("event log does not verify: {:#}", e))?;
+
+        // TA bindings: every ready wallet the TA signs for must be in this TA.
+        let (wallets, hsm) = self
+            .db
+            .run(|db| Ok((db.list_wallets()?, db.list_wallet_signers()?)))
+            .await?;
+        let mut checked = 0usize;
+        let mut missing = Vec::new();
+        for w in wallets.iter().filter(|w| w.status == "ready") {
+            if hsm.iter().any(|h| h.key_id == w.key_id) {
+                continue;
+            }
+            let id = match Uuid::parse_str(&w.key_id) {
+                Ok(id) => id,
+                Err(_) => continue,
+            };
+            match self.tee.warmup_cache(id).await {
+                Ok(_) => checked += 1,
+                Err(e) => missing.push(serde_json::json!({
+                    "keyId": w.key_id,
+                    "error": format!("{:#}", e),
+                })),
+            }
+        }
+        if !missing.is_empty() && !req.force {
+            return Err(anyhow!(
+                "{} of {} ready wallet(s) are not in this TA (first: {}); replicate them or promote with force",
+                missing.len(),
+                missing.len() + checked,
+                missing[0]["keyId"]
+            ));
+        }
+
+        let token = state.fencing_token + 1;
+        let peer = match config
+            .call::<_, PeerStatus>(
+                standby::FENCE_PATH,
+                &FenceRequest {
+                    fencing_token: token,
+                },
+            )
+            .await
+        {
+            Ok(peer) if peer.role == Role::Active => {
+                return Err(anyhow!(
+                    "peer {} is still active with fencing token {} and did not step down",
+                    peer.node_id,
+                    peer.fencing_token
+                ))
+            }
+            Ok(peer) if peer.fencing_token > token => {
+                return Err(anyhow!(
+                    "peer {} has seen fencing token {}, newer than ours",
+                    peer.node_id,
+                    peer.fencing_token
+                ))
+            }
+            Ok(peer) => serde_json::to_value(peer)?,
+            Err(e) => {
+                eprintln!(
+                    "⚠️  Standby promotion: old active not reachable ({:#}) — promoting anyway",
+                    e
+                );
+                serde_json::json!({ "error": format!("{:#}", e) })
+            }
+        };
+        let now = Utc::now().timestamp();
+        let state = self
+            .db
+            .run(move |db| db.promote_standby(token, now))
+            .await?;
+        println!(
+            "👑 Standby promoted to active: fencing token {}, log head {} ({} wallet(s) checked in the TA, {} missing)",
+            token,
+            head.events,
+            checked,
+            missing.len()
+        );
+        Ok(serde_json::json!({
+            "state": state,
+            "headSeq": head.events,
+            "walletsChecked": checked,
+            "missingWallets": missing,
+            "peer": peer,
+        }))
+    }
+
+    fn device_health_alert(
+        &self,
+        quarantined: bool,
+        reason: &str,
+        report: Option<&HealthReport>,
+        since: i64,
+    ) {
+        let config = &self.device_health;
+        if let (Some(url), Some(secret)) = (&config.webhook_url, &config.webhook_secret) {
+            let envelope = device_health::alert_envelope(
+                quarantined,
+                reason,
+                report,
+                since,
+                Utc::now().timestamp(),
+            );
+            match serde_json::to_string(&envelope) {
+                Ok(body) => {
+                    let (url, secret) = (url.clone(), secret.clone());
+                    tokio::spawn(async move {
+                        chain_watch::post_signed(&url, &secret, body, "device health").await
+                    });
+                }
+                Err(e) => eprintln!("⚠️  Device health alert: {}", e),
+            }
+        }
+    }
+
+    /// Status view; a wipe certificate seen for the first time is filed in
+    /// `device_wipes`, so a failed-auth wipe is kept even though the command
+    /// that triggered it only returned an error.
+    fn tamper_status_response(&self, status: proto::TamperStatus) -> Result<TamperStatusResponse> {
+        let last_wipe = status
+            .last_wipe
+            .map(WipeCertificateView::from_proof)
+            .transpose()?;
+        if let Some(view) = &last_wipe {
+            let signed = view.attestation.is_some();
+            let json = serde_json::to_string(view)?;
+            if self
+                .db
+                .record_device_wipe(&view.digest, &view.reason, &json, signed)?
+            {
+                eprintln!(
+                    "🔴 TA reports a device wipe ({}): {} wallets erased",
+                    view.reason, view.wallets
+                );
+            }
+        }
+        Ok(TamperStatusResponse {
+            failed_auth_limit: status.failed_auth_limit,
+            consecutive_failures: status.consecutive_failures,
+            last_order_sequence: status.last_order_sequence,
+            locked: status.locked.is_some(),
+            lock_reason: status.locked.as_ref().map(|l| format!("{:?}", l.reason)),
+            locked_since: status.locked.map(|l| l.since),
+            last_wipe,
+        })
+    }
+
+    pub async fn tamper_status(&self) -> Result<TamperStatusResponse> {
+        let status = self.tee.tamper_status().await?;
+        self.tamper_status_response(status)
+    }
+
+    /// Relay a signed tamper order. The TA checks the signer, deployment,
+    /// device and sequence; a panic wipe answers with its certificate.
+    pub async fn tamper_order(&self, req: TamperOrderRequest) -> Result<TamperOrderResponse> {
+        let order = req.order()?;
+        let signature = req.signature(&order)?;
+        println!(
+            "📝 KMS TamperOrder sequence {} ({})",
+            order.sequence,
+            req.action.trim()
+        );
+        let out = self.tee.tamper_order(order, signature).await?;
+        Ok(TamperOrderResponse {
+            wiped: out.wiped,
+            status: self.tamper_status_response(out.status)?,
+        })
+    }
+
+    /// Relay a signed log-level order; the TA checks it as a tamper order.
+    pub async fn set_log_level(&self, req: LogLevelOrderRequest) -> Result<SetLogLevelResponse> {
+        let order = req.order()?;
+        let signature = req.signature(&order)?;
+        println!(
+            "📝 KMS SetLogLevel sequence {} {:?} until {:?}",
+            order.sequence, order.levels, order.until
+        );
+        let out = self.tee.set_log_level(order, signature).await?;
+        Ok(SetLogLevelResponse {
+            levels: log_level::levels_map(&out.levels),
+            until: out.until,
+            previous_sequence: out.previous_sequence,
+        })
+    }
+
+    /// Report stuck transactions and nonce gaps from the ledger, and build,
+    /// confirm and sign a replacement for one nonce.
+    pub async fn rescue_transaction(
+        &self,
+        req: RescueTransactionRequest,
+    ) -> Result<RescueTransactionResponse> {
+        let wallet_uuid = Uuid::parse_str(&req.key_id)?;
+        let key_id = wallet_uuid.to_string();
+        let chain_id = req.chain_id;
+        let wallet = self
+            .db
+            .get_wallet(&key_id)?
+            .ok_or_else(|| anyhow!("Key not found: {}", key_id))?;
+        self.ensure_not_frozen(&key_id)?;
+        let derivation_path = req
+            .derivation_path
+            .or(wallet.derivation_path)
+            .ok_or_else(|| anyhow!("No derivation path available for this key"))?;
+        Self::validate_derivation_path(&derivation_path)?;
+        let address = self
+            .db
+            .address_for_key_path(&key_id, &derivation_path)?
+            .ok_or_else(|| anyhow!("No derived address for {} at {}", key_id, derivation_path))?;
+
+        if let Some(confirmed) = req.confirmed_nonce {
+            self.db
+                .mark_txs_confirmed_below(&address, chain_id, confirmed)?;
+        }
+        let pending = self.db.list_pending_txs(&address, chain_id)?;
+        let now = Utc::now().timestamp();
+        let stuck_after = req.stuck_after_seconds.unwrap_or(DEFAULT_STUCK_AFTER_SECS) as i64;
+        let stuck: Vec<_> = pending
+            .iter()
+            .filter(|r| now - r.created_at >= stuck_after)
+            .collect();
+        let nonces: Vec<u64> = pending.iter().map(|r| r.entry.nonce).collect();
+        let nonce_gaps = tx_rescue::nonce_gaps(&nonces, req.confirmed_nonce);
+        let mut resp = RescueTransactionResponse {
+            address: address.clone(),
+            stuck: stuck
+                .iter()
+                .map(|r| StuckTransaction {
+                    nonce: r.entry.nonce,
+                    transaction_hash: r.entry.tx_hash.clone(),
+                    gas_price: format!("0x{:x}", r.entry.gas_price),
+                    age_seconds: (now - r.created_at) as u64,
+                })
+                .collect(),
+            nonce_gaps,
+            replacement: None,
+            signed_transaction: None,
+            transaction_hash: None,
+        };
+        let nonce = match req.nonce {
+            Some(n) => n,
+            None => return Ok(resp),
+        };
+
+        let floor = match req.gas_price.as_deref() {
+            Some(g) => Some(u128::from_str_radix(g.trim_start_matches("0x"), 16)?),
+            None => None,
+        };
+        let mut from = [0u8; 20];
+        hex::decode_to_slice(address.trim_start_matches("0x"), &mut from)?;
+        let (mode, replaces, transaction) = if resp.nonce_gaps.contains(&nonce) {
+            // Nothing to replace: price the filler like the txs queued behind it.
+            let queued = pending.iter().map(|r| r.entry.gas_price).max().unwrap_or(0);
+            let gas_price = queued.max(floor.unwrap_or(0));
+            if gas_price == 0 {
+                return Err(anyhow!("gasPrice is required to fill nonce gap {}", nonce));
+            }
+            let tx = tx_rescue::self_transfer(chain_id, nonce as u128, from, gas_price);
+            (RescueMode::Cancel, None, tx)
+        } else {
+            let original = stuck
+                .iter()
+                .find(|r| r.entry.nonce == nonce)
+                .ok_or_else(|| {
+                    anyhow!(
+                        "nonce {} is neither a stuck transaction nor a gap for {} on chain {}",
+                        nonce,
+                        address,
+                        chain_id
+                    )
+                })?;
+            let mode = RescueMode::parse(req.mode.as_deref().unwrap_or("speedup"))?;
+            let raw = hex::decode(original.entry.signed_tx.trim_start_matches("0x"))?;
+            let signed = proto::offline::decode_signed_legacy(&raw)
+                .map_err(|e| anyhow!("ledger entry {}: {}", original.id, e))?;
+            let gas_price = tx_rescue::bumped_gas_price(
+                original.entry.gas_price,
+                req.fee_bump_percent
+                    .unwrap_or(tx_rescue::DEFAULT_FEE_BUMP_PERCENT),
+                floor,
+            )?;
+            let tx = tx_rescue::replacement(&signed.transaction, from, mode, gas_price);
+            (mode, Some(original.id), tx)
+        };
+        let summary = proto::calldata::summarize_transaction(&transaction, &[]);
+        resp.replacement = Some(RescueReplacement {
+            nonce,
+            mode: mode.as_str().to_string(),
+            transaction: EthereumTransaction::from_proto(&transaction),
+            signing_hash: format!(
+                "0x{}",
+                hex::encode(proto::offline::legacy_signing_hash(&transaction))
+            ),
+            summary: summary.clone(),
+        });
+        if req.webauthn_assertion.is_none() {
+            // Confirmation step: the caller shows `summary` and runs the ceremony.
+            return Ok(resp);
+        }
+        let assertion = self
+            .resolve_passkey_assertion(&key_id, None, req.webauthn_assertion.as_ref(), true)
+            .await?
+            .ok_or_else(|| anyhow!("Passkey assertion required for transaction rescue"))?;
+        let signed = self
+            .tee
+            .sign_transaction(
+                wallet_uuid,
+                &derivation_path,
+                transaction,
+                Some(assertion),
+                vec![],
+                false,
+            )
+            .await?;
+        let signer = key_pin::address_hex(&key_pin::signed_tx_signer(&signed)?);
+        enforce_key_pin(&self.db, &key_id, &derivation_path, &signer, None)?;
+        let entry = tx_rescue::ledger_entry(&key_id, &derivation_path, &signer, &signed)?;
+        self.db.insert_tx_history(&entry, replaces)?;
+        println!(
+            "🛟 TxRescue: wallet={} chain={} nonce={} {} \"{}\" tx={}",
+            key_id,
+            chain_id,
+            nonce,
+            mode.as_str(),
+            summary,
+            entry.tx_hash
+        );
+        resp.signed_transaction = Some(entry.signed_tx);
+        resp.transaction_hash = Some(entry.tx_hash);
+        Ok(resp)
+    }
+
+    pub async fn revoke_p256_session_key(
+        &self,
+        req: RevokeP256SessionKeyRequest,
+    ) -> Result<RevokeP256SessionKeyResponse> {
+        let (wallet_uuid, session_index) = parse_agent_key_id(&req.key_id)?;
+        let wallet_id_str = wallet_uuid.to_string();
+
+        // Require WebAuthn ceremony for replay protection
+        if req.webauthn_assertion.is_none() {
+            return Err(anyhow!(
+                "revoke-p256-session-key requires WebAuthn ceremony. \
+                 Legacy passkey assertions are not accepted."
+            ));
+        }
+        let assertion = self
+            .resolve_passkey_assertion(&wallet_id_str, None, req.webauthn_assertion.as_ref(), false)
+            .await?;
+        if assertion.is_none() {
+            return Err(anyhow!(
+                "Passkey assertion required to revoke P256 session key"
+            ));
+        }
+
+        // Lazy GC: clean up other expired P256 session keys for this wallet.
+        self.gc_expired_p256_session_keys(&wallet_id_str, wallet_uuid, None)
+            .await;
+
+        // Atomically mark the target key as revoked in DB.
+        let claimed = self
+            .db
+            .mark_p256_session_key_gc(&wallet_id_str, session_index)?;
+        if !claimed {
+            // mark_p256_session_key_gc returned 0 rows — either already revoked or not found.
+            let already_revoked = self
+                .db
+                .p256_session_key_is_revoked(&wallet_id_str, session_index)?;
+            if already_revoked {
+                // Idempotent: key is already revoked. Retry TEE delete in case it failed before,
+                // then confirm tee_deleted so GC's Pass 0 stops retrying this row.
+                match self
+                    .tee
+                    .delete_p256_session_key(wallet_uuid, session_index)
+                    .await
+                {
+                    Ok(_) => {
+                        let _ = self.db.mark_p256_tee_deleted(&wallet_id_str, session_index);
+                    }
+                    Err(e) => {
+                        eprintln!(
+                            "⚠️  RevokeP256SessionKey (idempotent): TEE delete retry failed {}:{}: {}",
+                            wallet_id_str, session_index, e
+                        );
+                    }
+                }
+                let revoked_at = Utc::now().timestamp();
+                println!(
+                    "✅ RevokeP256SessionKey (idempotent): wallet={} idx={}",
+                    wallet_id_str, session_index
+                );
+                return Ok(RevokeP256SessionKeyResponse {
+                    success: true,
+                    revoked_at,
+                });
+            }
+            return Err(anyhow!("P256 session key not found: {}", req.key_id));
+        }
+
+        // Delete TEE key material — best-effort, idempotent.
+        // If this fails, tee_deleted stays 0; GC's Pass 0 retries on the next trigger.
+        match self
+            .tee
+            .delete_p256_session_key(wallet_uuid, session_index)
+            .await
+        {
+            Ok(_) => {
+                let _ = self.db.mark_p256_tee_deleted(&wallet_id_str, session_index);
+            }
+            Err(e) => {
+                eprintln!(
+                    "⚠️  RevokeP256SessionKey: TEE delete failed (DB=revoked, GC will retry) {}:{}: {}",
+                    wallet_id_str, session_index, e
+                );
+            }
+        }
+
+        let revoked_at = Utc::now().timestamp();
+        println!(
+            "✅ RevokeP256SessionKey: wallet={} idx={}",
+            wallet_id_str, session_index
+        );
+
+        Ok(RevokeP256SessionKeyResponse {
+            success: true,
+            revoked_at,
+        })
+    }
+
+    /// POST /contact/begin-binding — owner starts a contact binding (#129). Q4 gate
+    /// (codex/opus checkpoint review): the owner ceremony challenge is consumed + the
+    /// owner's passkey verified BEFORE the binding row is touched — begin's upsert can
+    /// overwrite a verified row, so this ceremony is the ENTIRE protection for a verified
+    /// binding. delegate=false → host-authoritative bare-nonce (no TA; binding is host
+    /// side). Email bindings record the address now; the mail relay claims the row via
+    /// /contact/claim-email and the owner confirms with the mailed token.
+    /// ({account,channel} commitment binding is a follow-up — needs host-side payload
+    /// support in resolve_passkey_assertion.)
+    pub async fn begin_contact_binding(
+        &self,
+        req: BeginBindingRequest,
+    ) -> Result<BeginBindingResponse> {
+        // codex BLOCKER fix: resolve_passkey_assertion returns Ok(None) when no webauthn
+        // assertion is supplied — so the ceremony MUST be required explicitly, else a caller
+        // (incl. a compromised bot holding bindingCode+verifyToken) could omit `webauthn` and
+        // reach the DB write with NO owner ceremony, defeating the whole owner-gate.
+        // account may be the wallet key_id OR an address (SDK uses Address) — resolve to the
+        // canonical key_id so the binding row + ceremony key the same value.
+        let key_id = self.resolve_account_key_id(&req.account)?;
+        self.resolve_passkey_assertion(&key_id, None, req.webauthn_assertion.as_ref(), false)
+            .await?
+            .ok_or_else(|| anyhow!("owner WebAuthn ceremony required"))?;
+        // High-entropy one-time bindingCode (256-bit, OS CSPRNG) — DB matches on it.
+        use rand::RngCore;
+        let mut buf = [0u8; 32];
+        rand::rngs::OsRng.fill_bytes(&mut buf);
+        let binding_code = hex::encode(buf);
+        let ttl_secs: i64 = 600;
+        match req.channel.as_str() {
+            "telegram" => {
+                self.db.begin_contact_binding(
+                    &key_id,
+                    "telegram",
+                    &binding_code,
+                    None,
+                    ttl_secs,
+                )?;
+                self.audit(&key_id, "contact_begin", Some("telegram"));
+            }
+            "email" => {
+                let email = normalize_email(
+                    req.email
+                        .as_deref()
+                        .ok_or_else(|| anyhow!("email is required for channel 'email'"))?,
+                )?;
+                let hint = mask_email(&email);
+                self.db
+                    .begin_email_binding(&key_id, &binding_code, &email, &hint, ttl_secs)?;
+                self.audit(&key_id, "contact_begin", Some(&format!("email {}", hint)));
+            }
+            other => return Err(anyhow!("channel '{}' not supported", other)),
+        }
+        let expires_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0)
+            + ttl_secs;
+        Ok(BeginBindingResponse {
+            binding_code,
+            expires_at,
+        })
+    }
+
+    /// POST /contact/claim-binding (bot api-key) — the shared bot reports a /bind. Issues a
+    /// one-time verify_token (256-bit) the bot delivers to the chat; the binding only
+    /// completes when the OWNER returns the token via confirm (owner ceremony). #129.
+    pub async fn claim_contact_binding(
+        &self,
+        req: ClaimBindingRequest,
+    ) -> Result<ClaimBindingResponse> {
+        use rand::RngCore;
+        let mut buf = [0u8; 32];
+        rand::rngs::OsRng.fill_bytes(&mut buf);
+        let verify_token = hex::encode(buf);
+        let ttl_secs: i64 = 600;
+        let claimed = self.db.claim_contact_binding(
+            &req.binding_code,
+            &req.telegram_chat_id,
+            req.telegram_username.as_deref(),
+            &verify_token,
+            req.bot_id.as_deref(),
+            ttl_secs,
+        )?;
+        if !claimed {
+            return Err(anyhow!("invalid, expired, or already-claimed binding code"));
+        }
+        let expires_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0)
+            + ttl_secs;
+        Ok(ClaimBindingResponse {
+            verify_token,
+            expires_at,
+        })
+    }
+
+    /// POST /contact/claim-email (mail relay api-key) — the email counterpart of the
+    /// telegram claim. The relay gets the destination address plus a fresh one-time
+    /// verify_token to mail; the owner proves mailbox control by returning it through
+    /// /contact/confirm-binding under their passkey ceremony.
+    pub async fn claim_email_binding(
+        &self,
+        req: ClaimEmailBindingRequest,
+    ) -> Result<ClaimEmailBindingResponse> {
+        use rand::RngCore;
+        let mut buf = [0u8; 32];
+        rand::rngs::OsRng.fill_bytes(&mut buf);
+        let verify_token = hex::encode(buf);
+        let ttl_secs: i64 = 600;
+        let email = self
+            .db
+            .claim_email_binding(
+                &req.binding_code,
+                &verify_token,
+                req.relay_id.as_deref(),
+                ttl_secs,
+            )?
+            .ok_or_else(|| anyhow!("invalid, expired, or already-claimed binding code"))?;
+        let expires_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0)
+            + ttl_secs;
+        Ok(ClaimEmailBindingResponse {
+            email,
+            verify_token,
+            expires_at,
+        })
+    }
+
+    /// POST /contact/confirm-binding — OWNER (app passkey ceremony) returns the verify_token.
+    /// The owner ceremony is REQUIRED because the bot knows verify_token (it delivered it),
+    /// so a confirm gated only by api-key would let a compromised bot self-complete the
+    /// binding. The DB also matches the ceremony'd account (no cross-account confirm). #129.
+    pub async fn confirm_contact_binding(
+        &self,
+        req: ConfirmBindingRequest,
+    ) -> Result<ConfirmBindingResponse> {
+        // codex BLOCKER fix: resolve_passkey_assertion returns Ok(None) when no webauthn
+        // assertion is supplied — so the ceremony MUST be required explicitly, else a caller
+        // (incl. a compromised bot holding bindingCode+verifyToken) could omit `webauthn` and
+        // reach the DB write with NO owner ceremony, defeating the whole owner-gate.
+        // account may be the wallet key_id OR an address — resolve to key_id (the binding row
+        // was stored under key_id at begin, so the account-match must compare key_ids).
+        let key_id = self.resolve_account_key_id(&req.account)?;
+        self.resolve_passkey_assertion(&key_id, None, req.webauthn_assertion.as_ref(), false)
+            .await?
+            .ok_or_else(|| anyhow!("owner WebAuthn ceremony required"))?;
+        let ok = self
+            .db
+            .confirm_contact_binding(&key_id, &req.binding_code, &req.verify_token)?;
+        if !ok {
+            return Err(anyhow!(
+                "binding not confirmable (bad token, wrong account, not claimed, or expired)"
+            ));
+        }
+        self.audit(&key_id, "contact_verified", None);
+        Ok(ConfirmBindingResponse {
+            status: "verified".to_string(),
+        })
+    }
+
+    /// GET /contact/{account} (api-key; DVT node) — verified contacts only, no secrets.
+    /// PII: api-key authed, never public (#129 §3.5). Owner-ceremony read is a follow-up.
+    pub async fn get_contacts(&self, account: &str) -> Result<Vec<ContactView>> {
+        // account may be the wallet key_id OR an address (DVT has the userOp sender address).
+        let key_id = self.resolve_account_key_id(account)?;
+        let rows = self.db.get_verified_contacts(&key_id)?;
+        Ok(rows
+            .into_iter()
+            .map(|c| ContactView {
+                channel: c.channel,
+                contact_ref: c.contact_ref,
+                display_hint: c.display_hint,
+                status: c.status,
+                verified_at: c.verified_at,
+            })
+            .collect())
+    }
+
+    /// POST /contact/unbind — OWNER (app passkey ceremony) revokes a binding. #129.
+    pub async fn unbind_contact(&self, req: UnbindRequest) -> Result<UnbindResponse> {
+        // codex BLOCKER fix: resolve_passkey_assertion returns Ok(None) when no webauthn
+        // assertion is supplied — so the ceremony MUST be required explicitly, else a caller
+        // (incl. a compromised bot holding bindingCode+verifyToken) could omit `webauthn` and
+        // reach the DB write with NO owner ceremony, defeating the whole owner-gate.
+        // account may be the wallet key_id OR an address — resolve to the canonical key_id.
+        let key_id = self.resolve_account_key_id(&req.account)?;
+        self.resolve_passkey_assertion(&key_id, None, req.webauthn_assertion.as_ref(), false)
+            .await?
+            .ok_or_else(|| anyhow!("owner WebAuthn ceremony required"))?;
+        let removed = self.db.unbind_contact(&key_id, &req.channel)?;
+        if removed {
+            self.audit(&key_id, "contact_removed", Some(&req.channel));
+        }
+        Ok(UnbindResponse {
+            status: if removed { "revoked" } else { "not_found" }.to_string(),
+        })
+    }
+
+    pub async fn create_p256_session_key(
+        &self,
+        req: CreateP256SessionKeyRequest,
+    ) -> Result<CreateP256SessionKeyResponse> {
+        let wallet_id = Self::validate_key_id(&req.human_key_id)?;
+
+        // Verify human wallet exists
+        let _wallet = self
+            .db
+            .get_wallet(&req.human_key_id)?
+            .ok_or_else(|| anyhow!("Human wallet not found: {}", req.human_key_id))?;
+
+        // P256 session key creation requires WebAuthn ceremony (replay-protected)
+        if req.webauthn_assertion.is_none() {
+            return Err(anyhow!(
+                "create-p256-session-key requires WebAuthn ceremony. \
+                 Legacy passkey assertions are not accepted."
+            ));
+        }
+        let assertion = self
+            // #115: TA binds the mint challenge to the label → delegate to TA (true).
+            .resolve_passkey_assertion(
+                &req.human_key_id,
+                None,
+                req.webauthn_assertion.as_ref(),
+                true,
+            )
+            .await?;
+        if assertion.is_none() {
+            return Err(anyhow!(
+                "Passkey assertion required to create P256 session key"
+            ));
+        }
+
+        // Lazy GC: clean up expired P256 session keys for this wallet before creating a new one.
+        self.gc_expired_p256_session_keys(&req.human_key_id, wallet_id, None)
+            .await;
+
+        // Atomically check active/pending count and allocate next session_index.
+        let session_index = self.db.allocate_p256_session_key_pending(
+            &req.human_key_id,
+            &req.human_key_id,
+            Utc::now().timestamp(),
+            2,
+        )?;
+
+        // Generate P256 key pair in TEE (may take ~seconds on Cortex-A7)
+        let tee_result = match self
+            .tee
+            .create_p256_session_key(
+                wallet_id,
+                session_index,
+                &req.human_key_id,
+                24 * 3600,
+                assertion,
+                &req.label,
+            )
+            .await
+        {
+            Ok(r) => r,
+            Err(e) => {
+                match self
+                    .tee
+                    .delete_p256_session_key(wallet_id, session_index)
+                    .await
+                {
+                    Ok(_) => {
+                        let _ = self
+                            .db
+                            .delete_p256_session_key_pending(&req.human_key_id, session_index);
+                    }
+                    Err(tee_del_err) => {
+                        eprintln!(
+                            "⚠️  CreateP256SessionKey: TEE create+delete both failed for {}:{}: \
+                             create={} delete={} (DB pending row kept for GC retry)",
+                            req.human_key_id, session_index, e, tee_del_err
+                        );
+                    }
+                }
+                return Err(e);
+            }
+        };
+        let pub_key_x = hex::encode(&tee_result.pub_key_x);
+        let pub_key_y = hex::encode(&tee_result.pub_key_y);
+
+        // Assemble JWT credential from TEE-generated material (HMAC signed inside TEE)
+        let key_id = format!("{}:{}", req.human_key_id, session_index);
+        let (jwt, expires_at) = agent_jwt::assemble_p256_session_jwt(&tee_result)?;
+
+        let cred_hash = agent_jwt::credential_hash(&jwt);
+        if let Err(e) = self.db.activate_p256_session_key(
+            &req.human_key_id,
+            session_index,
+            &pub_key_x,
+            &pub_key_y,
+            &cred_hash,
+            expires_at,
+            2,
+        ) {
+            match self
+                .tee
+                .delete_p256_session_key(wallet_id, session_index)
+                .await
+            {
+                Ok(_) => {
+                    let _ = self
+                        .db
+                        .delete_p256_session_key_pending(&req.human_key_id, session_index);
+                }
+                Err(tee_err) => {
+                    eprintln!(
+                        "⚠️  CreateP256SessionKey cleanup: TEE delete failed for {}:{}: {} \
+                         (DB pending row kept for GC retry)",
+                        req.human_key_id, session_index, tee_err
+                    );
+                }
+            }
+            return Err(e);
+        }
+
+        println!(
+            "✅ CreateP256SessionKey: wallet={} idx={} x={}...",
+            req.human_key_id,
+            session_index,
+            &pub_key_x[..8]
+        );
+
+        Ok(CreateP256SessionKeyResponse {
+            key_id,
+            pub_key_x,
+            pub_key_y,
+            algorithm: "p256".to_string(),
+            agent_credential: jwt,
+            expires_at,
+        })
+    }
+}
+
+#[cfg(test)]
+mod request_deser_tests {
+    use super::*;
+
+    const WA: &str = r#"{"ChallengeId":"c","Credential":{"id":"i","rawId":"r","type":"public-key","response":{"clientDataJSON":"a","authenticatorData":"b","signature":"s"}}}"#;
+
+    #[test]
+    fn delete_key_request_minimal_webauthn() {
+        let body = format!(r#"{{"KeyId":"abc","WebAuthn":{}}}"#, WA);
+        let r: Result<DeleteKeyRequest, _> = serde_json::from_str(&body);
+        assert!(r.is_ok(), "DeleteKeyRequest deser failed: {:?}", r.err());
+    }
+
+    #[test]
+    fn derive_address_request_webauthn() {
+        let body = format!(
+            r#"{{"KeyId":"abc","DerivationPath":"m/44","WebAuthn":{}}}"#,
+            WA
+        );
+        let r: Result<DeriveAddressRequest, _> = serde_json::from_str(&body);
+        assert!(
+            r.is_ok(),
+            "DeriveAddressRequest deser failed: {:?}",
+            r.err()
+        );
+    }
+
+    #[test]
+    fn derive_address_request_chain() {
+        let r: DeriveAddressRequest = serde_json::from_str(
+            r#"{"KeyId":"abc","DerivationPath":"m/44'/195'/0'/0/0","Chain":"tron"}"#,
+        )
+        .unwrap();
+        let chain = KmsApiServer::parse_chain(r.chain.as_deref()).unwrap();
+        assert_eq!(chain, Chain::Tron);
+        assert!(KmsApiServer::validate_chain_path(&r.derivation_path, chain).is_ok());
+        // An Ethereum path is not a Tron path, and vice versa.
+        assert!(KmsApiServer::validate_chain_path("m/44'/60'/0'/0/0", chain).is_err());
+        assert!(KmsApiServer::validate_chain_path(&r.derivation_path, Chain::Bsc).is_err());
+        assert_eq!(KmsApiServer::parse_chain(None).unwrap(), Chain::Ethereum);
+        assert!(KmsApiServer::parse_chain(Some("solana")).is_err());
+    }
+
+    #[test]
+    fn sign_hash_request_userop_context() {
+        let body = format!(
+            r#"{{"KeyId":"abc","Hash":"0x{h}","Context":{{"Type":"UserOp","InnerHash":"0x{h}","EntryPoint":"0x{a}","ChainId":11155111}}}}"#,
+            h = "11".repeat(32),
+            a = "22".repeat(20)
+        );
+        let r: SignHashRequest = serde_json::from_str(&body).unwrap();
+        match r.context.unwrap().to_proto().unwrap() {
+            proto::SigningContext::UserOp {
+                entry_point,
+                chain_id,
+                ..
+            } => {
+                assert_eq!(entry_point, [0x22; 20]);
+                assert_eq!(chain_id, 11155111);
+            }
+            other => panic!("unexpected context {:?}", other),
+        }
+    }
+
+    #[test]
+    fn create_session_key_request_defaults() {
+        let body = format!(
+            r#"{{"keyId":"abc","chainId":10,"account":"0x{a}","targets":["0x{a}"],"expiry":1,"webAuthnAssertion":{wa}}}"#,
+            a = "11".repeat(20),
+            wa = WA
+        );
+        let r: CreateSessionKeyRequest = serde_json::from_str(&body).unwrap();
+        assert_eq!(r.value_cap, "0");
+        assert!(r.selectors.is_empty());
+        assert_eq!(r.hd_path, default_hd_path());
+        assert!(parse_wei("valueCap", "340282366920938463463374607431768211455").is_ok());
+        assert!(parse_wei("valueCap", "0x10").is_err());
+    }
+
+    #[test]
+    fn signing_context_rejects_short_preimage() {
+        let c: SigningContextRequest = serde_json::from_str(
+            r#"{"Type":"Eip712","DomainSeparator":"0x1234","StructHash":"0x00"}"#,
+        )
+        .unwrap();
+        assert!(c.to_proto().is_err());
+        let c: SigningContextRequest = serde_json::from_str(r#"{"Type":"PersonalMsg"}"#).unwrap();
+        assert_eq!(c.to_proto().unwrap(), proto::SigningContext::PersonalMsg);
+    }
+
+    #[test]
+    fn email_binding_request_and_helpers() {
+        let r: BeginBindingRequest = serde_json::from_str(
+            r#"{"account":"a","channel":"email","email":" Alice@Example.com "}"#,
+        )
+        .unwrap();
+        let e = normalize_email(r.email.as_deref().unwrap()).unwrap();
+        assert_eq!(e, "alice@example.com");
+        assert_eq!(mask_email(&e), "a***@example.com");
+        for bad in [
+            "",
+            "alice",
+            "@example.com",
+            "a@b",
+            "a b@example.com",
+            "a@b@c.com",
+        ] {
+            assert!(normalize_email(bad).is_err(), "{}", bad);
+        }
+        let r: BeginBindingRequest =
+            serde_json::from_str(r#"{"account":"a","channel":"telegram"}"#).unwrap();
+        assert!(r.email.is_none());
+    }
+
+    #[test]
+    fn statement_period_requires_finished_month() {
+        use chrono::TimeZone;
+        let now = Utc.with_ymd_and_hms(2026, 10, 16, 12, 0, 0).unwrap();
+        let (start, end) = statement_period("2025-12", now).unwrap();
+        assert_eq!(start.to_rfc3339(), "2025-12-01T00:00:00+00:00");
+        assert_eq!(end.to_rfc3339(), "2026-01-01T00:00:00+00:00");
+        assert!(statement_period("2026-09", now).is_ok());
+        assert!(statement_period("2026-10", now).is_err());
+        for bad in ["2026-9", "2026-13", "26-09", "2026-09-01"] {
+            assert!(statement_period(bad, now).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn allowance_rule_parsing() {
+        assert_eq!(
+            parse_allowance_rule("cap", Some("1000000")).unwrap(),
+            proto::AllowanceRule::Cap { max: 1_000_000 }
+        );
+        assert_eq!(
+            parse_allowance_rule("reject-unlimited", None).unwrap(),
+            proto::AllowanceRule::RejectUnlimited
+        );
+        assert!(parse_allowance_rule("cap", None).is_err());
+        assert!(parse_allowance_rule("off", Some("1")).is_err());
+        assert!(parse_allowance_rule("unlimited", None).is_err());
+        assert_eq!(
+            allowance_rule_name(&proto::AllowanceRule::Cap { max: 5 }),
+            "cap:5"
+        );
+    }
+
+    #[test]
+    fn deletion_certificate_view_requires_bound_attestation() {
+        use proto::erasure::{DeletionCertificate, DeletionProof, DELETION_CERT_VERSION};
+        let certificate = DeletionCertificate {
+            version: DELETION_CERT_VERSION,
+            wallet_id: Uuid::from_bytes([0x22; 16]),
+            deleted_at: 1_700_000_000,
+            rollback_counter: Some(9),
+        };
+        let evidence = proto::GetAttestationOutput {
+            nonce: certificate.digest().to_vec(),
+            ta_uuid: vec![0; 16],
+            ta_measurement: vec![0; 32],
+            signature: vec![1; 256],
+            attest_pubkey_exp: vec![1, 0, 1],
+            attest_pubkey_mod: vec![0xc5; 256],
+            sig_alg: 0x7041_4930,
+            ree_time_secs: 1_700_000_000,
+        };
+        let proof = DeletionProof {
+            certificate,
+            attestation: Some(evidence),
+        };
+        let view = DeletionCertificateView::from_proof(proof.clone()).unwrap();
+        assert_eq!(
+            view.attestation.as_ref().unwrap()["nonce"].as_str(),
+            Some(view.digest.as_str())
+        );
+        assert_eq!(view.wallet_id, "22222222-2222-2222-2222-222222222222");
+
+        let mut forged = proof;
+        forged.certificate.rollback_counter = Some(10);
+        assert!(DeletionCertificateView::from_proof(forged).is_err());
+    }
+
+    #[test]
+    fn tamper_order_response_flattens_status() {
+        use proto::tamper::{WipeCertificate, WipeProof, WipeReason, WIPE_CERT_VERSION};
+        let proof = WipeProof {
+            certificate: WipeCertificate {
+                version: WIPE_CERT_VERSION,
+                reason: WipeReason::PanicWipe,
+                wallets: 2,
+                wallet_set: [0x44; 32],
+                wiped_at: 1_700_000_000,
+                rollback_counter: None,
+            },
+            attestation: None,
+        };
+        let view = WipeCertificateView::from_proof(proof).unwrap();
+        assert_eq!(view.reason, "PanicWipe");
+        let resp = TamperOrderResponse {
+            wiped: true,
+            status: TamperStatusResponse {
+                failed_auth_limit: None,
+                consecutive_failures: 0,
+                last_order_sequence: 3,
+                locked: true,
+                lock_reason: Some("PanicWipe".into()),
+                locked_since: Some(1_700_000_000),
+                last_wipe: Some(view),
+            },
+        };
+        let json = serde_json::to_value(&resp).unwrap();
+        assert_eq!(json["wiped"], true);
+        assert_eq!(json["locked"], true);
+        assert_eq!(json["lastWipe"]["wallets"], 2);
+        assert!(json.get("failedAuthLimit").is_none());
+    }
+
+    #[test]
+    fn sign_permit_request_defaults_hd_path() {
+        let body = format!(
+            r#"{{"keyId":"abc","permit":{{"kind":"permit2-transfer","chainId":1,"token":"0x{t}","spender":"0x{t}","amount":"5","nonce":"0x2a","deadline":"max"}},"webAuthnAssertion":{wa}}}"#,
+            t = "11".repeat(20),
+            wa = WA
+        );
+        let r: SignPermitRequest = serde_json::from_str(&body).unwrap();
+        assert_eq!(r.hd_path, "m/44'/60'/0'/0/0");
+        let permit = r.permit.to_proto().unwrap();
+        assert!(permit
+            .summary()
+            .starts_with("Permit2 transfer: allow 0x1111"));
+        assert_eq!(permit.signature_deadline(), None);
+    }
+
+    #[test]
+    fn verify_request_uses_aws_field_names() {
+        let r: VerifyRequest = serde_json::from_str(
+            r#"{"KeyId":"k","Message":"aGk=","Signature":"MEQ=","SigningAlgorithm":"ECDSA_SHA_256"}"#,
+        )
+        .unwrap();
+        assert!(r.message_type.is_none() && r.derivation_path.is_none());
+        assert_eq!(r.signing_algorithm, "ECDSA_SHA_256");
+        // SigningAlgorithm is required, as in AWS.
+        assert!(serde_json::from_str::<VerifyRequest>(
+            r#"{"KeyId":"k","Message":"aGk=","Signature":"MEQ="}"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn multi_region_key_view() {
+        let region = |r: &str, device: &str, role: &str| KeyRegionRow {
+            key_id: "k".into(),
+            region: r.into(),
+            device_id: device.into(),
+            role: role.into(),
+            created_at: 0,
+        };
+        assert!(multi_region_configuration("k", &[], "us-east-1").is_none());
+        let regions = vec![
+            region("us-east-1", "dev-a", "PRIMARY"),
+            region("eu-west-1", "dev-b", "REPLICA"),
+        ];
+        let primary = multi_region_configuration("k", &regions, "us-east-1").unwrap();
+        assert_eq!(primary.multi_region_key_type, "PRIMARY");
+        assert_eq!(
+            primary.primary_key.unwrap().arn,
+            "arn:aws:kms:us-east-1:account:key/k"
+        );
+        let replica = multi_region_configuration("k", &regions, "eu-west-1").unwrap();
+        assert_eq!(replica.multi_region_key_type, "REPLICA");
+        assert_eq!(replica.replica_keys.len(), 1);
+        assert_eq!(replica.replica_keys[0].region, "eu-west-1");
+
+        let r: ReplicateKeyRequest = serde_json::from_str(&format!(
+            r#"{{"KeyId":"k","ReplicaRegion":"eu-west-1","ReplicationHello":"aa-repl-hello:x","WebAuthn":{}}}"#,
+            WA
+        ))
+        .unwrap();
+        assert!(r.replica_device_id.is_none() && r.webauthn.is_some());
+        // Old clients send no MultiRegion field.
+        let c: CreateKeyRequest = serde_json::from_str(
+            r#"{"Description":"d","KeyUsage":"SIGN_VERIFY","KeySpec":"ECC_SECG_P256K1","Origin":"AWS_KMS","PasskeyPublicKey":"0x04"}"#,
+        )
+        .unwrap();
+        assert!(c.multi_region.is_none());
+    }
+
+    #[test]
+    fn siwe_requests_deserialize() {
+        let body = format!(
+            r#"{{"keyId":"abc","message":"m","origin":"https://app.example","webAuthnAssertion":{}}}"#,
+            WA
+        );
+        let r: SiweSignRequest = serde_json::from_str(&body).unwrap();
+        assert_eq!(r.hd_path, "m/44'/60'/0'/0/0");
+        assert_eq!(r.origin.as_deref(), Some("https://app.example"));
+
+        let v: SiweVerifyRequest =
+            serde_json::from_str(r#"{"message":"m","signature":"0x00"}"#).unwrap();
+        assert!(v.domain.is_none() && v.nonce.is_none());
+        let invalid = serde_json::to_value(SiweVerifyResponse {
+            valid: false,
+            address: None,
+            chain_id: None,
+            reason: Some("sign-in message has expired".into()),
+        })
+        .unwrap();
+        assert_eq!(
+            invalid,
+            serde_json::json!({"valid": false, "reason": "sign-in message has expired"})
+        );
+    }
+
+    #[test]
+    fn capabilities_lists_disabled_commands_by_name() {
+        let caps = proto::GetCapabilitiesOutput {
+            ta_version: "0.8.0".into(),
+            features: vec![],
+            policy: Some(proto::deployment_policy::DeploymentPolicy {
+                format: proto::deployment_policy::POLICY_FORMAT_VERSION,
+                deployment: "rack-3".into(),
+                sequence: 2,
+                disabled_commands: vec![5, 7],
+            }),
+            policy_signer: Some([0xab; 20]),
+            config: None,
+            provisioning_key: Some([0xcd; 20]),
+        };
+        let resp = capabilities_response(caps);
+        assert_eq!(resp.disabled_commands, vec!["SignHash", "ExportPrivateKey"]);
+        assert!(resp
+            .enabled_commands
+            .contains(&"SignTransaction".to_string()));
+        assert!(!resp.enabled_commands.contains(&"SignHash".to_string()));
+        // removed ids (13, 16) are never listed
+        assert!(!resp.enabled_commands.iter().any(|c| c == "Unknown"));
+        assert_eq!(resp.policy_sequence, Some(2));
+        let json = serde_json::to_value(&resp).unwrap();
+        assert!(json.get("taConfig").is_none());
+        assert_eq!(json["provisioningKey"], format!("0x{}", "cd".repeat(20)));
+    }
+
+    #[test]
+    fn strict_tas_get_no_host_challenge() {
+        let mut caps = proto::GetCapabilitiesOutput {
+            ta_version: "0.8.0".into(),
+            features: vec!["export-secrets".into()],
+            policy: None,
+            policy_signer: None,
+            config: None,
+            provisioning_key: None,
+        };
+        assert!(!ta_requires_own_challenge(&caps));
+        caps.config = Some(proto::ta_config::TaConfig {
+            format: proto::ta_config::CONFIG_FORMAT_VERSION,
+            deployment: "rack-3".into(),
+            sequence: 1,
+            limits: proto::ta_config::TaLimits::DEFAULT,
+            require_ta_challenge: true,
+            require_signing_context: false,
+        });
+        assert!(ta_requires_own_challenge(&caps));
+        caps.config = None;
+        caps.features.push("strict-challenge".into());
+        assert!(ta_requires_own_challenge(&caps));
+    }
+
+    #[test]
+    fn replication_import_deser_and_device_view() {
+        let req: ReplicationImportRequest = serde_json::from_str(
+            r#"{"package":"aa-repl-pkg:AA","sourceDeviceId":"dev-a","wallet":{"description":"d","keyUsage":"SIGN_VERIFY","keySpec":"ECC_SECG_P256K1","origin":"EXTERNAL_KMS","passkeyPubkey":"0x04ab","credentialId":null}}"#,
+        )
+        .unwrap();
+        assert_eq!(req.source_device_id, "dev-a");
+        assert!(req.source_endpoint.is_none() && req.wallet.address.is_none());
+
+        let row = |id: &str, endpoint: Option<&str>, status: &str| WalletDeviceRow {
+            key_id: "k".into(),
+            device_id: id.into(),
+            endpoint: endpoint.map(|e| e.to_string()),
+            attestation_key_fp: "0xfp".into(),
+            status: status.into(),
+            added_at: 0,
+            revoked_at: None,
+            last_seen: 1_000,
+            quarantined: false,
+        };
+        let rows = vec![
+            row("dev-a", Some("https://a"), "active"),
+            row("dev-b", Some("https://b"), "revoked"),
+        ];
+        let resp = wallet_devices_response("k", &rows, 1_010);
+        assert_eq!(resp.route_device_id.as_deref(), Some("dev-a"));
+        assert_eq!(resp.route_endpoint.as_deref(), Some("https://a"));
+        assert!(resp.devices[0].healthy && !resp.devices[1].healthy);
+        // nothing heard from anyone for too long: no route
+        let stale = wallet_devices_response("k", &rows, 1_000 + 10_000);
+        assert!(stale.route_device_id.is_none());
+    }
+
+    #[test]
+    fn rescue_transaction_deser() {
+        let req: RescueTransactionRequest = serde_json::from_str(
+            r#"{"keyId":"k","chainId":1,"confirmedNonce":4,"nonce":5,"mode":"cancel","gasPrice":"0x3b9aca00"}"#,
+        )
+        .unwrap();
+        assert_eq!((req.chain_id, req.nonce), (1, Some(5)));
+        assert_eq!(req.confirmed_nonce, Some(4));
+        assert_eq!(req.mode.as_deref(), Some("cancel"));
+        assert!(req.derivation_path.is_none() && req.fee_bump_percent.is_none());
+        // report-only form
+        let req: RescueTransactionRequest =
+            serde_json::from_str(r#"{"keyId":"k","chainId":11155111}"#).unwrap();
+        assert!(req.nonce.is_none() && req.webauthn_assertion.is_none());
+    }
+
+    #[test]
+    fn export_offline_request_deser() {
+        let req: ExportOfflineRequest = serde_json::from_str(
+            r#"{"keyId":"4319f351-0b24-4097-b659-80ee4f824cdd","derivationPath":"m/44'/60'/0'/0/0","transaction":{"chainId":1,"nonce":9,"to":"0x3535353535353535353535353535353535353535","value":"de0b6b3a7640000","gasPrice":"4a817c800","gas":21000,"data":""}}"#,
+        )
+        .unwrap();
+        assert!(req.ttl_seconds.is_none());
+        let tx = req.transaction.to_proto().unwrap();
+        assert_eq!(tx.value, 1_000_000_000_000_000_000);
+        assert_eq!(tx.gas_price, 20_000_000_000);
+    }
+
+    #[test]
+    fn eip1559_transaction_deser() {
+        let req: SignRequest = serde_json::from_str(
+            r#"{"KeyId":"k","DerivationPath":"m/44'/60'/0'/0/0","Transaction":{"chainId":1,"nonce":9,"to":"0x3535353535353535353535353535353535353535","value":"de0b6b3a7640000","maxFeePerGas":"0x9502f9000","maxPriorityFeePerGas":"0x59682f00","gas":60000,"data":"0xa9059cbb","accessList":[{"address":"0x1111111111111111111111111111111111111111","storageKeys":["0x0000000000000000000000000000000000000000000000000000000000000001"]}]}}"#,
+        )
+        .unwrap();
+        let transaction = req.transaction.unwrap();
+        assert!(transaction.is_eip1559());
+        // legacy-only endpoints refuse the fee-market fields
+        assert!(transaction.to_proto().is_err());
+        let tx = transaction.to_eip1559().unwrap();
+        assert_eq!(
+            hex::encode(tx.signing_hash()),
+            "e44d5efc83d7effd4950273d8b3e9555e938f3d3340f79286f48745cff825caa"
+        );
+        assert_eq!(transaction.to_call().unwrap().gas_price, 40_000_000_000);
+
+        let mut mixed = transaction;
+        mixed.gas_price = "0x1".into();
+        assert!(mixed.to_eip1559().is_err());
+        mixed.gas_price = String::new();
+        mixed.max_priority_fee_per_gas = Some("0x9502f9001".into());
+        assert!(mixed.to_eip1559().is_err());
+    }
+
+    #[test]
+    fn multisig_policy_request_is_validated() {
+        let key = format!("0x02{}", "11".repeat(32));
+        let req: SetMultiSigPolicyRequest = serde_json::from_str(&format!(
+            r#"{{"keyId":"k","policy":{{"thresholdWei":"1000000000000000000","required":1,"cosigners":["{}"]}}}}"#,
+            key
+        ))
+        .unwrap();
+        let json = req.policy.unwrap();
+        let policy = json.to_proto().unwrap();
+        assert_eq!(policy.threshold_wei, 10u128.pow(18));
+        assert_eq!(policy.cosigners, vec![hex::decode(&key[2..]).unwrap()]);
+        assert_eq!(MultiSigPolicyJson::from_proto(&policy), json);
+
+        let two_of_one = MultiSigPolicyJson {
+            required: 2,
+            ..json
+        };
+        assert!(two_of_one.to_proto().is_err());
+        let remove: SetMultiSigPolicyRequest = serde_json::from_str(r#"{"keyId":"k"}"#).unwrap();
+        assert!(remove.policy.is_none());
+    }
+
+    #[test]
+    fn guardian_set_request_is_validated() {
+        let key = format!("0x03{}", "22".repeat(32));
+        let req: SetGuardiansRequest = serde_json::from_str(&format!(
+            r#"{{"keyId":"k","guardians":{{"threshold":1,"guardians":["{}"],"delaySecs":172800}}}}"#,
+            key
+        ))
+        .unwrap();
+        let json = req.guardians.unwrap();
+        let set = json.to_proto().unwrap();
+        assert_eq!(set.guardians, vec![hex::decode(&key[2..]).unwrap()]);
+        assert_eq!(GuardianSetJson::from_proto(&set), json);
+
+        let hasty = GuardianSetJson {
+            delay_secs: 60,
+            ..json
+        };
+        assert!(hasty.to_proto().is_err());
+        let status = proto::social_recovery::RecoveryStatus {
+            guardians: Some(set),
+            round: 4,
+            candidates: vec![proto::social_recovery::RecoveryCandidate {
+                passkey: vec![4, 1],
+                attestations: 1,
+            }],
+            armed: None,
+        };
+        let out = recovery_status_json("k", &status);
+        assert_eq!(out["round"], 4);
+        assert_eq!(out["candidates"][0]["passkeyPublicKey"], "0x0401");
+        assert!(out["armed"].is_null());
+    }
+
+    #[test]
+    fn nonce_tracking_json_keeps_full_nonces() {
+        let req: OverrideNonceRequest = serde_json::from_str(
+            r#"{"keyId":"k","chainId":10,"address":"0x3535353535353535353535353535353535353535","nonce":340282366920938463463374607431768211455}"#,
+        )
+        .unwrap();
+        assert_eq!(req.nonce, u128::MAX);
+        assert!(req.webauthn_assertion.is_none());
+
+        let armed = proto::nonce_guard::NonceOverride {
+            chain_id: 10,
+            address: [0x35; 20],
+            nonce: 7,
+            expires_at: 1_700_000_600,
+        };
+        let response = NonceTrackingResponse::from(proto::NonceTrackingOutput {
+            enabled: true,
+            previous: None,
+            entries: vec![proto::nonce_guard::NonceEntry {
+                chain_id: 10,
+                address: [0x35; 20],
+                highest: 9,
+            }],
+            pending_override: Some(armed),
+        });
+        let json = serde_json::to_value(&response).unwrap();
+        assert_eq!(json["nonces"][0]["highestNonce"], 9);
+        assert_eq!(json["pendingOverride"]["expiresAt"], 1_700_000_600u64);
+        assert!(json.get("previous").is_none());
+        let read: NonceTrackingRequest = serde_json::from_str(r#"{"keyId":"k"}"#).unwrap();
+        assert!(read.enabled.is_none());
+    }
+
+    #[test]
+    fn spending_policy_request_converts_both_ways() {
+        let req: SpendingPolicyRequest = serde_json::from_str(
+            r#"{"keyId":"k","policy":{"dailyLimitWei":"1000000000000000000","deniedDestinations":["0x3535353535353535353535353535353535353535"],"allowedChainIds":[10,1]}}"#,
+        )
+        .unwrap();
+        let json = req.policy.unwrap();
+        let policy = json.to_proto().unwrap();
+        assert_eq!(policy.daily_limit_wei, Some(10u128.pow(18)));
+        assert_eq!(policy.denied_destinations, vec![[0x35; 20]]);
+        assert!(policy.allowed_destinations.is_empty());
+        assert_eq!(policy.max_gas_price_wei, None);
+        assert_eq!(SpendingPolicyJson::from_proto(&policy), json);
+
+        let bad = SpendingPolicyJson {
+            max_gas_price_wei: Some("0x10".into()),
+            ..Default::default()
+        };
+        assert!(bad.to_proto().is_err());
+        let read: SpendingPolicyRequest = serde_json::from_str(r#"{"keyId":"k"}"#).unwrap();
+        assert!(read.policy.is_none());
+    }
+
+    #[test]
+    fn describe_transaction_request_decodes_approve() {
+        let calldata = format!(
+            "0x095ea7b3{}{}",
+            format!("{:0>64}", "11".repeat(20)),
+            "ff".repeat(32)
+        );
+        let body = format!(
+            r#"{{"Transaction":{{"chainId":1,"nonce":0,"to":"0xdAC17F958D2ee523a2206206994597C13D831ec7","value":"0","gasPrice":"1","gas":60000,"data":"{}"}}}}"#,
+            calldata
+        );
+        let req: DescribeTransactionRequest = serde_json::from_str(&body).unwrap();
+        assert!(req.summary_abis.is_none());
+        let tx = req.transaction.to_proto().unwrap();
+        assert_eq!(
+            proto::calldata::summarize_transaction(&tx, &[]),
+            "approve USDT unlimited to 0x1111111111111111111111111111111111111111"
+        );
+    }
+}
+
+#[cfg(test)]
+mod passkey_lockout_tests {
+    use super::*;
+    use crate::api_error::ErrorKind;
+    use std::time::Duration;
+
+    fn server() -> KmsApiServer {
+        let mut server = KmsApiServer::new(KmsDb::open_memory().unwrap());
+        server.passkey_lockout = PasskeyLockout::new(3, Duration::from_secs(60), 100);
+        server
+    }
+
+    fn bad_assertion(server: &KmsApiServer, key_id: &str) -> anyhow::Error {
+        server
+            .with_passkey_lockout(key_id, || -> Result<()> {
+                Err(anyhow!("WebAuthn signature verification failed"))
+            })
+            .unwrap_err()
+    }
+
+    #[tokio::test]
+    async fn repeated_failures_lock_with_429() {
+        let server = server();
+        for _ in 0..3 {
+            let e = bad_assertion(&server, "w");
+            assert_ne!(ErrorKind::of(&e).status(), 429, "{}", e);
+        }
+
+        let mut called = false;
+        let e = server
+            .with_passkey_lockout("w", || {
+                called = true;
+                Ok(())
+            })
+            .unwrap_err();
+        assert!(!called, "a locked wallet must not reach verification");
+        assert_eq!(ErrorKind::of(&e).status(), 429);
+        assert!(server.with_passkey_lockout("other", || Ok(())).is_ok());
+
+        let events = server.db.list_account_events("w", 10, None).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event, "passkey_locked");
+    }
+
+    #[tokio::test]
+    async fn a_verified_assertion_resets_the_count() {
+        let server = server();
+        bad_assertion(&server, "w");
+        bad_assertion(&server, "w");
+        assert_eq!(server.with_passkey_lockout("w", || Ok(7)).unwrap(), 7);
+        bad_assertion(&server, "w");
+        bad_assertion(&server, "w");
+        assert!(server.with_passkey_lockout("w", || Ok(())).is_ok());
+    }
+}
+
+#[cfg(test)]
+mod complete_registration_tests {
+    use super::*;
+    use crate::api_error::ErrorKind;
+
+    #[tokio::test]
+    async fn a_challenge_of_another_purpose_is_refused_and_kept() {
+        let server = KmsApiServer::new(KmsDb::open_memory().unwrap());
+        server
+            .db
+            .store_challenge(
+                "c1",
+                &[7; 32],
+                Some("w"),
+                "authentication",
+                "localhost",
+                300,
+            )
+            .unwrap();
+        let req: webauthn::CompleteRegistrationRequest = serde_json::from_str(
+            r#"{"ChallengeId":"c1","Credential":{"id":"i","rawId":"i","type":"public-key",
+                "response":{"clientDataJSON":"e30","attestationObject":"oA"}}}"#,
+        )
+        .unwrap();
+
+        let e = server.complete_registration(req).await.unwrap_err();
+        assert!(
+            e.to_string().contains("not issued for registration"),
+            "{}",
+            e
+        );
+        assert_eq!(ErrorKind::of(&e).status(), 400);
+
+        assert!(server.db.challenge_created_at("c1").unwrap().is_some());
+        assert!(server.db.list_wallets().unwrap().is_empty());
+    }
+}
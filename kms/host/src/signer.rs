@@ -0,0 +1,202 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Signing backends behind one interface.
+//!
+//! DeriveAddress, Sign (transaction and message) and SignHash go through a
+//! [`Signer`] picked per wallet: the TA ([`TeeHandle`]) unless the wallet has
+//! a `wallet_signers` row (db.rs), in which case its key is an HSM object
+//! reached through PKCS#11 (pkcs11.rs). The API, key pinning and the tx
+//! ledger do not change with the backend. The other TA commands (sessions,
+//! agent keys, permits, typed data, offline signing) stay TA-only; an HSM
+//! wallet has no TA wallet, so the TA answers them with "wallet not found".
+
+use anyhow::Result;
+use proto::eip1559::Eip1559Transaction;
+use proto::paymaster::FeeQuote;
+use proto::{EthTransaction, PasskeyAssertion, SigningContext};
+use std::future::Future;
+use std::pin::Pin;
+use uuid::Uuid;
+
+use crate::ta_client::TeeHandle;
+
+/// `wallet_signers.backend` of an HSM-held wallet.
+pub const PKCS11: &str = "pkcs11";
+
+pub type SignFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T>> + Send + 'a>>;
+
+/// Same arguments and results as the `TeeHandle` methods of the same name:
+/// an address, a signed EIP-155 or EIP-1559 transaction, a 65-byte r ‖ s ‖ v
+/// signature.
+pub trait Signer: Send + Sync {
+    /// "tee" or [`PKCS11`], for logs.
+    fn backend(&self) -> &'static str;
+
+    /// Whether the backend checks the passkey assertion's challenge against
+    /// the payload itself, as the TA does. When it does not, the CA holds
+    /// the challenge to the bare one-time nonce instead.
+    fn binds_passkey_to_payload(&self) -> bool;
+
+    fn derive_address<'a>(
+        &'a self,
+        wallet_id: Uuid,
+        hd_path: &'a str,
+        passkey_assertion: Option<PasskeyAssertion>,
+    ) -> SignFuture<'a, [u8; 20]>;
+
+    fn sign_transaction<'a>(
+        &'a self,
+        wallet_id: Uuid,
+        hd_path: &'a str,
+        transaction: EthTransaction,
+        passkey_assertion: Option<PasskeyAssertion>,
+        summary_abis: Vec<String>,
+        summary_committed: bool,
+    ) -> SignFuture<'a, Vec<u8>>;
+
+    fn sign_eip1559_transaction<'a>(
+        &'a self,
+        wallet_id: Uuid,
+        hd_path: &'a str,
+        transaction: Eip1559Transaction,
+        passkey_assertion: Option<PasskeyAssertion>,
+        summary_abis: Vec<String>,
+        summary_committed: bool,
+    ) -> SignFuture<'a, Vec<u8>>;
+
+    fn sign_message<'a>(
+        &'a self,
+        wallet_id: Uuid,
+        hd_path: &'a str,
+        message: &'a [u8],
+        passkey_assertion: Option<PasskeyAssertion>,
+        context: SigningContext,
+    ) -> SignFuture<'a, Vec<u8>>;
+
+    fn sign_hash<'a>(
+        &'a self,
+        wallet_id: Uuid,
+        hd_path: &'a str,
+        hash: &'a [u8; 32],
+        passkey_assertion: Option<PasskeyAssertion>,
+        context: SigningContext,
+        fee_quote: Option<FeeQuote>,
+    ) -> SignFuture<'a, Vec<u8>>;
+}
+
+impl Signer for TeeHandle {
+    fn backend(&self) -> &'static str {
+        "tee"
+    }
+
+    fn binds_passkey_to_payload(&self) -> bool {
+        true
+    }
+
+    fn derive_address<'a>(
+        &'a self,
+        wallet_id: Uuid,
+        hd_path: &'a str,
+        passkey_assertion: Option<PasskeyAssertion>,
+    ) -> SignFuture<'a, [u8; 20]> {
+        Box::pin(TeeHandle::derive_address(
+            self,
+            wallet_id,
+            hd_path,
+            passkey_assertion,
+        ))
+    }
+
+    fn sign_transaction<'a>(
+        &'a self,
+        wallet_id: Uuid,
+        hd_path: &'a str,
+        transaction: EthTransaction,
+        passkey_assertion: Option<PasskeyAssertion>,
+        summary_abis: Vec<String>,
+        summary_committed: bool,
+    ) -> SignFuture<'a, Vec<u8>> {
+        Box::pin(TeeHandle::sign_transaction(
+            self,
+            wallet_id,
+            hd_path,
+            transaction,
+            passkey_assertion,
+            summary_abis,
+            summary_committed,
+        ))
+    }
+
+    fn sign_eip1559_transaction<'a>(
+        &'a self,
+        wallet_id: Uuid,
+        hd_path: &'a str,
+        transaction: Eip1559Transaction,
+        passkey_assertion: Option<PasskeyAssertion>,
+        summary_abis: Vec<String>,
+        summary_committed: bool,
+    ) -> SignFuture<'a, Vec<u8>> {
+        Box::pin(TeeHandle::sign_eip1559_transaction(
+            self,
+            wallet_id,
+            hd_path,
+            transaction,
+            passkey_assertion,
+            summary_abis,
+            summary_committed,
+        ))
+    }
+
+    fn sign_message<'a>(
+        &'a self,
+        wallet_id: Uuid,
+        hd_path: &'a str,
+        message: &'a [u8],
+        passkey_assertion: Option<PasskeyAssertion>,
+        context: SigningContext,
+    ) -> SignFuture<'a, Vec<u8>> {
+        Box::pin(TeeHandle::sign_message(
+            self,
+            wallet_id,
+            hd_path,
+            message,
+            passkey_assertion,
+            context,
+        ))
+    }
+
+    fn sign_hash<'a>(
+        &'a self,
+        wallet_id: Uuid,
+        hd_path: &'a str,
+        hash: &'a [u8; 32],
+        passkey_assertion: Option<PasskeyAssertion>,
+        context: SigningContext,
+        fee_quote: Option<FeeQuote>,
+    ) -> SignFuture<'a, Vec<u8>> {
+        Box::pin(TeeHandle::sign_hash(
+            self,
+            wallet_id,
+            hd_path,
+            hash,
+            passkey_assertion,
+            context,
+            fee_quote,
+        ))
+    }
+}
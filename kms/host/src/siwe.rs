@@ -0,0 +1,123 @@
+//! Sign-In with Ethereum (EIP-4361) — verifying a sign-in from anyone.
+//!
+//! `POST /kms/siwe/verify` lets a relying party behind this CA check a
+//! sign-in produced by any EOA wallet, AirAccount or not: the text must be a
+//! canonical EIP-4361 message (`proto::siwe`, the parser the TA uses before
+//! signing one), its time window must hold against the host clock, and the
+//! signature must recover to the address in the message. The caller's
+//! expected domain and nonce are checked when given; the nonce store itself
+//! is the relying party's. Contract wallets (ERC-1271) need an RPC call and
+//! are not verified here.
+
+use crate::key_pin;
+use anyhow::{anyhow, Result};
+use proto::siwe::SiweMessage;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Verified {
+    pub message: SiweMessage,
+    /// Recovered signer (equals `message.address`).
+    pub address: [u8; 20],
+}
+
+pub fn verify(
+    text: &str,
+    signature: &[u8],
+    expected_domain: Option<&str>,
+    expected_nonce: Option<&str>,
+    now: i64,
+) -> Result<Verified> {
+    let message = SiweMessage::parse(text).map_err(|e| anyhow!("{}", e))?;
+    if let Some(domain) = expected_domain {
+        if message.domain != domain {
+            return Err(anyhow!(
+                "sign-in is for {:?}, expected {:?}",
+                message.domain,
+                domain
+            ));
+        }
+    }
+    if let Some(nonce) = expected_nonce {
+        if message.nonce != nonce {
+            return Err(anyhow!("sign-in nonce does not match"));
+        }
+    }
+    message.check_validity(now).map_err(|e| anyhow!("{}", e))?;
+    let address = key_pin::recover_signer(&message.digest(), signature)?;
+    if address != message.address {
+        return Err(anyhow!(
+            "signature is by {}, not the sign-in address",
+            key_pin::address_hex(&address)
+        ));
+    }
+    Ok(Verified { message, address })
+}
+
+/// Authority of an `Origin` header value (`https://app.example:8443` →
+/// `app.example:8443`), for matching the message's domain.
+pub fn origin_authority(origin: &str) -> &str {
+    let rest = origin.split("://").nth(1).unwrap_or(origin);
+    rest.trim_end_matches('/')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use k256::ecdsa::SigningKey;
+    use sha3::{Digest, Keccak256};
+
+    fn sign(key: &SigningKey, digest: &[u8; 32]) -> Vec<u8> {
+        let (sig, recid) = key.sign_prehash_recoverable(digest).unwrap();
+        let mut out = sig.to_bytes().to_vec();
+        out.push(27 + recid.to_byte());
+        out
+    }
+
+    fn address_of(key: &SigningKey) -> [u8; 20] {
+        let point = key.verifying_key().to_encoded_point(false);
+        let mut a = [0u8; 20];
+        a.copy_from_slice(&Keccak256::digest(&point.as_bytes()[1..])[12..]);
+        a
+    }
+
+    #[test]
+    fn verify_sign_in() {
+        let sk = SigningKey::from_slice(&[0x42; 32]).unwrap();
+        let message = SiweMessage {
+            scheme: None,
+            domain: "app.example".into(),
+            address: address_of(&sk),
+            statement: Some("Sign in to app.example".into()),
+            uri: "https://app.example/login".into(),
+            version: "1".into(),
+            chain_id: 1,
+            nonce: "abcdef0123".into(),
+            issued_at: "2021-09-30T16:25:24Z".into(),
+            expiration_time: Some("2021-09-30T16:35:24Z".into()),
+            not_before: None,
+            request_id: None,
+            resources: vec![],
+        };
+        let text = message.to_message();
+        let sig = sign(&sk, &message.digest());
+        let now = message.issued_at_secs().unwrap() + 30;
+
+        let ok = verify(&text, &sig, Some("app.example"), Some("abcdef0123"), now).unwrap();
+        assert_eq!(ok.address, message.address);
+
+        assert!(verify(&text, &sig, Some("other.example"), None, now).is_err());
+        assert!(verify(&text, &sig, None, Some("0123abcdef"), now).is_err());
+        assert!(verify(&text, &sig, None, None, now + 3600).is_err());
+        let other = sign(
+            &SigningKey::from_slice(&[0x43; 32]).unwrap(),
+            &message.digest(),
+        );
+        assert!(verify(&text, &other, None, None, now).is_err());
+
+        assert_eq!(
+            origin_authority("https://app.example:8443/"),
+            "app.example:8443"
+        );
+        assert_eq!(origin_authority("app.example"), "app.example");
+    }
+}
@@ -0,0 +1,561 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Software TEE — INSECURE DEV MODE (`soft-tee` feature).
+//!
+//! Answers proto commands in-process, so the CA, portal and SDKs can be run
+//! and integrated on a machine without OP-TEE. `ta_client` sends every
+//! command here instead of opening a TA session: same bincode input and
+//! output, same error text. Seeds are written in plaintext to
+//! `KMS_SOFT_TEE_STORE` (default `soft-tee-wallets.json`).
+//!
+//! Covered: CreateWallet, RemoveWallet, DeriveAddress, DeriveAddressAuto,
+//! SignTransaction, SignEip1559Transaction, SignMessage, SignHash,
+//! GetChallenge, WarmupCache, TaStats, GetCapabilities and GetProtocolVersion.
+//! Everything else answers `wire::UnsupportedVersion`, like an older TA. Passkey assertions are
+//! checked against the wallet's P-256 key and the clientDataJSON hash; the
+//! one-time nonce and payload commitment are not.
+//!
+//! Nothing here is secret from the host: [`acknowledge`] refuses to run
+//! without `KMS_INSECURE_DEV_MODE=1` or on a device that has a real TEE,
+//! and the CA marks every reply, health report and audit entry with
+//! [`MARKER`].
+
+use anyhow::{anyhow, Context, Result};
+use hmac::{Hmac, Mac};
+use k256::ecdsa::SigningKey;
+use k256::elliptic_curve::PrimeField;
+use k256::{FieldBytes, Scalar, SecretKey};
+use p256::ecdsa::{signature::Verifier, Signature, VerifyingKey};
+use proto::chain::{Chain, EVM_COIN_TYPE};
+use proto::eip1559::{signed_rlp, SignedEip1559Tx};
+use proto::hd_path::{parse_coin_path, parse_path, InvalidPath, HARDENED_BIT};
+use proto::offline::{legacy_signing_hash, signed_legacy_rlp, SignedLegacyTx};
+use proto::wire::{UnsupportedVersion, PROTOCOL_VERSION};
+use proto::{Command, PasskeyAssertion};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256, Sha512};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+/// Stamped on every reply, health report and audit entry of this build.
+pub const MARKER: &str = "INSECURE DEV MODE";
+
+/// Reply header carrying [`MARKER`].
+pub const HEADER: &str = "x-airaccount-insecure";
+
+/// Must be `1` for a soft-TEE build to start.
+pub const ACK_ENV: &str = "KMS_INSECURE_DEV_MODE";
+
+/// Where the plaintext wallet seeds live.
+pub const STORE_ENV: &str = "KMS_SOFT_TEE_STORE";
+
+/// A soft-TEE build on a device with this node would be shipping by mistake.
+const TEE_DEVICE: &str = "/dev/tee0";
+
+/// Refuse to run unless the operator said yes and there is no real TEE.
+/// Prints the banner either way.
+pub fn acknowledge() -> Result<()> {
+    eprintln!("{}", banner());
+    if Path::new(TEE_DEVICE).exists() {
+        return Err(anyhow!(
+            "{} exists: this device has a real TEE, refusing to run a soft-TEE build",
+            TEE_DEVICE
+        ));
+    }
+    if std::env::var(ACK_ENV).as_deref() != Ok("1") {
+        return Err(anyhow!(
+            "soft-TEE build: set {}=1 to run it; keys are NOT protected",
+            ACK_ENV
+        ));
+    }
+    Ok(())
+}
+
+pub fn banner() -> String {
+    let line = "!".repeat(72);
+    format!(
+        "{line}\n!!  {MARKER}: software TEE, wallet seeds are plaintext on disk\n\
+         !!  Never use this build with real funds or ship it to a device\n{line}",
+        line = line,
+        MARKER = MARKER
+    )
+}
+
+/// `[INSECURE DEV MODE] <detail>` for an audit entry.
+pub fn audit_detail(detail: Option<&str>) -> String {
+    match detail {
+        Some(detail) => format!("[{}] {}", MARKER, detail),
+        None => format!("[{}]", MARKER),
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct SoftWallet {
+    #[serde(with = "hex")]
+    seed: Vec<u8>,
+    #[serde(with = "hex")]
+    passkey_pubkey: Vec<u8>,
+    next_address_index: u32,
+}
+
+/// The wallets of one store file. Every mutation is written back at once.
+pub struct SoftTee {
+    path: PathBuf,
+    wallets: BTreeMap<Uuid, SoftWallet>,
+}
+
+impl SoftTee {
+    /// The store named by `KMS_SOFT_TEE_STORE`.
+    pub fn from_env() -> Result<Self> {
+        let path = std::env::var(STORE_ENV).unwrap_or_else(|_| "soft-tee-wallets.json".to_string());
+        Self::open(path)
+    }
+
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let wallets = match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .with_context(|| format!("soft-TEE store {}", path.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e).with_context(|| format!("soft-TEE store {}", path.display())),
+        };
+        Ok(Self { path, wallets })
+    }
+
+    fn save(&self) -> Result<()> {
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(&self.wallets)?)?;
+        std::fs::rename(&tmp, &self.path)
+            .with_context(|| format!("soft-TEE store {}", self.path.display()))
+    }
+
+    /// One command, bincode in and out, as the TA would answer it.
+    pub fn invoke(&mut self, command: Command, input: &[u8]) -> Result<Vec<u8>> {
+        match command {
+            Command::CreateWallet => {
+                let input: proto::CreateWalletInput = decode(input)?;
+                encode(&self.create_wallet(input)?)
+            }
+            Command::RemoveWallet => {
+                let input: proto::RemoveWalletInput = decode(input)?;
+                let wallet = self.wallet(&input.wallet_id)?;
+                check_passkey(wallet, input.passkey_assertion.as_ref())?;
+                self.wallets.remove(&input.wallet_id);
+                self.save()?;
+                // No deletion certificate, like a TA without proof-of-erasure.
+                Ok(Vec::new())
+            }
+            Command::DeriveAddress => {
+                let input: proto::DeriveAddressInput = decode(input)?;
+                let wallet = self.wallet(&input.wallet_id)?;
+                if input.passkey_assertion.is_some() {
+                    check_passkey(wallet, input.passkey_assertion.as_ref())?;
+                }
+                let (address, public_key) = derive_address(wallet, &input.hd_path, input.chain)?;
+                encode(&proto::DeriveAddressOutput {
+                    address,
+                    public_key,
+                    encoded_address: input.chain.encode_address(&address),
+                })
+            }
+            Command::DeriveAddressAuto => {
+                let input: proto::DeriveAddressAutoInput = decode(input)?;
+                let wallet = self
+                    .wallets
+                    .get_mut(&input.wallet_id)
+                    .ok_or_else(|| anyhow!("wallet not found: {}", input.wallet_id))?;
+                let derivation_path = proto::hd_path::eth_path(0, wallet.next_address_index);
+                wallet.next_address_index += 1;
+                let (address, public_key) =
+                    derive_address(wallet, &derivation_path, Chain::Ethereum)?;
+                self.save()?;
+                encode(&proto::DeriveAddressAutoOutput {
+                    wallet_id: input.wallet_id,
+                    address,
+                    public_key,
+                    derivation_path,
+                })
+            }
+            Command::SignTransaction => {
+                let input: proto::SignTransactionInput = decode(input)?;
+                let wallet = self.wallet(&input.wallet_id)?;
+                check_passkey(wallet, input.passkey_assertion.as_ref())?;
+                let key = derive_key(wallet, &input.hd_path)?;
+                let hash = legacy_signing_hash(&input.transaction);
+                let signature = sign(&key, &hash)?;
+                let mut r = [0u8; 32];
+                let mut s = [0u8; 32];
+                r.copy_from_slice(&signature[..32]);
+                s.copy_from_slice(&signature[32..64]);
+                encode(&proto::SignTransactionOutput {
+                    signature: signed_legacy_rlp(&SignedLegacyTx {
+                        transaction: input.transaction,
+                        r,
+                        s,
+                        recovery_id: signature[64] - 27,
+                    }),
+                })
+            }
+            Command::SignEip1559Transaction => {
+                let input: proto::SignEip1559TransactionInput = decode(input)?;
+                input.transaction.check().map_err(|e| anyhow!(e))?;
+                let wallet = self.wallet(&input.wallet_id)?;
+                check_passkey(wallet, input.passkey_assertion.as_ref())?;
+                let key = derive_key(wallet, &input.hd_path)?;
+                let signature = sign(&key, &input.transaction.signing_hash())?;
+                let mut r = [0u8; 32];
+                let mut s = [0u8; 32];
+                r.copy_from_slice(&signature[..32]);
+                s.copy_from_slice(&signature[32..64]);
+                encode(&proto::SignEip1559TransactionOutput {
+                    raw_transaction: signed_rlp(&SignedEip1559Tx {
+                        transaction: input.transaction,
+                        y_parity: signature[64] - 27,
+                        r,
+                        s,
+                    }),
+                })
+            }
+            Command::SignMessage => {
+                let input: proto::SignMessageInput = decode(input)?;
+                let wallet = self.wallet(&input.wallet_id)?;
+                check_passkey(wallet, input.passkey_assertion.as_ref())?;
+                let digest = crate::key_pin::message_digest(&input.context, &input.message)
+                    .ok_or_else(|| anyhow!("signing context not valid for SignMessage"))?;
+                let signature = sign(&derive_key(wallet, &input.hd_path)?, &digest)?;
+                encode(&proto::SignMessageOutput {
+                    signature: signature.to_vec(),
+                })
+            }
+            Command::SignHash => {
+                let input: proto::SignHashInput = decode(input)?;
+                let wallet = self.wallet(&input.wallet_id)?;
+                check_passkey(wallet, input.passkey_assertion.as_ref())?;
+                let signature = sign(&derive_key(wallet, &input.hd_path)?, &input.hash)?;
+                encode(&proto::SignHashOutput {
+                    signature: signature.to_vec(),
+                })
+            }
+            Command::GetChallenge => {
+                let input: proto::GetChallengeInput = decode(input)?;
+                self.wallet(&input.wallet_id)?;
+                let mut nonce = vec![0u8; 32];
+                rand::thread_rng().fill_bytes(&mut nonce);
+                encode(&proto::GetChallengeOutput { nonce })
+            }
+            Command::WarmupCache => {
+                let input: proto::WarmupCacheInput = decode(input)?;
+                self.wallet(&input.wallet_id)?;
+                encode(&proto::WarmupCacheOutput {
+                    cached: true,
+                    cache_size: self.wallets.len() as u32,
+                })
+            }
+            Command::TaStats => encode(&proto::TaStatsOutput {
+                commands: Vec::new(),
+                storage: None,
+            }),
+            Command::GetCapabilities => encode(&proto::GetCapabilitiesOutput {
+                ta_version: format!("{}-soft", env!("CARGO_PKG_VERSION")),
+                features: vec!["soft-tee".to_string()],
+                policy: None,
+                policy_signer: None,
+                config: None,
+                provisioning_key: None,
+            }),
+            Command::GetProtocolVersion => encode(&proto::GetProtocolVersionOutput {
+                protocol_version: PROTOCOL_VERSION,
+                min_protocol_version: proto::wire::MIN_PROTOCOL_VERSION,
+                max_command_id: proto::buffers::MAX_COMMAND_ID,
+            }),
+            other => Err(anyhow::Error::new(UnsupportedVersion {
+                command: u32::from(other),
+                ca_version: PROTOCOL_VERSION,
+                ta_version: PROTOCOL_VERSION,
+            })),
+        }
+    }
+
+    fn wallet(&self, id: &Uuid) -> Result<&SoftWallet> {
+        self.wallets
+            .get(id)
+            .ok_or_else(|| anyhow!("wallet not found: {}", id))
+    }
+
+    fn create_wallet(
+        &mut self,
+        input: proto::CreateWalletInput,
+    ) -> Result<proto::CreateWalletOutput> {
+        p256::PublicKey::from_sec1_bytes(&input.passkey_pubkey)
+            .map_err(|_| anyhow!("passkey_pubkey is not a P-256 point"))?;
+        let mut seed = vec![0u8; 64];
+        rand::thread_rng().fill_bytes(&mut seed);
+        let wallet_id = Uuid::new_v4();
+        self.wallets.insert(
+            wallet_id,
+            SoftWallet {
+                seed,
+                passkey_pubkey: input.passkey_pubkey,
+                next_address_index: 0,
+            },
+        );
+        self.save()?;
+        Ok(proto::CreateWalletOutput {
+            wallet_id,
+            mnemonic: String::new(),
+        })
+    }
+}
+
+fn decode<T: serde::de::DeserializeOwned>(input: &[u8]) -> Result<T> {
+    bincode::deserialize(input).map_err(|e| anyhow!("bad input: {}", e))
+}
+
+fn encode<T: Serialize>(output: &T) -> Result<Vec<u8>> {
+    Ok(bincode::serialize(output)?)
+}
+
+/// P-256 signature over `authenticatorData ‖ SHA-256(clientDataJSON)`.
+fn check_passkey(wallet: &SoftWallet, assertion: Option<&PasskeyAssertion>) -> Result<()> {
+    let assertion = assertion.ok_or_else(|| anyhow!("passkey assertion required"))?;
+    if let Some(json) = &assertion.client_data_json {
+        if Sha256::digest(json)[..] != assertion.client_data_hash[..] {
+            return Err(anyhow!("clientDataJSON does not match client_data_hash"));
+        }
+    }
+    let key = VerifyingKey::from_sec1_bytes(&wallet.passkey_pubkey)
+        .map_err(|_| anyhow!("stored passkey is not a P-256 key"))?;
+    let signature = Signature::from_scalars(assertion.signature_r, assertion.signature_s)
+        .map_err(|_| anyhow!("passkey verification failed: malformed signature"))?;
+    let mut signed = assertion.authenticator_data.clone();
+    signed.extend_from_slice(&assertion.client_data_hash);
+    key.verify(&signed, &signature)
+        .map_err(|_| anyhow!("passkey verification failed"))
+}
+
+/// BIP32 from the wallet seed, over the TA's `m/44'/60'/0'/account/address`.
+fn derive_key(wallet: &SoftWallet, hd_path: &str) -> Result<SigningKey> {
+    derive_coin_key(wallet, hd_path, EVM_COIN_TYPE)
+}
+
+/// `derive_key` under another coin type, as the TA's `Wallet::derive_chain_address`.
+fn derive_coin_key(wallet: &SoftWallet, hd_path: &str, coin_type: u32) -> Result<SigningKey> {
+    parse_coin_path(hd_path, coin_type).map_err(InvalidPath)?;
+    let path = parse_path(hd_path).map_err(InvalidPath)?;
+    let (mut key, mut chain) = hmac_split(b"Bitcoin seed", &[&wallet.seed])?;
+    for &index in path.levels() {
+        (key, chain) = child_key(&key, &chain, index)?;
+    }
+    SigningKey::from_bytes(&key).map_err(|_| anyhow!("invalid derived key"))
+}
+
+/// CKDpriv: the child private key and chain code at `index`.
+fn child_key(key: &FieldBytes, chain: &[u8], index: u32) -> Result<(FieldBytes, FieldBytes)> {
+    let secret = SecretKey::from_bytes(key).map_err(|_| anyhow!("invalid derived key"))?;
+    let index_bytes = index.to_be_bytes();
+    let (tweak, chain) = if index & HARDENED_BIT != 0 {
+        hmac_split(chain, &[&[0u8], &key[..], &index_bytes])?
+    } else {
+        hmac_split(chain, &[&secret.public_key().to_sec1_bytes(), &index_bytes])?
+    };
+    let tweak = Option::<Scalar>::from(Scalar::from_repr(tweak))
+        .ok_or_else(|| anyhow!("derived tweak out of range"))?;
+    Ok(((tweak + *secret.to_nonzero_scalar()).to_bytes(), chain))
+}
+
+fn hmac_split(key: &[u8], parts: &[&[u8]]) -> Result<(FieldBytes, FieldBytes)> {
+    let mut mac = Hmac::<Sha512>::new_from_slice(key).map_err(|e| anyhow!("{}", e))?;
+    for part in parts {
+        mac.update(part);
+    }
+    let out = mac.finalize().into_bytes();
+    let mut left = FieldBytes::default();
+    let mut right = FieldBytes::default();
+    left.copy_from_slice(&out[..32]);
+    right.copy_from_slice(&out[32..]);
+    Ok((left, right))
+}
+
+fn derive_address(wallet: &SoftWallet, hd_path: &str, chain: Chain) -> Result<([u8; 20], Vec<u8>)> {
+    let key = derive_coin_key(wallet, hd_path, chain.coin_type())?;
+    let point = key.verifying_key().to_encoded_point(false);
+    let mut uncompressed = [0u8; 65];
+    uncompressed.copy_from_slice(point.as_bytes());
+    let compressed = key.verifying_key().to_encoded_point(true);
+    Ok((
+        crate::pkcs11::address(&uncompressed),
+        compressed.as_bytes().to_vec(),
+    ))
+}
+
+/// r ‖ s ‖ v with v = 27/28, as the TA's `sign_recoverable`.
+fn sign(key: &SigningKey, digest: &[u8; 32]) -> Result<[u8; 65]> {
+    let (signature, recovery_id) = key
+        .sign_prehash_recoverable(digest)
+        .map_err(|e| anyhow!("signing failed: {}", e))?;
+    let mut out = [0u8; 65];
+    out[..64].copy_from_slice(&signature.to_bytes());
+    out[64] = 27 + recovery_id.to_byte();
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use p256::ecdsa::signature::Signer;
+
+    fn store() -> (SoftTee, PathBuf) {
+        let path = std::env::temp_dir().join(format!("soft-tee-{}.json", Uuid::new_v4()));
+        (SoftTee::open(&path).unwrap(), path)
+    }
+
+    fn assertion(passkey: &p256::ecdsa::SigningKey) -> PasskeyAssertion {
+        let json = br#"{"type":"webauthn.get","challenge":"x"}"#.to_vec();
+        let client_data_hash: [u8; 32] = Sha256::digest(&json).into();
+        let authenticator_data = vec![0u8; 37];
+        let mut signed = authenticator_data.clone();
+        signed.extend_from_slice(&client_data_hash);
+        let signature: Signature = passkey.sign(&signed);
+        let (r, s) = signature.split_bytes();
+        PasskeyAssertion {
+            authenticator_data,
+            client_data_hash,
+            signature_r: r.into(),
+            signature_s: s.into(),
+            client_data_json: Some(json),
+        }
+    }
+
+    #[test]
+    fn bip32_matches_the_reference_vector() {
+        // BIP32 test vector 1, m/0'/1: one hardened and one normal step.
+        let seed = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+        let (mut key, mut chain) = hmac_split(b"Bitcoin seed", &[&seed]).unwrap();
+        for index in [HARDENED_BIT, 1] {
+            (key, chain) = child_key(&key, &chain, index).unwrap();
+        }
+        assert_eq!(
+            hex::encode(key),
+            "3c6cb8d0f6a264c91ea8b5030fadaa8e538b020f0a387421a12de9319dc93368"
+        );
+        assert_eq!(
+            hex::encode(chain),
+            "2a7857631386ba23dacac34180dd1983734e444fdbf774041578e9b6adb37c19"
+        );
+    }
+
+    #[test]
+    fn tron_derives_under_its_own_coin_type() {
+        // "abandon ×11 about", as TronLink shows its first account.
+        let wallet = SoftWallet {
+            seed: hex::decode(
+                "5eb00bbddcf069084889a8ab9155568165f5c453ccb85e70811aaed6f6da5fc1\
+                 9a5ac40b389cd370d086206dec8aa6c43daea6690f20ad3d8d48b2d2ce9e38e4",
+            )
+            .unwrap(),
+            passkey_pubkey: Vec::new(),
+            next_address_index: 0,
+        };
+        let (address, _) = derive_address(&wallet, "m/44'/195'/0'/0/0", Chain::Tron).unwrap();
+        assert_eq!(
+            Chain::Tron.encode_address(&address),
+            "TUEZSdKsoDHQMeZwihtdoBiN46zxhGWYdH"
+        );
+        assert!(derive_address(&wallet, "m/44'/60'/0'/0/0", Chain::Tron).is_err());
+    }
+
+    #[test]
+    fn signs_what_the_ca_checks_and_persists() {
+        let (mut tee, path) = store();
+        let passkey = p256::ecdsa::SigningKey::random(&mut rand::thread_rng());
+        let pubkey = passkey
+            .verifying_key()
+            .to_encoded_point(false)
+            .as_bytes()
+            .to_vec();
+        let out = tee
+            .invoke(
+                Command::CreateWallet,
+                &bincode::serialize(&proto::CreateWalletInput {
+                    passkey_pubkey: pubkey,
+                    entropy_seed: None,
+                    prf_output: None,
+                })
+                .unwrap(),
+            )
+            .unwrap();
+        let wallet_id = bincode::deserialize::<proto::CreateWalletOutput>(&out)
+            .unwrap()
+            .wallet_id;
+
+        let auto: proto::DeriveAddressAutoOutput = bincode::deserialize(
+            &tee.invoke(
+                Command::DeriveAddressAuto,
+                &bincode::serialize(&proto::DeriveAddressAutoInput { wallet_id }).unwrap(),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(auto.derivation_path, "m/44'/60'/0'/0/0");
+
+        let hash = [7u8; 32];
+        let sign_hash = |tee: &mut SoftTee, passkey_assertion| {
+            tee.invoke(
+                Command::SignHash,
+                &bincode::serialize(&proto::SignHashInput {
+                    wallet_id,
+                    hd_path: auto.derivation_path.clone(),
+                    hash,
+                    passkey_assertion,
+                    context: proto::SigningContext::Raw,
+                    fee_quote: None,
+                })
+                .unwrap(),
+            )
+        };
+        let out: proto::SignHashOutput =
+            bincode::deserialize(&sign_hash(&mut tee, Some(assertion(&passkey))).unwrap()).unwrap();
+        assert_eq!(
+            crate::key_pin::recover_signer(&hash, &out.signature).unwrap(),
+            auto.address
+        );
+        assert!(sign_hash(&mut tee, None).is_err());
+        let stranger = p256::ecdsa::SigningKey::random(&mut rand::thread_rng());
+        assert!(sign_hash(&mut tee, Some(assertion(&stranger))).is_err());
+
+        // Reopened from disk: same key, next index.
+        let mut reopened = SoftTee::open(&path).unwrap();
+        let again: proto::DeriveAddressAutoOutput = bincode::deserialize(
+            &reopened
+                .invoke(
+                    Command::DeriveAddressAuto,
+                    &bincode::serialize(&proto::DeriveAddressAutoInput { wallet_id }).unwrap(),
+                )
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(again.derivation_path, "m/44'/60'/0'/0/1");
+        assert_ne!(again.address, auto.address);
+
+        let err = reopened.invoke(Command::BlsSign, &[]).unwrap_err();
+        assert!(UnsupportedVersion::find(&format!("{:#}", err)).is_some());
+        std::fs::remove_file(path).unwrap();
+    }
+}
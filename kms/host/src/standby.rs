@@ -0,0 +1,1169 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Active-standby CA pairs.
+//!
+//! Two CAs, each with its own TA holding the same wallets (seed replication,
+//! replication.rs), share one database: the standby pulls from the active
+//! every `KMS_STANDBY_INTERVAL_SECS` over `/standby/sync`. The log behind
+//! wallets, addresses and the tx ledger arrives as `ca_events` and is
+//! checked link by link (event_store.rs); every other table that is not
+//! about this node is copied whole when its digest differs, or by tail when
+//! it is append-only. Pending jobs — refill proposals, fee quotes, offline
+//! requests, contact bindings — are rows in those tables and travel with
+//! them.
+//!
+//! The channel is a pre-shared secret (`KMS_STANDBY_SECRET`): every request
+//! and reply is AES-256-GCM sealed under HKDF-derived keys, a reply bound to
+//! its request's nonce, and a request older than [`MAX_SKEW_SECS`] refused.
+//!
+//! Only the active takes writes. Each promotion raises the fencing token;
+//! the standby drops batches from an active whose token is lower than one it
+//! has seen, and an active that learns of a higher token — from a pull, a
+//! fence request or its own watchdog — fences itself and refuses writes
+//! until an operator re-seeds it as the new standby.
+
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use anyhow::{anyhow, bail, Context, Result};
+use hkdf::Hkdf;
+use rand::RngCore;
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{params, Connection, OptionalExtension, TransactionBehavior};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use crate::db::KmsDb;
+use crate::event_store::{self, EventRecord};
+use crate::replication::local_device_id;
+use crate::webauthn::{b64url_decode, b64url_encode};
+
+/// A sealed request this far from the receiver's clock is refused.
+pub const MAX_SKEW_SECS: i64 = 60;
+const DEFAULT_INTERVAL_SECS: u64 = 5;
+const CALL_TIMEOUT_SECS: u64 = 30;
+/// Events, and tail rows per append-only table, in one batch.
+pub const BATCH_LIMIT: usize = 500;
+
+pub const SYNC_PATH: &str = "/standby/sync";
+pub const STATUS_PATH: &str = "/standby/status";
+pub const FENCE_PATH: &str = "/standby/fence";
+
+/// Copied whole whenever the standby's digest differs from the active's.
+pub const SNAPSHOT_TABLES: &[&str] = &[
+    "api_keys",
+    "agent_keys",
+    "p256_session_keys",
+    "scoped_session_keys",
+    "contact_bindings",
+    "offline_requests",
+    "chain_events",
+    "notification_prefs",
+    "remote_approvers",
+    "stealth_meta",
+    "stealth_announcements",
+    "tenants",
+    "tenant_wallets",
+    "provisioning_batches",
+    "fee_payments",
+    "refresh_families",
+    "deletion_certificates",
+    "wallet_signers",
+    "wallet_devices",
+    "key_regions",
+    "identities",
+    "identity_links",
+    "gas_tanks",
+    "gas_tank_topups",
+];
+
+/// Append-only with an AUTOINCREMENT `id`: only rows past the standby's
+/// last id are sent.
+pub const APPEND_TABLES: &[&str] = &["tx_log", "account_audit"];
+
+/// About this node, its TA or its own requests in flight; never copied.
+pub const LOCAL_TABLES: &[&str] = &[
+    "challenges",
+    "jwt_secret_meta",
+    "device_health_checks",
+    "device_quarantine",
+    "device_wipes",
+    "standby_state",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Active,
+    Standby,
+    /// A former active that saw a higher fencing token.
+    Fenced,
+}
+
+impl Role {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Role::Active => "active",
+            Role::Standby => "standby",
+            Role::Fenced => "fenced",
+        }
+    }
+
+    pub fn parse(s: &str) -> Result<Self> {
+        match s {
+            "active" => Ok(Role::Active),
+            "standby" => Ok(Role::Standby),
+            "fenced" => Ok(Role::Fenced),
+            other => bail!("unknown standby role '{}'", other),
+        }
+    }
+}
+
+/// This node's row of `standby_state` (db.rs).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StandbyState {
+    pub role: Role,
+    pub fencing_token: u64,
+    /// Last batch applied (standby).
+    pub synced_at: Option<i64>,
+    pub promoted_at: Option<i64>,
+    pub updated_at: i64,
+}
+
+static ACCEPTS_WRITES: AtomicBool = AtomicBool::new(true);
+
+/// Whether this node may change the database: always, unless it is a
+/// standby or fenced member of a pair. Checked by the API's write guard
+/// and by background jobs that write (chain watcher, gas tanks, sweeps).
+pub fn accepts_writes() -> bool {
+    ACCEPTS_WRITES.load(Ordering::Relaxed)
+}
+
+fn publish(role: Role) {
+    ACCEPTS_WRITES.store(role == Role::Active, Ordering::Relaxed);
+}
+
+/// Paths a standby still answers with a write method. The conformance suite
+/// posts but never writes.
+pub fn exempt_from_write_guard(path: &str) -> bool {
+    path.starts_with("/standby/")
+        || path == "/admin/standby"
+        || path.starts_with("/admin/standby/")
+        || path.starts_with("/api/conformance/")
+}
+
+#[derive(Clone)]
+pub struct StandbyConfig {
+    /// Role this node starts in when it has no `standby_state` yet.
+    pub role: Role,
+    /// The other node's kms-api base URL.
+    pub peer: String,
+    pub interval_secs: u64,
+    request_key: [u8; 32],
+    reply_key: [u8; 32],
+}
+
+impl StandbyConfig {
+    /// `KMS_STANDBY_ROLE` (active | standby; required to enable),
+    /// `KMS_STANDBY_PEER`, `KMS_STANDBY_SECRET` (hex, ≥ 32 bytes, the same
+    /// on both nodes) and `KMS_STANDBY_INTERVAL_SECS`.
+    pub fn from_env() -> Option<Result<Self>> {
+        let role = std::env::var("KMS_STANDBY_ROLE")
+            .ok()
+            .filter(|v| !v.is_empty())?;
+        let var = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+        Some(Self::new(
+            &role,
+            var("KMS_STANDBY_PEER"),
+            var("KMS_STANDBY_SECRET"),
+            var("KMS_STANDBY_INTERVAL_SECS"),
+        ))
+    }
+
+    pub fn new(
+        role: &str,
+        peer: Option<String>,
+        secret: Option<String>,
+        interval: Option<String>,
+    ) -> Result<Self> {
+        let role = match Role::parse(role)? {
+            Role::Fenced => bail!("KMS_STANDBY_ROLE must be active or standby"),
+            role => role,
+        };
+        let peer = peer.ok_or_else(|| anyhow!("KMS_STANDBY_ROLE needs KMS_STANDBY_PEER"))?;
+        if !peer.starts_with("http://") {
+            bail!("KMS_STANDBY_PEER must be http:// (no TLS in the CA; the channel is sealed)");
+        }
+        let secret = secret.ok_or_else(|| anyhow!("KMS_STANDBY_ROLE needs KMS_STANDBY_SECRET"))?;
+        let secret = hex::decode(secret.trim_start_matches("0x"))
+            .map_err(|_| anyhow!("KMS_STANDBY_SECRET must be hex"))?;
+        if secret.len() < 32 {
+            bail!("KMS_STANDBY_SECRET must be at least 32 bytes");
+        }
+        let interval_secs =
+            match interval {
+                Some(v) => v.parse().ok().filter(|s| *s > 0).ok_or_else(|| {
+                    anyhow!("KMS_STANDBY_INTERVAL_SECS must be a positive integer")
+                })?,
+                None => DEFAULT_INTERVAL_SECS,
+            };
+        let hk = Hkdf::<Sha256>::new(Some(b"airaccount-standby-v1"), &secret);
+        let mut request_key = [0u8; 32];
+        let mut reply_key = [0u8; 32];
+        hk.expand(b"request", &mut request_key)
+            .and_then(|_| hk.expand(b"reply", &mut reply_key))
+            .map_err(|_| anyhow!("standby key derivation failed"))?;
+        Ok(Self {
+            role,
+            peer: peer.trim_end_matches('/').to_string(),
+            interval_secs,
+            request_key,
+            reply_key,
+        })
+    }
+}
+
+// ── Channel ──
+
+/// An AES-256-GCM ciphertext on the wire.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Sealed {
+    /// 12 bytes, hex.
+    pub nonce: String,
+    /// base64url.
+    pub ciphertext: String,
+}
+
+/// What a request carries around its message.
+#[derive(Serialize, Deserialize)]
+struct Envelope<T> {
+    node_id: String,
+    at: i64,
+    body: T,
+}
+
+fn seal(key: &[u8; 32], aad: &[u8], plaintext: &[u8]) -> Result<Sealed> {
+    let mut nonce = [0u8; 12];
+    rand::thread_rng().fill_bytes(&mut nonce);
+    let ciphertext = Aes256Gcm::new(key.into())
+        .encrypt(
+            &Nonce::from(nonce),
+            Payload {
+                msg: plaintext,
+                aad,
+            },
+        )
+        .map_err(|_| anyhow!("seal failed"))?;
+    Ok(Sealed {
+        nonce: hex::encode(nonce),
+        ciphertext: b64url_encode(&ciphertext),
+    })
+}
+
+fn open(key: &[u8; 32], aad: &[u8], sealed: &Sealed) -> Result<Vec<u8>> {
+    let nonce: [u8; 12] = hex::decode(&sealed.nonce)
+        .ok()
+        .and_then(|n| n.try_into().ok())
+        .ok_or_else(|| anyhow!("sealed message has a bad nonce"))?;
+    let ciphertext = b64url_decode(&sealed.ciphertext)?;
+    Aes256Gcm::new(key.into())
+        .decrypt(
+            &Nonce::from(nonce),
+            Payload {
+                msg: &ciphertext,
+                aad,
+            },
+        )
+        .map_err(|_| anyhow!("sealed message does not open: wrong KMS_STANDBY_SECRET or tampered"))
+}
+
+impl StandbyConfig {
+    /// Seal a request for `path` on the peer.
+    pub fn seal_request<T: Serialize>(&self, path: &str, body: &T, now: i64) -> Result<Sealed> {
+        let envelope = Envelope {
+            node_id: local_device_id(),
+            at: now,
+            body,
+        };
+        seal(
+            &self.request_key,
+            path.as_bytes(),
+            &serde_json::to_vec(&envelope)?,
+        )
+    }
+
+    /// Open a request sent to `path`. Returns the sender's node id and the
+    /// message.
+    pub fn open_request<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        sealed: &Sealed,
+        now: i64,
+    ) -> Result<(String, T)> {
+        let plain = open(&self.request_key, path.as_bytes(), sealed)?;
+        let envelope: Envelope<T> =
+            serde_json::from_slice(&plain).context("sealed request is not a standby message")?;
+        if (now - envelope.at).abs() > MAX_SKEW_SECS {
+            bail!(
+                "sealed request from {} is {}s off this node's clock",
+                envelope.node_id,
+                now - envelope.at
+            );
+        }
+        Ok((envelope.node_id, envelope.body))
+    }
+
+    /// Seal the reply to `request`; it only opens against that request.
+    pub fn seal_reply<T: Serialize>(&self, request: &Sealed, body: &T) -> Result<Sealed> {
+        seal(
+            &self.reply_key,
+            request.nonce.as_bytes(),
+            &serde_json::to_vec(body)?,
+        )
+    }
+
+    pub fn open_reply<T: DeserializeOwned>(&self, request: &Sealed, sealed: &Sealed) -> Result<T> {
+        let plain = open(&self.reply_key, request.nonce.as_bytes(), sealed)?;
+        serde_json::from_slice(&plain).context("sealed reply is not a standby message")
+    }
+
+    /// POST a sealed request to the peer and open its reply.
+    pub async fn call<Req: Serialize, Resp: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &Req,
+    ) -> Result<Resp> {
+        use warp::hyper::{body, Body, Client, Request};
+        let sealed = self.seal_request(path, body, chrono::Utc::now().timestamp())?;
+        let req = Request::post(format!("{}{}", self.peer, path))
+            .header("content-type", "application/json")
+            .body(Body::from(serde_json::to_string(&sealed)?))?;
+        let resp = tokio::time::timeout(
+            Duration::from_secs(CALL_TIMEOUT_SECS),
+            Client::new().request(req),
+        )
+        .await
+        .map_err(|_| anyhow!("{} did not answer within {}s", path, CALL_TIMEOUT_SECS))?
+        .with_context(|| format!("standby peer {} unreachable", self.peer))?;
+        let status = resp.status();
+        let bytes = body::to_bytes(resp.into_body()).await?;
+        if !status.is_success() {
+            let detail = serde_json::from_slice::<serde_json::Value>(&bytes)
+                .ok()
+                .and_then(|v| v["detail"].as_str().map(str::to_string))
+                .unwrap_or_default();
+            bail!("peer refused {}: HTTP {} {}", path, status, detail);
+        }
+        let reply: Sealed = serde_json::from_slice(&bytes).context("peer reply is not sealed")?;
+        self.open_reply(&sealed, &reply)
+    }
+}
+
+// ── Messages ──
+
+/// Standby → active: what the standby already has.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PullRequest {
+    pub schema_version: u32,
+    pub fencing_token: u64,
+    pub after_seq: u64,
+    /// Hash of event `after_seq` on the standby.
+    pub head_hash: String,
+    /// SHA-256 of each snapshot table.
+    pub digests: BTreeMap<String, String>,
+    /// Highest `id` of each append-only table.
+    pub tails: BTreeMap<String, i64>,
+}
+
+/// One SQLite value.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Cell {
+    Null,
+    Integer(i64),
+    Real(f64),
+    Text(String),
+    /// hex
+    Blob(String),
+}
+
+impl From<SqlValue> for Cell {
+    fn from(v: SqlValue) -> Self {
+        match v {
+            SqlValue::Null => Cell::Null,
+            SqlValue::Integer(i) => Cell::Integer(i),
+            SqlValue::Real(f) => Cell::Real(f),
+            SqlValue::Text(s) => Cell::Text(s),
+            SqlValue::Blob(b) => Cell::Blob(hex::encode(b)),
+        }
+    }
+}
+
+impl Cell {
+    fn into_sql(self) -> Result<SqlValue> {
+        Ok(match self {
+            Cell::Null => SqlValue::Null,
+            Cell::Integer(i) => SqlValue::Integer(i),
+            Cell::Real(f) => SqlValue::Real(f),
+            Cell::Text(s) => SqlValue::Text(s),
+            Cell::Blob(h) => SqlValue::Blob(hex::decode(h).context("blob cell")?),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TableRows {
+    pub table: String,
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Cell>>,
+    /// Rows to add after the standby's tail, instead of the whole table.
+    pub tail: bool,
+}
+
+/// Active → standby.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncBatch {
+    pub fencing_token: u64,
+    /// The active's last event; more are coming if `events` ends before it.
+    pub head_seq: u64,
+    pub events: Vec<EventRecord>,
+    pub tables: Vec<TableRows>,
+}
+
+/// Either node's answer to `/standby/status` and `/standby/fence`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeerStatus {
+    pub node_id: String,
+    pub role: Role,
+    pub fencing_token: u64,
+    pub head_seq: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FenceRequest {
+    /// The token the sender is about to take as the new active.
+    pub fencing_token: u64,
+}
+
+// ── Tables ──
+
+/// Columns of `table`, sorted, so both nodes agree on the order whatever
+/// order their migrations added them in.
+fn columns(conn: &Connection, table: &str) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let mut names = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    if names.is_empty() {
+        bail!("no table {}", table);
+    }
+    names.sort();
+    Ok(names)
+}
+
+/// Rows of `source` (a table or a subquery), in a canonical order.
+fn select(
+    conn: &Connection,
+    source: &str,
+    columns: &[String],
+    args: &[&dyn rusqlite::ToSql],
+) -> Result<Vec<Vec<Cell>>> {
+    let list = columns.join(", ");
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM {} ORDER BY {}",
+        list, source, list
+    ))?;
+    let width = columns.len();
+    let mut rows = stmt.query(args)?;
+    let mut out = Vec::new();
+    while let Some(row) = rows.next()? {
+        out.push(
+            (0..width)
+                .map(|i| row.get::<_, SqlValue>(i).map(Cell::from))
+                .collect::<rusqlite::Result<Vec<_>>>()?,
+        );
+    }
+    Ok(out)
+}
+
+fn table_digest(conn: &Connection, table: &str) -> Result<String> {
+    let columns = columns(conn, table)?;
+    let mut h = Sha256::new();
+    h.update(serde_json::to_vec(&columns)?);
+    for row in select(conn, table, &columns, &[])? {
+        h.update(serde_json::to_vec(&row)?);
+    }
+    Ok(hex::encode(h.finalize()))
+}
+
+fn tail_id(conn: &Connection, table: &str) -> Result<i64> {
+    Ok(conn.query_row(
+        &format!("SELECT COALESCE(MAX(id), 0) FROM {}", table),
+        [],
+        |r| r.get(0),
+    )?)
+}
+
+/// The standby's side of a pull: what it has.
+pub fn pull_request(conn: &Connection) -> Result<PullRequest> {
+    let (after_seq, head_hash) = event_store::head(conn)?;
+    let fencing_token = state(conn)?.map(|s| s.fencing_token).unwrap_or(0);
+    let mut digests = BTreeMap::new();
+    for table in SNAPSHOT_TABLES {
+        digests.insert(table.to_string(), table_digest(conn, table)?);
+    }
+    let mut tails = BTreeMap::new();
+    for table in APPEND_TABLES {
+        tails.insert(table.to_string(), tail_id(conn, table)?);
+    }
+    Ok(PullRequest {
+        schema_version: schema_version(conn)?,
+        fencing_token,
+        after_seq,
+        head_hash,
+        digests,
+        tails,
+    })
+}
+
+/// The active's side: events and rows the standby is missing. Refuses a
+/// standby whose log is not a prefix of this one.
+pub fn sync_batch(conn: &Connection, req: &PullRequest, fencing_token: u64) -> Result<SyncBatch> {
+    let (head_seq, _) = event_store::head(conn)?;
+    if req.after_seq > 0 {
+        let hash: Option<String> = conn
+            .query_row(
+                "SELECT hash FROM ca_events WHERE seq=?1",
+                params![req.after_seq as i64],
+                |r| r.get(0),
+            )
+            .optional()?;
+        if hash.as_deref() != Some(req.head_hash.as_str()) {
+            bail!(
+                "standby log diverged from the active at seq {} — re-seed the standby",
+                req.after_seq
+            );
+        }
+    }
+    let events = event_store::events_after(conn, req.after_seq, BATCH_LIMIT)?;
+    let mut tables = Vec::new();
+    for table in SNAPSHOT_TABLES {
+        if req.digests.get(*table) == Some(&table_digest(conn, table)?) {
+            continue;
+        }
+        let columns = columns(conn, table)?;
+        tables.push(TableRows {
+            table: table.to_string(),
+            rows: select(conn, table, &columns, &[])?,
+            columns,
+            tail: false,
+        });
+    }
+    for table in APPEND_TABLES {
+        let after = req.tails.get(*table).copied().unwrap_or(0);
+        let columns = columns(conn, table)?;
+        let rows = select(
+            conn,
+            &format!(
+                "(SELECT * FROM {} WHERE id > ?1 ORDER BY id LIMIT {})",
+                table, BATCH_LIMIT
+            ),
+            &columns,
+            &[&after],
+        )?;
+        if !rows.is_empty() {
+            tables.push(TableRows {
+                table: table.to_string(),
+                columns,
+                rows,
+                tail: true,
+            });
+        }
+    }
+    Ok(SyncBatch {
+        fencing_token,
+        head_seq,
+        events,
+        tables,
+    })
+}
+
+// ── State ──
+
+pub fn state(conn: &Connection) -> Result<Option<StandbyState>> {
+    conn.query_row(
+        "SELECT role, fencing_token, synced_at, promoted_at, updated_at \
+         FROM standby_state WHERE id=1",
+        [],
+        |r| {
+            Ok((
+                r.get::<_, String>(0)?,
+                r.get::<_, i64>(1)?,
+                r.get(2)?,
+                r.get(3)?,
+                r.get(4)?,
+            ))
+        },
+    )
+    .optional()?
+    .map(|(role, token, synced_at, promoted_at, updated_at)| {
+        Ok(StandbyState {
+            role: Role::parse(&role)?,
+            fencing_token: token as u64,
+            synced_at,
+            promoted_at,
+            updated_at,
+        })
+    })
+    .transpose()
+}
+
+fn write_state(conn: &Connection, s: &StandbyState) -> Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO standby_state \
+         (id, role, fencing_token, synced_at, promoted_at, updated_at) \
+         VALUES (1,?1,?2,?3,?4,?5)",
+        params![
+            s.role.as_str(),
+            s.fencing_token as i64,
+            s.synced_at,
+            s.promoted_at,
+            s.updated_at
+        ],
+    )?;
+    publish(s.role);
+    Ok(())
+}
+
+/// Settle this node's role at startup. A node without state takes the
+/// configured role (an active with token 1). Afterwards the stored role
+/// wins — a restart with `KMS_STANDBY_ROLE=active` does not undo a fence
+/// or a failover — except that a fenced node configured as standby rejoins
+/// as one.
+pub fn init(conn: &Connection, configured: Role, now: i64) -> Result<StandbyState> {
+    let state = match state(conn)? {
+        None => StandbyState {
+            role: configured,
+            fencing_token: u64::from(configured == Role::Active),
+            synced_at: None,
+            promoted_at: None,
+            updated_at: now,
+        },
+        Some(s) if s.role == Role::Fenced && configured == Role::Standby => StandbyState {
+            role: Role::Standby,
+            updated_at: now,
+            ..s
+        },
+        Some(s) => s,
+    };
+    write_state(conn, &state)?;
+    Ok(state)
+}
+
+/// Another node holds `token`. A higher one is remembered, and an active
+/// that sees it steps down.
+pub fn observe_token(conn: &Connection, token: u64, now: i64) -> Result<StandbyState> {
+    let mut s = state(conn)?.ok_or_else(|| anyhow!("standby state not initialised"))?;
+    if token > s.fencing_token {
+        s.fencing_token = token;
+        if s.role == Role::Active {
+            s.role = Role::Fenced;
+        }
+        s.updated_at = now;
+        write_state(conn, &s)?;
+    }
+    Ok(s)
+}
+
+/// Make this standby the active under `token`.
+pub fn promote(conn: &mut Connection, token: u64, now: i64) -> Result<StandbyState> {
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let mut s = state(&tx)?.ok_or_else(|| anyhow!("standby state not initialised"))?;
+    if s.role != Role::Standby {
+        bail!(
+            "this node is {}; only a standby can be promoted",
+            s.role.as_str()
+        );
+    }
+    if token <= s.fencing_token {
+        bail!("fencing token {} is not above {}", token, s.fencing_token);
+    }
+    s.role = Role::Active;
+    s.fencing_token = token;
+    s.promoted_at = Some(now);
+    s.updated_at = now;
+    write_state(&tx, &s)?;
+    tx.commit()?;
+    Ok(s)
+}
+
+fn schema_version(conn: &Connection) -> Result<u32> {
+    Ok(conn.query_row("PRAGMA user_version", [], |r| r.get(0))?)
+}
+
+/// `/standby/sync` on the active: answer a pull, after checking that the
+/// standby runs the same schema and has not seen a newer active.
+pub fn serve_sync(conn: &Connection, req: &PullRequest, now: i64) -> Result<SyncBatch> {
+    let version = schema_version(conn)?;
+    if req.schema_version != version {
+        bail!(
+            "standby schema version {} differs from the active's {} — upgrade both",
+            req.schema_version,
+            version
+        );
+    }
+    let s = observe_token(conn, req.fencing_token, now)?;
+    if s.role != Role::Active {
+        bail!(
+            "this node is {} (fencing token {}), not the active",
+            s.role.as_str(),
+            s.fencing_token
+        );
+    }
+    sync_batch(conn, req, s.fencing_token)
+}
+
+/// Apply a batch from the active in one transaction. Foreign keys are off
+/// meanwhile: a table copied whole may name a wallet whose event is still
+/// to come. Returns the events applied.
+pub fn apply_batch(conn: &mut Connection, batch: &SyncBatch, now: i64) -> Result<usize> {
+    conn.execute_batch("PRAGMA foreign_keys=OFF")?;
+    let result = apply_rows(conn, batch, now);
+    conn.execute_batch("PRAGMA foreign_keys=ON")?;
+    result
+}
+
+fn apply_rows(conn: &mut Connection, batch: &SyncBatch, now: i64) -> Result<usize> {
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let mut s = state(&tx)?.ok_or_else(|| anyhow!("standby state not initialised"))?;
+    if s.role != Role::Standby {
+        bail!("this node is {}, not a standby", s.role.as_str());
+    }
+    if batch.fencing_token < s.fencing_token {
+        bail!(
+            "batch from a stale active: fencing token {} is below {}",
+            batch.fencing_token,
+            s.fencing_token
+        );
+    }
+    for e in &batch.events {
+        event_store::ingest(&tx, e)?;
+    }
+    for t in &batch.tables {
+        let known = if t.tail {
+            APPEND_TABLES.contains(&t.table.as_str())
+        } else {
+            SNAPSHOT_TABLES.contains(&t.table.as_str())
+        };
+        if !known {
+            bail!(
+                "batch carries table {} this node does not replicate",
+                t.table
+            );
+        }
+        if columns(&tx, &t.table)? != t.columns {
+            bail!("columns of {} differ between active and standby", t.table);
+        }
+        if !t.tail {
+            tx.execute(&format!("DELETE FROM {}", t.table), [])?;
+        }
+        let mut stmt = tx.prepare(&format!(
+            "INSERT INTO {} ({}) VALUES ({})",
+            t.table,
+            t.columns.join(", "),
+            vec!["?"; t.columns.len()].join(",")
+        ))?;
+        for row in &t.rows {
+            if row.len() != t.columns.len() {
+                bail!("a row of {} has {} cells", t.table, row.len());
+            }
+            let values = row
+                .iter()
+                .cloned()
+                .map(Cell::into_sql)
+                .collect::<Result<Vec<_>>>()?;
+            stmt.execute(rusqlite::params_from_iter(values))?;
+        }
+    }
+    s.fencing_token = batch.fencing_token;
+    s.synced_at = Some(now);
+    s.updated_at = now;
+    write_state(&tx, &s)?;
+    tx.commit()?;
+    Ok(batch.events.len())
+}
+
+// ── Serving and pulling ──
+
+fn status(db: &KmsDb) -> Result<PeerStatus> {
+    let s = db
+        .standby_state()?
+        .ok_or_else(|| anyhow!("standby state not initialised"))?;
+    Ok(PeerStatus {
+        node_id: local_device_id(),
+        role: s.role,
+        fencing_token: s.fencing_token,
+        head_seq: db.event_head()?.0,
+    })
+}
+
+/// Answer a sealed request to `/standby/{path}` from the peer.
+pub async fn serve(
+    config: &StandbyConfig,
+    db: &KmsDb,
+    path: &str,
+    sealed: Sealed,
+) -> Result<Sealed> {
+    let now = chrono::Utc::now().timestamp();
+    let full = format!("/standby/{}", path);
+    match full.as_str() {
+        SYNC_PATH => {
+            let (node, req): (String, PullRequest) =
+                config.open_request(SYNC_PATH, &sealed, now)?;
+            let batch = db
+                .run(move |db| db.serve_standby_sync(&req, now))
+                .await
+                .with_context(|| format!("pull from {}", node))?;
+            config.seal_reply(&sealed, &batch)
+        }
+        STATUS_PATH => {
+            let _: (String, ()) = config.open_request(STATUS_PATH, &sealed, now)?;
+            let status = db.run(status).await?;
+            config.seal_reply(&sealed, &status)
+        }
+        FENCE_PATH => {
+            let (node, req): (String, FenceRequest) =
+                config.open_request(FENCE_PATH, &sealed, now)?;
+            let token = req.fencing_token;
+            let (before, after) = db
+                .run(move |db| {
+                    let before = db.standby_state()?;
+                    db.observe_fencing_token(token, now)?;
+                    Ok((before, status(db)?))
+                })
+                .await?;
+            if before.map(|s| s.role) == Some(Role::Active) && after.role == Role::Fenced {
+                eprintln!(
+                    "🚧 Standby: fenced by {} (token {}) — this node refuses writes",
+                    node, token
+                );
+            }
+            config.seal_reply(&sealed, &after)
+        }
+        _ => bail!("no standby endpoint {}", full),
+    }
+}
+
+/// Pull until caught up with the active. Returns the events applied.
+async fn pull(config: &StandbyConfig, db: &KmsDb) -> Result<usize> {
+    let mut applied = 0;
+    loop {
+        let req = db.run(|db| db.standby_pull_request()).await?;
+        let batch: SyncBatch = config.call(SYNC_PATH, &req).await?;
+        let more = batch.events.len() == BATCH_LIMIT
+            || batch
+                .tables
+                .iter()
+                .any(|t| t.tail && t.rows.len() == BATCH_LIMIT);
+        let now = chrono::Utc::now().timestamp();
+        applied += db
+            .run(move |db| db.apply_standby_batch(&batch, now))
+            .await?;
+        if !more {
+            return Ok(applied);
+        }
+    }
+}
+
+/// The active's watchdog: a peer that is active under a higher token was
+/// promoted while this node was cut off, so this node steps down.
+async fn watch_peer(config: &StandbyConfig, db: &KmsDb) -> Result<()> {
+    let peer: PeerStatus = config.call(STATUS_PATH, &()).await?;
+    if peer.role != Role::Active {
+        return Ok(());
+    }
+    let now = chrono::Utc::now().timestamp();
+    let token = peer.fencing_token;
+    let s = db
+        .run(move |db| db.observe_fencing_token(token, now))
+        .await?;
+    if s.role == Role::Fenced {
+        eprintln!(
+            "🚧 Standby: peer {} is active with token {} — this node fenced itself",
+            peer.node_id, peer.fencing_token
+        );
+    } else {
+        bail!(
+            "peer {} is also active with token {} (ours {}): split brain, fence one by hand",
+            peer.node_id,
+            peer.fencing_token,
+            s.fencing_token
+        );
+    }
+    Ok(())
+}
+
+/// Pull (standby) or watch the peer (active) every interval. A failure is
+/// logged when it first appears, not on every tick.
+pub async fn run(config: StandbyConfig, db: KmsDb) {
+    let mut tick = tokio::time::interval(Duration::from_secs(config.interval_secs));
+    let mut last_error = None;
+    loop {
+        tick.tick().await;
+        let role = match db.run(|db| db.standby_state()).await {
+            Ok(Some(s)) => s.role,
+            Ok(None) => continue,
+            Err(e) => {
+                eprintln!("⚠️  Standby: {:#}", e);
+                continue;
+            }
+        };
+        let outcome = match role {
+            Role::Standby => pull(&config, &db).await.map(|n| {
+                if n > 0 {
+                    println!("🔁 Standby: applied {} event(s) from {}", n, config.peer);
+                }
+            }),
+            Role::Active => watch_peer(&config, &db).await,
+            Role::Fenced => Ok(()),
+        };
+        match outcome {
+            Ok(()) => {
+                if last_error.take().is_some() {
+                    println!("🔁 Standby: {} reachable again", config.peer);
+                }
+            }
+            Err(e) => {
+                let message = format!("{:#}", e);
+                if last_error.as_ref() != Some(&message) {
+                    eprintln!("⚠️  Standby ({}): {}", role.as_str(), message);
+                    last_error = Some(message);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::WalletRow;
+
+    fn config(secret: &str) -> StandbyConfig {
+        StandbyConfig::new(
+            "standby",
+            Some("http://10.0.0.1:3000/".into()),
+            Some(secret.into()),
+            None,
+        )
+        .unwrap()
+    }
+
+    fn wallet(key_id: &str) -> WalletRow {
+        WalletRow {
+            key_id: key_id.to_string(),
+            address: None,
+            public_key: None,
+            derivation_path: None,
+            description: "standby".to_string(),
+            key_usage: "SIGN_VERIFY".to_string(),
+            key_spec: "ECC_SECG_P256K1".to_string(),
+            origin: "EXTERNAL_KMS".to_string(),
+            passkey_pubkey: Some("0x04abcd".to_string()),
+            credential_id: None,
+            sign_count: 0,
+            status: "creating".to_string(),
+            error_msg: None,
+            created_at: "2026-10-16T00:00:00Z".to_string(),
+        }
+    }
+
+    fn pair() -> (KmsDb, KmsDb) {
+        let active = KmsDb::open_memory().unwrap();
+        let standby = KmsDb::open_memory().unwrap();
+        active.init_standby(Role::Active, 0).unwrap();
+        standby.init_standby(Role::Standby, 0).unwrap();
+        (active, standby)
+    }
+
+    /// One pull, through JSON as on the wire.
+    fn sync(active: &KmsDb, standby: &KmsDb) -> Result<usize> {
+        let req = standby.standby_pull_request()?;
+        let batch = active.serve_standby_sync(&req, 100)?;
+        let batch: SyncBatch = serde_json::from_str(&serde_json::to_string(&batch)?)?;
+        standby.apply_standby_batch(&batch, 100)
+    }
+
+    #[test]
+    fn every_table_is_classified() {
+        let path = std::env::temp_dir().join(format!("kms-standby-{}.db", uuid::Uuid::new_v4()));
+        KmsDb::open(path.to_str().unwrap()).unwrap();
+        let conn = Connection::open(&path).unwrap();
+        let mut stmt = conn
+            .prepare(
+                "SELECT name FROM sqlite_master WHERE type='table' AND name NOT LIKE 'sqlite_%'",
+            )
+            .unwrap();
+        let tables: Vec<String> = stmt
+            .query_map([], |r| r.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        for table in &tables {
+            let by_log =
+                table == "ca_events" || event_store::PROJECTIONS.iter().any(|(t, _, _)| t == table);
+            let listed = [SNAPSHOT_TABLES, APPEND_TABLES, LOCAL_TABLES]
+                .iter()
+                .filter(|list| list.contains(&table.as_str()))
+                .count();
+            assert_eq!(
+                listed + usize::from(by_log),
+                1,
+                "{}: replicate it (SNAPSHOT/APPEND) or keep it LOCAL",
+                table
+            );
+        }
+        for table in SNAPSHOT_TABLES
+            .iter()
+            .chain(APPEND_TABLES)
+            .chain(LOCAL_TABLES)
+        {
+            assert!(tables.iter().any(|t| t == table), "no table {}", table);
+        }
+        drop(stmt);
+        drop(conn);
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn sealed_messages_bind_key_path_request_and_time() {
+        let c = config(&"11".repeat(32));
+        let req = c.seal_request(SYNC_PATH, &7u32, 1_000).unwrap();
+        let (node, body): (String, u32) = c.open_request(SYNC_PATH, &req, 1_030).unwrap();
+        assert_eq!((node, body), (local_device_id(), 7));
+        assert!(c.open_request::<u32>(FENCE_PATH, &req, 1_000).is_err());
+        assert!(c.open_request::<u32>(SYNC_PATH, &req, 1_061).is_err());
+        assert!(config(&"22".repeat(32))
+            .open_request::<u32>(SYNC_PATH, &req, 1_000)
+            .is_err());
+
+        let reply = c.seal_reply(&req, &"batch").unwrap();
+        assert_eq!(c.open_reply::<String>(&req, &reply).unwrap(), "batch");
+        let other = c.seal_request(SYNC_PATH, &7u32, 1_000).unwrap();
+        assert!(c.open_reply::<String>(&other, &reply).is_err());
+        // A request sealed as a reply (or the reverse) does not open.
+        assert!(c.open_reply::<u32>(&other, &req).is_err());
+
+        let secret = Some("11".repeat(32));
+        let peer = Some("http://10.0.0.1:3000".to_string());
+        assert!(StandbyConfig::new("fenced", peer.clone(), secret.clone(), None).is_err());
+        assert!(StandbyConfig::new("active", Some("https://x".into()), secret, None).is_err());
+        assert!(StandbyConfig::new("active", peer, Some("11".repeat(16)), None).is_err());
+    }
+
+    #[test]
+    fn standby_catches_up_and_refuses_a_diverged_log() {
+        let (active, standby) = pair();
+        active.insert_wallet(&wallet("w1")).unwrap();
+        active
+            .update_wallet_derived("w1", "0xaddr", "0xpub", "m/44'/60'/0'/0/0", "ready")
+            .unwrap();
+        let api_key = active.generate_api_key("svc").unwrap();
+        active
+            .record_account_event("w1", "contact_verified", None, None)
+            .unwrap();
+
+        assert_eq!(sync(&active, &standby).unwrap(), 2);
+        assert_eq!(standby.get_wallet("w1").unwrap().unwrap().status, "ready");
+        assert!(standby.validate_api_key(&api_key).unwrap());
+        assert_eq!(
+            standby.list_account_events("w1", 10, None).unwrap().len(),
+            1
+        );
+        assert_eq!(
+            standby.verify_event_chain().unwrap(),
+            active.verify_event_chain().unwrap()
+        );
+        assert_eq!(
+            standby.standby_pull_request().unwrap().digests,
+            active.standby_pull_request().unwrap().digests
+        );
+        // Nothing new: an empty batch.
+        let batch = active
+            .serve_standby_sync(&standby.standby_pull_request().unwrap(), 100)
+            .unwrap();
+        assert!(batch.events.is_empty() && batch.tables.is_empty());
+
+        // A write that reached the standby's DB some other way.
+        standby.insert_wallet(&wallet("w2")).unwrap();
+        active.insert_wallet(&wallet("w3")).unwrap();
+        let err = sync(&active, &standby).unwrap_err().to_string();
+        assert!(err.contains("diverged"), "{}", err);
+    }
+
+    #[test]
+    fn a_higher_fencing_token_fences_the_old_active() {
+        let (active, standby) = pair();
+        let promoted = standby.promote_standby(2, 50).unwrap();
+        assert_eq!((promoted.role, promoted.fencing_token), (Role::Active, 2));
+        assert!(standby.promote_standby(3, 50).is_err(), "already active");
+
+        // The old active hears of token 2 (fence request or a pull).
+        let old = active.observe_fencing_token(2, 60).unwrap();
+        assert_eq!((old.role, old.fencing_token), (Role::Fenced, 2));
+        assert!(active.observe_fencing_token(1, 60).unwrap().role == Role::Fenced);
+        // Restarted as active, it stays fenced; configured as standby, it rejoins.
+        assert_eq!(
+            active.init_standby(Role::Active, 70).unwrap().role,
+            Role::Fenced
+        );
+        let rejoined = active.init_standby(Role::Standby, 70).unwrap();
+        assert_eq!((rejoined.role, rejoined.fencing_token), (Role::Standby, 2));
+
+        // A standby that has seen token 2 drops batches from token 1.
+        let stale = SyncBatch {
+            fencing_token: 1,
+            head_seq: 0,
+            events: vec![],
+            tables: vec![],
+        };
+        let err = active
+            .apply_standby_batch(&stale, 80)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("stale active"), "{}", err);
+
+        // An active answering a pull that carries a higher token steps down.
+        let (active, standby) = pair();
+        standby.observe_fencing_token(5, 90).unwrap();
+        let req = standby.standby_pull_request().unwrap();
+        let err = active.serve_standby_sync(&req, 90).unwrap_err().to_string();
+        assert!(err.contains("not the active"), "{}", err);
+        assert_eq!(active.standby_state().unwrap().unwrap().role, Role::Fenced);
+    }
+}
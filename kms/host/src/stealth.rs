@@ -0,0 +1,343 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! EIP-5564 stealth addresses — the CA half.
+//!
+//! Sending needs only the receiver's public meta-address, so
+//! [`generate`] runs here for any `st:eth:` address, ours or not. Receiving
+//! needs the viewing key, which stays in the TA: the chain watcher stores
+//! every scheme-1 `Announcement` of the ERC-5564 announcer in
+//! `stealth_announcements`, and the scanner in api_server.rs hands them to
+//! `StealthScan` in batches per registered meta-address. A match becomes a
+//! `stealth-payment-in` row of `chain_events`, next to the wallet's other
+//! deposits. Pure functions here.
+
+use anyhow::{anyhow, Result};
+use k256::elliptic_curve::sec1::ToEncodedPoint;
+use k256::{ProjectivePoint, PublicKey, SecretKey};
+use proto::stealth::{hashed_secret, StealthMetaAddress, ANNOUNCER, SCHEME_ID};
+use serde_json::{json, Value};
+use sha3::{Digest, Keccak256};
+use std::convert::TryInto;
+
+use crate::chain_watch::{decimal, KIND_STEALTH_IN};
+use crate::db::ChainEvent;
+
+/// keccak256("Announcement(uint256,address,address,bytes,bytes)")
+pub const ANNOUNCEMENT_TOPIC: &str =
+    "0x5f0eab8057630ba7676c49b4f21a0231414e79474595be8e4c432fbf6bf0f4e7";
+
+/// Metadata token of a native-ETH payment (and its selector, repeated).
+const NATIVE_TOKEN: [u8; 20] = [0xee; 20];
+
+/// What a sender needs to pay a meta-address and announce it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GeneratedStealthAddress {
+    pub stealth_address: [u8; 20],
+    /// Compressed `e·G`, the announcement's `ephemeralPubKey`.
+    pub ephemeral_pubkey: Vec<u8>,
+    /// First byte of the announcement's metadata.
+    pub view_tag: u8,
+}
+
+/// One stored announcement.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Announcement {
+    pub chain_id: u64,
+    /// Lowercase 0x address.
+    pub stealth_address: String,
+    pub caller: String,
+    pub ephemeral_pubkey: Vec<u8>,
+    pub metadata: Vec<u8>,
+    pub tx_hash: String,
+    pub block_number: u64,
+    pub log_index: i64,
+}
+
+fn point(bytes: &[u8], what: &str) -> Result<PublicKey> {
+    PublicKey::from_sec1_bytes(bytes).map_err(|_| anyhow!("{} is not a secp256k1 point", what))
+}
+
+fn address_of(key: &PublicKey) -> [u8; 20] {
+    let hash = Keccak256::digest(&key.to_encoded_point(false).as_bytes()[1..]);
+    let mut address = [0u8; 20];
+    address.copy_from_slice(&hash[12..]);
+    address
+}
+
+/// The stealth address `P_spend + s_h·G` for `meta` under the sender's
+/// ephemeral key.
+pub fn generate(
+    meta: &StealthMetaAddress,
+    ephemeral_secret: &SecretKey,
+) -> Result<GeneratedStealthAddress> {
+    let spending = point(&meta.spending_pubkey, "spending key")?;
+    let viewing = point(&meta.viewing_pubkey, "viewing key")?;
+    let shared = (viewing.to_projective() * *ephemeral_secret.to_nonzero_scalar()).to_affine();
+    let s_h = hashed_secret(shared.to_encoded_point(true).as_bytes());
+    let tweak = SecretKey::from_slice(&s_h)
+        .map_err(|_| anyhow!("hashed secret is not a scalar; pick another ephemeral key"))?;
+    let stealth = PublicKey::from_affine(
+        (ProjectivePoint::GENERATOR * *tweak.to_nonzero_scalar() + spending.to_projective())
+            .to_affine(),
+    )
+    .map_err(|_| anyhow!("stealth key is the point at infinity"))?;
+    Ok(GeneratedStealthAddress {
+        stealth_address: address_of(&stealth),
+        ephemeral_pubkey: ephemeral_secret
+            .public_key()
+            .to_encoded_point(true)
+            .as_bytes()
+            .to_vec(),
+        view_tag: s_h[0],
+    })
+}
+
+/// `eth_subscribe` params for scheme-1 announcements.
+pub fn announcement_subscription() -> Value {
+    json!(["logs", {
+        "address": ANNOUNCER,
+        "topics": [ANNOUNCEMENT_TOPIC, format!("0x{:064x}", SCHEME_ID)],
+    }])
+}
+
+/// The `bytes` argument whose head sits at `slot` of ABI-encoded `data`.
+fn abi_bytes(data: &[u8], slot: usize) -> Option<Vec<u8>> {
+    let word = |at: usize| -> Option<usize> {
+        let w = data.get(at..at.checked_add(32)?)?;
+        if w[..24].iter().any(|b| *b != 0) {
+            return None;
+        }
+        Some(u64::from_be_bytes(w[24..].try_into().ok()?) as usize)
+    };
+    let offset = word(slot * 32)?;
+    let len = word(offset)?;
+    let start = offset.checked_add(32)?;
+    data.get(start..start.checked_add(len)?).map(<[u8]>::to_vec)
+}
+
+/// A scheme-1 announcement log and the node's reorg flag. Anything else,
+/// including a malformed announcement, is `None`.
+pub fn announcement_from_log(chain_id: u64, log: &Value) -> Option<(Announcement, bool)> {
+    let emitter = log["address"].as_str()?;
+    if !emitter.eq_ignore_ascii_case(ANNOUNCER) {
+        return None;
+    }
+    let topics: Vec<&str> = log["topics"]
+        .as_array()?
+        .iter()
+        .filter_map(Value::as_str)
+        .collect();
+    if topics.len() != 4
+        || !topics[0].eq_ignore_ascii_case(ANNOUNCEMENT_TOPIC)
+        || topics[1] != format!("0x{:064x}", SCHEME_ID)
+    {
+        return None;
+    }
+    let topic_address = |t: &str| -> Option<String> {
+        let t = t.trim_start_matches("0x");
+        (t.len() == 64).then(|| format!("0x{}", t[24..].to_lowercase()))
+    };
+    let quantity = |v: &Value| u64::from_str_radix(v.as_str()?.trim_start_matches("0x"), 16).ok();
+    let data = hex::decode(log["data"].as_str()?.trim_start_matches("0x")).ok()?;
+    let ephemeral_pubkey = abi_bytes(&data, 0)?;
+    let metadata = abi_bytes(&data, 1)?;
+    if ephemeral_pubkey.len() != 33 || metadata.is_empty() {
+        return None;
+    }
+    Some((
+        Announcement {
+            chain_id,
+            stealth_address: topic_address(topics[2])?,
+            caller: topic_address(topics[3])?,
+            ephemeral_pubkey,
+            metadata,
+            tx_hash: log["transactionHash"].as_str()?.to_lowercase(),
+            block_number: quantity(&log["blockNumber"])?,
+            log_index: quantity(&log["logIndex"])? as i64,
+        },
+        log["removed"].as_bool().unwrap_or(false),
+    ))
+}
+
+impl Announcement {
+    pub fn candidate(&self) -> Option<proto::stealth::StealthCandidate> {
+        let address = hex::decode(self.stealth_address.trim_start_matches("0x")).ok()?;
+        Some(proto::stealth::StealthCandidate {
+            stealth_address: address.try_into().ok()?,
+            ephemeral_pubkey: self.ephemeral_pubkey.clone(),
+            view_tag: *self.metadata.first()?,
+        })
+    }
+
+    /// The payment's ledger row. Token and amount come from the metadata
+    /// layout EIP-5564 suggests (view tag, selector, token, amount); a
+    /// sender that put something else there still gets a row, without them.
+    pub fn payment_event(&self, key_id: &str) -> ChainEvent {
+        let (token, value) = match self.metadata.get(5..57) {
+            Some(m) => {
+                let token = if m[..20] == NATIVE_TOKEN {
+                    None
+                } else {
+                    Some(format!("0x{}", hex::encode(&m[..20])))
+                };
+                (token, decimal(&hex::encode(&m[20..])))
+            }
+            None => (None, None),
+        };
+        ChainEvent {
+            key_id: key_id.to_string(),
+            address: self.stealth_address.clone(),
+            chain_id: self.chain_id,
+            kind: KIND_STEALTH_IN.to_string(),
+            token,
+            from_address: Some(self.caller.clone()),
+            to_address: Some(self.stealth_address.clone()),
+            value,
+            topic0: Some(ANNOUNCEMENT_TOPIC.to_string()),
+            tx_hash: self.tx_hash.clone(),
+            block_number: self.block_number,
+            log_index: self.log_index,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Account 0 of "test … junk"; the same announcements are in
+    // ta/conformance/vectors/derivation.txt.
+    const META: &str = "st:eth:0x0227051fb9fdc475ad7da23a0e2797551e11053eb65a51f73237ff04a1ea425dd302d8e2df8391bfb3cd03393ce94a8c7f4ca459c1d217e659e89d285e57d1cd6e53";
+
+    #[test]
+    fn generates_the_reference_stealth_addresses() {
+        let meta = StealthMetaAddress::parse(META).unwrap();
+        for (secret, ephemeral, tag, address) in [
+            (
+                "5c79ef78cb9626630d7bbb825fef7b63d9fddbe4c067aa96e9f5b37e1fe84b0e",
+                "02fa448bd728ec4dc931d0adcecdd3e05d7536e2a2c4e7b56c895a97ca80778feb",
+                0x33,
+                "0x3B9ABCafb03cb26a8677b6bf151a1395B099DBD0",
+            ),
+            (
+                "1305056fb29a5c9709af7124e172910a1624480cb95367c351c90e8623bc2dd7",
+                "0206f48fa41d4d79cb858b741000982a592ab58e8ba82b11171ebe95239fbfa993",
+                0xe5,
+                "0x4042eF826046F4B996D8B7D068A44F4De215a754",
+            ),
+        ] {
+            let e = SecretKey::from_slice(&hex::decode(secret).unwrap()).unwrap();
+            let out = generate(&meta, &e).unwrap();
+            assert_eq!(hex::encode(&out.ephemeral_pubkey), ephemeral);
+            assert_eq!(out.view_tag, tag);
+            assert_eq!(
+                proto::eip55::to_checksum_address(&out.stealth_address),
+                address
+            );
+        }
+    }
+
+    fn log(metadata: &str) -> Value {
+        let eph = "02fa448bd728ec4dc931d0adcecdd3e05d7536e2a2c4e7b56c895a97ca80778feb";
+        let meta_len = metadata.len() / 2;
+        let data = format!(
+            "{:064x}{:064x}{:064x}{:0<128}{:064x}{:0<width$}",
+            64,
+            160,
+            33,
+            eph,
+            meta_len,
+            metadata,
+            width = (meta_len + 31) / 32 * 64
+        );
+        json!({
+            "address": "0x55649E01B5Df198D18D95b5cc5051630cfD45564",
+            "topics": [
+                ANNOUNCEMENT_TOPIC,
+                format!("0x{:064x}", 1),
+                "0x0000000000000000000000003b9abcafb03cb26a8677b6bf151a1395b099dbd0",
+                "0x0000000000000000000000001111111111111111111111111111111111111111",
+            ],
+            "data": format!("0x{}", data),
+            "transactionHash": "0xABC",
+            "blockNumber": "0x10",
+            "logIndex": "0x2",
+        })
+    }
+
+    #[test]
+    fn announcement_logs_decode_into_ledger_rows() {
+        // view tag ‖ 0xeeeeeeee ‖ 0xee…ee ‖ 1 ETH: a native payment.
+        let native = format!(
+            "33{}{}{:064x}",
+            "ee".repeat(4),
+            "ee".repeat(20),
+            10u128.pow(18)
+        );
+        let (ann, removed) = announcement_from_log(1, &log(&native)).unwrap();
+        assert!(!removed);
+        assert_eq!(
+            ann.stealth_address,
+            "0x3b9abcafb03cb26a8677b6bf151a1395b099dbd0"
+        );
+        assert_eq!(ann.caller, "0x1111111111111111111111111111111111111111");
+        assert_eq!(
+            (ann.tx_hash.as_str(), ann.block_number, ann.log_index),
+            ("0xabc", 16, 2)
+        );
+        let candidate = ann.candidate().unwrap();
+        assert_eq!(candidate.view_tag, 0x33);
+        assert_eq!(candidate.ephemeral_pubkey, ann.ephemeral_pubkey);
+
+        let e = ann.payment_event("w-1");
+        assert_eq!(e.kind, KIND_STEALTH_IN);
+        assert_eq!(e.token, None);
+        assert_eq!(e.value.as_deref(), Some("1000000000000000000"));
+        assert_eq!(e.from_address.as_deref(), Some(ann.caller.as_str()));
+
+        // An ERC-20 payment names its token; a bare view tag carries no amount.
+        let erc20 = format!("33a9059cbb{}{:064x}", "42".repeat(20), 5);
+        let e = announcement_from_log(1, &log(&erc20))
+            .unwrap()
+            .0
+            .payment_event("w-1");
+        assert_eq!(e.token.as_deref(), Some(&*format!("0x{}", "42".repeat(20))));
+        assert_eq!(e.value.as_deref(), Some("5"));
+        let bare = announcement_from_log(1, &log("33"))
+            .unwrap()
+            .0
+            .payment_event("w-1");
+        assert_eq!((bare.token, bare.value), (None, None));
+
+        // Another emitter, another scheme, or no view tag: not an announcement.
+        let mut other = log(&native);
+        other["address"] = json!("0x2222222222222222222222222222222222222222");
+        assert!(announcement_from_log(1, &other).is_none());
+        let mut scheme2 = log(&native);
+        scheme2["topics"][1] = json!(format!("0x{:064x}", 2));
+        assert!(announcement_from_log(1, &scheme2).is_none());
+        assert!(announcement_from_log(1, &log("")).is_none());
+    }
+
+    #[test]
+    fn subscription_filters_on_scheme_one() {
+        let sub = announcement_subscription();
+        assert_eq!(sub[1]["address"], ANNOUNCER);
+        assert_eq!(sub[1]["topics"][1], format!("0x{:064x}", 1));
+    }
+}
@@ -19,34 +19,74 @@
 //! This module provides a clean interface for HTTP API server to call TA functions
 
 use anyhow::{Context as AnyhowContext, Result};
+#[cfg(not(feature = "soft-tee"))]
 use optee_teec::{Context, Operation, ParamType, Uuid};
+#[cfg(not(feature = "soft-tee"))]
 use optee_teec::{ParamNone, ParamTmpRef, ParamValue};
-use std::sync::atomic::{AtomicUsize, Ordering};
+use proto::erasure::DeletionProof;
+use proto::hd_path::InvalidPath;
+use proto::multisig::MultiSigPending;
+use proto::nonce_guard::NonceRefused;
+use proto::spending_policy::PolicyViolation;
+use proto::storage_quota::StorageExhausted;
+#[cfg(not(feature = "soft-tee"))]
+use proto::wire::RequestHeader;
+use proto::wire::{decode_output, UnsupportedVersion};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
-use std::sync::Mutex;
-use std::time::Instant;
+use std::sync::{Condvar, Mutex};
+use std::time::{Instant, SystemTime};
 
-const OUTPUT_MAX_SIZE: usize = 4096;
+use crate::otel;
+use crate::response_cache::{CacheConfig, CacheStats, ResponseCache};
+
+/// UUID the TA was signed under: ours, or eth_wallet's for a compat TA.
+#[cfg(not(feature = "eth-wallet-compat"))]
+pub const TA_UUID: &str = proto::UUID;
+#[cfg(feature = "eth-wallet-compat")]
+pub const TA_UUID: &str = proto::ETH_WALLET_UUID;
 
 /// TA Client for managing sessions with the Trusted Application
+#[cfg(not(feature = "soft-tee"))]
 pub struct TaClient {
     ctx: Context,
     uuid: Uuid,
 }
 
+/// Soft-TEE build: each command goes to the store on disk (`soft_tee`).
+#[cfg(feature = "soft-tee")]
+pub struct TaClient {}
+
+#[cfg(feature = "soft-tee")]
+impl TaClient {
+    pub fn new() -> Result<Self> {
+        crate::soft_tee::acknowledge()?;
+        Ok(Self {})
+    }
+
+    fn invoke_command(&mut self, command: proto::Command, input: &[u8]) -> Result<Vec<u8>> {
+        crate::soft_tee::SoftTee::from_env()?
+            .invoke(command, input)
+            .map_err(soft_tee_error)
+    }
+}
+
 impl TaClient {
     /// Create a new TA client
+    #[cfg(not(feature = "soft-tee"))]
     pub fn new() -> Result<Self> {
         let ctx =
             Context::new().map_err(|e| anyhow::anyhow!("Failed to create TEE context: {:?}", e))?;
 
-        let uuid = Uuid::parse_str(proto::UUID)
-            .map_err(|_| anyhow::anyhow!("Invalid UUID in proto::UUID"))?;
+        let uuid =
+            Uuid::parse_str(TA_UUID).map_err(|_| anyhow::anyhow!("Invalid TA UUID {}", TA_UUID))?;
 
         Ok(Self { ctx, uuid })
     }
 
     /// Invoke a command in the TA
+    #[cfg(not(feature = "soft-tee"))]
     fn invoke_command(&mut self, command: proto::Command, input: &[u8]) -> Result<Vec<u8>> {
         let mut session = self
             .ctx
@@ -54,9 +94,13 @@ impl TaClient {
             .map_err(|e| anyhow::anyhow!("Failed to open TA session: {:?}", e))?;
 
         let p0 = ParamTmpRef::new_input(input);
-        let mut output = vec![0u8; OUTPUT_MAX_SIZE];
+        let mut output = vec![0u8; proto::buffers::max_output_len(command)];
         let p1 = ParamTmpRef::new_output(output.as_mut_slice());
-        let p2 = ParamValue::new(0, 0, ParamType::ValueInout);
+        let p2 = ParamValue::new(
+            0,
+            RequestHeader::new(proto::PRIORITY_INTERACTIVE).to_hint(),
+            ParamType::ValueInout,
+        );
 
         let mut operation = Operation::new(0, p0, p1, p2, ParamNone);
 
@@ -68,11 +112,7 @@ impl TaClient {
             Err(e) => {
                 let output_len = operation.parameters().2.a() as usize;
                 let err_message = String::from_utf8_lossy(&output[..output_len]);
-                Err(anyhow::anyhow!(
-                    "TA command failed: {} (error: {:?})",
-                    err_message,
-                    e
-                ))
+                Err(command_error(&err_message, e))
             }
         }
     }
@@ -83,30 +123,32 @@ impl TaClient {
         let input = proto::CreateWalletInput {
             passkey_pubkey: passkey_pubkey.to_vec(),
             entropy_seed: None,
+            prf_output: None,
         };
         let serialized_input =
             bincode::serialize(&input).context("Failed to serialize CreateWalletInput")?;
         let serialized_output =
             self.invoke_command(proto::Command::CreateWallet, &serialized_input)?;
-        let output: proto::CreateWalletOutput = bincode::deserialize(&serialized_output)
+        let output: proto::CreateWalletOutput = decode_output(&serialized_output)
             .context("Failed to deserialize CreateWalletOutput")?;
         Ok(output.wallet_id)
     }
 
-    /// Remove a wallet from the TA
+    /// Remove a wallet from the TA. Returns its deletion certificate (None
+    /// from a TA that predates proof-of-erasure).
     pub fn remove_wallet(
         &mut self,
         wallet_id: uuid::Uuid,
         passkey_assertion: Option<proto::PasskeyAssertion>,
-    ) -> Result<()> {
+    ) -> Result<Option<DeletionProof>> {
         let input = proto::RemoveWalletInput {
             wallet_id,
             passkey_assertion,
         };
         let serialized_input =
             bincode::serialize(&input).context("Failed to serialize RemoveWalletInput")?;
-        self.invoke_command(proto::Command::RemoveWallet, &serialized_input)?;
-        Ok(())
+        let out = self.invoke_command(proto::Command::RemoveWallet, &serialized_input)?;
+        decode_remove_wallet_output(&out)
     }
 
     /// Issue #49: request a fresh one-time WebAuthn challenge nonce from the TA.
@@ -120,7 +162,7 @@ impl TaClient {
             bincode::serialize(&input).context("Failed to serialize GetChallengeInput")?;
         let serialized_output =
             self.invoke_command(proto::Command::GetChallenge, &serialized_input)?;
-        let output: proto::GetChallengeOutput = bincode::deserialize(&serialized_output)
+        let output: proto::GetChallengeOutput = decode_output(&serialized_output)
             .context("Failed to deserialize GetChallengeOutput")?;
         Ok(output.nonce)
     }
@@ -137,12 +179,13 @@ impl TaClient {
             wallet_id,
             hd_path: hd_path.to_string(),
             passkey_assertion,
+            chain: proto::chain::Chain::Ethereum,
         };
         let serialized_input =
             bincode::serialize(&input).context("Failed to serialize DeriveAddressInput")?;
         let serialized_output =
             self.invoke_command(proto::Command::DeriveAddress, &serialized_input)?;
-        let output: proto::DeriveAddressOutput = bincode::deserialize(&serialized_output)
+        let output: proto::DeriveAddressOutput = decode_output(&serialized_output)
             .context("Failed to deserialize DeriveAddressOutput")?;
         Ok(output.address)
     }
@@ -161,12 +204,14 @@ impl TaClient {
             hd_path: hd_path.to_string(),
             transaction,
             passkey_assertion,
+            summary_abis: Vec::new(),
+            summary_committed: false,
         };
         let serialized_input =
             bincode::serialize(&input).context("Failed to serialize SignTransactionInput")?;
         let serialized_output =
             self.invoke_command(proto::Command::SignTransaction, &serialized_input)?;
-        let output: proto::SignTransactionOutput = bincode::deserialize(&serialized_output)
+        let output: proto::SignTransactionOutput = decode_output(&serialized_output)
             .context("Failed to deserialize SignTransactionOutput")?;
         Ok(output.signature)
     }
@@ -179,19 +224,21 @@ impl TaClient {
         hd_path: &str,
         message: &[u8],
         passkey_assertion: Option<proto::PasskeyAssertion>,
+        context: proto::SigningContext,
     ) -> Result<Vec<u8>> {
         let input = proto::SignMessageInput {
             wallet_id,
             hd_path: hd_path.to_string(),
             message: message.to_vec(),
             passkey_assertion,
+            context,
         };
         let serialized_input =
             bincode::serialize(&input).context("Failed to serialize SignMessageInput")?;
         let serialized_output =
             self.invoke_command(proto::Command::SignMessage, &serialized_input)?;
-        let output: proto::SignMessageOutput = bincode::deserialize(&serialized_output)
-            .context("Failed to deserialize SignMessageOutput")?;
+        let output: proto::SignMessageOutput =
+            decode_output(&serialized_output).context("Failed to deserialize SignMessageOutput")?;
         Ok(output.signature)
     }
 
@@ -203,18 +250,21 @@ impl TaClient {
         hd_path: &str,
         hash: &[u8; 32],
         passkey_assertion: Option<proto::PasskeyAssertion>,
+        context: proto::SigningContext,
     ) -> Result<Vec<u8>> {
         let input = proto::SignHashInput {
             wallet_id,
             hd_path: hd_path.to_string(),
             hash: *hash,
             passkey_assertion,
+            context,
+            fee_quote: None,
         };
         let serialized_input =
             bincode::serialize(&input).context("Failed to serialize SignHashInput")?;
         let serialized_output = self.invoke_command(proto::Command::SignHash, &serialized_input)?;
-        let output: proto::SignHashOutput = bincode::deserialize(&serialized_output)
-            .context("Failed to deserialize SignHashOutput")?;
+        let output: proto::SignHashOutput =
+            decode_output(&serialized_output).context("Failed to deserialize SignHashOutput")?;
         Ok(output.signature)
     }
 
@@ -229,7 +279,7 @@ impl TaClient {
             bincode::serialize(&input).context("Failed to serialize DeriveAddressAutoInput")?;
         let serialized_output =
             self.invoke_command(proto::Command::DeriveAddressAuto, &serialized_input)?;
-        let output: proto::DeriveAddressAutoOutput = bincode::deserialize(&serialized_output)
+        let output: proto::DeriveAddressAutoOutput = decode_output(&serialized_output)
             .context("Failed to deserialize DeriveAddressAutoOutput")?;
         Ok((
             output.wallet_id,
@@ -239,6 +289,106 @@ impl TaClient {
         ))
     }
 
+    /// Per-command latency histograms; also the cheapest "is the TA alive" probe.
+    pub fn ta_stats(&mut self) -> Result<proto::TaStatsOutput> {
+        let serialized_input = bincode::serialize(&proto::TaStatsInput {})
+            .context("Failed to serialize TaStatsInput")?;
+        let serialized_output = self.invoke_command(proto::Command::TaStats, &serialized_input)?;
+        decode_output(&serialized_output).context("Failed to deserialize TaStatsOutput")
+    }
+
+    pub fn read_rollback_counter(&mut self) -> Result<u64> {
+        let serialized_input = bincode::serialize(&proto::ReadRollbackCounterInput {})
+            .context("Failed to serialize ReadRollbackCounterInput")?;
+        let serialized_output =
+            self.invoke_command(proto::Command::ReadRollbackCounter, &serialized_input)?;
+        let output: proto::ReadRollbackCounterOutput = decode_output(&serialized_output)
+            .context("Failed to deserialize ReadRollbackCounterOutput")?;
+        Ok(output.counter)
+    }
+
+    pub fn get_attestation(&mut self, nonce: Vec<u8>) -> Result<proto::GetAttestationOutput> {
+        let serialized_input = bincode::serialize(&proto::GetAttestationInput { nonce })
+            .context("Failed to serialize GetAttestationInput")?;
+        let serialized_output =
+            self.invoke_command(proto::Command::GetAttestation, &serialized_input)?;
+        decode_output(&serialized_output).context("Failed to deserialize GetAttestationOutput")
+    }
+
+    pub fn get_capabilities(&mut self) -> Result<proto::GetCapabilitiesOutput> {
+        let serialized_input = bincode::serialize(&proto::GetCapabilitiesInput {})
+            .context("Failed to serialize GetCapabilitiesInput")?;
+        let serialized_output =
+            self.invoke_command(proto::Command::GetCapabilities, &serialized_input)?;
+        decode_output(&serialized_output).context("Failed to deserialize GetCapabilitiesOutput")
+    }
+
+    pub fn get_protocol_version(&mut self) -> Result<proto::GetProtocolVersionOutput> {
+        let serialized_input = bincode::serialize(&proto::GetProtocolVersionInput {})
+            .context("Failed to serialize GetProtocolVersionInput")?;
+        decode_protocol_version(
+            self.invoke_command(proto::Command::GetProtocolVersion, &serialized_input),
+        )
+    }
+
+    pub fn install_deployment_policy(
+        &mut self,
+        policy: proto::deployment_policy::DeploymentPolicy,
+        signature: Vec<u8>,
+    ) -> Result<proto::InstallDeploymentPolicyOutput> {
+        let serialized_input =
+            bincode::serialize(&proto::InstallDeploymentPolicyInput { policy, signature })
+                .context("Failed to serialize InstallDeploymentPolicyInput")?;
+        let serialized_output =
+            self.invoke_command(proto::Command::InstallDeploymentPolicy, &serialized_input)?;
+        decode_output(&serialized_output)
+            .context("Failed to deserialize InstallDeploymentPolicyOutput")
+    }
+
+    pub fn install_config(
+        &mut self,
+        config: proto::ta_config::TaConfig,
+        signature: Vec<u8>,
+    ) -> Result<proto::InstallConfigOutput> {
+        let serialized_input = bincode::serialize(&proto::InstallConfigInput { config, signature })
+            .context("Failed to serialize InstallConfigInput")?;
+        let serialized_output =
+            self.invoke_command(proto::Command::InstallConfig, &serialized_input)?;
+        decode_output(&serialized_output).context("Failed to deserialize InstallConfigOutput")
+    }
+
+    pub fn tamper_order(
+        &mut self,
+        order: proto::tamper::TamperOrder,
+        signature: Vec<u8>,
+    ) -> Result<proto::TamperOrderOutput> {
+        let serialized_input = bincode::serialize(&proto::TamperOrderInput { order, signature })
+            .context("Failed to serialize TamperOrderInput")?;
+        let serialized_output =
+            self.invoke_command(proto::Command::TamperOrder, &serialized_input)?;
+        decode_output(&serialized_output).context("Failed to deserialize TamperOrderOutput")
+    }
+
+    pub fn tamper_status(&mut self) -> Result<proto::TamperStatus> {
+        let serialized_input = bincode::serialize(&proto::GetTamperStatusInput {})
+            .context("Failed to serialize GetTamperStatusInput")?;
+        let serialized_output =
+            self.invoke_command(proto::Command::GetTamperStatus, &serialized_input)?;
+        decode_output(&serialized_output).context("Failed to deserialize TamperStatus")
+    }
+
+    pub fn set_log_level(
+        &mut self,
+        order: proto::log_level::LogLevelOrder,
+        signature: Vec<u8>,
+    ) -> Result<proto::SetLogLevelOutput> {
+        let serialized_input = bincode::serialize(&proto::SetLogLevelInput { order, signature })
+            .context("Failed to serialize SetLogLevelInput")?;
+        let serialized_output =
+            self.invoke_command(proto::Command::SetLogLevel, &serialized_input)?;
+        decode_output(&serialized_output).context("Failed to deserialize SetLogLevelOutput")
+    }
+
     /// Verify a WebAuthn PassKey (P-256/secp256r1) signature inside TEE
     pub fn verify_passkey(
         &mut self,
@@ -261,7 +411,7 @@ impl TaClient {
             bincode::serialize(&input).context("Failed to serialize VerifyPasskeyInput")?;
         let serialized_output =
             self.invoke_command(proto::Command::VerifyPasskey, &serialized_input)?;
-        let output: proto::VerifyPasskeyOutput = bincode::deserialize(&serialized_output)
+        let output: proto::VerifyPasskeyOutput = decode_output(&serialized_output)
             .context("Failed to deserialize VerifyPasskeyOutput")?;
         Ok(output.valid)
     }
@@ -326,24 +476,276 @@ impl TaClient {
         let output_bytes =
             self.invoke_command(proto::Command::ExportPrivateKey, &serialized_input)?;
 
-        let output: proto::ExportPrivateKeyOutput = bincode::deserialize(&output_bytes)
+        let output: proto::ExportPrivateKeyOutput = decode_output(&output_bytes)
             .with_context(|| "Failed to deserialize ExportPrivateKeyOutput")?;
 
         Ok(output.private_key)
     }
+
+    /// Passphrase keystore of the wallet entropy, sealed in the TA.
+    pub fn export_keystore(
+        &mut self,
+        wallet_id: uuid::Uuid,
+        passphrase: &str,
+        kdf: proto::kdf::KdfConfig,
+        passkey_assertion: Option<proto::PasskeyAssertion>,
+    ) -> Result<proto::kdf::Keystore> {
+        let input = proto::ExportKeystoreInput {
+            wallet_id,
+            passphrase: passphrase.to_string(),
+            kdf,
+            passkey_assertion,
+        };
+
+        let serialized_input = bincode::serialize(&input)?;
+        let output_bytes =
+            self.invoke_command(proto::Command::ExportKeystore, &serialized_input)?;
+
+        let output: proto::ExportKeystoreOutput = decode_output(&output_bytes)
+            .with_context(|| "Failed to deserialize ExportKeystoreOutput")?;
+
+        Ok(output.keystore)
+    }
+
+    /// Wallet entropy sealed under a password and this device's sealing key.
+    pub fn export_encrypted_backup(
+        &mut self,
+        wallet_id: uuid::Uuid,
+        password: &str,
+        kdf: proto::kdf::KdfConfig,
+        passkey_assertion: Option<proto::PasskeyAssertion>,
+    ) -> Result<proto::backup::EncryptedBackup> {
+        let input = proto::ExportEncryptedBackupInput {
+            wallet_id,
+            password: password.to_string(),
+            kdf,
+            passkey_assertion,
+        };
+
+        let serialized_input = bincode::serialize(&input)?;
+        let output_bytes =
+            self.invoke_command(proto::Command::ExportEncryptedBackup, &serialized_input)?;
+
+        let output: proto::ExportEncryptedBackupOutput = decode_output(&output_bytes)
+            .with_context(|| "Failed to deserialize ExportEncryptedBackupOutput")?;
+
+        Ok(output.backup)
+    }
+
+    /// Restore a wallet from a backup sealed on this device.
+    pub fn import_encrypted_backup(
+        &mut self,
+        backup: proto::backup::EncryptedBackup,
+        password: &str,
+    ) -> Result<proto::ImportEncryptedBackupOutput> {
+        let input = proto::ImportEncryptedBackupInput {
+            backup,
+            password: password.to_string(),
+        };
+
+        let serialized_input = bincode::serialize(&input)?;
+        let output_bytes =
+            self.invoke_command(proto::Command::ImportEncryptedBackup, &serialized_input)?;
+
+        decode_output(&output_bytes)
+            .with_context(|| "Failed to deserialize ImportEncryptedBackupOutput")
+    }
+
+    /// The Solana account at `hd_path` (`m/44'/501'/account'/0'`).
+    pub fn derive_solana_address(
+        &mut self,
+        wallet_id: uuid::Uuid,
+        hd_path: &str,
+        passkey_assertion: Option<proto::PasskeyAssertion>,
+    ) -> Result<proto::DeriveSolanaAddressOutput> {
+        let input = proto::DeriveSolanaAddressInput {
+            wallet_id,
+            hd_path: hd_path.to_string(),
+            passkey_assertion,
+        };
+
+        let serialized_input = bincode::serialize(&input)?;
+        let output_bytes =
+            self.invoke_command(proto::Command::DeriveSolanaAddress, &serialized_input)?;
+
+        decode_output(&output_bytes)
+            .with_context(|| "Failed to deserialize DeriveSolanaAddressOutput")
+    }
 }
 
 // ========================================
 // TeeHandle — persistent session via dedicated TEE thread
 // ========================================
 
+/// Scheduling lane for a TEE command. Interactive = a user is waiting on the
+/// HTTP response (signing, passkey flows); Batch = background or maintenance
+/// work that can wait behind them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    Interactive,
+    Batch,
+}
+
+impl Priority {
+    /// Default lane per command. Only known background/maintenance commands
+    /// are batch; anything new is interactive until classified.
+    pub fn for_command(command: proto::Command) -> Self {
+        match command {
+            proto::Command::DeriveAddressAuto
+            | proto::Command::WarmupCache
+            | proto::Command::ReadRollbackCounter
+            | proto::Command::GetAttestation
+            | proto::Command::TaStats
+            | proto::Command::CrashDumps
+            | proto::Command::GetCapabilities
+            | proto::Command::GetProtocolVersion
+            | proto::Command::SelfTest
+            | proto::Command::ScavengeStorage
+            | proto::Command::ThresholdPresignBegin
+            | proto::Command::ThresholdPresignFinish
+            | proto::Command::ThresholdStatus => Priority::Batch,
+            _ => Priority::Interactive,
+        }
+    }
+
+    /// Hint carried to the TA in p2.b, with this build's protocol version
+    /// (see `proto::wire::RequestHeader`).
+    #[cfg(not(feature = "soft-tee"))]
+    fn wire_hint(self) -> u32 {
+        let priority = match self {
+            Priority::Interactive => proto::PRIORITY_INTERACTIVE,
+            Priority::Batch => proto::PRIORITY_BATCH,
+        };
+        RequestHeader::new(priority).to_hint()
+    }
+}
+
 struct TeeCommand {
     command: proto::Command,
     input: Vec<u8>,
-    reply: tokio::sync::oneshot::Sender<Result<Vec<u8>>>,
+    priority: Priority,
+    reply: tokio::sync::oneshot::Sender<TeeReply>,
     /// T3 backpressure: when this command was enqueued. The worker drops it
     /// (without invoking the TA) if it has waited past MAX_QUEUE_WAIT_SECS.
     enqueued_at: Instant,
+    /// The caller's deadline, if the HTTP request carried one.
+    context: Option<RequestContext>,
+}
+
+/// Worker → caller: the TA's answer and when the worker ran it.
+struct TeeReply {
+    result: Result<Vec<u8>>,
+    /// Wall-clock start and end of the TA invoke; None if it never ran.
+    invoked: Option<(SystemTime, SystemTime)>,
+    /// The session was reopened and the command retried.
+    reconnected: bool,
+}
+
+impl TeeReply {
+    fn not_invoked(result: Result<Vec<u8>>) -> Self {
+        Self {
+            result,
+            invoked: None,
+            reconnected: false,
+        }
+    }
+}
+
+impl TeeCommand {
+    /// Nobody will read the reply: the caller's future was dropped (client
+    /// disconnected) or its deadline has passed.
+    fn abandoned(&self) -> bool {
+        self.reply.is_closed()
+            || matches!(self.context.as_ref().and_then(|c| c.deadline), Some(d) if Instant::now() >= d)
+    }
+}
+
+/// Request header: how long the caller will wait, in milliseconds.
+pub const TIMEOUT_HEADER: &str = "x-request-timeout-ms";
+/// Request/response header: id tying a reply to the CA's log lines.
+pub const CORRELATION_HEADER: &str = "x-correlation-id";
+
+/// Per-request deadline and correlation id. The HTTP layer wraps each request
+/// future in [`RequestContext::scope`]; `TeeHandle::call` reads it, so a TEE
+/// wait never outlives the caller that asked for it.
+#[derive(Clone, Debug)]
+pub struct RequestContext {
+    pub correlation_id: String,
+    /// None when the client sent no timeout; TEE_CALL_TIMEOUT_SECS still applies.
+    pub deadline: Option<Instant>,
+    /// Span that TEE calls made for this request are exported under.
+    pub trace: Option<otel::TraceContext>,
+}
+
+tokio::task_local! {
+    static REQUEST_CONTEXT: RequestContext;
+}
+
+impl RequestContext {
+    /// From the [`TIMEOUT_HEADER`] and [`CORRELATION_HEADER`] values. A timeout
+    /// that does not parse is ignored. The correlation id is echoed into logs and
+    /// headers, so anything but 1-64 characters of `[A-Za-z0-9._-]` is replaced
+    /// by a random one.
+    pub fn from_headers(timeout_ms: Option<&str>, correlation_id: Option<&str>) -> Self {
+        let deadline = timeout_ms
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .map(|ms| Instant::now() + std::time::Duration::from_millis(ms));
+        let correlation_id = match correlation_id {
+            Some(id)
+                if !id.is_empty()
+                    && id.len() <= 64
+                    && id
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || "._-".contains(c)) =>
+            {
+                id.to_string()
+            }
+            _ => {
+                use rand::RngCore;
+                let mut id = [0u8; 8];
+                rand::rngs::OsRng.fill_bytes(&mut id);
+                hex::encode(id)
+            }
+        };
+        Self {
+            correlation_id,
+            deadline,
+            trace: None,
+        }
+    }
+
+    /// Run `fut` with this context visible to every TEE call it makes.
+    pub async fn scope<F: std::future::Future>(self, fut: F) -> F::Output {
+        REQUEST_CONTEXT.scope(self, fut).await
+    }
+
+    /// Run `fut` as a child span of the current request's span; the TEE calls
+    /// it makes are exported under that child.
+    pub async fn in_span<T, F>(name: &'static str, fut: F) -> Result<T>
+    where
+        F: std::future::Future<Output = Result<T>>,
+    {
+        let ctx = Self::current();
+        let mut span = otel::Span::start(
+            name,
+            otel::SpanKind::Internal,
+            ctx.as_ref().and_then(|c| c.trace),
+        );
+        let result = match ctx {
+            Some(mut ctx) => {
+                ctx.trace = Some(span.context());
+                ctx.scope(fut).await
+            }
+            None => fut.await,
+        };
+        span.record_result(&result);
+        result
+    }
+
+    /// The context of the request being served, if any.
+    pub fn current() -> Option<Self> {
+        REQUEST_CONTEXT.try_with(|c| c.clone()).ok()
+    }
 }
 
 // ── T3 queue backpressure ──
@@ -356,6 +758,63 @@ struct TeeCommand {
 /// single worker: at ~80 warm-sign/s, 32 deep ≈ <0.5s drain — honest backpressure.
 const MAX_QUEUE_DEPTH: usize = 32;
 
+/// Slots of MAX_QUEUE_DEPTH that only interactive commands may take. The batch
+/// lane fast-fails at MAX_QUEUE_DEPTH - INTERACTIVE_RESERVED, so a saturating
+/// batch job can never fill the queue in front of a user signing.
+const INTERACTIVE_RESERVED: usize = 8;
+
+/// The two lanes feeding the single TEE worker. The worker always drains the
+/// interactive lane first; batch runs only when no interactive command waits.
+struct Lanes {
+    queues: Mutex<(VecDeque<TeeCommand>, VecDeque<TeeCommand>)>,
+    ready: Condvar,
+    /// Cleared when the worker thread exits (panic included).
+    worker_alive: AtomicBool,
+}
+
+impl Lanes {
+    fn new() -> Self {
+        Self {
+            queues: Mutex::new((VecDeque::new(), VecDeque::new())),
+            ready: Condvar::new(),
+            worker_alive: AtomicBool::new(true),
+        }
+    }
+
+    fn push(&self, cmd: TeeCommand) -> Result<()> {
+        if !self.worker_alive.load(Ordering::SeqCst) {
+            return Err(anyhow::anyhow!("TEE worker thread has exited"));
+        }
+        let mut q = self.queues.lock().unwrap();
+        match cmd.priority {
+            Priority::Interactive => q.0.push_back(cmd),
+            Priority::Batch => q.1.push_back(cmd),
+        }
+        self.ready.notify_one();
+        Ok(())
+    }
+
+    fn pop_blocking(&self) -> TeeCommand {
+        let mut q = self.queues.lock().unwrap();
+        loop {
+            if let Some(cmd) = q.0.pop_front().or_else(|| q.1.pop_front()) {
+                return cmd;
+            }
+            q = self.ready.wait(q).unwrap();
+        }
+    }
+}
+
+/// Marks the lanes dead when the worker thread unwinds or returns, so callers
+/// fail fast instead of waiting out the call timeout.
+struct WorkerAliveGuard(Arc<Lanes>);
+
+impl Drop for WorkerAliveGuard {
+    fn drop(&mut self) {
+        self.0.worker_alive.store(false, Ordering::SeqCst);
+    }
+}
+
 /// A command that has waited longer than this is dropped by the worker BEFORE
 /// invoking the TA: the caller has almost certainly moved on, so spending a
 /// serial TA slot on it only delays live requests. Kept below
@@ -445,6 +904,33 @@ impl CircuitBreaker {
     }
 }
 
+/// Holds one accepted call in `pending` (and `pending_batch`) until dropped, so
+/// every exit path releases it: reply, timeout, enqueue failure, or the
+/// caller's future being cancelled.
+struct LaneSlot<'a> {
+    handle: &'a TeeHandle,
+    priority: Priority,
+}
+
+impl<'a> LaneSlot<'a> {
+    fn acquire(handle: &'a TeeHandle, priority: Priority) -> Self {
+        handle.pending.fetch_add(1, Ordering::SeqCst);
+        if priority == Priority::Batch {
+            handle.pending_batch.fetch_add(1, Ordering::SeqCst);
+        }
+        Self { handle, priority }
+    }
+}
+
+impl Drop for LaneSlot<'_> {
+    fn drop(&mut self) {
+        self.handle.pending.fetch_sub(1, Ordering::SeqCst);
+        if self.priority == Priority::Batch {
+            self.handle.pending_batch.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+}
+
 /// Cloneable async handle to a single long-lived TEE session.
 /// All TEE calls are serialised through one worker thread, avoiding the
 /// ~4.4s open_session overhead on every request.
@@ -453,21 +939,52 @@ impl CircuitBreaker {
 /// requests for 30s to prevent cascading crashes. Auto-recovers.
 #[derive(Clone)]
 pub struct TeeHandle {
-    tx: std::sync::mpsc::Sender<TeeCommand>,
+    lanes: Arc<Lanes>,
     pending: Arc<AtomicUsize>,
+    /// Batch-lane share of `pending`.
+    pending_batch: Arc<AtomicUsize>,
     cb: Arc<CircuitBreaker>,
+    /// Set when the installed TA failed its release check; no session is
+    /// ever opened and every command fails with this reason.
+    refused: Option<Arc<str>>,
+    /// Set while the device health check has this node quarantined. Unlike
+    /// `refused` the session stays open: inspection commands still run, so
+    /// the next check can clear it, but nothing that uses a key does.
+    quarantine: Arc<std::sync::RwLock<Option<Arc<str>>>>,
+    /// Off unless `enable_response_cache` is called (kms-api does; kms-admin
+    /// always asks the TA).
+    cache: Arc<ResponseCache>,
+}
+
+/// What a quarantined node still sends to its TA: the health check itself
+/// and the read-only views an operator needs to find out what went wrong.
+pub fn runs_in_quarantine(command: proto::Command) -> bool {
+    matches!(
+        command,
+        proto::Command::SelfTest
+            | proto::Command::GetAttestation
+            | proto::Command::TaStats
+            | proto::Command::CrashDumps
+            | proto::Command::GetCapabilities
+            | proto::Command::GetProtocolVersion
+            | proto::Command::ReadRollbackCounter
+            | proto::Command::GetTamperStatus
+    )
 }
 
 impl TeeHandle {
     /// Spawn the TEE worker thread and return a handle.
     /// Panics if the initial Context / Session cannot be created.
     pub fn new() -> Self {
-        let (tx, rx) = std::sync::mpsc::channel::<TeeCommand>();
+        let lanes = Arc::new(Lanes::new());
         let pending = Arc::new(AtomicUsize::new(0));
+        let pending_batch = Arc::new(AtomicUsize::new(0));
         let cb = Arc::new(CircuitBreaker::new());
 
+        let worker_lanes = lanes.clone();
         std::thread::spawn(move || {
-            tee_worker_loop(rx);
+            let _alive = WorkerAliveGuard(worker_lanes.clone());
+            tee_worker_loop(&worker_lanes);
         });
 
         println!("🔗 TeeHandle: worker thread spawned, session will be opened on first command");
@@ -476,7 +993,53 @@ impl TeeHandle {
             CB_THRESHOLD, CB_RECOVERY_SECS
         );
 
-        Self { tx, pending, cb }
+        println!(
+            "🚦 TEE lanes: {} slots, {} reserved for interactive commands",
+            MAX_QUEUE_DEPTH, INTERACTIVE_RESERVED
+        );
+
+        Self {
+            lanes,
+            pending,
+            pending_batch,
+            cb,
+            refused: None,
+            quarantine: Arc::new(std::sync::RwLock::new(None)),
+            cache: Arc::new(ResponseCache::new(None)),
+        }
+    }
+
+    /// Serve repeated reads (`call_cached`) from memory. None = off.
+    pub fn enable_response_cache(&mut self, config: Option<CacheConfig>) {
+        self.cache = Arc::new(ResponseCache::new(config));
+    }
+
+    pub fn response_cache_stats(&self) -> CacheStats {
+        self.cache.stats()
+    }
+
+    /// Refuse every command from now on. The worker opens its session on the
+    /// first command, so a refused TA image is never loaded.
+    pub fn refuse(&mut self, reason: String) {
+        self.refused = Some(reason.into());
+    }
+
+    /// Drain this node: from now on only `runs_in_quarantine` commands reach
+    /// the TA. Shared by every clone of the handle.
+    pub fn quarantine(&self, reason: &str) {
+        *self.quarantine.write().unwrap_or_else(|e| e.into_inner()) = Some(reason.into());
+    }
+
+    pub fn release_quarantine(&self) {
+        *self.quarantine.write().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+
+    pub fn quarantine_reason(&self) -> Option<String> {
+        self.quarantine
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_deref()
+            .map(str::to_string)
     }
 
     /// Number of commands currently queued (for QueueStatus).
@@ -484,6 +1047,11 @@ impl TeeHandle {
         self.pending.load(Ordering::SeqCst)
     }
 
+    /// Batch-lane commands currently queued (subset of `pending_count`).
+    pub fn pending_batch_count(&self) -> usize {
+        self.pending_batch.load(Ordering::SeqCst)
+    }
+
     /// Circuit breaker status for diagnostics.
     pub fn circuit_breaker_status(&self) -> (bool, usize) {
         (self.cb.is_open(), self.cb.failure_count())
@@ -497,59 +1065,186 @@ impl TeeHandle {
     const TEE_CALL_TIMEOUT_SECS: u64 = 30;
 
     async fn call(&self, command: proto::Command, input: Vec<u8>) -> Result<Vec<u8>> {
+        self.call_in(command, Priority::for_command(command), input)
+            .await
+    }
+
+    /// `call` for a read whose answer only the TA can change. A quarantined
+    /// or refused handle bypasses the cache so it refuses as `call` would.
+    async fn call_cached(
+        &self,
+        command: proto::Command,
+        wallet: Option<uuid::Uuid>,
+        input: Vec<u8>,
+    ) -> Result<Vec<u8>> {
+        let usable = self.refused.is_none() && self.quarantine_reason().is_none();
+        if usable {
+            if let Some(out) = self.cache.get(wallet, command, &input) {
+                return Ok(out);
+            }
+        }
+        let epoch = self.cache.epoch();
+        let out = self.call(command, input.clone()).await?;
+        if usable {
+            self.cache.put(wallet, command, input, out.clone(), epoch);
+        }
+        Ok(out)
+    }
+
+    /// `call` for a command that changes what `call_cached` may hold for
+    /// `wallet`. Invalidates on error too: a command that timed out here may
+    /// still have run in the TA.
+    async fn call_invalidating(
+        &self,
+        command: proto::Command,
+        wallet: Option<uuid::Uuid>,
+        input: Vec<u8>,
+    ) -> Result<Vec<u8>> {
+        let result = self.call(command, input).await;
+        self.cache.invalidate(wallet);
+        result
+    }
+
+    /// `call` in a lane other than the command's default, for bulk runs of
+    /// a command that is normally interactive.
+    async fn call_in(
+        &self,
+        command: proto::Command,
+        priority: Priority,
+        input: Vec<u8>,
+    ) -> Result<Vec<u8>> {
+        if let Some(reason) = &self.refused {
+            return Err(anyhow::anyhow!("TA refused: {}", reason));
+        }
+        if !runs_in_quarantine(command) {
+            if let Some(reason) = self.quarantine_reason() {
+                return Err(anyhow::anyhow!(
+                    "device quarantined: {:?} not sent ({})",
+                    command,
+                    reason
+                ));
+            }
+        }
         // Circuit breaker: reject immediately if TA is repeatedly failing
         self.cb.check()?;
 
+        // Wait no longer than the caller will: its deadline, capped by our own.
+        let context = RequestContext::current();
+        let correlation_id = context
+            .as_ref()
+            .map_or("-", |c| c.correlation_id.as_str())
+            .to_string();
+        let cap = std::time::Duration::from_secs(TEE_CALL_TIMEOUT_SECS);
+        let wait = match context.as_ref().and_then(|c| c.deadline) {
+            Some(deadline) => deadline.saturating_duration_since(Instant::now()).min(cap),
+            None => cap,
+        };
+        if wait.is_zero() {
+            return Err(anyhow::anyhow!(
+                "TEE deadline exceeded: {:?} not sent, the caller's deadline has passed \
+                 (correlation id {})",
+                command,
+                correlation_id
+            ));
+        }
+
         // T3: bounded queue. Fast-fail with 429 rather than enqueue behind a
         // backlog that would only time out. Checked before the counter bump so
         // MAX_QUEUE_DEPTH is the true ceiling of accepted-but-unfinished work.
+        // Batch stops short of the interactive reservation.
         let depth = self.pending.load(Ordering::SeqCst);
-        if depth >= MAX_QUEUE_DEPTH {
+        let limit = match priority {
+            Priority::Interactive => MAX_QUEUE_DEPTH,
+            Priority::Batch => MAX_QUEUE_DEPTH - INTERACTIVE_RESERVED,
+        };
+        if depth >= limit {
             return Err(anyhow::anyhow!(
-                "TEE queue full: {} in-flight (max {}) — retry shortly",
+                "TEE queue full: {} in-flight (max {} for {:?}) — retry shortly",
                 depth,
-                MAX_QUEUE_DEPTH
+                limit,
+                priority
             ));
         }
 
-        self.pending.fetch_add(1, Ordering::SeqCst);
+        let _lane = LaneSlot::acquire(self, priority);
+        let parent = context.as_ref().and_then(|c| c.trace);
+        let mut checkout =
+            otel::Span::start("tee.session.checkout", otel::SpanKind::Internal, parent);
+        checkout.attr("kms.ta.command", format!("{:?}", command));
+        checkout.attr("kms.ta.priority", format!("{:?}", priority));
+        checkout.attr("kms.ta.queue_depth", depth);
+        let input_bytes = input.len();
         let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
-        self.tx
-            .send(TeeCommand {
-                command,
-                input,
-                reply: reply_tx,
-                enqueued_at: Instant::now(),
-            })
-            .map_err(|_| anyhow::anyhow!("TEE worker thread has exited"))?;
+        self.lanes.push(TeeCommand {
+            command,
+            input,
+            priority,
+            reply: reply_tx,
+            enqueued_at: Instant::now(),
+            context,
+        })?;
         // P0-1: bound the wait. The worker itself cannot be interrupted (the
         // TA invoke is a blocking syscall), but the HTTP caller must not hang
         // forever — and a hung TA must eventually open the circuit breaker.
-        let result = match tokio::time::timeout(
-            std::time::Duration::from_secs(TEE_CALL_TIMEOUT_SECS),
-            reply_rx,
-        )
-        .await
-        {
-            Ok(inner) => {
-                self.pending.fetch_sub(1, Ordering::SeqCst);
-                inner.map_err(|_| anyhow::anyhow!("TEE worker dropped reply channel"))?
-            }
+        let reply = match tokio::time::timeout(wait, reply_rx).await {
+            Ok(inner) => inner.map_err(|_| anyhow::anyhow!("TEE worker dropped reply channel"))?,
             Err(_elapsed) => {
+                checkout.set_error("no TEE answer before the deadline");
                 // The command may still be executing in the worker; we only
-                // stop waiting. Decrement pending so the counter doesn't leak
-                // (the worker's eventual reply_tx.send() fails silently).
-                self.pending.fetch_sub(1, Ordering::SeqCst);
+                // stop waiting. The LaneSlot guard releases pending so the
+                // counter doesn't leak (the worker's eventual reply_tx.send()
+                // fails silently). A queued command is skipped by the worker.
+                if wait < cap {
+                    // The caller's deadline, not a hung TA: no breaker failure.
+                    return Err(anyhow::anyhow!(
+                        "TEE deadline exceeded: {:?} not answered within the caller's {}ms — \
+                         outcome unknown (correlation id {})",
+                        command,
+                        wait.as_millis(),
+                        correlation_id
+                    ));
+                }
                 self.cb.record_failure();
                 return Err(anyhow::anyhow!(
                     "TEE call timeout: {:?} did not complete within {}s — outcome unknown, \
-                     TA may still be processing (circuit breaker failure recorded)",
+                     TA may still be processing (circuit breaker failure recorded, \
+                     correlation id {})",
                     command,
-                    TEE_CALL_TIMEOUT_SECS
+                    TEE_CALL_TIMEOUT_SECS,
+                    correlation_id
                 ));
             }
         };
 
+        let result = match reply.invoked {
+            Some((start, end)) => {
+                checkout.attr("kms.ta.session_reconnect", reply.reconnected);
+                checkout.end_at(start);
+                let mut span =
+                    otel::Span::start_at("ta.invoke", otel::SpanKind::Client, parent, start);
+                span.attr("kms.ta.command", format!("{:?}", command));
+                span.attr("kms.ta.command_id", command as u32);
+                span.attr("kms.ta.input_bytes", input_bytes);
+                span.attr(
+                    "kms.ta.latency_ms",
+                    end.duration_since(start).unwrap_or_default().as_millis() as i64,
+                );
+                match &reply.result {
+                    Ok(out) => span.attr("kms.ta.output_bytes", out.len()),
+                    // TA messages can quote the input; the span only says it failed.
+                    Err(_) => span.set_error("TA command failed"),
+                }
+                span.end_at(end);
+                reply.result
+            }
+            None => {
+                if reply.result.is_err() {
+                    checkout.set_error("not invoked");
+                }
+                reply.result
+            }
+        };
+
         // Update circuit breaker based on result
         match &result {
             Ok(_) => self.cb.record_success(),
@@ -570,7 +1265,29 @@ impl TeeHandle {
         result
     }
 
-    pub async fn create_wallet(&self, passkey_pubkey: &[u8]) -> Result<uuid::Uuid> {
+    /// `prf_output`: optional WebAuthn PRF result the TA mixes into the entropy.
+    pub async fn create_wallet(
+        &self,
+        passkey_pubkey: &[u8],
+        prf_output: Option<[u8; 32]>,
+    ) -> Result<uuid::Uuid> {
+        self.create_wallet_in(passkey_pubkey, prf_output, Priority::Interactive)
+            .await
+    }
+
+    /// CreateWallet in the batch lane, for bulk provisioning: nobody is
+    /// waiting on one wallet of a batch, and signing must not queue behind it.
+    pub async fn create_wallet_batch(&self, passkey_pubkey: &[u8]) -> Result<uuid::Uuid> {
+        self.create_wallet_in(passkey_pubkey, None, Priority::Batch)
+            .await
+    }
+
+    async fn create_wallet_in(
+        &self,
+        passkey_pubkey: &[u8],
+        prf_output: Option<[u8; 32]>,
+        priority: Priority,
+    ) -> Result<uuid::Uuid> {
         // Generate 48 bytes of entropy from the OS CSPRNG (/dev/urandom-backed OsRng).
         // Passed to the TA so it can skip TEE_GenerateRandom() and avoid CAAM TRNG hangs.
         // This is safe: OsRng is cryptographically secure.  The entropy never leaves the TA.
@@ -581,11 +1298,14 @@ impl TeeHandle {
         let input = bincode::serialize(&proto::CreateWalletInput {
             passkey_pubkey: passkey_pubkey.to_vec(),
             entropy_seed: Some(seed),
+            prf_output,
         })
         .context("Failed to serialize CreateWalletInput")?;
-        let out = self.call(proto::Command::CreateWallet, input).await?;
+        let out = self
+            .call_in(proto::Command::CreateWallet, priority, input)
+            .await?;
         let output: proto::CreateWalletOutput =
-            bincode::deserialize(&out).context("Failed to deserialize CreateWalletOutput")?;
+            decode_output(&out).context("Failed to deserialize CreateWalletOutput")?;
         Ok(output.wallet_id)
     }
 
@@ -593,14 +1313,16 @@ impl TeeHandle {
         &self,
         wallet_id: uuid::Uuid,
         passkey_assertion: Option<proto::PasskeyAssertion>,
-    ) -> Result<()> {
+    ) -> Result<Option<DeletionProof>> {
         let input = bincode::serialize(&proto::RemoveWalletInput {
             wallet_id,
             passkey_assertion,
         })
         .context("Failed to serialize RemoveWalletInput")?;
-        self.call(proto::Command::RemoveWallet, input).await?;
-        Ok(())
+        let out = self
+            .call_invalidating(proto::Command::RemoveWallet, Some(wallet_id), input)
+            .await?;
+        decode_remove_wallet_output(&out)
     }
 
     /// Issue #49: request a fresh one-time WebAuthn challenge nonce from the TA.
@@ -612,7 +1334,7 @@ impl TeeHandle {
             .context("Failed to serialize GetChallengeInput")?;
         let out = self.call(proto::Command::GetChallenge, input).await?;
         let output: proto::GetChallengeOutput =
-            bincode::deserialize(&out).context("Failed to deserialize GetChallengeOutput")?;
+            decode_output(&out).context("Failed to deserialize GetChallengeOutput")?;
         Ok(output.nonce)
     }
 
@@ -624,7 +1346,7 @@ impl TeeHandle {
             .context("Failed to serialize BlsGenKeyInput")?;
         let out = self.call(proto::Command::BlsGenKey, input).await?;
         let output: proto::BlsGenKeyOutput =
-            bincode::deserialize(&out).context("Failed to deserialize BlsGenKeyOutput")?;
+            decode_output(&out).context("Failed to deserialize BlsGenKeyOutput")?;
         anyhow::ensure!(
             output.public_key.len() == 48,
             "BLS pubkey length invalid (expected 48, got {})",
@@ -643,7 +1365,7 @@ impl TeeHandle {
             .context("Failed to serialize BlsSignInput")?;
         let out = self.call(proto::Command::BlsSign, input).await?;
         let output: proto::BlsSignOutput =
-            bincode::deserialize(&out).context("Failed to deserialize BlsSignOutput")?;
+            decode_output(&out).context("Failed to deserialize BlsSignOutput")?;
         anyhow::ensure!(
             output.signature.len() == 256 && output.signature_compact.len() == 96,
             "BLS signature length invalid (EIP-2537 {} want 256, compact {} want 96)",
@@ -661,7 +1383,7 @@ impl TeeHandle {
             .context("Failed to serialize BlsPopSignInput")?;
         let out = self.call(proto::Command::BlsPopSign, input).await?;
         let output: proto::BlsPopSignOutput =
-            bincode::deserialize(&out).context("Failed to deserialize BlsPopSignOutput")?;
+            decode_output(&out).context("Failed to deserialize BlsPopSignOutput")?;
         anyhow::ensure!(
             output.public_key.len() == 128
                 && output.pop_point.len() == 256
@@ -674,15 +1396,40 @@ impl TeeHandle {
         Ok((output.public_key, output.pop_point, output.pop_signature))
     }
 
-    /// Return the sealed BLS key's 48B compressed G1 public key.
-    pub async fn bls_pubkey(&self, key_id: uuid::Uuid) -> Result<Vec<u8>> {
-        let input = bincode::serialize(&proto::BlsPubKeyInput { key_id })
-            .context("Failed to serialize BlsPubKeyInput")?;
-        let out = self.call(proto::Command::BlsPubKey, input).await?;
-        let output: proto::BlsPubKeyOutput =
-            bincode::deserialize(&out).context("Failed to deserialize BlsPubKeyOutput")?;
-        Ok(output.public_key)
-    }
+    /// Slashing-protected beacon signing. Returns (EIP-2537 G2 256B, compact G2 96B);
+    /// the TA refuses double/surround votes and repeat block proposals.
+    pub async fn bls_sign_attestation(
+        &self,
+        input: proto::BlsSignAttestationInput,
+    ) -> Result<(Vec<u8>, Vec<u8>)> {
+        let input =
+            bincode::serialize(&input).context("Failed to serialize BlsSignAttestationInput")?;
+        let out = self.call(proto::Command::BlsSignAttestation, input).await?;
+        let output: proto::BlsSignOutput =
+            decode_output(&out).context("Failed to deserialize BlsSignOutput")?;
+        Ok((output.signature, output.signature_compact))
+    }
+
+    pub async fn bls_sign_block(
+        &self,
+        input: proto::BlsSignBlockInput,
+    ) -> Result<(Vec<u8>, Vec<u8>)> {
+        let input = bincode::serialize(&input).context("Failed to serialize BlsSignBlockInput")?;
+        let out = self.call(proto::Command::BlsSignBlock, input).await?;
+        let output: proto::BlsSignOutput =
+            decode_output(&out).context("Failed to deserialize BlsSignOutput")?;
+        Ok((output.signature, output.signature_compact))
+    }
+
+    /// Return the sealed BLS key's 48B compressed G1 public key.
+    pub async fn bls_pubkey(&self, key_id: uuid::Uuid) -> Result<Vec<u8>> {
+        let input = bincode::serialize(&proto::BlsPubKeyInput { key_id })
+            .context("Failed to serialize BlsPubKeyInput")?;
+        let out = self.call(proto::Command::BlsPubKey, input).await?;
+        let output: proto::BlsPubKeyOutput =
+            decode_output(&out).context("Failed to deserialize BlsPubKeyOutput")?;
+        Ok(output.public_key)
+    }
 
     /// Remove the sealed BLS singleton (delete every stored BLS key). Recovers from
     /// an orphaned key whose key_id was lost, or rotates. Returns the count removed.
@@ -691,7 +1438,7 @@ impl TeeHandle {
             .context("Failed to serialize BlsRemoveInput")?;
         let out = self.call(proto::Command::BlsRemove, input).await?;
         let output: proto::BlsRemoveOutput =
-            bincode::deserialize(&out).context("Failed to deserialize BlsRemoveOutput")?;
+            decode_output(&out).context("Failed to deserialize BlsRemoveOutput")?;
         Ok(output.removed)
     }
 
@@ -704,7 +1451,7 @@ impl TeeHandle {
             .context("Failed to serialize KeeperGenKeyInput")?;
         let out = self.call(proto::Command::KeeperGenKey, input).await?;
         let output: proto::KeeperGenKeyOutput =
-            bincode::deserialize(&out).context("Failed to deserialize KeeperGenKeyOutput")?;
+            decode_output(&out).context("Failed to deserialize KeeperGenKeyOutput")?;
         anyhow::ensure!(
             output.public_key.len() == 65 && output.public_key[0] == 0x04,
             "keeper pubkey invalid (expected 65B uncompressed 0x04.., got {}B)",
@@ -720,7 +1467,7 @@ impl TeeHandle {
             .context("Failed to serialize KeeperSignInput")?;
         let out = self.call(proto::Command::KeeperSign, input).await?;
         let output: proto::KeeperSignOutput =
-            bincode::deserialize(&out).context("Failed to deserialize KeeperSignOutput")?;
+            decode_output(&out).context("Failed to deserialize KeeperSignOutput")?;
         anyhow::ensure!(
             output.signature.len() == 65,
             "keeper signature length invalid (expected 65, got {})",
@@ -735,18 +1482,19 @@ impl TeeHandle {
             .context("Failed to serialize KeeperPubKeyInput")?;
         let out = self.call(proto::Command::KeeperPubKey, input).await?;
         let output: proto::KeeperPubKeyOutput =
-            bincode::deserialize(&out).context("Failed to deserialize KeeperPubKeyOutput")?;
+            decode_output(&out).context("Failed to deserialize KeeperPubKeyOutput")?;
         Ok((output.public_key, output.address))
     }
 
     /// Force-remove a gap key from TEE secure storage.
-    /// Only called when `api_server` has confirmed the wallet's passkey_pubkey
+    /// Only called when `service` has confirmed the wallet's passkey_pubkey
     /// is not a valid P-256 curve point. Requires TA v0.20.0+ (ForceRemoveWallet = 23).
     /// On older TAs returns an error which the caller handles gracefully.
     pub async fn force_remove_wallet(&self, wallet_id: uuid::Uuid) -> Result<()> {
         let input = bincode::serialize(&proto::ForceRemoveWalletInput { wallet_id })
             .context("Failed to serialize ForceRemoveWalletInput")?;
-        self.call(proto::Command::ForceRemoveWallet, input).await?;
+        self.call_invalidating(proto::Command::ForceRemoveWallet, Some(wallet_id), input)
+            .await?;
         Ok(())
     }
 
@@ -760,51 +1508,109 @@ impl TeeHandle {
             wallet_id,
             hd_path: hd_path.to_string(),
             passkey_assertion,
+            chain: proto::chain::Chain::Ethereum,
         })
         .context("Failed to serialize DeriveAddressInput")?;
         let out = self.call(proto::Command::DeriveAddress, input).await?;
         let output: proto::DeriveAddressOutput =
-            bincode::deserialize(&out).context("Failed to deserialize DeriveAddressOutput")?;
+            decode_output(&out).context("Failed to deserialize DeriveAddressOutput")?;
         Ok(output.address)
     }
 
+    /// The address `hd_path` gives on `chain`, in the chain's own notation.
+    /// A v1 TA ignores `chain` and derives under coin type 60; it also leaves
+    /// `encoded_address` empty, which is how a non-EVM answer from it is told
+    /// apart and refused instead of returned as the wrong account.
+    pub async fn derive_chain_address(
+        &self,
+        wallet_id: uuid::Uuid,
+        hd_path: &str,
+        chain: proto::chain::Chain,
+        passkey_assertion: Option<proto::PasskeyAssertion>,
+    ) -> Result<String> {
+        let input = bincode::serialize(&proto::DeriveAddressInput {
+            wallet_id,
+            hd_path: hd_path.to_string(),
+            passkey_assertion,
+            chain,
+        })
+        .context("Failed to serialize DeriveAddressInput")?;
+        let out = self.call(proto::Command::DeriveAddress, input).await?;
+        let output: proto::DeriveAddressOutput =
+            decode_output(&out).context("Failed to deserialize DeriveAddressOutput")?;
+        chain_address(chain, output)
+    }
+
     pub async fn sign_transaction(
         &self,
         wallet_id: uuid::Uuid,
         hd_path: &str,
         transaction: proto::EthTransaction,
         passkey_assertion: Option<proto::PasskeyAssertion>,
+        summary_abis: Vec<String>,
+        summary_committed: bool,
     ) -> Result<Vec<u8>> {
         let input = bincode::serialize(&proto::SignTransactionInput {
             wallet_id,
             hd_path: hd_path.to_string(),
             transaction,
             passkey_assertion,
+            summary_abis,
+            summary_committed,
         })
         .context("Failed to serialize SignTransactionInput")?;
         let out = self.call(proto::Command::SignTransaction, input).await?;
         let output: proto::SignTransactionOutput =
-            bincode::deserialize(&out).context("Failed to deserialize SignTransactionOutput")?;
+            decode_output(&out).context("Failed to deserialize SignTransactionOutput")?;
         Ok(output.signature)
     }
 
+    /// The broadcast-ready `0x02 ‖ rlp(...)`; the TA encodes and hashes it.
+    pub async fn sign_eip1559_transaction(
+        &self,
+        wallet_id: uuid::Uuid,
+        hd_path: &str,
+        transaction: proto::eip1559::Eip1559Transaction,
+        passkey_assertion: Option<proto::PasskeyAssertion>,
+        summary_abis: Vec<String>,
+        summary_committed: bool,
+    ) -> Result<Vec<u8>> {
+        let input = bincode::serialize(&proto::SignEip1559TransactionInput {
+            wallet_id,
+            hd_path: hd_path.to_string(),
+            transaction,
+            passkey_assertion,
+            summary_abis,
+            summary_committed,
+        })
+        .context("Failed to serialize SignEip1559TransactionInput")?;
+        let out = self
+            .call(proto::Command::SignEip1559Transaction, input)
+            .await?;
+        let output: proto::SignEip1559TransactionOutput =
+            decode_output(&out).context("Failed to deserialize SignEip1559TransactionOutput")?;
+        Ok(output.raw_transaction)
+    }
+
     pub async fn sign_message(
         &self,
         wallet_id: uuid::Uuid,
         hd_path: &str,
         message: &[u8],
         passkey_assertion: Option<proto::PasskeyAssertion>,
+        context: proto::SigningContext,
     ) -> Result<Vec<u8>> {
         let input = bincode::serialize(&proto::SignMessageInput {
             wallet_id,
             hd_path: hd_path.to_string(),
             message: message.to_vec(),
             passkey_assertion,
+            context,
         })
         .context("Failed to serialize SignMessageInput")?;
         let out = self.call(proto::Command::SignMessage, input).await?;
         let output: proto::SignMessageOutput =
-            bincode::deserialize(&out).context("Failed to deserialize SignMessageOutput")?;
+            decode_output(&out).context("Failed to deserialize SignMessageOutput")?;
         Ok(output.signature)
     }
 
@@ -814,17 +1620,21 @@ impl TeeHandle {
         hd_path: &str,
         hash: &[u8; 32],
         passkey_assertion: Option<proto::PasskeyAssertion>,
+        context: proto::SigningContext,
+        fee_quote: Option<proto::paymaster::FeeQuote>,
     ) -> Result<Vec<u8>> {
         let input = bincode::serialize(&proto::SignHashInput {
             wallet_id,
             hd_path: hd_path.to_string(),
             hash: *hash,
             passkey_assertion,
+            context,
+            fee_quote,
         })
         .context("Failed to serialize SignHashInput")?;
         let out = self.call(proto::Command::SignHash, input).await?;
         let output: proto::SignHashOutput =
-            bincode::deserialize(&out).context("Failed to deserialize SignHashOutput")?;
+            decode_output(&out).context("Failed to deserialize SignHashOutput")?;
         Ok(output.signature)
     }
 
@@ -836,7 +1646,7 @@ impl TeeHandle {
             .context("Failed to serialize DeriveAddressAutoInput")?;
         let out = self.call(proto::Command::DeriveAddressAuto, input).await?;
         let output: proto::DeriveAddressAutoOutput =
-            bincode::deserialize(&out).context("Failed to deserialize DeriveAddressAutoOutput")?;
+            decode_output(&out).context("Failed to deserialize DeriveAddressAutoOutput")?;
         Ok((
             output.wallet_id,
             output.address,
@@ -865,7 +1675,7 @@ impl TeeHandle {
         .context("Failed to serialize VerifyPasskeyInput")?;
         let out = self.call(proto::Command::VerifyPasskey, input).await?;
         let output: proto::VerifyPasskeyOutput =
-            bincode::deserialize(&out).context("Failed to deserialize VerifyPasskeyOutput")?;
+            decode_output(&out).context("Failed to deserialize VerifyPasskeyOutput")?;
         Ok(output.valid)
     }
 
@@ -881,8 +1691,8 @@ impl TeeHandle {
             passkey_assertion,
         })?;
         let out = self.call(proto::Command::ExportPrivateKey, input).await?;
-        let output: proto::ExportPrivateKeyOutput = bincode::deserialize(&out)
-            .with_context(|| "Failed to deserialize ExportPrivateKeyOutput")?;
+        let output: proto::ExportPrivateKeyOutput =
+            decode_output(&out).with_context(|| "Failed to deserialize ExportPrivateKeyOutput")?;
         Ok(output.private_key)
     }
 
@@ -902,7 +1712,7 @@ impl TeeHandle {
         .context("Failed to serialize RegisterPasskeyTaInput")?;
         let out = self.call(proto::Command::RegisterPasskeyTa, input).await?;
         let output: proto::RegisterPasskeyTaOutput =
-            bincode::deserialize(&out).context("Failed to deserialize RegisterPasskeyTaOutput")?;
+            decode_output(&out).context("Failed to deserialize RegisterPasskeyTaOutput")?;
         Ok(output.registered)
     }
 
@@ -912,7 +1722,7 @@ impl TeeHandle {
             .context("Failed to serialize WarmupCacheInput")?;
         let out = self.call(proto::Command::WarmupCache, input).await?;
         let output: proto::WarmupCacheOutput =
-            bincode::deserialize(&out).context("Failed to deserialize WarmupCacheOutput")?;
+            decode_output(&out).context("Failed to deserialize WarmupCacheOutput")?;
         Ok(output.cache_size)
     }
 
@@ -925,6 +1735,7 @@ impl TeeHandle {
         passkey_assertion: Option<proto::PasskeyAssertion>,
         label: &str,
         is_refresh: bool,
+        refresh: Option<proto::AgentRefresh>,
     ) -> Result<proto::CreateAgentKeyOutput> {
         let input = bincode::serialize(&proto::CreateAgentKeyInput {
             wallet_id,
@@ -934,11 +1745,12 @@ impl TeeHandle {
             passkey_assertion,
             label: label.to_string(), // #115
             is_refresh,               // #115
+            refresh,
         })
         .context("Failed to serialize CreateAgentKeyInput")?;
         let out = self.call(proto::Command::CreateAgentKey, input).await?;
         let output: proto::CreateAgentKeyOutput =
-            bincode::deserialize(&out).context("Failed to deserialize CreateAgentKeyOutput")?;
+            decode_output(&out).context("Failed to deserialize CreateAgentKeyOutput")?;
         Ok(output)
     }
 
@@ -964,7 +1776,7 @@ impl TeeHandle {
         .context("Failed to serialize SignAgentUserOpInput")?;
         let out = self.call(proto::Command::SignAgentUserOp, input).await?;
         let output: proto::SignAgentUserOpOutput =
-            bincode::deserialize(&out).context("Failed to deserialize SignAgentUserOpOutput")?;
+            decode_output(&out).context("Failed to deserialize SignAgentUserOpOutput")?;
         Ok(output.signature)
     }
 
@@ -982,7 +1794,7 @@ impl TeeHandle {
         .context("Failed to serialize JwtHmacVerifyInput")?;
         let out = self.call(proto::Command::JwtHmacVerify, input).await?;
         let output: proto::JwtHmacVerifyOutput =
-            bincode::deserialize(&out).context("Failed to deserialize JwtHmacVerifyOutput")?;
+            decode_output(&out).context("Failed to deserialize JwtHmacVerifyOutput")?;
         Ok(output.valid)
     }
 
@@ -991,7 +1803,7 @@ impl TeeHandle {
             .context("Failed to serialize JwtRotateSecretInput")?;
         let out = self.call(proto::Command::JwtRotateSecret, input).await?;
         let output: proto::JwtRotateSecretOutput =
-            bincode::deserialize(&out).context("Failed to deserialize JwtRotateSecretOutput")?;
+            decode_output(&out).context("Failed to deserialize JwtRotateSecretOutput")?;
         Ok(output)
     }
 
@@ -1003,7 +1815,7 @@ impl TeeHandle {
             bincode::serialize(&input).context("Failed to serialize SignTypedDataInput")?;
         let out = self.call(proto::Command::SignTypedData, serialized).await?;
         let output: proto::SignTypedDataOutput =
-            bincode::deserialize(&out).context("Failed to deserialize SignTypedDataOutput")?;
+            decode_output(&out).context("Failed to deserialize SignTypedDataOutput")?;
         Ok(output)
     }
 
@@ -1017,7 +1829,7 @@ impl TeeHandle {
             .call(proto::Command::SignGrantSession, serialized)
             .await?;
         let output: proto::SignGrantSessionOutput =
-            bincode::deserialize(&out).context("Failed to deserialize SignGrantSessionOutput")?;
+            decode_output(&out).context("Failed to deserialize SignGrantSessionOutput")?;
         Ok(output)
     }
 
@@ -1030,8 +1842,8 @@ impl TeeHandle {
         let out = self
             .call(proto::Command::SignP256GrantSession, serialized)
             .await?;
-        let output: proto::SignP256GrantSessionOutput = bincode::deserialize(&out)
-            .context("Failed to deserialize SignP256GrantSessionOutput")?;
+        let output: proto::SignP256GrantSessionOutput =
+            decode_output(&out).context("Failed to deserialize SignP256GrantSessionOutput")?;
         Ok(output)
     }
 
@@ -1046,7 +1858,7 @@ impl TeeHandle {
             .context("Failed to serialize GetAttestationInput")?;
         let out = self.call(proto::Command::GetAttestation, input).await?;
         let output: proto::GetAttestationOutput =
-            bincode::deserialize(&out).context("Failed to deserialize GetAttestationOutput")?;
+            decode_output(&out).context("Failed to deserialize GetAttestationOutput")?;
         Ok(output)
     }
 
@@ -1057,11 +1869,47 @@ impl TeeHandle {
         let out = self
             .call(proto::Command::ReadRollbackCounter, input)
             .await?;
-        let output: proto::ReadRollbackCounterOutput = bincode::deserialize(&out)
-            .context("Failed to deserialize ReadRollbackCounterOutput")?;
+        let output: proto::ReadRollbackCounterOutput =
+            decode_output(&out).context("Failed to deserialize ReadRollbackCounterOutput")?;
         Ok(output.counter)
     }
 
+    /// Per-command latency histograms recorded by the TA dispatcher.
+    pub async fn ta_stats(&self) -> Result<proto::TaStatsOutput> {
+        let input = bincode::serialize(&proto::TaStatsInput {})
+            .context("Failed to serialize TaStatsInput")?;
+        let out = self.call(proto::Command::TaStats, input).await?;
+        decode_output(&out).context("Failed to deserialize TaStatsOutput")
+    }
+
+    /// The TA's known-answer tests; see `device_health`.
+    pub async fn self_test(&self) -> Result<proto::SelfTestOutput> {
+        let input = bincode::serialize(&proto::SelfTestInput {})
+            .context("Failed to serialize SelfTestInput")?;
+        let out = self.call(proto::Command::SelfTest, input).await?;
+        decode_output(&out).context("Failed to deserialize SelfTestOutput")
+    }
+
+    /// The TA's postmortem records of internal failures; `clear` empties them.
+    pub async fn crash_dumps(&self, clear: bool) -> Result<proto::CrashDumpsOutput> {
+        let input = bincode::serialize(&proto::CrashDumpsInput { clear })
+            .context("Failed to serialize CrashDumpsInput")?;
+        let out = self.call(proto::Command::CrashDumps, input).await?;
+        decode_output(&out).context("Failed to deserialize CrashDumpsOutput")
+    }
+
+    /// Orphaned secure-storage objects; `confirm` (an earlier report's
+    /// digest) deletes exactly that set.
+    pub async fn scavenge_storage(
+        &self,
+        confirm: Option<[u8; 32]>,
+    ) -> Result<proto::ScavengeStorageOutput> {
+        let input = bincode::serialize(&proto::ScavengeStorageInput { confirm })
+            .context("Failed to serialize ScavengeStorageInput")?;
+        let out = self.call(proto::Command::ScavengeStorage, input).await?;
+        decode_output(&out).context("Failed to deserialize ScavengeStorageOutput")
+    }
+
     pub async fn create_p256_session_key(
         &self,
         wallet_id: uuid::Uuid,
@@ -1084,8 +1932,8 @@ impl TeeHandle {
         let out = self
             .call(proto::Command::CreateP256SessionKey, input)
             .await?;
-        let output: proto::CreateP256SessionKeyOutput = bincode::deserialize(&out)
-            .context("Failed to deserialize CreateP256SessionKeyOutput")?;
+        let output: proto::CreateP256SessionKeyOutput =
+            decode_output(&out).context("Failed to deserialize CreateP256SessionKeyOutput")?;
         Ok(output)
     }
 
@@ -1111,7 +1959,7 @@ impl TeeHandle {
         .context("Failed to serialize SignP256UserOpInput")?;
         let out = self.call(proto::Command::SignP256UserOp, input).await?;
         let output: proto::SignP256UserOpOutput =
-            bincode::deserialize(&out).context("Failed to deserialize SignP256UserOpOutput")?;
+            decode_output(&out).context("Failed to deserialize SignP256UserOpOutput")?;
         Ok(output.signature)
     }
 
@@ -1130,23 +1978,936 @@ impl TeeHandle {
         let out = self
             .call(proto::Command::DeleteP256SessionKey, input)
             .await?;
-        let output: proto::DeleteP256SessionKeyOutput = bincode::deserialize(&out)
-            .context("Failed to deserialize DeleteP256SessionKeyOutput")?;
+        let output: proto::DeleteP256SessionKeyOutput =
+            decode_output(&out).context("Failed to deserialize DeleteP256SessionKeyOutput")?;
         Ok(output.deleted)
     }
+
+    pub async fn create_scoped_session_key(
+        &self,
+        input: proto::CreateScopedSessionKeyInput,
+    ) -> Result<proto::CreateScopedSessionKeyOutput> {
+        let serialized = bincode::serialize(&input)
+            .context("Failed to serialize CreateScopedSessionKeyInput")?;
+        let out = self
+            .call(proto::Command::CreateScopedSessionKey, serialized)
+            .await?;
+        let output: proto::CreateScopedSessionKeyOutput =
+            decode_output(&out).context("Failed to deserialize CreateScopedSessionKeyOutput")?;
+        Ok(output)
+    }
+
+    pub async fn sign_with_session_key(
+        &self,
+        input: proto::SignWithSessionKeyInput,
+    ) -> Result<Vec<u8>> {
+        let serialized =
+            bincode::serialize(&input).context("Failed to serialize SignWithSessionKeyInput")?;
+        let out = self
+            .call(proto::Command::SignWithSessionKey, serialized)
+            .await?;
+        let output: proto::SignWithSessionKeyOutput =
+            decode_output(&out).context("Failed to deserialize SignWithSessionKeyOutput")?;
+        Ok(output.signature)
+    }
+
+    /// Returns false if the index was already revoked (idempotent).
+    pub async fn revoke_scoped_session_key(
+        &self,
+        wallet_id: uuid::Uuid,
+        session_index: u32,
+        passkey_assertion: Option<proto::PasskeyAssertion>,
+    ) -> Result<bool> {
+        let input = bincode::serialize(&proto::RevokeScopedSessionKeyInput {
+            wallet_id,
+            session_index,
+            passkey_assertion,
+        })
+        .context("Failed to serialize RevokeScopedSessionKeyInput")?;
+        let out = self
+            .call(proto::Command::RevokeScopedSessionKey, input)
+            .await?;
+        let output: proto::RevokeScopedSessionKeyOutput =
+            decode_output(&out).context("Failed to deserialize RevokeScopedSessionKeyOutput")?;
+        Ok(output.revoked)
+    }
+
+    /// Returns the rule that was replaced.
+    pub async fn set_allowance_policy(
+        &self,
+        wallet_id: uuid::Uuid,
+        rule: proto::AllowanceRule,
+        passkey_assertion: Option<proto::PasskeyAssertion>,
+    ) -> Result<proto::AllowanceRule> {
+        let input = bincode::serialize(&proto::SetAllowancePolicyInput {
+            wallet_id,
+            rule,
+            passkey_assertion,
+        })
+        .context("Failed to serialize SetAllowancePolicyInput")?;
+        let out = self.call(proto::Command::SetAllowancePolicy, input).await?;
+        let output: proto::SetAllowancePolicyOutput =
+            decode_output(&out).context("Failed to deserialize SetAllowancePolicyOutput")?;
+        Ok(output.previous)
+    }
+
+    /// Returns the summary the TA armed the override for.
+    pub async fn confirm_allowance_override(
+        &self,
+        wallet_id: uuid::Uuid,
+        transaction: proto::EthTransaction,
+        summary_abis: Vec<String>,
+        passkey_assertion: Option<proto::PasskeyAssertion>,
+    ) -> Result<String> {
+        let input = bincode::serialize(&proto::ConfirmAllowanceOverrideInput {
+            wallet_id,
+            transaction,
+            summary_abis,
+            passkey_assertion,
+        })
+        .context("Failed to serialize ConfirmAllowanceOverrideInput")?;
+        let out = self
+            .call(proto::Command::ConfirmAllowanceOverride, input)
+            .await?;
+        let output: proto::ConfirmAllowanceOverrideOutput =
+            decode_output(&out).context("Failed to deserialize ConfirmAllowanceOverrideOutput")?;
+        Ok(output.summary)
+    }
+
+    /// Returns the previous allow-list.
+    pub async fn set_spender_allow_list(
+        &self,
+        wallet_id: uuid::Uuid,
+        spenders: Vec<[u8; 20]>,
+        passkey_assertion: Option<proto::PasskeyAssertion>,
+    ) -> Result<Vec<[u8; 20]>> {
+        let input = bincode::serialize(&proto::SetSpenderAllowListInput {
+            wallet_id,
+            spenders,
+            passkey_assertion,
+        })
+        .context("Failed to serialize SetSpenderAllowListInput")?;
+        let out = self
+            .call(proto::Command::SetSpenderAllowList, input)
+            .await?;
+        let output: proto::SetSpenderAllowListOutput =
+            decode_output(&out).context("Failed to deserialize SetSpenderAllowListOutput")?;
+        Ok(output.previous)
+    }
+
+    /// Read the wallet's derivation-path policy (`set = None`) or replace it.
+    pub async fn path_policy(
+        &self,
+        wallet_id: uuid::Uuid,
+        set: Option<Vec<proto::hd_path::PathPattern>>,
+        passkey_assertion: Option<proto::PasskeyAssertion>,
+    ) -> Result<proto::PathPolicyOutput> {
+        let setting = set.is_some();
+        let input = bincode::serialize(&proto::PathPolicyInput {
+            wallet_id,
+            set,
+            passkey_assertion,
+        })
+        .context("Failed to serialize PathPolicyInput")?;
+        let out = if setting {
+            self.call_invalidating(proto::Command::PathPolicy, Some(wallet_id), input)
+                .await?
+        } else {
+            self.call_cached(proto::Command::PathPolicy, Some(wallet_id), input)
+                .await?
+        };
+        decode_output(&out).context("Failed to deserialize PathPolicyOutput")
+    }
+
+    /// Not cached: every signature can change `spent_today_wei`.
+    pub async fn spending_policy(
+        &self,
+        wallet_id: uuid::Uuid,
+        set: Option<proto::spending_policy::SpendingPolicy>,
+        passkey_assertion: Option<proto::PasskeyAssertion>,
+    ) -> Result<proto::SpendingPolicyOutput> {
+        let input = bincode::serialize(&proto::SpendingPolicyInput {
+            wallet_id,
+            set,
+            passkey_assertion,
+        })
+        .context("Failed to serialize SpendingPolicyInput")?;
+        let out = self.call(proto::Command::SpendingPolicy, input).await?;
+        decode_output(&out).context("Failed to deserialize SpendingPolicyOutput")
+    }
+
+    /// Not cached: every signature moves the ledger.
+    pub async fn nonce_tracking(
+        &self,
+        wallet_id: uuid::Uuid,
+        set: Option<bool>,
+        passkey_assertion: Option<proto::PasskeyAssertion>,
+    ) -> Result<proto::NonceTrackingOutput> {
+        let input = bincode::serialize(&proto::NonceTrackingInput {
+            wallet_id,
+            set,
+            passkey_assertion,
+        })
+        .context("Failed to serialize NonceTrackingInput")?;
+        let out = self.call(proto::Command::NonceTracking, input).await?;
+        decode_output(&out).context("Failed to deserialize NonceTrackingOutput")
+    }
+
+    pub async fn override_nonce(
+        &self,
+        input: proto::OverrideNonceInput,
+    ) -> Result<proto::OverrideNonceOutput> {
+        let input = bincode::serialize(&input).context("Failed to serialize OverrideNonceInput")?;
+        let out = self.call(proto::Command::OverrideNonce, input).await?;
+        decode_output(&out).context("Failed to deserialize OverrideNonceOutput")
+    }
+
+    pub async fn derive_solana_address(
+        &self,
+        wallet_id: uuid::Uuid,
+        hd_path: &str,
+        passkey_assertion: Option<proto::PasskeyAssertion>,
+    ) -> Result<proto::DeriveSolanaAddressOutput> {
+        let input = bincode::serialize(&proto::DeriveSolanaAddressInput {
+            wallet_id,
+            hd_path: hd_path.to_string(),
+            passkey_assertion,
+        })
+        .context("Failed to serialize DeriveSolanaAddressInput")?;
+        let out = self
+            .call(proto::Command::DeriveSolanaAddress, input)
+            .await?;
+        decode_output(&out).context("Failed to deserialize DeriveSolanaAddressOutput")
+    }
+
+    /// Sign `message` (a serialized Solana transaction message, or an
+    /// off-chain message) with the account's ed25519 key. The assertion
+    /// commits to `proto::solana::signing_payload(message)`.
+    pub async fn sign_solana_message(
+        &self,
+        wallet_id: uuid::Uuid,
+        hd_path: &str,
+        message: Vec<u8>,
+        passkey_assertion: Option<proto::PasskeyAssertion>,
+    ) -> Result<proto::SignSolanaMessageOutput> {
+        let input = bincode::serialize(&proto::SignSolanaMessageInput {
+            wallet_id,
+            hd_path: hd_path.to_string(),
+            message,
+            passkey_assertion,
+        })
+        .context("Failed to serialize SignSolanaMessageInput")?;
+        let out = self.call(proto::Command::SignSolanaMessage, input).await?;
+        decode_output(&out).context("Failed to deserialize SignSolanaMessageOutput")
+    }
+
+    /// Pair `approver_key` with the wallet, or unpair with None; returns the
+    /// key it replaced.
+    pub async fn pair_approver(
+        &self,
+        wallet_id: uuid::Uuid,
+        approver_key: Option<Vec<u8>>,
+        passkey_assertion: Option<proto::PasskeyAssertion>,
+    ) -> Result<Option<Vec<u8>>> {
+        let input = bincode::serialize(&proto::PairApproverInput {
+            wallet_id,
+            approver_key,
+            passkey_assertion,
+        })
+        .context("Failed to serialize PairApproverInput")?;
+        let out = self.call(proto::Command::PairApprover, input).await?;
+        let output: proto::PairApproverOutput =
+            decode_output(&out).context("Failed to deserialize PairApproverOutput")?;
+        Ok(output.previous)
+    }
+
+    /// Hand the phone's signature over a Sign payload to the TA; returns the
+    /// TA time the armed approval lapses at.
+    pub async fn remote_approve(
+        &self,
+        wallet_id: uuid::Uuid,
+        payload: [u8; 32],
+        signature: Vec<u8>,
+    ) -> Result<i64> {
+        let input = bincode::serialize(&proto::RemoteApproveInput {
+            wallet_id,
+            payload,
+            signature,
+        })
+        .context("Failed to serialize RemoteApproveInput")?;
+        let out = self.call(proto::Command::RemoteApprove, input).await?;
+        let output: proto::RemoteApproveOutput =
+            decode_output(&out).context("Failed to deserialize RemoteApproveOutput")?;
+        Ok(output.expires_at)
+    }
+
+    /// Set the wallet's co-signer policy, or remove it with None.
+    pub async fn set_multisig_policy(
+        &self,
+        wallet_id: uuid::Uuid,
+        policy: Option<proto::multisig::MultiSigPolicy>,
+        passkey_assertion: Option<proto::PasskeyAssertion>,
+    ) -> Result<proto::SetMultiSigPolicyOutput> {
+        let input = bincode::serialize(&proto::SetMultiSigPolicyInput {
+            wallet_id,
+            policy,
+            passkey_assertion,
+        })
+        .context("Failed to serialize SetMultiSigPolicyInput")?;
+        let out = self.call(proto::Command::SetMultiSigPolicy, input).await?;
+        decode_output(&out).context("Failed to deserialize SetMultiSigPolicyOutput")
+    }
+
+    /// Hand one co-signer's signature over a held operation to the TA; the
+    /// approval that reaches the threshold gets the signature back.
+    pub async fn approve_multisig(
+        &self,
+        wallet_id: uuid::Uuid,
+        operation: [u8; 32],
+        cosigner_key: Vec<u8>,
+        signature: Vec<u8>,
+    ) -> Result<proto::ApproveMultiSigOutput> {
+        let input = bincode::serialize(&proto::ApproveMultiSigInput {
+            wallet_id,
+            operation,
+            cosigner_key,
+            signature,
+        })
+        .context("Failed to serialize ApproveMultiSigInput")?;
+        let out = self.call(proto::Command::ApproveMultiSig, input).await?;
+        decode_output(&out).context("Failed to deserialize ApproveMultiSigOutput")
+    }
+
+    /// Register recovery guardians, or remove them with None; the round in
+    /// progress ends either way.
+    pub async fn set_guardians(
+        &self,
+        wallet_id: uuid::Uuid,
+        guardians: Option<proto::social_recovery::GuardianSet>,
+        passkey_assertion: Option<proto::PasskeyAssertion>,
+    ) -> Result<proto::SetGuardiansOutput> {
+        let input = bincode::serialize(&proto::SetGuardiansInput {
+            wallet_id,
+            guardians,
+            passkey_assertion,
+        })
+        .context("Failed to serialize SetGuardiansInput")?;
+        let out = self.call(proto::Command::SetGuardians, input).await?;
+        decode_output(&out).context("Failed to deserialize SetGuardiansOutput")
+    }
+
+    pub async fn recovery_status(
+        &self,
+        wallet_id: uuid::Uuid,
+    ) -> Result<proto::social_recovery::RecoveryStatus> {
+        let input = bincode::serialize(&proto::RecoveryStatusInput { wallet_id })
+            .context("Failed to serialize RecoveryStatusInput")?;
+        let out = self.call(proto::Command::RecoveryStatus, input).await?;
+        let output: proto::RecoveryStatusOutput =
+            decode_output(&out).context("Failed to deserialize RecoveryStatusOutput")?;
+        Ok(output.status)
+    }
+
+    /// Hand one guardian's signed attestation of `new_passkey` to the TA.
+    pub async fn recovery_attest(
+        &self,
+        wallet_id: uuid::Uuid,
+        round: u64,
+        guardian_key: Vec<u8>,
+        new_passkey: Vec<u8>,
+        signature: Vec<u8>,
+    ) -> Result<proto::RecoveryAttestOutput> {
+        let input = bincode::serialize(&proto::RecoveryAttestInput {
+            wallet_id,
+            round,
+            guardian_key,
+            new_passkey,
+            signature,
+        })
+        .context("Failed to serialize RecoveryAttestInput")?;
+        let out = self.call(proto::Command::RecoveryAttest, input).await?;
+        decode_output(&out).context("Failed to deserialize RecoveryAttestOutput")
+    }
+
+    pub async fn cancel_recovery(
+        &self,
+        wallet_id: uuid::Uuid,
+        passkey_assertion: Option<proto::PasskeyAssertion>,
+    ) -> Result<proto::social_recovery::RecoveryStatus> {
+        let input = bincode::serialize(&proto::CancelRecoveryInput {
+            wallet_id,
+            passkey_assertion,
+        })
+        .context("Failed to serialize CancelRecoveryInput")?;
+        let out = self.call(proto::Command::CancelRecovery, input).await?;
+        let output: proto::CancelRecoveryOutput =
+            decode_output(&out).context("Failed to deserialize CancelRecoveryOutput")?;
+        Ok(output.status)
+    }
+
+    /// Rebind the wallet to the attested passkey once the timelock is over.
+    pub async fn complete_recovery(
+        &self,
+        wallet_id: uuid::Uuid,
+    ) -> Result<proto::CompleteRecoveryOutput> {
+        let input = bincode::serialize(&proto::CompleteRecoveryInput { wallet_id })
+            .context("Failed to serialize CompleteRecoveryInput")?;
+        let out = self.call(proto::Command::CompleteRecovery, input).await?;
+        decode_output(&out).context("Failed to deserialize CompleteRecoveryOutput")
+    }
+
+    /// Switch the wallet to BLS signatures under `aggregator`, or back to
+    /// ECDSA with None.
+    pub async fn set_aggregator(
+        &self,
+        wallet_id: uuid::Uuid,
+        aggregator: Option<[u8; 20]>,
+        passkey_assertion: Option<proto::PasskeyAssertion>,
+    ) -> Result<proto::SetAggregatorOutput> {
+        let input = bincode::serialize(&proto::SetAggregatorInput {
+            wallet_id,
+            aggregator,
+            passkey_assertion,
+        })
+        .context("Failed to serialize SetAggregatorInput")?;
+        let out = self.call(proto::Command::SetAggregator, input).await?;
+        decode_output(&out).context("Failed to deserialize SetAggregatorOutput")
+    }
+
+    pub async fn sign_aggregated_user_op(
+        &self,
+        wallet_id: uuid::Uuid,
+        user_op_hash: [u8; 32],
+        passkey_assertion: Option<proto::PasskeyAssertion>,
+    ) -> Result<proto::SignAggregatedUserOpOutput> {
+        let input = bincode::serialize(&proto::SignAggregatedUserOpInput {
+            wallet_id,
+            user_op_hash,
+            passkey_assertion,
+        })
+        .context("Failed to serialize SignAggregatedUserOpInput")?;
+        let out = self.call(proto::Command::SignAggregatedUserOp, input).await?;
+        decode_output(&out).context("Failed to deserialize SignAggregatedUserOpOutput")
+    }
+
+    pub async fn sign_permit(
+        &self,
+        input: proto::SignPermitInput,
+    ) -> Result<proto::SignPermitOutput> {
+        let input = bincode::serialize(&input).context("Failed to serialize SignPermitInput")?;
+        let out = self.call(proto::Command::SignPermit, input).await?;
+        decode_output(&out).context("Failed to deserialize SignPermitOutput")
+    }
+
+    /// New wallet from a passphrase keystore; the TA decrypts it.
+    pub async fn import_keystore(
+        &self,
+        passkey_pubkey: &[u8],
+        keystore: proto::kdf::Keystore,
+        passphrase: String,
+    ) -> Result<uuid::Uuid> {
+        let input = bincode::serialize(&proto::ImportKeystoreInput {
+            passkey_pubkey: passkey_pubkey.to_vec(),
+            keystore,
+            passphrase,
+        })
+        .context("Failed to serialize ImportKeystoreInput")?;
+        let out = self.call(proto::Command::ImportKeystore, input).await?;
+        let output: proto::ImportKeystoreOutput =
+            decode_output(&out).context("Failed to deserialize ImportKeystoreOutput")?;
+        Ok(output.wallet_id)
+    }
+
+    pub async fn export_encrypted_backup(
+        &self,
+        wallet_id: uuid::Uuid,
+        password: String,
+        kdf: proto::kdf::KdfConfig,
+        passkey_assertion: Option<proto::PasskeyAssertion>,
+    ) -> Result<proto::backup::EncryptedBackup> {
+        let input = bincode::serialize(&proto::ExportEncryptedBackupInput {
+            wallet_id,
+            password,
+            kdf,
+            passkey_assertion,
+        })
+        .context("Failed to serialize ExportEncryptedBackupInput")?;
+        let out = self
+            .call(proto::Command::ExportEncryptedBackup, input)
+            .await?;
+        let output: proto::ExportEncryptedBackupOutput =
+            decode_output(&out).context("Failed to deserialize ExportEncryptedBackupOutput")?;
+        Ok(output.backup)
+    }
+
+    pub async fn import_encrypted_backup(
+        &self,
+        backup: proto::backup::EncryptedBackup,
+        password: String,
+    ) -> Result<proto::ImportEncryptedBackupOutput> {
+        let input = bincode::serialize(&proto::ImportEncryptedBackupInput { backup, password })
+            .context("Failed to serialize ImportEncryptedBackupInput")?;
+        let out = self
+            .call(proto::Command::ImportEncryptedBackup, input)
+            .await?;
+        decode_output(&out).context("Failed to deserialize ImportEncryptedBackupOutput")
+    }
+
+    pub async fn dapp_storage_put(
+        &self,
+        input: proto::DappStoragePutInput,
+    ) -> Result<proto::DappStoragePutOutput> {
+        let input =
+            bincode::serialize(&input).context("Failed to serialize DappStoragePutInput")?;
+        let out = self.call(proto::Command::DappStoragePut, input).await?;
+        decode_output(&out).context("Failed to deserialize DappStoragePutOutput")
+    }
+
+    pub async fn dapp_storage_get(&self, input: proto::DappStorageGetInput) -> Result<Vec<u8>> {
+        let input =
+            bincode::serialize(&input).context("Failed to serialize DappStorageGetInput")?;
+        let out = self.call(proto::Command::DappStorageGet, input).await?;
+        let output: proto::DappStorageGetOutput =
+            decode_output(&out).context("Failed to deserialize DappStorageGetOutput")?;
+        Ok(output.value)
+    }
+
+    /// False if the key was not stored.
+    pub async fn dapp_storage_delete(&self, input: proto::DappStorageDeleteInput) -> Result<bool> {
+        let input =
+            bincode::serialize(&input).context("Failed to serialize DappStorageDeleteInput")?;
+        let out = self.call(proto::Command::DappStorageDelete, input).await?;
+        let output: proto::DappStorageDeleteOutput =
+            decode_output(&out).context("Failed to deserialize DappStorageDeleteOutput")?;
+        Ok(output.existed)
+    }
+
+    /// Secrets the wallet holds after enrolling.
+    pub async fn otp_enroll(&self, input: proto::OtpEnrollInput) -> Result<u32> {
+        let input = bincode::serialize(&input).context("Failed to serialize OtpEnrollInput")?;
+        let out = self.call(proto::Command::OtpEnroll, input).await?;
+        let output: proto::OtpEnrollOutput =
+            decode_output(&out).context("Failed to deserialize OtpEnrollOutput")?;
+        Ok(output.entries)
+    }
+
+    pub async fn otp_code(&self, input: proto::OtpCodeInput) -> Result<proto::OtpCodeOutput> {
+        let input = bincode::serialize(&input).context("Failed to serialize OtpCodeInput")?;
+        let out = self.call(proto::Command::OtpCode, input).await?;
+        decode_output(&out).context("Failed to deserialize OtpCodeOutput")
+    }
+
+    /// False if nothing was enrolled under the label.
+    pub async fn otp_remove(&self, input: proto::OtpRemoveInput) -> Result<bool> {
+        let input = bincode::serialize(&input).context("Failed to serialize OtpRemoveInput")?;
+        let out = self.call(proto::Command::OtpRemove, input).await?;
+        let output: proto::OtpRemoveOutput =
+            decode_output(&out).context("Failed to deserialize OtpRemoveOutput")?;
+        Ok(output.existed)
+    }
+
+    pub async fn otp_list(&self, input: proto::OtpListInput) -> Result<Vec<proto::OtpEntry>> {
+        let input = bincode::serialize(&input).context("Failed to serialize OtpListInput")?;
+        let out = self.call(proto::Command::OtpList, input).await?;
+        let output: proto::OtpListOutput =
+            decode_output(&out).context("Failed to deserialize OtpListOutput")?;
+        Ok(output.entries)
+    }
+
+    pub async fn link_wallet(
+        &self,
+        input: proto::LinkWalletInput,
+    ) -> Result<proto::LinkWalletOutput> {
+        let input = bincode::serialize(&input).context("Failed to serialize LinkWalletInput")?;
+        let out = self.call(proto::Command::LinkWallet, input).await?;
+        decode_output(&out).context("Failed to deserialize LinkWalletOutput")
+    }
+
+    pub async fn migration_offer(
+        &self,
+        account_xpub: proto::migration::AccountXpub,
+    ) -> Result<proto::MigrationOfferOutput> {
+        let input = bincode::serialize(&proto::MigrationOfferInput { account_xpub })
+            .context("Failed to serialize MigrationOfferInput")?;
+        let out = self.call(proto::Command::MigrationOffer, input).await?;
+        decode_output(&out).context("Failed to deserialize MigrationOfferOutput")
+    }
+
+    pub async fn migration_import(
+        &self,
+        input: proto::MigrationImportInput,
+    ) -> Result<proto::MigrationImportOutput> {
+        let input =
+            bincode::serialize(&input).context("Failed to serialize MigrationImportInput")?;
+        let out = self.call(proto::Command::MigrationImport, input).await?;
+        decode_output(&out).context("Failed to deserialize MigrationImportOutput")
+    }
+
+    pub async fn import_wallet(
+        &self,
+        passkey_pubkey: &[u8],
+        phrase: String,
+    ) -> Result<proto::ImportWalletOutput> {
+        let input = bincode::serialize(&proto::ImportWalletInput {
+            passkey_pubkey: passkey_pubkey.to_vec(),
+            phrase,
+        })
+        .context("Failed to serialize ImportWalletInput")?;
+        let out = self.call(proto::Command::ImportWallet, input).await?;
+        decode_output(&out).context("Failed to deserialize ImportWalletOutput")
+    }
+
+    pub async fn bip85_export(
+        &self,
+        input: proto::Bip85ExportInput,
+    ) -> Result<proto::bip85::Bip85Package> {
+        let input = bincode::serialize(&input).context("Failed to serialize Bip85ExportInput")?;
+        let out = self.call(proto::Command::Bip85Export, input).await?;
+        let output: proto::Bip85ExportOutput =
+            decode_output(&out).context("Failed to deserialize Bip85ExportOutput")?;
+        Ok(output.package)
+    }
+
+    pub async fn open_decrypt_session(
+        &self,
+        input: proto::OpenDecryptSessionInput,
+    ) -> Result<proto::OpenDecryptSessionOutput> {
+        let input =
+            bincode::serialize(&input).context("Failed to serialize OpenDecryptSessionInput")?;
+        let out = self.call(proto::Command::OpenDecryptSession, input).await?;
+        decode_output(&out).context("Failed to deserialize OpenDecryptSessionOutput")
+    }
+
+    pub async fn ecies_decrypt(
+        &self,
+        input: proto::EciesDecryptInput,
+    ) -> Result<proto::EciesDecryptOutput> {
+        let input = bincode::serialize(&input).context("Failed to serialize EciesDecryptInput")?;
+        let out = self.call(proto::Command::EciesDecrypt, input).await?;
+        decode_output(&out).context("Failed to deserialize EciesDecryptOutput")
+    }
+
+    pub async fn get_stealth_meta_address(
+        &self,
+        input: proto::GetStealthMetaAddressInput,
+    ) -> Result<proto::stealth::StealthMetaAddress> {
+        let wallet_id = input.wallet_id;
+        let input =
+            bincode::serialize(&input).context("Failed to serialize GetStealthMetaAddressInput")?;
+        let out = self
+            .call_cached(
+                proto::Command::GetStealthMetaAddress,
+                Some(wallet_id),
+                input,
+            )
+            .await?;
+        let output: proto::GetStealthMetaAddressOutput =
+            decode_output(&out).context("Failed to deserialize GetStealthMetaAddressOutput")?;
+        Ok(output.meta_address)
+    }
+
+    pub async fn stealth_scan(&self, input: proto::StealthScanInput) -> Result<Vec<u32>> {
+        let input = bincode::serialize(&input).context("Failed to serialize StealthScanInput")?;
+        let out = self.call(proto::Command::StealthScan, input).await?;
+        let output: proto::StealthScanOutput =
+            decode_output(&out).context("Failed to deserialize StealthScanOutput")?;
+        Ok(output.matches)
+    }
+
+    pub async fn get_capabilities(&self) -> Result<proto::GetCapabilitiesOutput> {
+        let input = bincode::serialize(&proto::GetCapabilitiesInput {})
+            .context("Failed to serialize GetCapabilitiesInput")?;
+        let out = self
+            .call_cached(proto::Command::GetCapabilities, None, input)
+            .await?;
+        decode_output(&out).context("Failed to deserialize GetCapabilitiesOutput")
+    }
+
+    pub async fn protocol_version(&self) -> Result<proto::GetProtocolVersionOutput> {
+        let input = bincode::serialize(&proto::GetProtocolVersionInput {})
+            .context("Failed to serialize GetProtocolVersionInput")?;
+        decode_protocol_version(self.call(proto::Command::GetProtocolVersion, input).await)
+    }
+
+    /// Agree on a protocol version with the TA (`proto::wire::negotiate`).
+    /// A TA with no version in common is refused, so no command reaches it.
+    pub async fn handshake(&mut self) -> Result<u16> {
+        let ta = self.protocol_version().await?;
+        match proto::wire::negotiate(ta.min_protocol_version, ta.protocol_version) {
+            Ok(version) => Ok(version),
+            Err(mismatch) => {
+                self.refuse(mismatch.to_string());
+                Err(mismatch.into())
+            }
+        }
+    }
+
+    pub async fn sign_offline(
+        &self,
+        request: proto::offline::OfflineSignRequest,
+        passkey_assertion: Option<proto::PasskeyAssertion>,
+    ) -> Result<proto::offline::OfflineSignResponse> {
+        let input = bincode::serialize(&proto::SignOfflineInput {
+            request,
+            passkey_assertion,
+        })
+        .context("Failed to serialize SignOfflineInput")?;
+        let out = self.call(proto::Command::SignOffline, input).await?;
+        let output: proto::SignOfflineOutput =
+            decode_output(&out).context("Failed to deserialize SignOfflineOutput")?;
+        Ok(output.response)
+    }
+
+    pub async fn replication_offer(&self) -> Result<proto::replication::ReplicationHello> {
+        let input = bincode::serialize(&proto::ReplicationOfferInput {})
+            .context("Failed to serialize ReplicationOfferInput")?;
+        let out = self.call(proto::Command::ReplicationOffer, input).await?;
+        let output: proto::ReplicationOfferOutput =
+            decode_output(&out).context("Failed to deserialize ReplicationOfferOutput")?;
+        Ok(output.hello)
+    }
+
+    pub async fn replication_export(
+        &self,
+        wallet_id: uuid::Uuid,
+        peer: proto::replication::ReplicationHello,
+        passkey_assertion: Option<proto::PasskeyAssertion>,
+    ) -> Result<proto::replication::ReplicationPackage> {
+        let input = bincode::serialize(&proto::ReplicationExportInput {
+            wallet_id,
+            peer,
+            passkey_assertion,
+        })
+        .context("Failed to serialize ReplicationExportInput")?;
+        let out = self.call(proto::Command::ReplicationExport, input).await?;
+        let output: proto::ReplicationExportOutput =
+            decode_output(&out).context("Failed to deserialize ReplicationExportOutput")?;
+        Ok(output.package)
+    }
+
+    pub async fn replication_import(
+        &self,
+        package: proto::replication::ReplicationPackage,
+    ) -> Result<proto::ReplicationImportOutput> {
+        let input = bincode::serialize(&proto::ReplicationImportInput { package })
+            .context("Failed to serialize ReplicationImportInput")?;
+        let out = self.call(proto::Command::ReplicationImport, input).await?;
+        decode_output(&out).context("Failed to deserialize ReplicationImportOutput")
+    }
+
+    pub async fn tamper_order(
+        &self,
+        order: proto::tamper::TamperOrder,
+        signature: Vec<u8>,
+    ) -> Result<proto::TamperOrderOutput> {
+        let input = bincode::serialize(&proto::TamperOrderInput { order, signature })
+            .context("Failed to serialize TamperOrderInput")?;
+        let out = self.call(proto::Command::TamperOrder, input).await?;
+        decode_output(&out).context("Failed to deserialize TamperOrderOutput")
+    }
+
+    pub async fn tamper_status(&self) -> Result<proto::TamperStatus> {
+        let input = bincode::serialize(&proto::GetTamperStatusInput {})
+            .context("Failed to serialize GetTamperStatusInput")?;
+        let out = self.call(proto::Command::GetTamperStatus, input).await?;
+        decode_output(&out).context("Failed to deserialize TamperStatus")
+    }
+
+    pub async fn set_log_level(
+        &self,
+        order: proto::log_level::LogLevelOrder,
+        signature: Vec<u8>,
+    ) -> Result<proto::SetLogLevelOutput> {
+        let input = bincode::serialize(&proto::SetLogLevelInput { order, signature })
+            .context("Failed to serialize SetLogLevelInput")?;
+        let out = self.call(proto::Command::SetLogLevel, input).await?;
+        decode_output(&out).context("Failed to deserialize SetLogLevelOutput")
+    }
+
+    pub async fn threshold_keygen_begin(
+        &self,
+        input: proto::ThresholdKeygenBeginInput,
+    ) -> Result<proto::ThresholdKeygenBeginOutput> {
+        let input =
+            bincode::serialize(&input).context("Failed to serialize ThresholdKeygenBeginInput")?;
+        let out = self
+            .call(proto::Command::ThresholdKeygenBegin, input)
+            .await?;
+        decode_output(&out).context("Failed to deserialize ThresholdKeygenBeginOutput")
+    }
+
+    pub async fn threshold_keygen_finish(
+        &self,
+        input: proto::ThresholdKeygenFinishInput,
+    ) -> Result<proto::ThresholdKeygenFinishOutput> {
+        let input =
+            bincode::serialize(&input).context("Failed to serialize ThresholdKeygenFinishInput")?;
+        let out = self
+            .call(proto::Command::ThresholdKeygenFinish, input)
+            .await?;
+        decode_output(&out).context("Failed to deserialize ThresholdKeygenFinishOutput")
+    }
+
+    pub async fn threshold_presign_begin(
+        &self,
+        input: proto::ThresholdPresignBeginInput,
+    ) -> Result<proto::ThresholdPresignBeginOutput> {
+        let input =
+            bincode::serialize(&input).context("Failed to serialize ThresholdPresignBeginInput")?;
+        let out = self
+            .call(proto::Command::ThresholdPresignBegin, input)
+            .await?;
+        decode_output(&out).context("Failed to deserialize ThresholdPresignBeginOutput")
+    }
+
+    pub async fn threshold_presign_finish(
+        &self,
+        input: proto::ThresholdPresignFinishInput,
+    ) -> Result<proto::ThresholdPresignFinishOutput> {
+        let input = bincode::serialize(&input)
+            .context("Failed to serialize ThresholdPresignFinishInput")?;
+        let out = self
+            .call(proto::Command::ThresholdPresignFinish, input)
+            .await?;
+        decode_output(&out).context("Failed to deserialize ThresholdPresignFinishOutput")
+    }
+
+    pub async fn threshold_sign(
+        &self,
+        input: proto::ThresholdSignInput,
+    ) -> Result<proto::ThresholdSignOutput> {
+        let input = bincode::serialize(&input).context("Failed to serialize ThresholdSignInput")?;
+        let out = self.call(proto::Command::ThresholdSign, input).await?;
+        decode_output(&out).context("Failed to deserialize ThresholdSignOutput")
+    }
+
+    pub async fn threshold_recombine(
+        &self,
+        input: proto::ThresholdRecombineInput,
+    ) -> Result<proto::ThresholdRecombineOutput> {
+        let input =
+            bincode::serialize(&input).context("Failed to serialize ThresholdRecombineInput")?;
+        let out = self.call(proto::Command::ThresholdRecombine, input).await?;
+        decode_output(&out).context("Failed to deserialize ThresholdRecombineOutput")
+    }
+
+    pub async fn threshold_status(
+        &self,
+        input: proto::ThresholdStatusInput,
+    ) -> Result<proto::ThresholdStatusOutput> {
+        let input =
+            bincode::serialize(&input).context("Failed to serialize ThresholdStatusInput")?;
+        let out = self.call(proto::Command::ThresholdStatus, input).await?;
+        decode_output(&out).context("Failed to deserialize ThresholdStatusOutput")
+    }
 }
 
 // ---- TEE worker thread ----
 
+/// Invoke `command`, through a chunked transfer (`proto::transfer`) when its
+/// input is too large for one invoke. A TA without transfers gets the direct
+/// invoke, as before.
+#[cfg(not(feature = "soft-tee"))]
 fn invoke_on_session(
     session: &mut optee_teec::Session,
     command: proto::Command,
     input: &[u8],
+    priority: Priority,
+) -> Result<Vec<u8>> {
+    if !proto::transfer::needs_transfer(command, input.len()) {
+        return invoke_direct(session, command, input, priority);
+    }
+    match invoke_transfer(session, command, input, priority) {
+        Err(e) if transfer_unsupported(&e) => invoke_direct(session, command, input, priority),
+        result => result,
+    }
+}
+
+#[cfg(not(feature = "soft-tee"))]
+fn transfer_unsupported(e: &anyhow::Error) -> bool {
+    matches!(
+        e.downcast_ref::<UnsupportedVersion>(),
+        Some(u) if u.command == proto::Command::BeginTransfer as u32
+    )
+}
+
+/// One transfer on `session`: Begin, each input chunk, End, then the output
+/// chunks, checked against the digest the TA answered at End.
+#[cfg(not(feature = "soft-tee"))]
+fn invoke_transfer(
+    session: &mut optee_teec::Session,
+    command: proto::Command,
+    input: &[u8],
+    priority: Priority,
+) -> Result<Vec<u8>> {
+    use proto::transfer::{chunk_at, payload_digest, Reassembly, MAX_TRANSFER_LEN};
+
+    if input.len() > MAX_TRANSFER_LEN {
+        return Err(proto::transfer::TransferError::TooLarge(input.len()).into());
+    }
+    let mut step = |cmd: proto::Command, request: Vec<u8>| -> Result<Vec<u8>> {
+        invoke_direct(session, cmd, &request, priority)
+    };
+
+    let begin = proto::BeginTransferInput {
+        command: command as u32,
+        input_len: input.len() as u32,
+        input_digest: payload_digest(input),
+    };
+    let out = step(proto::Command::BeginTransfer, bincode::serialize(&begin)?)?;
+    let begun: proto::BeginTransferOutput =
+        decode_output(&out).context("Failed to deserialize BeginTransferOutput")?;
+    let transfer_id = begun.transfer_id;
+
+    let mut seq = 0;
+    while let Some(chunk) = chunk_at(transfer_id, input, seq) {
+        let request = bincode::serialize(&proto::ContinueTransferInput {
+            transfer_id,
+            chunk: Some(chunk),
+        })?;
+        step(proto::Command::ContinueTransfer, request)?;
+        seq += 1;
+    }
+
+    let end = proto::EndTransferInput { transfer_id };
+    let out = step(proto::Command::EndTransfer, bincode::serialize(&end)?)?;
+    let ended: proto::EndTransferOutput =
+        decode_output(&out).context("Failed to deserialize EndTransferOutput")?;
+
+    let mut output = Reassembly::new(transfer_id, ended.output_len, ended.output_digest)?;
+    for _ in 0..ended.chunks {
+        let request = bincode::serialize(&proto::ContinueTransferInput {
+            transfer_id,
+            chunk: None,
+        })?;
+        let out = step(proto::Command::ContinueTransfer, request)?;
+        let next: proto::ContinueTransferOutput =
+            decode_output(&out).context("Failed to deserialize ContinueTransferOutput")?;
+        let chunk = next.chunk.ok_or_else(|| {
+            anyhow::anyhow!(
+                "TA closed transfer {:#x} after {} of {} output chunks",
+                transfer_id,
+                output.next_seq(),
+                ended.chunks
+            )
+        })?;
+        output.push(&chunk)?;
+    }
+    Ok(output.finish()?)
+}
+
+#[cfg(not(feature = "soft-tee"))]
+fn invoke_direct(
+    session: &mut optee_teec::Session,
+    command: proto::Command,
+    input: &[u8],
+    priority: Priority,
 ) -> Result<Vec<u8>> {
     let p0 = ParamTmpRef::new_input(input);
-    let mut output = vec![0u8; OUTPUT_MAX_SIZE];
+    let mut output = vec![0u8; proto::buffers::max_output_len(command)];
     let p1 = ParamTmpRef::new_output(output.as_mut_slice());
-    let p2 = ParamValue::new(0, 0, ParamType::ValueInout);
+    // a = output length (set by the TA), b = request header (read by the TA).
+    let p2 = ParamValue::new(0, priority.wire_hint(), ParamType::ValueInout);
     let mut operation = Operation::new(0, p0, p1, p2, ParamNone);
 
     match session.invoke_command(command as u32, &mut operation) {
@@ -1157,15 +2918,93 @@ fn invoke_on_session(
         Err(e) => {
             let len = operation.parameters().2.a() as usize;
             let msg = String::from_utf8_lossy(&output[..len]);
-            Err(anyhow::anyhow!(
-                "TA command failed: {} (error: {:?})",
-                msg,
-                e
-            ))
+            Err(command_error(&msg, e))
         }
     }
 }
 
+/// A failed invoke. A command the TA does not dispatch comes back as
+/// `proto::wire::UnsupportedVersion`, a full secure storage as
+/// `proto::storage_quota::StorageExhausted`, a malformed derivation path as
+/// `proto::hd_path::InvalidPath`, a refused transaction as
+/// `proto::spending_policy::PolicyViolation` (or a reused nonce as
+/// `proto::nonce_guard::NonceRefused`) and one held for co-signers as
+/// `proto::multisig::MultiSigPending`, so callers can tell them from a TA
+/// fault.
+fn command_error(message: &str, code: impl std::fmt::Debug) -> anyhow::Error {
+    if let Some(unsupported) = UnsupportedVersion::find(message) {
+        return anyhow::Error::new(unsupported);
+    }
+    if let Some(invalid) = InvalidPath::find(message) {
+        return anyhow::Error::new(invalid);
+    }
+    if let Some(violation) = PolicyViolation::find(message) {
+        return anyhow::Error::new(violation);
+    }
+    if let Some(refused) = NonceRefused::find(message) {
+        return anyhow::Error::new(refused);
+    }
+    if let Some(pending) = MultiSigPending::find(message) {
+        return anyhow::Error::new(pending);
+    }
+    match StorageExhausted::find(message) {
+        Some(exhausted) => anyhow::Error::new(exhausted),
+        None => anyhow::anyhow!("TA command failed: {} (error: {:?})", message, code),
+    }
+}
+
+/// A soft-TEE failure, shaped like the TA's error text.
+#[cfg(feature = "soft-tee")]
+fn soft_tee_error(e: anyhow::Error) -> anyhow::Error {
+    command_error(&format!("{:#}", e), crate::soft_tee::MARKER)
+}
+
+/// RemoveWallet output: empty from a TA without proof-of-erasure.
+fn decode_remove_wallet_output(out: &[u8]) -> Result<Option<DeletionProof>> {
+    if out.is_empty() {
+        return Ok(None);
+    }
+    let output: proto::RemoveWalletOutput =
+        decode_output(out).context("Failed to deserialize RemoveWalletOutput")?;
+    Ok(Some(output.proof))
+}
+
+/// `encoded_address` from the TA, or, from a v1 TA that does not fill it,
+/// the EVM form of the hash; a non-EVM chain then is `UnsupportedVersion`.
+fn chain_address(chain: proto::chain::Chain, output: proto::DeriveAddressOutput) -> Result<String> {
+    if !output.encoded_address.is_empty() {
+        return Ok(output.encoded_address);
+    }
+    if chain.is_evm() {
+        return Ok(chain.encode_address(&output.address));
+    }
+    Err(anyhow::Error::new(UnsupportedVersion {
+        command: proto::Command::DeriveAddress as u32,
+        ca_version: proto::wire::PROTOCOL_VERSION,
+        ta_version: 1,
+    }))
+}
+
+/// GetProtocolVersion answer. A TA from before the command refuses it as
+/// `UnsupportedVersion`, which still names its protocol version; such a TA
+/// accepts every older version and no command from GetProtocolVersion on.
+fn decode_protocol_version(result: Result<Vec<u8>>) -> Result<proto::GetProtocolVersionOutput> {
+    match result {
+        Ok(out) => decode_output(&out).context("Failed to deserialize GetProtocolVersionOutput"),
+        Err(e) => match e.downcast_ref::<UnsupportedVersion>() {
+            Some(u) if u.command == proto::Command::GetProtocolVersion as u32 => {
+                Ok(proto::GetProtocolVersionOutput {
+                    protocol_version: u.ta_version,
+                    min_protocol_version: 0,
+                    max_command_id: proto::Command::GetProtocolVersion as u32 - 1,
+                })
+            }
+            _ => Err(e),
+        },
+    }
+}
+
+#[cfg(not(feature = "soft-tee"))]
 fn is_session_error(result: &Result<Vec<u8>>) -> bool {
     match result {
         Err(e) => {
@@ -1181,26 +3020,72 @@ fn is_session_error(result: &Result<Vec<u8>>) -> bool {
     }
 }
 
-fn tee_worker_loop(rx: std::sync::mpsc::Receiver<TeeCommand>) {
+/// Soft-TEE build: the same lanes and deadlines, answered in-process. A
+/// store that does not open fails every command, as a TA that won't load.
+#[cfg(feature = "soft-tee")]
+fn tee_worker_loop(lanes: &Lanes) {
+    let mut tee = crate::soft_tee::acknowledge().and_then(|()| crate::soft_tee::SoftTee::from_env());
+    println!("🔓 TEE worker: {} (software TEE)", crate::soft_tee::MARKER);
+
+    loop {
+        let cmd = lanes.pop_blocking();
+        let waited = cmd.enqueued_at.elapsed().as_secs();
+        if waited >= MAX_QUEUE_WAIT_SECS {
+            let _ = cmd.reply.send(TeeReply::not_invoked(Err(anyhow::anyhow!(
+                "TEE request dropped: queued {waited}s (> {MAX_QUEUE_WAIT_SECS}s deadline) — server overloaded"
+            ))));
+            continue;
+        }
+        if cmd.abandoned() {
+            continue;
+        }
+        let started = SystemTime::now();
+        let result = match &mut tee {
+            Ok(tee) => tee.invoke(cmd.command, &cmd.input).map_err(soft_tee_error),
+            Err(e) => Err(anyhow::anyhow!("{:#}", e)),
+        };
+        let _ = cmd.reply.send(TeeReply {
+            result,
+            invoked: Some((started, SystemTime::now())),
+            reconnected: false,
+        });
+    }
+}
+
+#[cfg(not(feature = "soft-tee"))]
+fn tee_worker_loop(lanes: &Lanes) {
     let mut ctx = Context::new().expect("TEE Context::new failed");
-    let uuid = Uuid::parse_str(proto::UUID).expect("Invalid TA UUID");
+    let uuid = Uuid::parse_str(TA_UUID).expect("Invalid TA UUID");
     let mut session = ctx
         .open_session(uuid.clone())
         .expect("Initial open_session failed");
     println!("🔗 TEE worker: session opened");
 
-    for cmd in rx.iter() {
+    loop {
+        let cmd = lanes.pop_blocking();
         // T3: shed a command that has waited past the deadline BEFORE spending a
         // serial TA slot on it — the caller has very likely already timed out.
         let waited = cmd.enqueued_at.elapsed().as_secs();
         if waited >= MAX_QUEUE_WAIT_SECS {
-            let _ = cmd.reply.send(Err(anyhow::anyhow!(
+            let _ = cmd.reply.send(TeeReply::not_invoked(Err(anyhow::anyhow!(
                 "TEE request dropped: queued {waited}s (> {MAX_QUEUE_WAIT_SECS}s deadline) — server overloaded"
-            )));
+            ))));
+            continue;
+        }
+        // The caller is gone: don't hold the session for a reply nobody reads.
+        if cmd.abandoned() {
+            eprintln!(
+                "⏱️  TEE worker: skipped {:?}, caller gone (correlation id {})",
+                cmd.command,
+                cmd.context
+                    .as_ref()
+                    .map_or("-", |c| c.correlation_id.as_str())
+            );
             continue;
         }
 
-        let result = invoke_on_session(&mut session, cmd.command, &cmd.input);
+        let started = SystemTime::now();
+        let result = invoke_on_session(&mut session, cmd.command, &cmd.input, cmd.priority);
 
         if is_session_error(&result) {
             eprintln!("⚠️  TEE session error, attempting reconnect…");
@@ -1208,29 +3093,194 @@ fn tee_worker_loop(rx: std::sync::mpsc::Receiver<TeeCommand>) {
                 Ok(new_session) => {
                     session = new_session;
                     println!("🔗 TEE worker: session reconnected");
-                    let retry = invoke_on_session(&mut session, cmd.command, &cmd.input);
-                    let _ = cmd.reply.send(retry);
+                    if cmd.abandoned() {
+                        continue;
+                    }
+                    let retry =
+                        invoke_on_session(&mut session, cmd.command, &cmd.input, cmd.priority);
+                    let _ = cmd.reply.send(TeeReply {
+                        result: retry,
+                        invoked: Some((started, SystemTime::now())),
+                        reconnected: true,
+                    });
                     continue;
                 }
                 Err(e) => {
                     eprintln!("❌ TEE reconnect failed: {:?}", e);
                     // Send the original error
-                    let _ = cmd.reply.send(result);
+                    let _ = cmd.reply.send(TeeReply {
+                        result,
+                        invoked: Some((started, SystemTime::now())),
+                        reconnected: false,
+                    });
                     continue;
                 }
             }
         }
 
-        let _ = cmd.reply.send(result);
+        let _ = cmd.reply.send(TeeReply {
+            result,
+            invoked: Some((started, SystemTime::now())),
+            reconnected: false,
+        });
     }
-
-    println!("🔗 TEE worker: channel closed, exiting");
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn background_commands_use_batch_lane() {
+        assert_eq!(
+            Priority::for_command(proto::Command::DeriveAddressAuto),
+            Priority::Batch
+        );
+        assert_eq!(
+            Priority::for_command(proto::Command::TaStats),
+            Priority::Batch
+        );
+        assert_eq!(
+            Priority::for_command(proto::Command::SignHash),
+            Priority::Interactive
+        );
+        assert_eq!(
+            Priority::for_command(proto::Command::Unknown),
+            Priority::Interactive
+        );
+    }
+
+    #[test]
+    fn quarantine_refuses_everything_but_inspection() {
+        let handle = TeeHandle::new();
+        let clone = handle.clone();
+        handle.quarantine("self-test failed: rng");
+        assert_eq!(
+            clone.quarantine_reason().as_deref(),
+            Some("self-test failed: rng")
+        );
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let err = rt
+            .block_on(clone.call(proto::Command::SignHash, Vec::new()))
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("device quarantined"), "{}", err);
+        assert!(crate::replication::tee_unavailable(&err));
+        assert!(runs_in_quarantine(proto::Command::SelfTest));
+        assert!(runs_in_quarantine(proto::Command::GetAttestation));
+        assert!(!runs_in_quarantine(proto::Command::CreateWallet));
+        handle.release_quarantine();
+        assert!(clone.quarantine_reason().is_none());
+    }
+
+    #[test]
+    fn ta_without_the_handshake_command_reports_its_version() {
+        let legacy = decode_protocol_version(Err(anyhow::Error::new(UnsupportedVersion {
+            command: proto::Command::GetProtocolVersion as u32,
+            ca_version: proto::wire::PROTOCOL_VERSION,
+            ta_version: 0,
+        })))
+        .unwrap();
+        assert_eq!(legacy.protocol_version, 0);
+        assert_eq!(
+            legacy.max_command_id,
+            proto::Command::GetProtocolVersion as u32 - 1
+        );
+        assert_eq!(
+            proto::wire::negotiate(legacy.min_protocol_version, legacy.protocol_version),
+            Ok(0)
+        );
+        let other = decode_protocol_version(Err(anyhow::Error::new(UnsupportedVersion {
+            command: proto::Command::BeginTransfer as u32,
+            ca_version: 0,
+            ta_version: 0,
+        })));
+        assert!(other.is_err());
+    }
+
+    #[test]
+    fn v1_ta_answers_only_evm_chains() {
+        use proto::chain::Chain;
+        let v1 = || proto::DeriveAddressOutput {
+            address: [0x11; 20],
+            public_key: Vec::new(),
+            encoded_address: String::new(),
+        };
+        assert_eq!(
+            chain_address(Chain::Bsc, v1()).unwrap(),
+            Chain::Ethereum.encode_address(&[0x11; 20])
+        );
+        let err = chain_address(Chain::Tron, v1()).unwrap_err();
+        assert!(err.downcast_ref::<UnsupportedVersion>().is_some());
+        let v2 = proto::DeriveAddressOutput {
+            encoded_address: "T-address".to_string(),
+            ..v1()
+        };
+        assert_eq!(chain_address(Chain::Tron, v2).unwrap(), "T-address");
+    }
+
+    #[test]
+    fn interactive_lane_drains_first() {
+        let lanes = Lanes::new();
+        let mk = |command, priority| {
+            let (reply, _rx) = tokio::sync::oneshot::channel();
+            TeeCommand {
+                command,
+                input: Vec::new(),
+                priority,
+                reply,
+                enqueued_at: Instant::now(),
+                context: None,
+            }
+        };
+        lanes
+            .push(mk(proto::Command::WarmupCache, Priority::Batch))
+            .unwrap();
+        lanes
+            .push(mk(proto::Command::SignHash, Priority::Interactive))
+            .unwrap();
+        assert_eq!(lanes.pop_blocking().command, proto::Command::SignHash);
+        assert_eq!(lanes.pop_blocking().command, proto::Command::WarmupCache);
+        lanes.worker_alive.store(false, Ordering::SeqCst);
+        assert!(lanes
+            .push(mk(proto::Command::SignHash, Priority::Interactive))
+            .is_err());
+    }
+
+    #[test]
+    fn commands_past_the_callers_deadline_are_abandoned() {
+        let (reply, rx) = tokio::sync::oneshot::channel();
+        let mut cmd = TeeCommand {
+            command: proto::Command::SignHash,
+            input: Vec::new(),
+            priority: Priority::Interactive,
+            reply,
+            enqueued_at: Instant::now(),
+            context: Some(RequestContext::from_headers(Some("60000"), Some("req-1"))),
+        };
+        assert!(!cmd.abandoned());
+        cmd.context = Some(RequestContext::from_headers(Some("0"), None));
+        assert!(cmd.abandoned());
+        cmd.context = None;
+        drop(rx);
+        assert!(cmd.abandoned());
+    }
+
+    #[test]
+    fn correlation_id_is_kept_only_when_safe() {
+        let ctx = RequestContext::from_headers(Some("nope"), Some("req-1.a_B"));
+        assert_eq!(ctx.correlation_id, "req-1.a_B");
+        assert!(ctx.deadline.is_none());
+        for bad in ["", "a b", "x\r\nSet-Cookie: y", "a".repeat(65).as_str()] {
+            let ctx = RequestContext::from_headers(None, Some(bad));
+            assert_eq!(ctx.correlation_id.len(), 16);
+            assert_ne!(ctx.correlation_id, bad);
+        }
+    }
+
     #[test]
     fn test_ta_client_creation() {
         // This test will only pass in OP-TEE environment
@@ -0,0 +1,94 @@
+//! Sealed TA config — the JSON form `airaccount-provision --ta-config-file`
+//! reads and `airaccount-backup` carries.
+//!
+//! `limits` names only the values to change (camelCase `TaLimits` fields);
+//! the rest take `TaLimits::DEFAULT`, not whatever the device has installed,
+//! because the signed text must list every value. The TA checks the
+//! signature against its compiled-in provisioning key.
+
+use anyhow::{anyhow, Context, Result};
+use proto::ta_config::{TaConfig, TaLimits, CONFIG_FORMAT_VERSION};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaConfigRequest {
+    pub deployment: String,
+    pub sequence: u64,
+    /// Limits to change, e.g. `{"maxWallets": 50000}`.
+    #[serde(default)]
+    pub limits: serde_json::Map<String, serde_json::Value>,
+    #[serde(default)]
+    pub require_ta_challenge: bool,
+    #[serde(default)]
+    pub require_signing_context: bool,
+    /// 0x-hex personal_sign signature by the provisioning key.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+impl TaConfigRequest {
+    pub fn config(&self) -> Result<TaConfig> {
+        let mut limits = serde_json::to_value(TaLimits::DEFAULT)?;
+        for (name, value) in &self.limits {
+            limits[name.as_str()] = value.clone();
+        }
+        let limits: TaLimits = serde_json::from_value(limits).context("limits")?;
+        let config = TaConfig {
+            format: CONFIG_FORMAT_VERSION,
+            deployment: self.deployment.clone(),
+            sequence: self.sequence,
+            limits,
+            require_ta_challenge: self.require_ta_challenge,
+            require_signing_context: self.require_signing_context,
+        };
+        config.validate().map_err(|e| anyhow!("{}", e))?;
+        Ok(config)
+    }
+
+    /// The signature bytes, or an error carrying the text to sign.
+    pub fn signature(&self, config: &TaConfig) -> Result<Vec<u8>> {
+        match &self.signature {
+            Some(sig) => {
+                hex::decode(sig.trim_start_matches("0x")).context("TA config signature is not hex")
+            }
+            None => Err(anyhow!(
+                "TA config is unsigned; sign exactly this text with the provisioning key:\n{}",
+                config.message()
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(json: &str) -> TaConfigRequest {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn overrides_only_named_limits() {
+        let req = request(
+            r#"{"deployment":"rack-3","sequence":1,"limits":{"maxWallets":50000},"requireTaChallenge":true}"#,
+        );
+        let config = req.config().unwrap();
+        assert_eq!(config.limits.max_wallets, 50_000);
+        assert_eq!(
+            config.limits.challenge_ttl_secs,
+            TaLimits::DEFAULT.challenge_ttl_secs
+        );
+        assert!(config.require_ta_challenge && !config.require_signing_context);
+        assert!(req
+            .signature(&config)
+            .unwrap_err()
+            .to_string()
+            .ends_with(&config.message()));
+        let typo = request(r#"{"deployment":"rack-3","sequence":1,"limits":{"maxWallet":50000}}"#);
+        assert!(typo.config().is_err());
+        let over =
+            request(r#"{"deployment":"rack-3","sequence":1,"limits":{"agentJwtTtlSecs":172800}}"#);
+        assert!(over.config().is_err());
+    }
+}
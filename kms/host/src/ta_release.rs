@@ -0,0 +1,287 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Signed TA release manifests.
+//!
+//! A release ships the `.ta` image with a JSON manifest naming its UUID,
+//! version, sha256 and the oldest CA it works with, personal_signed by a
+//! release key. `airaccount-provision --ta-manifest` checks it before
+//! installing an image; kms-api checks the installed image at startup
+//! (`KMS_TA_MANIFEST`) and, if it does not match, never opens a session to it.
+//!
+//! OP-TEE's own signature check proves the image was built with the TA
+//! signing key; this one pins which build the operator meant to deploy, so
+//! an older signed TA dropped into the TA dir is refused too.
+
+use crate::key_pin::{address_hex, message_digest, recover_signer};
+use crate::node_backup::version_triple;
+use anyhow::{anyhow, bail, Context, Result};
+use proto::SigningContext;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+/// Default OP-TEE TA load directory.
+pub const DEFAULT_TA_DIR: &str = "/lib/optee_armtz";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReleaseManifest {
+    pub ta_uuid: String,
+    pub ta_version: String,
+    /// Hex sha256 of the `.ta` file.
+    pub sha256: String,
+    /// Oldest CA version that speaks this TA's protocol.
+    pub min_ca_version: String,
+    /// 0x-hex personal_sign signature by a release key over [`Self::message`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+/// What a manifest vouched for, once checked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifiedRelease {
+    pub ta_version: String,
+    pub sha256: String,
+    pub min_ca_version: String,
+    pub signer: [u8; 20],
+}
+
+impl ReleaseManifest {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let text =
+            std::fs::read_to_string(path).with_context(|| format!("read {}", path.display()))?;
+        serde_json::from_str(&text).with_context(|| format!("parse {}", path.display()))
+    }
+
+    /// The text the release key signs.
+    pub fn message(&self) -> String {
+        format!(
+            "AirAccount TA release v1\nta: {}\nversion: {}\nsha256: {}\nmin CA version: {}",
+            self.ta_uuid.to_ascii_lowercase(),
+            self.ta_version,
+            self.sha256.to_ascii_lowercase(),
+            self.min_ca_version
+        )
+    }
+
+    /// Check the manifest against `image` and the running CA. Unsigned,
+    /// foreign-signed, mismatched or too-new releases are errors.
+    pub fn verify(
+        &self,
+        image: &[u8],
+        ta_uuid: &str,
+        ca_version: &str,
+        signers: &[[u8; 20]],
+    ) -> Result<VerifiedRelease> {
+        let message = self.message();
+        let signature = match &self.signature {
+            Some(sig) => hex::decode(sig.trim_start_matches("0x"))
+                .context("release manifest signature is not hex")?,
+            None => bail!(
+                "TA release manifest is unsigned; sign exactly this text with a release key:\n{}",
+                message
+            ),
+        };
+        let digest = message_digest(&SigningContext::PersonalMsg, message.as_bytes())
+            .expect("personal_sign digest");
+        let signer = recover_signer(&digest, &signature).context("release manifest signature")?;
+        if !signers.contains(&signer) {
+            bail!(
+                "release manifest is signed by {}, which is not a trusted release key",
+                address_hex(&signer)
+            );
+        }
+        if !self.ta_uuid.eq_ignore_ascii_case(ta_uuid) {
+            bail!(
+                "release manifest is for TA {}, this CA loads {}",
+                self.ta_uuid,
+                ta_uuid
+            );
+        }
+        let sha256 = hex::encode(Sha256::digest(image));
+        if !self.sha256.eq_ignore_ascii_case(&sha256) {
+            bail!(
+                "TA image sha256 {} does not match the release manifest ({})",
+                sha256,
+                self.sha256
+            );
+        }
+        version_triple(&self.ta_version)
+            .ok_or_else(|| anyhow!("bad taVersion {:?}", self.ta_version))?;
+        let min = version_triple(&self.min_ca_version)
+            .ok_or_else(|| anyhow!("bad minCaVersion {:?}", self.min_ca_version))?;
+        let ca = version_triple(ca_version).ok_or_else(|| anyhow!("bad CA version"))?;
+        if ca < min {
+            bail!(
+                "TA {} needs CA {} or newer, this is {}",
+                self.ta_version,
+                self.min_ca_version,
+                ca_version
+            );
+        }
+        Ok(VerifiedRelease {
+            ta_version: self.ta_version.clone(),
+            sha256,
+            min_ca_version: self.min_ca_version.clone(),
+            signer,
+        })
+    }
+}
+
+/// Comma-separated 0x addresses of the release keys.
+pub fn parse_signers(list: &str) -> Result<Vec<[u8; 20]>> {
+    let signers = list
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| {
+            let bytes = hex::decode(s.trim_start_matches("0x"))
+                .ok()
+                .filter(|b| b.len() == 20)
+                .ok_or_else(|| anyhow!("release signer {:?} is not a 20-byte address", s))?;
+            let mut address = [0u8; 20];
+            address.copy_from_slice(&bytes);
+            Ok(address)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    if signers.is_empty() {
+        bail!("no release signers given");
+    }
+    Ok(signers)
+}
+
+/// kms-api's startup check of the installed TA.
+#[derive(Debug, Clone)]
+pub struct ReleaseCheckConfig {
+    pub manifest: PathBuf,
+    pub ta_dir: PathBuf,
+    pub signers: Vec<[u8; 20]>,
+}
+
+impl ReleaseCheckConfig {
+    /// `KMS_TA_MANIFEST` and `KMS_TA_RELEASE_SIGNERS` (`KMS_TA_DIR` defaults
+    /// to the OP-TEE load dir). None if neither is set; naming signers
+    /// without a manifest is an error, so the image counts as unsigned.
+    pub fn from_env() -> Option<Result<Self>> {
+        let var = |k: &str| std::env::var(k).ok().filter(|v| !v.is_empty());
+        let manifest = var("KMS_TA_MANIFEST");
+        let signers = var("KMS_TA_RELEASE_SIGNERS");
+        if manifest.is_none() && signers.is_none() {
+            return None;
+        }
+        Some((|| {
+            let signers =
+                signers.ok_or_else(|| anyhow!("KMS_TA_MANIFEST needs KMS_TA_RELEASE_SIGNERS"))?;
+            let manifest = manifest.ok_or_else(|| {
+                anyhow!("KMS_TA_RELEASE_SIGNERS is set but the TA has no KMS_TA_MANIFEST")
+            })?;
+            Ok(Self {
+                manifest: manifest.into(),
+                ta_dir: var("KMS_TA_DIR")
+                    .unwrap_or_else(|| DEFAULT_TA_DIR.to_string())
+                    .into(),
+                signers: parse_signers(&signers)?,
+            })
+        })())
+    }
+
+    /// Verify `<ta_dir>/<ta_uuid>.ta` against the manifest.
+    pub fn check(&self, ta_uuid: &str, ca_version: &str) -> Result<VerifiedRelease> {
+        let manifest = ReleaseManifest::from_file(&self.manifest)?;
+        let path = self.ta_dir.join(format!("{}.ta", ta_uuid.trim()));
+        let image = std::fs::read(&path).with_context(|| format!("read {}", path.display()))?;
+        manifest.verify(&image, ta_uuid.trim(), ca_version, &self.signers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use k256::ecdsa::SigningKey;
+
+    const UUID: &str = "4319f351-0b24-4097-b659-80ee4f824cdd";
+
+    fn signed(key: &SigningKey, image: &[u8], min_ca: &str) -> ReleaseManifest {
+        let mut m = ReleaseManifest {
+            ta_uuid: UUID.to_string(),
+            ta_version: "0.29.0".to_string(),
+            sha256: hex::encode(Sha256::digest(image)),
+            min_ca_version: min_ca.to_string(),
+            signature: None,
+        };
+        let digest = message_digest(&SigningContext::PersonalMsg, m.message().as_bytes()).unwrap();
+        let (sig, recid) = key.sign_prehash_recoverable(&digest).unwrap();
+        let mut bytes = sig.to_bytes().to_vec();
+        bytes.push(27 + recid.to_byte());
+        m.signature = Some(format!("0x{}", hex::encode(bytes)));
+        m
+    }
+
+    fn address(key: &SigningKey) -> [u8; 20] {
+        let point = key.verifying_key().to_encoded_point(false);
+        let mut a = [0u8; 20];
+        a.copy_from_slice(&sha3::Keccak256::digest(&point.as_bytes()[1..])[12..]);
+        a
+    }
+
+    #[test]
+    fn accepts_only_the_signed_image() {
+        let key = SigningKey::from_slice(&[0x11; 32]).unwrap();
+        let trusted = [address(&key)];
+        let image = b"signed TA image";
+        let m = signed(&key, image, "0.28.0");
+
+        let ok = m.verify(image, UUID, "0.29.0", &trusted).unwrap();
+        assert_eq!(ok.signer, trusted[0]);
+        assert_eq!(ok.ta_version, "0.29.0");
+
+        assert!(m.verify(b"other image", UUID, "0.29.0", &trusted).is_err());
+        assert!(m
+            .verify(image, proto::ETH_WALLET_UUID, "0.29.0", &trusted)
+            .is_err());
+        let stranger = SigningKey::from_slice(&[0x22; 32]).unwrap();
+        assert!(m
+            .verify(image, UUID, "0.29.0", &[address(&stranger)])
+            .is_err());
+
+        let too_new = signed(&key, image, "0.30.0");
+        let err = too_new.verify(image, UUID, "0.29.0", &trusted).unwrap_err();
+        assert!(err.to_string().contains("needs CA 0.30.0"));
+
+        // any edit to a signed field breaks the signature
+        let mut edited = m.clone();
+        edited.min_ca_version = "0.1.0".to_string();
+        assert!(edited.verify(image, UUID, "0.29.0", &trusted).is_err());
+
+        let mut unsigned = m;
+        unsigned.signature = None;
+        let err = unsigned
+            .verify(image, UUID, "0.29.0", &trusted)
+            .unwrap_err();
+        assert!(err.to_string().contains("AirAccount TA release v1"));
+    }
+
+    #[test]
+    fn parses_signer_lists() {
+        let list =
+            "0x9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f, 9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f";
+        assert_eq!(parse_signers(list).unwrap().len(), 2);
+        assert!(parse_signers("0x1234").is_err());
+        assert!(parse_signers(" , ").is_err());
+    }
+}
@@ -0,0 +1,102 @@
+//! Erase-on-tamper — the JSON form of a signed tamper order.
+//!
+//! The same document is accepted by `POST /kms/tamper/order` and by
+//! `airaccount-provision --tamper-order-file`: `action` is the `action:` line
+//! of the signed text (`panic-wipe`, `unlock`, `failed-auth-limit 5`,
+//! `failed-auth-limit off`). The TA checks the signature against the pinned
+//! deployment-policy signer; this side only builds the order and, when the
+//! signature is missing, says exactly what to sign.
+
+use anyhow::{anyhow, Context, Result};
+use proto::tamper::{TamperOrder, TAMPER_ORDER_FORMAT};
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TamperOrderRequest {
+    pub deployment: String,
+    pub sequence: u64,
+    /// 0x-hex attestation-key fingerprint of one device; absent = every
+    /// device of the deployment.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device: Option<String>,
+    pub action: String,
+    /// 0x-hex personal_sign signature over the order text.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+impl TamperOrderRequest {
+    pub fn order(&self) -> Result<TamperOrder> {
+        let device = match &self.device {
+            Some(fp) => {
+                let bytes = hex::decode(fp.trim_start_matches("0x"))
+                    .context("device fingerprint is not hex")?;
+                Some(
+                    <[u8; 32]>::try_from(bytes.as_slice())
+                        .map_err(|_| anyhow!("device fingerprint must be 32 bytes"))?,
+                )
+            }
+            None => None,
+        };
+        let order = TamperOrder {
+            format: TAMPER_ORDER_FORMAT,
+            deployment: self.deployment.clone(),
+            device,
+            sequence: self.sequence,
+            action: self.action.parse().map_err(|e| anyhow!("{}", e))?,
+        };
+        order.validate().map_err(|e| anyhow!("{}", e))?;
+        Ok(order)
+    }
+
+    /// The signature bytes, or an error carrying the text to sign.
+    pub fn signature(&self, order: &TamperOrder) -> Result<Vec<u8>> {
+        match &self.signature {
+            Some(sig) => hex::decode(sig.trim_start_matches("0x")).context("signature is not hex"),
+            None => Err(anyhow!(
+                "tamper order is unsigned; personal_sign exactly this text:\n{}",
+                order.message()
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proto::tamper::TamperAction;
+
+    fn request(json: &str) -> TamperOrderRequest {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn order_from_json() {
+        let req = request(r#"{"deployment":"rack-3","sequence":4,"action":"failed-auth-limit 5"}"#);
+        let order = req.order().unwrap();
+        assert_eq!(order.action, TamperAction::SetFailedAuthLimit(Some(5)));
+        let unsigned = req.signature(&order).unwrap_err().to_string();
+        assert!(unsigned.ends_with(&order.message()));
+
+        let targeted = request(&format!(
+            r#"{{"deployment":"rack-3","sequence":5,"device":"0x{}","action":"unlock","signature":"0x1b"}}"#,
+            "ab".repeat(32)
+        ));
+        let order = targeted.order().unwrap();
+        assert_eq!(order.device, Some([0xab; 32]));
+        assert_eq!(targeted.signature(&order).unwrap(), vec![0x1b]);
+
+        assert!(
+            request(r#"{"deployment":"rack-3","sequence":6,"action":"failed-auth-limit 1"}"#)
+                .order()
+                .is_err()
+        );
+        assert!(request(
+            r#"{"deployment":"rack-3","sequence":6,"device":"0xab","action":"unlock"}"#
+        )
+        .order()
+        .is_err());
+    }
+}
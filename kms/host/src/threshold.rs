@@ -0,0 +1,714 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! 2-of-2 threshold ECDSA, CA side (feature `threshold-ecdsa`). The TA holds
+//! one share of a wallet's threshold key and a remote co-signer the other;
+//! the CA only carries the `proto::threshold` messages between them and
+//! sees nothing that signs.
+//!
+//! [`ThresholdTa`] is the TA's half ([`TeeHandle`] implements it) and
+//! [`CoSigner`] the remote one; [`HttpCoSigner`] posts each message as JSON
+//! to `KMS_THRESHOLD_COSIGNER_URL`. The flows:
+//!
+//! - [`keygen`]: the TA commits to its share, the co-signer answers with its
+//!   own, the TA opens the commitment and the co-signer checks it.
+//! - [`refill`]: the same exchange for a batch of nonces, which become the
+//!   presignatures both sides keep.
+//! - [`sign`]: the co-signer's half on its oldest presignature, finished by
+//!   the TA. One co-signer round trip, plus a refill when the pool is empty.
+//! - Fallback: the co-signer seals its share to the TA
+//!   ([`CoSigner::seal_share`]) and `ThresholdRecombine` takes it in with the
+//!   owner's passkey; from then on [`sign`] skips the co-signer.
+
+use anyhow::{anyhow, bail, Context, Result};
+use proto::threshold::{
+    KeygenCommit, KeygenReveal, PartyKey, PresignCommit, PresignReply, PresignReveal, SignReply,
+    MAX_PRESIGN_BATCH,
+};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::future::Future;
+use std::pin::Pin;
+use uuid::Uuid;
+
+pub type ThresholdFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T>> + Send + 'a>>;
+
+/// How long the co-signer gets per message.
+pub const COSIGNER_TIMEOUT_SECS: u64 = 10;
+
+/// The remote party. Messages are the `proto::threshold` types; each method
+/// is one request.
+pub trait CoSigner: Send + Sync {
+    fn keygen<'a>(
+        &'a self,
+        wallet_id: Uuid,
+        commit: &'a KeygenCommit,
+    ) -> ThresholdFuture<'a, PartyKey>;
+
+    fn keygen_finish<'a>(
+        &'a self,
+        wallet_id: Uuid,
+        reveal: &'a KeygenReveal,
+    ) -> ThresholdFuture<'a, ()>;
+
+    fn presign<'a>(
+        &'a self,
+        wallet_id: Uuid,
+        commits: &'a [PresignCommit],
+    ) -> ThresholdFuture<'a, Vec<PresignReply>>;
+
+    fn presign_finish<'a>(
+        &'a self,
+        wallet_id: Uuid,
+        reveals: &'a [PresignReveal],
+    ) -> ThresholdFuture<'a, ()>;
+
+    /// Its half of a signature on `digest`, on its oldest presignature.
+    fn sign(&self, wallet_id: Uuid, digest: [u8; 32]) -> ThresholdFuture<'_, SignReply>;
+
+    /// Its share, sealed to the TA's share point.
+    fn seal_share(&self, wallet_id: Uuid) -> ThresholdFuture<'_, Vec<u8>>;
+}
+
+/// The TA's half: one method per `Threshold*` command.
+pub trait ThresholdTa: Send + Sync {
+    fn keygen_begin(
+        &self,
+        input: proto::ThresholdKeygenBeginInput,
+    ) -> ThresholdFuture<'_, proto::ThresholdKeygenBeginOutput>;
+    fn keygen_finish(
+        &self,
+        input: proto::ThresholdKeygenFinishInput,
+    ) -> ThresholdFuture<'_, proto::ThresholdKeygenFinishOutput>;
+    fn presign_begin(
+        &self,
+        input: proto::ThresholdPresignBeginInput,
+    ) -> ThresholdFuture<'_, proto::ThresholdPresignBeginOutput>;
+    fn presign_finish(
+        &self,
+        input: proto::ThresholdPresignFinishInput,
+    ) -> ThresholdFuture<'_, proto::ThresholdPresignFinishOutput>;
+    fn sign(
+        &self,
+        input: proto::ThresholdSignInput,
+    ) -> ThresholdFuture<'_, proto::ThresholdSignOutput>;
+    fn status(
+        &self,
+        input: proto::ThresholdStatusInput,
+    ) -> ThresholdFuture<'_, proto::ThresholdStatusOutput>;
+}
+
+#[cfg(any(feature = "tee", feature = "soft-tee"))]
+impl ThresholdTa for crate::ta_client::TeeHandle {
+    fn keygen_begin(
+        &self,
+        input: proto::ThresholdKeygenBeginInput,
+    ) -> ThresholdFuture<'_, proto::ThresholdKeygenBeginOutput> {
+        Box::pin(self.threshold_keygen_begin(input))
+    }
+    fn keygen_finish(
+        &self,
+        input: proto::ThresholdKeygenFinishInput,
+    ) -> ThresholdFuture<'_, proto::ThresholdKeygenFinishOutput> {
+        Box::pin(self.threshold_keygen_finish(input))
+    }
+    fn presign_begin(
+        &self,
+        input: proto::ThresholdPresignBeginInput,
+    ) -> ThresholdFuture<'_, proto::ThresholdPresignBeginOutput> {
+        Box::pin(self.threshold_presign_begin(input))
+    }
+    fn presign_finish(
+        &self,
+        input: proto::ThresholdPresignFinishInput,
+    ) -> ThresholdFuture<'_, proto::ThresholdPresignFinishOutput> {
+        Box::pin(self.threshold_presign_finish(input))
+    }
+    fn sign(
+        &self,
+        input: proto::ThresholdSignInput,
+    ) -> ThresholdFuture<'_, proto::ThresholdSignOutput> {
+        Box::pin(self.threshold_sign(input))
+    }
+    fn status(
+        &self,
+        input: proto::ThresholdStatusInput,
+    ) -> ThresholdFuture<'_, proto::ThresholdStatusOutput> {
+        Box::pin(self.threshold_status(input))
+    }
+}
+
+/// Create `wallet_id`'s threshold key. The assertion commits to
+/// `proto::threshold::keygen_commitment(wallet_id)`.
+pub async fn keygen(
+    ta: &dyn ThresholdTa,
+    cosigner: &dyn CoSigner,
+    wallet_id: Uuid,
+    passkey_assertion: Option<proto::PasskeyAssertion>,
+) -> Result<proto::ThresholdKeygenFinishOutput> {
+    let begin = ta
+        .keygen_begin(proto::ThresholdKeygenBeginInput {
+            wallet_id,
+            passkey_assertion,
+        })
+        .await?;
+    let cosigner_key = cosigner
+        .keygen(wallet_id, &begin.commit)
+        .await
+        .context("co-signer keygen")?;
+    let finish = ta
+        .keygen_finish(proto::ThresholdKeygenFinishInput {
+            wallet_id,
+            cosigner_key,
+        })
+        .await?;
+    cosigner
+        .keygen_finish(wallet_id, &finish.reveal)
+        .await
+        .context("co-signer keygen")?;
+    Ok(finish)
+}
+
+/// Add `count` presignatures, in batches of `MAX_PRESIGN_BATCH`; returns
+/// the TA's pool size. A batch the co-signer fails to finish leaves the
+/// TA ahead of it, which the next signature clears.
+pub async fn refill(
+    ta: &dyn ThresholdTa,
+    cosigner: &dyn CoSigner,
+    wallet_id: Uuid,
+    count: u32,
+) -> Result<u32> {
+    let mut left = count;
+    let mut available = 0;
+    while left > 0 {
+        let batch = left.min(MAX_PRESIGN_BATCH as u32);
+        let begin = ta
+            .presign_begin(proto::ThresholdPresignBeginInput {
+                wallet_id,
+                count: batch,
+            })
+            .await?;
+        let replies = cosigner
+            .presign(wallet_id, &begin.commits)
+            .await
+            .context("co-signer presign")?;
+        let finish = ta
+            .presign_finish(proto::ThresholdPresignFinishInput { wallet_id, replies })
+            .await?;
+        cosigner
+            .presign_finish(wallet_id, &finish.reveals)
+            .await
+            .context("co-signer presign")?;
+        available = finish.available;
+        left -= batch;
+    }
+    Ok(available)
+}
+
+/// Sign `digest` with `wallet_id`'s threshold key. The assertion commits to
+/// `proto::threshold::sign_commitment(wallet_id, digest)`; after the
+/// fallback ceremony the TA signs alone.
+pub async fn sign(
+    ta: &dyn ThresholdTa,
+    cosigner: &dyn CoSigner,
+    wallet_id: Uuid,
+    digest: [u8; 32],
+    passkey_assertion: Option<proto::PasskeyAssertion>,
+) -> Result<proto::ThresholdSignOutput> {
+    let status = ta.status(proto::ThresholdStatusInput { wallet_id }).await?;
+    if status.public_key.is_none() {
+        bail!("wallet has no threshold key: {}", wallet_id);
+    }
+    let reply = if status.recombined {
+        None
+    } else {
+        if status.available == 0 {
+            refill(ta, cosigner, wallet_id, MAX_PRESIGN_BATCH as u32).await?;
+        }
+        Some(
+            cosigner
+                .sign(wallet_id, digest)
+                .await
+                .context("co-signer sign")?,
+        )
+    };
+    ta.sign(proto::ThresholdSignInput {
+        wallet_id,
+        digest,
+        reply,
+        passkey_assertion,
+    })
+    .await
+}
+
+/// POSTs `{"walletId", <message>}` to `<url>/<step>` and reads the reply
+/// message as JSON. Steps: `keygen`, `keygen-finish`, `presign`,
+/// `presign-finish`, `sign`, `seal-share`.
+pub struct HttpCoSigner {
+    pub url: String,
+}
+
+impl HttpCoSigner {
+    /// `KMS_THRESHOLD_COSIGNER_URL` (http://…); without it the threshold
+    /// routes answer 503.
+    pub fn from_env() -> Option<Result<Self>> {
+        let url = std::env::var("KMS_THRESHOLD_COSIGNER_URL")
+            .ok()
+            .filter(|v| !v.is_empty())?;
+        Some(Self::new(&url))
+    }
+
+    pub fn new(url: &str) -> Result<Self> {
+        if !url.starts_with("http://") {
+            bail!("KMS_THRESHOLD_COSIGNER_URL must be an http:// URL (no TLS in the CA)");
+        }
+        Ok(Self {
+            url: url.trim_end_matches('/').to_string(),
+        })
+    }
+
+    async fn post<T: DeserializeOwned>(&self, step: &str, body: Value) -> Result<T> {
+        use warp::hyper::{body, Body, Client, Request};
+        let req = Request::post(format!("{}/{}", self.url, step))
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))?;
+        let resp = tokio::time::timeout(
+            std::time::Duration::from_secs(COSIGNER_TIMEOUT_SECS),
+            Client::new().request(req),
+        )
+        .await
+        .map_err(|_| {
+            anyhow!(
+                "co-signer did not answer {} within {}s",
+                step,
+                COSIGNER_TIMEOUT_SECS
+            )
+        })?
+        .context("co-signer unreachable")?;
+        let status = resp.status();
+        let bytes = body::to_bytes(resp.into_body()).await?;
+        if !status.is_success() {
+            bail!("co-signer returned HTTP {} for {}", status, step);
+        }
+        serde_json::from_slice(&bytes).with_context(|| format!("co-signer {} reply", step))
+    }
+}
+
+impl CoSigner for HttpCoSigner {
+    fn keygen<'a>(
+        &'a self,
+        wallet_id: Uuid,
+        commit: &'a KeygenCommit,
+    ) -> ThresholdFuture<'a, PartyKey> {
+        Box::pin(self.post("keygen", json!({ "walletId": wallet_id, "commit": commit })))
+    }
+
+    fn keygen_finish<'a>(
+        &'a self,
+        wallet_id: Uuid,
+        reveal: &'a KeygenReveal,
+    ) -> ThresholdFuture<'a, ()> {
+        Box::pin(async move {
+            self.post::<serde::de::IgnoredAny>(
+                "keygen-finish",
+                json!({ "walletId": wallet_id, "reveal": reveal }),
+            )
+            .await
+            .map(|_| ())
+        })
+    }
+
+    fn presign<'a>(
+        &'a self,
+        wallet_id: Uuid,
+        commits: &'a [PresignCommit],
+    ) -> ThresholdFuture<'a, Vec<PresignReply>> {
+        Box::pin(self.post(
+            "presign",
+            json!({ "walletId": wallet_id, "commits": commits }),
+        ))
+    }
+
+    fn presign_finish<'a>(
+        &'a self,
+        wallet_id: Uuid,
+        reveals: &'a [PresignReveal],
+    ) -> ThresholdFuture<'a, ()> {
+        Box::pin(async move {
+            self.post::<serde::de::IgnoredAny>(
+                "presign-finish",
+                json!({ "walletId": wallet_id, "reveals": reveals }),
+            )
+            .await
+            .map(|_| ())
+        })
+    }
+
+    fn sign(&self, wallet_id: Uuid, digest: [u8; 32]) -> ThresholdFuture<'_, SignReply> {
+        Box::pin(self.post(
+            "sign",
+            json!({ "walletId": wallet_id, "digest": format!("0x{}", hex::encode(digest)) }),
+        ))
+    }
+
+    fn seal_share(&self, wallet_id: Uuid) -> ThresholdFuture<'_, Vec<u8>> {
+        Box::pin(self.post("seal-share", json!({ "walletId": wallet_id })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use k256::ecdsa::{RecoveryId, Signature, VerifyingKey};
+    use proto::threshold::{self as t, CoSignerPresignState, CoSignerPresignature};
+    use std::sync::Mutex;
+
+    struct OsRng;
+
+    impl proto::provider::Rng for OsRng {
+        fn fill(&mut self, buf: &mut [u8]) {
+            rand::RngCore::fill_bytes(&mut rand::rngs::OsRng, buf)
+        }
+    }
+
+    fn err<E: std::fmt::Display>(e: E) -> anyhow::Error {
+        anyhow!("{}", e)
+    }
+
+    /// The TA's side of the commands, on `proto::threshold` alone. The
+    /// passkey is not checked; the TA does that.
+    #[derive(Default)]
+    struct FakeTa(Mutex<TaState>);
+
+    #[derive(Default)]
+    struct TaState {
+        keygen: Option<t::TaKeygenState>,
+        share: Option<t::TaShare>,
+        next_id: u64,
+        pending: Vec<t::TaPresignState>,
+        pool: Vec<t::TaPresignature>,
+        recombined: Option<t::RecombinedKey>,
+    }
+
+    impl FakeTa {
+        fn recombine(&self, sealed_share: &[u8]) -> Result<()> {
+            let mut s = self.0.lock().unwrap();
+            let share = s.share.take().ok_or_else(|| anyhow!("no share"))?;
+            let secret = t::recombine(&share, sealed_share).map_err(err)?;
+            s.pool.clear();
+            s.recombined = Some(t::RecombinedKey {
+                secret,
+                public_key: share.public_key.clone(),
+            });
+            Ok(())
+        }
+    }
+
+    impl ThresholdTa for FakeTa {
+        fn keygen_begin(
+            &self,
+            input: proto::ThresholdKeygenBeginInput,
+        ) -> ThresholdFuture<'_, proto::ThresholdKeygenBeginOutput> {
+            let (state, commit) = t::keygen_commit(&mut OsRng, &input.wallet_id);
+            self.0.lock().unwrap().keygen = Some(state);
+            Box::pin(async move { Ok(proto::ThresholdKeygenBeginOutput { commit }) })
+        }
+
+        fn keygen_finish(
+            &self,
+            input: proto::ThresholdKeygenFinishInput,
+        ) -> ThresholdFuture<'_, proto::ThresholdKeygenFinishOutput> {
+            Box::pin(async move {
+                let mut s = self.0.lock().unwrap();
+                let state = s.keygen.take().ok_or_else(|| anyhow!("no keygen"))?;
+                let primes = t::paillier_primes(&mut OsRng);
+                let (share, reveal) = t::keygen_finish(
+                    &mut OsRng,
+                    &input.wallet_id,
+                    &state,
+                    &input.cosigner_key,
+                    &primes,
+                )
+                .map_err(err)?;
+                let public_key = share.public_key.clone();
+                let address = t::address(&public_key).map_err(err)?;
+                s.share = Some(share);
+                Ok(proto::ThresholdKeygenFinishOutput {
+                    reveal,
+                    public_key,
+                    address,
+                })
+            })
+        }
+
+        fn presign_begin(
+            &self,
+            input: proto::ThresholdPresignBeginInput,
+        ) -> ThresholdFuture<'_, proto::ThresholdPresignBeginOutput> {
+            Box::pin(async move {
+                let mut s = self.0.lock().unwrap();
+                let (states, commits) = t::presign_commit(
+                    &mut OsRng,
+                    &input.wallet_id,
+                    s.next_id,
+                    input.count as usize,
+                )
+                .map_err(err)?;
+                s.next_id += input.count as u64;
+                s.pending = states;
+                Ok(proto::ThresholdPresignBeginOutput { commits })
+            })
+        }
+
+        fn presign_finish(
+            &self,
+            input: proto::ThresholdPresignFinishInput,
+        ) -> ThresholdFuture<'_, proto::ThresholdPresignFinishOutput> {
+            Box::pin(async move {
+                let mut s = self.0.lock().unwrap();
+                let pending = std::mem::take(&mut s.pending);
+                let (presignatures, reveals) =
+                    t::presign_finish(&input.wallet_id, &pending, &input.replies).map_err(err)?;
+                s.pool.extend(presignatures);
+                Ok(proto::ThresholdPresignFinishOutput {
+                    reveals,
+                    available: s.pool.len() as u32,
+                })
+            })
+        }
+
+        fn sign(
+            &self,
+            input: proto::ThresholdSignInput,
+        ) -> ThresholdFuture<'_, proto::ThresholdSignOutput> {
+            Box::pin(async move {
+                let mut s = self.0.lock().unwrap();
+                let signature = match (&s.recombined, &input.reply) {
+                    (Some(key), _) => t::sign_with_secret(&key.secret, &input.digest),
+                    (None, Some(reply)) => {
+                        let at = s
+                            .pool
+                            .iter()
+                            .position(|p| p.id == reply.presignature_id)
+                            .ok_or_else(|| anyhow!("presignature not in the pool"))?;
+                        let presignature = s.pool.drain(..=at).next_back().unwrap();
+                        let share = s.share.as_ref().unwrap();
+                        t::sign_finish(share, &presignature, &input.digest, reply)
+                    }
+                    (None, None) => return Err(anyhow!("no co-signer reply")),
+                }
+                .map_err(err)?;
+                Ok(proto::ThresholdSignOutput {
+                    signature: signature.to_vec(),
+                    available: s.pool.len() as u32,
+                })
+            })
+        }
+
+        fn status(
+            &self,
+            _input: proto::ThresholdStatusInput,
+        ) -> ThresholdFuture<'_, proto::ThresholdStatusOutput> {
+            Box::pin(async move {
+                let s = self.0.lock().unwrap();
+                let public_key = match (&s.recombined, &s.share) {
+                    (Some(key), _) => Some(key.public_key.clone()),
+                    (None, Some(share)) => Some(share.public_key.clone()),
+                    (None, None) => None,
+                };
+                Ok(proto::ThresholdStatusOutput {
+                    address: public_key.as_deref().map(|k| t::address(k).unwrap()),
+                    public_key,
+                    available: s.pool.len() as u32,
+                    recombined: s.recombined.is_some(),
+                })
+            })
+        }
+    }
+
+    /// An in-process co-signer; `up` false makes it refuse everything.
+    struct LocalCoSigner {
+        state: Mutex<CoSignerState>,
+        up: std::sync::atomic::AtomicBool,
+    }
+
+    #[derive(Default)]
+    struct CoSignerState {
+        keygen: Option<t::CoSignerKeygenState>,
+        share: Option<t::CoSignerShare>,
+        pending: Vec<CoSignerPresignState>,
+        pool: Vec<CoSignerPresignature>,
+    }
+
+    impl LocalCoSigner {
+        fn new() -> Self {
+            LocalCoSigner {
+                state: Mutex::new(CoSignerState::default()),
+                up: std::sync::atomic::AtomicBool::new(true),
+            }
+        }
+
+        fn with<T>(
+            &self,
+            f: impl FnOnce(&mut CoSignerState) -> Result<T, &'static str>,
+        ) -> Result<T> {
+            if !self.up.load(std::sync::atomic::Ordering::SeqCst) {
+                bail!("co-signer unreachable");
+            }
+            f(&mut self.state.lock().unwrap()).map_err(err)
+        }
+    }
+
+    impl CoSigner for LocalCoSigner {
+        fn keygen<'a>(
+            &'a self,
+            wallet_id: Uuid,
+            commit: &'a KeygenCommit,
+        ) -> ThresholdFuture<'a, PartyKey> {
+            let out = self.with(|s| {
+                let (state, key) = t::cosigner_keygen(&mut OsRng, &wallet_id, commit);
+                s.keygen = Some(state);
+                Ok(key)
+            });
+            Box::pin(async move { out })
+        }
+
+        fn keygen_finish<'a>(
+            &'a self,
+            wallet_id: Uuid,
+            reveal: &'a KeygenReveal,
+        ) -> ThresholdFuture<'a, ()> {
+            let out = self.with(|s| {
+                let state = s.keygen.take().ok_or("no keygen")?;
+                s.share = Some(t::cosigner_keygen_finish(&wallet_id, &state, reveal)?);
+                Ok(())
+            });
+            Box::pin(async move { out })
+        }
+
+        fn presign<'a>(
+            &'a self,
+            wallet_id: Uuid,
+            commits: &'a [PresignCommit],
+        ) -> ThresholdFuture<'a, Vec<PresignReply>> {
+            let out = self.with(|s| {
+                let (states, replies) = t::cosigner_presign(&mut OsRng, &wallet_id, commits)?;
+                s.pending = states;
+                Ok(replies)
+            });
+            Box::pin(async move { out })
+        }
+
+        fn presign_finish<'a>(
+            &'a self,
+            wallet_id: Uuid,
+            reveals: &'a [PresignReveal],
+        ) -> ThresholdFuture<'a, ()> {
+            let out = self.with(|s| {
+                let pending = std::mem::take(&mut s.pending);
+                let pool = t::cosigner_presign_finish(&wallet_id, &pending, reveals)?;
+                s.pool.extend(pool);
+                Ok(())
+            });
+            Box::pin(async move { out })
+        }
+
+        fn sign(&self, _wallet_id: Uuid, digest: [u8; 32]) -> ThresholdFuture<'_, SignReply> {
+            let out = self.with(|s| {
+                if s.pool.is_empty() {
+                    return Err("no presignature");
+                }
+                let presignature = s.pool.remove(0);
+                t::cosigner_sign(
+                    &mut OsRng,
+                    s.share.as_ref().unwrap(),
+                    &presignature,
+                    &digest,
+                )
+            });
+            Box::pin(async move { out })
+        }
+
+        fn seal_share(&self, _wallet_id: Uuid) -> ThresholdFuture<'_, Vec<u8>> {
+            let out = self.with(|s| t::cosigner_seal_share(&mut OsRng, s.share.as_ref().unwrap()));
+            Box::pin(async move { out })
+        }
+    }
+
+    fn signer_of(digest: &[u8; 32], signature: &[u8]) -> [u8; 20] {
+        let key = VerifyingKey::recover_from_prehash(
+            digest,
+            &Signature::from_slice(&signature[..64]).unwrap(),
+            RecoveryId::from_byte(signature[64] - 27).unwrap(),
+        )
+        .unwrap();
+        t::address(key.to_encoded_point(true).as_bytes()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn keygen_presign_sign_and_fallback() {
+        let ta = FakeTa::default();
+        let cosigner = LocalCoSigner::new();
+        let w = Uuid::new_v4();
+
+        let key = keygen(&ta, &cosigner, w, None).await.unwrap();
+
+        // The first signature fills the empty pool.
+        let digest = [0x5a; 32];
+        let out = sign(&ta, &cosigner, w, digest, None).await.unwrap();
+        assert_eq!(signer_of(&digest, &out.signature), key.address);
+        assert_eq!(out.available, MAX_PRESIGN_BATCH as u32 - 1);
+
+        // A presignature the co-signer lost is cleared by the next one used.
+        cosigner.state.lock().unwrap().pool.remove(0);
+        let digest = [0x5b; 32];
+        let out = sign(&ta, &cosigner, w, digest, None).await.unwrap();
+        assert_eq!(signer_of(&digest, &out.signature), key.address);
+        assert_eq!(out.available, MAX_PRESIGN_BATCH as u32 - 3);
+        assert_eq!(refill(&ta, &cosigner, w, 10).await.unwrap(), 15);
+
+        // Co-signer gone: nothing signs until its sealed share is taken in.
+        let sealed = cosigner.seal_share(w).await.unwrap();
+        cosigner
+            .up
+            .store(false, std::sync::atomic::Ordering::SeqCst);
+        assert!(sign(&ta, &cosigner, w, digest, None).await.is_err());
+        ta.recombine(&sealed).unwrap();
+        let digest = [0x5c; 32];
+        let out = sign(&ta, &cosigner, w, digest, None).await.unwrap();
+        assert_eq!(signer_of(&digest, &out.signature), key.address);
+    }
+
+    #[tokio::test]
+    async fn no_key_no_signature() {
+        let ta = FakeTa::default();
+        let cosigner = LocalCoSigner::new();
+        let e = sign(&ta, &cosigner, Uuid::new_v4(), [1; 32], None)
+            .await
+            .unwrap_err();
+        assert!(e.to_string().contains("no threshold key"), "{}", e);
+    }
+
+    #[test]
+    fn cosigner_url_must_be_http() {
+        assert!(HttpCoSigner::new("https://cosigner.example").is_err());
+        assert_eq!(
+            HttpCoSigner::new("http://cosigner:8080/").unwrap().url,
+            "http://cosigner:8080"
+        );
+    }
+}
@@ -0,0 +1,177 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! `POST /kms/transfer/token` — an ERC-20 transfer from a human amount.
+//!
+//! Bundled tokens are named by symbol ("12.50 USDC"); anything else by
+//! address plus its `decimals`. The amount goes through
+//! `proto::amount::TokenAmount`, so it is exact or refused. The result is
+//! signed through the existing paths — a `transfer(to, amount)` transaction
+//! through `Sign`, or with `permit` an EIP-2612 permit for exactly that amount
+//! through `SignPermit` — so the TA's summary check and allowance guard apply
+//! unchanged.
+
+use anyhow::{anyhow, Result};
+use proto::amount::{split_symbol, TokenAmount};
+use proto::calldata::{selector, token_by_symbol, token_info};
+use proto::eip55::parse_address;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedToken {
+    pub address: [u8; 20],
+    /// None for a token outside the bundled list.
+    pub symbol: Option<&'static str>,
+    pub decimals: u32,
+}
+
+/// `token` is a bundled symbol or a 0x address. `decimals` is required for an
+/// unlisted address and, when given for a listed token, must match.
+pub fn resolve_token(chain_id: u64, token: &str, decimals: Option<u32>) -> Result<ResolvedToken> {
+    let token = token.trim();
+    let resolved = if token.starts_with("0x") {
+        let address = parse_address(token).map_err(|e| anyhow!("token: {}", e))?;
+        match (token_info(chain_id, &address), decimals) {
+            (Some((symbol, d)), _) => ResolvedToken {
+                address,
+                symbol: Some(symbol),
+                decimals: d,
+            },
+            (None, Some(d)) => ResolvedToken {
+                address,
+                symbol: None,
+                decimals: d,
+            },
+            (None, None) => {
+                return Err(anyhow!(
+                    "token {} is not bundled for chain {}; pass its decimals",
+                    token,
+                    chain_id
+                ))
+            }
+        }
+    } else {
+        let (address, d) = token_by_symbol(chain_id, token).ok_or_else(|| {
+            anyhow!(
+                "unknown token {} on chain {}; pass its address and decimals",
+                token,
+                chain_id
+            )
+        })?;
+        ResolvedToken {
+            address,
+            symbol: token_info(chain_id, &address).map(|(s, _)| s),
+            decimals: d,
+        }
+    };
+    if let Some(given) = decimals {
+        if given != resolved.decimals {
+            return Err(anyhow!(
+                "token has {} decimals, not {}",
+                resolved.decimals,
+                given
+            ));
+        }
+    }
+    Ok(resolved)
+}
+
+/// "12.50" or "12.50 USDC". A symbol, if written, must be the token's own.
+pub fn parse_amount(text: &str, token: &ResolvedToken) -> Result<TokenAmount> {
+    let (number, symbol) = split_symbol(text);
+    if let Some(symbol) = symbol {
+        if !matches!(token.symbol, Some(s) if s.eq_ignore_ascii_case(symbol)) {
+            return Err(anyhow!(
+                "amount is in {} but the token is {}",
+                symbol,
+                token.symbol.unwrap_or("an unlisted token")
+            ));
+        }
+    }
+    let amount = TokenAmount::parse(number, token.decimals).map_err(|e| anyhow!(e))?;
+    if amount.base_units == 0 {
+        return Err(anyhow!("amount must be greater than zero"));
+    }
+    Ok(amount)
+}
+
+/// ABI-encoded `transfer(to, amount)`.
+pub fn transfer_calldata(to: &[u8; 20], amount: &TokenAmount) -> Vec<u8> {
+    let mut data = selector("transfer(address,uint256)").to_vec();
+    data.extend_from_slice(&[0u8; 12]);
+    data.extend_from_slice(to);
+    data.extend_from_slice(&amount.to_word());
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proto::calldata::{decode_transaction, DecodedCall};
+    use proto::EthTransaction;
+
+    const USDC: &str = "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48";
+
+    #[test]
+    fn symbol_and_address_resolve_to_the_same_token() {
+        let by_symbol = resolve_token(1, "usdc", None).unwrap();
+        let by_address = resolve_token(1, USDC, Some(6)).unwrap();
+        assert_eq!(by_symbol, by_address);
+        assert_eq!(by_symbol.symbol, Some("USDC"));
+        assert!(resolve_token(1, USDC, Some(18)).is_err());
+        assert!(resolve_token(5, "USDC", None).is_err());
+        let other = "0x1111111111111111111111111111111111111111";
+        assert!(resolve_token(1, other, None).is_err());
+        assert_eq!(resolve_token(1, other, Some(8)).unwrap().decimals, 8);
+    }
+
+    #[test]
+    fn amounts_are_exact_and_checked_against_the_symbol() {
+        let usdc = resolve_token(1, "USDC", None).unwrap();
+        assert_eq!(
+            parse_amount("12.50 USDC", &usdc).unwrap().base_units,
+            12_500_000
+        );
+        assert!(parse_amount("12.50 DAI", &usdc).is_err());
+        assert!(parse_amount("12.5000001", &usdc).is_err());
+        assert!(parse_amount("0", &usdc).is_err());
+    }
+
+    #[test]
+    fn calldata_decodes_as_the_transfer_the_ta_will_summarise() {
+        let usdc = resolve_token(1, "USDC", None).unwrap();
+        let amount = parse_amount("12.5", &usdc).unwrap();
+        let to = [0x22; 20];
+        let tx = EthTransaction {
+            chain_id: 1,
+            nonce: 0,
+            to: Some(usdc.address),
+            value: 0,
+            gas_price: 1,
+            gas: 100_000,
+            data: transfer_calldata(&to, &amount),
+        };
+        assert_eq!(
+            decode_transaction(&tx, &[]),
+            DecodedCall::Transfer {
+                token: usdc.address,
+                to,
+                amount: amount.to_word(),
+            }
+        );
+        assert!(proto::calldata::summarize_transaction(&tx, &[]).contains("12.5 USDC"));
+    }
+}
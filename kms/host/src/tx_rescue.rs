@@ -0,0 +1,189 @@
+//! Stuck-transaction rescue — replacement building and nonce-gap detection.
+//!
+//! A legacy transaction stuck in the mempool is replaced by signing another
+//! one at the same nonce with a higher gas price: either the same call again
+//! (speed-up) or a zero-value transfer to self (cancel). Nodes only accept
+//! the replacement if the price rises by at least 10%. A nonce that was never
+//! signed (or whose transaction was dropped) blocks every later one; it is
+//! repaired with a self-transfer at that nonce. Pure functions here; the
+//! ledger is `tx_history` in db.rs and the endpoint is in api_server.rs.
+
+use anyhow::{anyhow, Result};
+use proto::offline::decode_signed_legacy;
+use proto::EthTransaction;
+use sha3::{Digest, Keccak256};
+use std::convert::TryFrom;
+
+use crate::db::SignedTxEntry;
+
+/// Replacement rule enforced by geth/erigon txpools (`--txpool.pricebump`).
+pub const MIN_FEE_BUMP_PERCENT: u32 = 10;
+pub const DEFAULT_FEE_BUMP_PERCENT: u32 = 15;
+/// Keep a typo from signing a replacement at 100× the original price.
+pub const MAX_FEE_BUMP_PERCENT: u32 = 500;
+/// Gas limit of a plain ETH transfer (the cancel transaction).
+pub const TRANSFER_GAS: u128 = 21_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RescueMode {
+    /// Same call, higher gas price.
+    SpeedUp,
+    /// Zero-value self-transfer, higher gas price.
+    Cancel,
+}
+
+impl RescueMode {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s {
+            "speedup" => Ok(RescueMode::SpeedUp),
+            "cancel" => Ok(RescueMode::Cancel),
+            other => Err(anyhow!(
+                "mode must be \"speedup\" or \"cancel\", got {:?}",
+                other
+            )),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RescueMode::SpeedUp => "speedup",
+            RescueMode::Cancel => "cancel",
+        }
+    }
+}
+
+/// Gas price for a replacement: `old` raised by `percent` (rounded up, and by
+/// at least 1 wei), or `floor` if that is higher.
+pub fn bumped_gas_price(old: u128, percent: u32, floor: Option<u128>) -> Result<u128> {
+    if !(MIN_FEE_BUMP_PERCENT..=MAX_FEE_BUMP_PERCENT).contains(&percent) {
+        return Err(anyhow!(
+            "feeBumpPercent must be {}..={}",
+            MIN_FEE_BUMP_PERCENT,
+            MAX_FEE_BUMP_PERCENT
+        ));
+    }
+    let scaled = old
+        .checked_mul(100 + percent as u128)
+        .ok_or_else(|| anyhow!("gas price overflow"))?;
+    let bumped = scaled.div_ceil(100).max(old + 1);
+    Ok(bumped.max(floor.unwrap_or(0)))
+}
+
+/// The transaction to sign in place of `original` (same chain and nonce).
+pub fn replacement(
+    original: &EthTransaction,
+    from: [u8; 20],
+    mode: RescueMode,
+    gas_price: u128,
+) -> EthTransaction {
+    match mode {
+        RescueMode::SpeedUp => EthTransaction {
+            gas_price,
+            ..original.clone()
+        },
+        RescueMode::Cancel => self_transfer(original.chain_id, original.nonce, from, gas_price),
+    }
+}
+
+/// Zero-value transfer to self: cancels a stuck nonce or fills a gap.
+pub fn self_transfer(
+    chain_id: u64,
+    nonce: u128,
+    from: [u8; 20],
+    gas_price: u128,
+) -> EthTransaction {
+    EthTransaction {
+        chain_id,
+        nonce,
+        to: Some(from),
+        value: 0,
+        gas_price,
+        gas: TRANSFER_GAS,
+        data: vec![],
+    }
+}
+
+/// Nonces below the highest pending one that have no pending transaction.
+/// `confirmed_nonce` is the account's on-chain nonce (the next one to be
+/// mined); without it the ledger's lowest pending nonce is the start.
+pub fn nonce_gaps(pending: &[u64], confirmed_nonce: Option<u64>) -> Vec<u64> {
+    let max = match pending.iter().max() {
+        Some(m) => *m,
+        None => return Vec::new(),
+    };
+    let start = confirmed_nonce.unwrap_or_else(|| *pending.iter().min().unwrap_or(&max));
+    (start..max).filter(|n| !pending.contains(n)).collect()
+}
+
+/// Ledger entry for a transaction the TA just signed.
+pub fn ledger_entry(
+    key_id: &str,
+    derivation_path: &str,
+    address: &str,
+    signed_tx: &[u8],
+) -> Result<SignedTxEntry> {
+    let signed =
+        decode_signed_legacy(signed_tx).map_err(|e| anyhow!("signed transaction: {}", e))?;
+    Ok(SignedTxEntry {
+        key_id: key_id.to_string(),
+        address: address.to_lowercase(),
+        derivation_path: derivation_path.to_string(),
+        chain_id: signed.transaction.chain_id,
+        nonce: u64::try_from(signed.transaction.nonce)
+            .map_err(|_| anyhow!("nonce out of range"))?,
+        gas_price: signed.transaction.gas_price,
+        signed_tx: format!("0x{}", hex::encode(signed_tx)),
+        tx_hash: format!("0x{}", hex::encode(Keccak256::digest(signed_tx))),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stuck() -> EthTransaction {
+        EthTransaction {
+            chain_id: 11155111,
+            nonce: 7,
+            to: Some([0x35; 20]),
+            value: 5,
+            gas_price: 1_000_000_001,
+            gas: 60_000,
+            data: vec![0xa9, 0x05, 0x9c, 0xbb],
+        }
+    }
+
+    #[test]
+    fn bump_rounds_up_and_honours_floor() {
+        assert_eq!(
+            bumped_gas_price(1_000_000_001, 10, None).unwrap(),
+            1_100_000_002
+        );
+        assert_eq!(bumped_gas_price(1, 10, None).unwrap(), 2);
+        assert_eq!(bumped_gas_price(100, 10, Some(500)).unwrap(), 500);
+        assert!(bumped_gas_price(100, 9, None).is_err());
+        assert!(bumped_gas_price(100, 501, None).is_err());
+    }
+
+    #[test]
+    fn replacement_keeps_nonce() {
+        let from = [0xaa; 20];
+        let sped = replacement(&stuck(), from, RescueMode::SpeedUp, 2_000_000_000);
+        assert_eq!((sped.nonce, sped.data.clone()), (7, stuck().data));
+        assert_eq!(sped.gas_price, 2_000_000_000);
+
+        let cancel = replacement(&stuck(), from, RescueMode::Cancel, 2_000_000_000);
+        assert_eq!(cancel.nonce, 7);
+        assert_eq!(cancel.to, Some(from));
+        assert_eq!((cancel.value, cancel.gas), (0, TRANSFER_GAS));
+        assert!(cancel.data.is_empty());
+    }
+
+    #[test]
+    fn gaps_below_highest_pending() {
+        assert_eq!(nonce_gaps(&[5, 8], None), vec![6, 7]);
+        assert_eq!(nonce_gaps(&[5, 8], Some(3)), vec![3, 4, 6, 7]);
+        assert!(nonce_gaps(&[5, 6], Some(5)).is_empty());
+        assert!(nonce_gaps(&[], Some(5)).is_empty());
+    }
+}
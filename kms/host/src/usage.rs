@@ -0,0 +1,228 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Which dapps use a wallet: signing ops tagged with their origin.
+//!
+//! Sign, SignHash and SIWE sign-ins are logged in `tx_log` with the origin
+//! that asked for them ([`RequestOrigin::tag`]) and, for a transaction, its
+//! chain and native value. `GET /kms/wallet/:id/usage` sums those rows per
+//! origin ([`report`]), so a user can see which dapps actually sign with their
+//! account and how much each one moved.
+//!
+//! The tag says who the caller claims to be acting for. Only a SIWE sign-in
+//! binds it (the message domain must match); for the rest it is as honest as
+//! the wallet app that forwarded it, and it never gates a signature.
+
+use crate::db::{OriginOpCount, OriginValue};
+use crate::siwe::origin_authority;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+
+/// Longest origin tag stored (a DNS name plus a port).
+pub const MAX_ORIGIN_LEN: usize = 260;
+/// Tag of an op nothing identified, and of rows logged before tagging.
+pub const UNKNOWN_ORIGIN: &str = "unknown";
+
+/// What a signing request said about where it came from.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RequestOrigin {
+    /// `x-dapp-origin`: the dapp a wallet app signs for, e.g. the peer URL of
+    /// a WalletConnect session.
+    pub dapp: Option<String>,
+    /// `Origin` of a browser dapp calling the CA directly.
+    pub origin: Option<String>,
+    /// `x-api-key`.
+    pub api_key: Option<String>,
+    /// That key's label in `api_keys`, looked up by the caller.
+    pub api_key_label: Option<String>,
+}
+
+impl RequestOrigin {
+    /// The first of: the dapp origin, the browser origin, the API key.
+    pub fn tag(&self) -> String {
+        [&self.dapp, &self.origin]
+            .iter()
+            .filter_map(|o| o.as_deref().and_then(normalize))
+            .next()
+            .or_else(|| {
+                let label = self.api_key_label.as_deref().unwrap_or("");
+                self.api_key.as_deref().map(|key| api_key_tag(key, label))
+            })
+            .unwrap_or_else(|| UNKNOWN_ORIGIN.to_string())
+    }
+}
+
+/// `https://App.example/` → `app.example`. None for anything that is not a
+/// plausible authority.
+pub fn normalize(origin: &str) -> Option<String> {
+    let authority = origin_authority(origin.trim()).to_ascii_lowercase();
+    let plausible = !authority.is_empty()
+        && authority.len() <= MAX_ORIGIN_LEN
+        && authority
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"-.:[]_".contains(&b));
+    plausible.then_some(authority)
+}
+
+/// `api-key:<label>`, or a short hash of the key when it has no label, so
+/// the report never shows the key itself.
+fn api_key_tag(key: &str, label: &str) -> String {
+    let label: String = label
+        .trim()
+        .chars()
+        .filter(|c| !c.is_control())
+        .take(64)
+        .collect();
+    if label.is_empty() {
+        let digest = Sha256::digest(key.as_bytes());
+        format!("api-key:#{}", hex::encode(&digest[..4]))
+    } else {
+        format!("api-key:{}", label)
+    }
+}
+
+/// One origin's line in a usage report.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OriginUsage {
+    pub origin: String,
+    pub signatures: i64,
+    pub failed: i64,
+    pub ops: BTreeMap<String, i64>,
+    /// Native value of signed transactions, wei per chain id.
+    pub value_moved: BTreeMap<u64, u128>,
+}
+
+impl OriginUsage {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "origin": self.origin,
+            "signatures": self.signatures,
+            "failed": self.failed,
+            "ops": self.ops,
+            "valueMoved": self
+                .value_moved
+                .iter()
+                .map(|(chain_id, wei)| json!({ "chainId": chain_id, "wei": wei.to_string() }))
+                .collect::<Vec<_>>(),
+        })
+    }
+}
+
+/// Fold tx_log totals into one line per origin, busiest first.
+pub fn report(counts: &[OriginOpCount], values: &[OriginValue]) -> Vec<OriginUsage> {
+    fn label(origin: &str) -> String {
+        if origin.is_empty() {
+            UNKNOWN_ORIGIN.to_string()
+        } else {
+            origin.to_string()
+        }
+    }
+    let mut by_origin: BTreeMap<String, OriginUsage> = BTreeMap::new();
+    for c in counts {
+        let origin = label(&c.origin);
+        let u = by_origin
+            .entry(origin.clone())
+            .or_insert_with(|| OriginUsage {
+                origin,
+                ..Default::default()
+            });
+        u.signatures += c.succeeded;
+        u.failed += c.failed;
+        *u.ops.entry(c.op.clone()).or_default() += c.succeeded;
+    }
+    for v in values {
+        let origin = label(&v.origin);
+        let u = by_origin
+            .entry(origin.clone())
+            .or_insert_with(|| OriginUsage {
+                origin,
+                ..Default::default()
+            });
+        let moved = u.value_moved.entry(v.chain_id).or_default();
+        *moved = moved.saturating_add(v.value_wei);
+    }
+    let mut lines: Vec<OriginUsage> = by_origin.into_values().collect();
+    lines.sort_by_key(|l| std::cmp::Reverse(l.signatures));
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tag_prefers_the_dapp_then_the_page_then_the_key() {
+        let mut o = RequestOrigin {
+            dapp: Some("https://Swap.Example/".into()),
+            origin: Some("https://wallet.example".into()),
+            api_key: Some("secret-key".into()),
+            api_key_label: Some("mobile app".into()),
+        };
+        assert_eq!(o.tag(), "swap.example");
+        o.dapp = Some("javascript:alert(1)".into());
+        assert_eq!(o.tag(), "wallet.example");
+        o.origin = None;
+        assert_eq!(o.tag(), "api-key:mobile app");
+        o.api_key_label = Some(" ".into());
+        let hashed = o.tag();
+        assert!(hashed.starts_with("api-key:#") && !hashed.contains("secret"));
+        assert_eq!(RequestOrigin::default().tag(), UNKNOWN_ORIGIN);
+        assert_eq!(
+            normalize("http://localhost:5173").as_deref(),
+            Some("localhost:5173")
+        );
+        assert_eq!(normalize(&"a".repeat(MAX_ORIGIN_LEN + 1)), None);
+    }
+
+    #[test]
+    fn report_sums_ops_and_value_per_origin() {
+        let count = |origin: &str, op: &str, succeeded, failed| OriginOpCount {
+            origin: origin.into(),
+            op: op.into(),
+            succeeded,
+            failed,
+        };
+        let value = |origin: &str, chain_id, value_wei| OriginValue {
+            origin: origin.into(),
+            chain_id,
+            value_wei,
+        };
+        let lines = report(
+            &[
+                count("", "SignHash", 1, 0),
+                count("app.example", "Sign", 3, 1),
+                count("app.example", "SignHash", 2, 0),
+            ],
+            &[
+                value("app.example", 1, 7),
+                value("app.example", 1, u128::MAX),
+                value("app.example", 10, 4),
+            ],
+        );
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].origin, "app.example");
+        assert_eq!(lines[0].signatures, 5);
+        assert_eq!(lines[0].failed, 1);
+        assert_eq!(lines[0].ops["Sign"], 3);
+        assert_eq!(lines[0].value_moved[&1], u128::MAX);
+        assert_eq!(lines[0].value_moved[&10], 4);
+        assert_eq!(lines[1].origin, UNKNOWN_ORIGIN);
+        let json = lines[0].to_json();
+        assert_eq!(json["valueMoved"][1]["wei"], "4");
+    }
+}
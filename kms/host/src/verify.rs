@@ -0,0 +1,248 @@
+//! `TrentService.Verify` — AWS-KMS-shaped signature verification.
+//!
+//! No TEE call and no key material: the CA already knows the address at every
+//! derived (KeyId, DerivationPath) from `address_index`, and an ECDSA
+//! signature over a digest is valid for a secp256k1 key exactly when one of
+//! its two recovery ids recovers that key's address. Both DER (what AWS SDKs
+//! send) and the 64/65-byte r ‖ s [‖ v] form returned by Sign / SignHash are
+//! accepted; high-S signatures verify as they do in AWS KMS.
+//!
+//! Failures carry the AWS exception name ([`KmsException`]) so the API layer
+//! can answer `{"__type": ..., "message": ...}` like AWS does.
+
+use crate::key_pin;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use k256::ecdsa::Signature;
+use sha2::{Digest, Sha256};
+use std::fmt;
+
+/// The only algorithm AWS KMS offers for ECC_SECG_P256K1 keys.
+pub const ECDSA_SHA_256: &str = "ECDSA_SHA_256";
+
+/// AWS KMS: a RAW message may be at most 4096 bytes.
+pub const MAX_RAW_MESSAGE_BYTES: usize = 4096;
+
+/// Known AWS signing algorithms that exist but do not fit a secp256k1 key.
+const OTHER_AWS_ALGORITHMS: &[&str] = &[
+    "ECDSA_SHA_384",
+    "ECDSA_SHA_512",
+    "RSASSA_PSS_SHA_256",
+    "RSASSA_PSS_SHA_384",
+    "RSASSA_PSS_SHA_512",
+    "RSASSA_PKCS1_V1_5_SHA_256",
+    "RSASSA_PKCS1_V1_5_SHA_384",
+    "RSASSA_PKCS1_V1_5_SHA_512",
+    "SM2DSA",
+    "ML_DSA_SHAKE_256",
+];
+
+/// An error with an AWS KMS exception name (`__type`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KmsException {
+    pub kind: &'static str,
+    pub message: String,
+}
+
+impl fmt::Display for KmsException {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.kind, self.message)
+    }
+}
+
+impl std::error::Error for KmsException {}
+
+pub fn exception(kind: &'static str, message: impl Into<String>) -> anyhow::Error {
+    KmsException {
+        kind,
+        message: message.into(),
+    }
+    .into()
+}
+
+fn validation(message: impl Into<String>) -> anyhow::Error {
+    exception("ValidationException", message)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageType {
+    Raw,
+    Digest,
+}
+
+impl MessageType {
+    /// AWS default is RAW.
+    pub fn parse(v: Option<&str>) -> anyhow::Result<Self> {
+        match v.unwrap_or("RAW") {
+            "RAW" => Ok(MessageType::Raw),
+            "DIGEST" => Ok(MessageType::Digest),
+            other => Err(validation(format!(
+                "MessageType must be RAW or DIGEST, got {:?}",
+                other
+            ))),
+        }
+    }
+}
+
+/// The 32-byte digest the signature must be over.
+pub fn digest(
+    algorithm: &str,
+    message_type: MessageType,
+    message: &[u8],
+) -> anyhow::Result<[u8; 32]> {
+    if algorithm != ECDSA_SHA_256 {
+        if OTHER_AWS_ALGORITHMS.contains(&algorithm) {
+            return Err(exception(
+                "InvalidKeyUsageException",
+                format!(
+                    "SigningAlgorithm {} is not valid for ECC_SECG_P256K1 keys; use {}",
+                    algorithm, ECDSA_SHA_256
+                ),
+            ));
+        }
+        return Err(validation(format!(
+            "unknown SigningAlgorithm {:?}",
+            algorithm
+        )));
+    }
+    match message_type {
+        MessageType::Raw => {
+            if message.len() > MAX_RAW_MESSAGE_BYTES {
+                return Err(validation(format!(
+                    "RAW message is {} bytes (max {}); hash it and send MessageType DIGEST",
+                    message.len(),
+                    MAX_RAW_MESSAGE_BYTES
+                )));
+            }
+            Ok(Sha256::digest(message).into())
+        }
+        MessageType::Digest => {
+            let mut d = [0u8; 32];
+            if message.len() != d.len() {
+                return Err(validation(format!(
+                    "Digest is invalid length for algorithm {}: {} bytes",
+                    algorithm,
+                    message.len()
+                )));
+            }
+            d.copy_from_slice(message);
+            Ok(d)
+        }
+    }
+}
+
+/// Blob field: `0x`-hex or base64 (AWS sends base64).
+pub fn decode_blob(field: &str, v: &str) -> anyhow::Result<Vec<u8>> {
+    let decoded = match v.strip_prefix("0x") {
+        Some(h) => hex::decode(h).ok(),
+        None => STANDARD.decode(v).ok(),
+    };
+    decoded.ok_or_else(|| validation(format!("{} must be base64 or 0x-hex", field)))
+}
+
+fn invalid_signature() -> anyhow::Error {
+    exception(
+        "KMSInvalidSignatureException",
+        "The signature is not valid for the message and key",
+    )
+}
+
+/// r ‖ s (low-S) from DER or 64/65-byte r ‖ s [‖ v].
+fn signature_rs(signature: &[u8]) -> anyhow::Result<[u8; 64]> {
+    let sig = match signature.len() {
+        64 | 65 => Signature::from_slice(&signature[..64]),
+        _ => Signature::from_der(signature),
+    }
+    .map_err(|_| invalid_signature())?;
+    let sig = sig.normalize_s().unwrap_or(sig);
+    let mut rs = [0u8; 64];
+    rs.copy_from_slice(&sig.to_bytes());
+    Ok(rs)
+}
+
+/// Ok when `signature` over `digest` is by the key whose address is `address`.
+pub fn verify_for_address(
+    digest: &[u8; 32],
+    signature: &[u8],
+    address: &[u8; 20],
+) -> anyhow::Result<()> {
+    let rs = signature_rs(signature)?;
+    let mut candidate = [0u8; 65];
+    candidate[..64].copy_from_slice(&rs);
+    for recovery_id in 0..2 {
+        candidate[64] = recovery_id;
+        if key_pin::recover_signer(digest, &candidate).ok().as_ref() == Some(address) {
+            return Ok(());
+        }
+    }
+    Err(invalid_signature())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use k256::ecdsa::SigningKey;
+    use sha3::Keccak256;
+
+    fn address_of(key: &SigningKey) -> [u8; 20] {
+        let point = key.verifying_key().to_encoded_point(false);
+        let mut a = [0u8; 20];
+        a.copy_from_slice(&Keccak256::digest(&point.as_bytes()[1..])[12..]);
+        a
+    }
+
+    fn kind(e: anyhow::Error) -> &'static str {
+        e.downcast_ref::<KmsException>().unwrap().kind
+    }
+
+    #[test]
+    fn verifies_der_and_rsv_including_high_s() {
+        let key = SigningKey::from_slice(&[0x42; 32]).unwrap();
+        let address = address_of(&key);
+        let d = digest(ECDSA_SHA_256, MessageType::Raw, b"hello").unwrap();
+        assert_eq!(d, <[u8; 32]>::from(Sha256::digest(b"hello")));
+        let (sig, recid): (Signature, _) = key.sign_prehash_recoverable(&d).unwrap();
+
+        assert!(verify_for_address(&d, sig.to_der().as_bytes(), &address).is_ok());
+        let mut rsv = sig.to_bytes().to_vec();
+        rsv.push(27 + recid.to_byte());
+        assert!(verify_for_address(&d, &rsv, &address).is_ok());
+        // (r, n - s) is the same signature; AWS accepts it, so do we.
+        let high_s = Signature::from_scalars(sig.r(), -*sig.s()).unwrap();
+        assert!(verify_for_address(&d, &high_s.to_bytes(), &address).is_ok());
+
+        let other = digest(ECDSA_SHA_256, MessageType::Raw, b"hellO").unwrap();
+        let err = verify_for_address(&other, &rsv, &address).unwrap_err();
+        assert_eq!(kind(err), "KMSInvalidSignatureException");
+        assert_eq!(
+            kind(verify_for_address(&d, &[1, 2, 3], &address).unwrap_err()),
+            "KMSInvalidSignatureException"
+        );
+    }
+
+    #[test]
+    fn algorithm_and_message_type_errors_are_aws_shaped() {
+        assert_eq!(
+            kind(digest("ECDSA_SHA_384", MessageType::Raw, b"x").unwrap_err()),
+            "InvalidKeyUsageException"
+        );
+        assert_eq!(
+            kind(digest("ECDSA_KECCAK", MessageType::Raw, b"x").unwrap_err()),
+            "ValidationException"
+        );
+        assert_eq!(
+            kind(digest(ECDSA_SHA_256, MessageType::Digest, &[0; 31]).unwrap_err()),
+            "ValidationException"
+        );
+        assert_eq!(
+            digest(ECDSA_SHA_256, MessageType::Digest, &[7; 32]).unwrap(),
+            [7; 32]
+        );
+        assert_eq!(
+            kind(MessageType::parse(Some("EXTERNAL_MU")).unwrap_err()),
+            "ValidationException"
+        );
+        assert_eq!(MessageType::parse(None).unwrap(), MessageType::Raw);
+        assert_eq!(decode_blob("Message", "aGk=").unwrap(), b"hi");
+        assert_eq!(decode_blob("Message", "0x6869").unwrap(), b"hi");
+    }
+}
@@ -1,7 +1,7 @@
 //! WebAuthn ceremony logic — compatible with SimpleWebAuthn.
 //!
 //! Pure functions: parse attestation, verify assertions, generate options.
-//! No IO or TA calls — those happen in api_server.rs.
+//! No IO or TA calls — those happen in service.rs.
 
 use anyhow::{anyhow, Result};
 use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
@@ -112,6 +112,10 @@ pub struct RegistrationOptions {
     pub exclude_credentials: Vec<CredentialDescriptor>,
     #[serde(rename = "authenticatorSelection")]
     pub authenticator_selection: AuthenticatorSelection,
+    /// WebAuthn extensions — requests the PRF (hmac-secret) evaluation used as
+    /// an extra wallet-entropy factor. Ignored by authenticators without PRF.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub extensions: Option<serde_json::Value>,
 }
 
 /// Server → Browser: PublicKeyCredentialRequestOptionsJSON
@@ -218,6 +222,9 @@ pub struct BeginRegistrationRequest {
     pub key_spec: Option<String>,
     #[serde(rename = "Origin", default)]
     pub origin: Option<String>,
+    /// Role from `KMS_FIDO_ATTESTATION_POLICY` the passkey must qualify for.
+    #[serde(rename = "Role", skip_serializing_if = "Option::is_none", default)]
+    pub role: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -267,6 +274,35 @@ pub struct VerifiedRegistration {
     pub public_key: Vec<u8>, // 65 bytes uncompressed P-256
     pub sign_count: u32,
     pub transports: Option<Vec<String>>,
+    /// PRF output evaluated at create() over `PRF_ENTROPY_SALT`, if the
+    /// authenticator supports it.
+    pub prf_output: Option<[u8; 32]>,
+    /// Authenticator model, all-zero when the browser anonymised it.
+    pub aaguid: Uuid,
+    pub attestation_type: AttestationType,
+    /// Attestation certificates (leaf first) for a `Basic` attestation.
+    pub x5c: Vec<Vec<u8>>,
+}
+
+/// Attestation whose signature checked out, as stored on the credential.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttestationType {
+    /// `none`, or a format/algorithm this server does not verify.
+    None,
+    /// `packed` signed by the credential key itself.
+    SelfAttestation,
+    /// `packed` or `fido-u2f` with an attestation certificate chain.
+    Basic,
+}
+
+impl AttestationType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AttestationType::None => "none",
+            AttestationType::SelfAttestation => "self",
+            AttestationType::Basic => "basic",
+        }
+    }
 }
 
 pub struct VerifiedAuthentication {
@@ -279,6 +315,37 @@ pub struct VerifiedAuthentication {
 // Registration options generation
 // ========================================
 
+/// Fixed PRF input for the wallet-entropy factor. Fixed (not per-challenge) so
+/// the same credential can re-evaluate it later, e.g. to prove possession.
+/// Browsers hash it as SHA-256("WebAuthn PRF" ‖ 0x00 ‖ salt) before hmac-secret.
+pub const PRF_ENTROPY_SALT: &[u8] = b"AirAccount.wallet-entropy.v1";
+
+/// `extensions` block asking the authenticator to evaluate PRF at create().
+pub fn prf_extension_request() -> serde_json::Value {
+    serde_json::json!({ "prf": { "eval": { "first": b64url_encode(PRF_ENTROPY_SALT) } } })
+}
+
+/// Pull `clientExtensionResults.prf.results.first` (base64url, 32 bytes).
+///
+/// Client extension outputs are NOT covered by the authenticator signature, so
+/// the value is only ever used as an additional entropy input — never as an
+/// authorization signal. Absent = authenticator/browser without PRF support.
+pub fn extract_prf_output(
+    client_extension_results: &serde_json::Value,
+) -> Result<Option<[u8; 32]>> {
+    let first = match client_extension_results["prf"]["results"]["first"].as_str() {
+        Some(v) => v,
+        None => return Ok(None),
+    };
+    let bytes = b64url_decode(first).map_err(|e| anyhow!("Invalid PRF output: {}", e))?;
+    if bytes.len() != 32 {
+        return Err(anyhow!("PRF output must be 32 bytes, got {}", bytes.len()));
+    }
+    let mut out = [0u8; 32];
+    out.copy_from_slice(&bytes);
+    Ok(Some(out))
+}
+
 pub fn generate_registration_options(
     rp_name: &str,
     rp_id: &str,
@@ -317,6 +384,7 @@ pub fn generate_registration_options(
                 resident_key: Some("preferred".to_string()),
                 user_verification: Some("required".to_string()),
             },
+            extensions: Some(prf_extension_request()),
         },
     };
     (challenge_id, challenge_bytes, resp)
@@ -369,6 +437,8 @@ pub fn verify_registration_response(
         ));
     }
 
+    let client_data_hash = Sha256::digest(&client_data_bytes);
+
     // 2. Decode attestationObject (CBOR)
     let att_obj_bytes = b64url_decode(&response.response.attestation_object)?;
     let cbor: ciborium::Value = ciborium::from_reader(&att_obj_bytes[..])
@@ -393,6 +463,22 @@ pub fn verify_registration_response(
             }
         })
         .ok_or_else(|| anyhow!("missing authData in attestationObject"))?;
+    let fmt = map
+        .iter()
+        .find_map(|(k, v)| match (k, v) {
+            (ciborium::Value::Text(k), ciborium::Value::Text(f)) if k == "fmt" => Some(f.clone()),
+            _ => None,
+        })
+        .ok_or_else(|| anyhow!("missing fmt in attestationObject"))?;
+    let att_stmt = map
+        .iter()
+        .find_map(|(k, v)| match (k, v) {
+            (ciborium::Value::Text(k), ciborium::Value::Map(m)) if k == "attStmt" => {
+                Some(m.as_slice())
+            }
+            _ => None,
+        })
+        .unwrap_or(&[]);
 
     // 4. Verify rpIdHash
     let expected_rp_hash = Sha256::digest(expected_rp_id.as_bytes());
@@ -424,7 +510,7 @@ pub fn verify_registration_response(
     if auth_data.len() < 55 {
         return Err(anyhow!("authData too short for attested credential data"));
     }
-    // aaguid = auth_data[37..53] (skip)
+    let aaguid = Uuid::from_slice(&auth_data[37..53]).map_err(|e| anyhow!("aaguid: {}", e))?;
     let cred_id_len = u16::from_be_bytes(
         auth_data[53..55]
             .try_into()
@@ -466,14 +552,115 @@ pub fn verify_registration_response(
     // Validate it's a valid P-256 point
     EncodedPoint::from_bytes(&pubkey).map_err(|e| anyhow!("Invalid P-256 point: {:?}", e))?;
 
+    // 9. Verify the attestation statement
+    let (attestation_type, x5c) = verify_attestation_statement(
+        &fmt,
+        att_stmt,
+        &auth_data,
+        &client_data_hash,
+        &credential_id,
+        &pubkey,
+    )?;
+
     Ok(VerifiedRegistration {
         credential_id,
         public_key: pubkey,
         sign_count,
         transports: response.response.transports.clone(),
+        prf_output: extract_prf_output(&response.client_extension_results)?,
+        aaguid,
+        attestation_type,
+        x5c,
     })
 }
 
+/// Check the `packed` / `fido-u2f` signature. Other formats, and certificate
+/// keys other than P-256, come back as `None`: the credential still
+/// registers, just without attestation. A signature that fails is an error.
+fn verify_attestation_statement(
+    fmt: &str,
+    att_stmt: &[(ciborium::Value, ciborium::Value)],
+    auth_data: &[u8],
+    client_data_hash: &[u8],
+    credential_id: &[u8],
+    credential_pubkey: &[u8],
+) -> Result<(AttestationType, Vec<Vec<u8>>)> {
+    let field = |name: &str| {
+        att_stmt
+            .iter()
+            .find(|(k, _)| matches!(k, ciborium::Value::Text(s) if s == name))
+            .map(|(_, v)| v)
+    };
+    let unattested = || Ok((AttestationType::None, Vec::new()));
+    if fmt != "packed" && fmt != "fido-u2f" {
+        return unattested();
+    }
+    if fmt == "packed" {
+        match field("alg") {
+            Some(ciborium::Value::Integer(i)) if i128::from(*i) == -7 => {}
+            Some(ciborium::Value::Integer(_)) => return unattested(),
+            _ => return Err(anyhow!("packed attStmt missing alg")),
+        }
+    }
+    let x5c: Vec<Vec<u8>> = match field("x5c") {
+        Some(ciborium::Value::Array(certs)) => certs
+            .iter()
+            .map(|c| match c {
+                ciborium::Value::Bytes(b) => Ok(b.clone()),
+                _ => Err(anyhow!("attStmt x5c entry is not bytes")),
+            })
+            .collect::<Result<_>>()?,
+        Some(_) => return Err(anyhow!("attStmt x5c is not an array")),
+        None => Vec::new(),
+    };
+    let leaf_key = match x5c.first() {
+        Some(der) => match crate::fido_mds::Certificate::parse(der)?.p256_key() {
+            Some(k) => Some(k),
+            None => return unattested(),
+        },
+        None => None,
+    };
+    let sig = match field("sig") {
+        Some(ciborium::Value::Bytes(b)) => {
+            Signature::from_der(b).map_err(|e| anyhow!("attStmt sig is not DER ECDSA: {:?}", e))?
+        }
+        _ => return Err(anyhow!("attStmt missing sig for fmt '{}'", fmt)),
+    };
+
+    let (signed, key, kind) = if fmt == "packed" {
+        let signed = [auth_data, client_data_hash].concat();
+        match leaf_key {
+            Some(k) => (signed, k, AttestationType::Basic),
+            None => {
+                let point = EncodedPoint::from_bytes(credential_pubkey)
+                    .map_err(|e| anyhow!("Invalid P-256 point: {:?}", e))?;
+                let k = VerifyingKey::from_encoded_point(&point)
+                    .map_err(|e| anyhow!("Invalid P-256 key: {:?}", e))?;
+                (signed, k, AttestationType::SelfAttestation)
+            }
+        }
+    } else {
+        let k = leaf_key.ok_or_else(|| anyhow!("fido-u2f attStmt needs x5c"))?;
+        let signed = [
+            &[0u8][..],
+            &auth_data[0..32],
+            client_data_hash,
+            credential_id,
+            credential_pubkey,
+        ]
+        .concat();
+        (signed, k, AttestationType::Basic)
+    };
+    key.verify(&signed, &sig)
+        .map_err(|_| anyhow!("attestation signature invalid (fmt '{}')", fmt))?;
+    let x5c = if kind == AttestationType::Basic {
+        x5c
+    } else {
+        Vec::new()
+    };
+    Ok((kind, x5c))
+}
+
 fn find_cose_bytes(map: &[(ciborium::Value, ciborium::Value)], label: i64) -> Option<Vec<u8>> {
     map.iter().find_map(|(k, v)| {
         let matches = match k {
@@ -728,6 +915,27 @@ mod tests {
         assert_eq!(resp.options.attestation, "none");
     }
 
+    #[test]
+    fn registration_options_request_prf() {
+        let (_, _, resp) =
+            generate_registration_options("AirAccount", "aastar.io", "alice", "Alice", vec![]);
+        let ext = resp.options.extensions.unwrap();
+        assert_eq!(
+            b64url_decode(ext["prf"]["eval"]["first"].as_str().unwrap()).unwrap(),
+            PRF_ENTROPY_SALT
+        );
+    }
+
+    #[test]
+    fn prf_output_extraction() {
+        assert_eq!(extract_prf_output(&serde_json::json!({})).unwrap(), None);
+        let ok = serde_json::json!({ "prf": { "enabled": true, "results": { "first": b64url_encode(&[7u8; 32]) } } });
+        assert_eq!(extract_prf_output(&ok).unwrap(), Some([7u8; 32]));
+        let short =
+            serde_json::json!({ "prf": { "results": { "first": b64url_encode(&[7u8; 16]) } } });
+        assert!(extract_prf_output(&short).is_err());
+    }
+
     #[test]
     fn authentication_options_structure() {
         let creds = vec![CredentialDescriptor {
@@ -766,8 +974,123 @@ mod tests {
         assert!(result.is_err());
     }
 
+    /// A registration response whose attestationObject is `fmt`/`att_stmt`
+    /// over a fresh credential key. Returns (response, challenge, authData,
+    /// clientDataHash).
+    fn packed_registration(
+        att_stmt: impl FnOnce(&[u8], &[u8]) -> Vec<(ciborium::Value, ciborium::Value)>,
+        cred_key: &SigningKey,
+    ) -> (RegistrationResponseJSON, Vec<u8>) {
+        use ciborium::Value;
+        let challenge = vec![9u8; 32];
+        let client_data = serde_json::json!({
+            "type": "webauthn.create",
+            "challenge": b64url_encode(&challenge),
+            "origin": "https://aastar.io",
+        })
+        .to_string();
+        let point = cred_key.verifying_key().to_encoded_point(false);
+        let cose = Value::Map(vec![
+            (Value::Integer(1.into()), Value::Integer(2.into())),
+            (Value::Integer(3.into()), Value::Integer((-7).into())),
+            (Value::Integer((-1).into()), Value::Integer(1.into())),
+            (
+                Value::Integer((-2).into()),
+                Value::Bytes(point.x().unwrap().to_vec()),
+            ),
+            (
+                Value::Integer((-3).into()),
+                Value::Bytes(point.y().unwrap().to_vec()),
+            ),
+        ]);
+        let mut auth_data = Sha256::digest(b"aastar.io").to_vec();
+        auth_data.push(0x45); // UP | UV | AT
+        auth_data.extend_from_slice(&[0, 0, 0, 1]);
+        auth_data.extend_from_slice(
+            Uuid::parse_str("2fc0579f-8113-47ea-b116-bb5a8db9202a")
+                .unwrap()
+                .as_bytes(),
+        );
+        auth_data.extend_from_slice(&[0, 4]);
+        auth_data.extend_from_slice(b"cred");
+        ciborium::into_writer(&cose, &mut auth_data).unwrap();
+        let cdh = Sha256::digest(client_data.as_bytes());
+        let obj = Value::Map(vec![
+            (Value::Text("fmt".into()), Value::Text("packed".into())),
+            (
+                Value::Text("attStmt".into()),
+                Value::Map(att_stmt(&auth_data, &cdh)),
+            ),
+            (Value::Text("authData".into()), Value::Bytes(auth_data)),
+        ]);
+        let mut att_obj = Vec::new();
+        ciborium::into_writer(&obj, &mut att_obj).unwrap();
+        let resp = RegistrationResponseJSON {
+            id: b64url_encode(b"cred"),
+            raw_id: b64url_encode(b"cred"),
+            response: AttestationResponseJSON {
+                client_data_json: b64url_encode(client_data.as_bytes()),
+                attestation_object: b64url_encode(&att_obj),
+                transports: None,
+            },
+            type_: "public-key".to_string(),
+            authenticator_attachment: None,
+            client_extension_results: serde_json::json!({}),
+        };
+        (resp, challenge)
+    }
+
+    fn packed_stmt(
+        signer: &SigningKey,
+        x5c: Option<Vec<u8>>,
+        auth_data: &[u8],
+        cdh: &[u8],
+    ) -> Vec<(ciborium::Value, ciborium::Value)> {
+        use ciborium::Value;
+        let sig: Signature = signer.sign(&[auth_data, cdh].concat());
+        let mut stmt = vec![
+            (Value::Text("alg".into()), Value::Integer((-7).into())),
+            (
+                Value::Text("sig".into()),
+                Value::Bytes(sig.to_der().as_bytes().to_vec()),
+            ),
+        ];
+        if let Some(cert) = x5c {
+            stmt.push((
+                Value::Text("x5c".into()),
+                Value::Array(vec![Value::Bytes(cert)]),
+            ));
+        }
+        stmt
+    }
+
+    #[test]
+    fn verify_registration_packed_attestation() {
+        let origins = ["https://aastar.io".to_string()];
+        let cred = SigningKey::from_slice(&[5; 32]).unwrap();
+        let (resp, challenge) = packed_registration(|a, c| packed_stmt(&cred, None, a, c), &cred);
+        let v = verify_registration_response(&resp, &challenge, &origins, "aastar.io").unwrap();
+        assert_eq!(v.attestation_type, AttestationType::SelfAttestation);
+        assert_eq!(v.aaguid.to_string(), "2fc0579f-8113-47ea-b116-bb5a8db9202a");
+
+        let attester = SigningKey::from_slice(&[6; 32]).unwrap();
+        let cert = crate::fido_mds::tests::make_cert("Att", &attester, "Root", &attester);
+        let (resp, challenge) = packed_registration(
+            |a, c| packed_stmt(&attester, Some(cert.clone()), a, c),
+            &cred,
+        );
+        let v = verify_registration_response(&resp, &challenge, &origins, "aastar.io").unwrap();
+        assert_eq!(v.attestation_type, AttestationType::Basic);
+        assert_eq!(v.x5c, vec![cert.clone()]);
+
+        // Signed by a key other than the certificate's.
+        let (resp, challenge) =
+            packed_registration(|a, c| packed_stmt(&cred, Some(cert), a, c), &cred);
+        assert!(verify_registration_response(&resp, &challenge, &origins, "aastar.io").is_err());
+    }
+
     // ── P-256 ECDSA signature verification tests ──
-    // These test the same logic as api_server::verify_passkey_ca
+    // These test the same logic as service::KmsApiServer::verify_passkey_ca
 
     use p256::ecdsa::signature::Signer;
     use p256::ecdsa::SigningKey;
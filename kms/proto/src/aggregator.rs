@@ -0,0 +1,82 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! ERC-4337 signature-aggregator accounts (BLS12-381).
+//!
+//! A wallet opts in with `SetAggregator`, naming the IAggregator contract
+//! its account validates through. From then on `SignAggregatedUserOp`
+//! BLS-signs userOpHashes with a key the TA derives from the wallet seed at
+//! [`key_levels`]; the ECDSA keys of the wallet are untouched. The CA groups
+//! such userOps per (EntryPoint, aggregator) and submits each group with the
+//! aggregator address set, so the bundler verifies one aggregate signature
+//! for the group instead of one per op.
+//!
+//! Keys and signatures use the EIP-2537 encodings of the DVT BLS key
+//! (`BlsSign`) and the same DST, so one on-chain verifier serves both.
+
+use sha3::{Digest, Keccak256};
+use uuid::Uuid;
+
+/// G1 public key, EIP-2537 (x ‖ y, each in a 64-byte slot).
+pub const BLS_PUBLIC_KEY_LEN: usize = 128;
+/// G2 signature, EIP-2537 (four 64-byte slots).
+pub const BLS_SIGNATURE_LEN: usize = 256;
+/// Compressed G2 signature.
+pub const BLS_SIGNATURE_COMPACT_LEN: usize = 96;
+
+/// BIP32 levels (each hardened) of the node whose key seeds the wallet's
+/// aggregator BLS key: m/12381'/4337'/0'.
+pub fn key_levels() -> [u32; 3] {
+    [12381, 4337, 0]
+}
+
+/// Passkey commitment for switching `wallet_id` to `aggregator`, or back to
+/// plain ECDSA (None).
+pub fn scheme_commitment(wallet_id: &Uuid, aggregator: Option<&[u8; 20]>) -> [u8; 32] {
+    let mut h = Keccak256::new();
+    h.update(b"AA-AGGREGATOR-SCHEME-v1");
+    h.update(wallet_id.as_bytes());
+    match aggregator {
+        Some(aggregator) => {
+            h.update([1u8]);
+            h.update(aggregator);
+        }
+        None => h.update([0u8]),
+    }
+    h.finalize().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scheme_commitment_binds_wallet_and_aggregator() {
+        let w = Uuid::from_bytes([1; 16]);
+        let a = [0xaa; 20];
+        assert_ne!(scheme_commitment(&w, Some(&a)), scheme_commitment(&w, None));
+        assert_ne!(
+            scheme_commitment(&w, Some(&a)),
+            scheme_commitment(&w, Some(&[0xbb; 20]))
+        );
+        assert_ne!(
+            scheme_commitment(&w, None),
+            scheme_commitment(&Uuid::nil(), None)
+        );
+        assert_eq!(key_levels(), [12381, 4337, 0]);
+    }
+}
@@ -0,0 +1,145 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Token amounts written for people ("12.50 USDC"), in integer base units.
+//!
+//! [`TokenAmount::parse`] never goes through a float and never rounds: more
+//! fractional digits than the token has, a sign, an exponent or a digit
+//! separator is an error. [`TokenAmount`]'s `Display` is the formatting the
+//! TA's transaction summaries use, so what the user typed, what the CA
+//! echoes and what the passkey confirms agree digit for digit.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::calldata::{format_units, Word};
+
+/// 10^38 is the largest power of ten a u128 holds.
+pub const MAX_DECIMALS: u32 = 38;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TokenAmount {
+    pub base_units: u128,
+    pub decimals: u32,
+}
+
+impl TokenAmount {
+    /// `text` is digits with at most one `.`, e.g. "12", "12.5", "0.000001".
+    pub fn parse(text: &str, decimals: u32) -> Result<Self, String> {
+        if decimals > MAX_DECIMALS {
+            return Err(format!(
+                "tokens with {} decimals are not supported",
+                decimals
+            ));
+        }
+        let text = text.trim();
+        let (whole, frac) = match text.split_once('.') {
+            Some((w, f)) => (w, Some(f)),
+            None => (text, None),
+        };
+        let digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+        if !digits(whole) || !frac.into_iter().all(digits) {
+            return Err(format!("amount must be plain decimal digits: {:?}", text));
+        }
+        let frac = frac.unwrap_or("");
+        if frac.len() as u32 > decimals {
+            return Err(format!(
+                "amount {} has more than the token's {} decimals",
+                text, decimals
+            ));
+        }
+        let overflow = || format!("amount {} is too large", text);
+        let scale = 10u128.pow(decimals);
+        let whole: u128 = whole.parse().map_err(|_| overflow())?;
+        let frac_units = if frac.is_empty() {
+            0
+        } else {
+            frac.parse::<u128>().map_err(|_| overflow())? * 10u128.pow(decimals - frac.len() as u32)
+        };
+        let base_units = whole
+            .checked_mul(scale)
+            .and_then(|w| w.checked_add(frac_units))
+            .ok_or_else(overflow)?;
+        Ok(TokenAmount {
+            base_units,
+            decimals,
+        })
+    }
+
+    /// The amount as a uint256 ABI word.
+    pub fn to_word(&self) -> Word {
+        let mut w = [0u8; 32];
+        w[16..].copy_from_slice(&self.base_units.to_be_bytes());
+        w
+    }
+}
+
+impl fmt::Display for TokenAmount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&format_units(self.base_units, self.decimals))
+    }
+}
+
+/// "12.50 USDC" → ("12.50", Some("USDC")); "12.50" → ("12.50", None).
+pub fn split_symbol(text: &str) -> (&str, Option<&str>) {
+    let text = text.trim();
+    match text.split_once(char::is_whitespace) {
+        Some((amount, symbol)) => (amount, Some(symbol.trim())),
+        None => (text, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_exact_base_units() {
+        let units = |t: &str, d: u32| TokenAmount::parse(t, d).map(|a| a.base_units);
+        assert_eq!(units("12.50", 6), Ok(12_500_000));
+        assert_eq!(units("0.000001", 6), Ok(1));
+        assert_eq!(units("7", 0), Ok(7));
+        assert_eq!(units("1.0", 18), Ok(1_000_000_000_000_000_000));
+        // 0.1 + 0.2 style drift cannot happen: no floats anywhere.
+        assert_eq!(units("0.3", 18), Ok(300_000_000_000_000_000));
+    }
+
+    #[test]
+    fn refuses_anything_it_would_round_or_guess() {
+        for bad in [
+            "", ".5", "5.", "-1", "+1", "1e6", "1,000", "1_000", "1.2.3", "0x10", " . ",
+        ]
+        .iter()
+        {
+            assert!(TokenAmount::parse(bad, 6).is_err(), "{:?}", bad);
+        }
+        assert!(TokenAmount::parse("0.0000001", 6).is_err());
+        assert!(TokenAmount::parse("1", 39).is_err());
+        assert!(TokenAmount::parse("340282366920938463463374607431768211456", 0).is_err());
+        assert!(TokenAmount::parse("340282366920938463463.374607431768211456", 18).is_err());
+    }
+
+    #[test]
+    fn display_roundtrips_and_symbols_split() {
+        let a = TokenAmount::parse("12.50", 6).unwrap();
+        assert_eq!(a.to_string(), "12.5");
+        assert_eq!(TokenAmount::parse(&a.to_string(), 6), Ok(a));
+        assert_eq!(a.to_word()[31], (12_500_000u128 & 0xff) as u8);
+        assert_eq!(split_symbol(" 12.50 USDC "), ("12.50", Some("USDC")));
+        assert_eq!(split_symbol("12.50"), ("12.50", None));
+    }
+}
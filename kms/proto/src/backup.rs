@@ -0,0 +1,215 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Encrypted wallet backups (`ExportEncryptedBackup` /
+//! `ImportEncryptedBackup`).
+//!
+//! The secret is the wallet's 32-byte entropy in a [`Keystore`], as for
+//! `ExportKeystore`, but the keystore passphrase is not the user's password:
+//! it is [`sealed_passphrase`], keccak256 of the TA's device sealing key, the
+//! [`BackupHeader`] and the password. Opening a backup takes the password
+//! *and* the board (and TA) that sealed it, so a copied file plus a leaked
+//! password is not enough; and since the header is part of the passphrase,
+//! editing it (to bind another passkey, say) fails the keystore MAC.
+//! Backups use Argon2id only.
+
+use crate::kdf::{KdfConfig, Keystore};
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+use uuid::Uuid;
+
+pub const BACKUP_VERSION: u8 = 1;
+/// Shortest password the TA seals a backup under.
+pub const MIN_PASSWORD_LEN: usize = 8;
+
+/// What a backup restores besides the entropy. Authenticated, not secret.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BackupHeader {
+    pub version: u8,
+    /// The restored wallet keeps its id, so every record keyed by it still
+    /// applies.
+    pub wallet_id: Uuid,
+    /// Owner passkey at export time (65 bytes, 0x04‖x‖y).
+    pub passkey_pubkey: Vec<u8>,
+    pub next_address_index: u32,
+    pub next_account_index: u32,
+    /// TA time (unix seconds) of the export.
+    pub created_at: u64,
+    /// [`device_fingerprint`] of the sealing key.
+    pub device: [u8; 32],
+}
+
+impl BackupHeader {
+    pub fn digest(&self) -> [u8; 32] {
+        let mut h = Keccak256::new();
+        h.update(b"AA-BACKUP-HEADER-v1");
+        h.update([self.version]);
+        h.update(self.wallet_id.as_bytes());
+        h.update((self.passkey_pubkey.len() as u32).to_be_bytes());
+        h.update(&self.passkey_pubkey);
+        h.update(self.next_address_index.to_be_bytes());
+        h.update(self.next_account_index.to_be_bytes());
+        h.update(self.created_at.to_be_bytes());
+        h.update(self.device);
+        h.finalize().into()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct EncryptedBackup {
+    pub header: BackupHeader,
+    pub keystore: Keystore,
+}
+
+impl EncryptedBackup {
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if self.header.version != BACKUP_VERSION {
+            return Err("unsupported backup version");
+        }
+        let pk = &self.header.passkey_pubkey;
+        if pk.len() != 65 || pk[0] != 0x04 {
+            return Err("backup passkey must be 65 bytes uncompressed (0x04||x||y)");
+        }
+        if !matches!(self.keystore.kdf, KdfConfig::Argon2id { .. }) {
+            return Err("backups are sealed with argon2id only");
+        }
+        self.keystore.validate()?;
+        if self.keystore.ciphertext.len() != 32 {
+            return Err("backup must hold 32 bytes of wallet entropy");
+        }
+        Ok(())
+    }
+}
+
+/// Public name of a device sealing key, so a restore on the wrong board
+/// fails with a clear error instead of a MAC mismatch.
+pub fn device_fingerprint(sealing_key: &[u8; 32]) -> [u8; 32] {
+    let mut h = Keccak256::new();
+    h.update(b"AA-BACKUP-DEVICE-v1");
+    h.update(sealing_key);
+    h.finalize().into()
+}
+
+/// The keystore passphrase: the password peppered with the device sealing
+/// key and bound to the header.
+pub fn sealed_passphrase(
+    sealing_key: &[u8; 32],
+    header: &BackupHeader,
+    password: &[u8],
+) -> [u8; 32] {
+    let mut h = Keccak256::new();
+    h.update(b"AA-BACKUP-SEAL-v1");
+    h.update(sealing_key);
+    h.update(header.digest());
+    h.update(password);
+    h.finalize().into()
+}
+
+/// Passkey commitment for `ExportEncryptedBackup`: this wallet, under these
+/// KDF parameters.
+pub fn export_commitment(wallet_id: &Uuid, kdf: &KdfConfig) -> [u8; 32] {
+    let mut h = Keccak256::new();
+    h.update(b"AA-BACKUP-EXPORT-v1");
+    h.update(wallet_id.as_bytes());
+    h.update(kdf.to_string().as_bytes());
+    h.finalize().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header() -> BackupHeader {
+        BackupHeader {
+            version: BACKUP_VERSION,
+            wallet_id: Uuid::from_bytes([3; 16]),
+            passkey_pubkey: [vec![0x04], vec![9; 64]].concat(),
+            next_address_index: 2,
+            next_account_index: 0,
+            created_at: 1_700_000_000,
+            device: device_fingerprint(&[7; 32]),
+        }
+    }
+
+    fn backup(kdf: KdfConfig) -> EncryptedBackup {
+        EncryptedBackup {
+            header: header(),
+            keystore: Keystore {
+                kdf,
+                salt: vec![1; 32],
+                iv: [2; 16],
+                ciphertext: vec![3; 32],
+                mac: [4; 32],
+            },
+        }
+    }
+
+    #[test]
+    fn backup_shape() {
+        let argon = KdfConfig::defaults_for("argon2id").unwrap();
+        assert!(backup(argon).validate().is_ok());
+        assert!(backup(KdfConfig::defaults_for("scrypt").unwrap())
+            .validate()
+            .is_err());
+        let mut b = backup(argon);
+        b.keystore.ciphertext.pop();
+        assert!(b.validate().is_err());
+        let mut b = backup(argon);
+        b.header.passkey_pubkey[0] = 0x02;
+        assert!(b.validate().is_err());
+        let mut b = backup(argon);
+        b.header.version = 2;
+        assert!(b.validate().is_err());
+    }
+
+    #[test]
+    fn the_passphrase_binds_device_header_and_password() {
+        let h = header();
+        let p = sealed_passphrase(&[7; 32], &h, b"correct horse");
+        assert_ne!(p, sealed_passphrase(&[8; 32], &h, b"correct horse"));
+        assert_ne!(p, sealed_passphrase(&[7; 32], &h, b"correct horsf"));
+        let mut rebound = h.clone();
+        rebound.passkey_pubkey[1] ^= 1;
+        assert_ne!(p, sealed_passphrase(&[7; 32], &rebound, b"correct horse"));
+        let mut moved = h;
+        moved.next_address_index += 1;
+        assert_ne!(p, sealed_passphrase(&[7; 32], &moved, b"correct horse"));
+
+        let w = Uuid::from_bytes([3; 16]);
+        let kdf = KdfConfig::defaults_for("argon2id").unwrap();
+        assert_ne!(
+            export_commitment(&w, &kdf),
+            crate::kdf::export_commitment(&w, &kdf)
+        );
+    }
+
+    #[cfg(feature = "kdf")]
+    #[test]
+    fn a_sealed_backup_opens_only_on_its_device() {
+        let kdf = KdfConfig::Argon2id {
+            memory_kib: 64,
+            iterations: 3,
+            parallelism: 1,
+        };
+        let h = header();
+        let pass = sealed_passphrase(&[7; 32], &h, b"password");
+        let keystore = Keystore::seal(&[0x5a; 32], &pass, kdf, vec![1; 32], [2; 16]).unwrap();
+        assert_eq!(keystore.open(&pass).unwrap(), vec![0x5a; 32]);
+        let elsewhere = sealed_passphrase(&[8; 32], &h, b"password");
+        assert!(keystore.open(&elsewhere).is_err());
+    }
+}
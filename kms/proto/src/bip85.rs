@@ -0,0 +1,194 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! BIP85 child secrets, exported sealed to a recipient key.
+//!
+//! The TA derives `m/83696968'/<app>'/…/<index>'` from the wallet seed, runs
+//! HMAC-SHA512("bip-entropy-from-k", k) and truncates for the application.
+//! The child entropy leaves the TEE only as a [`Bip85Package`]: ECDH between
+//! a fresh TA key and the recipient's secp256k1 key, then the same
+//! encrypt-then-MAC as seed replication. The CA carries ciphertext only.
+
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+use uuid::Uuid;
+
+/// BIP85 purpose, "83696968'".
+pub const BIP85_PURPOSE: u32 = 83_696_968;
+
+const DOMAIN: &[u8] = b"AirAccount-bip85-v1";
+
+/// The BIP85 applications the TA derives. Indices are hardened, so below 2^31.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bip85App {
+    /// BIP39 mnemonic (English): the recipient turns the entropy into words.
+    Bip39 { words: u32, index: u32 },
+    /// Raw entropy, 16-64 bytes.
+    Hex { num_bytes: u32, index: u32 },
+}
+
+impl Bip85App {
+    pub fn validate(&self) -> Result<(), &'static str> {
+        match *self {
+            Bip85App::Bip39 { words, index } => {
+                if ![12, 18, 24].contains(&words) {
+                    return Err("BIP39 child must have 12, 18 or 24 words");
+                }
+                check_index(index)
+            }
+            Bip85App::Hex { num_bytes, index } => {
+                if !(16..=64).contains(&num_bytes) {
+                    return Err("hex child must be 16-64 bytes");
+                }
+                check_index(index)
+            }
+        }
+    }
+
+    /// Path levels after `m/`, all to be hardened.
+    pub fn path(&self) -> Vec<u32> {
+        match *self {
+            // English is language 0'.
+            Bip85App::Bip39 { words, index } => vec![BIP85_PURPOSE, 39, 0, words, index],
+            Bip85App::Hex { num_bytes, index } => vec![BIP85_PURPOSE, 128_169, num_bytes, index],
+        }
+    }
+
+    pub fn path_string(&self) -> String {
+        let levels: Vec<String> = self.path().iter().map(|l| format!("{}'", l)).collect();
+        format!("m/{}", levels.join("/"))
+    }
+
+    /// Bytes of the 64-byte BIP85 entropy this application keeps.
+    pub fn entropy_len(&self) -> usize {
+        match *self {
+            Bip85App::Bip39 { words, .. } => (words as usize) * 4 / 3,
+            Bip85App::Hex { num_bytes, .. } => num_bytes as usize,
+        }
+    }
+}
+
+fn check_index(index: u32) -> Result<(), &'static str> {
+    if index >= 0x8000_0000 {
+        return Err("BIP85 index must be below 2^31");
+    }
+    Ok(())
+}
+
+/// Passkey commitment for an export: this child, to this recipient only.
+pub fn export_commitment(wallet_id: &Uuid, app: &Bip85App, recipient_pubkey: &[u8]) -> [u8; 32] {
+    let mut h = Keccak256::new();
+    h.update(DOMAIN);
+    h.update(wallet_id.as_bytes());
+    h.update(app.path_string().as_bytes());
+    h.update([0u8]);
+    h.update(recipient_pubkey);
+    h.finalize().into()
+}
+
+/// A child secret sealed to the recipient's key.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Bip85Package {
+    pub wallet_id: Uuid,
+    pub app: Bip85App,
+    /// Compressed secp256k1 key the recipient runs ECDH against.
+    pub recipient_pubkey: Vec<u8>,
+    /// The TA's single-use compressed secp256k1 key.
+    pub ephemeral_pubkey: Vec<u8>,
+    /// Salt of the key derivation; random per export.
+    pub nonce: [u8; 16],
+    pub ciphertext: Vec<u8>,
+    /// HMAC-SHA256 over [`Self::aad`] ‖ ciphertext.
+    pub mac: [u8; 32],
+}
+
+impl Bip85Package {
+    /// Header bytes the MAC authenticates along with the ciphertext.
+    pub fn aad(&self) -> Vec<u8> {
+        let mut out = DOMAIN.to_vec();
+        out.extend_from_slice(self.wallet_id.as_bytes());
+        out.extend_from_slice(self.app.path_string().as_bytes());
+        out.push(0);
+        out.extend_from_slice(&self.nonce);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn application_paths_and_lengths() {
+        let mnemonic = Bip85App::Bip39 {
+            words: 12,
+            index: 0,
+        };
+        assert_eq!(mnemonic.path_string(), "m/83696968'/39'/0'/12'/0'");
+        assert_eq!(mnemonic.entropy_len(), 16);
+        assert_eq!(
+            Bip85App::Bip39 {
+                words: 24,
+                index: 0
+            }
+            .entropy_len(),
+            32
+        );
+        let hex = Bip85App::Hex {
+            num_bytes: 64,
+            index: 7,
+        };
+        assert_eq!(hex.path_string(), "m/83696968'/128169'/64'/7'");
+        assert_eq!(hex.entropy_len(), 64);
+
+        assert!(mnemonic.validate().is_ok());
+        assert!(Bip85App::Bip39 {
+            words: 15,
+            index: 0
+        }
+        .validate()
+        .is_err());
+        assert!(Bip85App::Hex {
+            num_bytes: 8,
+            index: 0
+        }
+        .validate()
+        .is_err());
+        assert!(Bip85App::Hex {
+            num_bytes: 32,
+            index: 0x8000_0000
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[test]
+    fn commitment_binds_child_and_recipient() {
+        let w = Uuid::nil();
+        let a = Bip85App::Hex {
+            num_bytes: 32,
+            index: 1,
+        };
+        let b = Bip85App::Hex {
+            num_bytes: 32,
+            index: 2,
+        };
+        let base = export_commitment(&w, &a, &[2; 33]);
+        assert_ne!(base, export_commitment(&w, &b, &[2; 33]));
+        assert_ne!(base, export_commitment(&w, &a, &[3; 33]));
+    }
+}
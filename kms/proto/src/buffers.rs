@@ -0,0 +1,150 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Sizes the CA and the TA have to agree on.
+//!
+//! The CA allocates the output parameter (p1) of every invoke and the TA
+//! serializes into it, answering SHORT_BUFFER when the output does not fit.
+//! The CA sizes p1 with [`max_output_len`]; the TA writes at most
+//! [`MAX_OUTPUT_LEN`] whatever it is given. Limits elsewhere in this crate
+//! that decide how large an output gets (a dapp storage value, an ECIES
+//! plaintext, the number of command ids) are checked against these sizes at
+//! compile time below, so raising one without the other fails the build
+//! rather than a call.
+
+use crate::Command;
+
+/// Highest command id in use. The TA's per-command tables and the CA's
+/// capability list run from 0 to here; bump it with every new command.
+pub const MAX_COMMAND_ID: u32 = Command::ThresholdStatus as u32;
+/// Per-command table slots: every id, plus one that larger ids fold into.
+pub const COMMAND_SLOTS: usize = MAX_COMMAND_ID as usize + 2;
+
+/// bincode length prefix of a `Vec` or `String`.
+pub const LEN_PREFIX: usize = 8;
+
+/// Output buffer of a command with no larger maximum below.
+pub const DEFAULT_OUTPUT_LEN: usize = 4096;
+/// The largest output buffer the CA allocates and the most the TA writes.
+pub const MAX_OUTPUT_LEN: usize = 8192;
+
+/// One `CommandLatency`: three u32s and the bucket counters.
+const COMMAND_LATENCY_LEN: usize =
+    3 * 4 + LEN_PREFIX + (crate::TA_LATENCY_BUCKETS_MS.len() + 1) * 4;
+/// `Some(StorageUsage)`: the Option tag and three u32s.
+const STORAGE_USAGE_LEN: usize = 1 + 3 * 4;
+/// `TaStatsOutput` with every slot in use.
+pub const TA_STATS_OUTPUT_LEN: usize =
+    LEN_PREFIX + COMMAND_SLOTS * COMMAND_LATENCY_LEN + STORAGE_USAGE_LEN;
+/// `DappStorageGetOutput` holding the largest value.
+pub const DAPP_STORAGE_GET_OUTPUT_LEN: usize = LEN_PREFIX + crate::dapp_storage::MAX_VALUE_BYTES;
+/// `EciesDecryptOutput` holding the largest plaintext.
+pub const ECIES_DECRYPT_OUTPUT_LEN: usize = LEN_PREFIX + crate::ecies::MAX_PLAINTEXT + 4;
+
+/// `ContinueTransferOutput` carrying a full chunk: the Option tag, the
+/// sequence number, the data and its digest.
+pub const CONTINUE_TRANSFER_OUTPUT_LEN: usize =
+    1 + 4 + LEN_PREFIX + crate::transfer::CHUNK_LEN + 32;
+
+/// The p1 buffer the CA allocates for `command`.
+pub const fn max_output_len(command: Command) -> usize {
+    match command {
+        Command::TaStats => TA_STATS_OUTPUT_LEN,
+        Command::DappStorageGet => DAPP_STORAGE_GET_OUTPUT_LEN,
+        _ => DEFAULT_OUTPUT_LEN,
+    }
+}
+
+const _: () = assert!(TA_STATS_OUTPUT_LEN <= MAX_OUTPUT_LEN);
+const _: () = assert!(DAPP_STORAGE_GET_OUTPUT_LEN <= MAX_OUTPUT_LEN);
+const _: () = assert!(ECIES_DECRYPT_OUTPUT_LEN <= DEFAULT_OUTPUT_LEN);
+const _: () = assert!(DEFAULT_OUTPUT_LEN <= MAX_OUTPUT_LEN);
+const _: () = assert!(CONTINUE_TRANSFER_OUTPUT_LEN <= DEFAULT_OUTPUT_LEN);
+const _: () = assert!(crate::transfer::MAX_DIRECT_INPUT <= crate::transfer::MAX_TRANSFER_LEN);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        CommandLatency, ContinueTransferOutput, DappStorageGetOutput, EciesDecryptOutput,
+        TaStatsOutput,
+    };
+
+    #[test]
+    fn maxima_match_the_encoded_worst_case() {
+        let stats = TaStatsOutput {
+            commands: vec![
+                CommandLatency {
+                    command: u32::MAX,
+                    count: u32::MAX,
+                    errors: u32::MAX,
+                    buckets: vec![u32::MAX; crate::TA_LATENCY_BUCKETS_MS.len() + 1],
+                };
+                COMMAND_SLOTS
+            ],
+            storage: Some(crate::storage_quota::StorageUsage {
+                wallets: u32::MAX,
+                max_wallets: u32::MAX,
+                exhausted: u32::MAX,
+            }),
+        };
+        let get = DappStorageGetOutput {
+            value: vec![0xff; crate::dapp_storage::MAX_VALUE_BYTES],
+        };
+        let decrypt = EciesDecryptOutput {
+            plaintext: vec![0xff; crate::ecies::MAX_PLAINTEXT],
+            remaining: u32::MAX,
+        };
+        let size = |n: u64| n as usize;
+        assert_eq!(
+            size(bincode::serialized_size(&stats).unwrap()),
+            max_output_len(Command::TaStats)
+        );
+        assert_eq!(
+            size(bincode::serialized_size(&get).unwrap()),
+            max_output_len(Command::DappStorageGet)
+        );
+        assert_eq!(
+            size(bincode::serialized_size(&decrypt).unwrap()),
+            ECIES_DECRYPT_OUTPUT_LEN
+        );
+        let chunk = ContinueTransferOutput {
+            chunk: Some(crate::transfer::TransferChunk::new(
+                u64::MAX,
+                u32::MAX,
+                vec![0xff; crate::transfer::CHUNK_LEN],
+            )),
+        };
+        assert_eq!(
+            size(bincode::serialized_size(&chunk).unwrap()),
+            CONTINUE_TRANSFER_OUTPUT_LEN
+        );
+    }
+
+    #[test]
+    fn max_command_id_is_the_last_command() {
+        assert_ne!(Command::from(MAX_COMMAND_ID), Command::Unknown);
+        for id in MAX_COMMAND_ID + 1..MAX_COMMAND_ID + 64 {
+            assert_eq!(
+                Command::from(id),
+                Command::Unknown,
+                "command {} is past MAX_COMMAND_ID",
+                id
+            );
+        }
+    }
+}
@@ -0,0 +1,608 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Calldata decoding and human-readable transaction summaries.
+//!
+//! Shared by CA and TA so both render the exact same string: the CA shows it
+//! to the client, the client commits to it in the WebAuthn challenge, and the
+//! TA recomputes it from the transaction it is about to sign. A CA that lies
+//! about what a transaction does therefore breaks the passkey check.
+//!
+//! Selectors come from a small bundled signature list (hashed at lookup, so
+//! there is no hand-copied 4-byte table to get wrong) plus optional
+//! caller-supplied signatures. Bundled entries always win, and a
+//! caller-supplied match is labelled as such: a 4-byte selector is cheap to
+//! collide, so those names are hints, not facts.
+
+use crate::amount::TokenAmount;
+use crate::EthTransaction;
+use sha3::{Digest, Keccak256};
+
+/// Function signatures the decoder knows without help.
+pub const BUNDLED_SIGNATURES: &[&str] = &[
+    "transfer(address,uint256)",
+    "approve(address,uint256)",
+    "transferFrom(address,address,uint256)",
+    "increaseAllowance(address,uint256)",
+    "setApprovalForAll(address,bool)",
+    "safeTransferFrom(address,address,uint256)",
+    "permit(address,address,uint256,uint256,uint8,bytes32,bytes32)",
+    "deposit()",
+    "withdraw(uint256)",
+    "execute(address,uint256,bytes)",
+];
+
+struct KnownToken {
+    chain_id: u64,
+    address: [u8; 20],
+    symbol: &'static str,
+    decimals: u32,
+}
+
+pub(crate) const fn addr(s: &[u8; 40]) -> [u8; 20] {
+    const fn nib(c: u8) -> u8 {
+        match c {
+            b'0'..=b'9' => c - b'0',
+            b'a'..=b'f' => c - b'a' + 10,
+            b'A'..=b'F' => c - b'A' + 10,
+            _ => panic!("bad hex"),
+        }
+    }
+    let mut out = [0u8; 20];
+    let mut i = 0;
+    while i < 20 {
+        out[i] = (nib(s[2 * i]) << 4) | nib(s[2 * i + 1]);
+        i += 1;
+    }
+    out
+}
+
+const KNOWN_TOKENS: &[KnownToken] = &[
+    KnownToken {
+        chain_id: 1,
+        address: addr(b"dAC17F958D2ee523a2206206994597C13D831ec7"),
+        symbol: "USDT",
+        decimals: 6,
+    },
+    KnownToken {
+        chain_id: 1,
+        address: addr(b"A0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48"),
+        symbol: "USDC",
+        decimals: 6,
+    },
+    KnownToken {
+        chain_id: 1,
+        address: addr(b"6B175474E89094C44Da98b954EedeAC495271d0F"),
+        symbol: "DAI",
+        decimals: 18,
+    },
+    KnownToken {
+        chain_id: 1,
+        address: addr(b"C02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2"),
+        symbol: "WETH",
+        decimals: 18,
+    },
+    KnownToken {
+        chain_id: 10,
+        address: addr(b"0b2C639c533813f4Aa9D7837CAf62653d097Ff85"),
+        symbol: "USDC",
+        decimals: 6,
+    },
+    KnownToken {
+        chain_id: 8453,
+        address: addr(b"833589fCD6eDb6E08f4c7C32D4f71b54bdA02913"),
+        symbol: "USDC",
+        decimals: 6,
+    },
+    KnownToken {
+        chain_id: 42161,
+        address: addr(b"af88d065e77c8cC2239327C5EDb3A432268e5831"),
+        symbol: "USDC",
+        decimals: 6,
+    },
+];
+
+/// (address, decimals) of a bundled token by symbol, case-insensitive.
+pub fn token_by_symbol(chain_id: u64, symbol: &str) -> Option<([u8; 20], u32)> {
+    KNOWN_TOKENS
+        .iter()
+        .find(|t| t.chain_id == chain_id && t.symbol.eq_ignore_ascii_case(symbol))
+        .map(|t| (t.address, t.decimals))
+}
+
+/// (symbol, decimals) of a bundled token by address.
+pub fn token_info(chain_id: u64, token: &[u8; 20]) -> Option<(&'static str, u32)> {
+    KNOWN_TOKENS
+        .iter()
+        .find(|t| t.chain_id == chain_id && &t.address == token)
+        .map(|t| (t.symbol, t.decimals))
+}
+
+/// A uint256 ABI word.
+pub type Word = [u8; 32];
+
+/// What a transaction does, as far as the decoder can tell.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodedCall {
+    Deploy {
+        init_code_len: usize,
+    },
+    NativeTransfer {
+        to: [u8; 20],
+    },
+    Transfer {
+        token: [u8; 20],
+        to: [u8; 20],
+        amount: Word,
+    },
+    Approve {
+        token: [u8; 20],
+        spender: [u8; 20],
+        amount: Word,
+    },
+    IncreaseAllowance {
+        token: [u8; 20],
+        spender: [u8; 20],
+        amount: Word,
+    },
+    TransferFrom {
+        token: [u8; 20],
+        from: [u8; 20],
+        to: [u8; 20],
+        amount: Word,
+    },
+    SetApprovalForAll {
+        collection: [u8; 20],
+        operator: [u8; 20],
+        approved: bool,
+    },
+    Permit {
+        token: [u8; 20],
+        owner: [u8; 20],
+        spender: [u8; 20],
+        amount: Word,
+    },
+    /// Selector matched a signature we can't decode field-by-field.
+    Named {
+        target: [u8; 20],
+        signature: String,
+        user_supplied: bool,
+    },
+    Unknown {
+        target: [u8; 20],
+        selector: [u8; 4],
+        data_len: usize,
+    },
+}
+
+pub fn selector(signature: &str) -> [u8; 4] {
+    let h = Keccak256::digest(signature.as_bytes());
+    [h[0], h[1], h[2], h[3]]
+}
+
+fn word(args: &[u8], i: usize) -> Option<Word> {
+    let w = args.get(i * 32..(i + 1) * 32)?;
+    let mut out = [0u8; 32];
+    out.copy_from_slice(w);
+    Some(out)
+}
+
+fn address_arg(args: &[u8], i: usize) -> Option<[u8; 20]> {
+    let w = word(args, i)?;
+    if w[..12].iter().any(|&b| b != 0) {
+        return None;
+    }
+    let mut out = [0u8; 20];
+    out.copy_from_slice(&w[12..]);
+    Some(out)
+}
+
+fn bool_arg(args: &[u8], i: usize) -> Option<bool> {
+    let w = word(args, i)?;
+    match (w[..31].iter().all(|&b| b == 0), w[31]) {
+        (true, 0) => Some(false),
+        (true, 1) => Some(true),
+        _ => None,
+    }
+}
+
+fn decode_known(signature: &str, target: [u8; 20], args: &[u8]) -> Option<DecodedCall> {
+    Some(match signature {
+        "transfer(address,uint256)" => DecodedCall::Transfer {
+            token: target,
+            to: address_arg(args, 0)?,
+            amount: word(args, 1)?,
+        },
+        "approve(address,uint256)" => DecodedCall::Approve {
+            token: target,
+            spender: address_arg(args, 0)?,
+            amount: word(args, 1)?,
+        },
+        "increaseAllowance(address,uint256)" => DecodedCall::IncreaseAllowance {
+            token: target,
+            spender: address_arg(args, 0)?,
+            amount: word(args, 1)?,
+        },
+        "transferFrom(address,address,uint256)" => DecodedCall::TransferFrom {
+            token: target,
+            from: address_arg(args, 0)?,
+            to: address_arg(args, 1)?,
+            amount: word(args, 2)?,
+        },
+        "setApprovalForAll(address,bool)" => DecodedCall::SetApprovalForAll {
+            collection: target,
+            operator: address_arg(args, 0)?,
+            approved: bool_arg(args, 1)?,
+        },
+        "permit(address,address,uint256,uint256,uint8,bytes32,bytes32)" => DecodedCall::Permit {
+            token: target,
+            owner: address_arg(args, 0)?,
+            spender: address_arg(args, 1)?,
+            amount: word(args, 2)?,
+        },
+        _ => return None,
+    })
+}
+
+/// Decode `tx` against the bundled signatures, then `user_signatures`.
+/// Malformed arguments for a known selector fall back to `Named` rather than
+/// guessing.
+pub fn decode_transaction(tx: &EthTransaction, user_signatures: &[String]) -> DecodedCall {
+    let target = match tx.to {
+        Some(to) => to,
+        None => {
+            return DecodedCall::Deploy {
+                init_code_len: tx.data.len(),
+            }
+        }
+    };
+    if tx.data.is_empty() {
+        return DecodedCall::NativeTransfer { to: target };
+    }
+    if tx.data.len() < 4 {
+        return DecodedCall::Unknown {
+            target,
+            selector: [0; 4],
+            data_len: tx.data.len(),
+        };
+    }
+    let mut sel = [0u8; 4];
+    sel.copy_from_slice(&tx.data[..4]);
+    let args = &tx.data[4..];
+
+    if let Some(sig) = BUNDLED_SIGNATURES.iter().find(|s| selector(s) == sel) {
+        return decode_known(sig, target, args).unwrap_or_else(|| DecodedCall::Named {
+            target,
+            signature: sig.to_string(),
+            user_supplied: false,
+        });
+    }
+    if let Some(sig) = user_signatures.iter().find(|s| selector(s) == sel) {
+        return DecodedCall::Named {
+            target,
+            signature: sig.clone(),
+            user_supplied: true,
+        };
+    }
+    DecodedCall::Unknown {
+        target,
+        selector: sel,
+        data_len: tx.data.len(),
+    }
+}
+
+/// Amounts that don't fit in u128 are treated as unlimited: no real token
+/// balance comes near it, and it catches both `type(uint256).max` and the
+/// "2^255" style infinite approvals.
+pub fn is_unlimited(amount: &Word) -> bool {
+    amount[..16].iter().any(|&b| b != 0)
+}
+
+fn word_u128(amount: &Word) -> Option<u128> {
+    if is_unlimited(amount) {
+        return None;
+    }
+    let mut lo = [0u8; 16];
+    lo.copy_from_slice(&amount[16..]);
+    Some(u128::from_be_bytes(lo))
+}
+
+/// Lowercase `0x`-prefixed address, the form every summary uses.
+pub fn hex_addr(a: &[u8; 20]) -> String {
+    let mut s = String::with_capacity(42);
+    s.push_str("0x");
+    for b in a {
+        s.push_str(&format!("{:02x}", b));
+    }
+    s
+}
+
+/// Integer-only decimal formatting, trailing zeros trimmed.
+pub fn format_units(value: u128, decimals: u32) -> String {
+    if decimals == 0 {
+        return value.to_string();
+    }
+    let base = 10u128.pow(decimals);
+    let whole = value / base;
+    let frac = value % base;
+    if frac == 0 {
+        return whole.to_string();
+    }
+    let frac = format!("{:0width$}", frac, width = decimals as usize);
+    format!("{}.{}", whole, frac.trim_end_matches('0'))
+}
+
+/// The native coin's symbol as summaries print it; "native" on chains not
+/// listed here.
+pub fn native_symbol(chain_id: u64) -> &'static str {
+    match chain_id {
+        1 | 10 | 8453 | 42161 | 11155111 | 11155420 | 84532 => "ETH",
+        _ => "native",
+    }
+}
+
+pub(crate) fn token_amount(chain_id: u64, token: &[u8; 20], amount: &Word) -> String {
+    let known = KNOWN_TOKENS
+        .iter()
+        .find(|t| t.chain_id == chain_id && &t.address == token);
+    match (word_u128(amount), known) {
+        (None, Some(t)) => format!("{} unlimited", t.symbol),
+        (None, None) => format!("unlimited of token {}", hex_addr(token)),
+        (Some(v), Some(t)) => format!(
+            "{} {}",
+            TokenAmount {
+                base_units: v,
+                decimals: t.decimals
+            },
+            t.symbol
+        ),
+        (Some(v), None) => format!("{} (raw units) of token {}", v, hex_addr(token)),
+    }
+}
+
+impl DecodedCall {
+    pub fn describe(&self, chain_id: u64) -> String {
+        match self {
+            DecodedCall::Deploy { init_code_len } => {
+                format!("deploy contract ({} bytes init code)", init_code_len)
+            }
+            DecodedCall::NativeTransfer { to } => format!("send to {}", hex_addr(to)),
+            DecodedCall::Transfer { token, to, amount } => format!(
+                "transfer {} to {}",
+                token_amount(chain_id, token, amount),
+                hex_addr(to)
+            ),
+            DecodedCall::Approve {
+                token,
+                spender,
+                amount,
+            } => format!(
+                "approve {} to {}",
+                token_amount(chain_id, token, amount),
+                hex_addr(spender)
+            ),
+            DecodedCall::IncreaseAllowance {
+                token,
+                spender,
+                amount,
+            } => format!(
+                "increase allowance by {} for {}",
+                token_amount(chain_id, token, amount),
+                hex_addr(spender)
+            ),
+            DecodedCall::TransferFrom {
+                token,
+                from,
+                to,
+                amount,
+            } => format!(
+                "transfer {} from {} to {}",
+                token_amount(chain_id, token, amount),
+                hex_addr(from),
+                hex_addr(to)
+            ),
+            DecodedCall::SetApprovalForAll {
+                collection,
+                operator,
+                approved,
+            } => format!(
+                "{} {} for ALL items of collection {}",
+                if *approved { "approve" } else { "revoke" },
+                hex_addr(operator),
+                hex_addr(collection)
+            ),
+            DecodedCall::Permit {
+                token,
+                owner,
+                spender,
+                amount,
+            } => format!(
+                "permit {} from {} to {}",
+                token_amount(chain_id, token, amount),
+                hex_addr(owner),
+                hex_addr(spender)
+            ),
+            DecodedCall::Named {
+                target,
+                signature,
+                user_supplied,
+            } => format!(
+                "call {}{} on {}",
+                signature,
+                if *user_supplied {
+                    " [caller-supplied ABI]"
+                } else {
+                    ""
+                },
+                hex_addr(target)
+            ),
+            DecodedCall::Unknown {
+                target,
+                selector,
+                data_len,
+            } => format!(
+                "call unknown function 0x{:02x}{:02x}{:02x}{:02x} on {} ({} bytes calldata)",
+                selector[0],
+                selector[1],
+                selector[2],
+                selector[3],
+                hex_addr(target),
+                data_len
+            ),
+        }
+    }
+}
+
+/// The one-line summary a user confirms: decoded action plus any native value.
+pub fn summarize_transaction(tx: &EthTransaction, user_signatures: &[String]) -> String {
+    let symbol = native_symbol(tx.chain_id);
+    match decode_transaction(tx, user_signatures) {
+        DecodedCall::NativeTransfer { to } => format!(
+            "send {} {} to {}",
+            format_units(tx.value, 18),
+            symbol,
+            hex_addr(&to)
+        ),
+        call if tx.value > 0 => format!(
+            "{}, with {} {}",
+            call.describe(tx.chain_id),
+            format_units(tx.value, 18),
+            symbol
+        ),
+        call => call.describe(tx.chain_id),
+    }
+}
+
+const CONFIRMATION_DOMAIN: &[u8] = b"AirAccount-tx-summary-v1";
+
+/// The payload a summary-committing client binds its WebAuthn challenge to
+/// (challenge = SHA-256(nonce || this)): keccak256(domain || tx_hash || summary).
+pub fn confirmation_digest(tx_hash: &[u8; 32], summary: &str) -> [u8; 32] {
+    let mut h = Keccak256::new();
+    h.update(CONFIRMATION_DOMAIN);
+    h.update(tx_hash);
+    h.update(summary.as_bytes());
+    h.finalize().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const USDT: [u8; 20] = addr(b"dAC17F958D2ee523a2206206994597C13D831ec7");
+    const SPENDER: [u8; 20] = [0x11; 20];
+
+    fn tx(to: Option<[u8; 20]>, data: Vec<u8>, value: u128) -> EthTransaction {
+        EthTransaction {
+            chain_id: 1,
+            nonce: 0,
+            to,
+            value,
+            gas_price: 0,
+            gas: 21000,
+            data,
+        }
+    }
+
+    fn call(sig: &str, words: &[Word]) -> Vec<u8> {
+        let mut d = selector(sig).to_vec();
+        for w in words {
+            d.extend_from_slice(w);
+        }
+        d
+    }
+
+    fn addr_word(a: &[u8; 20]) -> Word {
+        let mut w = [0u8; 32];
+        w[12..].copy_from_slice(a);
+        w
+    }
+
+    fn uint_word(v: u128) -> Word {
+        let mut w = [0u8; 32];
+        w[16..].copy_from_slice(&v.to_be_bytes());
+        w
+    }
+
+    #[test]
+    fn well_known_selectors() {
+        assert_eq!(
+            selector("transfer(address,uint256)"),
+            [0xa9, 0x05, 0x9c, 0xbb]
+        );
+        assert_eq!(
+            selector("approve(address,uint256)"),
+            [0x09, 0x5e, 0xa7, 0xb3]
+        );
+    }
+
+    #[test]
+    fn unlimited_usdt_approval_summary() {
+        let data = call(
+            "approve(address,uint256)",
+            &[addr_word(&SPENDER), [0xff; 32]],
+        );
+        let s = summarize_transaction(&tx(Some(USDT), data, 0), &[]);
+        assert_eq!(
+            s,
+            "approve USDT unlimited to 0x1111111111111111111111111111111111111111"
+        );
+    }
+
+    #[test]
+    fn transfer_amount_uses_token_decimals() {
+        let data = call(
+            "transfer(address,uint256)",
+            &[addr_word(&SPENDER), uint_word(12_500_000)],
+        );
+        let s = summarize_transaction(&tx(Some(USDT), data, 0), &[]);
+        assert!(s.starts_with("transfer 12.5 USDT to 0x1111"), "{}", s);
+    }
+
+    #[test]
+    fn native_send_and_unknown_calls() {
+        let s = summarize_transaction(&tx(Some(SPENDER), vec![], 1_500_000_000_000_000_000), &[]);
+        assert_eq!(
+            s,
+            "send 1.5 ETH to 0x1111111111111111111111111111111111111111"
+        );
+        let data = vec![0xde, 0xad, 0xbe, 0xef, 0, 0];
+        let s = summarize_transaction(&tx(Some(SPENDER), data.clone(), 0), &[]);
+        assert!(s.starts_with("call unknown function 0xdeadbeef"), "{}", s);
+        let user = vec!["stake(uint256)".to_string()];
+        let mut staked = selector("stake(uint256)").to_vec();
+        staked.extend_from_slice(&uint_word(1));
+        let s = summarize_transaction(&tx(Some(SPENDER), staked, 0), &user);
+        assert!(s.contains("stake(uint256) [caller-supplied ABI]"), "{}", s);
+    }
+
+    #[test]
+    fn malformed_args_are_not_guessed() {
+        // address word with dirty high bytes
+        let data = call("transfer(address,uint256)", &[[0xff; 32], uint_word(1)]);
+        let d = decode_transaction(&tx(Some(USDT), data, 0), &[]);
+        assert!(matches!(d, DecodedCall::Named { .. }));
+    }
+
+    #[test]
+    fn confirmation_digest_binds_summary() {
+        let h = [7u8; 32];
+        assert_ne!(confirmation_digest(&h, "a"), confirmation_digest(&h, "b"));
+        assert_eq!(format_units(1, 6), "0.000001");
+        assert_eq!(format_units(2_000_000, 6), "2");
+    }
+}
@@ -0,0 +1,211 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Chains `DeriveAddress` serves from one seed, and how each writes an
+//! address.
+//!
+//! The EVM chains share coin type 60 and the Keccak-160 address, so a path
+//! gives the same account on Ethereum, BSC and Polygon, as MetaMask does.
+//! Tron derives under coin type 195 (`m/44'/195'/0'/account/address`, as
+//! TronLink) and hashes the key the same way, but writes Base58Check over
+//! `0x41 ‖ hash`.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+
+/// Coin type of every EVM chain.
+pub const EVM_COIN_TYPE: u32 = 60;
+
+/// SLIP-44 coin type of Tron.
+pub const TRON_COIN_TYPE: u32 = 195;
+
+/// Version byte in front of a Tron address hash.
+pub const TRON_ADDRESS_PREFIX: u8 = 0x41;
+
+/// The wire tag is the variant's position: append only. Ethereum is tag 0,
+/// which is what an input from a CA that predates the field decodes to.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Chain {
+    #[default]
+    Ethereum,
+    Bsc,
+    Polygon,
+    Tron,
+}
+
+impl Chain {
+    pub const ALL: [Chain; 4] = [Chain::Ethereum, Chain::Bsc, Chain::Polygon, Chain::Tron];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Chain::Ethereum => "ethereum",
+            Chain::Bsc => "bsc",
+            Chain::Polygon => "polygon",
+            Chain::Tron => "tron",
+        }
+    }
+
+    /// BIP-44 coin type the TA derives under.
+    pub fn coin_type(self) -> u32 {
+        match self {
+            Chain::Tron => TRON_COIN_TYPE,
+            Chain::Ethereum | Chain::Bsc | Chain::Polygon => EVM_COIN_TYPE,
+        }
+    }
+
+    pub fn is_evm(self) -> bool {
+        self.coin_type() == EVM_COIN_TYPE
+    }
+
+    /// The chain's written form of a 20-byte address hash.
+    pub fn encode_address(self, hash: &[u8; 20]) -> String {
+        match self {
+            Chain::Tron => tron_address(hash),
+            Chain::Ethereum | Chain::Bsc | Chain::Polygon => {
+                crate::eip55::to_checksum_address(hash)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Chain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl std::str::FromStr for Chain {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        Chain::ALL
+            .iter()
+            .copied()
+            .find(|c| c.name().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| {
+                let names: Vec<&str> = Chain::ALL.iter().map(|c| c.name()).collect();
+                format!(
+                    "Unknown chain '{}' (expected one of {})",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// Base58Check of `0x41 ‖ hash`, e.g. `TUEZSdKsoDHQMeZwihtdoBiN46zxhGWYdH`.
+pub fn tron_address(hash: &[u8; 20]) -> String {
+    let mut payload = Vec::with_capacity(25);
+    payload.push(TRON_ADDRESS_PREFIX);
+    payload.extend_from_slice(hash);
+    let check = Sha256::digest(Sha256::digest(&payload));
+    payload.extend_from_slice(&check[..4]);
+    base58(&payload)
+}
+
+const BASE58_ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// Bitcoin-alphabet Base58: one leading `1` per leading zero byte.
+pub(crate) fn base58(bytes: &[u8]) -> String {
+    let zeros = bytes.iter().take_while(|&&b| b == 0).count();
+    // Little-endian base-58 digits of the big-endian number.
+    let mut digits: Vec<u8> = Vec::with_capacity(bytes.len() * 138 / 100 + 1);
+    for &byte in &bytes[zeros..] {
+        let mut carry = byte as u32;
+        for digit in digits.iter_mut() {
+            carry += (*digit as u32) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+    let mut out = String::with_capacity(zeros + digits.len());
+    out.push_str(&"1".repeat(zeros));
+    out.extend(
+        digits
+            .iter()
+            .rev()
+            .map(|&d| BASE58_ALPHABET[d as usize] as char),
+    );
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash(hex: &str) -> [u8; 20] {
+        let mut out = [0u8; 20];
+        for (i, b) in out.iter_mut().enumerate() {
+            *b = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).unwrap();
+        }
+        out
+    }
+
+    #[test]
+    fn tron_addresses_match_published_ones() {
+        // The USDT TRC-20 contract, as Tronscan lists it.
+        assert_eq!(
+            tron_address(&hash("a614f803b6fd780986a42c78ec9c7f77e6ded13c")),
+            "TR7NHqjeKQxGTCi8q8ZY4pL8otSzgjLj6t"
+        );
+        assert_eq!(base58(&[0, 0, 1]), "112");
+        assert_eq!(base58(&[]), "");
+    }
+
+    #[test]
+    fn evm_chains_share_one_address() {
+        let h = hash("f39fd6e51aad88f6f4ce6ab8827279cfffb92266");
+        for chain in [Chain::Ethereum, Chain::Bsc, Chain::Polygon].iter() {
+            assert!(chain.is_evm());
+            assert_eq!(
+                chain.encode_address(&h),
+                "0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266"
+            );
+        }
+        assert!(!Chain::Tron.is_evm());
+        assert_eq!(Chain::Tron.coin_type(), 195);
+    }
+
+    #[test]
+    fn names_parse_and_tags_stay_put() {
+        for chain in Chain::ALL.iter() {
+            assert_eq!(chain.name().parse::<Chain>(), Ok(*chain));
+        }
+        assert_eq!(" TRON ".parse::<Chain>(), Ok(Chain::Tron));
+        assert!("bitcoin".parse::<Chain>().is_err());
+        assert_eq!(Chain::default(), Chain::Ethereum);
+        // The tags are wire format.
+        let tags: Vec<Vec<u8>> = Chain::ALL
+            .iter()
+            .map(|c| bincode::serialize(c).unwrap())
+            .collect();
+        assert_eq!(
+            tags,
+            vec![
+                vec![0, 0, 0, 0],
+                vec![1, 0, 0, 0],
+                vec![2, 0, 0, 0],
+                vec![3, 0, 0, 0]
+            ]
+        );
+    }
+}
@@ -0,0 +1,112 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Postmortem records of internal TA failures.
+//!
+//! When a command fails inside the TEE core API or on a (de)serialization
+//! error, rather than on an ordinary refusal, the TA keeps a [`CrashRecord`]
+//! in a dedicated secure-storage slot. Records hold ids, codes and counters
+//! only — no message text, since messages can quote inputs — and are read
+//! back with `Command::CrashDumps` (`kms-admin crash-dumps`).
+
+use serde::{Deserialize, Serialize};
+
+/// Records kept; older ones are dropped first.
+pub const MAX_CRASH_RECORDS: usize = 16;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorClass {
+    /// A TEE core API call failed; the code is its TEE_Result.
+    Tee,
+    /// Input or a stored record did not (de)serialize.
+    Codec,
+    /// The output did not fit the CA's buffer.
+    OutputTooLarge,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CrashRecord {
+    /// Position in the TA's crash history, from 0; survives ring eviction.
+    pub seq: u64,
+    pub command: u32,
+    pub class: ErrorClass,
+    /// TEE_Result for `Tee`, 0 otherwise.
+    pub code: u32,
+    /// TEE system time (seconds since boot) at the failure.
+    pub uptime_secs: u32,
+    /// Commands dispatched since the TA was loaded, and how many failed.
+    pub invocations: u32,
+    pub failures: u32,
+    /// The same two counters for this command id.
+    pub command_invocations: u32,
+    pub command_failures: u32,
+}
+
+/// Ring of the latest [`MAX_CRASH_RECORDS`] records.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct CrashLog {
+    pub next_seq: u64,
+    pub records: Vec<CrashRecord>,
+}
+
+impl CrashLog {
+    /// Append `record`, assigning its `seq`.
+    pub fn push(&mut self, mut record: CrashRecord) {
+        record.seq = self.next_seq;
+        self.next_seq += 1;
+        if self.records.len() == MAX_CRASH_RECORDS {
+            self.records.remove(0);
+        }
+        self.records.push(record);
+    }
+
+    /// Records evicted (or cleared) since the log began.
+    pub fn dropped(&self) -> u64 {
+        self.next_seq - self.records.len() as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ring_keeps_latest_records() {
+        let mut log = CrashLog::default();
+        let r = CrashRecord {
+            seq: 99,
+            command: 5,
+            class: ErrorClass::Tee,
+            code: 0xffff_000c,
+            uptime_secs: 10,
+            invocations: 3,
+            failures: 1,
+            command_invocations: 1,
+            command_failures: 1,
+        };
+        for _ in 0..MAX_CRASH_RECORDS + 3 {
+            log.push(r);
+        }
+        assert_eq!(log.records.len(), MAX_CRASH_RECORDS);
+        assert_eq!(log.records[0].seq, 3);
+        assert_eq!(
+            log.records.last().unwrap().seq,
+            MAX_CRASH_RECORDS as u64 + 2
+        );
+        assert_eq!(log.dropped(), 3);
+    }
+}
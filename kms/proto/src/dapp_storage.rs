@@ -0,0 +1,188 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Per-wallet, per-app key-value storage in TEE secure storage.
+//!
+//! A dapp stores small blobs (encrypted notes, 2FA seeds) next to the wallet
+//! under its own app id. The TA keeps one [`Namespace`] index per
+//! (wallet, app) with the size of every key, enforces the quotas below on it,
+//! and wipes every namespace of a wallet when the wallet is erased. Each Put,
+//! Get and Delete is passkey-approved; the challenge commits to
+//! [`op_commitment`], so the CA cannot redirect an approval to another app,
+//! key or value.
+
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+/// Largest value one key may hold.
+pub const MAX_VALUE_BYTES: usize = 4096;
+/// Keys per (wallet, app).
+pub const MAX_KEYS_PER_APP: usize = 32;
+/// Sum of value sizes per (wallet, app).
+pub const MAX_BYTES_PER_APP: usize = 32 * 1024;
+
+const MAX_NAME_LEN: usize = 64;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageOp {
+    Put,
+    Get,
+    Delete,
+}
+
+/// App ids are lowercase DNS-like names ("notes.example.org"). No `_`, so the
+/// TA's `dapp_<wallet>_<app>_<key>` record ids split unambiguously.
+pub fn validate_app_id(app_id: &str) -> Result<(), &'static str> {
+    if app_id.is_empty() || app_id.len() > MAX_NAME_LEN {
+        return Err("app id must be 1-64 characters");
+    }
+    if !app_id
+        .bytes()
+        .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'.' || b == b'-')
+    {
+        return Err("app id may only contain a-z, 0-9, '.' and '-'");
+    }
+    Ok(())
+}
+
+pub fn validate_key(key: &str) -> Result<(), &'static str> {
+    if key.is_empty() || key.len() > MAX_NAME_LEN {
+        return Err("key must be 1-64 characters");
+    }
+    if !key
+        .bytes()
+        .all(|b| b.is_ascii_alphanumeric() || b == b'.' || b == b'-' || b == b'_')
+    {
+        return Err("key may only contain A-Z, a-z, 0-9, '.', '-' and '_'");
+    }
+    Ok(())
+}
+
+/// Passkey commitment for one storage operation. Put commits to the value's
+/// hash, so an approval cannot be replayed with other contents.
+pub fn op_commitment(
+    op: StorageOp,
+    wallet_id: &Uuid,
+    app_id: &str,
+    key: &str,
+    value: Option<&[u8]>,
+) -> [u8; 32] {
+    let mut h = Keccak256::new();
+    h.update(b"AA-DAPP-STORAGE-v1");
+    h.update([match op {
+        StorageOp::Put => 1u8,
+        StorageOp::Get => 2,
+        StorageOp::Delete => 3,
+    }]);
+    h.update(wallet_id.as_bytes());
+    h.update((app_id.len() as u32).to_be_bytes());
+    h.update(app_id.as_bytes());
+    h.update((key.len() as u32).to_be_bytes());
+    h.update(key.as_bytes());
+    if let Some(v) = value {
+        h.update(Keccak256::digest(v));
+    }
+    h.finalize().into()
+}
+
+/// Keys and value sizes of one (wallet, app) namespace.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Namespace {
+    pub sizes: BTreeMap<String, u32>,
+}
+
+impl Namespace {
+    pub fn used_bytes(&self) -> usize {
+        self.sizes.values().map(|&n| n as usize).sum()
+    }
+
+    /// Record `key` at `len` bytes if the quotas allow it. Replacing a key
+    /// counts only the size difference.
+    pub fn reserve(&mut self, key: &str, len: usize) -> Result<(), &'static str> {
+        if len > MAX_VALUE_BYTES {
+            return Err("value exceeds 4096 bytes");
+        }
+        let replaced = self.sizes.get(key).map(|&n| n as usize);
+        if replaced.is_none() && self.sizes.len() >= MAX_KEYS_PER_APP {
+            return Err("app key quota exhausted");
+        }
+        if self.used_bytes() - replaced.unwrap_or(0) + len > MAX_BYTES_PER_APP {
+            return Err("app storage quota exhausted");
+        }
+        self.sizes.insert(key.to_string(), len as u32);
+        Ok(())
+    }
+
+    /// True if the key was present.
+    pub fn remove(&mut self, key: &str) -> bool {
+        self.sizes.remove(key).is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotas_count_replacements_once() {
+        let mut ns = Namespace::default();
+        assert!(ns.reserve("a", MAX_VALUE_BYTES + 1).is_err());
+        for i in 0..MAX_BYTES_PER_APP / MAX_VALUE_BYTES {
+            ns.reserve(&format!("k{}", i), MAX_VALUE_BYTES).unwrap();
+        }
+        assert_eq!(ns.used_bytes(), MAX_BYTES_PER_APP);
+        assert!(ns.reserve("extra", 1).is_err());
+        // Same key, smaller value: fits.
+        ns.reserve("k0", 10).unwrap();
+        ns.reserve("extra", 1).unwrap();
+        assert!(ns.remove("extra"));
+        assert!(!ns.remove("extra"));
+
+        let mut ns = Namespace::default();
+        for i in 0..MAX_KEYS_PER_APP {
+            ns.reserve(&format!("k{}", i), 1).unwrap();
+        }
+        assert_eq!(ns.reserve("one-more", 1), Err("app key quota exhausted"));
+        ns.reserve("k3", 2).unwrap();
+    }
+
+    #[test]
+    fn names_and_commitments() {
+        assert!(validate_app_id("notes.example-1.org").is_ok());
+        assert!(validate_app_id("Notes").is_err());
+        assert!(validate_app_id("a_b").is_err());
+        assert!(validate_app_id("").is_err());
+        assert!(validate_key("totp_seed.v2").is_ok());
+        assert!(validate_key("a/b").is_err());
+        assert!(validate_key(&"k".repeat(65)).is_err());
+
+        let w = Uuid::nil();
+        let put = op_commitment(StorageOp::Put, &w, "app", "k", Some(b"v"));
+        assert_ne!(
+            put,
+            op_commitment(StorageOp::Put, &w, "app", "k", Some(b"w"))
+        );
+        assert_ne!(put, op_commitment(StorageOp::Delete, &w, "app", "k", None));
+        // Length prefixes keep ("ap", "pk") apart from ("app", "k").
+        assert_ne!(
+            op_commitment(StorageOp::Get, &w, "app", "k", None),
+            op_commitment(StorageOp::Get, &w, "ap", "pk", None)
+        );
+    }
+}
@@ -0,0 +1,155 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Deployment policy — which command ids a TA instance accepts.
+//!
+//! Installed once at provisioning and replaceable only by a policy with a
+//! higher `sequence` signed by the same key. The signature is an ordinary
+//! `personal_sign` over [`DeploymentPolicy::message`], so any Ethereum wallet
+//! or `cast wallet sign` can produce it and the operator can read what they
+//! are signing.
+
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+
+use crate::Command;
+
+pub const POLICY_FORMAT_VERSION: u8 = 1;
+
+/// Commands a policy cannot switch off: without them the policy could not be
+/// inspected or replaced.
+pub const ALWAYS_ENABLED: [Command; 4] = [
+    Command::GetProtocolVersion,
+    Command::TaStats,
+    Command::GetCapabilities,
+    Command::InstallDeploymentPolicy,
+];
+
+const MAX_DEPLOYMENT_LABEL: usize = 64;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DeploymentPolicy {
+    pub format: u8,
+    /// Operator label, e.g. "rack-3" — shown in the signed text.
+    pub deployment: String,
+    /// Strictly increasing across replacements.
+    pub sequence: u64,
+    /// Command ids, ascending, no duplicates.
+    pub disabled_commands: Vec<u32>,
+}
+
+impl DeploymentPolicy {
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if self.format != POLICY_FORMAT_VERSION {
+            return Err("unsupported deployment policy format");
+        }
+        if self.deployment.is_empty()
+            || self.deployment.len() > MAX_DEPLOYMENT_LABEL
+            || self.deployment.chars().any(|c| c.is_control())
+        {
+            return Err("deployment label must be 1-64 printable characters");
+        }
+        if self.disabled_commands.windows(2).any(|w| w[0] >= w[1]) {
+            return Err("disabled_commands must be ascending without duplicates");
+        }
+        for &id in &self.disabled_commands {
+            let command = Command::from(id);
+            if command == Command::Unknown {
+                return Err("disabled_commands names an unknown command id");
+            }
+            if ALWAYS_ENABLED.contains(&command) {
+                return Err(
+                    "policy cannot disable GetProtocolVersion, TaStats, GetCapabilities or InstallDeploymentPolicy",
+                );
+            }
+        }
+        Ok(())
+    }
+
+    pub fn allows(&self, command: Command) -> bool {
+        !self.disabled_commands.contains(&u32::from(command))
+    }
+
+    /// The text the operator signs.
+    pub fn message(&self) -> String {
+        let disabled = if self.disabled_commands.is_empty() {
+            "none".to_string()
+        } else {
+            self.disabled_commands
+                .iter()
+                .map(|id| id.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        format!(
+            "AirAccount deployment policy v{}\ndeployment: {}\nsequence: {}\ndisabled: {}",
+            self.format, self.deployment, self.sequence, disabled
+        )
+    }
+
+    /// EIP-191 digest of [`Self::message`] — what the signature recovers over.
+    pub fn digest(&self) -> [u8; 32] {
+        let message = self.message();
+        let mut h = Keccak256::new();
+        h.update(format!("\x19Ethereum Signed Message:\n{}", message.len()).as_bytes());
+        h.update(message.as_bytes());
+        h.finalize().into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(disabled: Vec<u32>) -> DeploymentPolicy {
+        DeploymentPolicy {
+            format: POLICY_FORMAT_VERSION,
+            deployment: "rack-3".into(),
+            sequence: 1,
+            disabled_commands: disabled,
+        }
+    }
+
+    #[test]
+    fn validate_rules() {
+        assert!(policy(vec![]).validate().is_ok());
+        // ExportPrivateKey + SignHash
+        let p = policy(vec![5, 7]);
+        assert!(p.validate().is_ok());
+        assert!(!p.allows(Command::SignHash));
+        assert!(p.allows(Command::SignTransaction));
+        assert!(policy(vec![7, 5]).validate().is_err());
+        assert!(policy(vec![5, 5]).validate().is_err());
+        assert!(policy(vec![13]).validate().is_err()); // removed id
+        assert!(policy(vec![u32::from(Command::InstallDeploymentPolicy)])
+            .validate()
+            .is_err());
+        let mut p = policy(vec![]);
+        p.deployment = "a\nsequence: 99".into();
+        assert!(p.validate().is_err());
+    }
+
+    #[test]
+    fn signed_text_is_stable() {
+        assert_eq!(
+            policy(vec![5, 7]).message(),
+            "AirAccount deployment policy v1\ndeployment: rack-3\nsequence: 1\ndisabled: 5,7"
+        );
+        assert!(policy(vec![]).message().ends_with("disabled: none"));
+        assert_ne!(policy(vec![5]).digest(), policy(vec![7]).digest());
+    }
+}
@@ -0,0 +1,268 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! ECIES payloads encrypted to a wallet key, and the decrypt sessions that
+//! let the TA open them.
+//!
+//! The envelope is the default of eciesjs / the `ecies` crate:
+//! `ephemeral pubkey (65, uncompressed) ‖ nonce (16) ‖ tag (16) ‖ ciphertext`.
+//! The AES-256-GCM key is HKDF-SHA256 (no salt, no info) over the ephemeral
+//! key followed by the uncompressed ECDH point. The TA does the ECDH with the
+//! derived key at the session's path; this module does the rest, under the
+//! `ecies` feature.
+//!
+//! The TA is a decryption oracle for whoever holds a session, so a session is
+//! narrow: one wallet, one path, a capped number of payloads and minutes, and
+//! opened by a passkey assertion over exactly those bounds.
+
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+use uuid::Uuid;
+
+use crate::hd_path::parse_eth_path;
+
+pub const EPHEMERAL_KEY_LEN: usize = 65;
+pub const NONCE_LEN: usize = 16;
+pub const TAG_LEN: usize = 16;
+/// Envelope bytes around the ciphertext.
+pub const OVERHEAD: usize = EPHEMERAL_KEY_LEN + NONCE_LEN + TAG_LEN;
+
+/// Largest plaintext the TA returns; the output has to fit the CA's
+/// default buffer with room for the framing (`buffers`, checked at build).
+pub const MAX_PLAINTEXT: usize = 3072;
+pub const MAX_SESSION_DECRYPTIONS: u32 = 64;
+pub const MAX_SESSION_TTL_SECS: u32 = 900;
+/// Sessions open at once across all wallets; the oldest is dropped first.
+pub const MAX_OPEN_SESSIONS: usize = 16;
+
+/// What an owner approves when opening a session.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DecryptScope {
+    /// The key payloads are encrypted to, `m/44'/60'/0'/account/index`.
+    pub hd_path: String,
+    pub max_decryptions: u32,
+    pub ttl_secs: u32,
+}
+
+impl DecryptScope {
+    pub fn validate(&self) -> Result<(), &'static str> {
+        parse_eth_path(&self.hd_path).map_err(|_| "hd_path is not an Ethereum account path")?;
+        if !(1..=MAX_SESSION_DECRYPTIONS).contains(&self.max_decryptions) {
+            return Err("max_decryptions must be 1-64");
+        }
+        if !(1..=MAX_SESSION_TTL_SECS).contains(&self.ttl_secs) {
+            return Err("ttl_secs must be 1-900");
+        }
+        Ok(())
+    }
+}
+
+/// Passkey commitment for opening a session with exactly this scope.
+pub fn session_commitment(wallet_id: &Uuid, scope: &DecryptScope) -> [u8; 32] {
+    let mut h = Keccak256::new();
+    h.update(b"AA-ECIES-SESSION-v1");
+    h.update(wallet_id.as_bytes());
+    h.update((scope.hd_path.len() as u32).to_be_bytes());
+    h.update(scope.hd_path.as_bytes());
+    h.update(scope.max_decryptions.to_be_bytes());
+    h.update(scope.ttl_secs.to_be_bytes());
+    h.finalize().into()
+}
+
+/// A parsed envelope, borrowing the caller's bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Envelope<'a> {
+    pub ephemeral_pubkey: &'a [u8],
+    pub nonce: &'a [u8],
+    pub tag: &'a [u8],
+    pub ciphertext: &'a [u8],
+}
+
+impl<'a> Envelope<'a> {
+    pub fn parse(bytes: &'a [u8]) -> Result<Self, &'static str> {
+        if bytes.len() < OVERHEAD {
+            return Err("ECIES payload is shorter than its header");
+        }
+        if bytes.len() - OVERHEAD > MAX_PLAINTEXT {
+            return Err("ECIES payload exceeds 3072 bytes of plaintext");
+        }
+        if bytes[0] != 0x04 {
+            return Err("ECIES ephemeral key must be uncompressed");
+        }
+        let (ephemeral_pubkey, rest) = bytes.split_at(EPHEMERAL_KEY_LEN);
+        let (nonce, rest) = rest.split_at(NONCE_LEN);
+        let (tag, ciphertext) = rest.split_at(TAG_LEN);
+        Ok(Envelope {
+            ephemeral_pubkey,
+            nonce,
+            tag,
+            ciphertext,
+        })
+    }
+
+    /// Derive the key from the uncompressed ECDH point and decrypt. A wrong
+    /// key or any altered byte fails the tag.
+    #[cfg(feature = "ecies")]
+    pub fn open(&self, shared_point: &[u8; 65]) -> Result<Vec<u8>, &'static str> {
+        use aes_gcm::aead::AeadInPlace;
+
+        let cipher = cipher(self.ephemeral_pubkey, shared_point)?;
+        let mut plaintext = self.ciphertext.to_vec();
+        cipher
+            .decrypt_in_place_detached(self.nonce.into(), &[], &mut plaintext, self.tag.into())
+            .map_err(|_| "ECIES payload failed authentication")?;
+        Ok(plaintext)
+    }
+
+    /// The other direction, for a sender that has done the ECDH with a fresh
+    /// ephemeral key: the envelope bytes `open` takes.
+    #[cfg(feature = "ecies")]
+    pub fn seal(
+        ephemeral_pubkey: &[u8; EPHEMERAL_KEY_LEN],
+        shared_point: &[u8; 65],
+        nonce: &[u8; NONCE_LEN],
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, &'static str> {
+        use aes_gcm::aead::AeadInPlace;
+
+        if plaintext.len() > MAX_PLAINTEXT {
+            return Err("ECIES payload exceeds 3072 bytes of plaintext");
+        }
+        let cipher = cipher(ephemeral_pubkey, shared_point)?;
+        let mut ciphertext = plaintext.to_vec();
+        let tag = cipher
+            .encrypt_in_place_detached(nonce.into(), &[], &mut ciphertext)
+            .map_err(|_| "ECIES encryption failed")?;
+        let mut out = Vec::with_capacity(OVERHEAD + ciphertext.len());
+        out.extend_from_slice(ephemeral_pubkey);
+        out.extend_from_slice(nonce);
+        out.extend_from_slice(&tag);
+        out.extend_from_slice(&ciphertext);
+        Ok(out)
+    }
+}
+
+/// AES-256-GCM (16-byte nonce) keyed by HKDF-SHA256 over the ephemeral key
+/// followed by the ECDH point.
+#[cfg(feature = "ecies")]
+fn cipher(
+    ephemeral_pubkey: &[u8],
+    shared_point: &[u8; 65],
+) -> Result<aes_gcm::AesGcm<aes_gcm::aes::Aes256, aes_gcm::aead::consts::U16>, &'static str> {
+    use aes_gcm::KeyInit;
+
+    let mut ikm = [0u8; 2 * EPHEMERAL_KEY_LEN];
+    ikm[..EPHEMERAL_KEY_LEN].copy_from_slice(ephemeral_pubkey);
+    ikm[EPHEMERAL_KEY_LEN..].copy_from_slice(shared_point);
+    let mut key = [0u8; 32];
+    hkdf::Hkdf::<sha2::Sha256>::new(None, &ikm)
+        .expand(&[], &mut key)
+        .map_err(|_| "HKDF expand failed")?;
+    ikm.fill(0);
+    let cipher = aes_gcm::AesGcm::new(&key.into());
+    key.fill(0);
+    Ok(cipher)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unhex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    // Sealed to m/44'/60'/0'/0/0 of "abandon … about" with eciesjs' layout.
+    const ENVELOPE: &str = "049c7a3a75b43dfa0c28c911a4ff1ff157a176116c4e4d00ca15fead57b4806c038cf0af9d9cfaa6fb03b8fa18f10b3281d8e01eb346447874072321ec98fd35ef000102030405060708090a0b0c0d0e0f3dabdf9a8b41654a7a0c5e005e82e0295b9ca60fda379d660df26728fb729a9823";
+
+    #[test]
+    fn envelope_layout_and_limits() {
+        let bytes = unhex(ENVELOPE);
+        let envelope = Envelope::parse(&bytes).unwrap();
+        assert_eq!(envelope.nonce, &unhex("000102030405060708090a0b0c0d0e0f")[..]);
+        assert_eq!(envelope.ciphertext.len(), "hello from a dapp".len());
+
+        assert!(Envelope::parse(&bytes[..OVERHEAD - 1]).is_err());
+        assert!(Envelope::parse(&vec![0x04; OVERHEAD + MAX_PLAINTEXT]).is_ok());
+        assert!(Envelope::parse(&vec![0x04; OVERHEAD + MAX_PLAINTEXT + 1]).is_err());
+        let mut compressed = bytes.clone();
+        compressed[0] = 0x02;
+        assert!(Envelope::parse(&compressed).is_err());
+    }
+
+    #[cfg(feature = "ecies")]
+    #[test]
+    fn opens_with_the_ecdh_point() {
+        use std::convert::TryInto;
+
+        let shared: [u8; 65] = unhex("04b8bc52253950dee10f389dd421a2a24a6268a1b842d8fd215cfac49b02c7ac10aa35b28e0aea8e4106b967ef2166f2336026a6cc07d28ea3d393af5a6a7f554f").try_into().unwrap();
+        let bytes = unhex(ENVELOPE);
+        let envelope = Envelope::parse(&bytes).unwrap();
+        assert_eq!(envelope.open(&shared).unwrap(), b"hello from a dapp");
+
+        let mut tampered = bytes.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(Envelope::parse(&tampered).unwrap().open(&shared).is_err());
+        let mut wrong = shared;
+        wrong[64] ^= 1;
+        assert!(envelope.open(&wrong).is_err());
+
+        let ephemeral: [u8; 65] = envelope.ephemeral_pubkey.try_into().unwrap();
+        let nonce: [u8; 16] = envelope.nonce.try_into().unwrap();
+        let resealed = Envelope::seal(&ephemeral, &shared, &nonce, b"hello from a dapp").unwrap();
+        assert_eq!(resealed, bytes);
+    }
+
+    #[test]
+    fn scope_bounds_and_commitment() {
+        let scope = DecryptScope {
+            hd_path: "m/44'/60'/0'/0/0".into(),
+            max_decryptions: 10,
+            ttl_secs: 300,
+        };
+        assert!(scope.validate().is_ok());
+        for bad in [
+            DecryptScope {
+                hd_path: "m/44'/60'/1'/0/0".into(),
+                ..scope.clone()
+            },
+            DecryptScope {
+                max_decryptions: 0,
+                ..scope.clone()
+            },
+            DecryptScope {
+                ttl_secs: MAX_SESSION_TTL_SECS + 1,
+                ..scope.clone()
+            },
+        ] {
+            assert!(bad.validate().is_err());
+        }
+
+        let w = Uuid::from_bytes([7; 16]);
+        let more = DecryptScope {
+            max_decryptions: 11,
+            ..scope.clone()
+        };
+        assert_ne!(
+            session_commitment(&w, &scope),
+            session_commitment(&w, &more)
+        );
+    }
+}
@@ -0,0 +1,332 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! EIP-1559 (type 2, dynamic fee) transactions.
+//!
+//! The TA encodes the transaction itself: it hashes
+//! `0x02 ‖ rlp([chain_id, nonce, max_priority_fee_per_gas, max_fee_per_gas,
+//! gas, to, value, data, access_list])`, signs that, and answers the
+//! broadcast-ready `0x02 ‖ rlp([..., y_parity, r, s])`. The CA never hands it
+//! a pre-computed hash for these. Legacy EIP-155 transactions stay on
+//! `SignTransaction`; their RLP is in `offline`.
+
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+use std::convert::TryInto;
+
+use crate::offline::{rlp_bytes, rlp_item, rlp_list, rlp_to_uint, rlp_uint, word32};
+use crate::EthTransaction;
+
+/// EIP-2718 type byte of a dynamic-fee transaction.
+pub const EIP1559_TX_TYPE: u8 = 0x02;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AccessListItem {
+    pub address: [u8; 20],
+    pub storage_keys: Vec<[u8; 32]>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Eip1559Transaction {
+    pub chain_id: u64,
+    pub nonce: u128,
+    pub max_priority_fee_per_gas: u128,
+    pub max_fee_per_gas: u128,
+    pub gas: u128,
+    pub to: Option<[u8; 20]>,
+    pub value: u128,
+    pub data: Vec<u8>,
+    #[serde(default)]
+    pub access_list: Vec<AccessListItem>,
+}
+
+impl Eip1559Transaction {
+    /// Refuses what no node would include: a tip above the fee cap.
+    pub fn check(&self) -> Result<(), &'static str> {
+        if self.max_priority_fee_per_gas > self.max_fee_per_gas {
+            return Err("maxPriorityFeePerGas exceeds maxFeePerGas");
+        }
+        Ok(())
+    }
+
+    /// The legacy view of this transaction, `gas_price` being the fee cap
+    /// (the most it can cost). What the calldata summary, allowance guard,
+    /// risk check and secure display see.
+    pub fn as_call(&self) -> EthTransaction {
+        EthTransaction {
+            chain_id: self.chain_id,
+            nonce: self.nonce,
+            to: self.to,
+            value: self.value,
+            gas_price: self.max_fee_per_gas,
+            gas: self.gas,
+            data: self.data.clone(),
+        }
+    }
+
+    fn fields(&self) -> Vec<u8> {
+        let mut items = Vec::new();
+        rlp_uint(&mut items, self.chain_id as u128);
+        rlp_uint(&mut items, self.nonce);
+        rlp_uint(&mut items, self.max_priority_fee_per_gas);
+        rlp_uint(&mut items, self.max_fee_per_gas);
+        rlp_uint(&mut items, self.gas);
+        rlp_bytes(&mut items, self.to.as_ref().map(|t| &t[..]).unwrap_or(&[]));
+        rlp_uint(&mut items, self.value);
+        rlp_bytes(&mut items, &self.data);
+        let mut list = Vec::new();
+        for entry in &self.access_list {
+            let mut keys = Vec::new();
+            for key in &entry.storage_keys {
+                rlp_bytes(&mut keys, key);
+            }
+            let mut item = Vec::new();
+            rlp_bytes(&mut item, &entry.address);
+            item.extend_from_slice(&rlp_list(&keys));
+            list.extend_from_slice(&rlp_list(&item));
+        }
+        items.extend_from_slice(&rlp_list(&list));
+        items
+    }
+
+    /// `0x02 ‖ rlp(fields)`, the signing payload.
+    pub fn unsigned_rlp(&self) -> Vec<u8> {
+        typed(&rlp_list(&self.fields()))
+    }
+
+    pub fn signing_hash(&self) -> [u8; 32] {
+        Keccak256::digest(self.unsigned_rlp()).into()
+    }
+}
+
+fn typed(payload: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(payload.len() + 1);
+    out.push(EIP1559_TX_TYPE);
+    out.extend_from_slice(payload);
+    out
+}
+
+/// A signed dynamic-fee transaction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignedEip1559Tx {
+    pub transaction: Eip1559Transaction,
+    /// 0 or 1.
+    pub y_parity: u8,
+    pub r: [u8; 32],
+    pub s: [u8; 32],
+}
+
+/// Encode a signed tx as SignEip1559Transaction returns it; the inverse of
+/// [`decode_signed`].
+pub fn signed_rlp(signed: &SignedEip1559Tx) -> Vec<u8> {
+    let mut items = signed.transaction.fields();
+    rlp_uint(&mut items, signed.y_parity as u128);
+    for word in [&signed.r, &signed.s] {
+        let skip = word.iter().take_while(|b| **b == 0).count();
+        rlp_bytes(&mut items, &word[skip..]);
+    }
+    typed(&rlp_list(&items))
+}
+
+fn list_items(body: &[u8]) -> Result<Vec<(bool, &[u8])>, &'static str> {
+    let mut rest = body;
+    let mut out = Vec::new();
+    while !rest.is_empty() {
+        let (is_list, item, next) = rlp_item(rest)?;
+        out.push((is_list, item));
+        rest = next;
+    }
+    Ok(out)
+}
+
+fn byte_string(field: (bool, &[u8])) -> Result<&[u8], &'static str> {
+    match field {
+        (false, b) => Ok(b),
+        _ => Err("rlp: unexpected list"),
+    }
+}
+
+fn decode_access_list(body: &[u8]) -> Result<Vec<AccessListItem>, &'static str> {
+    let mut out = Vec::new();
+    for (is_list, entry) in list_items(body)? {
+        let fields = list_items(entry)?;
+        if !is_list || fields.len() != 2 || !fields[1].0 {
+            return Err("bad access list entry");
+        }
+        let address = byte_string(fields[0])?
+            .try_into()
+            .map_err(|_| "bad access list address")?;
+        let mut storage_keys = Vec::new();
+        for key in list_items(fields[1].1)? {
+            let key = byte_string(key)?;
+            storage_keys.push(key.try_into().map_err(|_| "bad access list storage key")?);
+        }
+        out.push(AccessListItem {
+            address,
+            storage_keys,
+        });
+    }
+    Ok(out)
+}
+
+/// Decode a signed dynamic-fee transaction.
+pub fn decode_signed(raw: &[u8]) -> Result<SignedEip1559Tx, &'static str> {
+    let body = match raw.split_first() {
+        Some((&EIP1559_TX_TYPE, body)) => body,
+        _ => return Err("not an EIP-1559 transaction"),
+    };
+    let (list, body, rest) = rlp_item(body)?;
+    if !list || !rest.is_empty() {
+        return Err("rlp: not a single list");
+    }
+    let f = list_items(body)?;
+    if f.len() != 12 || !f[8].0 {
+        return Err("not a signed EIP-1559 transaction");
+    }
+    let mut s = Vec::with_capacity(12);
+    for (i, field) in f.iter().enumerate() {
+        if i != 8 {
+            s.push(byte_string(*field)?);
+        }
+    }
+    let to = match s[5].len() {
+        0 => None,
+        20 => s[5].try_into().ok(),
+        _ => return Err("bad 'to' length"),
+    };
+    let y_parity = rlp_to_uint(s[8], 1)?;
+    if y_parity > 1 {
+        return Err("bad y parity");
+    }
+    Ok(SignedEip1559Tx {
+        transaction: Eip1559Transaction {
+            chain_id: rlp_to_uint(s[0], 8)? as u64,
+            nonce: rlp_to_uint(s[1], 16)?,
+            max_priority_fee_per_gas: rlp_to_uint(s[2], 16)?,
+            max_fee_per_gas: rlp_to_uint(s[3], 16)?,
+            gas: rlp_to_uint(s[4], 16)?,
+            to,
+            value: rlp_to_uint(s[6], 16)?,
+            data: s[7].to_vec(),
+            access_list: decode_access_list(f[8].1)?,
+        },
+        y_parity: y_parity as u8,
+        r: word32(s[9])?,
+        s: word32(s[10])?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    fn transfer() -> Eip1559Transaction {
+        let mut key = [0u8; 32];
+        key[31] = 1;
+        Eip1559Transaction {
+            chain_id: 1,
+            nonce: 9,
+            max_priority_fee_per_gas: 1_500_000_000,
+            max_fee_per_gas: 40_000_000_000,
+            gas: 60_000,
+            to: Some([0x35; 20]),
+            value: 1_000_000_000_000_000_000,
+            data: vec![0xa9, 0x05, 0x9c, 0xbb],
+            access_list: vec![AccessListItem {
+                address: [0x11; 20],
+                storage_keys: vec![key],
+            }],
+        }
+    }
+
+    // Vectors produced with alloy-consensus 1.8 (`TxEip1559`).
+    const UNSIGNED: &str = "02f86d01098459682f008509502f900082ea60943535353535353535353535353535353535353535880de0b6b3a764000084a9059cbbf838f7941111111111111111111111111111111111111111e1a00000000000000000000000000000000000000000000000000000000000000001";
+    const SIGNED: &str = "02f89a01098459682f008509502f900082ea60943535353535353535353535353535353535353535880de0b6b3a764000084a9059cbbf838f7941111111111111111111111111111111111111111e1a00000000000000000000000000000000000000000000000000000000000000001019b1122000000000000000000000000000000000000000000000000008f033440000000000000000000000000";
+
+    fn signed() -> SignedEip1559Tx {
+        let mut r = [0u8; 32];
+        r[5..7].copy_from_slice(&[0x11, 0x22]);
+        let mut s = [0u8; 32];
+        s[17..20].copy_from_slice(&[0x03, 0x34, 0x40]);
+        SignedEip1559Tx {
+            transaction: transfer(),
+            y_parity: 1,
+            r,
+            s,
+        }
+    }
+
+    #[test]
+    fn alloy_vectors() {
+        let tx = transfer();
+        assert_eq!(tx.unsigned_rlp(), hex(UNSIGNED));
+        assert_eq!(
+            tx.signing_hash().to_vec(),
+            hex("e44d5efc83d7effd4950273d8b3e9555e938f3d3340f79286f48745cff825caa")
+        );
+        assert_eq!(signed_rlp(&signed()), hex(SIGNED));
+        assert_eq!(decode_signed(&hex(SIGNED)).unwrap(), signed());
+
+        let create = Eip1559Transaction {
+            chain_id: 11_155_111,
+            nonce: 0,
+            max_priority_fee_per_gas: 1,
+            max_fee_per_gas: 2,
+            gas: 21_000,
+            to: None,
+            value: 0,
+            data: vec![],
+            access_list: vec![],
+        };
+        assert_eq!(
+            create.unsigned_rlp(),
+            hex("02ce83aa36a7800102825208808080c0")
+        );
+        assert_eq!(
+            create.signing_hash().to_vec(),
+            hex("740bc7cb8ef5e8769c3e6a6c73cb286cc2e4ffb952bef85ba99e3c5bfa4c15f5")
+        );
+    }
+
+    #[test]
+    fn malformed_signed_tx_is_rejected() {
+        let raw = hex(SIGNED);
+        assert!(decode_signed(&raw[..raw.len() - 1]).is_err());
+        assert!(decode_signed(&raw[1..]).is_err());
+        let mut trailing = raw.clone();
+        trailing.push(0);
+        assert!(decode_signed(&trailing).is_err());
+        // the unsigned form is not a signed tx
+        assert!(decode_signed(&hex(UNSIGNED)).is_err());
+    }
+
+    #[test]
+    fn tip_above_fee_cap_is_refused() {
+        let mut tx = transfer();
+        assert!(tx.check().is_ok());
+        tx.max_priority_fee_per_gas = tx.max_fee_per_gas + 1;
+        assert!(tx.check().is_err());
+        assert_eq!(tx.as_call().gas_price, tx.max_fee_per_gas);
+    }
+}
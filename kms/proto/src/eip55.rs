@@ -0,0 +1,98 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! EIP-55 mixed-case checksum addresses.
+
+use sha3::{Digest, Keccak256};
+
+/// `0x`-prefixed EIP-55 checksummed form of `address`.
+pub fn to_checksum_address(address: &[u8; 20]) -> String {
+    let lower: String = address.iter().map(|b| format!("{:02x}", b)).collect();
+    let hash = Keccak256::digest(lower.as_bytes());
+    let mut out = String::with_capacity(42);
+    out.push_str("0x");
+    for (i, c) in lower.chars().enumerate() {
+        let nibble = (hash[i / 2] >> (if i % 2 == 0 { 4 } else { 0 })) & 0x0f;
+        if c.is_ascii_alphabetic() && nibble >= 8 {
+            out.push(c.to_ascii_uppercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Parse an address (`0x` optional). All-lowercase and all-uppercase input
+/// is accepted as unchecksummed; mixed case must be a valid EIP-55 checksum.
+pub fn parse_address(s: &str) -> Result<[u8; 20], &'static str> {
+    let hex = s.strip_prefix("0x").unwrap_or(s);
+    if hex.len() != 40 {
+        return Err("address must be 40 hex characters");
+    }
+    let mut address = [0u8; 20];
+    for (i, pair) in hex.as_bytes().chunks(2).enumerate() {
+        let hi = hex_value(pair[0]).ok_or("address is not hex")?;
+        let lo = hex_value(pair[1]).ok_or("address is not hex")?;
+        address[i] = (hi << 4) | lo;
+    }
+    let has_lower = hex.bytes().any(|b| b.is_ascii_lowercase());
+    let has_upper = hex.bytes().any(|b| b.is_ascii_uppercase());
+    if has_lower && has_upper && to_checksum_address(&address)[2..] != *hex {
+        return Err("address has an invalid EIP-55 checksum");
+    }
+    Ok(address)
+}
+
+pub(crate) fn hex_value(c: u8) -> Option<u8> {
+    match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'a'..=b'f' => Some(c - b'a' + 10),
+        b'A'..=b'F' => Some(c - b'A' + 10),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Vectors from the EIP-55 specification.
+    const CHECKSUMMED: [&str; 4] = [
+        "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+        "0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359",
+        "0xdbF03B407c01E7cD3CBea99509d93f8DDDC8C6FB",
+        "0xD1220A0cf47c7B9Be7A2E6BA89F429762e7b9aDb",
+    ];
+
+    #[test]
+    fn checksum_vectors() {
+        for s in CHECKSUMMED {
+            let address = parse_address(s).unwrap();
+            assert_eq!(to_checksum_address(&address), s);
+            assert_eq!(parse_address(&s.to_lowercase()), Ok(address));
+        }
+    }
+
+    #[test]
+    fn rejects_bad_checksum_and_shape() {
+        // one letter's case flipped
+        assert!(parse_address("0x5AAeb6053F3E94C9b9A09f33669435E7Ef1BeAed").is_err());
+        assert!(parse_address("5aaeb6053f3e94c9b9a09f33669435e7ef1beaed").is_ok());
+        assert!(parse_address("0x5aaeb6053f3e94c9b9a09f33669435e7ef1bea").is_err());
+        assert!(parse_address("0xzaaeb6053f3e94c9b9a09f33669435e7ef1beaed").is_err());
+    }
+}
@@ -0,0 +1,139 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Deletion certificates — proof that a wallet's key material was erased.
+//!
+//! `RemoveWallet` overwrites the stored wallet object with zeros, deletes it,
+//! bumps the anti-rollback counter and then has the attestation PTA sign
+//! `SHA256(nonce | ta_measurement)` with `nonce` = [`DeletionCertificate::digest`].
+//! A verifier holding the device's attestation key (the same reference value
+//! used for `GET /attestation`) can check an archived certificate long after
+//! the CA's database has moved on. The text form is what gets hashed so a
+//! compliance reviewer can read exactly what the device vouched for.
+
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+use uuid::Uuid;
+
+use crate::GetAttestationOutput;
+
+pub const DELETION_CERT_VERSION: u8 = 1;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DeletionCertificate {
+    pub version: u8,
+    pub wallet_id: Uuid,
+    /// REE wall-clock seconds when the TA erased the wallet. REE time is only
+    /// as trustworthy as the host clock; it is bound into the signature but
+    /// not independently attested.
+    pub deleted_at: u64,
+    /// Anti-rollback counter after the deletion. None when the device runs
+    /// without an RPMB counter (`ree-fs-only`).
+    pub rollback_counter: Option<u64>,
+}
+
+impl DeletionCertificate {
+    /// Canonical text of the certificate.
+    pub fn message(&self) -> String {
+        let counter = match self.rollback_counter {
+            Some(c) => c.to_string(),
+            None => "none".to_string(),
+        };
+        format!(
+            "AirAccount deletion certificate v{}\nwallet: {}\ndeleted_at: {}\nrollback_counter: {}",
+            self.version, self.wallet_id, self.deleted_at, counter
+        )
+    }
+
+    /// keccak256 of [`Self::message`] — the attestation nonce.
+    pub fn digest(&self) -> [u8; 32] {
+        Keccak256::digest(self.message().as_bytes()).into()
+    }
+}
+
+/// A certificate and the device signature over it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DeletionProof {
+    pub certificate: DeletionCertificate,
+    /// None when the attestation PTA is unavailable; the erasure itself still
+    /// happened, but the certificate is then only the TA's word via the CA.
+    pub attestation: Option<GetAttestationOutput>,
+}
+
+impl DeletionProof {
+    /// The attestation, if any, must be over this certificate.
+    pub fn check_binding(&self) -> Result<(), &'static str> {
+        match &self.attestation {
+            Some(a) if a.nonce[..] != self.certificate.digest()[..] => {
+                Err("attestation nonce is not the certificate digest")
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn certificate() -> DeletionCertificate {
+        DeletionCertificate {
+            version: DELETION_CERT_VERSION,
+            wallet_id: Uuid::from_bytes([0x22; 16]),
+            deleted_at: 1_700_000_000,
+            rollback_counter: Some(42),
+        }
+    }
+
+    #[test]
+    fn certificate_text_is_stable() {
+        assert_eq!(
+            certificate().message(),
+            "AirAccount deletion certificate v1\n\
+             wallet: 22222222-2222-2222-2222-222222222222\n\
+             deleted_at: 1700000000\n\
+             rollback_counter: 42"
+        );
+        let mut degraded = certificate();
+        degraded.rollback_counter = None;
+        assert!(degraded.message().ends_with("rollback_counter: none"));
+        assert_ne!(degraded.digest(), certificate().digest());
+    }
+
+    #[test]
+    fn attestation_must_cover_the_certificate() {
+        let attestation = GetAttestationOutput {
+            nonce: certificate().digest().to_vec(),
+            ta_uuid: vec![0; 16],
+            ta_measurement: vec![0; 32],
+            signature: vec![1; 256],
+            attest_pubkey_exp: vec![1, 0, 1],
+            attest_pubkey_mod: vec![0xc5; 256],
+            sig_alg: 0x7041_4930,
+            ree_time_secs: 1_700_000_000,
+        };
+        let mut proof = DeletionProof {
+            certificate: certificate(),
+            attestation: Some(attestation),
+        };
+        assert!(proof.check_binding().is_ok());
+        proof.certificate.deleted_at += 1;
+        assert!(proof.check_binding().is_err());
+        proof.attestation = None;
+        assert!(proof.check_binding().is_ok());
+    }
+}
@@ -0,0 +1,150 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Events the CA publishes to other systems, in one versioned envelope.
+//!
+//! Every outbound event — the chain-event webhook, the event list API and
+//! the account audit export — is an [`EventEnvelope`] around a payload type
+//! that names its own `type` and `version`. Within a version fields are only
+//! ever added, so consumers must ignore fields they do not know; renaming,
+//! removing or retyping a field is a new version. `tests/event_contract.rs`
+//! pins the JSON of every current version.
+
+use serde::{Deserialize, Serialize};
+
+/// A payload with a stable wire name and schema version.
+pub trait EventPayload: Serialize {
+    const TYPE: &'static str;
+    const VERSION: u32;
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct EventEnvelope<P> {
+    #[serde(rename = "type")]
+    pub event_type: String,
+    pub version: u32,
+    /// Unix seconds at which the CA observed or caused the event.
+    pub occurred_at: i64,
+    /// The `x-correlation-id` of the request that caused the event, or an id
+    /// derived from the event itself when no request did (chain events).
+    pub correlation_id: String,
+    pub payload: P,
+}
+
+impl<P: EventPayload> EventEnvelope<P> {
+    pub fn new(payload: P, occurred_at: i64, correlation_id: String) -> Self {
+        Self {
+            event_type: P::TYPE.to_string(),
+            version: P::VERSION,
+            occurred_at,
+            correlation_id,
+            payload,
+        }
+    }
+}
+
+/// A deposit or contract event recorded by the chain watcher.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ChainEventPayload {
+    pub id: i64,
+    pub key_id: String,
+    pub address: String,
+    pub chain_id: u64,
+    /// erc20-transfer-in | native-transfer-in | contract-event
+    pub kind: String,
+    pub token: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    /// Decimal base units.
+    pub value: Option<String>,
+    pub topic0: Option<String>,
+    pub tx_hash: String,
+    pub block_number: u64,
+    /// None for a native transfer, which has no log.
+    pub log_index: Option<i64>,
+    /// The block was reorged out after the event was first reported.
+    pub removed: bool,
+    /// The wallet's notification preferences ask the mail relay to email
+    /// this one to the wallet's bound address too. Webhook deliveries only;
+    /// omitted when false.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub email: bool,
+}
+
+impl EventPayload for ChainEventPayload {
+    const TYPE: &'static str = "chain.event";
+    const VERSION: u32 = 1;
+}
+
+/// An account lifecycle audit entry (contact binding, credential changes).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEventPayload {
+    pub account: String,
+    pub event: String,
+    /// Display hint only; never a secret.
+    pub detail: Option<String>,
+}
+
+impl EventPayload for AuditEventPayload {
+    const TYPE: &'static str = "account.audit";
+    const VERSION: u32 = 1;
+}
+
+/// A relayer gas tank crossed its balance floor, either way.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct GasTankAlertPayload {
+    pub address: String,
+    pub chain_id: u64,
+    pub label: String,
+    /// low | recovered
+    pub state: String,
+    /// Decimal wei.
+    pub balance_wei: String,
+    pub min_balance_wei: String,
+    /// The top-up proposed for the treasury's approvers, if one was.
+    pub top_up_id: Option<i64>,
+}
+
+impl EventPayload for GasTankAlertPayload {
+    const TYPE: &'static str = "gas_tank.alert";
+    const VERSION: u32 = 1;
+}
+
+/// The scheduled device health check quarantined this device, or an
+/// operator released it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceHealthPayload {
+    /// `KMS_DEVICE_ID`, if the device was provisioned with one.
+    pub device_id: Option<String>,
+    /// quarantined | released
+    pub state: String,
+    pub reason: String,
+    /// Names of the self-test checks that failed; empty when none ran.
+    pub failed_checks: Vec<String>,
+    /// Hex TA measurement from the attestation evidence, if any came back.
+    pub ta_measurement: Option<String>,
+}
+
+impl EventPayload for DeviceHealthPayload {
+    const TYPE: &'static str = "device.health";
+    const VERSION: u32 = 1;
+}
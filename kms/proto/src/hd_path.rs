@@ -0,0 +1,617 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! BIP-44 derivation paths the TA accepts.
+//!
+//! Only the standard Ethereum layout `m/44'/60'/0'/{account}/{address}` is
+//! derivable, with non-hardened account and address indices. The TA's
+//! `bip32_secp::parse_eth_path`, the CA's request validation and the browser
+//! SDK (through the wasm build) all call [`parse_eth_path`], so a path the
+//! SDK accepts is one the TA will derive.
+//! Other chains (`crate::chain`) keep the layout under their own coin type,
+//! `m/44'/{coin}'/0'/{account}/{address}`, read by [`parse_coin_path`].
+//!
+//! A wallet's path policy narrows that further: a list of [`PathPattern`]s
+//! such as `m/44'/60'/0'/0/*`, checked by the TA before every derivation.
+//!
+//! Arbitrary BIP32 paths (the TA's own hardened subtrees, the conformance
+//! vectors) go through [`parse_path`] and the same [`parse_index`]. A level
+//! is decimal without sign or leading zeros, below 2^31, and `'`, `h` or `H`
+//! marks it hardened; the `Display` forms always write `'`, so formatting a
+//! parsed path and parsing it again gives the same levels.
+//!
+//! The TA refuses a malformed path as [`InvalidPath`], which travels as
+//! error text like `wire::UnsupportedVersion`, so the CA answers it as a bad
+//! request rather than a TEE fault.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Set on a child index for hardened derivation.
+pub const HARDENED_BIT: u32 = 0x8000_0000;
+
+/// Longest path string the CA forwards to the TA.
+pub const MAX_PATH_LEN: usize = 64;
+
+/// Most patterns one wallet's path policy may hold.
+pub const MAX_PATH_PATTERNS: usize = 16;
+
+/// A BIP32 path: one child index per level, [`HARDENED_BIT`] set on the
+/// hardened ones. `m` alone is the master key.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DerivationPath(Vec<u32>);
+
+impl DerivationPath {
+    pub fn levels(&self) -> &[u32] {
+        &self.0
+    }
+
+    /// The levels without [`HARDENED_BIT`] when every one is hardened, the
+    /// form `bip32_secp::derive_hardened_key` takes.
+    pub fn hardened_levels(&self) -> Option<Vec<u32>> {
+        self.0
+            .iter()
+            .map(|&i| (i & HARDENED_BIT != 0).then_some(i & !HARDENED_BIT))
+            .collect()
+    }
+}
+
+impl fmt::Display for DerivationPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "m")?;
+        for &index in &self.0 {
+            write!(f, "/{}", Index(index))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for DerivationPath {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        parse_path(s)
+    }
+}
+
+/// A child index printed in canonical form: `n` or `n'`.
+struct Index(u32);
+
+impl fmt::Display for Index {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0 & HARDENED_BIT != 0 {
+            write!(f, "{}'", self.0 & !HARDENED_BIT)
+        } else {
+            write!(f, "{}", self.0)
+        }
+    }
+}
+
+/// A path the TA will not derive, with the parser's reason. See
+/// [`InvalidPath::find`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidPath(pub String);
+
+const INVALID_PATH_TAG: &str = "InvalidPath: ";
+
+impl fmt::Display for InvalidPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", INVALID_PATH_TAG, self.0)
+    }
+}
+
+impl std::error::Error for InvalidPath {}
+
+impl From<String> for InvalidPath {
+    fn from(reason: String) -> Self {
+        InvalidPath(reason)
+    }
+}
+
+impl InvalidPath {
+    /// Recover the error from a TA error message that contains it: the
+    /// reason runs to the end of the message.
+    pub fn find(message: &str) -> Option<Self> {
+        let reason = &message[message.find(INVALID_PATH_TAG)? + INVALID_PATH_TAG.len()..];
+        (!reason.is_empty()).then(|| InvalidPath(reason.to_string()))
+    }
+}
+
+/// Parse any `m/...` path of at most [`MAX_PATH_LEN`] characters.
+pub fn parse_path(path: &str) -> Result<DerivationPath, String> {
+    let path = path.trim();
+    check_len(path)?;
+    let mut parts = path.split('/');
+    if parts.next() != Some("m") {
+        return Err(format!("Path must start with 'm', got: {}", path));
+    }
+    parts
+        .map(parse_index)
+        .collect::<Result<Vec<u32>, String>>()
+        .map(DerivationPath)
+}
+
+/// `m/44'/60'/0'/account/address` as the TA derives it.
+pub fn eth_path(account: u32, address: u32) -> String {
+    coin_path(crate::chain::EVM_COIN_TYPE, account, address)
+}
+
+/// `m/44'/coin_type'/0'/account/address`, the layout of every chain in
+/// `crate::chain`.
+pub fn coin_path(coin_type: u32, account: u32, address: u32) -> String {
+    format!(
+        "m/44'/{}'/0'/{}/{}",
+        coin_type,
+        Index(account),
+        Index(address)
+    )
+}
+
+/// The canonical spelling of an Ethereum path: `h`/`H` become `'`.
+pub fn canonical_eth_path(path: &str) -> Result<String, String> {
+    parse_eth_path(path).map(|(account, address)| eth_path(account, address))
+}
+
+fn check_len(path: &str) -> Result<(), String> {
+    if path.len() > MAX_PATH_LEN {
+        return Err(format!(
+            "Derivation path too long: {} chars (max {})",
+            path.len(),
+            MAX_PATH_LEN
+        ));
+    }
+    Ok(())
+}
+
+/// Parse `m/44'/60'/0'/account/address` into (account, address).
+pub fn parse_eth_path(path: &str) -> Result<(u32, u32), String> {
+    parse_coin_path(path, crate::chain::EVM_COIN_TYPE)
+}
+
+/// Parse `m/44'/coin_type'/0'/account/address` into (account, address).
+pub fn parse_coin_path(path: &str, coin_type: u32) -> Result<(u32, u32), String> {
+    let (account, address) = split_coin_path(path, coin_type)?;
+    let account = parse_index(account)?;
+    let address = parse_index(address)?;
+    if account >= HARDENED_BIT || address >= HARDENED_BIT {
+        return Err(format!(
+            "Account and address indices must be non-hardened, got: {}",
+            path.trim()
+        ));
+    }
+    Ok((account, address))
+}
+
+/// Check the fixed `m/44'/coin_type'/0'` prefix; return the last two
+/// components.
+fn split_coin_path(path: &str, coin_type: u32) -> Result<(&str, &str), String> {
+    let path = path.trim();
+    check_len(path)?;
+    let parts: Vec<&str> = path.split('/').collect();
+
+    // Expect: m / 44' / coin' / 0' / account / address
+    if parts.len() != 6 {
+        return Err(format!(
+            "Expected path m/44'/{}'/0'/account/address, got: {}",
+            coin_type, path
+        ));
+    }
+    if parts[0] != "m" {
+        return Err(format!("Path must start with 'm', got: {}", path));
+    }
+
+    let purpose = parse_index(parts[1])?;
+    let coin = parse_index(parts[2])?;
+    let root_account = parse_index(parts[3])?;
+    if purpose != (44 | HARDENED_BIT)
+        || coin != (coin_type | HARDENED_BIT)
+        || root_account != HARDENED_BIT
+    {
+        return Err(format!(
+            "Only m/44'/{}'/0'/... paths supported, got: {}",
+            coin_type, path
+        ));
+    }
+
+    Ok((parts[4], parts[5]))
+}
+
+/// One path component; `'`, `h` or `H` marks it hardened. Either way the
+/// number must be below 2^31: `2147483648` is not another spelling of `0'`.
+pub fn parse_index(s: &str) -> Result<u32, String> {
+    let (digits, hardened) = match s.strip_suffix(|c: char| matches!(c, '\'' | 'h' | 'H')) {
+        Some(stripped) => (stripped, true),
+        None => (s, false),
+    };
+    // u32::from_str would also take "+5" and "007".
+    let decimal = !digits.is_empty()
+        && digits.bytes().all(|b| b.is_ascii_digit())
+        && (digits == "0" || !digits.starts_with('0'));
+    if !decimal {
+        return Err(format!("Invalid index: {}", s));
+    }
+    match digits.parse::<u32>() {
+        Ok(n) if n < HARDENED_BIT => Ok(if hardened { n | HARDENED_BIT } else { n }),
+        _ => Err(format!(
+            "Index out of range (max {}): {}",
+            HARDENED_BIT - 1,
+            s
+        )),
+    }
+}
+
+/// One account or address component of a [`PathPattern`].
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum IndexRange {
+    /// `*`: any non-hardened index.
+    Any,
+    /// `n` (first == last) or `first-last`, inclusive.
+    Span { first: u32, last: u32 },
+}
+
+impl IndexRange {
+    fn parse(s: &str) -> Result<Self, String> {
+        if s == "*" {
+            return Ok(IndexRange::Any);
+        }
+        let (first, last) = match s.split_once('-') {
+            Some((first, last)) => (first, last),
+            None => (s, s),
+        };
+        let span = IndexRange::Span {
+            first: parse_index(first)?,
+            last: parse_index(last)?,
+        };
+        span.validate()
+            .map_err(|e| format!("Invalid index range {}: {}", s, e))?;
+        Ok(span)
+    }
+
+    fn validate(self) -> Result<(), &'static str> {
+        match self {
+            IndexRange::Any => Ok(()),
+            IndexRange::Span { last, .. } if last >= HARDENED_BIT => {
+                Err("indices must be non-hardened")
+            }
+            IndexRange::Span { first, last } if first > last => Err("range is reversed"),
+            IndexRange::Span { .. } => Ok(()),
+        }
+    }
+
+    pub fn contains(self, index: u32) -> bool {
+        match self {
+            IndexRange::Any => index < HARDENED_BIT,
+            IndexRange::Span { first, last } => first <= index && index <= last,
+        }
+    }
+
+    /// Every index `other` admits, `self` admits too.
+    pub fn covers(self, other: IndexRange) -> bool {
+        match (self, other) {
+            (IndexRange::Any, _) => true,
+            (IndexRange::Span { .. }, IndexRange::Any) => false,
+            (IndexRange::Span { first, last }, IndexRange::Span { first: f, last: l }) => {
+                first <= f && l <= last
+            }
+        }
+    }
+}
+
+impl fmt::Display for IndexRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IndexRange::Any => write!(f, "*"),
+            IndexRange::Span { first, last } if first == last => write!(f, "{}", first),
+            IndexRange::Span { first, last } => write!(f, "{}-{}", first, last),
+        }
+    }
+}
+
+/// `m/44'/60'/0'/<account>/<address>`, each component a number, `lo-hi` or `*`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PathPattern {
+    pub account: IndexRange,
+    pub address: IndexRange,
+}
+
+impl PathPattern {
+    pub fn matches(&self, account: u32, address: u32) -> bool {
+        self.account.contains(account) && self.address.contains(address)
+    }
+
+    /// Every path `other` matches, `self` matches too.
+    pub fn covers(&self, other: &PathPattern) -> bool {
+        self.account.covers(other.account) && self.address.covers(other.address)
+    }
+}
+
+impl fmt::Display for PathPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "m/44'/60'/0'/{}/{}", self.account, self.address)
+    }
+}
+
+/// Parse a pattern such as `m/44'/60'/0'/0/*` or `m/44'/60'/0'/0/0-9`.
+pub fn parse_path_pattern(pattern: &str) -> Result<PathPattern, String> {
+    let (account, address) = split_coin_path(pattern, crate::chain::EVM_COIN_TYPE)?;
+    Ok(PathPattern {
+        account: IndexRange::parse(account)?,
+        address: IndexRange::parse(address)?,
+    })
+}
+
+/// Check a pattern list as received, before the TA stores it. Patterns that
+/// arrive by bincode never went through [`parse_path_pattern`].
+pub fn validate_path_patterns(patterns: &[PathPattern]) -> Result<(), String> {
+    if patterns.len() > MAX_PATH_PATTERNS {
+        return Err(format!(
+            "Path policy has {} patterns (max {})",
+            patterns.len(),
+            MAX_PATH_PATTERNS
+        ));
+    }
+    for p in patterns {
+        p.account
+            .validate()
+            .and(p.address.validate())
+            .map_err(|e| format!("Invalid path pattern {}: {}", p, e))?;
+    }
+    Ok(())
+}
+
+/// Whether a policy admits `m/44'/60'/0'/account/address`. Empty = any path.
+pub fn path_allowed(patterns: &[PathPattern], account: u32, address: u32) -> bool {
+    patterns.is_empty() || patterns.iter().any(|p| p.matches(account, address))
+}
+
+/// True when `new` admits no path `old` did not: replacing `old` with it can
+/// only take paths away. Conservative — a pattern spanning two old patterns
+/// without fitting in either does not count as narrowing.
+pub fn narrows(old: &[PathPattern], new: &[PathPattern]) -> bool {
+    if old.is_empty() {
+        return true;
+    }
+    !new.is_empty() && new.iter().all(|n| old.iter().any(|o| o.covers(n)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::{Rng, SeededRng};
+
+    #[test]
+    fn accepts_only_the_ethereum_layout() {
+        assert_eq!(parse_eth_path("m/44'/60'/0'/0/0"), Ok((0, 0)));
+        assert_eq!(parse_eth_path("m/44h/60h/0h/1/7"), Ok((1, 7)));
+        assert_eq!(parse_eth_path(" m/44'/60'/0'/0/9999 "), Ok((0, 9999)));
+        assert!(parse_eth_path("m/44'/60'/1'/0/0").is_err());
+        assert!(parse_eth_path("m/44'/60'/0'/0'/0").is_err());
+        assert!(parse_eth_path("m/44'/60'/0'/0").is_err());
+        assert!(parse_eth_path("m/44'/60'/0'/0/x").is_err());
+        assert!(parse_eth_path("n/44'/60'/0'/0/0").is_err());
+        assert!(parse_eth_path(&format!("m/44'/60'/0'/0/{}", "0".repeat(60))).is_err());
+    }
+
+    #[test]
+    fn other_coin_types_keep_the_same_layout() {
+        assert_eq!(parse_coin_path("m/44'/195'/0'/0/7", 195), Ok((0, 7)));
+        assert!(parse_coin_path("m/44'/60'/0'/0/7", 195).is_err());
+        assert!(parse_coin_path("m/44'/195'/0'/0/7", 60).is_err());
+        assert!(parse_coin_path("m/44'/195'/0'/0'/7", 195).is_err());
+        assert_eq!(coin_path(195, 1, 2), "m/44'/195'/0'/1/2");
+    }
+
+    #[test]
+    fn hardened_index_cannot_overflow_into_the_bit() {
+        assert_eq!(parse_index("5'"), Ok(5 | HARDENED_BIT));
+        assert_eq!(parse_index("2147483647"), Ok(0x7fff_ffff));
+        assert!(parse_index("2147483648'").is_err());
+        assert!(parse_index("2147483648").is_err());
+        assert!(parse_index("4294967295").is_err());
+        assert_eq!(parse_index("5H"), Ok(5 | HARDENED_BIT));
+        assert_eq!(parse_index("0h"), Ok(HARDENED_BIT));
+        for bad in [
+            "", "'", "h", "+5", "-1", "05", "00", "5''", "5h'", " 5", "5 ", "0x5",
+        ]
+        .iter()
+        {
+            assert!(parse_index(bad).is_err(), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn any_path_parses_in_every_hardened_spelling() {
+        let path = parse_path("m/0H/1/2h/2/1000000000").unwrap();
+        assert_eq!(
+            path.levels(),
+            &[HARDENED_BIT, 1, 2 | HARDENED_BIT, 2, 1_000_000_000]
+        );
+        assert_eq!(path.to_string(), "m/0'/1/2'/2/1000000000");
+        assert_eq!(path.hardened_levels(), None);
+        let hardened: DerivationPath = "m/44h/60'/0H".parse().unwrap();
+        assert_eq!(hardened.hardened_levels(), Some(vec![44, 60, 0]));
+        assert_eq!(parse_path(" m ").unwrap().levels(), &[] as &[u32]);
+        for bad in ["", "M/0", "/0", "m/", "m//0", "m/0/", "0/1", "m/a"].iter() {
+            assert!(parse_path(bad).is_err(), "{:?}", bad);
+        }
+        assert!(parse_path(&format!("m{}", "/1".repeat(32))).is_err());
+        assert_eq!(
+            canonical_eth_path("m/44H/60h/0'/3/4"),
+            Ok("m/44'/60'/0'/3/4".to_string())
+        );
+        assert_eq!(eth_path(1, 2), "m/44'/60'/0'/1/2");
+    }
+
+    #[test]
+    fn invalid_path_survives_the_error_text() {
+        let e = InvalidPath::from(parse_eth_path("m/44'/60'/1'/0/0").unwrap_err());
+        let text = format!("derive failed: {}", e);
+        assert_eq!(InvalidPath::find(&text), Some(e));
+        assert_eq!(InvalidPath::find("InvalidPath: "), None);
+        assert_eq!(InvalidPath::find("wallet not found"), None);
+    }
+
+    /// A random level in a random spelling: `'`, `h`, `H` or none; mostly
+    /// small numbers with the occasional one at the bound.
+    fn random_level(rng: &mut SeededRng) -> (u32, String) {
+        let mut b = [0u8; 5];
+        rng.fill(&mut b);
+        let raw = u32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+        let n = match b[4] % 4 {
+            0 => HARDENED_BIT - 1,
+            1 => raw & 0xff,
+            _ => raw & !HARDENED_BIT,
+        };
+        match (b[4] >> 2) % 4 {
+            0 => (n, n.to_string()),
+            1 => (n | HARDENED_BIT, format!("{}'", n)),
+            2 => (n | HARDENED_BIT, format!("{}h", n)),
+            _ => (n | HARDENED_BIT, format!("{}H", n)),
+        }
+    }
+
+    #[test]
+    fn parse_format_parse_is_the_identity() {
+        let mut rng = SeededRng::new([0x44; 32]);
+        for _ in 0..2000 {
+            let mut depth = [0u8; 1];
+            rng.fill(&mut depth);
+            let (levels, spelled): (Vec<u32>, Vec<String>) =
+                (0..depth[0] % 6).map(|_| random_level(&mut rng)).unzip();
+            let text = std::iter::once("m".to_string())
+                .chain(spelled)
+                .collect::<Vec<_>>()
+                .join("/");
+
+            let parsed = parse_path(&text).unwrap();
+            assert_eq!(parsed.levels(), levels.as_slice(), "{}", text);
+            let canonical = parsed.to_string();
+            assert!(!canonical.contains(['h', 'H']), "{}", canonical);
+            assert_eq!(parse_path(&canonical).unwrap(), parsed, "{}", text);
+            assert_eq!(parse_path(&canonical).unwrap().to_string(), canonical);
+        }
+    }
+
+    #[test]
+    fn eth_paths_round_trip_through_their_canonical_form() {
+        let mut rng = SeededRng::new([0x60; 32]);
+        for _ in 0..2000 {
+            let (account, account_text) = random_level(&mut rng);
+            let (address, address_text) = random_level(&mut rng);
+            let text = format!("m/44h/60H/0'/{}/{}", account_text, address_text);
+            let non_hardened = account < HARDENED_BIT && address < HARDENED_BIT;
+            match parse_eth_path(&text) {
+                Ok(parsed) => {
+                    assert!(non_hardened, "{}", text);
+                    assert_eq!(parsed, (account, address));
+                    let canonical = canonical_eth_path(&text).unwrap();
+                    assert_eq!(canonical, eth_path(account, address));
+                    assert_eq!(parse_eth_path(&canonical), Ok(parsed));
+                    let levels = parse_path(&canonical).unwrap();
+                    assert_eq!(
+                        levels.levels(),
+                        &[
+                            44 | HARDENED_BIT,
+                            60 | HARDENED_BIT,
+                            HARDENED_BIT,
+                            account,
+                            address
+                        ]
+                    );
+                }
+                Err(_) => assert!(!non_hardened, "{}", text),
+            }
+        }
+    }
+
+    fn pattern(s: &str) -> PathPattern {
+        parse_path_pattern(s).unwrap()
+    }
+
+    #[test]
+    fn patterns_parse_and_print_back() {
+        for s in [
+            "m/44'/60'/0'/0/*",
+            "m/44'/60'/0'/*/*",
+            "m/44'/60'/0'/1/0-9",
+            "m/44'/60'/0'/0/7",
+        ]
+        .iter()
+        {
+            assert_eq!(pattern(s).to_string(), *s);
+        }
+        assert_eq!(
+            pattern("m/44h/60h/0h/0/3-3").to_string(),
+            "m/44'/60'/0'/0/3"
+        );
+        assert!(parse_path_pattern("m/44'/60'/1'/0/*").is_err());
+        assert!(parse_path_pattern("m/44'/60'/0'/0'/*").is_err());
+        assert!(parse_path_pattern("m/44'/60'/0'/0/9-1").is_err());
+        assert!(parse_path_pattern("m/44'/60'/0'/0/1-").is_err());
+        assert!(parse_path_pattern("m/44'/60'/0'/0/**").is_err());
+        assert!(parse_path_pattern("m/44'/60'/0'/0").is_err());
+    }
+
+    #[test]
+    fn empty_policy_allows_every_path() {
+        let policy = [pattern("m/44'/60'/0'/0/*"), pattern("m/44'/60'/0'/1/0-4")];
+        assert!(path_allowed(&[], 7, 9));
+        assert!(path_allowed(&policy, 0, 123_456));
+        assert!(path_allowed(&policy, 1, 4));
+        assert!(!path_allowed(&policy, 1, 5));
+        assert!(!path_allowed(&policy, 2, 0));
+    }
+
+    #[test]
+    fn narrowing_only_takes_paths_away() {
+        let any = pattern("m/44'/60'/0'/*/*");
+        let first = pattern("m/44'/60'/0'/0/*");
+        let few = pattern("m/44'/60'/0'/0/0-9");
+        assert!(narrows(&[], &[first]));
+        assert!(narrows(&[any], &[first, few]));
+        assert!(narrows(&[first], &[few]));
+        assert!(!narrows(&[few], &[first]));
+        assert!(!narrows(&[first], &[]));
+        assert!(narrows(&[], &[]));
+        assert!(!narrows(
+            &[pattern("m/44'/60'/0'/0/0-4"), pattern("m/44'/60'/0'/0/5-9")],
+            &[few]
+        ));
+    }
+
+    #[test]
+    fn received_patterns_are_revalidated() {
+        let reversed = PathPattern {
+            account: IndexRange::Span { first: 0, last: 0 },
+            address: IndexRange::Span { first: 5, last: 1 },
+        };
+        let hardened = PathPattern {
+            account: IndexRange::Span {
+                first: 0,
+                last: HARDENED_BIT,
+            },
+            address: IndexRange::Any,
+        };
+        assert!(validate_path_patterns(&[pattern("m/44'/60'/0'/0/*")]).is_ok());
+        assert!(validate_path_patterns(&[reversed]).is_err());
+        assert!(validate_path_patterns(&[hardened]).is_err());
+        assert!(
+            validate_path_patterns(&vec![pattern("m/44'/60'/0'/0/*"); MAX_PATH_PATTERNS + 1])
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,188 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Account linkage: one user identity owning wallets across chains.
+//!
+//! Identities live in the CA. Linking or unlinking a wallet goes through the
+//! TA's `LinkWallet`, which checks the wallet's passkey (and the identity
+//! owner's, when another wallet joins) against [`link_commitment`] and signs
+//! a [`link_statement`] with the wallet's own key. The statement and
+//! signature are the portable proof of control an SDK can verify with plain
+//! ecrecover.
+
+use crate::eip55::to_checksum_address;
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+use uuid::Uuid;
+
+/// Chains named in one link.
+pub const MAX_CHAINS_PER_LINK: usize = 32;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkAction {
+    Link,
+    Unlink,
+}
+
+impl LinkAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LinkAction::Link => "link",
+            LinkAction::Unlink => "unlink",
+        }
+    }
+}
+
+/// A link names 1-32 distinct, ascending, non-zero chain ids; an unlink
+/// names none.
+pub fn validate_chain_ids(action: LinkAction, chain_ids: &[u64]) -> Result<(), &'static str> {
+    match action {
+        LinkAction::Unlink if chain_ids.is_empty() => Ok(()),
+        LinkAction::Unlink => Err("unlink takes no chain ids"),
+        LinkAction::Link => {
+            if chain_ids.is_empty() || chain_ids.len() > MAX_CHAINS_PER_LINK {
+                return Err("a link names 1-32 chain ids");
+            }
+            if chain_ids[0] == 0 || chain_ids.windows(2).any(|w| w[0] >= w[1]) {
+                return Err("chain ids must be non-zero, distinct and ascending");
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Passkey commitment for a link or unlink. Both the wallet's passkey and,
+/// when given, the identity owner's sign this same value, so the owner
+/// approves exactly this wallet, path and chain set.
+pub fn link_commitment(
+    action: LinkAction,
+    identity_id: &Uuid,
+    wallet_id: &Uuid,
+    hd_path: &str,
+    chain_ids: &[u64],
+    owner_wallet_id: Option<&Uuid>,
+) -> [u8; 32] {
+    let mut h = Keccak256::new();
+    h.update(match action {
+        LinkAction::Link => &b"AA-IDENTITY-LINK-v1"[..],
+        LinkAction::Unlink => &b"AA-IDENTITY-UNLINK-v1"[..],
+    });
+    h.update(identity_id.as_bytes());
+    h.update(wallet_id.as_bytes());
+    h.update((hd_path.len() as u32).to_be_bytes());
+    h.update(hd_path.as_bytes());
+    h.update((chain_ids.len() as u32).to_be_bytes());
+    for id in chain_ids {
+        h.update(id.to_be_bytes());
+    }
+    match owner_wallet_id {
+        Some(owner) => {
+            h.update([1u8]);
+            h.update(owner.as_bytes());
+        }
+        None => h.update([0u8]),
+    }
+    h.finalize().into()
+}
+
+/// The text the wallet key signs. `issued_at` is the TA's clock.
+pub fn link_statement(
+    action: LinkAction,
+    identity_id: &Uuid,
+    address: &[u8; 20],
+    chain_ids: &[u64],
+    issued_at: u64,
+) -> String {
+    let chains = if chain_ids.is_empty() {
+        "-".to_string()
+    } else {
+        chain_ids
+            .iter()
+            .map(u64::to_string)
+            .collect::<Vec<_>>()
+            .join(",")
+    };
+    format!(
+        "AirAccount identity link v1\naction: {}\nidentity: {}\naddress: {}\nchains: {}\nissued at: {}",
+        action.as_str(),
+        identity_id,
+        to_checksum_address(address),
+        chains,
+        issued_at
+    )
+}
+
+/// EIP-191 personal_sign digest of a statement.
+pub fn statement_digest(statement: &str) -> [u8; 32] {
+    let mut h = Keccak256::new();
+    h.update(format!("\x19Ethereum Signed Message:\n{}", statement.len()).as_bytes());
+    h.update(statement.as_bytes());
+    h.finalize().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chain_ids_and_statement() {
+        assert!(validate_chain_ids(LinkAction::Link, &[1, 10, 8453]).is_ok());
+        assert!(validate_chain_ids(LinkAction::Link, &[]).is_err());
+        assert!(validate_chain_ids(LinkAction::Link, &[10, 1]).is_err());
+        assert!(validate_chain_ids(LinkAction::Link, &[1, 1]).is_err());
+        assert!(validate_chain_ids(LinkAction::Link, &[0]).is_err());
+        assert!(validate_chain_ids(LinkAction::Link, &(1..=33).collect::<Vec<_>>()).is_err());
+        assert!(validate_chain_ids(LinkAction::Unlink, &[]).is_ok());
+        assert!(validate_chain_ids(LinkAction::Unlink, &[1]).is_err());
+
+        let id = Uuid::nil();
+        let address = [0x5a; 20];
+        assert_eq!(
+            link_statement(LinkAction::Link, &id, &address, &[1, 8453], 1_700_000_000),
+            format!(
+                "AirAccount identity link v1\naction: link\nidentity: {}\naddress: {}\n\
+                 chains: 1,8453\nissued at: 1700000000",
+                id,
+                to_checksum_address(&address)
+            )
+        );
+        assert!(link_statement(LinkAction::Unlink, &id, &address, &[], 0).contains("chains: -"));
+    }
+
+    #[test]
+    fn commitment_covers_every_field() {
+        let (i, w, o) = (Uuid::nil(), Uuid::from_u128(1), Uuid::from_u128(2));
+        let path = "m/44'/60'/0'/0/0";
+        let base = link_commitment(LinkAction::Link, &i, &w, path, &[1], None);
+        assert_ne!(
+            base,
+            link_commitment(LinkAction::Unlink, &i, &w, path, &[1], None)
+        );
+        assert_ne!(
+            base,
+            link_commitment(LinkAction::Link, &i, &w, path, &[1, 10], None)
+        );
+        assert_ne!(
+            base,
+            link_commitment(LinkAction::Link, &i, &w, "m/44'/60'/0'/0/1", &[1], None)
+        );
+        assert_ne!(
+            base,
+            link_commitment(LinkAction::Link, &i, &w, path, &[1], Some(&o))
+        );
+    }
+}
@@ -51,12 +51,19 @@ pub struct PasskeyAssertion {
 pub struct CreateWalletInput {
     /// P-256 public key in uncompressed format (65 bytes: 0x04 || x || y)
     pub passkey_pubkey: Vec<u8>,
-    /// Optional CA-provided entropy seed: 32 bytes wallet entropy + 16 bytes UUID seed (48 total).
+    /// Optional CA-provided entropy seed: 32 bytes wallet entropy, then 16 bytes
+    /// the TA ignores (48 total). The wallet id comes from the key (`wallet_id`).
     /// When present the TA uses this instead of TEE_GenerateRandom(), bypassing CAAM TRNG.
     /// Entropy is generated by the CA using Linux OsRng (/dev/urandom-backed CSPRNG).
     /// This is the fallback for boards where CAAM TRNG is unreliable or stuck.
     #[serde(default)]
     pub entropy_seed: Option<Vec<u8>>,
+    /// Optional WebAuthn PRF (hmac-secret) output for the registering credential.
+    /// The TA mixes it into the wallet entropy, so the seed depends on a secret
+    /// only the authenticator can produce — a weak or biased RNG alone no longer
+    /// determines the key. Last field for bincode compat.
+    #[serde(default)]
+    pub prf_output: Option<[u8; 32]>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -72,8 +79,12 @@ pub struct RemoveWalletInput {
     pub passkey_assertion: Option<PasskeyAssertion>,
 }
 
+/// TA builds that predate proof-of-erasure return an empty output; the CA
+/// treats that as "no certificate".
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
-pub struct RemoveWalletOutput {}
+pub struct RemoveWalletOutput {
+    pub proof: crate::erasure::DeletionProof,
+}
 
 /// Admin force-delete: removes wallet from TEE secure storage without passkey verification.
 /// Used exclusively for gap keys (passkey_pubkey bytes are not a valid P-256 curve point).
@@ -93,12 +104,20 @@ pub struct DeriveAddressInput {
     pub hd_path: String,
     #[serde(default)]
     pub passkey_assertion: Option<PasskeyAssertion>,
+    /// Protocol v2. `hd_path` is under this chain's coin type.
+    #[serde(default)]
+    pub chain: crate::chain::Chain,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct DeriveAddressOutput {
+    /// Keccak-160 of the public key, on every chain.
     pub address: [u8; 20],
     pub public_key: Vec<u8>,
+    /// Protocol v2: `address` as the requested chain writes it. Empty from
+    /// a v1 TA, which derived for Ethereum whatever chain was asked.
+    #[serde(default)]
+    pub encoded_address: String,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -119,6 +138,14 @@ pub struct SignTransactionInput {
     pub transaction: EthTransaction,
     #[serde(default)]
     pub passkey_assertion: Option<PasskeyAssertion>,
+    /// Extra function signatures (e.g. "stake(uint256)") for the calldata
+    /// summary; see `calldata::summarize_transaction`.
+    #[serde(default)]
+    pub summary_abis: Vec<String>,
+    /// The passkey challenge commits to `calldata::confirmation_digest` of
+    /// the summary instead of the bare tx hash.
+    #[serde(default)]
+    pub summary_committed: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -126,6 +153,40 @@ pub struct SignTransactionOutput {
     pub signature: Vec<u8>,
 }
 
+/// Domain-separation context declared by every SignMessage / SignHash call.
+///
+/// The TA rebuilds (or re-checks) the digest from the context's preimage, so a
+/// signature produced for one protocol cannot be replayed in another — e.g. a
+/// userOpHash signature re-used as a personal_sign, or an arbitrary 32-byte
+/// digest slipped in as "just a hash".
+///
+/// `Raw` is the legacy behaviour (digest signed exactly as given). It is only
+/// accepted by a TA built without the `strict-signing-context` feature.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub enum SigningContext {
+    /// Legacy / unspecified: SignHash signs `hash` as-is, SignMessage signs keccak256(message).
+    #[default]
+    Raw,
+    /// Legacy Ethereum transaction. Implicit for SignTransaction (TA builds the RLP).
+    EthTx,
+    /// ERC-4337 userOpHash = keccak256(abi.encode(inner_hash, entry_point, chain_id)),
+    /// where `inner_hash` is the hash of the packed UserOperation.
+    UserOp {
+        inner_hash: [u8; 32],
+        entry_point: [u8; 20],
+        chain_id: u64,
+    },
+    /// EIP-712 digest = keccak256(0x1901 ‖ domain_separator ‖ struct_hash).
+    Eip712 {
+        domain_separator: [u8; 32],
+        struct_hash: [u8; 32],
+    },
+    /// EIP-191 personal_sign: keccak256("\x19Ethereum Signed Message:\n" ‖ len ‖ message).
+    PersonalMsg,
+    /// Login message (EIP-4361 shape) signed as EIP-191 personal_sign.
+    Login,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct SignMessageInput {
     pub wallet_id: Uuid,
@@ -133,6 +194,9 @@ pub struct SignMessageInput {
     pub message: Vec<u8>,
     #[serde(default)]
     pub passkey_assertion: Option<PasskeyAssertion>,
+    /// Valid here: Raw, PersonalMsg, Login.
+    #[serde(default)]
+    pub context: SigningContext,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -147,6 +211,14 @@ pub struct SignHashInput {
     pub hash: [u8; 32],
     #[serde(default)]
     pub passkey_assertion: Option<PasskeyAssertion>,
+    /// Valid here: Raw, UserOp, Eip712. For UserOp/Eip712 the TA recomputes the
+    /// digest from the preimage and rejects the call if it differs from `hash`.
+    #[serde(default)]
+    pub context: SigningContext,
+    /// Fee paid in ERC-20 through a paymaster (UserOp context only): the
+    /// passkey then commits to `paymaster::fee_review_digest`, not `hash`.
+    #[serde(default)]
+    pub fee_quote: Option<crate::paymaster::FeeQuote>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -239,7 +311,7 @@ pub struct CreateAgentKeyInput {
     pub agent_index: u32,
     /// JWT sub claim (typically the human key ID string).
     pub subject: String,
-    /// JWT lifetime in seconds (TA caps at MAX_AGENT_JWT_TTL = 86400 (24h)).
+    /// JWT lifetime in seconds (TA caps at the config's agent-jwt-ttl, 86400 (24h) by default).
     /// TA computes iat = now() internally; host does not supply iat.
     pub ttl_secs: i64,
     /// Passkey assertion — mandatory when wallet has a PassKey bound.
@@ -259,6 +331,18 @@ pub struct CreateAgentKeyInput {
     /// to the matching tag/shape, which won't verify under the other branch.
     #[serde(default)]
     pub is_refresh: bool,
+    /// Open (passkey mint) or redeem (`secret` set, `is_refresh`, no passkey)
+    /// a refresh-token family; see `crate::refresh_token`.
+    #[serde(default)]
+    pub refresh: Option<AgentRefresh>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AgentRefresh {
+    /// SHA-256 of the client's device fingerprint.
+    pub device_hash: [u8; 32],
+    /// The refresh secret being redeemed; None opens a new family.
+    pub secret: Option<[u8; 32]>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -271,6 +355,9 @@ pub struct CreateAgentKeyOutput {
     pub jwt_header_b64: String,
     pub jwt_payload_b64: String,
     pub jwt_hmac: [u8; 32],
+    /// The next refresh secret, when the input asked for one.
+    #[serde(default)]
+    pub refresh_secret: Option<[u8; 32]>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -404,7 +491,7 @@ pub struct CreateP256SessionKeyInput {
     pub session_index: u32,
     /// JWT sub claim (typically the human key ID string).
     pub subject: String,
-    /// JWT lifetime in seconds (TA caps at MAX_AGENT_JWT_TTL = 86400 (24h)).
+    /// JWT lifetime in seconds (TA caps at the config's agent-jwt-ttl, 86400 (24h) by default).
     pub ttl_secs: i64,
     /// #111: WebAuthn assertion proving user presence. The TA re-verifies it
     /// (verify_passkey_for_wallet) BEFORE minting the session key + TEE-HMAC JWT,
@@ -540,7 +627,8 @@ pub struct ReadRollbackCounterOutput {
 /// The TA generates 32 bytes via `optee_utee::Random`, stores `(wallet_id, nonce, issued_at)`
 /// in an in-memory pending table, and returns the nonce. The host MUST use this nonce as the
 /// WebAuthn `challenge` presented to the browser, so the value the authenticator signs is the
-/// one the TA can later verify. The nonce is single-use and expires after `CHALLENGE_TTL_SECS`.
+/// one the TA can later verify. The nonce is single-use and expires after the TA config's
+/// challenge-ttl (300s by default).
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct GetChallengeInput {
     /// Wallet the challenge is bound to. A nonce issued for wallet A cannot be
@@ -586,7 +674,7 @@ pub struct GetAttestationInput {
 /// ⚠️ Trust-root scope (MVP): the attestation key is **self-generated by the
 /// device's OP-TEE on first use** — it carries NO certificate chain to an NXP
 /// root (confirmed against `core/pta/attestation.c` and RM00284 — see
-/// `docs/design/37-remote-attestation-design.md` R-1). A verifier therefore
+/// `docs/design/37-remote-attestation-design.md` §9 (R-1)). A verifier therefore
 /// trusts this key via TOFU / a published reference value, NOT via an NXP root.
 /// This proves "ran inside a real OP-TEE as this exact TA" but not "this is a
 /// genuine NXP part the verifier never trusted before". Closing that gap is
@@ -729,3 +817,1304 @@ pub struct BlsPopSignOutput {
     /// sk · popPoint as 256-byte EIP-2537 G2 (registerWithProof's `popSig`).
     pub pop_signature: Vec<u8>,
 }
+
+// Scoped AA session keys — a TEE-generated secondary secp256k1 key whose scope
+// is fixed by a permission object the root wallet key signs at issuance.
+
+/// What a scoped session key may do. The owner signs
+/// EIP-191(keccak256(abi.encode(tag, chainId, account, sessionKey,
+/// keccak(targets), keccak(selectors), valueCap, expiry))).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SessionPermission {
+    pub chain_id: u64,
+    /// ERC-4337 smart account the session acts for.
+    pub account: [u8; 20],
+    /// Contracts the session may call (non-empty).
+    pub targets: Vec<[u8; 20]>,
+    /// Allowed 4-byte selectors; empty = any selector on an allowed target.
+    pub selectors: Vec<[u8; 4]>,
+    /// Max native value (wei) per call.
+    pub value_cap: u128,
+    /// Unix seconds after which the session key is dead.
+    pub expiry: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CreateScopedSessionKeyInput {
+    pub wallet_id: Uuid,
+    /// Root-key path that signs the permission object.
+    pub hd_path: String,
+    /// Host-allocated, monotonic per wallet; a revoked index is never reusable.
+    pub session_index: u32,
+    pub permission: SessionPermission,
+    /// Required for any wallet with a passkey bound; bound to the permission hash.
+    pub passkey_assertion: Option<PasskeyAssertion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CreateScopedSessionKeyOutput {
+    pub session_address: [u8; 20],
+    pub permission_hash: [u8; 32],
+    /// 65 bytes: R(32) || S(32) || V(1), V = 27/28 — root key over EIP-191(permission_hash).
+    pub owner_signature: Vec<u8>,
+}
+
+/// The call a session signature is requested for, checked against the scope.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SessionCall {
+    pub target: [u8; 20],
+    pub selector: [u8; 4],
+    pub value: u128,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SignWithSessionKeyInput {
+    pub wallet_id: Uuid,
+    pub session_index: u32,
+    pub chain_id: u64,
+    pub call: SessionCall,
+    pub user_op_hash: [u8; 32],
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SignWithSessionKeyOutput {
+    /// 65 bytes: R(32) || S(32) || V(1), V = 27/28 — session key over EIP-191(userOpHash).
+    pub signature: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RevokeScopedSessionKeyInput {
+    pub wallet_id: Uuid,
+    pub session_index: u32,
+    pub passkey_assertion: Option<PasskeyAssertion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RevokeScopedSessionKeyOutput {
+    /// false if the index was already on the revocation list (idempotent).
+    pub revoked: bool,
+}
+
+// ── BLS validator signing with slashing protection ──
+// Both commands answer with `BlsSignOutput`; the TA derives the signing root
+// itself, so the caller can't pass an arbitrary message through these paths.
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BeaconCheckpoint {
+    pub epoch: u64,
+    pub root: [u8; 32],
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BlsSignAttestationInput {
+    pub key_id: Uuid,
+    pub slot: u64,
+    pub committee_index: u64,
+    pub beacon_block_root: [u8; 32],
+    pub source: BeaconCheckpoint,
+    pub target: BeaconCheckpoint,
+    /// compute_domain(DOMAIN_BEACON_ATTESTER, fork_version, genesis_validators_root).
+    pub domain: [u8; 32],
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BlsSignBlockInput {
+    pub key_id: Uuid,
+    pub slot: u64,
+    pub proposer_index: u64,
+    pub parent_root: [u8; 32],
+    pub state_root: [u8; 32],
+    pub body_root: [u8; 32],
+    /// compute_domain(DOMAIN_BEACON_PROPOSER, fork_version, genesis_validators_root).
+    pub domain: [u8; 32],
+}
+
+// ── TA command latency telemetry ──
+// The TA keeps one fixed-bucket histogram per command id (integer ms, no
+// floats in the TA). Only commands that have been called are returned; the
+// CA's buffer holds every slot (`buffers::TA_STATS_OUTPUT_LEN`).
+
+/// Inclusive upper bounds (ms) of the latency buckets. A sample above the last
+/// bound lands in one extra overflow bucket.
+pub const TA_LATENCY_BUCKETS_MS: [u32; 12] = [1, 2, 5, 10, 20, 50, 100, 200, 500, 1000, 2000, 5000];
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TaStatsInput {}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CommandLatency {
+    pub command: u32,
+    pub count: u32,
+    pub errors: u32,
+    /// `TA_LATENCY_BUCKETS_MS.len() + 1` counters; the last is the overflow bucket.
+    pub buckets: Vec<u32>,
+}
+
+impl CommandLatency {
+    /// Upper bound (ms) of the bucket holding the `pct`-th percentile sample.
+    /// `None` when there are no samples or the percentile falls in the
+    /// overflow bucket (slower than the last bound).
+    pub fn percentile_ms(&self, pct: u32) -> Option<u32> {
+        let total: u64 = self.buckets.iter().map(|&b| b as u64).sum();
+        if total == 0 {
+            return None;
+        }
+        // rank = ceil(total * pct / 100), at least 1
+        let rank = (total * pct.min(100) as u64).div_ceil(100).max(1);
+        let mut seen = 0u64;
+        for (i, &b) in self.buckets.iter().enumerate() {
+            seen += b as u64;
+            if seen >= rank {
+                return TA_LATENCY_BUCKETS_MS.get(i).copied();
+            }
+        }
+        None
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TaStatsOutput {
+    pub commands: Vec<CommandLatency>,
+    /// Wallets against the cap (`storage_quota`). None from an older TA.
+    /// Last field for bincode compat.
+    #[serde(default)]
+    pub storage: Option<crate::storage_quota::StorageUsage>,
+}
+
+// ── Allowance guard ──
+// A per-wallet rule over decoded approve / increaseAllowance / permit /
+// setApprovalForAll calls in SignTransaction. The TA decodes the calldata
+// itself (`calldata::decode_transaction`), so the CA cannot hide an approval.
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AllowanceRule {
+    #[default]
+    Off,
+    /// Refuse approvals that don't fit in u128 and setApprovalForAll(true).
+    RejectUnlimited,
+    /// Refuse approvals above `max` raw token units (and anything unlimited).
+    /// The TA never rewrites calldata: the user confirmed the tx as built, so
+    /// the dapp must resubmit with a smaller amount.
+    Cap { max: u128 },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SetAllowancePolicyInput {
+    pub wallet_id: Uuid,
+    pub rule: AllowanceRule,
+    pub passkey_assertion: Option<PasskeyAssertion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SetAllowancePolicyOutput {
+    pub previous: AllowanceRule,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ConfirmAllowanceOverrideInput {
+    pub wallet_id: Uuid,
+    pub transaction: EthTransaction,
+    pub summary_abis: Vec<String>,
+    pub passkey_assertion: Option<PasskeyAssertion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ConfirmAllowanceOverrideOutput {
+    /// The summary the override was armed for (what the user just confirmed).
+    pub summary: String,
+}
+
+/// Replaces the wallet's spender allow-list. Empty = any spender. Checked
+/// for approvals in SignTransaction and for every permit the TA signs.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SetSpenderAllowListInput {
+    pub wallet_id: Uuid,
+    pub spenders: Vec<[u8; 20]>,
+    pub passkey_assertion: Option<PasskeyAssertion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SetSpenderAllowListOutput {
+    pub previous: Vec<[u8; 20]>,
+}
+
+/// Reads the wallet's derivation-path policy (`set = None`) or replaces it.
+/// Empty = any path. A list that only narrows the current one needs no
+/// passkey; anything else needs one committed to the new list.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PathPolicyInput {
+    pub wallet_id: Uuid,
+    pub set: Option<Vec<crate::hd_path::PathPattern>>,
+    pub passkey_assertion: Option<PasskeyAssertion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PathPolicyOutput {
+    /// In force after the call.
+    pub patterns: Vec<crate::hd_path::PathPattern>,
+    /// Replaced by this call; empty on a read.
+    pub previous: Vec<crate::hd_path::PathPattern>,
+}
+
+// ── Remote approver ──
+
+/// Pairs `approver_key` (compressed secp256k1) with the wallet, replacing
+/// any earlier phone, or unpairs with None. Needs a passkey committed to
+/// `remote_approval::pairing_commitment`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PairApproverInput {
+    pub wallet_id: Uuid,
+    pub approver_key: Option<Vec<u8>>,
+    pub passkey_assertion: Option<PasskeyAssertion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PairApproverOutput {
+    pub previous: Option<Vec<u8>>,
+}
+
+/// The paired phone's approval of one Sign `payload`; see
+/// `remote_approval::approval_digest`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RemoteApproveInput {
+    pub wallet_id: Uuid,
+    pub payload: [u8; 32],
+    pub signature: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RemoteApproveOutput {
+    /// TA time after which the approval no longer counts.
+    pub expires_at: i64,
+}
+
+// ── Signature-aggregator accounts ──
+
+/// Switches the wallet to BLS signatures under `aggregator`, or back to
+/// ECDSA with None. Needs a passkey committed to
+/// `aggregator::scheme_commitment`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SetAggregatorInput {
+    pub wallet_id: Uuid,
+    pub aggregator: Option<[u8; 20]>,
+    pub passkey_assertion: Option<PasskeyAssertion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SetAggregatorOutput {
+    /// EIP-2537 G1 key the account registers with the aggregator; None
+    /// when the wallet went back to ECDSA.
+    pub public_key: Option<Vec<u8>>,
+    pub previous: Option<[u8; 20]>,
+}
+
+/// The passkey challenge is `user_op_hash`, as for SignHash.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SignAggregatedUserOpInput {
+    pub wallet_id: Uuid,
+    pub user_op_hash: [u8; 32],
+    pub passkey_assertion: Option<PasskeyAssertion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SignAggregatedUserOpOutput {
+    /// The aggregator the wallet is configured with.
+    pub aggregator: [u8; 20],
+    /// EIP-2537 G2 signature (256 bytes).
+    pub signature: Vec<u8>,
+    /// Compressed G2 (96 bytes).
+    pub signature_compact: Vec<u8>,
+}
+
+// ── Passphrase keystores ──
+
+/// The keystore secret is the 32-byte wallet entropy, so an export restores
+/// the same mnemonic (and every derived address) on import.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ExportKeystoreInput {
+    pub wallet_id: Uuid,
+    pub passphrase: String,
+    pub kdf: crate::kdf::KdfConfig,
+    /// Challenge commits to `kdf::export_commitment(wallet_id, kdf)`.
+    pub passkey_assertion: Option<PasskeyAssertion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ExportKeystoreOutput {
+    pub keystore: crate::kdf::Keystore,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ImportKeystoreInput {
+    /// Owner passkey of the new wallet, as for CreateWallet.
+    pub passkey_pubkey: Vec<u8>,
+    pub keystore: crate::kdf::Keystore,
+    pub passphrase: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ImportKeystoreOutput {
+    pub wallet_id: Uuid,
+}
+
+// ── Permits (EIP-2612 / Permit2) ──
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SignPermitInput {
+    pub wallet_id: Uuid,
+    pub hd_path: String,
+    pub permit: crate::permit::PermitRequest,
+    /// Challenge commits to `permit::review_digest(digest, summary)`.
+    pub passkey_assertion: Option<PasskeyAssertion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SignPermitOutput {
+    /// 65 bytes: R(32) || S(32) || V(1), V normalized to 27/28
+    pub signature: Vec<u8>,
+    /// The review the TA rebuilt and the passkey confirmed.
+    pub summary: String,
+}
+
+// ── Offline (air-gapped) signing ──
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SignOfflineInput {
+    pub request: crate::offline::OfflineSignRequest,
+    pub passkey_assertion: Option<PasskeyAssertion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SignOfflineOutput {
+    pub response: crate::offline::OfflineSignResponse,
+}
+
+// ── Deployment policy / capabilities ──
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct InstallDeploymentPolicyInput {
+    pub policy: crate::deployment_policy::DeploymentPolicy,
+    /// 65-byte r ‖ s ‖ v personal_sign over `policy.message()`.
+    pub signature: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct InstallDeploymentPolicyOutput {
+    pub previous_sequence: Option<u64>,
+    /// Address the signature recovered to; pinned by the first install.
+    pub signer: [u8; 20],
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GetCapabilitiesInput {}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GetCapabilitiesOutput {
+    pub ta_version: String,
+    /// Cargo features the TA image was built with.
+    pub features: Vec<String>,
+    /// None = no policy installed, every command enabled.
+    pub policy: Option<crate::deployment_policy::DeploymentPolicy>,
+    pub policy_signer: Option<[u8; 20]>,
+    /// None = no config installed, `TaLimits::DEFAULT` in force. Trailing
+    /// fields for bincode compat with TAs that predate InstallConfig.
+    #[serde(default)]
+    pub config: Option<crate::ta_config::TaConfig>,
+    /// Compiled-in InstallConfig signer; None = the image accepts no config.
+    #[serde(default)]
+    pub provisioning_key: Option<[u8; 20]>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GetProtocolVersionInput {}
+
+/// What `wire::negotiate` needs from the TA.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GetProtocolVersionOutput {
+    pub protocol_version: u16,
+    pub min_protocol_version: u16,
+    /// Ids above it are newer than the TA.
+    pub max_command_id: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct InstallConfigInput {
+    pub config: crate::ta_config::TaConfig,
+    /// 65-byte r ‖ s ‖ v personal_sign over `config.message()` by the
+    /// provisioning key.
+    pub signature: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct InstallConfigOutput {
+    pub previous_sequence: Option<u64>,
+}
+
+// ── Multi-device replication ──
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReplicationOfferInput {}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReplicationOfferOutput {
+    /// Role `Target`. Replaces any earlier offer still pending on this TA.
+    pub hello: crate::replication::ReplicationHello,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReplicationExportInput {
+    pub wallet_id: Uuid,
+    /// The target's offer.
+    pub peer: crate::replication::ReplicationHello,
+    /// Owner approval; the challenge payload is `peer.digest()`.
+    pub passkey_assertion: Option<PasskeyAssertion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReplicationExportOutput {
+    pub package: crate::replication::ReplicationPackage,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReplicationImportInput {
+    pub package: crate::replication::ReplicationPackage,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReplicationImportOutput {
+    pub wallet_id: Uuid,
+    /// Address at m/44'/60'/0'/0/0 — the CA compares it with the source's.
+    pub address: [u8; 20],
+}
+
+// ── Erase-on-tamper ──
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TamperOrderInput {
+    pub order: crate::tamper::TamperOrder,
+    /// 65-byte r ‖ s ‖ v personal_sign over `order.message()` by the pinned
+    /// deployment-policy signer.
+    pub signature: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TamperOrderOutput {
+    pub status: TamperStatus,
+    /// True when this order wiped the device; the proof is `status.last_wipe`.
+    pub wiped: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GetTamperStatusInput {}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TamperStatus {
+    /// None = automatic wipe off.
+    pub failed_auth_limit: Option<u32>,
+    pub consecutive_failures: u32,
+    pub last_order_sequence: u64,
+    /// Some while the TA refuses wallet commands after a wipe.
+    pub locked: Option<crate::tamper::TamperLock>,
+    pub last_wipe: Option<crate::tamper::WipeProof>,
+}
+
+// ── Dapp storage ──
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DappStoragePutInput {
+    pub wallet_id: Uuid,
+    pub app_id: String,
+    pub key: String,
+    pub value: Vec<u8>,
+    /// Challenge commits to `dapp_storage::op_commitment(Put, ..., value)`.
+    pub passkey_assertion: Option<PasskeyAssertion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DappStoragePutOutput {
+    /// The app's usage after the write.
+    pub used_keys: u32,
+    pub used_bytes: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DappStorageGetInput {
+    pub wallet_id: Uuid,
+    pub app_id: String,
+    pub key: String,
+    pub passkey_assertion: Option<PasskeyAssertion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DappStorageGetOutput {
+    pub value: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DappStorageDeleteInput {
+    pub wallet_id: Uuid,
+    pub app_id: String,
+    pub key: String,
+    pub passkey_assertion: Option<PasskeyAssertion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DappStorageDeleteOutput {
+    /// False if the key was not there (nothing changed).
+    pub existed: bool,
+}
+
+// ── BIP85 ──
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Bip85ExportInput {
+    pub wallet_id: Uuid,
+    pub app: crate::bip85::Bip85App,
+    /// Compressed secp256k1 key of the app or device being provisioned.
+    pub recipient_pubkey: Vec<u8>,
+    /// Challenge commits to `bip85::export_commitment(wallet_id, app, recipient_pubkey)`.
+    pub passkey_assertion: Option<PasskeyAssertion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Bip85ExportOutput {
+    pub package: crate::bip85::Bip85Package,
+}
+
+// ── Crash dumps ──
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CrashDumpsInput {
+    /// Empty the log after reading it.
+    pub clear: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CrashDumpsOutput {
+    /// Oldest first.
+    pub records: Vec<crate::crash_dump::CrashRecord>,
+    /// Records no longer held (evicted or cleared).
+    pub dropped: u64,
+}
+
+// ── OTP vault ──
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OtpEnrollInput {
+    pub wallet_id: Uuid,
+    pub label: String,
+    /// Raw shared secret (the CA decodes the issuer's base32).
+    pub secret: Vec<u8>,
+    pub params: crate::otp::OtpParams,
+    /// Challenge commits to `otp::enroll_commitment(wallet_id, label, secret, params)`.
+    pub passkey_assertion: Option<PasskeyAssertion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OtpEnrollOutput {
+    /// Secrets the wallet holds after enrolling this one.
+    pub entries: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OtpCodeInput {
+    pub wallet_id: Uuid,
+    pub label: String,
+    /// Challenge commits to `otp::op_commitment(Code, wallet_id, label)`.
+    pub passkey_assertion: Option<PasskeyAssertion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OtpCodeOutput {
+    /// Zero-padded to the secret's digit count.
+    pub code: String,
+    /// TOTP time step or HOTP counter the code was made for.
+    pub counter: u64,
+    /// Seconds left in the TOTP step; 0 for HOTP.
+    pub valid_for_secs: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OtpRemoveInput {
+    pub wallet_id: Uuid,
+    pub label: String,
+    /// Challenge commits to `otp::op_commitment(Remove, wallet_id, label)`.
+    pub passkey_assertion: Option<PasskeyAssertion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OtpRemoveOutput {
+    /// False if nothing was enrolled under the label.
+    pub existed: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OtpListInput {
+    pub wallet_id: Uuid,
+    /// Challenge commits to `otp::op_commitment(List, wallet_id, "")`.
+    pub passkey_assertion: Option<PasskeyAssertion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OtpEntry {
+    pub label: String,
+    pub params: crate::otp::OtpParams,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OtpListOutput {
+    /// Sorted by label.
+    pub entries: Vec<OtpEntry>,
+}
+
+// ── Identity links ──
+
+/// The identity owner's approval of another wallet joining or leaving.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OwnerApproval {
+    pub wallet_id: Uuid,
+    /// Challenge commits to the same `identity::link_commitment` as the
+    /// wallet's own assertion.
+    pub passkey_assertion: Option<PasskeyAssertion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LinkWalletInput {
+    pub action: crate::identity::LinkAction,
+    pub identity_id: Uuid,
+    pub wallet_id: Uuid,
+    /// Path of the linked address.
+    pub hd_path: String,
+    /// Ascending; empty for an unlink.
+    pub chain_ids: Vec<u64>,
+    /// Challenge commits to `identity::link_commitment(action, identity_id,
+    /// wallet_id, hd_path, chain_ids, owner wallet)`.
+    pub passkey_assertion: Option<PasskeyAssertion>,
+    /// Required by the CA unless the wallet is the identity's owner.
+    pub owner: Option<OwnerApproval>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LinkWalletOutput {
+    pub address: [u8; 20],
+    /// `identity::link_statement`, stamped with the TA's clock.
+    pub statement: String,
+    /// 65-byte r ‖ s ‖ v personal_sign signature by `address`.
+    pub signature: Vec<u8>,
+}
+
+// ── Hardware-wallet migration ──
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MigrationOfferInput {
+    /// Parsed and depth-checked by the CA; the TA checks the key is a point.
+    pub account_xpub: crate::migration::AccountXpub,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MigrationOfferOutput {
+    pub session_id: [u8; 16],
+    /// Compressed secp256k1 public key the client encrypts the phrase to.
+    pub ephemeral_pubkey: Vec<u8>,
+    /// TA (REE) clock seconds; the offer lapses `MIGRATION_TTL_SECS` later.
+    pub created_at: u64,
+    /// Evidence with nonce = `migration::offer_digest`. `None` when the
+    /// attestation PTA is not available on this device.
+    pub attestation: Option<GetAttestationOutput>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MigrationImportInput {
+    pub session_id: [u8; 16],
+    /// The client's ephemeral key (compressed).
+    pub client_pubkey: Vec<u8>,
+    /// The space-separated recovery phrase, sealed to the offer's key.
+    pub ciphertext: Vec<u8>,
+    /// HMAC-SHA256 over `migration::sealed_aad` ‖ ciphertext.
+    pub mac: [u8; 32],
+    /// 65-byte uncompressed P-256 key the new wallet is bound to.
+    pub passkey_pubkey: Vec<u8>,
+    pub confirmed: Vec<crate::migration::ConfirmedAddress>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MigrationImportOutput {
+    pub wallet_id: Uuid,
+    /// `m/44'/60'/0'/0/0` of the new wallet.
+    pub address: [u8; 20],
+}
+
+// ── TA log levels ──
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SetLogLevelInput {
+    pub order: crate::log_level::LogLevelOrder,
+    /// 65-byte r ‖ s ‖ v personal_sign over `order.message()` by the pinned
+    /// deployment-policy signer.
+    pub signature: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SetLogLevelOutput {
+    /// The levels now in force.
+    pub levels: crate::log_level::LogLevels,
+    pub until: Option<u64>,
+    /// Sequence of the order this one replaced; 0 = none before.
+    pub previous_sequence: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OpenDecryptSessionInput {
+    pub wallet_id: Uuid,
+    pub scope: crate::ecies::DecryptScope,
+    /// Challenge commits to `ecies::session_commitment(wallet_id, scope)`.
+    pub passkey_assertion: Option<PasskeyAssertion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OpenDecryptSessionOutput {
+    pub session_id: [u8; 16],
+    /// TA clock, UNIX seconds.
+    pub expires_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct EciesDecryptInput {
+    pub wallet_id: Uuid,
+    pub session_id: [u8; 16],
+    /// The whole envelope, as `ecies::Envelope::parse` reads it.
+    pub payload: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct EciesDecryptOutput {
+    pub plaintext: Vec<u8>,
+    /// Decryptions the session has left.
+    pub remaining: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GetStealthMetaAddressInput {
+    pub wallet_id: Uuid,
+    /// Hardened account level of `m/5564'/60'/account'`.
+    pub account: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GetStealthMetaAddressOutput {
+    pub meta_address: crate::stealth::StealthMetaAddress,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StealthScanInput {
+    pub wallet_id: Uuid,
+    pub account: u32,
+    /// At most `stealth::MAX_SCAN_BATCH`.
+    pub candidates: Vec<crate::stealth::StealthCandidate>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StealthScanOutput {
+    /// Indexes into `candidates` of the announcements paid to this account.
+    pub matches: Vec<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SelfTestInput {}
+
+/// One known-answer test. `detail` says what differed; empty on a pass.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SelfTestCheck {
+    pub name: String,
+    pub passed: bool,
+    pub detail: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SelfTestOutput {
+    pub checks: Vec<SelfTestCheck>,
+}
+
+impl SelfTestOutput {
+    pub fn passed(&self) -> bool {
+        !self.checks.is_empty() && self.checks.iter().all(|c| c.passed)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ScavengeStorageInput {
+    /// `digest` of an earlier report: delete exactly that set. None only
+    /// reports.
+    pub confirm: Option<[u8; 32]>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ScavengeStorageOutput {
+    /// Per-wallet objects examined.
+    pub scanned: u32,
+    pub orphans: u32,
+    pub by_kind: Vec<(crate::storage_gc::ObjectKind, u32)>,
+    /// The first `storage_gc::MAX_LISTED` orphans.
+    pub listed: Vec<crate::storage_gc::Orphan>,
+    /// `storage_gc::orphan_set_digest` of all of them, taken before deleting.
+    pub digest: [u8; 32],
+    pub deleted: u32,
+    /// Removed by this TA instance's automatic pass after it was loaded.
+    pub scavenged_at_load: u32,
+}
+
+// ── Chunked transfers (`transfer`) ──
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BeginTransferInput {
+    /// The command the assembled input is for.
+    pub command: u32,
+    pub input_len: u32,
+    /// `transfer::payload_digest` of the whole input.
+    pub input_digest: [u8; 32],
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BeginTransferOutput {
+    pub transfer_id: u64,
+    pub chunk_len: u32,
+}
+
+/// With a chunk: the next input chunk. Without: fetch the next output
+/// chunk, after EndTransfer.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ContinueTransferInput {
+    pub transfer_id: u64,
+    pub chunk: Option<crate::transfer::TransferChunk>,
+}
+
+/// The output chunk asked for; None while sending input.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ContinueTransferOutput {
+    pub chunk: Option<crate::transfer::TransferChunk>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct EndTransferInput {
+    pub transfer_id: u64,
+}
+
+/// The command ran; its output waits in the TA for ContinueTransfer.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct EndTransferOutput {
+    pub output_len: u32,
+    /// `transfer::payload_digest` of the whole output.
+    pub output_digest: [u8; 32],
+    pub chunks: u32,
+}
+
+// ── Wallet import ──
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ImportWalletInput {
+    /// 65-byte uncompressed P-256 key the new wallet is bound to.
+    pub passkey_pubkey: Vec<u8>,
+    /// 24 space-separated English BIP39 words; the seed uses the empty
+    /// passphrase, as for every wallet the TA creates.
+    pub phrase: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ImportWalletOutput {
+    pub wallet_id: Uuid,
+    /// `m/44'/60'/0'/0/0` of the new wallet.
+    pub address: [u8; 20],
+}
+
+// ── EIP-1559 signing ──
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SignEip1559TransactionInput {
+    pub wallet_id: Uuid,
+    pub hd_path: String,
+    pub transaction: crate::eip1559::Eip1559Transaction,
+    /// As for `SignTransactionInput`, over `Eip1559Transaction::signing_hash`.
+    #[serde(default)]
+    pub passkey_assertion: Option<PasskeyAssertion>,
+    #[serde(default)]
+    pub summary_abis: Vec<String>,
+    #[serde(default)]
+    pub summary_committed: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SignEip1559TransactionOutput {
+    /// `0x02 ‖ rlp([..., y_parity, r, s])`, ready for eth_sendRawTransaction.
+    pub raw_transaction: Vec<u8>,
+}
+
+// ── Spending policy ──
+
+/// Reads the policy with `set` None; otherwise replaces it, which needs a
+/// passkey committed to `spending_policy::policy_commitment`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SpendingPolicyInput {
+    pub wallet_id: Uuid,
+    pub set: Option<crate::spending_policy::SpendingPolicy>,
+    pub passkey_assertion: Option<PasskeyAssertion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SpendingPolicyOutput {
+    /// In force after the call.
+    pub policy: crate::spending_policy::SpendingPolicy,
+    /// Replaced by this call; None on a read.
+    pub previous: Option<crate::spending_policy::SpendingPolicy>,
+    /// Native value counted against today's limit (UTC, TA clock).
+    pub spent_today_wei: u128,
+}
+
+// ── Multi-sig approval ──
+
+/// Sets the co-signer policy, or removes it with None; either way held
+/// operations are dropped. Needs a passkey committed to
+/// `multisig::policy_commitment`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SetMultiSigPolicyInput {
+    pub wallet_id: Uuid,
+    pub policy: Option<crate::multisig::MultiSigPolicy>,
+    pub passkey_assertion: Option<PasskeyAssertion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SetMultiSigPolicyOutput {
+    pub previous: Option<crate::multisig::MultiSigPolicy>,
+    /// Held operations the change discarded.
+    pub dropped: u32,
+}
+
+/// No passkey: the co-signer's signature over
+/// `multisig::approval_digest` is the credential.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ApproveMultiSigInput {
+    pub wallet_id: Uuid,
+    /// From `MultiSigPending`.
+    pub operation: [u8; 32],
+    /// One of the policy's co-signer keys.
+    pub cosigner_key: Vec<u8>,
+    /// Compact r ‖ s.
+    pub signature: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ApproveMultiSigOutput {
+    pub approvals: u8,
+    pub required: u8,
+    /// Set by the approval that reaches `required`.
+    pub released: Option<MultiSigRelease>,
+}
+
+/// A held Sign's result, as SignTransaction / SignEip1559Transaction
+/// would have answered it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MultiSigRelease {
+    pub hd_path: String,
+    pub signed: Vec<u8>,
+}
+
+// ── Social recovery ──
+
+/// Registers guardians, or removes them with None; either way the round in
+/// progress ends. Needs a passkey committed to
+/// `social_recovery::guardians_commitment`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SetGuardiansInput {
+    pub wallet_id: Uuid,
+    pub guardians: Option<crate::social_recovery::GuardianSet>,
+    pub passkey_assertion: Option<PasskeyAssertion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SetGuardiansOutput {
+    pub previous: Option<crate::social_recovery::GuardianSet>,
+    /// A round with attestations was discarded.
+    pub cancelled: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RecoveryStatusInput {
+    pub wallet_id: Uuid,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RecoveryStatusOutput {
+    pub status: crate::social_recovery::RecoveryStatus,
+}
+
+/// No passkey: the guardian's signature over
+/// `social_recovery::attestation_digest` is the credential.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RecoveryAttestInput {
+    pub wallet_id: Uuid,
+    /// From `RecoveryStatus`; a stale round is refused.
+    pub round: u64,
+    /// One of the registered guardian keys.
+    pub guardian_key: Vec<u8>,
+    /// Uncompressed P-256 (0x04 ‖ x ‖ y), as for RegisterPasskeyTa.
+    pub new_passkey: Vec<u8>,
+    /// Compact r ‖ s.
+    pub signature: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RecoveryAttestOutput {
+    pub status: crate::social_recovery::RecoveryStatus,
+    /// This attestation reached the threshold and started the timelock.
+    pub armed_now: bool,
+}
+
+/// Needs the current passkey committed to
+/// `social_recovery::cancel_commitment` for the round in progress.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CancelRecoveryInput {
+    pub wallet_id: Uuid,
+    pub passkey_assertion: Option<PasskeyAssertion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CancelRecoveryOutput {
+    pub status: crate::social_recovery::RecoveryStatus,
+}
+
+/// No credential: the guardians' attestations and the elapsed timelock
+/// are the authorization.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CompleteRecoveryInput {
+    pub wallet_id: Uuid,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CompleteRecoveryOutput {
+    /// The wallet's passkey from now on.
+    pub passkey_pubkey: Vec<u8>,
+    /// The round that follows.
+    pub round: u64,
+}
+
+// ── Encrypted backups ──
+
+/// Needs the passkey committed to `backup::export_commitment(wallet_id,
+/// kdf)`; the kdf must be Argon2id.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ExportEncryptedBackupInput {
+    pub wallet_id: Uuid,
+    pub password: String,
+    pub kdf: crate::kdf::KdfConfig,
+    pub passkey_assertion: Option<PasskeyAssertion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ExportEncryptedBackupOutput {
+    pub backup: crate::backup::EncryptedBackup,
+}
+
+/// No passkey: the password and the device that sealed the backup are
+/// the authorization. Refused if the wallet already exists here.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ImportEncryptedBackupInput {
+    pub backup: crate::backup::EncryptedBackup,
+    pub password: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ImportEncryptedBackupOutput {
+    pub wallet_id: Uuid,
+    /// m/44'/60'/0'/0/0 of the restored wallet.
+    pub address: [u8; 20],
+    /// The passkey the wallet is bound to again.
+    pub passkey_pubkey: Vec<u8>,
+}
+
+// ── Nonce tracking ──
+
+/// Reads the ledger with `set` None; otherwise turns tracking on or off,
+/// which needs a passkey committed to `nonce_guard::tracking_commitment`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NonceTrackingInput {
+    pub wallet_id: Uuid,
+    pub set: Option<bool>,
+    pub passkey_assertion: Option<PasskeyAssertion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NonceTrackingOutput {
+    /// In force after the call.
+    pub enabled: bool,
+    /// Replaced by this call; None on a read.
+    pub previous: Option<bool>,
+    pub entries: Vec<crate::nonce_guard::NonceEntry>,
+    pub pending_override: Option<crate::nonce_guard::NonceOverride>,
+}
+
+/// Needs a passkey committed to `nonce_guard::override_commitment`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OverrideNonceInput {
+    pub wallet_id: Uuid,
+    pub chain_id: u64,
+    /// The signer (derived) address, not the wallet's first one.
+    pub address: [u8; 20],
+    pub nonce: u128,
+    pub passkey_assertion: Option<PasskeyAssertion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OverrideNonceOutput {
+    pub armed: crate::nonce_guard::NonceOverride,
+    /// Highest nonce signed for the pair so far.
+    pub highest: Option<u128>,
+}
+
+// ── Solana (ed25519) ──
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DeriveSolanaAddressInput {
+    pub wallet_id: Uuid,
+    /// `m/44'/501'/account'/0'` (`solana::parse_solana_path`).
+    pub hd_path: String,
+    pub passkey_assertion: Option<PasskeyAssertion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DeriveSolanaAddressOutput {
+    pub public_key: [u8; 32],
+    /// Base58 of `public_key`.
+    pub address: String,
+}
+
+/// Needs a passkey committed to `solana::signing_payload(message)`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SignSolanaMessageInput {
+    pub wallet_id: Uuid,
+    pub hd_path: String,
+    /// Signed as given, at most `solana::MAX_MESSAGE_LEN` bytes.
+    pub message: Vec<u8>,
+    pub passkey_assertion: Option<PasskeyAssertion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SignSolanaMessageOutput {
+    /// 64-byte ed25519 signature.
+    pub signature: Vec<u8>,
+    pub public_key: [u8; 32],
+}
+
+// ── Threshold ECDSA (2-of-2) ──
+
+/// Needs a passkey committed to `threshold::keygen_commitment`. Refused if
+/// the wallet already has a threshold key; a keygen in progress restarts.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ThresholdKeygenBeginInput {
+    pub wallet_id: Uuid,
+    pub passkey_assertion: Option<PasskeyAssertion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ThresholdKeygenBeginOutput {
+    pub commit: crate::threshold::KeygenCommit,
+}
+
+/// No passkey: it finishes the keygen the passkey began.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ThresholdKeygenFinishInput {
+    pub wallet_id: Uuid,
+    pub cosigner_key: crate::threshold::PartyKey,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ThresholdKeygenFinishOutput {
+    /// For the co-signer's `cosigner_keygen_finish`.
+    pub reveal: crate::threshold::KeygenReveal,
+    /// Joint public key, compressed.
+    pub public_key: Vec<u8>,
+    pub address: [u8; 20],
+}
+
+/// No passkey: a presignature signs nothing by itself. Replaces a batch
+/// still waiting for its replies.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ThresholdPresignBeginInput {
+    pub wallet_id: Uuid,
+    /// 1 to `threshold::MAX_PRESIGN_BATCH`, within the pool's room.
+    pub count: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ThresholdPresignBeginOutput {
+    pub commits: Vec<crate::threshold::PresignCommit>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ThresholdPresignFinishInput {
+    pub wallet_id: Uuid,
+    /// The co-signer's replies, in the order of the commits.
+    pub replies: Vec<crate::threshold::PresignReply>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ThresholdPresignFinishOutput {
+    /// For the co-signer's `cosigner_presign_finish`.
+    pub reveals: Vec<crate::threshold::PresignReveal>,
+    /// Presignatures in the pool now.
+    pub available: u32,
+}
+
+/// Needs a passkey committed to `threshold::sign_commitment`. The
+/// presignature `reply` names is spent, with every older one, before the
+/// signature is computed.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ThresholdSignInput {
+    pub wallet_id: Uuid,
+    pub digest: [u8; 32],
+    /// The co-signer's half; None once the key is recombined.
+    pub reply: Option<crate::threshold::SignReply>,
+    pub passkey_assertion: Option<PasskeyAssertion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ThresholdSignOutput {
+    /// 65 bytes r‖s‖v, v = 27/28.
+    pub signature: Vec<u8>,
+    /// Presignatures left in the pool.
+    pub available: u32,
+}
+
+/// Needs a passkey committed to `threshold::recombine_commitment`. The
+/// wallet's threshold key becomes a TA-only key; presignatures are dropped.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ThresholdRecombineInput {
+    pub wallet_id: Uuid,
+    /// The co-signer's share, ECIES-sealed to the TA's share point
+    /// (`threshold::cosigner_seal_share`).
+    pub sealed_share: Vec<u8>,
+    /// secp256k1 public key to seal the whole private key to; None keeps it
+    /// in the TA.
+    pub export_to: Option<Vec<u8>>,
+    pub passkey_assertion: Option<PasskeyAssertion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ThresholdRecombineOutput {
+    pub address: [u8; 20],
+    /// The private key sealed to `export_to`, as an `ecies::Envelope`.
+    pub exported: Option<Vec<u8>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ThresholdStatusInput {
+    pub wallet_id: Uuid,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ThresholdStatusOutput {
+    /// None until a keygen finishes.
+    pub public_key: Option<Vec<u8>>,
+    pub address: Option<[u8; 20]>,
+    pub available: u32,
+    /// The co-signer's share was taken in; signing no longer needs it.
+    pub recombined: bool,
+}
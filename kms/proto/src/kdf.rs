@@ -0,0 +1,461 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Passphrase keystores.
+//!
+//! A [`Keystore`] is the `crypto` section of a Web3 Secret Storage v3 file:
+//! AES-128-CTR under the first half of a passphrase-derived key, MAC =
+//! keccak256(second half ‖ ciphertext). [`KdfConfig`] picks the derivation
+//! per keystore — PBKDF2-HMAC-SHA256 and scrypt as in the v3 spec, plus
+//! Argon2id (`"kdf": "argon2id"`, outside the spec).
+//!
+//! The derivation and the cipher are behind the `kdf` feature, built with
+//! `default-features = false` so they run inside the TA. All bounds are set
+//! by the TA heap: a memory-hard KDF cannot use more than the TEE has.
+
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+use std::convert::TryFrom;
+use std::fmt;
+use uuid::Uuid;
+
+/// Largest KDF working set the TA accepts. The TA heap is 1 MiB
+/// (`ta_data_size` in ta/build.rs) and is shared with the wallet cache.
+pub const MAX_KDF_MEMORY_KIB: u32 = 512;
+pub const MIN_PBKDF2_ITERATIONS: u32 = 100_000;
+pub const MAX_PBKDF2_ITERATIONS: u32 = 10_000_000;
+pub const MIN_SCRYPT_LOG_N: u8 = 10;
+/// Argon2id with little memory needs more passes (RFC 9106 §4).
+pub const MIN_ARGON2_ITERATIONS: u32 = 3;
+pub const MAX_ARGON2_ITERATIONS: u32 = 256;
+pub const DERIVED_KEY_LEN: usize = 32;
+/// Longest secret a keystore carries (a BIP39 seed is the largest we store).
+pub const MAX_KEYSTORE_SECRET_LEN: usize = 64;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KdfConfig {
+    /// PBKDF2-HMAC-SHA256 (`"kdf": "pbkdf2"`, `"prf": "hmac-sha256"`).
+    Pbkdf2Sha256 { iterations: u32 },
+    /// scrypt with N = 2^log_n.
+    Scrypt { log_n: u8, r: u32, p: u32 },
+    /// Argon2id v1.3; `memory_kib` is m in KiB.
+    Argon2id {
+        memory_kib: u32,
+        iterations: u32,
+        parallelism: u32,
+    },
+}
+
+/// Argon2id with the whole TEE budget. The pass count makes up for the
+/// memory the TA cannot give (RFC 9106 asks for 64 MiB at t = 3).
+impl Default for KdfConfig {
+    fn default() -> Self {
+        KdfConfig::Argon2id {
+            memory_kib: MAX_KDF_MEMORY_KIB,
+            iterations: 16,
+            parallelism: 1,
+        }
+    }
+}
+
+impl KdfConfig {
+    /// The v3 `kdf` field.
+    pub fn name(&self) -> &'static str {
+        match self {
+            KdfConfig::Pbkdf2Sha256 { .. } => "pbkdf2",
+            KdfConfig::Scrypt { .. } => "scrypt",
+            KdfConfig::Argon2id { .. } => "argon2id",
+        }
+    }
+
+    /// Default parameters for a KDF name, as accepted by `FromStr`.
+    pub fn defaults_for(name: &str) -> Option<Self> {
+        match name {
+            "pbkdf2" => Some(KdfConfig::Pbkdf2Sha256 {
+                iterations: 262_144,
+            }),
+            "scrypt" => Some(KdfConfig::Scrypt {
+                log_n: 12,
+                r: 1,
+                p: 8,
+            }),
+            "argon2id" => Some(KdfConfig::default()),
+            _ => None,
+        }
+    }
+
+    /// Working memory in KiB (0 for PBKDF2).
+    pub fn memory_kib(&self) -> u64 {
+        match *self {
+            KdfConfig::Pbkdf2Sha256 { .. } => 0,
+            KdfConfig::Scrypt { log_n, r, .. } => (128u64 * r as u64) << log_n >> 10,
+            KdfConfig::Argon2id { memory_kib, .. } => memory_kib as u64,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), &'static str> {
+        match *self {
+            KdfConfig::Pbkdf2Sha256 { iterations } => {
+                if !(MIN_PBKDF2_ITERATIONS..=MAX_PBKDF2_ITERATIONS).contains(&iterations) {
+                    return Err("pbkdf2 iterations must be between 100000 and 10000000");
+                }
+            }
+            KdfConfig::Scrypt { log_n, r, p } => {
+                if !(MIN_SCRYPT_LOG_N..=20).contains(&log_n) {
+                    return Err("scrypt log_n must be between 10 and 20");
+                }
+                if !(1..=8).contains(&r) || !(1..=16).contains(&p) {
+                    return Err("scrypt r must be 1-8 and p 1-16");
+                }
+            }
+            KdfConfig::Argon2id {
+                memory_kib,
+                iterations,
+                parallelism,
+            } => {
+                if !(1..=4).contains(&parallelism) {
+                    return Err("argon2id parallelism must be 1-4");
+                }
+                if memory_kib < 64 || memory_kib < 8 * parallelism {
+                    return Err("argon2id memory must be at least 64 KiB and 8 KiB per lane");
+                }
+                if !(MIN_ARGON2_ITERATIONS..=MAX_ARGON2_ITERATIONS).contains(&iterations) {
+                    return Err("argon2id iterations must be between 3 and 256");
+                }
+            }
+        }
+        if self.memory_kib() > MAX_KDF_MEMORY_KIB as u64 {
+            return Err("kdf memory exceeds the TEE budget (512 KiB)");
+        }
+        Ok(())
+    }
+}
+
+/// `argon2id:m=512,t=16,p=1`, `scrypt:log_n=12,r=1,p=8`, `pbkdf2:c=262144`.
+impl fmt::Display for KdfConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            KdfConfig::Pbkdf2Sha256 { iterations } => write!(f, "pbkdf2:c={}", iterations),
+            KdfConfig::Scrypt { log_n, r, p } => {
+                write!(f, "scrypt:log_n={},r={},p={}", log_n, r, p)
+            }
+            KdfConfig::Argon2id {
+                memory_kib,
+                iterations,
+                parallelism,
+            } => write!(
+                f,
+                "argon2id:m={},t={},p={}",
+                memory_kib, iterations, parallelism
+            ),
+        }
+    }
+}
+
+/// Parses the `Display` form; a bare name or missing parameters take
+/// [`KdfConfig::defaults_for`]. The result is validated.
+impl std::str::FromStr for KdfConfig {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, params) = match s.trim().split_once(':') {
+            Some((name, params)) => (name, params),
+            None => (s.trim(), ""),
+        };
+        let mut kdf =
+            KdfConfig::defaults_for(name).ok_or("kdf must be argon2id, scrypt or pbkdf2")?;
+        for param in params.split(',').filter(|p| !p.is_empty()) {
+            let (key, value) = param
+                .split_once('=')
+                .ok_or("kdf parameter must be key=value")?;
+            let value: u32 = value
+                .trim()
+                .parse()
+                .map_err(|_| "kdf parameter must be a number")?;
+            match (&mut kdf, key.trim()) {
+                (KdfConfig::Pbkdf2Sha256 { iterations }, "c") => *iterations = value,
+                (KdfConfig::Scrypt { log_n, .. }, "log_n") => {
+                    *log_n = u8::try_from(value).map_err(|_| "scrypt log_n out of range")?
+                }
+                (KdfConfig::Scrypt { r, .. }, "r") => *r = value,
+                (KdfConfig::Scrypt { p, .. }, "p") => *p = value,
+                (KdfConfig::Argon2id { memory_kib, .. }, "m") => *memory_kib = value,
+                (KdfConfig::Argon2id { iterations, .. }, "t") => *iterations = value,
+                (KdfConfig::Argon2id { parallelism, .. }, "p") => *parallelism = value,
+                _ => return Err("unknown kdf parameter"),
+            }
+        }
+        kdf.validate()?;
+        Ok(kdf)
+    }
+}
+
+/// The `crypto` section of a v3 keystore, binary form. The CA renders it
+/// to and from the JSON file; the TA seals and opens it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Keystore {
+    pub kdf: KdfConfig,
+    pub salt: Vec<u8>,
+    pub iv: [u8; 16],
+    pub ciphertext: Vec<u8>,
+    pub mac: [u8; 32],
+}
+
+impl Keystore {
+    pub fn validate(&self) -> Result<(), &'static str> {
+        self.kdf.validate()?;
+        if !(16..=64).contains(&self.salt.len()) {
+            return Err("keystore salt must be 16-64 bytes");
+        }
+        if self.ciphertext.is_empty() || self.ciphertext.len() > MAX_KEYSTORE_SECRET_LEN {
+            return Err("keystore ciphertext must be 1-64 bytes");
+        }
+        Ok(())
+    }
+}
+
+/// keccak256(dk[16..32] ‖ ciphertext), the v3 MAC.
+pub fn keystore_mac(derived_key: &[u8; DERIVED_KEY_LEN], ciphertext: &[u8]) -> [u8; 32] {
+    let mut h = Keccak256::new();
+    h.update(&derived_key[16..]);
+    h.update(ciphertext);
+    h.finalize().into()
+}
+
+/// Passkey commitment for `ExportKeystore`: the assertion approves this
+/// wallet's export under these KDF parameters, so the CA cannot lower them.
+pub fn export_commitment(wallet_id: &Uuid, kdf: &KdfConfig) -> [u8; 32] {
+    let mut h = Keccak256::new();
+    h.update(b"AA-EXPORT-KEYSTORE-v1");
+    h.update(wallet_id.as_bytes());
+    h.update(kdf.to_string().as_bytes());
+    h.finalize().into()
+}
+
+#[cfg(feature = "kdf")]
+impl KdfConfig {
+    /// Derive the 32-byte keystore key. Runs in the TA.
+    pub fn derive(
+        &self,
+        passphrase: &[u8],
+        salt: &[u8],
+    ) -> Result<[u8; DERIVED_KEY_LEN], &'static str> {
+        self.validate()?;
+        let mut dk = [0u8; DERIVED_KEY_LEN];
+        match *self {
+            KdfConfig::Pbkdf2Sha256 { iterations } => {
+                pbkdf2::pbkdf2_hmac::<sha2::Sha256>(passphrase, salt, iterations, &mut dk)
+            }
+            KdfConfig::Scrypt { log_n, r, p } => {
+                let params = scrypt::Params::new(log_n, r, p, DERIVED_KEY_LEN)
+                    .map_err(|_| "invalid scrypt parameters")?;
+                scrypt::scrypt(passphrase, salt, &params, &mut dk)
+                    .map_err(|_| "scrypt derivation failed")?
+            }
+            KdfConfig::Argon2id {
+                memory_kib,
+                iterations,
+                parallelism,
+            } => {
+                let params =
+                    argon2::Params::new(memory_kib, iterations, parallelism, Some(DERIVED_KEY_LEN))
+                        .map_err(|_| "invalid argon2id parameters")?;
+                argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params)
+                    .hash_password_into(passphrase, salt, &mut dk)
+                    .map_err(|_| "argon2id derivation failed")?
+            }
+        }
+        Ok(dk)
+    }
+}
+
+#[cfg(feature = "kdf")]
+impl Keystore {
+    /// Encrypt `secret`. Salt and IV come from the caller (the TA RNG).
+    pub fn seal(
+        secret: &[u8],
+        passphrase: &[u8],
+        kdf: KdfConfig,
+        salt: Vec<u8>,
+        iv: [u8; 16],
+    ) -> Result<Self, &'static str> {
+        let mut keystore = Keystore {
+            kdf,
+            salt,
+            iv,
+            ciphertext: secret.to_vec(),
+            mac: [0u8; 32],
+        };
+        keystore.validate()?;
+        let mut dk = kdf.derive(passphrase, &keystore.salt)?;
+        aes_128_ctr(&dk, &iv, &mut keystore.ciphertext);
+        keystore.mac = keystore_mac(&dk, &keystore.ciphertext);
+        dk.fill(0);
+        Ok(keystore)
+    }
+
+    /// Check the MAC and decrypt. A wrong passphrase fails the MAC.
+    pub fn open(&self, passphrase: &[u8]) -> Result<Vec<u8>, &'static str> {
+        self.validate()?;
+        let mut dk = self.kdf.derive(passphrase, &self.salt)?;
+        let mac = keystore_mac(&dk, &self.ciphertext);
+        let diff = mac
+            .iter()
+            .zip(self.mac.iter())
+            .fold(0u8, |d, (a, b)| d | (a ^ b));
+        if diff != 0 {
+            dk.fill(0);
+            return Err("keystore MAC mismatch (wrong passphrase?)");
+        }
+        let mut secret = self.ciphertext.clone();
+        aes_128_ctr(&dk, &self.iv, &mut secret);
+        dk.fill(0);
+        Ok(secret)
+    }
+}
+
+#[cfg(feature = "kdf")]
+fn aes_128_ctr(derived_key: &[u8; DERIVED_KEY_LEN], iv: &[u8; 16], buf: &mut [u8]) {
+    use ctr::cipher::{KeyIvInit, StreamCipher};
+    let mut cipher = ctr::Ctr128BE::<aes::Aes128>::new(derived_key[..16].into(), iv.into());
+    cipher.apply_keystream(buf);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "kdf")]
+    use std::convert::TryInto;
+
+    #[test]
+    fn kdf_spec_roundtrips_and_fills_defaults() {
+        for kdf in [
+            KdfConfig::default(),
+            KdfConfig::Pbkdf2Sha256 {
+                iterations: 600_000,
+            },
+            KdfConfig::Scrypt {
+                log_n: 11,
+                r: 2,
+                p: 4,
+            },
+        ] {
+            assert_eq!(kdf.to_string().parse::<KdfConfig>(), Ok(kdf));
+        }
+        assert_eq!("argon2id".parse::<KdfConfig>(), Ok(KdfConfig::default()));
+        assert_eq!(
+            "pbkdf2".parse::<KdfConfig>(),
+            Ok(KdfConfig::Pbkdf2Sha256 {
+                iterations: 262_144
+            })
+        );
+        assert_eq!(
+            "argon2id:t=32".parse::<KdfConfig>(),
+            Ok(KdfConfig::Argon2id {
+                memory_kib: 512,
+                iterations: 32,
+                parallelism: 1
+            })
+        );
+        assert!("bcrypt".parse::<KdfConfig>().is_err());
+        assert!("scrypt:m=4".parse::<KdfConfig>().is_err());
+    }
+
+    #[test]
+    fn kdf_bounds_follow_the_tee_heap() {
+        // 1 MiB scrypt working set, 64 MiB argon2id: fine on a host, not in the TA.
+        assert!("scrypt:log_n=13,r=1".parse::<KdfConfig>().is_err());
+        assert!("argon2id:m=65536,t=3".parse::<KdfConfig>().is_err());
+        assert!("scrypt:log_n=12,r=1".parse::<KdfConfig>().is_ok());
+        assert!("pbkdf2:c=1000".parse::<KdfConfig>().is_err());
+        assert!("argon2id:m=512,t=1".parse::<KdfConfig>().is_err());
+    }
+
+    #[test]
+    fn export_commitment_binds_kdf_parameters() {
+        let id = Uuid::from_bytes([7u8; 16]);
+        let strong = KdfConfig::default();
+        let weak = KdfConfig::Argon2id {
+            memory_kib: 64,
+            iterations: 3,
+            parallelism: 1,
+        };
+        assert_ne!(
+            export_commitment(&id, &strong),
+            export_commitment(&id, &weak)
+        );
+    }
+
+    #[cfg(feature = "kdf")]
+    fn unhex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    // Web3 Secret Storage v3 test vector (PBKDF2-SHA-256).
+    #[cfg(feature = "kdf")]
+    #[test]
+    fn opens_web3_secret_storage_pbkdf2_vector() {
+        let keystore = Keystore {
+            kdf: KdfConfig::Pbkdf2Sha256 {
+                iterations: 262_144,
+            },
+            salt: unhex("ae3cd4e7013836a3df6bd7241b12db061dbe2c6785853cce422d148a624ce0bd"),
+            iv: unhex("6087dab2f9fdbbfaddc31a909735c1e6")
+                .try_into()
+                .unwrap(),
+            ciphertext: unhex("5318b4d5bcd28de64ee5559e671353e16f075ecae9f99c7a79a38af5f869aa46"),
+            mac: unhex("517ead924a9d0dc3124507e3393d175ce3ff7c1e96529c6c555ce9e51205e9b2")
+                .try_into()
+                .unwrap(),
+        };
+        assert_eq!(
+            keystore.open(b"testpassword").unwrap(),
+            unhex("7a28b5ba57c53603b0b07b56bba752f7784bf506fa95edc395f5cf6c7514fe9d")
+        );
+        assert!(keystore.open(b"testpassword!").is_err());
+    }
+
+    #[cfg(feature = "kdf")]
+    #[test]
+    fn seal_open_roundtrip_per_kdf() {
+        let secret = [0x5au8; 32];
+        for kdf in [
+            KdfConfig::Argon2id {
+                memory_kib: 64,
+                iterations: 3,
+                parallelism: 1,
+            },
+            KdfConfig::Scrypt {
+                log_n: 10,
+                r: 1,
+                p: 1,
+            },
+            KdfConfig::Pbkdf2Sha256 {
+                iterations: 100_000,
+            },
+        ] {
+            let ks =
+                Keystore::seal(&secret, b"correct horse", kdf, vec![1u8; 32], [2u8; 16]).unwrap();
+            assert_ne!(ks.ciphertext, secret);
+            assert_eq!(ks.open(b"correct horse").unwrap(), secret);
+            assert!(ks.open(b"wrong horse").is_err(), "{}", kdf);
+        }
+    }
+}
@@ -17,7 +17,53 @@
 
 use num_enum::{FromPrimitive, IntoPrimitive};
 
+pub mod aggregator;
+pub mod amount;
+pub mod backup;
+pub mod bip85;
+pub mod buffers;
+pub mod calldata;
+pub mod chain;
+mod civil;
+pub mod crash_dump;
+pub mod dapp_storage;
+pub mod deployment_policy;
+pub mod ecies;
+pub mod eip1559;
+pub mod eip55;
+pub mod erasure;
+pub mod event;
+pub mod hd_path;
+pub mod identity;
 mod in_out;
+pub mod kdf;
+pub mod log_level;
+pub mod migration;
+pub mod multisig;
+pub mod nonce_guard;
+pub mod offline;
+pub mod otp;
+pub mod paymaster;
+pub mod permit;
+pub mod provider;
+pub mod refresh_token;
+pub mod remote_approval;
+pub mod replication;
+pub mod secure_display;
+pub mod siwe;
+pub mod social_recovery;
+pub mod solana;
+pub mod spending_policy;
+pub mod stealth;
+pub mod storage_gc;
+pub mod storage_quota;
+pub mod ta_config;
+pub mod tamper;
+pub mod threshold;
+pub mod transfer;
+pub mod tx_builder;
+pub mod wallet_id;
+pub mod wire;
 pub use in_out::*;
 
 #[derive(FromPrimitive, IntoPrimitive, Debug, Copy, Clone, PartialEq, Eq)]
@@ -94,10 +140,219 @@ pub enum Command {
     /// a PoP for a given operator, never a forgery on a chosen message. Host loopback
     /// /pop, token-gated. Output is the EIP-2537 G2 pop signature.
     BlsPopSign = 34,
+    /// Issue a scoped AA session key: the TA generates a secondary secp256k1
+    /// key, the root wallet key signs its permission object (targets, selectors,
+    /// per-call value cap, expiry). Passkey-gated.
+    CreateScopedSessionKey = 35,
+    /// Sign a userOpHash with a scoped session key after the TA has checked the
+    /// declared call against the signed scope and the revocation list.
+    SignWithSessionKey = 36,
+    /// Revoke a scoped session key: its index goes on the TA-side revocation
+    /// list (effective immediately) and the sealed key is deleted.
+    RevokeScopedSessionKey = 37,
+    /// BLS-sign a beacon-chain attestation with the sealed BLS key. The TA
+    /// computes the SSZ signing root from the fields and refuses double or
+    /// surround votes against its secure-storage slashing record.
+    BlsSignAttestation = 38,
+    /// BLS-sign a beacon block header; refuses a second block at or below the
+    /// last signed slot.
+    BlsSignBlock = 39,
+    /// Per-command latency histograms recorded by the TA dispatcher since the
+    /// TA instance was loaded (in-memory only, reset on TA restart).
+    TaStats = 40,
+    /// Set the wallet's allowance guard (reject or cap ERC-20 approvals).
+    /// Passkey-gated; the rule is committed in the challenge.
+    SetAllowancePolicy = 41,
+    /// Second confirmation for one transaction that breaks the allowance
+    /// guard. Arms a single-use override bound to that tx's summary digest.
+    ConfirmAllowanceOverride = 42,
+    /// Sign an air-gapped request exported by another CA. The TA enforces the
+    /// request's expiry and refuses a request id it has already signed.
+    SignOffline = 43,
+    /// Capabilities handshake: TA version, compiled features and the
+    /// installed deployment policy. Never disabled by a policy.
+    GetCapabilities = 44,
+    /// Install or replace the signed deployment policy (command allow-list).
+    InstallDeploymentPolicy = 45,
+    /// Multi-device replication, target side: fresh attested ephemeral key.
+    ReplicationOffer = 46,
+    /// Multi-device replication, source side: passkey-approved, encrypted
+    /// export of a wallet to an attested peer TA.
+    ReplicationExport = 47,
+    /// Multi-device replication, target side: store the exported wallet.
+    ReplicationImport = 48,
+    /// Apply a signed tamper order: failed-auth wipe limit, panic wipe or
+    /// unlock after a wipe.
+    TamperOrder = 49,
+    /// Wipe policy, failure count, lock state and the last wipe certificate.
+    GetTamperStatus = 50,
+    /// Sign an EIP-2612 or Permit2 permit after a passkey review of the
+    /// decoded spender, amount and deadline.
+    SignPermit = 51,
+    /// Replace the wallet's spender allow-list (passkey-confirmed).
+    SetSpenderAllowList = 52,
+    /// Encrypt the wallet entropy into a passphrase keystore under the
+    /// requested KDF. Dev/test TAs only (`export-secrets`), like ExportPrivateKey.
+    ExportKeystore = 53,
+    /// Create a passkey-bound wallet from a keystore the TA decrypts itself.
+    ImportKeystore = 54,
+    /// Install or replace the sealed TA config (limits, hardening flags),
+    /// signed by the provisioning key compiled into the TA.
+    InstallConfig = 55,
+    /// Store a small blob in a dapp's namespace of the wallet (passkey-approved,
+    /// quota-checked in the TA).
+    DappStoragePut = 56,
+    /// Read a blob from a dapp's namespace (passkey-approved).
+    DappStorageGet = 57,
+    /// Delete a blob from a dapp's namespace (passkey-approved).
+    DappStorageDelete = 58,
+    /// Derive a BIP85 child secret and seal it to a recipient key. Refused
+    /// unless an installed deployment policy leaves it enabled.
+    Bip85Export = 59,
+    /// Read (and optionally clear) the TA's postmortem records of internal
+    /// failures. Codes and counters only; answered while locked.
+    CrashDumps = 60,
+    /// Store a TOTP/HOTP secret for the wallet (passkey-approved).
+    OtpEnroll = 61,
+    /// Generate the current code of an enrolled secret (passkey-approved).
+    OtpCode = 62,
+    /// Delete an enrolled secret (passkey-approved).
+    OtpRemove = 63,
+    /// Labels and parameters of a wallet's secrets; never the secrets.
+    OtpList = 64,
+    /// Link a wallet to, or unlink it from, a CA-side identity: checks the
+    /// wallet's passkey (and the identity owner's) and signs the statement.
+    LinkWallet = 65,
+    /// Open a hardware-wallet migration: an ephemeral key bound to the
+    /// account xpub being migrated.
+    MigrationOffer = 66,
+    /// Decrypt the migrated phrase, cross-check it against the xpub and the
+    /// confirmed addresses, then create the wallet.
+    MigrationImport = 67,
+    /// Apply a signed log-level order: per-category trace verbosity, kept
+    /// in secure storage.
+    SetLogLevel = 68,
+    /// Open an ECIES decrypt session on one key of the wallet: a path, a
+    /// number of payloads and a lifetime the passkey approves. Refused
+    /// unless an installed deployment policy leaves decryption enabled.
+    OpenDecryptSession = 69,
+    /// Decrypt one ECIES payload sent to the session's key.
+    EciesDecrypt = 70,
+    /// The EIP-5564 stealth meta-address of one account of the wallet.
+    GetStealthMetaAddress = 71,
+    /// Check a batch of announcements against the account's viewing key;
+    /// returns the ones paid to it.
+    StealthScan = 72,
+    /// Known-answer tests of the TA's crypto, RNG and secure storage. Reads
+    /// nothing secret and writes nothing; the CA runs it on a schedule.
+    SelfTest = 73,
+    /// Report secure-storage objects no wallet references; delete them when
+    /// the input confirms the digest of the reported set.
+    ScavengeStorage = 74,
+    /// Read or replace the wallet's derivation-path allow-list, which the
+    /// TA checks before deriving any key of the wallet.
+    PathPolicy = 75,
+    /// Pair the phone whose approval SignTransaction needs from now on, or
+    /// unpair it; passkey-confirmed either way.
+    PairApprover = 76,
+    /// Check the paired phone's signature over a Sign payload and arm a
+    /// one-shot approval for it.
+    RemoteApprove = 77,
+    /// Switch the wallet to BLS signatures for an ERC-4337 signature
+    /// aggregator, or back to ECDSA; passkey-confirmed either way.
+    SetAggregator = 78,
+    /// BLS-sign a userOpHash with the wallet's aggregator key. Refused
+    /// unless the wallet is configured with an aggregator.
+    SignAggregatedUserOp = 79,
+    /// Open a chunked transfer for one command whose input or output is too
+    /// large for a single invoke (`transfer`).
+    BeginTransfer = 80,
+    /// Send the next input chunk, or fetch the next output chunk.
+    ContinueTransfer = 81,
+    /// Check the assembled input, run the command and hold its output.
+    EndTransfer = 82,
+    /// Create a wallet from a user-supplied 24-word BIP39 phrase, checksum
+    /// checked in the TA.
+    ImportWallet = 83,
+    /// Sign an EIP-1559 (type 2) transaction; the TA RLP-encodes and hashes
+    /// it and answers the broadcast-ready raw transaction (`eip1559`).
+    SignEip1559Transaction = 84,
+    /// Read or set the wallet's spending policy, which every transaction
+    /// signature is checked against (`spending_policy`).
+    SpendingPolicy = 85,
+    /// Set or remove the wallet's co-signer policy: transactions above its
+    /// threshold wait for M of N approvals (`multisig`).
+    SetMultiSigPolicy = 86,
+    /// One co-signer's approval of a held transaction; the M-th releases
+    /// the signature.
+    ApproveMultiSig = 87,
+    /// Register or remove the wallet's recovery guardians
+    /// (`social_recovery`).
+    SetGuardians = 88,
+    /// The guardian set and the recovery round in progress; no passkey.
+    RecoveryStatus = 89,
+    /// One guardian's attestation of a new passkey; the one that reaches
+    /// the threshold starts the timelock.
+    RecoveryAttest = 90,
+    /// The owner stops the recovery round in progress.
+    CancelRecovery = 91,
+    /// After the timelock, rebind the wallet to the attested passkey.
+    CompleteRecovery = 92,
+    /// Seal the wallet entropy under a password and the device sealing key
+    /// (`backup`).
+    ExportEncryptedBackup = 93,
+    /// Restore a wallet from an encrypted backup sealed on this device.
+    ImportEncryptedBackup = 94,
+    /// Read the wallet's nonce ledger, or turn tracking on or off
+    /// (`nonce_guard`).
+    NonceTracking = 95,
+    /// Allow one signature of a nonce at or below the highest signed.
+    OverrideNonce = 96,
+    /// The TA's protocol version range and highest command id; answered
+    /// even by a locked TA, so the CA can negotiate first (`wire`).
+    GetProtocolVersion = 97,
+    /// The ed25519 public key and Base58 address of a Solana account
+    /// (`solana`).
+    DeriveSolanaAddress = 98,
+    /// Sign a Solana message with the account's ed25519 key.
+    SignSolanaMessage = 99,
+    /// Start a 2-of-2 threshold key for the wallet: the TA's share,
+    /// committed for the co-signer (`threshold`, feature `threshold-ecdsa`).
+    ThresholdKeygenBegin = 100,
+    /// Take the co-signer's share point and open the commitment to it.
+    ThresholdKeygenFinish = 101,
+    /// Commit to a batch of nonces for the presignature pool.
+    ThresholdPresignBegin = 102,
+    /// Combine the co-signer's nonces into presignatures.
+    ThresholdPresignFinish = 103,
+    /// Finish a signature from the co-signer's reply, using one
+    /// presignature; after recombination, sign alone.
+    ThresholdSign = 104,
+    /// Fallback ceremony: take in the co-signer's sealed share, and
+    /// optionally export the whole key sealed to a given public key.
+    ThresholdRecombine = 105,
+    /// The threshold key's address and presignature count; no passkey.
+    ThresholdStatus = 106,
     #[default]
     Unknown,
 }
 
+/// Ids of removed commands (13 = JwtHmacSign, 16 = JwtSignPayload). They
+/// are never reassigned, so an old CA that sends one gets
+/// `UnsupportedVersion` instead of some other command.
+pub const RETIRED_COMMAND_IDS: [u32; 2] = [13, 16];
+
+/// TA UUID of the upstream eth_wallet example. An `eth-wallet-compat` TA is
+/// signed under this UUID so unmodified eth_wallet CAs can still open it.
+pub const ETH_WALLET_UUID: &str = "70e328e2-8bca-4bb9-a5be-e7e639b97ec0";
+
+/// Scheduling hint the CA puts in the low half of the value parameter's `b`
+/// field on every invoke (see [`wire::RequestHeader`]). The TA is
+/// single-threaded today and only logs it; a multi-threaded TA can use it to
+/// order its own queue.
+pub const PRIORITY_INTERACTIVE: u32 = 0;
+pub const PRIORITY_BATCH: u32 = 1;
+
 // If Uuid::parse_str() returns an InvalidLength error, there may be an extra
 // newline in your uuid.txt file. You can remove it by running
 // `truncate -s 36 uuid.txt`.
@@ -153,6 +408,78 @@ mod tests {
         assert_eq!(u32::from(Command::KeeperPubKey), 32);
         assert_eq!(u32::from(Command::BlsRemove), 33);
         assert_eq!(u32::from(Command::BlsPopSign), 34);
+        assert_eq!(u32::from(Command::CreateScopedSessionKey), 35);
+        assert_eq!(u32::from(Command::SignWithSessionKey), 36);
+        assert_eq!(u32::from(Command::RevokeScopedSessionKey), 37);
+        assert_eq!(u32::from(Command::BlsSignAttestation), 38);
+        assert_eq!(u32::from(Command::BlsSignBlock), 39);
+        assert_eq!(u32::from(Command::TaStats), 40);
+        assert_eq!(u32::from(Command::SetAllowancePolicy), 41);
+        assert_eq!(u32::from(Command::ConfirmAllowanceOverride), 42);
+        assert_eq!(u32::from(Command::SignOffline), 43);
+        assert_eq!(u32::from(Command::GetCapabilities), 44);
+        assert_eq!(u32::from(Command::InstallDeploymentPolicy), 45);
+        assert_eq!(u32::from(Command::ReplicationOffer), 46);
+        assert_eq!(u32::from(Command::ReplicationExport), 47);
+        assert_eq!(u32::from(Command::ReplicationImport), 48);
+        assert_eq!(u32::from(Command::TamperOrder), 49);
+        assert_eq!(u32::from(Command::GetTamperStatus), 50);
+        assert_eq!(u32::from(Command::SignPermit), 51);
+        assert_eq!(u32::from(Command::SetSpenderAllowList), 52);
+        assert_eq!(u32::from(Command::ExportKeystore), 53);
+        assert_eq!(u32::from(Command::ImportKeystore), 54);
+        assert_eq!(u32::from(Command::InstallConfig), 55);
+        assert_eq!(u32::from(Command::DappStoragePut), 56);
+        assert_eq!(u32::from(Command::DappStorageGet), 57);
+        assert_eq!(u32::from(Command::DappStorageDelete), 58);
+        assert_eq!(u32::from(Command::Bip85Export), 59);
+        assert_eq!(u32::from(Command::CrashDumps), 60);
+        assert_eq!(u32::from(Command::OtpEnroll), 61);
+        assert_eq!(u32::from(Command::OtpCode), 62);
+        assert_eq!(u32::from(Command::OtpRemove), 63);
+        assert_eq!(u32::from(Command::OtpList), 64);
+        assert_eq!(u32::from(Command::LinkWallet), 65);
+        assert_eq!(u32::from(Command::MigrationOffer), 66);
+        assert_eq!(u32::from(Command::MigrationImport), 67);
+        assert_eq!(u32::from(Command::SetLogLevel), 68);
+        assert_eq!(u32::from(Command::OpenDecryptSession), 69);
+        assert_eq!(u32::from(Command::EciesDecrypt), 70);
+        assert_eq!(u32::from(Command::GetStealthMetaAddress), 71);
+        assert_eq!(u32::from(Command::StealthScan), 72);
+        assert_eq!(u32::from(Command::SelfTest), 73);
+        assert_eq!(u32::from(Command::ScavengeStorage), 74);
+        assert_eq!(u32::from(Command::PathPolicy), 75);
+        assert_eq!(u32::from(Command::PairApprover), 76);
+        assert_eq!(u32::from(Command::RemoteApprove), 77);
+        assert_eq!(u32::from(Command::SetAggregator), 78);
+        assert_eq!(u32::from(Command::SignAggregatedUserOp), 79);
+        assert_eq!(u32::from(Command::BeginTransfer), 80);
+        assert_eq!(u32::from(Command::ContinueTransfer), 81);
+        assert_eq!(u32::from(Command::EndTransfer), 82);
+        assert_eq!(u32::from(Command::ImportWallet), 83);
+        assert_eq!(u32::from(Command::SignEip1559Transaction), 84);
+        assert_eq!(u32::from(Command::SpendingPolicy), 85);
+        assert_eq!(u32::from(Command::SetMultiSigPolicy), 86);
+        assert_eq!(u32::from(Command::ApproveMultiSig), 87);
+        assert_eq!(u32::from(Command::SetGuardians), 88);
+        assert_eq!(u32::from(Command::RecoveryStatus), 89);
+        assert_eq!(u32::from(Command::RecoveryAttest), 90);
+        assert_eq!(u32::from(Command::CancelRecovery), 91);
+        assert_eq!(u32::from(Command::CompleteRecovery), 92);
+        assert_eq!(u32::from(Command::ExportEncryptedBackup), 93);
+        assert_eq!(u32::from(Command::ImportEncryptedBackup), 94);
+        assert_eq!(u32::from(Command::NonceTracking), 95);
+        assert_eq!(u32::from(Command::OverrideNonce), 96);
+        assert_eq!(u32::from(Command::GetProtocolVersion), 97);
+        assert_eq!(u32::from(Command::DeriveSolanaAddress), 98);
+        assert_eq!(u32::from(Command::SignSolanaMessage), 99);
+        assert_eq!(u32::from(Command::ThresholdKeygenBegin), 100);
+        assert_eq!(u32::from(Command::ThresholdKeygenFinish), 101);
+        assert_eq!(u32::from(Command::ThresholdPresignBegin), 102);
+        assert_eq!(u32::from(Command::ThresholdPresignFinish), 103);
+        assert_eq!(u32::from(Command::ThresholdSign), 104);
+        assert_eq!(u32::from(Command::ThresholdRecombine), 105);
+        assert_eq!(u32::from(Command::ThresholdStatus), 106);
     }
 
     #[test]
@@ -185,46 +512,23 @@ mod tests {
 
     #[test]
     fn unknown_u32_maps_to_unknown() {
-        assert!(matches!(Command::from(99u32), Command::Unknown));
+        assert!(matches!(Command::from(10_000u32), Command::Unknown));
         assert!(matches!(Command::from(u32::MAX), Command::Unknown));
     }
 
     #[test]
     fn command_roundtrip() {
-        // 13 (JwtHmacSign) and 16 (JwtSignPayload) removed — JWT signing oracle closed (Issue #16)
-        let valid_ids: &[u32] = &[
-            0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 14, 15, 17, 18, 19, 20, 21, 22, 23, 24, 25,
-            26, 27, 28, 29, 30, 31, 32, 33, 34,
-        ];
-        for &i in valid_ids {
-            let cmd = Command::from(i);
-            assert_eq!(u32::from(cmd), i);
-        }
-        // Removed command IDs must map to Unknown (prevent silent ID reuse regression)
-        assert_eq!(Command::from(13), Command::Unknown);
-        assert_eq!(Command::from(16), Command::Unknown);
-    }
-
-    /// P0-3 regression guard: every assigned command ID must be unique.
-    /// A duplicate discriminant is a compile error in Rust, but this test
-    /// additionally documents the full ID map and catches accidental
-    /// reuse of removed ids (13 = JwtHmacSign, 16 = JwtSignPayload).
-    #[test]
-    fn command_ids_unique_and_reserved_respected() {
-        let all: Vec<u32> = (0u32..=34)
-            .filter(|&i| !matches!(Command::from(i), Command::Unknown))
-            .collect();
-        let mut dedup = all.clone();
-        dedup.dedup();
-        assert_eq!(all, dedup, "duplicate command IDs detected");
-        // Removed IDs must NOT be assigned
-        for reserved in [13u32, 16] {
-            assert!(
-                matches!(Command::from(reserved), Command::Unknown),
-                "reserved command ID {} must not be assigned",
-                reserved
-            );
+        // Every id up to the last one is a command or retired: no holes.
+        for id in 0..=buffers::MAX_COMMAND_ID {
+            let cmd = Command::from(id);
+            if RETIRED_COMMAND_IDS.contains(&id) {
+                assert_eq!(cmd, Command::Unknown, "retired id {} reassigned", id);
+            } else {
+                assert_ne!(cmd, Command::Unknown, "command id {} unassigned", id);
+                assert_eq!(u32::from(cmd), id);
+            }
         }
+        assert_eq!(Command::from(buffers::MAX_COMMAND_ID + 1), Command::Unknown);
     }
 
     // ── UUID constant ──
@@ -255,6 +559,12 @@ mod tests {
         bincode_roundtrip(&CreateWalletInput {
             passkey_pubkey: vec![0x04; 65],
             entropy_seed: None,
+            prf_output: None,
+        });
+        bincode_roundtrip(&CreateWalletInput {
+            passkey_pubkey: vec![0x04; 65],
+            entropy_seed: Some(vec![0x11; 48]),
+            prf_output: Some([0x22; 32]),
         });
     }
 
@@ -275,7 +585,17 @@ mod tests {
             wallet_id: test_uuid(),
             passkey_assertion: None,
         });
-        bincode_roundtrip(&RemoveWalletOutput {});
+        bincode_roundtrip(&RemoveWalletOutput {
+            proof: erasure::DeletionProof {
+                certificate: erasure::DeletionCertificate {
+                    version: erasure::DELETION_CERT_VERSION,
+                    wallet_id: test_uuid(),
+                    deleted_at: 1_700_000_000,
+                    rollback_counter: Some(7),
+                },
+                attestation: None,
+            },
+        });
     }
 
     // ── DeriveAddress ──
@@ -286,6 +606,13 @@ mod tests {
             wallet_id: test_uuid(),
             hd_path: "m/44'/60'/0'/0/0".into(),
             passkey_assertion: None,
+            chain: chain::Chain::Ethereum,
+        });
+        bincode_roundtrip(&DeriveAddressInput {
+            wallet_id: test_uuid(),
+            hd_path: "m/44'/195'/0'/0/0".into(),
+            passkey_assertion: None,
+            chain: chain::Chain::Tron,
         });
     }
 
@@ -294,6 +621,7 @@ mod tests {
         bincode_roundtrip(&DeriveAddressOutput {
             address: [0xab; 20],
             public_key: vec![0x04; 65],
+            encoded_address: "0xABaBaBaBABabABabAbAbABAbABabababaBaBABaB".into(),
         });
     }
 
@@ -358,6 +686,8 @@ mod tests {
                 data: vec![],
             },
             passkey_assertion: None,
+            summary_abis: vec!["stake(uint256)".into()],
+            summary_committed: true,
         };
         bincode_roundtrip(&input);
         bincode_roundtrip(&SignTransactionOutput {
@@ -374,6 +704,7 @@ mod tests {
             hd_path: "m/44'/60'/0'/0/0".into(),
             message: b"hello world".to_vec(),
             passkey_assertion: None,
+            context: SigningContext::Raw,
         });
         bincode_roundtrip(&SignMessageOutput {
             signature: vec![0u8; 65],
@@ -389,6 +720,8 @@ mod tests {
             hd_path: "m/44'/60'/0'/0/0".into(),
             hash: [0xaa; 32],
             passkey_assertion: None,
+            context: SigningContext::Raw,
+            fee_quote: None,
         });
         bincode_roundtrip(&SignHashOutput {
             signature: vec![0u8; 65],
@@ -468,6 +801,10 @@ mod tests {
             passkey_assertion: None,
             label: "agent-0".to_string(), // #115
             is_refresh: false,            // #115
+            refresh: Some(AgentRefresh {
+                device_hash: [0x44; 32],
+                secret: None,
+            }),
         });
         bincode_roundtrip(&CreateAgentKeyInput {
             wallet_id: test_uuid(),
@@ -483,6 +820,7 @@ mod tests {
             }),
             label: "test-agent".to_string(), // #115
             is_refresh: true,                // #115 (refresh path)
+            refresh: None,
         });
         bincode_roundtrip(&CreateAgentKeyOutput {
             agent_address: [0xab; 20],
@@ -491,6 +829,7 @@ mod tests {
             jwt_header_b64: "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCIsImtpZCI6InYxMjM0In0".to_string(),
             jwt_payload_b64: "eyJzdWIiOiJ0ZXN0In0".to_string(),
             jwt_hmac: [0xbb; 32],
+            refresh_secret: Some([0x55; 32]),
         });
     }
 
@@ -561,6 +900,54 @@ mod tests {
         assert_eq!(tx.to, decoded.to);
     }
 
+    // ── Signing context (domain separation) ──
+
+    #[test]
+    fn signing_context_roundtrip() {
+        for context in [
+            SigningContext::Raw,
+            SigningContext::EthTx,
+            SigningContext::UserOp {
+                inner_hash: [0x11; 32],
+                entry_point: [0x22; 20],
+                chain_id: 11155111,
+            },
+            SigningContext::Eip712 {
+                domain_separator: [0x33; 32],
+                struct_hash: [0x44; 32],
+            },
+            SigningContext::PersonalMsg,
+            SigningContext::Login,
+        ] {
+            bincode_roundtrip(&SignHashInput {
+                wallet_id: test_uuid(),
+                hd_path: "m/44'/60'/0'/0/0".into(),
+                hash: [0xaa; 32],
+                passkey_assertion: None,
+                context: context.clone(),
+                fee_quote: None,
+            });
+            bincode_roundtrip(&SignMessageInput {
+                wallet_id: test_uuid(),
+                hd_path: "m/44'/60'/0'/0/0".into(),
+                message: b"hi".to_vec(),
+                passkey_assertion: None,
+                context,
+            });
+        }
+    }
+
+    #[test]
+    fn signing_context_json_defaults_to_raw() {
+        let json = format!(
+            r#"{{"wallet_id":"{}","hd_path":"m/44'/60'/0'/0/0","hash":[{}],"passkey_assertion":null}}"#,
+            test_uuid(),
+            vec!["0"; 32].join(",")
+        );
+        let decoded: SignHashInput = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.context, SigningContext::Raw);
+    }
+
     // ── PartialEq requirement for bincode_roundtrip ──
     // (not derived on original structs, so we test field-by-field for JSON)
 
@@ -573,6 +960,8 @@ mod tests {
             hd_path: "m/44'/60'/0'/0/1".into(),
             hash,
             passkey_assertion: None,
+            context: SigningContext::Raw,
+            fee_quote: None,
         };
         let bytes = bincode::serialize(&input).unwrap();
         let decoded: SignHashInput = bincode::deserialize(&bytes).unwrap();
@@ -634,6 +1023,8 @@ mod tests {
             hd_path: "m/44'/60'/0'/0/0".into(),
             hash: [0xff; 32],
             passkey_assertion: Some(assertion),
+            context: SigningContext::Raw,
+            fee_quote: None,
         });
     }
 
@@ -844,6 +1235,1084 @@ mod tests {
         });
     }
 
+    #[test]
+    fn scoped_session_key_roundtrip() {
+        let permission = SessionPermission {
+            chain_id: 10,
+            account: [0xab; 20],
+            targets: vec![[0x01; 20], [0x02; 20]],
+            selectors: vec![[0xa9, 0x05, 0x9c, 0xbb]],
+            value_cap: u128::MAX,
+            expiry: 1_700_000_000,
+        };
+        bincode_roundtrip(&CreateScopedSessionKeyInput {
+            wallet_id: test_uuid(),
+            hd_path: "m/44'/60'/0'/0/0".into(),
+            session_index: 3,
+            permission,
+            passkey_assertion: None,
+        });
+        bincode_roundtrip(&CreateScopedSessionKeyOutput {
+            session_address: [0x55; 20],
+            permission_hash: [0x66; 32],
+            owner_signature: vec![0u8; 65],
+        });
+        bincode_roundtrip(&SignWithSessionKeyInput {
+            wallet_id: test_uuid(),
+            session_index: 3,
+            chain_id: 10,
+            call: SessionCall {
+                target: [0x01; 20],
+                selector: [0xa9, 0x05, 0x9c, 0xbb],
+                value: 0,
+            },
+            user_op_hash: [0xcc; 32],
+        });
+        bincode_roundtrip(&SignWithSessionKeyOutput {
+            signature: vec![0u8; 65],
+        });
+        bincode_roundtrip(&RevokeScopedSessionKeyInput {
+            wallet_id: test_uuid(),
+            session_index: 3,
+            passkey_assertion: None,
+        });
+        bincode_roundtrip(&RevokeScopedSessionKeyOutput { revoked: true });
+    }
+
+    #[test]
+    fn bls_validator_signing_roundtrip() {
+        bincode_roundtrip(&BlsSignAttestationInput {
+            key_id: test_uuid(),
+            slot: 100,
+            committee_index: 3,
+            beacon_block_root: [0x11; 32],
+            source: BeaconCheckpoint {
+                epoch: 2,
+                root: [0x22; 32],
+            },
+            target: BeaconCheckpoint {
+                epoch: 3,
+                root: [0x33; 32],
+            },
+            domain: [0x44; 32],
+        });
+        bincode_roundtrip(&BlsSignBlockInput {
+            key_id: test_uuid(),
+            slot: 101,
+            proposer_index: 7,
+            parent_root: [0x55; 32],
+            state_root: [0x66; 32],
+            body_root: [0x77; 32],
+            domain: [0x88; 32],
+        });
+    }
+
+    #[test]
+    fn ta_stats_roundtrip_and_percentiles() {
+        let mut buckets = vec![0u32; TA_LATENCY_BUCKETS_MS.len() + 1];
+        buckets[2] = 90; // <= 5 ms
+        buckets[5] = 9; // <= 50 ms
+        buckets[TA_LATENCY_BUCKETS_MS.len()] = 1; // > 5000 ms
+        let lat = CommandLatency {
+            command: u32::from(Command::SignHash),
+            count: 100,
+            errors: 1,
+            buckets,
+        };
+        bincode_roundtrip(&TaStatsOutput {
+            commands: vec![lat.clone()],
+            storage: Some(storage_quota::StorageUsage {
+                wallets: 3,
+                max_wallets: 30_000,
+                exhausted: 0,
+            }),
+        });
+        assert_eq!(lat.percentile_ms(50), Some(5));
+        assert_eq!(lat.percentile_ms(90), Some(5));
+        assert_eq!(lat.percentile_ms(95), Some(50));
+        assert_eq!(lat.percentile_ms(99), Some(50));
+        assert_eq!(lat.percentile_ms(100), None);
+        let empty = CommandLatency {
+            command: 0,
+            count: 0,
+            errors: 0,
+            buckets: vec![0; TA_LATENCY_BUCKETS_MS.len() + 1],
+        };
+        assert_eq!(empty.percentile_ms(50), None);
+    }
+
+    #[test]
+    fn allowance_policy_roundtrip() {
+        for rule in [
+            AllowanceRule::Off,
+            AllowanceRule::RejectUnlimited,
+            AllowanceRule::Cap { max: 1_000_000 },
+        ] {
+            bincode_roundtrip(&SetAllowancePolicyInput {
+                wallet_id: test_uuid(),
+                rule,
+                passkey_assertion: None,
+            });
+        }
+        bincode_roundtrip(&SetAllowancePolicyOutput {
+            previous: AllowanceRule::Off,
+        });
+        bincode_roundtrip(&ConfirmAllowanceOverrideInput {
+            wallet_id: test_uuid(),
+            transaction: EthTransaction {
+                chain_id: 1,
+                nonce: 3,
+                to: Some([0x22; 20]),
+                value: 0,
+                gas_price: 1,
+                gas: 60_000,
+                data: vec![0x09, 0x5e, 0xa7, 0xb3],
+            },
+            summary_abis: vec![],
+            passkey_assertion: None,
+        });
+        bincode_roundtrip(&ConfirmAllowanceOverrideOutput {
+            summary: "approve USDT unlimited to 0x…".into(),
+        });
+    }
+
+    #[test]
+    fn sign_offline_roundtrip() {
+        let request = offline::OfflineSignRequest {
+            version: offline::OFFLINE_FORMAT_VERSION,
+            request_id: [0x5a; 16],
+            wallet_id: test_uuid(),
+            hd_path: "m/44'/60'/0'/0/0".into(),
+            transaction: EthTransaction {
+                chain_id: 1,
+                nonce: 9,
+                to: Some([0x35; 20]),
+                value: 1,
+                gas_price: 20,
+                gas: 21_000,
+                data: vec![],
+            },
+            created_at: 1_700_000_000,
+            expires_at: 1_700_003_600,
+        };
+        bincode_roundtrip(&SignOfflineInput {
+            request,
+            passkey_assertion: None,
+        });
+        bincode_roundtrip(&SignOfflineOutput {
+            response: offline::OfflineSignResponse {
+                version: offline::OFFLINE_FORMAT_VERSION,
+                request_id: [0x5a; 16],
+                signed_transaction: vec![0xf8, 0x6c],
+            },
+        });
+    }
+
+    #[test]
+    fn deployment_policy_roundtrip() {
+        let policy = deployment_policy::DeploymentPolicy {
+            format: deployment_policy::POLICY_FORMAT_VERSION,
+            deployment: "rack-3".into(),
+            sequence: 2,
+            disabled_commands: vec![5, 7],
+        };
+        bincode_roundtrip(&InstallDeploymentPolicyInput {
+            policy: policy.clone(),
+            signature: vec![0x11; 65],
+        });
+        bincode_roundtrip(&InstallDeploymentPolicyOutput {
+            previous_sequence: Some(1),
+            signer: [0x22; 20],
+        });
+        bincode_roundtrip(&GetCapabilitiesInput {});
+        bincode_roundtrip(&GetCapabilitiesOutput {
+            ta_version: "0.8.0".into(),
+            features: vec!["strict-challenge".into()],
+            policy: Some(policy),
+            policy_signer: Some([0x22; 20]),
+            config: None,
+            provisioning_key: None,
+        });
+        let config = ta_config::TaConfig {
+            format: ta_config::CONFIG_FORMAT_VERSION,
+            deployment: "rack-3".into(),
+            sequence: 4,
+            limits: ta_config::TaLimits::DEFAULT,
+            require_ta_challenge: true,
+            require_signing_context: true,
+        };
+        bincode_roundtrip(&InstallConfigInput {
+            config: config.clone(),
+            signature: vec![0x11; 65],
+        });
+        bincode_roundtrip(&InstallConfigOutput {
+            previous_sequence: Some(3),
+        });
+        bincode_roundtrip(&GetCapabilitiesOutput {
+            ta_version: "0.8.0".into(),
+            features: vec![],
+            policy: None,
+            policy_signer: None,
+            config: Some(config),
+            provisioning_key: Some([0x33; 20]),
+        });
+        bincode_roundtrip(&GetProtocolVersionInput {});
+        bincode_roundtrip(&GetProtocolVersionOutput {
+            protocol_version: wire::PROTOCOL_VERSION,
+            min_protocol_version: wire::MIN_PROTOCOL_VERSION,
+            max_command_id: buffers::MAX_COMMAND_ID,
+        });
+    }
+
+    #[test]
+    fn replication_roundtrip() {
+        let hello = replication::ReplicationHello {
+            version: replication::REPLICATION_VERSION,
+            role: replication::ReplicationRole::Target,
+            session_id: [0x5e; 16],
+            ephemeral_pubkey: vec![0x02; 33],
+            created_at: 1_700_000_000,
+            attestation: GetAttestationOutput {
+                nonce: vec![0xab; 32],
+                ta_uuid: vec![0; 16],
+                ta_measurement: vec![0x4d; 32],
+                signature: vec![1; 256],
+                attest_pubkey_exp: vec![1, 0, 1],
+                attest_pubkey_mod: vec![0xc5; 256],
+                sig_alg: 0x7041_4930,
+                ree_time_secs: 1_700_000_000,
+            },
+        };
+        bincode_roundtrip(&ReplicationOfferInput {});
+        bincode_roundtrip(&ReplicationOfferOutput {
+            hello: hello.clone(),
+        });
+        bincode_roundtrip(&ReplicationExportInput {
+            wallet_id: test_uuid(),
+            peer: hello.clone(),
+            passkey_assertion: None,
+        });
+        let package = replication::ReplicationPackage {
+            version: replication::REPLICATION_VERSION,
+            session_id: [0x5e; 16],
+            wallet_id: test_uuid(),
+            source: hello,
+            ciphertext: vec![0xaa; 300],
+            mac: [0x11; 32],
+        };
+        bincode_roundtrip(&ReplicationExportOutput {
+            package: package.clone(),
+        });
+        bincode_roundtrip(&ReplicationImportInput { package });
+        bincode_roundtrip(&ReplicationImportOutput {
+            wallet_id: test_uuid(),
+            address: [0x33; 20],
+        });
+    }
+
+    #[test]
+    fn tamper_roundtrip() {
+        let order = tamper::TamperOrder {
+            format: tamper::TAMPER_ORDER_FORMAT,
+            deployment: "rack-3".into(),
+            device: Some([0xab; 32]),
+            sequence: 2,
+            action: tamper::TamperAction::SetFailedAuthLimit(Some(5)),
+        };
+        bincode_roundtrip(&TamperOrderInput {
+            order,
+            signature: vec![0x1b; 65],
+        });
+        let status = TamperStatus {
+            failed_auth_limit: Some(5),
+            consecutive_failures: 2,
+            last_order_sequence: 2,
+            locked: Some(tamper::TamperLock {
+                reason: tamper::WipeReason::FailedAuth(5),
+                since: 1_700_000_000,
+            }),
+            last_wipe: Some(tamper::WipeProof {
+                certificate: tamper::WipeCertificate {
+                    version: tamper::WIPE_CERT_VERSION,
+                    reason: tamper::WipeReason::FailedAuth(5),
+                    wallets: 3,
+                    wallet_set: [0x44; 32],
+                    wiped_at: 1_700_000_000,
+                    rollback_counter: Some(9),
+                },
+                attestation: None,
+            }),
+        };
+        bincode_roundtrip(&GetTamperStatusInput {});
+        bincode_roundtrip(&status);
+        bincode_roundtrip(&TamperOrderOutput {
+            status,
+            wiped: true,
+        });
+    }
+
+    #[test]
+    fn permit_roundtrip() {
+        let permit = permit::PermitRequest::Permit2Single(permit::Permit2Single {
+            chain_id: 1,
+            token: [0xa0; 20],
+            amount: [0x01; 32],
+            expiration: 1_700_000_000,
+            nonce: 4,
+            spender: [0x11; 20],
+            sig_deadline: [0xff; 32],
+        });
+        bincode_roundtrip(&SignPermitInput {
+            wallet_id: test_uuid(),
+            hd_path: "m/44'/60'/0'/0/0".into(),
+            permit,
+            passkey_assertion: None,
+        });
+        bincode_roundtrip(&SignPermitOutput {
+            signature: vec![0x1b; 65],
+            summary: "Permit2 allowance".into(),
+        });
+        bincode_roundtrip(&SetSpenderAllowListInput {
+            wallet_id: test_uuid(),
+            spenders: vec![[0x11; 20], [0x22; 20]],
+            passkey_assertion: None,
+        });
+        bincode_roundtrip(&SetSpenderAllowListOutput { previous: vec![] });
+        let keystore = kdf::Keystore {
+            kdf: kdf::KdfConfig::default(),
+            salt: vec![0x33; 32],
+            iv: [0x44; 16],
+            ciphertext: vec![0x55; 32],
+            mac: [0x66; 32],
+        };
+        bincode_roundtrip(&ExportKeystoreInput {
+            wallet_id: test_uuid(),
+            passphrase: "pass".into(),
+            kdf: kdf::KdfConfig::Scrypt {
+                log_n: 12,
+                r: 1,
+                p: 8,
+            },
+            passkey_assertion: None,
+        });
+        bincode_roundtrip(&ExportKeystoreOutput {
+            keystore: keystore.clone(),
+        });
+        bincode_roundtrip(&ImportKeystoreInput {
+            passkey_pubkey: vec![0x04; 65],
+            keystore,
+            passphrase: "pass".into(),
+        });
+        bincode_roundtrip(&ImportKeystoreOutput {
+            wallet_id: test_uuid(),
+        });
+        bincode_roundtrip(&DappStoragePutInput {
+            wallet_id: test_uuid(),
+            app_id: "notes.example.org".into(),
+            key: "totp".into(),
+            value: vec![0x77; 40],
+            passkey_assertion: None,
+        });
+        bincode_roundtrip(&DappStoragePutOutput {
+            used_keys: 1,
+            used_bytes: 40,
+        });
+        bincode_roundtrip(&DappStorageGetInput {
+            wallet_id: test_uuid(),
+            app_id: "notes.example.org".into(),
+            key: "totp".into(),
+            passkey_assertion: None,
+        });
+        bincode_roundtrip(&DappStorageGetOutput {
+            value: vec![0x77; 40],
+        });
+        bincode_roundtrip(&DappStorageDeleteInput {
+            wallet_id: test_uuid(),
+            app_id: "notes.example.org".into(),
+            key: "totp".into(),
+            passkey_assertion: None,
+        });
+        bincode_roundtrip(&DappStorageDeleteOutput { existed: true });
+        let app = bip85::Bip85App::Bip39 {
+            words: 24,
+            index: 3,
+        };
+        bincode_roundtrip(&Bip85ExportInput {
+            wallet_id: test_uuid(),
+            app,
+            recipient_pubkey: vec![0x02; 33],
+            passkey_assertion: None,
+        });
+        bincode_roundtrip(&Bip85ExportOutput {
+            package: bip85::Bip85Package {
+                wallet_id: test_uuid(),
+                app,
+                recipient_pubkey: vec![0x02; 33],
+                ephemeral_pubkey: vec![0x03; 33],
+                nonce: [0x44; 16],
+                ciphertext: vec![0x55; 32],
+                mac: [0x66; 32],
+            },
+        });
+        bincode_roundtrip(&CrashDumpsInput { clear: true });
+        bincode_roundtrip(&CrashDumpsOutput {
+            records: vec![crash_dump::CrashRecord {
+                seq: 4,
+                command: 3,
+                class: crash_dump::ErrorClass::Codec,
+                code: 0,
+                uptime_secs: 120,
+                invocations: 40,
+                failures: 2,
+                command_invocations: 9,
+                command_failures: 1,
+            }],
+            dropped: 4,
+        });
+        let params = otp::OtpParams {
+            algorithm: otp::OtpAlgorithm::Sha256,
+            digits: 8,
+            kind: otp::OtpKind::Totp { period_secs: 30 },
+        };
+        bincode_roundtrip(&OtpEnrollInput {
+            wallet_id: test_uuid(),
+            label: "github".into(),
+            secret: vec![0x31; 20],
+            params,
+            passkey_assertion: None,
+        });
+        bincode_roundtrip(&OtpEnrollOutput { entries: 3 });
+        bincode_roundtrip(&OtpCodeInput {
+            wallet_id: test_uuid(),
+            label: "github".into(),
+            passkey_assertion: None,
+        });
+        bincode_roundtrip(&OtpCodeOutput {
+            code: "00123456".into(),
+            counter: 56_666_666,
+            valid_for_secs: 12,
+        });
+        bincode_roundtrip(&OtpRemoveInput {
+            wallet_id: test_uuid(),
+            label: "github".into(),
+            passkey_assertion: None,
+        });
+        bincode_roundtrip(&OtpRemoveOutput { existed: false });
+        bincode_roundtrip(&OtpListInput {
+            wallet_id: test_uuid(),
+            passkey_assertion: None,
+        });
+        bincode_roundtrip(&OtpListOutput {
+            entries: vec![OtpEntry {
+                label: "github".into(),
+                params: otp::OtpParams {
+                    kind: otp::OtpKind::Hotp,
+                    ..params
+                },
+            }],
+        });
+    }
+
+    #[test]
+    fn link_wallet_roundtrip() {
+        bincode_roundtrip(&LinkWalletInput {
+            action: identity::LinkAction::Link,
+            identity_id: test_uuid(),
+            wallet_id: test_uuid(),
+            hd_path: "m/44'/60'/0'/0/0".into(),
+            chain_ids: vec![1, 10, 8453],
+            passkey_assertion: None,
+            owner: Some(OwnerApproval {
+                wallet_id: test_uuid(),
+                passkey_assertion: None,
+            }),
+        });
+        bincode_roundtrip(&LinkWalletOutput {
+            address: [0x5a; 20],
+            statement: "AirAccount identity link v1".into(),
+            signature: vec![0x1b; 65],
+        });
+    }
+
+    #[test]
+    fn migration_roundtrip() {
+        let account_xpub = migration::AccountXpub {
+            public_key: vec![0x02; 33],
+            chain_code: [0xcc; 32],
+        };
+        bincode_roundtrip(&MigrationOfferInput { account_xpub });
+        bincode_roundtrip(&MigrationOfferOutput {
+            session_id: [0x5e; 16],
+            ephemeral_pubkey: vec![0x03; 33],
+            created_at: 1_700_000_000,
+            attestation: None,
+        });
+        bincode_roundtrip(&MigrationImportInput {
+            session_id: [0x5e; 16],
+            client_pubkey: vec![0x02; 33],
+            ciphertext: vec![0xab; 160],
+            mac: [0x11; 32],
+            passkey_pubkey: vec![0x04; 65],
+            confirmed: vec![migration::ConfirmedAddress {
+                index: 0,
+                address: [0x5a; 20],
+            }],
+        });
+        bincode_roundtrip(&MigrationImportOutput {
+            wallet_id: test_uuid(),
+            address: [0x5a; 20],
+        });
+    }
+
+    #[test]
+    fn set_log_level_roundtrip() {
+        let mut levels = log_level::LogLevels::DEFAULT;
+        levels.set(log_level::LogCategory::Storage, log_level::LogLevel::Debug);
+        bincode_roundtrip(&SetLogLevelInput {
+            order: log_level::LogLevelOrder {
+                format: log_level::LOG_ORDER_FORMAT,
+                deployment: "rack-3".into(),
+                device: Some([0xab; 32]),
+                sequence: 3,
+                levels,
+                until: Some(1_700_003_600),
+            },
+            signature: vec![0x1b; 65],
+        });
+        bincode_roundtrip(&SetLogLevelOutput {
+            levels,
+            until: Some(1_700_003_600),
+            previous_sequence: 2,
+        });
+    }
+
+    #[test]
+    fn ecies_decrypt_roundtrip() {
+        bincode_roundtrip(&OpenDecryptSessionInput {
+            wallet_id: test_uuid(),
+            scope: ecies::DecryptScope {
+                hd_path: "m/44'/60'/0'/0/0".into(),
+                max_decryptions: 10,
+                ttl_secs: 300,
+            },
+            passkey_assertion: None,
+        });
+        bincode_roundtrip(&OpenDecryptSessionOutput {
+            session_id: [0x31; 16],
+            expires_at: 1_700_000_300,
+        });
+        bincode_roundtrip(&EciesDecryptInput {
+            wallet_id: test_uuid(),
+            session_id: [0x31; 16],
+            payload: vec![0x04; ecies::OVERHEAD + 5],
+        });
+        bincode_roundtrip(&EciesDecryptOutput {
+            plaintext: b"hello".to_vec(),
+            remaining: 9,
+        });
+    }
+
+    #[test]
+    fn stealth_roundtrip() {
+        bincode_roundtrip(&GetStealthMetaAddressInput {
+            wallet_id: test_uuid(),
+            account: 0,
+        });
+        bincode_roundtrip(&GetStealthMetaAddressOutput {
+            meta_address: stealth::StealthMetaAddress {
+                spending_pubkey: vec![0x02; 33],
+                viewing_pubkey: vec![0x03; 33],
+            },
+        });
+        bincode_roundtrip(&StealthScanInput {
+            wallet_id: test_uuid(),
+            account: 2,
+            candidates: vec![stealth::StealthCandidate {
+                stealth_address: [0x3b; 20],
+                ephemeral_pubkey: vec![0x02; 33],
+                view_tag: 0x33,
+            }],
+        });
+        bincode_roundtrip(&StealthScanOutput {
+            matches: vec![0, 5],
+        });
+    }
+
+    #[test]
+    fn self_test_roundtrip() {
+        bincode_roundtrip(&SelfTestInput {});
+        bincode_roundtrip(&SelfTestOutput {
+            checks: vec![
+                SelfTestCheck {
+                    name: "sha256".to_string(),
+                    passed: true,
+                    detail: String::new(),
+                },
+                SelfTestCheck {
+                    name: "rng".to_string(),
+                    passed: false,
+                    detail: "two draws were equal".to_string(),
+                },
+            ],
+        });
+    }
+
+    #[test]
+    fn scavenge_storage_roundtrip() {
+        bincode_roundtrip(&ScavengeStorageInput { confirm: None });
+        bincode_roundtrip(&ScavengeStorageInput {
+            confirm: Some([7u8; 32]),
+        });
+        let orphan = storage_gc::Orphan {
+            kind: storage_gc::ObjectKind::OtpSecret,
+            store_id: "otp_a1b2c3d4-e5f6-7890-abcd-ef1234567890_mail".to_string(),
+            reason: storage_gc::OrphanReason::WalletMissing,
+        };
+        bincode_roundtrip(&ScavengeStorageOutput {
+            scanned: 12,
+            orphans: 1,
+            by_kind: vec![(storage_gc::ObjectKind::OtpSecret, 1)],
+            digest: storage_gc::orphan_set_digest(std::slice::from_ref(&orphan)),
+            listed: vec![orphan],
+            deleted: 0,
+            scavenged_at_load: 3,
+        });
+    }
+
+    #[test]
+    fn path_policy_roundtrip() {
+        let first = hd_path::parse_path_pattern("m/44'/60'/0'/0/*").unwrap();
+        let few = hd_path::parse_path_pattern("m/44'/60'/0'/0/0-9").unwrap();
+        bincode_roundtrip(&PathPolicyInput {
+            wallet_id: test_uuid(),
+            set: None,
+            passkey_assertion: None,
+        });
+        bincode_roundtrip(&PathPolicyInput {
+            wallet_id: test_uuid(),
+            set: Some(vec![first, few]),
+            passkey_assertion: None,
+        });
+        bincode_roundtrip(&PathPolicyOutput {
+            patterns: vec![few],
+            previous: vec![first],
+        });
+    }
+
+    #[test]
+    fn remote_approval_roundtrip() {
+        bincode_roundtrip(&PairApproverInput {
+            wallet_id: test_uuid(),
+            approver_key: Some(vec![0x02; remote_approval::APPROVER_KEY_LEN]),
+            passkey_assertion: None,
+        });
+        bincode_roundtrip(&PairApproverOutput {
+            previous: Some(vec![0x03; remote_approval::APPROVER_KEY_LEN]),
+        });
+        bincode_roundtrip(&RemoteApproveInput {
+            wallet_id: test_uuid(),
+            payload: [0xab; 32],
+            signature: vec![0x11; remote_approval::APPROVAL_SIGNATURE_LEN],
+        });
+        bincode_roundtrip(&RemoteApproveOutput {
+            expires_at: 1_800_000_000,
+        });
+    }
+
+    #[test]
+    fn aggregator_roundtrip() {
+        bincode_roundtrip(&SetAggregatorInput {
+            wallet_id: test_uuid(),
+            aggregator: Some([0xaa; 20]),
+            passkey_assertion: None,
+        });
+        bincode_roundtrip(&SetAggregatorOutput {
+            public_key: Some(vec![0x11; aggregator::BLS_PUBLIC_KEY_LEN]),
+            previous: None,
+        });
+        bincode_roundtrip(&SignAggregatedUserOpInput {
+            wallet_id: test_uuid(),
+            user_op_hash: [0xab; 32],
+            passkey_assertion: None,
+        });
+        bincode_roundtrip(&SignAggregatedUserOpOutput {
+            aggregator: [0xaa; 20],
+            signature: vec![0x22; aggregator::BLS_SIGNATURE_LEN],
+            signature_compact: vec![0x33; aggregator::BLS_SIGNATURE_COMPACT_LEN],
+        });
+    }
+
+    #[test]
+    fn transfer_roundtrip() {
+        bincode_roundtrip(&BeginTransferInput {
+            command: u32::from(Command::StealthScan),
+            input_len: 20_000,
+            input_digest: [0x11; 32],
+        });
+        bincode_roundtrip(&BeginTransferOutput {
+            transfer_id: u64::MAX,
+            chunk_len: transfer::CHUNK_LEN as u32,
+        });
+        bincode_roundtrip(&ContinueTransferInput {
+            transfer_id: 9,
+            chunk: Some(transfer::TransferChunk::new(9, 0, vec![1, 2, 3])),
+        });
+        bincode_roundtrip(&ContinueTransferOutput { chunk: None });
+        bincode_roundtrip(&EndTransferInput { transfer_id: 9 });
+        bincode_roundtrip(&EndTransferOutput {
+            output_len: 7000,
+            output_digest: [0x22; 32],
+            chunks: 3,
+        });
+    }
+
+    #[test]
+    fn import_wallet_roundtrip() {
+        bincode_roundtrip(&ImportWalletInput {
+            passkey_pubkey: vec![0x04; 65],
+            phrase: "abandon ".repeat(23) + "art",
+        });
+        bincode_roundtrip(&ImportWalletOutput {
+            wallet_id: test_uuid(),
+            address: [0xab; 20],
+        });
+    }
+
+    #[test]
+    fn sign_eip1559_transaction_roundtrip() {
+        bincode_roundtrip(&SignEip1559TransactionInput {
+            wallet_id: test_uuid(),
+            hd_path: "m/44'/60'/0'/0/0".into(),
+            transaction: eip1559::Eip1559Transaction {
+                chain_id: 1,
+                nonce: 9,
+                max_priority_fee_per_gas: 1_500_000_000,
+                max_fee_per_gas: 40_000_000_000,
+                gas: 60_000,
+                to: Some([0x35; 20]),
+                value: 1,
+                data: vec![0xa9, 0x05, 0x9c, 0xbb],
+                access_list: vec![eip1559::AccessListItem {
+                    address: [0x11; 20],
+                    storage_keys: vec![[0x01; 32]],
+                }],
+            },
+            passkey_assertion: None,
+            summary_abis: vec!["stake(uint256)".into()],
+            summary_committed: true,
+        });
+        bincode_roundtrip(&SignEip1559TransactionOutput {
+            raw_transaction: vec![0x02, 0xc0],
+        });
+    }
+
+    #[test]
+    fn spending_policy_roundtrip() {
+        let policy = spending_policy::SpendingPolicy {
+            daily_limit_wei: Some(10u128.pow(18)),
+            allowed_destinations: vec![[0x35; 20]],
+            denied_destinations: vec![[0x66; 20]],
+            max_gas_price_wei: Some(50_000_000_000),
+            allowed_chain_ids: vec![1, 10],
+        };
+        bincode_roundtrip(&SpendingPolicyInput {
+            wallet_id: test_uuid(),
+            set: None,
+            passkey_assertion: None,
+        });
+        bincode_roundtrip(&SpendingPolicyInput {
+            wallet_id: test_uuid(),
+            set: Some(policy.clone()),
+            passkey_assertion: None,
+        });
+        bincode_roundtrip(&SpendingPolicyOutput {
+            policy,
+            previous: None,
+            spent_today_wei: 3,
+        });
+    }
+
+    #[test]
+    fn multisig_roundtrip() {
+        let policy = multisig::MultiSigPolicy {
+            threshold_wei: 10u128.pow(18),
+            required: 2,
+            cosigners: vec![vec![0x02; 33], vec![0x03; 33]],
+        };
+        bincode_roundtrip(&SetMultiSigPolicyInput {
+            wallet_id: test_uuid(),
+            policy: Some(policy.clone()),
+            passkey_assertion: None,
+        });
+        bincode_roundtrip(&SetMultiSigPolicyOutput {
+            previous: Some(policy),
+            dropped: 1,
+        });
+        bincode_roundtrip(&ApproveMultiSigInput {
+            wallet_id: test_uuid(),
+            operation: [0x11; 32],
+            cosigner_key: vec![0x02; 33],
+            signature: vec![0x22; 64],
+        });
+        bincode_roundtrip(&ApproveMultiSigOutput {
+            approvals: 2,
+            required: 2,
+            released: Some(MultiSigRelease {
+                hd_path: "m/44'/60'/0'/0/0".into(),
+                signed: vec![0xf8, 0x6c],
+            }),
+        });
+    }
+
+    #[test]
+    fn social_recovery_roundtrip() {
+        let guardians = social_recovery::GuardianSet {
+            threshold: 2,
+            guardians: vec![vec![0x02; 33], vec![0x03; 33], vec![0x02; 33]],
+            delay_secs: 86_400,
+        };
+        let status = social_recovery::RecoveryStatus {
+            guardians: Some(guardians.clone()),
+            round: 3,
+            candidates: vec![social_recovery::RecoveryCandidate {
+                passkey: vec![0x04; 65],
+                attestations: 2,
+            }],
+            armed: Some(social_recovery::ArmedRecovery {
+                passkey: vec![0x04; 65],
+                ready_at: 1_700_086_400,
+            }),
+        };
+        bincode_roundtrip(&SetGuardiansInput {
+            wallet_id: test_uuid(),
+            guardians: Some(guardians.clone()),
+            passkey_assertion: None,
+        });
+        bincode_roundtrip(&SetGuardiansOutput {
+            previous: Some(guardians),
+            cancelled: true,
+        });
+        bincode_roundtrip(&RecoveryStatusInput {
+            wallet_id: test_uuid(),
+        });
+        bincode_roundtrip(&RecoveryStatusOutput {
+            status: status.clone(),
+        });
+        bincode_roundtrip(&RecoveryAttestInput {
+            wallet_id: test_uuid(),
+            round: 3,
+            guardian_key: vec![0x02; 33],
+            new_passkey: vec![0x04; 65],
+            signature: vec![0x22; 64],
+        });
+        bincode_roundtrip(&RecoveryAttestOutput {
+            status: status.clone(),
+            armed_now: true,
+        });
+        bincode_roundtrip(&CancelRecoveryInput {
+            wallet_id: test_uuid(),
+            passkey_assertion: None,
+        });
+        bincode_roundtrip(&CancelRecoveryOutput { status });
+        bincode_roundtrip(&CompleteRecoveryInput {
+            wallet_id: test_uuid(),
+        });
+        bincode_roundtrip(&CompleteRecoveryOutput {
+            passkey_pubkey: vec![0x04; 65],
+            round: 4,
+        });
+    }
+
+    #[test]
+    fn encrypted_backup_roundtrip() {
+        use crate::backup::{BackupHeader, EncryptedBackup, BACKUP_VERSION};
+        use crate::kdf::{KdfConfig, Keystore};
+        let backup = EncryptedBackup {
+            header: BackupHeader {
+                version: BACKUP_VERSION,
+                wallet_id: test_uuid(),
+                passkey_pubkey: vec![0x04; 65],
+                next_address_index: 3,
+                next_account_index: 1,
+                created_at: 1_700_000_000,
+                device: [0x11; 32],
+            },
+            keystore: Keystore {
+                kdf: KdfConfig::default(),
+                salt: vec![0x22; 32],
+                iv: [0x33; 16],
+                ciphertext: vec![0x44; 32],
+                mac: [0x55; 32],
+            },
+        };
+        bincode_roundtrip(&ExportEncryptedBackupInput {
+            wallet_id: test_uuid(),
+            password: "correct horse".into(),
+            kdf: KdfConfig::default(),
+            passkey_assertion: None,
+        });
+        bincode_roundtrip(&ExportEncryptedBackupOutput {
+            backup: backup.clone(),
+        });
+        bincode_roundtrip(&ImportEncryptedBackupInput {
+            backup,
+            password: "correct horse".into(),
+        });
+        bincode_roundtrip(&ImportEncryptedBackupOutput {
+            wallet_id: test_uuid(),
+            address: [0x66; 20],
+            passkey_pubkey: vec![0x04; 65],
+        });
+    }
+
+    #[test]
+    fn nonce_tracking_roundtrip() {
+        let armed = nonce_guard::NonceOverride {
+            chain_id: 1,
+            address: [0x35; 20],
+            nonce: 7,
+            expires_at: 1_700_000_600,
+        };
+        bincode_roundtrip(&NonceTrackingInput {
+            wallet_id: test_uuid(),
+            set: Some(true),
+            passkey_assertion: None,
+        });
+        bincode_roundtrip(&NonceTrackingOutput {
+            enabled: true,
+            previous: Some(false),
+            entries: vec![nonce_guard::NonceEntry {
+                chain_id: 1,
+                address: [0x35; 20],
+                highest: 9,
+            }],
+            pending_override: Some(armed),
+        });
+        bincode_roundtrip(&OverrideNonceInput {
+            wallet_id: test_uuid(),
+            chain_id: 1,
+            address: [0x35; 20],
+            nonce: 7,
+            passkey_assertion: None,
+        });
+        bincode_roundtrip(&OverrideNonceOutput {
+            armed,
+            highest: Some(9),
+        });
+    }
+
+    #[test]
+    fn solana_roundtrip() {
+        bincode_roundtrip(&DeriveSolanaAddressInput {
+            wallet_id: test_uuid(),
+            hd_path: solana::solana_path(0),
+            passkey_assertion: None,
+        });
+        bincode_roundtrip(&DeriveSolanaAddressOutput {
+            public_key: [0x36; 32],
+            address: solana::address(&[0x36; 32]),
+        });
+        bincode_roundtrip(&SignSolanaMessageInput {
+            wallet_id: test_uuid(),
+            hd_path: solana::solana_path(1),
+            message: vec![0x80; 200],
+            passkey_assertion: None,
+        });
+        bincode_roundtrip(&SignSolanaMessageOutput {
+            signature: vec![0x37; 64],
+            public_key: [0x36; 32],
+        });
+    }
+
+    #[test]
+    fn threshold_roundtrip() {
+        let key = threshold::PartyKey {
+            point: vec![0x02; 33],
+            proof_point: vec![0x03; 33],
+            proof_response: [0x38; 32],
+        };
+        bincode_roundtrip(&ThresholdKeygenBeginInput {
+            wallet_id: test_uuid(),
+            passkey_assertion: None,
+        });
+        bincode_roundtrip(&ThresholdKeygenBeginOutput {
+            commit: threshold::KeygenCommit {
+                commitment: [0x39; 32],
+            },
+        });
+        bincode_roundtrip(&ThresholdKeygenFinishInput {
+            wallet_id: test_uuid(),
+            cosigner_key: key.clone(),
+        });
+        bincode_roundtrip(&ThresholdKeygenFinishOutput {
+            reveal: threshold::KeygenReveal {
+                ta_key: key.clone(),
+                salt: [0x3a; 32],
+                paillier_n: vec![0xc1; 256],
+                encrypted_share: vec![0x3b; 512],
+            },
+            public_key: vec![0x02; 33],
+            address: [0x3c; 20],
+        });
+        bincode_roundtrip(&ThresholdPresignBeginInput {
+            wallet_id: test_uuid(),
+            count: 8,
+        });
+        bincode_roundtrip(&ThresholdPresignBeginOutput {
+            commits: vec![threshold::PresignCommit {
+                id: 3,
+                commitment: [0x3d; 32],
+            }],
+        });
+        bincode_roundtrip(&ThresholdPresignFinishInput {
+            wallet_id: test_uuid(),
+            replies: vec![threshold::PresignReply {
+                id: 3,
+                nonce: key.clone(),
+            }],
+        });
+        bincode_roundtrip(&ThresholdPresignFinishOutput {
+            reveals: vec![threshold::PresignReveal {
+                id: 3,
+                nonce: key,
+                salt: [0x3e; 32],
+            }],
+            available: 1,
+        });
+        bincode_roundtrip(&ThresholdSignInput {
+            wallet_id: test_uuid(),
+            digest: [0x3f; 32],
+            reply: Some(threshold::SignReply {
+                presignature_id: 3,
+                ciphertext: vec![0x40; 512],
+            }),
+            passkey_assertion: None,
+        });
+        bincode_roundtrip(&ThresholdSignOutput {
+            signature: vec![0x41; 65],
+            available: 0,
+        });
+        bincode_roundtrip(&ThresholdRecombineInput {
+            wallet_id: test_uuid(),
+            sealed_share: vec![0x42; 113],
+            export_to: Some(vec![0x04; 65]),
+            passkey_assertion: None,
+        });
+        bincode_roundtrip(&ThresholdRecombineOutput {
+            address: [0x3c; 20],
+            exported: None,
+        });
+        bincode_roundtrip(&ThresholdStatusInput {
+            wallet_id: test_uuid(),
+        });
+        bincode_roundtrip(&ThresholdStatusOutput {
+            public_key: Some(vec![0x02; 33]),
+            address: Some([0x3c; 20]),
+            available: 5,
+            recombined: false,
+        });
+    }
+
     #[test]
     fn delete_p256_session_key_roundtrip() {
         bincode_roundtrip(&DeleteP256SessionKeyInput {
@@ -0,0 +1,324 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! TA trace verbosity per category, set by a signed log-level order.
+//!
+//! An order is signed like a tamper order — `personal_sign` over
+//! [`LogLevelOrder::message`] by the pinned deployment-policy signer — names
+//! the installed deployment (and optionally one device) and carries a
+//! sequence above every log-level order the TA has accepted. `debug` adds
+//! transaction summaries and wallet ids to the trace, so an order that turns
+//! it on must expire within [`MAX_DEBUG_WINDOW_SECS`]; the TA falls back to
+//! the defaults once `until` has passed.
+
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+
+pub const LOG_ORDER_FORMAT: u8 = 1;
+
+/// Longest a `debug` level may stay on.
+pub const MAX_DEBUG_WINDOW_SECS: u64 = 7 * 24 * 3600;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogCategory {
+    /// Key generation, passkey signature checks, signing.
+    Crypto,
+    /// Secure storage, the RPMB counter, wallet caches, wipes.
+    Storage,
+    /// Challenges, agent and session keys.
+    Session,
+    /// Allowance rules, signing contexts, policy, config and tamper orders.
+    Policy,
+}
+
+impl LogCategory {
+    pub const ALL: [LogCategory; 4] = [
+        LogCategory::Crypto,
+        LogCategory::Storage,
+        LogCategory::Session,
+        LogCategory::Policy,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LogCategory::Crypto => "crypto",
+            LogCategory::Storage => "storage",
+            LogCategory::Session => "session",
+            LogCategory::Policy => "policy",
+        }
+    }
+}
+
+impl std::str::FromStr for LogCategory {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        LogCategory::ALL
+            .iter()
+            .find(|c| c.name() == s.trim())
+            .copied()
+            .ok_or("category must be crypto, storage, session or policy")
+    }
+}
+
+/// Each level includes the ones below it.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Off,
+    /// `[!]` lines: degraded modes, legacy paths, admin actions.
+    Warn,
+    /// `[+]` lines: what a release build has always printed.
+    Info,
+    /// Per-request detail formerly compiled into debug builds only.
+    Debug,
+}
+
+impl LogLevel {
+    pub fn name(&self) -> &'static str {
+        match self {
+            LogLevel::Off => "off",
+            LogLevel::Warn => "warn",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+        }
+    }
+}
+
+impl std::str::FromStr for LogLevel {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "off" => Ok(LogLevel::Off),
+            "warn" => Ok(LogLevel::Warn),
+            "info" => Ok(LogLevel::Info),
+            "debug" => Ok(LogLevel::Debug),
+            _ => Err("level must be off, warn, info or debug"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogLevels {
+    pub crypto: LogLevel,
+    pub storage: LogLevel,
+    pub session: LogLevel,
+    pub policy: LogLevel,
+}
+
+impl LogLevels {
+    /// A release build without an order.
+    pub const DEFAULT: LogLevels = LogLevels::all(LogLevel::Info);
+
+    pub const fn all(level: LogLevel) -> LogLevels {
+        LogLevels {
+            crypto: level,
+            storage: level,
+            session: level,
+            policy: level,
+        }
+    }
+
+    pub fn get(&self, category: LogCategory) -> LogLevel {
+        match category {
+            LogCategory::Crypto => self.crypto,
+            LogCategory::Storage => self.storage,
+            LogCategory::Session => self.session,
+            LogCategory::Policy => self.policy,
+        }
+    }
+
+    pub fn set(&mut self, category: LogCategory, level: LogLevel) {
+        match category {
+            LogCategory::Crypto => self.crypto = level,
+            LogCategory::Storage => self.storage = level,
+            LogCategory::Session => self.session = level,
+            LogCategory::Policy => self.policy = level,
+        }
+    }
+
+    pub fn enabled(&self, category: LogCategory, level: LogLevel) -> bool {
+        level != LogLevel::Off && level <= self.get(category)
+    }
+
+    fn max(&self) -> LogLevel {
+        LogCategory::ALL
+            .iter()
+            .map(|c| self.get(*c))
+            .max()
+            .unwrap_or(LogLevel::Off)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct LogLevelOrder {
+    pub format: u8,
+    /// Must equal the installed deployment policy's label.
+    pub deployment: String,
+    /// Attestation-key fingerprint of one device, as in a tamper order; None
+    /// addresses every device of the deployment.
+    pub device: Option<[u8; 32]>,
+    /// Strictly increasing across the log-level orders a TA accepts.
+    pub sequence: u64,
+    pub levels: LogLevels,
+    /// TA (REE) clock seconds after which the defaults apply again. Required
+    /// when any category is `debug`.
+    pub until: Option<u64>,
+}
+
+impl LogLevelOrder {
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if self.format != LOG_ORDER_FORMAT {
+            return Err("unsupported log-level order format");
+        }
+        if self.deployment.is_empty() || self.deployment.chars().any(|c| c.is_control()) {
+            return Err("log-level order must name the deployment");
+        }
+        if self.levels.max() == LogLevel::Debug && self.until.is_none() {
+            return Err("debug logging must expire: set until");
+        }
+        Ok(())
+    }
+
+    /// Check `until` against the TA clock.
+    pub fn check_window(&self, now: u64) -> Result<(), &'static str> {
+        match self.until {
+            Some(until) if until <= now => Err("log-level order has already expired"),
+            Some(until)
+                if self.levels.max() == LogLevel::Debug && until - now > MAX_DEBUG_WINDOW_SECS =>
+            {
+                Err("debug logging may stay on for at most 7 days")
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The text the operator signs.
+    pub fn message(&self) -> String {
+        let device = match &self.device {
+            Some(fp) => format!(
+                "0x{}",
+                fp.iter().map(|b| format!("{:02x}", b)).collect::<String>()
+            ),
+            None => "any".to_string(),
+        };
+        let mut text = format!(
+            "AirAccount TA log levels v{}\ndeployment: {}\ndevice: {}\nsequence: {}",
+            self.format, self.deployment, device, self.sequence
+        );
+        for category in LogCategory::ALL.iter() {
+            text.push_str(&format!(
+                "\n{}: {}",
+                category.name(),
+                self.levels.get(*category).name()
+            ));
+        }
+        match self.until {
+            Some(until) => text.push_str(&format!("\nuntil: {}", until)),
+            None => text.push_str("\nuntil: never"),
+        }
+        text
+    }
+
+    /// EIP-191 digest of [`Self::message`] — what the signature recovers over.
+    pub fn digest(&self) -> [u8; 32] {
+        let message = self.message();
+        let mut h = Keccak256::new();
+        h.update(format!("\x19Ethereum Signed Message:\n{}", message.len()).as_bytes());
+        h.update(message.as_bytes());
+        h.finalize().into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order(levels: LogLevels, until: Option<u64>) -> LogLevelOrder {
+        LogLevelOrder {
+            format: LOG_ORDER_FORMAT,
+            deployment: "rack-3".into(),
+            device: None,
+            sequence: 2,
+            levels,
+            until,
+        }
+    }
+
+    #[test]
+    fn levels_include_the_ones_below() {
+        let mut levels = LogLevels::DEFAULT;
+        levels.set(LogCategory::Storage, LogLevel::Off);
+        levels.set(LogCategory::Policy, LogLevel::Warn);
+        assert!(levels.enabled(LogCategory::Crypto, LogLevel::Info));
+        assert!(!levels.enabled(LogCategory::Crypto, LogLevel::Debug));
+        assert!(!levels.enabled(LogCategory::Storage, LogLevel::Warn));
+        assert!(levels.enabled(LogCategory::Policy, LogLevel::Warn));
+        assert!(!levels.enabled(LogCategory::Policy, LogLevel::Info));
+        assert!(!LogLevels::all(LogLevel::Debug).enabled(LogCategory::Session, LogLevel::Off));
+    }
+
+    #[test]
+    fn debug_must_expire_within_the_window() {
+        let mut debug = LogLevels::DEFAULT;
+        debug.set(LogCategory::Crypto, LogLevel::Debug);
+        assert!(order(debug, None).validate().is_err());
+        assert!(order(LogLevels::all(LogLevel::Warn), None)
+            .validate()
+            .is_ok());
+
+        let now = 1_700_000_000;
+        assert!(order(debug, Some(now + 3600)).check_window(now).is_ok());
+        assert!(order(debug, Some(now)).check_window(now).is_err());
+        assert!(order(debug, Some(now + MAX_DEBUG_WINDOW_SECS + 1))
+            .check_window(now)
+            .is_err());
+        // Only debug is bounded; a quieter temporary setting may run longer.
+        assert!(
+            order(LogLevels::DEFAULT, Some(now + MAX_DEBUG_WINDOW_SECS + 1))
+                .check_window(now)
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn signed_text_is_stable() {
+        let mut levels = LogLevels::DEFAULT;
+        levels.set(LogCategory::Session, LogLevel::Debug);
+        let o = order(levels, Some(1_700_003_600));
+        assert_eq!(
+            o.message(),
+            "AirAccount TA log levels v1\ndeployment: rack-3\ndevice: any\nsequence: 2\n\
+             crypto: info\nstorage: info\nsession: debug\npolicy: info\nuntil: 1700003600"
+        );
+        assert!(order(LogLevels::DEFAULT, None)
+            .message()
+            .ends_with("until: never"));
+        assert_ne!(o.digest(), order(LogLevels::DEFAULT, None).digest());
+        for category in LogCategory::ALL.iter() {
+            assert_eq!(category.name().parse::<LogCategory>(), Ok(*category));
+        }
+        for level in [
+            LogLevel::Off,
+            LogLevel::Warn,
+            LogLevel::Info,
+            LogLevel::Debug,
+        ] {
+            assert_eq!(level.name().parse::<LogLevel>(), Ok(level));
+        }
+    }
+}
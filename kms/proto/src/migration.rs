@@ -0,0 +1,167 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Hardware-wallet migration — moving a Ledger/Trezor seed into the TA.
+//!
+//! 1. The user exports the account xpub (`m/44'/60'/0'`) from the device. The
+//!    CA derives the first receive addresses from it so the user can compare
+//!    them with what the hardware wallet shows, and the TA opens a session:
+//!    a fresh secp256k1 ephemeral key bound to that xpub by
+//!    [`offer_digest`], which is also the attestation nonce.
+//! 2. The client encrypts the recovery phrase to the ephemeral key (the
+//!    replication transport: ECDH, HMAC-SHA256 keystream, encrypt-then-MAC
+//!    over [`sealed_aad`]) and names the addresses the user confirmed.
+//! 3. The TA decrypts, recomputes `m/44'/60'/0'` from the phrase and refuses
+//!    unless it equals the xpub and every confirmed address derives from the
+//!    seed. Only then is the wallet created.
+//!
+//! Scope is deliberately narrow: 24-word English BIP39 phrases without a
+//! passphrase, on the standard Ethereum path — the only layout the TA derives.
+
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+
+pub const MIGRATION_VERSION: u8 = 1;
+
+/// How long the TA keeps the ephemeral secret behind an offer.
+pub const MIGRATION_TTL_SECS: u64 = 600;
+
+/// The only account the TA derives, and so the only xpub it can check.
+pub const ACCOUNT_PATH: &str = "m/44'/60'/0'";
+
+/// Addresses a migration may name for the cross-check.
+pub const MAX_CONFIRMED_ADDRESSES: usize = 20;
+
+const DOMAIN: &[u8] = b"AirAccount-migration-v1";
+
+/// The public half of `m/44'/60'/0'` as exported by the hardware wallet.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AccountXpub {
+    /// Compressed secp256k1 public key (33 bytes).
+    pub public_key: Vec<u8>,
+    pub chain_code: [u8; 32],
+}
+
+/// A receive address the user compared against the hardware wallet screen.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConfirmedAddress {
+    /// `m/44'/60'/0'/0/{index}`.
+    pub index: u32,
+    pub address: [u8; 20],
+}
+
+/// At least one and at most [`MAX_CONFIRMED_ADDRESSES`], distinct indexes.
+pub fn validate_confirmed(confirmed: &[ConfirmedAddress]) -> Result<(), &'static str> {
+    if confirmed.is_empty() {
+        return Err("migration needs at least one confirmed address");
+    }
+    if confirmed.len() > MAX_CONFIRMED_ADDRESSES {
+        return Err("migration names at most 20 confirmed addresses");
+    }
+    for (i, c) in confirmed.iter().enumerate() {
+        if c.index >= 0x8000_0000 {
+            return Err("confirmed address index must be non-hardened");
+        }
+        if confirmed[..i].iter().any(|p| p.index == c.index) {
+            return Err("confirmed address indexes must be distinct");
+        }
+    }
+    Ok(())
+}
+
+/// Attestation nonce of an offer: commits the ephemeral key to the xpub the
+/// user is migrating, so the key cannot be replayed for another session.
+pub fn offer_digest(
+    session_id: &[u8; 16],
+    ephemeral_pubkey: &[u8],
+    xpub: &AccountXpub,
+    created_at: u64,
+) -> [u8; 32] {
+    let mut h = Keccak256::new();
+    h.update(DOMAIN);
+    h.update([MIGRATION_VERSION]);
+    h.update(session_id);
+    h.update((ephemeral_pubkey.len() as u32).to_be_bytes());
+    h.update(ephemeral_pubkey);
+    h.update((xpub.public_key.len() as u32).to_be_bytes());
+    h.update(&xpub.public_key);
+    h.update(xpub.chain_code);
+    h.update(created_at.to_be_bytes());
+    h.finalize().into()
+}
+
+/// Header bytes the MAC authenticates along with the encrypted phrase.
+pub fn sealed_aad(
+    session_id: &[u8; 16],
+    ta_pubkey: &[u8],
+    client_pubkey: &[u8],
+    xpub: &AccountXpub,
+) -> Vec<u8> {
+    let mut out = Vec::with_capacity(DOMAIN.len() + 1 + 16 + 33 * 3 + 32);
+    out.extend_from_slice(DOMAIN);
+    out.push(MIGRATION_VERSION);
+    out.extend_from_slice(session_id);
+    out.extend_from_slice(ta_pubkey);
+    out.extend_from_slice(client_pubkey);
+    out.extend_from_slice(&xpub.public_key);
+    out.extend_from_slice(&xpub.chain_code);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn xpub() -> AccountXpub {
+        AccountXpub {
+            public_key: vec![0x02; 33],
+            chain_code: [0xcc; 32],
+        }
+    }
+
+    #[test]
+    fn confirmed_addresses() {
+        let at = |index| ConfirmedAddress {
+            index,
+            address: [0x5a; 20],
+        };
+        assert!(validate_confirmed(&[at(0), at(3)]).is_ok());
+        assert!(validate_confirmed(&[]).is_err());
+        assert!(validate_confirmed(&[at(1), at(1)]).is_err());
+        assert!(validate_confirmed(&[at(0x8000_0000)]).is_err());
+        assert!(validate_confirmed(&(0..21).map(at).collect::<Vec<_>>()).is_err());
+    }
+
+    #[test]
+    fn digest_binds_the_xpub() {
+        let base = offer_digest(&[1; 16], &[0x03; 33], &xpub(), 1_700_000_000);
+        let mut other = xpub();
+        other.chain_code[0] ^= 1;
+        assert_ne!(
+            base,
+            offer_digest(&[1; 16], &[0x03; 33], &other, 1_700_000_000)
+        );
+        assert_ne!(
+            base,
+            offer_digest(&[2; 16], &[0x03; 33], &xpub(), 1_700_000_000)
+        );
+        assert_ne!(
+            sealed_aad(&[1; 16], &[0x03; 33], &[0x02; 33], &xpub()),
+            sealed_aad(&[1; 16], &[0x03; 33], &[0x02; 33], &other)
+        );
+    }
+}
@@ -0,0 +1,231 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! M-of-N co-signer approval of high-value transactions.
+//!
+//! The owner sets a [`MultiSigPolicy`] with a passkey
+//! (`SetMultiSigPolicy`): N co-signer keys, how many must approve, and the
+//! native value above which they must. A transaction Sign above the
+//! threshold still passes every other check and is signed, but the TA holds
+//! the signature as a pending operation and answers [`MultiSigPending`]
+//! instead. Each co-signer approves with `ApproveMultiSig`, a compact ECDSA
+//! signature over [`approval_digest`]; the approval that reaches M releases
+//! the held signature.
+//!
+//! Co-signer keys have the shape of the remote approver's
+//! (`remote_approval::validate_approver_key`), and the operation id is the
+//! payload the passkey confirmed — the tx signing hash, or
+//! `calldata::confirmation_digest` for a summary-committed Sign — so a
+//! co-signer app recomputes it from the transaction it is shown.
+
+use crate::remote_approval::validate_approver_key;
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+use uuid::Uuid;
+
+/// Co-signer keys per wallet.
+pub const MAX_COSIGNERS: usize = 10;
+/// Operations a wallet may have waiting; a Sign beyond that is refused.
+pub const MAX_PENDING: usize = 8;
+/// How long a held signature waits for its approvals.
+pub const PENDING_TTL_SECS: i64 = 86_400;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MultiSigPolicy {
+    /// A transaction sending more native value (wei) than this needs
+    /// approval; 0 = any transaction that sends value.
+    pub threshold_wei: u128,
+    /// M: approvals that release the signature.
+    pub required: u8,
+    /// N compressed secp256k1 keys, distinct.
+    pub cosigners: Vec<Vec<u8>>,
+}
+
+impl MultiSigPolicy {
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if self.cosigners.is_empty() || self.cosigners.len() > MAX_COSIGNERS {
+            return Err("a multi-sig policy needs 1 to 10 co-signer keys");
+        }
+        if self.required == 0 || self.required as usize > self.cosigners.len() {
+            return Err("required approvals must be between 1 and the number of co-signers");
+        }
+        for (i, key) in self.cosigners.iter().enumerate() {
+            validate_approver_key(key)?;
+            if self.cosigners[..i].contains(key) {
+                return Err("co-signer keys must be distinct");
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether a transaction sending `value` waits for the co-signers.
+    pub fn applies_to(&self, value: u128) -> bool {
+        value > self.threshold_wei
+    }
+}
+
+/// Passkey commitment for setting `policy`, or removing it (None).
+pub fn policy_commitment(wallet_id: &Uuid, policy: Option<&MultiSigPolicy>) -> [u8; 32] {
+    let mut h = Keccak256::new();
+    h.update(b"AA-MULTISIG-POLICY-v1");
+    h.update(wallet_id.as_bytes());
+    match policy {
+        Some(policy) => {
+            h.update([1u8]);
+            h.update(policy.threshold_wei.to_be_bytes());
+            h.update([policy.required, policy.cosigners.len() as u8]);
+            for key in &policy.cosigners {
+                h.update((key.len() as u32).to_be_bytes());
+                h.update(key);
+            }
+        }
+        None => h.update([0u8]),
+    }
+    h.finalize().into()
+}
+
+/// What a co-signer signs to approve `operation` for `wallet_id`.
+pub fn approval_digest(wallet_id: &Uuid, operation: &[u8; 32]) -> [u8; 32] {
+    let mut h = Keccak256::new();
+    h.update(b"AA-MULTISIG-APPROVAL-v1");
+    h.update(wallet_id.as_bytes());
+    h.update(operation);
+    h.finalize().into()
+}
+
+/// The Sign was held for co-signer approval. See [`MultiSigPending::find`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MultiSigPending {
+    pub operation: [u8; 32],
+    pub approvals: u8,
+    pub required: u8,
+}
+
+const PENDING_TAG: &str = "MultiSigPending: ";
+
+impl std::fmt::Display for MultiSigPending {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}operation 0x", PENDING_TAG)?;
+        for b in &self.operation {
+            write!(f, "{:02x}", b)?;
+        }
+        write!(
+            f,
+            " has {}/{} co-signer approvals",
+            self.approvals, self.required
+        )
+    }
+}
+
+impl std::error::Error for MultiSigPending {}
+
+impl MultiSigPending {
+    /// Recover the error from a TA error message that contains it.
+    pub fn find(message: &str) -> Option<Self> {
+        let text = &message[message.find(PENDING_TAG)? + PENDING_TAG.len()..];
+        let mut words = text.split(' ');
+        if words.next()? != "operation" {
+            return None;
+        }
+        let hex = words.next()?.strip_prefix("0x")?;
+        if hex.len() != 64 {
+            return None;
+        }
+        let mut operation = [0u8; 32];
+        for (i, byte) in operation.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(hex.get(i * 2..i * 2 + 2)?, 16).ok()?;
+        }
+        if words.next()? != "has" {
+            return None;
+        }
+        let (approvals, required) = words.next()?.split_once('/')?;
+        Some(MultiSigPending {
+            operation,
+            approvals: approvals.parse().ok()?,
+            required: required.parse().ok()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(required: u8, n: u8) -> MultiSigPolicy {
+        MultiSigPolicy {
+            threshold_wei: 10u128.pow(18),
+            required,
+            cosigners: (0..n).map(|i| [vec![0x02], vec![i; 32]].concat()).collect(),
+        }
+    }
+
+    #[test]
+    fn policy_shape() {
+        assert!(policy(2, 3).validate().is_ok());
+        assert!(policy(0, 3).validate().is_err());
+        assert!(policy(4, 3).validate().is_err());
+        assert!(policy(1, 0).validate().is_err());
+        assert!(policy(1, MAX_COSIGNERS as u8 + 1).validate().is_err());
+        let mut twice = policy(1, 2);
+        twice.cosigners[1] = twice.cosigners[0].clone();
+        assert!(twice.validate().is_err());
+        let mut uncompressed = policy(1, 1);
+        uncompressed.cosigners[0] = vec![0x04; 65];
+        assert!(uncompressed.validate().is_err());
+
+        let p = policy(2, 3);
+        assert!(!p.applies_to(10u128.pow(18)));
+        assert!(p.applies_to(10u128.pow(18) + 1));
+    }
+
+    #[test]
+    fn commitments_are_domain_separated() {
+        let w = Uuid::from_bytes([1; 16]);
+        let p = policy(2, 3);
+        assert_ne!(policy_commitment(&w, Some(&p)), policy_commitment(&w, None));
+        assert_ne!(
+            policy_commitment(&w, Some(&p)),
+            policy_commitment(&w, Some(&policy(3, 3)))
+        );
+        assert_ne!(
+            policy_commitment(&w, None),
+            policy_commitment(&Uuid::nil(), None)
+        );
+        let op = [7u8; 32];
+        assert_ne!(
+            approval_digest(&w, &op),
+            crate::remote_approval::approval_digest(&w, &op)
+        );
+        assert_ne!(approval_digest(&w, &op), approval_digest(&Uuid::nil(), &op));
+    }
+
+    #[test]
+    fn the_pending_state_survives_the_error_text() {
+        let p = MultiSigPending {
+            operation: [0xab; 32],
+            approvals: 1,
+            required: 3,
+        };
+        let text = format!("TA command failed: {} (error: BadParameters)", p);
+        assert_eq!(MultiSigPending::find(&text), Some(p));
+        assert_eq!(
+            MultiSigPending::find("MultiSigPending: operation 0x12"),
+            None
+        );
+        assert_eq!(MultiSigPending::find("wallet not found"), None);
+    }
+}
@@ -0,0 +1,376 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Per-wallet nonce tracking, off unless the owner turns it on
+//! (`Command::NonceTracking`).
+//!
+//! With tracking on, the TA keeps the highest nonce it has signed for each
+//! (chain_id, signer address) and refuses a transaction whose nonce is not
+//! above it, so a compromised CA cannot get a second transaction signed for
+//! a nonce (replacing or front-running the first) or replay an old one.
+//! A refusal is a [`NonceRefused`], which travels as error text like
+//! `spending_policy::PolicyViolation`.
+//!
+//! A fee bump or cancel legitimately re-signs a nonce: the owner arms a
+//! one-time [`NonceOverride`] for exactly that (chain, address, nonce) with
+//! a passkey (`Command::OverrideNonce`), and the next signature for it
+//! consumes the override.
+
+use crate::calldata::hex_addr;
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+use uuid::Uuid;
+
+/// (chain_id, address) pairs a wallet may track. Full = refuse (fail
+/// closed) rather than forget a pair whose nonces could then be reused.
+pub const MAX_TRACKED: usize = 64;
+/// How long an armed override waits for its signature.
+pub const OVERRIDE_TTL_SECS: u64 = 600;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NonceEntry {
+    pub chain_id: u64,
+    pub address: [u8; 20],
+    /// Highest nonce signed for this pair.
+    pub highest: u128,
+}
+
+/// One signature the owner allowed at or below the high-water mark.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NonceOverride {
+    pub chain_id: u64,
+    pub address: [u8; 20],
+    pub nonce: u128,
+    /// TA time (unix seconds).
+    pub expires_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct NonceLedger {
+    pub enabled: bool,
+    pub entries: Vec<NonceEntry>,
+    pub pending_override: Option<NonceOverride>,
+}
+
+impl NonceLedger {
+    pub fn highest(&self, chain_id: u64, address: &[u8; 20]) -> Option<u128> {
+        self.entries
+            .iter()
+            .find(|e| e.chain_id == chain_id && e.address == *address)
+            .map(|e| e.highest)
+    }
+
+    /// Turn tracking on or off. A change starts from an empty ledger, which
+    /// is also how the owner rewinds after a chain reset.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        if self.enabled != enabled {
+            *self = NonceLedger {
+                enabled,
+                ..Default::default()
+            };
+        }
+    }
+
+    /// Allow one signature of `nonce`, replacing any override still armed.
+    pub fn arm_override(
+        &mut self,
+        chain_id: u64,
+        address: [u8; 20],
+        nonce: u128,
+        now: u64,
+    ) -> Result<NonceOverride, &'static str> {
+        if !self.enabled {
+            return Err("nonce tracking is off for this wallet");
+        }
+        let armed = NonceOverride {
+            chain_id,
+            address,
+            nonce,
+            expires_at: now.saturating_add(OVERRIDE_TTL_SECS),
+        };
+        self.pending_override = Some(armed);
+        Ok(armed)
+    }
+
+    /// Admit a signature of `nonce` and raise the mark, consuming a matching
+    /// override if the nonce is not above it. The caller persists the ledger
+    /// before releasing the signature.
+    pub fn admit(
+        &mut self,
+        chain_id: u64,
+        address: [u8; 20],
+        nonce: u128,
+        now: u64,
+    ) -> Result<(), NonceRefused> {
+        if !self.enabled {
+            return Ok(());
+        }
+        if self.pending_override.is_some_and(|o| o.expires_at <= now) {
+            self.pending_override = None;
+        }
+        let index = self
+            .entries
+            .iter()
+            .position(|e| e.chain_id == chain_id && e.address == address);
+        match index {
+            Some(i) => {
+                let highest = self.entries[i].highest;
+                if nonce <= highest {
+                    let overridden = self.pending_override.is_some_and(|o| {
+                        o.chain_id == chain_id && o.address == address && o.nonce == nonce
+                    });
+                    if !overridden {
+                        return Err(NonceRefused::Reused {
+                            chain_id,
+                            address,
+                            nonce,
+                            highest,
+                        });
+                    }
+                    self.pending_override = None;
+                }
+                self.entries[i].highest = highest.max(nonce);
+            }
+            None => {
+                if self.entries.len() >= MAX_TRACKED {
+                    return Err(NonceRefused::LedgerFull {
+                        tracked: self.entries.len(),
+                    });
+                }
+                self.entries.push(NonceEntry {
+                    chain_id,
+                    address,
+                    highest: nonce,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Passkey commitment for turning tracking on or off.
+pub fn tracking_commitment(wallet_id: &Uuid, enabled: bool) -> [u8; 32] {
+    let mut h = Keccak256::new();
+    h.update(b"AA-NONCE-TRACKING-v1");
+    h.update(wallet_id.as_bytes());
+    h.update([enabled as u8]);
+    h.finalize().into()
+}
+
+/// Passkey commitment for `OverrideNonce`: this signer, chain and nonce.
+pub fn override_commitment(
+    wallet_id: &Uuid,
+    chain_id: u64,
+    address: &[u8; 20],
+    nonce: u128,
+) -> [u8; 32] {
+    let mut h = Keccak256::new();
+    h.update(b"AA-NONCE-OVERRIDE-v1");
+    h.update(wallet_id.as_bytes());
+    h.update(chain_id.to_be_bytes());
+    h.update(address);
+    h.update(nonce.to_be_bytes());
+    h.finalize().into()
+}
+
+/// Why the TA would not sign a nonce. See [`NonceRefused::find`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NonceRefused {
+    /// `nonce` is not above the highest one signed and no override is armed.
+    Reused {
+        chain_id: u64,
+        address: [u8; 20],
+        nonce: u128,
+        highest: u128,
+    },
+    LedgerFull {
+        tracked: usize,
+    },
+}
+
+const REFUSED_TAG: &str = "NonceRefused: ";
+
+impl NonceRefused {
+    /// Kebab-case reason, the first word after the tag.
+    pub fn reason(&self) -> &'static str {
+        match self {
+            NonceRefused::Reused { .. } => "nonce-reused",
+            NonceRefused::LedgerFull { .. } => "ledger-full",
+        }
+    }
+
+    /// Recover the error from a TA error message that contains it.
+    pub fn find(message: &str) -> Option<Self> {
+        let text = &message[message.find(REFUSED_TAG)? + REFUSED_TAG.len()..];
+        let mut words = text.split_whitespace();
+        let reason = words.next()?;
+        let fields: Vec<(&str, &str)> = words.map_while(|w| w.split_once('=')).collect();
+        let field = |name: &str| fields.iter().find(|(k, _)| *k == name).map(|(_, v)| *v);
+        Some(match reason {
+            "nonce-reused" => NonceRefused::Reused {
+                chain_id: field("chain_id")?.parse().ok()?,
+                address: crate::eip55::parse_address(field("address")?).ok()?,
+                nonce: field("nonce")?.parse().ok()?,
+                highest: field("highest")?.parse().ok()?,
+            },
+            "ledger-full" => NonceRefused::LedgerFull {
+                tracked: field("tracked")?.parse().ok()?,
+            },
+            _ => return None,
+        })
+    }
+}
+
+impl std::fmt::Display for NonceRefused {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", REFUSED_TAG, self.reason())?;
+        match self {
+            NonceRefused::Reused {
+                chain_id,
+                address,
+                nonce,
+                highest,
+            } => write!(
+                f,
+                " chain_id={} address={} nonce={} highest={}",
+                chain_id,
+                hex_addr(address),
+                nonce,
+                highest
+            ),
+            NonceRefused::LedgerFull { tracked } => write!(f, " tracked={}", tracked),
+        }
+    }
+}
+
+impl std::error::Error for NonceRefused {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ME: [u8; 20] = [0xa1; 20];
+
+    fn on() -> NonceLedger {
+        let mut ledger = NonceLedger::default();
+        ledger.set_enabled(true);
+        ledger
+    }
+
+    #[test]
+    fn off_admits_anything() {
+        let mut ledger = NonceLedger::default();
+        assert!(ledger.admit(1, ME, 5, 0).is_ok());
+        assert!(ledger.admit(1, ME, 5, 0).is_ok());
+        assert!(ledger.entries.is_empty());
+    }
+
+    #[test]
+    fn only_increasing_nonces_per_chain_and_address() {
+        let mut ledger = on();
+        ledger.admit(1, ME, 5, 0).unwrap();
+        assert_eq!(
+            ledger.admit(1, ME, 5, 0),
+            Err(NonceRefused::Reused {
+                chain_id: 1,
+                address: ME,
+                nonce: 5,
+                highest: 5
+            })
+        );
+        assert!(ledger.admit(1, ME, 4, 0).is_err());
+        // a gap is the CA's business; the mark just moves up
+        ledger.admit(1, ME, 9, 0).unwrap();
+        assert!(ledger.admit(1, ME, 6, 0).is_err());
+        // other chains and signers are tracked on their own
+        ledger.admit(10, ME, 0, 0).unwrap();
+        ledger.admit(1, [0xb0; 20], 0, 0).unwrap();
+        assert_eq!(ledger.highest(1, &ME), Some(9));
+        assert_eq!(ledger.entries.len(), 3);
+    }
+
+    #[test]
+    fn an_override_admits_one_signature_until_it_expires() {
+        let mut ledger = on();
+        ledger.admit(1, ME, 7, 0).unwrap();
+        ledger.arm_override(1, ME, 7, 100).unwrap();
+        // it names one (chain, address, nonce)
+        assert!(ledger.admit(1, ME, 6, 100).is_err());
+        assert!(ledger.admit(10, ME, 7, 100).is_ok());
+        ledger.admit(1, ME, 7, 100).unwrap();
+        assert!(ledger.pending_override.is_none());
+        assert!(ledger.admit(1, ME, 7, 100).is_err());
+        // the mark stays where it was
+        ledger.arm_override(1, ME, 3, 100).unwrap();
+        ledger.admit(1, ME, 3, 200).unwrap();
+        assert_eq!(ledger.highest(1, &ME), Some(7));
+
+        ledger.arm_override(1, ME, 7, 1000).unwrap();
+        assert!(ledger.admit(1, ME, 7, 1000 + OVERRIDE_TTL_SECS).is_err());
+        assert!(ledger.pending_override.is_none());
+        assert!(NonceLedger::default().arm_override(1, ME, 7, 0).is_err());
+    }
+
+    #[test]
+    fn toggling_starts_over_and_a_full_ledger_fails_closed() {
+        let mut ledger = on();
+        for i in 0..MAX_TRACKED {
+            ledger.admit(i as u64, ME, 0, 0).unwrap();
+        }
+        assert_eq!(
+            ledger.admit(9999, ME, 0, 0),
+            Err(NonceRefused::LedgerFull {
+                tracked: MAX_TRACKED
+            })
+        );
+        // a known pair still moves
+        ledger.admit(0, ME, 1, 0).unwrap();
+        ledger.set_enabled(true);
+        assert_eq!(ledger.entries.len(), MAX_TRACKED);
+        ledger.set_enabled(false);
+        ledger.set_enabled(true);
+        assert!(ledger.entries.is_empty());
+        assert!(ledger.admit(0, ME, 0, 0).is_ok());
+    }
+
+    #[test]
+    fn refusal_survives_the_error_text() {
+        for refused in [
+            NonceRefused::Reused {
+                chain_id: 10,
+                address: ME,
+                nonce: 3,
+                highest: 41,
+            },
+            NonceRefused::LedgerFull { tracked: 64 },
+        ] {
+            let message = format!("TA command failed: sign: {} (error: 0xffff0000)", refused);
+            assert_eq!(NonceRefused::find(&message), Some(refused));
+        }
+        assert_eq!(NonceRefused::find("NonceRefused: something-else"), None);
+
+        let w = Uuid::from_bytes([3; 16]);
+        assert_ne!(
+            tracking_commitment(&w, true),
+            tracking_commitment(&w, false)
+        );
+        assert_ne!(
+            override_commitment(&w, 1, &ME, 7),
+            override_commitment(&w, 1, &ME, 8)
+        );
+    }
+}
@@ -0,0 +1,346 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Air-gapped signing records.
+//!
+//! An online CA exports an [`OfflineSignRequest`] (CBOR, carried by QR), an
+//! offline device's TA signs it via `SignOffline`, and the online CA imports
+//! the [`OfflineSignResponse`] and checks it against the request it issued.
+//! `request_id` + `expires_at` are the replay protection on both sides: the
+//! TA refuses an id it has already signed or a request past its expiry, the
+//! online CA accepts one response per exported request.
+//!
+//! The legacy-tx RLP helpers let the online side, which has no TA key,
+//! check that the returned signed tx is exactly the one it asked for.
+
+use crate::EthTransaction;
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+use uuid::Uuid;
+
+pub const OFFLINE_FORMAT_VERSION: u8 = 1;
+
+/// Longest validity window a request may carry. Bounds the TA's replay log:
+/// an id only has to be remembered until its request expires.
+pub const MAX_OFFLINE_TTL_SECS: u64 = 24 * 3600;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OfflineSignRequest {
+    pub version: u8,
+    pub request_id: [u8; 16],
+    pub wallet_id: Uuid,
+    pub hd_path: String,
+    pub transaction: EthTransaction,
+    /// Unix seconds on the exporting CA.
+    pub created_at: u64,
+    pub expires_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OfflineSignResponse {
+    pub version: u8,
+    pub request_id: [u8; 16],
+    /// RLP of the EIP-155 signed legacy transaction.
+    pub signed_transaction: Vec<u8>,
+}
+
+impl OfflineSignRequest {
+    /// Shape and validity window at `now`. Both ends run it; clocks on an
+    /// air-gapped device drift, so the window is the only time check.
+    pub fn check(&self, now: u64) -> Result<(), &'static str> {
+        if self.version != OFFLINE_FORMAT_VERSION {
+            return Err("unsupported offline request version");
+        }
+        if self.expires_at <= self.created_at
+            || self.expires_at - self.created_at > MAX_OFFLINE_TTL_SECS
+        {
+            return Err("offline request validity window is invalid");
+        }
+        if now >= self.expires_at {
+            return Err("offline request expired");
+        }
+        Ok(())
+    }
+}
+
+/// Passkey commitment for SignOffline (challenge = SHA-256(nonce || this)):
+/// keccak256(domain || request_id || wallet_id || expires_at || tx_hash). A
+/// confirmation for one request cannot be replayed under another id or window.
+pub fn offline_commitment(req: &OfflineSignRequest, tx_hash: &[u8; 32]) -> [u8; 32] {
+    let mut h = Keccak256::new();
+    h.update(b"AirAccount-offline-sign-v1");
+    h.update(req.request_id);
+    h.update(req.wallet_id.as_bytes());
+    h.update(req.expires_at.to_be_bytes());
+    h.update(tx_hash);
+    h.finalize().into()
+}
+
+// ── Legacy transaction RLP (the helpers are shared with `eip1559`) ──
+
+fn rlp_len_prefix(out: &mut Vec<u8>, len: usize, short: u8) {
+    if len < 56 {
+        out.push(short + len as u8);
+    } else {
+        let be = (len as u64).to_be_bytes();
+        let skip = be.iter().take_while(|b| **b == 0).count();
+        out.push(short + 55 + (8 - skip) as u8);
+        out.extend_from_slice(&be[skip..]);
+    }
+}
+
+pub(crate) fn rlp_bytes(out: &mut Vec<u8>, b: &[u8]) {
+    if b.len() == 1 && b[0] < 0x80 {
+        out.push(b[0]);
+    } else {
+        rlp_len_prefix(out, b.len(), 0x80);
+        out.extend_from_slice(b);
+    }
+}
+
+pub(crate) fn rlp_uint(out: &mut Vec<u8>, v: u128) {
+    let be = v.to_be_bytes();
+    let skip = be.iter().take_while(|b| **b == 0).count();
+    rlp_bytes(out, &be[skip..]);
+}
+
+pub(crate) fn rlp_list(items: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(items.len() + 9);
+    rlp_len_prefix(&mut out, items.len(), 0xc0);
+    out.extend_from_slice(items);
+    out
+}
+
+fn tx_fields(tx: &EthTransaction) -> Vec<u8> {
+    let mut items = Vec::new();
+    rlp_uint(&mut items, tx.nonce);
+    rlp_uint(&mut items, tx.gas_price);
+    rlp_uint(&mut items, tx.gas);
+    rlp_bytes(&mut items, tx.to.as_ref().map(|t| &t[..]).unwrap_or(&[]));
+    rlp_uint(&mut items, tx.value);
+    rlp_bytes(&mut items, &tx.data);
+    items
+}
+
+/// EIP-155 unsigned payload: the legacy fields followed by (chain_id, 0, 0).
+pub fn unsigned_legacy_rlp(tx: &EthTransaction) -> Vec<u8> {
+    let mut items = tx_fields(tx);
+    rlp_uint(&mut items, tx.chain_id as u128);
+    rlp_uint(&mut items, 0);
+    rlp_uint(&mut items, 0);
+    rlp_list(&items)
+}
+
+/// EIP-155 signing hash of the legacy tx. Same digest the TA signs in
+/// SignTransaction (`Wallet::tx_signing_hash`).
+pub fn legacy_signing_hash(tx: &EthTransaction) -> [u8; 32] {
+    Keccak256::digest(unsigned_legacy_rlp(tx)).into()
+}
+
+/// A decoded EIP-155 signed legacy transaction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignedLegacyTx {
+    pub transaction: EthTransaction,
+    pub r: [u8; 32],
+    pub s: [u8; 32],
+    /// 0 or 1.
+    pub recovery_id: u8,
+}
+
+/// Encode an EIP-155 signed legacy tx, as SignTransaction returns it; the
+/// inverse of [`decode_signed_legacy`]. For signers outside the TA.
+pub fn signed_legacy_rlp(signed: &SignedLegacyTx) -> Vec<u8> {
+    let tx = &signed.transaction;
+    let mut items = tx_fields(tx);
+    rlp_uint(
+        &mut items,
+        tx.chain_id as u128 * 2 + 35 + signed.recovery_id as u128,
+    );
+    for word in [&signed.r, &signed.s] {
+        let skip = word.iter().take_while(|b| **b == 0).count();
+        rlp_bytes(&mut items, &word[skip..]);
+    }
+    rlp_list(&items)
+}
+
+/// Split one RLP item off `buf`: (is_list, payload, rest). Canonical form only.
+pub(crate) fn rlp_item(buf: &[u8]) -> Result<(bool, &[u8], &[u8]), &'static str> {
+    let (&tag, rest) = buf.split_first().ok_or("rlp: truncated")?;
+    let (list, off, len) = match tag {
+        0x00..=0x7f => return Ok((false, &buf[..1], rest)),
+        0x80..=0xb7 => (false, 0, (tag - 0x80) as usize),
+        0xc0..=0xf7 => (true, 0, (tag - 0xc0) as usize),
+        _ => {
+            let n = if tag >= 0xf8 { tag - 0xf7 } else { tag - 0xb7 } as usize;
+            let be = rest.get(..n).ok_or("rlp: truncated")?;
+            if n > 4 || be[0] == 0 {
+                return Err("rlp: bad length");
+            }
+            let len = be.iter().fold(0usize, |a, b| (a << 8) | *b as usize);
+            if len < 56 {
+                return Err("rlp: non-canonical length");
+            }
+            (tag >= 0xf8, n, len)
+        }
+    };
+    let payload = rest.get(off..off + len).ok_or("rlp: truncated")?;
+    if !list && len == 1 && payload[0] < 0x80 {
+        return Err("rlp: non-canonical byte");
+    }
+    Ok((list, payload, &rest[off + len..]))
+}
+
+pub(crate) fn rlp_to_uint(b: &[u8], max_len: usize) -> Result<u128, &'static str> {
+    if b.len() > max_len || b.first() == Some(&0) {
+        return Err("rlp: bad integer");
+    }
+    Ok(b.iter().fold(0u128, |a, x| (a << 8) | *x as u128))
+}
+
+pub(crate) fn word32(b: &[u8]) -> Result<[u8; 32], &'static str> {
+    if b.len() > 32 {
+        return Err("rlp: signature component too long");
+    }
+    let mut w = [0u8; 32];
+    w[32 - b.len()..].copy_from_slice(b);
+    Ok(w)
+}
+
+/// Decode an EIP-155 signed legacy tx (as returned by SignTransaction).
+pub fn decode_signed_legacy(raw: &[u8]) -> Result<SignedLegacyTx, &'static str> {
+    let (list, mut body, rest) = rlp_item(raw)?;
+    if !list || !rest.is_empty() {
+        return Err("rlp: not a single list");
+    }
+    let mut f: Vec<&[u8]> = Vec::with_capacity(9);
+    while !body.is_empty() {
+        let (is_list, item, next) = rlp_item(body)?;
+        if is_list {
+            return Err("rlp: nested list in legacy tx");
+        }
+        f.push(item);
+        body = next;
+    }
+    if f.len() != 9 {
+        return Err("not a signed legacy transaction");
+    }
+    let to = match f[3].len() {
+        0 => None,
+        20 => {
+            let mut a = [0u8; 20];
+            a.copy_from_slice(f[3]);
+            Some(a)
+        }
+        _ => return Err("bad 'to' length"),
+    };
+    let v = rlp_to_uint(f[6], 8)? as u64;
+    if v < 35 {
+        return Err("signature is not EIP-155");
+    }
+    let chain_id = (v - 35) / 2;
+    Ok(SignedLegacyTx {
+        transaction: EthTransaction {
+            chain_id,
+            nonce: rlp_to_uint(f[0], 16)?,
+            to,
+            value: rlp_to_uint(f[4], 16)?,
+            gas_price: rlp_to_uint(f[1], 16)?,
+            gas: rlp_to_uint(f[2], 16)?,
+            data: f[5].to_vec(),
+        },
+        r: word32(f[7])?,
+        s: word32(f[8])?,
+        recovery_id: ((v - 35) % 2) as u8,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    /// The worked example from EIP-155.
+    fn eip155_tx() -> EthTransaction {
+        EthTransaction {
+            chain_id: 1,
+            nonce: 9,
+            to: Some([0x35; 20]),
+            value: 1_000_000_000_000_000_000,
+            gas_price: 20_000_000_000,
+            gas: 21_000,
+            data: vec![],
+        }
+    }
+
+    const EIP155_SIGNED: &str = "f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83";
+
+    #[test]
+    fn eip155_vector() {
+        assert_eq!(
+            legacy_signing_hash(&eip155_tx()).to_vec(),
+            hex("daf5a779ae972f972197303d7b574746c7ef83eadac0f2791ad23db92e4c8e53")
+        );
+        let signed = decode_signed_legacy(&hex(EIP155_SIGNED)).unwrap();
+        assert_eq!(signed.transaction, eip155_tx());
+        assert_eq!(signed.recovery_id, 0);
+        assert_eq!(signed.r[0], 0x28);
+        assert_eq!(signed_legacy_rlp(&signed), hex(EIP155_SIGNED));
+    }
+
+    #[test]
+    fn malformed_signed_tx_is_rejected() {
+        let raw = hex(EIP155_SIGNED);
+        assert!(decode_signed_legacy(&raw[..raw.len() - 1]).is_err());
+        let mut trailing = raw.clone();
+        trailing.push(0);
+        assert!(decode_signed_legacy(&trailing).is_err());
+        // the unsigned 6-field form is not a signed tx
+        let mut items = tx_fields(&eip155_tx());
+        assert!(decode_signed_legacy(&rlp_list(&items)).is_err());
+        // nor is a pre-EIP-155 v
+        rlp_uint(&mut items, 27);
+        rlp_bytes(&mut items, &[1; 32]);
+        rlp_bytes(&mut items, &[1; 32]);
+        assert!(decode_signed_legacy(&rlp_list(&items)).is_err());
+    }
+
+    #[test]
+    fn request_window() {
+        let mut req = OfflineSignRequest {
+            version: OFFLINE_FORMAT_VERSION,
+            request_id: [7; 16],
+            wallet_id: Uuid::from_bytes([1; 16]),
+            hd_path: "m/44'/60'/0'/0/0".into(),
+            transaction: eip155_tx(),
+            created_at: 1000,
+            expires_at: 1600,
+        };
+        assert!(req.check(1599).is_ok());
+        assert!(req.check(1600).is_err());
+        let a = offline_commitment(&req, &[0; 32]);
+        req.request_id = [8; 16];
+        assert_ne!(a, offline_commitment(&req, &[0; 32]));
+        req.expires_at = 1000 + MAX_OFFLINE_TTL_SECS + 1;
+        assert!(req.check(1001).is_err());
+    }
+}
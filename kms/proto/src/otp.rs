@@ -0,0 +1,274 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! TOTP / HOTP secrets held in the TEE (RFC 4226, RFC 6238).
+//!
+//! A wallet enrolls 2FA secrets under a label; the TA stores them in secure
+//! storage and only ever returns codes. The HMAC runs in the TA; this module
+//! holds what both sides need: parameter checks, the base32 decoding of
+//! provisioning secrets, RFC 4226 dynamic truncation, the counter rule and
+//! the passkey commitments.
+
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+use uuid::Uuid;
+
+/// RFC 4226 asks for at least 128 bits, but many issuers hand out 80.
+pub const MIN_SECRET_BYTES: usize = 10;
+pub const MAX_SECRET_BYTES: usize = 64;
+/// Secrets per wallet.
+pub const MAX_ENTRIES_PER_WALLET: usize = 32;
+
+const MAX_LABEL_LEN: usize = 64;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OtpAlgorithm {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OtpKind {
+    /// RFC 6238: the counter is the TA's clock divided by the period.
+    Totp { period_secs: u32 },
+    /// RFC 4226: the counter is kept in the TA and advances on every code.
+    Hotp,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OtpParams {
+    pub algorithm: OtpAlgorithm,
+    pub digits: u8,
+    pub kind: OtpKind,
+}
+
+impl OtpParams {
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if !(6..=8).contains(&self.digits) {
+            return Err("digits must be 6, 7 or 8");
+        }
+        if let OtpKind::Totp { period_secs } = self.kind {
+            if !(15..=300).contains(&period_secs) {
+                return Err("period must be 15-300 seconds");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Labels name a secret within the wallet ("github", "aws.root").
+pub fn validate_label(label: &str) -> Result<(), &'static str> {
+    if label.is_empty() || label.len() > MAX_LABEL_LEN {
+        return Err("label must be 1-64 characters");
+    }
+    if !label
+        .bytes()
+        .all(|b| b.is_ascii_alphanumeric() || b"._-@".contains(&b))
+    {
+        return Err("label may only contain A-Z, a-z, 0-9, '.', '-', '_' and '@'");
+    }
+    Ok(())
+}
+
+pub fn validate_secret(secret: &[u8]) -> Result<(), &'static str> {
+    if secret.len() < MIN_SECRET_BYTES || secret.len() > MAX_SECRET_BYTES {
+        return Err("secret must be 10-64 bytes");
+    }
+    Ok(())
+}
+
+/// RFC 4648 base32 as issuers print it: case-insensitive, spaces and `=`
+/// padding ignored.
+pub fn base32_decode(s: &str) -> Result<Vec<u8>, &'static str> {
+    let mut out = Vec::with_capacity(s.len() * 5 / 8);
+    let mut acc: u64 = 0;
+    let mut bits = 0u32;
+    for c in s.bytes().filter(|&c| c != b' ' && c != b'=') {
+        let v = match c.to_ascii_uppercase() {
+            c @ b'A'..=b'Z' => c - b'A',
+            c @ b'2'..=b'7' => c - b'2' + 26,
+            _ => return Err("secret is not valid base32"),
+        };
+        acc = (acc << 5) | v as u64;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+        }
+    }
+    Ok(out)
+}
+
+/// RFC 4226 §5.3 dynamic truncation of an HMAC to `digits` decimal digits.
+pub fn truncate(mac: &[u8], digits: u8) -> u32 {
+    let offset = (mac[mac.len() - 1] & 0x0f) as usize;
+    let bin = u32::from_be_bytes([
+        mac[offset] & 0x7f,
+        mac[offset + 1],
+        mac[offset + 2],
+        mac[offset + 3],
+    ]);
+    bin % 10u32.pow(digits as u32)
+}
+
+/// Zero-padded decimal form of a code.
+pub fn format_code(code: u32, digits: u8) -> String {
+    format!("{:0width$}", code, width = digits as usize)
+}
+
+/// Counter for the next code, given the last one issued for this secret.
+///
+/// TOTP never goes back to an earlier time step: a host that rewinds the
+/// clock cannot replay codes already shown. HOTP advances by one per code.
+pub fn next_counter(kind: OtpKind, last: Option<u64>, now_secs: u64) -> Result<u64, &'static str> {
+    match kind {
+        OtpKind::Totp { period_secs } => {
+            let step = now_secs / period_secs as u64;
+            match last {
+                Some(last) if step < last => Err("clock is behind the last issued code"),
+                _ => Ok(step),
+            }
+        }
+        OtpKind::Hotp => Ok(match last {
+            Some(last) => last + 1,
+            None => 0,
+        }),
+    }
+}
+
+/// Seconds until a TOTP code expires; 0 for HOTP.
+pub fn valid_for_secs(kind: OtpKind, now_secs: u64) -> u32 {
+    match kind {
+        OtpKind::Totp { period_secs } => period_secs - (now_secs % period_secs as u64) as u32,
+        OtpKind::Hotp => 0,
+    }
+}
+
+/// Passkey commitment for enrolling `secret` under `label`. It covers the
+/// secret's hash and the parameters, so the CA cannot swap either.
+pub fn enroll_commitment(
+    wallet_id: &Uuid,
+    label: &str,
+    secret: &[u8],
+    params: &OtpParams,
+) -> [u8; 32] {
+    let mut h = Keccak256::new();
+    h.update(b"AA-OTP-ENROLL-v1");
+    h.update(wallet_id.as_bytes());
+    h.update((label.len() as u32).to_be_bytes());
+    h.update(label.as_bytes());
+    h.update(Keccak256::digest(secret));
+    h.update([
+        match params.algorithm {
+            OtpAlgorithm::Sha1 => 1u8,
+            OtpAlgorithm::Sha256 => 2,
+            OtpAlgorithm::Sha512 => 3,
+        },
+        params.digits,
+    ]);
+    match params.kind {
+        OtpKind::Totp { period_secs } => h.update(period_secs.to_be_bytes()),
+        OtpKind::Hotp => h.update([0u8; 4]),
+    }
+    h.finalize().into()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OtpOp {
+    Code,
+    Remove,
+    List,
+}
+
+/// Passkey commitment for generating a code from, or removing, `label`.
+/// Listing commits to the wallet alone (`label` = "").
+pub fn op_commitment(op: OtpOp, wallet_id: &Uuid, label: &str) -> [u8; 32] {
+    let mut h = Keccak256::new();
+    h.update(match op {
+        OtpOp::Code => &b"AA-OTP-CODE-v1"[..],
+        OtpOp::Remove => &b"AA-OTP-REMOVE-v1"[..],
+        OtpOp::List => &b"AA-OTP-LIST-v1"[..],
+    });
+    h.update(wallet_id.as_bytes());
+    h.update(label.as_bytes());
+    h.finalize().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rfc4226_truncation_and_base32() {
+        // RFC 4226 §5.4 worked example.
+        let mac = [
+            0x1f, 0x86, 0x98, 0x69, 0x0e, 0x02, 0xca, 0x16, 0x61, 0x85, 0x50, 0xef, 0x7f, 0x19,
+            0xda, 0x8e, 0x94, 0x5b, 0x55, 0x5a,
+        ];
+        assert_eq!(truncate(&mac, 6), 872921);
+        assert_eq!(format_code(42, 8), "00000042");
+
+        assert_eq!(
+            base32_decode("GEZDGNBV GY3TQOJQ gezdgnbv gy3tqojq").unwrap(),
+            b"1234567890123456789012345678901234567890"[..20].to_vec()
+        );
+        assert_eq!(base32_decode("MZXW6===").unwrap(), b"foo");
+        assert!(base32_decode("MZXW1").is_err());
+    }
+
+    #[test]
+    fn counters_and_params() {
+        let totp = OtpKind::Totp { period_secs: 30 };
+        assert_eq!(next_counter(totp, None, 59), Ok(1));
+        assert_eq!(next_counter(totp, Some(1), 59), Ok(1));
+        assert!(next_counter(totp, Some(2), 59).is_err());
+        assert_eq!(valid_for_secs(totp, 59), 1);
+        assert_eq!(next_counter(OtpKind::Hotp, None, 0), Ok(0));
+        assert_eq!(next_counter(OtpKind::Hotp, Some(4), 0), Ok(5));
+
+        let mut p = OtpParams {
+            algorithm: OtpAlgorithm::Sha1,
+            digits: 6,
+            kind: totp,
+        };
+        assert!(p.validate().is_ok());
+        p.digits = 9;
+        assert!(p.validate().is_err());
+        p.digits = 8;
+        p.kind = OtpKind::Totp { period_secs: 5 };
+        assert!(p.validate().is_err());
+
+        assert!(validate_label("aws.root@corp").is_ok());
+        assert!(validate_label("a/b").is_err());
+        assert!(validate_secret(&[0; 9]).is_err());
+
+        let w = Uuid::nil();
+        assert_ne!(
+            enroll_commitment(&w, "gh", b"0123456789", &p),
+            enroll_commitment(&w, "gh", b"0123456789x", &p)
+        );
+        assert_ne!(
+            op_commitment(OtpOp::Code, &w, "gh"),
+            op_commitment(OtpOp::Remove, &w, "gh")
+        );
+        assert_ne!(
+            op_commitment(OtpOp::List, &w, ""),
+            op_commitment(OtpOp::Remove, &w, "")
+        );
+    }
+}
@@ -0,0 +1,176 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! ERC-20 gas payment through a paymaster.
+//!
+//! The CA fetches a [`FeeQuote`] (token-per-gas rate and a cap) from the
+//! configured paymaster. When a UserOperation pays its fee in tokens, the
+//! SignHash passkey challenge commits to [`fee_review_digest`] — the
+//! userOpHash, the [`quote_hash`] and the summary the user was shown — and
+//! the TA rebuilds all three, so a CA cannot swap in a worse quote after the
+//! user confirmed.
+
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+
+use crate::calldata::{hex_addr, token_amount, Word};
+use crate::permit::format_timestamp;
+
+/// A paymaster's offer to sponsor gas in exchange for `token`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FeeQuote {
+    pub chain_id: u64,
+    pub paymaster: [u8; 20],
+    pub token: [u8; 20],
+    /// Token base units charged per 10^18 wei of gas cost.
+    pub exchange_rate: u128,
+    /// The most the paymaster may take for this UserOperation, in base units.
+    pub max_token_cost: u128,
+    /// Unix seconds; the TA refuses the quote after this.
+    pub valid_until: u64,
+    /// Chosen by the paymaster so two otherwise equal quotes differ.
+    pub quote_id: [u8; 32],
+}
+
+const QUOTE_DOMAIN: &[u8] = b"AirAccount-fee-quote-v1";
+const FEE_REVIEW_DOMAIN: &[u8] = b"AirAccount-fee-review-v1";
+
+fn u128_word(v: u128) -> Word {
+    let mut w = [0u8; 32];
+    w[16..].copy_from_slice(&v.to_be_bytes());
+    w
+}
+
+impl FeeQuote {
+    /// `chain_id` is the chain of the UserOperation being paid for.
+    pub fn validate(&self, chain_id: u64, now: u64) -> Result<(), &'static str> {
+        if self.chain_id != chain_id {
+            return Err("quote is for a different chain");
+        }
+        if self.paymaster == [0u8; 20] || self.token == [0u8; 20] {
+            return Err("quote needs a paymaster and a token");
+        }
+        if self.exchange_rate == 0 || self.max_token_cost == 0 {
+            return Err("quote has a zero rate or cap");
+        }
+        if now > self.valid_until {
+            return Err("quote has expired");
+        }
+        Ok(())
+    }
+
+    /// Token cost of `gas_cost_wei` at the quoted rate, rounded up; `None` on
+    /// overflow.
+    pub fn token_cost(&self, gas_cost_wei: u128) -> Option<u128> {
+        const WEI_PER_ETH: u128 = 1_000_000_000_000_000_000;
+        let scaled = gas_cost_wei.checked_mul(self.exchange_rate)?;
+        Some(scaled / WEI_PER_ETH + u128::from(scaled % WEI_PER_ETH != 0))
+    }
+
+    /// The line the user confirms.
+    pub fn summary(&self) -> String {
+        format!(
+            "pay gas with up to {} via paymaster {}, quote valid until {}",
+            token_amount(self.chain_id, &self.token, &u128_word(self.max_token_cost)),
+            hex_addr(&self.paymaster),
+            format_timestamp(self.valid_until)
+        )
+    }
+}
+
+/// keccak256(domain || chain_id || paymaster || token || rate || cap ||
+/// valid_until || quote_id), integers big-endian.
+pub fn quote_hash(quote: &FeeQuote) -> [u8; 32] {
+    let mut h = Keccak256::new();
+    h.update(QUOTE_DOMAIN);
+    h.update(quote.chain_id.to_be_bytes());
+    h.update(quote.paymaster);
+    h.update(quote.token);
+    h.update(quote.exchange_rate.to_be_bytes());
+    h.update(quote.max_token_cost.to_be_bytes());
+    h.update(quote.valid_until.to_be_bytes());
+    h.update(quote.quote_id);
+    h.finalize().into()
+}
+
+/// What the passkey challenge of a token-paid SignHash commits to
+/// (challenge = SHA-256(nonce || this)):
+/// keccak256(domain || userOpHash || quote_hash || summary).
+pub fn fee_review_digest(user_op_hash: &[u8; 32], quote: &FeeQuote) -> [u8; 32] {
+    let mut h = Keccak256::new();
+    h.update(FEE_REVIEW_DOMAIN);
+    h.update(user_op_hash);
+    h.update(quote_hash(quote));
+    h.update(quote.summary().as_bytes());
+    h.finalize().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quote() -> FeeQuote {
+        FeeQuote {
+            chain_id: 10,
+            paymaster: [0x11; 20],
+            token: [0x22; 20],
+            exchange_rate: 3_000_000_000,
+            max_token_cost: 5_000_000,
+            valid_until: 1_800_000_000,
+            quote_id: [7; 32],
+        }
+    }
+
+    #[test]
+    fn validate_checks_chain_expiry_and_zeroes() {
+        let q = quote();
+        assert!(q.validate(10, 1_800_000_000).is_ok());
+        assert_eq!(q.validate(1, 0), Err("quote is for a different chain"));
+        assert_eq!(q.validate(10, 1_800_000_001), Err("quote has expired"));
+        let mut free = quote();
+        free.max_token_cost = 0;
+        assert!(free.validate(10, 0).is_err());
+    }
+
+    #[test]
+    fn token_cost_rounds_up() {
+        let q = quote();
+        // 0.001 ETH of gas at 3000 USDC-units-per-ETH (6 decimals) = 3 USDC.
+        assert_eq!(q.token_cost(1_000_000_000_000_000), Some(3_000_000));
+        assert_eq!(q.token_cost(1), Some(1));
+        assert_eq!(q.token_cost(0), Some(0));
+        let mut huge = quote();
+        huge.exchange_rate = u128::MAX;
+        assert_eq!(huge.token_cost(2_000_000_000_000_000_000), None);
+    }
+
+    #[test]
+    fn review_digest_binds_every_quote_field() {
+        let op = [9u8; 32];
+        let base = fee_review_digest(&op, &quote());
+        let mut other = quote();
+        other.max_token_cost += 1;
+        assert_ne!(base, fee_review_digest(&op, &other));
+        let mut other = quote();
+        other.quote_id[0] = 0;
+        assert_ne!(base, fee_review_digest(&op, &other));
+        assert_ne!(base, fee_review_digest(&[8u8; 32], &quote()));
+        assert!(quote()
+            .summary()
+            .contains("0x1111111111111111111111111111111111111111"));
+    }
+}
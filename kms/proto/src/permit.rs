@@ -0,0 +1,606 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! ERC-20 permits: EIP-2612 `Permit` and Uniswap Permit2.
+//!
+//! A [`PermitRequest`] carries only the fields that matter; the EIP-712
+//! domain, type strings and struct hashes are fixed here, so the CA cannot
+//! smuggle a different struct under a familiar name. Permit2's structs nest,
+//! which the flat SignTypedData encoder does not handle, so their hashes are
+//! spelled out by hand.
+//!
+//! `SignPermit` rebuilds [`PermitRequest::summary`] in the TA and the passkey
+//! challenge commits to [`review_digest`] of it — the same trick as a
+//! summary-committed SignTransaction. [`typed_data_grant`] lets plain
+//! SignTypedData recognise a permit so the allowance guard cannot be
+//! sidestepped by signing the struct generically.
+
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+
+use crate::calldata::{addr, hex_addr, token_amount, Word};
+use crate::civil::civil_from_days;
+use crate::{Eip712Domain, Eip712FieldValue, Eip712TypeDef, Eip712Value};
+
+/// The canonical Permit2 deployment (same CREATE2 address on every chain).
+pub const PERMIT2_ADDRESS: [u8; 20] = addr(b"000000000022D473030F116dDEE9F6B43aC78BA3");
+
+const ERC2612_DOMAIN_TYPE: &str =
+    "EIP712Domain(string name,string version,uint256 chainId,address verifyingContract)";
+const PERMIT2_DOMAIN_TYPE: &str =
+    "EIP712Domain(string name,uint256 chainId,address verifyingContract)";
+const PERMIT_TYPE: &str =
+    "Permit(address owner,address spender,uint256 value,uint256 nonce,uint256 deadline)";
+const PERMIT_DETAILS_TYPE: &str =
+    "PermitDetails(address token,uint160 amount,uint48 expiration,uint48 nonce)";
+const PERMIT_SINGLE_TYPE: &str = "PermitSingle(PermitDetails details,address spender,uint256 sigDeadline)PermitDetails(address token,uint160 amount,uint48 expiration,uint48 nonce)";
+const TOKEN_PERMISSIONS_TYPE: &str = "TokenPermissions(address token,uint256 amount)";
+const PERMIT_TRANSFER_FROM_TYPE: &str = "PermitTransferFrom(TokenPermissions permitted,address spender,uint256 nonce,uint256 deadline)TokenPermissions(address token,uint256 amount)";
+
+/// Permit2 primary types. SignTypedData cannot encode them correctly, so it
+/// refuses them whenever an allowance guard is active.
+const PERMIT2_PRIMARY_TYPES: &[&str] = &[
+    "PermitSingle",
+    "PermitBatch",
+    "PermitTransferFrom",
+    "PermitBatchTransferFrom",
+    "PermitWitnessTransferFrom",
+    "PermitBatchWitnessTransferFrom",
+];
+
+/// Deadlines past year 9999 (including `type(uint256).max`) read as "never".
+const LAST_DISPLAYABLE_SECS: u64 = 253_402_300_799;
+
+/// EIP-2612 `permit(owner, spender, value, deadline, v, r, s)` on the token.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Erc2612Permit {
+    pub chain_id: u64,
+    pub token: [u8; 20],
+    /// The token's EIP-712 domain `name` and `version`.
+    pub token_name: String,
+    pub token_version: String,
+    /// Must be the signing address; the TA checks it.
+    pub owner: [u8; 20],
+    pub spender: [u8; 20],
+    pub value: Word,
+    pub nonce: Word,
+    pub deadline: Word,
+}
+
+/// Permit2 `AllowanceTransfer.permit` with a `PermitSingle`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Permit2Single {
+    pub chain_id: u64,
+    pub token: [u8; 20],
+    /// uint160
+    pub amount: Word,
+    /// uint48 unix seconds; 0 means "only in the block it is used".
+    pub expiration: u64,
+    /// uint48
+    pub nonce: u64,
+    pub spender: [u8; 20],
+    pub sig_deadline: Word,
+}
+
+/// Permit2 `SignatureTransfer.permitTransferFrom`: a one-shot transfer.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Permit2Transfer {
+    pub chain_id: u64,
+    pub token: [u8; 20],
+    pub amount: Word,
+    pub spender: [u8; 20],
+    pub nonce: Word,
+    pub deadline: Word,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum PermitRequest {
+    Erc2612(Erc2612Permit),
+    Permit2Single(Permit2Single),
+    Permit2Transfer(Permit2Transfer),
+}
+
+/// What a permit hands out, for the allowance guard.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PermitGrant {
+    pub chain_id: u64,
+    pub token: [u8; 20],
+    pub spender: [u8; 20],
+    pub amount: Word,
+}
+
+fn keccak(data: &[u8]) -> [u8; 32] {
+    Keccak256::digest(data).into()
+}
+
+fn hash_words(words: &[[u8; 32]]) -> [u8; 32] {
+    let mut h = Keccak256::new();
+    for w in words {
+        h.update(w);
+    }
+    h.finalize().into()
+}
+
+fn addr_word(a: &[u8; 20]) -> Word {
+    let mut w = [0u8; 32];
+    w[12..].copy_from_slice(a);
+    w
+}
+
+fn u64_word(v: u64) -> Word {
+    let mut w = [0u8; 32];
+    w[24..].copy_from_slice(&v.to_be_bytes());
+    w
+}
+
+fn word_u64(w: &Word) -> Option<u64> {
+    if w[..24].iter().any(|&b| b != 0) {
+        return None;
+    }
+    let mut lo = [0u8; 8];
+    lo.copy_from_slice(&w[24..]);
+    Some(u64::from_be_bytes(lo))
+}
+
+/// Unix seconds as `YYYY-MM-DD HH:MM:SS UTC` (civil-from-days, no tz data).
+pub fn format_timestamp(secs: u64) -> String {
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    let rem = secs % 86_400;
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+fn deadline_secs(w: &Word) -> Option<u64> {
+    word_u64(w).filter(|&s| s <= LAST_DISPLAYABLE_SECS)
+}
+
+fn describe_deadline(w: &Word) -> String {
+    match deadline_secs(w) {
+        Some(s) => format_timestamp(s),
+        None => "never".to_string(),
+    }
+}
+
+impl PermitRequest {
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if self.grant().spender == [0u8; 20] {
+            return Err("permit spender must not be the zero address");
+        }
+        match self {
+            PermitRequest::Erc2612(p) => {
+                if p.token_name.is_empty() || p.token_name.chars().any(|c| c.is_control()) {
+                    return Err("EIP-2612 permit needs the token's EIP-712 name");
+                }
+                if p.token_version.chars().any(|c| c.is_control()) {
+                    return Err("EIP-2612 token version must be printable");
+                }
+            }
+            PermitRequest::Permit2Single(p) => {
+                if p.amount[..12].iter().any(|&b| b != 0) {
+                    return Err("Permit2 amount must fit in uint160");
+                }
+                if p.expiration >= 1 << 48 || p.nonce >= 1 << 48 {
+                    return Err("Permit2 expiration and nonce must fit in uint48");
+                }
+            }
+            PermitRequest::Permit2Transfer(_) => {}
+        }
+        Ok(())
+    }
+
+    pub fn grant(&self) -> PermitGrant {
+        match self {
+            PermitRequest::Erc2612(p) => PermitGrant {
+                chain_id: p.chain_id,
+                token: p.token,
+                spender: p.spender,
+                amount: p.value,
+            },
+            PermitRequest::Permit2Single(p) => PermitGrant {
+                chain_id: p.chain_id,
+                token: p.token,
+                spender: p.spender,
+                amount: p.amount,
+            },
+            PermitRequest::Permit2Transfer(p) => PermitGrant {
+                chain_id: p.chain_id,
+                token: p.token,
+                spender: p.spender,
+                amount: p.amount,
+            },
+        }
+    }
+
+    /// When the signature stops being usable, in unix seconds; None = never.
+    pub fn signature_deadline(&self) -> Option<u64> {
+        match self {
+            PermitRequest::Erc2612(p) => deadline_secs(&p.deadline),
+            PermitRequest::Permit2Single(p) => deadline_secs(&p.sig_deadline),
+            PermitRequest::Permit2Transfer(p) => deadline_secs(&p.deadline),
+        }
+    }
+
+    pub fn domain_separator(&self) -> [u8; 32] {
+        match self {
+            PermitRequest::Erc2612(p) => hash_words(&[
+                keccak(ERC2612_DOMAIN_TYPE.as_bytes()),
+                keccak(p.token_name.as_bytes()),
+                keccak(p.token_version.as_bytes()),
+                u64_word(p.chain_id),
+                addr_word(&p.token),
+            ]),
+            _ => hash_words(&[
+                keccak(PERMIT2_DOMAIN_TYPE.as_bytes()),
+                keccak(b"Permit2"),
+                u64_word(self.grant().chain_id),
+                addr_word(&PERMIT2_ADDRESS),
+            ]),
+        }
+    }
+
+    pub fn struct_hash(&self) -> [u8; 32] {
+        match self {
+            PermitRequest::Erc2612(p) => hash_words(&[
+                keccak(PERMIT_TYPE.as_bytes()),
+                addr_word(&p.owner),
+                addr_word(&p.spender),
+                p.value,
+                p.nonce,
+                p.deadline,
+            ]),
+            PermitRequest::Permit2Single(p) => {
+                let details = hash_words(&[
+                    keccak(PERMIT_DETAILS_TYPE.as_bytes()),
+                    addr_word(&p.token),
+                    p.amount,
+                    u64_word(p.expiration),
+                    u64_word(p.nonce),
+                ]);
+                hash_words(&[
+                    keccak(PERMIT_SINGLE_TYPE.as_bytes()),
+                    details,
+                    addr_word(&p.spender),
+                    p.sig_deadline,
+                ])
+            }
+            PermitRequest::Permit2Transfer(p) => {
+                let permitted = hash_words(&[
+                    keccak(TOKEN_PERMISSIONS_TYPE.as_bytes()),
+                    addr_word(&p.token),
+                    p.amount,
+                ]);
+                hash_words(&[
+                    keccak(PERMIT_TRANSFER_FROM_TYPE.as_bytes()),
+                    permitted,
+                    addr_word(&p.spender),
+                    p.nonce,
+                    p.deadline,
+                ])
+            }
+        }
+    }
+
+    /// The EIP-712 digest the signature is over.
+    pub fn digest(&self) -> [u8; 32] {
+        let mut h = Keccak256::new();
+        h.update([0x19, 0x01]);
+        h.update(self.domain_separator());
+        h.update(self.struct_hash());
+        h.finalize().into()
+    }
+
+    /// The one-line review the user confirms: token and amount, spender and
+    /// deadlines.
+    pub fn summary(&self) -> String {
+        let g = self.grant();
+        let amount = token_amount(g.chain_id, &g.token, &g.amount);
+        match self {
+            PermitRequest::Erc2612(p) => format!(
+                "EIP-2612 permit: allow {} to spend {}, signature valid until {}",
+                hex_addr(&p.spender),
+                amount,
+                describe_deadline(&p.deadline)
+            ),
+            PermitRequest::Permit2Single(p) => {
+                let until = if p.expiration == 0 {
+                    "the block it is used in".to_string()
+                } else {
+                    format_timestamp(p.expiration)
+                };
+                format!(
+                    "Permit2 allowance: allow {} to spend {} until {}, signature valid until {}",
+                    hex_addr(&p.spender),
+                    amount,
+                    until,
+                    describe_deadline(&p.sig_deadline)
+                )
+            }
+            PermitRequest::Permit2Transfer(p) => format!(
+                "Permit2 transfer: allow {} to take {} once, signature valid until {}",
+                hex_addr(&p.spender),
+                amount,
+                describe_deadline(&p.deadline)
+            ),
+        }
+    }
+}
+
+const REVIEW_DOMAIN: &[u8] = b"AirAccount-permit-review-v1";
+
+/// What a SignPermit passkey challenge commits to
+/// (challenge = SHA-256(nonce || this)): keccak256(domain || digest || summary).
+pub fn review_digest(digest: &[u8; 32], summary: &str) -> [u8; 32] {
+    let mut h = Keccak256::new();
+    h.update(REVIEW_DOMAIN);
+    h.update(digest);
+    h.update(summary.as_bytes());
+    h.finalize().into()
+}
+
+fn field<'a>(message: &'a [Eip712FieldValue], name: &str) -> Option<&'a Eip712Value> {
+    message.iter().find(|f| f.name == name).map(|f| &f.value)
+}
+
+fn uint_value(v: Option<&Eip712Value>) -> Option<Word> {
+    match v? {
+        Eip712Value::Uint(bytes) if bytes.len() <= 32 => {
+            let mut w = [0u8; 32];
+            w[32 - bytes.len()..].copy_from_slice(bytes);
+            Some(w)
+        }
+        _ => None,
+    }
+}
+
+fn address_value(v: Option<&Eip712Value>) -> Option<[u8; 20]> {
+    match v? {
+        Eip712Value::Address(a) => Some(*a),
+        _ => None,
+    }
+}
+
+fn has_layout(types: &[Eip712TypeDef], name: &str, layout: &[(&str, &str)]) -> bool {
+    types.iter().any(|t| {
+        t.name == name
+            && t.fields.len() == layout.len()
+            && t.fields
+                .iter()
+                .zip(layout)
+                .all(|(f, (ty, n))| f.field_type == *ty && f.name == *n)
+    })
+}
+
+/// Recognise a permit arriving through generic SignTypedData. None = not a
+/// permit; `Some(Err)` = permit-shaped but not something the guard can judge.
+/// Covers EIP-2612 and the DAI-style `allowed` permit (treated as unlimited).
+pub fn typed_data_grant(
+    domain: &Eip712Domain,
+    primary_type: &str,
+    types: &[Eip712TypeDef],
+    message: &[Eip712FieldValue],
+) -> Option<Result<PermitGrant, &'static str>> {
+    if PERMIT2_PRIMARY_TYPES.contains(&primary_type) {
+        return Some(Err("Permit2 typed data must be signed with SignPermit"));
+    }
+    if primary_type != "Permit" {
+        return None;
+    }
+    let eip2612 = [
+        ("address", "owner"),
+        ("address", "spender"),
+        ("uint256", "value"),
+        ("uint256", "nonce"),
+        ("uint256", "deadline"),
+    ];
+    let dai = [
+        ("address", "holder"),
+        ("address", "spender"),
+        ("uint256", "nonce"),
+        ("uint256", "expiry"),
+        ("bool", "allowed"),
+    ];
+    let amount = if has_layout(types, "Permit", &eip2612) {
+        uint_value(field(message, "value"))
+    } else if has_layout(types, "Permit", &dai) {
+        match field(message, "allowed") {
+            Some(Eip712Value::Bool(true)) => Some([0xff; 32]),
+            Some(Eip712Value::Bool(false)) => Some([0; 32]),
+            _ => None,
+        }
+    } else {
+        return Some(Err("unrecognised Permit layout"));
+    };
+    let grant = (|| {
+        Some(PermitGrant {
+            chain_id: domain.chain_id?,
+            token: domain.verifying_contract?,
+            spender: address_value(field(message, "spender"))?,
+            amount: amount?,
+        })
+    })();
+    Some(grant.ok_or("Permit fields or domain incomplete"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Eip712TypeField, Eip712Value};
+
+    const USDC: [u8; 20] = addr(b"A0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48");
+
+    fn uint(v: u64) -> Word {
+        u64_word(v)
+    }
+
+    fn erc2612() -> Erc2612Permit {
+        Erc2612Permit {
+            chain_id: 1,
+            token: USDC,
+            token_name: "USD Coin".into(),
+            token_version: "2".into(),
+            owner: [0x22; 20],
+            spender: [0x11; 20],
+            value: uint(12_500_000),
+            nonce: uint(0),
+            deadline: uint(1_700_000_000),
+        }
+    }
+
+    #[test]
+    fn type_hashes_match_deployed_contracts() {
+        // OpenZeppelin ERC20Permit / Permit2 PermitHash constants.
+        assert_eq!(
+            to_hex(&keccak(PERMIT_TYPE.as_bytes())),
+            "6e71edae12b1b97f4d1f60370fef10105fa2faae0126114a169c64845d6126c9"
+        );
+        assert_eq!(
+            to_hex(&keccak(PERMIT_DETAILS_TYPE.as_bytes())),
+            "65626cad6cb96493bf6f5ebea28756c966f023ab9e8a83a7101849d5573b3678"
+        );
+        assert_eq!(
+            to_hex(&keccak(PERMIT_SINGLE_TYPE.as_bytes())),
+            "f3841cd1ff0085026a6327b620b67997ce40f282c88a8e905a7a5626e310f3d0"
+        );
+        assert_eq!(
+            to_hex(&keccak(TOKEN_PERMISSIONS_TYPE.as_bytes())),
+            "618358ac3db8dc274f0cd8829da7e234bd48cd73c4a740aede1adec9846d06a1"
+        );
+    }
+
+    fn to_hex(b: &[u8]) -> String {
+        b.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn summaries_name_spender_amount_and_deadline() {
+        let p = PermitRequest::Erc2612(erc2612());
+        assert_eq!(
+            p.summary(),
+            "EIP-2612 permit: allow 0x1111111111111111111111111111111111111111 to spend 12.5 USDC, signature valid until 2023-11-14 22:13:20 UTC"
+        );
+        assert_eq!(p.signature_deadline(), Some(1_700_000_000));
+
+        let single = PermitRequest::Permit2Single(Permit2Single {
+            chain_id: 1,
+            token: USDC,
+            amount: {
+                let mut w = [0u8; 32];
+                w[12..].copy_from_slice(&[0xff; 20]);
+                w
+            },
+            expiration: 0,
+            nonce: 3,
+            spender: [0x11; 20],
+            sig_deadline: [0xff; 32],
+        });
+        assert!(single.validate().is_ok());
+        let s = single.summary();
+        assert!(
+            s.contains("spend USDC unlimited until the block it is used in"),
+            "{}",
+            s
+        );
+        assert!(s.ends_with("signature valid until never"), "{}", s);
+        assert_eq!(single.signature_deadline(), None);
+        assert_ne!(single.digest(), p.digest());
+        assert_ne!(
+            review_digest(&p.digest(), &p.summary()),
+            review_digest(&p.digest(), "something else")
+        );
+    }
+
+    #[test]
+    fn validation_and_timestamps() {
+        let mut p = erc2612();
+        p.spender = [0; 20];
+        assert!(PermitRequest::Erc2612(p).validate().is_err());
+        let too_wide = PermitRequest::Permit2Single(Permit2Single {
+            chain_id: 1,
+            token: USDC,
+            amount: [0xff; 32],
+            expiration: 1,
+            nonce: 0,
+            spender: [0x11; 20],
+            sig_deadline: uint(1),
+        });
+        assert!(too_wide.validate().is_err());
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00:00 UTC");
+        assert_eq!(format_timestamp(951_782_400), "2000-02-29 00:00:00 UTC");
+    }
+
+    #[test]
+    fn typed_data_permits_are_recognised() {
+        let p = erc2612();
+        let domain = Eip712Domain {
+            name: Some(p.token_name.clone()),
+            version: Some(p.token_version.clone()),
+            chain_id: Some(1),
+            verifying_contract: Some(USDC),
+        };
+        let ty = |fields: &[(&str, &str)]| {
+            vec![Eip712TypeDef {
+                name: "Permit".into(),
+                fields: fields
+                    .iter()
+                    .map(|(t, n)| Eip712TypeField {
+                        name: n.to_string(),
+                        field_type: t.to_string(),
+                    })
+                    .collect(),
+            }]
+        };
+        let types = ty(&[
+            ("address", "owner"),
+            ("address", "spender"),
+            ("uint256", "value"),
+            ("uint256", "nonce"),
+            ("uint256", "deadline"),
+        ]);
+        let message = vec![
+            Eip712FieldValue {
+                name: "spender".into(),
+                value: Eip712Value::Address(p.spender),
+            },
+            Eip712FieldValue {
+                name: "value".into(),
+                value: Eip712Value::Uint(vec![0xbe, 0xbc, 0x20]),
+            },
+        ];
+        let grant = typed_data_grant(&domain, "Permit", &types, &message)
+            .unwrap()
+            .unwrap();
+        assert_eq!(grant, PermitRequest::Erc2612(p).grant());
+
+        let odd = ty(&[("address", "spender")]);
+        assert!(matches!(
+            typed_data_grant(&domain, "Permit", &odd, &message),
+            Some(Err(_))
+        ));
+        assert!(matches!(
+            typed_data_grant(&domain, "PermitSingle", &types, &message),
+            Some(Err(_))
+        ));
+        assert!(typed_data_grant(&domain, "Mail", &types, &message).is_none());
+    }
+}
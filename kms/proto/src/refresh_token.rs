@@ -0,0 +1,180 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Rotating refresh tokens for agent credentials.
+//!
+//! A passkey-verified CreateAgentKey may open a [`RefreshFamily`]: the TA
+//! draws a 32-byte secret, keeps only its hash next to the device fingerprint
+//! and the passkey it verified, and returns the secret once. Redeeming it
+//! (CreateAgentKey with `is_refresh` and no passkey) mints a new JWT and a new
+//! secret. The spent secret is remembered as `previous`, so seeing it again
+//! means the token was copied and the whole family is closed. The family is
+//! also closed by a changed passkey, another device or age; the client then
+//! goes back to a passkey ceremony.
+
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+
+/// A family outlives its JWTs; after this a passkey ceremony is needed again.
+pub const REFRESH_FAMILY_TTL_SECS: i64 = 30 * 24 * 3600;
+
+const SECRET_DOMAIN: &[u8] = b"AirAccount-refresh-secret-v1";
+const PASSKEY_DOMAIN: &[u8] = b"AirAccount-refresh-passkey-v1";
+
+/// Keccak256(domain || secret) — the only form of the secret the TA stores.
+pub fn secret_hash(secret: &[u8; 32]) -> [u8; 32] {
+    let mut h = Keccak256::new();
+    h.update(SECRET_DOMAIN);
+    h.update(secret);
+    h.finalize().into()
+}
+
+/// Stands in for the WebAuthn credential: re-registering the passkey changes it.
+pub fn passkey_hash(passkey_pubkey: &[u8]) -> [u8; 32] {
+    let mut h = Keccak256::new();
+    h.update(PASSKEY_DOMAIN);
+    h.update(passkey_pubkey);
+    h.finalize().into()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RefreshFamily {
+    pub agent_index: u32,
+    pub passkey_hash: [u8; 32],
+    /// Hash of the client's device fingerprint; the CA never sees a raw one.
+    pub device_hash: [u8; 32],
+    pub current: [u8; 32],
+    pub previous: Option<[u8; 32]>,
+    /// Rotations so far; 0 for the token handed out with the passkey mint.
+    pub generation: u32,
+    pub opened_at: i64,
+}
+
+/// What redeeming a secret against a family comes to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefreshCheck {
+    /// Mint, then [`RefreshFamily::rotate`].
+    Rotate,
+    /// Refuse and delete the family.
+    Close(&'static str),
+    /// Refuse; the family stays (an unknown secret is not evidence of theft).
+    Refuse(&'static str),
+}
+
+impl RefreshFamily {
+    pub fn open(
+        agent_index: u32,
+        passkey_hash: [u8; 32],
+        device_hash: [u8; 32],
+        secret: &[u8; 32],
+        now: i64,
+    ) -> Self {
+        Self {
+            agent_index,
+            passkey_hash,
+            device_hash,
+            current: secret_hash(secret),
+            previous: None,
+            generation: 0,
+            opened_at: now,
+        }
+    }
+
+    pub fn check(
+        &self,
+        agent_index: u32,
+        secret: &[u8; 32],
+        device_hash: &[u8; 32],
+        passkey_hash: &[u8; 32],
+        now: i64,
+    ) -> RefreshCheck {
+        let presented = secret_hash(secret);
+        if self.previous == Some(presented) {
+            return RefreshCheck::Close("refresh token reuse detected");
+        }
+        if presented != self.current || agent_index != self.agent_index {
+            return RefreshCheck::Refuse("refresh token is not valid");
+        }
+        if &self.device_hash != device_hash {
+            return RefreshCheck::Close("refresh token presented from another device");
+        }
+        if &self.passkey_hash != passkey_hash {
+            return RefreshCheck::Close("passkey changed since the refresh token was issued");
+        }
+        if now.saturating_sub(self.opened_at) > REFRESH_FAMILY_TTL_SECS {
+            return RefreshCheck::Close("refresh token family expired");
+        }
+        RefreshCheck::Rotate
+    }
+
+    pub fn rotate(&mut self, next_secret: &[u8; 32]) {
+        self.previous = Some(self.current);
+        self.current = secret_hash(next_secret);
+        self.generation += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEVICE: [u8; 32] = [1; 32];
+    const PASSKEY: [u8; 32] = [2; 32];
+
+    #[test]
+    fn rotation_makes_the_old_secret_a_reuse() {
+        let mut f = RefreshFamily::open(3, PASSKEY, DEVICE, &[7; 32], 1_000);
+        assert_eq!(
+            f.check(3, &[7; 32], &DEVICE, &PASSKEY, 2_000),
+            RefreshCheck::Rotate
+        );
+        f.rotate(&[8; 32]);
+        assert_eq!(f.generation, 1);
+        assert_eq!(
+            f.check(3, &[8; 32], &DEVICE, &PASSKEY, 2_000),
+            RefreshCheck::Rotate
+        );
+        assert!(matches!(
+            f.check(3, &[7; 32], &DEVICE, &PASSKEY, 2_000),
+            RefreshCheck::Close(_)
+        ));
+        assert!(matches!(
+            f.check(3, &[9; 32], &DEVICE, &PASSKEY, 2_000),
+            RefreshCheck::Refuse(_)
+        ));
+    }
+
+    #[test]
+    fn device_passkey_and_age_close_the_family() {
+        let f = RefreshFamily::open(0, PASSKEY, DEVICE, &[7; 32], 1_000);
+        let close = |c| matches!(c, RefreshCheck::Close(_));
+        assert!(close(f.check(0, &[7; 32], &[9; 32], &PASSKEY, 1_000)));
+        assert!(close(f.check(0, &[7; 32], &DEVICE, &[9; 32], 1_000)));
+        assert!(close(f.check(
+            0,
+            &[7; 32],
+            &DEVICE,
+            &PASSKEY,
+            1_001 + REFRESH_FAMILY_TTL_SECS
+        )));
+        assert!(matches!(
+            f.check(1, &[7; 32], &DEVICE, &PASSKEY, 1_000),
+            RefreshCheck::Refuse(_)
+        ));
+        assert_ne!(passkey_hash(&[4; 65]), secret_hash(&[4; 32]));
+    }
+}
@@ -0,0 +1,101 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Remote approval of transaction signing by a paired phone.
+//!
+//! The owner pairs one approver key per wallet with a passkey
+//! (`PairApprover`); from then on SignTransaction also needs the phone's
+//! approval. The phone holds a secp256k1 key: the CA seals the signing
+//! request to it (the ECIES layout of [`crate::ecies`]) so the push relay
+//! sees ciphertext only, and the phone answers with a compact ECDSA
+//! signature over [`approval_digest`]. `RemoteApprove` checks it in the TA
+//! and arms a one-shot approval that the matching SignTransaction consumes.
+//!
+//! The payload is the digest the passkey confirms for the same Sign — the
+//! tx signing hash, or `calldata::confirmation_digest` when the Sign is
+//! summary-committed — so the passkey and the phone approve the same thing.
+
+use sha3::{Digest, Keccak256};
+use uuid::Uuid;
+
+/// Compressed secp256k1 public key.
+pub const APPROVER_KEY_LEN: usize = 33;
+/// r ‖ s, low-S.
+pub const APPROVAL_SIGNATURE_LEN: usize = 64;
+/// How long an armed approval waits for its SignTransaction.
+pub const APPROVAL_TTL_SECS: i64 = 300;
+
+/// Shape check only; the TA and the CA parse the point itself.
+pub fn validate_approver_key(key: &[u8]) -> Result<(), &'static str> {
+    if key.len() != APPROVER_KEY_LEN || !matches!(key[0], 0x02 | 0x03) {
+        return Err("approver key must be a 33-byte compressed secp256k1 key");
+    }
+    Ok(())
+}
+
+/// Passkey commitment for pairing `key`, or unpairing (None).
+pub fn pairing_commitment(wallet_id: &Uuid, key: Option<&[u8]>) -> [u8; 32] {
+    let mut h = Keccak256::new();
+    h.update(b"AA-APPROVER-PAIR-v1");
+    h.update(wallet_id.as_bytes());
+    match key {
+        Some(key) => {
+            h.update([1u8]);
+            h.update(key);
+        }
+        None => h.update([0u8]),
+    }
+    h.finalize().into()
+}
+
+/// What the phone signs to approve `payload` for `wallet_id`.
+pub fn approval_digest(wallet_id: &Uuid, payload: &[u8; 32]) -> [u8; 32] {
+    let mut h = Keccak256::new();
+    h.update(b"AA-REMOTE-APPROVAL-v1");
+    h.update(wallet_id.as_bytes());
+    h.update(payload);
+    h.finalize().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_shape_and_domain_separation() {
+        let w = Uuid::from_bytes([1; 16]);
+        let key = [0x02; APPROVER_KEY_LEN];
+        assert!(validate_approver_key(&key).is_ok());
+        assert!(validate_approver_key(&[0x04; 65]).is_err());
+        assert!(validate_approver_key(&[0x05; APPROVER_KEY_LEN]).is_err());
+
+        assert_ne!(
+            pairing_commitment(&w, Some(&key)),
+            pairing_commitment(&w, None)
+        );
+        assert_ne!(
+            pairing_commitment(&w, None),
+            pairing_commitment(&Uuid::nil(), None)
+        );
+        let payload = [7u8; 32];
+        assert_ne!(approval_digest(&w, &payload), payload);
+        assert_ne!(
+            approval_digest(&w, &payload),
+            approval_digest(&Uuid::nil(), &payload)
+        );
+    }
+}
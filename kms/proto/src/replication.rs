@@ -0,0 +1,261 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Multi-device seed replication — the wire format of the ceremony.
+//!
+//! 1. The target device's TA makes a fresh secp256k1 ephemeral key and
+//!    returns a [`ReplicationHello`] (role `Target`) whose attestation nonce
+//!    is [`ReplicationHello::digest`].
+//! 2. The source device's TA checks that attestation (same TA measurement as
+//!    its own, signature by the peer's attestation key), has the wallet owner
+//!    approve the target hello digest with a passkey, and answers with a
+//!    [`ReplicationPackage`]: its own attested hello (role `Source`, same
+//!    session) and the wallet encrypted under ECDH(source eph, target eph).
+//! 3. The target TA checks the source attestation the same way, derives the
+//!    same key from the ephemeral secret it kept, and stores the wallet.
+//!
+//! The CAs only carry these structs between the devices; neither sees the
+//! seed. Both ephemeral keys are single-use, so a recorded package cannot be
+//! decrypted later and cannot be imported twice.
+
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+use uuid::Uuid;
+
+use crate::GetAttestationOutput;
+
+pub const REPLICATION_VERSION: u8 = 1;
+
+/// How long a hello stays acceptable, and how long the target keeps the
+/// ephemeral secret behind its offer.
+pub const REPLICATION_TTL_SECS: u64 = 600;
+
+/// Tolerated clock difference between two devices' REE clocks.
+pub const MAX_CLOCK_SKEW_SECS: u64 = 60;
+
+const DOMAIN: &[u8] = b"AirAccount-replication-v1";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplicationRole {
+    /// The device receiving the wallet; opens the session.
+    Target,
+    /// The device that holds the wallet and exports it.
+    Source,
+}
+
+impl ReplicationRole {
+    fn tag(self) -> u8 {
+        match self {
+            ReplicationRole::Target => 0,
+            ReplicationRole::Source => 1,
+        }
+    }
+}
+
+/// [`ReplicationHello::digest`] before the hello exists — the TA needs it as
+/// the nonce of the attestation that completes the hello.
+pub fn hello_digest(
+    version: u8,
+    role: ReplicationRole,
+    session_id: &[u8; 16],
+    ephemeral_pubkey: &[u8],
+    created_at: u64,
+) -> [u8; 32] {
+    let mut h = Keccak256::new();
+    h.update(DOMAIN);
+    h.update([version, role.tag()]);
+    h.update(session_id);
+    h.update((ephemeral_pubkey.len() as u32).to_be_bytes());
+    h.update(ephemeral_pubkey);
+    h.update(created_at.to_be_bytes());
+    h.finalize().into()
+}
+
+/// One side's attested ephemeral key.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReplicationHello {
+    pub version: u8,
+    pub role: ReplicationRole,
+    /// Chosen by the target; the source echoes it.
+    pub session_id: [u8; 16],
+    /// Compressed secp256k1 public key (33 bytes).
+    pub ephemeral_pubkey: Vec<u8>,
+    /// TA (REE) clock seconds when the hello was made.
+    pub created_at: u64,
+    /// Attestation evidence with nonce = [`Self::digest`].
+    pub attestation: GetAttestationOutput,
+}
+
+impl ReplicationHello {
+    /// What the attestation nonce — and, for the target hello, the owner's
+    /// passkey approval — commits to.
+    pub fn digest(&self) -> [u8; 32] {
+        hello_digest(
+            self.version,
+            self.role,
+            &self.session_id,
+            &self.ephemeral_pubkey,
+            self.created_at,
+        )
+    }
+
+    /// Checks that need no RSA: format, role, freshness, that the evidence is
+    /// over this hello, and that it measures the same TA as `own_measurement`.
+    /// The caller still verifies the attestation signature.
+    pub fn check(
+        &self,
+        role: ReplicationRole,
+        own_measurement: &[u8],
+        now: u64,
+    ) -> Result<(), &'static str> {
+        if self.version != REPLICATION_VERSION {
+            return Err("unsupported replication version");
+        }
+        if self.role != role {
+            return Err("replication hello has the wrong role");
+        }
+        if self.ephemeral_pubkey.len() != 33 {
+            return Err("ephemeral public key must be 33 bytes (compressed)");
+        }
+        if self.created_at > now.saturating_add(MAX_CLOCK_SKEW_SECS) {
+            return Err("replication hello is from the future");
+        }
+        if now.saturating_sub(self.created_at) > REPLICATION_TTL_SECS {
+            return Err("replication hello expired");
+        }
+        if self.attestation.nonce[..] != self.digest()[..] {
+            return Err("attestation nonce is not the hello digest");
+        }
+        if own_measurement.is_empty() || self.attestation.ta_measurement[..] != own_measurement[..]
+        {
+            return Err("peer runs a different TA build");
+        }
+        Ok(())
+    }
+}
+
+/// Encrypted wallet on its way from source to target.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReplicationPackage {
+    pub version: u8,
+    pub session_id: [u8; 16],
+    pub wallet_id: Uuid,
+    pub source: ReplicationHello,
+    pub ciphertext: Vec<u8>,
+    /// HMAC-SHA256 over [`Self::aad`] ‖ ciphertext.
+    pub mac: [u8; 32],
+}
+
+impl ReplicationPackage {
+    /// Header bytes the MAC authenticates along with the ciphertext.
+    pub fn aad(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(DOMAIN.len() + 1 + 16 + 16 + 32);
+        out.extend_from_slice(DOMAIN);
+        out.push(self.version);
+        out.extend_from_slice(&self.session_id);
+        out.extend_from_slice(self.wallet_id.as_bytes());
+        out.extend_from_slice(&self.source.digest());
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MEASUREMENT: [u8; 32] = [0x4d; 32];
+
+    fn hello(role: ReplicationRole) -> ReplicationHello {
+        let mut h = ReplicationHello {
+            version: REPLICATION_VERSION,
+            role,
+            session_id: [0x5e; 16],
+            ephemeral_pubkey: vec![0x02; 33],
+            created_at: 1_700_000_000,
+            attestation: GetAttestationOutput {
+                nonce: vec![],
+                ta_uuid: vec![0; 16],
+                ta_measurement: MEASUREMENT.to_vec(),
+                signature: vec![1; 256],
+                attest_pubkey_exp: vec![1, 0, 1],
+                attest_pubkey_mod: vec![0xc5; 256],
+                sig_alg: 0x7041_4930,
+                ree_time_secs: 1_700_000_000,
+            },
+        };
+        h.attestation.nonce = h.digest().to_vec();
+        h
+    }
+
+    #[test]
+    fn digest_separates_roles_and_keys() {
+        let target = hello(ReplicationRole::Target);
+        let source = hello(ReplicationRole::Source);
+        assert_ne!(target.digest(), source.digest());
+        let mut other = target.clone();
+        other.ephemeral_pubkey[32] ^= 1;
+        assert_ne!(other.digest(), target.digest());
+        // the attestation itself is not part of what it signs
+        other = target.clone();
+        other.attestation.signature = vec![2; 256];
+        assert_eq!(other.digest(), target.digest());
+    }
+
+    #[test]
+    fn check_rules() {
+        let now = 1_700_000_100;
+        let h = hello(ReplicationRole::Target);
+        assert!(h.check(ReplicationRole::Target, &MEASUREMENT, now).is_ok());
+        assert!(h.check(ReplicationRole::Source, &MEASUREMENT, now).is_err());
+        assert!(h.check(ReplicationRole::Target, &[0x4e; 32], now).is_err());
+        assert!(h
+            .check(
+                ReplicationRole::Target,
+                &MEASUREMENT,
+                now + REPLICATION_TTL_SECS
+            )
+            .is_err());
+        assert!(h
+            .check(ReplicationRole::Target, &MEASUREMENT, 1_700_000_000 - 61)
+            .is_err());
+
+        // evidence produced for a different hello
+        let mut swapped = h.clone();
+        swapped.created_at += 1;
+        assert!(swapped
+            .check(ReplicationRole::Target, &MEASUREMENT, now)
+            .is_err());
+    }
+
+    #[test]
+    fn aad_binds_wallet_and_source() {
+        let package = ReplicationPackage {
+            version: REPLICATION_VERSION,
+            session_id: [0x5e; 16],
+            wallet_id: Uuid::from_bytes([0x22; 16]),
+            source: hello(ReplicationRole::Source),
+            ciphertext: vec![0xaa; 48],
+            mac: [0; 32],
+        };
+        let mut other = package.clone();
+        other.wallet_id = Uuid::from_bytes([0x23; 16]);
+        assert_ne!(package.aad(), other.aad());
+        other = package.clone();
+        other.source.created_at += 1;
+        assert_ne!(package.aad(), other.aad());
+    }
+}
@@ -0,0 +1,186 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! The confirmation screen of a device with a TEE-driven display.
+//!
+//! A TA built with `secure-display` hands a [`Screen`] to the board's
+//! trusted-UI pseudo-TA before it signs a transaction. The PTA draws it on a
+//! display the REE cannot write to, pages through it, and reports a
+//! [`Verdict`] once the confirm or reject button is pressed or the wait runs
+//! out. The TA builds the text itself from the transaction it is about to
+//! sign; nothing the CA sends is shown verbatim except ABI signatures, which
+//! only shape the decoded summary.
+
+use crate::calldata::{format_units, native_symbol, summarize_transaction};
+use crate::eip55::to_checksum_address;
+use crate::EthTransaction;
+
+/// Characters per line: a 128 px wide panel with a 6 px font.
+pub const COLUMNS: usize = 21;
+
+/// Longest wait for a button press. Inside the CA's 30 s TEE call timeout,
+/// so a user who walks away gets a rejection rather than a CA timeout.
+pub const CONFIRM_TIMEOUT_SECS: u32 = 20;
+
+/// Upper bound on an encoded screen; the PTA's text buffer.
+pub const MAX_SCREEN_BYTES: usize = 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Screen {
+    pub lines: Vec<String>,
+}
+
+impl Screen {
+    /// Destination, amount and fee of `tx`, with the decoded call above them.
+    pub fn for_transaction(tx: &EthTransaction, abis: &[String]) -> Self {
+        let symbol = native_symbol(tx.chain_id);
+        let mut screen = Screen { lines: Vec::new() };
+        screen.push(&format!("SIGN TX chain {}", tx.chain_id));
+        screen.push(&summarize_transaction(tx, abis));
+        match &tx.to {
+            // Exactly two lines: the whole address, checksummed.
+            Some(to) => {
+                screen.push("to");
+                screen.push(&to_checksum_address(to));
+            }
+            None => screen.push("to (new contract)"),
+        }
+        screen.push(&format!("value {} {}", format_units(tx.value, 18), symbol));
+        screen.push(&format!(
+            "max fee {} {}",
+            format_units(tx.gas.saturating_mul(tx.gas_price), 18),
+            symbol
+        ));
+        screen.push(&format!("nonce {}", tx.nonce));
+        screen
+    }
+
+    /// Wrap `text` at [`COLUMNS`], breaking at spaces where one is close.
+    /// The panel font is ASCII; anything else shows as `?`.
+    fn push(&mut self, text: &str) {
+        let text: String = text
+            .chars()
+            .map(|c| {
+                if c.is_ascii_graphic() || c == ' ' {
+                    c
+                } else {
+                    '?'
+                }
+            })
+            .collect();
+        let mut rest = text.trim();
+        while rest.len() > COLUMNS {
+            let cut = match rest[..=COLUMNS].rfind(' ') {
+                Some(space) if space >= COLUMNS / 2 => space,
+                _ => COLUMNS,
+            };
+            self.lines.push(rest[..cut].trim_end().to_string());
+            rest = rest[cut..].trim_start();
+        }
+        self.lines.push(rest.to_string());
+    }
+
+    /// Lines separated by `\n`, as the PTA takes them.
+    pub fn encode(&self) -> Result<Vec<u8>, &'static str> {
+        let text = self.lines.join("\n");
+        if text.len() > MAX_SCREEN_BYTES {
+            return Err("confirmation screen does not fit the secure display buffer");
+        }
+        Ok(text.into_bytes())
+    }
+}
+
+/// What the PTA reports back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Approved,
+    Rejected,
+    TimedOut,
+}
+
+impl Verdict {
+    pub fn from_code(code: u32) -> Option<Self> {
+        match code {
+            1 => Some(Verdict::Approved),
+            2 => Some(Verdict::Rejected),
+            3 => Some(Verdict::TimedOut),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transfer() -> EthTransaction {
+        EthTransaction {
+            chain_id: 1,
+            nonce: 7,
+            to: Some([0x5a; 20]),
+            value: 1_500_000_000_000_000_000,
+            gas_price: 20_000_000_000,
+            gas: 21_000,
+            data: vec![],
+        }
+    }
+
+    #[test]
+    fn a_transfer_shows_destination_amount_and_fee() {
+        let screen = Screen::for_transaction(&transfer(), &[]);
+        assert_eq!(
+            screen.lines,
+            vec![
+                "SIGN TX chain 1",
+                "send 1.5 ETH to",
+                "0x5a5a5a5a5a5a5a5a5a5",
+                "a5a5a5a5a5a5a5a5a5a5a",
+                "to",
+                "0x5a5A5a5a5A5a5a5a5a5",
+                "A5a5A5A5a5a5A5A5A5A5A",
+                "value 1.5 ETH",
+                "max fee 0.00042 ETH",
+                "nonce 7",
+            ]
+        );
+        assert!(screen.lines.iter().all(|l| l.len() <= COLUMNS));
+        assert!(screen
+            .encode()
+            .unwrap()
+            .starts_with(b"SIGN TX chain 1\nsend"));
+    }
+
+    #[test]
+    fn odd_input_stays_on_the_panel() {
+        let mut screen = Screen { lines: Vec::new() };
+        screen.push("caf\u{e9} \u{202e}evil");
+        assert_eq!(screen.lines, vec!["caf? ?evil"]);
+
+        let mut deploy = transfer();
+        deploy.to = None;
+        deploy.data = vec![0xff; 4000];
+        let screen = Screen::for_transaction(&deploy, &[]);
+        assert!(screen.lines.contains(&"to (new contract)".to_string()));
+
+        let long = Screen {
+            lines: vec!["x".repeat(COLUMNS); MAX_SCREEN_BYTES / COLUMNS + 1],
+        };
+        assert!(long.encode().is_err());
+        assert_eq!(Verdict::from_code(1), Some(Verdict::Approved));
+        assert_eq!(Verdict::from_code(0), None);
+    }
+}
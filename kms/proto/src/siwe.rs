@@ -0,0 +1,472 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Sign-In with Ethereum (EIP-4361) messages.
+//!
+//! [`SiweMessage::parse`] accepts only the canonical text of the EIP-4361
+//! ABNF: a message that would render differently (extra whitespace, `\r\n`,
+//! a lowercase address, reordered fields) is rejected rather than
+//! normalised, so what the TA checks is byte-for-byte what gets signed.
+//!
+//! The TA calls [`SiweMessage::check_signable`] before a `Login` signature:
+//! the address must be the signing key's, the nonce present, the URI on the
+//! same authority as the domain, and the message fresh. The CA runs
+//! [`SiweMessage::check_validity`] when verifying a third party's sign-in.
+
+use sha3::{Digest, Keccak256};
+
+use crate::civil::{days_from_civil, days_in_month};
+use crate::eip55::{parse_address, to_checksum_address};
+
+pub const SIWE_HEADER: &str = " wants you to sign in with your Ethereum account:";
+pub const SIWE_VERSION: &str = "1";
+/// EIP-4361: at least 8 alphanumeric characters.
+pub const MIN_NONCE_LEN: usize = 8;
+/// Tolerated disagreement between the dapp's clock and ours.
+pub const CLOCK_SKEW_SECS: i64 = 300;
+/// A message issued longer ago than this is not signed: a sign-in request
+/// is answered within seconds, a stale one is likely replayed.
+pub const MAX_ISSUED_AGE_SECS: i64 = 600;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SiweMessage {
+    pub scheme: Option<String>,
+    pub domain: String,
+    pub address: [u8; 20],
+    pub statement: Option<String>,
+    pub uri: String,
+    pub version: String,
+    pub chain_id: u64,
+    pub nonce: String,
+    pub issued_at: String,
+    pub expiration_time: Option<String>,
+    pub not_before: Option<String>,
+    pub request_id: Option<String>,
+    pub resources: Vec<String>,
+}
+
+/// RFC 3339 `date-time` (`2021-09-30T16:25:24Z`, optional fraction, `Z` or
+/// `±HH:MM`) as unix seconds; fractions are truncated.
+pub fn parse_rfc3339(s: &str) -> Result<i64, &'static str> {
+    const BAD: &str = "timestamp is not RFC 3339";
+    let b = s.as_bytes();
+    if b.len() < 20 || b[4] != b'-' || b[7] != b'-' || b[13] != b':' || b[16] != b':' {
+        return Err(BAD);
+    }
+    if b[10] != b'T' && b[10] != b't' {
+        return Err(BAD);
+    }
+    let num = |r: core::ops::Range<usize>| -> Result<i64, &'static str> {
+        let d = &s[r];
+        if d.bytes().all(|c| c.is_ascii_digit()) {
+            d.parse().map_err(|_| BAD)
+        } else {
+            Err(BAD)
+        }
+    };
+    let (year, month, day) = (num(0..4)?, num(5..7)? as u32, num(8..10)? as u32);
+    let (hour, minute, second) = (num(11..13)?, num(14..16)?, num(17..19)?);
+    if !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) {
+        return Err(BAD);
+    }
+    if hour > 23 || minute > 59 || second > 60 {
+        return Err(BAD);
+    }
+    let mut rest = &s[19..];
+    if let Some(frac) = rest.strip_prefix('.') {
+        let digits = frac.bytes().take_while(u8::is_ascii_digit).count();
+        if digits == 0 {
+            return Err(BAD);
+        }
+        rest = &frac[digits..];
+    }
+    let offset = match rest {
+        "Z" | "z" => 0,
+        _ if rest.len() == 6 && rest.as_bytes()[3] == b':' => {
+            let sign = match rest.as_bytes()[0] {
+                b'+' => 1,
+                b'-' => -1,
+                _ => return Err(BAD),
+            };
+            let (oh, om) = (num(s.len() - 5..s.len() - 3)?, num(s.len() - 2..s.len())?);
+            if oh > 23 || om > 59 {
+                return Err(BAD);
+            }
+            sign * (oh * 3600 + om * 60)
+        }
+        _ => return Err(BAD),
+    };
+    Ok(days_from_civil(year, month, day) * 86_400 + hour * 3600 + minute * 60 + second - offset)
+}
+
+/// Host (and port) of an `scheme://[userinfo@]host[:port]/...` URI.
+fn uri_authority(uri: &str) -> Option<&str> {
+    let rest = &uri[uri.find("://")? + 3..];
+    let end = rest.find(['/', '?', '#'].as_ref()).unwrap_or(rest.len());
+    let authority = &rest[..end];
+    Some(authority.rsplit('@').next().unwrap_or(authority))
+}
+
+fn tagged<'a>(
+    lines: &mut core::iter::Peekable<core::str::Split<'a, char>>,
+    tag: &str,
+) -> Option<&'a str> {
+    let value = lines.peek()?.strip_prefix(tag)?;
+    lines.next();
+    Some(value)
+}
+
+impl SiweMessage {
+    pub fn parse(text: &str) -> Result<Self, &'static str> {
+        const SHAPE: &str = "message does not follow the EIP-4361 layout";
+        let mut lines = text.split('\n').peekable();
+        let origin = lines
+            .next()
+            .and_then(|l| l.strip_suffix(SIWE_HEADER))
+            .ok_or("not an EIP-4361 sign-in message")?;
+        let (scheme, domain) = match origin.find("://") {
+            Some(i) => (Some(origin[..i].to_string()), &origin[i + 3..]),
+            None => (None, origin),
+        };
+        let address_line = lines.next().ok_or(SHAPE)?;
+        let address = parse_address(address_line)?;
+        if to_checksum_address(&address) != address_line {
+            return Err("sign-in address must be 0x-prefixed EIP-55 checksummed");
+        }
+        if lines.next() != Some("") {
+            return Err(SHAPE);
+        }
+        let statement = match lines.next().ok_or(SHAPE)? {
+            "" => None,
+            s => {
+                if lines.next() != Some("") {
+                    return Err(SHAPE);
+                }
+                Some(s.to_string())
+            }
+        };
+        let uri = tagged(&mut lines, "URI: ").ok_or("missing URI")?;
+        let version = tagged(&mut lines, "Version: ").ok_or("missing Version")?;
+        let chain_id = tagged(&mut lines, "Chain ID: ").ok_or("missing Chain ID")?;
+        let nonce = tagged(&mut lines, "Nonce: ").ok_or("missing Nonce")?;
+        let issued_at = tagged(&mut lines, "Issued At: ").ok_or("missing Issued At")?;
+        let expiration_time = tagged(&mut lines, "Expiration Time: ").map(str::to_string);
+        let not_before = tagged(&mut lines, "Not Before: ").map(str::to_string);
+        let request_id = tagged(&mut lines, "Request ID: ").map(str::to_string);
+        let mut resources = Vec::new();
+        if tagged(&mut lines, "Resources:") == Some("") {
+            while let Some(r) = tagged(&mut lines, "- ") {
+                resources.push(r.to_string());
+            }
+        }
+        if lines.next().is_some() {
+            return Err(SHAPE);
+        }
+        if chain_id.is_empty() || chain_id.starts_with('0') || chain_id.starts_with('+') {
+            return Err("Chain ID must be a decimal number");
+        }
+        let message = SiweMessage {
+            scheme,
+            domain: domain.to_string(),
+            address,
+            statement,
+            uri: uri.to_string(),
+            version: version.to_string(),
+            chain_id: chain_id
+                .parse()
+                .map_err(|_| "Chain ID must be a decimal number")?,
+            nonce: nonce.to_string(),
+            issued_at: issued_at.to_string(),
+            expiration_time,
+            not_before,
+            request_id,
+            resources,
+        };
+        message.validate()?;
+        if message.to_message() != text {
+            return Err(SHAPE);
+        }
+        Ok(message)
+    }
+
+    /// Field-level checks; [`parse`](Self::parse) runs them, callers
+    /// building a message by hand should too.
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if self.domain.is_empty()
+            || self
+                .domain
+                .bytes()
+                .any(|c| c.is_ascii_whitespace() || c.is_ascii_control() || c == b'/')
+        {
+            return Err("domain must be a bare authority (host[:port])");
+        }
+        if let Some(scheme) = &self.scheme {
+            let ok = scheme
+                .as_bytes()
+                .first()
+                .is_some_and(u8::is_ascii_alphabetic)
+                && scheme
+                    .bytes()
+                    .all(|c| c.is_ascii_alphanumeric() || c == b'+' || c == b'-' || c == b'.');
+            if !ok {
+                return Err("invalid scheme");
+            }
+        }
+        let single_line = |s: &str| !s.bytes().any(|c| c == b'\n' || c == b'\r');
+        if let Some(statement) = &self.statement {
+            if statement.is_empty() || !single_line(statement) {
+                return Err("statement must be a single non-empty line");
+            }
+        }
+        if self.version != SIWE_VERSION {
+            return Err("unsupported SIWE version");
+        }
+        if self.chain_id == 0 {
+            return Err("Chain ID must be non-zero");
+        }
+        if self.nonce.len() < MIN_NONCE_LEN
+            || !self.nonce.bytes().all(|c| c.is_ascii_alphanumeric())
+        {
+            return Err("nonce must be at least 8 alphanumeric characters");
+        }
+        let uri_ok = |u: &str| {
+            u.contains(':')
+                && !u
+                    .bytes()
+                    .any(|c| c.is_ascii_whitespace() || c.is_ascii_control())
+        };
+        if !uri_ok(&self.uri) || !self.resources.iter().all(|r| uri_ok(r)) {
+            return Err("URI and resources must be absolute URIs");
+        }
+        // Domain binding: a page on one origin cannot ask for a sign-in
+        // that names another as the relying party.
+        if let Some(authority) = uri_authority(&self.uri) {
+            if authority != self.domain {
+                return Err("URI authority does not match the sign-in domain");
+            }
+            if let Some(scheme) = &self.scheme {
+                if !self.uri.starts_with(&format!("{}://", scheme)) {
+                    return Err("URI scheme does not match the sign-in scheme");
+                }
+            }
+        }
+        parse_rfc3339(&self.issued_at)?;
+        let window = (self.not_before_secs()?, self.expiration_secs()?);
+        if let (Some(nbf), Some(exp)) = window {
+            if exp <= nbf {
+                return Err("Expiration Time is not after Not Before");
+            }
+        }
+        if let Some(r) = &self.request_id {
+            if !single_line(r) {
+                return Err("Request ID must be a single line");
+            }
+        }
+        Ok(())
+    }
+
+    pub fn issued_at_secs(&self) -> Result<i64, &'static str> {
+        parse_rfc3339(&self.issued_at)
+    }
+
+    pub fn expiration_secs(&self) -> Result<Option<i64>, &'static str> {
+        self.expiration_time
+            .as_deref()
+            .map(parse_rfc3339)
+            .transpose()
+    }
+
+    pub fn not_before_secs(&self) -> Result<Option<i64>, &'static str> {
+        self.not_before.as_deref().map(parse_rfc3339).transpose()
+    }
+
+    /// The EIP-4361 text; `parse(m.to_message()) == m` for a valid message.
+    pub fn to_message(&self) -> String {
+        let mut out = String::new();
+        if let Some(scheme) = &self.scheme {
+            out.push_str(scheme);
+            out.push_str("://");
+        }
+        out.push_str(&self.domain);
+        out.push_str(SIWE_HEADER);
+        out.push('\n');
+        out.push_str(&to_checksum_address(&self.address));
+        out.push_str("\n\n");
+        if let Some(statement) = &self.statement {
+            out.push_str(statement);
+            out.push('\n');
+        }
+        out.push_str(&format!(
+            "\nURI: {}\nVersion: {}\nChain ID: {}\nNonce: {}\nIssued At: {}",
+            self.uri, self.version, self.chain_id, self.nonce, self.issued_at
+        ));
+        if let Some(v) = &self.expiration_time {
+            out.push_str(&format!("\nExpiration Time: {}", v));
+        }
+        if let Some(v) = &self.not_before {
+            out.push_str(&format!("\nNot Before: {}", v));
+        }
+        if let Some(v) = &self.request_id {
+            out.push_str(&format!("\nRequest ID: {}", v));
+        }
+        if !self.resources.is_empty() {
+            out.push_str("\nResources:");
+            for r in &self.resources {
+                out.push_str(&format!("\n- {}", r));
+            }
+        }
+        out
+    }
+
+    /// EIP-191 personal_sign digest of the message text.
+    pub fn digest(&self) -> [u8; 32] {
+        let message = self.to_message();
+        let mut h = Keccak256::new();
+        h.update(format!("\x19Ethereum Signed Message:\n{}", message.len()).as_bytes());
+        h.update(message.as_bytes());
+        h.finalize().into()
+    }
+
+    /// Expiration and Not Before against `now` (unix seconds), allowing
+    /// [`CLOCK_SKEW_SECS`] for Not Before.
+    pub fn check_validity(&self, now: i64) -> Result<(), &'static str> {
+        if let Some(exp) = self.expiration_secs()? {
+            if exp <= now {
+                return Err("sign-in message has expired");
+            }
+        }
+        if let Some(nbf) = self.not_before_secs()? {
+            if nbf > now + CLOCK_SKEW_SECS {
+                return Err("sign-in message is not valid yet");
+            }
+        }
+        Ok(())
+    }
+
+    /// Everything the signer enforces before producing a `Login` signature
+    /// for `signer`: the address is the signing key's, the message was
+    /// issued recently (not in the future) and is currently valid.
+    pub fn check_signable(&self, signer: &[u8; 20], now: i64) -> Result<(), &'static str> {
+        if &self.address != signer {
+            return Err("sign-in address is not the signing key's address");
+        }
+        let issued_at = self.issued_at_secs()?;
+        if issued_at > now + CLOCK_SKEW_SECS {
+            return Err("sign-in message is issued in the future");
+        }
+        if issued_at < now - MAX_ISSUED_AGE_SECS {
+            return Err("sign-in message is stale (Issued At too old)");
+        }
+        self.check_validity(now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // EIP-4361 example message (address from the spec, nonce widened).
+    const EXAMPLE: &str = "service.invalid wants you to sign in with your Ethereum account:
+0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2
+
+I accept the ServiceOrg Terms of Service: https://service.invalid/tos
+
+URI: https://service.invalid/login
+Version: 1
+Chain ID: 1
+Nonce: 32891756
+Issued At: 2021-09-30T16:25:24Z
+Resources:
+- ipfs://bafybeiemxf5abjwjbikoz4mc3a3dla6ual3jsgpdr4cjr3oz3evfyavhwq/
+- https://example.com/my-web2-claim.json";
+
+    #[test]
+    fn parse_round_trips_canonical_text() {
+        let m = SiweMessage::parse(EXAMPLE).unwrap();
+        assert_eq!(m.domain, "service.invalid");
+        assert_eq!(m.chain_id, 1);
+        assert_eq!(m.resources.len(), 2);
+        assert_eq!(m.issued_at_secs().unwrap(), 1_633_019_124);
+        assert_eq!(m.to_message(), EXAMPLE);
+
+        // No statement: two blank lines between address and URI.
+        let bare = SiweMessage {
+            statement: None,
+            resources: vec![],
+            scheme: Some("https".into()),
+            ..m.clone()
+        };
+        let text = bare.to_message();
+        assert!(text.contains("Cc2\n\n\nURI: "), "{}", text);
+        assert_eq!(SiweMessage::parse(&text).unwrap(), bare);
+    }
+
+    #[test]
+    fn non_canonical_or_unbound_messages_rejected() {
+        let bad = [
+            EXAMPLE.replace(
+                "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2",
+                "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2",
+            ),
+            EXAMPLE.replace('\n', "\r\n"),
+            EXAMPLE.replace("Nonce: 32891756", "Nonce: 1234"),
+            EXAMPLE.replace(
+                "https://service.invalid/login",
+                "https://evil.invalid/login",
+            ),
+            EXAMPLE.replace("Version: 1", "Version: 2"),
+            EXAMPLE.replace("Chain ID: 1", "Chain ID: 01"),
+            EXAMPLE.replace("16:25:24Z", "16:25:24"),
+            format!("{}\n", EXAMPLE),
+            EXAMPLE.replace("Version: 1\nChain ID: 1", "Chain ID: 1\nVersion: 1"),
+        ];
+        for text in &bad {
+            assert!(SiweMessage::parse(text).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn rfc3339_offsets_and_fractions() {
+        assert_eq!(parse_rfc3339("1970-01-01T00:00:00Z"), Ok(0));
+        assert_eq!(parse_rfc3339("1970-01-01T01:00:00.123+01:00"), Ok(0));
+        assert_eq!(parse_rfc3339("2000-02-29T00:00:00-00:30"), Ok(951_784_200));
+        assert!(parse_rfc3339("2001-02-29T00:00:00Z").is_err());
+        assert!(parse_rfc3339("2021-09-30 16:25:24Z").is_err());
+    }
+
+    #[test]
+    fn signable_checks_address_and_time() {
+        let m = SiweMessage {
+            expiration_time: Some("2021-09-30T17:00:00Z".into()),
+            ..SiweMessage::parse(EXAMPLE).unwrap()
+        };
+        let issued = m.issued_at_secs().unwrap();
+        let signer = m.address;
+        assert!(m.check_signable(&signer, issued + 60).is_ok());
+        assert!(m.check_signable(&[0u8; 20], issued + 60).is_err());
+        assert!(m
+            .check_signable(&signer, issued - CLOCK_SKEW_SECS - 1)
+            .is_err());
+        assert!(m
+            .check_signable(&signer, issued + MAX_ISSUED_AGE_SECS + 1)
+            .is_err());
+        // Past Expiration Time: still a parseable message, no longer valid.
+        assert!(m.check_validity(issued + 3600).is_err());
+        assert!(m.check_validity(issued + 3600 * 24 * 365).is_err());
+        assert!(m.check_validity(issued + 60).is_ok());
+    }
+}
@@ -0,0 +1,191 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Guardian-based recovery of a wallet whose passkey is lost.
+//!
+//! The owner registers a [`GuardianSet`] with a passkey (`SetGuardians`):
+//! guardian keys, how many must agree, and a timelock. Each guardian then
+//! attests a new passkey with `RecoveryAttest`, a compact ECDSA signature
+//! over [`attestation_digest`]. When `threshold` guardians name the same
+//! passkey the recovery is armed; after `delay_secs` anyone may finish it
+//! with `CompleteRecovery`, which rebinds the wallet to that passkey. Until
+//! then the owner, if they still hold the old passkey, can cancel it.
+//!
+//! Attestations are bound to the recovery `round`, which every cancel,
+//! completion and guardian change advances, so an old one never counts
+//! again. A guardian's attestation is final for its round.
+
+use crate::remote_approval::validate_approver_key;
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+use uuid::Uuid;
+
+/// Guardian keys per wallet.
+pub const MAX_GUARDIANS: usize = 10;
+/// Shortest timelock: the owner needs time to notice and cancel.
+pub const MIN_DELAY_SECS: u64 = 3_600;
+pub const MAX_DELAY_SECS: u64 = 30 * 86_400;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct GuardianSet {
+    /// Guardians that must attest the same passkey.
+    pub threshold: u8,
+    /// Compressed secp256k1 keys, distinct.
+    pub guardians: Vec<Vec<u8>>,
+    /// From the attestation that reaches `threshold` to completion.
+    pub delay_secs: u64,
+}
+
+impl GuardianSet {
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if self.guardians.is_empty() || self.guardians.len() > MAX_GUARDIANS {
+            return Err("a guardian set needs 1 to 10 guardian keys");
+        }
+        if self.threshold == 0 || self.threshold as usize > self.guardians.len() {
+            return Err("threshold must be between 1 and the number of guardians");
+        }
+        if !(MIN_DELAY_SECS..=MAX_DELAY_SECS).contains(&self.delay_secs) {
+            return Err("recovery delay must be between 1 hour and 30 days");
+        }
+        for (i, key) in self.guardians.iter().enumerate() {
+            validate_approver_key(key)?;
+            if self.guardians[..i].contains(key) {
+                return Err("guardian keys must be distinct");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Passkey commitment for registering `guardians`, or removing them (None).
+pub fn guardians_commitment(wallet_id: &Uuid, guardians: Option<&GuardianSet>) -> [u8; 32] {
+    let mut h = Keccak256::new();
+    h.update(b"AA-RECOVERY-GUARDIANS-v1");
+    h.update(wallet_id.as_bytes());
+    match guardians {
+        Some(set) => {
+            h.update([1u8]);
+            h.update([set.threshold, set.guardians.len() as u8]);
+            h.update(set.delay_secs.to_be_bytes());
+            for key in &set.guardians {
+                h.update((key.len() as u32).to_be_bytes());
+                h.update(key);
+            }
+        }
+        None => h.update([0u8]),
+    }
+    h.finalize().into()
+}
+
+/// Passkey commitment for cancelling recovery `round`.
+pub fn cancel_commitment(wallet_id: &Uuid, round: u64) -> [u8; 32] {
+    let mut h = Keccak256::new();
+    h.update(b"AA-RECOVERY-CANCEL-v1");
+    h.update(wallet_id.as_bytes());
+    h.update(round.to_be_bytes());
+    h.finalize().into()
+}
+
+/// What a guardian signs to name `new_passkey` in recovery `round`.
+pub fn attestation_digest(wallet_id: &Uuid, round: u64, new_passkey: &[u8]) -> [u8; 32] {
+    let mut h = Keccak256::new();
+    h.update(b"AA-RECOVERY-ATTEST-v1");
+    h.update(wallet_id.as_bytes());
+    h.update(round.to_be_bytes());
+    h.update(new_passkey);
+    h.finalize().into()
+}
+
+/// A passkey the guardians have named this round.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RecoveryCandidate {
+    pub passkey: Vec<u8>,
+    pub attestations: u8,
+}
+
+/// A candidate that reached the threshold.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ArmedRecovery {
+    pub passkey: Vec<u8>,
+    /// TA time (UNIX seconds) from which `CompleteRecovery` succeeds.
+    pub ready_at: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RecoveryStatus {
+    pub guardians: Option<GuardianSet>,
+    /// Guardians sign this; see [`attestation_digest`].
+    pub round: u64,
+    pub candidates: Vec<RecoveryCandidate>,
+    pub armed: Option<ArmedRecovery>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(threshold: u8, n: u8) -> GuardianSet {
+        GuardianSet {
+            threshold,
+            guardians: (0..n).map(|i| [vec![0x03], vec![i; 32]].concat()).collect(),
+            delay_secs: 2 * 86_400,
+        }
+    }
+
+    #[test]
+    fn guardian_set_shape() {
+        assert!(set(2, 3).validate().is_ok());
+        assert!(set(0, 3).validate().is_err());
+        assert!(set(4, 3).validate().is_err());
+        assert!(set(1, 0).validate().is_err());
+        assert!(set(1, MAX_GUARDIANS as u8 + 1).validate().is_err());
+        let mut twice = set(1, 2);
+        twice.guardians[1] = twice.guardians[0].clone();
+        assert!(twice.validate().is_err());
+        let mut hasty = set(2, 3);
+        hasty.delay_secs = MIN_DELAY_SECS - 1;
+        assert!(hasty.validate().is_err());
+        hasty.delay_secs = MAX_DELAY_SECS + 1;
+        assert!(hasty.validate().is_err());
+    }
+
+    #[test]
+    fn commitments_are_domain_separated() {
+        let w = Uuid::from_bytes([1; 16]);
+        let s = set(2, 3);
+        assert_ne!(
+            guardians_commitment(&w, Some(&s)),
+            guardians_commitment(&w, None)
+        );
+        let mut slower = s.clone();
+        slower.delay_secs += 1;
+        assert_ne!(
+            guardians_commitment(&w, Some(&s)),
+            guardians_commitment(&w, Some(&slower))
+        );
+        assert_ne!(cancel_commitment(&w, 1), cancel_commitment(&w, 2));
+        let pk = [4u8; 65];
+        assert_ne!(
+            attestation_digest(&w, 1, &pk),
+            attestation_digest(&w, 2, &pk)
+        );
+        assert_ne!(
+            attestation_digest(&w, 1, &pk),
+            attestation_digest(&Uuid::nil(), 1, &pk)
+        );
+    }
+}
@@ -0,0 +1,129 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Solana accounts: ed25519 keys the TA derives from the wallet seed.
+//!
+//! SLIP-0010 derives ed25519 keys along hardened levels only, so an account
+//! lives at `m/44'/501'/{account}'/0'`, as Phantom and `solana-keygen` write
+//! it. The address is the 32-byte public key in Base58. The TA signs a
+//! message (a serialized transaction message, or an off-chain message) as
+//! given; the passkey assertion commits to [`signing_payload`] of it.
+
+use crate::hd_path::{parse_path, HARDENED_BIT};
+use sha3::{Digest, Keccak256};
+
+/// SLIP-44 coin type of Solana.
+pub const SOLANA_COIN_TYPE: u32 = 501;
+
+/// HMAC key of the SLIP-0010 ed25519 master key.
+pub const SLIP10_ED25519_SEED: &[u8] = b"ed25519 seed";
+
+/// Longest message the TA signs: a Solana packet, which any transaction
+/// message fits in.
+pub const MAX_MESSAGE_LEN: usize = 1232;
+
+const DOMAIN: &[u8] = b"AirAccount-solana-v1";
+
+/// `m/44'/501'/account'/0'`.
+pub fn solana_path(account: u32) -> String {
+    format!("m/44'/{}'/{}'/0'", SOLANA_COIN_TYPE, account)
+}
+
+/// Parse `m/44'/501'/account'/0'` into the account index.
+pub fn parse_solana_path(path: &str) -> Result<u32, String> {
+    let levels = parse_path(path)?.hardened_levels().ok_or_else(|| {
+        format!(
+            "Every Solana path level must be hardened, got: {}",
+            path.trim()
+        )
+    })?;
+    match levels[..] {
+        [44, SOLANA_COIN_TYPE, account, 0] => Ok(account),
+        _ => Err(format!(
+            "Expected path m/44'/{}'/account'/0', got: {}",
+            SOLANA_COIN_TYPE,
+            path.trim()
+        )),
+    }
+}
+
+/// The path's levels as SLIP-0010 takes them, [`HARDENED_BIT`] set.
+pub fn path_levels(account: u32) -> [u32; 4] {
+    [
+        44 | HARDENED_BIT,
+        SOLANA_COIN_TYPE | HARDENED_BIT,
+        account | HARDENED_BIT,
+        HARDENED_BIT,
+    ]
+}
+
+/// Base58 of the public key.
+pub fn address(public_key: &[u8; 32]) -> String {
+    crate::chain::base58(public_key)
+}
+
+/// What the passkey assertion of a `SignSolanaMessage` commits to. Domain
+/// separated, so an assertion for these bytes never authorises an EVM hash.
+pub fn signing_payload(message: &[u8]) -> [u8; 32] {
+    let mut h = Keccak256::new();
+    h.update(DOMAIN);
+    h.update(message);
+    h.finalize().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_are_fully_hardened() {
+        assert_eq!(solana_path(0), "m/44'/501'/0'/0'");
+        assert_eq!(parse_solana_path("m/44h/501h/7h/0h"), Ok(7));
+        assert_eq!(parse_solana_path(&solana_path(3)), Ok(3));
+        assert_eq!(
+            path_levels(1),
+            [0x8000_002c, 0x8000_01f5, 0x8000_0001, 0x8000_0000]
+        );
+        for bad in &[
+            "m/44'/501'/0'/0",
+            "m/44'/501'/0'",
+            "m/44'/501'/0'/0'/0'",
+            "m/44'/60'/0'/0'",
+            "m/44'/501'/0'/1'",
+            "44'/501'/0'/0'",
+        ] {
+            assert!(parse_solana_path(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn address_is_base58_of_the_key() {
+        // The System Program is the all-zero key.
+        assert_eq!(address(&[0; 32]), "11111111111111111111111111111111");
+        let mut key = [0u8; 32];
+        key[31] = 1;
+        assert_eq!(address(&key), "11111111111111111111111111111112");
+    }
+
+    #[test]
+    fn payload_is_domain_separated() {
+        let message = b"solana message";
+        assert_ne!(signing_payload(message), signing_payload(b"solana messagf"));
+        let plain: [u8; 32] = Keccak256::digest(message).into();
+        assert_ne!(signing_payload(message), plain);
+    }
+}
@@ -0,0 +1,580 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Per-wallet spending policy, checked by the TA before every transaction
+//! signature.
+//!
+//! The owner sets it with a passkey (`Command::SpendingPolicy`): a daily
+//! cap on native value, destination allow / deny lists, a gas price
+//! ceiling and the chains the wallet may sign for. A transaction that
+//! breaks a rule is refused with a [`PolicyViolation`], which travels as
+//! error text like `wire::UnsupportedVersion` so the CA can answer with a
+//! typed error.
+//!
+//! Destinations are the transaction's `to` and, for a decoded ERC-20
+//! `transfer` / `transferFrom`, the token recipient. The daily limit counts
+//! native value only, per UTC day of the TA clock.
+
+use crate::calldata::{hex_addr, DecodedCall};
+use crate::EthTransaction;
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+use uuid::Uuid;
+
+/// Addresses per list.
+pub const MAX_DESTINATIONS: usize = 64;
+/// Entries in `allowed_chain_ids`.
+pub const MAX_CHAIN_IDS: usize = 32;
+
+pub const SECS_PER_DAY: u64 = 86_400;
+
+/// UTC day number of a unix time; the daily limit resets when it changes.
+pub fn day_of(unix_secs: u64) -> u64 {
+    unix_secs / SECS_PER_DAY
+}
+
+/// Every rule is optional; the default policy allows everything.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct SpendingPolicy {
+    /// Native value (wei) the wallet may send per UTC day.
+    pub daily_limit_wei: Option<u128>,
+    /// Non-empty = only these destinations. A contract creation has none
+    /// and is refused.
+    pub allowed_destinations: Vec<[u8; 20]>,
+    pub denied_destinations: Vec<[u8; 20]>,
+    /// Ceiling on `gas_price` (`max_fee_per_gas` for EIP-1559).
+    pub max_gas_price_wei: Option<u128>,
+    /// Non-empty = only these chains.
+    pub allowed_chain_ids: Vec<u64>,
+}
+
+impl SpendingPolicy {
+    pub fn is_off(&self) -> bool {
+        *self == SpendingPolicy::default()
+    }
+
+    /// Lists sorted and deduplicated, sizes and overlaps checked.
+    pub fn normalized(&self) -> Result<SpendingPolicy, &'static str> {
+        let mut policy = self.clone();
+        for list in [
+            &mut policy.allowed_destinations,
+            &mut policy.denied_destinations,
+        ] {
+            list.sort_unstable();
+            list.dedup();
+            if list.len() > MAX_DESTINATIONS {
+                return Err("too many destinations in the spending policy");
+            }
+        }
+        policy.allowed_chain_ids.sort_unstable();
+        policy.allowed_chain_ids.dedup();
+        if policy.allowed_chain_ids.len() > MAX_CHAIN_IDS {
+            return Err("too many chain ids in the spending policy");
+        }
+        if policy
+            .denied_destinations
+            .iter()
+            .any(|a| policy.allowed_destinations.binary_search(a).is_ok())
+        {
+            return Err("an address cannot be both allowed and denied");
+        }
+        Ok(policy)
+    }
+
+    /// Checks `tx` given the native value already sent today: chain, gas
+    /// price, destinations, then the daily limit. The first broken rule is
+    /// reported.
+    pub fn check(
+        &self,
+        tx: &EthTransaction,
+        decoded: &DecodedCall,
+        spent_today: u128,
+    ) -> Result<(), PolicyViolation> {
+        if !self.allowed_chain_ids.is_empty() && !self.allowed_chain_ids.contains(&tx.chain_id) {
+            return Err(PolicyViolation::ChainNotAllowed {
+                chain_id: tx.chain_id,
+            });
+        }
+        if let Some(max) = self.max_gas_price_wei {
+            if tx.gas_price > max {
+                return Err(PolicyViolation::GasPriceTooHigh {
+                    gas_price: tx.gas_price,
+                    max,
+                });
+            }
+        }
+        for destination in destinations(tx, decoded) {
+            if let Some(address) = destination {
+                if self.denied_destinations.contains(&address) {
+                    return Err(PolicyViolation::DestinationDenied { address });
+                }
+            }
+            if !self.allowed_destinations.is_empty()
+                && !destination.is_some_and(|a| self.allowed_destinations.contains(&a))
+            {
+                return Err(PolicyViolation::DestinationNotAllowed {
+                    address: destination,
+                });
+            }
+        }
+        if let Some(limit) = self.daily_limit_wei {
+            if tx.value > 0 && spent_today.saturating_add(tx.value) > limit {
+                return Err(PolicyViolation::DailyLimitExceeded {
+                    limit,
+                    spent: spent_today,
+                    value: tx.value,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `tx.to` (None for a contract creation), then the token recipient.
+fn destinations(tx: &EthTransaction, decoded: &DecodedCall) -> Vec<Option<[u8; 20]>> {
+    let mut out = vec![tx.to];
+    match decoded {
+        DecodedCall::Transfer { to, .. } | DecodedCall::TransferFrom { to, .. } => {
+            out.push(Some(*to))
+        }
+        _ => {}
+    }
+    out
+}
+
+/// Native value sent on one UTC day, kept by the TA next to the policy.
+///
+/// The day only moves forward. The TA clock may come from the REE, so a day
+/// earlier than `day` counts as `day`: setting the clock back does not reopen
+/// the limit, and a jump forward spends the days it skips.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DailySpend {
+    pub day: u64,
+    pub spent_wei: u128,
+}
+
+impl DailySpend {
+    pub fn spent_on(&self, day: u64) -> u128 {
+        if day <= self.day {
+            self.spent_wei
+        } else {
+            0
+        }
+    }
+
+    pub fn add(&mut self, day: u64, value: u128) {
+        self.spent_wei = self.spent_on(day).saturating_add(value);
+        self.day = self.day.max(day);
+    }
+}
+
+/// Passkey commitment for setting `policy`, over the policy as sent.
+pub fn policy_commitment(wallet_id: &Uuid, policy: &SpendingPolicy) -> [u8; 32] {
+    fn limit(h: &mut Keccak256, value: Option<u128>) {
+        match value {
+            Some(v) => {
+                h.update([1u8]);
+                h.update(v.to_be_bytes());
+            }
+            None => h.update([0u8]),
+        }
+    }
+    let mut h = Keccak256::new();
+    h.update(b"AA-SPENDING-POLICY-v1");
+    h.update(wallet_id.as_bytes());
+    limit(&mut h, policy.daily_limit_wei);
+    for list in [&policy.allowed_destinations, &policy.denied_destinations] {
+        h.update((list.len() as u32).to_be_bytes());
+        for address in list {
+            h.update(address);
+        }
+    }
+    limit(&mut h, policy.max_gas_price_wei);
+    h.update((policy.allowed_chain_ids.len() as u32).to_be_bytes());
+    for chain_id in &policy.allowed_chain_ids {
+        h.update(chain_id.to_be_bytes());
+    }
+    h.finalize().into()
+}
+
+/// The rule a transaction broke. See [`PolicyViolation::find`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PolicyViolation {
+    ChainNotAllowed {
+        chain_id: u64,
+    },
+    GasPriceTooHigh {
+        gas_price: u128,
+        max: u128,
+    },
+    DestinationDenied {
+        address: [u8; 20],
+    },
+    /// `address` is None for a contract creation.
+    DestinationNotAllowed {
+        address: Option<[u8; 20]>,
+    },
+    DailyLimitExceeded {
+        limit: u128,
+        spent: u128,
+        value: u128,
+    },
+}
+
+const VIOLATION_TAG: &str = "PolicyViolation: ";
+/// Stands in for the address of a contract creation.
+const CREATE: &str = "create";
+
+impl PolicyViolation {
+    /// Kebab-case rule name, the first word after the tag.
+    pub fn rule(&self) -> &'static str {
+        match self {
+            PolicyViolation::ChainNotAllowed { .. } => "chain-not-allowed",
+            PolicyViolation::GasPriceTooHigh { .. } => "gas-price-too-high",
+            PolicyViolation::DestinationDenied { .. } => "destination-denied",
+            PolicyViolation::DestinationNotAllowed { .. } => "destination-not-allowed",
+            PolicyViolation::DailyLimitExceeded { .. } => "daily-limit-exceeded",
+        }
+    }
+
+    /// Recover the error from a TA error message that contains it.
+    pub fn find(message: &str) -> Option<Self> {
+        let text = &message[message.find(VIOLATION_TAG)? + VIOLATION_TAG.len()..];
+        let mut words = text.split_whitespace();
+        let rule = words.next()?;
+        let fields: Vec<(&str, &str)> = words.map_while(|w| w.split_once('=')).collect();
+        let field = |name: &str| fields.iter().find(|(k, _)| *k == name).map(|(_, v)| *v);
+        let number = |name: &str| field(name)?.parse::<u128>().ok();
+        let address = |name: &str| crate::eip55::parse_address(field(name)?).ok();
+        Some(match rule {
+            "chain-not-allowed" => PolicyViolation::ChainNotAllowed {
+                chain_id: field("chain_id")?.parse().ok()?,
+            },
+            "gas-price-too-high" => PolicyViolation::GasPriceTooHigh {
+                gas_price: number("gas_price")?,
+                max: number("max")?,
+            },
+            "destination-denied" => PolicyViolation::DestinationDenied {
+                address: address("address")?,
+            },
+            "destination-not-allowed" => PolicyViolation::DestinationNotAllowed {
+                address: if field("address")? == CREATE {
+                    None
+                } else {
+                    Some(address("address")?)
+                },
+            },
+            "daily-limit-exceeded" => PolicyViolation::DailyLimitExceeded {
+                limit: number("limit")?,
+                spent: number("spent")?,
+                value: number("value")?,
+            },
+            _ => return None,
+        })
+    }
+}
+
+impl std::fmt::Display for PolicyViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", VIOLATION_TAG, self.rule())?;
+        match self {
+            PolicyViolation::ChainNotAllowed { chain_id } => write!(f, " chain_id={}", chain_id),
+            PolicyViolation::GasPriceTooHigh { gas_price, max } => {
+                write!(f, " gas_price={} max={}", gas_price, max)
+            }
+            PolicyViolation::DestinationDenied { address } => {
+                write!(f, " address={}", hex_addr(address))
+            }
+            PolicyViolation::DestinationNotAllowed { address } => write!(
+                f,
+                " address={}",
+                address
+                    .as_ref()
+                    .map_or_else(|| CREATE.to_string(), hex_addr)
+            ),
+            PolicyViolation::DailyLimitExceeded {
+                limit,
+                spent,
+                value,
+            } => write!(f, " limit={} spent={} value={}", limit, spent, value),
+        }
+    }
+}
+
+impl std::error::Error for PolicyViolation {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calldata::decode_transaction;
+
+    const ALICE: [u8; 20] = [0xa1; 20];
+    const BOB: [u8; 20] = [0xb0; 20];
+    const TOKEN: [u8; 20] = [0x70; 20];
+
+    fn tx(to: Option<[u8; 20]>, value: u128, data: Vec<u8>) -> EthTransaction {
+        EthTransaction {
+            chain_id: 1,
+            nonce: 0,
+            to,
+            value,
+            gas_price: 20_000_000_000,
+            gas: 60_000,
+            data,
+        }
+    }
+
+    fn check(
+        policy: &SpendingPolicy,
+        tx: &EthTransaction,
+        spent: u128,
+    ) -> Result<(), PolicyViolation> {
+        policy.check(tx, &decode_transaction(tx, &[]), spent)
+    }
+
+    fn erc20_transfer(to: [u8; 20]) -> Vec<u8> {
+        let mut data = crate::calldata::selector("transfer(address,uint256)").to_vec();
+        data.extend_from_slice(&[0u8; 12]);
+        data.extend_from_slice(&to);
+        data.extend_from_slice(&[0u8; 31]);
+        data.push(5);
+        data
+    }
+
+    #[test]
+    fn default_policy_allows_everything() {
+        let policy = SpendingPolicy::default();
+        assert!(policy.is_off());
+        assert_eq!(
+            check(&policy, &tx(None, u128::MAX, vec![0x60]), u128::MAX),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn chain_and_gas_price_rules() {
+        let policy = SpendingPolicy {
+            allowed_chain_ids: vec![10, 8453],
+            max_gas_price_wei: Some(10_000_000_000),
+            ..Default::default()
+        };
+        let mut t = tx(Some(BOB), 1, Vec::new());
+        assert_eq!(
+            check(&policy, &t, 0),
+            Err(PolicyViolation::ChainNotAllowed { chain_id: 1 })
+        );
+        t.chain_id = 10;
+        assert_eq!(
+            check(&policy, &t, 0),
+            Err(PolicyViolation::GasPriceTooHigh {
+                gas_price: 20_000_000_000,
+                max: 10_000_000_000
+            })
+        );
+        t.gas_price = 10_000_000_000;
+        assert_eq!(check(&policy, &t, 0), Ok(()));
+    }
+
+    #[test]
+    fn destinations_include_the_token_recipient() {
+        let deny = SpendingPolicy {
+            denied_destinations: vec![BOB],
+            ..Default::default()
+        };
+        let allow = SpendingPolicy {
+            allowed_destinations: vec![ALICE, TOKEN],
+            ..Default::default()
+        };
+        let direct = tx(Some(BOB), 1, Vec::new());
+        let via_token = tx(Some(TOKEN), 0, erc20_transfer(BOB));
+        for t in [&direct, &via_token] {
+            assert_eq!(
+                check(&deny, t, 0),
+                Err(PolicyViolation::DestinationDenied { address: BOB })
+            );
+            assert_eq!(
+                check(&allow, t, 0),
+                Err(PolicyViolation::DestinationNotAllowed { address: Some(BOB) })
+            );
+        }
+        assert_eq!(
+            check(&allow, &tx(Some(TOKEN), 0, erc20_transfer(ALICE)), 0),
+            Ok(())
+        );
+        assert_eq!(
+            check(&allow, &tx(None, 0, vec![0x60]), 0),
+            Err(PolicyViolation::DestinationNotAllowed { address: None })
+        );
+    }
+
+    #[test]
+    fn daily_limit_counts_native_value_per_day() {
+        let policy = SpendingPolicy {
+            daily_limit_wei: Some(100),
+            ..Default::default()
+        };
+        assert_eq!(check(&policy, &tx(Some(BOB), 60, Vec::new()), 40), Ok(()));
+        assert_eq!(
+            check(&policy, &tx(Some(BOB), 61, Vec::new()), 40),
+            Err(PolicyViolation::DailyLimitExceeded {
+                limit: 100,
+                spent: 40,
+                value: 61
+            })
+        );
+        // Zero-value calls pass even once the limit is used up.
+        assert_eq!(check(&policy, &tx(Some(BOB), 0, Vec::new()), 500), Ok(()));
+
+        let mut spend = DailySpend::default();
+        spend.add(day_of(SECS_PER_DAY * 3), 40);
+        spend.add(day_of(SECS_PER_DAY * 3 + 5), 20);
+        assert_eq!(spend.spent_on(3), 60);
+        assert_eq!(spend.spent_on(4), 0);
+        spend.add(4, 7);
+        assert_eq!(
+            spend,
+            DailySpend {
+                day: 4,
+                spent_wei: 7
+            }
+        );
+    }
+
+    #[test]
+    fn daily_spend_ignores_a_clock_set_back() {
+        let mut spend = DailySpend::default();
+        spend.add(10, 90);
+        // Back a day: still day 10's spend, and the day stays 10.
+        assert_eq!(spend.spent_on(9), 90);
+        spend.add(9, 5);
+        assert_eq!(
+            spend,
+            DailySpend {
+                day: 10,
+                spent_wei: 95
+            }
+        );
+        // Forward again to the same day: nothing was reset.
+        assert_eq!(spend.spent_on(10), 95);
+        // A jump forward starts a new day, and going back from it does not
+        // return to day 10's total.
+        spend.add(12, 30);
+        assert_eq!(spend.spent_on(12), 30);
+        assert_eq!(spend.spent_on(10), 30);
+        spend.add(10, 1);
+        assert_eq!(
+            spend,
+            DailySpend {
+                day: 12,
+                spent_wei: 31
+            }
+        );
+        assert_eq!(spend.spent_on(13), 0);
+    }
+
+    #[test]
+    fn normalized_sorts_dedups_and_rejects_overlap() {
+        let policy = SpendingPolicy {
+            allowed_destinations: vec![BOB, ALICE, BOB],
+            allowed_chain_ids: vec![10, 1, 10],
+            ..Default::default()
+        };
+        let n = policy.normalized().unwrap();
+        assert_eq!(n.allowed_destinations, vec![ALICE, BOB]);
+        assert_eq!(n.allowed_chain_ids, vec![1, 10]);
+
+        let overlap = SpendingPolicy {
+            allowed_destinations: vec![ALICE],
+            denied_destinations: vec![ALICE],
+            ..Default::default()
+        };
+        assert!(overlap.normalized().is_err());
+        let too_many = SpendingPolicy {
+            denied_destinations: (0..=MAX_DESTINATIONS as u8).map(|i| [i; 20]).collect(),
+            ..Default::default()
+        };
+        assert!(too_many.normalized().is_err());
+    }
+
+    #[test]
+    fn commitment_binds_every_rule() {
+        let w = Uuid::from_bytes([7; 16]);
+        let base = SpendingPolicy::default();
+        let variants = [
+            SpendingPolicy {
+                daily_limit_wei: Some(0),
+                ..Default::default()
+            },
+            SpendingPolicy {
+                allowed_destinations: vec![ALICE],
+                ..Default::default()
+            },
+            SpendingPolicy {
+                denied_destinations: vec![ALICE],
+                ..Default::default()
+            },
+            SpendingPolicy {
+                max_gas_price_wei: Some(0),
+                ..Default::default()
+            },
+            SpendingPolicy {
+                allowed_chain_ids: vec![1],
+                ..Default::default()
+            },
+        ];
+        let mut seen = vec![policy_commitment(&w, &base)];
+        for v in &variants {
+            let c = policy_commitment(&w, v);
+            assert!(!seen.contains(&c));
+            seen.push(c);
+        }
+        assert_ne!(
+            policy_commitment(&w, &base),
+            policy_commitment(&Uuid::from_bytes([8; 16]), &base)
+        );
+    }
+
+    #[test]
+    fn the_violation_survives_the_error_text() {
+        let all = [
+            PolicyViolation::ChainNotAllowed { chain_id: 137 },
+            PolicyViolation::GasPriceTooHigh {
+                gas_price: 2,
+                max: 1,
+            },
+            PolicyViolation::DestinationDenied { address: BOB },
+            PolicyViolation::DestinationNotAllowed {
+                address: Some(ALICE),
+            },
+            PolicyViolation::DestinationNotAllowed { address: None },
+            PolicyViolation::DailyLimitExceeded {
+                limit: u128::MAX,
+                spent: 3,
+                value: 4,
+            },
+        ];
+        for v in all {
+            let text = format!("TA command failed: {} (error: BadParameters)", v);
+            assert_eq!(PolicyViolation::find(&text), Some(v));
+        }
+        assert_eq!(
+            PolicyViolation::find("PolicyViolation: something-else"),
+            None
+        );
+        assert_eq!(PolicyViolation::find("wallet not found"), None);
+    }
+}
@@ -0,0 +1,149 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! EIP-5564 stealth addresses, scheme 1 (secp256k1 with view tags).
+//!
+//! A wallet publishes a meta-address `st:eth:0x<spending key><viewing key>`
+//! (two compressed points). A sender picks an ephemeral key `e` and
+//! computes `s = e·V`, `s_h = keccak256(s)` over the compressed point, and
+//! pays `P_spend + s_h·G`; the announcement carries `e·G` and the view tag
+//! `s_h[0]`. Both private keys stay in the TA, which derives them at
+//! [`spending_levels`] / [`viewing_levels`] and checks announcements in
+//! batches for the CA's scanner; the sender side runs in the CA, which
+//! needs nothing secret for it.
+
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+use std::fmt;
+
+/// EIP-5564 scheme id of secp256k1 with view tags.
+pub const SCHEME_ID: u32 = 1;
+
+/// The ERC-5564 announcer singleton, the same address on every chain.
+pub const ANNOUNCER: &str = "0x55649e01b5df198d18d95b5cc5051630cfd45564";
+
+/// Announcements the TA checks in one StealthScan.
+pub const MAX_SCAN_BATCH: usize = 128;
+
+const PREFIX: &str = "st:eth:0x";
+const KEY_LEN: usize = 33;
+
+/// BIP32 levels (each hardened) of the spending key of `account`:
+/// `m/5564'/60'/account'/0'`.
+pub fn spending_levels(account: u32) -> [u32; 4] {
+    [5564, 60, account, 0]
+}
+
+/// `m/5564'/60'/account'/1'`.
+pub fn viewing_levels(account: u32) -> [u32; 4] {
+    [5564, 60, account, 1]
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct StealthMetaAddress {
+    /// Compressed secp256k1 point.
+    pub spending_pubkey: Vec<u8>,
+    /// Compressed secp256k1 point.
+    pub viewing_pubkey: Vec<u8>,
+}
+
+impl StealthMetaAddress {
+    /// `st:eth:0x` followed by 66 bytes of hex. The points themselves are
+    /// checked by whoever does curve arithmetic with them.
+    pub fn parse(s: &str) -> Result<Self, &'static str> {
+        let hex = s
+            .trim()
+            .strip_prefix(PREFIX)
+            .ok_or("stealth meta-address must start with st:eth:0x")?;
+        if hex.len() != 4 * KEY_LEN || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err("stealth meta-address must carry two 33-byte keys in hex");
+        }
+        let bytes: Vec<u8> = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap_or(0))
+            .collect();
+        let meta = StealthMetaAddress {
+            spending_pubkey: bytes[..KEY_LEN].to_vec(),
+            viewing_pubkey: bytes[KEY_LEN..].to_vec(),
+        };
+        meta.validate()?;
+        Ok(meta)
+    }
+
+    pub fn validate(&self) -> Result<(), &'static str> {
+        for key in [&self.spending_pubkey, &self.viewing_pubkey] {
+            if key.len() != KEY_LEN || !(key[0] == 0x02 || key[0] == 0x03) {
+                return Err("stealth meta-address keys must be compressed points");
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for StealthMetaAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(PREFIX)?;
+        for b in self.spending_pubkey.iter().chain(&self.viewing_pubkey) {
+            write!(f, "{:02x}", b)?;
+        }
+        Ok(())
+    }
+}
+
+/// `s_h`: keccak256 of the compressed shared point. Its first byte is the
+/// view tag.
+pub fn hashed_secret(shared_compressed: &[u8]) -> [u8; 32] {
+    Keccak256::digest(shared_compressed).into()
+}
+
+/// One announcement as the scanner hands it to the TA.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct StealthCandidate {
+    pub stealth_address: [u8; 20],
+    /// Compressed `e·G` from the announcement.
+    pub ephemeral_pubkey: Vec<u8>,
+    /// First byte of the announcement's metadata.
+    pub view_tag: u8,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Spending and viewing keys of account 0 of "test … junk".
+    const META: &str = "st:eth:0x0227051fb9fdc475ad7da23a0e2797551e11053eb65a51f73237ff04a1ea425dd302d8e2df8391bfb3cd03393ce94a8c7f4ca459c1d217e659e89d285e57d1cd6e53";
+
+    #[test]
+    fn meta_address_roundtrips() {
+        let meta = StealthMetaAddress::parse(META).unwrap();
+        assert_eq!(meta.spending_pubkey[0], 0x02);
+        assert_eq!(meta.viewing_pubkey[..2], [0x02, 0xd8]);
+        assert_eq!(meta.to_string(), META);
+
+        assert!(StealthMetaAddress::parse(&META.replace("st:eth:", "st:btc:")).is_err());
+        assert!(StealthMetaAddress::parse(&META[..META.len() - 2]).is_err());
+        assert!(StealthMetaAddress::parse(&META.replace("0x02", "0x04")).is_err());
+        assert!(StealthMetaAddress::parse(&META.replace('e', "g")).is_err());
+    }
+
+    #[test]
+    fn key_paths_are_separate_hardened_branches() {
+        assert_eq!(spending_levels(0), [5564, 60, 0, 0]);
+        assert_eq!(viewing_levels(3), [5564, 60, 3, 1]);
+        assert_eq!(hashed_secret(&[]), &Keccak256::digest([])[..]);
+    }
+}
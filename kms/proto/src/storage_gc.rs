@@ -0,0 +1,306 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Orphaned objects in the TA's secure storage.
+//!
+//! Session keys, allowance policies, replay logs, refresh families, OTP
+//! secrets and dapp storage are keyed by their wallet and only ever reached
+//! through it. A removal cut short after the wallet delete (the dapp and OTP
+//! wipes run after it), or one from before those wipes existed, leaves
+//! objects no command will read again. `Command::ScavengeStorage` lists them
+//! and deletes them only when the caller echoes the [`orphan_set_digest`] of
+//! the set it was shown; the TA's automatic pass removes only objects whose
+//! wallet is gone. BLS slashing records are never collected: their history
+//! outlives the key on purpose.
+
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+use std::collections::{BTreeMap, BTreeSet};
+use uuid::Uuid;
+
+/// Orphans named in one `ScavengeStorage` output; the rest are counted.
+pub const MAX_LISTED: usize = 16;
+
+/// Objects the automatic pass deletes per TA load, so it cannot stall the
+/// command it follows.
+pub const AUTO_SCAVENGE_LIMIT: usize = 64;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ObjectKind {
+    P256SessionKey,
+    ScopedSessionKey,
+    SessionRevocations,
+    AllowancePolicy,
+    SpenderAllowList,
+    OfflineReplayLog,
+    RefreshFamily,
+    OtpSecret,
+    DappNamespace,
+    DappBlob,
+    PathPolicy,
+    RemoteApprover,
+    AggregatorScheme,
+    SpendingPolicy,
+    MultiSig,
+    SocialRecovery,
+    NonceLedger,
+}
+
+impl ObjectKind {
+    pub const ALL: [ObjectKind; 17] = [
+        ObjectKind::P256SessionKey,
+        ObjectKind::ScopedSessionKey,
+        ObjectKind::SessionRevocations,
+        ObjectKind::AllowancePolicy,
+        ObjectKind::SpenderAllowList,
+        ObjectKind::OfflineReplayLog,
+        ObjectKind::RefreshFamily,
+        ObjectKind::OtpSecret,
+        ObjectKind::DappNamespace,
+        ObjectKind::DappBlob,
+        ObjectKind::PathPolicy,
+        ObjectKind::RemoteApprover,
+        ObjectKind::AggregatorScheme,
+        ObjectKind::SpendingPolicy,
+        ObjectKind::MultiSig,
+        ObjectKind::SocialRecovery,
+        ObjectKind::NonceLedger,
+    ];
+
+    /// Store-id prefix; the wallet UUID follows it.
+    pub fn prefix(self) -> &'static str {
+        match self {
+            ObjectKind::P256SessionKey => "p256sk_",
+            ObjectKind::ScopedSessionKey => "ssk_",
+            ObjectKind::SessionRevocations => "sskrev_",
+            ObjectKind::AllowancePolicy => "allowpol_",
+            ObjectKind::SpenderAllowList => "spenders_",
+            ObjectKind::OfflineReplayLog => "offline_",
+            ObjectKind::RefreshFamily => "refresh_",
+            ObjectKind::OtpSecret => "otp_",
+            ObjectKind::DappNamespace => "dappns_",
+            ObjectKind::DappBlob => "dapp_",
+            ObjectKind::PathPolicy => "pathpol_",
+            ObjectKind::RemoteApprover => "approver_",
+            ObjectKind::AggregatorScheme => "aggsig_",
+            ObjectKind::SpendingPolicy => "spendpol_",
+            ObjectKind::MultiSig => "multisig_",
+            ObjectKind::SocialRecovery => "recovery_",
+            ObjectKind::NonceLedger => "nonce_",
+        }
+    }
+
+    /// One object per wallet: nothing follows the UUID.
+    fn per_wallet(self) -> bool {
+        matches!(
+            self,
+            ObjectKind::SessionRevocations
+                | ObjectKind::AllowancePolicy
+                | ObjectKind::SpenderAllowList
+                | ObjectKind::OfflineReplayLog
+                | ObjectKind::PathPolicy
+                | ObjectKind::RemoteApprover
+                | ObjectKind::AggregatorScheme
+                | ObjectKind::SpendingPolicy
+                | ObjectKind::MultiSig
+                | ObjectKind::SocialRecovery
+                | ObjectKind::NonceLedger
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrphanReason {
+    /// The wallet it belongs to is not in storage.
+    WalletMissing,
+    /// Not an id any command builds, so nothing can read it.
+    MalformedId,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Orphan {
+    pub kind: ObjectKind,
+    pub store_id: String,
+    pub reason: OrphanReason,
+}
+
+/// The wallet `store_id` belongs to, or None if it is not a `kind` id.
+pub fn owner_wallet(kind: ObjectKind, store_id: &str) -> Option<Uuid> {
+    let rest = store_id.strip_prefix(kind.prefix())?;
+    let uuid = rest.get(..36)?;
+    let tail = &rest[36..];
+    let well_formed = if kind.per_wallet() {
+        tail.is_empty()
+    } else {
+        tail.len() > 1 && tail.starts_with('_')
+    };
+    if !well_formed {
+        return None;
+    }
+    let wallet = Uuid::parse_str(uuid).ok()?;
+    // Ids are built from the hyphenated form; any other spelling is not one.
+    (wallet.to_string() == uuid).then_some(wallet)
+}
+
+/// Wallets whose presence decides `objects`, each once.
+pub fn referenced_wallets(objects: &[(ObjectKind, String)]) -> BTreeSet<Uuid> {
+    objects
+        .iter()
+        .filter_map(|(kind, id)| owner_wallet(*kind, id))
+        .collect()
+}
+
+/// The orphans among `objects`, given which of `referenced_wallets` are
+/// stored. Sorted, so the digest does not depend on listing order.
+pub fn find_orphans(objects: &[(ObjectKind, String)], wallets: &BTreeSet<Uuid>) -> Vec<Orphan> {
+    let mut orphans: Vec<Orphan> = objects
+        .iter()
+        .filter_map(|(kind, id)| {
+            let reason = match owner_wallet(*kind, id) {
+                None => OrphanReason::MalformedId,
+                Some(wallet) if !wallets.contains(&wallet) => OrphanReason::WalletMissing,
+                Some(_) => return None,
+            };
+            Some(Orphan {
+                kind: *kind,
+                store_id: id.clone(),
+                reason,
+            })
+        })
+        .collect();
+    orphans.sort_by(|a, b| (a.kind, &a.store_id).cmp(&(b.kind, &b.store_id)));
+    orphans.dedup();
+    orphans
+}
+
+/// Orphans per kind, for the report.
+pub fn count_by_kind(orphans: &[Orphan]) -> Vec<(ObjectKind, u32)> {
+    let mut counts = BTreeMap::new();
+    for o in orphans {
+        *counts.entry(o.kind).or_insert(0u32) += 1;
+    }
+    counts.into_iter().collect()
+}
+
+/// What a delete must be confirmed with: Keccak-256 over the sorted set.
+/// Any object appearing or disappearing in between changes it.
+pub fn orphan_set_digest(orphans: &[Orphan]) -> [u8; 32] {
+    let mut h = Keccak256::new();
+    h.update(b"AirAccount.OrphanSet.v1");
+    h.update((orphans.len() as u32).to_be_bytes());
+    for o in orphans {
+        h.update([o.kind as u8, o.reason as u8]);
+        h.update((o.store_id.len() as u32).to_be_bytes());
+        h.update(o.store_id.as_bytes());
+    }
+    h.finalize().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const A: &str = "4319f351-0b24-4097-b659-80ee4f824cdd";
+    const B: &str = "a1b2c3d4-e5f6-7890-abcd-ef1234567890";
+
+    #[test]
+    fn store_ids_parse_back_to_their_wallet() {
+        let a = Uuid::parse_str(A).unwrap();
+        for (kind, id) in [
+            (ObjectKind::P256SessionKey, format!("p256sk_{}_3", A)),
+            (ObjectKind::ScopedSessionKey, format!("ssk_{}_0", A)),
+            (ObjectKind::SessionRevocations, format!("sskrev_{}", A)),
+            (ObjectKind::AllowancePolicy, format!("allowpol_{}", A)),
+            (ObjectKind::SpenderAllowList, format!("spenders_{}", A)),
+            (ObjectKind::OfflineReplayLog, format!("offline_{}", A)),
+            (ObjectKind::RefreshFamily, format!("refresh_{}_1", A)),
+            (ObjectKind::OtpSecret, format!("otp_{}_github", A)),
+            (ObjectKind::DappNamespace, format!("dappns_{}_notes.org", A)),
+            (ObjectKind::DappBlob, format!("dapp_{}_notes.org_k_1", A)),
+            (ObjectKind::PathPolicy, format!("pathpol_{}", A)),
+            (ObjectKind::RemoteApprover, format!("approver_{}", A)),
+            (ObjectKind::AggregatorScheme, format!("aggsig_{}", A)),
+            (ObjectKind::SpendingPolicy, format!("spendpol_{}", A)),
+            (ObjectKind::MultiSig, format!("multisig_{}", A)),
+            (ObjectKind::SocialRecovery, format!("recovery_{}", A)),
+            (ObjectKind::NonceLedger, format!("nonce_{}", A)),
+        ] {
+            assert_eq!(owner_wallet(kind, &id), Some(a), "{}", id);
+        }
+        assert_eq!(ObjectKind::ALL.len(), 17);
+        for (kind, bad) in [
+            (ObjectKind::AllowancePolicy, format!("allowpol_{}_1", A)),
+            (ObjectKind::ScopedSessionKey, format!("ssk_{}", A)),
+            (ObjectKind::ScopedSessionKey, format!("ssk_{}_", A)),
+            (
+                ObjectKind::ScopedSessionKey,
+                format!("ssk_{}_0", A.to_uppercase()),
+            ),
+            (
+                ObjectKind::ScopedSessionKey,
+                format!("ssk_{}_0", A.replace('-', "")),
+            ),
+            (ObjectKind::ScopedSessionKey, "ssk_garbage_0".to_string()),
+            (ObjectKind::DappBlob, format!("dappns_{}_app", A)),
+        ] {
+            assert_eq!(owner_wallet(kind, &bad), None, "{}", bad);
+        }
+    }
+
+    #[test]
+    fn objects_of_missing_wallets_are_orphans() {
+        let a = Uuid::parse_str(A).unwrap();
+        let objects = vec![
+            (ObjectKind::OtpSecret, format!("otp_{}_mail", B)),
+            (ObjectKind::P256SessionKey, format!("p256sk_{}_0", A)),
+            (ObjectKind::DappBlob, format!("dapp_{}_app_k", B)),
+            (ObjectKind::AllowancePolicy, "allowpol_x".to_string()),
+            (ObjectKind::OtpSecret, format!("otp_{}_mail", B)),
+        ];
+        assert_eq!(referenced_wallets(&objects).len(), 2);
+        let stored: BTreeSet<Uuid> = std::iter::once(a).collect();
+        let orphans = find_orphans(&objects, &stored);
+        let named: Vec<(ObjectKind, OrphanReason)> =
+            orphans.iter().map(|o| (o.kind, o.reason)).collect();
+        assert_eq!(
+            named,
+            vec![
+                (ObjectKind::AllowancePolicy, OrphanReason::MalformedId),
+                (ObjectKind::OtpSecret, OrphanReason::WalletMissing),
+                (ObjectKind::DappBlob, OrphanReason::WalletMissing),
+            ]
+        );
+        assert_eq!(
+            count_by_kind(&orphans),
+            vec![
+                (ObjectKind::AllowancePolicy, 1),
+                (ObjectKind::OtpSecret, 1),
+                (ObjectKind::DappBlob, 1),
+            ]
+        );
+
+        // The digest is the set's: order-independent, changed by any member.
+        let mut reversed = objects.clone();
+        reversed.reverse();
+        let digest = orphan_set_digest(&orphans);
+        assert_eq!(orphan_set_digest(&find_orphans(&reversed, &stored)), digest);
+        assert_ne!(orphan_set_digest(&orphans[1..]), digest);
+        assert_ne!(orphan_set_digest(&[]), digest);
+        let all: BTreeSet<Uuid> = referenced_wallets(&objects);
+        assert_eq!(find_orphans(&objects, &all).len(), 1);
+    }
+}
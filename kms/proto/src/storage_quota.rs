@@ -0,0 +1,158 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! How full the TA's secure storage is, and the error when it is full.
+//!
+//! Wallets are capped by `TaLimits::max_wallets`; below that cap OP-TEE can
+//! still run out of room (`TEE_ERROR_STORAGE_NO_SPACE`). `TaStats` reports
+//! a [`StorageUsage`] so the CA can warn at [`WARN_PERCENT`] of the cap,
+//! before CreateWallet starts failing. When it does fail, the TA first
+//! deletes orphaned objects (`storage_gc`) and retries once, then answers
+//! [`StorageExhausted`], which travels as error text like
+//! `wire::UnsupportedVersion`.
+
+use serde::{Deserialize, Serialize};
+
+/// Share of the wallet cap at which the CA starts warning.
+pub const WARN_PERCENT: u32 = 80;
+
+/// `TEE_ERROR_STORAGE_NO_SPACE`.
+pub const TEE_ERROR_STORAGE_NO_SPACE: u32 = 0xFFFF_3041;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct StorageUsage {
+    pub wallets: u32,
+    /// `TaLimits::max_wallets` in force.
+    pub max_wallets: u32,
+    /// CreateWallet calls refused for lack of space since the TA was loaded.
+    pub exhausted: u32,
+}
+
+impl StorageUsage {
+    /// Wallets as a share of the cap, rounded down; 100 with no cap.
+    pub fn used_percent(&self) -> u32 {
+        if self.max_wallets == 0 {
+            return 100;
+        }
+        (self.wallets as u64 * 100 / self.max_wallets as u64).min(100) as u32
+    }
+
+    /// Past [`WARN_PERCENT`], or a create already refused.
+    pub fn needs_attention(&self) -> bool {
+        self.used_percent() >= WARN_PERCENT || self.exhausted > 0
+    }
+}
+
+/// Whether a secure-storage error means the store is out of space. The
+/// storage client reports OP-TEE errors as text, by kind name or code.
+pub fn is_no_space(message: &str) -> bool {
+    let m = message.to_ascii_lowercase();
+    m.contains("storagenospace")
+        || m.contains("storage_no_space")
+        || m.contains(&format!("{:#x}", TEE_ERROR_STORAGE_NO_SPACE))
+}
+
+/// No room for another wallet: the wallet cap is reached, or secure
+/// storage itself is full. See [`StorageExhausted::find`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StorageExhausted {
+    pub wallets: u32,
+    pub max_wallets: u32,
+    /// OP-TEE answered STORAGE_NO_SPACE below the cap.
+    pub no_space: bool,
+}
+
+const EXHAUSTED_TAG: &str = "StorageExhausted: ";
+const NO_SPACE: &str = "secure storage is full";
+const CAP_REACHED: &str = "wallet limit reached";
+
+impl std::fmt::Display for StorageExhausted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}{} ({}/{} wallets); delete orphaned objects or unused wallets",
+            EXHAUSTED_TAG,
+            if self.no_space { NO_SPACE } else { CAP_REACHED },
+            self.wallets,
+            self.max_wallets
+        )
+    }
+}
+
+impl std::error::Error for StorageExhausted {}
+
+impl StorageExhausted {
+    /// Recover the error from a TA error message that contains it.
+    pub fn find(message: &str) -> Option<Self> {
+        let text = &message[message.find(EXHAUSTED_TAG)? + EXHAUSTED_TAG.len()..];
+        let no_space = text.starts_with(NO_SPACE);
+        if !no_space && !text.starts_with(CAP_REACHED) {
+            return None;
+        }
+        let counts = &text[text.find('(')? + 1..];
+        let (wallets, rest) = counts.split_once('/')?;
+        let max_wallets = &rest[..rest.find(' ')?];
+        Some(StorageExhausted {
+            wallets: wallets.parse().ok()?,
+            max_wallets: max_wallets.parse().ok()?,
+            no_space,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_error_survives_the_error_text() {
+        for no_space in [false, true] {
+            let e = StorageExhausted {
+                wallets: 29_000,
+                max_wallets: 30_000,
+                no_space,
+            };
+            let text = format!("TA command failed: {} (error: BadParameters)", e);
+            assert_eq!(StorageExhausted::find(&text), Some(e));
+        }
+        assert_eq!(
+            StorageExhausted::find("StorageExhausted: something else"),
+            None
+        );
+        assert_eq!(StorageExhausted::find("wallet not found"), None);
+    }
+
+    #[test]
+    fn usage_warns_near_the_cap_and_after_a_refusal() {
+        let usage = |wallets, exhausted| StorageUsage {
+            wallets,
+            max_wallets: 1000,
+            exhausted,
+        };
+        assert_eq!(usage(799, 0).used_percent(), 79);
+        assert!(!usage(799, 0).needs_attention());
+        assert!(usage(800, 0).needs_attention());
+        assert!(usage(10, 1).needs_attention());
+        assert_eq!(usage(1200, 0).used_percent(), 100);
+        assert_eq!(StorageUsage::default().used_percent(), 100);
+
+        assert!(is_no_space("put failed: StorageNoSpace"));
+        assert!(is_no_space("TEE_ERROR_STORAGE_NO_SPACE"));
+        assert!(is_no_space("error code 0xffff3041"));
+        assert!(!is_no_space("ItemNotFound"));
+    }
+}
@@ -0,0 +1,206 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Sealed TA configuration — the limits and hardening switches a TA enforces.
+//!
+//! Signed like the deployment policy (`personal_sign` over
+//! [`TaConfig::message`]), but only the provisioning key compiled into the TA
+//! image may sign it. Limits move freely below [`TaLimits::CEILING`]; the two
+//! flags can only tighten what the build allows, never loosen it.
+
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+
+pub const CONFIG_FORMAT_VERSION: u8 = 1;
+
+const MAX_DEPLOYMENT_LABEL: usize = 64;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct TaLimits {
+    /// Wallets in secure storage before CreateWallet is refused.
+    pub max_wallets: u32,
+    /// Lifetime of a GetChallenge nonce.
+    pub challenge_ttl_secs: u32,
+    /// Longest agent JWT the TA issues or accepts.
+    pub agent_jwt_ttl_secs: u32,
+    /// Longest lifetime an owner may grant a scoped session key.
+    pub session_lifetime_secs: u32,
+    /// Longest spender allow-list per wallet.
+    pub max_allowed_spenders: u32,
+    /// How long a derived child key stays in the in-memory cache.
+    pub key_cache_ttl_secs: u32,
+    /// How long an armed allowance override waits for its transaction.
+    pub override_ttl_secs: u32,
+}
+
+impl TaLimits {
+    /// Enforced until a config is installed.
+    pub const DEFAULT: TaLimits = TaLimits {
+        max_wallets: 30_000,
+        challenge_ttl_secs: 300,
+        agent_jwt_ttl_secs: 24 * 3600,
+        session_lifetime_secs: 7 * 24 * 3600,
+        max_allowed_spenders: 32,
+        key_cache_ttl_secs: 300,
+        override_ttl_secs: 300,
+    };
+
+    /// Highest value a signed config may set. `max_wallets` is bounded by
+    /// REE-FS room (~300 000 wallets measured on FRDM-IMX93); the agent JWT
+    /// lifetime cannot be raised at all.
+    pub const CEILING: TaLimits = TaLimits {
+        max_wallets: 300_000,
+        challenge_ttl_secs: 900,
+        agent_jwt_ttl_secs: 24 * 3600,
+        session_lifetime_secs: 30 * 24 * 3600,
+        max_allowed_spenders: 128,
+        key_cache_ttl_secs: 3600,
+        override_ttl_secs: 900,
+    };
+
+    fn fields(&self) -> [(&'static str, u32); 7] {
+        [
+            ("max-wallets", self.max_wallets),
+            ("challenge-ttl", self.challenge_ttl_secs),
+            ("agent-jwt-ttl", self.agent_jwt_ttl_secs),
+            ("session-lifetime", self.session_lifetime_secs),
+            ("allowed-spenders", self.max_allowed_spenders),
+            ("key-cache-ttl", self.key_cache_ttl_secs),
+            ("override-ttl", self.override_ttl_secs),
+        ]
+    }
+
+    pub fn validate(&self) -> Result<(), &'static str> {
+        for ((_, value), (_, ceiling)) in self.fields().iter().zip(Self::CEILING.fields().iter()) {
+            if *value == 0 || value > ceiling {
+                return Err("TA limit is zero or above its ceiling");
+            }
+        }
+        // A challenge must survive one WebAuthn prompt.
+        if self.challenge_ttl_secs < 30 {
+            return Err("challenge-ttl must be at least 30 seconds");
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct TaConfig {
+    pub format: u8,
+    /// Operator label, shown in the signed text.
+    pub deployment: String,
+    /// Strictly increasing across replacements.
+    pub sequence: u64,
+    pub limits: TaLimits,
+    /// Every assertion must carry clientDataJSON bound to a TA nonce, as in a
+    /// `strict-challenge` build.
+    pub require_ta_challenge: bool,
+    /// SignHash without a declared signing context is refused, as in a
+    /// `strict-signing-context` build.
+    pub require_signing_context: bool,
+}
+
+impl TaConfig {
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if self.format != CONFIG_FORMAT_VERSION {
+            return Err("unsupported TA config format");
+        }
+        if self.deployment.is_empty()
+            || self.deployment.len() > MAX_DEPLOYMENT_LABEL
+            || self.deployment.chars().any(|c| c.is_control())
+        {
+            return Err("deployment label must be 1-64 printable characters");
+        }
+        self.limits.validate()
+    }
+
+    /// The text the provisioning key signs.
+    pub fn message(&self) -> String {
+        let yes_no = |b: bool| if b { "yes" } else { "no" };
+        let mut text = format!(
+            "AirAccount TA config v{}\ndeployment: {}\nsequence: {}",
+            self.format, self.deployment, self.sequence
+        );
+        for (name, value) in self.limits.fields().iter() {
+            text.push_str(&format!("\n{}: {}", name, value));
+        }
+        text.push_str(&format!(
+            "\nrequire-ta-challenge: {}\nrequire-signing-context: {}",
+            yes_no(self.require_ta_challenge),
+            yes_no(self.require_signing_context)
+        ));
+        text
+    }
+
+    /// EIP-191 digest of [`Self::message`] — what the signature recovers over.
+    pub fn digest(&self) -> [u8; 32] {
+        let message = self.message();
+        let mut h = Keccak256::new();
+        h.update(format!("\x19Ethereum Signed Message:\n{}", message.len()).as_bytes());
+        h.update(message.as_bytes());
+        h.finalize().into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> TaConfig {
+        TaConfig {
+            format: CONFIG_FORMAT_VERSION,
+            deployment: "rack-3".into(),
+            sequence: 1,
+            limits: TaLimits::DEFAULT,
+            require_ta_challenge: true,
+            require_signing_context: false,
+        }
+    }
+
+    #[test]
+    fn limits_stay_under_the_ceiling() {
+        assert!(config().validate().is_ok());
+        assert!(TaLimits::CEILING.validate().is_ok());
+        let mut c = config();
+        c.limits.max_wallets = 100;
+        assert!(c.validate().is_ok());
+        c.limits.agent_jwt_ttl_secs += 1;
+        assert!(c.validate().is_err());
+        let mut c = config();
+        c.limits.key_cache_ttl_secs = 0;
+        assert!(c.validate().is_err());
+        let mut c = config();
+        c.limits.challenge_ttl_secs = 10;
+        assert!(c.validate().is_err());
+        let mut c = config();
+        c.deployment = "a\nsequence: 9".into();
+        assert!(c.validate().is_err());
+    }
+
+    #[test]
+    fn signed_text_lists_every_setting() {
+        let text = config().message();
+        assert!(text.starts_with("AirAccount TA config v1\ndeployment: rack-3\nsequence: 1\n"));
+        assert!(text.contains("\nmax-wallets: 30000\n"));
+        assert!(text.ends_with("require-ta-challenge: yes\nrequire-signing-context: no"));
+        let mut other = config();
+        other.limits.override_ttl_secs = 60;
+        assert_ne!(config().digest(), other.digest());
+    }
+}
@@ -0,0 +1,338 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Erase-on-tamper — signed tamper orders and the wipe certificate.
+//!
+//! A [`TamperOrder`] sets the failed-authentication limit, wipes the device
+//! (panic wipe) or lifts the lock a wipe leaves behind. It is signed like the
+//! deployment policy — `personal_sign` over [`TamperOrder::message`] — and
+//! must recover to the pinned policy signer, name the installed deployment
+//! and carry a sequence above every order the TA has accepted before.
+//!
+//! A wipe erases every wallet (and the session keys that hang off them) the
+//! same way `RemoveWallet` does, then the attestation PTA signs one
+//! [`WipeCertificate`] for the whole set. Until an `Unlock` order arrives the
+//! TA refuses everything but the commands in [`LOCKED_ALLOWED`].
+
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+use uuid::Uuid;
+
+use crate::{Command, GetAttestationOutput};
+
+pub const TAMPER_ORDER_FORMAT: u8 = 1;
+pub const WIPE_CERT_VERSION: u8 = 1;
+
+/// Bounds for the failed-authentication limit. Below 3 a mistyped PIN or a
+/// cancelled prompt is enough to destroy a device.
+pub const MIN_FAILED_AUTH_LIMIT: u32 = 3;
+pub const MAX_FAILED_AUTH_LIMIT: u32 = 1000;
+
+/// What a locked TA still answers: enough to inspect it and unlock it.
+pub const LOCKED_ALLOWED: [Command; 7] = [
+    Command::GetProtocolVersion,
+    Command::TaStats,
+    Command::CrashDumps,
+    Command::GetCapabilities,
+    Command::InstallDeploymentPolicy,
+    Command::TamperOrder,
+    Command::GetTamperStatus,
+];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TamperAction {
+    /// Wipe after this many consecutive failed passkey authentications;
+    /// None turns the automatic wipe off.
+    SetFailedAuthLimit(Option<u32>),
+    /// Wipe now.
+    PanicWipe,
+    /// Leave the locked state after a wipe (re-provisioning).
+    Unlock,
+}
+
+impl TamperAction {
+    fn text(&self) -> String {
+        match self {
+            TamperAction::SetFailedAuthLimit(Some(n)) => format!("failed-auth-limit {}", n),
+            TamperAction::SetFailedAuthLimit(None) => "failed-auth-limit off".to_string(),
+            TamperAction::PanicWipe => "panic-wipe".to_string(),
+            TamperAction::Unlock => "unlock".to_string(),
+        }
+    }
+}
+
+/// Parses the `action:` text of the signed message, e.g. `failed-auth-limit 5`.
+impl std::str::FromStr for TamperAction {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "panic-wipe" => Ok(TamperAction::PanicWipe),
+            "unlock" => Ok(TamperAction::Unlock),
+            "failed-auth-limit off" => Ok(TamperAction::SetFailedAuthLimit(None)),
+            other => other
+                .strip_prefix("failed-auth-limit ")
+                .and_then(|n| n.parse().ok())
+                .map(|n| TamperAction::SetFailedAuthLimit(Some(n)))
+                .ok_or("action must be panic-wipe, unlock or failed-auth-limit <n|off>"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TamperOrder {
+    pub format: u8,
+    /// Must equal the installed deployment policy's label.
+    pub deployment: String,
+    /// keccak256(modulus ‖ exponent) of one device's attestation key; None
+    /// addresses every device of the deployment.
+    pub device: Option<[u8; 32]>,
+    /// Strictly increasing across all orders a TA accepts.
+    pub sequence: u64,
+    pub action: TamperAction,
+}
+
+impl TamperOrder {
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if self.format != TAMPER_ORDER_FORMAT {
+            return Err("unsupported tamper order format");
+        }
+        if self.deployment.is_empty() || self.deployment.chars().any(|c| c.is_control()) {
+            return Err("tamper order must name the deployment");
+        }
+        if let TamperAction::SetFailedAuthLimit(Some(n)) = self.action {
+            if !(MIN_FAILED_AUTH_LIMIT..=MAX_FAILED_AUTH_LIMIT).contains(&n) {
+                return Err("failed-auth limit must be between 3 and 1000");
+            }
+        }
+        Ok(())
+    }
+
+    /// The text the operator signs.
+    pub fn message(&self) -> String {
+        let device = match &self.device {
+            Some(fp) => format!("0x{}", to_hex(fp)),
+            None => "any".to_string(),
+        };
+        format!(
+            "AirAccount tamper order v{}\ndeployment: {}\ndevice: {}\nsequence: {}\naction: {}",
+            self.format,
+            self.deployment,
+            device,
+            self.sequence,
+            self.action.text()
+        )
+    }
+
+    /// EIP-191 digest of [`Self::message`] — what the signature recovers over.
+    pub fn digest(&self) -> [u8; 32] {
+        let message = self.message();
+        let mut h = Keccak256::new();
+        h.update(format!("\x19Ethereum Signed Message:\n{}", message.len()).as_bytes());
+        h.update(message.as_bytes());
+        h.finalize().into()
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WipeReason {
+    PanicWipe,
+    /// The limit that was reached.
+    FailedAuth(u32),
+}
+
+impl WipeReason {
+    fn text(&self) -> String {
+        match self {
+            WipeReason::PanicWipe => "panic-wipe".to_string(),
+            WipeReason::FailedAuth(n) => format!("failed-auth {}", n),
+        }
+    }
+}
+
+/// keccak256 over the erased wallet ids in ascending order, so a verifier
+/// holding the device's wallet list can check the certificate covers it.
+pub fn wallet_set_digest(ids: &[Uuid]) -> [u8; 32] {
+    let mut sorted = ids.to_vec();
+    sorted.sort_unstable();
+    let mut h = Keccak256::new();
+    for id in &sorted {
+        h.update(id.as_bytes());
+    }
+    h.finalize().into()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WipeCertificate {
+    pub version: u8,
+    pub reason: WipeReason,
+    pub wallets: u32,
+    /// [`wallet_set_digest`] of the erased wallets.
+    pub wallet_set: [u8; 32],
+    /// REE wall-clock seconds, as in a deletion certificate.
+    pub wiped_at: u64,
+    pub rollback_counter: Option<u64>,
+}
+
+impl WipeCertificate {
+    /// Canonical text of the certificate.
+    pub fn message(&self) -> String {
+        let counter = match self.rollback_counter {
+            Some(c) => c.to_string(),
+            None => "none".to_string(),
+        };
+        format!(
+            "AirAccount wipe certificate v{}\nreason: {}\nwallets: {}\nwallet_set: 0x{}\nwiped_at: {}\nrollback_counter: {}",
+            self.version,
+            self.reason.text(),
+            self.wallets,
+            to_hex(&self.wallet_set),
+            self.wiped_at,
+            counter
+        )
+    }
+
+    /// keccak256 of [`Self::message`] — the attestation nonce.
+    pub fn digest(&self) -> [u8; 32] {
+        Keccak256::digest(self.message().as_bytes()).into()
+    }
+}
+
+/// A wipe certificate and the device signature over it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WipeProof {
+    pub certificate: WipeCertificate,
+    pub attestation: Option<GetAttestationOutput>,
+}
+
+impl WipeProof {
+    pub fn check_binding(&self) -> Result<(), &'static str> {
+        match &self.attestation {
+            Some(a) if a.nonce[..] != self.certificate.digest()[..] => {
+                Err("attestation nonce is not the certificate digest")
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TamperLock {
+    pub reason: WipeReason,
+    pub since: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order(action: TamperAction) -> TamperOrder {
+        TamperOrder {
+            format: TAMPER_ORDER_FORMAT,
+            deployment: "rack-3".into(),
+            device: None,
+            sequence: 7,
+            action,
+        }
+    }
+
+    #[test]
+    fn validate_rules() {
+        assert!(order(TamperAction::PanicWipe).validate().is_ok());
+        assert!(order(TamperAction::SetFailedAuthLimit(None))
+            .validate()
+            .is_ok());
+        assert!(order(TamperAction::SetFailedAuthLimit(Some(5)))
+            .validate()
+            .is_ok());
+        assert!(order(TamperAction::SetFailedAuthLimit(Some(2)))
+            .validate()
+            .is_err());
+        assert!(order(TamperAction::SetFailedAuthLimit(Some(1001)))
+            .validate()
+            .is_err());
+        let mut o = order(TamperAction::Unlock);
+        o.deployment = "rack-3\naction: unlock".into();
+        assert!(o.validate().is_err());
+    }
+
+    #[test]
+    fn signed_text_is_stable() {
+        assert_eq!(
+            order(TamperAction::PanicWipe).message(),
+            "AirAccount tamper order v1\ndeployment: rack-3\ndevice: any\nsequence: 7\naction: panic-wipe"
+        );
+        let mut o = order(TamperAction::SetFailedAuthLimit(Some(5)));
+        o.device = Some([0xab; 32]);
+        let text = o.message();
+        assert!(text.contains(&format!("device: 0x{}", "ab".repeat(32))));
+        assert!(text.ends_with("action: failed-auth-limit 5"));
+        assert_ne!(
+            order(TamperAction::PanicWipe).digest(),
+            order(TamperAction::Unlock).digest()
+        );
+        for action in [
+            TamperAction::PanicWipe,
+            TamperAction::Unlock,
+            TamperAction::SetFailedAuthLimit(None),
+            TamperAction::SetFailedAuthLimit(Some(5)),
+        ] {
+            assert_eq!(action.text().parse::<TamperAction>(), Ok(action));
+        }
+        assert!("wipe".parse::<TamperAction>().is_err());
+    }
+
+    #[test]
+    fn wipe_certificate_binds_the_wallet_set() {
+        let a = Uuid::from_bytes([0x11; 16]);
+        let b = Uuid::from_bytes([0x22; 16]);
+        assert_eq!(wallet_set_digest(&[a, b]), wallet_set_digest(&[b, a]));
+        assert_ne!(wallet_set_digest(&[a]), wallet_set_digest(&[a, b]));
+
+        let certificate = WipeCertificate {
+            version: WIPE_CERT_VERSION,
+            reason: WipeReason::FailedAuth(5),
+            wallets: 2,
+            wallet_set: wallet_set_digest(&[a, b]),
+            wiped_at: 1_700_000_000,
+            rollback_counter: None,
+        };
+        assert!(certificate
+            .message()
+            .starts_with("AirAccount wipe certificate v1\nreason: failed-auth 5\nwallets: 2\n"));
+        let mut proof = WipeProof {
+            certificate: certificate.clone(),
+            attestation: Some(GetAttestationOutput {
+                nonce: certificate.digest().to_vec(),
+                ta_uuid: vec![0; 16],
+                ta_measurement: vec![0; 32],
+                signature: vec![1; 256],
+                attest_pubkey_exp: vec![1, 0, 1],
+                attest_pubkey_mod: vec![0xc5; 256],
+                sig_alg: 0x7041_4930,
+                ree_time_secs: 1_700_000_000,
+            }),
+        };
+        assert!(proof.check_binding().is_ok());
+        proof.certificate.wallets = 1;
+        assert!(proof.check_binding().is_err());
+    }
+}
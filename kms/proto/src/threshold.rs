@@ -0,0 +1,289 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! 2-of-2 threshold ECDSA on secp256k1: the TA holds one additive share of a
+//! wallet's threshold key, a remote co-signer the other, and neither can sign
+//! alone.
+//!
+//! The protocol is Lindell's two-party ECDSA (CRYPTO 2017) with the TA as P1:
+//! it owns the Paillier key, the co-signer holds `Enc(x_ta)` and answers a
+//! signing request with a ciphertext only the TA can finish. Nonces are
+//! exchanged ahead of time (the presignature pool), so signing is one round
+//! trip. Either party uses a presignature once and forgets it before the
+//! signature exists.
+//!
+//! The messages, the states each party keeps and the passkey commitments are
+//! always compiled; the arithmetic is behind the `threshold-ecdsa` feature.
+//! The co-signer side lives here too, so the CA's tests and a co-signer
+//! service share one implementation.
+//!
+//! **Not covered**: the Paillier well-formedness and range proofs of the
+//! paper. They protect the co-signer's share from a malicious TA, which this
+//! mode does not claim to (see `docs/threshold-ecdsa-2of2-design.md`).
+
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+use uuid::Uuid;
+
+#[cfg(feature = "threshold-ecdsa")]
+mod paillier;
+#[cfg(feature = "threshold-ecdsa")]
+mod party;
+#[cfg(feature = "threshold-ecdsa")]
+pub use party::*;
+
+/// Paillier modulus the TA generates.
+pub const PAILLIER_BITS: usize = 2048;
+/// Smallest modulus the co-signer accepts.
+pub const MIN_PAILLIER_BITS: u64 = 2048;
+/// Presignatures the TA keeps per key, finished or waiting for a reply.
+pub const MAX_PRESIGNATURES: usize = 32;
+/// Presignatures one round may add.
+pub const MAX_PRESIGN_BATCH: usize = 8;
+
+/// A party's public point with a Schnorr proof that it knows the secret.
+/// Points are SEC1 compressed (33 bytes).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PartyKey {
+    pub point: Vec<u8>,
+    pub proof_point: Vec<u8>,
+    pub proof_response: [u8; 32],
+}
+
+/// TA → co-signer: the TA's key share, committed.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct KeygenCommit {
+    pub commitment: [u8; 32],
+}
+
+/// TA → co-signer: the committed share opened, and `Enc(x_ta)` under the
+/// TA's Paillier key.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct KeygenReveal {
+    pub ta_key: PartyKey,
+    pub salt: [u8; 32],
+    pub paillier_n: Vec<u8>,
+    pub encrypted_share: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PresignCommit {
+    pub id: u64,
+    pub commitment: [u8; 32],
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PresignReply {
+    pub id: u64,
+    pub nonce: PartyKey,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PresignReveal {
+    pub id: u64,
+    pub nonce: PartyKey,
+    pub salt: [u8; 32],
+}
+
+/// Co-signer → TA: its half of the signature on one presignature.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SignReply {
+    pub presignature_id: u64,
+    /// Paillier ciphertext the TA decrypts to `k_cs⁻¹(m + r·x)` blinded by a
+    /// multiple of the group order.
+    pub ciphertext: Vec<u8>,
+}
+
+/// TA state between `keygen_commit` and `keygen_finish`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TaKeygenState {
+    pub secret: [u8; 32],
+    pub key: PartyKey,
+    pub salt: [u8; 32],
+}
+
+/// The TA's half of a threshold key.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TaShare {
+    pub secret: [u8; 32],
+    /// Joint public key, compressed.
+    pub public_key: Vec<u8>,
+    pub paillier_p: Vec<u8>,
+    pub paillier_q: Vec<u8>,
+}
+
+/// TA state between `presign_commit` and `presign_finish`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TaPresignState {
+    pub id: u64,
+    pub nonce: [u8; 32],
+    pub key: PartyKey,
+    pub salt: [u8; 32],
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TaPresignature {
+    pub id: u64,
+    pub nonce: [u8; 32],
+    /// `R = k_ta·k_cs·G`, compressed.
+    pub r_point: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CoSignerKeygenState {
+    pub secret: [u8; 32],
+    pub commitment: [u8; 32],
+}
+
+/// The co-signer's half of a threshold key.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CoSignerShare {
+    pub secret: [u8; 32],
+    pub public_key: Vec<u8>,
+    /// The TA's share point; the co-signer seals its share to it on recombine.
+    pub ta_point: Vec<u8>,
+    pub paillier_n: Vec<u8>,
+    pub encrypted_share: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CoSignerPresignState {
+    pub id: u64,
+    pub nonce: [u8; 32],
+    pub commitment: [u8; 32],
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CoSignerPresignature {
+    pub id: u64,
+    pub nonce: [u8; 32],
+    pub r_point: Vec<u8>,
+}
+
+/// The whole private key after the fallback ceremony; the TA signs with it
+/// alone from then on.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RecombinedKey {
+    pub secret: [u8; 32],
+    pub public_key: Vec<u8>,
+}
+
+/// The primes of the TA's Paillier key, big-endian.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PaillierPrimes {
+    pub p: Vec<u8>,
+    pub q: Vec<u8>,
+}
+
+macro_rules! wipe_on_drop {
+    ($($ty:ty => $($field:ident),+;)+) => {
+        $(impl Drop for $ty {
+            fn drop(&mut self) {
+                $(wipe(&mut self.$field);)+
+            }
+        })+
+    };
+}
+
+wipe_on_drop! {
+    TaKeygenState => secret;
+    TaShare => secret, paillier_p, paillier_q;
+    TaPresignState => nonce;
+    TaPresignature => nonce;
+    CoSignerKeygenState => secret;
+    CoSignerShare => secret;
+    CoSignerPresignState => nonce;
+    CoSignerPresignature => nonce;
+    RecombinedKey => secret;
+    PaillierPrimes => p, q;
+}
+
+/// Zero secret bytes in a way the optimiser keeps, before they are freed.
+fn wipe(bytes: &mut [u8]) {
+    for b in bytes.iter_mut() {
+        // SAFETY: `b` is a valid, aligned &mut u8.
+        unsafe { core::ptr::write_volatile(b, 0) };
+    }
+    core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+}
+
+/// Passkey commitment for creating `wallet_id`'s threshold key.
+pub fn keygen_commitment(wallet_id: &Uuid) -> [u8; 32] {
+    let mut h = Keccak256::new();
+    h.update(b"AA-THRESHOLD-KEYGEN-v1");
+    h.update(wallet_id.as_bytes());
+    h.finalize().into()
+}
+
+/// Passkey commitment for signing `digest` with `wallet_id`'s threshold key.
+pub fn sign_commitment(wallet_id: &Uuid, digest: &[u8; 32]) -> [u8; 32] {
+    let mut h = Keccak256::new();
+    h.update(b"AA-THRESHOLD-SIGN-v1");
+    h.update(wallet_id.as_bytes());
+    h.update(digest);
+    h.finalize().into()
+}
+
+/// Passkey commitment for taking in the co-signer's share, and for sealing
+/// the whole key to `export_to` if given.
+pub fn recombine_commitment(
+    wallet_id: &Uuid,
+    sealed_share: &[u8],
+    export_to: Option<&[u8]>,
+) -> [u8; 32] {
+    let mut h = Keccak256::new();
+    h.update(b"AA-THRESHOLD-RECOMBINE-v1");
+    h.update(wallet_id.as_bytes());
+    h.update(Keccak256::digest(sealed_share));
+    match export_to {
+        Some(key) => {
+            h.update([1u8]);
+            h.update((key.len() as u32).to_be_bytes());
+            h.update(key);
+        }
+        None => h.update([0u8]),
+    }
+    h.finalize().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commitments_bind_their_inputs() {
+        let w = Uuid::from_bytes([1; 16]);
+        let other = Uuid::from_bytes([2; 16]);
+        assert_ne!(keygen_commitment(&w), keygen_commitment(&other));
+        assert_ne!(sign_commitment(&w, &[0; 32]), sign_commitment(&w, &[1; 32]));
+        assert_ne!(
+            recombine_commitment(&w, b"share", None),
+            recombine_commitment(&w, b"share", Some(&[]))
+        );
+        assert_ne!(
+            recombine_commitment(&w, b"share", None),
+            recombine_commitment(&w, b"other", None)
+        );
+    }
+
+    #[test]
+    fn wipe_zeroes() {
+        let mut secret = [7u8; 32];
+        wipe(&mut secret);
+        assert_eq!(secret, [0; 32]);
+    }
+}
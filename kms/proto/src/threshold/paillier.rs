@@ -0,0 +1,246 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Paillier encryption with `g = n + 1`, and the prime generation it needs.
+//! Only what two-party ECDSA uses: encrypt, decrypt, add, multiply by a
+//! plaintext.
+
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+
+use crate::provider::Rng;
+
+/// Miller-Rabin rounds per candidate. For a random 1024-bit candidate the
+/// Damgård-Landrock-Pomerance bound puts the chance that a composite passes
+/// 8 rounds below 2^-155 (HAC table 4.4 needs 3 rounds for 2^-80). The
+/// candidates are random, so the adversarial 4^-8 bound does not apply.
+const MR_ROUNDS: usize = 8;
+
+pub struct PublicKey {
+    pub n: BigUint,
+    nn: BigUint,
+}
+
+pub struct SecretKey {
+    public: PublicKey,
+    /// φ(n) = (p-1)(q-1); `g = n + 1` makes it a valid λ.
+    phi: BigUint,
+    /// φ(n)⁻¹ mod n.
+    mu: BigUint,
+}
+
+impl PublicKey {
+    pub fn new(n: BigUint) -> Self {
+        let nn = &n * &n;
+        PublicKey { n, nn }
+    }
+
+    /// `(1 + m·n) · rⁿ mod n²`, with `m < n`.
+    pub fn encrypt(&self, rng: &mut dyn Rng, m: &BigUint) -> BigUint {
+        let r = random_unit(rng, &self.n);
+        let gm = (BigUint::one() + m * &self.n) % &self.nn;
+        gm * r.modpow(&self.n, &self.nn) % &self.nn
+    }
+
+    /// `Enc(a) ⊕ Enc(b) = Enc(a + b)`.
+    pub fn add(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        a * b % &self.nn
+    }
+
+    /// `k ⊗ Enc(a) = Enc(k·a)`.
+    pub fn mul(&self, c: &BigUint, k: &BigUint) -> BigUint {
+        c.modpow(k, &self.nn)
+    }
+
+    /// A ciphertext must be a unit below n².
+    pub fn is_ciphertext(&self, c: &BigUint) -> bool {
+        !c.is_zero() && c < &self.nn
+    }
+}
+
+impl SecretKey {
+    pub fn from_primes(p: BigUint, q: BigUint) -> Result<Self, &'static str> {
+        if p == q {
+            return Err("Paillier primes must differ");
+        }
+        let n = &p * &q;
+        let phi = (&p - 1u32) * (&q - 1u32);
+        let mu = phi
+            .modinv(&n)
+            .ok_or("Paillier primes do not give gcd(n, φ) = 1")?;
+        Ok(SecretKey {
+            public: PublicKey::new(n),
+            phi,
+            mu,
+        })
+    }
+
+    pub fn generate(rng: &mut dyn Rng, bits: usize) -> (Self, BigUint, BigUint) {
+        loop {
+            let p = generate_prime(rng, bits / 2);
+            let q = generate_prime(rng, bits / 2);
+            if let Ok(key) = SecretKey::from_primes(p.clone(), q.clone()) {
+                if key.public.n.bits() == bits as u64 {
+                    return (key, p, q);
+                }
+            }
+        }
+    }
+
+    pub fn public(&self) -> &PublicKey {
+        &self.public
+    }
+
+    /// `L(c^φ mod n²) · μ mod n`, with `L(u) = (u - 1) / n`.
+    pub fn decrypt(&self, c: &BigUint) -> BigUint {
+        let n = &self.public.n;
+        let u = c.modpow(&self.phi, &self.public.nn);
+        let l = (u - 1u32) / n;
+        l * &self.mu % n
+    }
+}
+
+/// Uniform in [1, bound) and coprime to it; a non-unit below an RSA modulus
+/// would factor it, so the check never fails in practice.
+fn random_unit(rng: &mut dyn Rng, bound: &BigUint) -> BigUint {
+    loop {
+        let r = random_below(rng, bound);
+        if !r.is_zero() && num_integer::Integer::gcd(&r, bound).is_one() {
+            return r;
+        }
+    }
+}
+
+/// Uniform in [0, bound), by rejection.
+pub fn random_below(rng: &mut dyn Rng, bound: &BigUint) -> BigUint {
+    let bits = bound.bits() as usize;
+    let mut buf = vec![0u8; bits.div_ceil(8)];
+    loop {
+        rng.fill(&mut buf);
+        let excess = buf.len() * 8 - bits;
+        buf[0] &= 0xff >> excess;
+        let r = BigUint::from_bytes_be(&buf);
+        if &r < bound {
+            return r;
+        }
+    }
+}
+
+/// A random prime of exactly `bits` bits with the top two bits set, so two
+/// of them multiply to a full `2·bits` modulus.
+pub fn generate_prime(rng: &mut dyn Rng, bits: usize) -> BigUint {
+    let small = small_primes();
+    let mut buf = vec![0u8; bits.div_ceil(8)];
+    loop {
+        rng.fill(&mut buf);
+        let excess = buf.len() * 8 - bits;
+        buf[0] &= 0xff >> excess;
+        buf[0] |= 0xc0 >> excess;
+        *buf.last_mut().unwrap() |= 1;
+        let candidate = BigUint::from_bytes_be(&buf);
+        if small
+            .iter()
+            .any(|&p| (&candidate % p).is_zero() && candidate != BigUint::from(p))
+        {
+            continue;
+        }
+        if is_probable_prime(rng, &candidate) {
+            return candidate;
+        }
+    }
+}
+
+fn is_probable_prime(rng: &mut dyn Rng, n: &BigUint) -> bool {
+    let one = BigUint::one();
+    let two = BigUint::from(2u32);
+    if n < &two {
+        return false;
+    }
+    if n == &two || n == &BigUint::from(3u32) {
+        return true;
+    }
+    let n_minus_1 = n - &one;
+    let s = n_minus_1.trailing_zeros().unwrap_or(0);
+    let d = &n_minus_1 >> s;
+    let base_bound = n - 3u32;
+    'witness: for _ in 0..MR_ROUNDS {
+        let a = random_below(rng, &base_bound) + &two;
+        let mut x = a.modpow(&d, n);
+        if x == one || x == n_minus_1 {
+            continue;
+        }
+        for _ in 1..s {
+            x = x.modpow(&two, n);
+            if x == n_minus_1 {
+                continue 'witness;
+            }
+        }
+        return false;
+    }
+    true
+}
+
+/// Primes below 2000, for trial division before Miller-Rabin.
+fn small_primes() -> Vec<u32> {
+    let mut sieve = vec![true; 2000];
+    let mut primes = Vec::new();
+    for i in 2..sieve.len() {
+        if sieve[i] {
+            primes.push(i as u32);
+            for j in (i * i..sieve.len()).step_by(i) {
+                sieve[j] = false;
+            }
+        }
+    }
+    primes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::SeededRng;
+
+    #[test]
+    fn primality() {
+        let mut rng = SeededRng::new([1; 32]);
+        for p in [2u32, 3, 5, 7919, 2_147_483_647] {
+            assert!(is_probable_prime(&mut rng, &BigUint::from(p)), "{}", p);
+        }
+        // 561 is a Carmichael number; 2^32 + 1 = 641 · 6700417.
+        for c in [1u64, 4, 561, 4_294_967_297] {
+            assert!(!is_probable_prime(&mut rng, &BigUint::from(c)), "{}", c);
+        }
+        let p = generate_prime(&mut rng, 128);
+        assert_eq!(p.bits(), 128);
+        assert!(p.bit(126));
+    }
+
+    #[test]
+    fn homomorphic_round_trip() {
+        let mut rng = SeededRng::new([2; 32]);
+        let (key, _, _) = SecretKey::generate(&mut rng, 512);
+        let pk = key.public();
+        let a = BigUint::from(123_456_789u64);
+        let b = BigUint::from(987_654_321u64);
+        let ca = pk.encrypt(&mut rng, &a);
+        let cb = pk.encrypt(&mut rng, &b);
+        assert_ne!(ca, pk.encrypt(&mut rng, &a));
+        assert_eq!(key.decrypt(&ca), a);
+        assert_eq!(key.decrypt(&pk.add(&ca, &cb)), &a + &b);
+        assert_eq!(key.decrypt(&pk.mul(&ca, &BigUint::from(3u32))), &a * 3u32);
+    }
+}
@@ -0,0 +1,716 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Both parties' steps. Each TA step is followed by the co-signer step that
+//! takes its message:
+//!
+//! ```text
+//! keygen   keygen_commit → cosigner_keygen → keygen_finish → cosigner_keygen_finish
+//! presign  presign_commit → cosigner_presign → presign_finish → cosigner_presign_finish
+//! sign     cosigner_sign → sign_finish
+//! fallback cosigner_seal_share → recombine, then sign_with_secret
+//! ```
+//!
+//! Every proof and commitment is bound to the wallet and, for nonces, to the
+//! presignature id, so a message cannot be replayed into another key or slot.
+
+use k256::ecdsa::{RecoveryId, Signature, SigningKey, VerifyingKey};
+use k256::elliptic_curve::group::{Group, GroupEncoding};
+use k256::elliptic_curve::ops::Reduce;
+use k256::elliptic_curve::point::AffineCoordinates;
+use k256::elliptic_curve::sec1::{FromEncodedPoint, ToEncodedPoint};
+use k256::elliptic_curve::PrimeField;
+use k256::{AffinePoint, EncodedPoint, FieldBytes, ProjectivePoint, Scalar, U256};
+use num_bigint::BigUint;
+use sha3::{Digest, Keccak256};
+use uuid::Uuid;
+
+use super::paillier::{self, random_below};
+use super::*;
+use crate::ecies::{Envelope, EPHEMERAL_KEY_LEN, NONCE_LEN};
+use crate::provider::Rng;
+
+type Result<T> = core::result::Result<T, &'static str>;
+
+const KEYGEN: &[u8] = b"AA-2PECDSA-KEYGEN-v1";
+const PRESIGN: &[u8] = b"AA-2PECDSA-PRESIGN-v1";
+const TA: u8 = 1;
+const COSIGNER: u8 = 2;
+
+/// secp256k1 group order.
+const ORDER: &str = "fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141";
+
+// ── Key generation ──
+
+pub fn keygen_commit(rng: &mut dyn Rng, wallet_id: &Uuid) -> (TaKeygenState, KeygenCommit) {
+    let ctx = context(KEYGEN, TA, wallet_id, 0);
+    let secret = random_scalar(rng);
+    let key = prove(rng, &secret, &ctx);
+    let salt = random_bytes(rng);
+    let commitment = commit(&ctx, &key, &salt);
+    let state = TaKeygenState {
+        secret: secret.to_bytes().into(),
+        key,
+        salt,
+    };
+    (state, KeygenCommit { commitment })
+}
+
+pub fn cosigner_keygen(
+    rng: &mut dyn Rng,
+    wallet_id: &Uuid,
+    commit: &KeygenCommit,
+) -> (CoSignerKeygenState, PartyKey) {
+    let secret = random_scalar(rng);
+    let key = prove(rng, &secret, &context(KEYGEN, COSIGNER, wallet_id, 0));
+    let state = CoSignerKeygenState {
+        secret: secret.to_bytes().into(),
+        commitment: commit.commitment,
+    };
+    (state, key)
+}
+
+/// Two fresh [`PAILLIER_BITS`]-bit Paillier primes. The slow part of key
+/// generation, kept apart so it can run ahead of the ceremony.
+pub fn paillier_primes(rng: &mut dyn Rng) -> PaillierPrimes {
+    let (_, p, q) = paillier::SecretKey::generate(rng, PAILLIER_BITS);
+    PaillierPrimes {
+        p: p.to_bytes_be(),
+        q: q.to_bytes_be(),
+    }
+}
+
+pub fn keygen_finish(
+    rng: &mut dyn Rng,
+    wallet_id: &Uuid,
+    state: &TaKeygenState,
+    reply: &PartyKey,
+    primes: &PaillierPrimes,
+) -> Result<(TaShare, KeygenReveal)> {
+    let cosigner_point = verify(reply, &context(KEYGEN, COSIGNER, wallet_id, 0))?;
+    let secret = parse_scalar(&state.secret)?;
+    let public_key = cosigner_point + ProjectivePoint::GENERATOR * secret;
+    if bool::from(public_key.is_identity()) {
+        return Err("threshold public key is the identity");
+    }
+    let key = paillier::SecretKey::from_primes(
+        BigUint::from_bytes_be(&primes.p),
+        BigUint::from_bytes_be(&primes.q),
+    )?;
+    let encrypted_share = key.public().encrypt(rng, &to_big(&secret));
+    let share = TaShare {
+        secret: state.secret,
+        public_key: encode(&public_key),
+        paillier_p: primes.p.clone(),
+        paillier_q: primes.q.clone(),
+    };
+    let reveal = KeygenReveal {
+        ta_key: state.key.clone(),
+        salt: state.salt,
+        paillier_n: key.public().n.to_bytes_be(),
+        encrypted_share: encrypted_share.to_bytes_be(),
+    };
+    Ok((share, reveal))
+}
+
+pub fn cosigner_keygen_finish(
+    wallet_id: &Uuid,
+    state: &CoSignerKeygenState,
+    reveal: &KeygenReveal,
+) -> Result<CoSignerShare> {
+    let ctx = context(KEYGEN, TA, wallet_id, 0);
+    if commit(&ctx, &reveal.ta_key, &reveal.salt) != state.commitment {
+        return Err("TA key share does not match its commitment");
+    }
+    let ta_point = verify(&reveal.ta_key, &ctx)?;
+    let n = BigUint::from_bytes_be(&reveal.paillier_n);
+    if n.bits() < MIN_PAILLIER_BITS || !n.bit(0) {
+        return Err("Paillier modulus is too small or even");
+    }
+    let encrypted_share = BigUint::from_bytes_be(&reveal.encrypted_share);
+    if !paillier::PublicKey::new(n).is_ciphertext(&encrypted_share) {
+        return Err("encrypted share is not a Paillier ciphertext");
+    }
+    let secret = parse_scalar(&state.secret)?;
+    let public_key = ta_point + ProjectivePoint::GENERATOR * secret;
+    if bool::from(public_key.is_identity()) {
+        return Err("threshold public key is the identity");
+    }
+    Ok(CoSignerShare {
+        secret: state.secret,
+        public_key: encode(&public_key),
+        ta_point: encode(&ta_point),
+        paillier_n: reveal.paillier_n.clone(),
+        encrypted_share: reveal.encrypted_share.clone(),
+    })
+}
+
+// ── Presignatures ──
+
+/// Nonces for ids `first_id..first_id + count`.
+pub fn presign_commit(
+    rng: &mut dyn Rng,
+    wallet_id: &Uuid,
+    first_id: u64,
+    count: usize,
+) -> Result<(Vec<TaPresignState>, Vec<PresignCommit>)> {
+    if count == 0 || count > MAX_PRESIGN_BATCH {
+        return Err("presignature batch must be 1 to 8");
+    }
+    let mut states = Vec::with_capacity(count);
+    let mut commits = Vec::with_capacity(count);
+    for id in first_id..first_id + count as u64 {
+        let ctx = context(PRESIGN, TA, wallet_id, id);
+        let nonce = random_scalar(rng);
+        let key = prove(rng, &nonce, &ctx);
+        let salt = random_bytes(rng);
+        commits.push(PresignCommit {
+            id,
+            commitment: commit(&ctx, &key, &salt),
+        });
+        states.push(TaPresignState {
+            id,
+            nonce: nonce.to_bytes().into(),
+            key,
+            salt,
+        });
+    }
+    Ok((states, commits))
+}
+
+pub fn cosigner_presign(
+    rng: &mut dyn Rng,
+    wallet_id: &Uuid,
+    commits: &[PresignCommit],
+) -> Result<(Vec<CoSignerPresignState>, Vec<PresignReply>)> {
+    if commits.is_empty() || commits.len() > MAX_PRESIGN_BATCH {
+        return Err("presignature batch must be 1 to 8");
+    }
+    let mut states = Vec::with_capacity(commits.len());
+    let mut replies = Vec::with_capacity(commits.len());
+    for c in commits {
+        let nonce = random_scalar(rng);
+        replies.push(PresignReply {
+            id: c.id,
+            nonce: prove(rng, &nonce, &context(PRESIGN, COSIGNER, wallet_id, c.id)),
+        });
+        states.push(CoSignerPresignState {
+            id: c.id,
+            nonce: nonce.to_bytes().into(),
+            commitment: c.commitment,
+        });
+    }
+    Ok((states, replies))
+}
+
+/// `R = k_ta·R_cs` for each reply; replies must answer `states` in order.
+pub fn presign_finish(
+    wallet_id: &Uuid,
+    states: &[TaPresignState],
+    replies: &[PresignReply],
+) -> Result<(Vec<TaPresignature>, Vec<PresignReveal>)> {
+    if states.len() != replies.len() || states.iter().zip(replies).any(|(s, r)| s.id != r.id) {
+        return Err("presignature replies do not match the batch");
+    }
+    let mut presignatures = Vec::with_capacity(states.len());
+    let mut reveals = Vec::with_capacity(states.len());
+    for (state, reply) in states.iter().zip(replies) {
+        let cosigner_point = verify(
+            &reply.nonce,
+            &context(PRESIGN, COSIGNER, wallet_id, state.id),
+        )?;
+        let r_point = cosigner_point * parse_scalar(&state.nonce)?;
+        presignatures.push(TaPresignature {
+            id: state.id,
+            nonce: state.nonce,
+            r_point: encode(&r_point),
+        });
+        reveals.push(PresignReveal {
+            id: state.id,
+            nonce: state.key.clone(),
+            salt: state.salt,
+        });
+    }
+    Ok((presignatures, reveals))
+}
+
+pub fn cosigner_presign_finish(
+    wallet_id: &Uuid,
+    states: &[CoSignerPresignState],
+    reveals: &[PresignReveal],
+) -> Result<Vec<CoSignerPresignature>> {
+    if states.len() != reveals.len() || states.iter().zip(reveals).any(|(s, r)| s.id != r.id) {
+        return Err("presignature reveals do not match the batch");
+    }
+    let mut presignatures = Vec::with_capacity(states.len());
+    for (state, reveal) in states.iter().zip(reveals) {
+        let ctx = context(PRESIGN, TA, wallet_id, state.id);
+        if commit(&ctx, &reveal.nonce, &reveal.salt) != state.commitment {
+            return Err("TA nonce does not match its commitment");
+        }
+        let ta_point = verify(&reveal.nonce, &ctx)?;
+        let r_point = ta_point * parse_scalar(&state.nonce)?;
+        presignatures.push(CoSignerPresignature {
+            id: state.id,
+            nonce: state.nonce,
+            r_point: encode(&r_point),
+        });
+    }
+    Ok(presignatures)
+}
+
+// ── Signing ──
+
+/// `c = Enc(ρ·q + k⁻¹(m + r·x_cs)) ⊕ (k⁻¹·r) ⊗ Enc(x_ta)`, with `ρ < q²`
+/// hiding the plaintext from the TA. The caller drops `presignature` after.
+pub fn cosigner_sign(
+    rng: &mut dyn Rng,
+    share: &CoSignerShare,
+    presignature: &CoSignerPresignature,
+    digest: &[u8; 32],
+) -> Result<SignReply> {
+    let k_inv = inverse(&parse_scalar(&presignature.nonce)?)?;
+    let r = r_scalar(&parse_point(&presignature.r_point)?)?;
+    let m = reduce(digest);
+    let x = parse_scalar(&share.secret)?;
+    let order = order();
+    let rho = random_below(rng, &(&order * &order));
+    let plain = to_big(&(k_inv * (m + r * x))) + rho * &order;
+
+    let key = paillier::PublicKey::new(BigUint::from_bytes_be(&share.paillier_n));
+    let encrypted_share = BigUint::from_bytes_be(&share.encrypted_share);
+    let c = key.add(
+        &key.encrypt(rng, &plain),
+        &key.mul(&encrypted_share, &to_big(&(k_inv * r))),
+    );
+    Ok(SignReply {
+        presignature_id: presignature.id,
+        ciphertext: c.to_bytes_be(),
+    })
+}
+
+/// `s = k_ta⁻¹ · Dec(c) mod q`, low-s, as `r ‖ s ‖ v` with v = 27/28. The
+/// signature is checked against the joint key before it is returned, so a
+/// bad reply from the co-signer only ever costs the presignature.
+pub fn sign_finish(
+    share: &TaShare,
+    presignature: &TaPresignature,
+    digest: &[u8; 32],
+    reply: &SignReply,
+) -> Result<[u8; 65]> {
+    if reply.presignature_id != presignature.id {
+        return Err("co-signer answered for another presignature");
+    }
+    let key = paillier::SecretKey::from_primes(
+        BigUint::from_bytes_be(&share.paillier_p),
+        BigUint::from_bytes_be(&share.paillier_q),
+    )?;
+    let c = BigUint::from_bytes_be(&reply.ciphertext);
+    if !key.public().is_ciphertext(&c) {
+        return Err("co-signer reply is not a Paillier ciphertext");
+    }
+    let s_partial = from_big(&key.decrypt(&c));
+    let s = inverse(&parse_scalar(&presignature.nonce)?)? * s_partial;
+    let r = r_scalar(&parse_point(&presignature.r_point)?)?;
+    let signature = Signature::from_scalars(r.to_bytes(), s.to_bytes())
+        .map_err(|_| "threshold signature has a zero scalar")?;
+    recoverable(&share.public_key, digest, signature)
+}
+
+// ── Fallback ──
+
+/// The co-signer's share sealed to the TA's share point, for `recombine`.
+pub fn cosigner_seal_share(rng: &mut dyn Rng, share: &CoSignerShare) -> Result<Vec<u8>> {
+    seal(rng, &share.ta_point, &share.secret)
+}
+
+/// Open the co-signer's sealed share and return the whole private key,
+/// checked against the joint public key.
+pub fn recombine(share: &TaShare, sealed_share: &[u8]) -> Result<[u8; 32]> {
+    let envelope = Envelope::parse(sealed_share)?;
+    let ephemeral = parse_point(envelope.ephemeral_pubkey)?;
+    let secret = parse_scalar(&share.secret)?;
+    let shared = uncompressed(&(ephemeral * secret));
+    let mut other = envelope.open(&shared)?;
+    if other.len() != 32 {
+        wipe(&mut other);
+        return Err("sealed share is not a 32-byte scalar");
+    }
+    let mut other_bytes = [0u8; 32];
+    other_bytes.copy_from_slice(&other);
+    wipe(&mut other);
+    let whole = parse_scalar(&other_bytes).map(|other| secret + other);
+    wipe(&mut other_bytes);
+    let whole = whole?;
+    if encode(&(ProjectivePoint::GENERATOR * whole)) != share.public_key {
+        return Err("recombined key does not match the threshold public key");
+    }
+    Ok(whole.to_bytes().into())
+}
+
+/// Sign with the recombined key, no co-signer: `r ‖ s ‖ v`, v = 27/28.
+pub fn sign_with_secret(secret: &[u8; 32], digest: &[u8; 32]) -> Result<[u8; 65]> {
+    let key = SigningKey::from_bytes(&FieldBytes::from(*secret))
+        .map_err(|_| "invalid secp256k1 secret")?;
+    let (signature, recovery_id) = key
+        .sign_prehash_recoverable(digest)
+        .map_err(|_| "signing failed")?;
+    let mut out = [0u8; 65];
+    out[..64].copy_from_slice(&signature.to_bytes());
+    out[64] = recovery_id.to_byte() + 27;
+    Ok(out)
+}
+
+/// ECIES-seal `plaintext` to a secp256k1 public key, in the layout
+/// `ecies::Envelope` opens.
+pub fn seal(rng: &mut dyn Rng, recipient: &[u8], plaintext: &[u8]) -> Result<Vec<u8>> {
+    let recipient = parse_point(recipient)?;
+    let ephemeral = random_scalar(rng);
+    let ephemeral_pubkey = uncompressed(&(ProjectivePoint::GENERATOR * ephemeral));
+    let shared = uncompressed(&(recipient * ephemeral));
+    let mut nonce = [0u8; NONCE_LEN];
+    rng.fill(&mut nonce);
+    Envelope::seal(&ephemeral_pubkey, &shared, &nonce, plaintext)
+}
+
+/// Ethereum address of a threshold public key.
+pub fn address(public_key: &[u8]) -> Result<[u8; 20]> {
+    let point = uncompressed(&parse_point(public_key)?);
+    let hash = Keccak256::digest(&point[1..]);
+    let mut out = [0u8; 20];
+    out.copy_from_slice(&hash[12..]);
+    Ok(out)
+}
+
+// ── Helpers ──
+
+fn context(phase: &[u8], role: u8, wallet_id: &Uuid, id: u64) -> Vec<u8> {
+    let mut ctx = phase.to_vec();
+    ctx.push(role);
+    ctx.extend_from_slice(wallet_id.as_bytes());
+    ctx.extend_from_slice(&id.to_be_bytes());
+    ctx
+}
+
+/// Schnorr proof of knowledge of `secret` for `secret·G`.
+fn prove(rng: &mut dyn Rng, secret: &Scalar, ctx: &[u8]) -> PartyKey {
+    let point = ProjectivePoint::GENERATOR * secret;
+    let a = random_scalar(rng);
+    let proof_point = ProjectivePoint::GENERATOR * a;
+    let e = challenge(ctx, &encode(&point), &encode(&proof_point));
+    PartyKey {
+        point: encode(&point),
+        proof_point: encode(&proof_point),
+        proof_response: (a + e * secret).to_bytes().into(),
+    }
+}
+
+fn verify(key: &PartyKey, ctx: &[u8]) -> Result<ProjectivePoint> {
+    let point = parse_point(&key.point)?;
+    let proof_point = parse_point(&key.proof_point)?;
+    let z = Option::<Scalar>::from(Scalar::from_repr(key.proof_response.into()))
+        .ok_or("proof response is not a scalar")?;
+    let e = challenge(ctx, &key.point, &key.proof_point);
+    if ProjectivePoint::GENERATOR * z != proof_point + point * e {
+        return Err("proof of knowledge does not verify");
+    }
+    Ok(point)
+}
+
+fn challenge(ctx: &[u8], point: &[u8], proof_point: &[u8]) -> Scalar {
+    let mut h = Keccak256::new();
+    h.update(ctx);
+    h.update(point);
+    h.update(proof_point);
+    reduce(&h.finalize().into())
+}
+
+fn commit(ctx: &[u8], key: &PartyKey, salt: &[u8; 32]) -> [u8; 32] {
+    let mut h = Keccak256::new();
+    h.update(b"AA-2PECDSA-COMMIT-v1");
+    h.update(ctx);
+    h.update(&key.point);
+    h.update(&key.proof_point);
+    h.update(key.proof_response);
+    h.update(salt);
+    h.finalize().into()
+}
+
+/// Find v by recovering the key: this is also the final verification.
+fn recoverable(public_key: &[u8], digest: &[u8; 32], signature: Signature) -> Result<[u8; 65]> {
+    let signature = signature.normalize_s().unwrap_or(signature);
+    for v in 0..=3u8 {
+        let id = RecoveryId::from_byte(v).ok_or("recovery id")?;
+        if let Ok(key) = VerifyingKey::recover_from_prehash(digest, &signature, id) {
+            if key.to_encoded_point(true).as_bytes() == public_key {
+                let mut out = [0u8; 65];
+                out[..64].copy_from_slice(&signature.to_bytes());
+                out[64] = v + 27;
+                return Ok(out);
+            }
+        }
+    }
+    Err("threshold signature does not verify against the joint key")
+}
+
+fn random_scalar(rng: &mut dyn Rng) -> Scalar {
+    loop {
+        let mut bytes = random_bytes(rng);
+        let candidate = Option::<Scalar>::from(Scalar::from_repr(bytes.into()));
+        wipe(&mut bytes);
+        match candidate {
+            Some(s) if !bool::from(s.is_zero()) => return s,
+            _ => continue,
+        }
+    }
+}
+
+fn random_bytes(rng: &mut dyn Rng) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    rng.fill(&mut bytes);
+    bytes
+}
+
+fn parse_scalar(bytes: &[u8; 32]) -> Result<Scalar> {
+    match Option::<Scalar>::from(Scalar::from_repr((*bytes).into())) {
+        Some(s) if !bool::from(s.is_zero()) => Ok(s),
+        _ => Err("secret is not a non-zero scalar"),
+    }
+}
+
+fn inverse(s: &Scalar) -> Result<Scalar> {
+    Option::<Scalar>::from(s.invert()).ok_or("scalar has no inverse")
+}
+
+fn reduce(bytes: &[u8; 32]) -> Scalar {
+    <Scalar as Reduce<U256>>::reduce_bytes(&FieldBytes::from(*bytes))
+}
+
+/// `r = R.x mod q`.
+fn r_scalar(point: &ProjectivePoint) -> Result<Scalar> {
+    let r = <Scalar as Reduce<U256>>::reduce_bytes(&point.to_affine().x());
+    if bool::from(r.is_zero()) {
+        return Err("presignature has r = 0");
+    }
+    Ok(r)
+}
+
+fn parse_point(bytes: &[u8]) -> Result<ProjectivePoint> {
+    let encoded = EncodedPoint::from_bytes(bytes).map_err(|_| "not a SEC1 point")?;
+    let point = Option::<AffinePoint>::from(AffinePoint::from_encoded_point(&encoded))
+        .ok_or("point is not on secp256k1")?;
+    let point = ProjectivePoint::from(point);
+    if bool::from(point.is_identity()) {
+        return Err("point is the identity");
+    }
+    Ok(point)
+}
+
+fn encode(point: &ProjectivePoint) -> Vec<u8> {
+    point.to_affine().to_bytes().to_vec()
+}
+
+fn uncompressed(point: &ProjectivePoint) -> [u8; EPHEMERAL_KEY_LEN] {
+    let mut out = [0u8; EPHEMERAL_KEY_LEN];
+    out.copy_from_slice(point.to_affine().to_encoded_point(false).as_bytes());
+    out
+}
+
+fn order() -> BigUint {
+    BigUint::parse_bytes(ORDER.as_bytes(), 16).expect("group order")
+}
+
+fn to_big(s: &Scalar) -> BigUint {
+    BigUint::from_bytes_be(&s.to_bytes())
+}
+
+fn from_big(n: &BigUint) -> Scalar {
+    let reduced = (n % order()).to_bytes_be();
+    let mut bytes = [0u8; 32];
+    bytes[32 - reduced.len()..].copy_from_slice(&reduced);
+    // Below the order by construction.
+    Option::<Scalar>::from(Scalar::from_repr(bytes.into())).unwrap_or(Scalar::ZERO)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::SeededRng;
+
+    // A fixed 2048-bit Paillier key: generating one takes seconds.
+    const P: &str = "e18b49fb7fed904fdd10797416ba207e658a7de21e57c95548535c0a5e36dbbb583d6af4dd0ac7adef16b83dfafe19ebc9ec8f5e28f472033f7eb23b90d111788a643616aa16dd3d96e98a9fa7fa04dabe6e2288efdc42cf0ec619c2820a978877944219c9fa9d597617c7d7437c9ccaa4c88a0af7a475accc0a5fba01c7611b";
+    const Q: &str = "c11b8e772fe3e23ec52c4499c8f992eed9dac54ec01f438673923544887a1123b5f1eafb798278dd0144ced49f63da8a06d95f4706e085b5b579380d740c1771eff8f0306dd5593e865534d03b8cb0761f7e0d0ba04c3f9e26afa587699b717ad9f07f55cbf0ba10481bb21d93e53cb77718097decd9b9cd28530e38066a0c43";
+
+    fn unhex(s: &str) -> Vec<u8> {
+        BigUint::parse_bytes(s.as_bytes(), 16)
+            .unwrap()
+            .to_bytes_be()
+    }
+
+    fn primes() -> PaillierPrimes {
+        PaillierPrimes {
+            p: unhex(P),
+            q: unhex(Q),
+        }
+    }
+
+    fn keygen(rng: &mut SeededRng, w: &Uuid) -> (TaShare, CoSignerShare) {
+        let (ta_state, commit) = keygen_commit(rng, w);
+        let (cs_state, reply) = cosigner_keygen(rng, w, &commit);
+        let (ta, reveal) = keygen_finish(rng, w, &ta_state, &reply, &primes()).unwrap();
+        let cs = cosigner_keygen_finish(w, &cs_state, &reveal).unwrap();
+        (ta, cs)
+    }
+
+    fn presign(
+        rng: &mut SeededRng,
+        w: &Uuid,
+        first_id: u64,
+        count: usize,
+    ) -> (Vec<TaPresignature>, Vec<CoSignerPresignature>) {
+        let (ta_states, commits) = presign_commit(rng, w, first_id, count).unwrap();
+        let (cs_states, replies) = cosigner_presign(rng, w, &commits).unwrap();
+        let (ta, reveals) = presign_finish(w, &ta_states, &replies).unwrap();
+        let cs = cosigner_presign_finish(w, &cs_states, &reveals).unwrap();
+        (ta, cs)
+    }
+
+    #[test]
+    fn keygen_presign_and_sign_round_trip() {
+        let mut rng = SeededRng::new([3; 32]);
+        let w = Uuid::from_bytes([9; 16]);
+        let (ta, cs) = keygen(&mut rng, &w);
+        assert_eq!(ta.public_key, cs.public_key);
+        assert_eq!(ta.public_key.len(), 33);
+
+        let (ta_pool, cs_pool) = presign(&mut rng, &w, 0, 2);
+        for (t, c) in ta_pool.iter().zip(&cs_pool) {
+            assert_eq!(t.r_point, c.r_point);
+        }
+        assert_ne!(ta_pool[0].r_point, ta_pool[1].r_point);
+
+        for (i, digest) in [[0x11u8; 32], [0x22u8; 32]].iter().enumerate() {
+            let reply = cosigner_sign(&mut rng, &cs, &cs_pool[i], digest).unwrap();
+            let sig = sign_finish(&ta, &ta_pool[i], digest, &reply).unwrap();
+            let signature = Signature::from_slice(&sig[..64]).unwrap();
+            assert!(signature.normalize_s().is_none(), "high s");
+            let recovered = VerifyingKey::recover_from_prehash(
+                digest,
+                &signature,
+                RecoveryId::from_byte(sig[64] - 27).unwrap(),
+            )
+            .unwrap();
+            assert_eq!(
+                recovered.to_encoded_point(true).as_bytes(),
+                &ta.public_key[..]
+            );
+        }
+    }
+
+    #[test]
+    fn a_bad_reply_never_yields_a_signature() {
+        let mut rng = SeededRng::new([4; 32]);
+        let w = Uuid::from_bytes([9; 16]);
+        let (ta, cs) = keygen(&mut rng, &w);
+        let (ta_pool, cs_pool) = presign(&mut rng, &w, 5, 2);
+        let digest = [0x33; 32];
+
+        // Signed for another digest, or on another presignature.
+        let other = cosigner_sign(&mut rng, &cs, &cs_pool[0], &[0x44; 32]).unwrap();
+        assert!(sign_finish(&ta, &ta_pool[0], &digest, &other).is_err());
+        let wrong_slot = cosigner_sign(&mut rng, &cs, &cs_pool[1], &digest).unwrap();
+        assert!(sign_finish(&ta, &ta_pool[0], &digest, &wrong_slot).is_err());
+        let mut garbage = wrong_slot.clone();
+        garbage.presignature_id = 5;
+        garbage.ciphertext = vec![0];
+        assert!(sign_finish(&ta, &ta_pool[0], &digest, &garbage).is_err());
+    }
+
+    #[test]
+    fn messages_are_bound_to_their_wallet_and_slot() {
+        let mut rng = SeededRng::new([5; 32]);
+        let w = Uuid::from_bytes([9; 16]);
+        let other = Uuid::from_bytes([8; 16]);
+
+        let (ta_state, commit) = keygen_commit(&mut rng, &w);
+        let (_, reply) = cosigner_keygen(&mut rng, &other, &commit);
+        assert!(keygen_finish(&mut rng, &w, &ta_state, &reply, &primes()).is_err());
+
+        let (cs_state, reply) = cosigner_keygen(&mut rng, &w, &commit);
+        let (_, mut reveal) = keygen_finish(&mut rng, &w, &ta_state, &reply, &primes()).unwrap();
+        reveal.salt[0] ^= 1;
+        assert!(cosigner_keygen_finish(&w, &cs_state, &reveal).is_err());
+
+        let (ta_states, commits) = presign_commit(&mut rng, &w, 0, 1).unwrap();
+        let (_, mut replies) = cosigner_presign(&mut rng, &w, &commits).unwrap();
+        replies[0].id = 1;
+        assert!(presign_finish(&w, &ta_states, &replies).is_err());
+        assert!(presign_commit(&mut rng, &w, 0, MAX_PRESIGN_BATCH + 1).is_err());
+    }
+
+    #[test]
+    fn small_paillier_modulus_is_refused() {
+        let mut rng = SeededRng::new([6; 32]);
+        let w = Uuid::from_bytes([9; 16]);
+        let (ta_state, commit) = keygen_commit(&mut rng, &w);
+        let (cs_state, reply) = cosigner_keygen(&mut rng, &w, &commit);
+        let small = PaillierPrimes {
+            p: paillier::generate_prime(&mut rng, 256).to_bytes_be(),
+            q: paillier::generate_prime(&mut rng, 256).to_bytes_be(),
+        };
+        let (_, reveal) = keygen_finish(&mut rng, &w, &ta_state, &reply, &small).unwrap();
+        assert!(cosigner_keygen_finish(&w, &cs_state, &reveal).is_err());
+    }
+
+    #[test]
+    fn recombine_and_export() {
+        let mut rng = SeededRng::new([7; 32]);
+        let w = Uuid::from_bytes([9; 16]);
+        let (ta, cs) = keygen(&mut rng, &w);
+
+        let sealed = cosigner_seal_share(&mut rng, &cs).unwrap();
+        let whole = recombine(&ta, &sealed).unwrap();
+        let digest = [0x55; 32];
+        let sig = sign_with_secret(&whole, &digest).unwrap();
+        let recovered = VerifyingKey::recover_from_prehash(
+            &digest,
+            &Signature::from_slice(&sig[..64]).unwrap(),
+            RecoveryId::from_byte(sig[64] - 27).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            recovered.to_encoded_point(true).as_bytes(),
+            &ta.public_key[..]
+        );
+
+        let mut tampered = sealed.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(recombine(&ta, &tampered).is_err());
+        // Sealed to a different TA share.
+        let (other_ta, _) = keygen(&mut rng, &w);
+        assert!(recombine(&other_ta, &sealed).is_err());
+
+        // Export: seal the whole key to an owner key and open it as the TA would.
+        let owner = random_scalar(&mut rng);
+        let owner_pub = uncompressed(&(ProjectivePoint::GENERATOR * owner));
+        let exported = seal(&mut rng, &owner_pub, &whole).unwrap();
+        let envelope = Envelope::parse(&exported).unwrap();
+        let shared = uncompressed(&(parse_point(envelope.ephemeral_pubkey).unwrap() * owner));
+        assert_eq!(envelope.open(&shared).unwrap(), whole.to_vec());
+        assert_eq!(address(&ta.public_key).unwrap().len(), 20);
+    }
+}
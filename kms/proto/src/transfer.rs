@@ -0,0 +1,326 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Chunked transfer of one command's input and output, for payloads larger
+//! than a single invoke carries.
+//!
+//! A direct invoke takes its input in p0 and answers in the p1 buffer the CA
+//! sized with `buffers::max_output_len`; past that the TA answers
+//! SHORT_BUFFER. Instead the CA can:
+//!
+//! 1. `BeginTransfer { command, input_len, input_digest }`: the TA opens the
+//!    transfer and answers its id;
+//! 2. `ContinueTransfer` with each [`TransferChunk`] of the input, in order;
+//! 3. `EndTransfer`: the TA checks the whole input against `input_digest`,
+//!    runs `command` on it through the normal dispatch (deployment policy
+//!    and tamper lock included) and keeps the output, answering its length,
+//!    digest and chunk count;
+//! 4. `ContinueTransfer` without a chunk, once per output chunk.
+//!
+//! Every chunk carries its sequence number and a Keccak-256 over the
+//! transfer id, sequence number and data, so a dropped, repeated, reordered
+//! or corrupted chunk, or one from another transfer, is refused. The TA
+//! holds one transfer per session, in memory; `BeginTransfer` drops an
+//! unfinished one.
+
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+
+use crate::buffers::{max_output_len, MAX_OUTPUT_LEN};
+use crate::Command;
+
+/// Data bytes in one chunk; a `ContinueTransferOutput` holding a full chunk
+/// fits the default output buffer.
+pub const CHUNK_LEN: usize = 3072;
+
+/// Largest input or output one transfer moves.
+pub const MAX_TRANSFER_LEN: usize = 64 * 1024;
+
+/// Largest input the CA still sends in a single invoke.
+pub const MAX_DIRECT_INPUT: usize = 16 * 1024;
+
+/// Whether the CA should send `command` through a transfer: the input is
+/// too large for one invoke, or the output can outgrow the largest buffer.
+pub fn needs_transfer(command: Command, input_len: usize) -> bool {
+    input_len > MAX_DIRECT_INPUT || max_output_len(command) > MAX_OUTPUT_LEN
+}
+
+/// Commands that cannot themselves be wrapped in a transfer.
+pub fn is_transfer_command(command: Command) -> bool {
+    matches!(
+        command,
+        Command::BeginTransfer | Command::ContinueTransfer | Command::EndTransfer
+    )
+}
+
+/// Keccak-256 of a whole input or output.
+pub fn payload_digest(bytes: &[u8]) -> [u8; 32] {
+    Keccak256::digest(bytes).into()
+}
+
+pub fn chunk_count(len: usize) -> u32 {
+    len.div_ceil(CHUNK_LEN) as u32
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TransferChunk {
+    /// 0 for the first chunk of each direction.
+    pub seq: u32,
+    pub data: Vec<u8>,
+    /// Keccak-256(transfer_id ‖ seq ‖ data), big-endian integers.
+    pub digest: [u8; 32],
+}
+
+impl TransferChunk {
+    pub fn new(transfer_id: u64, seq: u32, data: Vec<u8>) -> Self {
+        let digest = chunk_digest(transfer_id, seq, &data);
+        TransferChunk { seq, data, digest }
+    }
+}
+
+fn chunk_digest(transfer_id: u64, seq: u32, data: &[u8]) -> [u8; 32] {
+    let mut h = Keccak256::new();
+    h.update(transfer_id.to_be_bytes());
+    h.update(seq.to_be_bytes());
+    h.update(data);
+    h.finalize().into()
+}
+
+/// Chunk `seq` of `payload`, or None past the end.
+pub fn chunk_at(transfer_id: u64, payload: &[u8], seq: u32) -> Option<TransferChunk> {
+    let start = (seq as usize).checked_mul(CHUNK_LEN)?;
+    if start >= payload.len() {
+        return None;
+    }
+    let end = (start + CHUNK_LEN).min(payload.len());
+    Some(TransferChunk::new(
+        transfer_id,
+        seq,
+        payload[start..end].to_vec(),
+    ))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransferError {
+    TooLarge(usize),
+    UnknownTransfer(u64),
+    OutOfOrder {
+        expected: u32,
+        got: u32,
+    },
+    BadChunkDigest(u32),
+    /// More data than announced, or a chunk longer than [`CHUNK_LEN`].
+    Overrun,
+    Incomplete {
+        received: usize,
+        expected: usize,
+    },
+    BadPayloadDigest,
+    Nested(u32),
+}
+
+impl std::fmt::Display for TransferError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TransferError::TooLarge(len) => write!(
+                f,
+                "transfer of {} bytes exceeds {} bytes",
+                len, MAX_TRANSFER_LEN
+            ),
+            TransferError::UnknownTransfer(id) => write!(f, "no open transfer {:#x}", id),
+            TransferError::OutOfOrder { expected, got } => {
+                write!(
+                    f,
+                    "transfer chunk {} out of order, expected {}",
+                    got, expected
+                )
+            }
+            TransferError::BadChunkDigest(seq) => {
+                write!(f, "transfer chunk {} failed its integrity check", seq)
+            }
+            TransferError::Overrun => write!(f, "transfer chunk overruns the announced length"),
+            TransferError::Incomplete { received, expected } => write!(
+                f,
+                "transfer incomplete: {} of {} bytes received",
+                received, expected
+            ),
+            TransferError::BadPayloadDigest => write!(f, "transfer payload digest mismatch"),
+            TransferError::Nested(command) => {
+                write!(f, "command {} cannot be sent through a transfer", command)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TransferError {}
+
+/// One direction of a transfer, collected chunk by chunk: the TA's view of
+/// the input, the CA's of the output.
+#[derive(Debug, Clone)]
+pub struct Reassembly {
+    transfer_id: u64,
+    expected_len: usize,
+    digest: [u8; 32],
+    bytes: Vec<u8>,
+    next_seq: u32,
+}
+
+impl Reassembly {
+    pub fn new(transfer_id: u64, len: u32, digest: [u8; 32]) -> Result<Self, TransferError> {
+        let expected_len = len as usize;
+        if expected_len > MAX_TRANSFER_LEN {
+            return Err(TransferError::TooLarge(expected_len));
+        }
+        Ok(Reassembly {
+            transfer_id,
+            expected_len,
+            digest,
+            bytes: Vec::with_capacity(expected_len),
+            next_seq: 0,
+        })
+    }
+
+    pub fn transfer_id(&self) -> u64 {
+        self.transfer_id
+    }
+
+    /// Sequence number of the next chunk wanted.
+    pub fn next_seq(&self) -> u32 {
+        self.next_seq
+    }
+
+    pub fn push(&mut self, chunk: &TransferChunk) -> Result<(), TransferError> {
+        if chunk.seq != self.next_seq {
+            return Err(TransferError::OutOfOrder {
+                expected: self.next_seq,
+                got: chunk.seq,
+            });
+        }
+        if chunk.digest != chunk_digest(self.transfer_id, chunk.seq, &chunk.data) {
+            return Err(TransferError::BadChunkDigest(chunk.seq));
+        }
+        if chunk.data.len() > CHUNK_LEN || self.bytes.len() + chunk.data.len() > self.expected_len {
+            return Err(TransferError::Overrun);
+        }
+        self.bytes.extend_from_slice(&chunk.data);
+        self.next_seq += 1;
+        Ok(())
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.bytes.len() == self.expected_len
+    }
+
+    pub fn finish(self) -> Result<Vec<u8>, TransferError> {
+        if !self.is_complete() {
+            return Err(TransferError::Incomplete {
+                received: self.bytes.len(),
+                expected: self.expected_len,
+            });
+        }
+        if payload_digest(&self.bytes) != self.digest {
+            return Err(TransferError::BadPayloadDigest);
+        }
+        Ok(self.bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    fn reassemble(id: u64, bytes: &[u8]) -> Reassembly {
+        Reassembly::new(id, bytes.len() as u32, payload_digest(bytes)).unwrap()
+    }
+
+    #[test]
+    fn chunks_reassemble_in_order() {
+        for len in [0, 1, CHUNK_LEN, CHUNK_LEN + 1, 5 * CHUNK_LEN - 7] {
+            let bytes = payload(len);
+            let mut r = reassemble(7, &bytes);
+            let mut seq = 0;
+            while let Some(chunk) = chunk_at(7, &bytes, seq) {
+                r.push(&chunk).unwrap();
+                seq += 1;
+            }
+            assert_eq!(seq, chunk_count(len));
+            assert_eq!(r.finish().unwrap(), bytes);
+        }
+    }
+
+    #[test]
+    fn bad_chunks_are_refused() {
+        let bytes = payload(3 * CHUNK_LEN);
+        let mut r = reassemble(7, &bytes);
+        let first = chunk_at(7, &bytes, 0).unwrap();
+        let second = chunk_at(7, &bytes, 1).unwrap();
+
+        assert_eq!(
+            r.push(&second),
+            Err(TransferError::OutOfOrder {
+                expected: 0,
+                got: 1
+            })
+        );
+        assert_eq!(
+            r.push(&chunk_at(8, &bytes, 0).unwrap()),
+            Err(TransferError::BadChunkDigest(0))
+        );
+        let mut flipped = first.clone();
+        flipped.data[0] ^= 1;
+        assert_eq!(r.push(&flipped), Err(TransferError::BadChunkDigest(0)));
+
+        r.push(&first).unwrap();
+        assert_eq!(
+            r.push(&first),
+            Err(TransferError::OutOfOrder {
+                expected: 1,
+                got: 0
+            })
+        );
+        r.push(&second).unwrap();
+        assert_eq!(
+            r.clone().finish(),
+            Err(TransferError::Incomplete {
+                received: 2 * CHUNK_LEN,
+                expected: 3 * CHUNK_LEN
+            })
+        );
+
+        let mut wrong = Reassembly::new(7, bytes.len() as u32, [0; 32]).unwrap();
+        for seq in 0..3 {
+            wrong.push(&chunk_at(7, &bytes, seq).unwrap()).unwrap();
+        }
+        assert_eq!(wrong.finish(), Err(TransferError::BadPayloadDigest));
+
+        let mut short = reassemble(7, &bytes[..10]);
+        assert_eq!(short.push(&first), Err(TransferError::Overrun));
+        assert!(Reassembly::new(7, MAX_TRANSFER_LEN as u32 + 1, [0; 32]).is_err());
+    }
+
+    #[test]
+    fn only_large_inputs_need_a_transfer() {
+        assert!(!needs_transfer(Command::SignHash, MAX_DIRECT_INPUT));
+        assert!(needs_transfer(Command::StealthScan, MAX_DIRECT_INPUT + 1));
+        assert!(is_transfer_command(Command::EndTransfer));
+        assert!(!is_transfer_command(Command::TaStats));
+    }
+}
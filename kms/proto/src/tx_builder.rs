@@ -0,0 +1,116 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Legacy transactions from the hex-string JSON fields of the CA API.
+//!
+//! `Sign` and the SDK describe a transaction as `{chainId, nonce, to, value,
+//! gasPrice, gas, data}` with hex quantities. [`build_legacy`] is the one
+//! parser for that shape; [`unsigned_legacy_rlp`] and
+//! [`crate::offline::legacy_signing_hash`] give the payload the TA signs.
+
+use crate::eip55::{hex_value, parse_address};
+use crate::offline::{legacy_signing_hash, unsigned_legacy_rlp};
+use crate::EthTransaction;
+
+/// Hex quantity (`0x` optional), e.g. wei amounts.
+pub fn parse_quantity(s: &str, field: &str) -> Result<u128, String> {
+    u128::from_str_radix(s.strip_prefix("0x").unwrap_or(s), 16)
+        .map_err(|e| format!("Transaction.{}: {}", field, e))
+}
+
+/// Hex calldata (`0x` optional); empty means no data.
+pub fn parse_data(s: &str) -> Result<Vec<u8>, String> {
+    let pairs = s.strip_prefix("0x").unwrap_or(s).as_bytes().chunks_exact(2);
+    if !pairs.remainder().is_empty() {
+        return Err("Transaction.data: odd number of hex digits".to_string());
+    }
+    pairs
+        .map(|pair| match (hex_value(pair[0]), hex_value(pair[1])) {
+            (Some(hi), Some(lo)) => Ok((hi << 4) | lo),
+            _ => Err("Transaction.data: not hex".to_string()),
+        })
+        .collect()
+}
+
+/// Build the transaction `Sign` would forward to the TA. Contract creation
+/// (no `to`) is not signable through the API.
+pub fn build_legacy(
+    chain_id: u64,
+    nonce: u64,
+    to: &str,
+    value: &str,
+    gas_price: &str,
+    gas: u64,
+    data: &str,
+) -> Result<EthTransaction, String> {
+    Ok(EthTransaction {
+        chain_id,
+        nonce: nonce as u128,
+        to: Some(parse_address(to).map_err(|e| format!("Transaction.to: {}", e))?),
+        value: parse_quantity(value, "value")?,
+        gas_price: parse_quantity(gas_price, "gasPrice")?,
+        gas: gas as u128,
+        data: parse_data(data)?,
+    })
+}
+
+/// Unsigned EIP-155 payload and its signing hash, for display and for
+/// checking a signature without the TA.
+pub fn signing_payload(tx: &EthTransaction) -> (Vec<u8>, [u8; 32]) {
+    (unsigned_legacy_rlp(tx), legacy_signing_hash(tx))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_the_eip155_example() {
+        let tx = build_legacy(
+            1,
+            9,
+            "0x3535353535353535353535353535353535353535",
+            "0xde0b6b3a7640000",
+            "0x4a817c800",
+            21000,
+            "",
+        )
+        .unwrap();
+        let (rlp, hash) = signing_payload(&tx);
+        // EIP-155 worked example: signing data and signing hash
+        assert_eq!(
+            rlp.iter().map(|b| format!("{:02x}", b)).collect::<String>(),
+            "ec098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a764000080018080"
+        );
+        assert_eq!(
+            hash.iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<String>(),
+            "daf5a779ae972f972197303d7b574746c7ef83eadac0f2791ad23db92e4c8e53"
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_fields() {
+        let to = "0x3535353535353535353535353535353535353535";
+        assert!(build_legacy(1, 0, "0x35", "0x0", "0x1", 21000, "").is_err());
+        assert!(build_legacy(1, 0, to, "", "0x1", 21000, "").is_err());
+        assert!(build_legacy(1, 0, to, "0x0", "0x1", 21000, "0xabc").is_err());
+        assert!(build_legacy(1, 0, to, "0x0", "0x1", 21000, "0xzz").is_err());
+        assert_eq!(parse_data("0xa9059cbb"), Ok(vec![0xa9, 0x05, 0x9c, 0xbb]));
+    }
+}
@@ -0,0 +1,74 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Wallet ids derived from the wallet's own key.
+//!
+//! A wallet's id is the first 16 bytes of
+//! `keccak256("AirAccount.wallet-id.v1" || pubkey)`, where `pubkey` is the
+//! compressed public key of the account root `m/44'/60'/0'`, stamped as a
+//! custom (version 8) UUID. Creating or importing the same entropy again —
+//! on this device or another — gives the same id, and the id carries no
+//! creation order. Wallets that already hold a random (version 4) id keep
+//! it, as do wallets restored from their backups; both are ordinary UUIDs
+//! to every lookup.
+
+use sha3::{Digest, Keccak256};
+use uuid::Uuid;
+
+const DOMAIN: &[u8] = b"AirAccount.wallet-id.v1";
+
+/// The id of the wallet whose account root has compressed public key
+/// `account_pubkey`.
+pub fn from_account_pubkey(account_pubkey: &[u8; 33]) -> Uuid {
+    let hash = Keccak256::new()
+        .chain_update(DOMAIN)
+        .chain_update(account_pubkey)
+        .finalize();
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&hash[..16]);
+    uuid::Builder::from_custom_bytes(bytes).into_uuid()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pubkey(fill: u8) -> [u8; 33] {
+        let mut key = [fill; 33];
+        key[0] = 0x02;
+        key
+    }
+
+    #[test]
+    fn same_key_same_id() {
+        assert_eq!(
+            from_account_pubkey(&pubkey(7)),
+            from_account_pubkey(&pubkey(7))
+        );
+        assert_ne!(
+            from_account_pubkey(&pubkey(7)),
+            from_account_pubkey(&pubkey(8))
+        );
+    }
+
+    #[test]
+    fn ids_are_version_8() {
+        let id = from_account_pubkey(&pubkey(7));
+        assert_eq!(id.get_version_num(), 8);
+        assert_eq!(id.get_variant(), uuid::Variant::RFC4122);
+    }
+}
@@ -0,0 +1,284 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Protocol versioning of the CA → TA invoke, so the two can be upgraded
+//! independently.
+//!
+//! A request is the command id, the bincode input (p0) and the value
+//! parameter's `b`, which carries a [`RequestHeader`]: the priority hint in
+//! the low 16 bits and the CA's protocol version in the high 16. A CA that
+//! predates versioning sends version 0 there.
+//!
+//! The rules both sides rely on:
+//! 1. Messages only grow at the end; a nested struct may grow only while it
+//!    is the last field of every message holding it. A field added later
+//!    encodes its default as zero bytes (`Option` None, 0, `false`, an empty
+//!    `Vec` or `String`) and that default means what a peer without the field
+//!    meant. Growing an input bumps [`PROTOCOL_VERSION`].
+//! 2. Trailing bytes a decoder does not know are ignored — a newer peer's
+//!    fields. A message that ends early — an older peer's — is zero-filled by
+//!    [`decode`], which is what makes `#[serde(default)]` hold for bincode.
+//!    The TA zero-fills only when the CA declared an older version; at equal
+//!    versions a short input is malformed.
+//! 3. A reordered or retyped field needs a new command id, never an edit.
+//! 4. A TA answers a command id it does not dispatch with
+//!    [`UnsupportedVersion`], which the CA recognises in the error text.
+//!
+//! Each side also has an oldest version it still speaks,
+//! [`MIN_PROTOCOL_VERSION`]. The CA asks the TA for its range
+//! (`Command::GetProtocolVersion`) at startup and [`negotiate`]s; ranges that
+//! do not overlap stop the CA from sending anything.
+//!
+//! `tests/version_compat.rs` decodes the messages of every earlier version
+//! with the current types.
+
+use serde::de::DeserializeOwned;
+
+/// Version of the message layouts in this crate.
+///
+/// - 0: no version sent (any CA built before the header existed).
+/// - 1: the header; layouts as pinned in `tests/golden/wire-v1.txt`.
+/// - 2: `DeriveAddressInput::chain` and `DeriveAddressOutput::encoded_address`.
+pub const PROTOCOL_VERSION: u16 = 2;
+
+/// Oldest peer version this build still decodes: v0 messages by
+/// zero-filling. Raised only when an old layout stops being accepted.
+pub const MIN_PROTOCOL_VERSION: u16 = 0;
+const _: () = assert!(MIN_PROTOCOL_VERSION == 0, "negotiate assumes a floor of 0");
+
+/// Zero bytes appended to a message that ends early; more than any run of
+/// fields appended so far encodes to.
+const ZERO_FILL: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestHeader {
+    pub protocol_version: u16,
+    /// `PRIORITY_INTERACTIVE` or `PRIORITY_BATCH`.
+    pub priority: u32,
+}
+
+impl RequestHeader {
+    /// A header from this build.
+    pub fn new(priority: u32) -> Self {
+        RequestHeader {
+            protocol_version: PROTOCOL_VERSION,
+            priority,
+        }
+    }
+
+    pub fn to_hint(self) -> u32 {
+        (u32::from(self.protocol_version) << 16) | (self.priority & 0xffff)
+    }
+
+    pub fn from_hint(hint: u32) -> Self {
+        RequestHeader {
+            protocol_version: (hint >> 16) as u16,
+            priority: hint & 0xffff,
+        }
+    }
+}
+
+/// Decode a message from a peer at `peer_version`, zero-filling it if it
+/// ends early and the peer is older than this build.
+pub fn decode<T: DeserializeOwned>(bytes: &[u8], peer_version: u16) -> bincode::Result<T> {
+    match bincode::deserialize(bytes) {
+        Err(e) if peer_version < PROTOCOL_VERSION && ended_early(&e) => {
+            let mut filled = Vec::with_capacity(bytes.len() + ZERO_FILL);
+            filled.extend_from_slice(bytes);
+            filled.resize(bytes.len() + ZERO_FILL, 0);
+            bincode::deserialize(&filled).map_err(|_| e)
+        }
+        result => result,
+    }
+}
+
+/// Decode a TA output on the CA. Outputs carry no version, so a short one is
+/// always taken to come from an older TA.
+pub fn decode_output<T: DeserializeOwned>(bytes: &[u8]) -> bincode::Result<T> {
+    decode(bytes, 0)
+}
+
+fn ended_early(e: &bincode::Error) -> bool {
+    matches!(&**e, bincode::ErrorKind::Io(io) if io.kind() == std::io::ErrorKind::UnexpectedEof)
+}
+
+/// The TA does not dispatch `command`: it is newer than the TA, or was
+/// retired. Travels as error text; see [`UnsupportedVersion::find`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnsupportedVersion {
+    pub command: u32,
+    pub ca_version: u16,
+    pub ta_version: u16,
+}
+
+const UNSUPPORTED_TAG: &str = "UnsupportedVersion: command ";
+
+impl std::fmt::Display for UnsupportedVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}{} is not dispatched by this TA (TA protocol v{}, CA protocol v{})",
+            UNSUPPORTED_TAG, self.command, self.ta_version, self.ca_version
+        )
+    }
+}
+
+impl std::error::Error for UnsupportedVersion {}
+
+impl UnsupportedVersion {
+    /// Recover the error from a TA error message that contains it.
+    pub fn find(message: &str) -> Option<Self> {
+        let text = &message[message.find(UNSUPPORTED_TAG)?..];
+        let field = |label: &str| -> Option<u32> {
+            let rest = &text[text.find(label)? + label.len()..];
+            let end = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            rest[..end].parse().ok()
+        };
+        Some(UnsupportedVersion {
+            command: field("command ")?,
+            ta_version: field("TA protocol v")? as u16,
+            ca_version: field("CA protocol v")? as u16,
+        })
+    }
+}
+
+/// The peer's version range does not overlap this build's.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VersionMismatch {
+    pub min_version: u16,
+    pub version: u16,
+    pub peer_min_version: u16,
+    pub peer_version: u16,
+}
+
+impl std::fmt::Display for VersionMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "protocol mismatch: this side speaks v{}..=v{}, the peer v{}..=v{}",
+            self.min_version, self.version, self.peer_min_version, self.peer_version
+        )
+    }
+}
+
+impl std::error::Error for VersionMismatch {}
+
+/// The newest version both this build and a peer speaking
+/// `peer_min_version..=peer_version` understand.
+pub fn negotiate(peer_min_version: u16, peer_version: u16) -> Result<u16, VersionMismatch> {
+    let agreed = PROTOCOL_VERSION.min(peer_version);
+    // Every version this build speaks down to MIN_PROTOCOL_VERSION (0), so
+    // only the peer's floor can rule `agreed` out.
+    if agreed < peer_min_version {
+        return Err(VersionMismatch {
+            min_version: MIN_PROTOCOL_VERSION,
+            version: PROTOCOL_VERSION,
+            peer_min_version,
+            peer_version,
+        });
+    }
+    Ok(agreed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Before {
+        id: u32,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct After {
+        id: u32,
+        #[serde(default)]
+        note: Option<String>,
+        #[serde(default)]
+        count: u64,
+    }
+
+    #[test]
+    fn short_messages_are_zero_filled_only_from_older_peers() {
+        let old = bincode::serialize(&Before { id: 7 }).unwrap();
+        let want = After {
+            id: 7,
+            note: None,
+            count: 0,
+        };
+        assert_eq!(decode::<After>(&old, 0).unwrap(), want);
+        assert_eq!(decode_output::<After>(&old).unwrap(), want);
+        assert!(decode::<After>(&old, PROTOCOL_VERSION).is_err());
+
+        let new = bincode::serialize(&After {
+            id: 7,
+            note: Some("x".into()),
+            count: 3,
+        })
+        .unwrap();
+        assert_eq!(
+            decode::<Before>(&new, PROTOCOL_VERSION + 1).unwrap(),
+            Before { id: 7 }
+        );
+        // Zero-filling cannot rescue a message that is wrong, not short.
+        let mut bad = old.clone();
+        bad.push(2); // no such Option tag
+        assert!(decode::<After>(&bad, 0).is_err());
+    }
+
+    #[test]
+    fn header_and_error_text() {
+        let header = RequestHeader::new(crate::PRIORITY_BATCH);
+        assert_eq!(RequestHeader::from_hint(header.to_hint()), header);
+        // An unversioned CA sent the bare priority.
+        assert_eq!(
+            RequestHeader::from_hint(crate::PRIORITY_BATCH),
+            RequestHeader {
+                protocol_version: 0,
+                priority: crate::PRIORITY_BATCH
+            }
+        );
+
+        let err = UnsupportedVersion {
+            command: 70,
+            ca_version: 2,
+            ta_version: 1,
+        };
+        let relayed = format!("TA command failed: {} (error: BadParameters)", err);
+        assert_eq!(UnsupportedVersion::find(&relayed), Some(err));
+        assert_eq!(UnsupportedVersion::find("Unsupported command"), None);
+    }
+
+    #[test]
+    fn negotiation_picks_the_newest_common_version() {
+        assert_eq!(negotiate(0, PROTOCOL_VERSION), Ok(PROTOCOL_VERSION));
+        // an older peer: speak its version
+        assert_eq!(negotiate(0, 0), Ok(0));
+        // a newer peer that still speaks ours
+        assert_eq!(
+            negotiate(PROTOCOL_VERSION, PROTOCOL_VERSION + 3),
+            Ok(PROTOCOL_VERSION)
+        );
+        // a newer peer that has dropped ours
+        let err = negotiate(PROTOCOL_VERSION + 1, PROTOCOL_VERSION + 3).unwrap_err();
+        assert_eq!(err.peer_min_version, PROTOCOL_VERSION + 1);
+        assert!(err.to_string().starts_with("protocol mismatch"));
+    }
+}
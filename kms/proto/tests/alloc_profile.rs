@@ -0,0 +1,89 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Heap allocations of the TA's output path on the hot commands.
+//!
+//! The TA sizes each output and encodes it straight into the CA's shared
+//! buffer (`write_output` in ta/src/main.rs). These tests check, for the
+//! hot commands' outputs, that the sized write allocates nothing and gives
+//! the same bytes as `bincode::serialize`, so a regression shows up in CI
+//! rather than on the board.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use proto::*;
+
+struct Counting;
+
+thread_local! {
+    // Per thread, so tests running in parallel do not see each other.
+    static ALLOCS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCS.try_with(|n| n.set(n.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+fn allocations<R>(f: impl FnOnce() -> R) -> (usize, R) {
+    let before = ALLOCS.with(Cell::get);
+    let r = f();
+    (ALLOCS.with(Cell::get) - before, r)
+}
+
+/// Both ways the TA can produce a command's output.
+fn profile<T: serde::Serialize>(output: &T) {
+    // The CA's p1 buffer; preallocated before the command runs.
+    let mut out = [0u8; 4096];
+
+    let (old, vec) = allocations(|| bincode::serialize(output).unwrap());
+    let (new, len) = allocations(|| {
+        let len = bincode::serialized_size(output).unwrap() as usize;
+        bincode::serialize_into(&mut out[..len], output).unwrap();
+        len
+    });
+
+    assert!(old >= 1, "bincode::serialize allocates its Vec");
+    assert_eq!(new, 0, "sized write into the CA buffer allocates nothing");
+    assert_eq!(&out[..len], &vec[..], "same bytes on the wire");
+}
+
+#[test]
+fn sign_hash_output_writes_without_allocating() {
+    profile(&SignHashOutput {
+        signature: vec![0x5a; 65],
+    });
+}
+
+#[test]
+fn derive_address_output_writes_without_allocating() {
+    profile(&DeriveAddressOutput {
+        address: [0x11; 20],
+        public_key: vec![0x04; 65],
+        encoded_address: "TUEZSdKsoDHQMeZwihtdoBiN46zxhGWYdH".into(),
+    });
+}
@@ -0,0 +1,216 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Outbound event contract: golden JSON for every event type and version the
+//! CA publishes (webhooks, event list, audit export). Downstream consumers
+//! parse these shapes, so a field rename or retype must fail here and become
+//! a new version instead.
+//!
+//! Adding a new event type or version appends to the file:
+//!     KMS_UPDATE_GOLDEN=1 cargo test -p proto --test event_contract
+//! and the diff of `tests/golden/events-v1.txt` is then part of the review.
+//! Lines already in the file must not change.
+
+use proto::event::*;
+use serde_json::Value;
+
+const GOLDEN: &str = include_str!("golden/events-v1.txt");
+
+fn chain_event() -> EventEnvelope<ChainEventPayload> {
+    EventEnvelope::new(
+        ChainEventPayload {
+            id: 42,
+            key_id: "4319f351-0b24-4097-b659-80ee4f824cdd".into(),
+            address: "0x1111111111111111111111111111111111111111".into(),
+            chain_id: 10,
+            kind: "erc20-transfer-in".into(),
+            token: Some("0x0b2c639c533813f4aa9d7837caf62653d097ff85".into()),
+            from: Some("0x2222222222222222222222222222222222222222".into()),
+            to: Some("0x1111111111111111111111111111111111111111".into()),
+            value: Some("12500000".into()),
+            topic0: Some(
+                "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef".into(),
+            ),
+            tx_hash: format!("0x{}", "ab".repeat(32)),
+            block_number: 123_456,
+            log_index: Some(3),
+            removed: false,
+            email: false,
+        },
+        1_790_000_000,
+        "chain-event-42".into(),
+    )
+}
+
+fn audit_event() -> EventEnvelope<AuditEventPayload> {
+    EventEnvelope::new(
+        AuditEventPayload {
+            account: "4319f351-0b24-4097-b659-80ee4f824cdd".into(),
+            event: "passkey_changed".into(),
+            detail: None,
+        },
+        1_790_000_100,
+        "req-7f3a".into(),
+    )
+}
+
+fn gas_tank_alert() -> EventEnvelope<GasTankAlertPayload> {
+    EventEnvelope::new(
+        GasTankAlertPayload {
+            address: "0x3333333333333333333333333333333333333333".into(),
+            chain_id: 8453,
+            label: "bundler-1".into(),
+            state: "low".into(),
+            balance_wei: "40000000000000000".into(),
+            min_balance_wei: "100000000000000000".into(),
+            top_up_id: Some(7),
+        },
+        1_790_000_200,
+        "gas-tank-8453-0x3333333333333333333333333333333333333333".into(),
+    )
+}
+
+fn device_health() -> EventEnvelope<DeviceHealthPayload> {
+    EventEnvelope::new(
+        DeviceHealthPayload {
+            device_id: Some("6f1c2a8e-0c1d-4a55-9a7e-3f2b1c0d9e8a".into()),
+            state: "quarantined".into(),
+            reason: "self-test failed: rng".into(),
+            failed_checks: vec!["rng".into()],
+            ta_measurement: Some("ab".repeat(32)),
+        },
+        1_790_000_300,
+        "device-health-1790000300".into(),
+    )
+}
+
+fn cases() -> Vec<(String, String)> {
+    let line = |v: Value| serde_json::to_string(&v).unwrap();
+    vec![
+        (
+            format!("{}@{}", ChainEventPayload::TYPE, ChainEventPayload::VERSION),
+            line(serde_json::to_value(chain_event()).unwrap()),
+        ),
+        (
+            format!("{}@{}", AuditEventPayload::TYPE, AuditEventPayload::VERSION),
+            line(serde_json::to_value(audit_event()).unwrap()),
+        ),
+        (
+            format!(
+                "{}@{}",
+                GasTankAlertPayload::TYPE,
+                GasTankAlertPayload::VERSION
+            ),
+            line(serde_json::to_value(gas_tank_alert()).unwrap()),
+        ),
+        (
+            format!(
+                "{}@{}",
+                DeviceHealthPayload::TYPE,
+                DeviceHealthPayload::VERSION
+            ),
+            line(serde_json::to_value(device_health()).unwrap()),
+        ),
+    ]
+}
+
+fn parse_golden() -> Vec<(String, String)> {
+    GOLDEN
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(|l| {
+            let (name, json) = l
+                .split_once(' ')
+                .expect("golden line must be 'type@v json'");
+            (name.to_string(), json.to_string())
+        })
+        .collect()
+}
+
+#[test]
+fn event_json_matches_golden() {
+    let cases = cases();
+    if std::env::var("KMS_UPDATE_GOLDEN").ok().as_deref() == Some("1") {
+        let mut out =
+            String::from("# Generated by tests/event_contract.rs — do not edit by hand.\n");
+        for (name, json) in &cases {
+            out.push_str(&format!("{} {}\n", name, json));
+        }
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/events-v1.txt");
+        std::fs::write(path, out).unwrap();
+        return;
+    }
+    let golden = parse_golden();
+    for (name, json) in &cases {
+        let want = golden
+            .iter()
+            .find(|(n, _)| n == name)
+            .unwrap_or_else(|| panic!("{}: not in the golden file", name));
+        assert_eq!(
+            json, &want.1,
+            "{}: event shape changed — bump its VERSION instead",
+            name
+        );
+    }
+}
+
+#[test]
+fn golden_events_parse_with_current_types() {
+    for (name, json) in parse_golden() {
+        let raw: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            format!("{}@{}", raw["type"].as_str().unwrap(), raw["version"]),
+            name
+        );
+        match name.as_str() {
+            "chain.event@1" => {
+                let e: EventEnvelope<ChainEventPayload> = serde_json::from_str(&json).unwrap();
+                assert_eq!(e, chain_event());
+            }
+            "account.audit@1" => {
+                let e: EventEnvelope<AuditEventPayload> = serde_json::from_str(&json).unwrap();
+                assert_eq!(e, audit_event());
+            }
+            "gas_tank.alert@1" => {
+                let e: EventEnvelope<GasTankAlertPayload> = serde_json::from_str(&json).unwrap();
+                assert_eq!(e, gas_tank_alert());
+            }
+            "device.health@1" => {
+                let e: EventEnvelope<DeviceHealthPayload> = serde_json::from_str(&json).unwrap();
+                assert_eq!(e, device_health());
+            }
+            other => panic!("{}: golden event with no decoder", other),
+        }
+    }
+}
+
+#[test]
+fn consumers_on_an_older_build_ignore_added_fields() {
+    // What a consumer compiled against v1 sees when a later build adds fields
+    // to the payload and the envelope: it must still parse.
+    let mut raw = serde_json::to_value(chain_event()).unwrap();
+    raw["payload"]["confirmations"] = Value::from(12);
+    raw["source"] = Value::from("node-b");
+    let e: EventEnvelope<ChainEventPayload> = serde_json::from_value(raw).unwrap();
+    assert_eq!(e, chain_event());
+
+    // A type it does not know still has a readable envelope.
+    let unknown = r#"{"type":"wallet.frozen","version":3,"occurredAt":1,"correlationId":"x","payload":{"a":1}}"#;
+    let e: EventEnvelope<Value> = serde_json::from_str(unknown).unwrap();
+    assert_eq!((e.event_type.as_str(), e.version), ("wallet.frozen", 3));
+}
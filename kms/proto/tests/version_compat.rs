@@ -0,0 +1,292 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Protocol-version conformance: requests from every earlier version, and
+//! from a newer one, decode with the current types under the rules in
+//! `proto::wire`. A change that breaks one of these breaks a CA/TA pair that
+//! is upgraded one side at a time.
+//!
+//! v1 messages are the golden bytes `wire_contract.rs` pins. v0 (no header)
+//! has no single layout, so its cases are messages as they were sent before
+//! a field was appended, re-declared here with the fields they had then.
+
+use proto::wire::{self, RequestHeader, UnsupportedVersion, PROTOCOL_VERSION};
+use proto::*;
+use serde::Serialize;
+
+const GOLDEN_V1: &str = include_str!("golden/wire-v1.txt");
+
+/// Shapes that unversioned peers sent.
+mod v0 {
+    use serde::Serialize;
+
+    /// A CA before the CAAM-bypass entropy seed and the PRF output.
+    #[derive(Serialize)]
+    pub struct CreateWalletInput {
+        pub passkey_pubkey: Vec<u8>,
+    }
+
+    /// A CA before the PRF output.
+    #[derive(Serialize)]
+    pub struct CreateWalletInputWithSeed {
+        pub passkey_pubkey: Vec<u8>,
+        pub entropy_seed: Option<Vec<u8>>,
+    }
+
+    /// A TA before InstallConfig.
+    #[derive(Serialize)]
+    pub struct GetCapabilitiesOutput {
+        pub ta_version: String,
+        pub features: Vec<String>,
+        pub policy: Option<proto::deployment_policy::DeploymentPolicy>,
+        pub policy_signer: Option<[u8; 20]>,
+    }
+}
+
+/// Shapes of v1 messages that v2 appended to.
+mod v1 {
+    use serde::Serialize;
+    use uuid::Uuid;
+
+    /// A CA before `chain`.
+    #[derive(Serialize)]
+    pub struct DeriveAddressInput {
+        pub wallet_id: Uuid,
+        pub hd_path: String,
+        pub passkey_assertion: Option<proto::PasskeyAssertion>,
+    }
+
+    /// A TA before `encoded_address`.
+    #[derive(Serialize)]
+    pub struct DeriveAddressOutput {
+        pub address: [u8; 20],
+        pub public_key: Vec<u8>,
+    }
+}
+
+/// What the TA sees: the command id, the value-parameter hint, the input.
+struct Envelope {
+    command: u32,
+    hint: u32,
+    input: Vec<u8>,
+}
+
+impl Envelope {
+    fn new<T: Serialize>(command: Command, hint: u32, input: &T) -> Self {
+        Envelope {
+            command: command.into(),
+            hint,
+            input: bincode::serialize(input).unwrap(),
+        }
+    }
+
+    /// Decode as the TA's dispatcher does.
+    fn decode<T: serde::de::DeserializeOwned>(&self) -> bincode::Result<T> {
+        let header = RequestHeader::from_hint(self.hint);
+        wire::decode(&self.input, header.protocol_version)
+    }
+}
+
+fn golden_v1() -> Vec<(String, Vec<u8>)> {
+    GOLDEN_V1
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(|l| {
+            let (name, hex) = l.split_once('=').expect("golden line must be name=hex");
+            let bytes = (0..hex.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+                .collect();
+            (name.to_string(), bytes)
+        })
+        .collect()
+}
+
+/// Decode `bytes` as the message called `name` at `peer_version` and
+/// re-encode it.
+fn reencode(name: &str, bytes: &[u8], peer_version: u16) -> Vec<u8> {
+    macro_rules! messages {
+        ($($t:ident),* $(,)?) => {
+            match name {
+                $(stringify!($t) => bincode::serialize(
+                    &wire::decode::<$t>(bytes, peer_version)
+                        .unwrap_or_else(|e| panic!("{} from v{}: {}", name, peer_version, e)),
+                )
+                .unwrap(),)*
+                _ => panic!("{}: add it to version_compat.rs", name),
+            }
+        };
+    }
+    messages!(
+        CreateWalletInput,
+        CreateWalletOutput,
+        SignTransactionInput,
+        SignMessageInput,
+        SignHashInput,
+        SignHashOutput,
+        GetChallengeInput,
+        CreateScopedSessionKeyInput,
+        SignWithSessionKeyInput,
+    )
+}
+
+#[test]
+fn v0_requests_decode() {
+    // An unversioned CA put only the priority in the hint.
+    for (hint, priority) in [
+        (PRIORITY_INTERACTIVE, PRIORITY_INTERACTIVE),
+        (PRIORITY_BATCH, PRIORITY_BATCH),
+    ] {
+        assert_eq!(
+            RequestHeader::from_hint(hint),
+            RequestHeader {
+                protocol_version: 0,
+                priority
+            }
+        );
+    }
+
+    let bare = Envelope::new(
+        Command::CreateWallet,
+        PRIORITY_INTERACTIVE,
+        &v0::CreateWalletInput {
+            passkey_pubkey: vec![0x04; 65],
+        },
+    );
+    let input: CreateWalletInput = bare.decode().unwrap();
+    assert_eq!(input.passkey_pubkey, vec![0x04; 65]);
+    assert_eq!(input.entropy_seed, None);
+    assert_eq!(input.prf_output, None);
+
+    let seeded = Envelope::new(
+        Command::CreateWallet,
+        PRIORITY_BATCH,
+        &v0::CreateWalletInputWithSeed {
+            passkey_pubkey: vec![0x04; 65],
+            entropy_seed: Some(vec![0x33; 48]),
+        },
+    );
+    let input: CreateWalletInput = seeded.decode().unwrap();
+    assert_eq!(input.entropy_seed, Some(vec![0x33; 48]));
+    assert_eq!(input.prf_output, None);
+    assert_eq!(seeded.command, 0);
+}
+
+#[test]
+fn v0_outputs_decode() {
+    let bytes = bincode::serialize(&v0::GetCapabilitiesOutput {
+        ta_version: "0.20.0".into(),
+        features: vec!["ree-fs-only".into()],
+        policy: None,
+        policy_signer: Some([0x11; 20]),
+    })
+    .unwrap();
+    let caps: GetCapabilitiesOutput = wire::decode_output(&bytes).unwrap();
+    assert_eq!(caps.ta_version, "0.20.0");
+    assert_eq!(caps.policy_signer, Some([0x11; 20]));
+    assert_eq!(caps.config, None);
+    assert_eq!(caps.provisioning_key, None);
+}
+
+#[test]
+fn v1_requests_decode() {
+    for version in 0..=PROTOCOL_VERSION {
+        for (name, bytes) in golden_v1() {
+            assert_eq!(
+                reencode(&name, &bytes, version),
+                bytes,
+                "{} from v{}",
+                name,
+                version
+            );
+        }
+    }
+}
+
+#[test]
+fn v1_derive_address_means_ethereum() {
+    let hint = RequestHeader {
+        protocol_version: 1,
+        priority: PRIORITY_INTERACTIVE,
+    }
+    .to_hint();
+    let request = Envelope::new(
+        Command::DeriveAddress,
+        hint,
+        &v1::DeriveAddressInput {
+            wallet_id: uuid::Uuid::from_bytes([0x43; 16]),
+            hd_path: "m/44'/60'/0'/0/3".into(),
+            passkey_assertion: None,
+        },
+    );
+    let input: DeriveAddressInput = request.decode().unwrap();
+    assert_eq!(input.hd_path, "m/44'/60'/0'/0/3");
+    assert_eq!(input.chain, proto::chain::Chain::Ethereum);
+
+    // A v1 TA derived for Ethereum whatever was asked; the CA sees that as
+    // an empty `encoded_address`.
+    let bytes = bincode::serialize(&v1::DeriveAddressOutput {
+        address: [0x11; 20],
+        public_key: vec![0x02; 33],
+    })
+    .unwrap();
+    let output: DeriveAddressOutput = wire::decode_output(&bytes).unwrap();
+    assert_eq!(output.address, [0x11; 20]);
+    assert!(output.encoded_address.is_empty());
+}
+
+#[test]
+fn newer_requests_decode() {
+    // A newer peer's appended fields are trailing bytes to this build.
+    for (name, bytes) in golden_v1() {
+        let mut newer = bytes.clone();
+        newer.extend_from_slice(&[0x01, 0xff, 0x00, 0x7f]);
+        assert_eq!(
+            reencode(&name, &newer, PROTOCOL_VERSION + 1),
+            bytes,
+            "{}",
+            name
+        );
+    }
+    let header = RequestHeader::from_hint(
+        RequestHeader {
+            protocol_version: PROTOCOL_VERSION + 1,
+            priority: PRIORITY_BATCH,
+        }
+        .to_hint(),
+    );
+    assert_eq!(header.protocol_version, PROTOCOL_VERSION + 1);
+    assert_eq!(header.priority, PRIORITY_BATCH);
+}
+
+#[test]
+fn unknown_commands_are_rejected_with_the_versions() {
+    // What a TA answers a CA whose command it does not have, as the CA
+    // receives it.
+    let err = UnsupportedVersion {
+        command: 200,
+        ca_version: PROTOCOL_VERSION + 1,
+        ta_version: PROTOCOL_VERSION,
+    };
+    assert_eq!(Command::from(200), Command::Unknown);
+    let received = format!(
+        "TA command failed: {:?} (error: BadParameters)",
+        err.to_string()
+    );
+    assert_eq!(UnsupportedVersion::find(&received), Some(err));
+}
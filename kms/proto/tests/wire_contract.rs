@@ -0,0 +1,262 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! CA ↔ TA wire contract: golden bincode bytes for the messages both sides
+//! exchange. The host and the TA are built and deployed separately, so a field
+//! reorder or type change in `proto` silently breaks a mixed deployment; the
+//! lib.rs roundtrip tests cannot see that (they encode and decode with the same
+//! build). These vectors pin the bytes themselves.
+//!
+//! An intentional wire change regenerates the file:
+//!     KMS_UPDATE_GOLDEN=1 cargo test -p proto --test wire_contract
+//! and the diff of `tests/golden/wire-v1.txt` is then part of the review.
+
+use proto::*;
+use uuid::Uuid;
+
+const GOLDEN: &str = include_str!("golden/wire-v1.txt");
+
+fn wallet() -> Uuid {
+    Uuid::parse_str("4319f351-0b24-4097-b659-80ee4f824cdd").unwrap()
+}
+
+fn assertion() -> PasskeyAssertion {
+    PasskeyAssertion {
+        authenticator_data: vec![0xa5; 37],
+        client_data_hash: [0xbb; 32],
+        signature_r: [0x11; 32],
+        signature_s: [0x22; 32],
+        client_data_json: Some(br#"{"type":"webauthn.get"}"#.to_vec()),
+    }
+}
+
+fn enc<T: serde::Serialize>(v: &T) -> Vec<u8> {
+    bincode::serialize(v).unwrap()
+}
+
+/// (name, encoded bytes, decode-and-reencode check). Order is the file order.
+type Case = (&'static str, Vec<u8>, fn(&[u8]) -> Vec<u8>);
+
+fn cases() -> Vec<Case> {
+    fn re<T: serde::Serialize + serde::de::DeserializeOwned>(b: &[u8]) -> Vec<u8> {
+        enc(&bincode::deserialize::<T>(b).unwrap())
+    }
+    vec![
+        (
+            "CreateWalletInput",
+            enc(&CreateWalletInput {
+                passkey_pubkey: vec![0x04; 65],
+                entropy_seed: Some(vec![0x33; 48]),
+                prf_output: Some([0x44; 32]),
+            }),
+            re::<CreateWalletInput>,
+        ),
+        (
+            "CreateWalletOutput",
+            enc(&CreateWalletOutput {
+                wallet_id: wallet(),
+                mnemonic: String::new(),
+            }),
+            re::<CreateWalletOutput>,
+        ),
+        (
+            "SignTransactionInput",
+            enc(&SignTransactionInput {
+                wallet_id: wallet(),
+                hd_path: "m/44'/60'/0'/0/0".into(),
+                transaction: EthTransaction {
+                    chain_id: 11155111,
+                    nonce: 7,
+                    to: Some([0xde; 20]),
+                    value: 1_000_000_000_000_000_000,
+                    gas_price: 20_000_000_000,
+                    gas: 21_000,
+                    data: vec![0xa9, 0x05, 0x9c, 0xbb],
+                },
+                passkey_assertion: Some(assertion()),
+                summary_abis: Vec::new(),
+                summary_committed: false,
+            }),
+            re::<SignTransactionInput>,
+        ),
+        (
+            "SignMessageInput",
+            enc(&SignMessageInput {
+                wallet_id: wallet(),
+                hd_path: "m/44'/60'/0'/0/0".into(),
+                message: b"hello".to_vec(),
+                passkey_assertion: None,
+                context: SigningContext::PersonalMsg,
+            }),
+            re::<SignMessageInput>,
+        ),
+        (
+            "SignHashInput",
+            enc(&SignHashInput {
+                wallet_id: wallet(),
+                hd_path: "m/44'/60'/0'/0/0".into(),
+                hash: [0xaa; 32],
+                passkey_assertion: Some(assertion()),
+                context: SigningContext::UserOp {
+                    inner_hash: [0x01; 32],
+                    entry_point: [0x02; 20],
+                    chain_id: 10,
+                },
+                fee_quote: Some(paymaster::FeeQuote {
+                    chain_id: 10,
+                    paymaster: [0x03; 20],
+                    token: [0x04; 20],
+                    exchange_rate: 3_000_000_000,
+                    max_token_cost: 5_000_000,
+                    valid_until: 1_800_000_000,
+                    quote_id: [0x05; 32],
+                }),
+            }),
+            re::<SignHashInput>,
+        ),
+        (
+            "SignHashOutput",
+            enc(&SignHashOutput {
+                signature: vec![0x5a; 65],
+            }),
+            re::<SignHashOutput>,
+        ),
+        (
+            "GetChallengeInput",
+            enc(&GetChallengeInput {
+                wallet_id: wallet(),
+            }),
+            re::<GetChallengeInput>,
+        ),
+        (
+            "CreateScopedSessionKeyInput",
+            enc(&CreateScopedSessionKeyInput {
+                wallet_id: wallet(),
+                hd_path: "m/44'/60'/0'/0/0".into(),
+                session_index: 3,
+                permission: SessionPermission {
+                    chain_id: 10,
+                    account: [0xab; 20],
+                    targets: vec![[0x01; 20]],
+                    selectors: vec![[0xa9, 0x05, 0x9c, 0xbb]],
+                    value_cap: 1_000,
+                    expiry: 1_700_000_000,
+                },
+                passkey_assertion: None,
+            }),
+            re::<CreateScopedSessionKeyInput>,
+        ),
+        (
+            "SignWithSessionKeyInput",
+            enc(&SignWithSessionKeyInput {
+                wallet_id: wallet(),
+                session_index: 3,
+                chain_id: 10,
+                call: SessionCall {
+                    target: [0x01; 20],
+                    selector: [0xa9, 0x05, 0x9c, 0xbb],
+                    value: 0,
+                },
+                user_op_hash: [0xcc; 32],
+            }),
+            re::<SignWithSessionKeyInput>,
+        ),
+    ]
+}
+
+fn parse_golden() -> Vec<(String, Vec<u8>)> {
+    GOLDEN
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(|l| {
+            let (name, hex) = l.split_once('=').expect("golden line must be name=hex");
+            let bytes = (0..hex.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+                .collect();
+            (name.to_string(), bytes)
+        })
+        .collect()
+}
+
+fn to_hex(b: &[u8]) -> String {
+    b.iter().map(|x| format!("{:02x}", x)).collect()
+}
+
+#[test]
+fn wire_bytes_match_golden() {
+    let cases = cases();
+    if std::env::var("KMS_UPDATE_GOLDEN").ok().as_deref() == Some("1") {
+        let mut out =
+            String::from("# Generated by tests/wire_contract.rs — do not edit by hand.\n");
+        for (name, bytes, _) in &cases {
+            out.push_str(&format!("{}={}\n", name, to_hex(bytes)));
+        }
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/wire-v1.txt");
+        std::fs::write(path, out).unwrap();
+        return;
+    }
+    let golden = parse_golden();
+    assert_eq!(
+        golden.iter().map(|(n, _)| n.as_str()).collect::<Vec<_>>(),
+        cases.iter().map(|(n, _, _)| *n).collect::<Vec<_>>(),
+        "golden file and test cases list different messages"
+    );
+    for ((name, bytes, _), (_, want)) in cases.iter().zip(golden.iter()) {
+        assert_eq!(
+            to_hex(bytes),
+            to_hex(want),
+            "{}: wire encoding changed — old peers will misparse it",
+            name
+        );
+    }
+}
+
+#[test]
+fn golden_bytes_decode_with_current_types() {
+    for ((name, _, reencode), (_, want)) in cases().iter().zip(parse_golden().iter()) {
+        assert_eq!(
+            &reencode(want),
+            want,
+            "{}: golden bytes no longer decode",
+            name
+        );
+    }
+}
+
+#[test]
+fn command_ids_pinned() {
+    // The TA dispatches on the raw u32; the CA sends `command as u32`.
+    let pinned: &[(Command, u32)] = &[
+        (Command::CreateWallet, 0),
+        (Command::SignTransaction, 3),
+        (Command::SignMessage, 4),
+        (Command::SignHash, 5),
+        (Command::GetChallenge, 25),
+        (Command::GetAttestation, 26),
+        (Command::KeeperSign, 31),
+        (Command::BlsPopSign, 34),
+        (Command::CreateScopedSessionKey, 35),
+        (Command::SignWithSessionKey, 36),
+        (Command::RevokeScopedSessionKey, 37),
+    ];
+    for (cmd, id) in pinned {
+        assert_eq!(u32::from(*cmd), *id, "{:?}", cmd);
+        assert_eq!(Command::from(*id), *cmd);
+    }
+}
@@ -33,6 +33,16 @@ fn main() -> Result<(), Error> {
     }
     cc_build.compile("p256m");
 
+    // InstallConfig signer, read by src/ta_config.rs via option_env!. Checked
+    // here so a typo fails the build instead of shipping an image that
+    // silently refuses every config.
+    println!("cargo:rerun-if-env-changed=AIRACCOUNT_PROVISIONING_KEY");
+    if let Ok(key) = std::env::var("AIRACCOUNT_PROVISIONING_KEY") {
+        if let Err(e) = proto::eip55::parse_address(&key) {
+            panic!("AIRACCOUNT_PROVISIONING_KEY: {}", e);
+        }
+    }
+
     let ta_config = TaConfig::new_default_with_cargo_env(proto::UUID)?
         .ta_data_size(1024 * 1024)
         .ta_stack_size(128 * 1024);
@@ -15,14 +15,21 @@
 // specific language governing permissions and limitations
 // under the License.
 
-use serde::Serialize;
-use sha3::{Digest, Keccak256};
+//! The TA's key derivation and signing, built for the host.
+//!
+//! The TA crate only builds against the OP-TEE SDK, so `bip32_secp.rs` and
+//! `stealth.rs` — pure Rust over libsecp256k1 — are compiled here from the
+//! same files and checked against published vectors in `tests/derivation.rs`. An edit to it that
+//! changes a single derived byte fails CI before an image is built.
+//! `self_test.rs`, the known-answer half of the TA's `SelfTest`, is built
+//! here too, so a check that cannot pass is caught before it quarantines a
+//! fleet.
 
-pub fn keccak_hash_to_bytes<T>(data: &T) -> Vec<u8>
-where
-    T: ?Sized + Serialize + AsRef<[u8]>,
-{
-    let mut hasher = Keccak256::new();
-    hasher.update(data);
-    hasher.finalize().to_vec()
-}
+#[path = "../../src/bip32_secp.rs"]
+pub mod bip32_secp;
+
+#[path = "../../src/stealth.rs"]
+pub mod stealth;
+
+#[path = "../../src/self_test.rs"]
+pub mod self_test;
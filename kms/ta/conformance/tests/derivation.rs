@@ -0,0 +1,262 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Every line of `vectors/derivation.txt` against `bip32_secp`: keys through
+//! both the cached and the uncached account root, EIP-55 addresses, Tron
+//! addresses under coin type 195, signatures byte for byte (RFC 6979 nonce,
+//! low-S, v = 27/28), ECIES envelopes opened with the TA's ECDH, and
+//! EIP-5564 meta-addresses and announcements through `stealth`.
+//!
+//! The vectors are external — never regenerate them from this code. A
+//! failure here means the TA would derive different addresses than every
+//! other wallet for the same mnemonic.
+
+use pbkdf2::pbkdf2_hmac;
+use sha2::Sha512;
+use ta_conformance::bip32_secp;
+use ta_conformance::stealth::StealthKeys;
+
+const VECTORS: &str = include_str!("../vectors/derivation.txt");
+const HARDENED: u32 = 0x8000_0000;
+const TRON: u32 = proto::chain::TRON_COIN_TYPE;
+
+fn unhex(s: &str) -> Vec<u8> {
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+fn key(s: &str) -> [u8; 32] {
+    let mut k = [0u8; 32];
+    k.copy_from_slice(&unhex(s));
+    k
+}
+
+/// BIP39: PBKDF2-HMAC-SHA512(mnemonic, "mnemonic", 2048), as the TA's
+/// `Mnemonic::to_seed("")`.
+fn bip39_seed(words: &str) -> Vec<u8> {
+    let mut seed = vec![0u8; 64];
+    pbkdf2_hmac::<Sha512>(words.as_bytes(), b"mnemonic", 2048, &mut seed);
+    seed
+}
+
+/// `m/44'/0'` → [44, 0] when every level is hardened, parsed by the TA's
+/// own `proto::hd_path`.
+fn hardened_levels(path: &str) -> Option<Vec<u32>> {
+    proto::hd_path::parse_path(path).ok()?.hardened_levels()
+}
+
+struct Counts {
+    hardened: usize,
+    eth: usize,
+    tron: usize,
+    signatures: usize,
+    envelopes: usize,
+    stealth: usize,
+}
+
+fn check_all() -> Counts {
+    let mut counts = Counts {
+        hardened: 0,
+        eth: 0,
+        tron: 0,
+        signatures: 0,
+        envelopes: 0,
+        stealth: 0,
+    };
+    let mut seed: Option<Vec<u8>> = None;
+    for (n, line) in VECTORS.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let at = format!("derivation.txt:{}: {}", n + 1, line);
+        let (head, rest) = line.split_once(' ').expect(&at);
+        match head {
+            "seed" => seed = Some(unhex(rest)),
+            "mnemonic" => seed = Some(bip39_seed(rest)),
+            "sign" => {
+                let fields: Vec<&str> = rest.split(' ').collect();
+                let signature =
+                    bip32_secp::sign_recoverable(&key(fields[0]), &key(fields[1])).expect(&at);
+                assert_eq!(hex(&signature), fields[2], "{}", at);
+                counts.signatures += 1;
+            }
+            "ecies" => {
+                let fields: Vec<&str> = rest.split(' ').collect();
+                let payload = unhex(fields[1]);
+                let envelope = proto::ecies::Envelope::parse(&payload).expect(&at);
+                let point =
+                    bip32_secp::ecdh_point(&key(fields[0]), envelope.ephemeral_pubkey).expect(&at);
+                assert_eq!(hex(&envelope.open(&point).expect(&at)), fields[2], "{}", at);
+                counts.envelopes += 1;
+            }
+            "stealth" => {
+                let (account, meta) = rest.split_once(' ').expect(&at);
+                let keys =
+                    StealthKeys::derive(seed.as_deref().expect(&at), account.parse().unwrap())
+                        .expect(&at);
+                assert_eq!(keys.meta_address().to_string(), meta, "{}", at);
+                counts.stealth += 1;
+            }
+            "announce" => {
+                let fields: Vec<&str> = rest.split(' ').collect();
+                let account: u32 = fields[0].parse().unwrap();
+                let seed = seed.as_deref().expect(&at);
+                let mut address = [0u8; 20];
+                address.copy_from_slice(&unhex(&fields[3][2..]));
+                let candidate = proto::stealth::StealthCandidate {
+                    stealth_address: address,
+                    ephemeral_pubkey: unhex(fields[1]),
+                    view_tag: unhex(fields[2])[0],
+                };
+                let mut wrong_tag = candidate.clone();
+                wrong_tag.view_tag ^= 1;
+                let mut not_a_point = candidate.clone();
+                not_a_point.ephemeral_pubkey[0] = 0x05;
+                let batch = [wrong_tag, candidate.clone(), not_a_point];
+                let keys = StealthKeys::derive(seed, account).expect(&at);
+                assert_eq!(keys.scan(&batch), vec![1], "{}", at);
+                let other = StealthKeys::derive(seed, account + 1).expect(&at);
+                assert!(other.scan(&[candidate]).is_empty(), "{}", at);
+                assert_eq!(
+                    proto::eip55::to_checksum_address(&address),
+                    fields[3],
+                    "{}",
+                    at
+                );
+                counts.stealth += 1;
+            }
+            path => {
+                let seed = seed.as_deref().expect(&at);
+                let fields: Vec<&str> = rest.split(' ').collect();
+                if let Some(levels) = hardened_levels(path) {
+                    let k = bip32_secp::derive_hardened_key(seed, &levels).expect(&at);
+                    assert_eq!(hex(&k), fields[0], "{}", at);
+                    if levels == [44, 60, 0] {
+                        let root = bip32_secp::compute_account_root(seed).expect(&at);
+                        assert_eq!(hex(&root.key), fields[0], "{}", at);
+                    }
+                    counts.hardened += 1;
+                    continue;
+                }
+                if let Ok((account, address)) = proto::hd_path::parse_coin_path(path, TRON) {
+                    let key =
+                        bip32_secp::derive_coin_full(seed, TRON, account, address).expect(&at);
+                    assert_eq!(hex(&key.private_key), fields[0], "{}", at);
+                    assert_eq!(
+                        proto::chain::tron_address(&key.eth_address()),
+                        fields[1],
+                        "{}",
+                        at
+                    );
+                    counts.tron += 1;
+                    continue;
+                }
+                let (account, address) = bip32_secp::parse_eth_path(path).expect(&at);
+                let root = bip32_secp::compute_account_root(seed).expect(&at);
+                let cold = bip32_secp::derive_full(seed, None, account, address).expect(&at);
+                let warm = bip32_secp::derive_full(seed, Some(&root), account, address).expect(&at);
+                assert_eq!(hex(&cold.private_key), fields[0], "{}", at);
+                assert_eq!(warm.private_key, cold.private_key, "{} (cached root)", at);
+                assert_eq!(
+                    proto::eip55::to_checksum_address(&cold.eth_address()),
+                    fields[1],
+                    "{}",
+                    at
+                );
+                assert_eq!(
+                    &cold.public_key_uncompressed[1..33],
+                    &cold.public_key_compressed[1..],
+                    "{}",
+                    at
+                );
+                counts.eth += 1;
+            }
+        }
+    }
+    counts
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[test]
+fn derivation_matches_the_published_vectors() {
+    let counts = check_all();
+    // A vector file that silently stopped parsing would pass with nothing
+    // checked.
+    assert!(
+        counts.hardened >= 10,
+        "{} hardened vectors",
+        counts.hardened
+    );
+    assert!(counts.eth >= 28, "{} eth vectors", counts.eth);
+    assert!(counts.tron >= 8, "{} tron vectors", counts.tron);
+    assert!(counts.signatures >= 5, "{} signatures", counts.signatures);
+    assert!(counts.envelopes >= 2, "{} envelopes", counts.envelopes);
+    assert!(counts.stealth >= 5, "{} stealth vectors", counts.stealth);
+}
+
+#[test]
+fn the_account_root_survives_its_cache_encoding() {
+    let seed = bip39_seed("test test test test test test test test test test test junk");
+    let root = bip32_secp::compute_account_root(&seed).unwrap();
+    let restored = bip32_secp::CachedXPrv::deserialize(&root.serialize()).unwrap();
+    let key = bip32_secp::derive_full(&seed, Some(&restored), 0, 0).unwrap();
+    assert_eq!(
+        proto::eip55::to_checksum_address(&key.eth_address()),
+        "0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266"
+    );
+    assert!(bip32_secp::CachedXPrv::deserialize(&root.serialize()[..96]).is_err());
+    assert!(bip32_secp::derive_hardened_key(&seed, &[44 | HARDENED]).is_err());
+    assert!(bip32_secp::parse_eth_path("m/44'/60'/0'/0/2147483648").is_err());
+}
+
+#[test]
+fn self_test_known_answers_pass_on_the_host() {
+    let checks = ta_conformance::self_test::known_answer_tests();
+    assert_eq!(checks.len(), 5);
+    for c in &checks {
+        assert!(c.passed, "{}: {}", c.name, c.detail);
+    }
+    let failed = ta_conformance::self_test::check("broken", || Err("differs".to_string()));
+    assert!(!failed.passed);
+    assert_eq!(failed.detail, "differs");
+}
+
+#[test]
+fn wallet_id_is_stable_across_re_creation() {
+    let id = |words: &str| {
+        let root = bip32_secp::compute_account_root(&bip39_seed(words)).unwrap();
+        proto::wallet_id::from_account_pubkey(&root.pubkey)
+    };
+    let hardhat = "test test test test test test test test test test test junk";
+    // Pinned: a change here re-keys every stored wallet.
+    assert_eq!(
+        id(hardhat).to_string(),
+        "449eb30a-8f38-85b0-96f0-8947cb2375af"
+    );
+    assert_eq!(id(hardhat), id(hardhat));
+    assert_ne!(
+        id(hardhat),
+        id("abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about")
+    );
+    assert_eq!(id(hardhat).get_version_num(), 8);
+}
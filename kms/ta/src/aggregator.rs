@@ -0,0 +1,65 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! The aggregator a wallet signs userOps for (`proto::aggregator`), and
+//! the BLS key it signs them with. The key is not stored: it is derived
+//! from the wallet seed on every use, so it goes wherever the wallet goes
+//! (backup, replication) and dies with it.
+
+use anyhow::Result;
+use proto::aggregator::key_levels;
+use secure_db::Storable;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::bip32_secp::derive_hardened_key;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AggregatorScheme {
+    pub store_id: String,
+    /// None = the wallet signs userOps with ECDSA only.
+    pub aggregator: Option<[u8; 20]>,
+}
+
+impl Storable for AggregatorScheme {
+    type Key = String;
+    fn unique_id(&self) -> Self::Key {
+        self.store_id.clone()
+    }
+}
+
+impl AggregatorScheme {
+    pub fn store_id_for(wallet_id: &Uuid) -> String {
+        format!("aggsig_{}", wallet_id)
+    }
+
+    pub fn empty(wallet_id: &Uuid) -> Self {
+        Self {
+            store_id: Self::store_id_for(wallet_id),
+            aggregator: None,
+        }
+    }
+}
+
+/// The wallet's BLS secret key: the hardened child at `key_levels` is the
+/// IKM of the BLS KeyGen, as `bls::gen_keypair` uses TRNG output.
+pub fn derive_key(seed: &[u8]) -> Result<[u8; 32]> {
+    let mut ikm = derive_hardened_key(seed, &key_levels())?;
+    let keypair = crate::bls::gen_keypair(&ikm);
+    ikm.fill(0);
+    Ok(keypair?.0)
+}
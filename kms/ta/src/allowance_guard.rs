@@ -0,0 +1,403 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Allowance guard: per-wallet rule over decoded ERC-20 / ERC-721 approvals.
+//!
+//! Checked in SignTransaction against the TA's own calldata decode. Breaking
+//! the rule needs a second, separate passkey confirmation
+//! (`ConfirmAllowanceOverride`) whose challenge commits to the same summary
+//! digest the sign assertion commits to, so the override is single-use and
+//! only valid for the transaction the user actually saw.
+//!
+//! A separate spender allow-list, when non-empty, limits who may be granted
+//! an allowance at all — by calldata or by a signed EIP-2612 / Permit2
+//! permit. It lives in its own record so the stored rule keeps its layout.
+
+use proto::calldata::{hex_addr, is_unlimited, DecodedCall, Word};
+use proto::permit::PermitGrant;
+use proto::AllowanceRule;
+use secure_db::Storable;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+/// An armed override expires with the challenge that would use it (the TA
+/// config's override-ttl, 300s by default).
+pub fn override_ttl_secs() -> i64 {
+    crate::ta_config::limits().override_ttl_secs as i64
+}
+
+/// Bundled signatures that grant allowance. A call with one of these
+/// selectors that did not decode cleanly (e.g. dirty address padding, which
+/// pre-0.5 Solidity tokens silently truncate) counts as a violation.
+const GUARDED_SIGNATURES: &[&str] = &[
+    "approve(address,uint256)",
+    "increaseAllowance(address,uint256)",
+    "setApprovalForAll(address,bool)",
+    "permit(address,address,uint256,uint256,uint8,bytes32,bytes32)",
+];
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PendingOverride {
+    pub confirmation_digest: [u8; 32],
+    pub armed_at: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AllowancePolicy {
+    pub store_id: String,
+    pub rule: AllowanceRule,
+    pub pending_override: Option<PendingOverride>,
+}
+
+impl Storable for AllowancePolicy {
+    type Key = String;
+    fn unique_id(&self) -> Self::Key {
+        self.store_id.clone()
+    }
+}
+
+impl AllowancePolicy {
+    pub fn store_id_for(wallet_id: &Uuid) -> String {
+        format!("allowpol_{}", wallet_id)
+    }
+
+    pub fn empty(wallet_id: &Uuid) -> Self {
+        Self {
+            store_id: Self::store_id_for(wallet_id),
+            rule: AllowanceRule::Off,
+            pending_override: None,
+        }
+    }
+
+    /// One override at a time; arming a new one replaces the old.
+    pub fn arm_override(&mut self, confirmation_digest: [u8; 32], now: i64) {
+        self.pending_override = Some(PendingOverride {
+            confirmation_digest,
+            armed_at: now,
+        });
+    }
+
+    /// True (and cleared; the caller persists) if a fresh override for exactly
+    /// this digest was armed. A mismatched one is left in place so a wrong
+    /// request cannot burn the user's confirmation.
+    pub fn take_override(&mut self, confirmation_digest: &[u8; 32], now: i64) -> bool {
+        let ok = match &self.pending_override {
+            Some(p) => {
+                let age = now.saturating_sub(p.armed_at);
+                &p.confirmation_digest == confirmation_digest
+                    && (0..=override_ttl_secs()).contains(&age)
+            }
+            None => false,
+        };
+        if ok {
+            self.pending_override = None;
+        }
+        ok
+    }
+}
+
+/// Longest spender allow-list a wallet may hold (TA config, 32 by default).
+pub fn max_allowed_spenders() -> usize {
+    crate::ta_config::limits().max_allowed_spenders as usize
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SpenderAllowList {
+    pub store_id: String,
+    /// Sorted, no duplicates. Empty = any spender.
+    pub spenders: Vec<[u8; 20]>,
+}
+
+impl Storable for SpenderAllowList {
+    type Key = String;
+    fn unique_id(&self) -> Self::Key {
+        self.store_id.clone()
+    }
+}
+
+impl SpenderAllowList {
+    pub fn store_id_for(wallet_id: &Uuid) -> String {
+        format!("spenders_{}", wallet_id)
+    }
+
+    pub fn empty(wallet_id: &Uuid) -> Self {
+        Self {
+            store_id: Self::store_id_for(wallet_id),
+            spenders: Vec::new(),
+        }
+    }
+
+    pub fn set(&mut self, spenders: &[[u8; 20]]) -> Result<(), &'static str> {
+        if spenders.len() > max_allowed_spenders() {
+            return Err("spender allow-list is longer than the TA config allows");
+        }
+        if spenders.contains(&[0u8; 20]) {
+            return Err("the zero address cannot be an allowed spender");
+        }
+        let mut list = spenders.to_vec();
+        list.sort_unstable();
+        list.dedup();
+        self.spenders = list;
+        Ok(())
+    }
+
+    fn check(&self, spender: &[u8; 20]) -> Option<String> {
+        if self.spenders.is_empty() || self.spenders.binary_search(spender).is_ok() {
+            None
+        } else {
+            Some(format!(
+                "spender {} is not on the allow-list",
+                hex_addr(spender)
+            ))
+        }
+    }
+}
+
+/// True when either guard can refuse something.
+pub fn active(rule: &AllowanceRule, list: &SpenderAllowList) -> bool {
+    *rule != AllowanceRule::Off || !list.spenders.is_empty()
+}
+
+fn rule_bytes(rule: &AllowanceRule) -> [u8; 17] {
+    let mut out = [0u8; 17];
+    match rule {
+        AllowanceRule::Off => {}
+        AllowanceRule::RejectUnlimited => out[0] = 1,
+        AllowanceRule::Cap { max } => {
+            out[0] = 2;
+            out[1..].copy_from_slice(&max.to_be_bytes());
+        }
+    }
+    out
+}
+
+/// Passkey commitment for SetAllowancePolicy: the CA cannot swap the rule.
+pub fn policy_commitment(wallet_id: &Uuid, rule: &AllowanceRule) -> [u8; 32] {
+    let mut h = Sha256::new();
+    h.update(b"AA-ALLOWANCE-POLICY-v1");
+    h.update(wallet_id.as_bytes());
+    h.update(rule_bytes(rule));
+    h.finalize().into()
+}
+
+/// Passkey commitment for the override. Distinct tag, so the sign assertion
+/// (committed to the bare confirmation digest) can never double as it.
+pub fn override_commitment(wallet_id: &Uuid, confirmation_digest: &[u8; 32]) -> [u8; 32] {
+    let mut h = Sha256::new();
+    h.update(b"AA-ALLOWANCE-OVERRIDE-v1");
+    h.update(wallet_id.as_bytes());
+    h.update(confirmation_digest);
+    h.finalize().into()
+}
+
+/// Passkey commitment for SetSpenderAllowList, over the list as sent.
+pub fn spender_list_commitment(wallet_id: &Uuid, spenders: &[[u8; 20]]) -> [u8; 32] {
+    let mut h = Sha256::new();
+    h.update(b"AA-SPENDER-ALLOWLIST-v1");
+    h.update(wallet_id.as_bytes());
+    h.update((spenders.len() as u32).to_be_bytes());
+    for s in spenders {
+        h.update(s);
+    }
+    h.finalize().into()
+}
+
+fn check_amount(rule: &AllowanceRule, amount: &Word) -> Option<String> {
+    let unlimited = is_unlimited(amount);
+    match rule {
+        AllowanceRule::Off => None,
+        AllowanceRule::RejectUnlimited if unlimited => Some("unlimited approval".into()),
+        AllowanceRule::RejectUnlimited => None,
+        AllowanceRule::Cap { max } => {
+            let mut lo = [0u8; 16];
+            lo.copy_from_slice(&amount[16..]);
+            let value = u128::from_be_bytes(lo);
+            if unlimited {
+                Some(format!("unlimited approval (cap {})", max))
+            } else if value > *max {
+                Some(format!("approval of {} exceeds cap {}", value, max))
+            } else {
+                None
+            }
+        }
+    }
+}
+
+/// `Some(reason)` when `call` breaks `rule`.
+pub fn violation(rule: &AllowanceRule, call: &DecodedCall) -> Option<String> {
+    if *rule == AllowanceRule::Off {
+        return None;
+    }
+    match call {
+        DecodedCall::Approve { amount, .. }
+        | DecodedCall::IncreaseAllowance { amount, .. }
+        | DecodedCall::Permit { amount, .. } => check_amount(rule, amount),
+        DecodedCall::SetApprovalForAll { approved: true, .. } => {
+            Some("approval for all items of a collection".into())
+        }
+        DecodedCall::Named {
+            signature,
+            user_supplied: false,
+            ..
+        } if GUARDED_SIGNATURES.contains(&signature.as_str()) => {
+            Some(format!("undecodable {} call", signature))
+        }
+        _ => None,
+    }
+}
+
+/// `Some(reason)` when `call` grants an allowance to a spender outside `list`.
+pub fn spender_violation(list: &SpenderAllowList, call: &DecodedCall) -> Option<String> {
+    if list.spenders.is_empty() {
+        return None;
+    }
+    match call {
+        DecodedCall::Approve { spender, .. }
+        | DecodedCall::IncreaseAllowance { spender, .. }
+        | DecodedCall::Permit { spender, .. }
+        | DecodedCall::SetApprovalForAll {
+            operator: spender,
+            approved: true,
+            ..
+        } => list.check(spender),
+        DecodedCall::Named {
+            signature,
+            user_supplied: false,
+            ..
+        } if GUARDED_SIGNATURES.contains(&signature.as_str()) => {
+            Some(format!("undecodable {} call", signature))
+        }
+        _ => None,
+    }
+}
+
+/// `Some(reason)` when a signed permit breaks the rule or the allow-list.
+pub fn permit_violation(
+    rule: &AllowanceRule,
+    list: &SpenderAllowList,
+    grant: &PermitGrant,
+) -> Option<String> {
+    list.check(&grant.spender)
+        .or_else(|| check_amount(rule, &grant.amount))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn approve(amount: Word) -> DecodedCall {
+        DecodedCall::Approve {
+            token: [1; 20],
+            spender: [2; 20],
+            amount,
+        }
+    }
+
+    fn uint(v: u128) -> Word {
+        let mut w = [0u8; 32];
+        w[16..].copy_from_slice(&v.to_be_bytes());
+        w
+    }
+
+    #[test]
+    fn rules_over_decoded_approvals() {
+        let unlimited = approve([0xff; 32]);
+        let small = approve(uint(500));
+        assert!(violation(&AllowanceRule::Off, &unlimited).is_none());
+        assert!(violation(&AllowanceRule::RejectUnlimited, &unlimited).is_some());
+        assert!(violation(&AllowanceRule::RejectUnlimited, &small).is_none());
+        let cap = AllowanceRule::Cap { max: 1000 };
+        assert!(violation(&cap, &small).is_none());
+        assert!(violation(&cap, &approve(uint(1001))).is_some());
+        assert!(violation(&cap, &unlimited).is_some());
+        let nft = DecodedCall::SetApprovalForAll {
+            collection: [1; 20],
+            operator: [2; 20],
+            approved: true,
+        };
+        assert!(violation(&cap, &nft).is_some());
+        let malformed = DecodedCall::Named {
+            target: [1; 20],
+            signature: "approve(address,uint256)".into(),
+            user_supplied: false,
+        };
+        assert!(violation(&AllowanceRule::RejectUnlimited, &malformed).is_some());
+        let transfer = DecodedCall::Transfer {
+            token: [1; 20],
+            to: [2; 20],
+            amount: [0xff; 32],
+        };
+        assert!(violation(&cap, &transfer).is_none());
+    }
+
+    #[test]
+    fn spender_allow_list() {
+        let w = Uuid::from_bytes([9; 16]);
+        let mut list = SpenderAllowList::empty(&w);
+        assert!(spender_violation(&list, &approve([0xff; 32])).is_none());
+        assert!(list.set(&[[0; 20]]).is_err());
+        assert!(list
+            .set(&vec![[0x11; 20]; max_allowed_spenders() + 1])
+            .is_err());
+        list.set(&[[3; 20], [2; 20], [3; 20]]).unwrap();
+        assert_eq!(list.spenders, vec![[2; 20], [3; 20]]);
+        assert!(active(&AllowanceRule::Off, &list));
+        assert!(spender_violation(&list, &approve(uint(1))).is_none());
+        let nft = DecodedCall::SetApprovalForAll {
+            collection: [1; 20],
+            operator: [4; 20],
+            approved: true,
+        };
+        assert!(spender_violation(&list, &nft).is_some());
+
+        let grant = PermitGrant {
+            chain_id: 1,
+            token: [1; 20],
+            spender: [2; 20],
+            amount: [0xff; 32],
+        };
+        assert!(permit_violation(&AllowanceRule::Off, &list, &grant).is_none());
+        assert!(permit_violation(&AllowanceRule::RejectUnlimited, &list, &grant).is_some());
+        let stranger = PermitGrant {
+            spender: [5; 20],
+            amount: uint(1),
+            ..grant
+        };
+        assert!(permit_violation(&AllowanceRule::Off, &list, &stranger).is_some());
+        assert_ne!(
+            spender_list_commitment(&w, &[[2; 20]]),
+            spender_list_commitment(&w, &[[2; 20], [3; 20]])
+        );
+    }
+
+    #[test]
+    fn override_is_single_use_and_bound() {
+        let w = Uuid::from_bytes([9; 16]);
+        let mut p = AllowancePolicy::empty(&w);
+        p.arm_override([1; 32], 100);
+        assert!(!p.take_override(&[2; 32], 110));
+        assert!(!p.take_override(&[1; 32], 100 + override_ttl_secs() + 1));
+        assert!(p.take_override(&[1; 32], 110));
+        assert!(!p.take_override(&[1; 32], 111));
+        assert_ne!(
+            policy_commitment(&w, &AllowanceRule::Cap { max: 1 }),
+            policy_commitment(&w, &AllowanceRule::Cap { max: 2 })
+        );
+        assert_ne!(override_commitment(&w, &[1; 32]), [1; 32]);
+    }
+}
@@ -44,7 +44,11 @@
 //! `docs/design/37-remote-attestation-design.md` §9 (R-1).
 
 use anyhow::{anyhow, bail, Result};
-use optee_utee::{ParamIndex, TaSession, TaSessionBuilder, TeeParams, Time, Uuid};
+use optee_utee::{
+    AlgorithmId, Asymmetric, AttributeId, AttributeMemref, OperationMode, ParamIndex, TaSession,
+    TaSessionBuilder, TeeParams, TransientObject, TransientObjectType, Uuid,
+};
+use sha2::{Digest, Sha256};
 
 /// OP-TEE attestation PTA UUID (lib/libutee/include/pta_attestation.h).
 const PTA_ATTESTATION_UUID: &str = "39800861-182a-4720-9b67-2bcd622bc0b5";
@@ -104,9 +108,7 @@ pub fn get_attestation(
         get_ta_shdr_digest(&mut session, &pta_uuid_bytes, &input.nonce)?;
     let (attest_pubkey_exp, attest_pubkey_mod, sig_alg) = get_pubkey(&mut session)?;
 
-    let mut t = Time::new();
-    t.ree_time();
-    let ree_time_secs = t.seconds as u64;
+    let ree_time_secs = crate::provider::unix_secs();
 
     Ok(proto::GetAttestationOutput {
         nonce: input.nonce.clone(),
@@ -181,3 +183,64 @@ fn get_pubkey(session: &mut TaSession) -> Result<(Vec<u8>, Vec<u8>, u32)> {
     }
     Ok((exp, modulus, sig_alg))
 }
+
+/// Check another device's evidence: the RSA-PSS signature over
+/// `SHA256(nonce | ta_measurement)` under the public key it carries. Used by
+/// seed replication, where the peer is a second board running this TA.
+///
+/// This proves the evidence came from whoever holds that attestation key;
+/// whether the key belongs to a known device is the operator's call (the CA
+/// pins each device's key fingerprint at enrolment), since the PTA key has no
+/// certificate chain — see the trust-root caveat above.
+pub fn verify_peer_evidence(evidence: &proto::GetAttestationOutput) -> Result<()> {
+    if evidence.sig_alg != AlgorithmId::RsassaPkcs1PssMgf1Sha256 as u32 {
+        bail!(
+            "unexpected attestation algorithm 0x{:08x}",
+            evidence.sig_alg
+        );
+    }
+    let bits = evidence.attest_pubkey_mod.len() * 8;
+    if !(2048..=4096).contains(&bits) || evidence.signature.len() * 8 != bits {
+        bail!("attestation key / signature size mismatch ({} bits)", bits);
+    }
+
+    let mut digest_input = Sha256::new();
+    digest_input.update(&evidence.nonce);
+    digest_input.update(&evidence.ta_measurement);
+    let digest = digest_input.finalize();
+
+    let mut key = TransientObject::allocate(TransientObjectType::RsaPublicKey, bits)
+        .map_err(|e| anyhow!("allocate RSA key object: {:?}", e))?;
+    let modulus = AttributeMemref::from_ref(AttributeId::RsaModulus, &evidence.attest_pubkey_mod);
+    let exponent =
+        AttributeMemref::from_ref(AttributeId::RsaPublicExponent, &evidence.attest_pubkey_exp);
+    key.populate(&[modulus.into(), exponent.into()])
+        .map_err(|e| anyhow!("load peer attestation key: {:?}", e))?;
+
+    let op = Asymmetric::allocate(
+        AlgorithmId::RsassaPkcs1PssMgf1Sha256,
+        OperationMode::Verify,
+        bits,
+    )
+    .map_err(|e| anyhow!("allocate RSA-PSS verify: {:?}", e))?;
+    op.set_key(&key)
+        .map_err(|e| anyhow!("set peer attestation key: {:?}", e))?;
+    op.verify_digest(&[], &digest, &evidence.signature)
+        .map_err(|_| anyhow!("peer attestation signature is invalid"))
+}
+
+/// keccak256(modulus ‖ exponent) of this device's attestation key — the
+/// device id a tamper order can be addressed to, and the fingerprint the CA
+/// pins for replication peers.
+pub fn device_fingerprint() -> Result<[u8; 32]> {
+    let pta_uuid = Uuid::parse_str(PTA_ATTESTATION_UUID)
+        .map_err(|e| anyhow!("invalid attestation PTA UUID: {:?}", e))?;
+    let mut session = TaSessionBuilder::new(pta_uuid)
+        .build()
+        .map_err(|e| anyhow!("open attestation PTA session failed: {:?}", e))?;
+    let (exp, modulus, _) = get_pubkey(&mut session)?;
+    let mut h = sha3::Keccak256::new();
+    h.update(&modulus);
+    h.update(&exp);
+    Ok(h.finalize().into())
+}
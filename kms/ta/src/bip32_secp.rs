@@ -10,10 +10,15 @@
 /// Point multiplication count for m/44'/60'/0'/0/N:
 ///   First call (no cache):  3 hardened(0) + 2 normal(2) + final(0, point-add) = 2
 ///   Cached call:            read cache(0) + 2 normal(2) + final(0, point-add) = 2
+///
+/// Keys, addresses and signatures from this module are checked byte for byte
+/// against BIP32 / BIP44 / ethers vectors by `ta/conformance`, which builds
+/// this file on a plain host.
 use anyhow::{anyhow, Result};
 use hmac::{Hmac, Mac};
-use secp256k1::{PublicKey, Scalar, Secp256k1, SecretKey};
+use secp256k1::{Message, PublicKey, Scalar, Secp256k1, SecretKey};
 use sha2::Sha512;
+use sha3::{Digest, Keccak256};
 
 type HmacSha512 = Hmac<Sha512>;
 
@@ -30,6 +35,33 @@ pub struct DerivedKey {
     pub public_key_uncompressed: [u8; 65],
 }
 
+impl DerivedKey {
+    /// keccak256(uncompressed pubkey without the 0x04 prefix), last 20 bytes.
+    pub fn eth_address(&self) -> [u8; 20] {
+        let hash = Keccak256::digest(&self.public_key_uncompressed[1..]);
+        let mut address = [0u8; 20];
+        address.copy_from_slice(&hash[12..]);
+        address
+    }
+}
+
+impl Drop for DerivedKey {
+    fn drop(&mut self) {
+        wipe(&mut self.private_key);
+    }
+}
+
+/// Zero key material that is about to go out of scope. Volatile writes + fence:
+/// a plain store to memory that is never read again is a dead write the
+/// optimiser may drop (same as `CachedChildKey`'s Drop in key_cache.rs).
+pub fn wipe(bytes: &mut [u8]) {
+    for b in bytes.iter_mut() {
+        // SAFETY: `b` is a valid, aligned &mut u8.
+        unsafe { core::ptr::write_volatile(b, 0) };
+    }
+    core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+}
+
 /// Cached intermediate extended private key (m/44'/60'/0').
 /// Stored as 97 bytes: key(32) + chain(32) + compressed_pubkey(33)
 pub struct CachedXPrv {
@@ -75,16 +107,20 @@ fn hmac_sha512(key: &[u8], data: &[u8]) -> [u8; 64] {
 /// seed → HMAC-SHA512("Bitcoin seed", seed) → (master_key, master_chain)
 /// Zero point multiplications.
 fn master_key_from_seed(seed: &[u8]) -> Result<([u8; 32], [u8; 32])> {
-    let hmac_out = hmac_sha512(BIP32_SEED_KEY, seed);
+    let mut hmac_out = hmac_sha512(BIP32_SEED_KEY, seed);
     let mut key = [0u8; 32];
     let mut chain = [0u8; 32];
     key.copy_from_slice(&hmac_out[..32]);
     chain.copy_from_slice(&hmac_out[32..]);
+    wipe(&mut hmac_out);
     // Validate: must be a valid secret key (non-zero, < curve order)
     SecretKey::from_slice(&key).map_err(|e| anyhow!("Invalid master key: {}", e))?;
     Ok((key, chain))
 }
 
+/// (child key, child chain code, parent compressed pubkey if it was computed).
+type Child = ([u8; 32], [u8; 32], Option<[u8; 33]>);
+
 /// BIP32 single-level child derivation using libsecp256k1.
 /// No fingerprint computation.
 ///
@@ -95,7 +131,7 @@ fn derive_child(
     parent_chain: &[u8; 32],
     parent_pubkey: Option<&[u8; 33]>, // if available, skips 1 point_mul for normal children
     index: u32,
-) -> Result<([u8; 32], [u8; 32], Option<[u8; 33]>)> {
+) -> Result<Child> {
     let hardened = index >= HARDENED_BIT;
 
     // Build HMAC input: 37 bytes
@@ -125,7 +161,9 @@ fn derive_child(
     data[33..37].copy_from_slice(&index.to_be_bytes());
 
     // HMAC-SHA512(parent_chain, data)
-    let hmac_out = hmac_sha512(parent_chain, &data);
+    let mut hmac_out = hmac_sha512(parent_chain, &data);
+    // A hardened child's input holds the parent key.
+    wipe(&mut data);
     let il = &hmac_out[..32];
     let ir = &hmac_out[32..];
 
@@ -143,32 +181,40 @@ fn derive_child(
 
     let mut child_chain = [0u8; 32];
     child_chain.copy_from_slice(ir);
+    wipe(&mut hmac_out);
 
     Ok((child_key, child_chain, parent_pk_bytes))
 }
 
-/// Derive the hardened prefix m/44'/60'/0' from seed.
+/// Derive the hardened prefix m/44'/coin_type'/0' from seed (60 for every
+/// EVM chain).
 /// All three levels are hardened → 0 point multiplications.
 /// Returns extended key + its compressed public key (1 point_mul for the pubkey).
-fn derive_account_root(seed: &[u8]) -> Result<CachedXPrv> {
+fn derive_account_root(seed: &[u8], coin_type: u32) -> Result<CachedXPrv> {
     let (mut key, mut chain) = master_key_from_seed(seed)?;
 
     // m → 44' (hardened, 0 point_mul)
     let (k, c, _) = derive_child(&key, &chain, None, 44 | HARDENED_BIT)?;
+    wipe(&mut key);
+    wipe(&mut chain);
     key = k;
     chain = c;
 
-    // 44' → 60' (hardened, 0 point_mul)
-    let (k, c, _) = derive_child(&key, &chain, None, 60 | HARDENED_BIT)?;
+    // 44' → coin_type' (hardened, 0 point_mul)
+    let (k, c, _) = derive_child(&key, &chain, None, coin_type | HARDENED_BIT)?;
+    wipe(&mut key);
+    wipe(&mut chain);
     key = k;
     chain = c;
 
-    // 60' → 0' (hardened, 0 point_mul)
-    let (k, c, _) = derive_child(&key, &chain, None, 0 | HARDENED_BIT)?;
+    // coin_type' → 0' (hardened, 0 point_mul)
+    let (k, c, _) = derive_child(&key, &chain, None, HARDENED_BIT)?;
+    wipe(&mut key);
+    wipe(&mut chain);
     key = k;
     chain = c;
 
-    // Compute public key of m/44'/60'/0' for caching
+    // Compute public key of m/44'/coin_type'/0' for caching
     // This costs 1 point_mul, but we only do it once (on cache miss)
     let secp = Secp256k1::signing_only();
     let sk = SecretKey::from_slice(&key).map_err(|e| anyhow!("Invalid account key: {}", e))?;
@@ -182,7 +228,8 @@ fn derive_account_root(seed: &[u8]) -> Result<CachedXPrv> {
 }
 
 /// Derive full path and return private key + public key.
-/// Uses cached m/44'/60'/0' when available.
+/// Starts from `cached_account` (an account root, m/44'/60'/0' unless the
+/// caller derived another coin type's) or derives m/44'/60'/0'.
 ///
 /// With cache: 2 point multiplications (for 2 normal child levels)
 /// Without cache: 2 point multiplications + 1 for caching pubkey = 3
@@ -196,7 +243,7 @@ pub fn derive_full(
     let (mut key, mut chain, parent_pk) = match cached_account {
         Some(cached) => (cached.key, cached.chain, Some(cached.pubkey)),
         None => {
-            let root = derive_account_root(seed)?;
+            let root = derive_account_root(seed, proto::chain::EVM_COIN_TYPE)?;
             (root.key, root.chain, Some(root.pubkey))
         }
     };
@@ -204,6 +251,8 @@ pub fn derive_full(
     // 0' → account_index (normal): 1 point_mul
     // We pass parent_pk so it skips recomputing if available
     let (k, c, new_pk) = derive_child(&key, &chain, parent_pk.as_ref(), account_index)?;
+    wipe(&mut key);
+    wipe(&mut chain);
     key = k;
     chain = c;
     // new_pk is the parent's compressed pubkey (for normal children).
@@ -222,7 +271,7 @@ pub fn derive_full(
     let mut data = [0u8; 37];
     data[..33].copy_from_slice(&parent_pk_obj.serialize());
     data[33..37].copy_from_slice(&address_index.to_be_bytes());
-    let hmac_out = hmac_sha512(&chain, &data);
+    let mut hmac_out = hmac_sha512(&chain, &data);
     let il = &hmac_out[..32];
 
     // child_key = IL + parent_key (mod n)
@@ -231,6 +280,7 @@ pub fn derive_full(
         .map_err(|_| anyhow!("BIP32: invalid IL at final level"))?
         .add_tweak(&parent_scalar)
         .map_err(|_| anyhow!("BIP32: child key overflow at final level"))?;
+    wipe(&mut hmac_out);
 
     // child_pk = child_sk * G (1 point_mul)
     let child_pk = PublicKey::from_secret_key(&secp, &child_sk);
@@ -247,8 +297,8 @@ pub fn derive_full(
     pk_uncompressed.copy_from_slice(&uncompressed);
 
     // Zero intermediate key material
-    key.iter_mut().for_each(|b| *b = 0);
-    chain.iter_mut().for_each(|b| *b = 0);
+    wipe(&mut key);
+    wipe(&mut chain);
 
     Ok(DerivedKey {
         private_key,
@@ -260,66 +310,79 @@ pub fn derive_full(
 /// Derive account root (m/44'/60'/0') for caching.
 /// Call this once after seed is available, store the result in secure storage.
 pub fn compute_account_root(seed: &[u8]) -> Result<CachedXPrv> {
-    derive_account_root(seed)
+    derive_account_root(seed, proto::chain::EVM_COIN_TYPE)
 }
 
-/// Parse a BIP44 derivation path like "m/44'/60'/0'/0/0".
-/// Returns (account_index, address_index).
-/// Currently only supports the standard Ethereum path structure:
-///   m/44'/60'/0'/{account}/{address}
-pub fn parse_eth_path(path: &str) -> Result<(u32, u32)> {
-    let path = path.trim();
-    let parts: Vec<&str> = path.split('/').collect();
-
-    // Expect: m / 44' / 60' / 0' / account / address
-    if parts.len() != 6 {
-        return Err(anyhow!(
-            "Expected path m/44'/60'/0'/account/address, got: {}",
-            path
-        ));
-    }
-    if parts[0] != "m" {
-        return Err(anyhow!("Path must start with 'm', got: {}", path));
-    }
+/// Derive m/44'/coin_type'/0'/account/address for a non-EVM chain. The
+/// account root is computed each time: only coin type 60 is cached.
+pub fn derive_coin_full(
+    seed: &[u8],
+    coin_type: u32,
+    account_index: u32,
+    address_index: u32,
+) -> Result<DerivedKey> {
+    let root = derive_account_root(seed, coin_type)?;
+    derive_full(seed, Some(&root), account_index, address_index)
+}
 
-    // Validate hardened prefix
-    let p1 = parse_index(parts[1])?;
-    let p2 = parse_index(parts[2])?;
-    let p3 = parse_index(parts[3])?;
-    if p1 != (44 | HARDENED_BIT) || p2 != (60 | HARDENED_BIT) || p3 != (0 | HARDENED_BIT) {
-        return Err(anyhow!(
-            "Only m/44'/60'/0'/... paths supported, got: {}",
-            path
-        ));
+/// Private key at an all-hardened path (levels given unhardened), e.g. the
+/// BIP85 `m/83696968'/…` children. Zero point multiplications.
+pub fn derive_hardened_key(seed: &[u8], levels: &[u32]) -> Result<[u8; 32]> {
+    let (mut key, mut chain) = master_key_from_seed(seed)?;
+    for &level in levels {
+        if level >= HARDENED_BIT {
+            return Err(anyhow!("path level {} is already hardened", level));
+        }
+        let (k, c, _) = derive_child(&key, &chain, None, level | HARDENED_BIT)?;
+        wipe(&mut key);
+        wipe(&mut chain);
+        key = k;
+        chain = c;
     }
+    wipe(&mut chain);
+    Ok(key)
+}
 
-    let account = parse_index(parts[4])?;
-    let address = parse_index(parts[5])?;
+/// r ‖ s ‖ v over a 32-byte digest: RFC 6979 nonce, low-S, v = 27 + recovery
+/// id — the form ethers and `ecrecover` expect.
+pub fn sign_recoverable(private_key: &[u8; 32], digest: &[u8; 32]) -> Result<Vec<u8>> {
+    let secret_key = SecretKey::from_slice(private_key)?;
+    let secp = Secp256k1::signing_only();
+    let msg = Message::from_slice(digest)?;
+    let sig = secp.sign_ecdsa_recoverable(&msg, &secret_key);
+    let (recovery_id, sig_bytes) = sig.serialize_compact();
+    let mut signature = Vec::with_capacity(65);
+    signature.extend_from_slice(&sig_bytes);
+    signature.push(recovery_id.to_i32() as u8 + 27);
+    Ok(signature)
+}
 
-    // Account and address must be normal (non-hardened)
-    if account >= HARDENED_BIT || address >= HARDENED_BIT {
-        return Err(anyhow!(
-            "Account and address indices must be non-hardened, got: {}",
-            path
-        ));
-    }
+/// Uncompressed ECDH point `private_key · peer` (`04 ‖ x ‖ y`), the input
+/// ECIES derives its key from.
+pub fn ecdh_point(private_key: &[u8; 32], peer: &[u8]) -> Result<[u8; 65]> {
+    let secret_key = SecretKey::from_slice(private_key)?;
+    let peer =
+        PublicKey::from_slice(peer).map_err(|_| anyhow!("peer key is not a secp256k1 point"))?;
+    let mut xy = secp256k1::ecdh::shared_secret_point(&peer, &secret_key);
+    let mut point = [0u8; 65];
+    point[0] = 0x04;
+    point[1..].copy_from_slice(&xy);
+    xy.fill(0);
+    Ok(point)
+}
 
-    Ok((account, address))
+/// Parse a BIP44 derivation path like "m/44'/60'/0'/0/0".
+/// Returns (account_index, address_index).
+/// Only the standard Ethereum path structure m/44'/60'/0'/{account}/{address}
+/// is supported; the rules live in `proto::hd_path` so the CA and the browser
+/// SDK validate exactly what the TA derives. A malformed path is a
+/// `proto::hd_path::InvalidPath`.
+pub fn parse_eth_path(path: &str) -> Result<(u32, u32)> {
+    proto::hd_path::parse_eth_path(path).map_err(|e| proto::hd_path::InvalidPath(e).into())
 }
 
-fn parse_index(s: &str) -> Result<u32> {
-    if let Some(stripped) = s.strip_suffix('\'') {
-        let n: u32 = stripped
-            .parse()
-            .map_err(|_| anyhow!("Invalid index: {}", s))?;
-        Ok(n | HARDENED_BIT)
-    } else if let Some(stripped) = s.strip_suffix('h') {
-        let n: u32 = stripped
-            .parse()
-            .map_err(|_| anyhow!("Invalid index: {}", s))?;
-        Ok(n | HARDENED_BIT)
-    } else {
-        let n: u32 = s.parse().map_err(|_| anyhow!("Invalid index: {}", s))?;
-        Ok(n)
-    }
+/// `parse_eth_path` under another coin type: m/44'/{coin_type}'/0'/{account}/{address}.
+pub fn parse_coin_path(path: &str, coin_type: u32) -> Result<(u32, u32)> {
+    proto::hd_path::parse_coin_path(path, coin_type)
+        .map_err(|e| proto::hd_path::InvalidPath(e).into())
 }
@@ -0,0 +1,154 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! BIP85 entropy and the sealed export of a child secret.
+//!
+//! The key at the application path comes from `bip32_secp`; this module
+//! turns it into the BIP85 entropy and seals the truncated child to the
+//! recipient with the replication transport (ECDH + encrypt-then-MAC).
+
+use hmac::{Hmac, Mac};
+use proto::bip85::{Bip85App, Bip85Package};
+use secp256k1::{PublicKey, SecretKey};
+use sha2::Sha512;
+use uuid::Uuid;
+
+type HmacSha512 = Hmac<Sha512>;
+
+const ENTROPY_KEY: &[u8] = b"bip-entropy-from-k";
+
+/// HMAC-SHA512("bip-entropy-from-k", k) for the private key `k` at the
+/// application path.
+pub fn entropy_from_k(k: &[u8; 32]) -> [u8; 64] {
+    let mut mac = HmacSha512::new_from_slice(ENTROPY_KEY).expect("HMAC accepts any key length");
+    mac.update(k);
+    let mut out = [0u8; 64];
+    out.copy_from_slice(&mac.finalize().into_bytes());
+    out
+}
+
+/// Seal `child` to `recipient` under the ephemeral key `secret`.
+pub fn seal_child(
+    wallet_id: Uuid,
+    app: Bip85App,
+    secret: &SecretKey,
+    ephemeral_pubkey: Vec<u8>,
+    recipient: &PublicKey,
+    nonce: [u8; 16],
+    child: &[u8],
+) -> Bip85Package {
+    let recipient_pubkey = recipient.serialize().to_vec();
+    let keys = crate::replication::session_keys(
+        secret,
+        recipient,
+        &nonce,
+        &recipient_pubkey,
+        &ephemeral_pubkey,
+    );
+    let mut package = Bip85Package {
+        wallet_id,
+        app,
+        recipient_pubkey,
+        ephemeral_pubkey,
+        nonce,
+        ciphertext: Vec::new(),
+        mac: [0u8; 32],
+    };
+    let (ciphertext, mac) = crate::replication::seal(&keys, &package.aad(), child);
+    package.ciphertext = ciphertext;
+    package.mac = mac;
+    package
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use secp256k1::Secp256k1;
+
+    fn unhex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn bip85_test_vectors() {
+        // BIP85 test cases 1 and 2: derived key -> derived entropy.
+        let cases = [
+            (
+                "cca20ccb0e9a90feb0912870c3323b24874b0ca3d8018c4b96d0b97c0e82ded0",
+                "efecfbccffea313214232d29e71563d941229afb4338c21f9517c41aaa0d16f0\
+                 0b83d2a09ef747e7a64e8e2bd5a14869e693da66ce94ac2da570ab7ee48618f7",
+            ),
+            (
+                "503776919131758bb7de7beb6c0ae24894f4ec042c26032890c29359216e21ba",
+                "70c6e3e8ebee8dc4c0dbba66076819bb8c09672527c4277ca8729532ad711872\
+                 218f826919f6b67218adde99018a6df9095ab2b58d803b5b93ec9802085a690e",
+            ),
+        ];
+        for (k, entropy) in cases.iter() {
+            let mut key = [0u8; 32];
+            key.copy_from_slice(&unhex(k));
+            assert_eq!(entropy_from_k(&key).to_vec(), unhex(entropy));
+        }
+    }
+
+    #[test]
+    fn recipient_opens_the_package() {
+        let secp = Secp256k1::new();
+        let eph = SecretKey::from_slice(&[0x11; 32]).unwrap();
+        let eph_pub = PublicKey::from_secret_key(&secp, &eph).serialize().to_vec();
+        let rcpt = SecretKey::from_slice(&[0x22; 32]).unwrap();
+        let rcpt_pub = PublicKey::from_secret_key(&secp, &rcpt);
+        let app = Bip85App::Hex {
+            num_bytes: 32,
+            index: 0,
+        };
+        let package = seal_child(
+            Uuid::nil(),
+            app,
+            &eph,
+            eph_pub.clone(),
+            &rcpt_pub,
+            [7; 16],
+            &[0xab; 32],
+        );
+
+        let keys = crate::replication::session_keys(
+            &rcpt,
+            &PublicKey::from_slice(&package.ephemeral_pubkey).unwrap(),
+            &package.nonce,
+            &package.recipient_pubkey,
+            &eph_pub,
+        );
+        let opened =
+            crate::replication::open(&keys, &package.aad(), &package.ciphertext, &package.mac)
+                .unwrap();
+        assert_eq!(opened, vec![0xab; 32]);
+
+        // The header is authenticated: another index does not open.
+        let mut other = package.clone();
+        other.app = Bip85App::Hex {
+            num_bytes: 32,
+            index: 1,
+        };
+        assert!(
+            crate::replication::open(&keys, &other.aad(), &other.ciphertext, &other.mac).is_err()
+        );
+    }
+}
@@ -75,6 +75,13 @@ pub fn sign_pop(sk_bytes: &[u8; 32]) -> Result<([u8; 128], [u8; 256], [u8; 256])
     Ok((public_key, pop_point, pop_sig))
 }
 
+/// EIP-2537 128B G1 public key of `sk_bytes` — the layout an on-chain verifier
+/// (e.g. an ERC-4337 aggregator) registers.
+pub fn pubkey_eip2537(sk_bytes: &[u8; 32]) -> Result<[u8; 128]> {
+    let sk = SecretKey::from_bytes(sk_bytes).map_err(|e| anyhow!("BLS sk from_bytes: {:?}", e))?;
+    Ok(encode_g1_eip2537(&sk.sk_to_pk().serialize()))
+}
+
 /// 从密封私钥字节恢复 48B 压缩 G1 公钥(校验/恢复用,handler 走存储的公钥)。
 #[allow(dead_code)]
 pub fn pubkey(sk_bytes: &[u8; 32]) -> Result<[u8; 48]> {
@@ -0,0 +1,227 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Pending WebAuthn challenge table (issue #49 — TA-side anti-replay).
+//!
+//! The TA issues one-time 32-byte nonces via GetChallenge and verifies and
+//! consumes them inside `verify_passkey_for_wallet`. This closes the replay
+//! hole where a compromised CA could resubmit one captured assertion to
+//! authorize arbitrary payloads: the nonce is signed by the authenticator (it
+//! IS the WebAuthn challenge), checked against the TA's own table, and
+//! deleted on first use.
+//!
+//! Constraints:
+//!   * IN-MEMORY only, never secure storage. Losing nonces on TA restart only
+//!     forces a re-challenge, and the table is never written after a storage
+//!     write (H-3).
+//!   * Vec instead of HashMap — std HashMap's SipHasher pulls getrandom,
+//!     which panics in the OP-TEE TA.
+//!   * A [`TaGlobal`], not a thread_local: OP-TEE may dispatch consecutive
+//!     InvokeCommands onto different pool threads, and a thread_local nonce
+//!     issued by GetChallenge was invisible to the verify on the next call
+//!     (flaky "No pending challenge", #49 / #61). The table has no storage
+//!     fallback, so correctness must not depend on thread affinity.
+//!
+//! [`ChallengeTable`] is plain data so the state machine can be checked on the
+//! host; nonce generation and the clock stay in main.rs.
+
+use uuid::Uuid;
+
+use crate::ta_global::TaGlobal;
+
+/// Upper bound on simultaneously-pending challenges. Bounds memory and limits a
+/// compromised CA's ability to exhaust the TA by spamming GetChallenge. Oldest
+/// entries are evicted when full (a dropped pending challenge just forces the
+/// honest client to re-request — it cannot authorize anything).
+pub const MAX_PENDING_CHALLENGES: usize = 256;
+
+struct PendingChallenge {
+    wallet_id: Uuid,
+    nonce: [u8; 32],
+    issued_at: i64,
+}
+
+pub struct ChallengeTable {
+    entries: Vec<PendingChallenge>,
+    capacity: usize,
+}
+
+impl ChallengeTable {
+    pub const fn new(capacity: usize) -> Self {
+        ChallengeTable {
+            entries: Vec::new(),
+            capacity,
+        }
+    }
+
+    /// Record `nonce` for `wallet_id`. Replaces any previously-pending nonce
+    /// for the same wallet (only the latest challenge is valid).
+    pub fn issue(&mut self, wallet_id: &Uuid, nonce: [u8; 32], issued_at: i64) {
+        self.entries.retain(|e| &e.wallet_id != wallet_id);
+        if self.entries.len() >= self.capacity {
+            if let Some((idx, _)) = self
+                .entries
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, e)| e.issued_at)
+            {
+                self.entries.swap_remove(idx);
+            }
+        }
+        self.entries.push(PendingChallenge {
+            wallet_id: *wallet_id,
+            nonce,
+            issued_at,
+        });
+    }
+
+    /// The pending (nonce, issued_at) for `wallet_id`, left in place.
+    pub fn peek(&self, wallet_id: &Uuid) -> Option<([u8; 32], i64)> {
+        self.entries
+            .iter()
+            .find(|e| &e.wallet_id == wallet_id)
+            .map(|e| (e.nonce, e.issued_at))
+    }
+
+    /// Remove and return the pending nonce for `wallet_id`.
+    pub fn consume(&mut self, wallet_id: &Uuid) -> Option<([u8; 32], i64)> {
+        let idx = self
+            .entries
+            .iter()
+            .position(|e| &e.wallet_id == wallet_id)?;
+        let e = self.entries.swap_remove(idx);
+        Some((e.nonce, e.issued_at))
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+static PENDING: TaGlobal<ChallengeTable> =
+    TaGlobal::new(ChallengeTable::new(MAX_PENDING_CHALLENGES));
+
+pub fn issue(wallet_id: &Uuid, nonce: [u8; 32], issued_at: i64) {
+    PENDING.with(|t| t.issue(wallet_id, nonce, issued_at));
+}
+
+/// Look up the pending nonce WITHOUT removing it. A request carrying a
+/// wrong or expired challenge must not burn a victim's still-valid nonce
+/// (DoS-on-nonce); [`consume`] runs only after every check has passed.
+pub fn peek(wallet_id: &Uuid) -> Option<([u8; 32], i64)> {
+    PENDING.with(|t| t.peek(wallet_id))
+}
+
+/// Look up and remove the pending nonce: a replayed assertion finds nothing.
+pub fn consume(wallet_id: &Uuid) -> Option<([u8; 32], i64)> {
+    PENDING.with(|t| t.consume(wallet_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WALLETS: u8 = 3;
+
+    #[derive(Clone, Copy, Debug)]
+    enum Op {
+        Issue(u8),
+        Peek(u8),
+        Consume(u8),
+    }
+
+    fn wallet(w: u8) -> Uuid {
+        Uuid::from_bytes([w; 16])
+    }
+
+    /// Every command sequence of length `depth` over `WALLETS` wallets.
+    fn schedules(depth: usize) -> Vec<Vec<Op>> {
+        let ops: Vec<Op> = (0..WALLETS)
+            .flat_map(|w| [Op::Issue(w), Op::Peek(w), Op::Consume(w)])
+            .collect();
+        let mut out = vec![vec![]];
+        for _ in 0..depth {
+            out = out
+                .into_iter()
+                .flat_map(|s| {
+                    ops.iter().map(move |&op| {
+                        let mut next = s.clone();
+                        next.push(op);
+                        next
+                    })
+                })
+                .collect();
+        }
+        out
+    }
+
+    /// Model check: run every schedule against the table (capacity 2, so
+    /// eviction is exercised) and against a per-wallet reference model of
+    /// the anti-replay rules.
+    #[test]
+    fn every_schedule_matches_the_reference_model() {
+        for schedule in schedules(5) {
+            let mut table = ChallengeTable::new(2);
+            // model[w] = Some((nonce, issued_at)) for the live nonce of w.
+            let mut model: [Option<([u8; 32], i64)>; WALLETS as usize] = [None; WALLETS as usize];
+            for (step, &op) in schedule.iter().enumerate() {
+                let now = step as i64;
+                match op {
+                    Op::Issue(w) => {
+                        let nonce = [step as u8 + 1; 32];
+                        table.issue(&wallet(w), nonce, now);
+                        model[w as usize] = Some((nonce, now));
+                        // Over capacity: the oldest other wallet's nonce goes.
+                        let live: Vec<usize> =
+                            (0..model.len()).filter(|&i| model[i].is_some()).collect();
+                        if live.len() > 2 {
+                            let oldest =
+                                *live.iter().min_by_key(|&&i| model[i].unwrap().1).unwrap();
+                            model[oldest] = None;
+                        }
+                    }
+                    Op::Peek(w) => {
+                        assert_eq!(table.peek(&wallet(w)), model[w as usize], "{:?}", schedule);
+                    }
+                    Op::Consume(w) => {
+                        assert_eq!(
+                            table.consume(&wallet(w)),
+                            model[w as usize].take(),
+                            "{:?}",
+                            schedule
+                        );
+                    }
+                }
+                assert!(table.len() <= 2);
+                assert_eq!(table.len(), model.iter().filter(|m| m.is_some()).count());
+            }
+        }
+    }
+
+    #[test]
+    fn reissue_invalidates_and_consume_is_one_time() {
+        let mut t = ChallengeTable::new(MAX_PENDING_CHALLENGES);
+        let id = wallet(9);
+        t.issue(&id, [1; 32], 10);
+        t.issue(&id, [2; 32], 11);
+        assert_eq!(t.len(), 1);
+        assert_eq!(t.peek(&id), Some(([2; 32], 11)));
+        assert_eq!(t.consume(&id), Some(([2; 32], 11)));
+        assert_eq!(t.consume(&id), None);
+    }
+}
@@ -0,0 +1,51 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Secure-storage slot holding the `proto::crash_dump` ring.
+//!
+//! The dispatcher appends to it after a command has returned (so after any
+//! storage write of that command, never before a thread_local access — H-3).
+//! It is kept across a tamper wipe: it holds no key material and a wipe is
+//! exactly when the history is wanted.
+
+use proto::crash_dump::CrashLog;
+use secure_db::Storable;
+use serde::{Deserialize, Serialize};
+
+pub const STORE_ID: &str = "crash_log";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CrashLogRecord {
+    pub store_id: String,
+    pub log: CrashLog,
+}
+
+impl Default for CrashLogRecord {
+    fn default() -> Self {
+        Self {
+            store_id: STORE_ID.to_string(),
+            log: CrashLog::default(),
+        }
+    }
+}
+
+impl Storable for CrashLogRecord {
+    type Key = String;
+    fn unique_id(&self) -> Self::Key {
+        self.store_id.clone()
+    }
+}
@@ -0,0 +1,119 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Secure-storage records for dapp key-value storage.
+//!
+//! One [`DappNamespaceRecord`] per (wallet, app) indexes the keys and their
+//! sizes; each value is its own [`DappBlob`]. The index is written before
+//! the blob on Put and after it on Delete, so it never misses a blob that
+//! exists and wiping a wallet by its indexes finds everything. Quotas and
+//! naming are `proto::dapp_storage`.
+
+use proto::dapp_storage::Namespace;
+use secure_db::Storable;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DappNamespaceRecord {
+    pub store_id: String,
+    pub wallet_id: Uuid,
+    pub app_id: String,
+    pub namespace: Namespace,
+}
+
+impl Storable for DappNamespaceRecord {
+    type Key = String;
+    fn unique_id(&self) -> Self::Key {
+        self.store_id.clone()
+    }
+}
+
+impl DappNamespaceRecord {
+    pub fn store_id_for(wallet_id: &Uuid, app_id: &str) -> String {
+        format!("dappns_{}_{}", wallet_id, app_id)
+    }
+
+    pub fn empty(wallet_id: &Uuid, app_id: &str) -> Self {
+        Self {
+            store_id: Self::store_id_for(wallet_id, app_id),
+            wallet_id: *wallet_id,
+            app_id: app_id.to_string(),
+            namespace: Namespace::default(),
+        }
+    }
+
+    pub fn blob_id(&self, key: &str) -> String {
+        DappBlob::store_id_for(&self.wallet_id, &self.app_id, key)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DappBlob {
+    pub store_id: String,
+    pub value: Vec<u8>,
+}
+
+impl Storable for DappBlob {
+    type Key = String;
+    fn unique_id(&self) -> Self::Key {
+        self.store_id.clone()
+    }
+}
+
+impl DappBlob {
+    pub fn store_id_for(wallet_id: &Uuid, app_id: &str, key: &str) -> String {
+        format!("dapp_{}_{}_{}", wallet_id, app_id, key)
+    }
+}
+
+/// Namespace record ids of one wallet, out of every namespace id stored.
+pub fn wallet_namespaces<'a, I>(wallet_id: &Uuid, ids: I) -> Vec<String>
+where
+    I: IntoIterator<Item = &'a String>,
+{
+    let prefix = format!("dappns_{}_", wallet_id);
+    ids.into_iter()
+        .filter(|id| id.starts_with(&prefix))
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn namespaces_are_found_per_wallet() {
+        let a = Uuid::parse_str("4319f351-0b24-4097-b659-80ee4f824cdd").unwrap();
+        let b = Uuid::parse_str("a1b2c3d4-e5f6-7890-abcd-ef1234567890").unwrap();
+        let ns = DappNamespaceRecord::empty(&a, "notes.example.org");
+        assert_eq!(
+            ns.blob_id("totp"),
+            "dapp_4319f351-0b24-4097-b659-80ee4f824cdd_notes.example.org_totp"
+        );
+        let ids = vec![
+            ns.store_id.clone(),
+            DappNamespaceRecord::store_id_for(&b, "notes.example.org"),
+            DappNamespaceRecord::store_id_for(&a, "vault"),
+        ];
+        assert_eq!(
+            wallet_namespaces(&a, &ids),
+            vec![ids[0].clone(), ids[2].clone()]
+        );
+    }
+}
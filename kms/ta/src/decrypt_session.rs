@@ -0,0 +1,142 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Open ECIES decrypt sessions.
+//!
+//! In memory only, like the challenge table: a TA restart closes every
+//! session and the owner opens a new one with a fresh assertion. A session
+//! is found by its TA-chosen id AND its wallet, so an id leaked from one
+//! wallet's client opens nothing of another's. Each decryption spends one
+//! use; an expired or spent session is removed when next looked up.
+
+use uuid::Uuid;
+
+use crate::ta_global::TaGlobal;
+
+struct Session {
+    id: [u8; 16],
+    wallet_id: Uuid,
+    hd_path: String,
+    remaining: u32,
+    expires_at: u64,
+}
+
+pub struct SessionTable {
+    entries: Vec<Session>,
+    capacity: usize,
+}
+
+impl SessionTable {
+    pub const fn new(capacity: usize) -> Self {
+        SessionTable {
+            entries: Vec::new(),
+            capacity,
+        }
+    }
+
+    /// Record a session; the one closest to expiry makes room when full.
+    pub fn open(
+        &mut self,
+        id: [u8; 16],
+        wallet_id: &Uuid,
+        hd_path: &str,
+        uses: u32,
+        expires_at: u64,
+    ) {
+        if self.entries.len() >= self.capacity {
+            if let Some((idx, _)) = self
+                .entries
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, s)| s.expires_at)
+            {
+                self.entries.swap_remove(idx);
+            }
+        }
+        self.entries.push(Session {
+            id,
+            wallet_id: *wallet_id,
+            hd_path: hd_path.to_string(),
+            remaining: uses,
+            expires_at,
+        });
+    }
+
+    /// Spend one use: the session's path and the uses left after this one.
+    pub fn spend(
+        &mut self,
+        id: &[u8; 16],
+        wallet_id: &Uuid,
+        now: u64,
+    ) -> Result<(String, u32), &'static str> {
+        self.entries.retain(|s| s.expires_at > now && s.remaining > 0);
+        let session = self
+            .entries
+            .iter_mut()
+            .find(|s| &s.id == id && &s.wallet_id == wallet_id)
+            .ok_or("no open decrypt session with this id for this wallet")?;
+        session.remaining -= 1;
+        Ok((session.hd_path.clone(), session.remaining))
+    }
+}
+
+static SESSIONS: TaGlobal<SessionTable> =
+    TaGlobal::new(SessionTable::new(proto::ecies::MAX_OPEN_SESSIONS));
+
+pub fn open(id: [u8; 16], wallet_id: &Uuid, hd_path: &str, uses: u32, expires_at: u64) {
+    SESSIONS.with(|t| t.open(id, wallet_id, hd_path, uses, expires_at));
+}
+
+pub fn spend(id: &[u8; 16], wallet_id: &Uuid, now: u64) -> Result<(String, u32), &'static str> {
+    SESSIONS.with(|t| t.spend(id, wallet_id, now))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PATH: &str = "m/44'/60'/0'/0/0";
+
+    #[test]
+    fn uses_run_out_and_sessions_expire() {
+        let w = Uuid::from_bytes([1; 16]);
+        let mut t = SessionTable::new(4);
+        t.open([9; 16], &w, PATH, 2, 1_000);
+        assert_eq!(t.spend(&[9; 16], &w, 900), Ok((PATH.to_string(), 1)));
+        assert_eq!(t.spend(&[9; 16], &w, 900), Ok((PATH.to_string(), 0)));
+        assert!(t.spend(&[9; 16], &w, 900).is_err());
+
+        t.open([8; 16], &w, PATH, 5, 1_000);
+        assert!(t.spend(&[8; 16], &w, 1_000).is_err());
+        assert!(t.entries.is_empty());
+    }
+
+    #[test]
+    fn a_session_belongs_to_its_wallet() {
+        let (a, b) = (Uuid::from_bytes([1; 16]), Uuid::from_bytes([2; 16]));
+        let mut t = SessionTable::new(2);
+        t.open([9; 16], &a, PATH, 3, 1_000);
+        assert!(t.spend(&[9; 16], &b, 0).is_err());
+        assert!(t.spend(&[7; 16], &a, 0).is_err());
+
+        // Full: the session nearest expiry goes.
+        t.open([8; 16], &b, PATH, 3, 2_000);
+        t.open([7; 16], &b, PATH, 3, 3_000);
+        assert!(t.spend(&[9; 16], &a, 0).is_err());
+        assert!(t.spend(&[8; 16], &b, 0).is_ok());
+    }
+}
@@ -0,0 +1,105 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Installed deployment policy (command allow-list) and its per-instance cache.
+//!
+//! The first install pins the signer address; every replacement must recover
+//! to the same address and carry a higher sequence, so a compromised CA can
+//! neither swap the policy for a laxer one nor replay an older one. The
+//! dispatcher consults the cache on every invoke; it is filled from secure
+//! storage on first use. Another session's install is seen by instances
+//! opened after it — provisioning installs before kms-api starts.
+
+use proto::deployment_policy::DeploymentPolicy;
+use secure_db::Storable;
+use serde::{Deserialize, Serialize};
+
+use crate::ta_global::TaGlobal;
+
+pub const STORE_ID: &str = "deployment_policy";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PolicyRecord {
+    pub store_id: String,
+    pub signer: [u8; 20],
+    pub policy: DeploymentPolicy,
+}
+
+impl Storable for PolicyRecord {
+    type Key = String;
+    fn unique_id(&self) -> Self::Key {
+        self.store_id.clone()
+    }
+}
+
+/// Check `policy`, signed by `signer`, may replace `current`.
+pub fn check_replacement(
+    current: Option<&PolicyRecord>,
+    policy: &DeploymentPolicy,
+    signer: [u8; 20],
+) -> Result<(), &'static str> {
+    policy.validate()?;
+    if let Some(current) = current {
+        if current.signer != signer {
+            return Err("deployment policy is not signed by the pinned policy key");
+        }
+        if policy.sequence <= current.policy.sequence {
+            return Err("deployment policy sequence must increase");
+        }
+    }
+    Ok(())
+}
+
+/// None = not loaded yet; Some(None) = loaded, nothing installed.
+static POLICY: TaGlobal<Option<Option<PolicyRecord>>> = TaGlobal::new(None);
+
+pub fn cached() -> Option<Option<PolicyRecord>> {
+    POLICY.with(|p| p.clone())
+}
+
+pub fn set_cached(record: Option<PolicyRecord>) {
+    POLICY.with(|p| *p = Some(record));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proto::deployment_policy::POLICY_FORMAT_VERSION;
+
+    fn policy(sequence: u64) -> DeploymentPolicy {
+        DeploymentPolicy {
+            format: POLICY_FORMAT_VERSION,
+            deployment: "rack-3".into(),
+            sequence,
+            disabled_commands: vec![7],
+        }
+    }
+
+    #[test]
+    fn first_install_pins_signer_and_sequence_only_rises() {
+        assert!(check_replacement(None, &policy(1), [1; 20]).is_ok());
+        let current = PolicyRecord {
+            store_id: STORE_ID.into(),
+            signer: [1; 20],
+            policy: policy(3),
+        };
+        assert!(check_replacement(Some(&current), &policy(4), [1; 20]).is_ok());
+        assert!(check_replacement(Some(&current), &policy(3), [1; 20]).is_err());
+        assert!(check_replacement(Some(&current), &policy(2), [1; 20]).is_err());
+        assert!(check_replacement(Some(&current), &policy(9), [2; 20]).is_err());
+    }
+}
@@ -0,0 +1,61 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+//! The device sealing key of encrypted backups (`proto::backup`).
+//!
+//! OP-TEE's **system PTA** derives it from the hardware unique key (HUK)
+//! and this TA's UUID (`PTA_SYSTEM_DERIVE_TA_UNIQUE_KEY`), so it is the same
+//! after a reboot, a TA update or a wiped secure storage, differs on every
+//! board, and no other TA — and nothing in the REE — can obtain it. It is
+//! never stored.
+
+use anyhow::{anyhow, bail, Result};
+use optee_utee::{ParamIndex, TaSessionBuilder, TeeParams, Uuid};
+
+/// OP-TEE system PTA UUID (lib/libutee/include/pta_system.h).
+const PTA_SYSTEM_UUID: &str = "3a2f8978-5dc0-11e8-9c2d-fa7ae01bbebc";
+
+/// PTA command ID (pta_system.h).
+const PTA_SYSTEM_DERIVE_TA_UNIQUE_KEY: u32 = 1;
+
+/// Mixed into the derivation, so the key is used for backups and nothing
+/// else.
+const BACKUP_SEAL_LABEL: &[u8] = b"AirAccount.backup-seal.v1";
+
+pub fn backup_sealing_key() -> Result<[u8; 32]> {
+    let pta_uuid = Uuid::parse_str(PTA_SYSTEM_UUID)
+        .map_err(|e| anyhow!("invalid system PTA UUID: {:?}", e))?;
+    let mut session = TaSessionBuilder::new(pta_uuid)
+        .build()
+        .map_err(|e| anyhow!("open system PTA session failed: {:?}", e))?;
+
+    let mut key = [0u8; 32];
+    let mut params = TeeParams::new()
+        .with_memref_in(ParamIndex::Arg0, BACKUP_SEAL_LABEL)
+        .with_memref_out(ParamIndex::Arg1, &mut key);
+    session
+        .invoke_command(PTA_SYSTEM_DERIVE_TA_UNIQUE_KEY, &mut params)
+        .map_err(|e| anyhow!("DERIVE_TA_UNIQUE_KEY failed: {:?}", e))?;
+    let written = params[ParamIndex::Arg1]
+        .written_slice()
+        .map(|s| s.len())
+        .unwrap_or(0);
+    drop(params);
+    if written != key.len() {
+        bail!("DERIVE_TA_UNIQUE_KEY returned {} bytes, not 32", written);
+    }
+    Ok(key)
+}
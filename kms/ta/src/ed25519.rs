@@ -0,0 +1,154 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Solana keys: SLIP-0010 ed25519 derivation and signing.
+//!
+//! SLIP-0010 for ed25519 is BIP32 without curve arithmetic: every level is
+//! hardened and a child key is the left half of
+//! HMAC-SHA512(chain code, 0x00 ‖ key ‖ index). The 32-byte key is the
+//! RFC 8032 secret, which ed25519-dalek expands and signs with.
+
+use anyhow::{bail, Result};
+use ed25519_dalek::{Signer, SigningKey};
+use hmac::{Hmac, Mac};
+use proto::hd_path::{InvalidPath, HARDENED_BIT};
+use proto::solana::{path_levels, SLIP10_ED25519_SEED};
+use sha2::Sha512;
+
+type HmacSha512 = Hmac<Sha512>;
+
+/// (key, chain code) at `levels` below the seed's master key.
+pub fn derive(seed: &[u8], levels: &[u32]) -> Result<([u8; 32], [u8; 32])> {
+    let (mut key, mut chain) = hmac_split(SLIP10_ED25519_SEED, &[seed]);
+    for &index in levels {
+        if index & HARDENED_BIT == 0 {
+            bail!("SLIP-0010 derives ed25519 keys at hardened levels only");
+        }
+        let (child, child_chain) = hmac_split(&chain, &[&[0u8], &key, &index.to_be_bytes()]);
+        key = child;
+        chain = child_chain;
+    }
+    Ok((key, chain))
+}
+
+/// Account index of `m/44'/501'/account'/0'`; a malformed path is
+/// `InvalidPath`.
+pub fn parse_solana_path(hd_path: &str) -> Result<u32> {
+    proto::solana::parse_solana_path(hd_path).map_err(|e| InvalidPath(e).into())
+}
+
+/// The key of Solana account `account`.
+pub fn solana_key(seed: &[u8], account: u32) -> Result<SigningKey> {
+    let (secret, _) = derive(seed, &path_levels(account))?;
+    Ok(SigningKey::from_bytes(&secret))
+}
+
+pub fn public_key(key: &SigningKey) -> [u8; 32] {
+    key.verifying_key().to_bytes()
+}
+
+pub fn sign(key: &SigningKey, message: &[u8]) -> [u8; 64] {
+    key.sign(message).to_bytes()
+}
+
+fn hmac_split(key: &[u8], parts: &[&[u8]]) -> ([u8; 32], [u8; 32]) {
+    let mut mac = HmacSha512::new_from_slice(key).expect("HMAC accepts any key length");
+    for part in parts {
+        mac.update(part);
+    }
+    let out = mac.finalize().into_bytes();
+    let mut left = [0u8; 32];
+    let mut right = [0u8; 32];
+    left.copy_from_slice(&out[..32]);
+    right.copy_from_slice(&out[32..]);
+    (left, right)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex32(s: &str) -> [u8; 32] {
+        let mut out = [0u8; 32];
+        out.copy_from_slice(&hex::decode(s).unwrap());
+        out
+    }
+
+    #[test]
+    fn slip10_vector_1() {
+        let seed = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+        let cases = [
+            (
+                vec![],
+                "2b4be7f19ee27bbf30c667b642d5f4aa69fd169872f8fc3059c08ebae2eb19e7",
+                "90046a93de5380a72b5e45010748567d5ea02bbf6522f979e05c0d8d8ca9fffb",
+                "a4b2856bfec510abab89753fac1ac0e1112364e7d250545963f135f2a33188ed",
+            ),
+            (
+                vec![HARDENED_BIT],
+                "68e0fe46dfb67e368c75379acec591dad19df3cde26e63b93a8e704f1dade7a3",
+                "8b59aa11380b624e81507a27fedda59fea6d0b779a778918a2fd3590e16e9c69",
+                "8c8a13df77a28f3445213a0f432fde644acaa215fc72dcdf300d5efaa85d350c",
+            ),
+            (
+                vec![HARDENED_BIT, 1 | HARDENED_BIT, 2 | HARDENED_BIT],
+                "92a5b23c0b8a99e37d07df3fb9966917f5d06e02ddbd909c7e184371463e9fc9",
+                "2e69929e00b5ab250f49c3fb1c12f252de4fed2c1db88387094a0f8c4c9ccd6c",
+                "ae98736566d30ed0e9d2f4486a64bc95740d89c7db33f52121f8ea8f76ff0fc1",
+            ),
+        ];
+        for (levels, key, chain, public) in cases.iter() {
+            let (k, c) = derive(&seed, levels).unwrap();
+            assert_eq!(k, hex32(key));
+            assert_eq!(c, hex32(chain));
+            assert_eq!(public_key(&SigningKey::from_bytes(&k)), hex32(public));
+        }
+        assert!(derive(&seed, &[0]).is_err());
+    }
+
+    #[test]
+    fn rfc8032_test_1() {
+        let key = SigningKey::from_bytes(&hex32(
+            "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60",
+        ));
+        assert_eq!(
+            public_key(&key),
+            hex32("d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a")
+        );
+        assert_eq!(
+            hex::encode(&sign(&key, b"")[..]),
+            "e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e065224901555fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b"
+        );
+    }
+
+    #[test]
+    fn phantom_first_account() {
+        // BIP39 seed of "abandon ×11 about".
+        let seed = hex::decode(
+            "5eb00bbddcf069084889a8ab9155568165f5c453ccb85e70811aaed6f6da5fc1\
+             9a5ac40b389cd370d086206dec8aa6c43daea6690f20ad3d8d48b2d2ce9e38e4",
+        )
+        .unwrap();
+        let account = parse_solana_path("m/44'/501'/0'/0'").unwrap();
+        let key = solana_key(&seed, account).unwrap();
+        assert_eq!(
+            proto::solana::address(&public_key(&key)),
+            "HAgk14JpMQLgt6rVgv7cBQFJWFto5Dqxi472uT3DKpqk"
+        );
+        assert!(parse_solana_path("m/44'/501'/0'/0").is_err());
+    }
+}
@@ -409,4 +409,47 @@ mod tests {
         let msg = vec![Eip712FieldValue { name: "y".into(), value: Eip712Value::Bool(true) }];
         assert!(eip712_digest(&domain, &td, &msg).is_err());
     }
+
+    #[test]
+    fn erc2612_permit_matches_proto_permit_digest() {
+        // SignPermit hashes EIP-2612 by hand; generic SignTypedData must agree.
+        let permit = proto::permit::Erc2612Permit {
+            chain_id: 1,
+            token: [0xa0; 20],
+            token_name: "USD Coin".into(),
+            token_version: "2".into(),
+            owner: [0x22; 20],
+            spender: [0x11; 20],
+            value: [0xff; 32],
+            nonce: [0; 32],
+            deadline: [0xff; 32],
+        };
+        let domain = Eip712Domain {
+            name: Some(permit.token_name.clone()),
+            version: Some(permit.token_version.clone()),
+            chain_id: Some(permit.chain_id),
+            verifying_contract: Some(permit.token),
+        };
+        let field = |n: &str, t: &str| Eip712TypeField { name: n.into(), field_type: t.into() };
+        let td = Eip712TypeDef {
+            name: "Permit".into(),
+            fields: vec![
+                field("owner", "address"),
+                field("spender", "address"),
+                field("value", "uint256"),
+                field("nonce", "uint256"),
+                field("deadline", "uint256"),
+            ],
+        };
+        let value = |n: &str, v: Eip712Value| Eip712FieldValue { name: n.into(), value: v };
+        let msg = vec![
+            value("owner", Eip712Value::Address(permit.owner)),
+            value("spender", Eip712Value::Address(permit.spender)),
+            value("value", Eip712Value::Uint(permit.value.to_vec())),
+            value("nonce", Eip712Value::Uint(vec![0])),
+            value("deadline", Eip712Value::Uint(permit.deadline.to_vec())),
+        ];
+        let generic = eip712_digest(&domain, &td, &msg).unwrap();
+        assert_eq!(generic, proto::permit::PermitRequest::Erc2612(permit).digest());
+    }
 }
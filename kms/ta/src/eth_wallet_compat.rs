@@ -0,0 +1,167 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Upstream eth_wallet wire format (feature `eth-wallet-compat`).
+//!
+//! eth_wallet used the same ids for its four commands (CreateWallet = 0 …
+//! SignTransaction = 3) but older input structs without passkey fields. Our
+//! inputs only ever append fields, so a legacy payload is a strict prefix of
+//! ours and fails to decode as the AirAccount type. That is the discriminator:
+//! AirAccount decode first, the legacy shape only when that fails.
+//!
+//! A request from an AirAccount CA of an older protocol version is a prefix
+//! of ours too, but longer than eth_wallet's: the legacy shape must take the
+//! whole input, or a v1 DeriveAddress would be read as eth_wallet's.
+//!
+//! Outputs are byte-identical between the two (CreateWalletOutput,
+//! SignTransactionOutput, and DeriveAddressOutput as of protocol v1), so only
+//! inputs live here. The legacy RemoveWallet answers with eth_wallet's empty
+//! output, without the deletion certificate.
+
+use bincode::Options;
+use proto::{Command, EthTransaction};
+use serde::{de::DeserializeOwned, Deserialize};
+use uuid::Uuid;
+
+#[derive(Deserialize, Debug)]
+pub struct LegacyRemoveWalletInput {
+    pub wallet_id: Uuid,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct LegacyDeriveAddressInput {
+    pub wallet_id: Uuid,
+    pub hd_path: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct LegacySignTransactionInput {
+    pub wallet_id: Uuid,
+    pub hd_path: String,
+    pub transaction: EthTransaction,
+}
+
+#[derive(Debug)]
+pub enum LegacyRequest {
+    CreateWallet,
+    RemoveWallet(LegacyRemoveWalletInput),
+    DeriveAddress(LegacyDeriveAddressInput),
+    SignTransaction(LegacySignTransactionInput),
+}
+
+impl LegacyRequest {
+    /// The AirAccount command with the same id, for the deployment policy.
+    pub fn command(&self) -> Command {
+        match self {
+            LegacyRequest::CreateWallet => Command::CreateWallet,
+            LegacyRequest::RemoveWallet(_) => Command::RemoveWallet,
+            LegacyRequest::DeriveAddress(_) => Command::DeriveAddress,
+            LegacyRequest::SignTransaction(_) => Command::SignTransaction,
+        }
+    }
+}
+
+fn legacy_only<Ours: DeserializeOwned, Theirs: DeserializeOwned>(input: &[u8]) -> Option<Theirs> {
+    if bincode::deserialize::<Ours>(input).is_ok() {
+        return None;
+    }
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .reject_trailing_bytes()
+        .deserialize::<Theirs>(input)
+        .ok()
+}
+
+/// `Some` when `input` is an eth_wallet request for `command` and NOT a valid
+/// AirAccount request; `None` leaves it to the normal dispatcher.
+pub fn classify(command: Command, input: &[u8]) -> Option<LegacyRequest> {
+    match command {
+        // eth_wallet's CreateWalletInput is an empty struct → zero bytes.
+        Command::CreateWallet if input.is_empty() => Some(LegacyRequest::CreateWallet),
+        Command::RemoveWallet => {
+            legacy_only::<proto::RemoveWalletInput, _>(input).map(LegacyRequest::RemoveWallet)
+        }
+        Command::DeriveAddress => {
+            legacy_only::<proto::DeriveAddressInput, _>(input).map(LegacyRequest::DeriveAddress)
+        }
+        Command::SignTransaction => {
+            legacy_only::<proto::SignTransactionInput, _>(input).map(LegacyRequest::SignTransaction)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Serialize;
+
+    #[derive(Serialize)]
+    struct UpstreamDerive {
+        wallet_id: Uuid,
+        hd_path: String,
+    }
+
+    fn wid() -> Uuid {
+        Uuid::from_bytes([0x33; 16])
+    }
+
+    #[test]
+    fn upstream_payloads_are_recognised() {
+        assert!(matches!(
+            classify(Command::CreateWallet, &[]),
+            Some(LegacyRequest::CreateWallet)
+        ));
+        let legacy = bincode::serialize(&UpstreamDerive {
+            wallet_id: wid(),
+            hd_path: "m/44'/60'/0'/0/0".into(),
+        })
+        .unwrap();
+        match classify(Command::DeriveAddress, &legacy) {
+            Some(LegacyRequest::DeriveAddress(d)) => assert_eq!(d.wallet_id, wid()),
+            other => panic!("expected legacy derive, got {:?}", other),
+        }
+        let remove = bincode::serialize(&wid()).unwrap();
+        assert!(matches!(
+            classify(Command::RemoveWallet, &remove),
+            Some(LegacyRequest::RemoveWallet(_))
+        ));
+    }
+
+    #[test]
+    fn airaccount_payloads_are_left_alone() {
+        let ours = bincode::serialize(&proto::DeriveAddressInput {
+            wallet_id: wid(),
+            hd_path: "m/44'/60'/0'/0/0".into(),
+            passkey_assertion: None,
+            chain: proto::chain::Chain::Tron,
+        })
+        .unwrap();
+        assert!(classify(Command::DeriveAddress, &ours).is_none());
+        // A protocol v1 CA's DeriveAddress, from before `chain`.
+        assert!(classify(Command::DeriveAddress, &ours[..ours.len() - 4]).is_none());
+        let create = bincode::serialize(&proto::CreateWalletInput {
+            passkey_pubkey: vec![4; 65],
+            entropy_seed: None,
+            prf_output: None,
+        })
+        .unwrap();
+        assert!(classify(Command::CreateWallet, &create).is_none());
+        // other command ids never take the legacy path
+        assert!(classify(Command::SignHash, &[]).is_none());
+    }
+}
@@ -0,0 +1,269 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Derived child-key cache (m/44'/60'/0'/a/b → secp256k1 key).
+//!
+//! `WALLET_CACHE` already keeps the seed and the m/44'/60'/0' account root, but
+//! every Sign/SignHash still walks the last two BIP32 levels (2 point_mul + 2
+//! HMAC-SHA512 on Cortex-A). Repeat signers almost always reuse one path, so
+//! the finished child key is cached here.
+//!
+//! Constraints (same as the pending-challenge table in challenge.rs):
+//!   * TA memory only — never persisted. Losing it just means one re-derive.
+//!   * Vec + linear scan, no HashMap (SipHasher → getrandom panics in the TA).
+//!   * A `TaGlobal`, NOT thread_local: derivation can happen after a
+//!     secure-storage write in the same command (derive_address_auto), and TLS
+//!     is unusable after such a write (H-3).
+//!   * The BIP32 walk runs outside the global's borrow: a miss copies nothing
+//!     out, derives, then re-enters only to insert.
+//!   * Bounded (`KEY_CACHE_CAPACITY`) with a hard TTL; entries are zeroized on
+//!     eviction, invalidation, expiry and session close.
+
+use uuid::Uuid;
+
+use crate::bip32_secp::DerivedKey;
+use crate::ta_global::TaGlobal;
+
+/// Max cached child keys. Small on purpose: the cache holds raw private keys.
+pub const KEY_CACHE_CAPACITY: usize = 64;
+
+/// An entry older than this (the TA config's key-cache-ttl, 300s by default)
+/// is dropped (and zeroized) instead of being served.
+pub fn key_cache_ttl_secs() -> i64 {
+    crate::ta_config::limits().key_cache_ttl_secs as i64
+}
+
+struct CachedChildKey {
+    wallet_id: Uuid,
+    account: u32,
+    address: u32,
+    private_key: [u8; 32],
+    public_key_compressed: [u8; 33],
+    public_key_uncompressed: [u8; 65],
+    inserted_at: i64,
+    tick: u64,
+}
+
+impl CachedChildKey {
+    fn to_derived(&self) -> DerivedKey {
+        DerivedKey {
+            private_key: self.private_key,
+            public_key_compressed: self.public_key_compressed,
+            public_key_uncompressed: self.public_key_uncompressed,
+        }
+    }
+
+    fn expired(&self, now: i64) -> bool {
+        // A clock that jumped backwards is treated as expired too — the REE
+        // clock is host-controlled and must not be able to pin a key forever.
+        now < self.inserted_at || now - self.inserted_at > key_cache_ttl_secs()
+    }
+}
+
+impl Drop for CachedChildKey {
+    fn drop(&mut self) {
+        // Volatile writes + fence: the entry is about to be freed, so a plain
+        // store is a dead write the optimiser may drop. zeroize is not a TA
+        // dependency (see P256SessionKey's Drop in main.rs).
+        for b in self.private_key.iter_mut() {
+            // SAFETY: `b` is a valid, aligned &mut u8.
+            unsafe { core::ptr::write_volatile(b, 0) };
+        }
+        core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+    }
+}
+
+struct ChildKeyCache {
+    entries: Vec<CachedChildKey>,
+    tick: u64,
+    hits: u64,
+    misses: u64,
+}
+
+impl ChildKeyCache {
+    const fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            tick: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    fn get(
+        &mut self,
+        wallet_id: &Uuid,
+        account: u32,
+        address: u32,
+        now: i64,
+    ) -> Option<DerivedKey> {
+        self.entries.retain(|e| !e.expired(now));
+        self.tick += 1;
+        let tick = self.tick;
+        let found = self
+            .entries
+            .iter_mut()
+            .find(|e| &e.wallet_id == wallet_id && e.account == account && e.address == address)
+            .map(|e| {
+                e.tick = tick;
+                e.to_derived()
+            });
+        if found.is_some() {
+            self.hits += 1;
+        } else {
+            self.misses += 1;
+        }
+        found
+    }
+
+    fn put(&mut self, wallet_id: &Uuid, account: u32, address: u32, key: &DerivedKey, now: i64) {
+        self.tick += 1;
+        self.entries.retain(|e| {
+            !(&e.wallet_id == wallet_id && e.account == account && e.address == address)
+        });
+        if self.entries.len() >= KEY_CACHE_CAPACITY {
+            if let Some((idx, _)) = self.entries.iter().enumerate().min_by_key(|(_, e)| e.tick) {
+                self.entries.swap_remove(idx);
+            }
+        }
+        self.entries.push(CachedChildKey {
+            wallet_id: *wallet_id,
+            account,
+            address,
+            private_key: key.private_key,
+            public_key_compressed: key.public_key_compressed,
+            public_key_uncompressed: key.public_key_uncompressed,
+            inserted_at: now,
+            tick: self.tick,
+        });
+    }
+
+    fn invalidate(&mut self, wallet_id: &Uuid) {
+        self.entries.retain(|e| &e.wallet_id != wallet_id);
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+static CHILD_KEY_CACHE: TaGlobal<ChildKeyCache> = TaGlobal::new(ChildKeyCache::new());
+
+/// Return the cached child key for (wallet, account, address) or derive it with
+/// `derive` and cache the result.
+pub fn get_or_derive(
+    wallet_id: &Uuid,
+    account: u32,
+    address: u32,
+    now: i64,
+    derive: impl FnOnce() -> anyhow::Result<DerivedKey>,
+) -> anyhow::Result<DerivedKey> {
+    if let Some(k) = CHILD_KEY_CACHE.with(|c| c.get(wallet_id, account, address, now)) {
+        return Ok(k);
+    }
+    let k = derive()?;
+    CHILD_KEY_CACHE.with(|c| c.put(wallet_id, account, address, &k, now));
+    Ok(k)
+}
+
+/// Drop every cached child key of one wallet. Call on anything that changes
+/// what the wallet may sign with: deletion, passkey (policy) change.
+pub fn invalidate_wallet(wallet_id: &Uuid) {
+    CHILD_KEY_CACHE.with(|c| c.invalidate(wallet_id));
+}
+
+/// Drop (and zeroize) every cached child key. Called on session close/destroy.
+pub fn clear() {
+    CHILD_KEY_CACHE.with(|c| c.clear());
+}
+
+/// (entries, hits, misses) — diagnostic only, surfaced through WarmupCache logs.
+pub fn stats() -> (usize, u64, u64) {
+    CHILD_KEY_CACHE.with(|c| (c.entries.len(), c.hits, c.misses))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(b: u8) -> DerivedKey {
+        DerivedKey {
+            private_key: [b; 32],
+            public_key_compressed: [b; 33],
+            public_key_uncompressed: [b; 65],
+        }
+    }
+
+    #[test]
+    fn hit_after_put_and_miss_on_other_path() {
+        let id = Uuid::from_bytes([1; 16]);
+        let mut c = ChildKeyCache::new();
+        c.put(&id, 0, 0, &key(7), 100);
+        assert_eq!(c.get(&id, 0, 0, 101).unwrap().private_key, [7; 32]);
+        assert!(c.get(&id, 0, 1, 101).is_none());
+        assert_eq!((c.hits, c.misses), (1, 1));
+    }
+
+    #[test]
+    fn ttl_and_clock_rollback_expire() {
+        let id = Uuid::from_bytes([2; 16]);
+        let mut c = ChildKeyCache::new();
+        c.put(&id, 0, 0, &key(1), 1000);
+        assert!(c.get(&id, 0, 0, 1000 + key_cache_ttl_secs() + 1).is_none());
+        c.put(&id, 0, 0, &key(1), 1000);
+        assert!(c.get(&id, 0, 0, 999).is_none());
+        assert!(c.entries.is_empty());
+    }
+
+    #[test]
+    fn invalidate_only_touches_one_wallet() {
+        let a = Uuid::from_bytes([3; 16]);
+        let b = Uuid::from_bytes([4; 16]);
+        let mut c = ChildKeyCache::new();
+        c.put(&a, 0, 0, &key(1), 0);
+        c.put(&b, 0, 0, &key(2), 0);
+        c.invalidate(&a);
+        assert!(c.get(&a, 0, 0, 1).is_none());
+        assert!(c.get(&b, 0, 0, 1).is_some());
+    }
+
+    #[test]
+    fn bounded_with_lru_eviction() {
+        let id = Uuid::from_bytes([5; 16]);
+        let mut c = ChildKeyCache::new();
+        for i in 0..KEY_CACHE_CAPACITY as u32 {
+            c.put(&id, 0, i, &key(1), 0);
+        }
+        // Touch address 0 so address 1 becomes LRU.
+        assert!(c.get(&id, 0, 0, 1).is_some());
+        c.put(&id, 0, 9999, &key(1), 1);
+        assert_eq!(c.entries.len(), KEY_CACHE_CAPACITY);
+        assert!(c.get(&id, 0, 0, 2).is_some());
+        assert!(c.get(&id, 0, 1, 2).is_none());
+    }
+
+    #[test]
+    fn drop_wipes_the_private_key() {
+        let id = Uuid::from_bytes([6; 16]);
+        let mut c = ChildKeyCache::new();
+        c.put(&id, 0, 0, &key(9), 0);
+        let mut entry = core::mem::ManuallyDrop::new(c.entries.pop().unwrap());
+        // SAFETY: dropped once; the storage stays alive in the ManuallyDrop.
+        unsafe { core::mem::ManuallyDrop::drop(&mut entry) };
+        assert_eq!(entry.private_key, [0; 32]);
+    }
+}
@@ -0,0 +1,206 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Per-category trace verbosity and the record that keeps it.
+//!
+//! `ta_log!` (main.rs) consults [`enabled`], which reads the levels in force
+//! from a process-global cell. The dispatcher loads the record once per
+//! instance and calls [`refresh`] with the TA clock before every command, so
+//! an expired order drops back to the build's defaults without a storage
+//! write. Until the first command, e.g. in `open_session`, the defaults apply.
+
+use proto::log_level::{LogCategory, LogLevel, LogLevelOrder, LogLevels};
+use secure_db::Storable;
+use serde::{Deserialize, Serialize};
+
+use crate::ta_global::TaGlobal;
+
+pub const STORE_ID: &str = "log_levels";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct LogRecord {
+    pub store_id: String,
+    pub levels: LogLevels,
+    pub until: Option<u64>,
+    /// Kept after the order expires, so it cannot be replayed.
+    pub last_order_sequence: u64,
+}
+
+impl Storable for LogRecord {
+    type Key = String;
+    fn unique_id(&self) -> Self::Key {
+        self.store_id.clone()
+    }
+}
+
+impl LogRecord {
+    pub fn from_order(order: &LogLevelOrder) -> Self {
+        LogRecord {
+            store_id: STORE_ID.to_string(),
+            levels: order.levels,
+            until: order.until,
+            last_order_sequence: order.sequence,
+        }
+    }
+
+    /// The levels in force at `now`.
+    pub fn effective(&self, now: u64) -> LogLevels {
+        match self.until {
+            Some(until) if now >= until => defaults(),
+            _ => self.levels,
+        }
+    }
+}
+
+/// Without an order: debug builds keep printing what `dbg_println!` did.
+pub const fn defaults() -> LogLevels {
+    if cfg!(debug_assertions) {
+        LogLevels::all(LogLevel::Debug)
+    } else {
+        LogLevels::DEFAULT
+    }
+}
+
+/// Check a signed order against the pinned policy signer, the installed
+/// deployment, this device and the TA clock.
+pub fn check_order(
+    current: Option<&LogRecord>,
+    policy: Option<(&[u8; 20], &str)>,
+    device: Option<&[u8; 32]>,
+    order: &LogLevelOrder,
+    signer: [u8; 20],
+    now: u64,
+) -> Result<(), &'static str> {
+    order.validate()?;
+    let (pinned, deployment) = policy
+        .ok_or("log-level orders need an installed deployment policy (it pins the signer)")?;
+    if *pinned != signer {
+        return Err("log-level order is not signed by the pinned policy key");
+    }
+    if order.deployment != deployment {
+        return Err("log-level order is for another deployment");
+    }
+    if let Some(target) = &order.device {
+        if device != Some(target) {
+            return Err("log-level order is for another device");
+        }
+    }
+    if order.sequence <= current.map_or(0, |r| r.last_order_sequence) {
+        return Err("log-level order sequence must increase");
+    }
+    order.check_window(now)
+}
+
+struct LogCell {
+    /// None = not loaded yet; Some(None) = loaded, no order accepted.
+    record: Option<Option<LogRecord>>,
+    levels: LogLevels,
+}
+
+static LOG: TaGlobal<LogCell> = TaGlobal::new(LogCell {
+    record: None,
+    levels: defaults(),
+});
+
+pub fn cached() -> Option<Option<LogRecord>> {
+    LOG.with(|c| c.record.clone())
+}
+
+pub fn set_cached(record: Option<LogRecord>) {
+    LOG.with(|c| c.record = Some(record));
+}
+
+/// Recompute the levels in force from the cached record.
+pub fn refresh(now: u64) {
+    LOG.with(|c| {
+        c.levels = match &c.record {
+            Some(Some(record)) => record.effective(now),
+            _ => defaults(),
+        }
+    });
+}
+
+pub fn enabled(category: LogCategory, level: LogLevel) -> bool {
+    LOG.with(|c| c.levels.enabled(category, level))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proto::log_level::LOG_ORDER_FORMAT;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn order(sequence: u64, levels: LogLevels, until: Option<u64>) -> LogLevelOrder {
+        LogLevelOrder {
+            format: LOG_ORDER_FORMAT,
+            deployment: "rack-3".into(),
+            device: None,
+            sequence,
+            levels,
+            until,
+        }
+    }
+
+    #[test]
+    fn orders_need_the_policy_signer_and_a_rising_sequence() {
+        let policy = Some((&[1u8; 20], "rack-3"));
+        let quiet = order(2, LogLevels::all(LogLevel::Warn), None);
+        assert!(check_order(None, policy, None, &quiet, [1; 20], NOW).is_ok());
+        assert!(check_order(None, None, None, &quiet, [1; 20], NOW).is_err());
+        assert!(check_order(None, policy, None, &quiet, [2; 20], NOW).is_err());
+        assert!(check_order(None, Some((&[1; 20], "rack-4")), None, &quiet, [1; 20], NOW).is_err());
+
+        let current = LogRecord::from_order(&quiet);
+        assert!(check_order(Some(&current), policy, None, &quiet, [1; 20], NOW).is_err());
+
+        let mut targeted = order(3, LogLevels::DEFAULT, None);
+        targeted.device = Some([0xab; 32]);
+        assert!(check_order(
+            Some(&current),
+            policy,
+            Some(&[0xcd; 32]),
+            &targeted,
+            [1; 20],
+            NOW
+        )
+        .is_err());
+        assert!(check_order(
+            Some(&current),
+            policy,
+            Some(&[0xab; 32]),
+            &targeted,
+            [1; 20],
+            NOW
+        )
+        .is_ok());
+
+        let debug = order(3, LogLevels::all(LogLevel::Debug), Some(NOW - 1));
+        assert!(check_order(Some(&current), policy, None, &debug, [1; 20], NOW).is_err());
+    }
+
+    #[test]
+    fn an_expired_order_falls_back_to_the_defaults() {
+        let mut levels = LogLevels::DEFAULT;
+        levels.set(LogCategory::Crypto, LogLevel::Debug);
+        let record = LogRecord::from_order(&order(4, levels, Some(NOW + 60)));
+        assert_eq!(record.effective(NOW), levels);
+        assert_eq!(record.effective(NOW + 60), defaults());
+        let permanent = LogRecord::from_order(&order(5, LogLevels::all(LogLevel::Off), None));
+        assert_eq!(permanent.effective(u64::MAX), LogLevels::all(LogLevel::Off));
+    }
+}
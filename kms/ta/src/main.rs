@@ -17,19 +17,62 @@
 
 #![no_main]
 
+mod aggregator;
+mod allowance_guard;
 mod attestation;
 mod bip32_secp;
+mod bip85;
+mod challenge;
+mod crash_dump;
+mod dapp_storage;
+mod decrypt_session;
+mod deployment_policy;
+mod device_seal;
+mod ed25519;
 mod eip712;
-mod hash;
+#[cfg(feature = "eth-wallet-compat")]
+mod eth_wallet_compat;
+mod key_cache;
+mod log_level;
+mod migration;
+mod multisig;
+mod nonce_guard;
+mod offline_replay;
+mod otp_vault;
+mod path_policy;
+mod provider;
+mod refresh_token;
+mod remote_approval;
+mod replication;
+#[cfg(feature = "secure-display")]
+mod secure_display;
+mod self_test;
+mod session_scope;
+mod signing_context;
+mod slashing;
+mod social_recovery;
+mod spending_policy;
+mod stealth;
+mod storage_gc;
+mod storage_quota;
+mod ta_config;
+mod ta_global;
+mod tamper;
+mod telemetry;
+mod threshold_vault;
+mod transfer;
 mod wallet;
 
 use optee_utee::{
     ta_close_session, ta_create, ta_destroy, ta_invoke_command, ta_open_session, trace_println,
 };
-use optee_utee::{DataFlag, Error, ErrorKind, ObjectStorageConstants, Parameters, PersistentObject, Random, Time};
+use optee_utee::{DataFlag, Error, ErrorKind, ObjectStorageConstants, Parameters, PersistentObject};
 
 // SPIKE
 mod bls;
+use proto::log_level::{LogCategory, LogLevel};
+use proto::storage_gc::ObjectKind;
+use proto::wire::{self, RequestHeader};
 use proto::Command;
 use secure_db::{SecureStorageClient, Storable};
 
@@ -52,6 +95,16 @@ const RPMB_COUNTER_ID: &[u8] = b"kms_arc_v1";
 
 type HmacSha256 = Hmac<Sha256>;
 
+/// `trace_println!` gated by the category's level in force (log_level.rs);
+/// SetLogLevel changes it without rebuilding the TA.
+macro_rules! ta_log {
+    ($category:ident, $level:ident, $($arg:tt)*) => {
+        if log_level::enabled(LogCategory::$category, LogLevel::$level) {
+            trace_println!($($arg)*);
+        }
+    };
+}
+
 // ========================================
 // LRU Wallet Cache (TA is single-threaded)
 // ========================================
@@ -249,10 +302,10 @@ impl JwtSecretStore {
         }
 
         let mut secret = vec![0u8; 32];
-        Random::generate(secret.as_mut_slice());
+        provider::fill(secret.as_mut_slice());
 
         let mut kid_bytes = [0u8; 8];
-        Random::generate(&mut kid_bytes);
+        provider::fill(&mut kid_bytes);
         let kid = format!("v{}", hex::encode(kid_bytes));
 
         self.entries.push(JwtSecretEntry {
@@ -287,6 +340,7 @@ fn cache_put(wallet: &Wallet) {
 // signable from a stale cache entry.
 fn cache_remove(wallet_id: &Uuid) {
     WALLET_CACHE.with(|c| c.borrow_mut().remove(wallet_id));
+    key_cache::invalidate_wallet(wallet_id);
 }
 
 fn cache_len() -> usize {
@@ -294,41 +348,8 @@ fn cache_len() -> usize {
 }
 
 // ========================================
-// Pending WebAuthn challenge table (issue #49 — TA-side anti-replay)
+// WebAuthn challenge policy (issue #49); the table itself is challenge.rs
 // ========================================
-// The TA issues one-time 32-byte nonces via GetChallenge and verifies/consumes
-// them inside verify_passkey_for_wallet. This closes the replay hole where a
-// compromised CA could resubmit one captured assertion to authorize arbitrary
-// payloads: the nonce is signed by the authenticator (it IS the WebAuthn
-// challenge), checked against the TA's own table, and deleted on first use.
-//
-// Design constraints (see kms memory + issue #49):
-//   * IN-MEMORY only, never TEE secure storage. Nonces are short-lived; losing
-//     them on TA restart only forces a re-challenge. This also avoids the
-//     secure-storage-write-then-TLS-access hazard (H-3): we never touch this
-//     table after a storage write inside a single command.
-//   * Vec instead of HashMap — std HashMap's SipHasher pulls getrandom, which
-//     panics in the OP-TEE TA. Same rationale as WALLET_CACHE above.
-//   * PROCESS-GLOBAL static, NOT thread_local. The host serializes TA calls
-//     (single-worker queue in ta_client.rs) so access is strictly serial — but
-//     OP-TEE may dispatch consecutive InvokeCommands onto DIFFERENT pool
-//     threads. A thread_local nonce issued by GetChallenge would then be
-//     invisible to the verify on the very next call, which showed up as a flaky
-//     "No pending challenge" (#49 / #61). A global UnsafeCell shares the table
-//     across all TA threads; the serial-invocation guarantee makes the unsafe
-//     access sound without a lock or a new crate. Unlike WALLET_CACHE, this
-//     table has NO secure-storage fallback — a lost entry is unrecoverable, so
-//     correctness here must not depend on thread affinity.
-
-/// Challenge nonce lifetime. An assertion whose nonce was issued more than this
-/// many seconds ago is rejected even if the nonce still matches.
-const CHALLENGE_TTL_SECS: i64 = 300;
-
-/// Upper bound on simultaneously-pending challenges. Bounds memory and limits a
-/// compromised CA's ability to exhaust the TA by spamming GetChallenge. Oldest
-/// entries are evicted when full (a dropped pending challenge just forces the
-/// honest client to re-request — it cannot authorize anything).
-const MAX_PENDING_CHALLENGES: usize = 256;
 
 /// Issue #49 enforcement policy.
 ///
@@ -355,48 +376,10 @@ const ENFORCE_TA_CHALLENGE: bool = true;
 #[cfg(not(feature = "strict-challenge"))]
 const ENFORCE_TA_CHALLENGE: bool = false;
 
-struct PendingChallenge {
-    wallet_id: Uuid,
-    nonce: [u8; 32],
-    issued_at: i64,
-}
-
-/// Process-global pending-nonce table, shared across ALL TA pool threads.
-/// See the "Design constraints" block above for why this must be a global
-/// static rather than a `thread_local` (flaky "No pending challenge", #49/#61).
-struct GlobalChallenges(core::cell::UnsafeCell<Vec<PendingChallenge>>);
-
-// SAFETY: this cell is only ever accessed serially, for two independent reasons
-// rooted in the OP-TEE / GP execution model — NOT in any host-side discipline
-// (the CA's single-worker queue is merely an additional Rust-side serialization,
-// not what makes this sound):
-//   1. Same session: GP TEE Client API `TEEC_InvokeCommand` is a BLOCKING call,
-//      so a client cannot have two in-flight commands on one session — commands
-//      on a given session are inherently serial. The KMS CA uses ONE persistent
-//      session (ta_client.rs keeps it open to avoid the ~4.4s per-open cost), so
-//      all real traffic flows through that single session.
-//   2. Different sessions: this TA is built with default properties
-//      (TA_FLAGS = 0 → gpd.ta.singleInstance = false), so EACH session gets its
-//      own TA instance in its own address space — a second session has its own
-//      copy of this static and cannot alias the first session's cell.
-// In both cases no two threads ever hold a `&mut` to the SAME cell; `with_pending`
-// further confines the `&mut` to one synchronous closure that never escapes across
-// an InvokeCommand boundary. (If the TA were ever rebuilt as singleInstance +
-// multiSession, this reasoning breaks and an explicit TA-side lock would be
-// required; the build uses default flags today and ta.json is absent, so it is
-// not.)
-unsafe impl Sync for GlobalChallenges {}
-
-static PENDING_CHALLENGES: GlobalChallenges =
-    GlobalChallenges(core::cell::UnsafeCell::new(Vec::new()));
-
-/// Run `f` with exclusive access to the global pending-challenge table.
-/// SAFETY: serial TA invocation (see `GlobalChallenges`) guarantees no
-/// concurrent borrow; the `&mut` does not escape this function.
-fn with_pending<R>(f: impl FnOnce(&mut Vec<PendingChallenge>) -> R) -> R {
-    // SAFETY: see GlobalChallenges — serial access, borrow confined to `f`.
-    let tbl = unsafe { &mut *PENDING_CHALLENGES.0.get() };
-    f(tbl)
+/// STRICT when built so, or when the installed TA config asks for it — a
+/// config can turn strict mode on for a transition image, never off.
+fn enforce_ta_challenge() -> bool {
+    ENFORCE_TA_CHALLENGE || ta_config::require_ta_challenge()
 }
 
 /// Generate a fresh 32-byte nonce, record it for `wallet_id`, and return it.
@@ -404,57 +387,11 @@ fn with_pending<R>(f: impl FnOnce(&mut Vec<PendingChallenge>) -> R) -> R {
 /// challenge is valid — requesting a new one invalidates the old).
 fn challenge_issue(wallet_id: &Uuid) -> [u8; 32] {
     let mut nonce = [0u8; 32];
-    Random::generate(&mut nonce);
-    let issued_at = tee_unix_secs();
-    with_pending(|tbl| {
-        // Drop any existing pending challenge for this wallet (one live nonce per wallet).
-        tbl.retain(|e| &e.wallet_id != wallet_id);
-        // Bound memory: evict the oldest entry if at capacity.
-        if tbl.len() >= MAX_PENDING_CHALLENGES {
-            if let Some((idx, _)) = tbl
-                .iter()
-                .enumerate()
-                .min_by_key(|(_, e)| e.issued_at)
-            {
-                tbl.swap_remove(idx);
-            }
-        }
-        tbl.push(PendingChallenge {
-            wallet_id: *wallet_id,
-            nonce,
-            issued_at,
-        });
-    });
+    provider::fill(&mut nonce);
+    challenge::issue(wallet_id, nonce, tee_unix_secs());
     nonce
 }
 
-/// Look up the pending nonce for `wallet_id` WITHOUT removing it.
-/// Used to verify an incoming challenge before committing to consuming it: a
-/// request carrying a wrong/expired challenge must NOT burn a victim's still-
-/// valid pending nonce (DoS-on-nonce). The nonce is consumed (challenge_consume)
-/// only after every binding/length/match/TTL check has passed.
-fn challenge_peek(wallet_id: &Uuid) -> Option<([u8; 32], i64)> {
-    with_pending(|tbl| {
-        tbl.iter()
-            .find(|e| &e.wallet_id == wallet_id)
-            .map(|e| (e.nonce, e.issued_at))
-    })
-}
-
-/// Look up and CONSUME (remove) the pending nonce for `wallet_id`.
-/// Returns the (nonce, issued_at) if one was present. The removal makes the
-/// nonce strictly one-time: a replayed assertion finds nothing to match.
-fn challenge_consume(wallet_id: &Uuid) -> Option<([u8; 32], i64)> {
-    with_pending(|tbl| {
-        if let Some(idx) = tbl.iter().position(|e| &e.wallet_id == wallet_id) {
-            let e = tbl.swap_remove(idx);
-            Some((e.nonce, e.issued_at))
-        } else {
-            None
-        }
-    })
-}
-
 // ── P256 Session Key storage ──
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
@@ -569,7 +506,9 @@ fn rpmb_read_counter_ex() -> Result<(u64, bool)> {
                 // "counter absent" rather than failing every operation;
                 // epoch_check's C-2 path handles counter==absent gracefully.
                 // Anti-rollback is inactive in REE-FS mode (tracked in #50).
-                trace_println!(
+                ta_log!(
+                    Storage,
+                    Warn,
                     "[!] RPMB counter unreadable ({:?}) — RPMB unavailable, anti-rollback degraded to REE-FS mode",
                     e
                 );
@@ -620,7 +559,7 @@ fn rpmb_write_counter(value: u64) -> Result<()> {
         &value.to_be_bytes(),
     ) {
         Ok(_) => {
-            trace_println!("[+] RPMB anti-rollback counter written: {}", value);
+            ta_log!(Storage, Info, "[+] RPMB anti-rollback counter written: {}", value);
             Ok(())
         }
         Err(e) => {
@@ -628,7 +567,9 @@ fn rpmb_write_counter(value: u64) -> Result<()> {
             // counter write instead of failing the whole mutation. Anti-rollback
             // is inactive in REE-FS mode — acceptable degradation, tracked in
             // #50; the wallet itself is still persisted via REE-FS.
-            trace_println!(
+            ta_log!(
+                Storage,
+                Warn,
                 "[!] RPMB counter write skipped ({:?}) — RPMB unavailable, anti-rollback degraded to REE-FS mode",
                 e
             );
@@ -688,7 +629,9 @@ fn epoch_check(
     if !counter_present {
         // C-2: counter object missing → no baseline exists. Re-establish it
         // from this wallet's epoch rather than rejecting a legitimate wallet.
-        trace_println!(
+        ta_log!(
+            Storage,
+            Warn,
             "[!] anti-rollback: RPMB counter absent, re-initializing to epoch {} for {:?}",
             epoch, wallet_id
         );
@@ -732,7 +675,9 @@ fn load_wallet_cached(wallet_id: &Uuid) -> Result<Wallet> {
         let changed = w.ensure_seed_cached()?;
         if changed {
             // Update in-memory cache only — NO db.put (would corrupt TLS)
-            trace_println!(
+            ta_log!(
+                Storage,
+                Warn,
                 "[!] load_wallet_cached: cold seed computed for {:?}, memory-only cache",
                 wallet_id
             );
@@ -740,7 +685,9 @@ fn load_wallet_cached(wallet_id: &Uuid) -> Result<Wallet> {
         }
         // Recovery: complete interrupted RPMB write — AFTER all TLS (cache_put above)
         if needs_recovery {
-            trace_println!(
+            ta_log!(
+                Storage,
+                Warn,
                 "[!] load_wallet_cached: recovering RPMB counter to {} for {:?}",
                 w.rollback_epoch, wallet_id
             );
@@ -759,7 +706,9 @@ fn load_wallet_cached(wallet_id: &Uuid) -> Result<Wallet> {
 
     let changed = w.ensure_seed_cached()?;
     if changed {
-        trace_println!(
+        ta_log!(
+            Storage,
+            Warn,
             "[!] load_wallet_cached: cold seed from storage for {:?}, memory-only cache",
             wallet_id
         );
@@ -768,7 +717,9 @@ fn load_wallet_cached(wallet_id: &Uuid) -> Result<Wallet> {
     cache_put(&w);
     // Recovery: complete interrupted RPMB write — AFTER cache_put (last TLS access)
     if needs_recovery {
-        trace_println!(
+        ta_log!(
+            Storage,
+            Warn,
             "[!] load_wallet_cached: recovering RPMB counter to {} for {:?}",
             w.rollback_epoch, wallet_id
         );
@@ -780,6 +731,9 @@ fn load_wallet_cached(wallet_id: &Uuid) -> Result<Wallet> {
 #[ta_create]
 fn create() -> optee_utee::Result<()> {
     trace_println!("[+] TA create");
+    // Storage is not reachable yet; the orphan pass runs after this
+    // instance's first command (storage_gc.rs).
+    storage_gc::arm();
     Ok(())
 }
 
@@ -803,21 +757,14 @@ fn open_session(_params: &mut Parameters) -> optee_utee::Result<()> {
 #[ta_close_session]
 fn close_session() {
     trace_println!("[+] TA close session");
+    // Derived child keys live only as long as the session that produced them.
+    key_cache::clear();
 }
 
 #[ta_destroy]
 fn destroy() {
     trace_println!("[+] TA destroy");
-}
-
-#[cfg(debug_assertions)]
-macro_rules! dbg_println {
-    ($($arg:tt)*) => (trace_println!($($arg)*));
-}
-
-#[cfg(not(debug_assertions))]
-macro_rules! dbg_println {
-    ($($arg:tt)*) => {};
+    key_cache::clear();
 }
 
 // p256-m FFI: P-256 ECDSA verify, sign, and key generation inside TA.
@@ -836,7 +783,7 @@ pub extern "C" fn p256_generate_random(output: *mut u8, output_size: u32) -> i32
         return -1; // P256_RANDOM_FAILED
     }
     let buf = unsafe { std::slice::from_raw_parts_mut(output, output_size as usize) };
-    Random::generate(buf);
+    provider::fill(buf);
     0 // P256_SUCCESS
 }
 
@@ -874,6 +821,9 @@ const DEV_LOCALHOST_RP_ID_HASH: [u8; 32] = [
 /// Two-layer defense: CA pre-verifies with Rust p256 crate before enqueuing the TA call;
 /// TA re-verifies with p256-m (C, ~320ms on Cortex-A7) as defense-in-depth.
 /// Both layers must pass for any sensitive operation.
+///
+/// A supplied assertion that fails counts towards the erase-on-tamper limit
+/// (settled by the dispatcher, see `settle_tamper_guard`); a missing one does not.
 fn verify_passkey_for_wallet(
     wallet: &Wallet,
     assertion: Option<&proto::PasskeyAssertion>,
@@ -882,6 +832,18 @@ fn verify_passkey_for_wallet(
     // can only authorise its declared payload. `None` for non-signing ops (e.g.
     // derive/register/remove) and for sign ops not yet wired to compute it.
     expected_payload: Option<&[u8; 32]>,
+) -> Result<()> {
+    let result = check_passkey_for_wallet(wallet, assertion, expected_payload);
+    if assertion.is_some() {
+        tamper::note_auth(result.is_ok());
+    }
+    result
+}
+
+fn check_passkey_for_wallet(
+    wallet: &Wallet,
+    assertion: Option<&proto::PasskeyAssertion>,
+    expected_payload: Option<&[u8; 32]>,
 ) -> Result<()> {
     let _pubkey = match wallet.get_passkey() {
         Some(pk) => pk,
@@ -928,7 +890,7 @@ fn verify_passkey_for_wallet(
             "WebAuthn rpId hash mismatch: expected SHA-256(\"aastar.io\"), got different value"
         ));
     }
-    trace_println!("[+] rpId hash verified in TA (constant-time)");
+    ta_log!(Crypto, Info, "[+] rpId hash verified in TA (constant-time)");
 
     // Verify User Presence (UP) flag — bit 0 of flags byte (offset 32).
     // Ensures the user physically interacted with the authenticator.
@@ -971,7 +933,9 @@ fn verify_passkey_for_wallet(
     use sha2::Digest;
     let hash_of_signed = sha2::Sha256::digest(&signed_data);
 
-    trace_println!(
+    ta_log!(
+        Crypto,
+        Info,
         "[+] p256-m verify: sig={}B pubkey={}B hash={}B",
         sig_bytes.len(),
         pubkey_xy.len(),
@@ -987,7 +951,7 @@ fn verify_passkey_for_wallet(
         )
     };
 
-    trace_println!("[+] p256-m verify result: {}", ret);
+    ta_log!(Crypto, Info, "[+] p256-m verify result: {}", ret);
 
     if ret != 0 {
         return Err(anyhow!(
@@ -1009,7 +973,7 @@ fn verify_passkey_for_wallet(
 ///     (3) consume the TA's pending nonce for this wallet and require an exact,
 ///     constant-time match plus a fresh (un-expired) issue time. The nonce is
 ///     deleted on first lookup, making it strictly one-time.
-///   * `client_data_json` absent → governed by `ENFORCE_TA_CHALLENGE`:
+///   * `client_data_json` absent → governed by `enforce_ta_challenge()`:
 ///     transition mode logs a warning and allows (legacy ECDSA-only path);
 ///     strict mode rejects.
 fn verify_challenge_binding(
@@ -1020,7 +984,7 @@ fn verify_challenge_binding(
     let client_data_json = match assertion.client_data_json.as_ref() {
         Some(json) => json,
         None => {
-            if ENFORCE_TA_CHALLENGE {
+            if enforce_ta_challenge() {
                 return Err(anyhow!(
                     "Issue #49 strict mode: assertion missing clientDataJSON; \
                      obtain a challenge via GetChallenge and resubmit"
@@ -1029,8 +993,10 @@ fn verify_challenge_binding(
             // Transition: do NOT leave a stale nonce around for this wallet — if
             // one was issued but this legacy assertion bypassed binding, drop it
             // so it cannot be paired with a future replay.
-            let _ = challenge_consume(wallet_id);
-            trace_println!(
+            let _ = challenge::consume(wallet_id);
+            ta_log!(
+                Session,
+                Warn,
                 "[!] Issue #49 TRANSITION: assertion without clientDataJSON accepted (legacy path); \
                  migrate client to GetChallenge flow"
             );
@@ -1061,20 +1027,18 @@ fn verify_challenge_binding(
     // (3) PEEK (do not yet consume) the TA's pending nonce for this wallet. We
     // only remove it once every check below passes, so a request with a wrong or
     // expired challenge cannot burn a victim's still-valid nonce (DoS-on-nonce).
-    let (nonce, issued_at) = challenge_peek(wallet_id).ok_or_else(|| {
+    let (nonce, issued_at) = challenge::peek(wallet_id).ok_or_else(|| {
         anyhow!("No pending challenge for this wallet (replay, expired, or GetChallenge not called)")
     })?;
 
     // Freshness: reject a nonce that, while matching, was issued too long ago.
     // tee_unix_secs uses REE time (TA SystemTime::now() panics — see kms memory).
+    // The nonce lifetime is the TA config's challenge-ttl (300s by default).
     let now = tee_unix_secs();
     let age = now.saturating_sub(issued_at);
-    if age < 0 || age > CHALLENGE_TTL_SECS {
-        return Err(anyhow!(
-            "challenge expired (age {}s > TTL {}s)",
-            age,
-            CHALLENGE_TTL_SECS
-        ));
+    let ttl = ta_config::limits().challenge_ttl_secs as i64;
+    if age < 0 || age > ttl {
+        return Err(anyhow!("challenge expired (age {}s > TTL {}s)", age, ttl));
     }
 
     // (3.5) Issue #68 — payload-COMMITMENT binding (closes V4: CA payload-swap).
@@ -1116,12 +1080,14 @@ fn verify_challenge_binding(
 
             if ct_eq32(&challenge_bytes, &committed) {
                 // Good: the user's signature commits to exactly this payload.
-            } else if !ENFORCE_TA_CHALLENGE && ct_eq32(&challenge_bytes, &nonce) {
+            } else if !enforce_ta_challenge() && ct_eq32(&challenge_bytes, &nonce) {
                 // Transition only: a legacy client used the plain nonce (not yet
                 // computing the commitment). Allowed for migration — V4 stays open
                 // on this path until clients commit AND the strict image ships
                 // (no regression vs pre-#68). Rejected in strict mode (else branch).
-                trace_println!(
+                ta_log!(
+                    Session,
+                    Warn,
                     "[!] Issue #68 TRANSITION: signing op used plain nonce (no payload \
                      commitment); migrate client to challenge = SHA256(nonce||payload)"
                 );
@@ -1143,9 +1109,11 @@ fn verify_challenge_binding(
     // All checks passed — NOW consume the nonce (strictly one-time). Consuming
     // here rather than before the checks means a failed verification leaves the
     // legitimate nonce intact for the real client to retry.
-    let _ = challenge_consume(wallet_id);
+    let _ = challenge::consume(wallet_id);
 
-    trace_println!(
+    ta_log!(
+        Session,
+        Info,
         "[+] Issue #49/#68: challenge verified + consumed (age {}s, payload-committed={})",
         age,
         expected_payload.is_some()
@@ -1263,25 +1231,61 @@ fn create_wallet(input: &proto::CreateWalletInput) -> Result<proto::CreateWallet
             input.passkey_pubkey.len()
         ));
     }
+    create_wallet_inner(
+        Some(&input.passkey_pubkey),
+        input.entropy_seed.as_ref(),
+        input.prf_output.as_ref(),
+    )
+}
 
+/// Shared by CreateWallet and the eth_wallet-compat create. `passkey_pubkey`
+/// is None only on the compat path, whose wallets are then usable only via the
+/// legacy (passkey-less) command shapes.
+fn create_wallet_inner(
+    passkey_pubkey: Option<&Vec<u8>>,
+    entropy_seed: Option<&Vec<u8>>,
+    prf_output: Option<&[u8; 32]>,
+) -> Result<proto::CreateWalletOutput> {
     // Read RPMB counter before any thread_local access (reads don't corrupt TLS).
     let epoch = rpmb_next_epoch()?;
 
     // If the CA supplied pre-generated entropy (CAAM-bypass mode), use it directly.
     // Otherwise fall back to TEE_GenerateRandom() — which can hang if CAAM TRNG is stuck.
-    let mut wallet = match &input.entropy_seed {
+    let mut wallet = match entropy_seed {
         Some(seed) => {
-            dbg_println!("[+] create_wallet: using CA-provided entropy (CAAM bypass)");
+            ta_log!(
+                Crypto,
+                Debug,
+                "[+] create_wallet: using CA-provided entropy (CAAM bypass)"
+            );
             Wallet::from_seed(seed)?
         }
         None => {
-            dbg_println!("[+] create_wallet: using TEE_GenerateRandom (hardware TRNG)");
+            ta_log!(
+                Crypto,
+                Debug,
+                "[+] create_wallet: using TEE_GenerateRandom (hardware TRNG)"
+            );
             Wallet::new()?
         }
     };
-    wallet.set_passkey(input.passkey_pubkey.clone());
+    // Passkey PRF factor: the final entropy also depends on the authenticator's
+    // hmac-secret, so neither the TEE TRNG nor CA-supplied entropy alone fixes it.
+    if let Some(prf) = prf_output {
+        ta_log!(
+            Crypto,
+            Debug,
+            "[+] create_wallet: mixing passkey PRF output into entropy"
+        );
+        wallet.mix_prf_entropy(prf)?;
+    }
+    if let Some(pk) = passkey_pubkey {
+        wallet.set_passkey(pk.clone());
+    }
     wallet.rollback_epoch = epoch;
-    let wallet_id = wallet.get_id();
+    // The id comes from the account public key (`proto::wallet_id`), so it
+    // is fixed only now that the entropy is final.
+    let wallet_id = wallet.assign_derived_id()?;
 
     // Mnemonic never crosses TEE boundary in production.
     // Only populated with the export-secrets feature (dev/test).
@@ -1290,11 +1294,16 @@ fn create_wallet(input: &proto::CreateWalletInput) -> Result<proto::CreateWallet
     #[cfg(not(feature = "export-secrets"))]
     let mnemonic = String::new();
 
-    dbg_println!("[+] Wallet ID: {:?}", wallet_id);
+    ta_log!(Storage, Debug, "[+] Wallet ID: {:?}", wallet_id);
 
     // Open storage once (a single key-list read does not corrupt TLS); reused
     // for both the count check and the save below.
     let db_client = open_storage()?;
+    // Same entropy, same id: a second create or import of a wallet already
+    // here would overwrite it, passkey and address counters included.
+    if db_client.get::<Wallet>(&wallet_id).is_ok() {
+        bail!("wallet {} already exists on this device", wallet_id);
+    }
 
     // M-4: bound total wallet count to prevent storage exhaustion (DoS).
     // count_entries reads ONLY the in-memory key list — no per-entry object
@@ -1311,27 +1320,58 @@ fn create_wallet(input: &proto::CreateWalletInput) -> Result<proto::CreateWallet
     // therefore does NOT shrink this budget: capacity stays bounded by REE-FS.
     // Measured on FRDM-IMX93: ~100 wallets occupy ~476 KB and /var/lib/tee has
     // >1 GB free → physical room for ~300 000 wallets. We cap at 30 000 (~140 MB)
-    // to keep ~10x headroom AND a hard DoS ceiling on a compromised CA. The old
-    // value of 100 was three orders of magnitude too low for a community/city-
-    // scale KMS and only ever bit us via repeated-E2E test pollution.
+    // by default to keep ~10x headroom AND a hard DoS ceiling on a compromised
+    // CA. The old value of 100 was three orders of magnitude too low for a
+    // community/city-scale KMS and only ever bit us via repeated-E2E test
+    // pollution.
     //
-    // Kept as a build-time const (NOT a runtime/CA-supplied config) on purpose:
-    // this is a security boundary, so a compromised CA must not be able to raise
-    // it. Operators needing a different ceiling change this line and rebuild.
-    const MAX_WALLETS: usize = 30_000;
+    // NOT a CA-supplied setting: this is a security boundary, so a compromised
+    // CA must not be able to raise it. Operators change it with a TA config
+    // signed by the provisioning key (InstallConfig), capped at the ~300 000
+    // REE-FS figure above (TaLimits::CEILING).
+    let max_wallets = ta_config::limits().max_wallets as usize;
     let existing = db_client.count_entries::<Wallet>()?;
-    if existing >= MAX_WALLETS {
-        return Err(anyhow!(
-            "wallet limit reached ({}/{}) — cannot create more wallets",
-            existing, MAX_WALLETS
-        ));
+    let exhausted = |no_space| proto::storage_quota::StorageExhausted {
+        wallets: existing as u32,
+        max_wallets: max_wallets as u32,
+        no_space,
+    };
+    if existing >= max_wallets {
+        storage_quota::record_exhausted();
+        return Err(exhausted(false).into());
     }
 
     // save_wallet does cache_put (TLS) then db.put (corrupts TLS). After this,
     // no more thread_local access — safe to call rpmb_write_counter.
-    save_wallet(&db_client, &wallet)?;
+    if let Err(e) = save_wallet(&db_client, &wallet) {
+        if !proto::storage_quota::is_no_space(&format!("{:?}", e)) {
+            return Err(e);
+        }
+        // Full below the cap: free what no wallet owns and try once more.
+        // The wallet is already cached, so storage only from here.
+        let (deleted, _) = scavenge_automatic(&db_client)?;
+        ta_log!(
+            Storage,
+            Warn,
+            "[!] create_wallet: secure storage full, {} orphaned objects deleted, retrying",
+            deleted
+        );
+        match db_client.put(&wallet) {
+            Err(e) if proto::storage_quota::is_no_space(&format!("{:?}", e)) => {
+                storage_quota::record_exhausted();
+                return Err(exhausted(true).into());
+            }
+            retried => retried?,
+        }
+    }
     rpmb_write_counter(epoch)?;
-    dbg_println!("[+] Wallet saved (passkey bound, RPMB epoch={})", epoch);
+    ta_log!(
+        Storage,
+        Debug,
+        "[+] Wallet saved (passkey bound: {}, RPMB epoch={})",
+        passkey_pubkey.is_some(),
+        epoch
+    );
 
     Ok(proto::CreateWalletOutput {
         wallet_id,
@@ -1340,7 +1380,7 @@ fn create_wallet(input: &proto::CreateWalletInput) -> Result<proto::CreateWallet
 }
 
 fn remove_wallet(input: &proto::RemoveWalletInput) -> Result<proto::RemoveWalletOutput> {
-    trace_println!("[+] Removing wallet: {:?}", input.wallet_id);
+    ta_log!(Storage, Info, "[+] Removing wallet: {:?}", input.wallet_id);
 
     // Read RPMB epoch before any thread_local access (read doesn't corrupt TLS).
     let next_epoch = rpmb_next_epoch()?;
@@ -1361,15 +1401,66 @@ fn remove_wallet(input: &proto::RemoveWalletInput) -> Result<proto::RemoveWallet
     // corrupts TLS).
     cache_remove(&input.wallet_id);
 
-    // delete_entry corrupts TLS; write RPMB counter after.
-    db_client.delete_entry::<Wallet>(&input.wallet_id)?;
-    rpmb_write_counter(next_epoch)?;
-    trace_println!(
-        "[+] Wallet removed (passkey verified, RPMB epoch={})",
-        next_epoch
+    let output = erase_wallet(&db_client, &wallet, next_epoch)?;
+    ta_log!(
+        Storage,
+        Info,
+        "[+] Wallet removed (passkey verified, RPMB epoch={}, certificate signed: {})",
+        next_epoch,
+        output.proof.attestation.is_some()
     );
 
-    Ok(proto::RemoveWalletOutput {})
+    Ok(output)
+}
+
+/// Proof-of-erasure. Overwrite the stored wallet with a zeroed record of the
+/// same size, delete it, persist `next_epoch`, then have the attestation PTA
+/// sign the deletion certificate. Secure storage encrypts each object under
+/// its own key, which is destroyed with the object; the overwrite makes sure
+/// the last version written under that key held no secrets either.
+///
+/// Runs after every thread_local access (db.put corrupts TLS). A missing
+/// attestation PTA leaves the certificate unsigned instead of failing a
+/// deletion that has already happened.
+fn erase_wallet(
+    db_client: &SecureStorageClient,
+    wallet: &Wallet,
+    next_epoch: u64,
+) -> Result<proto::RemoveWalletOutput> {
+    let wallet_id = wallet.get_id();
+    db_client.put(&wallet.erased())?;
+    db_client.delete_entry::<Wallet>(&wallet_id)?;
+    wipe_dapp_storage(db_client, &wallet_id)?;
+    wipe_otp_secrets(db_client, &wallet_id)?;
+    wipe_threshold_key(db_client, &wallet_id)?;
+    rpmb_write_counter(next_epoch)?;
+
+    let (counter, present) = rpmb_read_counter_ex()?;
+    let certificate = proto::erasure::DeletionCertificate {
+        version: proto::erasure::DELETION_CERT_VERSION,
+        wallet_id,
+        deleted_at: tee_unix_secs() as u64,
+        rollback_counter: if present { Some(counter) } else { None },
+    };
+    let nonce = certificate.digest().to_vec();
+    let attestation = match attestation::get_attestation(&proto::GetAttestationInput { nonce }) {
+        Ok(evidence) => Some(evidence),
+        Err(e) => {
+            ta_log!(
+                Storage,
+                Warn,
+                "[!] deletion certificate left unsigned: {:?}",
+                e
+            );
+            None
+        }
+    };
+    Ok(proto::RemoveWalletOutput {
+        proof: proto::erasure::DeletionProof {
+            certificate,
+            attestation,
+        },
+    })
 }
 
 /// Force-remove a wallet from TEE secure storage WITHOUT passkey verification.
@@ -1383,7 +1474,13 @@ fn remove_wallet(input: &proto::RemoveWalletInput) -> Result<proto::RemoveWallet
 fn force_remove_wallet(
     input: &proto::ForceRemoveWalletInput,
 ) -> Result<proto::ForceRemoveWalletOutput> {
-    trace_println!("[!] ForceRemoveWallet (gap key): {:?}", input.wallet_id);
+    ta_log!(
+        Storage,
+        Warn,
+        "[!] ForceRemoveWallet (gap key): {:?}",
+        input.wallet_id
+    );
+    key_cache::invalidate_wallet(&input.wallet_id);
 
     let db_client = SecureStorageClient::open(DB_NAME)?;
     // Confirm the entry exists before deleting
@@ -1400,1392 +1497,5385 @@ fn force_remove_wallet(
         }
     }
 
+    db_client.put(&wallet.erased())?;
     db_client.delete_entry::<Wallet>(&input.wallet_id)?;
-    trace_println!("[!] Gap key purged from TEE secure storage");
+    wipe_dapp_storage(&db_client, &input.wallet_id)?;
+    wipe_otp_secrets(&db_client, &input.wallet_id)?;
+    wipe_threshold_key(&db_client, &input.wallet_id)?;
+    ta_log!(Storage, Warn, "[!] Gap key purged from TEE secure storage");
     Ok(proto::ForceRemoveWalletOutput {})
 }
 
 fn derive_address(input: &proto::DeriveAddressInput) -> Result<proto::DeriveAddressOutput> {
     let wallet = load_wallet_cached(&input.wallet_id)?;
     verify_passkey_for_wallet(&wallet, input.passkey_assertion.as_ref(), None)?;
-    let (address, public_key) = wallet.derive_address(&input.hd_path)?;
+    let (address, public_key) = wallet.derive_chain_address(&input.hd_path, input.chain)?;
     Ok(proto::DeriveAddressOutput {
         address,
         public_key,
+        encoded_address: input.chain.encode_address(&address),
     })
 }
 
 fn sign_transaction(input: &proto::SignTransactionInput) -> Result<proto::SignTransactionOutput> {
-    let wallet = load_wallet_cached(&input.wallet_id)?;
     // Issue #68: bind the challenge to the exact tx digest (RLP keccak) that will
     // be signed — mirrors the LegacyTransaction sign_transaction builds.
     let tx_hash = Wallet::tx_signing_hash(&input.transaction);
-    verify_passkey_for_wallet(&wallet, input.passkey_assertion.as_ref(), Some(&tx_hash))?;
-    let signature = wallet.sign_transaction(&input.hd_path, &input.transaction)?;
+    let signature = sign_checked(input, tx_hash, |wallet| {
+        wallet.sign_transaction(&input.hd_path, &input.transaction)
+    })?;
     Ok(proto::SignTransactionOutput { signature })
 }
 
-fn sign_message(input: &proto::SignMessageInput) -> Result<proto::SignMessageOutput> {
-    let wallet = load_wallet_cached(&input.wallet_id)?;
-    // Issue #68: bind to keccak256(message) — exactly what sign_message signs.
-    let mut msg_hash = [0u8; 32];
-    msg_hash.copy_from_slice(&hash::keccak_hash_to_bytes(input.message.as_slice())[..32]);
-    verify_passkey_for_wallet(&wallet, input.passkey_assertion.as_ref(), Some(&msg_hash))?;
-    let signature = wallet.sign_message(&input.hd_path, &input.message)?;
-    Ok(proto::SignMessageOutput { signature })
+fn sign_eip1559_transaction(
+    input: &proto::SignEip1559TransactionInput,
+) -> Result<proto::SignEip1559TransactionOutput> {
+    input.transaction.check().map_err(|e| anyhow!(e))?;
+    // The checks see the fee cap as the gas price; the passkey and any
+    // approval bind the type-2 signing hash.
+    let view = proto::SignTransactionInput {
+        wallet_id: input.wallet_id,
+        hd_path: input.hd_path.clone(),
+        transaction: input.transaction.as_call(),
+        passkey_assertion: input.passkey_assertion.clone(),
+        summary_abis: input.summary_abis.clone(),
+        summary_committed: input.summary_committed,
+    };
+    let raw_transaction = sign_checked(&view, input.transaction.signing_hash(), |wallet| {
+        wallet.sign_eip1559_transaction(&input.hd_path, &input.transaction)
+    })?;
+    Ok(proto::SignEip1559TransactionOutput { raw_transaction })
 }
 
-fn sign_hash(input: &proto::SignHashInput) -> Result<proto::SignHashOutput> {
+/// Remote approval, passkey, allowance and spending policy, nonce tracking
+/// and secure display for a transaction whose signing hash is `tx_hash`, then `sign`.
+/// Above the wallet's multi-sig threshold the signature is held for the
+/// co-signers and the Sign answers `MultiSigPending`.
+fn sign_checked(
+    input: &proto::SignTransactionInput,
+    tx_hash: [u8; 32],
+    sign: impl FnOnce(&Wallet) -> Result<Vec<u8>>,
+) -> Result<Vec<u8>> {
     let wallet = load_wallet_cached(&input.wallet_id)?;
-    // Issue #68: SignHash is the canonical "sign this exact 32-byte digest" path
-    // (ERC-4337 userOpHash). Bind the challenge to that digest so a payload-bound
-    // assertion can only authorise this hash, not a CA-substituted one.
-    verify_passkey_for_wallet(&wallet, input.passkey_assertion.as_ref(), Some(&input.hash))?;
-    let signature = wallet.sign_hash(&input.hd_path, &input.hash)?;
-    Ok(proto::SignHashOutput { signature })
-}
-
-// ── Variant B: BLS (DVT 共签)—— 密钥在 TA 内生成+密封，永不出 TEE ──
-
-/// 生成独立 BLS12-381 密钥(TEE TRNG 熵)→ 密封 secure storage → 返回 48B 压缩公钥。
-/// 一次性 provision;已存在则拒绝(不覆盖）。无 passkey 门（DVT 自主签名，授权在 host owner-auth）。
-fn bls_gen_key(input: &proto::BlsGenKeyInput) -> Result<proto::BlsGenKeyOutput> {
+    // A summary-committing client confirmed the decoded action, not just the
+    // hash: rebuild the summary here from the tx we are about to sign, so a CA
+    // that showed a different description fails the passkey check.
+    let payload = if input.summary_committed {
+        let summary =
+            proto::calldata::summarize_transaction(&input.transaction, &input.summary_abis);
+        ta_log!(Crypto, Debug, "[+] SignTransaction summary: {}", summary);
+        proto::calldata::confirmation_digest(&tx_hash, &summary)
+    } else {
+        tx_hash
+    };
     let db = open_storage()?;
-    let key_id = input.key_id.to_string();
-    // Codex Med#3: enforce a singleton — one BLS key per board. Stops a looped
-    // /gen-key from filling secure storage. Removing the key is an explicit op.
-    if db.count_entries::<BlsKey>()? > 0 {
-        return Err(anyhow!(
-            "a BLS key already exists (singleton); remove it before provisioning a new one"
-        ));
+    // Before the passkey, so a Sign sent ahead of the phone's answer does
+    // not use up the ceremony.
+    let mut approver = load_remote_approver(&db, &input.wallet_id)?;
+    let remote = approver.key.is_some();
+    let now = tee_unix_secs();
+    if remote && !approver.approves(&payload, now) {
+        bail!("remote approval required: the paired approver has not approved this transaction");
     }
-    if db.get::<BlsKey>(&key_id).is_ok() {
-        return Err(anyhow!("BLS key already exists: {}", key_id));
+    verify_passkey_for_wallet(&wallet, input.passkey_assertion.as_ref(), Some(&payload))?;
+
+    let mut policy = load_allowance_policy(&db, &input.wallet_id);
+    let spenders = load_spender_allow_list(&db, &input.wallet_id);
+    let decoded = proto::calldata::decode_transaction(&input.transaction, &input.summary_abis);
+    let violation = allowance_guard::violation(&policy.rule, &decoded)
+        .or_else(|| allowance_guard::spender_violation(&spenders, &decoded));
+    if let Some(ref reason) = violation {
+        // The override is bound to the summary digest, so only a
+        // summary-committed sign can ever match one.
+        if !input.summary_committed || !policy.take_override(&payload, tee_unix_secs()) {
+            bail!(
+                "allowance policy: {}; confirm with ConfirmAllowanceOverride first",
+                reason
+            );
+        }
+        ta_log!(Policy, Warn, "[!] allowance policy overridden: {}", reason);
     }
-    let mut ikm = [0u8; 32];
-    Random::generate(&mut ikm);
-    let (sk, pk) = bls::gen_keypair(&ikm)?;
-    if !bls::pubkey_valid(&pk) {
-        return Err(anyhow!("BLS keygen produced invalid pubkey"));
+    // Not overridable: the owner changes the policy instead.
+    let spend = check_spending_policy(&db, &input.wallet_id, &input.transaction, &decoded)?;
+    let nonces = check_nonce(
+        &db,
+        &wallet,
+        &input.wallet_id,
+        &input.hd_path,
+        &input.transaction,
+    )?;
+    let mut multisig = load_multisig(&db, &input.wallet_id)?;
+    let held = multisig.holds(input.transaction.value);
+    // Last, once every other check has passed: the user is only asked to
+    // press the button for a transaction the TA would otherwise sign.
+    #[cfg(feature = "secure-display")]
+    secure_display::confirm(&proto::secure_display::Screen::for_transaction(
+        &input.transaction,
+        &input.summary_abis,
+    ))?;
+    // H-3: sign before the storage write below.
+    let signature = sign(&wallet)?;
+    // Held before anything is consumed, so a full queue refuses cleanly.
+    let pending = if held {
+        Some(multisig.hold(payload, &input.hd_path, signature.clone(), now)?)
+    } else {
+        None
+    };
+    if remote {
+        // Consumed before the signature leaves, as the override below is.
+        approver.take(&payload, now);
+        db.put(&approver)
+            .map_err(|e| anyhow!("Failed to consume remote approval: {}", e))?;
     }
-    db.put(&BlsKey {
-        key_id: key_id.clone(),
-        private_key: sk,
-        public_key: pk.to_vec(),
-    })?;
-    Ok(proto::BlsGenKeyOutput {
-        key_id: input.key_id,
-        public_key: pk.to_vec(),
-    })
+    if violation.is_some() {
+        // Consume the override before releasing the signature; if the write
+        // fails the signature is dropped.
+        db.put(&policy)
+            .map_err(|e| anyhow!("Failed to consume allowance override: {}", e))?;
+    }
+    if let Some(record) = spend {
+        db.put(&record)
+            .map_err(|e| anyhow!("Failed to record daily spend: {}", e))?;
+    }
+    if let Some(record) = nonces {
+        db.put(&record)
+            .map_err(|e| anyhow!("Failed to record signed nonce: {}", e))?;
+    }
+    if let Some(pending) = pending {
+        db.put(&multisig)
+            .map_err(|e| anyhow!("Failed to hold signature for co-signers: {}", e))?;
+        ta_log!(Policy, Warn, "[!] {}", pending);
+        return Err(pending.into());
+    }
+    Ok(signature)
 }
 
-/// 用密封的 BLS 私钥签 32B message（userOpHash）。私钥不出 TEE，只回签名。
-fn bls_sign(input: &proto::BlsSignInput) -> Result<proto::BlsSignOutput> {
-    let db = open_storage()?;
-    let k = db
-        .get::<BlsKey>(&input.key_id.to_string())
-        .map_err(|_| anyhow!("BLS key not found: {}", input.key_id))?;
-    let (eip2537, compact) = bls::sign(&k.private_key, &input.message)?;
-    Ok(proto::BlsSignOutput {
-        signature: eip2537.to_vec(),
-        signature_compact: compact.to_vec(),
-    })
+fn load_allowance_policy(
+    db: &SecureStorageClient,
+    wallet_id: &Uuid,
+) -> allowance_guard::AllowancePolicy {
+    db.get::<allowance_guard::AllowancePolicy>(&allowance_guard::AllowancePolicy::store_id_for(
+        wallet_id,
+    ))
+    .unwrap_or_else(|_| allowance_guard::AllowancePolicy::empty(wallet_id))
 }
 
-/// CC-37 staked registration: BLS proof-of-possession (RFC-standard self-PoP over the node's
-/// OWN pubkey). Derives the pubkey from the sealed key and signs it under BLS_DST — the caller
-/// supplies no message, so /pop is not a signing oracle. Returns the DvtPop tuple.
-fn bls_pop_sign(input: &proto::BlsPopSignInput) -> Result<proto::BlsPopSignOutput> {
-    let db = open_storage()?;
-    let k = db
-        .get::<BlsKey>(&input.key_id.to_string())
-        .map_err(|_| anyhow!("BLS key not found: {}", input.key_id))?;
-    let (public_key, pop_point, pop_signature) = bls::sign_pop(&k.private_key)?;
-    Ok(proto::BlsPopSignOutput {
-        public_key: public_key.to_vec(),
-        pop_point: pop_point.to_vec(),
-        pop_signature: pop_signature.to_vec(),
-    })
+fn load_spender_allow_list(
+    db: &SecureStorageClient,
+    wallet_id: &Uuid,
+) -> allowance_guard::SpenderAllowList {
+    db.get::<allowance_guard::SpenderAllowList>(&allowance_guard::SpenderAllowList::store_id_for(
+        wallet_id,
+    ))
+    .unwrap_or_else(|_| allowance_guard::SpenderAllowList::empty(wallet_id))
 }
 
-/// 返回密封 BLS 密钥的 48B 压缩公钥。
-fn bls_pubkey(input: &proto::BlsPubKeyInput) -> Result<proto::BlsPubKeyOutput> {
+fn set_allowance_policy(
+    input: &proto::SetAllowancePolicyInput,
+) -> Result<proto::SetAllowancePolicyOutput> {
+    ta_log!(
+        Policy,
+        Warn,
+        "[!] Set allowance policy for wallet: {:?} -> {:?}",
+        input.wallet_id,
+        input.rule
+    );
+    let wallet = load_wallet_cached(&input.wallet_id)?;
+    verify_passkey_for_wallet(
+        &wallet,
+        input.passkey_assertion.as_ref(),
+        Some(&allowance_guard::policy_commitment(
+            &input.wallet_id,
+            &input.rule,
+        )),
+    )?;
     let db = open_storage()?;
-    let k = db
-        .get::<BlsKey>(&input.key_id.to_string())
-        .map_err(|_| anyhow!("BLS key not found: {}", input.key_id))?;
-    Ok(proto::BlsPubKeyOutput {
-        public_key: k.public_key,
-    })
+    let mut policy = load_allowance_policy(&db, &input.wallet_id);
+    let previous = policy.rule;
+    policy.rule = input.rule;
+    // An override armed under the old rule must not leak into the new one.
+    policy.pending_override = None;
+    db.put(&policy)
+        .map_err(|e| anyhow!("Failed to save allowance policy: {}", e))?;
+    Ok(proto::SetAllowancePolicyOutput { previous })
 }
 
-/// 删除密封的 BLS 单例(枚举所有 BlsKey 逐个 delete)。用于恢复"key_id 丢失的孤儿
-/// key"或轮换——不需要知道 key_id(枚举得到)。返回删除数量。host 侧双门控。
-fn bls_remove(_input: &proto::BlsRemoveInput) -> Result<proto::BlsRemoveOutput> {
+fn set_spender_allow_list(
+    input: &proto::SetSpenderAllowListInput,
+) -> Result<proto::SetSpenderAllowListOutput> {
+    ta_log!(
+        Policy,
+        Warn,
+        "[!] Set spender allow-list for wallet: {:?} ({} spenders)",
+        input.wallet_id,
+        input.spenders.len()
+    );
+    let wallet = load_wallet_cached(&input.wallet_id)?;
+    verify_passkey_for_wallet(
+        &wallet,
+        input.passkey_assertion.as_ref(),
+        Some(&allowance_guard::spender_list_commitment(
+            &input.wallet_id,
+            &input.spenders,
+        )),
+    )?;
     let db = open_storage()?;
-    let entries = db.list_entries::<BlsKey>()?;
-    let mut removed = 0u32;
-    for key_id in entries.keys() {
-        db.delete_entry::<BlsKey>(key_id)?;
-        removed += 1;
-    }
-    Ok(proto::BlsRemoveOutput { removed })
+    let mut list = load_spender_allow_list(&db, &input.wallet_id);
+    let previous = list.spenders.clone();
+    list.set(&input.spenders).map_err(|e| anyhow!("{}", e))?;
+    db.put(&list)
+        .map_err(|e| anyhow!("Failed to save spender allow-list: {}", e))?;
+    Ok(proto::SetSpenderAllowListOutput { previous })
 }
 
-// ── CC-34: keeper/operator ECDSA(secp256k1)—— 密钥在 TA 内生成+密封，永不出 TEE ──
+// ── Spending policy ──
 
-/// Ethereum address = last 20 bytes of keccak256(uncompressed_pubkey[1..]).
-fn eth_address_from_uncompressed(pk65: &[u8; 65]) -> [u8; 20] {
-    let h = eip712::keccak(&pk65[1..]);
-    let mut addr = [0u8; 20];
-    addr.copy_from_slice(&h[12..]);
-    addr
+/// Fail closed: an unreadable record must not read as "no policy".
+fn load_spending_record(
+    db: &SecureStorageClient,
+    wallet_id: &Uuid,
+) -> Result<spending_policy::SpendingRecord> {
+    let store_id = spending_policy::SpendingRecord::store_id_for(wallet_id);
+    match db.get::<spending_policy::SpendingRecord>(&store_id) {
+        Ok(record) => Ok(record),
+        Err(e) => {
+            let msg = e.to_string();
+            if msg.contains("ItemNotFound") || msg.contains("ITEM_NOT_FOUND") {
+                Ok(spending_policy::SpendingRecord::empty(wallet_id))
+            } else {
+                Err(anyhow!("spending policy: secure storage error: {}", msg))
+            }
+        }
+    }
 }
 
-/// 生成独立 secp256k1 keeper 密钥(TEE TRNG 熵)→ 密封 secure storage → 返回 65B 未压缩
-/// 公钥 + 20B 以太坊地址(充值 EOA)。一次性 provision;已存在则拒绝(singleton,不覆盖)。
-/// 无 passkey 门（keeper 自主签名，授权在 host provisioning-gate + X-Signer-Token）。
-fn keeper_gen_key(input: &proto::KeeperGenKeyInput) -> Result<proto::KeeperGenKeyOutput> {
-    let db = open_storage()?;
-    let key_id = input.key_id.to_string();
-    // Singleton: one keeper key per board (mirrors bls_gen_key). Stops a looped
-    // /gen-keeper-eoa from filling secure storage.
-    if db.count_entries::<KeeperKey>()? > 0 {
-        return Err(anyhow!(
-            "a keeper key already exists (singleton); remove it before provisioning a new one"
-        ));
+/// Refuses `tx` with a `PolicyViolation` if it breaks the wallet's spending
+/// policy. Otherwise returns the record to write before the signature
+/// leaves, when `tx` counts against the daily limit.
+fn check_spending_policy(
+    db: &SecureStorageClient,
+    wallet_id: &Uuid,
+    tx: &proto::EthTransaction,
+    decoded: &proto::calldata::DecodedCall,
+) -> Result<Option<spending_policy::SpendingRecord>> {
+    let mut record = load_spending_record(db, wallet_id)?;
+    if record.policy.is_off() {
+        return Ok(None);
     }
-    if db.get::<KeeperKey>(&key_id).is_ok() {
-        return Err(anyhow!("keeper key already exists: {}", key_id));
+    let day = proto::spending_policy::day_of(tee_unix_secs().max(0) as u64);
+    if let Err(violation) = record.policy.check(tx, decoded, record.spend.spent_on(day)) {
+        ta_log!(Policy, Warn, "[!] {} (wallet {:?})", violation, wallet_id);
+        return Err(violation.into());
     }
-    let secp = secp256k1::Secp256k1::new();
-    // Rejection-sample TEE randomness until it is a valid secp256k1 scalar.
-    let secret_key = loop {
-        let mut sk_bytes = [0u8; 32];
-        Random::generate(&mut sk_bytes);
-        if let Ok(sk) = secp256k1::SecretKey::from_slice(&sk_bytes) {
-            break sk;
+    if !record.counts(tx.value) {
+        return Ok(None);
+    }
+    record.spend.add(day, tx.value);
+    Ok(Some(record))
+}
+
+fn spending_policy(input: &proto::SpendingPolicyInput) -> Result<proto::SpendingPolicyOutput> {
+    let wallet = load_wallet_cached(&input.wallet_id)?;
+    let db = open_storage()?;
+    let mut record = load_spending_record(&db, &input.wallet_id)?;
+    let day = proto::spending_policy::day_of(tee_unix_secs().max(0) as u64);
+    let requested = match &input.set {
+        Some(requested) => requested,
+        None => {
+            return Ok(proto::SpendingPolicyOutput {
+                spent_today_wei: record.spend.spent_on(day),
+                policy: record.policy,
+                previous: None,
+            })
         }
     };
-    let pk = secp256k1::PublicKey::from_secret_key(&secp, &secret_key);
-    let pk_uncompressed = pk.serialize_uncompressed(); // [u8; 65], leading 0x04
-    let address = eth_address_from_uncompressed(&pk_uncompressed);
-    db.put(&KeeperKey {
-        key_id: key_id.clone(),
-        private_key: secret_key.secret_bytes(),
-        public_key: pk_uncompressed.to_vec(),
-    })?;
-    Ok(proto::KeeperGenKeyOutput {
-        key_id: input.key_id,
-        public_key: pk_uncompressed.to_vec(),
-        address,
+    ta_log!(
+        Policy,
+        Warn,
+        "[!] Set spending policy for wallet: {:?} -> {:?}",
+        input.wallet_id,
+        requested
+    );
+    let policy = requested.normalized().map_err(|e| anyhow!(e))?;
+    verify_passkey_for_wallet(
+        &wallet,
+        input.passkey_assertion.as_ref(),
+        Some(&proto::spending_policy::policy_commitment(
+            &input.wallet_id,
+            requested,
+        )),
+    )?;
+    let previous = std::mem::replace(&mut record.policy, policy);
+    db.put(&record)
+        .map_err(|e| anyhow!("Failed to save spending policy: {}", e))?;
+    Ok(proto::SpendingPolicyOutput {
+        spent_today_wei: record.spend.spent_on(day),
+        policy: record.policy,
+        previous: Some(previous),
     })
 }
 
-/// 用密封的 keeper 私钥签 32B raw digest（不再 hash）→ 65B recoverable r||s||v (v=27/28)。
-/// 私钥不出 TEE，只回签名。rust-secp256k1 的 sign_ecdsa_recoverable 产出 canonical low-S。
-fn keeper_sign(input: &proto::KeeperSignInput) -> Result<proto::KeeperSignOutput> {
-    let db = open_storage()?;
-    let k = db
-        .get::<KeeperKey>(&input.key_id.to_string())
-        .map_err(|_| anyhow!("keeper key not found: {}", input.key_id))?;
-    let secret_key = secp256k1::SecretKey::from_slice(&k.private_key)?;
-    let secp = secp256k1::Secp256k1::new();
-    let message = secp256k1::Message::from_slice(&input.digest)?;
-    let sig = secp.sign_ecdsa_recoverable(&message, &secret_key);
-    let (recovery_id, sig_bytes) = sig.serialize_compact();
-    let mut signature = Vec::with_capacity(65);
-    signature.extend_from_slice(&sig_bytes); // r(32) || s(32)
-    signature.push(recovery_id.to_i32() as u8 + 27); // v = 27/28
-    Ok(proto::KeeperSignOutput { signature })
+// ── Nonce tracking ──
+
+/// Fail closed: an unreadable ledger must not read as "tracking off".
+fn load_nonce_record(
+    db: &SecureStorageClient,
+    wallet_id: &Uuid,
+) -> Result<nonce_guard::NonceRecord> {
+    let store_id = nonce_guard::NonceRecord::store_id_for(wallet_id);
+    match db.get::<nonce_guard::NonceRecord>(&store_id) {
+        Ok(record) => Ok(record),
+        Err(e) => {
+            let msg = e.to_string();
+            if msg.contains("ItemNotFound") || msg.contains("ITEM_NOT_FOUND") {
+                Ok(nonce_guard::NonceRecord::empty(wallet_id))
+            } else {
+                Err(anyhow!("nonce tracking: secure storage error: {}", msg))
+            }
+        }
+    }
 }
 
-/// 返回密封 keeper 密钥的 65B 未压缩公钥 + 20B 地址。
-fn keeper_pubkey(input: &proto::KeeperPubKeyInput) -> Result<proto::KeeperPubKeyOutput> {
+/// With tracking on, refuses `tx` with a `NonceRefused` unless its nonce is
+/// above the highest `hd_path`'s address has signed on its chain (or an
+/// override allows it). Otherwise returns the record to write before the
+/// signature leaves.
+fn check_nonce(
+    db: &SecureStorageClient,
+    wallet: &Wallet,
+    wallet_id: &Uuid,
+    hd_path: &str,
+    tx: &proto::EthTransaction,
+) -> Result<Option<nonce_guard::NonceRecord>> {
+    let mut record = load_nonce_record(db, wallet_id)?;
+    if !record.ledger.enabled {
+        return Ok(None);
+    }
+    let (address, _) = wallet.derive_address(hd_path)?;
+    let now = tee_unix_secs().max(0) as u64;
+    if let Err(refused) = record.ledger.admit(tx.chain_id, address, tx.nonce, now) {
+        ta_log!(Policy, Warn, "[!] {} (wallet {:?})", refused, wallet_id);
+        return Err(refused.into());
+    }
+    Ok(Some(record))
+}
+
+fn nonce_tracking(input: &proto::NonceTrackingInput) -> Result<proto::NonceTrackingOutput> {
+    let wallet = load_wallet_cached(&input.wallet_id)?;
     let db = open_storage()?;
-    let k = db
-        .get::<KeeperKey>(&input.key_id.to_string())
-        .map_err(|_| anyhow!("keeper key not found: {}", input.key_id))?;
-    let mut pk65 = [0u8; 65];
-    if k.public_key.len() != 65 {
-        return Err(anyhow!(
-            "sealed keeper pubkey length invalid: {}",
-            k.public_key.len()
-        ));
+    let mut record = load_nonce_record(&db, &input.wallet_id)?;
+    let mut previous = None;
+    if let Some(enabled) = input.set {
+        ta_log!(
+            Policy,
+            Warn,
+            "[!] Set nonce tracking for wallet: {:?} -> {}",
+            input.wallet_id,
+            enabled
+        );
+        verify_passkey_for_wallet(
+            &wallet,
+            input.passkey_assertion.as_ref(),
+            Some(&proto::nonce_guard::tracking_commitment(
+                &input.wallet_id,
+                enabled,
+            )),
+        )?;
+        previous = Some(record.ledger.enabled);
+        record.ledger.set_enabled(enabled);
+        db.put(&record)
+            .map_err(|e| anyhow!("Failed to save nonce tracking: {}", e))?;
     }
-    pk65.copy_from_slice(&k.public_key);
-    let address = eth_address_from_uncompressed(&pk65);
-    Ok(proto::KeeperPubKeyOutput {
-        public_key: k.public_key,
-        address,
+    Ok(proto::NonceTrackingOutput {
+        enabled: record.ledger.enabled,
+        previous,
+        entries: record.ledger.entries,
+        pending_override: record.ledger.pending_override,
     })
 }
 
-// H-1 (DOWNGRADED to Medium — tracked as an accepted limitation / follow-up issue):
-// DeriveAddressAuto carries no passkey assertion and mutates+persists wallet state
-// (next_address_index). It is invoked by the CA immediately after wallet creation,
-// before any passkey-gated flow exists for the new wallet, so requiring a passkey
-// here would break the legitimate creation path (no user assertion is available at
-// that instant). A *compromised CA* could call it directly to advance the address
-// index up to the per-wallet cap (100, enforced in Wallet::increment_address_index),
-// but it cannot exfiltrate keys, sign, or affect other wallets, and the index is now
-// anti-rollback protected (M-5). Net effect of abuse is bounded address churn for a
-// single wallet. A proper fix (bind to a just-created-this-session token, or move the
-// call inline into create_wallet) is deferred to a dedicated issue to avoid breaking
-// the creation flow in this bugfix pass.
-fn derive_address_auto(
-    input: &proto::DeriveAddressAutoInput,
-) -> Result<proto::DeriveAddressAutoOutput> {
-    // M-5: this mutation bumps next_address_index and persists it. Bump the RPMB
-    // anti-rollback epoch too, otherwise the address index could be silently
-    // rolled back to re-derive (reuse) a previously issued address. Read the next
-    // epoch before any thread_local (cache) access; write the counter only after
-    // save_wallet (last TLS access), matching create_wallet's ordering.
-    let epoch = rpmb_next_epoch()?;
+fn override_nonce(input: &proto::OverrideNonceInput) -> Result<proto::OverrideNonceOutput> {
+    ta_log!(
+        Policy,
+        Warn,
+        "[!] Nonce override for wallet {:?}: chain {} nonce {}",
+        input.wallet_id,
+        input.chain_id,
+        input.nonce
+    );
+    let wallet = load_wallet_cached(&input.wallet_id)?;
+    let db = open_storage()?;
+    let mut record = load_nonce_record(&db, &input.wallet_id)?;
+    // Before the passkey, so a wallet without tracking keeps its ceremony.
+    let armed = record
+        .ledger
+        .arm_override(
+            input.chain_id,
+            input.address,
+            input.nonce,
+            tee_unix_secs().max(0) as u64,
+        )
+        .map_err(|e| anyhow!(e))?;
+    verify_passkey_for_wallet(
+        &wallet,
+        input.passkey_assertion.as_ref(),
+        Some(&proto::nonce_guard::override_commitment(
+            &input.wallet_id,
+            input.chain_id,
+            &input.address,
+            input.nonce,
+        )),
+    )?;
+    db.put(&record)
+        .map_err(|e| anyhow!("Failed to arm nonce override: {}", e))?;
+    Ok(proto::OverrideNonceOutput {
+        armed,
+        highest: record.ledger.highest(input.chain_id, &input.address),
+    })
+}
 
-    let db_client = open_storage()?;
+// ── Derivation-path policy ──
 
-    dbg_println!("[+] DeriveAddressAuto for wallet: {:?}", input.wallet_id);
-    let mut wallet = match cache_get(&input.wallet_id) {
-        Some(w) => w,
-        None => db_client
-            .get::<Wallet>(&input.wallet_id)
-            .map_err(|e| anyhow!("wallet not found: {:?}", e))?,
+/// Fail closed: an unreadable policy must not read as "any path".
+fn load_path_policy(db: &SecureStorageClient, wallet_id: &Uuid) -> Result<path_policy::PathPolicy> {
+    match db.get::<path_policy::PathPolicy>(&path_policy::PathPolicy::store_id_for(wallet_id)) {
+        Ok(policy) => Ok(policy),
+        Err(e) => {
+            let msg = e.to_string();
+            if msg.contains("ItemNotFound") || msg.contains("ITEM_NOT_FOUND") {
+                Ok(path_policy::PathPolicy::empty(wallet_id))
+            } else {
+                Err(anyhow!("path policy: secure storage error: {}", msg))
+            }
+        }
+    }
+}
+
+/// Called by `Wallet::derive_key` before every child-key derivation.
+fn enforce_path_policy(wallet_id: &Uuid, coin_type: u32, account: u32, address: u32) -> Result<()> {
+    let patterns = match path_policy::cached(wallet_id) {
+        Some(patterns) => patterns,
+        None => {
+            let db = open_storage()?;
+            let patterns = load_path_policy(&db, wallet_id)?.patterns;
+            path_policy::set_cached(wallet_id, patterns.clone());
+            patterns
+        }
     };
+    if !proto::hd_path::path_allowed(&patterns, account, address) {
+        bail!(
+            "derivation path {} is not allowed by the wallet's path policy",
+            proto::hd_path::coin_path(coin_type, account, address)
+        );
+    }
+    Ok(())
+}
 
-    let address_index = wallet.increment_address_index()?;
-    wallet.ensure_seed_cached()?;
-    wallet.rollback_epoch = epoch;
+fn path_policy(input: &proto::PathPolicyInput) -> Result<proto::PathPolicyOutput> {
+    let wallet = load_wallet_cached(&input.wallet_id)?;
+    let db = open_storage()?;
+    let mut policy = load_path_policy(&db, &input.wallet_id)?;
+    let requested = match &input.set {
+        Some(requested) => requested,
+        None => {
+            return Ok(proto::PathPolicyOutput {
+                patterns: policy.patterns,
+                previous: Vec::new(),
+            })
+        }
+    };
+    ta_log!(
+        Policy,
+        Warn,
+        "[!] Set path policy for wallet: {:?} ({} patterns)",
+        input.wallet_id,
+        requested.len()
+    );
+    let previous = policy.patterns.clone();
+    policy.set(requested).map_err(|e| anyhow!("{}", e))?;
+    // Taking paths away is an operator's call; adding them is the owner's.
+    if !proto::hd_path::narrows(&previous, &policy.patterns) {
+        verify_passkey_for_wallet(
+            &wallet,
+            input.passkey_assertion.as_ref(),
+            Some(&path_policy::commitment(&input.wallet_id, requested)),
+        )?;
+    }
+    db.put(&policy)
+        .map_err(|e| anyhow!("Failed to save path policy: {}", e))?;
+    // Process-global cell, not TLS — safe after db.put (H-3).
+    path_policy::set_cached(&input.wallet_id, policy.patterns.clone());
+    Ok(proto::PathPolicyOutput {
+        patterns: policy.patterns,
+        previous,
+    })
+}
 
-    let derivation_path = format!("m/44'/60'/0'/0/{}", address_index);
-    let (address, public_key) = wallet.derive_address(&derivation_path)?;
+// ── Remote approver ──
 
-    // save_wallet does cache_put (TLS) then db.put (corrupts TLS). After this,
-    // no more thread_local access — safe to call rpmb_write_counter.
-    save_wallet(&db_client, &wallet)?;
-    rpmb_write_counter(epoch)?;
+/// Fail closed: an unreadable record must not read as "no phone paired".
+fn load_remote_approver(
+    db: &SecureStorageClient,
+    wallet_id: &Uuid,
+) -> Result<remote_approval::RemoteApprover> {
+    let store_id = remote_approval::RemoteApprover::store_id_for(wallet_id);
+    match db.get::<remote_approval::RemoteApprover>(&store_id) {
+        Ok(approver) => Ok(approver),
+        Err(e) => {
+            let msg = e.to_string();
+            if msg.contains("ItemNotFound") || msg.contains("ITEM_NOT_FOUND") {
+                Ok(remote_approval::RemoteApprover::empty(wallet_id))
+            } else {
+                Err(anyhow!("remote approver: secure storage error: {}", msg))
+            }
+        }
+    }
+}
 
-    Ok(proto::DeriveAddressAutoOutput {
-        wallet_id: input.wallet_id,
-        address,
-        public_key,
-        derivation_path,
+fn pair_approver(input: &proto::PairApproverInput) -> Result<proto::PairApproverOutput> {
+    let wallet = load_wallet_cached(&input.wallet_id)?;
+    if let Some(key) = &input.approver_key {
+        remote_approval::check_key(key)?;
+    }
+    // Pairing and unpairing both change who must agree to a Sign.
+    verify_passkey_for_wallet(
+        &wallet,
+        input.passkey_assertion.as_ref(),
+        Some(&proto::remote_approval::pairing_commitment(
+            &input.wallet_id,
+            input.approver_key.as_deref(),
+        )),
+    )?;
+    let db = open_storage()?;
+    let mut approver = load_remote_approver(&db, &input.wallet_id)?;
+    ta_log!(
+        Policy,
+        Warn,
+        "[!] {} remote approver for wallet: {:?}",
+        if input.approver_key.is_some() {
+            "Pair"
+        } else {
+            "Unpair"
+        },
+        input.wallet_id
+    );
+    let previous = approver.pair(input.approver_key.clone());
+    db.put(&approver)
+        .map_err(|e| anyhow!("Failed to save remote approver: {}", e))?;
+    Ok(proto::PairApproverOutput { previous })
+}
+
+/// No passkey: the phone's signature is the credential, and it only arms
+/// an approval the Sign still needs the passkey for.
+fn remote_approve(input: &proto::RemoteApproveInput) -> Result<proto::RemoteApproveOutput> {
+    let db = open_storage()?;
+    let mut approver = load_remote_approver(&db, &input.wallet_id)?;
+    let key = approver
+        .key
+        .as_ref()
+        .ok_or_else(|| anyhow!("no remote approver is paired with this wallet"))?;
+    let digest = proto::remote_approval::approval_digest(&input.wallet_id, &input.payload);
+    remote_approval::verify(key, &digest, &input.signature)?;
+    let now = tee_unix_secs();
+    approver.arm(input.payload, now);
+    db.put(&approver)
+        .map_err(|e| anyhow!("Failed to arm remote approval: {}", e))?;
+    Ok(proto::RemoteApproveOutput {
+        expires_at: now + proto::remote_approval::APPROVAL_TTL_SECS,
     })
 }
 
-// Production builds: unconditionally reject — private key must never leave the TEE.
-#[cfg(not(feature = "export-secrets"))]
-fn export_private_key(
-    _input: &proto::ExportPrivateKeyInput,
-) -> Result<proto::ExportPrivateKeyOutput> {
-    Err(anyhow!("ExportPrivateKey is disabled in production TA builds"))
+// ── Multi-sig approval (proto::multisig) ──
+
+/// Fail closed: an unreadable record must not read as "no co-signers".
+fn load_multisig(db: &SecureStorageClient, wallet_id: &Uuid) -> Result<multisig::MultiSigState> {
+    let store_id = multisig::MultiSigState::store_id_for(wallet_id);
+    match db.get::<multisig::MultiSigState>(&store_id) {
+        Ok(state) => Ok(state),
+        Err(e) => {
+            let msg = e.to_string();
+            if msg.contains("ItemNotFound") || msg.contains("ITEM_NOT_FOUND") {
+                Ok(multisig::MultiSigState::empty(wallet_id))
+            } else {
+                Err(anyhow!("multi-sig policy: secure storage error: {}", msg))
+            }
+        }
+    }
 }
 
-// Dev/test builds only (--features export-secrets): allow explicit export with passkey or admin bypass.
-#[cfg(feature = "export-secrets")]
-fn export_private_key(
-    input: &proto::ExportPrivateKeyInput,
-) -> Result<proto::ExportPrivateKeyOutput> {
-    dbg_println!(
-        "[+] Export private key for wallet: {:?}, path: {}",
+fn set_multisig_policy(
+    input: &proto::SetMultiSigPolicyInput,
+) -> Result<proto::SetMultiSigPolicyOutput> {
+    let wallet = load_wallet_cached(&input.wallet_id)?;
+    if let Some(policy) = &input.policy {
+        policy.validate().map_err(|e| anyhow!(e))?;
+        for key in &policy.cosigners {
+            remote_approval::check_key(key)?;
+        }
+    }
+    // Adding, changing and removing co-signers all change who must agree.
+    verify_passkey_for_wallet(
+        &wallet,
+        input.passkey_assertion.as_ref(),
+        Some(&proto::multisig::policy_commitment(
+            &input.wallet_id,
+            input.policy.as_ref(),
+        )),
+    )?;
+    let db = open_storage()?;
+    let mut state = load_multisig(&db, &input.wallet_id)?;
+    ta_log!(
+        Policy,
+        Warn,
+        "[!] Set multi-sig policy for wallet: {:?} -> {}",
         input.wallet_id,
-        input.derivation_path
+        match &input.policy {
+            Some(p) => format!(
+                "{}-of-{} above {} wei",
+                p.required,
+                p.cosigners.len(),
+                p.threshold_wei
+            ),
+            None => "none".to_string(),
+        }
     );
+    let (previous, dropped) = state.set(input.policy.clone());
+    db.put(&state)
+        .map_err(|e| anyhow!("Failed to save multi-sig policy: {}", e))?;
+    Ok(proto::SetMultiSigPolicyOutput { previous, dropped })
+}
 
-    let wallet = load_wallet_cached(&input.wallet_id)?;
-
-    if input.passkey_assertion.is_some() {
-        verify_passkey_for_wallet(&wallet, input.passkey_assertion.as_ref(), None)?;
-    } else {
-        dbg_println!("[+] ExportPrivateKey: dev admin mode (no passkey assertion)");
-    }
+/// No passkey: the co-signer's signature is the credential, and the
+/// owner's passkey already authorized the held Sign.
+fn approve_multisig(input: &proto::ApproveMultiSigInput) -> Result<proto::ApproveMultiSigOutput> {
+    let db = open_storage()?;
+    let mut state = load_multisig(&db, &input.wallet_id)?;
+    let index = state.cosigner_index(&input.cosigner_key)?;
+    let digest = proto::multisig::approval_digest(&input.wallet_id, &input.operation);
+    remote_approval::verify(&input.cosigner_key, &digest, &input.signature)?;
+    let approved = state.approve(&input.operation, index, tee_unix_secs())?;
+    // Persisted before the signature leaves, so it is released only once.
+    db.put(&state)
+        .map_err(|e| anyhow!("Failed to record multi-sig approval: {}", e))?;
+    Ok(match approved {
+        multisig::Approved::Waiting {
+            approvals,
+            required,
+        } => proto::ApproveMultiSigOutput {
+            approvals,
+            required,
+            released: None,
+        },
+        multisig::Approved::Released { op, required } => {
+            ta_log!(
+                Policy,
+                Warn,
+                "[!] multi-sig operation released for wallet {:?}",
+                input.wallet_id
+            );
+            proto::ApproveMultiSigOutput {
+                approvals: op.approved_by.len() as u8,
+                required,
+                released: Some(proto::MultiSigRelease {
+                    hd_path: op.hd_path,
+                    signed: op.signed,
+                }),
+            }
+        }
+    })
+}
 
-    let private_key = wallet.export_private_key(&input.derivation_path)?;
+// ── Social recovery (proto::social_recovery) ──
 
-    Ok(proto::ExportPrivateKeyOutput { private_key })
+/// Fail closed: an unreadable record must not read as "no guardians", nor
+/// lose a round's attestations.
+fn load_recovery(
+    db: &SecureStorageClient,
+    wallet_id: &Uuid,
+) -> Result<social_recovery::RecoveryRecord> {
+    let store_id = social_recovery::RecoveryRecord::store_id_for(wallet_id);
+    match db.get::<social_recovery::RecoveryRecord>(&store_id) {
+        Ok(record) => Ok(record),
+        Err(e) => {
+            let msg = e.to_string();
+            if msg.contains("ItemNotFound") || msg.contains("ITEM_NOT_FOUND") {
+                Ok(social_recovery::RecoveryRecord::empty(wallet_id))
+            } else {
+                Err(anyhow!("social recovery: secure storage error: {}", msg))
+            }
+        }
+    }
 }
 
-// M-3: no longer wired into handle_invoke (removed from dispatch to avoid being
-// used as an auth oracle). Kept (allow-dead-code) only as a documentation stub.
-#[allow(dead_code)]
-fn verify_passkey(_input: &proto::VerifyPasskeyInput) -> Result<proto::VerifyPasskeyOutput> {
-    dbg_println!("[+] Verify passkey for wallet: {:?}", _input.wallet_id);
-
-    // Standalone VerifyPasskey TA command: not exposed via any HTTP endpoint.
-    // Actual signing operations use verify_passkey_for_wallet() which calls p256-m.
-    // This stub exists for future diagnostic use only.
-    Ok(proto::VerifyPasskeyOutput { valid: true })
+fn set_guardians(input: &proto::SetGuardiansInput) -> Result<proto::SetGuardiansOutput> {
+    let wallet = load_wallet_cached(&input.wallet_id)?;
+    if let Some(set) = &input.guardians {
+        set.validate().map_err(|e| anyhow!(e))?;
+        for key in &set.guardians {
+            remote_approval::check_key(key)?;
+        }
+    }
+    verify_passkey_for_wallet(
+        &wallet,
+        input.passkey_assertion.as_ref(),
+        Some(&proto::social_recovery::guardians_commitment(
+            &input.wallet_id,
+            input.guardians.as_ref(),
+        )),
+    )?;
+    let db = open_storage()?;
+    let mut record = load_recovery(&db, &input.wallet_id)?;
+    ta_log!(
+        Policy,
+        Warn,
+        "[!] Set recovery guardians for wallet: {:?} -> {}",
+        input.wallet_id,
+        match &input.guardians {
+            Some(g) => format!(
+                "{}-of-{}, {} s timelock",
+                g.threshold,
+                g.guardians.len(),
+                g.delay_secs
+            ),
+            None => "none".to_string(),
+        }
+    );
+    let (previous, cancelled) = record.set_guardians(input.guardians.clone());
+    db.put(&record)
+        .map_err(|e| anyhow!("Failed to save recovery guardians: {}", e))?;
+    Ok(proto::SetGuardiansOutput {
+        previous,
+        cancelled,
+    })
 }
 
-fn register_passkey_ta(
-    input: &proto::RegisterPasskeyTaInput,
-) -> Result<proto::RegisterPasskeyTaOutput> {
-    trace_println!("[+] Registering passkey for wallet: {:?}", input.wallet_id);
+fn recovery_status(input: &proto::RecoveryStatusInput) -> Result<proto::RecoveryStatusOutput> {
+    load_wallet_cached(&input.wallet_id)?;
+    let db = open_storage()?;
+    Ok(proto::RecoveryStatusOutput {
+        status: load_recovery(&db, &input.wallet_id)?.status(),
+    })
+}
 
-    if input.passkey_pubkey.len() != 65 || input.passkey_pubkey[0] != 0x04 {
+/// No passkey: the owner has lost it. The guardian's signature is the
+/// credential.
+fn recovery_attest(input: &proto::RecoveryAttestInput) -> Result<proto::RecoveryAttestOutput> {
+    load_wallet_cached(&input.wallet_id)?;
+    if input.new_passkey.len() != 65 || input.new_passkey[0] != 0x04 {
         bail!(
             "PassKey public key must be 65 bytes uncompressed (0x04 || x || y), got {} bytes",
-            input.passkey_pubkey.len()
+            input.new_passkey.len()
         );
     }
+    let db = open_storage()?;
+    let mut record = load_recovery(&db, &input.wallet_id)?;
+    if input.round != record.round {
+        bail!(
+            "recovery round {} is over; the current round is {}",
+            input.round,
+            record.round
+        );
+    }
+    let index = record.guardian_index(&input.guardian_key)?;
+    let digest = proto::social_recovery::attestation_digest(
+        &input.wallet_id,
+        input.round,
+        &input.new_passkey,
+    );
+    remote_approval::verify(&input.guardian_key, &digest, &input.signature)?;
+    let armed_now = record.vote(index, &input.new_passkey, tee_unix_secs())?;
+    db.put(&record)
+        .map_err(|e| anyhow!("Failed to record recovery attestation: {}", e))?;
+    if armed_now {
+        ta_log!(
+            Policy,
+            Warn,
+            "[!] recovery armed for wallet {:?} (round {})",
+            input.wallet_id,
+            record.round
+        );
+    }
+    Ok(proto::RecoveryAttestOutput {
+        status: record.status(),
+        armed_now,
+    })
+}
+
+fn cancel_recovery(input: &proto::CancelRecoveryInput) -> Result<proto::CancelRecoveryOutput> {
+    let wallet = load_wallet_cached(&input.wallet_id)?;
+    let db = open_storage()?;
+    let mut record = load_recovery(&db, &input.wallet_id)?;
+    verify_passkey_for_wallet(
+        &wallet,
+        input.passkey_assertion.as_ref(),
+        Some(&proto::social_recovery::cancel_commitment(
+            &input.wallet_id,
+            record.round,
+        )),
+    )?;
+    record.next_round();
+    db.put(&record)
+        .map_err(|e| anyhow!("Failed to cancel recovery: {}", e))?;
+    ta_log!(
+        Policy,
+        Warn,
+        "[!] recovery cancelled by the owner for wallet {:?}",
+        input.wallet_id
+    );
+    Ok(proto::CancelRecoveryOutput {
+        status: record.status(),
+    })
+}
 
+/// No credential: the guardians attested and the owner did not cancel
+/// within the timelock. Rebinds like RegisterPasskeyTa.
+fn complete_recovery(
+    input: &proto::CompleteRecoveryInput,
+) -> Result<proto::CompleteRecoveryOutput> {
     // Read RPMB epoch before load_wallet_cached (which touches thread_local cache).
     let epoch = rpmb_next_epoch()?;
 
     let mut wallet = load_wallet_cached(&input.wallet_id)?;
-    // Verify current passkey before allowing change
-    verify_passkey_for_wallet(&wallet, input.passkey_assertion.as_ref(), None)?;
-    wallet.set_passkey(input.passkey_pubkey.clone());
-    wallet.rollback_epoch = epoch;
-
     let db = open_storage()?;
-    // save_wallet does cache_put (TLS) then db.put (corrupts TLS).
+    let mut record = load_recovery(&db, &input.wallet_id)?;
+    let passkey = record.take_ready(tee_unix_secs())?;
+    key_cache::invalidate_wallet(&input.wallet_id);
+    wallet.set_passkey(passkey.clone());
+    wallet.rollback_epoch = epoch;
+    // Wallet first: if the record write is lost, completing again binds
+    // the same key.
     save_wallet(&db, &wallet)?;
     rpmb_write_counter(epoch)?;
-    trace_println!("[+] PassKey registered, wallet saved (RPMB epoch={})", epoch);
-
-    Ok(proto::RegisterPasskeyTaOutput { registered: true })
-}
-
-fn warmup_cache(input: &proto::WarmupCacheInput) -> Result<proto::WarmupCacheOutput> {
-    dbg_println!("[+] Warmup cache for wallet: {:?}", input.wallet_id);
-    let _wallet = load_wallet_cached(&input.wallet_id)?;
-    Ok(proto::WarmupCacheOutput {
-        cached: true,
-        cache_size: cache_len() as u32,
+    db.put(&record)
+        .map_err(|e| anyhow!("Failed to close the recovery round: {}", e))?;
+    ta_log!(
+        Policy,
+        Warn,
+        "[!] wallet {:?} recovered to a new passkey (RPMB epoch={})",
+        input.wallet_id,
+        epoch
+    );
+    Ok(proto::CompleteRecoveryOutput {
+        passkey_pubkey: passkey,
+        round: record.round,
     })
 }
 
-fn read_rollback_counter(
-    _input: &proto::ReadRollbackCounterInput,
-) -> Result<proto::ReadRollbackCounterOutput> {
-    let counter = rpmb_read_counter()?;
-    Ok(proto::ReadRollbackCounterOutput { counter })
-}
+// ── Encrypted backups (proto::backup) ──
 
-/// Issue #49: issue a fresh one-time WebAuthn challenge nonce bound to a wallet.
-///
-/// Requires the wallet to exist (and thus have a passkey bound) so a compromised
-/// CA cannot farm nonces for non-existent wallets. The nonce is held in-memory
-/// only and consumed by the next signing assertion. No secure-storage write
-/// happens here, so there is no TLS/thread_local hazard (H-3).
-fn get_challenge(input: &proto::GetChallengeInput) -> Result<proto::GetChallengeOutput> {
-    dbg_println!("[+] GetChallenge for wallet: {:?}", input.wallet_id);
-    // Ensure the wallet exists before issuing a nonce. load_wallet_cached errors
-    // if the wallet is unknown.
-    let _wallet = load_wallet_cached(&input.wallet_id)?;
-    // Issue #68: the nonce stays a plain random value. Payload binding is rooted
-    // in the CLIENT's challenge derivation (challenge = SHA256(nonce||payload)),
-    // verified at signing time in verify_challenge_binding — so the TA needs no
-    // payload at issue time and there is no CA-controllable bound field.
-    let nonce = challenge_issue(&input.wallet_id);
-    Ok(proto::GetChallengeOutput {
-        nonce: nonce.to_vec(),
+/// Production export of the wallet entropy, behind the passkey (dev/test
+/// builds also allow the passkey-less admin mode, as ExportKeystore does).
+/// The KDF runs here and the sealing key never leaves the TA, so the CA
+/// sees the password and the sealed blob only.
+fn export_encrypted_backup(
+    input: &proto::ExportEncryptedBackupInput,
+) -> Result<proto::ExportEncryptedBackupOutput> {
+    use proto::backup::{BackupHeader, EncryptedBackup, BACKUP_VERSION, MIN_PASSWORD_LEN};
+
+    if !matches!(input.kdf, proto::kdf::KdfConfig::Argon2id { .. }) {
+        bail!("backups are sealed with argon2id only");
+    }
+    input.kdf.validate().map_err(|e| anyhow!("{}", e))?;
+    if input.password.chars().count() < MIN_PASSWORD_LEN {
+        bail!(
+            "backup password must be at least {} characters",
+            MIN_PASSWORD_LEN
+        );
+    }
+    let wallet = load_wallet_cached(&input.wallet_id)?;
+    if input.passkey_assertion.is_some() || !cfg!(feature = "export-secrets") {
+        verify_passkey_for_wallet(
+            &wallet,
+            input.passkey_assertion.as_ref(),
+            Some(&proto::backup::export_commitment(
+                &input.wallet_id,
+                &input.kdf,
+            )),
+        )?;
+    } else {
+        ta_log!(
+            Crypto,
+            Debug,
+            "[+] ExportEncryptedBackup: dev admin mode (no passkey assertion)"
+        );
+    }
+    let passkey_pubkey = wallet
+        .get_passkey()
+        .ok_or_else(|| anyhow!("Wallet has no PassKey bound. Cannot verify."))?
+        .to_vec();
+    let mut sealing_key = device_seal::backup_sealing_key()?;
+    let header = BackupHeader {
+        version: BACKUP_VERSION,
+        wallet_id: input.wallet_id,
+        passkey_pubkey,
+        next_address_index: wallet.get_next_address_index(),
+        next_account_index: wallet.get_next_account_index(),
+        created_at: tee_unix_secs().max(0) as u64,
+        device: proto::backup::device_fingerprint(&sealing_key),
+    };
+    let mut passphrase =
+        proto::backup::sealed_passphrase(&sealing_key, &header, input.password.as_bytes());
+    sealing_key.fill(0);
+    let mut salt = vec![0u8; 32];
+    provider::fill(salt.as_mut_slice());
+    let mut iv = [0u8; 16];
+    provider::fill(&mut iv);
+    let keystore = proto::kdf::Keystore::seal(wallet.entropy(), &passphrase, input.kdf, salt, iv);
+    passphrase.fill(0);
+    ta_log!(
+        Crypto,
+        Warn,
+        "[!] Encrypted backup of wallet {:?} ({})",
+        input.wallet_id,
+        input.kdf
+    );
+    Ok(proto::ExportEncryptedBackupOutput {
+        backup: EncryptedBackup {
+            header,
+            keystore: keystore.map_err(|e| anyhow!("{}", e))?,
+        },
     })
 }
 
-fn agent_derivation_path(agent_index: u32) -> String {
-    format!("m/44'/60'/0'/1/{}", agent_index)
+/// Restores the wallet under its own id and passkey, like a replication
+/// import. Only the device that sealed the backup can open it.
+fn import_encrypted_backup(
+    input: &proto::ImportEncryptedBackupInput,
+) -> Result<proto::ImportEncryptedBackupOutput> {
+    let backup = &input.backup;
+    backup.validate().map_err(|e| anyhow!("{}", e))?;
+    let header = &backup.header;
+    // Read RPMB epoch before any thread_local access (read doesn't corrupt TLS).
+    let epoch = rpmb_next_epoch()?;
+    let db = open_storage()?;
+    if db.get::<Wallet>(&header.wallet_id).is_ok() {
+        bail!("wallet {} already exists on this device", header.wallet_id);
+    }
+    let max_wallets = ta_config::limits().max_wallets as usize;
+    let existing = db.count_entries::<Wallet>()?;
+    if existing >= max_wallets {
+        storage_quota::record_exhausted();
+        return Err(proto::storage_quota::StorageExhausted {
+            wallets: existing as u32,
+            max_wallets: max_wallets as u32,
+            no_space: false,
+        }
+        .into());
+    }
+
+    let mut sealing_key = device_seal::backup_sealing_key()?;
+    let on_this_device = proto::backup::device_fingerprint(&sealing_key) == header.device;
+    let mut passphrase =
+        proto::backup::sealed_passphrase(&sealing_key, header, input.password.as_bytes());
+    sealing_key.fill(0);
+    if !on_this_device {
+        passphrase.fill(0);
+        bail!("backup was sealed on a different device");
+    }
+    let entropy = backup.keystore.open(&passphrase);
+    passphrase.fill(0);
+    let entropy = entropy.map_err(|_| anyhow!("backup MAC mismatch (wrong password?)"))?;
+    let mut wallet = Wallet::restore(
+        header.wallet_id,
+        entropy,
+        header.passkey_pubkey.clone(),
+        header.next_address_index,
+        header.next_account_index,
+    )?;
+    wallet.rollback_epoch = epoch;
+    // Derivation touches the key cache (TLS) — before the writes below.
+    let (address, _) = wallet.derive_address("m/44'/60'/0'/0/0")?;
+
+    save_wallet(&db, &wallet)?;
+    rpmb_write_counter(epoch)?;
+    ta_log!(
+        Crypto,
+        Warn,
+        "[!] wallet {:?} restored from an encrypted backup (RPMB epoch={})",
+        header.wallet_id,
+        epoch
+    );
+    Ok(proto::ImportEncryptedBackupOutput {
+        wallet_id: header.wallet_id,
+        address,
+        passkey_pubkey: header.passkey_pubkey.clone(),
+    })
 }
 
-/// Maximum allowed JWT lifetime: 24 hours.
-/// Shortened from 7 days (2026-06-22) to bound the delegated-signing window: a
-/// leaked/abused credential — or a ttl a compromised CA tried to stretch — is valid
-/// at most 24h. Agents/sessions re-mint (re-auth with passkey) daily.
-const MAX_AGENT_JWT_TTL: i64 = 24 * 3600;
+// ── Signature-aggregator accounts (proto::aggregator) ──
 
-/// Current wall-clock time (UNIX epoch seconds) read from the REE clock via TEE_GetREETime.
-///
-/// `std::time::SystemTime::now()` is NOT wired into the OP-TEE TA runtime — calling it panics
-/// the TA (observed on real i.MX93 hardware: create-agent-key / refresh-agent-credential aborted
-/// with a TA panic). The TA must obtain time through the optee-utee `Time` API instead.
-///
-/// REE time is "as trusted as the REE itself" (the host can shift the system clock), but the host
-/// still cannot inject `iat`/`exp` into the HMAC-signed JWT payload directly — the TA computes and
-/// signs them, so H-3 (TA owns iat; host only supplies the capped ttl_secs) still holds.
-fn tee_unix_secs() -> i64 {
-    let mut t = Time::new();
-    t.ree_time();
-    t.seconds as i64
+/// Fail closed, like the remote approver: an unreadable record must not
+/// read as "ECDSA only" any more than as "configured".
+fn load_aggregator_scheme(
+    db: &SecureStorageClient,
+    wallet_id: &Uuid,
+) -> Result<aggregator::AggregatorScheme> {
+    let store_id = aggregator::AggregatorScheme::store_id_for(wallet_id);
+    match db.get::<aggregator::AggregatorScheme>(&store_id) {
+        Ok(scheme) => Ok(scheme),
+        Err(e) => {
+            let msg = e.to_string();
+            if msg.contains("ItemNotFound") || msg.contains("ITEM_NOT_FOUND") {
+                Ok(aggregator::AggregatorScheme::empty(wallet_id))
+            } else {
+                Err(anyhow!("aggregator scheme: secure storage error: {}", msg))
+            }
+        }
+    }
 }
 
-fn create_agent_key(input: &proto::CreateAgentKeyInput) -> Result<proto::CreateAgentKeyOutput> {
-    dbg_println!(
-        "[+] Create agent key for wallet: {:?}, agent_index: {}",
+fn set_aggregator(input: &proto::SetAggregatorInput) -> Result<proto::SetAggregatorOutput> {
+    let wallet = load_wallet_cached(&input.wallet_id)?;
+    verify_passkey_for_wallet(
+        &wallet,
+        input.passkey_assertion.as_ref(),
+        Some(&proto::aggregator::scheme_commitment(
+            &input.wallet_id,
+            input.aggregator.as_ref(),
+        )),
+    )?;
+    let public_key = match input.aggregator {
+        Some(_) => {
+            let mut sk = aggregator::derive_key(&wallet.get_seed()?)?;
+            let pk = bls::pubkey_eip2537(&sk);
+            sk.fill(0);
+            Some(pk?.to_vec())
+        }
+        None => None,
+    };
+    let db = open_storage()?;
+    let mut scheme = load_aggregator_scheme(&db, &input.wallet_id)?;
+    let previous = std::mem::replace(&mut scheme.aggregator, input.aggregator);
+    db.put(&scheme)
+        .map_err(|e| anyhow!("Failed to save aggregator scheme: {}", e))?;
+    ta_log!(
+        Policy,
+        Info,
+        "[+] wallet {:?} aggregator: {}",
         input.wallet_id,
-        input.agent_index
+        match &input.aggregator {
+            Some(a) => hex::encode(a),
+            None => "none (ECDSA)".to_string(),
+        }
     );
+    Ok(proto::SetAggregatorOutput {
+        public_key,
+        previous,
+    })
+}
 
+fn sign_aggregated_user_op(
+    input: &proto::SignAggregatedUserOpInput,
+) -> Result<proto::SignAggregatedUserOpOutput> {
     let wallet = load_wallet_cached(&input.wallet_id)?;
+    let db = open_storage()?;
+    let aggregator = load_aggregator_scheme(&db, &input.wallet_id)?
+        .aggregator
+        .ok_or_else(|| anyhow!("wallet is not configured with a signature aggregator"))?;
+    verify_passkey_for_wallet(
+        &wallet,
+        input.passkey_assertion.as_ref(),
+        Some(&input.user_op_hash),
+    )?;
+    let mut sk = aggregator::derive_key(&wallet.get_seed()?)?;
+    let signed = bls::sign(&sk, &input.user_op_hash);
+    sk.fill(0);
+    let (eip2537, compact) = signed?;
+    Ok(proto::SignAggregatedUserOpOutput {
+        aggregator,
+        signature: eip2537.to_vec(),
+        signature_compact: compact.to_vec(),
+    })
+}
 
-    // C-1 (#111): TA-side user-presence verification — blocks a compromised host from
-    // minting agent credentials without the user. (This is the critical line; it stays.)
-    // #115: bind the challenge to a CLIENT-AUTHORIZED commitment so a compromised CA
-    // cannot ride the gesture with different inputs. CREATE binds the label (+wallet);
-    // REFRESH binds the agent_index being re-minted (+wallet) under a DISTINCT tag, so a
-    // refresh assertion can't be replayed to create (and vice versa) even for an empty
-    // label. The CA can't flip is_refresh: the client committed to the matching shape.
-    // transition accepts the bare nonce; strict requires challenge == SHA-256(nonce‖digest).
-    // Server-derived params (the allocated index for CREATE, subject, ttl) are NOT bound —
-    // ttl is bounded by MAX_AGENT_JWT_TTL.
-    let mint_digest = if input.is_refresh {
-        agent_refresh_digest(&input.wallet_id, input.agent_index)
-    } else {
-        mint_label_digest(&input.wallet_id, &input.label, b"AA-AGENT-MINT-v2")
-    };
-    verify_passkey_for_wallet(&wallet, input.passkey_assertion.as_ref(), Some(&mint_digest))?;
-
-    // H-1: Enforce TTL bounds inside TA (host-supplied, but TA caps it).
-    if input.ttl_secs <= 0 || input.ttl_secs > MAX_AGENT_JWT_TTL {
-        return Err(anyhow!("ttl_secs must be in 1..=86400"));
-    }
-    // H-2: Subject must not contain JSON-special characters that could inject claims.
-    validate_jwt_subject(&input.subject)?;
-
-    let derivation_path = agent_derivation_path(input.agent_index);
-    let (agent_address, public_key_compressed) = wallet.derive_address(&derivation_path)?;
+fn load_dapp_namespace(
+    db: &SecureStorageClient,
+    wallet_id: &Uuid,
+    app_id: &str,
+) -> dapp_storage::DappNamespaceRecord {
+    db.get::<dapp_storage::DappNamespaceRecord>(&dapp_storage::DappNamespaceRecord::store_id_for(
+        wallet_id, app_id,
+    ))
+    .unwrap_or_else(|_| dapp_storage::DappNamespaceRecord::empty(wallet_id, app_id))
+}
 
-    // Build JWT payload entirely inside TEE — iat computed from the TA's view of the clock so
-    // a compromised host cannot supply iat=0 or iat=far_future to shift the TTL window.
-    // H-3: TA owns iat; host only supplies ttl_secs (capped above).
-    let iat = tee_unix_secs();
-    let agent_addr_hex = format!("0x{}", hex::encode(agent_address));
-    let wallet_id_str = input.wallet_id.to_string();
-    let exp = iat.checked_add(input.ttl_secs)
-        .ok_or_else(|| anyhow!("JWT exp overflow"))?;
-    let payload_json = format!(
-        "{{\"sub\":\"{sub}\",\"wallet_id\":\"{wid}\",\"agent_index\":{idx},\"agent_address\":\"{addr}\",\"iat\":{iat},\"exp\":{exp}}}",
-        sub  = input.subject,
-        wid  = wallet_id_str,
-        idx  = input.agent_index,
-        addr = agent_addr_hex,
-        iat  = iat,
-        exp  = exp,
-    );
-    let jwt_out = jwt_sign_payload_internal(&payload_json)?;
+/// Passkey check shared by the three dapp storage commands.
+fn authorize_dapp_storage(
+    op: proto::dapp_storage::StorageOp,
+    wallet_id: &Uuid,
+    app_id: &str,
+    key: &str,
+    value: Option<&[u8]>,
+    assertion: Option<&proto::PasskeyAssertion>,
+) -> Result<()> {
+    proto::dapp_storage::validate_app_id(app_id).map_err(|e| anyhow!("{}", e))?;
+    proto::dapp_storage::validate_key(key).map_err(|e| anyhow!("{}", e))?;
+    let wallet = load_wallet_cached(wallet_id)?;
+    let commitment = proto::dapp_storage::op_commitment(op, wallet_id, app_id, key, value);
+    verify_passkey_for_wallet(&wallet, assertion, Some(&commitment))
+}
 
-    Ok(proto::CreateAgentKeyOutput {
-        agent_address,
-        public_key_compressed,
-        jwt_kid:        jwt_out.kid,
-        jwt_header_b64: jwt_out.header_b64,
-        jwt_payload_b64: jwt_out.payload_b64,
-        jwt_hmac:       jwt_out.hmac,
+fn dapp_storage_put(input: &proto::DappStoragePutInput) -> Result<proto::DappStoragePutOutput> {
+    authorize_dapp_storage(
+        proto::dapp_storage::StorageOp::Put,
+        &input.wallet_id,
+        &input.app_id,
+        &input.key,
+        Some(&input.value),
+        input.passkey_assertion.as_ref(),
+    )?;
+    let db = open_storage()?;
+    let mut ns = load_dapp_namespace(&db, &input.wallet_id, &input.app_id);
+    ns.namespace
+        .reserve(&input.key, input.value.len())
+        .map_err(|e| anyhow!("{}", e))?;
+    // Index first: a failed blob write leaves a key that reads as missing,
+    // never a blob the wallet wipe cannot find.
+    db.put(&ns)
+        .map_err(|e| anyhow!("Failed to save dapp namespace: {}", e))?;
+    db.put(&dapp_storage::DappBlob {
+        store_id: ns.blob_id(&input.key),
+        value: input.value.clone(),
+    })
+    .map_err(|e| anyhow!("Failed to save dapp blob: {}", e))?;
+    Ok(proto::DappStoragePutOutput {
+        used_keys: ns.namespace.sizes.len() as u32,
+        used_bytes: ns.namespace.used_bytes() as u32,
     })
 }
 
-fn sign_agent_user_op(input: &proto::SignAgentUserOpInput) -> Result<proto::SignAgentUserOpOutput> {
-    dbg_println!(
-        "[+] Sign agent user op for wallet: {:?}, agent_index: {}",
-        input.wallet_id,
-        input.agent_index
-    );
+fn dapp_storage_get(input: &proto::DappStorageGetInput) -> Result<proto::DappStorageGetOutput> {
+    authorize_dapp_storage(
+        proto::dapp_storage::StorageOp::Get,
+        &input.wallet_id,
+        &input.app_id,
+        &input.key,
+        None,
+        input.passkey_assertion.as_ref(),
+    )?;
+    let db = open_storage()?;
+    let blob = db
+        .get::<dapp_storage::DappBlob>(&dapp_storage::DappBlob::store_id_for(
+            &input.wallet_id,
+            &input.app_id,
+            &input.key,
+        ))
+        .map_err(|_| anyhow!("dapp storage key not found: {}", input.key))?;
+    Ok(proto::DappStorageGetOutput { value: blob.value })
+}
 
-    // TA-side JWT authorization: verify HMAC proof before signing.
-    // Prevents a compromised CA from bypassing host-side JWT verification.
-    let jwt_verify_input = proto::JwtHmacVerifyInput {
-        kid: input.jwt_kid.clone(),
-        message: input.jwt_signing_input.clone(),
-        expected_hmac: input.jwt_hmac.clone(),
-    };
-    let verify_result = jwt_hmac_verify(&jwt_verify_input)?;
-    if !verify_result.valid {
-        return Err(anyhow!("TA: agent JWT credential verification failed"));
+fn dapp_storage_delete(
+    input: &proto::DappStorageDeleteInput,
+) -> Result<proto::DappStorageDeleteOutput> {
+    authorize_dapp_storage(
+        proto::dapp_storage::StorageOp::Delete,
+        &input.wallet_id,
+        &input.app_id,
+        &input.key,
+        None,
+        input.passkey_assertion.as_ref(),
+    )?;
+    let db = open_storage()?;
+    let mut ns = load_dapp_namespace(&db, &input.wallet_id, &input.app_id);
+    if !ns.namespace.remove(&input.key) {
+        return Ok(proto::DappStorageDeleteOutput { existed: false });
+    }
+    db.delete_entry::<dapp_storage::DappBlob>(&ns.blob_id(&input.key))
+        .map_err(|e| anyhow!("Failed to delete dapp blob: {}", e))?;
+    if ns.namespace.sizes.is_empty() {
+        db.delete_entry::<dapp_storage::DappNamespaceRecord>(&ns.store_id)?;
+    } else {
+        db.put(&ns)
+            .map_err(|e| anyhow!("Failed to save dapp namespace: {}", e))?;
     }
+    Ok(proto::DappStorageDeleteOutput { existed: true })
+}
 
-    // Bind JWT claims to request parameters — prevents a compromised host from using
-    // wallet A's JWT to request signing for wallet B (Issue #16 claim-binding fix).
-    verify_jwt_wallet_claims(&input.jwt_signing_input, &input.wallet_id, input.agent_index)?;
+/// Every dapp namespace of an erased wallet goes with it. Blobs an index
+/// lists but that were never written are skipped.
+fn wipe_dapp_storage(db: &SecureStorageClient, wallet_id: &Uuid) -> Result<()> {
+    let all = db.list_entries::<dapp_storage::DappNamespaceRecord>()?;
+    for id in dapp_storage::wallet_namespaces(wallet_id, all.keys()) {
+        let ns = db.get::<dapp_storage::DappNamespaceRecord>(&id)?;
+        for key in ns.namespace.sizes.keys() {
+            let _ = db.delete_entry::<dapp_storage::DappBlob>(&ns.blob_id(key));
+        }
+        db.delete_entry::<dapp_storage::DappNamespaceRecord>(&id)?;
+    }
+    Ok(())
+}
 
+fn otp_enroll(input: &proto::OtpEnrollInput) -> Result<proto::OtpEnrollOutput> {
+    otp_vault::check_enrollment(&input.label, &input.secret, &input.params)
+        .map_err(|e| anyhow!("{}", e))?;
     let wallet = load_wallet_cached(&input.wallet_id)?;
-    let derivation_path = agent_derivation_path(input.agent_index);
-    let private_key = wallet.export_private_key(&derivation_path)?;
-
-    // Derive the agent key's Ethereum address from its public key (inside TEE, safe).
-    let (agent_key_address, _) = wallet.derive_address(&derivation_path)?;
+    let commitment =
+        proto::otp::enroll_commitment(&input.wallet_id, &input.label, &input.secret, &input.params);
+    verify_passkey_for_wallet(&wallet, input.passkey_assertion.as_ref(), Some(&commitment))?;
 
-    let mut eip191 = b"\x19Ethereum Signed Message:\n32".to_vec();
-    eip191.extend_from_slice(&input.user_op_hash);
-    let digest = Keccak256::digest(&eip191);
+    let db = open_storage()?;
+    let all = db.list_entries::<otp_vault::OtpSecretRecord>()?;
+    let existing = otp_vault::wallet_entries(&input.wallet_id, all.keys());
+    let store_id = otp_vault::OtpSecretRecord::store_id_for(&input.wallet_id, &input.label);
+    // Re-enrolling a label would silently replace a working secret; the user
+    // removes it first.
+    if existing.contains(&store_id) {
+        bail!("OTP label already enrolled: {}", input.label);
+    }
+    if existing.len() >= proto::otp::MAX_ENTRIES_PER_WALLET {
+        bail!(
+            "wallet already holds {} OTP secrets",
+            proto::otp::MAX_ENTRIES_PER_WALLET
+        );
+    }
+    db.put(&otp_vault::OtpSecretRecord {
+        store_id,
+        wallet_id: input.wallet_id,
+        label: input.label.clone(),
+        secret: input.secret.clone(),
+        params: input.params,
+        last_counter: None,
+    })
+    .map_err(|e| anyhow!("Failed to save OTP secret: {}", e))?;
+    Ok(proto::OtpEnrollOutput {
+        entries: existing.len() as u32 + 1,
+    })
+}
 
-    let secret_key = secp256k1::SecretKey::from_slice(&private_key)?;
-    let secp = secp256k1::Secp256k1::new();
-    let message = secp256k1::Message::from_slice(&digest[..])?;
-    let sig = secp.sign_ecdsa_recoverable(&message, &secret_key);
-    let (recovery_id, sig_bytes) = sig.serialize_compact();
+fn load_otp_secret(
+    db: &SecureStorageClient,
+    wallet_id: &Uuid,
+    label: &str,
+) -> Result<otp_vault::OtpSecretRecord> {
+    db.get::<otp_vault::OtpSecretRecord>(&otp_vault::OtpSecretRecord::store_id_for(
+        wallet_id, label,
+    ))
+    .map_err(|_| anyhow!("OTP label not enrolled: {}", label))
+}
 
-    // v0.17.2 wire format: [0x08][account(20)][key(20)][r(32)][s(32)][v(1)] = 106 bytes
-    // account = Smart Account contract address (prevents cross-account session-key abuse)
-    // key     = agent secp256k1 EOA address (derived from private key inside TEE)
-    let mut signature = Vec::with_capacity(106);
-    signature.push(0x08u8);
-    signature.extend_from_slice(&input.account_address);
-    signature.extend_from_slice(&agent_key_address);
-    signature.extend_from_slice(&sig_bytes);
-    signature.push(recovery_id.to_i32() as u8 + 27);
+/// TOTP reads the REE clock (`tee_unix_secs`); the stored last step keeps a
+/// rewound clock from bringing back the codes of earlier steps.
+fn otp_code(input: &proto::OtpCodeInput) -> Result<proto::OtpCodeOutput> {
+    proto::otp::validate_label(&input.label).map_err(|e| anyhow!("{}", e))?;
+    let wallet = load_wallet_cached(&input.wallet_id)?;
+    let commitment =
+        proto::otp::op_commitment(proto::otp::OtpOp::Code, &input.wallet_id, &input.label);
+    verify_passkey_for_wallet(&wallet, input.passkey_assertion.as_ref(), Some(&commitment))?;
 
-    Ok(proto::SignAgentUserOpOutput { signature })
+    let db = open_storage()?;
+    let mut record = load_otp_secret(&db, &input.wallet_id, &input.label)?;
+    let now = tee_unix_secs();
+    if now <= 0 && record.params.kind != proto::otp::OtpKind::Hotp {
+        bail!("TEE clock unavailable; cannot generate a TOTP code");
+    }
+    let now = now as u64;
+    let counter = proto::otp::next_counter(record.params.kind, record.last_counter, now)
+        .map_err(|e| anyhow!("{}", e))?;
+    let code = otp_vault::hotp(
+        record.params.algorithm,
+        &record.secret,
+        counter,
+        record.params.digits,
+    );
+    // Persist before returning: an HOTP code must never be issued twice.
+    if record.last_counter != Some(counter) {
+        record.last_counter = Some(counter);
+        db.put(&record)
+            .map_err(|e| anyhow!("Failed to save OTP counter: {}", e))?;
+    }
+    Ok(proto::OtpCodeOutput {
+        code: proto::otp::format_code(code, record.params.digits),
+        counter,
+        valid_for_secs: proto::otp::valid_for_secs(record.params.kind, now),
+    })
 }
 
-fn hmac_sha256(secret: &[u8], message: &[u8]) -> Result<[u8; 32]> {
-    let mut mac =
-        HmacSha256::new_from_slice(secret).map_err(|_| anyhow!("Invalid JWT HMAC secret"))?;
-    mac.update(message);
-    let bytes = mac.finalize().into_bytes();
-    let mut out = [0u8; 32];
-    out.copy_from_slice(&bytes);
-    Ok(out)
+fn otp_remove(input: &proto::OtpRemoveInput) -> Result<proto::OtpRemoveOutput> {
+    proto::otp::validate_label(&input.label).map_err(|e| anyhow!("{}", e))?;
+    let wallet = load_wallet_cached(&input.wallet_id)?;
+    let commitment =
+        proto::otp::op_commitment(proto::otp::OtpOp::Remove, &input.wallet_id, &input.label);
+    verify_passkey_for_wallet(&wallet, input.passkey_assertion.as_ref(), Some(&commitment))?;
+
+    let db = open_storage()?;
+    let store_id = otp_vault::OtpSecretRecord::store_id_for(&input.wallet_id, &input.label);
+    let all = db.list_entries::<otp_vault::OtpSecretRecord>()?;
+    if !all.contains_key(&store_id) {
+        return Ok(proto::OtpRemoveOutput { existed: false });
+    }
+    db.delete_entry::<otp_vault::OtpSecretRecord>(&store_id)
+        .map_err(|e| anyhow!("Failed to delete OTP secret: {}", e))?;
+    Ok(proto::OtpRemoveOutput { existed: true })
 }
 
+/// Labels name the services the wallet holds 2FA for, so listing them needs
+/// the passkey like every other OTP command.
+fn otp_list(input: &proto::OtpListInput) -> Result<proto::OtpListOutput> {
+    let wallet = load_wallet_cached(&input.wallet_id)?;
+    let commitment = proto::otp::op_commitment(proto::otp::OtpOp::List, &input.wallet_id, "");
+    verify_passkey_for_wallet(&wallet, input.passkey_assertion.as_ref(), Some(&commitment))?;
 
-/// Internal JWT signing helper — NOT exposed as a TA command.
-/// Accepts a pre-built payload JSON string, computes the full JWT signing_input
-/// and HMAC using the current TEE-stored secret.
-struct JwtSignedMaterial {
-    kid: String,
-    header_b64: String,
-    payload_b64: String,
-    hmac: [u8; 32],
+    let db = open_storage()?;
+    let all = db.list_entries::<otp_vault::OtpSecretRecord>()?;
+    let mut entries = Vec::new();
+    for id in otp_vault::wallet_entries(&input.wallet_id, all.keys()) {
+        let record = db.get::<otp_vault::OtpSecretRecord>(&id)?;
+        entries.push(proto::OtpEntry {
+            label: record.label,
+            params: record.params,
+        });
+    }
+    Ok(proto::OtpListOutput { entries })
 }
 
-fn jwt_sign_payload_internal(payload_json: &str) -> Result<JwtSignedMaterial> {
-    use base64ct::{Base64UrlUnpadded, Encoding};
+/// OTP secrets of an erased wallet go with it.
+fn wipe_otp_secrets(db: &SecureStorageClient, wallet_id: &Uuid) -> Result<()> {
+    let all = db.list_entries::<otp_vault::OtpSecretRecord>()?;
+    for id in otp_vault::wallet_entries(wallet_id, all.keys()) {
+        db.delete_entry::<otp_vault::OtpSecretRecord>(&id)?;
+    }
+    Ok(())
+}
 
-    let db = open_storage()?;
-    let mut store = JwtSecretStore::load(&db);
-    let current = store.ensure_current()?.clone();
-    store.save(&db)?;
+/// Proof of control for a CA-side identity link. The CA decides whose
+/// approval a link needs; the TA checks every passkey it is given against the
+/// one commitment and signs the statement with the linked address's key.
+fn link_wallet(input: &proto::LinkWalletInput) -> Result<proto::LinkWalletOutput> {
+    use proto::identity;
 
-    let header_json = format!(
-        "{{\"alg\":\"HS256\",\"typ\":\"JWT\",\"kid\":\"{}\"}}",
-        current.kid
+    identity::validate_chain_ids(input.action, &input.chain_ids).map_err(|e| anyhow!("{}", e))?;
+    let wallet = load_wallet_cached(&input.wallet_id)?;
+    let commitment = identity::link_commitment(
+        input.action,
+        &input.identity_id,
+        &input.wallet_id,
+        &input.hd_path,
+        &input.chain_ids,
+        input.owner.as_ref().map(|o| &o.wallet_id),
     );
-    let header_b64 = Base64UrlUnpadded::encode_string(header_json.as_bytes());
-    let payload_b64 = Base64UrlUnpadded::encode_string(payload_json.as_bytes());
-
-    let signing_input = format!("{}.{}", header_b64, payload_b64);
-    let hmac = hmac_sha256(&current.secret, signing_input.as_bytes())?;
+    verify_passkey_for_wallet(&wallet, input.passkey_assertion.as_ref(), Some(&commitment))?;
+    if let Some(owner) = &input.owner {
+        if owner.wallet_id == input.wallet_id {
+            bail!("owner approval must come from another wallet");
+        }
+        let owner_wallet = load_wallet_cached(&owner.wallet_id)?;
+        verify_passkey_for_wallet(
+            &owner_wallet,
+            owner.passkey_assertion.as_ref(),
+            Some(&commitment),
+        )?;
+    }
+    let (address, _) = wallet.derive_address(&input.hd_path)?;
+    let statement = identity::link_statement(
+        input.action,
+        &input.identity_id,
+        &address,
+        &input.chain_ids,
+        tee_unix_secs().max(0) as u64,
+    );
+    let signature = wallet.sign_hash(&input.hd_path, &identity::statement_digest(&statement))?;
+    Ok(proto::LinkWalletOutput {
+        address,
+        statement,
+        signature,
+    })
+}
 
-    Ok(JwtSignedMaterial { kid: current.kid, header_b64, payload_b64, hmac })
+// Production builds: the wallet entropy never leaves the TEE, encrypted or not.
+#[cfg(not(feature = "export-secrets"))]
+fn export_keystore(_input: &proto::ExportKeystoreInput) -> Result<proto::ExportKeystoreOutput> {
+    Err(anyhow!(
+        "ExportKeystore is disabled in production TA builds"
+    ))
 }
 
-/// Extract a u64 field value from a compact JSON object without serde_json.
-fn extract_json_u64_field(json: &str, key: &str) -> Result<u64> {
-    let pattern = format!("\"{}\":", key);
-    let start = json.find(&*pattern)
-        .ok_or_else(|| anyhow!("JWT claim '{}' not found", key))?
-        + pattern.len();
-    let rest = json[start..].trim_start_matches(' ');
-    let end = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
-    rest[..end].parse::<u64>().map_err(|_| anyhow!("JWT claim '{}' is not a u64", key))
+/// The KDF runs here, so the CA handles only the passphrase and the sealed
+/// keystore. Dev/test builds allow the passkey-less admin mode, as
+/// ExportPrivateKey does.
+#[cfg(feature = "export-secrets")]
+fn export_keystore(input: &proto::ExportKeystoreInput) -> Result<proto::ExportKeystoreOutput> {
+    ta_log!(
+        Crypto,
+        Warn,
+        "[!] Export keystore for wallet: {:?} ({})",
+        input.wallet_id,
+        input.kdf
+    );
+    input.kdf.validate().map_err(|e| anyhow!("{}", e))?;
+    let wallet = load_wallet_cached(&input.wallet_id)?;
+    if input.passkey_assertion.is_some() {
+        verify_passkey_for_wallet(
+            &wallet,
+            input.passkey_assertion.as_ref(),
+            Some(&proto::kdf::export_commitment(&input.wallet_id, &input.kdf)),
+        )?;
+    } else {
+        ta_log!(
+            Crypto,
+            Debug,
+            "[+] ExportKeystore: dev admin mode (no passkey assertion)"
+        );
+    }
+    let mut salt = vec![0u8; 32];
+    provider::fill(salt.as_mut_slice());
+    let mut iv = [0u8; 16];
+    provider::fill(&mut iv);
+    let keystore = proto::kdf::Keystore::seal(
+        wallet.entropy(),
+        input.passphrase.as_bytes(),
+        input.kdf,
+        salt,
+        iv,
+    )
+    .map_err(|e| anyhow!("{}", e))?;
+    Ok(proto::ExportKeystoreOutput { keystore })
 }
 
-/// Validate that `subject` contains only characters safe to embed directly in a JSON string
-/// without escaping. Prevents claim injection via a crafted subject value (H-2).
-fn validate_jwt_subject(s: &str) -> Result<()> {
-    if s.is_empty() || s.len() > 256 {
-        return Err(anyhow!("JWT subject length must be 1..=256"));
+/// Decrypts in the TEE and stores the entropy as a new wallet, exactly as
+/// CreateWallet would with CA-supplied entropy. The id is fresh: an import
+/// next to the exported original must not collide with it.
+fn import_keystore(input: &proto::ImportKeystoreInput) -> Result<proto::ImportKeystoreOutput> {
+    if input.passkey_pubkey.len() != 65 || input.passkey_pubkey[0] != 0x04 {
+        bail!(
+            "PassKey pubkey must be 65 bytes uncompressed (0x04||x||y), got {} bytes",
+            input.passkey_pubkey.len()
+        );
     }
-    if !s.bytes().all(|b| b.is_ascii_alphanumeric() || b"-_:.@+/=".contains(&b)) {
-        return Err(anyhow!("JWT subject contains characters not allowed in JSON (no quotes, backslashes, or controls)"));
+    ta_log!(Crypto, Warn, "[!] Import keystore ({})", input.keystore.kdf);
+    let mut entropy = input
+        .keystore
+        .open(input.passphrase.as_bytes())
+        .map_err(|e| anyhow!("{}", e))?;
+    if entropy.len() != 32 {
+        entropy.fill(0);
+        bail!(
+            "keystore must hold 32 bytes of wallet entropy, got {}",
+            entropy.len()
+        );
     }
-    Ok(())
+    let created = create_wallet_inner(Some(&input.passkey_pubkey), Some(&entropy), None);
+    entropy.fill(0);
+    Ok(proto::ImportKeystoreOutput {
+        wallet_id: created?.wallet_id,
+    })
 }
 
-/// After HMAC verification, decode the JWT payload and verify that wallet_id and
-/// agent_index match the request parameters. Prevents a compromised host from
-/// using a legitimate JWT for wallet A to request signing for wallet B.
-fn verify_jwt_wallet_claims(
-    signing_input: &[u8],
-    expected_wallet_id: &uuid::Uuid,
-    expected_agent_index: u32,
-) -> Result<()> {
-    use base64ct::{Base64UrlUnpadded, Encoding};
-
-    let input_str = core::str::from_utf8(signing_input)
-        .map_err(|_| anyhow!("JWT signing_input is not UTF-8"))?;
-    let dot = input_str.find('.')
-        .ok_or_else(|| anyhow!("JWT signing_input missing dot separator"))?;
-    let payload_b64 = &input_str[dot + 1..];
-    let payload_bytes = Base64UrlUnpadded::decode_vec(payload_b64)
-        .map_err(|_| anyhow!("JWT payload base64 decode failed"))?;
-    let payload_str = core::str::from_utf8(&payload_bytes)
-        .map_err(|_| anyhow!("JWT payload is not UTF-8"))?;
+/// Second confirmation for a transaction the allowance guard refuses. The
+/// challenge commits to `override_commitment(summary digest)`; the following
+/// summary-committed SignTransaction consumes it.
+fn confirm_allowance_override(
+    input: &proto::ConfirmAllowanceOverrideInput,
+) -> Result<proto::ConfirmAllowanceOverrideOutput> {
+    let wallet = load_wallet_cached(&input.wallet_id)?;
+    let tx_hash = Wallet::tx_signing_hash(&input.transaction);
+    let summary = proto::calldata::summarize_transaction(&input.transaction, &input.summary_abis);
+    let digest = proto::calldata::confirmation_digest(&tx_hash, &summary);
 
-    let jwt_wallet_id = extract_json_str_field(payload_str, "wallet_id")?;
-    if jwt_wallet_id != expected_wallet_id.to_string() {
-        return Err(anyhow!("JWT wallet_id claim does not match request"));
+    let db = open_storage()?;
+    let mut policy = load_allowance_policy(&db, &input.wallet_id);
+    let spenders = load_spender_allow_list(&db, &input.wallet_id);
+    let decoded = proto::calldata::decode_transaction(&input.transaction, &input.summary_abis);
+    if allowance_guard::violation(&policy.rule, &decoded).is_none()
+        && allowance_guard::spender_violation(&spenders, &decoded).is_none()
+    {
+        bail!("transaction is within the allowance policy; nothing to override");
     }
+    verify_passkey_for_wallet(
+        &wallet,
+        input.passkey_assertion.as_ref(),
+        Some(&allowance_guard::override_commitment(
+            &input.wallet_id,
+            &digest,
+        )),
+    )?;
+    policy.arm_override(digest, tee_unix_secs());
+    db.put(&policy)
+        .map_err(|e| anyhow!("Failed to arm allowance override: {}", e))?;
+    ta_log!(Policy, Warn, "[!] allowance override armed: {}", summary);
+    Ok(proto::ConfirmAllowanceOverrideOutput { summary })
+}
 
-    // H-3: use u64 then bounds-check before casting to avoid silent truncation
-    let jwt_agent_index_u64 = extract_json_u64_field(payload_str, "agent_index")?;
-    if jwt_agent_index_u64 > u32::MAX as u64 {
-        return Err(anyhow!("JWT agent_index overflow"));
+/// Air-gapped signing: the request was exported by another CA and carried
+/// here by QR. Expiry and the per-wallet replay log are checked in the TA, so
+/// a captured QR cannot be signed twice even through a compromised CA.
+fn sign_offline(input: &proto::SignOfflineInput) -> Result<proto::SignOfflineOutput> {
+    let req = &input.request;
+    let now = tee_unix_secs().max(0) as u64;
+    req.check(now).map_err(|e| anyhow!("{}", e))?;
+    let wallet = load_wallet_cached(&req.wallet_id)?;
+    let tx_hash = Wallet::tx_signing_hash(&req.transaction);
+    let commitment = proto::offline::offline_commitment(req, &tx_hash);
+    verify_passkey_for_wallet(&wallet, input.passkey_assertion.as_ref(), Some(&commitment))?;
+
+    let db = open_storage()?;
+    // The allowance override needs a summary-committed sign; offline there is
+    // no such path, so a violating request is simply refused.
+    let policy = load_allowance_policy(&db, &req.wallet_id);
+    let spenders = load_spender_allow_list(&db, &req.wallet_id);
+    let decoded = proto::calldata::decode_transaction(&req.transaction, &[]);
+    if let Some(reason) = allowance_guard::violation(&policy.rule, &decoded)
+        .or_else(|| allowance_guard::spender_violation(&spenders, &decoded))
+    {
+        bail!("allowance policy: {}; not overridable offline", reason);
     }
-    if jwt_agent_index_u64 as u32 != expected_agent_index {
-        return Err(anyhow!("JWT agent_index claim does not match request"));
+    let spend = check_spending_policy(&db, &req.wallet_id, &req.transaction, &decoded)?;
+    // Co-signers approve online; an offline request cannot wait for them.
+    if load_multisig(&db, &req.wallet_id)?.holds(req.transaction.value) {
+        bail!("multi-sig policy: transactions above the threshold cannot be signed offline");
     }
+    let nonces = check_nonce(&db, &wallet, &req.wallet_id, &req.hd_path, &req.transaction)?;
+    let mut log = db
+        .get::<offline_replay::OfflineReplayLog>(&offline_replay::OfflineReplayLog::store_id_for(
+            &req.wallet_id,
+        ))
+        .unwrap_or_else(|_| offline_replay::OfflineReplayLog::empty(&req.wallet_id));
+    log.record(req.request_id, req.expires_at, now)
+        .map_err(|e| anyhow!("{}", e))?;
+    // H-3: sign before the storage write; the signature is only released
+    // once the request id is durably recorded.
+    let signed_transaction = wallet.sign_transaction(&req.hd_path, &req.transaction)?;
+    db.put(&log)
+        .map_err(|e| anyhow!("Failed to record offline request: {}", e))?;
+    if let Some(record) = spend {
+        db.put(&record)
+            .map_err(|e| anyhow!("Failed to record daily spend: {}", e))?;
+    }
+    if let Some(record) = nonces {
+        db.put(&record)
+            .map_err(|e| anyhow!("Failed to record signed nonce: {}", e))?;
+    }
+    ta_log!(
+        Crypto,
+        Info,
+        "[+] offline request signed for wallet {:?}",
+        req.wallet_id
+    );
+    Ok(proto::SignOfflineOutput {
+        response: proto::offline::OfflineSignResponse {
+            version: proto::offline::OFFLINE_FORMAT_VERSION,
+            request_id: req.request_id,
+            signed_transaction,
+        },
+    })
+}
 
-    // Structural exp/iat check: exp must be after iat and within the TTL cap.
-    let iat = extract_json_u64_field(payload_str, "iat")?;
-    let exp = extract_json_u64_field(payload_str, "exp")?;
-    if exp <= iat || exp.saturating_sub(iat) > MAX_AGENT_JWT_TTL as u64 {
-        return Err(anyhow!("JWT exp/iat structurally invalid or exceeds TTL cap"));
+/// Raw digests need a transition build AND no TA config requiring a context.
+fn allow_raw_digest() -> bool {
+    signing_context::ALLOW_RAW_DIGEST && !ta_config::require_signing_context()
+}
+
+fn sign_message(input: &proto::SignMessageInput) -> Result<proto::SignMessageOutput> {
+    // Domain separation: the TA builds the digest for the declared context
+    // (legacy Raw = keccak256(message); PersonalMsg/Login = EIP-191).
+    let msg_hash =
+        signing_context::message_digest(&input.context, &input.message, allow_raw_digest())?;
+    if input.context == proto::SigningContext::Raw {
+        ta_log!(
+            Policy,
+            Warn,
+            "[!] SignMessage without signing context (legacy raw keccak)"
+        );
+    }
+    let wallet = load_wallet_cached(&input.wallet_id)?;
+    if input.context == proto::SigningContext::Login {
+        // EIP-4361: only sign in as the key's own address, and only for a
+        // fresh, currently valid message. Checked here so a CA cannot skip it.
+        let text = core::str::from_utf8(&input.message)
+            .map_err(|_| anyhow!("Login message must be UTF-8"))?;
+        let siwe = proto::siwe::SiweMessage::parse(text).map_err(|e| anyhow!("{}", e))?;
+        let (address, _) = wallet.derive_address(&input.hd_path)?;
+        siwe.check_signable(&address, tee_unix_secs())
+            .map_err(|e| anyhow!("sign-in refused: {}", e))?;
     }
+    // Issue #68: bind to the digest that is actually signed.
+    verify_passkey_for_wallet(&wallet, input.passkey_assertion.as_ref(), Some(&msg_hash))?;
+    let signature = wallet.sign_hash(&input.hd_path, &msg_hash)?;
+    Ok(proto::SignMessageOutput { signature })
+}
 
-    // #15: runtime expiry check against the TEE-side trusted clock. tee_unix_secs()
-    // reads REE time via optee_utee::Time::ree_time() — the same trusted-time source
-    // the #49 challenge-binding TTL already relies on (the old "no trusted clock in
-    // mock mode" note was stale). Reject a JWT whose exp is at/before now. Guard on
-    // now > 0 so a mock/uninitialized clock (returns 0) skips the check rather than
-    // rejecting every token; on real hardware ree_time is a valid unix timestamp.
-    let now = tee_unix_secs();
-    if now > 0 {
-        if now as u64 >= exp {
-            return Err(anyhow!("JWT expired (now {} >= exp {})", now, exp));
+fn sign_hash(input: &proto::SignHashInput) -> Result<proto::SignHashOutput> {
+    // Domain separation: a UserOp/EIP-712 hash is recomputed from its preimage;
+    // an undeclared (Raw) digest is only signed on a non-strict build.
+    signing_context::check_hash_context(&input.context, &input.hash, allow_raw_digest())?;
+    if input.context == proto::SigningContext::Raw {
+        ta_log!(
+            Policy,
+            Warn,
+            "[!] SignHash without signing context (legacy raw digest)"
+        );
+    }
+    let wallet = load_wallet_cached(&input.wallet_id)?;
+    // Issue #68: SignHash is the canonical "sign this exact 32-byte digest" path
+    // (ERC-4337 userOpHash). Bind the challenge to that digest so a payload-bound
+    // assertion can only authorise this hash, not a CA-substituted one.
+    // A token-paid UserOp also binds the paymaster quote the user accepted.
+    let payload = match &input.fee_quote {
+        Some(quote) => {
+            let chain_id = match input.context {
+                proto::SigningContext::UserOp { chain_id, .. } => chain_id,
+                _ => bail!("a fee quote is only valid with a UserOp signing context"),
+            };
+            quote
+                .validate(chain_id, tee_unix_secs().max(0) as u64)
+                .map_err(|e| anyhow!("fee quote refused: {}", e))?;
+            ta_log!(Crypto, Debug, "[+] SignHash fee: {}", quote.summary());
+            proto::paymaster::fee_review_digest(&input.hash, quote)
         }
-    } else {
-        // Observability: the REE clock returned <= 0 (mock/uninitialized or a
-        // fault). We skip the runtime expiry check rather than reject every
-        // token, but surface it — a PERSISTENT now<=0 on real hardware means JWT
-        // expiry is silently NOT being enforced and must be investigated.
-        trace_println!(
-            "[!] #15: TEE REE clock returned {} (<=0); JWT runtime expiry NOT enforced this call",
-            now
+        None => input.hash,
+    };
+    verify_passkey_for_wallet(&wallet, input.passkey_assertion.as_ref(), Some(&payload))?;
+    let signature = wallet.sign_hash(&input.hd_path, &input.hash)?;
+    Ok(proto::SignHashOutput { signature })
+}
+
+fn derive_solana_address(
+    input: &proto::DeriveSolanaAddressInput,
+) -> Result<proto::DeriveSolanaAddressOutput> {
+    let wallet = load_wallet_cached(&input.wallet_id)?;
+    verify_passkey_for_wallet(&wallet, input.passkey_assertion.as_ref(), None)?;
+    let public_key = ed25519::public_key(&wallet.solana_key(&input.hd_path)?);
+    Ok(proto::DeriveSolanaAddressOutput {
+        public_key,
+        address: proto::solana::address(&public_key),
+    })
+}
+
+fn sign_solana_message(
+    input: &proto::SignSolanaMessageInput,
+) -> Result<proto::SignSolanaMessageOutput> {
+    if input.message.len() > proto::solana::MAX_MESSAGE_LEN {
+        bail!(
+            "Solana message too long: {} bytes (max {})",
+            input.message.len(),
+            proto::solana::MAX_MESSAGE_LEN
         );
     }
+    let wallet = load_wallet_cached(&input.wallet_id)?;
+    // Issue #68: bind to the bytes that are actually signed.
+    let payload = proto::solana::signing_payload(&input.message);
+    verify_passkey_for_wallet(&wallet, input.passkey_assertion.as_ref(), Some(&payload))?;
+    let key = wallet.solana_key(&input.hd_path)?;
+    Ok(proto::SignSolanaMessageOutput {
+        signature: ed25519::sign(&key, &input.message).to_vec(),
+        public_key: ed25519::public_key(&key),
+    })
+}
 
+// ── Threshold ECDSA (feature threshold-ecdsa) ──
+
+/// A threshold share of an erased wallet goes with it.
+fn wipe_threshold_key(db: &SecureStorageClient, wallet_id: &Uuid) -> Result<()> {
+    let store_id = threshold_vault::ThresholdRecord::store_id_for(wallet_id);
+    let all = db.list_entries::<threshold_vault::ThresholdRecord>()?;
+    if all.contains_key(&store_id) {
+        db.delete_entry::<threshold_vault::ThresholdRecord>(&store_id)?;
+    }
     Ok(())
 }
 
-fn create_p256_session_key(
-    input: &proto::CreateP256SessionKeyInput,
-) -> Result<proto::CreateP256SessionKeyOutput> {
-    dbg_println!(
-        "[+] Create P256 session key for wallet: {:?}, index: {}",
-        input.wallet_id,
-        input.session_index
-    );
+#[cfg(feature = "threshold-ecdsa")]
+fn load_threshold_record(
+    db: &SecureStorageClient,
+    wallet_id: &Uuid,
+) -> threshold_vault::ThresholdRecord {
+    db.get::<threshold_vault::ThresholdRecord>(&threshold_vault::ThresholdRecord::store_id_for(
+        wallet_id,
+    ))
+    .unwrap_or_else(|_| threshold_vault::ThresholdRecord::empty(wallet_id))
+}
 
-    // Verify the wallet exists so we don't create orphaned session keys
+#[cfg(feature = "threshold-ecdsa")]
+fn threshold_keygen_begin(
+    input: &proto::ThresholdKeygenBeginInput,
+) -> Result<proto::ThresholdKeygenBeginOutput> {
     let wallet = load_wallet_cached(&input.wallet_id)?;
+    let commitment = proto::threshold::keygen_commitment(&input.wallet_id);
+    verify_passkey_for_wallet(&wallet, input.passkey_assertion.as_ref(), Some(&commitment))?;
 
-    // #111: re-verify user presence IN THE TEE before minting the session key +
-    // TEE-HMAC JWT. Without this, a compromised CA could issue CreateP256SessionKey
-    // directly and mint a signing credential with no user assertion (the host's
-    // WebAuthn check would be the only barrier — defeated by a malicious host).
-    // C-1 (#111): TA-side user-presence verification (critical line; stays).
-    // #115: bind the challenge to the CLIENT-AUTHORIZED label (+ wallet_id). See
-    // create_agent_key for the full rationale (server-derived index/subject/ttl NOT
-    // bound; transition accepts bare nonce, strict requires the commitment).
-    verify_passkey_for_wallet(
-        &wallet,
-        input.passkey_assertion.as_ref(),
-        Some(&mint_label_digest(&input.wallet_id, &input.label, b"AA-P256-SESSION-MINT-v2")),
-    )?;
-
-    // Enforce TTL bounds (same as create_agent_key)
-    if input.ttl_secs <= 0 || input.ttl_secs > MAX_AGENT_JWT_TTL {
-        return Err(anyhow!("ttl_secs must be in 1..=86400"));
+    let db = open_storage()?;
+    let mut record = load_threshold_record(&db, &input.wallet_id);
+    if record.share.is_some() || record.recombined.is_some() {
+        bail!("wallet already has a threshold key: {}", input.wallet_id);
     }
-    validate_jwt_subject(&input.subject)?;
+    let (state, commit) =
+        proto::threshold::keygen_commit(&mut provider::ProviderRng, &input.wallet_id);
+    record.keygen = Some(state);
+    db.put(&record)
+        .map_err(|e| anyhow!("Failed to save threshold keygen: {}", e))?;
+    Ok(proto::ThresholdKeygenBeginOutput { commit })
+}
 
-    // Generate P-256 key pair via p256-m using OP-TEE hardware RNG (p256_generate_random).
-    // priv[32] = private scalar; pub[64] = x(32) || y(32) (uncompressed, no 0x04 prefix)
-    let mut priv_bytes = [0u8; 32];
-    let mut pub_bytes = [0u8; 64];
-    let ret = unsafe { p256_gen_keypair(priv_bytes.as_mut_ptr(), pub_bytes.as_mut_ptr()) };
-    if ret != 0 {
-        return Err(anyhow!("p256_gen_keypair failed (code {})", ret));
+/// Generates the Paillier key, the slow step: seconds on an i.MX93.
+#[cfg(feature = "threshold-ecdsa")]
+fn threshold_keygen_finish(
+    input: &proto::ThresholdKeygenFinishInput,
+) -> Result<proto::ThresholdKeygenFinishOutput> {
+    let db = open_storage()?;
+    let mut record = load_threshold_record(&db, &input.wallet_id);
+    let state = record
+        .keygen
+        .take()
+        .ok_or_else(|| anyhow!("no threshold keygen in progress for {}", input.wallet_id))?;
+    let mut rng = provider::ProviderRng;
+    let primes = proto::threshold::paillier_primes(&mut rng);
+    let (share, reveal) = proto::threshold::keygen_finish(
+        &mut rng,
+        &input.wallet_id,
+        &state,
+        &input.cosigner_key,
+        &primes,
+    )
+    .map_err(|e| anyhow!("{}", e))?;
+    let public_key = share.public_key.clone();
+    let address = proto::threshold::address(&public_key).map_err(|e| anyhow!("{}", e))?;
+    record.share = Some(share);
+    db.put(&record)
+        .map_err(|e| anyhow!("Failed to save threshold share: {}", e))?;
+    ta_log!(
+        Crypto,
+        Info,
+        "[+] threshold key for {:?}: 0x{}",
+        input.wallet_id,
+        hex::encode(address)
+    );
+    Ok(proto::ThresholdKeygenFinishOutput {
+        reveal,
+        public_key,
+        address,
+    })
+}
+
+#[cfg(feature = "threshold-ecdsa")]
+fn threshold_presign_begin(
+    input: &proto::ThresholdPresignBeginInput,
+) -> Result<proto::ThresholdPresignBeginOutput> {
+    let db = open_storage()?;
+    let mut record = load_threshold_record(&db, &input.wallet_id);
+    if record.share.is_none() {
+        bail!("wallet has no co-signed threshold key: {}", input.wallet_id);
+    }
+    let count = input.count as usize;
+    if count > record.room() {
+        bail!(
+            "presignature pool holds at most {} and has {}",
+            proto::threshold::MAX_PRESIGNATURES,
+            record.available()
+        );
     }
+    let (states, commits) = proto::threshold::presign_commit(
+        &mut provider::ProviderRng,
+        &input.wallet_id,
+        record.next_presign_id,
+        count,
+    )
+    .map_err(|e| anyhow!("{}", e))?;
+    record.next_presign_id += count as u64;
+    record.pending = states;
+    db.put(&record)
+        .map_err(|e| anyhow!("Failed to save presignature batch: {}", e))?;
+    Ok(proto::ThresholdPresignBeginOutput { commits })
+}
 
-    // Persist both private key and public key in TEE secure storage.
-    // Public key is stored so sign_p256_user_op can embed it in the 149-byte output
-    // without needing to re-derive it from the private key.
+#[cfg(feature = "threshold-ecdsa")]
+fn threshold_presign_finish(
+    input: &proto::ThresholdPresignFinishInput,
+) -> Result<proto::ThresholdPresignFinishOutput> {
     let db = open_storage()?;
-    let sk = P256SessionKey {
-        store_id: P256SessionKey::store_id_for(&input.wallet_id, input.session_index),
-        private_key: priv_bytes.to_vec(),
-        pub_key: pub_bytes.to_vec(),
-    };
-    sk.save(&db)?;
+    let mut record = load_threshold_record(&db, &input.wallet_id);
+    if record.pending.is_empty() {
+        bail!("no presignature batch waiting for replies");
+    }
+    let pending = core::mem::take(&mut record.pending);
+    let (presignatures, reveals) =
+        proto::threshold::presign_finish(&input.wallet_id, &pending, &input.replies)
+            .map_err(|e| anyhow!("{}", e))?;
+    if presignatures.len() > record.room() {
+        bail!("presignature pool is full");
+    }
+    record.presignatures.extend(presignatures);
+    db.put(&record)
+        .map_err(|e| anyhow!("Failed to save presignatures: {}", e))?;
+    Ok(proto::ThresholdPresignFinishOutput {
+        reveals,
+        available: record.available(),
+    })
+}
 
-    let mut pub_key_x = [0u8; 32];
-    let mut pub_key_y = [0u8; 32];
-    pub_key_x.copy_from_slice(&pub_bytes[..32]);
-    pub_key_y.copy_from_slice(&pub_bytes[32..]);
+/// The presignature is out of storage before the signature is computed; a
+/// failure after that costs the presignature, never a second use of it.
+#[cfg(feature = "threshold-ecdsa")]
+fn threshold_sign(input: &proto::ThresholdSignInput) -> Result<proto::ThresholdSignOutput> {
+    let wallet = load_wallet_cached(&input.wallet_id)?;
+    let commitment = proto::threshold::sign_commitment(&input.wallet_id, &input.digest);
+    verify_passkey_for_wallet(&wallet, input.passkey_assertion.as_ref(), Some(&commitment))?;
 
-    // Issue JWT credential for this session key (TEE-HMAC'd, same mechanism as agent keys).
-    // agent_index is repurposed as session_index so verify_credential on the host works unchanged.
-    let iat = tee_unix_secs();
-    let exp = iat.checked_add(input.ttl_secs)
-        .ok_or_else(|| anyhow!("JWT exp overflow"))?;
-    let wallet_id_str = input.wallet_id.to_string();
-    let payload_json = format!(
-        "{{\"sub\":\"{sub}\",\"wallet_id\":\"{wid}\",\"agent_index\":{idx},\"agent_address\":\"0x0000000000000000000000000000000000000000\",\"iat\":{iat},\"exp\":{exp}}}",
-        sub  = input.subject,
-        wid  = wallet_id_str,
-        idx  = input.session_index,
-        iat  = iat,
-        exp  = exp,
+    let db = open_storage()?;
+    let mut record = load_threshold_record(&db, &input.wallet_id);
+    if let Some(key) = &record.recombined {
+        let signature = proto::threshold::sign_with_secret(&key.secret, &input.digest)
+            .map_err(|e| anyhow!("{}", e))?;
+        return Ok(proto::ThresholdSignOutput {
+            signature: signature.to_vec(),
+            available: 0,
+        });
+    }
+    let share = record
+        .share
+        .clone()
+        .ok_or_else(|| anyhow!("wallet has no threshold key: {}", input.wallet_id))?;
+    let reply = input
+        .reply
+        .as_ref()
+        .ok_or_else(|| anyhow!("threshold signing needs the co-signer's reply"))?;
+    let presignature = record
+        .take_presignature(reply.presignature_id)
+        .ok_or_else(|| anyhow!("presignature {} is not in the pool", reply.presignature_id))?;
+    db.put(&record)
+        .map_err(|e| anyhow!("Failed to spend presignature: {}", e))?;
+    let signature = proto::threshold::sign_finish(&share, &presignature, &input.digest, reply)
+        .map_err(|e| anyhow!("{}", e))?;
+    Ok(proto::ThresholdSignOutput {
+        signature: signature.to_vec(),
+        available: record.available(),
+    })
+}
+
+/// Fallback for a co-signer that is gone for good: the TA takes its sealed
+/// share and signs alone from then on, optionally sealing the whole key to
+/// the owner. Not reversible.
+#[cfg(feature = "threshold-ecdsa")]
+fn threshold_recombine(
+    input: &proto::ThresholdRecombineInput,
+) -> Result<proto::ThresholdRecombineOutput> {
+    let wallet = load_wallet_cached(&input.wallet_id)?;
+    let commitment = proto::threshold::recombine_commitment(
+        &input.wallet_id,
+        &input.sealed_share,
+        input.export_to.as_deref(),
     );
-    let jwt_out = jwt_sign_payload_internal(&payload_json)?;
+    verify_passkey_for_wallet(&wallet, input.passkey_assertion.as_ref(), Some(&commitment))?;
 
-    Ok(proto::CreateP256SessionKeyOutput {
-        pub_key_x,
-        pub_key_y,
-        jwt_kid:         jwt_out.kid,
-        jwt_header_b64:  jwt_out.header_b64,
-        jwt_payload_b64: jwt_out.payload_b64,
-        jwt_hmac:        jwt_out.hmac,
+    let db = open_storage()?;
+    let mut record = load_threshold_record(&db, &input.wallet_id);
+    let share = record
+        .share
+        .take()
+        .ok_or_else(|| anyhow!("wallet has no co-signed threshold key: {}", input.wallet_id))?;
+    let key = proto::threshold::RecombinedKey {
+        secret: proto::threshold::recombine(&share, &input.sealed_share)
+            .map_err(|e| anyhow!("{}", e))?,
+        public_key: share.public_key.clone(),
+    };
+    let address = proto::threshold::address(&key.public_key).map_err(|e| anyhow!("{}", e))?;
+    let exported = match &input.export_to {
+        Some(recipient) => Some(
+            proto::threshold::seal(&mut provider::ProviderRng, recipient, &key.secret)
+                .map_err(|e| anyhow!("{}", e))?,
+        ),
+        None => None,
+    };
+    record.pending.clear();
+    record.presignatures.clear();
+    record.recombined = Some(key);
+    db.put(&record)
+        .map_err(|e| anyhow!("Failed to save recombined key: {}", e))?;
+    ta_log!(
+        Crypto,
+        Warn,
+        "[!] threshold key of {:?} recombined{}",
+        input.wallet_id,
+        if exported.is_some() {
+            " and exported"
+        } else {
+            ""
+        }
+    );
+    Ok(proto::ThresholdRecombineOutput { address, exported })
+}
+
+#[cfg(feature = "threshold-ecdsa")]
+fn threshold_status(input: &proto::ThresholdStatusInput) -> Result<proto::ThresholdStatusOutput> {
+    let db = open_storage()?;
+    let record = load_threshold_record(&db, &input.wallet_id);
+    let public_key = match (&record.recombined, &record.share) {
+        (Some(key), _) => Some(key.public_key.clone()),
+        (None, Some(share)) => Some(share.public_key.clone()),
+        (None, None) => None,
+    };
+    let address = match &public_key {
+        Some(key) => Some(proto::threshold::address(key).map_err(|e| anyhow!("{}", e))?),
+        None => None,
+    };
+    Ok(proto::ThresholdStatusOutput {
+        public_key,
+        address,
+        available: record.available(),
+        recombined: record.recombined.is_some(),
+    })
+}
+
+// Builds without the feature answer every threshold command with an error.
+#[cfg(not(feature = "threshold-ecdsa"))]
+macro_rules! threshold_disabled {
+    ($($name:ident($input:ident) -> $output:ident;)+) => {
+        $(fn $name(_input: &proto::$input) -> Result<proto::$output> {
+            Err(anyhow!("threshold ECDSA is not built into this TA (feature threshold-ecdsa)"))
+        })+
+    };
+}
+
+#[cfg(not(feature = "threshold-ecdsa"))]
+threshold_disabled! {
+    threshold_keygen_begin(ThresholdKeygenBeginInput) -> ThresholdKeygenBeginOutput;
+    threshold_keygen_finish(ThresholdKeygenFinishInput) -> ThresholdKeygenFinishOutput;
+    threshold_presign_begin(ThresholdPresignBeginInput) -> ThresholdPresignBeginOutput;
+    threshold_presign_finish(ThresholdPresignFinishInput) -> ThresholdPresignFinishOutput;
+    threshold_sign(ThresholdSignInput) -> ThresholdSignOutput;
+    threshold_recombine(ThresholdRecombineInput) -> ThresholdRecombineOutput;
+    threshold_status(ThresholdStatusInput) -> ThresholdStatusOutput;
+}
+
+// ── Variant B: BLS (DVT 共签)—— 密钥在 TA 内生成+密封，永不出 TEE ──
+
+/// 生成独立 BLS12-381 密钥(TEE TRNG 熵)→ 密封 secure storage → 返回 48B 压缩公钥。
+/// 一次性 provision;已存在则拒绝(不覆盖）。无 passkey 门（DVT 自主签名，授权在 host owner-auth）。
+fn bls_gen_key(input: &proto::BlsGenKeyInput) -> Result<proto::BlsGenKeyOutput> {
+    let db = open_storage()?;
+    let key_id = input.key_id.to_string();
+    // Codex Med#3: enforce a singleton — one BLS key per board. Stops a looped
+    // /gen-key from filling secure storage. Removing the key is an explicit op.
+    if db.count_entries::<BlsKey>()? > 0 {
+        return Err(anyhow!(
+            "a BLS key already exists (singleton); remove it before provisioning a new one"
+        ));
+    }
+    if db.get::<BlsKey>(&key_id).is_ok() {
+        return Err(anyhow!("BLS key already exists: {}", key_id));
+    }
+    let mut ikm = [0u8; 32];
+    provider::fill(&mut ikm);
+    let (sk, pk) = bls::gen_keypair(&ikm)?;
+    if !bls::pubkey_valid(&pk) {
+        return Err(anyhow!("BLS keygen produced invalid pubkey"));
+    }
+    db.put(&BlsKey {
+        key_id: key_id.clone(),
+        private_key: sk,
+        public_key: pk.to_vec(),
+    })?;
+    Ok(proto::BlsGenKeyOutput {
+        key_id: input.key_id,
+        public_key: pk.to_vec(),
+    })
+}
+
+/// 用密封的 BLS 私钥签 32B message（userOpHash）。私钥不出 TEE，只回签名。
+fn bls_sign(input: &proto::BlsSignInput) -> Result<proto::BlsSignOutput> {
+    let db = open_storage()?;
+    let k = db
+        .get::<BlsKey>(&input.key_id.to_string())
+        .map_err(|_| anyhow!("BLS key not found: {}", input.key_id))?;
+    // A key that has signed consensus messages is a validator key: a raw
+    // 32-byte oracle would let the caller sign any slashable root around the
+    // slashing record, so it only signs through the checked commands below.
+    if db
+        .get::<slashing::SlashingRecord>(&slashing::SlashingRecord::store_id_for(&k.key_id))
+        .is_ok()
+    {
+        bail!(
+            "BLS key {} is a validator key; raw BlsSign refused",
+            input.key_id
+        );
+    }
+    let (eip2537, compact) = bls::sign(&k.private_key, &input.message)?;
+    Ok(proto::BlsSignOutput {
+        signature: eip2537.to_vec(),
+        signature_compact: compact.to_vec(),
+    })
+}
+
+/// CC-37 staked registration: BLS proof-of-possession (RFC-standard self-PoP over the node's
+/// OWN pubkey). Derives the pubkey from the sealed key and signs it under BLS_DST — the caller
+/// supplies no message, so /pop is not a signing oracle. Returns the DvtPop tuple.
+fn bls_pop_sign(input: &proto::BlsPopSignInput) -> Result<proto::BlsPopSignOutput> {
+    let db = open_storage()?;
+    let k = db
+        .get::<BlsKey>(&input.key_id.to_string())
+        .map_err(|_| anyhow!("BLS key not found: {}", input.key_id))?;
+    let (public_key, pop_point, pop_signature) = bls::sign_pop(&k.private_key)?;
+    Ok(proto::BlsPopSignOutput {
+        public_key: public_key.to_vec(),
+        pop_point: pop_point.to_vec(),
+        pop_signature: pop_signature.to_vec(),
+    })
+}
+
+/// Check-record-persist, then sign. The updated slashing record is stored
+/// before the signature exists, so a TA crash can at worst drop a signature.
+fn bls_sign_guarded(
+    key_id: &Uuid,
+    signing_root: [u8; 32],
+    check: impl FnOnce(&mut slashing::SlashingRecord) -> Result<()>,
+) -> Result<proto::BlsSignOutput> {
+    let db = open_storage()?;
+    let k = db
+        .get::<BlsKey>(&key_id.to_string())
+        .map_err(|_| anyhow!("BLS key not found: {}", key_id))?;
+    let store_id = slashing::SlashingRecord::store_id_for(&k.key_id);
+    let mut record = db
+        .get::<slashing::SlashingRecord>(&store_id)
+        .unwrap_or_else(|_| slashing::SlashingRecord::empty(&k.key_id));
+    check(&mut record)?;
+    db.put(&record)?;
+    let (eip2537, compact) = bls::sign(&k.private_key, &signing_root)?;
+    Ok(proto::BlsSignOutput {
+        signature: eip2537.to_vec(),
+        signature_compact: compact.to_vec(),
+    })
+}
+
+fn bls_sign_attestation(input: &proto::BlsSignAttestationInput) -> Result<proto::BlsSignOutput> {
+    let root = slashing::signing_root(
+        &slashing::attestation_data_root(
+            input.slot,
+            input.committee_index,
+            &input.beacon_block_root,
+            &input.source,
+            &input.target,
+        ),
+        &input.domain,
+    );
+    bls_sign_guarded(&input.key_id, root, |r| {
+        r.check_and_record_attestation(input.source.epoch, input.target.epoch, root)
+    })
+}
+
+fn bls_sign_block(input: &proto::BlsSignBlockInput) -> Result<proto::BlsSignOutput> {
+    let root = slashing::signing_root(
+        &slashing::block_header_root(
+            input.slot,
+            input.proposer_index,
+            &input.parent_root,
+            &input.state_root,
+            &input.body_root,
+        ),
+        &input.domain,
+    );
+    bls_sign_guarded(&input.key_id, root, |r| {
+        r.check_and_record_block(input.slot, root)
+    })
+}
+
+/// 返回密封 BLS 密钥的 48B 压缩公钥。
+fn bls_pubkey(input: &proto::BlsPubKeyInput) -> Result<proto::BlsPubKeyOutput> {
+    let db = open_storage()?;
+    let k = db
+        .get::<BlsKey>(&input.key_id.to_string())
+        .map_err(|_| anyhow!("BLS key not found: {}", input.key_id))?;
+    Ok(proto::BlsPubKeyOutput {
+        public_key: k.public_key,
+    })
+}
+
+/// 删除密封的 BLS 单例(枚举所有 BlsKey 逐个 delete)。用于恢复"key_id 丢失的孤儿
+/// key"或轮换——不需要知道 key_id(枚举得到)。返回删除数量。host 侧双门控。
+fn bls_remove(_input: &proto::BlsRemoveInput) -> Result<proto::BlsRemoveOutput> {
+    let db = open_storage()?;
+    let entries = db.list_entries::<BlsKey>()?;
+    let mut removed = 0u32;
+    for key_id in entries.keys() {
+        db.delete_entry::<BlsKey>(key_id)?;
+        removed += 1;
+    }
+    Ok(proto::BlsRemoveOutput { removed })
+}
+
+// ── CC-34: keeper/operator ECDSA(secp256k1)—— 密钥在 TA 内生成+密封，永不出 TEE ──
+
+/// Ethereum address = last 20 bytes of keccak256(uncompressed_pubkey[1..]).
+fn eth_address_from_uncompressed(pk65: &[u8; 65]) -> [u8; 20] {
+    let h = eip712::keccak(&pk65[1..]);
+    let mut addr = [0u8; 20];
+    addr.copy_from_slice(&h[12..]);
+    addr
+}
+
+/// 生成独立 secp256k1 keeper 密钥(TEE TRNG 熵)→ 密封 secure storage → 返回 65B 未压缩
+/// 公钥 + 20B 以太坊地址(充值 EOA)。一次性 provision;已存在则拒绝(singleton,不覆盖)。
+/// 无 passkey 门（keeper 自主签名，授权在 host provisioning-gate + X-Signer-Token）。
+fn keeper_gen_key(input: &proto::KeeperGenKeyInput) -> Result<proto::KeeperGenKeyOutput> {
+    let db = open_storage()?;
+    let key_id = input.key_id.to_string();
+    // Singleton: one keeper key per board (mirrors bls_gen_key). Stops a looped
+    // /gen-keeper-eoa from filling secure storage.
+    if db.count_entries::<KeeperKey>()? > 0 {
+        return Err(anyhow!(
+            "a keeper key already exists (singleton); remove it before provisioning a new one"
+        ));
+    }
+    if db.get::<KeeperKey>(&key_id).is_ok() {
+        return Err(anyhow!("keeper key already exists: {}", key_id));
+    }
+    let secp = secp256k1::Secp256k1::new();
+    // Rejection-sample TEE randomness until it is a valid secp256k1 scalar.
+    let secret_key = loop {
+        let mut sk_bytes = [0u8; 32];
+        provider::fill(&mut sk_bytes);
+        if let Ok(sk) = secp256k1::SecretKey::from_slice(&sk_bytes) {
+            break sk;
+        }
+    };
+    let pk = secp256k1::PublicKey::from_secret_key(&secp, &secret_key);
+    let pk_uncompressed = pk.serialize_uncompressed(); // [u8; 65], leading 0x04
+    let address = eth_address_from_uncompressed(&pk_uncompressed);
+    db.put(&KeeperKey {
+        key_id: key_id.clone(),
+        private_key: secret_key.secret_bytes(),
+        public_key: pk_uncompressed.to_vec(),
+    })?;
+    Ok(proto::KeeperGenKeyOutput {
+        key_id: input.key_id,
+        public_key: pk_uncompressed.to_vec(),
+        address,
+    })
+}
+
+/// 用密封的 keeper 私钥签 32B raw digest（不再 hash）→ 65B recoverable r||s||v (v=27/28)。
+/// 私钥不出 TEE，只回签名。rust-secp256k1 的 sign_ecdsa_recoverable 产出 canonical low-S。
+fn keeper_sign(input: &proto::KeeperSignInput) -> Result<proto::KeeperSignOutput> {
+    let db = open_storage()?;
+    let k = db
+        .get::<KeeperKey>(&input.key_id.to_string())
+        .map_err(|_| anyhow!("keeper key not found: {}", input.key_id))?;
+    let secret_key = secp256k1::SecretKey::from_slice(&k.private_key)?;
+    let secp = secp256k1::Secp256k1::new();
+    let message = secp256k1::Message::from_slice(&input.digest)?;
+    let sig = secp.sign_ecdsa_recoverable(&message, &secret_key);
+    let (recovery_id, sig_bytes) = sig.serialize_compact();
+    let mut signature = Vec::with_capacity(65);
+    signature.extend_from_slice(&sig_bytes); // r(32) || s(32)
+    signature.push(recovery_id.to_i32() as u8 + 27); // v = 27/28
+    Ok(proto::KeeperSignOutput { signature })
+}
+
+/// 返回密封 keeper 密钥的 65B 未压缩公钥 + 20B 地址。
+fn keeper_pubkey(input: &proto::KeeperPubKeyInput) -> Result<proto::KeeperPubKeyOutput> {
+    let db = open_storage()?;
+    let k = db
+        .get::<KeeperKey>(&input.key_id.to_string())
+        .map_err(|_| anyhow!("keeper key not found: {}", input.key_id))?;
+    let mut pk65 = [0u8; 65];
+    if k.public_key.len() != 65 {
+        return Err(anyhow!(
+            "sealed keeper pubkey length invalid: {}",
+            k.public_key.len()
+        ));
+    }
+    pk65.copy_from_slice(&k.public_key);
+    let address = eth_address_from_uncompressed(&pk65);
+    Ok(proto::KeeperPubKeyOutput {
+        public_key: k.public_key,
+        address,
     })
 }
 
-fn sign_p256_user_op(
-    input: &proto::SignP256UserOpInput,
-) -> Result<proto::SignP256UserOpOutput> {
-    dbg_println!(
-        "[+] Sign P256 user op for wallet: {:?}, index: {}",
+// H-1 (DOWNGRADED to Medium — tracked as an accepted limitation / follow-up issue):
+// DeriveAddressAuto carries no passkey assertion and mutates+persists wallet state
+// (next_address_index). It is invoked by the CA immediately after wallet creation,
+// before any passkey-gated flow exists for the new wallet, so requiring a passkey
+// here would break the legitimate creation path (no user assertion is available at
+// that instant). A *compromised CA* could call it directly to advance the address
+// index up to the per-wallet cap (100, enforced in Wallet::increment_address_index),
+// but it cannot exfiltrate keys, sign, or affect other wallets, and the index is now
+// anti-rollback protected (M-5). Net effect of abuse is bounded address churn for a
+// single wallet. A proper fix (bind to a just-created-this-session token, or move the
+// call inline into create_wallet) is deferred to a dedicated issue to avoid breaking
+// the creation flow in this bugfix pass.
+fn derive_address_auto(
+    input: &proto::DeriveAddressAutoInput,
+) -> Result<proto::DeriveAddressAutoOutput> {
+    // M-5: this mutation bumps next_address_index and persists it. Bump the RPMB
+    // anti-rollback epoch too, otherwise the address index could be silently
+    // rolled back to re-derive (reuse) a previously issued address. Read the next
+    // epoch before any thread_local (cache) access; write the counter only after
+    // save_wallet (last TLS access), matching create_wallet's ordering.
+    let epoch = rpmb_next_epoch()?;
+
+    let db_client = open_storage()?;
+
+    ta_log!(
+        Crypto,
+        Debug,
+        "[+] DeriveAddressAuto for wallet: {:?}",
+        input.wallet_id
+    );
+    let mut wallet = match cache_get(&input.wallet_id) {
+        Some(w) => w,
+        None => db_client
+            .get::<Wallet>(&input.wallet_id)
+            .map_err(|e| anyhow!("wallet not found: {:?}", e))?,
+    };
+
+    let address_index = wallet.increment_address_index()?;
+    wallet.ensure_seed_cached()?;
+    wallet.rollback_epoch = epoch;
+
+    let derivation_path = proto::hd_path::eth_path(0, address_index);
+    let (address, public_key) = wallet.derive_address(&derivation_path)?;
+
+    // save_wallet does cache_put (TLS) then db.put (corrupts TLS). After this,
+    // no more thread_local access — safe to call rpmb_write_counter.
+    save_wallet(&db_client, &wallet)?;
+    rpmb_write_counter(epoch)?;
+
+    Ok(proto::DeriveAddressAutoOutput {
+        wallet_id: input.wallet_id,
+        address,
+        public_key,
+        derivation_path,
+    })
+}
+
+// Production builds: unconditionally reject — private key must never leave the TEE.
+#[cfg(not(feature = "export-secrets"))]
+fn export_private_key(
+    _input: &proto::ExportPrivateKeyInput,
+) -> Result<proto::ExportPrivateKeyOutput> {
+    Err(anyhow!("ExportPrivateKey is disabled in production TA builds"))
+}
+
+// Dev/test builds only (--features export-secrets): allow explicit export with passkey or admin bypass.
+#[cfg(feature = "export-secrets")]
+fn export_private_key(
+    input: &proto::ExportPrivateKeyInput,
+) -> Result<proto::ExportPrivateKeyOutput> {
+    ta_log!(
+        Crypto,
+        Debug,
+        "[+] Export private key for wallet: {:?}, path: {}",
+        input.wallet_id,
+        input.derivation_path
+    );
+
+    let wallet = load_wallet_cached(&input.wallet_id)?;
+
+    if input.passkey_assertion.is_some() {
+        verify_passkey_for_wallet(&wallet, input.passkey_assertion.as_ref(), None)?;
+    } else {
+        ta_log!(
+            Crypto,
+            Debug,
+            "[+] ExportPrivateKey: dev admin mode (no passkey assertion)"
+        );
+    }
+
+    let private_key = wallet.export_private_key(&input.derivation_path)?;
+
+    Ok(proto::ExportPrivateKeyOutput { private_key })
+}
+
+// M-3: no longer wired into handle_invoke (removed from dispatch to avoid being
+// used as an auth oracle). Kept (allow-dead-code) only as a documentation stub.
+#[allow(dead_code)]
+fn verify_passkey(_input: &proto::VerifyPasskeyInput) -> Result<proto::VerifyPasskeyOutput> {
+    ta_log!(
+        Crypto,
+        Debug,
+        "[+] Verify passkey for wallet: {:?}",
+        _input.wallet_id
+    );
+
+    // Standalone VerifyPasskey TA command: not exposed via any HTTP endpoint.
+    // Actual signing operations use verify_passkey_for_wallet() which calls p256-m.
+    // This stub exists for future diagnostic use only.
+    Ok(proto::VerifyPasskeyOutput { valid: true })
+}
+
+fn register_passkey_ta(
+    input: &proto::RegisterPasskeyTaInput,
+) -> Result<proto::RegisterPasskeyTaOutput> {
+    ta_log!(
+        Session,
+        Info,
+        "[+] Registering passkey for wallet: {:?}",
+        input.wallet_id
+    );
+
+    if input.passkey_pubkey.len() != 65 || input.passkey_pubkey[0] != 0x04 {
+        bail!(
+            "PassKey public key must be 65 bytes uncompressed (0x04 || x || y), got {} bytes",
+            input.passkey_pubkey.len()
+        );
+    }
+
+    // Read RPMB epoch before load_wallet_cached (which touches thread_local cache).
+    let epoch = rpmb_next_epoch()?;
+
+    let mut wallet = load_wallet_cached(&input.wallet_id)?;
+    // Verify current passkey before allowing change
+    verify_passkey_for_wallet(&wallet, input.passkey_assertion.as_ref(), None)?;
+    // Owner credential changed: child keys derived under the old authorization
+    // must not outlive it.
+    key_cache::invalidate_wallet(&input.wallet_id);
+    wallet.set_passkey(input.passkey_pubkey.clone());
+    wallet.rollback_epoch = epoch;
+
+    let db = open_storage()?;
+    // save_wallet does cache_put (TLS) then db.put (corrupts TLS).
+    save_wallet(&db, &wallet)?;
+    rpmb_write_counter(epoch)?;
+    ta_log!(
+        Session,
+        Info,
+        "[+] PassKey registered, wallet saved (RPMB epoch={})",
+        epoch
+    );
+
+    Ok(proto::RegisterPasskeyTaOutput { registered: true })
+}
+
+fn warmup_cache(input: &proto::WarmupCacheInput) -> Result<proto::WarmupCacheOutput> {
+    ta_log!(
+        Storage,
+        Debug,
+        "[+] Warmup cache for wallet: {:?}",
+        input.wallet_id
+    );
+    let _wallet = load_wallet_cached(&input.wallet_id)?;
+    let kc = key_cache::stats();
+    ta_log!(
+        Storage,
+        Debug,
+        "[+] Child-key cache: {} entries, {} hits, {} misses",
+        kc.0,
+        kc.1,
+        kc.2
+    );
+    Ok(proto::WarmupCacheOutput {
+        cached: true,
+        cache_size: cache_len() as u32,
+    })
+}
+
+fn read_rollback_counter(
+    _input: &proto::ReadRollbackCounterInput,
+) -> Result<proto::ReadRollbackCounterOutput> {
+    let counter = rpmb_read_counter()?;
+    Ok(proto::ReadRollbackCounterOutput { counter })
+}
+
+/// Issue #49: issue a fresh one-time WebAuthn challenge nonce bound to a wallet.
+///
+/// Requires the wallet to exist (and thus have a passkey bound) so a compromised
+/// CA cannot farm nonces for non-existent wallets. The nonce is held in-memory
+/// only and consumed by the next signing assertion. No secure-storage write
+/// happens here, so there is no TLS/thread_local hazard (H-3).
+fn get_challenge(input: &proto::GetChallengeInput) -> Result<proto::GetChallengeOutput> {
+    ta_log!(
+        Session,
+        Debug,
+        "[+] GetChallenge for wallet: {:?}",
+        input.wallet_id
+    );
+    // Ensure the wallet exists before issuing a nonce. load_wallet_cached errors
+    // if the wallet is unknown.
+    let _wallet = load_wallet_cached(&input.wallet_id)?;
+    // Issue #68: the nonce stays a plain random value. Payload binding is rooted
+    // in the CLIENT's challenge derivation (challenge = SHA256(nonce||payload)),
+    // verified at signing time in verify_challenge_binding — so the TA needs no
+    // payload at issue time and there is no CA-controllable bound field.
+    let nonce = challenge_issue(&input.wallet_id);
+    Ok(proto::GetChallengeOutput {
+        nonce: nonce.to_vec(),
+    })
+}
+
+fn agent_derivation_path(agent_index: u32) -> String {
+    format!("m/44'/60'/0'/1/{}", agent_index)
+}
+
+/// Maximum allowed JWT lifetime: 24 hours, or less if the TA config says so.
+/// Shortened from 7 days (2026-06-22) to bound the delegated-signing window: a
+/// leaked/abused credential — or a ttl a compromised CA tried to stretch — is valid
+/// at most 24h. Agents/sessions re-mint (re-auth with passkey) daily. 24h is also
+/// the config ceiling: InstallConfig can shorten the window, never stretch it.
+fn max_agent_jwt_ttl() -> i64 {
+    ta_config::limits().agent_jwt_ttl_secs as i64
+}
+
+/// Current wall-clock time (UNIX epoch seconds) read from the REE clock via TEE_GetREETime.
+///
+/// `std::time::SystemTime::now()` is NOT wired into the OP-TEE TA runtime — calling it panics
+/// the TA (observed on real i.MX93 hardware: create-agent-key / refresh-agent-credential aborted
+/// with a TA panic). The TA must obtain time through the optee-utee `Time` API instead.
+///
+/// REE time is "as trusted as the REE itself" (the host can shift the system clock), but the host
+/// still cannot inject `iat`/`exp` into the HMAC-signed JWT payload directly — the TA computes and
+/// signs them, so H-3 (TA owns iat; host only supplies the capped ttl_secs) still holds.
+fn tee_unix_secs() -> i64 {
+    provider::unix_secs() as i64
+}
+
+fn create_agent_key(input: &proto::CreateAgentKeyInput) -> Result<proto::CreateAgentKeyOutput> {
+    ta_log!(
+        Session,
+        Debug,
+        "[+] Create agent key for wallet: {:?}, agent_index: {}",
+        input.wallet_id,
+        input.agent_index
+    );
+
+    let wallet = load_wallet_cached(&input.wallet_id)?;
+
+    // C-1 (#111): TA-side user-presence verification — blocks a compromised host from
+    // minting agent credentials without the user. (This is the critical line; it stays.)
+    // #115: bind the challenge to a CLIENT-AUTHORIZED commitment so a compromised CA
+    // cannot ride the gesture with different inputs. CREATE binds the label (+wallet);
+    // REFRESH binds the agent_index being re-minted (+wallet) under a DISTINCT tag, so a
+    // refresh assertion can't be replayed to create (and vice versa) even for an empty
+    // label. The CA can't flip is_refresh: the client committed to the matching shape.
+    // transition accepts the bare nonce; strict requires challenge == SHA-256(nonce‖digest).
+    // Server-derived params (the allocated index for CREATE, subject, ttl) are NOT bound —
+    // ttl is bounded by max_agent_jwt_ttl().
+    //
+    // A refresh secret replaces the gesture only for REFRESH, and only while its family
+    // (opened by an earlier passkey mint) still names this device and this passkey.
+    let passkey_hash = proto::refresh_token::passkey_hash(wallet.get_passkey().unwrap_or(&[]));
+    let redeem = input.refresh.as_ref().and_then(|r| r.secret.map(|s| (r.device_hash, s)));
+    let mut redeemed: Option<refresh_token::RefreshRecord> = None;
+    if let Some((device_hash, secret)) = redeem {
+        if !input.is_refresh || input.passkey_assertion.is_some() {
+            bail!("a refresh token only re-mints an existing agent credential, without a passkey");
+        }
+        let db = open_storage()?;
+        let store_id = refresh_token::RefreshRecord::store_id_for(&input.wallet_id, input.agent_index);
+        let record = db
+            .get::<refresh_token::RefreshRecord>(&store_id)
+            .map_err(|_| anyhow!("no refresh token family for this agent key; authenticate with the passkey"))?;
+        let now = tee_unix_secs();
+        match record.family.check(input.agent_index, &secret, &device_hash, &passkey_hash, now) {
+            proto::refresh_token::RefreshCheck::Rotate => redeemed = Some(record),
+            proto::refresh_token::RefreshCheck::Refuse(why) => bail!("{}", why),
+            proto::refresh_token::RefreshCheck::Close(why) => {
+                // Last storage call of this invocation (H-3).
+                db.delete_entry::<refresh_token::RefreshRecord>(&store_id)?;
+                bail!("{}; refresh token family revoked", why);
+            }
+        }
+    } else {
+        let mint_digest = if input.is_refresh {
+            agent_refresh_digest(&input.wallet_id, input.agent_index)
+        } else {
+            mint_label_digest(&input.wallet_id, &input.label, b"AA-AGENT-MINT-v2")
+        };
+        verify_passkey_for_wallet(&wallet, input.passkey_assertion.as_ref(), Some(&mint_digest))?;
+    }
+
+    // H-1: Enforce TTL bounds inside TA (host-supplied, but TA caps it).
+    let max_ttl = max_agent_jwt_ttl();
+    if input.ttl_secs <= 0 || input.ttl_secs > max_ttl {
+        return Err(anyhow!("ttl_secs must be in 1..={}", max_ttl));
+    }
+    // H-2: Subject must not contain JSON-special characters that could inject claims.
+    validate_jwt_subject(&input.subject)?;
+
+    let derivation_path = agent_derivation_path(input.agent_index);
+    let (agent_address, public_key_compressed) = wallet.derive_address(&derivation_path)?;
+
+    // Build JWT payload entirely inside TEE — iat computed from the TA's view of the clock so
+    // a compromised host cannot supply iat=0 or iat=far_future to shift the TTL window.
+    // H-3: TA owns iat; host only supplies ttl_secs (capped above).
+    let iat = tee_unix_secs();
+    let agent_addr_hex = format!("0x{}", hex::encode(agent_address));
+    let wallet_id_str = input.wallet_id.to_string();
+    let exp = iat.checked_add(input.ttl_secs)
+        .ok_or_else(|| anyhow!("JWT exp overflow"))?;
+    let payload_json = format!(
+        "{{\"sub\":\"{sub}\",\"wallet_id\":\"{wid}\",\"agent_index\":{idx},\"agent_address\":\"{addr}\",\"iat\":{iat},\"exp\":{exp}}}",
+        sub  = input.subject,
+        wid  = wallet_id_str,
+        idx  = input.agent_index,
+        addr = agent_addr_hex,
+        iat  = iat,
+        exp  = exp,
+    );
+    let jwt_out = jwt_sign_payload_internal(&payload_json)?;
+
+    // After the JWT secret store write: no thread_local access from here on (H-3).
+    let refresh_secret = match &input.refresh {
+        Some(refresh) => {
+            let mut next = [0u8; 32];
+            provider::fill(&mut next);
+            let record = match redeemed {
+                Some(mut record) => {
+                    record.family.rotate(&next);
+                    record
+                }
+                None => refresh_token::RefreshRecord::new(
+                    &input.wallet_id,
+                    proto::refresh_token::RefreshFamily::open(
+                        input.agent_index,
+                        passkey_hash,
+                        refresh.device_hash,
+                        &next,
+                        iat,
+                    ),
+                ),
+            };
+            open_storage()?
+                .put(&record)
+                .map_err(|e| anyhow!("Failed to save refresh token family: {}", e))?;
+            Some(next)
+        }
+        None => None,
+    };
+
+    Ok(proto::CreateAgentKeyOutput {
+        agent_address,
+        public_key_compressed,
+        jwt_kid:        jwt_out.kid,
+        jwt_header_b64: jwt_out.header_b64,
+        jwt_payload_b64: jwt_out.payload_b64,
+        jwt_hmac:       jwt_out.hmac,
+        refresh_secret,
+    })
+}
+
+fn sign_agent_user_op(input: &proto::SignAgentUserOpInput) -> Result<proto::SignAgentUserOpOutput> {
+    ta_log!(
+        Session,
+        Debug,
+        "[+] Sign agent user op for wallet: {:?}, agent_index: {}",
+        input.wallet_id,
+        input.agent_index
+    );
+
+    // TA-side JWT authorization: verify HMAC proof before signing.
+    // Prevents a compromised CA from bypassing host-side JWT verification.
+    let jwt_verify_input = proto::JwtHmacVerifyInput {
+        kid: input.jwt_kid.clone(),
+        message: input.jwt_signing_input.clone(),
+        expected_hmac: input.jwt_hmac.clone(),
+    };
+    let verify_result = jwt_hmac_verify(&jwt_verify_input)?;
+    if !verify_result.valid {
+        return Err(anyhow!("TA: agent JWT credential verification failed"));
+    }
+
+    // Bind JWT claims to request parameters — prevents a compromised host from using
+    // wallet A's JWT to request signing for wallet B (Issue #16 claim-binding fix).
+    verify_jwt_wallet_claims(&input.jwt_signing_input, &input.wallet_id, input.agent_index)?;
+
+    let wallet = load_wallet_cached(&input.wallet_id)?;
+    let derivation_path = agent_derivation_path(input.agent_index);
+    let private_key = wallet.export_private_key(&derivation_path)?;
+
+    // Derive the agent key's Ethereum address from its public key (inside TEE, safe).
+    let (agent_key_address, _) = wallet.derive_address(&derivation_path)?;
+
+    let mut eip191 = b"\x19Ethereum Signed Message:\n32".to_vec();
+    eip191.extend_from_slice(&input.user_op_hash);
+    let digest = Keccak256::digest(&eip191);
+
+    let secret_key = secp256k1::SecretKey::from_slice(&private_key)?;
+    let secp = secp256k1::Secp256k1::new();
+    let message = secp256k1::Message::from_slice(&digest[..])?;
+    let sig = secp.sign_ecdsa_recoverable(&message, &secret_key);
+    let (recovery_id, sig_bytes) = sig.serialize_compact();
+
+    // v0.17.2 wire format: [0x08][account(20)][key(20)][r(32)][s(32)][v(1)] = 106 bytes
+    // account = Smart Account contract address (prevents cross-account session-key abuse)
+    // key     = agent secp256k1 EOA address (derived from private key inside TEE)
+    let mut signature = Vec::with_capacity(106);
+    signature.push(0x08u8);
+    signature.extend_from_slice(&input.account_address);
+    signature.extend_from_slice(&agent_key_address);
+    signature.extend_from_slice(&sig_bytes);
+    signature.push(recovery_id.to_i32() as u8 + 27);
+
+    Ok(proto::SignAgentUserOpOutput { signature })
+}
+
+fn hmac_sha256(secret: &[u8], message: &[u8]) -> Result<[u8; 32]> {
+    let mut mac =
+        HmacSha256::new_from_slice(secret).map_err(|_| anyhow!("Invalid JWT HMAC secret"))?;
+    mac.update(message);
+    let bytes = mac.finalize().into_bytes();
+    let mut out = [0u8; 32];
+    out.copy_from_slice(&bytes);
+    Ok(out)
+}
+
+
+/// Internal JWT signing helper — NOT exposed as a TA command.
+/// Accepts a pre-built payload JSON string, computes the full JWT signing_input
+/// and HMAC using the current TEE-stored secret.
+struct JwtSignedMaterial {
+    kid: String,
+    header_b64: String,
+    payload_b64: String,
+    hmac: [u8; 32],
+}
+
+fn jwt_sign_payload_internal(payload_json: &str) -> Result<JwtSignedMaterial> {
+    use base64ct::{Base64UrlUnpadded, Encoding};
+
+    let db = open_storage()?;
+    let mut store = JwtSecretStore::load(&db);
+    let current = store.ensure_current()?.clone();
+    store.save(&db)?;
+
+    let header_json = format!(
+        "{{\"alg\":\"HS256\",\"typ\":\"JWT\",\"kid\":\"{}\"}}",
+        current.kid
+    );
+    let header_b64 = Base64UrlUnpadded::encode_string(header_json.as_bytes());
+    let payload_b64 = Base64UrlUnpadded::encode_string(payload_json.as_bytes());
+
+    let signing_input = format!("{}.{}", header_b64, payload_b64);
+    let hmac = hmac_sha256(&current.secret, signing_input.as_bytes())?;
+
+    Ok(JwtSignedMaterial { kid: current.kid, header_b64, payload_b64, hmac })
+}
+
+/// Extract a u64 field value from a compact JSON object without serde_json.
+fn extract_json_u64_field(json: &str, key: &str) -> Result<u64> {
+    let pattern = format!("\"{}\":", key);
+    let start = json.find(&*pattern)
+        .ok_or_else(|| anyhow!("JWT claim '{}' not found", key))?
+        + pattern.len();
+    let rest = json[start..].trim_start_matches(' ');
+    let end = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+    rest[..end].parse::<u64>().map_err(|_| anyhow!("JWT claim '{}' is not a u64", key))
+}
+
+/// Validate that `subject` contains only characters safe to embed directly in a JSON string
+/// without escaping. Prevents claim injection via a crafted subject value (H-2).
+fn validate_jwt_subject(s: &str) -> Result<()> {
+    if s.is_empty() || s.len() > 256 {
+        return Err(anyhow!("JWT subject length must be 1..=256"));
+    }
+    if !s.bytes().all(|b| b.is_ascii_alphanumeric() || b"-_:.@+/=".contains(&b)) {
+        return Err(anyhow!("JWT subject contains characters not allowed in JSON (no quotes, backslashes, or controls)"));
+    }
+    Ok(())
+}
+
+/// After HMAC verification, decode the JWT payload and verify that wallet_id and
+/// agent_index match the request parameters. Prevents a compromised host from
+/// using a legitimate JWT for wallet A to request signing for wallet B.
+fn verify_jwt_wallet_claims(
+    signing_input: &[u8],
+    expected_wallet_id: &uuid::Uuid,
+    expected_agent_index: u32,
+) -> Result<()> {
+    use base64ct::{Base64UrlUnpadded, Encoding};
+
+    let input_str = core::str::from_utf8(signing_input)
+        .map_err(|_| anyhow!("JWT signing_input is not UTF-8"))?;
+    let dot = input_str.find('.')
+        .ok_or_else(|| anyhow!("JWT signing_input missing dot separator"))?;
+    let payload_b64 = &input_str[dot + 1..];
+    let payload_bytes = Base64UrlUnpadded::decode_vec(payload_b64)
+        .map_err(|_| anyhow!("JWT payload base64 decode failed"))?;
+    let payload_str = core::str::from_utf8(&payload_bytes)
+        .map_err(|_| anyhow!("JWT payload is not UTF-8"))?;
+
+    let jwt_wallet_id = extract_json_str_field(payload_str, "wallet_id")?;
+    if jwt_wallet_id != expected_wallet_id.to_string() {
+        return Err(anyhow!("JWT wallet_id claim does not match request"));
+    }
+
+    // H-3: use u64 then bounds-check before casting to avoid silent truncation
+    let jwt_agent_index_u64 = extract_json_u64_field(payload_str, "agent_index")?;
+    if jwt_agent_index_u64 > u32::MAX as u64 {
+        return Err(anyhow!("JWT agent_index overflow"));
+    }
+    if jwt_agent_index_u64 as u32 != expected_agent_index {
+        return Err(anyhow!("JWT agent_index claim does not match request"));
+    }
+
+    // Structural exp/iat check: exp must be after iat and within the TTL cap.
+    let iat = extract_json_u64_field(payload_str, "iat")?;
+    let exp = extract_json_u64_field(payload_str, "exp")?;
+    if exp <= iat || exp.saturating_sub(iat) > max_agent_jwt_ttl() as u64 {
+        return Err(anyhow!("JWT exp/iat structurally invalid or exceeds TTL cap"));
+    }
+
+    // #15: runtime expiry check against the TEE-side trusted clock. tee_unix_secs()
+    // reads REE time via optee_utee::Time::ree_time() — the same trusted-time source
+    // the #49 challenge-binding TTL already relies on (the old "no trusted clock in
+    // mock mode" note was stale). Reject a JWT whose exp is at/before now. Guard on
+    // now > 0 so a mock/uninitialized clock (returns 0) skips the check rather than
+    // rejecting every token; on real hardware ree_time is a valid unix timestamp.
+    let now = tee_unix_secs();
+    if now > 0 {
+        if now as u64 >= exp {
+            return Err(anyhow!("JWT expired (now {} >= exp {})", now, exp));
+        }
+    } else {
+        // Observability: the REE clock returned <= 0 (mock/uninitialized or a
+        // fault). We skip the runtime expiry check rather than reject every
+        // token, but surface it — a PERSISTENT now<=0 on real hardware means JWT
+        // expiry is silently NOT being enforced and must be investigated.
+        ta_log!(
+            Session,
+            Warn,
+            "[!] #15: TEE REE clock returned {} (<=0); JWT runtime expiry NOT enforced this call",
+            now
+        );
+    }
+
+    Ok(())
+}
+
+fn create_p256_session_key(
+    input: &proto::CreateP256SessionKeyInput,
+) -> Result<proto::CreateP256SessionKeyOutput> {
+    ta_log!(
+        Session,
+        Debug,
+        "[+] Create P256 session key for wallet: {:?}, index: {}",
+        input.wallet_id,
+        input.session_index
+    );
+
+    // Verify the wallet exists so we don't create orphaned session keys
+    let wallet = load_wallet_cached(&input.wallet_id)?;
+
+    // #111: re-verify user presence IN THE TEE before minting the session key +
+    // TEE-HMAC JWT. Without this, a compromised CA could issue CreateP256SessionKey
+    // directly and mint a signing credential with no user assertion (the host's
+    // WebAuthn check would be the only barrier — defeated by a malicious host).
+    // C-1 (#111): TA-side user-presence verification (critical line; stays).
+    // #115: bind the challenge to the CLIENT-AUTHORIZED label (+ wallet_id). See
+    // create_agent_key for the full rationale (server-derived index/subject/ttl NOT
+    // bound; transition accepts bare nonce, strict requires the commitment).
+    verify_passkey_for_wallet(
+        &wallet,
+        input.passkey_assertion.as_ref(),
+        Some(&mint_label_digest(&input.wallet_id, &input.label, b"AA-P256-SESSION-MINT-v2")),
+    )?;
+
+    // Enforce TTL bounds (same as create_agent_key)
+    let max_ttl = max_agent_jwt_ttl();
+    if input.ttl_secs <= 0 || input.ttl_secs > max_ttl {
+        return Err(anyhow!("ttl_secs must be in 1..={}", max_ttl));
+    }
+    validate_jwt_subject(&input.subject)?;
+
+    // Generate P-256 key pair via p256-m using OP-TEE hardware RNG (p256_generate_random).
+    // priv[32] = private scalar; pub[64] = x(32) || y(32) (uncompressed, no 0x04 prefix)
+    let mut priv_bytes = [0u8; 32];
+    let mut pub_bytes = [0u8; 64];
+    let ret = unsafe { p256_gen_keypair(priv_bytes.as_mut_ptr(), pub_bytes.as_mut_ptr()) };
+    if ret != 0 {
+        return Err(anyhow!("p256_gen_keypair failed (code {})", ret));
+    }
+
+    // Persist both private key and public key in TEE secure storage.
+    // Public key is stored so sign_p256_user_op can embed it in the 149-byte output
+    // without needing to re-derive it from the private key.
+    let db = open_storage()?;
+    let sk = P256SessionKey {
+        store_id: P256SessionKey::store_id_for(&input.wallet_id, input.session_index),
+        private_key: priv_bytes.to_vec(),
+        pub_key: pub_bytes.to_vec(),
+    };
+    sk.save(&db)?;
+
+    let mut pub_key_x = [0u8; 32];
+    let mut pub_key_y = [0u8; 32];
+    pub_key_x.copy_from_slice(&pub_bytes[..32]);
+    pub_key_y.copy_from_slice(&pub_bytes[32..]);
+
+    // Issue JWT credential for this session key (TEE-HMAC'd, same mechanism as agent keys).
+    // agent_index is repurposed as session_index so verify_credential on the host works unchanged.
+    let iat = tee_unix_secs();
+    let exp = iat.checked_add(input.ttl_secs)
+        .ok_or_else(|| anyhow!("JWT exp overflow"))?;
+    let wallet_id_str = input.wallet_id.to_string();
+    let payload_json = format!(
+        "{{\"sub\":\"{sub}\",\"wallet_id\":\"{wid}\",\"agent_index\":{idx},\"agent_address\":\"0x0000000000000000000000000000000000000000\",\"iat\":{iat},\"exp\":{exp}}}",
+        sub  = input.subject,
+        wid  = wallet_id_str,
+        idx  = input.session_index,
+        iat  = iat,
+        exp  = exp,
+    );
+    let jwt_out = jwt_sign_payload_internal(&payload_json)?;
+
+    Ok(proto::CreateP256SessionKeyOutput {
+        pub_key_x,
+        pub_key_y,
+        jwt_kid:         jwt_out.kid,
+        jwt_header_b64:  jwt_out.header_b64,
+        jwt_payload_b64: jwt_out.payload_b64,
+        jwt_hmac:        jwt_out.hmac,
+    })
+}
+
+fn sign_p256_user_op(
+    input: &proto::SignP256UserOpInput,
+) -> Result<proto::SignP256UserOpOutput> {
+    ta_log!(
+        Session,
+        Debug,
+        "[+] Sign P256 user op for wallet: {:?}, index: {}",
+        input.wallet_id,
+        input.session_index
+    );
+
+    // TA-side JWT authorization: verify HMAC proof before any signing (defense-in-depth).
+    // Prevents a compromised CA from bypassing host-side JWT verification.
+    let jwt_verify_input = proto::JwtHmacVerifyInput {
+        kid: input.jwt_kid.clone(),
+        message: input.jwt_signing_input.clone(),
+        expected_hmac: input.jwt_hmac.clone(),
+    };
+    let verify_result = jwt_hmac_verify(&jwt_verify_input)?;
+    if !verify_result.valid {
+        return Err(anyhow!("TA: P256 session JWT credential verification failed"));
+    }
+
+    // Load P-256 key pair from TEE secure storage
+    let db = open_storage()?;
+    let sk = P256SessionKey::load(&db, &input.wallet_id, input.session_index)?;
+    if sk.private_key.len() != 32 {
+        return Err(anyhow!(
+            "Corrupt P256 private key: expected 32 bytes, got {}",
+            sk.private_key.len()
+        ));
+    }
+    if sk.pub_key.len() != 64 {
+        return Err(anyhow!(
+            "Corrupt P256 public key: expected 64 bytes, got {}",
+            sk.pub_key.len()
+        ));
+    }
+
+    // EIP-191 prefix: "\x19Ethereum Signed Message:\n32" || userOpHash
+    let mut eip191 = b"\x19Ethereum Signed Message:\n32".to_vec();
+    eip191.extend_from_slice(&input.user_op_hash);
+    let digest = Keccak256::digest(&eip191);
+
+    // Sign with P-256 via p256-m (internally uses p256_generate_random for nonce)
+    // sig[64] = r(32) || s(32) big-endian integers
+    let mut sig_bytes = [0u8; 64];
+    let ret = unsafe {
+        p256_ecdsa_sign(
+            sig_bytes.as_mut_ptr(),
+            sk.private_key.as_ptr(),
+            digest.as_ptr(),
+            digest.len(),
+        )
+    };
+    if ret != 0 {
+        return Err(anyhow!("p256_ecdsa_sign failed (code {})", ret));
+    }
+
+    // v0.18.1 wire format: [0x08][account(20)][keyX(32)][keyY(32)][r(32)][s(32)] = 149 bytes
+    // account = ERC-4337 Smart Account address (prevents cross-account session key abuse)
+    // keyX/keyY = P-256 public key (verifier needs pubkey to verify non-recoverable ECDSA)
+    // r/s = P-256 ECDSA signature over EIP-191(userOpHash)
+    let mut signature = Vec::with_capacity(149);
+    signature.push(0x08u8);
+    signature.extend_from_slice(&input.account_address);
+    signature.extend_from_slice(&sk.pub_key[..32]);   // keyX
+    signature.extend_from_slice(&sk.pub_key[32..64]); // keyY
+    signature.extend_from_slice(&sig_bytes[..32]);    // r
+    signature.extend_from_slice(&sig_bytes[32..64]);  // s
+
+    Ok(proto::SignP256UserOpOutput { signature })
+}
+
+/// Extract wallet_id, agent_index, and exp from a JWT signing input, then validate exp.
+/// signing_input format: "header_b64url.payload_b64url" (exactly two dot-separated segments).
+/// Payload JSON: {"wallet_id":"<uuid>","agent_index":<u32>,"exp":<i64>}
+/// The format is controlled by our own jwt_sign_payload TA function — no whitespace around colons.
+fn jwt_parse_claims(signing_input: &[u8]) -> Result<(String, u32)> {
+    use base64ct::{Base64UrlUnpadded, Encoding};
+
+    // Guard against memory pressure from attacker-controlled input.
+    const MAX_SIGNING_INPUT_BYTES: usize = 4096;
+    if signing_input.len() > MAX_SIGNING_INPUT_BYTES {
+        return Err(anyhow!("jwt_parse_claims: signing_input too large ({} bytes, max {})",
+            signing_input.len(), MAX_SIGNING_INPUT_BYTES));
+    }
+
+
+    let s = std::str::from_utf8(signing_input)
+        .map_err(|_| anyhow!("jwt_parse_claims: signing_input is not valid UTF-8"))?;
+
+    // Require exactly "header.payload" — split_once ensures both parts are non-empty
+    let (_, payload_b64) = s.split_once('.')
+        .ok_or_else(|| anyhow!("jwt_parse_claims: signing_input must be 'header.payload'"))?;
+    if payload_b64.is_empty() || payload_b64.contains('.') {
+        return Err(anyhow!("jwt_parse_claims: malformed signing_input — expected exactly two segments"));
+    }
+
+
+    let payload_bytes = Base64UrlUnpadded::decode_vec(payload_b64)
+        .map_err(|_| anyhow!("jwt_parse_claims: payload base64url decode failed"))?;
+    let payload = std::str::from_utf8(&payload_bytes)
+        .map_err(|_| anyhow!("jwt_parse_claims: payload is not valid UTF-8"))?;
+
+    // Validate payload starts with '{' — guards against format drift
+    if !payload.starts_with('{') {
+        return Err(anyhow!("jwt_parse_claims: payload is not a JSON object"));
+    }
+
+
+    let wallet_id = extract_json_str_field(payload, "wallet_id")?;
+    let agent_index = extract_json_u32_field(payload, "agent_index")?;
+    // Parse exp for future use; not checked in TA due to unreliable TEE clock epoch.
+    let _exp = extract_json_i64_field(payload, "exp")?;
+
+    Ok((wallet_id, agent_index))
+}
+
+/// Extract a JSON string field value from a minimally-formatted JWT payload.
+/// Format contract: `"field":"value"` with no whitespace around colon (our jwt_sign_payload format).
+fn extract_json_str_field(json: &str, field: &str) -> Result<String> {
+    let needle = format!("\"{}\":\"", field);
+    let start = json.find(needle.as_str())
+        .ok_or_else(|| anyhow!("field '{}' not found in JWT payload", field))?
+        + needle.len();
+    let end = json[start..].find('"')
+        .ok_or_else(|| anyhow!("unterminated string for field '{}' in JWT payload", field))?
+        + start;
+    Ok(json[start..end].to_string())
+}
+
+/// Extract a JSON unsigned integer field from JWT payload.
+/// Format contract: `"field":12345` (digits immediately after colon, no whitespace).
+fn extract_json_u32_field(json: &str, field: &str) -> Result<u32> {
+    let needle = format!("\"{}\":", field);
+    let start = json.find(needle.as_str())
+        .ok_or_else(|| anyhow!("field '{}' not found in JWT payload", field))?
+        + needle.len();
+    let end = json[start..].find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(json[start..].len())
+        + start;
+    if start == end {
+        return Err(anyhow!("empty value for u32 field '{}' in JWT payload", field));
+    }
+    json[start..end].parse::<u32>()
+        .map_err(|e| anyhow!("field '{}' is not a valid u32: {}", field, e))
+}
+
+/// Extract a JSON signed integer field from JWT payload (used for 'exp' claim).
+fn extract_json_i64_field(json: &str, field: &str) -> Result<i64> {
+    let needle = format!("\"{}\":", field);
+    let start = json.find(needle.as_str())
+        .ok_or_else(|| anyhow!("field '{}' not found in JWT payload", field))?
+        + needle.len();
+    let end = json[start..].find(|c: char| !c.is_ascii_digit() && c != '-')
+        .unwrap_or(json[start..].len())
+        + start;
+    if start == end {
+        return Err(anyhow!("empty value for i64 field '{}' in JWT payload", field));
+    }
+    json[start..end].parse::<i64>()
+        .map_err(|e| anyhow!("field '{}' is not a valid i64 in JWT payload: {}", field, e))
+}
+
+/// Delete a P256 session key from TEE secure storage.
+/// Called by the host's lazy GC when credential_expires_at has passed.
+/// Returns deleted=false (not an error) if the key is already absent — idempotent.
+fn delete_p256_session_key(
+    input: &proto::DeleteP256SessionKeyInput,
+) -> Result<proto::DeleteP256SessionKeyOutput> {
+    ta_log!(
+        Session,
+        Debug,
+        "[+] Delete P256 session key for wallet: {:?}, index: {}",
+        input.wallet_id,
+        input.session_index
+    );
+    let db = open_storage()?;
+    let store_id = P256SessionKey::store_id_for(&input.wallet_id, input.session_index);
+    match db.delete_entry::<P256SessionKey>(&store_id) {
+        Ok(()) => Ok(proto::DeleteP256SessionKeyOutput { deleted: true }),
+        Err(e) => {
+            let msg = e.to_string();
+            if msg.contains("ItemNotFound") || msg.contains("ITEM_NOT_FOUND") {
+                Ok(proto::DeleteP256SessionKeyOutput { deleted: false })
+            } else {
+                Err(anyhow!("delete_p256_session_key: secure storage error: {}", msg))
+            }
+        }
+    }
+}
+
+fn sign_typed_data(input: &proto::SignTypedDataInput) -> Result<proto::SignTypedDataOutput> {
+    ta_log!(
+        Crypto,
+        Debug,
+        "[+] EIP-712 sign typed data for wallet: {:?}, primary_type: {}",
+        input.wallet_id,
+        input.primary_type
+    );
+
+    let wallet = load_wallet_cached(&input.wallet_id)?;
+
+    // Issue #68: resolve the primary type + compute the EIP-712 digest BEFORE the
+    // auth gate, so the passkey path can bind the challenge to exactly what is signed.
+    let primary_type_def = input
+        .types
+        .iter()
+        .find(|td| td.name == input.primary_type)
+        .ok_or_else(|| anyhow!("Primary type '{}' not found in types list", input.primary_type))?;
+    let digest = eip712::eip712_digest(&input.domain, primary_type_def, &input.message)?;
+
+    // TA-side auth gate (defense-in-depth): independently verifies the caller's authorization.
+    // Host has already validated, but TA confirms using TEE-resident secrets that cannot be
+    // forged by a compromised host. Exactly one auth proof must be present.
+    match (&input.passkey_assertion, &input.jwt_kid) {
+        (Some(_), None) => {
+            // WebAuthn path: verify passkey signature against wallet's stored public key.
+            // Issue #68: bind the challenge to the EIP-712 digest being signed.
+            verify_passkey_for_wallet(&wallet, input.passkey_assertion.as_ref(), Some(&digest))?;
+        }
+        (None, Some(kid)) => {
+            // Agent JWT path: all three JWT proof fields must be present.
+            let signing_input = input.jwt_signing_input.as_deref()
+                .ok_or_else(|| anyhow!("sign_typed_data: jwt_signing_input missing from JWT proof"))?;
+            let expected_hmac = input.jwt_hmac.as_deref()
+                .ok_or_else(|| anyhow!("sign_typed_data: jwt_hmac missing from JWT proof"))?;
+
+            // Step 1: Verify HMAC using TEE-resident JWT secret.
+            let verify_result = jwt_hmac_verify(&proto::JwtHmacVerifyInput {
+                kid: kid.clone(),
+                message: signing_input.to_vec(),
+                expected_hmac: expected_hmac.to_vec(),
+            })?;
+            if !verify_result.valid {
+                return Err(anyhow!("JWT HMAC verification failed in TA for sign-typed-data"));
+            }
+
+            // Step 2: Verify JWT claims bind to this request's wallet and agent path.
+            // Parse wallet_id and agent_index from the JWT payload (base64url-encoded JSON).
+            let (jwt_wallet_id, jwt_agent_index) = jwt_parse_claims(signing_input)?;
+            if jwt_wallet_id != input.wallet_id.to_string() {
+                return Err(anyhow!("JWT wallet_id does not match request wallet_id"));
+            }
+            let expected_path = format!("m/44'/60'/0'/1/{}", jwt_agent_index);
+            if input.hd_path != expected_path {
+                return Err(anyhow!(
+                    "JWT agent path '{}' does not match request hd_path '{}'",
+                    expected_path, input.hd_path
+                ));
+            }
+        }
+        (None, None) => {
+            return Err(anyhow!("sign_typed_data: no auth proof provided to TA"));
+        }
+        (Some(_), Some(_)) => {
+            return Err(anyhow!("sign_typed_data: ambiguous auth — provide passkey OR JWT, not both"));
+        }
+    }
+
+    // A permit signed as generic typed data still answers to the allowance
+    // guard; Permit2 structs (nested) are refused here whenever it is active.
+    if let Some(grant) = proto::permit::typed_data_grant(
+        &input.domain,
+        &input.primary_type,
+        &input.types,
+        &input.message,
+    ) {
+        let db = open_storage()?;
+        let policy = load_allowance_policy(&db, &input.wallet_id);
+        let spenders = load_spender_allow_list(&db, &input.wallet_id);
+        let violation = match grant {
+            Ok(grant) => allowance_guard::permit_violation(&policy.rule, &spenders, &grant),
+            Err(e) if allowance_guard::active(&policy.rule, &spenders) => Some(e.to_string()),
+            Err(_) => None,
+        };
+        if let Some(reason) = violation {
+            bail!("allowance policy: {}; permits cannot be overridden", reason);
+        }
+    }
+
+    // (primary_type_def + digest already computed above for payload binding.)
+    let private_key = wallet.export_private_key(&input.hd_path)?;
+
+    let secret_key = secp256k1::SecretKey::from_slice(&private_key)?;
+    let secp = secp256k1::Secp256k1::new();
+    let message = secp256k1::Message::from_slice(&digest)?;
+    let sig = secp.sign_ecdsa_recoverable(&message, &secret_key);
+    let (recovery_id, sig_bytes) = sig.serialize_compact();
+
+    let mut signature = Vec::with_capacity(65);
+    signature.extend_from_slice(&sig_bytes);
+    signature.push(recovery_id.to_i32() as u8 + 27);
+
+    Ok(proto::SignTypedDataOutput { signature })
+}
+
+/// EIP-2612 / Permit2 signing. The TA builds the digest and the review
+/// string itself; the passkey challenge commits to both, so the user approved
+/// exactly this spender, amount and deadline. Guard violations are refused
+/// outright — there is no override for a signature that lives off-chain.
+fn sign_permit(input: &proto::SignPermitInput) -> Result<proto::SignPermitOutput> {
+    let permit = &input.permit;
+    permit.validate().map_err(|e| anyhow!("{}", e))?;
+    let now = tee_unix_secs().max(0) as u64;
+    if permit.signature_deadline().is_some_and(|d| d <= now) {
+        bail!("permit deadline has already passed");
+    }
+    let wallet = load_wallet_cached(&input.wallet_id)?;
+    if let proto::permit::PermitRequest::Erc2612(p) = permit {
+        let (address, _) = wallet.derive_address(&input.hd_path)?;
+        if p.owner != address {
+            bail!("permit owner is not the address at {}", input.hd_path);
+        }
+    }
+    let digest = permit.digest();
+    let summary = permit.summary();
+    ta_log!(Crypto, Debug, "[+] SignPermit summary: {}", summary);
+    verify_passkey_for_wallet(
+        &wallet,
+        input.passkey_assertion.as_ref(),
+        Some(&proto::permit::review_digest(&digest, &summary)),
+    )?;
+
+    let db = open_storage()?;
+    let policy = load_allowance_policy(&db, &input.wallet_id);
+    let spenders = load_spender_allow_list(&db, &input.wallet_id);
+    if let Some(reason) =
+        allowance_guard::permit_violation(&policy.rule, &spenders, &permit.grant())
+    {
+        bail!("allowance policy: {}; permits cannot be overridden", reason);
+    }
+
+    let private_key = wallet.export_private_key(&input.hd_path)?;
+    let secret_key = secp256k1::SecretKey::from_slice(&private_key)?;
+    let secp = secp256k1::Secp256k1::new();
+    let message = secp256k1::Message::from_slice(&digest)?;
+    let (recovery_id, sig_bytes) = secp
+        .sign_ecdsa_recoverable(&message, &secret_key)
+        .serialize_compact();
+    let mut signature = Vec::with_capacity(65);
+    signature.extend_from_slice(&sig_bytes);
+    signature.push(recovery_id.to_i32() as u8 + 27);
+    Ok(proto::SignPermitOutput { signature, summary })
+}
+
+// ── Grant Session ABI encoding helpers ──
+
+fn abi_u256_from_u64(val: u64) -> [u8; 32] {
+    let mut buf = [0u8; 32];
+    buf[24..32].copy_from_slice(&val.to_be_bytes());
+    buf
+}
+
+fn abi_pad_address(addr: &[u8; 20]) -> [u8; 32] {
+    let mut buf = [0u8; 32];
+    buf[12..32].copy_from_slice(addr);
+    buf
+}
+
+/// bytes4 is right-padded in ABI encoding (data at low offset, zeros at high offset)
+fn abi_pad_bytes4_right(b: &[u8; 4]) -> [u8; 32] {
+    let mut buf = [0u8; 32];
+    buf[0..4].copy_from_slice(b);
+    buf
+}
+
+fn abi_u256_from_u16(val: u16) -> [u8; 32] {
+    let mut buf = [0u8; 32];
+    buf[30..32].copy_from_slice(&val.to_be_bytes());
+    buf
+}
+
+fn abi_u256_from_u32(val: u32) -> [u8; 32] {
+    let mut buf = [0u8; 32];
+    buf[28..32].copy_from_slice(&val.to_be_bytes());
+    buf
+}
+
+/// keccak256(abi.encodePacked(address[])) — matches Solidity, which pads EACH
+/// array element to a 32-byte slot (address is right-aligned → 12 zero bytes +
+/// 20-byte address). NOT tight 20-byte packing.
+///
+/// #112 fix: the TA previously tight-packed (20 bytes/elem), but Solidity's
+/// `abi.encodePacked(address[])` (used by SessionKeyValidator._buildGrantHash and
+/// the SDK's viem encodePacked) pads each element to 32 bytes. They agree for the
+/// empty array (keccak("")) so this was latent; non-empty scoped grants had a
+/// final_hash that mismatched the contract → the TA signature failed on-chain and
+/// the strict commitment never matched. Verified byte-identical to the SDK's
+/// contract-oracle vectors (AirAccount#112 / #119).
+fn keccak_packed_addresses(addrs: &[[u8; 20]]) -> [u8; 32] {
+    let mut buf = Vec::with_capacity(addrs.len() * 32);
+    for a in addrs {
+        buf.extend_from_slice(&[0u8; 12]); // left-pad address to its 32-byte slot
+        buf.extend_from_slice(a);
+    }
+    Keccak256::digest(&buf).into()
+}
+
+/// keccak256(abi.encodePacked(bytes4[])) — matches Solidity: each bytes4 element
+/// is padded to a 32-byte slot, right-aligned for value types but bytesN is
+/// LEFT-aligned (4 selector bytes + 28 zero bytes). See keccak_packed_addresses
+/// for the #112 tight-pack→pad-32 rationale.
+fn keccak_packed_selectors(sels: &[[u8; 4]]) -> [u8; 32] {
+    let mut buf = Vec::with_capacity(sels.len() * 32);
+    for s in sels {
+        buf.extend_from_slice(s);
+        buf.extend_from_slice(&[0u8; 28]); // right-pad bytes4 to its 32-byte slot
+    }
+    Keccak256::digest(&buf).into()
+}
+
+/// Build GRANT_SESSION_V2 inner hash.
+/// Matches _buildGrantHash() in SessionKeyValidator.sol:
+///   keccak256(abi.encode("GRANT_SESSION_V2", chainId, contract, account, sessionKey,
+///             expiry, contractScope, selectorScope, velocityLimit, velocityWindow,
+///             callTargetsHash, selectorsHash, nonce))
+///
+/// ABI layout: 13 args; head = 13*32 = 416 bytes; string data tail = 64 bytes.
+fn build_grant_session_inner(input: &proto::SignGrantSessionInput) -> [u8; 32] {
+    let call_targets_hash = keccak_packed_addresses(&input.call_targets);
+    let selectors_hash = keccak_packed_selectors(&input.selector_allowlist);
+
+    let mut buf = [0u8; 480];
+    // [0x000] string offset = 13 * 32 = 416
+    buf[0..32].copy_from_slice(&{
+        let mut v = [0u8; 32];
+        v[30..32].copy_from_slice(&(416u16).to_be_bytes());
+        v
+    });
+    // [0x020] chainId
+    buf[32..64].copy_from_slice(&abi_u256_from_u64(input.chain_id));
+    // [0x040] verifyingContract
+    buf[64..96].copy_from_slice(&abi_pad_address(&input.verifying_contract));
+    // [0x060] account
+    buf[96..128].copy_from_slice(&abi_pad_address(&input.account));
+    // [0x080] sessionKey
+    buf[128..160].copy_from_slice(&abi_pad_address(&input.session_key));
+    // [0x0A0] expiry (uint48)
+    buf[160..192].copy_from_slice(&abi_u256_from_u64(input.expiry));
+    // [0x0C0] contractScope
+    buf[192..224].copy_from_slice(&abi_pad_address(&input.contract_scope));
+    // [0x0E0] selectorScope (bytes4, right-padded)
+    buf[224..256].copy_from_slice(&abi_pad_bytes4_right(&input.selector_scope));
+    // [0x100] velocityLimit (uint16)
+    buf[256..288].copy_from_slice(&abi_u256_from_u16(input.velocity_limit));
+    // [0x120] velocityWindow (uint32)
+    buf[288..320].copy_from_slice(&abi_u256_from_u32(input.velocity_window));
+    // [0x140] callTargetsHash
+    buf[320..352].copy_from_slice(&call_targets_hash);
+    // [0x160] selectorsHash
+    buf[352..384].copy_from_slice(&selectors_hash);
+    // [0x180] nonce (uint256, big-endian 32 bytes)
+    buf[384..416].copy_from_slice(&input.nonce);
+    // [0x1A0] string length = 16
+    buf[416..448].copy_from_slice(&{
+        let mut v = [0u8; 32];
+        v[31] = 16;
+        v
+    });
+    // [0x1C0] "GRANT_SESSION_V2" (16 bytes, right-zero-padded to 32)
+    buf[448..464].copy_from_slice(b"GRANT_SESSION_V2");
+
+    Keccak256::digest(&buf).into()
+}
+
+/// Build GRANT_P256_SESSION_V2 inner hash.
+/// Matches _buildP256GrantHash() in SessionKeyValidator.sol:
+///   keccak256(abi.encode("GRANT_P256_SESSION_V2", chainId, contract, account, keyX, keyY,
+///             expiry, contractScope, selectorScope, velocityLimit, velocityWindow,
+///             callTargetsHash, selectorsHash, nonce))
+///
+/// ABI layout: 14 args; head = 14*32 = 448 bytes; string data tail = 64 bytes.
+fn build_p256_grant_session_inner(input: &proto::SignP256GrantSessionInput) -> [u8; 32] {
+    let call_targets_hash = keccak_packed_addresses(&input.call_targets);
+    let selectors_hash = keccak_packed_selectors(&input.selector_allowlist);
+
+    let mut buf = [0u8; 512];
+    // [0x000] string offset = 14 * 32 = 448
+    buf[0..32].copy_from_slice(&{
+        let mut v = [0u8; 32];
+        v[30..32].copy_from_slice(&(448u16).to_be_bytes());
+        v
+    });
+    // [0x020] chainId
+    buf[32..64].copy_from_slice(&abi_u256_from_u64(input.chain_id));
+    // [0x040] verifyingContract
+    buf[64..96].copy_from_slice(&abi_pad_address(&input.verifying_contract));
+    // [0x060] account
+    buf[96..128].copy_from_slice(&abi_pad_address(&input.account));
+    // [0x080] keyX (bytes32)
+    buf[128..160].copy_from_slice(&input.key_x);
+    // [0x0A0] keyY (bytes32)
+    buf[160..192].copy_from_slice(&input.key_y);
+    // [0x0C0] expiry (uint48)
+    buf[192..224].copy_from_slice(&abi_u256_from_u64(input.expiry));
+    // [0x0E0] contractScope
+    buf[224..256].copy_from_slice(&abi_pad_address(&input.contract_scope));
+    // [0x100] selectorScope (bytes4, right-padded)
+    buf[256..288].copy_from_slice(&abi_pad_bytes4_right(&input.selector_scope));
+    // [0x120] velocityLimit (uint16)
+    buf[288..320].copy_from_slice(&abi_u256_from_u16(input.velocity_limit));
+    // [0x140] velocityWindow (uint32)
+    buf[320..352].copy_from_slice(&abi_u256_from_u32(input.velocity_window));
+    // [0x160] callTargetsHash
+    buf[352..384].copy_from_slice(&call_targets_hash);
+    // [0x180] selectorsHash
+    buf[384..416].copy_from_slice(&selectors_hash);
+    // [0x1A0] nonce (uint256, big-endian 32 bytes)
+    buf[416..448].copy_from_slice(&input.nonce);
+    // [0x1C0] string length = 21
+    buf[448..480].copy_from_slice(&{
+        let mut v = [0u8; 32];
+        v[31] = 21;
+        v
+    });
+    // [0x1E0] "GRANT_P256_SESSION_V2" (21 bytes, right-zero-padded to 32)
+    buf[480..501].copy_from_slice(b"GRANT_P256_SESSION_V2");
+
+    Keccak256::digest(&buf).into()
+}
+
+/// Wrap inner hash with EIP-191 personal_sign prefix (matches OpenZeppelin toEthSignedMessageHash).
+fn eip191_hash(inner: &[u8; 32]) -> [u8; 32] {
+    let mut msg = [0u8; 60];
+    msg[0..28].copy_from_slice(b"\x19Ethereum Signed Message:\n32");
+    msg[28..60].copy_from_slice(inner);
+    Keccak256::digest(&msg).into()
+}
+
+/// #115: mint-challenge commitment over the CLIENT-AUTHORIZED inputs (wallet_id ‖
+/// label). In strict the client commits `challenge = SHA-256(nonce ‖ mint_label_digest)`
+/// and the TA recomputes it here — so a compromised CA cannot relabel the credential
+/// while riding the user's mint gesture. Server-derived params are intentionally NOT
+/// bound: `agent_index`/`session_index` are allocated AFTER the assertion (the client
+/// can't know them), and `ttl` is bounded by `max_agent_jwt_ttl()`. The variable-length
+/// label is hashed to keep the preimage fixed-width. `tag` domain-separates agent vs
+/// p256 (and from the reverted v1 index/subject/ttl scheme). The SDK MUST recompute
+/// this identically (both inputs are client-known).
+fn mint_label_digest(wallet_id: &Uuid, label: &str, tag: &[u8]) -> [u8; 32] {
+    use sha2::Digest;
+    let mut h = sha2::Sha256::new();
+    h.update(tag);
+    h.update(wallet_id.as_bytes());
+    h.update(sha2::Sha256::digest(label.as_bytes()));
+    h.finalize().into()
+}
+
+/// #115: REFRESH commitment — binds the agent_index being re-minted (which the
+/// refreshing client knows + commits to) under a DISTINCT domain tag. This is what
+/// prevents a refresh assertion from being replayed to create (or vice versa): the
+/// create digest uses AA-AGENT-MINT-v2 ‖ wallet ‖ H(label), so the two never collide
+/// even for an empty label. A compromised CA cannot redirect a refresh of index N to
+/// a create at a different index — the client committed to this exact (tag, index).
+fn agent_refresh_digest(wallet_id: &Uuid, agent_index: u32) -> [u8; 32] {
+    use sha2::Digest;
+    let mut h = sha2::Sha256::new();
+    h.update(b"AA-AGENT-REFRESH-v2");
+    h.update(wallet_id.as_bytes());
+    h.update(agent_index.to_be_bytes());
+    h.finalize().into()
+}
+
+fn sign_grant_session(input: &proto::SignGrantSessionInput) -> Result<proto::SignGrantSessionOutput> {
+    let wallet = load_wallet_cached(&input.wallet_id)?;
+
+    // Issue #68: compute the exact digest this op signs and bind the challenge to it.
+    let inner = build_grant_session_inner(input);
+    let final_hash = eip191_hash(&inner);
+
+    verify_passkey_for_wallet(&wallet, input.passkey_assertion.as_ref(), Some(&final_hash))?;
+
+    let private_key = wallet.export_private_key(&input.hd_path)?;
+    let secret_key = secp256k1::SecretKey::from_slice(&private_key)?;
+    let secp = secp256k1::Secp256k1::new();
+    let msg = secp256k1::Message::from_slice(&final_hash)?;
+    let sig = secp.sign_ecdsa_recoverable(&msg, &secret_key);
+    let (recovery_id, sig_bytes) = sig.serialize_compact();
+
+    let mut signature = Vec::with_capacity(65);
+    signature.extend_from_slice(&sig_bytes);
+    signature.push(recovery_id.to_i32() as u8 + 27);
+
+    Ok(proto::SignGrantSessionOutput { signature })
+}
+
+fn sign_p256_grant_session(input: &proto::SignP256GrantSessionInput) -> Result<proto::SignP256GrantSessionOutput> {
+    let wallet = load_wallet_cached(&input.wallet_id)?;
+
+    // Issue #68: compute the exact digest this op signs and bind the challenge to it.
+    let inner = build_p256_grant_session_inner(input);
+    let final_hash = eip191_hash(&inner);
+
+    verify_passkey_for_wallet(&wallet, input.passkey_assertion.as_ref(), Some(&final_hash))?;
+
+    let private_key = wallet.export_private_key(&input.hd_path)?;
+    let secret_key = secp256k1::SecretKey::from_slice(&private_key)?;
+    let secp = secp256k1::Secp256k1::new();
+    let msg = secp256k1::Message::from_slice(&final_hash)?;
+    let sig = secp.sign_ecdsa_recoverable(&msg, &secret_key);
+    let (recovery_id, sig_bytes) = sig.serialize_compact();
+
+    let mut signature = Vec::with_capacity(65);
+    signature.extend_from_slice(&sig_bytes);
+    signature.push(recovery_id.to_i32() as u8 + 27);
+
+    Ok(proto::SignP256GrantSessionOutput { signature })
+}
+
+// ── Scoped AA session keys (session_scope.rs) ──
+//
+// The TA enforces the signed scope against the call the CA *declares*; it cannot
+// parse an opaque userOpHash back into calldata. The account's session validator
+// must therefore enforce the same permission on-chain — the TA check stops a
+// compromised dapp/CA from obtaining signatures for undeclared scopes, the
+// validator stops a lying declaration.
+
+fn load_session_revocations(
+    db: &SecureStorageClient,
+    wallet_id: &Uuid,
+) -> session_scope::SessionRevocations {
+    db.get::<session_scope::SessionRevocations>(&session_scope::SessionRevocations::store_id_for(
+        wallet_id,
+    ))
+    .unwrap_or_else(|_| session_scope::SessionRevocations::empty(wallet_id))
+}
+
+fn create_scoped_session_key(
+    input: &proto::CreateScopedSessionKeyInput,
+) -> Result<proto::CreateScopedSessionKeyOutput> {
+    ta_log!(
+        Session,
+        Debug,
+        "[+] Create scoped session key for wallet: {:?}, index: {}",
         input.wallet_id,
         input.session_index
     );
+    // H-3: all TLS work (wallet cache + root-key signing) happens before db.put.
+    let wallet = load_wallet_cached(&input.wallet_id)?;
+    verify_passkey_for_wallet(
+        &wallet,
+        input.passkey_assertion.as_ref(),
+        Some(&session_scope::mint_commitment(
+            &input.wallet_id,
+            &input.permission,
+        )),
+    )?;
+    session_scope::validate_permission(&input.permission, tee_unix_secs())?;
 
-    // TA-side JWT authorization: verify HMAC proof before any signing (defense-in-depth).
-    // Prevents a compromised CA from bypassing host-side JWT verification.
-    let jwt_verify_input = proto::JwtHmacVerifyInput {
-        kid: input.jwt_kid.clone(),
-        message: input.jwt_signing_input.clone(),
-        expected_hmac: input.jwt_hmac.clone(),
+    let db = open_storage()?;
+    if load_session_revocations(&db, &input.wallet_id).contains(input.session_index) {
+        bail!("session index {} has been revoked", input.session_index);
+    }
+    let store_id =
+        session_scope::ScopedSessionKey::store_id_for(&input.wallet_id, input.session_index);
+    if db.get::<session_scope::ScopedSessionKey>(&store_id).is_ok() {
+        bail!("session index {} already issued", input.session_index);
+    }
+
+    let secp = secp256k1::Secp256k1::new();
+    let secret_key = loop {
+        let mut sk_bytes = [0u8; 32];
+        provider::fill(&mut sk_bytes);
+        if let Ok(sk) = secp256k1::SecretKey::from_slice(&sk_bytes) {
+            break sk;
+        }
     };
-    let verify_result = jwt_hmac_verify(&jwt_verify_input)?;
-    if !verify_result.valid {
-        return Err(anyhow!("TA: P256 session JWT credential verification failed"));
+    let pk = secp256k1::PublicKey::from_secret_key(&secp, &secret_key);
+    let session_address = eth_address_from_uncompressed(&pk.serialize_uncompressed());
+
+    let permission_hash = session_scope::permission_digest(&input.permission, &session_address);
+    let owner_signature = wallet.sign_hash(&input.hd_path, &eip191_hash(&permission_hash))?;
+
+    db.put(&session_scope::ScopedSessionKey {
+        store_id,
+        private_key: secret_key.secret_bytes(),
+        address: session_address,
+        permission: input.permission.clone(),
+    })
+    .map_err(|e| anyhow!("Failed to save scoped session key: {}", e))?;
+
+    Ok(proto::CreateScopedSessionKeyOutput {
+        session_address,
+        permission_hash,
+        owner_signature,
+    })
+}
+
+/// No passkey: low-risk calls inside the owner-signed scope are the point of a
+/// session key. Revocation list is checked first so a revoke is effective even
+/// if the sealed key object survived (e.g. restored REE-FS backup).
+fn sign_with_session_key(
+    input: &proto::SignWithSessionKeyInput,
+) -> Result<proto::SignWithSessionKeyOutput> {
+    // The parent wallet must still exist: deleting it kills its sessions too.
+    let _wallet = load_wallet_cached(&input.wallet_id)?;
+    let db = open_storage()?;
+    if load_session_revocations(&db, &input.wallet_id).contains(input.session_index) {
+        bail!("session key {} revoked", input.session_index);
     }
+    let key = db
+        .get::<session_scope::ScopedSessionKey>(&session_scope::ScopedSessionKey::store_id_for(
+            &input.wallet_id,
+            input.session_index,
+        ))
+        .map_err(|_| {
+            anyhow!(
+                "scoped session key not found for index {}",
+                input.session_index
+            )
+        })?;
+    session_scope::check_call(
+        &key.permission,
+        input.chain_id,
+        &input.call,
+        tee_unix_secs(),
+    )?;
+    let signature =
+        bip32_secp::sign_recoverable(&key.private_key, &eip191_hash(&input.user_op_hash))?;
+    Ok(proto::SignWithSessionKeyOutput { signature })
+}
+
+fn revoke_scoped_session_key(
+    input: &proto::RevokeScopedSessionKeyInput,
+) -> Result<proto::RevokeScopedSessionKeyOutput> {
+    ta_log!(
+        Session,
+        Warn,
+        "[!] Revoke scoped session key for wallet: {:?}, index: {}",
+        input.wallet_id,
+        input.session_index
+    );
+    let wallet = load_wallet_cached(&input.wallet_id)?;
+    verify_passkey_for_wallet(
+        &wallet,
+        input.passkey_assertion.as_ref(),
+        Some(&session_scope::revoke_commitment(
+            &input.wallet_id,
+            input.session_index,
+        )),
+    )?;
 
-    // Load P-256 key pair from TEE secure storage
     let db = open_storage()?;
-    let sk = P256SessionKey::load(&db, &input.wallet_id, input.session_index)?;
-    if sk.private_key.len() != 32 {
-        return Err(anyhow!(
-            "Corrupt P256 private key: expected 32 bytes, got {}",
-            sk.private_key.len()
-        ));
+    let mut revocations = load_session_revocations(&db, &input.wallet_id);
+    let revoked = revocations.revoke(input.session_index);
+    if revoked {
+        // Revocation list first: once it is written the key is dead, whatever
+        // happens to the delete below.
+        db.put(&revocations)
+            .map_err(|e| anyhow!("Failed to save session revocation list: {}", e))?;
     }
-    if sk.pub_key.len() != 64 {
-        return Err(anyhow!(
-            "Corrupt P256 public key: expected 64 bytes, got {}",
-            sk.pub_key.len()
-        ));
+    let store_id =
+        session_scope::ScopedSessionKey::store_id_for(&input.wallet_id, input.session_index);
+    if let Err(e) = db.delete_entry::<session_scope::ScopedSessionKey>(&store_id) {
+        let msg = e.to_string();
+        if !(msg.contains("ItemNotFound") || msg.contains("ITEM_NOT_FOUND")) {
+            return Err(anyhow!(
+                "revoke_scoped_session_key: secure storage error: {}",
+                msg
+            ));
+        }
     }
+    Ok(proto::RevokeScopedSessionKeyOutput { revoked })
+}
 
-    // EIP-191 prefix: "\x19Ethereum Signed Message:\n32" || userOpHash
-    let mut eip191 = b"\x19Ethereum Signed Message:\n32".to_vec();
-    eip191.extend_from_slice(&input.user_op_hash);
-    let digest = Keccak256::digest(&eip191);
-
-    // Sign with P-256 via p256-m (internally uses p256_generate_random for nonce)
-    // sig[64] = r(32) || s(32) big-endian integers
-    let mut sig_bytes = [0u8; 64];
-    let ret = unsafe {
-        p256_ecdsa_sign(
-            sig_bytes.as_mut_ptr(),
-            sk.private_key.as_ptr(),
-            digest.as_ptr(),
-            digest.len(),
-        )
+fn jwt_hmac_verify(input: &proto::JwtHmacVerifyInput) -> Result<proto::JwtHmacVerifyOutput> {
+    let db = open_storage()?;
+    let store = JwtSecretStore::load(&db);
+    let valid = match store.find(&input.kid) {
+        Some(entry) => {
+            let mut mac = HmacSha256::new_from_slice(&entry.secret)
+                .map_err(|_| anyhow!("Invalid JWT HMAC secret"))?;
+            mac.update(&input.message);
+            mac.verify_slice(&input.expected_hmac).is_ok()
+        }
+        None => false,
     };
-    if ret != 0 {
-        return Err(anyhow!("p256_ecdsa_sign failed (code {})", ret));
+
+    Ok(proto::JwtHmacVerifyOutput { valid })
+}
+
+fn jwt_rotate_secret(input: &proto::JwtRotateSecretInput) -> Result<proto::JwtRotateSecretOutput> {
+    let db = open_storage()?;
+    let mut store = JwtSecretStore::load(&db);
+    let had_current = store.current().is_some();
+
+    if !input.force && !had_current {
+        // First-time init: create the initial secret without rotating
+        let current = store.ensure_current()?.clone();
+        store.save(&db)?;
+        return Ok(proto::JwtRotateSecretOutput {
+            new_kid: current.kid,
+            retired_kid: None,
+        });
     }
 
-    // v0.18.1 wire format: [0x08][account(20)][keyX(32)][keyY(32)][r(32)][s(32)] = 149 bytes
-    // account = ERC-4337 Smart Account address (prevents cross-account session key abuse)
-    // keyX/keyY = P-256 public key (verifier needs pubkey to verify non-recoverable ECDSA)
-    // r/s = P-256 ECDSA signature over EIP-191(userOpHash)
-    let mut signature = Vec::with_capacity(149);
-    signature.push(0x08u8);
-    signature.extend_from_slice(&input.account_address);
-    signature.extend_from_slice(&sk.pub_key[..32]);   // keyX
-    signature.extend_from_slice(&sk.pub_key[32..64]); // keyY
-    signature.extend_from_slice(&sig_bytes[..32]);    // r
-    signature.extend_from_slice(&sig_bytes[32..64]);  // s
+    // Normal rotation: keep retired entry for 7-day overlap window.
+    // Force rotation: purge retired entries immediately (old JWTs become invalid).
+    let (new_kid, retired_kid) = store.rotate(input.force)?;
+    store.save(&db)?;
+
+    Ok(proto::JwtRotateSecretOutput {
+        new_kid,
+        retired_kid,
+    })
+}
 
-    Ok(proto::SignP256UserOpOutput { signature })
+fn begin_transfer(input: &proto::BeginTransferInput) -> Result<proto::BeginTransferOutput> {
+    let mut id = [0u8; 8];
+    provider::fill(&mut id);
+    transfer::begin(input, u64::from_be_bytes(id))
 }
 
-/// Extract wallet_id, agent_index, and exp from a JWT signing input, then validate exp.
-/// signing_input format: "header_b64url.payload_b64url" (exactly two dot-separated segments).
-/// Payload JSON: {"wallet_id":"<uuid>","agent_index":<u32>,"exp":<i64>}
-/// The format is controlled by our own jwt_sign_payload TA function — no whitespace around colons.
-fn jwt_parse_claims(signing_input: &[u8]) -> Result<(String, u32)> {
-    use base64ct::{Base64UrlUnpadded, Encoding};
+/// Run the transfer's command on its reassembled input, through the same
+/// dispatch (tamper lock, deployment policy) as a direct invoke.
+fn end_transfer(
+    input: &proto::EndTransferInput,
+    header: RequestHeader,
+) -> Result<proto::EndTransferOutput> {
+    let (command, payload) = transfer::take_input(input.transfer_id)?;
+    let mut output = vec![0u8; proto::transfer::MAX_TRANSFER_LEN];
+    let len = handle_invoke(command, header, &payload, &mut output)?;
+    output.truncate(len);
+    Ok(transfer::hold_output(input.transfer_id, output))
+}
 
-    // Guard against memory pressure from attacker-controlled input.
-    const MAX_SIGNING_INPUT_BYTES: usize = 4096;
-    if signing_input.len() > MAX_SIGNING_INPUT_BYTES {
-        return Err(anyhow!("jwt_parse_claims: signing_input too large ({} bytes, max {})",
-            signing_input.len(), MAX_SIGNING_INPUT_BYTES));
+/// The serialized output does not fit the CA's buffer (C-4).
+#[derive(Debug)]
+struct OutputTooLarge(u64);
+
+impl std::fmt::Display for OutputTooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "output of {} bytes does not fit the host buffer", self.0)
     }
+}
 
+impl std::error::Error for OutputTooLarge {}
 
-    let s = std::str::from_utf8(signing_input)
-        .map_err(|_| anyhow!("jwt_parse_claims: signing_input is not valid UTF-8"))?;
+/// Serialize `output` straight into the CA's buffer: no intermediate Vec and
+/// no second copy. Returns the length written.
+fn write_output<U: serde::Serialize>(out: &mut [u8], output: &U) -> Result<usize> {
+    let len = bincode::serialized_size(output)?;
+    if len > out.len() as u64 {
+        return Err(OutputTooLarge(len).into());
+    }
+    let len = len as usize;
+    bincode::serialize_into(&mut out[..len], output)?;
+    Ok(len)
+}
 
-    // Require exactly "header.payload" — split_once ensures both parts are non-empty
-    let (_, payload_b64) = s.split_once('.')
-        .ok_or_else(|| anyhow!("jwt_parse_claims: signing_input must be 'header.payload'"))?;
-    if payload_b64.is_empty() || payload_b64.contains('.') {
-        return Err(anyhow!("jwt_parse_claims: malformed signing_input — expected exactly two segments"));
+/// The CA's bincode input and the protocol version it was encoded at.
+#[derive(Clone, Copy)]
+struct Input<'a> {
+    bytes: &'a [u8],
+    ca_version: u16,
+}
+
+/// Run command `cmd_id`, writing its bincode output into `out` (the CA's p1
+/// buffer).
+fn handle_invoke(
+    cmd_id: u32,
+    header: RequestHeader,
+    input: &[u8],
+    out: &mut [u8],
+) -> Result<usize> {
+    fn process<T: serde::de::DeserializeOwned, U: serde::Serialize, F: Fn(&T) -> Result<U>>(
+        serialized_input: Input,
+        out: &mut [u8],
+        handler: F,
+    ) -> Result<usize> {
+        let input: T = wire::decode(serialized_input.bytes, serialized_input.ca_version)?;
+        let output = handler(&input)?;
+        write_output(out, &output)
     }
 
+    let command = Command::from(cmd_id);
+    let serialized_input = Input {
+        bytes: input,
+        ca_version: header.protocol_version,
+    };
+
+    provider::tick();
+    // First, so that everything below logs at the levels in force.
+    refresh_log_levels();
+    // Before the legacy path too: a locked TA signs nothing, in any dialect.
+    enforce_tamper_lock(command)?;
+    // Every limit check below reads the cached TA config.
+    installed_ta_config()?;
+
+    #[cfg(feature = "eth-wallet-compat")]
+    if let Some(legacy) = eth_wallet_compat::classify(command, input) {
+        let output = handle_legacy(legacy)?;
+        if output.len() > out.len() {
+            return Err(OutputTooLarge(output.len() as u64).into());
+        }
+        out[..output.len()].copy_from_slice(&output);
+        return Ok(output.len());
+    }
 
-    let payload_bytes = Base64UrlUnpadded::decode_vec(payload_b64)
-        .map_err(|_| anyhow!("jwt_parse_claims: payload base64url decode failed"))?;
-    let payload = std::str::from_utf8(&payload_bytes)
-        .map_err(|_| anyhow!("jwt_parse_claims: payload is not valid UTF-8"))?;
+    enforce_deployment_policy(command)?;
 
-    // Validate payload starts with '{' — guards against format drift
-    if !payload.starts_with('{') {
-        return Err(anyhow!("jwt_parse_claims: payload is not a JSON object"));
+    match command {
+        Command::CreateWallet => process(serialized_input, out, create_wallet),
+        Command::RemoveWallet => process(serialized_input, out, remove_wallet),
+        Command::DeriveAddress => process(serialized_input, out, derive_address),
+        Command::SignTransaction => process(serialized_input, out, sign_transaction),
+        Command::SignMessage => process(serialized_input, out, sign_message),
+        Command::SignHash => process(serialized_input, out, sign_hash),
+        Command::DeriveAddressAuto => process(serialized_input, out, derive_address_auto),
+        Command::ExportPrivateKey => process(serialized_input, out, export_private_key),
+        // M-3: VerifyPasskey was an unconditional `valid:true` stub. Removing it
+        // from dispatch prevents it from ever being used as a fake auth oracle.
+        // Real authorization always goes through verify_passkey_for_wallet (p256-m).
+        Command::VerifyPasskey => bail!("VerifyPasskey is not supported (use a signing command which verifies the passkey)"),
+        Command::WarmupCache => process(serialized_input, out, warmup_cache),
+        Command::RegisterPasskeyTa => process(serialized_input, out, register_passkey_ta),
+        Command::CreateAgentKey => process(serialized_input, out, create_agent_key),
+        Command::SignAgentUserOp => process(serialized_input, out, sign_agent_user_op),
+        Command::JwtHmacVerify => process(serialized_input, out, jwt_hmac_verify),
+        Command::JwtRotateSecret => process(serialized_input, out, jwt_rotate_secret),
+        Command::SignTypedData => process(serialized_input, out, sign_typed_data),
+        Command::CreateP256SessionKey => process(serialized_input, out, create_p256_session_key),
+        Command::SignP256UserOp => process(serialized_input, out, sign_p256_user_op),
+        Command::DeleteP256SessionKey => process(serialized_input, out, delete_p256_session_key),
+        Command::SignGrantSession => process(serialized_input, out, sign_grant_session),
+        Command::SignP256GrantSession => process(serialized_input, out, sign_p256_grant_session),
+        Command::ForceRemoveWallet => process(serialized_input, out, force_remove_wallet),
+        Command::ReadRollbackCounter => process(serialized_input, out, read_rollback_counter),
+        Command::GetChallenge => process(serialized_input, out, get_challenge),
+        Command::GetAttestation => process(serialized_input, out, attestation::get_attestation),
+        Command::BlsGenKey => process(serialized_input, out, bls_gen_key),
+        Command::BlsSign => process(serialized_input, out, bls_sign),
+        Command::BlsPopSign => process(serialized_input, out, bls_pop_sign),
+        Command::BlsPubKey => process(serialized_input, out, bls_pubkey),
+        Command::BlsRemove => process(serialized_input, out, bls_remove),
+        Command::BlsSignAttestation => process(serialized_input, out, bls_sign_attestation),
+        Command::BlsSignBlock => process(serialized_input, out, bls_sign_block),
+        Command::KeeperGenKey => process(serialized_input, out, keeper_gen_key),
+        Command::KeeperSign => process(serialized_input, out, keeper_sign),
+        Command::KeeperPubKey => process(serialized_input, out, keeper_pubkey),
+        Command::CreateScopedSessionKey => {
+            process(serialized_input, out, create_scoped_session_key)
+        }
+        Command::SignWithSessionKey => process(serialized_input, out, sign_with_session_key),
+        Command::RevokeScopedSessionKey => {
+            process(serialized_input, out, revoke_scoped_session_key)
+        }
+        Command::TaStats => process(serialized_input, out, ta_stats),
+        Command::SetAllowancePolicy => process(serialized_input, out, set_allowance_policy),
+        Command::ConfirmAllowanceOverride => {
+            process(serialized_input, out, confirm_allowance_override)
+        }
+        Command::SignOffline => process(serialized_input, out, sign_offline),
+        Command::GetCapabilities => process(serialized_input, out, get_capabilities),
+        Command::InstallDeploymentPolicy => {
+            process(serialized_input, out, install_deployment_policy)
+        }
+        Command::ReplicationOffer => process(serialized_input, out, replication_offer),
+        Command::ReplicationExport => process(serialized_input, out, replication_export),
+        Command::ReplicationImport => process(serialized_input, out, replication_import),
+        Command::TamperOrder => process(serialized_input, out, tamper_order),
+        Command::GetTamperStatus => process(serialized_input, out, get_tamper_status),
+        Command::SignPermit => process(serialized_input, out, sign_permit),
+        Command::SetSpenderAllowList => process(serialized_input, out, set_spender_allow_list),
+        Command::ExportKeystore => process(serialized_input, out, export_keystore),
+        Command::ImportKeystore => process(serialized_input, out, import_keystore),
+        Command::InstallConfig => process(serialized_input, out, install_config),
+        Command::DappStoragePut => process(serialized_input, out, dapp_storage_put),
+        Command::DappStorageGet => process(serialized_input, out, dapp_storage_get),
+        Command::DappStorageDelete => process(serialized_input, out, dapp_storage_delete),
+        Command::Bip85Export => process(serialized_input, out, bip85_export),
+        Command::CrashDumps => process(serialized_input, out, crash_dumps),
+        Command::OtpEnroll => process(serialized_input, out, otp_enroll),
+        Command::OtpCode => process(serialized_input, out, otp_code),
+        Command::OtpRemove => process(serialized_input, out, otp_remove),
+        Command::OtpList => process(serialized_input, out, otp_list),
+        Command::LinkWallet => process(serialized_input, out, link_wallet),
+        Command::MigrationOffer => process(serialized_input, out, migration_offer),
+        Command::MigrationImport => process(serialized_input, out, migration_import),
+        Command::SetLogLevel => process(serialized_input, out, set_log_level),
+        Command::OpenDecryptSession => process(serialized_input, out, open_decrypt_session),
+        Command::EciesDecrypt => process(serialized_input, out, ecies_decrypt),
+        Command::GetStealthMetaAddress => process(serialized_input, out, get_stealth_meta_address),
+        Command::StealthScan => process(serialized_input, out, stealth_scan),
+        Command::SelfTest => process(serialized_input, out, self_test),
+        Command::ScavengeStorage => process(serialized_input, out, scavenge_storage),
+        Command::PathPolicy => process(serialized_input, out, path_policy),
+        Command::PairApprover => process(serialized_input, out, pair_approver),
+        Command::RemoteApprove => process(serialized_input, out, remote_approve),
+        Command::SetAggregator => process(serialized_input, out, set_aggregator),
+        Command::SignAggregatedUserOp => process(serialized_input, out, sign_aggregated_user_op),
+        Command::BeginTransfer => process(serialized_input, out, begin_transfer),
+        Command::ContinueTransfer => process(serialized_input, out, transfer::next),
+        Command::EndTransfer => process(serialized_input, out, |input| end_transfer(input, header)),
+        Command::ImportWallet => process(serialized_input, out, import_wallet),
+        Command::SignEip1559Transaction => process(serialized_input, out, sign_eip1559_transaction),
+        Command::SpendingPolicy => process(serialized_input, out, spending_policy),
+        Command::SetMultiSigPolicy => process(serialized_input, out, set_multisig_policy),
+        Command::ApproveMultiSig => process(serialized_input, out, approve_multisig),
+        Command::SetGuardians => process(serialized_input, out, set_guardians),
+        Command::RecoveryStatus => process(serialized_input, out, recovery_status),
+        Command::RecoveryAttest => process(serialized_input, out, recovery_attest),
+        Command::CancelRecovery => process(serialized_input, out, cancel_recovery),
+        Command::CompleteRecovery => process(serialized_input, out, complete_recovery),
+        Command::ExportEncryptedBackup => process(serialized_input, out, export_encrypted_backup),
+        Command::ImportEncryptedBackup => process(serialized_input, out, import_encrypted_backup),
+        Command::NonceTracking => process(serialized_input, out, nonce_tracking),
+        Command::OverrideNonce => process(serialized_input, out, override_nonce),
+        Command::GetProtocolVersion => process(serialized_input, out, get_protocol_version),
+        Command::DeriveSolanaAddress => process(serialized_input, out, derive_solana_address),
+        Command::SignSolanaMessage => process(serialized_input, out, sign_solana_message),
+        Command::ThresholdKeygenBegin => process(serialized_input, out, threshold_keygen_begin),
+        Command::ThresholdKeygenFinish => process(serialized_input, out, threshold_keygen_finish),
+        Command::ThresholdPresignBegin => process(serialized_input, out, threshold_presign_begin),
+        Command::ThresholdPresignFinish => process(serialized_input, out, threshold_presign_finish),
+        Command::ThresholdSign => process(serialized_input, out, threshold_sign),
+        Command::ThresholdRecombine => process(serialized_input, out, threshold_recombine),
+        Command::ThresholdStatus => process(serialized_input, out, threshold_status),
+        // Newer than this TA, or retired: tell the CA which side to upgrade.
+        // No catch-all arm: a command added to proto without a handler here
+        // does not build.
+        Command::Unknown => Err(wire::UnsupportedVersion {
+            command: cmd_id,
+            ca_version: header.protocol_version,
+            ta_version: wire::PROTOCOL_VERSION,
+        }
+        .into()),
     }
+}
 
+// ── eth_wallet compatibility (feature eth-wallet-compat) ──
+// Legacy clients have no passkey, so the legacy shapes only ever act on
+// wallets that have none (created through the legacy CreateWallet). A
+// passkey-bound wallet is never reachable without an assertion.
 
-    let wallet_id = extract_json_str_field(payload, "wallet_id")?;
-    let agent_index = extract_json_u32_field(payload, "agent_index")?;
-    // Parse exp for future use; not checked in TA due to unreliable TEE clock epoch.
-    let _exp = extract_json_i64_field(payload, "exp")?;
+#[cfg(feature = "eth-wallet-compat")]
+fn require_unbound(wallet: &Wallet) -> Result<()> {
+    if wallet.get_passkey().is_some() {
+        bail!("eth_wallet compat: wallet has a PassKey bound; use the AirAccount command with an assertion");
+    }
+    Ok(())
+}
 
-    Ok((wallet_id, agent_index))
+#[cfg(feature = "eth-wallet-compat")]
+fn handle_legacy(request: eth_wallet_compat::LegacyRequest) -> Result<Vec<u8>> {
+    use eth_wallet_compat::LegacyRequest;
+    // The legacy path returns before the dispatcher's own policy check.
+    enforce_deployment_policy(request.command())?;
+    ta_log!(Policy, Warn, "[!] eth_wallet compat request: {:?}", request);
+    match request {
+        LegacyRequest::CreateWallet => {
+            Ok(bincode::serialize(&create_wallet_inner(None, None, None)?)?)
+        }
+        LegacyRequest::RemoveWallet(input) => {
+            let next_epoch = rpmb_next_epoch()?;
+            let db_client = open_storage()?;
+            let wallet = db_client
+                .get::<Wallet>(&input.wallet_id)
+                .map_err(|e| anyhow!("wallet not found: {:?}", e))?;
+            require_unbound(&wallet)?;
+            // Same ordering as remove_wallet: cache first (TLS), then the delete.
+            // eth_wallet's RemoveWalletOutput is empty; the certificate is dropped.
+            cache_remove(&input.wallet_id);
+            erase_wallet(&db_client, &wallet, next_epoch)?;
+            Ok(Vec::new())
+        }
+        LegacyRequest::DeriveAddress(input) => {
+            let wallet = load_wallet_cached(&input.wallet_id)?;
+            require_unbound(&wallet)?;
+            let (address, public_key) = wallet.derive_address(&input.hd_path)?;
+            // eth_wallet's DeriveAddressOutput: our v1 layout, without the
+            // encoded address.
+            Ok(bincode::serialize(&(address, public_key))?)
+        }
+        LegacyRequest::SignTransaction(input) => {
+            let wallet = load_wallet_cached(&input.wallet_id)?;
+            require_unbound(&wallet)?;
+            let signature = wallet.sign_transaction(&input.hd_path, &input.transaction)?;
+            Ok(bincode::serialize(&proto::SignTransactionOutput {
+                signature,
+            })?)
+        }
+    }
 }
 
-/// Extract a JSON string field value from a minimally-formatted JWT payload.
-/// Format contract: `"field":"value"` with no whitespace around colon (our jwt_sign_payload format).
-fn extract_json_str_field(json: &str, field: &str) -> Result<String> {
-    let needle = format!("\"{}\":\"", field);
-    let start = json.find(needle.as_str())
-        .ok_or_else(|| anyhow!("field '{}' not found in JWT payload", field))?
-        + needle.len();
-    let end = json[start..].find('"')
-        .ok_or_else(|| anyhow!("unterminated string for field '{}' in JWT payload", field))?
-        + start;
-    Ok(json[start..end].to_string())
+/// The histograms, then the wallet count. A storage failure only leaves
+/// `storage` empty: latency must still be readable on a sick store.
+fn ta_stats(_input: &proto::TaStatsInput) -> Result<proto::TaStatsOutput> {
+    let mut stats = telemetry::snapshot();
+    stats.storage = open_storage()
+        .and_then(|db| storage_quota::usage(&db))
+        .ok();
+    Ok(stats)
 }
 
-/// Extract a JSON unsigned integer field from JWT payload.
-/// Format contract: `"field":12345` (digits immediately after colon, no whitespace).
-fn extract_json_u32_field(json: &str, field: &str) -> Result<u32> {
-    let needle = format!("\"{}\":", field);
-    let start = json.find(needle.as_str())
-        .ok_or_else(|| anyhow!("field '{}' not found in JWT payload", field))?
-        + needle.len();
-    let end = json[start..].find(|c: char| !c.is_ascii_digit())
-        .unwrap_or(json[start..].len())
-        + start;
-    if start == end {
-        return Err(anyhow!("empty value for u32 field '{}' in JWT payload", field));
-    }
-    json[start..end].parse::<u32>()
-        .map_err(|e| anyhow!("field '{}' is not a valid u32: {}", field, e))
+/// Known answers from `self_test`, then the checks that need OP-TEE. Read
+/// only: no wallet is loaded and nothing is written, so it is safe on a
+/// device the CA suspects.
+fn self_test(_input: &proto::SelfTestInput) -> Result<proto::SelfTestOutput> {
+    let mut checks = self_test::known_answer_tests();
+    checks.push(self_test::check("rng", || {
+        let (mut a, mut b) = ([0u8; 32], [0u8; 32]);
+        provider::fill(&mut a);
+        provider::fill(&mut b);
+        if a == [0u8; 32] || b == [0u8; 32] {
+            Err("RNG returned all zeros".to_string())
+        } else if a == b {
+            Err("two RNG draws were equal".to_string())
+        } else {
+            Ok(())
+        }
+    }));
+    checks.push(self_test::check("secure_storage", || {
+        open_storage().map(|_| ()).map_err(|e| e.to_string())
+    }));
+    checks.push(self_test::check("rollback_counter", || {
+        rpmb_read_counter().map(|_| ()).map_err(|e| e.to_string())
+    }));
+    Ok(proto::SelfTestOutput { checks })
 }
 
-/// Extract a JSON signed integer field from JWT payload (used for 'exp' claim).
-fn extract_json_i64_field(json: &str, field: &str) -> Result<i64> {
-    let needle = format!("\"{}\":", field);
-    let start = json.find(needle.as_str())
-        .ok_or_else(|| anyhow!("field '{}' not found in JWT payload", field))?
-        + needle.len();
-    let end = json[start..].find(|c: char| !c.is_ascii_digit() && c != '-')
-        .unwrap_or(json[start..].len())
-        + start;
-    if start == end {
-        return Err(anyhow!("empty value for i64 field '{}' in JWT payload", field));
+// ── Orphaned secure-storage objects (proto::storage_gc) ──
+
+/// Store ids of every stored object of `kind`.
+fn stored_ids(db: &SecureStorageClient, kind: ObjectKind) -> Result<Vec<String>> {
+    let ids: Vec<String> = match kind {
+        ObjectKind::P256SessionKey => db
+            .list_entries::<P256SessionKey>()?
+            .keys()
+            .cloned()
+            .collect(),
+        ObjectKind::ScopedSessionKey => db
+            .list_entries::<session_scope::ScopedSessionKey>()?
+            .keys()
+            .cloned()
+            .collect(),
+        ObjectKind::SessionRevocations => db
+            .list_entries::<session_scope::SessionRevocations>()?
+            .keys()
+            .cloned()
+            .collect(),
+        ObjectKind::AllowancePolicy => db
+            .list_entries::<allowance_guard::AllowancePolicy>()?
+            .keys()
+            .cloned()
+            .collect(),
+        ObjectKind::SpenderAllowList => db
+            .list_entries::<allowance_guard::SpenderAllowList>()?
+            .keys()
+            .cloned()
+            .collect(),
+        ObjectKind::OfflineReplayLog => db
+            .list_entries::<offline_replay::OfflineReplayLog>()?
+            .keys()
+            .cloned()
+            .collect(),
+        ObjectKind::RefreshFamily => db
+            .list_entries::<refresh_token::RefreshRecord>()?
+            .keys()
+            .cloned()
+            .collect(),
+        ObjectKind::OtpSecret => db
+            .list_entries::<otp_vault::OtpSecretRecord>()?
+            .keys()
+            .cloned()
+            .collect(),
+        ObjectKind::DappNamespace => db
+            .list_entries::<dapp_storage::DappNamespaceRecord>()?
+            .keys()
+            .cloned()
+            .collect(),
+        ObjectKind::DappBlob => db
+            .list_entries::<dapp_storage::DappBlob>()?
+            .keys()
+            .cloned()
+            .collect(),
+        ObjectKind::PathPolicy => db
+            .list_entries::<path_policy::PathPolicy>()?
+            .keys()
+            .cloned()
+            .collect(),
+        ObjectKind::RemoteApprover => db
+            .list_entries::<remote_approval::RemoteApprover>()?
+            .keys()
+            .cloned()
+            .collect(),
+        ObjectKind::AggregatorScheme => db
+            .list_entries::<aggregator::AggregatorScheme>()?
+            .keys()
+            .cloned()
+            .collect(),
+        ObjectKind::SpendingPolicy => db
+            .list_entries::<spending_policy::SpendingRecord>()?
+            .keys()
+            .cloned()
+            .collect(),
+        ObjectKind::MultiSig => db
+            .list_entries::<multisig::MultiSigState>()?
+            .keys()
+            .cloned()
+            .collect(),
+        ObjectKind::SocialRecovery => db
+            .list_entries::<social_recovery::RecoveryRecord>()?
+            .keys()
+            .cloned()
+            .collect(),
+        ObjectKind::NonceLedger => db
+            .list_entries::<nonce_guard::NonceRecord>()?
+            .keys()
+            .cloned()
+            .collect(),
+    };
+    Ok(ids)
+}
+
+fn delete_stored(db: &SecureStorageClient, kind: ObjectKind, store_id: &String) -> Result<()> {
+    match kind {
+        ObjectKind::P256SessionKey => db.delete_entry::<P256SessionKey>(store_id)?,
+        ObjectKind::ScopedSessionKey => {
+            db.delete_entry::<session_scope::ScopedSessionKey>(store_id)?
+        }
+        ObjectKind::SessionRevocations => {
+            db.delete_entry::<session_scope::SessionRevocations>(store_id)?
+        }
+        ObjectKind::AllowancePolicy => {
+            db.delete_entry::<allowance_guard::AllowancePolicy>(store_id)?
+        }
+        ObjectKind::SpenderAllowList => {
+            db.delete_entry::<allowance_guard::SpenderAllowList>(store_id)?
+        }
+        ObjectKind::OfflineReplayLog => {
+            db.delete_entry::<offline_replay::OfflineReplayLog>(store_id)?
+        }
+        ObjectKind::RefreshFamily => db.delete_entry::<refresh_token::RefreshRecord>(store_id)?,
+        ObjectKind::OtpSecret => db.delete_entry::<otp_vault::OtpSecretRecord>(store_id)?,
+        ObjectKind::DappNamespace => {
+            db.delete_entry::<dapp_storage::DappNamespaceRecord>(store_id)?
+        }
+        ObjectKind::DappBlob => db.delete_entry::<dapp_storage::DappBlob>(store_id)?,
+        ObjectKind::PathPolicy => db.delete_entry::<path_policy::PathPolicy>(store_id)?,
+        ObjectKind::RemoteApprover => {
+            db.delete_entry::<remote_approval::RemoteApprover>(store_id)?
+        }
+        ObjectKind::AggregatorScheme => {
+            db.delete_entry::<aggregator::AggregatorScheme>(store_id)?
+        }
+        ObjectKind::SpendingPolicy => {
+            db.delete_entry::<spending_policy::SpendingRecord>(store_id)?
+        }
+        ObjectKind::MultiSig => db.delete_entry::<multisig::MultiSigState>(store_id)?,
+        ObjectKind::SocialRecovery => {
+            db.delete_entry::<social_recovery::RecoveryRecord>(store_id)?
+        }
+        ObjectKind::NonceLedger => db.delete_entry::<nonce_guard::NonceRecord>(store_id)?,
     }
-    json[start..end].parse::<i64>()
-        .map_err(|e| anyhow!("field '{}' is not a valid i64 in JWT payload: {}", field, e))
+    Ok(())
 }
 
-/// Delete a P256 session key from TEE secure storage.
-/// Called by the host's lazy GC when credential_expires_at has passed.
-/// Returns deleted=false (not an error) if the key is already absent — idempotent.
-fn delete_p256_session_key(
-    input: &proto::DeleteP256SessionKeyInput,
-) -> Result<proto::DeleteP256SessionKeyOutput> {
-    dbg_println!(
-        "[+] Delete P256 session key for wallet: {:?}, index: {}",
-        input.wallet_id,
-        input.session_index
-    );
-    let db = open_storage()?;
-    let store_id = P256SessionKey::store_id_for(&input.wallet_id, input.session_index);
-    match db.delete_entry::<P256SessionKey>(&store_id) {
-        Ok(()) => Ok(proto::DeleteP256SessionKeyOutput { deleted: true }),
+/// Only `ItemNotFound` means gone: a wallet that fails to read for any
+/// other reason stops the scan instead of orphaning its objects.
+fn wallet_stored(db: &SecureStorageClient, wallet_id: &Uuid) -> Result<bool> {
+    match db.get::<Wallet>(wallet_id) {
+        Ok(_) => Ok(true),
         Err(e) => {
             let msg = e.to_string();
             if msg.contains("ItemNotFound") || msg.contains("ITEM_NOT_FOUND") {
-                Ok(proto::DeleteP256SessionKeyOutput { deleted: false })
+                Ok(false)
             } else {
-                Err(anyhow!("delete_p256_session_key: secure storage error: {}", msg))
+                Err(anyhow!(
+                    "wallet {}: secure storage error: {}",
+                    wallet_id,
+                    msg
+                ))
             }
         }
     }
 }
 
-fn sign_typed_data(input: &proto::SignTypedDataInput) -> Result<proto::SignTypedDataOutput> {
-    dbg_println!(
-        "[+] EIP-712 sign typed data for wallet: {:?}, primary_type: {}",
-        input.wallet_id,
-        input.primary_type
-    );
+/// Every per-wallet object, and the orphans among them. Reads only; the
+/// wallets probed are those the objects name, not the whole wallet index.
+fn scan_orphans(db: &SecureStorageClient) -> Result<(u32, Vec<proto::storage_gc::Orphan>)> {
+    let mut objects = Vec::new();
+    for kind in ObjectKind::ALL {
+        objects.extend(stored_ids(db, kind)?.into_iter().map(|id| (kind, id)));
+    }
+    let mut wallets = std::collections::BTreeSet::new();
+    for wallet_id in proto::storage_gc::referenced_wallets(&objects) {
+        if wallet_stored(db, &wallet_id)? {
+            wallets.insert(wallet_id);
+        }
+    }
+    let orphans = proto::storage_gc::find_orphans(&objects, &wallets);
+    Ok((objects.len() as u32, orphans))
+}
 
-    let wallet = load_wallet_cached(&input.wallet_id)?;
+/// No passkey: the orphans belong to no wallet. Deleting needs the digest
+/// of a report, recomputed here, so what goes is exactly what the operator
+/// was shown. Storage only after the scan, no thread_local access.
+fn scavenge_storage(input: &proto::ScavengeStorageInput) -> Result<proto::ScavengeStorageOutput> {
+    // An operator is on it; a pass between report and delete would only
+    // change the digest under them.
+    storage_gc::take_armed();
+    let db = open_storage()?;
+    let (scanned, orphans) = scan_orphans(&db)?;
+    let digest = proto::storage_gc::orphan_set_digest(&orphans);
+    let mut deleted = 0u32;
+    if let Some(confirm) = input.confirm {
+        if confirm != digest {
+            bail!(
+                "orphan set changed since it was reported (now {} objects, digest 0x{}); scan again",
+                orphans.len(),
+                hex::encode(digest)
+            );
+        }
+        for orphan in &orphans {
+            delete_stored(&db, orphan.kind, &orphan.store_id)?;
+            deleted += 1;
+        }
+        ta_log!(
+            Storage,
+            Info,
+            "[+] ScavengeStorage: {} orphaned objects deleted",
+            deleted
+        );
+    }
+    Ok(proto::ScavengeStorageOutput {
+        scanned,
+        orphans: orphans.len() as u32,
+        by_kind: proto::storage_gc::count_by_kind(&orphans),
+        listed: orphans
+            .iter()
+            .take(proto::storage_gc::MAX_LISTED)
+            .cloned()
+            .collect(),
+        digest,
+        deleted,
+        scavenged_at_load: storage_gc::scavenged_at_load(),
+    })
+}
 
-    // Issue #68: resolve the primary type + compute the EIP-712 digest BEFORE the
-    // auth gate, so the passkey path can bind the challenge to exactly what is signed.
-    let primary_type_def = input
-        .types
-        .iter()
-        .find(|td| td.name == input.primary_type)
-        .ok_or_else(|| anyhow!("Primary type '{}' not found in types list", input.primary_type))?;
-    let digest = eip712::eip712_digest(&input.domain, primary_type_def, &input.message)?;
+/// The pass `create` armed. A locked device is left as the wipe left it.
+fn scavenge_after_load() -> Result<()> {
+    if tamper::sticky_lock() || tamper_guard()?.locked.is_some() {
+        return Ok(());
+    }
+    let db = open_storage()?;
+    let (deleted, found) = scavenge_automatic(&db)?;
+    if found > 0 {
+        ta_log!(
+            Storage,
+            Warn,
+            "[!] orphan pass: deleted {} of {} orphaned objects",
+            deleted,
+            found
+        );
+    }
+    Ok(())
+}
 
-    // TA-side auth gate (defense-in-depth): independently verifies the caller's authorization.
-    // Host has already validated, but TA confirms using TEE-resident secrets that cannot be
-    // forged by a compromised host. Exactly one auth proof must be present.
-    match (&input.passkey_assertion, &input.jwt_kid) {
-        (Some(_), None) => {
-            // WebAuthn path: verify passkey signature against wallet's stored public key.
-            // Issue #68: bind the challenge to the EIP-712 digest being signed.
-            verify_passkey_for_wallet(&wallet, input.passkey_assertion.as_ref(), Some(&digest))?;
+/// Delete what `storage_gc::automatic` picks; (deleted, orphans found).
+fn scavenge_automatic(db: &SecureStorageClient) -> Result<(usize, usize)> {
+    let (_, orphans) = scan_orphans(db)?;
+    let picked = storage_gc::automatic(&orphans);
+    for orphan in &picked {
+        delete_stored(db, orphan.kind, &orphan.store_id)?;
+        storage_gc::record(1);
+    }
+    Ok((picked.len(), orphans.len()))
+}
+
+// ── Postmortem crash dumps ──
+
+/// Internal failures worth a crash record: the TEE core API failed or bytes
+/// did not decode. Ordinary refusals (`bail!`) are not recorded.
+fn crash_class(e: &anyhow::Error) -> Option<(proto::crash_dump::ErrorClass, u32)> {
+    use proto::crash_dump::ErrorClass;
+    e.chain().find_map(|cause| {
+        if let Some(tee) = cause.downcast_ref::<Error>() {
+            Some((ErrorClass::Tee, tee.raw_code()))
+        } else if cause.downcast_ref::<bincode::Error>().is_some() {
+            Some((ErrorClass::Codec, 0))
+        } else if cause.downcast_ref::<OutputTooLarge>().is_some() {
+            Some((ErrorClass::OutputTooLarge, 0))
+        } else {
+            None
         }
-        (None, Some(kid)) => {
-            // Agent JWT path: all three JWT proof fields must be present.
-            let signing_input = input.jwt_signing_input.as_deref()
-                .ok_or_else(|| anyhow!("sign_typed_data: jwt_signing_input missing from JWT proof"))?;
-            let expected_hmac = input.jwt_hmac.as_deref()
-                .ok_or_else(|| anyhow!("sign_typed_data: jwt_hmac missing from JWT proof"))?;
+    })
+}
 
-            // Step 1: Verify HMAC using TEE-resident JWT secret.
-            let verify_result = jwt_hmac_verify(&proto::JwtHmacVerifyInput {
-                kid: kid.clone(),
-                message: signing_input.to_vec(),
-                expected_hmac: expected_hmac.to_vec(),
-            })?;
-            if !verify_result.valid {
-                return Err(anyhow!("JWT HMAC verification failed in TA for sign-typed-data"));
+fn load_crash_log(db: &SecureStorageClient) -> Result<crash_dump::CrashLogRecord> {
+    match db.get::<crash_dump::CrashLogRecord>(&crash_dump::STORE_ID.to_string()) {
+        Ok(record) => Ok(record),
+        Err(e) => {
+            let msg = e.to_string();
+            if !(msg.contains("ItemNotFound") || msg.contains("ITEM_NOT_FOUND")) {
+                return Err(anyhow!("crash log: secure storage error: {}", msg));
             }
+            Ok(crash_dump::CrashLogRecord::default())
+        }
+    }
+}
 
-            // Step 2: Verify JWT claims bind to this request's wallet and agent path.
-            // Parse wallet_id and agent_index from the JWT payload (base64url-encoded JSON).
-            let (jwt_wallet_id, jwt_agent_index) = jwt_parse_claims(signing_input)?;
-            if jwt_wallet_id != input.wallet_id.to_string() {
-                return Err(anyhow!("JWT wallet_id does not match request wallet_id"));
-            }
-            let expected_path = format!("m/44'/60'/0'/1/{}", jwt_agent_index);
-            if input.hd_path != expected_path {
-                return Err(anyhow!(
-                    "JWT agent path '{}' does not match request hd_path '{}'",
-                    expected_path, input.hd_path
-                ));
+/// Append a record for a failed command, after it ran (`invoke_command`).
+fn record_crash(cmd_id: u32, class: proto::crash_dump::ErrorClass, code: u32) -> Result<()> {
+    let counters = telemetry::counters(cmd_id);
+    let db = open_storage()?;
+    let mut record = load_crash_log(&db)?;
+    record.log.push(proto::crash_dump::CrashRecord {
+        seq: 0,
+        command: cmd_id,
+        class,
+        code,
+        uptime_secs: tee_system_time().0,
+        invocations: counters.invocations,
+        failures: counters.failures,
+        command_invocations: counters.command_invocations,
+        command_failures: counters.command_failures,
+    });
+    db.put(&record)
+        .map_err(|e| anyhow!("Failed to save crash log: {}", e))
+}
+
+fn crash_dumps(input: &proto::CrashDumpsInput) -> Result<proto::CrashDumpsOutput> {
+    let db = open_storage()?;
+    let mut record = load_crash_log(&db)?;
+    let output = proto::CrashDumpsOutput {
+        records: record.log.records.clone(),
+        dropped: record.log.dropped(),
+    };
+    if input.clear && !record.log.records.is_empty() {
+        record.log.records.clear();
+        db.put(&record)
+            .map_err(|e| anyhow!("Failed to clear crash log: {}", e))?;
+    }
+    Ok(output)
+}
+
+// ── Deployment policy (command allow-list) and capabilities handshake ──
+
+fn installed_deployment_policy() -> Result<Option<deployment_policy::PolicyRecord>> {
+    if let Some(record) = deployment_policy::cached() {
+        return Ok(record);
+    }
+    let db = open_storage()?;
+    let record =
+        match db.get::<deployment_policy::PolicyRecord>(&deployment_policy::STORE_ID.to_string()) {
+            Ok(record) => Some(record),
+            Err(e) => {
+                let msg = e.to_string();
+                if !(msg.contains("ItemNotFound") || msg.contains("ITEM_NOT_FOUND")) {
+                    // Fail closed: an unreadable policy must not read as "none".
+                    return Err(anyhow!("deployment policy: secure storage error: {}", msg));
+                }
+                None
             }
-        }
-        (None, None) => {
-            return Err(anyhow!("sign_typed_data: no auth proof provided to TA"));
-        }
-        (Some(_), Some(_)) => {
-            return Err(anyhow!("sign_typed_data: ambiguous auth — provide passkey OR JWT, not both"));
+        };
+    deployment_policy::set_cached(record.clone());
+    Ok(record)
+}
+
+fn enforce_deployment_policy(command: Command) -> Result<()> {
+    if proto::deployment_policy::ALWAYS_ENABLED.contains(&command) {
+        return Ok(());
+    }
+    if let Some(record) = installed_deployment_policy()? {
+        if !record.policy.allows(command) {
+            bail!(
+                "{:?} is disabled by deployment policy \"{}\" (sequence {})",
+                command,
+                record.policy.deployment,
+                record.policy.sequence
+            );
         }
     }
+    Ok(())
+}
 
-    // (primary_type_def + digest already computed above for payload binding.)
-    let private_key = wallet.export_private_key(&input.hd_path)?;
-
-    let secret_key = secp256k1::SecretKey::from_slice(&private_key)?;
+/// Ethereum address that produced a 65-byte r ‖ s ‖ v signature over `digest`.
+fn recover_eth_address(digest: &[u8; 32], signature: &[u8]) -> Result<[u8; 20]> {
+    if signature.len() != 65 {
+        bail!("expected a 65-byte signature");
+    }
+    let v = match signature[64] {
+        v @ 0..=1 => v,
+        v @ 27..=28 => v - 27,
+        _ => bail!("bad signature recovery id"),
+    };
+    let recid = secp256k1::ecdsa::RecoveryId::from_i32(v as i32)?;
+    let sig = secp256k1::ecdsa::RecoverableSignature::from_compact(&signature[..64], recid)?;
     let secp = secp256k1::Secp256k1::new();
-    let message = secp256k1::Message::from_slice(&digest)?;
-    let sig = secp.sign_ecdsa_recoverable(&message, &secret_key);
-    let (recovery_id, sig_bytes) = sig.serialize_compact();
+    let public_key = secp.recover_ecdsa(&secp256k1::Message::from_slice(digest)?, &sig)?;
+    let mut address = [0u8; 20];
+    address.copy_from_slice(&Keccak256::digest(&public_key.serialize_uncompressed()[1..])[12..]);
+    Ok(address)
+}
 
-    let mut signature = Vec::with_capacity(65);
-    signature.extend_from_slice(&sig_bytes);
-    signature.push(recovery_id.to_i32() as u8 + 27);
+/// No passkey: the policy carries its own operator signature, and the first
+/// install pins that signer. Replacements must be newer and signed by it.
+fn install_deployment_policy(
+    input: &proto::InstallDeploymentPolicyInput,
+) -> Result<proto::InstallDeploymentPolicyOutput> {
+    let signer = recover_eth_address(&input.policy.digest(), &input.signature)?;
+    let current = installed_deployment_policy()?;
+    deployment_policy::check_replacement(current.as_ref(), &input.policy, signer)
+        .map_err(|e| anyhow!("{}", e))?;
+    let record = deployment_policy::PolicyRecord {
+        store_id: deployment_policy::STORE_ID.to_string(),
+        signer,
+        policy: input.policy.clone(),
+    };
+    let db = open_storage()?;
+    db.put(&record)
+        .map_err(|e| anyhow!("Failed to save deployment policy: {}", e))?;
+    // Process-global cell, not TLS — safe after db.put (H-3).
+    deployment_policy::set_cached(Some(record));
+    ta_log!(
+        Policy,
+        Warn,
+        "[!] deployment policy \"{}\" sequence {} installed, disabled {:?}",
+        input.policy.deployment,
+        input.policy.sequence,
+        input.policy.disabled_commands
+    );
+    Ok(proto::InstallDeploymentPolicyOutput {
+        previous_sequence: current.map(|r| r.policy.sequence),
+        signer,
+    })
+}
 
-    Ok(proto::SignTypedDataOutput { signature })
+// ========================================
+// Multi-device replication (same seed on N devices)
+// ========================================
+
+/// Fresh ephemeral key for one replication session (rejection-sampled).
+fn replication_ephemeral_key() -> (secp256k1::SecretKey, Vec<u8>) {
+    let secret = loop {
+        let mut sk_bytes = [0u8; 32];
+        provider::fill(&mut sk_bytes);
+        if let Ok(sk) = secp256k1::SecretKey::from_slice(&sk_bytes) {
+            break sk;
+        }
+    };
+    let public = secp256k1::PublicKey::from_secret_key(&secp256k1::Secp256k1::new(), &secret);
+    (secret, public.serialize().to_vec())
 }
 
-// ── Grant Session ABI encoding helpers ──
+/// BIP85 child secret sealed to the recipient key. Opt-in per deployment:
+/// with no deployment policy installed the command is refused, so a fresh
+/// TA never hands out derived secrets until the operator has signed a
+/// policy that leaves it enabled. The owner approves each child and
+/// recipient with a passkey.
+fn bip85_export(input: &proto::Bip85ExportInput) -> Result<proto::Bip85ExportOutput> {
+    input.app.validate().map_err(|e| anyhow!("{}", e))?;
+    match installed_deployment_policy()? {
+        Some(record) if record.policy.allows(Command::Bip85Export) => {}
+        _ => bail!("Bip85Export needs an installed deployment policy that enables it"),
+    }
+    if input.recipient_pubkey.len() != 33 {
+        bail!("recipient key must be compressed (33 bytes)");
+    }
+    let recipient = secp256k1::PublicKey::from_slice(&input.recipient_pubkey)
+        .map_err(|_| anyhow!("recipient key is not a secp256k1 point"))?;
+    let wallet = load_wallet_cached(&input.wallet_id)?;
+    verify_passkey_for_wallet(
+        &wallet,
+        input.passkey_assertion.as_ref(),
+        Some(&proto::bip85::export_commitment(
+            &input.wallet_id,
+            &input.app,
+            &input.recipient_pubkey,
+        )),
+    )?;
 
-fn abi_u256_from_u64(val: u64) -> [u8; 32] {
-    let mut buf = [0u8; 32];
-    buf[24..32].copy_from_slice(&val.to_be_bytes());
-    buf
+    let seed = wallet.get_seed()?;
+    let mut k = bip32_secp::derive_hardened_key(&seed, &input.app.path())?;
+    let mut entropy = bip85::entropy_from_k(&k);
+    let (secret, ephemeral_pubkey) = replication_ephemeral_key();
+    let mut nonce = [0u8; 16];
+    provider::fill(&mut nonce);
+    let package = bip85::seal_child(
+        input.wallet_id,
+        input.app,
+        &secret,
+        ephemeral_pubkey,
+        &recipient,
+        nonce,
+        &entropy[..input.app.entropy_len()],
+    );
+    k.iter_mut().for_each(|b| *b = 0);
+    entropy.iter_mut().for_each(|b| *b = 0);
+    ta_log!(
+        Crypto,
+        Info,
+        "[+] BIP85 child {} of wallet {:?} exported",
+        input.app.path_string(),
+        input.wallet_id
+    );
+    Ok(proto::Bip85ExportOutput { package })
 }
 
-fn abi_pad_address(addr: &[u8; 20]) -> [u8; 32] {
-    let mut buf = [0u8; 32];
-    buf[12..32].copy_from_slice(addr);
-    buf
+/// Decryption is opt-in per deployment, like BIP85 export: both commands
+/// need an installed policy that leaves them enabled.
+fn require_decryption_enabled() -> Result<()> {
+    match installed_deployment_policy()? {
+        Some(record)
+            if record.policy.allows(Command::OpenDecryptSession)
+                && record.policy.allows(Command::EciesDecrypt) =>
+        {
+            Ok(())
+        }
+        _ => bail!("ECIES decryption needs an installed deployment policy that enables it"),
+    }
 }
 
-/// bytes4 is right-padded in ABI encoding (data at low offset, zeros at high offset)
-fn abi_pad_bytes4_right(b: &[u8; 4]) -> [u8; 32] {
-    let mut buf = [0u8; 32];
-    buf[0..4].copy_from_slice(b);
-    buf
+/// Passkey-approved session on one key of the wallet, bounded in uses and
+/// time. Opening a session decrypts nothing.
+fn open_decrypt_session(
+    input: &proto::OpenDecryptSessionInput,
+) -> Result<proto::OpenDecryptSessionOutput> {
+    require_decryption_enabled()?;
+    input.scope.validate().map_err(|e| anyhow!("{}", e))?;
+    let wallet = load_wallet_cached(&input.wallet_id)?;
+    verify_passkey_for_wallet(
+        &wallet,
+        input.passkey_assertion.as_ref(),
+        Some(&proto::ecies::session_commitment(
+            &input.wallet_id,
+            &input.scope,
+        )),
+    )?;
+
+    let mut session_id = [0u8; 16];
+    provider::fill(&mut session_id);
+    let expires_at = provider::unix_secs() + input.scope.ttl_secs as u64;
+    decrypt_session::open(
+        session_id,
+        &input.wallet_id,
+        &input.scope.hd_path,
+        input.scope.max_decryptions,
+        expires_at,
+    );
+    ta_log!(
+        Crypto,
+        Info,
+        "[+] decrypt session for wallet {:?} at {}: {} uses, {} s",
+        input.wallet_id,
+        input.scope.hd_path,
+        input.scope.max_decryptions,
+        input.scope.ttl_secs
+    );
+    Ok(proto::OpenDecryptSessionOutput {
+        session_id,
+        expires_at,
+    })
 }
 
-fn abi_u256_from_u16(val: u16) -> [u8; 32] {
-    let mut buf = [0u8; 32];
-    buf[30..32].copy_from_slice(&val.to_be_bytes());
-    buf
+/// Open one ECIES payload with the session's key. The envelope is checked
+/// before a use is spent; a payload that fails authentication still spends
+/// one, so a session cannot be used to probe keys for free.
+fn ecies_decrypt(input: &proto::EciesDecryptInput) -> Result<proto::EciesDecryptOutput> {
+    require_decryption_enabled()?;
+    let envelope = proto::ecies::Envelope::parse(&input.payload).map_err(|e| anyhow!("{}", e))?;
+    let (hd_path, remaining) =
+        decrypt_session::spend(&input.session_id, &input.wallet_id, provider::unix_secs())
+            .map_err(|e| anyhow!("{}", e))?;
+    let wallet = load_wallet_cached(&input.wallet_id)?;
+    let mut shared = wallet.ecdh_point(&hd_path, envelope.ephemeral_pubkey)?;
+    let opened = envelope.open(&shared);
+    shared.fill(0);
+    let plaintext = opened.map_err(|e| anyhow!("{}", e))?;
+    ta_log!(
+        Crypto,
+        Debug,
+        "[+] ECIES payload of {} bytes decrypted for wallet {:?}, {} uses left",
+        plaintext.len(),
+        input.wallet_id,
+        remaining
+    );
+    Ok(proto::EciesDecryptOutput {
+        plaintext,
+        remaining,
+    })
 }
 
-fn abi_u256_from_u32(val: u32) -> [u8; 32] {
-    let mut buf = [0u8; 32];
-    buf[28..32].copy_from_slice(&val.to_be_bytes());
-    buf
+/// Public keys only, so no passkey: the meta-address is meant to be
+/// published.
+fn get_stealth_meta_address(
+    input: &proto::GetStealthMetaAddressInput,
+) -> Result<proto::GetStealthMetaAddressOutput> {
+    let wallet = load_wallet_cached(&input.wallet_id)?;
+    let keys = stealth::StealthKeys::derive(&wallet.get_seed()?, input.account)?;
+    Ok(proto::GetStealthMetaAddressOutput {
+        meta_address: keys.meta_address(),
+    })
 }
 
-/// keccak256(abi.encodePacked(address[])) — matches Solidity, which pads EACH
-/// array element to a 32-byte slot (address is right-aligned → 12 zero bytes +
-/// 20-byte address). NOT tight 20-byte packing.
-///
-/// #112 fix: the TA previously tight-packed (20 bytes/elem), but Solidity's
-/// `abi.encodePacked(address[])` (used by SessionKeyValidator._buildGrantHash and
-/// the SDK's viem encodePacked) pads each element to 32 bytes. They agree for the
-/// empty array (keccak("")) so this was latent; non-empty scoped grants had a
-/// final_hash that mismatched the contract → the TA signature failed on-chain and
-/// the strict commitment never matched. Verified byte-identical to the SDK's
-/// contract-oracle vectors (AirAccount#112 / #119).
-fn keccak_packed_addresses(addrs: &[[u8; 20]]) -> [u8; 32] {
-    let mut buf = Vec::with_capacity(addrs.len() * 32);
-    for a in addrs {
-        buf.extend_from_slice(&[0u8; 12]); // left-pad address to its 32-byte slot
-        buf.extend_from_slice(a);
+/// Which of a batch of announcements pay this account. The scanner learns
+/// that much and no key, which is what EIP-5564 gives a viewing-key holder.
+fn stealth_scan(input: &proto::StealthScanInput) -> Result<proto::StealthScanOutput> {
+    if input.candidates.len() > proto::stealth::MAX_SCAN_BATCH {
+        bail!(
+            "at most {} announcements per scan",
+            proto::stealth::MAX_SCAN_BATCH
+        );
     }
-    Keccak256::digest(&buf).into()
+    let wallet = load_wallet_cached(&input.wallet_id)?;
+    let keys = stealth::StealthKeys::derive(&wallet.get_seed()?, input.account)?;
+    let matches = keys.scan(&input.candidates);
+    ta_log!(
+        Crypto,
+        Debug,
+        "[+] stealth scan for wallet {:?} account {}: {} of {} announcements match",
+        input.wallet_id,
+        input.account,
+        matches.len(),
+        input.candidates.len()
+    );
+    Ok(proto::StealthScanOutput { matches })
 }
 
-/// keccak256(abi.encodePacked(bytes4[])) — matches Solidity: each bytes4 element
-/// is padded to a 32-byte slot, right-aligned for value types but bytesN is
-/// LEFT-aligned (4 selector bytes + 28 zero bytes). See keccak_packed_addresses
-/// for the #112 tight-pack→pad-32 rationale.
-fn keccak_packed_selectors(sels: &[[u8; 4]]) -> [u8; 32] {
-    let mut buf = Vec::with_capacity(sels.len() * 32);
-    for s in sels {
-        buf.extend_from_slice(s);
-        buf.extend_from_slice(&[0u8; 28]); // right-pad bytes4 to its 32-byte slot
-    }
-    Keccak256::digest(&buf).into()
+/// Target side, step 1: attested ephemeral key. The secret stays in secure
+/// storage as the single pending offer; a new offer replaces it.
+fn replication_offer(
+    _input: &proto::ReplicationOfferInput,
+) -> Result<proto::ReplicationOfferOutput> {
+    use proto::replication::{
+        hello_digest, ReplicationHello, ReplicationRole, REPLICATION_VERSION,
+    };
+
+    let (secret, ephemeral_pubkey) = replication_ephemeral_key();
+    let mut session_id = [0u8; 16];
+    provider::fill(&mut session_id);
+    let created_at = tee_unix_secs().max(0) as u64;
+    let nonce = hello_digest(
+        REPLICATION_VERSION,
+        ReplicationRole::Target,
+        &session_id,
+        &ephemeral_pubkey,
+        created_at,
+    );
+    let attestation = attestation::get_attestation(&proto::GetAttestationInput {
+        nonce: nonce.to_vec(),
+    })
+    .map_err(|e| anyhow!("replication needs the attestation PTA: {}", e))?;
+
+    let db = open_storage()?;
+    db.put(&replication::PendingOffer {
+        store_id: replication::OFFER_STORE_ID.to_string(),
+        session_id,
+        ephemeral_secret: secret.secret_bytes(),
+        ephemeral_pubkey: ephemeral_pubkey.clone(),
+        created_at,
+        measurement: attestation.ta_measurement.clone(),
+    })?;
+    ta_log!(Crypto, Info, "[+] replication offer opened");
+    Ok(proto::ReplicationOfferOutput {
+        hello: ReplicationHello {
+            version: REPLICATION_VERSION,
+            role: ReplicationRole::Target,
+            session_id,
+            ephemeral_pubkey,
+            created_at,
+            attestation,
+        },
+    })
 }
 
-/// Build GRANT_SESSION_V2 inner hash.
-/// Matches _buildGrantHash() in SessionKeyValidator.sol:
-///   keccak256(abi.encode("GRANT_SESSION_V2", chainId, contract, account, sessionKey,
-///             expiry, contractScope, selectorScope, velocityLimit, velocityWindow,
-///             callTargetsHash, selectorsHash, nonce))
-///
-/// ABI layout: 13 args; head = 13*32 = 416 bytes; string data tail = 64 bytes.
-fn build_grant_session_inner(input: &proto::SignGrantSessionInput) -> [u8; 32] {
-    let call_targets_hash = keccak_packed_addresses(&input.call_targets);
-    let selectors_hash = keccak_packed_selectors(&input.selector_allowlist);
+/// Source side: check the target runs this same TA, have the owner approve
+/// the target's hello with the wallet passkey, then encrypt the wallet to it.
+fn replication_export(
+    input: &proto::ReplicationExportInput,
+) -> Result<proto::ReplicationExportOutput> {
+    use proto::replication::{
+        hello_digest, ReplicationHello, ReplicationPackage, ReplicationRole, REPLICATION_VERSION,
+    };
+    use std::convert::TryFrom;
 
-    let mut buf = [0u8; 480];
-    // [0x000] string offset = 13 * 32 = 416
-    buf[0..32].copy_from_slice(&{
-        let mut v = [0u8; 32];
-        v[30..32].copy_from_slice(&(416u16).to_be_bytes());
-        v
-    });
-    // [0x020] chainId
-    buf[32..64].copy_from_slice(&abi_u256_from_u64(input.chain_id));
-    // [0x040] verifyingContract
-    buf[64..96].copy_from_slice(&abi_pad_address(&input.verifying_contract));
-    // [0x060] account
-    buf[96..128].copy_from_slice(&abi_pad_address(&input.account));
-    // [0x080] sessionKey
-    buf[128..160].copy_from_slice(&abi_pad_address(&input.session_key));
-    // [0x0A0] expiry (uint48)
-    buf[160..192].copy_from_slice(&abi_u256_from_u64(input.expiry));
-    // [0x0C0] contractScope
-    buf[192..224].copy_from_slice(&abi_pad_address(&input.contract_scope));
-    // [0x0E0] selectorScope (bytes4, right-padded)
-    buf[224..256].copy_from_slice(&abi_pad_bytes4_right(&input.selector_scope));
-    // [0x100] velocityLimit (uint16)
-    buf[256..288].copy_from_slice(&abi_u256_from_u16(input.velocity_limit));
-    // [0x120] velocityWindow (uint32)
-    buf[288..320].copy_from_slice(&abi_u256_from_u32(input.velocity_window));
-    // [0x140] callTargetsHash
-    buf[320..352].copy_from_slice(&call_targets_hash);
-    // [0x160] selectorsHash
-    buf[352..384].copy_from_slice(&selectors_hash);
-    // [0x180] nonce (uint256, big-endian 32 bytes)
-    buf[384..416].copy_from_slice(&input.nonce);
-    // [0x1A0] string length = 16
-    buf[416..448].copy_from_slice(&{
-        let mut v = [0u8; 32];
-        v[31] = 16;
-        v
-    });
-    // [0x1C0] "GRANT_SESSION_V2" (16 bytes, right-zero-padded to 32)
-    buf[448..464].copy_from_slice(b"GRANT_SESSION_V2");
+    let peer = &input.peer;
+    let now = tee_unix_secs().max(0) as u64;
+    let wallet = load_wallet_cached(&input.wallet_id)?;
 
-    Keccak256::digest(&buf).into()
+    let (secret, ephemeral_pubkey) = replication_ephemeral_key();
+    let nonce = hello_digest(
+        REPLICATION_VERSION,
+        ReplicationRole::Source,
+        &peer.session_id,
+        &ephemeral_pubkey,
+        now,
+    );
+    let own = attestation::get_attestation(&proto::GetAttestationInput {
+        nonce: nonce.to_vec(),
+    })
+    .map_err(|e| anyhow!("replication needs the attestation PTA: {}", e))?;
+    peer.check(ReplicationRole::Target, &own.ta_measurement, now)
+        .map_err(|e| anyhow!("target device: {}", e))?;
+    attestation::verify_peer_evidence(&peer.attestation)?;
+    verify_passkey_for_wallet(
+        &wallet,
+        input.passkey_assertion.as_ref(),
+        Some(&peer.digest()),
+    )?;
+
+    let peer_pub = secp256k1::PublicKey::from_slice(&peer.ephemeral_pubkey)
+        .map_err(|_| anyhow!("target ephemeral key is not a secp256k1 point"))?;
+    let keys = replication::session_keys(
+        &secret,
+        &peer_pub,
+        &peer.session_id,
+        &peer.ephemeral_pubkey,
+        &ephemeral_pubkey,
+    );
+    let mut package = ReplicationPackage {
+        version: REPLICATION_VERSION,
+        session_id: peer.session_id,
+        wallet_id: input.wallet_id,
+        source: ReplicationHello {
+            version: REPLICATION_VERSION,
+            role: ReplicationRole::Source,
+            session_id: peer.session_id,
+            ephemeral_pubkey,
+            created_at: now,
+            attestation: own,
+        },
+        ciphertext: Vec::new(),
+        mac: [0u8; 32],
+    };
+    let plaintext = Vec::<u8>::try_from(wallet)?;
+    let (ciphertext, mac) = replication::seal(&keys, &package.aad(), &plaintext);
+    package.ciphertext = ciphertext;
+    package.mac = mac;
+    ta_log!(
+        Crypto,
+        Info,
+        "[+] wallet {:?} exported for replication",
+        input.wallet_id
+    );
+    Ok(proto::ReplicationExportOutput { package })
 }
 
-/// Build GRANT_P256_SESSION_V2 inner hash.
-/// Matches _buildP256GrantHash() in SessionKeyValidator.sol:
-///   keccak256(abi.encode("GRANT_P256_SESSION_V2", chainId, contract, account, keyX, keyY,
-///             expiry, contractScope, selectorScope, velocityLimit, velocityWindow,
-///             callTargetsHash, selectorsHash, nonce))
-///
-/// ABI layout: 14 args; head = 14*32 = 448 bytes; string data tail = 64 bytes.
-fn build_p256_grant_session_inner(input: &proto::SignP256GrantSessionInput) -> [u8; 32] {
-    let call_targets_hash = keccak_packed_addresses(&input.call_targets);
-    let selectors_hash = keccak_packed_selectors(&input.selector_allowlist);
+/// Target side, step 2: authenticate and store the wallet under a fresh
+/// epoch, then retire the offer so the package cannot be imported twice.
+fn replication_import(
+    input: &proto::ReplicationImportInput,
+) -> Result<proto::ReplicationImportOutput> {
+    use proto::replication::{ReplicationRole, REPLICATION_TTL_SECS, REPLICATION_VERSION};
+    use std::convert::TryFrom;
 
-    let mut buf = [0u8; 512];
-    // [0x000] string offset = 14 * 32 = 448
-    buf[0..32].copy_from_slice(&{
-        let mut v = [0u8; 32];
-        v[30..32].copy_from_slice(&(448u16).to_be_bytes());
-        v
-    });
-    // [0x020] chainId
-    buf[32..64].copy_from_slice(&abi_u256_from_u64(input.chain_id));
-    // [0x040] verifyingContract
-    buf[64..96].copy_from_slice(&abi_pad_address(&input.verifying_contract));
-    // [0x060] account
-    buf[96..128].copy_from_slice(&abi_pad_address(&input.account));
-    // [0x080] keyX (bytes32)
-    buf[128..160].copy_from_slice(&input.key_x);
-    // [0x0A0] keyY (bytes32)
-    buf[160..192].copy_from_slice(&input.key_y);
-    // [0x0C0] expiry (uint48)
-    buf[192..224].copy_from_slice(&abi_u256_from_u64(input.expiry));
-    // [0x0E0] contractScope
-    buf[224..256].copy_from_slice(&abi_pad_address(&input.contract_scope));
-    // [0x100] selectorScope (bytes4, right-padded)
-    buf[256..288].copy_from_slice(&abi_pad_bytes4_right(&input.selector_scope));
-    // [0x120] velocityLimit (uint16)
-    buf[288..320].copy_from_slice(&abi_u256_from_u16(input.velocity_limit));
-    // [0x140] velocityWindow (uint32)
-    buf[320..352].copy_from_slice(&abi_u256_from_u32(input.velocity_window));
-    // [0x160] callTargetsHash
-    buf[352..384].copy_from_slice(&call_targets_hash);
-    // [0x180] selectorsHash
-    buf[384..416].copy_from_slice(&selectors_hash);
-    // [0x1A0] nonce (uint256, big-endian 32 bytes)
-    buf[416..448].copy_from_slice(&input.nonce);
-    // [0x1C0] string length = 21
-    buf[448..480].copy_from_slice(&{
-        let mut v = [0u8; 32];
-        v[31] = 21;
-        v
-    });
-    // [0x1E0] "GRANT_P256_SESSION_V2" (21 bytes, right-zero-padded to 32)
-    buf[480..501].copy_from_slice(b"GRANT_P256_SESSION_V2");
+    let package = &input.package;
+    let now = tee_unix_secs().max(0) as u64;
+    // Read RPMB epoch before any thread_local access (read doesn't corrupt TLS).
+    let epoch = rpmb_next_epoch()?;
+    let db = open_storage()?;
+    let offer_id = replication::OFFER_STORE_ID.to_string();
+    let offer = db
+        .get::<replication::PendingOffer>(&offer_id)
+        .map_err(|_| anyhow!("no replication offer is pending on this device"))?;
+    if package.version != REPLICATION_VERSION
+        || package.session_id != offer.session_id
+        || package.source.session_id != offer.session_id
+    {
+        bail!("package is for a different replication session");
+    }
+    if now.saturating_sub(offer.created_at) > REPLICATION_TTL_SECS {
+        bail!("replication offer expired; open a new one");
+    }
+    package
+        .source
+        .check(ReplicationRole::Source, &offer.measurement, now)
+        .map_err(|e| anyhow!("source device: {}", e))?;
+    attestation::verify_peer_evidence(&package.source.attestation)?;
+
+    let secret = secp256k1::SecretKey::from_slice(&offer.ephemeral_secret)
+        .map_err(|_| anyhow!("stored replication offer is corrupt"))?;
+    let source_pub = secp256k1::PublicKey::from_slice(&package.source.ephemeral_pubkey)
+        .map_err(|_| anyhow!("source ephemeral key is not a secp256k1 point"))?;
+    let keys = replication::session_keys(
+        &secret,
+        &source_pub,
+        &offer.session_id,
+        &offer.ephemeral_pubkey,
+        &package.source.ephemeral_pubkey,
+    );
+    let plaintext = replication::open(&keys, &package.aad(), &package.ciphertext, &package.mac)
+        .map_err(|e| anyhow!("{}", e))?;
+    let mut wallet = Wallet::try_from(plaintext)?;
+    if wallet.get_id() != package.wallet_id {
+        bail!("package wallet id does not match its contents");
+    }
+    if db.get::<Wallet>(&package.wallet_id).is_ok() {
+        bail!("wallet {} already exists on this device", package.wallet_id);
+    }
+    wallet.rollback_epoch = epoch;
+    // Derivation touches the key cache (TLS) — before the writes below.
+    let (address, _) = wallet.derive_address("m/44'/60'/0'/0/0")?;
+
+    save_wallet(&db, &wallet)?;
+    db.delete_entry::<replication::PendingOffer>(&offer_id)?;
+    rpmb_write_counter(epoch)?;
+    ta_log!(
+        Crypto,
+        Info,
+        "[+] wallet {:?} imported by replication (RPMB epoch={})",
+        package.wallet_id,
+        epoch
+    );
+    Ok(proto::ReplicationImportOutput {
+        wallet_id: package.wallet_id,
+        address,
+    })
+}
 
-    Keccak256::digest(&buf).into()
+/// Hardware-wallet migration, step 1: an ephemeral key bound to the xpub the
+/// user exported. Replaces any earlier migration offer.
+fn migration_offer(input: &proto::MigrationOfferInput) -> Result<proto::MigrationOfferOutput> {
+    let xpub = &input.account_xpub;
+    if xpub.public_key.len() != 33 || secp256k1::PublicKey::from_slice(&xpub.public_key).is_err() {
+        bail!("account xpub key is not a compressed secp256k1 point");
+    }
+    let (secret, ephemeral_pubkey) = replication_ephemeral_key();
+    let mut session_id = [0u8; 16];
+    provider::fill(&mut session_id);
+    let created_at = tee_unix_secs().max(0) as u64;
+    let nonce = proto::migration::offer_digest(&session_id, &ephemeral_pubkey, xpub, created_at);
+    let attestation = attestation::get_attestation(&proto::GetAttestationInput {
+        nonce: nonce.to_vec(),
+    })
+    .ok();
+
+    let db = open_storage()?;
+    db.put(&migration::PendingMigration {
+        store_id: migration::MIGRATION_STORE_ID.to_string(),
+        session_id,
+        ephemeral_secret: secret.secret_bytes(),
+        ephemeral_pubkey: ephemeral_pubkey.clone(),
+        account_xpub: xpub.clone(),
+        created_at,
+    })?;
+    ta_log!(Crypto, Info, "[+] migration offer opened");
+    Ok(proto::MigrationOfferOutput {
+        session_id,
+        ephemeral_pubkey,
+        created_at,
+        attestation,
+    })
 }
 
-/// Wrap inner hash with EIP-191 personal_sign prefix (matches OpenZeppelin toEthSignedMessageHash).
-fn eip191_hash(inner: &[u8; 32]) -> [u8; 32] {
-    let mut msg = [0u8; 60];
-    msg[0..28].copy_from_slice(b"\x19Ethereum Signed Message:\n32");
-    msg[28..60].copy_from_slice(inner);
-    Keccak256::digest(&msg).into()
+/// Step 2: decrypt the phrase and create the wallet only if it reproduces
+/// the xpub and every confirmed address. A failed check keeps the offer so
+/// the user can retry within its lifetime.
+fn migration_import(input: &proto::MigrationImportInput) -> Result<proto::MigrationImportOutput> {
+    if input.passkey_pubkey.len() != 65 || input.passkey_pubkey[0] != 0x04 {
+        bail!(
+            "PassKey pubkey must be 65 bytes uncompressed (0x04||x||y), got {} bytes",
+            input.passkey_pubkey.len()
+        );
+    }
+    proto::migration::validate_confirmed(&input.confirmed).map_err(|e| anyhow!("{}", e))?;
+    let now = tee_unix_secs().max(0) as u64;
+    let db = open_storage()?;
+    let offer_id = migration::MIGRATION_STORE_ID.to_string();
+    let offer = db
+        .get::<migration::PendingMigration>(&offer_id)
+        .map_err(|_| anyhow!("no migration is pending on this device"))?;
+    if input.session_id != offer.session_id {
+        bail!("package is for a different migration session");
+    }
+    if now.saturating_sub(offer.created_at) > proto::migration::MIGRATION_TTL_SECS {
+        bail!("migration offer expired; begin again");
+    }
+
+    let secret = secp256k1::SecretKey::from_slice(&offer.ephemeral_secret)
+        .map_err(|_| anyhow!("stored migration offer is corrupt"))?;
+    let client_pub = secp256k1::PublicKey::from_slice(&input.client_pubkey)
+        .map_err(|_| anyhow!("client ephemeral key is not a secp256k1 point"))?;
+    let keys = replication::session_keys(
+        &secret,
+        &client_pub,
+        &offer.session_id,
+        &offer.ephemeral_pubkey,
+        &input.client_pubkey,
+    );
+    let aad = proto::migration::sealed_aad(
+        &offer.session_id,
+        &offer.ephemeral_pubkey,
+        &input.client_pubkey,
+        &offer.account_xpub,
+    );
+    let mut plaintext = replication::open(&keys, &aad, &input.ciphertext, &input.mac)
+        .map_err(|_| anyhow!("migration package failed authentication"))?;
+    let parsed = std::str::from_utf8(&plaintext)
+        .map_err(|_| anyhow!("{}", INVALID_PHRASE))
+        .and_then(parse_phrase);
+    plaintext.fill(0);
+    let mnemonic = parsed?;
+
+    let seed = mnemonic.to_seed("");
+    let root = bip32_secp::compute_account_root(seed.as_bytes())?;
+    let derive = |index: u32| {
+        bip32_secp::derive_full(seed.as_bytes(), Some(&root), 0, index)
+            .map(|k| eth_address_from_uncompressed(&k.public_key_uncompressed))
+            .map_err(|e| e.to_string())
+    };
+    migration::cross_check(
+        &offer.account_xpub,
+        &root.pubkey,
+        &root.chain,
+        &input.confirmed,
+        derive,
+    )
+    .map_err(|e| anyhow!("{}", e))?;
+    let address = derive(0).map_err(|e| anyhow!("{}", e))?;
+
+    let mut entropy = mnemonic.entropy().to_vec();
+    let created = create_wallet_inner(Some(&input.passkey_pubkey), Some(&entropy), None);
+    entropy.fill(0);
+    let wallet_id = created?.wallet_id;
+    db.delete_entry::<migration::PendingMigration>(&offer_id)?;
+    ta_log!(
+        Crypto,
+        Info,
+        "[+] wallet {:?} imported by hardware-wallet migration",
+        wallet_id
+    );
+    Ok(proto::MigrationImportOutput { wallet_id, address })
 }
 
-/// #115: mint-challenge commitment over the CLIENT-AUTHORIZED inputs (wallet_id ‖
-/// label). In strict the client commits `challenge = SHA-256(nonce ‖ mint_label_digest)`
-/// and the TA recomputes it here — so a compromised CA cannot relabel the credential
-/// while riding the user's mint gesture. Server-derived params are intentionally NOT
-/// bound: `agent_index`/`session_index` are allocated AFTER the assertion (the client
-/// can't know them), and `ttl` is bounded by `MAX_AGENT_JWT_TTL`. The variable-length
-/// label is hashed to keep the preimage fixed-width. `tag` domain-separates agent vs
-/// p256 (and from the reverted v1 index/subject/ttl scheme). The SDK MUST recompute
-/// this identically (both inputs are client-known).
-fn mint_label_digest(wallet_id: &Uuid, label: &str, tag: &[u8]) -> [u8; 32] {
-    use sha2::Digest;
-    let mut h = sha2::Sha256::new();
-    h.update(tag);
-    h.update(wallet_id.as_bytes());
-    h.update(sha2::Sha256::digest(label.as_bytes()));
-    h.finalize().into()
+const INVALID_PHRASE: &str = "not a valid 24-word English BIP39 recovery phrase";
+
+/// Words in any whitespace, checksum checked by `Mnemonic::new`.
+fn parse_phrase(phrase: &str) -> Result<bip32::Mnemonic> {
+    let words = phrase.split_whitespace().collect::<Vec<_>>().join(" ");
+    bip32::Mnemonic::new(words, bip32::Language::English).map_err(|_| anyhow!("{}", INVALID_PHRASE))
 }
 
-/// #115: REFRESH commitment — binds the agent_index being re-minted (which the
-/// refreshing client knows + commits to) under a DISTINCT domain tag. This is what
-/// prevents a refresh assertion from being replayed to create (or vice versa): the
-/// create digest uses AA-AGENT-MINT-v2 ‖ wallet ‖ H(label), so the two never collide
-/// even for an empty label. A compromised CA cannot redirect a refresh of index N to
-/// a create at a different index — the client committed to this exact (tag, index).
-fn agent_refresh_digest(wallet_id: &Uuid, agent_index: u32) -> [u8; 32] {
-    use sha2::Digest;
-    let mut h = sha2::Sha256::new();
-    h.update(b"AA-AGENT-REFRESH-v2");
-    h.update(wallet_id.as_bytes());
-    h.update(agent_index.to_be_bytes());
-    h.finalize().into()
+/// Store a wallet for a phrase the user already holds. Unlike the
+/// migration there is nothing to cross-check it against, so the CA shows
+/// the returned address for the user to compare.
+fn import_wallet(input: &proto::ImportWalletInput) -> Result<proto::ImportWalletOutput> {
+    if input.passkey_pubkey.len() != 65 || input.passkey_pubkey[0] != 0x04 {
+        bail!(
+            "PassKey pubkey must be 65 bytes uncompressed (0x04||x||y), got {} bytes",
+            input.passkey_pubkey.len()
+        );
+    }
+    let mnemonic = parse_phrase(&input.phrase)?;
+    let seed = mnemonic.to_seed("");
+    let root = bip32_secp::compute_account_root(seed.as_bytes())?;
+    let key = bip32_secp::derive_full(seed.as_bytes(), Some(&root), 0, 0)?;
+    let address = eth_address_from_uncompressed(&key.public_key_uncompressed);
+
+    let mut entropy = mnemonic.entropy().to_vec();
+    let created = create_wallet_inner(Some(&input.passkey_pubkey), Some(&entropy), None);
+    entropy.fill(0);
+    let wallet_id = created?.wallet_id;
+    ta_log!(
+        Crypto,
+        Info,
+        "[+] wallet {:?} imported from a recovery phrase",
+        wallet_id
+    );
+    Ok(proto::ImportWalletOutput { wallet_id, address })
 }
 
-fn sign_grant_session(input: &proto::SignGrantSessionInput) -> Result<proto::SignGrantSessionOutput> {
-    let wallet = load_wallet_cached(&input.wallet_id)?;
+fn compiled_features() -> Vec<String> {
+    let mut features = Vec::new();
+    for (enabled, name) in [
+        (cfg!(feature = "export-secrets"), "export-secrets"),
+        (cfg!(feature = "dev-rpid"), "dev-rpid"),
+        (cfg!(feature = "ree-fs-only"), "ree-fs-only"),
+        (cfg!(feature = "strict-challenge"), "strict-challenge"),
+        (
+            cfg!(feature = "strict-signing-context"),
+            "strict-signing-context",
+        ),
+        (cfg!(feature = "eth-wallet-compat"), "eth-wallet-compat"),
+        (cfg!(feature = "deterministic"), "deterministic"),
+        (cfg!(feature = "secure-display"), "secure-display"),
+        (cfg!(feature = "threshold-ecdsa"), "threshold-ecdsa"),
+    ] {
+        if enabled {
+            features.push(name.to_string());
+        }
+    }
+    features
+}
 
-    // Issue #68: compute the exact digest this op signs and bind the challenge to it.
-    let inner = build_grant_session_inner(input);
-    let final_hash = eip191_hash(&inner);
+fn get_capabilities(_input: &proto::GetCapabilitiesInput) -> Result<proto::GetCapabilitiesOutput> {
+    let record = installed_deployment_policy()?;
+    Ok(proto::GetCapabilitiesOutput {
+        ta_version: env!("CARGO_PKG_VERSION").to_string(),
+        features: compiled_features(),
+        policy_signer: record.as_ref().map(|r| r.signer),
+        policy: record.map(|r| r.policy),
+        config: installed_ta_config()?,
+        provisioning_key: ta_config::provisioning_key(),
+    })
+}
 
-    verify_passkey_for_wallet(&wallet, input.passkey_assertion.as_ref(), Some(&final_hash))?;
+fn get_protocol_version(
+    _input: &proto::GetProtocolVersionInput,
+) -> Result<proto::GetProtocolVersionOutput> {
+    Ok(proto::GetProtocolVersionOutput {
+        protocol_version: wire::PROTOCOL_VERSION,
+        min_protocol_version: wire::MIN_PROTOCOL_VERSION,
+        max_command_id: proto::buffers::MAX_COMMAND_ID,
+    })
+}
 
-    let private_key = wallet.export_private_key(&input.hd_path)?;
-    let secret_key = secp256k1::SecretKey::from_slice(&private_key)?;
-    let secp = secp256k1::Secp256k1::new();
-    let msg = secp256k1::Message::from_slice(&final_hash)?;
-    let sig = secp.sign_ecdsa_recoverable(&msg, &secret_key);
-    let (recovery_id, sig_bytes) = sig.serialize_compact();
+// ── Sealed TA config (limits, hardening flags) ──
 
-    let mut signature = Vec::with_capacity(65);
-    signature.extend_from_slice(&sig_bytes);
-    signature.push(recovery_id.to_i32() as u8 + 27);
+fn installed_ta_config() -> Result<Option<proto::ta_config::TaConfig>> {
+    if let Some(config) = ta_config::cached() {
+        return Ok(config);
+    }
+    let db = open_storage()?;
+    let config = match db.get::<ta_config::ConfigRecord>(&ta_config::STORE_ID.to_string()) {
+        Ok(record) => Some(record.config),
+        Err(e) => {
+            let msg = e.to_string();
+            if !(msg.contains("ItemNotFound") || msg.contains("ITEM_NOT_FOUND")) {
+                // Fail closed: an unreadable config must not fall back to the
+                // defaults, which may be laxer than what was installed.
+                return Err(anyhow!("TA config: secure storage error: {}", msg));
+            }
+            None
+        }
+    };
+    ta_config::set_cached(config.clone());
+    Ok(config)
+}
 
-    Ok(proto::SignGrantSessionOutput { signature })
+/// No passkey: the config is signed by the provisioning key compiled into
+/// this image, so neither the CA nor a wallet owner can change limits.
+fn install_config(input: &proto::InstallConfigInput) -> Result<proto::InstallConfigOutput> {
+    let signer = recover_eth_address(&input.config.digest(), &input.signature)?;
+    let current = installed_ta_config()?;
+    ta_config::check_install(
+        current.as_ref(),
+        &input.config,
+        signer,
+        ta_config::provisioning_key(),
+    )
+    .map_err(|e| anyhow!("{}", e))?;
+    let record = ta_config::ConfigRecord {
+        store_id: ta_config::STORE_ID.to_string(),
+        config: input.config.clone(),
+    };
+    let db = open_storage()?;
+    db.put(&record)
+        .map_err(|e| anyhow!("Failed to save TA config: {}", e))?;
+    // Process-global cell, not TLS — safe after db.put (H-3).
+    ta_config::set_cached(Some(input.config.clone()));
+    ta_log!(
+        Policy,
+        Warn,
+        "[!] TA config \"{}\" sequence {} installed: {:?}",
+        input.config.deployment,
+        input.config.sequence,
+        input.config.limits
+    );
+    Ok(proto::InstallConfigOutput {
+        previous_sequence: current.map(|c| c.sequence),
+    })
 }
 
-fn sign_p256_grant_session(input: &proto::SignP256GrantSessionInput) -> Result<proto::SignP256GrantSessionOutput> {
-    let wallet = load_wallet_cached(&input.wallet_id)?;
+// ── TA log levels (SetLogLevel) ──
 
-    // Issue #68: compute the exact digest this op signs and bind the challenge to it.
-    let inner = build_p256_grant_session_inner(input);
-    let final_hash = eip191_hash(&inner);
+/// Straight from storage; fails closed, unlike [`refresh_log_levels`].
+fn load_log_record() -> Result<Option<log_level::LogRecord>> {
+    let db = open_storage()?;
+    match db.get::<log_level::LogRecord>(&log_level::STORE_ID.to_string()) {
+        Ok(record) => Ok(Some(record)),
+        Err(e) => {
+            let msg = e.to_string();
+            if !(msg.contains("ItemNotFound") || msg.contains("ITEM_NOT_FOUND")) {
+                return Err(anyhow!("log levels: secure storage error: {}", msg));
+            }
+            Ok(None)
+        }
+    }
+}
 
-    verify_passkey_for_wallet(&wallet, input.passkey_assertion.as_ref(), Some(&final_hash))?;
+/// Load the record once per instance, then apply it at the TA clock so an
+/// expired order lapses without a write. An unreadable record costs only
+/// verbosity: the command runs at the defaults and the next one retries.
+fn refresh_log_levels() {
+    if log_level::cached().is_none() {
+        match load_log_record() {
+            Ok(record) => log_level::set_cached(record),
+            Err(e) => {
+                trace_println!("[!] {}; using the default log levels", e);
+            }
+        }
+    }
+    log_level::refresh(tee_unix_secs().max(0) as u64);
+}
 
-    let private_key = wallet.export_private_key(&input.hd_path)?;
-    let secret_key = secp256k1::SecretKey::from_slice(&private_key)?;
-    let secp = secp256k1::Secp256k1::new();
-    let msg = secp256k1::Message::from_slice(&final_hash)?;
-    let sig = secp.sign_ecdsa_recoverable(&msg, &secret_key);
-    let (recovery_id, sig_bytes) = sig.serialize_compact();
+/// Signed like a tamper order: the pinned deployment-policy key, the
+/// installed deployment, optionally one device, a rising sequence.
+fn set_log_level(input: &proto::SetLogLevelInput) -> Result<proto::SetLogLevelOutput> {
+    let signer = recover_eth_address(&input.order.digest(), &input.signature)?;
+    let policy = installed_deployment_policy()?;
+    let device = match input.order.device {
+        Some(_) => Some(attestation::device_fingerprint()?),
+        None => None,
+    };
+    let current = load_log_record()?;
+    let now = tee_unix_secs().max(0) as u64;
+    log_level::check_order(
+        current.as_ref(),
+        policy
+            .as_ref()
+            .map(|r| (&r.signer, r.policy.deployment.as_str())),
+        device.as_ref(),
+        &input.order,
+        signer,
+        now,
+    )
+    .map_err(|e| anyhow!("{}", e))?;
+
+    let record = log_level::LogRecord::from_order(&input.order);
+    let db = open_storage()?;
+    db.put(&record)
+        .map_err(|e| anyhow!("Failed to save log levels: {}", e))?;
+    // Process-global cell, not TLS — safe after db.put (H-3).
+    log_level::set_cached(Some(record));
+    log_level::refresh(now);
+    // Unconditional: the change itself must show even when it silences a category.
+    trace_println!(
+        "[!] log-level order sequence {} applied: {:?} until {:?}",
+        input.order.sequence,
+        input.order.levels,
+        input.order.until
+    );
+    Ok(proto::SetLogLevelOutput {
+        levels: input.order.levels,
+        until: input.order.until,
+        previous_sequence: current.map_or(0, |r| r.last_order_sequence),
+    })
+}
 
-    let mut signature = Vec::with_capacity(65);
-    signature.extend_from_slice(&sig_bytes);
-    signature.push(recovery_id.to_i32() as u8 + 27);
+// ========================================
+// Erase-on-tamper (failed-auth wipe, panic wipe, lock)
+// ========================================
 
-    Ok(proto::SignP256GrantSessionOutput { signature })
+fn tamper_guard() -> Result<tamper::TamperGuard> {
+    if let Some(guard) = tamper::cached() {
+        return Ok(guard);
+    }
+    let guard = load_tamper_guard()?;
+    tamper::set_cached(guard.clone());
+    Ok(guard)
 }
 
-fn jwt_hmac_verify(input: &proto::JwtHmacVerifyInput) -> Result<proto::JwtHmacVerifyOutput> {
+/// Straight from storage: another session may have applied an order since
+/// this instance filled its cache.
+fn load_tamper_guard() -> Result<tamper::TamperGuard> {
     let db = open_storage()?;
-    let store = JwtSecretStore::load(&db);
-    let valid = match store.find(&input.kid) {
-        Some(entry) => {
-            let mut mac = HmacSha256::new_from_slice(&entry.secret)
-                .map_err(|_| anyhow!("Invalid JWT HMAC secret"))?;
-            mac.update(&input.message);
-            mac.verify_slice(&input.expected_hmac).is_ok()
+    match db.get::<tamper::TamperGuard>(&tamper::STORE_ID.to_string()) {
+        Ok(guard) => Ok(guard),
+        Err(e) => {
+            let msg = e.to_string();
+            if !(msg.contains("ItemNotFound") || msg.contains("ITEM_NOT_FOUND")) {
+                // Fail closed, as for the deployment policy: an unreadable
+                // guard must not read as "unlocked".
+                return Err(anyhow!("tamper guard: secure storage error: {}", msg));
+            }
+            Ok(tamper::TamperGuard::new())
         }
-        None => false,
+    }
+}
+
+fn save_tamper_guard(db: &SecureStorageClient, guard: &tamper::TamperGuard) -> Result<()> {
+    db.put(guard)
+        .map_err(|e| anyhow!("Failed to save tamper guard: {}", e))?;
+    // Process-global cell, not TLS — safe after db.put (H-3).
+    tamper::set_cached(guard.clone());
+    Ok(())
+}
+
+fn enforce_tamper_lock(command: Command) -> Result<()> {
+    if proto::tamper::LOCKED_ALLOWED.contains(&command) {
+        return Ok(());
+    }
+    if let Some(lock) = tamper_guard()?.locked {
+        bail!(
+            "device was wiped ({:?}) and is locked since {}; re-provision and send an unlock order",
+            lock.reason,
+            lock.since
+        );
+    }
+    if tamper::sticky_lock() {
+        bail!(
+            "device was unlocked after a wipe; reopen the TA session (restart kms-api) to resume"
+        );
+    }
+    Ok(())
+}
+
+/// Persist the outcome of this command's passkey checks. Runs after the
+/// command, so the write follows every thread_local access it made.
+fn settle_tamper_guard<T>(result: Result<T>) -> Result<T> {
+    let settled = match tamper::take_auth_outcome() {
+        tamper::AuthOutcome::None => return result,
+        tamper::AuthOutcome::Passed => reset_failed_auth().map(|_| None),
+        tamper::AuthOutcome::Failed => record_failed_auth(),
     };
+    match (settled, result) {
+        (Ok(Some(reason)), Err(e)) => {
+            Err(e.context(format!("device wiped and locked ({:?})", reason)))
+        }
+        (Ok(Some(reason)), Ok(_)) => Err(anyhow!("device wiped and locked ({:?})", reason)),
+        (Ok(None), result) => result,
+        (Err(e), result) => {
+            ta_log!(Storage, Warn, "[!] tamper guard not updated: {:?}", e);
+            result
+        }
+    }
+}
 
-    Ok(proto::JwtHmacVerifyOutput { valid })
+fn reset_failed_auth() -> Result<()> {
+    if tamper_guard()?.consecutive_failures == 0 {
+        return Ok(());
+    }
+    let mut guard = load_tamper_guard()?;
+    guard.consecutive_failures = 0;
+    save_tamper_guard(&open_storage()?, &guard)
 }
 
-fn jwt_rotate_secret(input: &proto::JwtRotateSecretInput) -> Result<proto::JwtRotateSecretOutput> {
+/// Count a failed authentication; wipe once the limit is reached.
+fn record_failed_auth() -> Result<Option<proto::tamper::WipeReason>> {
+    let mut guard = load_tamper_guard()?;
+    let before = guard.consecutive_failures;
+    let reason = guard.record_failure();
     let db = open_storage()?;
-    let mut store = JwtSecretStore::load(&db);
-    let had_current = store.current().is_some();
+    match reason {
+        Some(reason) => {
+            wipe_device(&db, &mut guard, reason)?;
+            Ok(Some(reason))
+        }
+        None => {
+            if guard.consecutive_failures != before {
+                save_tamper_guard(&db, &guard)?;
+            }
+            Ok(None)
+        }
+    }
+}
 
-    if !input.force && !had_current {
-        // First-time init: create the initial secret without rotating
-        let current = store.ensure_current()?.clone();
-        store.save(&db)?;
-        return Ok(proto::JwtRotateSecretOutput {
-            new_kid: current.kid,
-            retired_kid: None,
-        });
+/// Erase every wallet and session key, then have the attestation PTA sign
+/// one certificate for the whole set. The lock is written first, so a wipe
+/// cut short leaves the survivors unusable rather than unlocked. Storage only,
+/// no thread_local access: this instance stays locked for good, so its
+/// wallet caches are never read again.
+fn wipe_device(
+    db: &SecureStorageClient,
+    guard: &mut tamper::TamperGuard,
+    reason: proto::tamper::WipeReason,
+) -> Result<proto::tamper::WipeProof> {
+    let next_epoch = rpmb_next_epoch()?;
+    tamper::stick_lock();
+    key_cache::clear();
+    let wiped_at = tee_unix_secs() as u64;
+    guard.locked = Some(proto::tamper::TamperLock {
+        reason,
+        since: wiped_at,
+    });
+    guard.consecutive_failures = 0;
+    save_tamper_guard(db, guard)?;
+
+    let wallet_ids: Vec<Uuid> = db.list_entries::<Wallet>()?.keys().cloned().collect();
+    for wallet_id in &wallet_ids {
+        let wallet = db.get::<Wallet>(wallet_id)?;
+        db.put(&wallet.erased())?;
+        db.delete_entry::<Wallet>(wallet_id)?;
     }
+    for key in db.list_entries::<P256SessionKey>()?.keys() {
+        db.delete_entry::<P256SessionKey>(key)?;
+    }
+    for key in db.list_entries::<session_scope::ScopedSessionKey>()?.keys() {
+        db.delete_entry::<session_scope::ScopedSessionKey>(key)?;
+    }
+    for key in db.list_entries::<dapp_storage::DappBlob>()?.keys() {
+        db.delete_entry::<dapp_storage::DappBlob>(key)?;
+    }
+    for key in db
+        .list_entries::<dapp_storage::DappNamespaceRecord>()?
+        .keys()
+    {
+        db.delete_entry::<dapp_storage::DappNamespaceRecord>(key)?;
+    }
+    for key in db.list_entries::<otp_vault::OtpSecretRecord>()?.keys() {
+        db.delete_entry::<otp_vault::OtpSecretRecord>(key)?;
+    }
+    rpmb_write_counter(next_epoch)?;
 
-    // Normal rotation: keep retired entry for 7-day overlap window.
-    // Force rotation: purge retired entries immediately (old JWTs become invalid).
-    let (new_kid, retired_kid) = store.rotate(input.force)?;
-    store.save(&db)?;
+    let (counter, present) = rpmb_read_counter_ex()?;
+    let certificate = proto::tamper::WipeCertificate {
+        version: proto::tamper::WIPE_CERT_VERSION,
+        reason,
+        wallets: wallet_ids.len() as u32,
+        wallet_set: proto::tamper::wallet_set_digest(&wallet_ids),
+        wiped_at,
+        rollback_counter: if present { Some(counter) } else { None },
+    };
+    let nonce = certificate.digest().to_vec();
+    let attestation = match attestation::get_attestation(&proto::GetAttestationInput { nonce }) {
+        Ok(evidence) => Some(evidence),
+        Err(e) => {
+            ta_log!(Storage, Warn, "[!] wipe certificate left unsigned: {:?}", e);
+            None
+        }
+    };
+    let proof = proto::tamper::WipeProof {
+        certificate,
+        attestation,
+    };
+    guard.last_wipe = Some(proof.clone());
+    save_tamper_guard(db, guard)?;
+    ta_log!(
+        Storage,
+        Warn,
+        "[!] device wiped ({:?}): {} wallets erased, RPMB epoch={}",
+        reason,
+        wallet_ids.len(),
+        next_epoch
+    );
+    Ok(proof)
+}
 
-    Ok(proto::JwtRotateSecretOutput {
-        new_kid,
-        retired_kid,
+fn tamper_order(input: &proto::TamperOrderInput) -> Result<proto::TamperOrderOutput> {
+    let signer = recover_eth_address(&input.order.digest(), &input.signature)?;
+    let policy = installed_deployment_policy()?;
+    let device = match input.order.device {
+        Some(_) => Some(attestation::device_fingerprint()?),
+        None => None,
+    };
+    let mut guard = load_tamper_guard()?;
+    tamper::check_order(
+        &guard,
+        policy
+            .as_ref()
+            .map(|r| (&r.signer, r.policy.deployment.as_str())),
+        device.as_ref(),
+        &input.order,
+        signer,
+    )
+    .map_err(|e| anyhow!("{}", e))?;
+
+    guard.last_order_sequence = input.order.sequence;
+    let db = open_storage()?;
+    let wiped = match input.order.action {
+        proto::tamper::TamperAction::SetFailedAuthLimit(limit) => {
+            guard.failed_auth_limit = limit;
+            guard.consecutive_failures = 0;
+            save_tamper_guard(&db, &guard)?;
+            false
+        }
+        proto::tamper::TamperAction::PanicWipe => {
+            wipe_device(&db, &mut guard, proto::tamper::WipeReason::PanicWipe)?;
+            true
+        }
+        proto::tamper::TamperAction::Unlock => {
+            guard.locked = None;
+            save_tamper_guard(&db, &guard)?;
+            false
+        }
+    };
+    ta_log!(
+        Policy,
+        Warn,
+        "[!] tamper order sequence {} applied: {:?}",
+        input.order.sequence,
+        input.order.action
+    );
+    Ok(proto::TamperOrderOutput {
+        status: guard.status(),
+        wiped,
     })
 }
 
-fn handle_invoke(command: Command, serialized_input: &[u8]) -> Result<Vec<u8>> {
-    fn process<T: serde::de::DeserializeOwned, U: serde::Serialize, F: Fn(&T) -> Result<U>>(
-        serialized_input: &[u8],
-        handler: F,
-    ) -> Result<Vec<u8>> {
-        let input: T = bincode::deserialize(serialized_input)?;
-        let output = handler(&input)?;
-        let serialized_output = bincode::serialize(&output)?;
-        Ok(serialized_output)
-    }
+fn get_tamper_status(_input: &proto::GetTamperStatusInput) -> Result<proto::TamperStatus> {
+    Ok(load_tamper_guard()?.status())
+}
 
-    match command {
-        Command::CreateWallet => process(serialized_input, create_wallet),
-        Command::RemoveWallet => process(serialized_input, remove_wallet),
-        Command::DeriveAddress => process(serialized_input, derive_address),
-        Command::SignTransaction => process(serialized_input, sign_transaction),
-        Command::SignMessage => process(serialized_input, sign_message),
-        Command::SignHash => process(serialized_input, sign_hash),
-        Command::DeriveAddressAuto => process(serialized_input, derive_address_auto),
-        Command::ExportPrivateKey => process(serialized_input, export_private_key),
-        // M-3: VerifyPasskey was an unconditional `valid:true` stub. Removing it
-        // from dispatch prevents it from ever being used as a fake auth oracle.
-        // Real authorization always goes through verify_passkey_for_wallet (p256-m).
-        Command::VerifyPasskey => bail!("VerifyPasskey is not supported (use a signing command which verifies the passkey)"),
-        Command::WarmupCache => process(serialized_input, warmup_cache),
-        Command::RegisterPasskeyTa => process(serialized_input, register_passkey_ta),
-        Command::CreateAgentKey => process(serialized_input, create_agent_key),
-        Command::SignAgentUserOp => process(serialized_input, sign_agent_user_op),
-        Command::JwtHmacVerify => process(serialized_input, jwt_hmac_verify),
-        Command::JwtRotateSecret => process(serialized_input, jwt_rotate_secret),
-        Command::SignTypedData => process(serialized_input, sign_typed_data),
-        Command::CreateP256SessionKey => process(serialized_input, create_p256_session_key),
-        Command::SignP256UserOp => process(serialized_input, sign_p256_user_op),
-        Command::DeleteP256SessionKey => process(serialized_input, delete_p256_session_key),
-        Command::SignGrantSession => process(serialized_input, sign_grant_session),
-        Command::SignP256GrantSession => process(serialized_input, sign_p256_grant_session),
-        Command::ForceRemoveWallet => process(serialized_input, force_remove_wallet),
-        Command::ReadRollbackCounter => process(serialized_input, read_rollback_counter),
-        Command::GetChallenge => process(serialized_input, get_challenge),
-        Command::GetAttestation => process(serialized_input, attestation::get_attestation),
-        Command::BlsGenKey => process(serialized_input, bls_gen_key),
-        Command::BlsSign => process(serialized_input, bls_sign),
-        Command::BlsPopSign => process(serialized_input, bls_pop_sign),
-        Command::BlsPubKey => process(serialized_input, bls_pubkey),
-        Command::BlsRemove => process(serialized_input, bls_remove),
-        Command::KeeperGenKey => process(serialized_input, keeper_gen_key),
-        Command::KeeperSign => process(serialized_input, keeper_sign),
-        Command::KeeperPubKey => process(serialized_input, keeper_pubkey),
-        _ => bail!("Unsupported command"),
-    }
-}
-
-// Output buffer size the host allocates for p1 (see ta_client.rs OUTPUT_MAX_SIZE).
+/// TEE system time as (seconds, millis) — only used for latency deltas.
+fn tee_system_time() -> (u32, u32) {
+    provider::system_time()
+}
+
+// Most the TA writes to p1; the CA sizes p1 per command from the same
+// module (proto::buffers::max_output_len).
 // C-4: the TA must never report an output length larger than the host buffer.
 // If it did, a host that trusts p2.a() (the returned length) and slices its
-// 4096-byte buffer with it would panic / read OOB. We bound both the success
-// payload and error messages to this size and signal SHORT_BUFFER explicitly.
-const OUTPUT_BUF_SIZE: usize = 4096;
+// buffer with it would panic / read OOB. We bound both the success payload
+// and error messages to this size and signal SHORT_BUFFER explicitly.
+const OUTPUT_BUF_SIZE: usize = proto::buffers::MAX_OUTPUT_LEN;
 
 #[ta_invoke_command]
 fn invoke_command(cmd_id: u32, params: &mut Parameters) -> optee_utee::Result<()> {
-    dbg_println!("[+] TA invoke command");
+    ta_log!(Session, Debug, "[+] TA invoke command");
     let mut p0 = unsafe { params.0.as_memref()? };
     let mut p1 = unsafe { params.1.as_memref()? };
     let mut p2 = unsafe { params.2.as_value()? };
+    // CA protocol version and scheduling hint (proto::PRIORITY_*). Commands
+    // run serially here, so the priority is informational until the TA grows
+    // its own queue.
+    let header = RequestHeader::from_hint(p2.b());
+    ta_log!(
+        Session,
+        Debug,
+        "[+] cmd {} priority {} CA protocol v{}",
+        cmd_id,
+        if header.priority == proto::PRIORITY_BATCH {
+            "batch"
+        } else {
+            "interactive"
+        },
+        header.protocol_version
+    );
+
+    let started = tee_system_time();
+    let out_len = p1.buffer().len().min(OUTPUT_BUF_SIZE);
+    let result = handle_invoke(cmd_id, header, p0.buffer(), &mut p1.buffer()[..out_len]);
+    let written = *result.as_ref().unwrap_or(&0);
+    let result = settle_tamper_guard(result);
+    telemetry::record(
+        cmd_id,
+        telemetry::elapsed_ms(started, tee_system_time()),
+        result.is_ok(),
+    );
+    if let Some((class, code)) = result.as_ref().err().and_then(crash_class) {
+        if let Err(e) = record_crash(cmd_id, class, code) {
+            ta_log!(Storage, Warn, "[!] crash record not saved: {:?}", e);
+        }
+    }
+    // Once per instance, after everything the command itself writes.
+    if storage_gc::take_armed() {
+        if let Err(e) = scavenge_after_load() {
+            ta_log!(Storage, Warn, "[!] orphan pass skipped: {:?}", e);
+        }
+    }
 
-    let output_vec = match handle_invoke(Command::from(cmd_id), p0.buffer()) {
-        Ok(output) => output,
+    match result {
+        Ok(len) => {
+            p2.set_a(len as u32);
+            Ok(())
+        }
+        // C-4: reject oversized output instead of letting the host slice past
+        // its buffer with a length it cannot satisfy. Return
+        // SHORT_BUFFER and set p2 to 0 so the host does not slice with a bogus
+        // length.
+        Err(e) if e.downcast_ref::<OutputTooLarge>().is_some() => {
+            p2.set_a(0);
+            Err(Error::new(ErrorKind::ShortBuffer))
+        }
         Err(e) => {
+            // The output is already in the shared buffer when the tamper guard
+            // turns a finished command into an error; never hand it over.
+            p1.buffer()[..written].fill(0);
             // C-4: cap the error message so it can never exceed the host buffer.
             let mut err_message = format!("{:?}", e).into_bytes();
             err_message.truncate(OUTPUT_BUF_SIZE);
@@ -2797,24 +6887,9 @@ fn invoke_command(cmd_id: u32, params: &mut Parameters) -> optee_utee::Result<()
                 .write(&err_message)
                 .map_err(|_| Error::new(ErrorKind::BadState))?;
             p2.set_a(err_message.len() as u32);
-            return Err(Error::new(ErrorKind::BadParameters));
+            Err(Error::new(ErrorKind::BadParameters))
         }
-    };
-
-    // C-4: reject oversized output instead of letting the host slice past its
-    // 4096-byte buffer with a length it cannot satisfy. Return SHORT_BUFFER and
-    // set p2 to 0 so the host does not slice with a bogus length.
-    if output_vec.len() > OUTPUT_BUF_SIZE || output_vec.len() > p1.buffer().len() {
-        p2.set_a(0);
-        return Err(Error::new(ErrorKind::ShortBuffer));
     }
-
-    p1.buffer()
-        .write(&output_vec)
-        .map_err(|_| Error::new(ErrorKind::BadState))?;
-    p2.set_a(output_vec.len() as u32);
-
-    Ok(())
 }
 
 // H-D: anti-rollback epoch_check boundary tests. This is the core security
@@ -2878,4 +6953,42 @@ mod rollback_tests {
     }
 }
 
+#[cfg(all(test, feature = "eth-wallet-compat"))]
+mod eth_wallet_compat_tests {
+    use super::*;
+    use proto::deployment_policy::{DeploymentPolicy, POLICY_FORMAT_VERSION};
+
+    #[test]
+    fn deployment_policy_refuses_legacy_sign() {
+        deployment_policy::set_cached(Some(deployment_policy::PolicyRecord {
+            store_id: deployment_policy::STORE_ID.into(),
+            signer: [1; 20],
+            policy: DeploymentPolicy {
+                format: POLICY_FORMAT_VERSION,
+                deployment: "rack-3".into(),
+                sequence: 1,
+                disabled_commands: vec![u32::from(Command::SignTransaction)],
+            },
+        }));
+        let legacy = bincode::serialize(&(
+            uuid::Uuid::from_bytes([0x22; 16]),
+            "m/44'/60'/0'/0/0".to_string(),
+            proto::EthTransaction {
+                chain_id: 1,
+                nonce: 0,
+                to: Some([0x11; 20]),
+                value: 1,
+                gas_price: 1,
+                gas: 21000,
+                data: Vec::new(),
+            },
+        ))
+        .unwrap();
+        let request = eth_wallet_compat::classify(Command::SignTransaction, &legacy)
+            .expect("an eth_wallet SignTransaction payload");
+        let err = handle_legacy(request).unwrap_err();
+        assert!(err.to_string().contains("disabled by deployment policy"), "{}", err);
+    }
+}
+
 include!(concat!(env!("OUT_DIR"), "/user_ta_header.rs"));
@@ -0,0 +1,116 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Hardware-wallet migration: the open session and the cross-check.
+//!
+//! The pending offer is a single secure-storage record like the replication
+//! offer, but it also remembers the xpub the user is migrating. The phrase is
+//! only accepted when it reproduces that xpub and every address the user
+//! confirmed on the hardware wallet — a corrupted or mistyped phrase, a
+//! BIP39 passphrase on the device, or a different account all stop here,
+//! before anything is stored.
+
+use proto::migration::{AccountXpub, ConfirmedAddress};
+use secure_db::Storable;
+use serde::{Deserialize, Serialize};
+
+pub const MIGRATION_STORE_ID: &str = "migration_offer";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PendingMigration {
+    pub store_id: String,
+    pub session_id: [u8; 16],
+    pub ephemeral_secret: [u8; 32],
+    pub ephemeral_pubkey: Vec<u8>,
+    pub account_xpub: AccountXpub,
+    pub created_at: u64,
+}
+
+impl Storable for PendingMigration {
+    type Key = String;
+    fn unique_id(&self) -> Self::Key {
+        self.store_id.clone()
+    }
+}
+
+/// Compare the account node recomputed from the phrase with the xpub, then
+/// each confirmed address with `derive(index)` (`m/44'/60'/0'/0/{index}`).
+pub fn cross_check<F>(
+    xpub: &AccountXpub,
+    root_pubkey: &[u8; 33],
+    root_chain: &[u8; 32],
+    confirmed: &[ConfirmedAddress],
+    mut derive: F,
+) -> Result<(), String>
+where
+    F: FnMut(u32) -> Result<[u8; 20], String>,
+{
+    if xpub.public_key[..] != root_pubkey[..] || xpub.chain_code != *root_chain {
+        return Err(
+            "the recovery phrase does not reproduce the imported xpub (wrong phrase, \
+             a BIP39 passphrase, or another account); nothing was stored"
+                .to_string(),
+        );
+    }
+    for c in confirmed {
+        if derive(c.index)? != c.address {
+            return Err(format!(
+                "address m/44'/60'/0'/0/{} derived from the phrase differs from the \
+                 confirmed one; nothing was stored",
+                c.index
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cross_check_needs_xpub_and_every_address() {
+        let xpub = AccountXpub {
+            public_key: vec![0x02; 33],
+            chain_code: [0xcc; 32],
+        };
+        let derive = |i: u32| Ok([i as u8; 20]);
+        let confirmed = [
+            ConfirmedAddress {
+                index: 0,
+                address: [0; 20],
+            },
+            ConfirmedAddress {
+                index: 7,
+                address: [7; 20],
+            },
+        ];
+        assert!(cross_check(&xpub, &[0x02; 33], &[0xcc; 32], &confirmed, derive).is_ok());
+        assert!(
+            cross_check(&xpub, &[0x03; 33], &[0xcc; 32], &confirmed, derive)
+                .unwrap_err()
+                .contains("xpub")
+        );
+        assert!(cross_check(&xpub, &[0x02; 33], &[0xcd; 32], &confirmed, derive).is_err());
+
+        let mut wrong = confirmed;
+        wrong[1].address = [8; 20];
+        assert!(cross_check(&xpub, &[0x02; 33], &[0xcc; 32], &wrong, derive)
+            .unwrap_err()
+            .contains("0/7"));
+    }
+}
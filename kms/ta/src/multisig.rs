@@ -0,0 +1,244 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+//! The wallet's co-signer policy (`proto::multisig`) and the signatures
+//! held for it. A held signature never leaves the TA until its operation
+//! has `required` approvals; changing the policy drops every held one.
+
+use anyhow::{anyhow, bail, Result};
+use proto::multisig::{MultiSigPending, MultiSigPolicy, MAX_PENDING, PENDING_TTL_SECS};
+use secure_db::Storable;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PendingOperation {
+    /// The payload the passkey confirmed; co-signers approve this.
+    pub operation: [u8; 32],
+    pub hd_path: String,
+    pub signed: Vec<u8>,
+    pub created_at: i64,
+    /// Indices into `MultiSigPolicy::cosigners`, sorted.
+    pub approved_by: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MultiSigState {
+    pub store_id: String,
+    /// None = no co-signers; the owner's passkey alone signs.
+    pub policy: Option<MultiSigPolicy>,
+    pub pending: Vec<PendingOperation>,
+}
+
+impl Storable for MultiSigState {
+    type Key = String;
+    fn unique_id(&self) -> Self::Key {
+        self.store_id.clone()
+    }
+}
+
+/// What one approval achieved.
+pub enum Approved {
+    Waiting {
+        approvals: u8,
+        required: u8,
+    },
+    /// The operation is removed; the caller persists, then releases.
+    Released {
+        op: PendingOperation,
+        required: u8,
+    },
+}
+
+impl MultiSigState {
+    pub fn store_id_for(wallet_id: &Uuid) -> String {
+        format!("multisig_{}", wallet_id)
+    }
+
+    pub fn empty(wallet_id: &Uuid) -> Self {
+        Self {
+            store_id: Self::store_id_for(wallet_id),
+            policy: None,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Whether a transaction sending `value` must be held.
+    pub fn holds(&self, value: u128) -> bool {
+        self.policy.as_ref().is_some_and(|p| p.applies_to(value))
+    }
+
+    /// Replace the policy; returns the old one and how many held
+    /// operations went with it.
+    pub fn set(&mut self, policy: Option<MultiSigPolicy>) -> (Option<MultiSigPolicy>, u32) {
+        let dropped = self.pending.len() as u32;
+        self.pending.clear();
+        (std::mem::replace(&mut self.policy, policy), dropped)
+    }
+
+    fn prune(&mut self, now: i64) {
+        self.pending
+            .retain(|p| (0..=PENDING_TTL_SECS).contains(&now.saturating_sub(p.created_at)));
+    }
+
+    /// Hold `signed` until the co-signers approve `operation`. Holding the
+    /// same operation again replaces the signature and keeps the approvals.
+    pub fn hold(
+        &mut self,
+        operation: [u8; 32],
+        hd_path: &str,
+        signed: Vec<u8>,
+        now: i64,
+    ) -> Result<MultiSigPending> {
+        let required = self
+            .policy
+            .as_ref()
+            .ok_or_else(|| anyhow!("no multi-sig policy is set for this wallet"))?
+            .required;
+        self.prune(now);
+        let approvals = match self.pending.iter_mut().find(|p| p.operation == operation) {
+            Some(p) => {
+                p.hd_path = hd_path.to_string();
+                p.signed = signed;
+                p.approved_by.len() as u8
+            }
+            None => {
+                if self.pending.len() >= MAX_PENDING {
+                    bail!(
+                        "{} transactions already wait for co-signer approval",
+                        MAX_PENDING
+                    );
+                }
+                self.pending.push(PendingOperation {
+                    operation,
+                    hd_path: hd_path.to_string(),
+                    signed,
+                    created_at: now,
+                    approved_by: Vec::new(),
+                });
+                0
+            }
+        };
+        Ok(MultiSigPending {
+            operation,
+            approvals,
+            required,
+        })
+    }
+
+    /// Record co-signer `index`'s (already verified) approval of
+    /// `operation`. Approving twice counts once.
+    pub fn approve(&mut self, operation: &[u8; 32], index: u8, now: i64) -> Result<Approved> {
+        let required = self
+            .policy
+            .as_ref()
+            .ok_or_else(|| anyhow!("no multi-sig policy is set for this wallet"))?
+            .required;
+        self.prune(now);
+        let at = self
+            .pending
+            .iter()
+            .position(|p| &p.operation == operation)
+            .ok_or_else(|| anyhow!("no pending multi-sig operation matches (expired?)"))?;
+        let op = &mut self.pending[at];
+        if let Err(i) = op.approved_by.binary_search(&index) {
+            op.approved_by.insert(i, index);
+        }
+        let approvals = op.approved_by.len() as u8;
+        if approvals < required {
+            return Ok(Approved::Waiting {
+                approvals,
+                required,
+            });
+        }
+        Ok(Approved::Released {
+            op: self.pending.remove(at),
+            required,
+        })
+    }
+
+    /// Position of `key` among the policy's co-signers.
+    pub fn cosigner_index(&self, key: &[u8]) -> Result<u8> {
+        self.policy
+            .as_ref()
+            .and_then(|p| p.cosigners.iter().position(|k| k == key))
+            .map(|i| i as u8)
+            .ok_or_else(|| anyhow!("not a co-signer of this wallet"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state() -> MultiSigState {
+        let mut state = MultiSigState::empty(&Uuid::from_bytes([5; 16]));
+        state.set(Some(MultiSigPolicy {
+            threshold_wei: 100,
+            required: 2,
+            cosigners: vec![vec![0x02; 33], vec![0x03; 33], vec![0x02, 1, 2]],
+        }));
+        state
+    }
+
+    #[test]
+    fn held_signature_is_released_at_the_threshold() {
+        let mut s = state();
+        assert!(!s.holds(100));
+        assert!(s.holds(101));
+        let pending = s
+            .hold([1; 32], "m/44'/60'/0'/0/0", vec![0xaa], 1_000)
+            .unwrap();
+        assert_eq!((pending.approvals, pending.required), (0, 2));
+        assert!(matches!(
+            s.approve(&[1; 32], 0, 1_010).unwrap(),
+            Approved::Waiting { approvals: 1, .. }
+        ));
+        // The same co-signer twice is still one approval.
+        assert!(matches!(
+            s.approve(&[1; 32], 0, 1_020).unwrap(),
+            Approved::Waiting { approvals: 1, .. }
+        ));
+        assert!(s.approve(&[2; 32], 1, 1_020).is_err());
+        match s.approve(&[1; 32], 1, 1_030).unwrap() {
+            Approved::Released { op, required } => {
+                assert_eq!(op.signed, vec![0xaa]);
+                assert_eq!(required, 2);
+            }
+            Approved::Waiting { .. } => panic!("not released"),
+        }
+        assert!(s.pending.is_empty());
+        assert_eq!(s.cosigner_index(&[0x03; 33]).unwrap(), 1);
+        assert!(s.cosigner_index(&[0x03; 32]).is_err());
+    }
+
+    #[test]
+    fn held_operations_expire_and_are_bounded() {
+        let mut s = state();
+        s.hold([1; 32], "m", vec![1], 0).unwrap();
+        s.approve(&[1; 32], 2, 10).unwrap();
+        // Held again: new signature, approvals kept.
+        assert_eq!(s.hold([1; 32], "m", vec![2], 20).unwrap().approvals, 1);
+        assert!(s.approve(&[1; 32], 0, PENDING_TTL_SECS + 1).is_err());
+
+        for i in 0..MAX_PENDING as u8 {
+            s.hold([i; 32], "m", vec![i], 100).unwrap();
+        }
+        assert!(s.hold([0xff; 32], "m", vec![], 100).is_err());
+        assert_eq!(s.set(None).1, MAX_PENDING as u32);
+        assert!(!s.holds(u128::MAX));
+    }
+}
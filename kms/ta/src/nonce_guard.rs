@@ -0,0 +1,49 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+//! The wallet's nonce ledger (`proto::nonce_guard`): whether tracking is on,
+//! the highest nonce signed per (chain, signer) and an armed override.
+
+use proto::nonce_guard::NonceLedger;
+use secure_db::Storable;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct NonceRecord {
+    pub store_id: String,
+    pub ledger: NonceLedger,
+}
+
+impl Storable for NonceRecord {
+    type Key = String;
+    fn unique_id(&self) -> Self::Key {
+        self.store_id.clone()
+    }
+}
+
+impl NonceRecord {
+    pub fn store_id_for(wallet_id: &Uuid) -> String {
+        format!("nonce_{}", wallet_id)
+    }
+
+    pub fn empty(wallet_id: &Uuid) -> Self {
+        Self {
+            store_id: Self::store_id_for(wallet_id),
+            ledger: NonceLedger::default(),
+        }
+    }
+}
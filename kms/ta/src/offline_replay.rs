@@ -0,0 +1,106 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Per-wallet log of offline request ids the TA has signed.
+//!
+//! An offline request is a bearer object (it travels by QR), so the TA must
+//! not sign the same one twice. Ids are kept only until their request
+//! expires — after that `OfflineSignRequest::check` refuses it anyway — which
+//! with `MAX_OFFLINE_TTL_SECS` keeps the log small.
+
+use secure_db::Storable;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Unexpired signed requests a wallet may have outstanding. Full = refuse
+/// (fail closed) rather than evict an id that could then be replayed.
+pub const MAX_LIVE_REQUESTS: usize = 64;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct OfflineReplayLog {
+    pub store_id: String,
+    /// (request_id, expires_at)
+    pub seen: Vec<([u8; 16], u64)>,
+}
+
+impl Storable for OfflineReplayLog {
+    type Key = String;
+    fn unique_id(&self) -> Self::Key {
+        self.store_id.clone()
+    }
+}
+
+impl OfflineReplayLog {
+    pub fn store_id_for(wallet_id: &Uuid) -> String {
+        format!("offline_{}", wallet_id)
+    }
+
+    pub fn empty(wallet_id: &Uuid) -> Self {
+        Self {
+            store_id: Self::store_id_for(wallet_id),
+            seen: Vec::new(),
+        }
+    }
+
+    /// Drop expired ids, then record `request_id` unless it is already there.
+    /// The caller persists the log before releasing the signature.
+    pub fn record(
+        &mut self,
+        request_id: [u8; 16],
+        expires_at: u64,
+        now: u64,
+    ) -> Result<(), &'static str> {
+        self.seen.retain(|(_, exp)| *exp > now);
+        if self.seen.iter().any(|(id, _)| *id == request_id) {
+            return Err("offline request already signed (replay)");
+        }
+        if self.seen.len() >= MAX_LIVE_REQUESTS {
+            return Err("too many unexpired offline requests for this wallet");
+        }
+        self.seen.push((request_id, expires_at));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replay_refused_until_expiry() {
+        let mut log = OfflineReplayLog::empty(&Uuid::from_bytes([3; 16]));
+        assert!(log.record([1; 16], 200, 100).is_ok());
+        assert!(log.record([1; 16], 200, 150).is_err());
+        assert!(log.record([2; 16], 300, 150).is_ok());
+        // [1] has expired and is pruned; [2] is still live
+        assert!(log.record([3; 16], 400, 250).is_ok());
+        assert_eq!(log.seen.len(), 2);
+    }
+
+    #[test]
+    fn full_log_fails_closed() {
+        let mut log = OfflineReplayLog::empty(&Uuid::from_bytes([3; 16]));
+        for i in 0..MAX_LIVE_REQUESTS {
+            let mut id = [0u8; 16];
+            id[0] = i as u8;
+            log.record(id, 1000, 0).unwrap();
+        }
+        assert!(log.record([0xff; 16], 1000, 0).is_err());
+        // once they expire there is room again
+        assert!(log.record([0xff; 16], 2000, 1000).is_ok());
+    }
+}
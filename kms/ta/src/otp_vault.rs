@@ -0,0 +1,134 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! TOTP/HOTP secrets in secure storage, and the HMAC that turns them into
+//! codes. One [`OtpSecretRecord`] per (wallet, label); the secret never
+//! leaves the TA. Parameters, truncation and the counter rule are
+//! `proto::otp`.
+
+use hmac::{Hmac, Mac};
+use proto::otp::{OtpAlgorithm, OtpParams};
+use secure_db::Storable;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct OtpSecretRecord {
+    pub store_id: String,
+    pub wallet_id: Uuid,
+    pub label: String,
+    pub secret: Vec<u8>,
+    pub params: OtpParams,
+    /// Time step or HOTP counter of the last code issued; None until then.
+    pub last_counter: Option<u64>,
+}
+
+impl Storable for OtpSecretRecord {
+    type Key = String;
+    fn unique_id(&self) -> Self::Key {
+        self.store_id.clone()
+    }
+}
+
+impl OtpSecretRecord {
+    pub fn store_id_for(wallet_id: &Uuid, label: &str) -> String {
+        format!("otp_{}_{}", wallet_id, label)
+    }
+}
+
+/// Record ids of one wallet, out of every OTP record id stored.
+pub fn wallet_entries<'a, I>(wallet_id: &Uuid, ids: I) -> Vec<String>
+where
+    I: IntoIterator<Item = &'a String>,
+{
+    let prefix = format!("otp_{}_", wallet_id);
+    let mut ids: Vec<String> = ids
+        .into_iter()
+        .filter(|id| id.starts_with(&prefix))
+        .cloned()
+        .collect();
+    ids.sort();
+    ids
+}
+
+/// RFC 4226 HOTP value of `secret` at `counter` (TOTP passes the time step).
+pub fn hotp(algorithm: OtpAlgorithm, secret: &[u8], counter: u64, digits: u8) -> u32 {
+    let msg = counter.to_be_bytes();
+    // HMAC accepts keys of any length; new_from_slice cannot fail.
+    let mac = match algorithm {
+        OtpAlgorithm::Sha1 => mac_bytes::<Hmac<sha1::Sha1>>(secret, &msg),
+        OtpAlgorithm::Sha256 => mac_bytes::<Hmac<sha2::Sha256>>(secret, &msg),
+        OtpAlgorithm::Sha512 => mac_bytes::<Hmac<sha2::Sha512>>(secret, &msg),
+    };
+    proto::otp::truncate(&mac, digits)
+}
+
+fn mac_bytes<M: Mac + hmac::digest::KeyInit>(key: &[u8], msg: &[u8]) -> Vec<u8> {
+    let mut m = <M as Mac>::new_from_slice(key).expect("HMAC takes any key length");
+    m.update(msg);
+    m.finalize().into_bytes().to_vec()
+}
+
+/// Accepts the same parameters the record will be stored with.
+pub fn check_enrollment(
+    label: &str,
+    secret: &[u8],
+    params: &OtpParams,
+) -> Result<(), &'static str> {
+    proto::otp::validate_label(label)?;
+    proto::otp::validate_secret(secret)?;
+    params.validate()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 6238 Appendix B: the seed is "1234567890" repeated to the hash size.
+    fn seed(len: usize) -> Vec<u8> {
+        b"1234567890".iter().cycle().take(len).copied().collect()
+    }
+
+    #[test]
+    fn rfc6238_vectors() {
+        let step = |t: u64| t / 30;
+        assert_eq!(hotp(OtpAlgorithm::Sha1, &seed(20), step(59), 8), 94287082);
+        assert_eq!(hotp(OtpAlgorithm::Sha256, &seed(32), step(59), 8), 46119246);
+        assert_eq!(hotp(OtpAlgorithm::Sha512, &seed(64), step(59), 8), 90693936);
+        assert_eq!(
+            hotp(OtpAlgorithm::Sha1, &seed(20), step(1111111109), 8),
+            7081804
+        );
+        // RFC 4226 Appendix D, counter 1.
+        assert_eq!(hotp(OtpAlgorithm::Sha1, &seed(20), 1, 6), 287082);
+    }
+
+    #[test]
+    fn entries_are_found_per_wallet() {
+        let a = Uuid::parse_str("4319f351-0b24-4097-b659-80ee4f824cdd").unwrap();
+        let b = Uuid::parse_str("a1b2c3d4-e5f6-7890-abcd-ef1234567890").unwrap();
+        let ids = vec![
+            OtpSecretRecord::store_id_for(&a, "gitlab"),
+            OtpSecretRecord::store_id_for(&b, "github"),
+            OtpSecretRecord::store_id_for(&a, "aws"),
+        ];
+        assert_eq!(
+            wallet_entries(&a, &ids),
+            vec![ids[2].clone(), ids[0].clone()]
+        );
+    }
+}
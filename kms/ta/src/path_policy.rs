@@ -0,0 +1,127 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Per-wallet derivation-path allow-list.
+//!
+//! `Wallet::derive_key` is the only way to a child key, so checking the
+//! policy there covers addresses, transaction / hash / typed-data signing,
+//! ECDH and key export alike: a compromised CA cannot reach an account the
+//! owner did not allow, whatever command it sends. The check runs before the
+//! child-key cache, so a key derived before the policy changed is not served
+//! from it either.
+//!
+//! Policies are cached per TA instance and read from secure storage on a
+//! miss; a read error other than ItemNotFound refuses the derivation. As
+//! with the deployment policy, another session's change is seen by
+//! instances opened after it.
+
+use proto::hd_path::PathPattern;
+use secure_db::Storable;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+use crate::ta_global::TaGlobal;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PathPolicy {
+    pub store_id: String,
+    /// Sorted, no duplicates. Empty = any path.
+    pub patterns: Vec<PathPattern>,
+}
+
+impl Storable for PathPolicy {
+    type Key = String;
+    fn unique_id(&self) -> Self::Key {
+        self.store_id.clone()
+    }
+}
+
+impl PathPolicy {
+    pub fn store_id_for(wallet_id: &Uuid) -> String {
+        format!("pathpol_{}", wallet_id)
+    }
+
+    pub fn empty(wallet_id: &Uuid) -> Self {
+        Self {
+            store_id: Self::store_id_for(wallet_id),
+            patterns: Vec::new(),
+        }
+    }
+
+    pub fn set(&mut self, patterns: &[PathPattern]) -> Result<(), String> {
+        proto::hd_path::validate_path_patterns(patterns)?;
+        let mut list = patterns.to_vec();
+        list.sort_unstable();
+        list.dedup();
+        self.patterns = list;
+        Ok(())
+    }
+}
+
+/// Passkey commitment for widening the policy, over the list as sent.
+pub fn commitment(wallet_id: &Uuid, patterns: &[PathPattern]) -> [u8; 32] {
+    let mut h = Sha256::new();
+    h.update(b"AA-PATH-POLICY-v1");
+    h.update(wallet_id.as_bytes());
+    h.update((patterns.len() as u32).to_be_bytes());
+    for p in patterns {
+        h.update(p.to_string().as_bytes());
+        h.update([0u8]);
+    }
+    h.finalize().into()
+}
+
+/// Wallets whose policy this instance holds; cleared when full.
+const MAX_CACHED: usize = 64;
+
+static CACHE: TaGlobal<BTreeMap<Uuid, Vec<PathPattern>>> = TaGlobal::new(BTreeMap::new());
+
+pub fn cached(wallet_id: &Uuid) -> Option<Vec<PathPattern>> {
+    CACHE.with(|c| c.get(wallet_id).cloned())
+}
+
+pub fn set_cached(wallet_id: &Uuid, patterns: Vec<PathPattern>) {
+    CACHE.with(|c| {
+        if c.len() >= MAX_CACHED && !c.contains_key(wallet_id) {
+            c.clear();
+        }
+        c.insert(*wallet_id, patterns);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proto::hd_path::parse_path_pattern;
+
+    #[test]
+    fn set_sorts_dedups_and_validates() {
+        let w = Uuid::from_bytes([7; 16]);
+        let first = parse_path_pattern("m/44'/60'/0'/0/*").unwrap();
+        let few = parse_path_pattern("m/44'/60'/0'/0/0-9").unwrap();
+        let mut policy = PathPolicy::empty(&w);
+        policy.set(&[first, few, first]).unwrap();
+        assert_eq!(policy.patterns.len(), 2);
+        assert!(policy
+            .set(&vec![few; proto::hd_path::MAX_PATH_PATTERNS + 1])
+            .is_err());
+        assert_ne!(commitment(&w, &[first]), commitment(&w, &[few]));
+        assert_ne!(commitment(&w, &[first]), commitment(&w, &[first, few]));
+    }
+}
@@ -0,0 +1,112 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! The TA's clock and RNG. Every read of time or randomness in the TA goes
+//! through [`unix_secs`], [`system_time`] or [`fill`].
+//!
+//! Normally these are the TEE's. A build with the `deterministic` feature
+//! (TEST ONLY) swaps in `proto::provider::FixedClock` at
+//! `FixedClock::EPOCH` and a `SeededRng` on [`TEST_SEED`], and the
+//! dispatcher moves the clock one second per command — so a scripted run
+//! against a fresh session creates the same wallets, signatures and audit
+//! records every time. Each session is its own TA instance, so each starts
+//! from the epoch and the seed.
+
+use proto::provider::{Clock, Rng};
+
+#[cfg(not(feature = "deterministic"))]
+pub struct TeeClock;
+
+#[cfg(not(feature = "deterministic"))]
+impl Clock for TeeClock {
+    fn unix_secs(&self) -> u64 {
+        let mut t = optee_utee::Time::new();
+        t.ree_time();
+        t.seconds as u64
+    }
+
+    fn system_time(&self) -> (u32, u32) {
+        let mut t = optee_utee::Time::new();
+        t.system_time();
+        (t.seconds, t.millis)
+    }
+}
+
+#[cfg(not(feature = "deterministic"))]
+pub struct TeeRng;
+
+#[cfg(not(feature = "deterministic"))]
+impl Rng for TeeRng {
+    fn fill(&mut self, buf: &mut [u8]) {
+        optee_utee::Random::generate(buf);
+    }
+}
+
+#[cfg(not(feature = "deterministic"))]
+fn with_provider<R>(f: impl FnOnce(&mut dyn Clock, &mut dyn Rng) -> R) -> R {
+    f(&mut TeeClock, &mut TeeRng)
+}
+
+/// Public on purpose: anyone can recompute every key a test TA makes.
+#[cfg(feature = "deterministic")]
+pub const TEST_SEED: [u8; 32] = *b"AirAccount deterministic TA seed";
+
+#[cfg(feature = "deterministic")]
+static DETERMINISTIC: crate::ta_global::TaGlobal<(
+    proto::provider::FixedClock,
+    proto::provider::SeededRng,
+)> = crate::ta_global::TaGlobal::new((
+    proto::provider::FixedClock::new(proto::provider::FixedClock::EPOCH),
+    proto::provider::SeededRng::new(TEST_SEED),
+));
+
+#[cfg(feature = "deterministic")]
+fn with_provider<R>(f: impl FnOnce(&mut dyn Clock, &mut dyn Rng) -> R) -> R {
+    DETERMINISTIC.with(|(clock, rng)| f(clock, rng))
+}
+
+/// UNIX seconds from the REE clock.
+pub fn unix_secs() -> u64 {
+    with_provider(|clock, _| clock.unix_secs())
+}
+
+/// TEE system time as (seconds, millis).
+pub fn system_time() -> (u32, u32) {
+    with_provider(|clock, _| clock.system_time())
+}
+
+/// Fill `buf` with random bytes.
+pub fn fill(buf: &mut [u8]) {
+    with_provider(|_, rng| rng.fill(buf))
+}
+
+/// [`fill`] as a `proto::provider::Rng`, for proto code that takes one.
+#[cfg(feature = "threshold-ecdsa")]
+pub struct ProviderRng;
+
+#[cfg(feature = "threshold-ecdsa")]
+impl Rng for ProviderRng {
+    fn fill(&mut self, buf: &mut [u8]) {
+        fill(buf)
+    }
+}
+
+/// Called by the dispatcher once per command.
+pub fn tick() {
+    #[cfg(feature = "deterministic")]
+    DETERMINISTIC.with(|(clock, _)| clock.advance(1));
+}
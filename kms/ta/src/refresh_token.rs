@@ -0,0 +1,53 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Secure-storage record for an agent credential's refresh-token family.
+//!
+//! One family per (wallet, agent index); opening a new one with a passkey
+//! replaces the old. The rotation rules are `proto::refresh_token`, shared
+//! with the CA's tests.
+
+use proto::refresh_token::RefreshFamily;
+use secure_db::Storable;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RefreshRecord {
+    pub store_id: String,
+    pub family: RefreshFamily,
+}
+
+impl Storable for RefreshRecord {
+    type Key = String;
+    fn unique_id(&self) -> Self::Key {
+        self.store_id.clone()
+    }
+}
+
+impl RefreshRecord {
+    pub fn store_id_for(wallet_id: &Uuid, agent_index: u32) -> String {
+        format!("refresh_{}_{}", wallet_id, agent_index)
+    }
+
+    pub fn new(wallet_id: &Uuid, family: RefreshFamily) -> Self {
+        Self {
+            store_id: Self::store_id_for(wallet_id, family.agent_index),
+            family,
+        }
+    }
+}
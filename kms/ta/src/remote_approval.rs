@@ -0,0 +1,154 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! The phone paired as a wallet's second approver, and the approval it
+//! armed. Kept in secure storage rather than in the instance, so an
+//! approval armed through one TA session is seen by the Sign in another.
+
+use anyhow::{anyhow, Result};
+use proto::remote_approval::{APPROVAL_SIGNATURE_LEN, APPROVAL_TTL_SECS};
+use secp256k1::{ecdsa::Signature, Message, PublicKey, Secp256k1};
+use secure_db::Storable;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PendingApproval {
+    pub payload: [u8; 32],
+    pub armed_at: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RemoteApprover {
+    pub store_id: String,
+    /// Compressed secp256k1 key; None = unpaired, the passkey alone signs.
+    pub key: Option<Vec<u8>>,
+    /// One at a time, as with allowance overrides.
+    pub pending: Option<PendingApproval>,
+}
+
+impl Storable for RemoteApprover {
+    type Key = String;
+    fn unique_id(&self) -> Self::Key {
+        self.store_id.clone()
+    }
+}
+
+impl RemoteApprover {
+    pub fn store_id_for(wallet_id: &Uuid) -> String {
+        format!("approver_{}", wallet_id)
+    }
+
+    pub fn empty(wallet_id: &Uuid) -> Self {
+        Self {
+            store_id: Self::store_id_for(wallet_id),
+            key: None,
+            pending: None,
+        }
+    }
+
+    /// Replace the phone; an approval the old one armed goes with it.
+    pub fn pair(&mut self, key: Option<Vec<u8>>) -> Option<Vec<u8>> {
+        self.pending = None;
+        std::mem::replace(&mut self.key, key)
+    }
+
+    pub fn arm(&mut self, payload: [u8; 32], now: i64) {
+        self.pending = Some(PendingApproval {
+            payload,
+            armed_at: now,
+        });
+    }
+
+    /// A fresh approval of exactly `payload` is armed.
+    pub fn approves(&self, payload: &[u8; 32], now: i64) -> bool {
+        match &self.pending {
+            Some(p) => {
+                &p.payload == payload
+                    && (0..=APPROVAL_TTL_SECS).contains(&now.saturating_sub(p.armed_at))
+            }
+            None => false,
+        }
+    }
+
+    /// Consume it (the caller persists). A mismatched approval stays armed
+    /// so a wrong request cannot burn the phone's answer.
+    pub fn take(&mut self, payload: &[u8; 32], now: i64) -> bool {
+        let ok = self.approves(payload, now);
+        if ok {
+            self.pending = None;
+        }
+        ok
+    }
+}
+
+/// Parse the key the owner pairs, so a bad one fails at pairing time.
+pub fn check_key(key: &[u8]) -> Result<()> {
+    proto::remote_approval::validate_approver_key(key).map_err(|e| anyhow!(e))?;
+    PublicKey::from_slice(key).map_err(|_| anyhow!("approver key is not a secp256k1 point"))?;
+    Ok(())
+}
+
+/// Compact r ‖ s by `key` over `digest`; a high-S signature is accepted.
+pub fn verify(key: &[u8], digest: &[u8; 32], signature: &[u8]) -> Result<()> {
+    if signature.len() != APPROVAL_SIGNATURE_LEN {
+        return Err(anyhow!("approval signature must be 64 bytes (r ‖ s)"));
+    }
+    let key = PublicKey::from_slice(key).map_err(|_| anyhow!("stored approver key is invalid"))?;
+    let mut sig = Signature::from_compact(signature)
+        .map_err(|_| anyhow!("approval signature is malformed"))?;
+    sig.normalize_s();
+    Secp256k1::verification_only()
+        .verify_ecdsa(&Message::from_slice(digest)?, &sig, &key)
+        .map_err(|_| anyhow!("approval signature does not verify with the paired key"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn approval_is_one_shot_fresh_and_exact() {
+        let w = Uuid::from_bytes([3; 16]);
+        let mut approver = RemoteApprover::empty(&w);
+        assert_eq!(approver.pair(Some(vec![0x02; 33])), None);
+        approver.arm([1; 32], 1_000);
+        assert!(!approver.take(&[2; 32], 1_010));
+        assert!(!approver.approves(&[1; 32], 1_000 + APPROVAL_TTL_SECS + 1));
+        assert!(approver.take(&[1; 32], 1_010));
+        assert!(!approver.take(&[1; 32], 1_010));
+
+        approver.arm([1; 32], 1_000);
+        assert_eq!(approver.pair(None), Some(vec![0x02; 33]));
+        assert!(!approver.approves(&[1; 32], 1_010));
+    }
+
+    #[test]
+    fn verifies_a_compact_signature() {
+        let secp = Secp256k1::new();
+        let sk = secp256k1::SecretKey::from_slice(&[0x42; 32]).unwrap();
+        let key = PublicKey::from_secret_key(&secp, &sk).serialize();
+        check_key(&key).unwrap();
+        let digest = [9u8; 32];
+        let sig = secp
+            .sign_ecdsa(&Message::from_slice(&digest).unwrap(), &sk)
+            .serialize_compact();
+        verify(&key, &digest, &sig).unwrap();
+        assert!(verify(&key, &[8u8; 32], &sig).is_err());
+        assert!(verify(&key, &digest, &sig[..63]).is_err());
+    }
+}
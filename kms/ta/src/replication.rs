@@ -0,0 +1,164 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Seed replication transport: ECDH key agreement and encrypt-then-MAC.
+//!
+//! Both sides run secp256k1 ECDH between their ephemeral keys, extract with
+//! HMAC-SHA256 keyed by the session id and expand into separate encryption
+//! and MAC keys bound to both public keys. The cipher is an HMAC-SHA256
+//! counter-mode keystream — built from primitives the TA already links, and
+//! safe here because each key pair encrypts exactly one package. The pending
+//! offer (the target's ephemeral secret) is a single secure-storage record,
+//! replaced by every new offer and deleted by the import that uses it.
+
+use hmac::{Hmac, Mac};
+use secp256k1::ecdh::SharedSecret;
+use secp256k1::{PublicKey, SecretKey};
+use secure_db::Storable;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub const OFFER_STORE_ID: &str = "replication_offer";
+
+/// The target's side of an open replication session.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PendingOffer {
+    pub store_id: String,
+    pub session_id: [u8; 16],
+    pub ephemeral_secret: [u8; 32],
+    pub ephemeral_pubkey: Vec<u8>,
+    pub created_at: u64,
+    /// This TA's measurement as reported by the PTA when the offer was made.
+    pub measurement: Vec<u8>,
+}
+
+impl Storable for PendingOffer {
+    type Key = String;
+    fn unique_id(&self) -> Self::Key {
+        self.store_id.clone()
+    }
+}
+
+pub struct SessionKeys {
+    enc: [u8; 32],
+    mac: [u8; 32],
+}
+
+fn hmac(key: &[u8], parts: &[&[u8]]) -> [u8; 32] {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts any key length");
+    for part in parts {
+        mac.update(part);
+    }
+    mac.finalize().into_bytes().into()
+}
+
+/// Keys for one session. Both sides pass the same `target_pub` / `source_pub`
+/// (compressed), so the result is the same whichever secret is local.
+pub fn session_keys(
+    own_secret: &SecretKey,
+    peer_pub: &PublicKey,
+    session_id: &[u8; 16],
+    target_pub: &[u8],
+    source_pub: &[u8],
+) -> SessionKeys {
+    let shared = SharedSecret::new(peer_pub, own_secret).secret_bytes();
+    let prk = hmac(session_id, &[&shared]);
+    SessionKeys {
+        enc: hmac(&prk, &[b"enc", target_pub, source_pub, &[1]]),
+        mac: hmac(&prk, &[b"mac", target_pub, source_pub, &[2]]),
+    }
+}
+
+fn apply_keystream(key: &[u8; 32], data: &mut [u8]) {
+    for (i, chunk) in data.chunks_mut(32).enumerate() {
+        let block = hmac(key, &[&(i as u64).to_be_bytes()]);
+        for (b, k) in chunk.iter_mut().zip(block.iter()) {
+            *b ^= k;
+        }
+    }
+}
+
+/// Encrypt `plaintext`; the tag covers `aad` ‖ ciphertext.
+pub fn seal(keys: &SessionKeys, aad: &[u8], plaintext: &[u8]) -> (Vec<u8>, [u8; 32]) {
+    let mut ciphertext = plaintext.to_vec();
+    apply_keystream(&keys.enc, &mut ciphertext);
+    let tag = hmac(&keys.mac, &[aad, &ciphertext]);
+    (ciphertext, tag)
+}
+
+/// Check the tag (constant time), then decrypt.
+pub fn open(
+    keys: &SessionKeys,
+    aad: &[u8],
+    ciphertext: &[u8],
+    tag: &[u8; 32],
+) -> Result<Vec<u8>, &'static str> {
+    let mut mac = HmacSha256::new_from_slice(&keys.mac).expect("HMAC accepts any key length");
+    mac.update(aad);
+    mac.update(ciphertext);
+    mac.verify_slice(tag)
+        .map_err(|_| "replication package failed authentication")?;
+    let mut plaintext = ciphertext.to_vec();
+    apply_keystream(&keys.enc, &mut plaintext);
+    Ok(plaintext)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use secp256k1::Secp256k1;
+
+    fn keypair(byte: u8) -> (SecretKey, PublicKey) {
+        let sk = SecretKey::from_slice(&[byte; 32]).unwrap();
+        (sk, PublicKey::from_secret_key(&Secp256k1::new(), &sk))
+    }
+
+    #[test]
+    fn both_sides_agree_and_roundtrip() {
+        let (target_sk, target_pk) = keypair(0x11);
+        let (source_sk, source_pk) = keypair(0x22);
+        let (t, s) = (target_pk.serialize(), source_pk.serialize());
+        let session = [0x5e; 16];
+        let at_source = session_keys(&source_sk, &target_pk, &session, &t, &s);
+        let at_target = session_keys(&target_sk, &source_pk, &session, &t, &s);
+
+        let wallet = vec![0xab; 100];
+        let (ciphertext, tag) = seal(&at_source, b"header", &wallet);
+        assert_ne!(ciphertext, wallet);
+        assert_eq!(
+            open(&at_target, b"header", &ciphertext, &tag).unwrap(),
+            wallet
+        );
+    }
+
+    #[test]
+    fn tampering_and_wrong_session_fail() {
+        let (target_sk, target_pk) = keypair(0x11);
+        let (source_sk, source_pk) = keypair(0x22);
+        let (t, s) = (target_pk.serialize(), source_pk.serialize());
+        let keys = session_keys(&source_sk, &target_pk, &[0x5e; 16], &t, &s);
+        let (mut ciphertext, tag) = seal(&keys, b"header", &[0xab; 40]);
+
+        assert!(open(&keys, b"other header", &ciphertext, &tag).is_err());
+        let other_session = session_keys(&target_sk, &source_pk, &[0x5f; 16], &t, &s);
+        assert!(open(&other_session, b"header", &ciphertext, &tag).is_err());
+        ciphertext[39] ^= 1;
+        assert!(open(&keys, b"header", &ciphertext, &tag).is_err());
+    }
+}
@@ -0,0 +1,77 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! On-device confirmation through the board's trusted-UI PTA (`secure-display`
+//! builds only).
+//!
+//! The PTA is not part of upstream OP-TEE: a board that drives its screen and
+//! buttons from secure world (an SPI panel and two GPIOs assigned to the
+//! secure side) ships it in its OP-TEE build. One command:
+//!
+//!  * `CONFIRM` (0x0): params = (MEMREF_IN screen text, VALUE_IN a = timeout
+//!    seconds, VALUE_OUT a = verdict). The text is ASCII lines separated by
+//!    `\n`, at most `proto::secure_display::COLUMNS` wide. The PTA shows every
+//!    page before it accepts the confirm button, and answers 1 = approved,
+//!    2 = rejected, 3 = timed out.
+//!
+//! The panel, the GPIO numbers and the debounce are the PTA's business; the
+//! TA only trusts that the REE can neither draw on the panel nor press the
+//! button.
+
+use anyhow::{anyhow, bail, Result};
+use optee_utee::{ParamIndex, TaSessionBuilder, TeeParams, Uuid};
+use proto::secure_display::{Screen, Verdict, CONFIRM_TIMEOUT_SECS};
+
+const PTA_SECURE_DISPLAY_UUID: &str = "b866a232-a152-4d2d-8cc0-941a9079d951";
+
+const PTA_SECURE_DISPLAY_CONFIRM: u32 = 0x0;
+
+/// Show `screen` and wait for the user. Ok only on an approval: a
+/// rejection, a timeout or a missing PTA all keep the signature back.
+pub fn confirm(screen: &Screen) -> Result<()> {
+    let text = screen.encode().map_err(|e| anyhow!("{}", e))?;
+    let pta_uuid = Uuid::parse_str(PTA_SECURE_DISPLAY_UUID)
+        .map_err(|e| anyhow!("invalid secure display PTA UUID: {:?}", e))?;
+    let mut session = TaSessionBuilder::new(pta_uuid).build().map_err(|e| {
+        anyhow!(
+            "open secure display PTA session failed: {:?} (does this board ship it?)",
+            e
+        )
+    })?;
+
+    let mut params = TeeParams::new()
+        .with_memref_in(ParamIndex::Arg0, &text)
+        .with_value_in(ParamIndex::Arg1, CONFIRM_TIMEOUT_SECS, 0)
+        .with_value_out(ParamIndex::Arg2, 0, 0);
+    session
+        .invoke_command(PTA_SECURE_DISPLAY_CONFIRM, &mut params)
+        .map_err(|e| anyhow!("secure display CONFIRM failed: {:?}", e))?;
+    let code = params[ParamIndex::Arg2]
+        .output_value()
+        .ok_or_else(|| anyhow!("secure display returned no verdict"))?
+        .0;
+
+    match Verdict::from_code(code) {
+        Some(Verdict::Approved) => Ok(()),
+        Some(Verdict::Rejected) => bail!("transaction rejected on the device"),
+        Some(Verdict::TimedOut) => bail!(
+            "no confirmation on the device within {}s",
+            CONFIRM_TIMEOUT_SECS
+        ),
+        None => bail!("secure display returned unknown verdict {}", code),
+    }
+}
@@ -0,0 +1,130 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Known-answer tests for `SelfTest`.
+//!
+//! Each check runs the code the TA signs with over a published input and
+//! compares the output byte for byte: a miscompiled or corrupted image, or a
+//! faulty crypto path on this board, fails here before it signs for a user.
+//! The answers come from the same external vectors as
+//! `conformance/vectors/derivation.txt`; none are secret and nothing touches
+//! a wallet. The RNG and storage checks need OP-TEE and live in `main.rs`.
+
+use proto::SelfTestCheck;
+use sha2::Sha256;
+use sha3::{Digest, Keccak256};
+
+use crate::bip32_secp;
+
+/// BIP32 test vector 1.
+const BIP32_SEED: &str = "000102030405060708090a0b0c0d0e0f";
+const BIP32_MASTER_KEY: &str = "e8f32e723decf4051aefac8e2c93c9c5b214313817cdb01a1494b917c8436b35";
+const BIP32_M_0H_KEY: &str = "edb2e14f9ee77d26dd93b4ecede8d16ed408ce149b6cd80b0715a2d911a0afea";
+
+/// BIP39 seed of "test test … junk" (the Hardhat/Anvil mnemonic), so the
+/// check needs no PBKDF2 of its own.
+const ETH_SEED: &str = "9dfc3c64c2f8bede1533b6a79f8570e5943e0b8fd1cf77107adf7b72cef42185\
+                        d564a3aee24cab43f80e3c4538087d70fc824eabbad596a23c97b6ee8322ccc0";
+const ETH_KEY_0_0: &str = "ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+const ETH_ADDRESS_0_0: &str = "f39fd6e51aad88f6f4ce6ab8827279cfffb92266";
+
+const SIGN_DIGEST: &str = "a0dc65ffca799873cbea0ac274015b9526505daaaed385155425f7337704883e";
+/// Private key 1 over `SIGN_DIGEST`: RFC 6979 nonce, low-S, v = 27/28.
+const SIGN_SIGNATURE: &str = "934b1ea10a4b3c1757e2b0c017d0b6143ce3c9a7e6a4a49860d7a6ab210ee3d8\
+                              2442ce9d2b916064108014783e923ec36b49743e2ffa1c4496f01a512aafd9e51c";
+
+/// The pure checks, in a fixed order.
+pub fn known_answer_tests() -> Vec<SelfTestCheck> {
+    vec![
+        check("sha256", || {
+            expect(
+                &Sha256::digest(b"abc"),
+                "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            )
+        }),
+        check("keccak256", || {
+            expect(
+                &Keccak256::digest(b""),
+                "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470",
+            )
+        }),
+        check("bip32", || {
+            let seed = unhex(BIP32_SEED);
+            expect(&derive(&seed, &[])?, BIP32_MASTER_KEY)?;
+            expect(&derive(&seed, &[0])?, BIP32_M_0H_KEY)
+        }),
+        check("bip44_eth", || {
+            let seed = unhex(ETH_SEED);
+            let key = bip32_secp::derive_full(&seed, None, 0, 0).map_err(|e| e.to_string())?;
+            expect(&key.private_key, ETH_KEY_0_0)?;
+            let root = bip32_secp::compute_account_root(&seed).map_err(|e| e.to_string())?;
+            let warm =
+                bip32_secp::derive_full(&seed, Some(&root), 0, 0).map_err(|e| e.to_string())?;
+            if warm.private_key != key.private_key {
+                return Err("cached account root derived a different key".to_string());
+            }
+            expect(&key.eth_address(), ETH_ADDRESS_0_0)
+        }),
+        check("ecdsa_rfc6979", || {
+            let mut private_key = [0u8; 32];
+            private_key[31] = 1;
+            let mut digest = [0u8; 32];
+            digest.copy_from_slice(&unhex(SIGN_DIGEST));
+            let signature =
+                bip32_secp::sign_recoverable(&private_key, &digest).map_err(|e| e.to_string())?;
+            expect(&signature, SIGN_SIGNATURE)
+        }),
+    ]
+}
+
+/// Run one check; `Err` carries what differed.
+pub fn check(name: &str, f: impl FnOnce() -> Result<(), String>) -> SelfTestCheck {
+    let (passed, detail) = match f() {
+        Ok(()) => (true, String::new()),
+        Err(detail) => (false, detail),
+    };
+    SelfTestCheck {
+        name: name.to_string(),
+        passed,
+        detail,
+    }
+}
+
+fn derive(seed: &[u8], levels: &[u32]) -> Result<[u8; 32], String> {
+    bip32_secp::derive_hardened_key(seed, levels).map_err(|e| e.to_string())
+}
+
+/// Test keys are public, so the mismatch can name both values.
+fn expect(got: &[u8], want: &str) -> Result<(), String> {
+    let got = hex(got);
+    if got == want {
+        Ok(())
+    } else {
+        Err(format!("expected {}, got {}", want, got))
+    }
+}
+
+fn unhex(s: &str) -> Vec<u8> {
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap_or(0))
+        .collect()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
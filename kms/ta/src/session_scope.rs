@@ -0,0 +1,322 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Scoped AA session keys: permission encoding, scope enforcement and the
+//! per-wallet revocation list.
+//!
+//! A scoped session key is a TEE-generated secp256k1 key whose permission
+//! object (targets / selectors / per-call value cap / expiry) is signed by the
+//! root wallet key at issuance. The TA re-checks every call against that scope
+//! before signing, so a dapp holding the session can only ever get signatures
+//! for calls the owner approved — and never touches the root key.
+
+use anyhow::{anyhow, bail, Result};
+use proto::{SessionCall, SessionPermission};
+use secure_db::Storable;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use sha3::{Digest, Keccak256};
+use uuid::Uuid;
+
+/// Bounds keep a permission object (and its stored form) small.
+pub const MAX_SESSION_TARGETS: usize = 16;
+pub const MAX_SESSION_SELECTORS: usize = 32;
+/// Longest lifetime an owner may grant a scoped session key (TA config's
+/// session-lifetime, 7 days by default).
+pub fn max_session_lifetime_secs() -> u64 {
+    crate::ta_config::limits().session_lifetime_secs as u64
+}
+/// Revoked indices remembered per wallet. Indices are host-allocated and
+/// monotonic, so the list only needs to outlive a restore-from-backup window.
+pub const MAX_REVOKED_PER_WALLET: usize = 256;
+
+const PERMISSION_TAG: &[u8] = b"AirAccount.SessionPermission.v1";
+
+/// Sealed scoped session key. No `Debug` — the private key must never reach a log.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct ScopedSessionKey {
+    pub store_id: String,
+    pub private_key: [u8; 32],
+    pub address: [u8; 20],
+    pub permission: SessionPermission,
+}
+
+impl Storable for ScopedSessionKey {
+    type Key = String;
+    fn unique_id(&self) -> Self::Key {
+        self.store_id.clone()
+    }
+}
+
+impl Drop for ScopedSessionKey {
+    fn drop(&mut self) {
+        self.private_key.iter_mut().for_each(|b| *b = 0);
+    }
+}
+
+impl ScopedSessionKey {
+    pub fn store_id_for(wallet_id: &Uuid, session_index: u32) -> String {
+        format!("ssk_{}_{}", wallet_id, session_index)
+    }
+}
+
+/// Per-wallet revocation list. A revoked index can never be re-issued or used,
+/// even if an old copy of the sealed key is restored from a storage backup.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SessionRevocations {
+    pub store_id: String,
+    pub revoked: Vec<u32>,
+}
+
+impl Storable for SessionRevocations {
+    type Key = String;
+    fn unique_id(&self) -> Self::Key {
+        self.store_id.clone()
+    }
+}
+
+impl SessionRevocations {
+    pub fn store_id_for(wallet_id: &Uuid) -> String {
+        format!("sskrev_{}", wallet_id)
+    }
+
+    pub fn empty(wallet_id: &Uuid) -> Self {
+        Self {
+            store_id: Self::store_id_for(wallet_id),
+            revoked: Vec::new(),
+        }
+    }
+
+    pub fn contains(&self, session_index: u32) -> bool {
+        self.revoked.contains(&session_index)
+    }
+
+    /// Record `session_index`; returns false if it was already revoked.
+    pub fn revoke(&mut self, session_index: u32) -> bool {
+        if self.contains(session_index) {
+            return false;
+        }
+        if self.revoked.len() >= MAX_REVOKED_PER_WALLET {
+            // Drop the oldest (lowest) index: host indices only grow.
+            if let Some((i, _)) = self.revoked.iter().enumerate().min_by_key(|(_, v)| **v) {
+                self.revoked.swap_remove(i);
+            }
+        }
+        self.revoked.push(session_index);
+        true
+    }
+}
+
+/// Structural checks on a permission object before the owner signs it.
+pub fn validate_permission(p: &SessionPermission, now: i64) -> Result<()> {
+    if p.targets.is_empty() {
+        bail!("session permission must name at least one target");
+    }
+    if p.targets.len() > MAX_SESSION_TARGETS {
+        bail!("too many session targets (max {})", MAX_SESSION_TARGETS);
+    }
+    if p.selectors.len() > MAX_SESSION_SELECTORS {
+        bail!("too many session selectors (max {})", MAX_SESSION_SELECTORS);
+    }
+    if now > 0 {
+        let now = now as u64;
+        if p.expiry <= now {
+            bail!("session permission already expired");
+        }
+        let max_lifetime = max_session_lifetime_secs();
+        if p.expiry - now > max_lifetime {
+            bail!("session lifetime exceeds {}s", max_lifetime);
+        }
+    }
+    Ok(())
+}
+
+/// keccak256(abi.encode(keccak(tag), chainId, account, sessionKey,
+///   keccak(targets padded), keccak(selectors padded), valueCap, expiry)).
+/// The owner's root key signs EIP-191(this); on-chain validators recompute it.
+pub fn permission_digest(p: &SessionPermission, session_key: &[u8; 20]) -> [u8; 32] {
+    let mut targets = Vec::with_capacity(p.targets.len() * 32);
+    for t in &p.targets {
+        targets.extend_from_slice(&[0u8; 12]);
+        targets.extend_from_slice(t);
+    }
+    let mut selectors = Vec::with_capacity(p.selectors.len() * 32);
+    for s in &p.selectors {
+        selectors.extend_from_slice(s);
+        selectors.extend_from_slice(&[0u8; 28]);
+    }
+    let mut buf = [0u8; 256];
+    buf[..32].copy_from_slice(&Keccak256::digest(PERMISSION_TAG));
+    buf[56..64].copy_from_slice(&p.chain_id.to_be_bytes());
+    buf[76..96].copy_from_slice(&p.account);
+    buf[108..128].copy_from_slice(session_key);
+    buf[128..160].copy_from_slice(&Keccak256::digest(&targets));
+    buf[160..192].copy_from_slice(&Keccak256::digest(&selectors));
+    buf[208..224].copy_from_slice(&p.value_cap.to_be_bytes());
+    buf[248..256].copy_from_slice(&p.expiry.to_be_bytes());
+    Keccak256::digest(buf).into()
+}
+
+/// Passkey commitment for issuance. The session address is generated inside
+/// the TA after the assertion, so the client commits to the permission digest
+/// computed with a zero session key: SHA-256(tag ‖ wallet_id ‖ digest).
+pub fn mint_commitment(wallet_id: &Uuid, p: &SessionPermission) -> [u8; 32] {
+    let mut h = Sha256::new();
+    h.update(b"AA-SCOPED-SESSION-MINT-v1");
+    h.update(wallet_id.as_bytes());
+    h.update(permission_digest(p, &[0u8; 20]));
+    h.finalize().into()
+}
+
+/// Passkey commitment for revocation, distinct tag so a mint assertion can
+/// never be replayed as a revoke (or vice versa).
+pub fn revoke_commitment(wallet_id: &Uuid, session_index: u32) -> [u8; 32] {
+    let mut h = Sha256::new();
+    h.update(b"AA-SCOPED-SESSION-REVOKE-v1");
+    h.update(wallet_id.as_bytes());
+    h.update(session_index.to_be_bytes());
+    h.finalize().into()
+}
+
+/// Enforce the signed scope for one call. An empty selector list means "any
+/// selector on an allowed target"; targets are always explicit.
+pub fn check_call(
+    p: &SessionPermission,
+    chain_id: u64,
+    call: &SessionCall,
+    now: i64,
+) -> Result<()> {
+    if chain_id != p.chain_id {
+        bail!("session key not valid on chain {}", chain_id);
+    }
+    if now > 0 && now as u64 >= p.expiry {
+        bail!("session key expired");
+    }
+    if !p.targets.contains(&call.target) {
+        return Err(anyhow!(
+            "call target 0x{} outside session scope",
+            hex::encode(call.target)
+        ));
+    }
+    if !p.selectors.is_empty() && !p.selectors.contains(&call.selector) {
+        return Err(anyhow!(
+            "selector 0x{} outside session scope",
+            hex::encode(call.selector)
+        ));
+    }
+    if call.value > p.value_cap {
+        bail!(
+            "call value {} exceeds session cap {}",
+            call.value,
+            p.value_cap
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn perm() -> SessionPermission {
+        SessionPermission {
+            chain_id: 10,
+            account: [0xaa; 20],
+            targets: vec![[0x01; 20]],
+            selectors: vec![[0xa9, 0x05, 0x9c, 0xbb]],
+            value_cap: 1_000,
+            expiry: 2_000,
+        }
+    }
+
+    fn call(value: u128) -> SessionCall {
+        SessionCall {
+            target: [0x01; 20],
+            selector: [0xa9, 0x05, 0x9c, 0xbb],
+            value,
+        }
+    }
+
+    #[test]
+    fn in_scope_call_passes() {
+        assert!(check_call(&perm(), 10, &call(1_000), 1_000).is_ok());
+    }
+
+    #[test]
+    fn out_of_scope_calls_rejected() {
+        let p = perm();
+        assert!(check_call(&p, 1, &call(0), 1_000).is_err());
+        assert!(check_call(&p, 10, &call(1_001), 1_000).is_err());
+        assert!(check_call(&p, 10, &call(0), 2_000).is_err());
+        let mut c = call(0);
+        c.target = [0x02; 20];
+        assert!(check_call(&p, 10, &c, 1_000).is_err());
+        let mut c = call(0);
+        c.selector = [0; 4];
+        assert!(check_call(&p, 10, &c, 1_000).is_err());
+    }
+
+    #[test]
+    fn permission_digest_binds_every_field() {
+        let base = permission_digest(&perm(), &[0x55; 20]);
+        assert_ne!(base, permission_digest(&perm(), &[0x56; 20]));
+        let mut p = perm();
+        p.value_cap += 1;
+        assert_ne!(base, permission_digest(&p, &[0x55; 20]));
+        let mut p = perm();
+        p.selectors.clear();
+        assert_ne!(base, permission_digest(&p, &[0x55; 20]));
+    }
+
+    #[test]
+    fn mint_and_revoke_commitments_are_domain_separated() {
+        let id = Uuid::from_bytes([7; 16]);
+        let other = Uuid::from_bytes([8; 16]);
+        assert_ne!(
+            mint_commitment(&id, &perm()),
+            mint_commitment(&other, &perm())
+        );
+        assert_ne!(revoke_commitment(&id, 0), revoke_commitment(&id, 1));
+        assert_ne!(mint_commitment(&id, &perm()), revoke_commitment(&id, 0));
+    }
+
+    #[test]
+    fn validate_rejects_bad_lifetimes() {
+        assert!(validate_permission(&perm(), 1_000).is_ok());
+        assert!(validate_permission(&perm(), 2_000).is_err());
+        let mut p = perm();
+        p.expiry = 1_000 + max_session_lifetime_secs() + 1;
+        assert!(validate_permission(&p, 1_000).is_err());
+        let mut p = perm();
+        p.targets.clear();
+        assert!(validate_permission(&p, 1_000).is_err());
+    }
+
+    #[test]
+    fn revocation_list_is_bounded_and_idempotent() {
+        let id = Uuid::from_bytes([9; 16]);
+        let mut r = SessionRevocations::empty(&id);
+        assert!(r.revoke(1));
+        assert!(!r.revoke(1));
+        for i in 2..=(MAX_REVOKED_PER_WALLET as u32 + 1) {
+            r.revoke(i);
+        }
+        assert_eq!(r.revoked.len(), MAX_REVOKED_PER_WALLET);
+        assert!(!r.contains(1));
+        assert!(r.contains(MAX_REVOKED_PER_WALLET as u32 + 1));
+    }
+}
@@ -0,0 +1,182 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Domain-separated signing contexts for SignMessage / SignHash.
+//!
+//! The TA never trusts the CA's word for *what* a digest is: for every context
+//! with a known construction it rebuilds the digest from the declared preimage.
+//! Only `Raw` signs an opaque digest, and only on a non-strict build.
+
+use anyhow::{anyhow, bail, Result};
+use proto::siwe::SiweMessage;
+use proto::SigningContext;
+use sha3::{Digest, Keccak256};
+
+/// `strict-signing-context` feature: refuse `SigningContext::Raw` outright.
+/// Off (default) = TRANSITION: legacy clients that send no context keep working
+/// and are logged, mirroring the ENFORCE_TA_CHALLENGE rollout.
+#[cfg(feature = "strict-signing-context")]
+pub const ALLOW_RAW_DIGEST: bool = false;
+#[cfg(not(feature = "strict-signing-context"))]
+pub const ALLOW_RAW_DIGEST: bool = true;
+
+fn context_name(ctx: &SigningContext) -> &'static str {
+    match ctx {
+        SigningContext::Raw => "Raw",
+        SigningContext::EthTx => "EthTx",
+        SigningContext::UserOp { .. } => "UserOp",
+        SigningContext::Eip712 { .. } => "Eip712",
+        SigningContext::PersonalMsg => "PersonalMsg",
+        SigningContext::Login => "Login",
+    }
+}
+
+fn check_raw_allowed(allow_raw: bool) -> Result<()> {
+    if !allow_raw {
+        bail!("raw digest signing refused: declare a signing context (strict-signing-context)");
+    }
+    Ok(())
+}
+
+/// EIP-191 personal_sign digest over an arbitrary-length message.
+pub fn personal_message_digest(message: &[u8]) -> [u8; 32] {
+    let mut h = Keccak256::new();
+    h.update(b"\x19Ethereum Signed Message:\n");
+    h.update(message.len().to_string().as_bytes());
+    h.update(message);
+    h.finalize().into()
+}
+
+/// ERC-4337 userOpHash = keccak256(abi.encode(bytes32 inner, address ep, uint256 chainId)).
+pub fn user_op_digest(inner_hash: &[u8; 32], entry_point: &[u8; 20], chain_id: u64) -> [u8; 32] {
+    let mut buf = [0u8; 96];
+    buf[..32].copy_from_slice(inner_hash);
+    buf[44..64].copy_from_slice(entry_point);
+    buf[88..96].copy_from_slice(&chain_id.to_be_bytes());
+    Keccak256::digest(buf).into()
+}
+
+/// EIP-712 digest = keccak256(0x19 0x01 ‖ domainSeparator ‖ structHash).
+pub fn eip712_digest(domain_separator: &[u8; 32], struct_hash: &[u8; 32]) -> [u8; 32] {
+    let mut buf = [0u8; 66];
+    buf[0] = 0x19;
+    buf[1] = 0x01;
+    buf[2..34].copy_from_slice(domain_separator);
+    buf[34..66].copy_from_slice(struct_hash);
+    Keccak256::digest(buf).into()
+}
+
+/// Digest SignMessage will sign under `ctx`. `Raw` keeps the legacy
+/// keccak256(message) construction.
+pub fn message_digest(ctx: &SigningContext, message: &[u8], allow_raw: bool) -> Result<[u8; 32]> {
+    match ctx {
+        SigningContext::Raw => {
+            check_raw_allowed(allow_raw)?;
+            Ok(Keccak256::digest(message).into())
+        }
+        SigningContext::PersonalMsg => Ok(personal_message_digest(message)),
+        SigningContext::Login => {
+            let text = core::str::from_utf8(message)
+                .map_err(|_| anyhow!("Login message must be UTF-8"))?;
+            // A full, canonical EIP-4361 message; the caller checks the
+            // address and times against the key and the TEE clock.
+            SiweMessage::parse(text).map_err(|e| anyhow!("Login message rejected: {}", e))?;
+            Ok(personal_message_digest(message))
+        }
+        other => Err(anyhow!(
+            "signing context {} is not valid for SignMessage",
+            context_name(other)
+        )),
+    }
+}
+
+/// Check that `hash` really is the digest declared by `ctx` before SignHash
+/// signs it.
+pub fn check_hash_context(ctx: &SigningContext, hash: &[u8; 32], allow_raw: bool) -> Result<()> {
+    let expected = match ctx {
+        SigningContext::Raw => return check_raw_allowed(allow_raw),
+        SigningContext::UserOp {
+            inner_hash,
+            entry_point,
+            chain_id,
+        } => user_op_digest(inner_hash, entry_point, *chain_id),
+        SigningContext::Eip712 {
+            domain_separator,
+            struct_hash,
+        } => eip712_digest(domain_separator, struct_hash),
+        other => bail!(
+            "signing context {} is not valid for SignHash",
+            context_name(other)
+        ),
+    };
+    if &expected != hash {
+        bail!(
+            "hash does not match the declared {} construction",
+            context_name(ctx)
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn personal_digest_matches_known_vector() {
+        // ethers.utils.hashMessage("hello")
+        let d = personal_message_digest(b"hello");
+        assert_eq!(
+            hex::encode(d),
+            "50b2c43fd39106bafbba0da34fc430e1f91e3c96ea2acee2bc34119f92b37750"
+        );
+    }
+
+    #[test]
+    fn raw_refused_when_policy_forbids() {
+        assert!(message_digest(&SigningContext::Raw, b"x", false).is_err());
+        assert!(check_hash_context(&SigningContext::Raw, &[0; 32], false).is_err());
+        assert!(check_hash_context(&SigningContext::Raw, &[0; 32], true).is_ok());
+    }
+
+    #[test]
+    fn userop_hash_must_match_preimage() {
+        let ctx = SigningContext::UserOp {
+            inner_hash: [1; 32],
+            entry_point: [2; 20],
+            chain_id: 1,
+        };
+        let good = user_op_digest(&[1; 32], &[2; 20], 1);
+        assert!(check_hash_context(&ctx, &good, false).is_ok());
+        assert!(check_hash_context(&ctx, &[0; 32], true).is_err());
+    }
+
+    #[test]
+    fn contexts_not_interchangeable() {
+        assert!(message_digest(&SigningContext::EthTx, b"x", true).is_err());
+        assert!(check_hash_context(&SigningContext::PersonalMsg, &[0; 32], true).is_err());
+        assert!(message_digest(&SigningContext::Login, b"hello", true).is_err());
+        // Login wants a complete EIP-4361 message, not just its first line.
+        let header_only = b"app.example wants you to sign in with your Ethereum account:\n0xabc";
+        assert!(message_digest(&SigningContext::Login, header_only, true).is_err());
+        let siwe = "app.example wants you to sign in with your Ethereum account:\n\
+                    0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2\n\n\n\
+                    URI: https://app.example/login\nVersion: 1\nChain ID: 1\n\
+                    Nonce: 32891756\nIssued At: 2021-09-30T16:25:24Z";
+        assert!(message_digest(&SigningContext::Login, siwe.as_bytes(), false).is_ok());
+    }
+}
@@ -0,0 +1,260 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Ethereum consensus-layer signing for the sealed BLS key: SSZ signing roots
+//! for blocks and attestations, and EIP-3076-style minimal slashing protection.
+//!
+//! The TA computes the signing root itself from the decoded fields, so the
+//! slot / epochs it checks are the ones the signature actually commits to.
+//! The caller only supplies the 32-byte domain (fork-specific); a wrong domain
+//! just yields a signature the chain rejects, never a slashable one.
+
+use anyhow::{bail, Result};
+use proto::BeaconCheckpoint;
+use secure_db::Storable;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// High-water marks for one BLS key. Written to secure storage BEFORE the
+/// signature is produced, so a crash between the two can only lose a
+/// signature, never allow a second conflicting one.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SlashingRecord {
+    pub store_id: String,
+    pub last_block_slot: Option<u64>,
+    pub last_block_root: [u8; 32],
+    pub last_source_epoch: Option<u64>,
+    pub last_target_epoch: Option<u64>,
+    pub last_attestation_root: [u8; 32],
+}
+
+impl Storable for SlashingRecord {
+    type Key = String;
+    fn unique_id(&self) -> Self::Key {
+        self.store_id.clone()
+    }
+}
+
+impl SlashingRecord {
+    pub fn store_id_for(bls_key_id: &str) -> String {
+        format!("blsslash_{}", bls_key_id)
+    }
+
+    pub fn empty(bls_key_id: &str) -> Self {
+        Self {
+            store_id: Self::store_id_for(bls_key_id),
+            last_block_slot: None,
+            last_block_root: [0u8; 32],
+            last_source_epoch: None,
+            last_target_epoch: None,
+            last_attestation_root: [0u8; 32],
+        }
+    }
+
+    /// A block is safe if its slot is past the last signed one. Re-signing the
+    /// identical block (same slot, same signing root) is allowed so a client
+    /// retry after a lost response doesn't wedge the validator.
+    pub fn check_and_record_block(&mut self, slot: u64, signing_root: [u8; 32]) -> Result<()> {
+        if let Some(last) = self.last_block_slot {
+            let same = slot == last && signing_root == self.last_block_root;
+            if slot <= last && !same {
+                bail!(
+                    "slashing protection: block at slot {} (last signed {})",
+                    slot,
+                    last
+                );
+            }
+        }
+        self.last_block_slot = Some(slot);
+        self.last_block_root = signing_root;
+        Ok(())
+    }
+
+    /// Minimal EIP-3076 rule: source never decreases and target strictly
+    /// increases. Together these exclude both double votes and surround votes
+    /// against anything signed through this TA.
+    pub fn check_and_record_attestation(
+        &mut self,
+        source_epoch: u64,
+        target_epoch: u64,
+        signing_root: [u8; 32],
+    ) -> Result<()> {
+        if source_epoch > target_epoch {
+            bail!(
+                "attestation source epoch {} after target {}",
+                source_epoch,
+                target_epoch
+            );
+        }
+        if let Some(last_source) = self.last_source_epoch {
+            if source_epoch < last_source {
+                bail!(
+                    "slashing protection: source epoch {} below last signed {}",
+                    source_epoch,
+                    last_source
+                );
+            }
+        }
+        if let Some(last_target) = self.last_target_epoch {
+            let same = target_epoch == last_target
+                && Some(source_epoch) == self.last_source_epoch
+                && signing_root == self.last_attestation_root;
+            if target_epoch <= last_target && !same {
+                bail!(
+                    "slashing protection: target epoch {} (last signed {})",
+                    target_epoch,
+                    last_target
+                );
+            }
+        }
+        self.last_source_epoch = Some(source_epoch);
+        self.last_target_epoch = Some(target_epoch);
+        self.last_attestation_root = signing_root;
+        Ok(())
+    }
+}
+
+fn u64_leaf(v: u64) -> [u8; 32] {
+    let mut leaf = [0u8; 32];
+    leaf[..8].copy_from_slice(&v.to_le_bytes());
+    leaf
+}
+
+fn hash_pair(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let mut h = Sha256::new();
+    h.update(a);
+    h.update(b);
+    h.finalize().into()
+}
+
+/// SSZ merkleization of a container's field roots (zero-padded to 8 leaves,
+/// enough for every container used here).
+fn merkleize8(fields: &[[u8; 32]]) -> [u8; 32] {
+    debug_assert!(fields.len() <= 8);
+    let mut layer = [[0u8; 32]; 8];
+    layer[..fields.len()].copy_from_slice(fields);
+    let mut width = 8;
+    while width > 1 {
+        for i in 0..width / 2 {
+            layer[i] = hash_pair(&layer[2 * i], &layer[2 * i + 1]);
+        }
+        width /= 2;
+    }
+    layer[0]
+}
+
+fn checkpoint_root(cp: &BeaconCheckpoint) -> [u8; 32] {
+    hash_pair(&u64_leaf(cp.epoch), &cp.root)
+}
+
+/// hash_tree_root(SigningData { object_root, domain }).
+pub fn signing_root(object_root: &[u8; 32], domain: &[u8; 32]) -> [u8; 32] {
+    hash_pair(object_root, domain)
+}
+
+/// hash_tree_root(AttestationData).
+pub fn attestation_data_root(
+    slot: u64,
+    committee_index: u64,
+    beacon_block_root: &[u8; 32],
+    source: &BeaconCheckpoint,
+    target: &BeaconCheckpoint,
+) -> [u8; 32] {
+    merkleize8(&[
+        u64_leaf(slot),
+        u64_leaf(committee_index),
+        *beacon_block_root,
+        checkpoint_root(source),
+        checkpoint_root(target),
+    ])
+}
+
+/// hash_tree_root(BeaconBlockHeader). Signing the header root is equivalent to
+/// signing the block, since body_root commits to the body.
+pub fn block_header_root(
+    slot: u64,
+    proposer_index: u64,
+    parent_root: &[u8; 32],
+    state_root: &[u8; 32],
+    body_root: &[u8; 32],
+) -> [u8; 32] {
+    merkleize8(&[
+        u64_leaf(slot),
+        u64_leaf(proposer_index),
+        *parent_root,
+        *state_root,
+        *body_root,
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cp(epoch: u64, b: u8) -> BeaconCheckpoint {
+        BeaconCheckpoint {
+            epoch,
+            root: [b; 32],
+        }
+    }
+
+    #[test]
+    fn ssz_roots_match_reference() {
+        // Reference values computed independently (Python hashlib, SSZ spec).
+        let att = attestation_data_root(1, 2, &[3; 32], &cp(4, 5), &cp(6, 7));
+        assert_eq!(hex::encode(att), ATT_ROOT);
+        let blk = block_header_root(9, 10, &[11; 32], &[12; 32], &[13; 32]);
+        assert_eq!(hex::encode(blk), BLOCK_ROOT);
+        assert_eq!(
+            hex::encode(signing_root(&blk, &[14; 32])),
+            BLOCK_SIGNING_ROOT
+        );
+    }
+
+    const ATT_ROOT: &str = "0c0ba487d715c60b4737037f588cda683de4ce9cba299a6e97edf9b3ccb1f7ac";
+    const BLOCK_ROOT: &str = "d5c6437dd676d3fb4e44c0b0064aa77fa264409b37179fc2a2085622b3576b3b";
+    const BLOCK_SIGNING_ROOT: &str =
+        "f7256c396b6b49ebb6b42b9b8f33cd66502e8dae811d3d870fa91bebc624ac45";
+
+    #[test]
+    fn block_double_sign_refused() {
+        let mut r = SlashingRecord::empty("k");
+        r.check_and_record_block(10, [1; 32]).unwrap();
+        assert!(r.check_and_record_block(10, [1; 32]).is_ok());
+        assert!(r.check_and_record_block(10, [2; 32]).is_err());
+        assert!(r.check_and_record_block(9, [3; 32]).is_err());
+        r.check_and_record_block(11, [4; 32]).unwrap();
+        assert_eq!(r.last_block_slot, Some(11));
+    }
+
+    #[test]
+    fn attestation_double_and_surround_refused() {
+        let mut r = SlashingRecord::empty("k");
+        r.check_and_record_attestation(5, 6, [1; 32]).unwrap();
+        // identical retry
+        assert!(r.check_and_record_attestation(5, 6, [1; 32]).is_ok());
+        // double vote: same target, different data
+        assert!(r.check_and_record_attestation(5, 6, [2; 32]).is_err());
+        // surrounding vote: earlier source, later target
+        assert!(r.check_and_record_attestation(4, 7, [3; 32]).is_err());
+        // surrounded vote: target not past the last one
+        assert!(r.check_and_record_attestation(6, 6, [4; 32]).is_err());
+        assert!(r.check_and_record_attestation(7, 6, [5; 32]).is_err());
+        r.check_and_record_attestation(6, 8, [6; 32]).unwrap();
+        assert_eq!(r.last_target_epoch, Some(8));
+    }
+}
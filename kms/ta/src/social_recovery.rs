@@ -0,0 +1,209 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+//! The wallet's recovery guardians (`proto::social_recovery`) and the
+//! round in progress. Rebinding the passkey itself is the caller's: this
+//! record only says when, and to which key.
+
+use anyhow::{anyhow, bail, Result};
+use proto::social_recovery::{ArmedRecovery, GuardianSet, RecoveryCandidate, RecoveryStatus};
+use secure_db::Storable;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Guardian `guardian` (an index into `GuardianSet::guardians`) names
+/// `passkey` this round.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Vote {
+    pub guardian: u8,
+    pub passkey: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RecoveryRecord {
+    pub store_id: String,
+    /// None = no guardians; the wallet cannot be recovered this way.
+    pub guardians: Option<GuardianSet>,
+    pub round: u64,
+    pub votes: Vec<Vote>,
+    pub armed: Option<ArmedRecovery>,
+}
+
+impl Storable for RecoveryRecord {
+    type Key = String;
+    fn unique_id(&self) -> Self::Key {
+        self.store_id.clone()
+    }
+}
+
+impl RecoveryRecord {
+    pub fn store_id_for(wallet_id: &Uuid) -> String {
+        format!("recovery_{}", wallet_id)
+    }
+
+    pub fn empty(wallet_id: &Uuid) -> Self {
+        Self {
+            store_id: Self::store_id_for(wallet_id),
+            guardians: None,
+            round: 0,
+            votes: Vec::new(),
+            armed: None,
+        }
+    }
+
+    /// End the round: its attestations never count again. Returns whether
+    /// it had any.
+    pub fn next_round(&mut self) -> bool {
+        let had_votes = !self.votes.is_empty();
+        self.votes.clear();
+        self.armed = None;
+        self.round += 1;
+        had_votes
+    }
+
+    /// Replace the guardians; returns the old set and whether a round in
+    /// progress was discarded.
+    pub fn set_guardians(&mut self, guardians: Option<GuardianSet>) -> (Option<GuardianSet>, bool) {
+        let cancelled = self.next_round();
+        (std::mem::replace(&mut self.guardians, guardians), cancelled)
+    }
+
+    /// Position of `key` among the guardians.
+    pub fn guardian_index(&self, key: &[u8]) -> Result<u8> {
+        self.guardians
+            .as_ref()
+            .and_then(|g| g.guardians.iter().position(|k| k == key))
+            .map(|i| i as u8)
+            .ok_or_else(|| anyhow!("not a recovery guardian of this wallet"))
+    }
+
+    fn attestations(&self, passkey: &[u8]) -> u8 {
+        self.votes.iter().filter(|v| v.passkey == passkey).count() as u8
+    }
+
+    /// Record guardian `index`'s (already verified) attestation of
+    /// `passkey`. Returns true when it arms the recovery.
+    pub fn vote(&mut self, index: u8, passkey: &[u8], now: i64) -> Result<bool> {
+        let set = self
+            .guardians
+            .as_ref()
+            .ok_or_else(|| anyhow!("no recovery guardians are registered for this wallet"))?;
+        let (threshold, delay) = (set.threshold, set.delay_secs as i64);
+        match self.votes.iter().find(|v| v.guardian == index) {
+            Some(v) if v.passkey == passkey => return Ok(false),
+            Some(_) => bail!("this guardian already attested another passkey this round"),
+            None => self.votes.push(Vote {
+                guardian: index,
+                passkey: passkey.to_vec(),
+            }),
+        }
+        if self.armed.is_some() || self.attestations(passkey) < threshold {
+            return Ok(false);
+        }
+        self.armed = Some(ArmedRecovery {
+            passkey: passkey.to_vec(),
+            ready_at: now.saturating_add(delay),
+        });
+        Ok(true)
+    }
+
+    /// The passkey to bind once the timelock has passed; the round ends.
+    pub fn take_ready(&mut self, now: i64) -> Result<Vec<u8>> {
+        let armed = self
+            .armed
+            .as_ref()
+            .ok_or_else(|| anyhow!("no recovery has reached its guardian threshold"))?;
+        if now < armed.ready_at {
+            bail!(
+                "recovery timelock has {} s left",
+                armed.ready_at.saturating_sub(now)
+            );
+        }
+        let passkey = armed.passkey.clone();
+        self.next_round();
+        Ok(passkey)
+    }
+
+    pub fn status(&self) -> RecoveryStatus {
+        let mut candidates: Vec<RecoveryCandidate> = Vec::new();
+        for vote in &self.votes {
+            if !candidates.iter().any(|c| c.passkey == vote.passkey) {
+                candidates.push(RecoveryCandidate {
+                    passkey: vote.passkey.clone(),
+                    attestations: self.attestations(&vote.passkey),
+                });
+            }
+        }
+        RecoveryStatus {
+            guardians: self.guardians.clone(),
+            round: self.round,
+            candidates,
+            armed: self.armed.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: i64 = 86_400;
+
+    fn record() -> RecoveryRecord {
+        let mut r = RecoveryRecord::empty(&Uuid::from_bytes([6; 16]));
+        r.set_guardians(Some(GuardianSet {
+            threshold: 2,
+            guardians: vec![vec![0x02; 33], vec![0x03; 33], vec![0x02, 1, 2]],
+            delay_secs: DAY as u64,
+        }));
+        r
+    }
+
+    #[test]
+    fn threshold_arms_and_the_timelock_releases() {
+        let mut r = record();
+        let round = r.round;
+        let (new, rogue) = (vec![0x04; 65], vec![0x04, 9]);
+        assert!(!r.vote(0, &new, 100).unwrap());
+        assert!(!r.vote(0, &new, 110).unwrap());
+        assert!(r.vote(0, &rogue, 120).is_err());
+        assert!(!r.vote(1, &rogue, 130).unwrap());
+        assert_eq!(r.status().candidates.len(), 2);
+        assert!(r.take_ready(200).is_err());
+
+        assert!(r.vote(2, &new, 1_000).unwrap());
+        assert_eq!(r.armed.as_ref().unwrap().ready_at, 1_000 + DAY);
+        assert!(r.take_ready(1_000 + DAY - 1).is_err());
+        assert_eq!(r.take_ready(1_000 + DAY).unwrap(), new);
+        assert_eq!(r.round, round + 1);
+        assert!(r.status().candidates.is_empty() && r.armed.is_none());
+        assert!(r.take_ready(i64::MAX).is_err());
+    }
+
+    #[test]
+    fn guardian_changes_end_the_round() {
+        let mut r = record();
+        r.vote(0, &[0x04; 65], 0).unwrap();
+        assert_eq!(r.guardian_index(&[0x03; 33]).unwrap(), 1);
+        assert!(r.guardian_index(&[0x03; 32]).is_err());
+        let round = r.round;
+        let (previous, cancelled) = r.set_guardians(None);
+        assert!(previous.is_some() && cancelled);
+        assert_eq!(r.round, round + 1);
+        assert!(r.vote(0, &[0x04; 65], 0).is_err());
+        assert!(!r.next_round());
+    }
+}
@@ -0,0 +1,58 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+//! The wallet's spending policy (`proto::spending_policy`) and the native
+//! value it has sent today. Both live in one record, so the spend is reset
+//! with the policy and a signature is released only after its value has
+//! been counted.
+
+use proto::spending_policy::{DailySpend, SpendingPolicy};
+use secure_db::Storable;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SpendingRecord {
+    pub store_id: String,
+    pub policy: SpendingPolicy,
+    pub spend: DailySpend,
+}
+
+impl Storable for SpendingRecord {
+    type Key = String;
+    fn unique_id(&self) -> Self::Key {
+        self.store_id.clone()
+    }
+}
+
+impl SpendingRecord {
+    pub fn store_id_for(wallet_id: &Uuid) -> String {
+        format!("spendpol_{}", wallet_id)
+    }
+
+    pub fn empty(wallet_id: &Uuid) -> Self {
+        Self {
+            store_id: Self::store_id_for(wallet_id),
+            policy: SpendingPolicy::default(),
+            spend: DailySpend::default(),
+        }
+    }
+
+    /// Whether a signature of `value` must be counted before it leaves.
+    pub fn counts(&self, value: u128) -> bool {
+        self.policy.daily_limit_wei.is_some() && value > 0
+    }
+}
@@ -0,0 +1,107 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Receiver side of EIP-5564 stealth addresses.
+//!
+//! Both keys of an account come from `bip32_secp` at the hardened paths in
+//! `proto::stealth`. Only their public halves leave the TA. Announcements
+//! are chain data anyone can post, so a malformed ephemeral key is a miss,
+//! not an error, and one bad log cannot stop a scan.
+
+use anyhow::Result;
+use proto::stealth::{hashed_secret, spending_levels, viewing_levels};
+use proto::stealth::{StealthCandidate, StealthMetaAddress};
+use secp256k1::{PublicKey, Scalar, Secp256k1, SecretKey};
+use sha3::{Digest, Keccak256};
+
+use crate::bip32_secp::derive_hardened_key;
+
+pub struct StealthKeys {
+    spending: SecretKey,
+    viewing: SecretKey,
+}
+
+impl StealthKeys {
+    pub fn derive(seed: &[u8], account: u32) -> Result<Self> {
+        let mut spending = derive_hardened_key(seed, &spending_levels(account))?;
+        let mut viewing = derive_hardened_key(seed, &viewing_levels(account))?;
+        let keys = SecretKey::from_slice(&spending).and_then(|s| {
+            Ok(StealthKeys {
+                spending: s,
+                viewing: SecretKey::from_slice(&viewing)?,
+            })
+        });
+        spending.fill(0);
+        viewing.fill(0);
+        Ok(keys?)
+    }
+
+    pub fn meta_address(&self) -> StealthMetaAddress {
+        let secp = Secp256k1::signing_only();
+        StealthMetaAddress {
+            spending_pubkey: self.spending.public_key(&secp).serialize().to_vec(),
+            viewing_pubkey: self.viewing.public_key(&secp).serialize().to_vec(),
+        }
+    }
+
+    /// Indexes of the candidates paid to this account. The view tag rules
+    /// out all but about 1/256 of the others before the second point
+    /// multiplication.
+    pub fn scan(&self, candidates: &[StealthCandidate]) -> Vec<u32> {
+        let secp = Secp256k1::new();
+        let spending_pubkey = self.spending.public_key(&secp);
+        let viewing = Scalar::from(self.viewing);
+        candidates
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| {
+                stealth_address(&secp, &spending_pubkey, &viewing, c)
+                    .is_some_and(|address| address == c.stealth_address)
+            })
+            .map(|(i, _)| i as u32)
+            .collect()
+    }
+}
+
+/// `P_spend + s_h·G` as an address, or `None` when the view tag differs or
+/// the announcement's key is not a point.
+fn stealth_address(
+    secp: &Secp256k1<secp256k1::All>,
+    spending_pubkey: &PublicKey,
+    viewing: &Scalar,
+    candidate: &StealthCandidate,
+) -> Option<[u8; 20]> {
+    let ephemeral = PublicKey::from_slice(&candidate.ephemeral_pubkey).ok()?;
+    let shared = ephemeral.mul_tweak(secp, viewing).ok()?;
+    let s_h = hashed_secret(&shared.serialize());
+    if s_h[0] != candidate.view_tag {
+        return None;
+    }
+    let tweak = Scalar::from_be_bytes(s_h).ok()?;
+    let stealth = spending_pubkey.add_exp_tweak(secp, &tweak).ok()?;
+    let hash = Keccak256::digest(&stealth.serialize_uncompressed()[1..]);
+    let mut address = [0u8; 20];
+    address.copy_from_slice(&hash[12..]);
+    Some(address)
+}
+
+impl Drop for StealthKeys {
+    fn drop(&mut self) {
+        self.spending.non_secure_erase();
+        self.viewing.non_secure_erase();
+    }
+}
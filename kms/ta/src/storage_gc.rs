@@ -0,0 +1,91 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! The automatic orphan pass (`proto::storage_gc`).
+//!
+//! `ta_create` arms one pass per TA instance, but cannot run it: secure
+//! storage is not reachable that early (see `open_session`). The dispatcher
+//! runs it after the instance's first command instead, once that command's
+//! output is in the CA's buffer and its own storage writes are done.
+
+use proto::storage_gc::{Orphan, OrphanReason, AUTO_SCAVENGE_LIMIT};
+
+use crate::ta_global::TaGlobal;
+
+struct PassState {
+    armed: bool,
+    scavenged: u32,
+}
+
+static PASS: TaGlobal<PassState> = TaGlobal::new(PassState {
+    armed: false,
+    scavenged: 0,
+});
+
+pub fn arm() {
+    PASS.with(|p| p.armed = true);
+}
+
+/// True once per armed instance.
+pub fn take_armed() -> bool {
+    PASS.with(|p| std::mem::replace(&mut p.armed, false))
+}
+
+pub fn record(deleted: u32) {
+    PASS.with(|p| p.scavenged += deleted);
+}
+
+pub fn scavenged_at_load() -> u32 {
+    PASS.with(|p| p.scavenged)
+}
+
+/// What the unattended pass may delete: objects of a wallet that is gone,
+/// at most [`AUTO_SCAVENGE_LIMIT`]. Malformed ids wait for an operator.
+pub fn automatic(orphans: &[Orphan]) -> Vec<&Orphan> {
+    orphans
+        .iter()
+        .filter(|o| o.reason == OrphanReason::WalletMissing)
+        .take(AUTO_SCAVENGE_LIMIT)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proto::storage_gc::ObjectKind;
+
+    #[test]
+    fn the_pass_runs_once_and_skips_malformed_ids() {
+        arm();
+        assert!(take_armed());
+        assert!(!take_armed());
+
+        let orphan = |reason, i: usize| Orphan {
+            kind: ObjectKind::P256SessionKey,
+            store_id: format!("p256sk_{}", i),
+            reason,
+        };
+        let mut orphans = vec![orphan(OrphanReason::MalformedId, 0)];
+        orphans
+            .extend((1..=AUTO_SCAVENGE_LIMIT + 1).map(|i| orphan(OrphanReason::WalletMissing, i)));
+        let picked = automatic(&orphans);
+        assert_eq!(picked.len(), AUTO_SCAVENGE_LIMIT);
+        assert!(picked
+            .iter()
+            .all(|o| o.reason == OrphanReason::WalletMissing));
+    }
+}
@@ -0,0 +1,45 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Wallet count against the cap, reported by `TaStats`
+//! (`proto::storage_quota`).
+//!
+//! The refusal counter is a `TaGlobal`: it is bumped after CreateWallet's
+//! storage writes, when TLS is no longer usable (H-3). Never persisted.
+
+use anyhow::Result;
+use proto::storage_quota::StorageUsage;
+use secure_db::SecureStorageClient;
+
+use crate::ta_global::TaGlobal;
+use crate::wallet::Wallet;
+
+static EXHAUSTED: TaGlobal<u32> = TaGlobal::new(0);
+
+/// One CreateWallet refused with `StorageExhausted`.
+pub fn record_exhausted() {
+    EXHAUSTED.with(|n| *n = n.saturating_add(1));
+}
+
+/// Reads only the wallet key list, like CreateWallet's cap check.
+pub fn usage(db: &SecureStorageClient) -> Result<StorageUsage> {
+    Ok(StorageUsage {
+        wallets: db.count_entries::<Wallet>()? as u32,
+        max_wallets: crate::ta_config::limits().max_wallets,
+        exhausted: EXHAUSTED.with(|n| *n),
+    })
+}
@@ -0,0 +1,134 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Installed TA config (limits, hardening flags) and its per-instance cache.
+//!
+//! Unlike the deployment policy, the signer is not pinned on first install:
+//! it is the provisioning key compiled into the image
+//! (`AIRACCOUNT_PROVISIONING_KEY` at build time). An image built without one
+//! refuses InstallConfig and runs on `TaLimits::DEFAULT`. Limit checks read
+//! [`limits`], which the dispatcher fills from secure storage before any
+//! handler runs.
+
+use proto::ta_config::{TaConfig, TaLimits};
+use secure_db::Storable;
+use serde::{Deserialize, Serialize};
+
+use crate::ta_global::TaGlobal;
+
+pub const STORE_ID: &str = "ta_config";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ConfigRecord {
+    pub store_id: String,
+    pub config: TaConfig,
+}
+
+impl Storable for ConfigRecord {
+    type Key = String;
+    fn unique_id(&self) -> Self::Key {
+        self.store_id.clone()
+    }
+}
+
+/// The compiled-in InstallConfig signer, if this image has one.
+pub fn provisioning_key() -> Option<[u8; 20]> {
+    // build.rs rejects a malformed key, so parse failure cannot happen here.
+    option_env!("AIRACCOUNT_PROVISIONING_KEY").and_then(|s| proto::eip55::parse_address(s).ok())
+}
+
+/// Check `config`, signed by `signer`, may replace `current` under the
+/// provisioning key `key`.
+pub fn check_install(
+    current: Option<&TaConfig>,
+    config: &TaConfig,
+    signer: [u8; 20],
+    key: Option<[u8; 20]>,
+) -> Result<(), &'static str> {
+    let key = key.ok_or("this TA image has no provisioning key; InstallConfig is disabled")?;
+    config.validate()?;
+    if signer != key {
+        return Err("TA config is not signed by the provisioning key");
+    }
+    if let Some(current) = current {
+        if config.sequence <= current.sequence {
+            return Err("TA config sequence must increase");
+        }
+    }
+    Ok(())
+}
+
+/// None = not loaded yet; Some(None) = loaded, nothing installed.
+static CONFIG: TaGlobal<Option<Option<TaConfig>>> = TaGlobal::new(None);
+
+pub fn cached() -> Option<Option<TaConfig>> {
+    CONFIG.with(|c| c.clone())
+}
+
+pub fn set_cached(config: Option<TaConfig>) {
+    CONFIG.with(|c| *c = Some(config));
+}
+
+/// Limits in force; the defaults until a config is installed.
+pub fn limits() -> TaLimits {
+    CONFIG.with(|c| match c {
+        Some(Some(config)) => config.limits,
+        _ => TaLimits::DEFAULT,
+    })
+}
+
+/// The config's own flag; main.rs ORs it with the `strict-challenge` build.
+pub fn require_ta_challenge() -> bool {
+    CONFIG.with(|c| matches!(c, Some(Some(config)) if config.require_ta_challenge))
+}
+
+/// The config's own flag; main.rs ORs it with the `strict-signing-context`
+/// build.
+pub fn require_signing_context() -> bool {
+    CONFIG.with(|c| matches!(c, Some(Some(config)) if config.require_signing_context))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proto::ta_config::CONFIG_FORMAT_VERSION;
+
+    fn config(sequence: u64) -> TaConfig {
+        TaConfig {
+            format: CONFIG_FORMAT_VERSION,
+            deployment: "rack-3".into(),
+            sequence,
+            limits: TaLimits::DEFAULT,
+            require_ta_challenge: false,
+            require_signing_context: false,
+        }
+    }
+
+    #[test]
+    fn only_the_provisioning_key_installs_and_sequence_only_rises() {
+        let key = Some([1; 20]);
+        assert!(check_install(None, &config(1), [1; 20], None).is_err());
+        assert!(check_install(None, &config(1), [1; 20], key).is_ok());
+        assert!(check_install(None, &config(1), [2; 20], key).is_err());
+        let current = config(3);
+        assert!(check_install(Some(&current), &config(4), [1; 20], key).is_ok());
+        assert!(check_install(Some(&current), &config(3), [1; 20], key).is_err());
+        let mut over = config(5);
+        over.limits.max_wallets = TaLimits::CEILING.max_wallets + 1;
+        assert!(check_install(Some(&current), &over, [1; 20], key).is_err());
+    }
+}
@@ -0,0 +1,97 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Process-global TA state: the one place that claims `Sync` for it.
+//!
+//! The pending-challenge table, child-key cache, latency histograms and the
+//! cached deployment-policy / tamper / TA-config records must outlive a
+//! secure-storage write in the same command, so they cannot be
+//! `thread_local` (H-3), and OP-TEE may run consecutive commands on
+//! different pool threads (#49/#61). [`TaGlobal`] holds each in a `RefCell`
+//! and never hands out a reference: the borrow is confined to one closure,
+//! and a re-entrant borrow panics instead of aliasing. No mutex: access is
+//! serial (see the `Sync` impl), so a lock would never be contended — and
+//! re-entrancy, the one real hazard, would deadlock a spin lock where the
+//! borrow flag reports it.
+//!
+//! Keep closures short — copy the state out, then derive keys, verify
+//! signatures or touch storage after the closure returns.
+
+use std::cell::RefCell;
+
+pub struct TaGlobal<T>(RefCell<T>);
+
+// SAFETY: every access is serial, for two independent reasons rooted in the
+// OP-TEE / GP execution model — not in any host-side discipline (the CA's
+// single-worker queue is an additional serialization, not what makes this
+// sound):
+//   1. Same session: `TEEC_InvokeCommand` is a BLOCKING call, so a client
+//      cannot have two in-flight commands on one session. The KMS CA uses ONE
+//      persistent session (ta_client.rs keeps it open to avoid the ~4.4s
+//      per-open cost), so all real traffic flows through that session.
+//   2. Different sessions: this TA is built with default properties
+//      (TA_FLAGS = 0 → gpd.ta.singleInstance = false), so EACH session gets
+//      its own TA instance in its own address space with its own statics.
+// The `RefCell` flag is therefore never touched by two threads at once. (If
+// the TA were ever rebuilt as singleInstance + multiSession, this reasoning
+// breaks and a real lock is required; ta.json is absent, so it is not.)
+unsafe impl<T: Send> Sync for TaGlobal<T> {}
+
+impl<T> TaGlobal<T> {
+    pub const fn new(value: T) -> Self {
+        TaGlobal(RefCell::new(value))
+    }
+
+    /// Run `f` with exclusive access. Panics if called from inside another
+    /// `with` on the same global.
+    pub fn with<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        let mut state = self
+            .0
+            .try_borrow_mut()
+            .expect("re-entrant access to a TA global");
+        f(&mut state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static COUNTER: TaGlobal<u32> = TaGlobal::new(0);
+    static OTHER: TaGlobal<Vec<u32>> = TaGlobal::new(Vec::new());
+
+    #[test]
+    fn state_persists_and_nesting_distinct_globals_is_fine() {
+        COUNTER.with(|c| *c += 1);
+        let seen = COUNTER.with(|c| {
+            OTHER.with(|o| o.push(*c));
+            *c
+        });
+        assert!(seen >= 1);
+        assert_eq!(OTHER.with(|o| o.last().copied()), Some(seen));
+    }
+
+    #[test]
+    fn reentrant_access_panics_instead_of_aliasing() {
+        let g = TaGlobal::new(1u8);
+        let nested =
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| g.with(|_| g.with(|v| *v))));
+        assert!(nested.is_err());
+        // The flag is released on unwind; the global stays usable.
+        assert_eq!(g.with(|v| *v), 1);
+    }
+}
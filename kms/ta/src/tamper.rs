@@ -0,0 +1,255 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Erase-on-tamper state: wipe policy, failure count, lock.
+//!
+//! `verify_passkey_for_wallet` only notes its outcome in a process-global
+//! `TaGlobal`; the dispatcher settles it after the command has finished, so the
+//! counter write never precedes a thread_local access (H-3). The record
+//! survives a wipe — its `last_order_sequence` is what stops a recorded
+//! panic-wipe or unlock order from being replayed.
+//!
+//! An instance that wiped, or that first saw the device unlocked and later
+//! locked, stays locked until the TA is reloaded even after an `Unlock`
+//! order: its per-thread wallet caches may still hold erased wallets.
+
+use proto::tamper::{TamperAction, TamperLock, TamperOrder, WipeProof, WipeReason};
+use secure_db::Storable;
+use serde::{Deserialize, Serialize};
+
+use crate::ta_global::TaGlobal;
+
+pub const STORE_ID: &str = "tamper_guard";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct TamperGuard {
+    pub store_id: String,
+    pub failed_auth_limit: Option<u32>,
+    pub consecutive_failures: u32,
+    pub last_order_sequence: u64,
+    pub locked: Option<TamperLock>,
+    pub last_wipe: Option<WipeProof>,
+}
+
+impl Storable for TamperGuard {
+    type Key = String;
+    fn unique_id(&self) -> Self::Key {
+        self.store_id.clone()
+    }
+}
+
+impl TamperGuard {
+    pub fn new() -> Self {
+        TamperGuard {
+            store_id: STORE_ID.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Count one failed authentication. Returns the wipe reason once the
+    /// limit is reached; nothing is counted while no limit is set.
+    pub fn record_failure(&mut self) -> Option<WipeReason> {
+        let limit = self.failed_auth_limit?;
+        if self.locked.is_some() {
+            return None;
+        }
+        self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+        if self.consecutive_failures >= limit {
+            Some(WipeReason::FailedAuth(limit))
+        } else {
+            None
+        }
+    }
+
+    pub fn status(&self) -> proto::TamperStatus {
+        proto::TamperStatus {
+            failed_auth_limit: self.failed_auth_limit,
+            consecutive_failures: self.consecutive_failures,
+            last_order_sequence: self.last_order_sequence,
+            locked: self.locked.clone(),
+            last_wipe: self.last_wipe.clone(),
+        }
+    }
+}
+
+/// Check a signed order against the pinned policy signer, the installed
+/// deployment and this device's attestation-key fingerprint.
+pub fn check_order(
+    guard: &TamperGuard,
+    policy: Option<(&[u8; 20], &str)>,
+    device: Option<&[u8; 32]>,
+    order: &TamperOrder,
+    signer: [u8; 20],
+) -> Result<(), &'static str> {
+    order.validate()?;
+    let (pinned, deployment) =
+        policy.ok_or("tamper orders need an installed deployment policy (it pins the signer)")?;
+    if *pinned != signer {
+        return Err("tamper order is not signed by the pinned policy key");
+    }
+    if order.deployment != deployment {
+        return Err("tamper order is for another deployment");
+    }
+    if let Some(target) = &order.device {
+        if device != Some(target) {
+            return Err("tamper order is for another device");
+        }
+    }
+    if order.sequence <= guard.last_order_sequence {
+        return Err("tamper order sequence must increase");
+    }
+    if order.action == TamperAction::Unlock && guard.locked.is_none() {
+        return Err("device is not locked");
+    }
+    Ok(())
+}
+
+/// Outcome of the passkey checks in the current command.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum AuthOutcome {
+    None,
+    Passed,
+    Failed,
+}
+
+struct TamperCell {
+    /// None = not loaded yet.
+    guard: Option<TamperGuard>,
+    outcome: AuthOutcome,
+    sticky_lock: bool,
+}
+
+static TAMPER: TaGlobal<TamperCell> = TaGlobal::new(TamperCell {
+    guard: None,
+    outcome: AuthOutcome::None,
+    sticky_lock: false,
+});
+
+pub fn cached() -> Option<TamperGuard> {
+    TAMPER.with(|c| c.guard.clone())
+}
+
+pub fn set_cached(guard: TamperGuard) {
+    TAMPER.with(|c| {
+        let was_unlocked = c.guard.as_ref().is_some_and(|g| g.locked.is_none());
+        if guard.locked.is_some() && was_unlocked {
+            c.sticky_lock = true;
+        }
+        c.guard = Some(guard);
+    });
+}
+
+/// Mark the instance locked for the rest of its life (it just wiped).
+pub fn stick_lock() {
+    TAMPER.with(|c| c.sticky_lock = true);
+}
+
+pub fn sticky_lock() -> bool {
+    TAMPER.with(|c| c.sticky_lock)
+}
+
+/// A failure anywhere in the command wins over a later success.
+pub fn note_auth(passed: bool) {
+    TAMPER.with(|c| {
+        c.outcome = match (c.outcome, passed) {
+            (AuthOutcome::Failed, _) | (_, false) => AuthOutcome::Failed,
+            _ => AuthOutcome::Passed,
+        };
+    });
+}
+
+pub fn take_auth_outcome() -> AuthOutcome {
+    TAMPER.with(|c| std::mem::replace(&mut c.outcome, AuthOutcome::None))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proto::tamper::TAMPER_ORDER_FORMAT;
+
+    fn order(sequence: u64, action: TamperAction) -> TamperOrder {
+        TamperOrder {
+            format: TAMPER_ORDER_FORMAT,
+            deployment: "rack-3".into(),
+            device: None,
+            sequence,
+            action,
+        }
+    }
+
+    #[test]
+    fn failures_count_only_with_a_limit() {
+        let mut guard = TamperGuard::new();
+        assert_eq!(guard.record_failure(), None);
+        assert_eq!(guard.consecutive_failures, 0);
+        guard.failed_auth_limit = Some(3);
+        assert_eq!(guard.record_failure(), None);
+        assert_eq!(guard.record_failure(), None);
+        assert_eq!(guard.record_failure(), Some(WipeReason::FailedAuth(3)));
+        guard.locked = Some(TamperLock {
+            reason: WipeReason::FailedAuth(3),
+            since: 1,
+        });
+        assert_eq!(guard.record_failure(), None);
+    }
+
+    #[test]
+    fn order_rules() {
+        let guard = TamperGuard {
+            last_order_sequence: 4,
+            ..TamperGuard::new()
+        };
+        let policy = Some((&[1u8; 20], "rack-3"));
+        let wipe = order(5, TamperAction::PanicWipe);
+        assert!(check_order(&guard, policy, None, &wipe, [1; 20]).is_ok());
+        assert!(check_order(&guard, None, None, &wipe, [1; 20]).is_err());
+        assert!(check_order(&guard, policy, None, &wipe, [2; 20]).is_err());
+        assert!(check_order(&guard, Some((&[1; 20], "rack-4")), None, &wipe, [1; 20]).is_err());
+        assert!(check_order(
+            &guard,
+            policy,
+            None,
+            &order(4, TamperAction::PanicWipe),
+            [1; 20]
+        )
+        .is_err());
+        assert!(check_order(
+            &guard,
+            policy,
+            None,
+            &order(5, TamperAction::Unlock),
+            [1; 20]
+        )
+        .is_err());
+
+        let mut targeted = wipe.clone();
+        targeted.device = Some([0xab; 32]);
+        assert!(check_order(&guard, policy, Some(&[0xab; 32]), &targeted, [1; 20]).is_ok());
+        assert!(check_order(&guard, policy, Some(&[0xac; 32]), &targeted, [1; 20]).is_err());
+        assert!(check_order(&guard, policy, None, &targeted, [1; 20]).is_err());
+    }
+
+    #[test]
+    fn failure_outcome_is_sticky_within_a_command() {
+        note_auth(false);
+        note_auth(true);
+        assert!(take_auth_outcome() == AuthOutcome::Failed);
+        assert!(take_auth_outcome() == AuthOutcome::None);
+        note_auth(true);
+        assert!(take_auth_outcome() == AuthOutcome::Passed);
+    }
+}
@@ -0,0 +1,163 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Per-command latency histograms for the invoke dispatcher, read back via
+//! `Command::TaStats`.
+//!
+//! Integer milliseconds into the fixed buckets of `proto::TA_LATENCY_BUCKETS_MS`.
+//! The table is a `TaGlobal` for the same reason as the child key cache: commands that write secure storage leave TLS unusable (H-3), and
+//! every command is recorded after it returns. Never persisted.
+
+use proto::{CommandLatency, TaStatsOutput, TA_LATENCY_BUCKETS_MS};
+
+use crate::ta_global::TaGlobal;
+
+/// Command ids past `buffers::MAX_COMMAND_ID` are folded into the last slot
+/// (Unknown).
+const MAX_COMMANDS: usize = proto::buffers::COMMAND_SLOTS;
+const BUCKETS: usize = TA_LATENCY_BUCKETS_MS.len() + 1;
+
+#[derive(Clone, Copy)]
+struct Slot {
+    count: u32,
+    errors: u32,
+    buckets: [u32; BUCKETS],
+}
+
+const EMPTY_SLOT: Slot = Slot {
+    count: 0,
+    errors: 0,
+    buckets: [0; BUCKETS],
+};
+
+struct Histograms([Slot; MAX_COMMANDS]);
+
+static HISTOGRAMS: TaGlobal<Histograms> = TaGlobal::new(Histograms([EMPTY_SLOT; MAX_COMMANDS]));
+
+fn bucket_index(elapsed_ms: u32) -> usize {
+    TA_LATENCY_BUCKETS_MS
+        .iter()
+        .position(|&bound| elapsed_ms <= bound)
+        .unwrap_or(TA_LATENCY_BUCKETS_MS.len())
+}
+
+impl Histograms {
+    fn record(&mut self, command: u32, elapsed_ms: u32, ok: bool) {
+        let slot = &mut self.0[(command as usize).min(MAX_COMMANDS - 1)];
+        slot.count = slot.count.saturating_add(1);
+        if !ok {
+            slot.errors = slot.errors.saturating_add(1);
+        }
+        let b = &mut slot.buckets[bucket_index(elapsed_ms)];
+        *b = b.saturating_add(1);
+    }
+
+    fn counters(&self, command: u32) -> Counters {
+        let slot = &self.0[(command as usize).min(MAX_COMMANDS - 1)];
+        Counters {
+            invocations: self.0.iter().fold(0u32, |n, s| n.saturating_add(s.count)),
+            failures: self.0.iter().fold(0u32, |n, s| n.saturating_add(s.errors)),
+            command_invocations: slot.count,
+            command_failures: slot.errors,
+        }
+    }
+
+    fn snapshot(&self) -> TaStatsOutput {
+        let commands = self
+            .0
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| s.count > 0)
+            .map(|(id, s)| CommandLatency {
+                command: id as u32,
+                count: s.count,
+                errors: s.errors,
+                buckets: s.buckets.to_vec(),
+            })
+            .collect();
+        TaStatsOutput {
+            commands,
+            storage: None,
+        }
+    }
+}
+
+/// Record one dispatched command.
+pub fn record(command: u32, elapsed_ms: u32, ok: bool) {
+    HISTOGRAMS.with(|h| h.record(command, elapsed_ms, ok));
+}
+
+pub fn snapshot() -> TaStatsOutput {
+    HISTOGRAMS.with(|h| h.snapshot())
+}
+
+/// Dispatch counts since the TA was loaded, for crash records.
+pub struct Counters {
+    pub invocations: u32,
+    pub failures: u32,
+    pub command_invocations: u32,
+    pub command_failures: u32,
+}
+
+pub fn counters(command: u32) -> Counters {
+    HISTOGRAMS.with(|h| h.counters(command))
+}
+
+/// Milliseconds between two (seconds, millis) readings of the TEE system
+/// clock, saturating instead of wrapping if the clock went backwards.
+pub fn elapsed_ms(start: (u32, u32), end: (u32, u32)) -> u32 {
+    let start_ms = start.0 as u64 * 1000 + start.1 as u64;
+    let end_ms = end.0 as u64 * 1000 + end.1 as u64;
+    end_ms.saturating_sub(start_ms).min(u32::MAX as u64) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buckets_are_inclusive_upper_bounds() {
+        assert_eq!(bucket_index(0), 0);
+        assert_eq!(bucket_index(1), 0);
+        assert_eq!(bucket_index(2), 1);
+        assert_eq!(bucket_index(3), 2);
+        assert_eq!(bucket_index(5000), TA_LATENCY_BUCKETS_MS.len() - 1);
+        assert_eq!(bucket_index(5001), TA_LATENCY_BUCKETS_MS.len());
+        assert_eq!(elapsed_ms((10, 900), (11, 50)), 150);
+        assert_eq!(elapsed_ms((11, 0), (10, 0)), 0);
+    }
+
+    #[test]
+    fn snapshot_reports_only_called_commands() {
+        let mut h = Histograms([EMPTY_SLOT; MAX_COMMANDS]);
+        h.record(5, 40, true);
+        h.record(5, 3, false);
+        h.record(999, 1, true);
+        let out = h.snapshot();
+        assert_eq!(out.commands.len(), 2);
+        let sign = &out.commands[0];
+        assert_eq!((sign.command, sign.count, sign.errors), (5, 2, 1));
+        assert_eq!(sign.buckets.len(), BUCKETS);
+        assert_eq!(sign.percentile_ms(50), Some(5));
+        assert_eq!(sign.percentile_ms(99), Some(50));
+        assert_eq!(out.commands[1].command, (MAX_COMMANDS - 1) as u32);
+
+        let c = h.counters(5);
+        assert_eq!((c.invocations, c.failures), (3, 1));
+        assert_eq!((c.command_invocations, c.command_failures), (2, 1));
+    }
+}
@@ -0,0 +1,125 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! The TA's half of a wallet's 2-of-2 threshold key, in secure storage.
+//! One [`ThresholdRecord`] per wallet holds the share, the keygen and
+//! presignature batch waiting for the co-signer, and the presignature pool.
+//! The protocol is `proto::threshold`; the record exists in every build so
+//! erasing a wallet also erases a share made by a `threshold-ecdsa` build.
+//!
+//! A presignature is removed from the pool, and the record written, before
+//! the signature that uses it is computed: a nonce used twice gives away the
+//! share, a lost one only costs a co-signer round.
+
+use proto::threshold::{RecombinedKey, TaKeygenState, TaPresignState, TaPresignature, TaShare};
+use secure_db::Storable;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ThresholdRecord {
+    pub store_id: String,
+    pub wallet_id: Uuid,
+    /// Between `ThresholdKeygenBegin` and `ThresholdKeygenFinish`.
+    pub keygen: Option<TaKeygenState>,
+    pub share: Option<TaShare>,
+    /// Presignature ids are never reused, even for a batch left unfinished.
+    pub next_presign_id: u64,
+    /// The batch waiting for the co-signer's replies.
+    pub pending: Vec<TaPresignState>,
+    /// Oldest first.
+    pub presignatures: Vec<TaPresignature>,
+    /// Set by the fallback ceremony; the share and the pool are gone then.
+    pub recombined: Option<RecombinedKey>,
+}
+
+impl Storable for ThresholdRecord {
+    type Key = String;
+    fn unique_id(&self) -> Self::Key {
+        self.store_id.clone()
+    }
+}
+
+impl ThresholdRecord {
+    pub fn store_id_for(wallet_id: &Uuid) -> String {
+        format!("threshold_{}", wallet_id)
+    }
+}
+
+#[cfg(feature = "threshold-ecdsa")]
+impl ThresholdRecord {
+    pub fn empty(wallet_id: &Uuid) -> Self {
+        Self {
+            store_id: Self::store_id_for(wallet_id),
+            wallet_id: *wallet_id,
+            keygen: None,
+            share: None,
+            next_presign_id: 0,
+            pending: Vec::new(),
+            presignatures: Vec::new(),
+            recombined: None,
+        }
+    }
+
+    /// Presignatures one more batch may add.
+    pub fn room(&self) -> usize {
+        proto::threshold::MAX_PRESIGNATURES.saturating_sub(self.presignatures.len())
+    }
+
+    pub fn available(&self) -> u32 {
+        self.presignatures.len() as u32
+    }
+
+    /// Remove presignature `id` from the pool, with every older one: the
+    /// co-signer spends its oldest first, so an older one left here was
+    /// spent or lost on its side. None, and the pool untouched, if `id` is
+    /// not in it.
+    pub fn take_presignature(&mut self, id: u64) -> Option<TaPresignature> {
+        let at = self.presignatures.iter().position(|p| p.id == id)?;
+        let mut spent: Vec<TaPresignature> = self.presignatures.drain(..=at).collect();
+        spent.pop()
+    }
+}
+
+#[cfg(all(test, feature = "threshold-ecdsa"))]
+mod tests {
+    use super::*;
+
+    fn presignature(id: u64) -> TaPresignature {
+        TaPresignature {
+            id,
+            nonce: [id as u8 + 1; 32],
+            r_point: vec![0x02; 33],
+        }
+    }
+
+    #[test]
+    fn taking_a_presignature_spends_the_older_ones() {
+        let wallet = Uuid::parse_str("4319f351-0b24-4097-b659-80ee4f824cdd").unwrap();
+        let mut record = ThresholdRecord::empty(&wallet);
+        record.presignatures = (3..8).map(presignature).collect();
+        assert_eq!(record.room(), proto::threshold::MAX_PRESIGNATURES - 5);
+
+        assert!(record.take_presignature(9).is_none());
+        assert_eq!(record.available(), 5);
+
+        assert_eq!(record.take_presignature(5).unwrap().id, 5);
+        let left: Vec<u64> = record.presignatures.iter().map(|p| p.id).collect();
+        assert_eq!(left, vec![6, 7]);
+        assert!(record.take_presignature(5).is_none());
+    }
+}
@@ -0,0 +1,126 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! The open chunked transfer (`proto::transfer`), one per TA instance.
+//!
+//! In memory only, never secure storage: a reloaded TA has no transfer open
+//! and the CA starts over. EndTransfer takes the input out before running the
+//! wrapped command, so the command may itself use other `TaGlobal`s.
+
+use anyhow::Result;
+use proto::transfer::{chunk_at, is_transfer_command, Reassembly, TransferError, CHUNK_LEN};
+use proto::Command;
+
+use crate::ta_global::TaGlobal;
+
+enum Transfer {
+    Receiving {
+        command: u32,
+        input: Reassembly,
+    },
+    Sending {
+        transfer_id: u64,
+        output: Vec<u8>,
+        next_seq: u32,
+    },
+}
+
+static OPEN: TaGlobal<Option<Transfer>> = TaGlobal::new(None);
+
+/// Open a transfer for `input.command`, dropping any unfinished one.
+pub fn begin(
+    input: &proto::BeginTransferInput,
+    transfer_id: u64,
+) -> Result<proto::BeginTransferOutput> {
+    if is_transfer_command(Command::from(input.command)) {
+        return Err(TransferError::Nested(input.command).into());
+    }
+    let reassembly = Reassembly::new(transfer_id, input.input_len, input.input_digest)?;
+    OPEN.with(|open| {
+        *open = Some(Transfer::Receiving {
+            command: input.command,
+            input: reassembly,
+        })
+    });
+    Ok(proto::BeginTransferOutput {
+        transfer_id,
+        chunk_len: CHUNK_LEN as u32,
+    })
+}
+
+/// Take an input chunk, or hand out the next output chunk. The transfer is
+/// closed after its last output chunk.
+pub fn next(input: &proto::ContinueTransferInput) -> Result<proto::ContinueTransferOutput> {
+    OPEN.with(|open| {
+        let chunk = match (open.as_mut(), &input.chunk) {
+            (Some(Transfer::Receiving { input: r, .. }), Some(chunk))
+                if r.transfer_id() == input.transfer_id =>
+            {
+                r.push(chunk)?;
+                None
+            }
+            (
+                Some(Transfer::Sending {
+                    transfer_id,
+                    output,
+                    next_seq,
+                }),
+                None,
+            ) if *transfer_id == input.transfer_id => {
+                let chunk = chunk_at(*transfer_id, output, *next_seq);
+                *next_seq += 1;
+                if (*next_seq as usize) * CHUNK_LEN >= output.len() {
+                    *open = None;
+                }
+                chunk
+            }
+            _ => return Err(TransferError::UnknownTransfer(input.transfer_id).into()),
+        };
+        Ok(proto::ContinueTransferOutput { chunk })
+    })
+}
+
+/// The command and its checked input, for EndTransfer to run. The transfer
+/// stays closed until `hold_output`, so a failing command leaves nothing.
+pub fn take_input(transfer_id: u64) -> Result<(u32, Vec<u8>)> {
+    let open = OPEN.with(|open| open.take());
+    match open {
+        Some(Transfer::Receiving { command, input }) if input.transfer_id() == transfer_id => {
+            Ok((command, input.finish()?))
+        }
+        _ => Err(TransferError::UnknownTransfer(transfer_id).into()),
+    }
+}
+
+/// Keep the command's output for ContinueTransfer.
+pub fn hold_output(transfer_id: u64, output: Vec<u8>) -> proto::EndTransferOutput {
+    let result = proto::EndTransferOutput {
+        output_len: output.len() as u32,
+        output_digest: proto::transfer::payload_digest(&output),
+        chunks: proto::transfer::chunk_count(output.len()),
+    };
+    if !output.is_empty() {
+        OPEN.with(|open| {
+            *open = Some(Transfer::Sending {
+                transfer_id,
+                output,
+                next_seq: 0,
+            })
+        });
+    }
+    result
+}
@@ -22,9 +22,12 @@ use std::convert::{TryFrom, TryInto};
 use uuid::Uuid;
 
 use crate::bip32_secp::{self, CachedXPrv, DerivedKey};
-use crate::hash::keccak_hash_to_bytes;
+use crate::ed25519;
+use crate::key_cache;
+use ed25519_dalek::SigningKey;
 use ethereum_tx_sign::Transaction;
-use optee_utee::Random;
+use proto::chain::Chain;
+use proto::eip1559::{Eip1559Transaction, SignedEip1559Tx};
 use proto::EthTransaction;
 use secure_db::Storable;
 
@@ -59,19 +62,10 @@ impl Storable for Wallet {
 impl Wallet {
     pub fn new() -> Result<Self> {
         let mut entropy = vec![0u8; 32];
-        Random::generate(entropy.as_mut() as _);
-
-        let mut random_bytes = vec![0u8; 16];
-        Random::generate(random_bytes.as_mut() as _);
-        let uuid = uuid::Builder::from_random_bytes(
-            random_bytes
-                .try_into()
-                .map_err(|_| anyhow!("[-] Wallet::new(): invalid random bytes"))?,
-        )
-        .into_uuid();
+        crate::provider::fill(&mut entropy);
 
         Ok(Self {
-            id: uuid,
+            id: Uuid::nil(),
             entropy,
             next_address_index: 0,
             next_account_index: 0,
@@ -83,20 +77,17 @@ impl Wallet {
     }
 
     /// Create a wallet from CA-provided entropy seed (CAAM bypass mode).
-    /// seed: 48 bytes — first 32 are BIP39 wallet entropy, last 16 are UUID bytes.
+    /// seed: at least 32 bytes of BIP39 wallet entropy. The CA sends 48; the
+    /// trailing bytes are not used.
     /// Used when the hardware TRNG (CAAM) is unreliable or stuck.
     pub fn from_seed(seed: &[u8]) -> Result<Self> {
-        if seed.len() < 48 {
-            return Err(anyhow!("[-] Wallet::from_seed(): need 48 bytes, got {}", seed.len()));
+        if seed.len() < 32 {
+            return Err(anyhow!("[-] Wallet::from_seed(): need 32 bytes, got {}", seed.len()));
         }
         let entropy = seed[..32].to_vec();
-        let uuid_bytes: [u8; 16] = seed[32..48]
-            .try_into()
-            .map_err(|_| anyhow!("[-] Wallet::from_seed(): invalid uuid bytes"))?;
-        let uuid = uuid::Builder::from_random_bytes(uuid_bytes).into_uuid();
 
         Ok(Self {
-            id: uuid,
+            id: Uuid::nil(),
             entropy,
             next_address_index: 0,
             next_account_index: 0,
@@ -107,10 +98,58 @@ impl Wallet {
         })
     }
 
+    /// Rebuild a wallet from an encrypted backup (`proto::backup`): same id,
+    /// entropy, passkey and address counters as when it was exported.
+    pub fn restore(
+        id: Uuid,
+        entropy: Vec<u8>,
+        passkey_pubkey: Vec<u8>,
+        next_address_index: u32,
+        next_account_index: u32,
+    ) -> Result<Self> {
+        if entropy.len() != 32 {
+            return Err(anyhow!(
+                "[-] Wallet::restore(): need 32 bytes of entropy, got {}",
+                entropy.len()
+            ));
+        }
+        Ok(Self {
+            id,
+            entropy,
+            next_address_index,
+            next_account_index,
+            cached_seed: None,
+            cached_account_root: None,
+            passkey_pubkey: Some(passkey_pubkey),
+            rollback_epoch: 0,
+        })
+    }
+
+    /// Mix a WebAuthn PRF (hmac-secret) output into the wallet entropy:
+    /// entropy' = HMAC-SHA256(key = prf, "AirAccount.prf-entropy.v1" ‖ entropy).
+    /// Only valid before the wallet is first saved — the mnemonic changes.
+    pub fn mix_prf_entropy(&mut self, prf_output: &[u8; 32]) -> Result<()> {
+        use hmac::{Hmac, Mac};
+        let mut mac = <Hmac<sha2::Sha256> as Mac>::new_from_slice(prf_output)
+            .map_err(|_| anyhow!("[-] mix_prf_entropy(): invalid HMAC key"))?;
+        mac.update(b"AirAccount.prf-entropy.v1");
+        mac.update(&self.entropy);
+        let mixed = mac.finalize().into_bytes();
+        self.entropy.iter_mut().for_each(|b| *b = 0);
+        self.entropy = mixed.to_vec();
+        self.cached_seed = None;
+        self.cached_account_root = None;
+        Ok(())
+    }
+
     pub fn get_next_address_index(&self) -> u32 {
         self.next_address_index
     }
 
+    pub fn get_next_account_index(&self) -> u32 {
+        self.next_account_index
+    }
+
     pub fn increment_address_index(&mut self) -> Result<u32> {
         const MAX_ADDRESSES_PER_WALLET: u32 = 100;
 
@@ -131,6 +170,35 @@ impl Wallet {
         self.id
     }
 
+    /// Set the id from the account root's public key
+    /// (`proto::wallet_id`). `new` and `from_seed` leave it nil: call this
+    /// once the entropy is final, i.e. after any PRF mix.
+    pub fn assign_derived_id(&mut self) -> Result<Uuid> {
+        self.ensure_seed_cached()?;
+        let root = self
+            .get_account_root()?
+            .ok_or_else(|| anyhow!("[-] assign_derived_id(): no account root"))?;
+        self.id = proto::wallet_id::from_account_pubkey(&root.pubkey);
+        Ok(self.id)
+    }
+
+    /// Same id and field sizes with every secret zeroed — written over the
+    /// stored object before it is deleted, so the last version of the object
+    /// in secure storage holds no key material.
+    pub fn erased(&self) -> Wallet {
+        let zeroed = |v: &Option<Vec<u8>>| v.as_ref().map(|b| vec![0u8; b.len()]);
+        Wallet {
+            id: self.id,
+            entropy: vec![0u8; self.entropy.len()],
+            next_address_index: 0,
+            next_account_index: 0,
+            cached_seed: zeroed(&self.cached_seed),
+            cached_account_root: zeroed(&self.cached_account_root),
+            passkey_pubkey: zeroed(&self.passkey_pubkey),
+            rollback_epoch: 0,
+        }
+    }
+
     pub fn get_mnemonic(&self) -> Result<String> {
         let mnemonic = Mnemonic::from_entropy(
             self.entropy.as_slice().try_into()?,
@@ -139,6 +207,12 @@ impl Wallet {
         Ok(mnemonic.phrase().to_string())
     }
 
+    /// Raw BIP39 entropy, for the passphrase keystore export and the
+    /// encrypted backup.
+    pub fn entropy(&self) -> &[u8] {
+        &self.entropy
+    }
+
     pub fn get_seed(&self) -> Result<Vec<u8>> {
         if let Some(ref seed) = self.cached_seed {
             return Ok(seed.clone());
@@ -186,21 +260,46 @@ impl Wallet {
     }
 
     /// Derive key using optimized libsecp256k1 path.
+    /// Finished child keys are served from `key_cache` when still fresh.
     fn derive_key(&self, hd_path: &str) -> Result<DerivedKey> {
-        let seed = self.get_seed()?;
         let (account, address) = bip32_secp::parse_eth_path(hd_path)?;
-        let cached = self.get_account_root()?;
-        bip32_secp::derive_full(&seed, cached.as_ref(), account, address)
+        crate::enforce_path_policy(&self.id, proto::chain::EVM_COIN_TYPE, account, address)?;
+        key_cache::get_or_derive(&self.id, account, address, crate::tee_unix_secs(), || {
+            let seed = self.get_seed()?;
+            let cached = self.get_account_root()?;
+            bip32_secp::derive_full(&seed, cached.as_ref(), account, address)
+        })
     }
 
     pub fn derive_address(&self, hd_path: &str) -> Result<([u8; 20], Vec<u8>)> {
         let derived = self.derive_key(hd_path)?;
+        Ok((derived.eth_address(), derived.public_key_compressed.to_vec()))
+    }
 
-        // Ethereum address: Keccak256(uncompressed_pubkey[1..]) → last 20 bytes
-        let uncompressed_no_prefix = &derived.public_key_uncompressed[1..];
-        let address = &keccak_hash_to_bytes(uncompressed_no_prefix)[12..];
+    /// `derive_address` on `chain`. EVM chains share the Ethereum key; the
+    /// others derive under their own coin type, with the same path policy on
+    /// (account, address) and past `key_cache`, which holds coin type 60.
+    pub fn derive_chain_address(&self, hd_path: &str, chain: Chain) -> Result<([u8; 20], Vec<u8>)> {
+        if chain.is_evm() {
+            return self.derive_address(hd_path);
+        }
+        let coin_type = chain.coin_type();
+        let (account, address) = bip32_secp::parse_coin_path(hd_path, coin_type)?;
+        crate::enforce_path_policy(&self.id, coin_type, account, address)?;
+        let seed = self.get_seed()?;
+        let derived = bip32_secp::derive_coin_full(&seed, coin_type, account, address)?;
+        Ok((
+            derived.eth_address(),
+            derived.public_key_compressed.to_vec(),
+        ))
+    }
 
-        Ok((address.try_into()?, derived.public_key_compressed.to_vec()))
+    /// The ed25519 key of the Solana account at `hd_path`. The path policy
+    /// applies to (account, 0); Solana paths have no address level.
+    pub fn solana_key(&self, hd_path: &str) -> Result<SigningKey> {
+        let account = ed25519::parse_solana_path(hd_path)?;
+        crate::enforce_path_policy(&self.id, proto::solana::SOLANA_COIN_TYPE, account, 0)?;
+        ed25519::solana_key(&self.get_seed()?, account)
     }
 
     pub fn sign_transaction(&self, hd_path: &str, transaction: &EthTransaction) -> Result<Vec<u8>> {
@@ -240,44 +339,35 @@ impl Wallet {
         .hash()
     }
 
-    pub fn sign_message(&self, hd_path: &str, message: &[u8]) -> Result<Vec<u8>> {
-        let derived = self.derive_key(hd_path)?;
-
-        let message_hash = keccak_hash_to_bytes(message);
-
-        let secret_key = secp256k1::SecretKey::from_slice(&derived.private_key)?;
-        let secp = secp256k1::Secp256k1::new();
-
-        let mut hash_array = [0u8; 32];
-        hash_array.copy_from_slice(&message_hash[..32]);
-        let message_obj = secp256k1::Message::from_slice(&hash_array)?;
-
-        let sig = secp.sign_ecdsa_recoverable(&message_obj, &secret_key);
-        let (recovery_id, sig_bytes) = sig.serialize_compact();
-
-        let mut signature = Vec::with_capacity(65);
-        signature.extend_from_slice(&sig_bytes);
-        signature.push(recovery_id.to_i32() as u8 + 27);
-
-        Ok(signature)
+    /// The signed `0x02 ‖ rlp(...)` of an EIP-1559 transaction, encoded and
+    /// hashed here (`proto::eip1559`) rather than trusted from the CA.
+    pub fn sign_eip1559_transaction(
+        &self,
+        hd_path: &str,
+        transaction: &Eip1559Transaction,
+    ) -> Result<Vec<u8>> {
+        let signature = self.sign_hash(hd_path, &transaction.signing_hash())?;
+        let mut r = [0u8; 32];
+        let mut s = [0u8; 32];
+        r.copy_from_slice(&signature[..32]);
+        s.copy_from_slice(&signature[32..64]);
+        Ok(proto::eip1559::signed_rlp(&SignedEip1559Tx {
+            transaction: transaction.clone(),
+            y_parity: signature[64] - 27,
+            r,
+            s,
+        }))
     }
 
     pub fn sign_hash(&self, hd_path: &str, hash: &[u8; 32]) -> Result<Vec<u8>> {
         let derived = self.derive_key(hd_path)?;
+        bip32_secp::sign_recoverable(&derived.private_key, hash)
+    }
 
-        let secret_key = secp256k1::SecretKey::from_slice(&derived.private_key)?;
-        let secp = secp256k1::Secp256k1::new();
-
-        let message_obj = secp256k1::Message::from_slice(hash)?;
-
-        let sig = secp.sign_ecdsa_recoverable(&message_obj, &secret_key);
-        let (recovery_id, sig_bytes) = sig.serialize_compact();
-
-        let mut signature = Vec::with_capacity(65);
-        signature.extend_from_slice(&sig_bytes);
-        signature.push(recovery_id.to_i32() as u8 + 27);
-
-        Ok(signature)
+    /// ECDH point of the key at `hd_path` with `peer`, for ECIES.
+    pub fn ecdh_point(&self, hd_path: &str, peer: &[u8]) -> Result<[u8; 65]> {
+        let derived = self.derive_key(hd_path)?;
+        bip32_secp::ecdh_point(&derived.private_key, peer)
     }
 
     pub fn export_private_key(&self, hd_path: &str) -> Result<Vec<u8>> {
@@ -401,6 +491,37 @@ mod compat_tests {
         assert_eq!(w.passkey_pubkey.as_deref(), Some(&[0x04; 65][..]));
     }
 
+    #[test]
+    fn prf_mix_changes_entropy_and_drops_caches() {
+        let legacy = legacy_fixture();
+        let mut w = Wallet::from_seed(&[0xAA; 48]).unwrap();
+        w.cached_seed = legacy.cached_seed;
+        w.mix_prf_entropy(&[0x01; 32]).unwrap();
+        assert_eq!(w.entropy.len(), 32);
+        assert_ne!(w.entropy, vec![0xAA; 32]);
+        assert!(w.cached_seed.is_none());
+        let mut other = Wallet::from_seed(&[0xAA; 48]).unwrap();
+        other.mix_prf_entropy(&[0x02; 32]).unwrap();
+        assert_ne!(w.entropy, other.entropy, "PRF output must matter");
+    }
+
+    #[test]
+    fn re_created_wallet_gets_the_same_id() {
+        let mut first = Wallet::from_seed(&[0xAA; 48]).unwrap();
+        let mut again = Wallet::from_seed(&[0xAA; 32]).unwrap();
+        let id = first.assign_derived_id().unwrap();
+        assert_eq!(again.assign_derived_id().unwrap(), id);
+        assert_eq!(id.get_version_num(), 8);
+        let mut other = Wallet::from_seed(&[0xAB; 48]).unwrap();
+        assert_ne!(other.assign_derived_id().unwrap(), id);
+        first.mix_prf_entropy(&[0x01; 32]).unwrap();
+        assert_ne!(
+            first.assign_derived_id().unwrap(),
+            id,
+            "id follows the final entropy"
+        );
+    }
+
     #[test]
     fn wallet_current_roundtrip_preserves_rollback_epoch() {
         let legacy = legacy_fixture();
@@ -420,6 +541,36 @@ mod compat_tests {
         assert_eq!(back, w);
     }
 
+    #[test]
+    fn erased_wallet_keeps_layout_and_drops_secrets() {
+        let w = Wallet::try_from(bincode::serialize(&legacy_fixture()).unwrap()).unwrap();
+        let erased = w.erased();
+        assert_eq!(erased.get_id(), w.id);
+        assert!(erased.entropy.iter().all(|b| *b == 0));
+        assert_eq!(erased.entropy.len(), w.entropy.len());
+        assert_eq!(
+            erased.cached_seed.as_ref().map(|s| s.len()),
+            w.cached_seed.as_ref().map(|s| s.len())
+        );
+        assert!(erased.cached_seed.iter().flatten().all(|b| *b == 0));
+        assert!(erased.passkey_pubkey.iter().flatten().all(|b| *b == 0));
+        let bytes: Vec<u8> = erased.try_into().unwrap();
+        assert!(Wallet::try_from(bytes).is_ok());
+    }
+
+    #[test]
+    fn restored_wallet_keeps_id_passkey_and_counters() {
+        let legacy = legacy_fixture();
+        let w = Wallet::restore(legacy.id, legacy.entropy, vec![0x04; 65], 7, 2).unwrap();
+        assert_eq!(w.get_id(), Uuid::from_bytes([0x11; 16]));
+        assert_eq!(w.entropy(), &[0xAA; 32][..]);
+        assert_eq!(w.get_passkey(), Some(&[0x04; 65][..]));
+        assert_eq!(w.get_next_address_index(), 7);
+        assert_eq!(w.get_next_account_index(), 2);
+        assert_eq!(w.rollback_epoch, 0);
+        assert!(Wallet::restore(w.id, vec![0xAA; 16], vec![0x04; 65], 0, 0).is_err());
+    }
+
     #[test]
     fn wallet_corrupt_bytes_rejected() {
         assert!(Wallet::try_from(vec![0xFFu8; 8]).is_err());
@@ -74,6 +74,10 @@ declare -A OP_TIMES
 declare -A OP_SUMS
 declare -A OP_MINS
 declare -A OP_MAXS
+# Round 1 of each op is the cold path for its derivation path (TA child-key
+# cache miss); rounds 2..N hit the cache. Tracked separately so the cache win
+# shows up in the summary instead of being averaged away.
+declare -A OP_FIRST
 
 benchmark_op() {
     local name="$1"
@@ -109,6 +113,7 @@ benchmark_op() {
         elapsed=$((end - start))
 
         OP_SUMS[$name]=$(( ${OP_SUMS[$name]} + elapsed ))
+        [ "$r" -eq 1 ] && OP_FIRST[$name]=$elapsed
         [ "$elapsed" -lt "${OP_MINS[$name]}" ] && OP_MINS[$name]=$elapsed
         [ "$elapsed" -gt "${OP_MAXS[$name]}" ] && OP_MAXS[$name]=$elapsed
 
@@ -116,7 +121,11 @@ benchmark_op() {
     done
 
     local avg=$(( ${OP_SUMS[$name]} / ROUNDS ))
-    printf "${GREEN} OK ${NC} %-24s  avg=%4dms  min=%4dms  max=%4dms\n" "$name" "$avg" "${OP_MINS[$name]}" "${OP_MAXS[$name]}"
+    local warm=$avg
+    if [ "$ROUNDS" -gt 1 ]; then
+        warm=$(( (${OP_SUMS[$name]} - ${OP_FIRST[$name]}) / (ROUNDS - 1) ))
+    fi
+    printf "${GREEN} OK ${NC} %-24s  avg=%4dms  min=%4dms  max=%4dms  cold=%4dms  warm=%4dms\n" "$name" "$avg" "${OP_MINS[$name]}" "${OP_MAXS[$name]}" "${OP_FIRST[$name]}" "$warm"
 }
 
 echo "${YELLOW}Running benchmarks ($ROUNDS rounds each)...${NC}"
@@ -178,6 +187,22 @@ printf "${GREEN} OK ${NC} %-24s  avg=%4dms  min=%4dms  max=%4dms\n" "DescribeKey
 
 echo ""
 
+# ── TA-internal latency ──
+# HTTP timings above include the network, axum/warp and the CA queue. /stats
+# reports what the TA itself measured per command (histogram bucket upper
+# bounds, cumulative since the TA was loaded), which is the number to compare
+# across TA builds. /stats refreshes it at most every 10s.
+sleep 10
+TA_LATENCY=$(curl -s --max-time 10 "$BASE/stats" $API_KEY_HDR 2>/dev/null | python3 -c "
+import sys, json
+lat = json.load(sys.stdin).get('ta_latency') or {}
+for cmd in ('SignHash', 'SignMessage', 'SignTransaction', 'DeriveAddress'):
+    c = lat.get(cmd)
+    if c:
+        fmt = lambda v: '>5000' if v is None else str(v)
+        print('| %s | %d | %sms | %sms | %sms |' % (cmd, c['count'], fmt(c['p50_ms']), fmt(c['p95_ms']), fmt(c['p99_ms'])))
+" 2>/dev/null || true)
+
 # ── Step 3: Cleanup ──
 echo "${YELLOW}Cleaning up...${NC}"
 ssh -o ConnectTimeout=5 root@192.168.7.2 "/usr/local/bin/kms remove-wallet -w $KEY_ID" 2>/dev/null || true
@@ -189,14 +214,24 @@ echo "${BOLD}================================================================${N
 echo "${BOLD}  Performance Results (Markdown)${NC}"
 echo "${BOLD}================================================================${NC}"
 echo ""
-echo "| Operation | Avg | Min | Max | Rounds |"
-echo "|-----------|-----|-----|-----|--------|"
+echo "| Operation | Avg | Min | Max | Cold (1st) | Warm (2..N) | Rounds |"
+echo "|-----------|-----|-----|-----|------------|-------------|--------|"
 for name in "SignHash" "Sign (message)" "Sign (transaction)" "DeriveAddress"; do
     if [ -n "${OP_SUMS[$name]+x}" ]; then
         avg=$(( ${OP_SUMS[$name]} / ROUNDS ))
-        echo "| $name | ${avg}ms | ${OP_MINS[$name]}ms | ${OP_MAXS[$name]}ms | $ROUNDS |"
+        warm=$avg
+        [ "$ROUNDS" -gt 1 ] && warm=$(( (${OP_SUMS[$name]} - ${OP_FIRST[$name]}) / (ROUNDS - 1) ))
+        echo "| $name | ${avg}ms | ${OP_MINS[$name]}ms | ${OP_MAXS[$name]}ms | ${OP_FIRST[$name]}ms | ${warm}ms | $ROUNDS |"
     fi
 done
-echo "| health | $((HEALTH_SUM / ROUNDS))ms | ${HEALTH_MIN}ms | ${HEALTH_MAX}ms | $ROUNDS |"
+echo "| health | $((HEALTH_SUM / ROUNDS))ms | ${HEALTH_MIN}ms | ${HEALTH_MAX}ms | - | - | $ROUNDS |"
 echo "| DescribeKey | $((DESC_SUM / ROUNDS))ms | ${DESC_MIN}ms | ${DESC_MAX}ms | $ROUNDS |"
 echo ""
+if [ -n "$TA_LATENCY" ]; then
+    echo "TA-internal latency (since TA load):"
+    echo ""
+    echo "| TA command | Count | p50 | p95 | p99 |"
+    echo "|------------|-------|-----|-----|-----|"
+    echo "$TA_LATENCY"
+    echo ""
+fi
@@ -0,0 +1,126 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Browser bindings for the validation logic in `proto`.
+//!
+//! Every export is a thin wrapper: the rules themselves are the ones the CA
+//! and TA run, so the SDK rejects exactly what the device would reject.
+//! Errors surface as thrown JS `Error`s carrying proto's message.
+
+use proto::deployment_policy::{DeploymentPolicy, POLICY_FORMAT_VERSION};
+use proto::{eip55, hd_path, tx_builder, Command};
+use wasm_bindgen::prelude::*;
+
+fn hex(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(2 + bytes.len() * 2);
+    out.push_str("0x");
+    for b in bytes {
+        out.push_str(&format!("{:02x}", b));
+    }
+    out
+}
+
+/// `[account, address]` of a path the TA will derive; throws otherwise.
+#[wasm_bindgen(js_name = parseDerivationPath)]
+pub fn parse_derivation_path(path: &str) -> Result<Box<[u32]>, JsError> {
+    let (account, address) = hd_path::parse_eth_path(path).map_err(|e| JsError::new(&e))?;
+    Ok(vec![account, address].into_boxed_slice())
+}
+
+/// The path in canonical form (`h`/`H` written as `'`);
+/// throws on a path the TA will not derive.
+#[wasm_bindgen(js_name = canonicalDerivationPath)]
+pub fn canonical_derivation_path(path: &str) -> Result<String, JsError> {
+    hd_path::canonical_eth_path(path).map_err(|e| JsError::new(&e))
+}
+
+#[wasm_bindgen(js_name = isValidAddress)]
+pub fn is_valid_address(address: &str) -> bool {
+    eip55::parse_address(address).is_ok()
+}
+
+/// EIP-55 form of `address`; throws on a bad mixed-case checksum.
+#[wasm_bindgen(js_name = toChecksumAddress)]
+pub fn to_checksum_address(address: &str) -> Result<String, JsError> {
+    let bytes = eip55::parse_address(address).map_err(JsError::new)?;
+    Ok(eip55::to_checksum_address(&bytes))
+}
+
+/// Unsigned EIP-155 payload of a legacy transaction, built from the same
+/// fields (and with the same checks) as the `Sign` API's `transaction`.
+#[wasm_bindgen]
+pub struct LegacyTransaction {
+    unsigned_rlp: String,
+    signing_hash: String,
+}
+
+#[wasm_bindgen]
+impl LegacyTransaction {
+    #[wasm_bindgen(getter, js_name = unsignedRlp)]
+    pub fn unsigned_rlp(&self) -> String {
+        self.unsigned_rlp.clone()
+    }
+
+    /// keccak256 of the payload — the digest the TA signs.
+    #[wasm_bindgen(getter, js_name = signingHash)]
+    pub fn signing_hash(&self) -> String {
+        self.signing_hash.clone()
+    }
+}
+
+#[wasm_bindgen(js_name = buildLegacyTransaction)]
+pub fn build_legacy_transaction(
+    chain_id: u64,
+    nonce: u64,
+    to: &str,
+    value: &str,
+    gas_price: &str,
+    gas: u64,
+    data: &str,
+) -> Result<LegacyTransaction, JsError> {
+    let tx = tx_builder::build_legacy(chain_id, nonce, to, value, gas_price, gas, data)
+        .map_err(|e| JsError::new(&e))?;
+    let (rlp, hash) = tx_builder::signing_payload(&tx);
+    Ok(LegacyTransaction {
+        unsigned_rlp: hex(&rlp),
+        signing_hash: hex(&hash),
+    })
+}
+
+/// Command name for a TA command id ("Unknown" for unassigned ids).
+#[wasm_bindgen(js_name = commandName)]
+pub fn command_name(id: u32) -> String {
+    format!("{:?}", Command::from(id))
+}
+
+/// The text an operator `personal_sign`s to authorise a deployment policy;
+/// throws if the TA would refuse the policy.
+#[wasm_bindgen(js_name = deploymentPolicyMessage)]
+pub fn deployment_policy_message(
+    deployment: &str,
+    sequence: u64,
+    disabled_commands: Box<[u32]>,
+) -> Result<String, JsError> {
+    let policy = DeploymentPolicy {
+        format: POLICY_FORMAT_VERSION,
+        deployment: deployment.to_string(),
+        sequence,
+        disabled_commands: disabled_commands.into_vec(),
+    };
+    policy.validate().map_err(JsError::new)?;
+    Ok(policy.message())
+}
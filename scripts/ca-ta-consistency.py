@@ -32,7 +32,7 @@ import os
 
 ROOT = os.path.dirname(os.path.dirname(os.path.abspath(__file__)))
 TA = os.path.join(ROOT, "kms/ta/src/main.rs")
-HOST = os.path.join(ROOT, "kms/host/src/api_server.rs")
+HOST = os.path.join(ROOT, "kms/host/src/service.rs")
 
 # --- audited mapping tables (#122) --------------------------------------------
 # Cross-named / _strict-routed ops: host handler fn -> TA verify fn it reaches.
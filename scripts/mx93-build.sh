@@ -20,6 +20,9 @@ set -euo pipefail
 MODE="${1:-all}"
 CONTAINER="${TEACLAVE_CONTAINER:-teaclave_dev_env}"
 UUID="4319f351-0b24-4097-b659-80ee4f824cdd"
+# MX93_ETH_WALLET_COMPAT=1: migration image signed under the upstream eth_wallet
+# UUID so unmodified eth_wallet CAs keep working (see TA eth-wallet-compat).
+[[ "${MX93_ETH_WALLET_COMPAT:-0}" == "1" ]] && UUID="70e328e2-8bca-4bb9-a5be-e7e639b97ec0"
 
 SCRIPT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
 PROJECT_ROOT="$(cd "$SCRIPT_DIR/.." && pwd)"
@@ -85,6 +88,10 @@ build_ta() {
         TA_FEATURES="$TA_FEATURES,dev-rpid"
         warn "DEV-RPID (TEST) TA — accepts rpId=localhost in addition to aastar.io. DO NOT flash to a production board."
     fi
+    if [[ "${MX93_ETH_WALLET_COMPAT:-0}" == "1" ]]; then
+        TA_FEATURES="$TA_FEATURES,eth-wallet-compat"
+        warn "ETH_WALLET COMPAT TA — also serves passkey-less eth_wallet requests (UUID $UUID). Migration boards only."
+    fi
     log "Building TA (aarch64-unknown-optee, nightly-2024-05-15, features: $TA_FEATURES)..."
     docker exec "$CONTAINER" bash -c '
       set -e
@@ -141,6 +148,9 @@ build_ca() {
     if [[ "${MX93_STRICT_CHALLENGE:-0}" == "1" ]]; then
         CA_FEATS="${CA_FEATS:+$CA_FEATS,}strict-challenge"
     fi
+    if [[ "${MX93_ETH_WALLET_COMPAT:-0}" == "1" ]]; then
+        CA_FEATS="${CA_FEATS:+$CA_FEATS,}eth-wallet-compat"
+    fi
     local CA_FEAT_ARG=""
     [[ -n "$CA_FEATS" ]] && CA_FEAT_ARG="--features $CA_FEATS"
     log "Building CA (aarch64-unknown-linux-gnu, stable 1.88, features arg: '${CA_FEAT_ARG:-<none>}')..."
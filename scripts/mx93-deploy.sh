@@ -25,6 +25,9 @@ PROJECT_ROOT="$(cd "$SCRIPT_DIR/.." && pwd)"
 BUILD_OUT="$PROJECT_ROOT/build/mx93"
 SERVICE_FILE="$PROJECT_ROOT/kms/deploy/mx93/kms-api.service"
 UUID="4319f351-0b24-4097-b659-80ee4f824cdd"
+# MX93_ETH_WALLET_COMPAT=1: migration image signed under the upstream eth_wallet
+# UUID so unmodified eth_wallet CAs keep working (see TA eth-wallet-compat).
+[[ "${MX93_ETH_WALLET_COMPAT:-0}" == "1" ]] && UUID="70e328e2-8bca-4bb9-a5be-e7e639b97ec0"
 
 BOARD_IP="${MX93_BOARD_IP:-}"
 BOARD_USER="${MX93_BOARD_USER:-root}"